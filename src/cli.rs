@@ -0,0 +1,262 @@
+//! Companion subcommands for using the tool over SSH without the web UI. They work against
+//! the same database as a running capture (named by `--database` or DATABASE_URL) and
+//! reuse the scanners, endpoint summaries and probes the web UI uses.
+
+use std::io::Write;
+use std::net::Ipv4Addr;
+
+use clap::Subcommand;
+
+use crate::db::{EndpointSummary, get_endpoint_summaries, get_setting_i64, new_connection_result};
+use crate::network::hostname_resolver::HostnameResolver;
+use crate::scanner::ScanType;
+use crate::scanner::netbios::NetBiosScanner;
+use crate::web::{format_last_seen, get_scan_manager};
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a network scan and record what it finds
+    Scan {
+        /// Scan types to run, comma-separated (arp, icmp, ndp, netbios, port, snmp, ssdp)
+        #[arg(long = "type", value_delimiter = ',', required = true)]
+        types: Vec<ScanType>,
+    },
+    /// Work with discovered endpoints
+    Endpoints {
+        #[command(subcommand)]
+        action: EndpointsCommand,
+    },
+    /// Export discovered endpoints
+    Export {
+        #[command(subcommand)]
+        format: ExportCommand,
+    },
+    /// Look up a device's hostname and NetBIOS name
+    Probe {
+        /// IPv4 address of the device
+        ip: Ipv4Addr,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EndpointsCommand {
+    /// List endpoints with their addresses, vendor, type and when they were last seen
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportCommand {
+    /// Endpoints as CSV, the same columns as the spreadsheet export
+    Csv {
+        /// File to write (standard output when omitted)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+}
+
+/// Run a subcommand to completion. Errors are messages for the user.
+pub async fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Scan { types } => scan(types).await,
+        Command::Endpoints {
+            action: EndpointsCommand::List,
+        } => list_endpoints().await,
+        Command::Export {
+            format: ExportCommand::Csv { output },
+        } => export_csv(output).await,
+        Command::Probe { ip } => probe(ip).await,
+    }
+}
+
+async fn scan(types: Vec<ScanType>) -> Result<(), String> {
+    let manager = get_scan_manager();
+    manager.start_scan(types).await?;
+    println!("Scan started");
+
+    let mut last_progress = None;
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        let status = manager.get_status().await;
+        if !status.running {
+            println!("Scan finished: {} devices found", status.discovered_count);
+            break;
+        }
+        let progress = (status.progress_percent, status.current_phase);
+        if last_progress.as_ref() != Some(&progress) {
+            println!(
+                "{:>3}% {} ({} found)",
+                progress.0,
+                progress.1.as_deref().unwrap_or(""),
+                status.discovered_count
+            );
+            last_progress = Some(progress);
+        }
+    }
+
+    // Results are recorded by the scan event task; let it catch up before exiting
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    Ok(())
+}
+
+/// Endpoint summaries as kept by a running capture, by name
+async fn load_summaries() -> Result<Vec<EndpointSummary>, String> {
+    let summaries = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        get_endpoint_summaries(&conn, 0).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if summaries.is_empty() {
+        return Err(
+            "No endpoint summaries in this database yet (they are built while capture runs)"
+                .to_string(),
+        );
+    }
+    Ok(summaries)
+}
+
+async fn list_endpoints() -> Result<(), String> {
+    let summaries = load_summaries().await?;
+    let now = chrono::Utc::now().timestamp();
+    println!(
+        "{:<32} {:<16} {:<18} {:<20} {:<14} LAST SEEN",
+        "NAME", "IP", "MAC", "VENDOR", "TYPE"
+    );
+    for summary in &summaries {
+        println!(
+            "{:<32} {:<16} {:<18} {:<20} {:<14} {}",
+            summary.name,
+            summary.ips.first().map(String::as_str).unwrap_or("-"),
+            summary.macs.first().map(String::as_str).unwrap_or("-"),
+            summary.vendor.as_deref().unwrap_or("-"),
+            summary.device_type.as_deref().unwrap_or("-"),
+            summary
+                .last_seen_at
+                .map(|ts| format_last_seen(now - ts))
+                .unwrap_or_else(|| "-".to_string()),
+        );
+    }
+    println!("{} endpoints", summaries.len());
+    Ok(())
+}
+
+/// Quote a CSV field when it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn endpoints_csv(summaries: &[EndpointSummary], now: i64, active_threshold: i64) -> String {
+    let mut csv = String::from("Name,IP,MAC,Vendor,Model,Device Type,Last Seen,Online\n");
+    for summary in summaries {
+        let online = summary
+            .last_seen_at
+            .is_some_and(|ts| ts >= now - active_threshold);
+        let last_seen = summary
+            .last_seen_at
+            .map(|ts| format_last_seen(now - ts))
+            .unwrap_or_else(|| "-".to_string());
+        let fields = [
+            summary.name.as_str(),
+            &summary.ips.join(", "),
+            &summary.macs.join(", "),
+            summary.vendor.as_deref().unwrap_or(""),
+            summary.model.as_deref().unwrap_or(""),
+            summary.device_type.as_deref().unwrap_or(""),
+            &last_seen,
+            if online { "Yes" } else { "No" },
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+async fn export_csv(output: Option<String>) -> Result<(), String> {
+    let summaries = load_summaries().await?;
+    let active_threshold =
+        tokio::task::spawn_blocking(|| get_setting_i64("active_threshold_seconds", 120))
+            .await
+            .map_err(|e| e.to_string())?;
+    let csv = endpoints_csv(&summaries, chrono::Utc::now().timestamp(), active_threshold);
+    match output {
+        Some(path) => {
+            std::fs::write(&path, csv).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            eprintln!("Wrote {} endpoints to {}", summaries.len(), path);
+        }
+        None => std::io::stdout()
+            .write_all(csv.as_bytes())
+            .map_err(|e| e.to_string())?,
+    }
+    Ok(())
+}
+
+async fn probe(ip: Ipv4Addr) -> Result<(), String> {
+    let config = get_scan_manager().get_config().await;
+    let timeout_ms = config.probe_timeout_ms(ScanType::NETBIOS, 2000);
+    let (hostname, netbios) = tokio::task::spawn_blocking(move || {
+        let hostname = HostnameResolver::resolve(&ip.to_string());
+        let netbios = NetBiosScanner::new().with_timeout(timeout_ms).query_ip(ip);
+        (hostname, netbios)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    println!("IP:           {}", ip);
+    println!("Hostname:     {}", hostname.as_deref().unwrap_or("-"));
+    match netbios {
+        Some(netbios) => {
+            println!("NetBIOS name: {}", netbios.netbios_name);
+            println!(
+                "Workgroup:    {}",
+                netbios.group_name.as_deref().unwrap_or("-")
+            );
+            println!("MAC:          {}", netbios.mac.as_deref().unwrap_or("-"));
+        }
+        None => println!("NetBIOS name: -"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoints_csv() {
+        let summary = EndpointSummary {
+            endpoint_id: 1,
+            name: "Living \"Room\" TV".to_string(),
+            vendor: Some("LG".to_string()),
+            model: None,
+            device_type: Some("tv".to_string()),
+            bytes: 0,
+            last_seen_at: Some(1000),
+            ips: vec!["192.168.1.20".to_string(), "fe80::1".to_string()],
+            macs: vec!["aa:bb:cc:dd:ee:ff".to_string()],
+        };
+        let csv = endpoints_csv(&[summary], 1030, 120);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("Name,IP,MAC,Vendor,Model,Device Type,Last Seen,Online")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                "\"Living \"\"Room\"\" TV\",\"192.168.1.20, fe80::1\",aa:bb:cc:dd:ee:ff,LG,,tv,Just now,Yes"
+            )
+        );
+    }
+
+    #[test]
+    fn test_scan_types_parse() {
+        assert_eq!("ARP".parse::<ScanType>(), Ok(ScanType::ARP));
+        assert_eq!("netbios".parse::<ScanType>(), Ok(ScanType::NETBIOS));
+        assert!("nmap".parse::<ScanType>().is_err());
+    }
+}
@@ -0,0 +1,433 @@
+//! Startup configuration file. `config.toml` (or the file given with `--config` or
+//! `CONFIG_FILE`) groups capture, web, database, scanner, and notification options in one
+//! place. Each value is layered under the existing mechanisms: the file is overridden by
+//! environment variables, which are overridden by settings changed in the web UI, and
+//! command-line flags still beat everything. Options that were environment variables are
+//! applied by exporting the variable when it is unset; options that are runtime settings
+//! are written to the settings table unless the user has changed them there.
+
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::db::validate_setting;
+
+/// Read when no path is given and it exists in the working directory
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+static LOADED_CONFIG: OnceLock<LoadedConfig> = OnceLock::new();
+
+/// Where an effective value came from, lowest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSource {
+    Default,
+    ConfigFile,
+    Environment,
+    Database,
+    CommandLine,
+}
+
+impl ValueSource {
+    /// The form kept in the settings table's `source` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueSource::Default => "default",
+            ValueSource::ConfigFile => "config_file",
+            ValueSource::Environment => "environment",
+            ValueSource::Database => "database",
+            ValueSource::CommandLine => "command_line",
+        }
+    }
+
+    /// Rows without a known source were written through the settings API
+    fn from_setting_source(source: Option<&str>) -> Self {
+        match source {
+            Some("default") => ValueSource::Default,
+            Some("config_file") => ValueSource::ConfigFile,
+            Some("environment") => ValueSource::Environment,
+            _ => ValueSource::Database,
+        }
+    }
+}
+
+/// How an option reaches the code that uses it
+enum Backing {
+    /// Read from an environment variable at startup
+    Environment {
+        var: &'static str,
+        default: &'static str,
+    },
+    /// A runtime setting, optionally also taken from an environment variable
+    Setting {
+        key: &'static str,
+        var: Option<&'static str>,
+    },
+}
+
+struct ConfigOption {
+    /// `section.name` in the file
+    key: &'static str,
+    backing: Backing,
+}
+
+const fn from_env(key: &'static str, var: &'static str, default: &'static str) -> ConfigOption {
+    ConfigOption {
+        key,
+        backing: Backing::Environment { var, default },
+    }
+}
+
+const fn setting(key: &'static str, setting: &'static str) -> ConfigOption {
+    ConfigOption {
+        key,
+        backing: Backing::Setting {
+            key: setting,
+            var: None,
+        },
+    }
+}
+
+const OPTIONS: &[ConfigOption] = &[
+    from_env("capture.interfaces", "MONITOR_INTERFACES", ""),
+    setting("capture.mode", "capture_mode"),
+    setting(
+        "capture.flow_interval_seconds",
+        "flow_aggregation_interval_seconds",
+    ),
+    setting("capture.store_broadcast_traffic", "store_broadcast_traffic"),
+    setting("capture.privacy_mode", "privacy_mode"),
+    from_env("web.port", "WEB_PORT", "8080"),
+    from_env("web.bind", "WEB_BIND", "127.0.0.1"),
+    from_env("web.trusted_proxies", "TRUSTED_PROXIES", ""),
+    from_env("web.advertise", "MDNS_ADVERTISE", "false"),
+    from_env("web.read_replica", "READ_REPLICA", "false"),
+    from_env("control.socket", "CONTROL_SOCKET", ""),
+    from_env("database.path", "DATABASE_URL", ""),
+    from_env(
+        "database.channel_buffer_size",
+        "CHANNEL_BUFFER_SIZE",
+        "50000",
+    ),
+    ConfigOption {
+        key: "database.retention_days",
+        backing: Backing::Setting {
+            key: "data_retention_days",
+            var: Some("DATA_RETENTION_DAYS"),
+        },
+    },
+    setting(
+        "database.cleanup_interval_seconds",
+        "cleanup_interval_seconds",
+    ),
+    setting("scanner.snmp_communities", "snmp_communities"),
+    setting(
+        "scanner.auto_scan_interval_minutes",
+        "auto_scan_interval_minutes",
+    ),
+    setting(
+        "scanner.host_table_interval_seconds",
+        "host_table_interval_seconds",
+    ),
+    setting(
+        "scanner.printer_poll_interval_minutes",
+        "printer_poll_interval_minutes",
+    ),
+    setting(
+        "scanner.nas_poll_interval_minutes",
+        "nas_poll_interval_minutes",
+    ),
+    setting(
+        "scanner.ups_poll_interval_seconds",
+        "ups_poll_interval_seconds",
+    ),
+    setting(
+        "notifications.dedup_cooldown_seconds",
+        "notification_dedup_cooldown_seconds",
+    ),
+    setting(
+        "notifications.certificate_expiry_warning_days",
+        "certificate_expiry_warning_days",
+    ),
+    setting(
+        "notifications.printer_supply_low_percent",
+        "printer_supply_low_percent",
+    ),
+    setting(
+        "notifications.ups_low_battery_percent",
+        "ups_low_battery_percent",
+    ),
+];
+
+/// The file as read at startup, and which options were given elsewhere
+#[derive(Debug, Default)]
+struct LoadedConfig {
+    path: Option<PathBuf>,
+    /// Values from the file by `section.name`, in the form their setting or variable takes
+    values: BTreeMap<String, String>,
+    /// Options whose environment variable was already set when the file was applied
+    from_environment: HashSet<&'static str>,
+    /// Options given as command-line flags, with their values
+    command_line: BTreeMap<&'static str, String>,
+}
+
+/// One option's effective value, for `/api/config/effective`
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveValue {
+    pub key: &'static str,
+    pub value: String,
+    pub source: ValueSource,
+    /// Environment variable that also sets it
+    pub env: Option<&'static str>,
+    /// Runtime setting that holds it
+    pub setting: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveConfig {
+    pub config_file: Option<String>,
+    pub values: Vec<EffectiveValue>,
+}
+
+/// Parse a config file into `section.name` values. Unknown keys and values of the wrong
+/// shape are errors, so a typo doesn't silently leave the default in place.
+fn parse_config(text: &str) -> Result<BTreeMap<String, String>, String> {
+    let table: toml::Table = text.parse().map_err(|e| format!("{}", e))?;
+    let mut values = BTreeMap::new();
+    for (section, entries) in &table {
+        let toml::Value::Table(entries) = entries else {
+            return Err(format!("'{}' must be a [section]", section));
+        };
+        for (name, value) in entries {
+            let key = format!("{}.{}", section, name);
+            let Some(option) = OPTIONS.iter().find(|option| option.key == key) else {
+                return Err(format!("Unknown option '{}'", key));
+            };
+            let value = match value {
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                toml::Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        toml::Value::String(s) => Ok(s.clone()),
+                        _ => Err(format!("'{}' must be a list of strings", key)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(","),
+                _ => return Err(format!("'{}' has an unsupported value", key)),
+            };
+            let value = match option.backing {
+                Backing::Setting { key: setting, .. } => {
+                    validate_setting(setting, &value).map_err(|e| format!("'{}': {}", key, e))?
+                }
+                Backing::Environment { .. } => value,
+            };
+            values.insert(key, value);
+        }
+    }
+    Ok(values)
+}
+
+/// Read the config file and export its environment-backed options. Call once at startup,
+/// before any threads start; `command_line` lists options given as flags, with their
+/// values.
+pub fn load(path: Option<&str>, command_line: &[(&'static str, String)]) -> Result<(), String> {
+    let path = match path
+        .map(str::to_string)
+        .or_else(|| env::var("CONFIG_FILE").ok())
+    {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+    };
+
+    let mut loaded = LoadedConfig {
+        command_line: command_line.iter().cloned().collect(),
+        ..Default::default()
+    };
+    if let Some(path) = path {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        loaded.values = parse_config(&text)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+        println!("Using config file: {}", path.display());
+        loaded.path = Some(path);
+    }
+
+    for option in OPTIONS {
+        let var = match option.backing {
+            Backing::Environment { var, .. } => var,
+            Backing::Setting { var: Some(var), .. } => var,
+            Backing::Setting { var: None, .. } => continue,
+        };
+        if env::var_os(var).is_some() {
+            loaded.from_environment.insert(option.key);
+        } else if let Some(value) = loaded.values.get(option.key) {
+            // SAFETY: called during single-threaded startup, before anything reads the
+            // environment from another thread
+            unsafe { env::set_var(var, value) };
+        }
+    }
+
+    let _ = LOADED_CONFIG.set(loaded);
+    Ok(())
+}
+
+/// The config file read at startup, if there was one
+pub fn loaded_config_path() -> Option<PathBuf> {
+    LOADED_CONFIG.get()?.path.clone()
+}
+
+/// Values for runtime settings that come from the file or the environment rather than
+/// the built-in defaults: (setting, value, source)
+pub fn setting_layers() -> Vec<(&'static str, String, ValueSource)> {
+    let Some(loaded) = LOADED_CONFIG.get() else {
+        return Vec::new();
+    };
+    OPTIONS
+        .iter()
+        .filter_map(|option| {
+            let Backing::Setting { key, var } = option.backing else {
+                return None;
+            };
+            let from_env = var
+                .filter(|_| loaded.from_environment.contains(option.key))
+                .and_then(|var| env::var(var).ok());
+            match from_env {
+                Some(value) => match validate_setting(key, &value) {
+                    Ok(value) => Some((key, value, ValueSource::Environment)),
+                    Err(e) => {
+                        eprintln!("Ignoring {}: {}", var.unwrap_or_default(), e);
+                        None
+                    }
+                },
+                None => loaded
+                    .values
+                    .get(option.key)
+                    .map(|value| (key, value.clone(), ValueSource::ConfigFile)),
+            }
+        })
+        .collect()
+}
+
+/// Every option's current value and where it came from
+pub fn effective_config(conn: &Connection) -> rusqlite::Result<EffectiveConfig> {
+    let empty = LoadedConfig::default();
+    let loaded = LOADED_CONFIG.get().unwrap_or(&empty);
+
+    let mut values = Vec::with_capacity(OPTIONS.len());
+    for option in OPTIONS {
+        let value = match option.backing {
+            Backing::Setting { key, var } => {
+                let row: Option<(String, Option<String>)> = conn
+                    .query_row(
+                        "SELECT value, source FROM settings WHERE key = ?1",
+                        [key],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?;
+                let (value, source) = row.unwrap_or_default();
+                EffectiveValue {
+                    key: option.key,
+                    value,
+                    source: ValueSource::from_setting_source(source.as_deref()),
+                    env: var,
+                    setting: Some(key),
+                }
+            }
+            Backing::Environment { var, default } => {
+                let (value, source) = if let Some(value) = loaded.command_line.get(option.key) {
+                    (value.clone(), ValueSource::CommandLine)
+                } else if loaded.from_environment.contains(option.key) {
+                    (env::var(var).unwrap_or_default(), ValueSource::Environment)
+                } else if let Some(value) = loaded.values.get(option.key) {
+                    (value.clone(), ValueSource::ConfigFile)
+                } else {
+                    // Derived defaults (like the database path) are exported at startup
+                    let value = env::var(var).unwrap_or_else(|_| default.to_string());
+                    (value, ValueSource::Default)
+                };
+                EffectiveValue {
+                    key: option.key,
+                    value,
+                    source,
+                    env: Some(var),
+                    setting: None,
+                }
+            }
+        };
+        values.push(value);
+    }
+
+    Ok(EffectiveConfig {
+        config_file: loaded
+            .path
+            .as_deref()
+            .map(Path::display)
+            .map(|path| path.to_string()),
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let values = parse_config(
+            r#"
+            [capture]
+            interfaces = ["eth0", "wlan0"]
+            mode = "flow"
+
+            [web]
+            port = 9000
+            advertise = true
+
+            [scanner]
+            snmp_communities = ["public", "lan"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(values["capture.interfaces"], "eth0,wlan0");
+        assert_eq!(values["capture.mode"], "flow");
+        assert_eq!(values["web.port"], "9000");
+        assert_eq!(values["web.advertise"], "true");
+        assert_eq!(values["scanner.snmp_communities"], "public,lan");
+
+        assert!(
+            parse_config("[web]\nprot = 9000")
+                .unwrap_err()
+                .contains("web.prot")
+        );
+        assert!(parse_config("port = 9000").is_err());
+        assert!(
+            parse_config("[database]\nretention_days = -3")
+                .unwrap_err()
+                .contains("between")
+        );
+    }
+
+    #[test]
+    fn test_value_source_round_trip() {
+        for source in [
+            ValueSource::Default,
+            ValueSource::ConfigFile,
+            ValueSource::Environment,
+        ] {
+            assert_eq!(
+                ValueSource::from_setting_source(Some(source.as_str())),
+                source
+            );
+        }
+        assert_eq!(
+            ValueSource::from_setting_source(None),
+            ValueSource::Database
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! Local control socket for scripts and agents on the same machine. It speaks JSON-RPC 2.0,
+//! one request per line, over a Unix domain socket (Linux/macOS) or a named pipe (Windows),
+//! so nothing is exposed on the network. The socket sits next to the database (`eth0.db`
+//! gets `eth0.sock`) unless CONTROL_SOCKET names another path, or is `off`.
+//!
+//! Methods: `scan.start {types}`, `scan.stop`, `scan.status`, `endpoints.list {since?}`,
+//! `endpoints.get {name}`, `capture.status`, `capture.pause`, and `capture.resume`.
+
+use std::env;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::db::{
+    get_endpoint_summaries, insert_notification, is_capture_scheduled_off, new_connection,
+    new_connection_result,
+};
+use crate::scanner::ScanType;
+use crate::web::get_scan_manager;
+use crate::{is_capture_paused, set_capture_paused};
+
+/// Longest request line accepted; a client sending more is disconnected
+const MAX_REQUEST_BYTES: u64 = 1024 * 1024;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The method ran but failed
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => json!({ "jsonrpc": "2.0", "error": error, "id": id }),
+    }
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // Methods without required params may be called with none at all
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+/// Run a database read off the async runtime
+async fn blocking<T, F>(f: F) -> Result<T, RpcError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?
+        .map_err(|e| RpcError::new(SERVER_ERROR, e))
+}
+
+#[derive(Deserialize)]
+struct ScanStartParams {
+    types: Vec<ScanType>,
+}
+
+#[derive(Deserialize)]
+struct EndpointsListParams {
+    #[serde(default)]
+    since: i64,
+}
+
+#[derive(Deserialize)]
+struct EndpointsGetParams {
+    name: String,
+}
+
+async fn dispatch(method: &str, raw_params: Value) -> Result<Value, RpcError> {
+    match method {
+        "scan.start" => {
+            let ScanStartParams { types } = params(raw_params)?;
+            get_scan_manager()
+                .start_scan(types.clone())
+                .await
+                .map_err(|e| RpcError::new(SERVER_ERROR, e))?;
+            let names: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+            let details = format!("Scan types: {}", names.join(", "));
+            tokio::task::spawn_blocking(move || {
+                let conn = new_connection();
+                insert_notification(
+                    &conn,
+                    "scan_started",
+                    "Network scan started",
+                    Some(&details),
+                    None,
+                );
+            });
+            Ok(json!({ "started": names }))
+        }
+        "scan.stop" => {
+            get_scan_manager().stop_scan().await;
+            tokio::task::spawn_blocking(|| {
+                let conn = new_connection();
+                insert_notification(&conn, "scan_stopped", "Network scan stopped", None, None);
+            });
+            Ok(json!({ "stopped": true }))
+        }
+        "scan.status" => to_value(get_scan_manager().get_status().await),
+        "endpoints.list" => {
+            let EndpointsListParams { since } = params(raw_params)?;
+            let summaries = blocking(move || {
+                let conn = new_connection_result().map_err(|e| e.to_string())?;
+                get_endpoint_summaries(&conn, since).map_err(|e| e.to_string())
+            })
+            .await?;
+            to_value(summaries)
+        }
+        "endpoints.get" => {
+            let EndpointsGetParams { name } = params(raw_params)?;
+            let summaries = blocking(|| {
+                let conn = new_connection_result().map_err(|e| e.to_string())?;
+                get_endpoint_summaries(&conn, 0).map_err(|e| e.to_string())
+            })
+            .await?;
+            let summary = summaries
+                .into_iter()
+                .find(|s| s.name.eq_ignore_ascii_case(&name))
+                .ok_or_else(|| {
+                    RpcError::new(SERVER_ERROR, format!("Endpoint '{}' not found", name))
+                })?;
+            to_value(summary)
+        }
+        "capture.status" => Ok(capture_status()),
+        "capture.pause" | "capture.resume" => {
+            let paused = method == "capture.pause";
+            blocking(move || {
+                set_capture_paused(paused);
+                Ok(())
+            })
+            .await?;
+            Ok(capture_status())
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{}'", method),
+        )),
+    }
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
+
+fn capture_status() -> Value {
+    json!({
+        "paused": is_capture_paused(),
+        "scheduled_pause": is_capture_scheduled_off(),
+    })
+}
+
+/// Answer one request line
+async fn handle_line(line: &str) -> Value {
+    let request: RpcRequest = match serde_json::from_str::<Value>(line) {
+        Err(e) => return response(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => {
+                return response(
+                    Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, e.to_string())),
+                );
+            }
+        },
+    };
+    if request.jsonrpc.as_deref().is_some_and(|v| v != "2.0") {
+        return response(
+            request.id,
+            Err(RpcError::new(
+                INVALID_REQUEST,
+                "Only JSON-RPC 2.0 is supported",
+            )),
+        );
+    }
+    response(request.id, dispatch(&request.method, request.params).await)
+}
+
+/// Serve one client until it disconnects
+async fn serve_client<S: AsyncRead + AsyncWrite>(stream: S) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match (&mut reader)
+            .take(MAX_REQUEST_BYTES)
+            .read_line(&mut line)
+            .await
+        {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let too_long = !line.ends_with('\n') && line.len() as u64 >= MAX_REQUEST_BYTES;
+        if line.trim().is_empty() && !too_long {
+            continue;
+        }
+        let reply = if too_long {
+            response(
+                Value::Null,
+                Err(RpcError::new(INVALID_REQUEST, "Request is too long")),
+            )
+        } else {
+            handle_line(line.trim()).await
+        };
+        let mut reply = reply.to_string();
+        reply.push('\n');
+        if writer.write_all(reply.as_bytes()).await.is_err() || too_long {
+            return;
+        }
+    }
+}
+
+/// Where the socket goes: CONTROL_SOCKET, or next to the database. None when disabled.
+fn socket_path() -> Option<PathBuf> {
+    match env::var("CONTROL_SOCKET") {
+        Ok(path) if matches!(path.to_lowercase().as_str(), "off" | "false" | "0") => None,
+        Ok(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => Some(default_socket_path(
+            &env::var("DATABASE_URL").unwrap_or_default(),
+        )),
+    }
+}
+
+#[cfg(unix)]
+fn default_socket_path(database_url: &str) -> PathBuf {
+    let db_path = database_url
+        .strip_prefix("sqlite://")
+        .unwrap_or(database_url);
+    if db_path.is_empty() || db_path == ":memory:" {
+        return PathBuf::from("network.sock");
+    }
+    PathBuf::from(db_path).with_extension("sock")
+}
+
+#[cfg(windows)]
+fn default_socket_path(database_url: &str) -> PathBuf {
+    let db_path = database_url
+        .strip_prefix("sqlite://")
+        .unwrap_or(database_url);
+    let stem = std::path::Path::new(db_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|s| !s.is_empty() && *s != ":memory:")
+        .unwrap_or("network");
+    PathBuf::from(format!(r"\\.\pipe\rust_network_discovery_tool-{}", stem))
+}
+
+/// Start the control socket in the background, unless CONTROL_SOCKET is `off`
+pub fn start() {
+    let Some(path) = socket_path() else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = listen(&path).await {
+            eprintln!("Control socket {} unavailable: {}", path.display(), e);
+        }
+    });
+}
+
+#[cfg(unix)]
+async fn listen(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use tokio::net::UnixListener;
+
+    // A socket left behind by an instance that didn't shut down cleanly is replaced; one
+    // that still answers belongs to a running instance
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "path exists and is not a socket",
+            ));
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                "another instance is listening",
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    // Only the user running the tool may connect
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    println!("Control socket listening on {}", path.display());
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(serve_client(stream));
+    }
+}
+
+#[cfg(windows)]
+async fn listen(path: &std::path::Path) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = path.as_os_str();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .reject_remote_clients(true)
+        .create(name)?;
+    println!("Control pipe listening on {}", path.display());
+    loop {
+        server.connect().await?;
+        let client = server;
+        // The next client connects to a fresh instance of the pipe
+        server = ServerOptions::new()
+            .reject_remote_clients(true)
+            .create(name)?;
+        tokio::spawn(serve_client(client));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        EndpointSummary, create_endpoint_summary_table, delete_endpoint_summaries,
+        upsert_endpoint_summaries,
+    };
+
+    #[tokio::test]
+    async fn test_rpc_errors() {
+        let reply = handle_line("{not json").await;
+        assert_eq!(reply["error"]["code"], PARSE_ERROR);
+        assert_eq!(reply["id"], Value::Null);
+
+        let reply = handle_line(r#"{"jsonrpc":"2.0","method":"nope","id":7}"#).await;
+        assert_eq!(reply["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(reply["id"], 7);
+
+        let reply = handle_line(r#"{"jsonrpc":"2.0","method":"scan.start","id":"a"}"#).await;
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+
+        let reply = handle_line(r#"{"jsonrpc":"1.0","method":"capture.status","id":1}"#).await;
+        assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+
+        let reply = handle_line(r#"{"jsonrpc":"2.0","method":"capture.status","id":2}"#).await;
+        assert!(reply["result"]["paused"].is_boolean());
+    }
+
+    #[tokio::test]
+    async fn test_endpoints_methods() {
+        // Seen far in the future, so no other test's endpoints share the window
+        const SEEN: i64 = 4_000_000_000;
+        let name = "rpc-test-endpoint".to_string();
+        let summary = EndpointSummary {
+            endpoint_id: 900_001,
+            name: name.clone(),
+            vendor: Some("Acme".to_string()),
+            model: None,
+            device_type: Some("printer".to_string()),
+            bytes: 1234,
+            last_seen_at: Some(SEEN),
+            ips: vec!["192.0.2.20".to_string()],
+            macs: vec![],
+        };
+        let seeded = name.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = new_connection();
+            create_endpoint_summary_table(&conn).unwrap();
+            delete_endpoint_summaries(&conn, &[seeded]).unwrap();
+            upsert_endpoint_summaries(&conn, &[summary]).unwrap();
+        })
+        .await
+        .unwrap();
+
+        let reply = handle_line(
+            r#"{"jsonrpc":"2.0","method":"endpoints.list","params":{"since":4000000000},"id":1}"#,
+        )
+        .await;
+        let listed = reply["result"].as_array().unwrap();
+        assert_eq!(listed.len(), 1, "{}", reply);
+        assert_eq!(listed[0]["name"], "rpc-test-endpoint");
+        assert_eq!(listed[0]["ips"], json!(["192.0.2.20"]));
+
+        let reply = handle_line(
+            r#"{"jsonrpc":"2.0","method":"endpoints.get","params":{"name":"RPC-Test-Endpoint"},"id":2}"#,
+        )
+        .await;
+        assert_eq!(reply["id"], 2);
+        assert_eq!(reply["result"]["endpoint_id"], 900_001);
+        assert_eq!(reply["result"]["vendor"], "Acme");
+        assert_eq!(reply["result"]["bytes"], 1234);
+
+        let reply = handle_line(
+            r#"{"jsonrpc":"2.0","method":"endpoints.get","params":{"name":"no-such-endpoint"},"id":3}"#,
+        )
+        .await;
+        assert_eq!(reply["error"]["code"], SERVER_ERROR);
+        let reply =
+            handle_line(r#"{"jsonrpc":"2.0","method":"endpoints.get","params":{},"id":4}"#).await;
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+
+        tokio::task::spawn_blocking(move || {
+            delete_endpoint_summaries(&new_connection(), &[name]).unwrap();
+        })
+        .await
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_default_socket_path() {
+        assert_eq!(default_socket_path("eth0.db"), PathBuf::from("eth0.sock"));
+        assert_eq!(
+            default_socket_path("sqlite:///var/lib/nd/network.db"),
+            PathBuf::from("/var/lib/nd/network.sock")
+        );
+        assert_eq!(
+            default_socket_path(":memory:"),
+            PathBuf::from("network.sock")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        let server_path = path.clone();
+        tokio::spawn(async move { listen(&server_path).await });
+        for _ in 0..200 {
+            if path.exists() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+
+        let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"capture.status\",\"id\":1}\n")
+            .await
+            .unwrap();
+        let mut reply = String::new();
+        BufReader::new(reader).read_line(&mut reply).await.unwrap();
+        let reply: Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["id"], 1);
+        assert!(reply["result"]["paused"].is_boolean());
+    }
+}
@@ -0,0 +1,200 @@
+//! Timeline of system activity that explains gaps in the data: live capture starting,
+//! stopping, pausing and its scheduled windows, pcap imports and retention pruning.
+//! Scan runs already have their own table and are merged in when the timeline is read.
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+use super::new_connection_result;
+
+/// Kind given to scan runs on the timeline
+const SCAN_KIND: &str = "scan";
+
+/// One entry on the activity timeline
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActivityEvent {
+    /// e.g. "capture_started", "capture_paused", "pcap_imported", "retention_pruned", "scan"
+    pub kind: String,
+    pub at: i64,
+    /// When the activity finished, for ones that span time (scans)
+    pub ended_at: Option<i64>,
+    pub details: serde_json::Value,
+}
+
+pub fn create_activity_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS activity_events (
+            id INTEGER PRIMARY KEY,
+            created_at INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            details TEXT NOT NULL DEFAULT '{}'
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_activity_events_created_at ON activity_events (created_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn insert_activity_event(
+    conn: &Connection,
+    kind: &str,
+    details: &serde_json::Value,
+    at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO activity_events (created_at, kind, details) VALUES (?1, ?2, ?3)",
+        params![at, kind, details.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Record an activity event now. Failures are logged, never returned: the timeline
+/// must not get in the way of the capture or import being recorded.
+pub fn record_activity_event(kind: &str, details: serde_json::Value) {
+    let result = new_connection_result().and_then(|conn| {
+        insert_activity_event(&conn, kind, &details, chrono::Utc::now().timestamp())
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to record {} activity: {}", kind, e);
+    }
+}
+
+/// Activity between `since` and `until` (inclusive), newest first. Scans count when
+/// they overlap the range. `kind` keeps only one kind of event.
+pub fn get_activity(
+    conn: &Connection,
+    since: i64,
+    until: i64,
+    kind: Option<&str>,
+    limit: usize,
+) -> Result<Vec<ActivityEvent>> {
+    let mut events = Vec::new();
+
+    if kind.is_none_or(|kind| kind != SCAN_KIND) {
+        let mut stmt = conn.prepare(
+            "SELECT kind, created_at, details FROM activity_events
+             WHERE created_at BETWEEN ?1 AND ?2 AND (?3 IS NULL OR kind = ?3)
+             ORDER BY created_at DESC, id DESC LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(params![since, until, kind, limit as i64], |row| {
+            let details: String = row.get(2)?;
+            Ok(ActivityEvent {
+                kind: row.get(0)?,
+                at: row.get(1)?,
+                ended_at: None,
+                details: serde_json::from_str(&details).unwrap_or_default(),
+            })
+        })?;
+        for row in rows {
+            events.push(row?);
+        }
+    }
+
+    if kind.is_none_or(|kind| kind == SCAN_KIND) {
+        let mut stmt = conn.prepare(
+            "SELECT id, started_at, finished_at, scan_types, host_count FROM scan_runs
+             WHERE finished_at >= ?1 AND started_at <= ?2
+             ORDER BY started_at DESC, id DESC LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(params![since, until, limit as i64], |row| {
+            let scan_types: String = row.get(3)?;
+            let scan_types: Vec<&str> = scan_types.split(',').filter(|t| !t.is_empty()).collect();
+            Ok(ActivityEvent {
+                kind: SCAN_KIND.to_string(),
+                at: row.get(1)?,
+                ended_at: Some(row.get(2)?),
+                details: serde_json::json!({
+                    "scan_run_id": row.get::<_, i64>(0)?,
+                    "scan_types": scan_types,
+                    "host_count": row.get::<_, i64>(4)?,
+                }),
+            })
+        })?;
+        for row in rows {
+            events.push(row?);
+        }
+    }
+
+    // Stable sort keeps each source's newest-first tie order
+    events.sort_by_key(|e| std::cmp::Reverse(e.at));
+    events.truncate(limit);
+    Ok(events)
+}
+
+/// Delete activity events older than the retention period
+pub fn purge_activity_events(conn: &Connection, retention_days: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM activity_events WHERE created_at < (strftime('%s', 'now') - ?1)",
+        [retention_days * 24 * 60 * 60],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+    use crate::db::scan_runs::create_scan_runs_table;
+
+    #[test]
+    fn test_activity_merges_scan_runs() {
+        let conn = new_test_connection();
+        create_scan_runs_table(&conn).unwrap();
+        let event = |kind, details: serde_json::Value, at| {
+            insert_activity_event(&conn, kind, &details, at).unwrap()
+        };
+        event(
+            "capture_started",
+            serde_json::json!({"interface": "eth0"}),
+            100,
+        );
+        event("capture_paused", serde_json::json!({}), 200);
+        event("capture_resumed", serde_json::json!({}), 500);
+        event(
+            "retention_pruned",
+            serde_json::json!({"communications": 42}),
+            900,
+        );
+        conn.execute(
+            "INSERT INTO scan_runs (started_at, finished_at, scan_types, host_count, snapshot)
+             VALUES (280, 320, 'arp,icmp', 5, '{}')",
+            [],
+        )
+        .unwrap();
+
+        let kinds = |since, until, kind| {
+            get_activity(&conn, since, until, kind, 100)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(0, 1000, None),
+            vec![
+                "retention_pruned",
+                "capture_resumed",
+                "scan",
+                "capture_paused",
+                "capture_started"
+            ]
+        );
+        // The scan overlaps the start of the range
+        assert_eq!(kinds(300, 600, None), vec!["capture_resumed", "scan"]);
+        assert_eq!(kinds(0, 1000, Some("scan")), vec!["scan"]);
+        assert_eq!(
+            kinds(0, 1000, Some("capture_paused")),
+            vec!["capture_paused"]
+        );
+
+        let scan = get_activity(&conn, 0, 1000, Some("scan"), 1).unwrap();
+        assert_eq!(scan[0].ended_at, Some(320));
+        assert_eq!(
+            scan[0].details["scan_types"],
+            serde_json::json!(["arp", "icmp"])
+        );
+        assert_eq!(get_activity(&conn, 0, 1000, None, 2).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,265 @@
+//! Icons and colors the endpoint table uses for each device type, with optional
+//! overrides per type and per endpoint. Types fall back to the built-in map below;
+//! an endpoint's own icon or color wins over its type's.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+/// Icon and color for every built-in classification
+pub const DEFAULT_TYPE_APPEARANCE: &[(&str, &str, &str)] = &[
+    ("local", "💻", "#06b6d4"),
+    ("gateway", "🌐", "#3b82f6"),
+    ("internet", "🌍", "#6b7280"),
+    ("printer", "🖨️", "#f59e0b"),
+    ("tv", "📺", "#a855f7"),
+    ("gaming", "🎮", "#ef4444"),
+    ("phone", "📱", "#22c55e"),
+    ("virtualization", "🖥", "#8b5cf6"),
+    ("soundbar", "🔊", "#ec4899"),
+    ("appliance", "🏠", "#d97706"),
+    ("nas", "🗄️", "#0ea5e9"),
+    ("ups", "🔋", "#84cc16"),
+    ("other", "❓", "#64748b"),
+];
+
+/// Longest icon accepted, in characters (emoji with modifiers span several)
+const MAX_ICON_CHARS: usize = 16;
+
+pub fn create_appearance_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS device_type_appearance (
+            device_type TEXT PRIMARY KEY,
+            icon TEXT,
+            color TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_appearance (
+            endpoint_id INTEGER PRIMARY KEY REFERENCES endpoints(id) ON DELETE CASCADE,
+            icon TEXT,
+            color TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A device type's effective icon and color
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TypeAppearance {
+    pub icon: String,
+    pub color: String,
+    /// Differs from the built-in default
+    pub customized: bool,
+}
+
+/// An endpoint's own overrides; unset fields use its type's
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointAppearance {
+    pub icon: Option<String>,
+    pub color: Option<String>,
+}
+
+/// Normalize a `#rgb` or `#rrggbb` color to lowercase `#rrggbb`
+pub fn parse_color(color: &str) -> Option<String> {
+    let hex = color.trim().strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    Some(format!("#{}", hex.to_ascii_lowercase()))
+}
+
+/// Trim an icon, rejecting empty and overlong ones
+pub fn parse_icon(icon: &str) -> Option<String> {
+    let icon = icon.trim();
+    if icon.is_empty() || icon.chars().count() > MAX_ICON_CHARS {
+        return None;
+    }
+    Some(icon.to_string())
+}
+
+pub fn is_known_device_type(device_type: &str) -> bool {
+    DEFAULT_TYPE_APPEARANCE
+        .iter()
+        .any(|(t, _, _)| *t == device_type)
+}
+
+/// Icon and color of every built-in device type, with overrides applied
+pub fn get_type_appearances(conn: &Connection) -> Result<HashMap<String, TypeAppearance>> {
+    let mut overrides: HashMap<String, (Option<String>, Option<String>)> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT device_type, icon, color FROM device_type_appearance")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?)))
+    })?;
+    for row in rows {
+        let (device_type, look) = row?;
+        overrides.insert(device_type, look);
+    }
+
+    Ok(DEFAULT_TYPE_APPEARANCE
+        .iter()
+        .map(|(device_type, icon, color)| {
+            let (custom_icon, custom_color) = overrides.remove(*device_type).unwrap_or_default();
+            let appearance = TypeAppearance {
+                customized: custom_icon.is_some() || custom_color.is_some(),
+                icon: custom_icon.unwrap_or_else(|| icon.to_string()),
+                color: custom_color.unwrap_or_else(|| color.to_string()),
+            };
+            (device_type.to_string(), appearance)
+        })
+        .collect())
+}
+
+/// Override a device type's icon and/or color; None for both restores the default
+pub fn set_type_appearance(
+    conn: &Connection,
+    device_type: &str,
+    icon: Option<&str>,
+    color: Option<&str>,
+) -> Result<()> {
+    if icon.is_none() && color.is_none() {
+        conn.execute(
+            "DELETE FROM device_type_appearance WHERE device_type = ?1",
+            [device_type],
+        )?;
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO device_type_appearance (device_type, icon, color)
+             VALUES (?1, ?2, ?3)",
+            params![device_type, icon, color],
+        )?;
+    }
+    Ok(())
+}
+
+/// Endpoints with their own icon or color, keyed by lowercase display name
+pub fn get_endpoint_appearances(conn: &Connection) -> Result<HashMap<String, EndpointAppearance>> {
+    let mut stmt = conn.prepare(
+        "SELECT LOWER(e.display_name), a.icon, a.color
+         FROM endpoint_appearance a
+         JOIN endpoints e ON e.id = a.endpoint_id
+         WHERE e.display_name IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            EndpointAppearance {
+                icon: row.get(1)?,
+                color: row.get(2)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Set an endpoint's own icon and/or color; None for both clears them
+pub fn set_endpoint_appearance(
+    conn: &Connection,
+    endpoint_id: i64,
+    icon: Option<&str>,
+    color: Option<&str>,
+) -> Result<()> {
+    if icon.is_none() && color.is_none() {
+        conn.execute(
+            "DELETE FROM endpoint_appearance WHERE endpoint_id = ?1",
+            [endpoint_id],
+        )?;
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO endpoint_appearance (endpoint_id, icon, color)
+             VALUES (?1, ?2, ?3)",
+            params![endpoint_id, icon, color],
+        )?;
+    }
+    Ok(())
+}
+
+/// Move an endpoint's icon and color to the endpoint it is merged into, unless that
+/// one has its own
+pub fn reassign_endpoint_appearance(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE endpoint_appearance SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_appearance WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_parse_color_and_icon() {
+        assert_eq!(parse_color("#F59E0B").as_deref(), Some("#f59e0b"));
+        assert_eq!(parse_color(" #0af ").as_deref(), Some("#00aaff"));
+        assert!(parse_color("f59e0b").is_none());
+        assert!(parse_color("#f59e0").is_none());
+        assert!(parse_color("#zzzzzz").is_none());
+        assert!(parse_color("red").is_none());
+
+        assert_eq!(parse_icon(" 🧊 ").as_deref(), Some("🧊"));
+        assert!(parse_icon("   ").is_none());
+        assert!(parse_icon(&"x".repeat(MAX_ICON_CHARS + 1)).is_none());
+    }
+
+    #[test]
+    fn test_type_appearance_overrides() {
+        let conn = new_test_connection();
+        let defaults = get_type_appearances(&conn).unwrap();
+        assert_eq!(defaults.len(), DEFAULT_TYPE_APPEARANCE.len());
+        assert_eq!(defaults["printer"].icon, "🖨️");
+        assert!(!defaults["printer"].customized);
+
+        set_type_appearance(&conn, "printer", None, Some("#112233")).unwrap();
+        let printer = &get_type_appearances(&conn).unwrap()["printer"];
+        assert_eq!(printer.icon, "🖨️");
+        assert_eq!(printer.color, "#112233");
+        assert!(printer.customized);
+
+        set_type_appearance(&conn, "printer", None, None).unwrap();
+        assert_eq!(get_type_appearances(&conn).unwrap(), defaults);
+        assert!(is_known_device_type("gaming"));
+        assert!(!is_known_device_type("toaster"));
+    }
+
+    #[test]
+    fn test_endpoint_appearance_reassign() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'nas'), (2, 1, 'nas-old')",
+            [],
+        )
+        .unwrap();
+
+        set_endpoint_appearance(&conn, 1, Some("🗄️"), None).unwrap();
+        set_endpoint_appearance(&conn, 2, None, Some("#ff0000")).unwrap();
+        reassign_endpoint_appearance(&conn, 2, 1).unwrap();
+
+        let appearances = get_endpoint_appearances(&conn).unwrap();
+        assert_eq!(appearances.len(), 1);
+        // The target's own settings win over the merged endpoint's
+        assert_eq!(
+            appearances["nas"],
+            EndpointAppearance {
+                icon: Some("🗄️".to_string()),
+                color: None,
+            }
+        );
+
+        set_endpoint_appearance(&conn, 1, None, None).unwrap();
+        assert!(get_endpoint_appearances(&conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,516 @@
+//! Database backup and restore. Backups are zip archives holding a consistent snapshot
+//! of the live database (including settings and device credentials, taken with the
+//! SQLite online backup API), the startup config file when one was loaded, and a
+//! manifest. Uploaded archives are restored, as are bare database files from before
+//! backups were archived. Optional scheduled backups are written inside the data
+//! directory and rotated.
+
+use std::fs;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use tokio::task;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::{get_database_url, get_setting, get_setting_i64, new_connection_result};
+
+/// Prefix used for backup file names so rotation only touches our own files
+const BACKUP_FILE_PREFIX: &str = "awareness-backup-";
+
+/// Length of the `%Y%m%d-%H%M%S` stamp in backup file names
+const BACKUP_STAMP_LEN: usize = 15;
+
+/// Archive entries
+const ARCHIVE_DATABASE: &str = "database.db";
+const ARCHIVE_CONFIG: &str = "config.toml";
+const ARCHIVE_MANIFEST: &str = "manifest.json";
+
+/// Largest backup accepted for restore, both as uploaded and as extracted from an archive
+pub const MAX_BACKUP_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Tables that must exist in a file for it to be accepted as a restore source
+const REQUIRED_TABLES: &[&str] = &["endpoints", "settings"];
+
+/// Flag to ensure the backup scheduler is only started once
+static BACKUP_SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Number of backups and restores currently running (downloads can overlap the schedule)
+static BACKUPS_IN_PROGRESS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a backup or restore as running for as long as it is alive
+struct BackupInProgress;
+
+impl BackupInProgress {
+    fn start() -> Self {
+        BACKUPS_IN_PROGRESS.fetch_add(1, Ordering::SeqCst);
+        BackupInProgress
+    }
+}
+
+impl Drop for BackupInProgress {
+    fn drop(&mut self) {
+        BACKUPS_IN_PROGRESS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Whether a backup or restore is running right now
+pub fn is_backup_in_progress() -> bool {
+    BACKUPS_IN_PROGRESS.load(Ordering::Relaxed) > 0
+}
+
+/// Build a timestamped backup file name, e.g. "awareness-backup-20240101-120000.zip"
+pub fn backup_file_name() -> String {
+    format!(
+        "{}{}.zip",
+        BACKUP_FILE_PREFIX,
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    )
+}
+
+/// Whether a file name is one `backup_file_name` produced (or the bare `.db` backups
+/// written before archives). Rotation deletes nothing else.
+fn is_backup_file_name(name: &str) -> bool {
+    let Some(rest) = name.strip_prefix(BACKUP_FILE_PREFIX) else {
+        return false;
+    };
+    let Some(stamp) = rest
+        .strip_suffix(".zip")
+        .or_else(|| rest.strip_suffix(".db"))
+    else {
+        return false;
+    };
+    stamp.len() == BACKUP_STAMP_LEN
+        && stamp.char_indices().all(|(i, c)| match i {
+            8 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+/// The directory holding the database, which scheduled backups must stay inside
+fn data_directory() -> PathBuf {
+    let db_url = get_database_url();
+    let db_path = Path::new(db_url.strip_prefix("sqlite://").unwrap_or(&db_url));
+    match db_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => std::env::current_dir().unwrap_or_default(),
+    }
+}
+
+/// Check a `backup_directory` setting: a path relative to the data directory that
+/// doesn't climb out of it. Empty disables scheduled backups.
+pub fn check_backup_directory(value: &str) -> Result<(), String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(());
+    }
+    if Path::new(value)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        Ok(())
+    } else {
+        Err(
+            "expected a directory inside the data directory, without '..' or a leading '/'"
+                .to_string(),
+        )
+    }
+}
+
+/// Where scheduled backups go for a `backup_directory` setting
+fn resolve_backup_directory(setting: &str, data_dir: &Path) -> Result<PathBuf, String> {
+    check_backup_directory(setting)
+        .map(|()| data_dir.join(setting.trim()))
+        .map_err(|e| format!("Invalid backup_directory '{}': {}", setting, e))
+}
+
+/// Write a consistent snapshot of the live database to `dest`.
+/// Uses the SQLite online backup API so concurrent writers are safe.
+pub fn create_backup(dest: &Path) -> Result<(), String> {
+    let _running = BackupInProgress::start();
+    let conn = new_connection_result().map_err(|e| e.to_string())?;
+    conn.backup(DatabaseName::Main, dest, None)
+        .map_err(|e| format!("Backup failed: {}", e))
+}
+
+/// Run `f` on a snapshot of the live database in a temporary file
+fn with_snapshot<T>(f: impl FnOnce(&Path) -> Result<T, String>) -> Result<T, String> {
+    let temp_path =
+        std::env::temp_dir().join(format!("awareness_backup_{}.db", uuid::Uuid::new_v4()));
+    let result = create_backup(&temp_path).and_then(|()| f(&temp_path));
+    let _ = fs::remove_file(&temp_path);
+    result
+}
+
+/// Write a backup archive: the database snapshot, the config file if given, and a
+/// manifest describing them
+fn write_backup_archive<W: Write + Seek>(
+    out: W,
+    snapshot: &Path,
+    config: Option<&Path>,
+) -> Result<W, String> {
+    let archive_error = |e: zip::result::ZipError| format!("Failed to write backup archive: {}", e);
+    let io_error = |e: std::io::Error| format!("Failed to write backup archive: {}", e);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut archive = ZipWriter::new(out);
+    archive
+        .start_file(ARCHIVE_DATABASE, options)
+        .map_err(archive_error)?;
+    let mut database =
+        fs::File::open(snapshot).map_err(|e| format!("Failed to read backup: {}", e))?;
+    std::io::copy(&mut database, &mut archive).map_err(io_error)?;
+
+    let config = match config {
+        Some(path) => Some(
+            fs::read(path)
+                .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+    if let Some(config) = &config {
+        archive
+            .start_file(ARCHIVE_CONFIG, options)
+            .map_err(archive_error)?;
+        archive.write_all(config).map_err(io_error)?;
+    }
+
+    let manifest = serde_json::json!({
+        "format": 1,
+        "created_at": chrono::Utc::now().to_rfc3339(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "database": ARCHIVE_DATABASE,
+        "config": config.is_some().then_some(ARCHIVE_CONFIG),
+    });
+    archive
+        .start_file(ARCHIVE_MANIFEST, options)
+        .map_err(archive_error)?;
+    archive
+        .write_all(manifest.to_string().as_bytes())
+        .map_err(io_error)?;
+
+    archive.finish().map_err(archive_error)
+}
+
+/// Write a backup archive of the live database to `dest`
+pub fn create_backup_archive(dest: &Path) -> Result<(), String> {
+    let config = crate::config::loaded_config_path();
+    with_snapshot(|snapshot| {
+        let file = fs::File::create(dest)
+            .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+        write_backup_archive(file, snapshot, config.as_deref()).map(|_| ())
+    })
+}
+
+/// Produce a backup archive of the live database as bytes (for download)
+pub fn create_backup_bytes() -> Result<Vec<u8>, String> {
+    let config = crate::config::loaded_config_path();
+    with_snapshot(|snapshot| {
+        write_backup_archive(Cursor::new(Vec::new()), snapshot, config.as_deref())
+            .map(Cursor::into_inner)
+    })
+}
+
+/// Whether a file starts like a zip archive (rather than an SQLite database)
+fn is_archive(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| magic == *b"PK\x03\x04")
+}
+
+/// Copy the database snapshot out of a backup archive into `dest`
+fn extract_backup_database(archive: &Path, dest: &Path, max_bytes: u64) -> Result<(), String> {
+    let file = fs::File::open(archive).map_err(|e| format!("Failed to read backup: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Not a valid backup archive: {}", e))?;
+    let mut database = archive
+        .by_name(ARCHIVE_DATABASE)
+        .map_err(|_| format!("Backup archive is missing {}", ARCHIVE_DATABASE))?;
+    let mut out =
+        fs::File::create(dest).map_err(|e| format!("Failed to write temp file: {}", e))?;
+    // The size in the archive's directory is the uploader's claim; count what comes out
+    let copied = std::io::copy(&mut (&mut database).take(max_bytes + 1), &mut out)
+        .map_err(|e| format!("Failed to extract backup: {}", e))?;
+    if copied > max_bytes {
+        return Err(format!(
+            "Backup database is larger than {} MB",
+            max_bytes / (1024 * 1024)
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a file is an intact SQLite database produced by this application
+pub fn validate_backup(path: &Path) -> Result<(), String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Not a valid database file: {}", e))?;
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Not a valid database file: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("Database integrity check failed: {}", integrity));
+    }
+
+    for table in REQUIRED_TABLES {
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+                [table],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !exists {
+            return Err(format!("Backup is missing the '{}' table", table));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the live database contents with the backup at `src`, an archive or a bare
+/// database file. The source is validated first so a bad upload can't wipe existing data.
+pub fn restore_backup(src: &Path) -> Result<(), String> {
+    if is_archive(src) {
+        let temp_path =
+            std::env::temp_dir().join(format!("awareness_restore_{}.db", uuid::Uuid::new_v4()));
+        let result = extract_backup_database(src, &temp_path, MAX_BACKUP_BYTES)
+            .and_then(|()| restore_database(&temp_path));
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+    restore_database(src)
+}
+
+fn restore_database(src: &Path) -> Result<(), String> {
+    validate_backup(src)?;
+
+    let _running = BackupInProgress::start();
+    let mut conn = new_connection_result().map_err(|e| e.to_string())?;
+    conn.restore(
+        DatabaseName::Main,
+        src,
+        None::<fn(rusqlite::backup::Progress)>,
+    )
+    .map_err(|e| format!("Restore failed: {}", e))
+}
+
+/// Delete the oldest backups in `dir`, keeping the newest `keep` files. Only files
+/// named like our backups are considered. Returns the number of files removed.
+pub fn rotate_backups(dir: &Path, keep: usize) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+
+    // Timestamped names sort chronologically
+    let mut backups: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(is_backup_file_name)
+        })
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    backups
+        .into_iter()
+        .take(excess)
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// Run one scheduled backup into the configured directory, then rotate old files
+fn run_scheduled_backup(setting: &str, keep: usize) -> Result<PathBuf, String> {
+    let dir = resolve_backup_directory(setting, &data_directory())?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    let dest = dir.join(backup_file_name());
+    create_backup_archive(&dest)?;
+    let removed = rotate_backups(&dir, keep);
+    if removed > 0 {
+        println!("Removed {} old backup(s) from {}", removed, dir.display());
+    }
+    Ok(dest)
+}
+
+/// Start the scheduled backup task. Backups are written every `backup_interval_hours`
+/// to the `backup_directory` setting inside the data directory (disabled when empty),
+/// keeping the newest `backup_keep_count` files. Settings are re-read each cycle so
+/// changes apply live.
+pub fn start_backup_scheduler() {
+    if BACKUP_SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        // Let tables be created before the first check
+        tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+
+        loop {
+            let interval_hours = get_setting_i64("backup_interval_hours", 24).max(1) as u64;
+
+            let directory = get_setting("backup_directory").filter(|d| !d.trim().is_empty());
+            if let Some(directory) = directory {
+                let keep = get_setting_i64("backup_keep_count", 7).max(1) as usize;
+                let result =
+                    task::spawn_blocking(move || run_scheduled_backup(&directory, keep)).await;
+
+                match result {
+                    Ok(Ok(path)) => println!("Scheduled backup written to {}", path.display()),
+                    Ok(Err(e)) => eprintln!("Scheduled backup failed: {}", e),
+                    Err(e) => eprintln!("Scheduled backup task failed: {}", e),
+                }
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(interval_hours * 3600)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rotate_backups_keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "awareness-backup-20240101-120000.db",
+            "awareness-backup-20240102-120000.db",
+            "awareness-backup-20240103-120000.zip",
+            "awareness-backup-20240104-120000.zip",
+            "awareness-backup-20240105-120000.zip",
+            // Not ours, or not finished: never removed
+            "notes.txt",
+            "network.db",
+            "awareness-backup-latest.zip",
+            "awareness-backup-20230101-120000.zip.part",
+        ] {
+            fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        fs::create_dir(dir.path().join("awareness-backup-20220101-120000.zip")).unwrap();
+
+        assert_eq!(rotate_backups(dir.path(), 2), 3);
+        assert_eq!(
+            file_names(dir.path()),
+            vec![
+                "awareness-backup-20220101-120000.zip",
+                "awareness-backup-20230101-120000.zip.part",
+                "awareness-backup-20240104-120000.zip",
+                "awareness-backup-20240105-120000.zip",
+                "awareness-backup-latest.zip",
+                "network.db",
+                "notes.txt",
+            ]
+        );
+
+        // Within the retention count nothing goes
+        assert_eq!(rotate_backups(dir.path(), 2), 0);
+        assert_eq!(rotate_backups(dir.path(), 10), 0);
+        assert_eq!(rotate_backups(&dir.path().join("missing"), 1), 0);
+    }
+
+    #[test]
+    fn test_backup_file_names() {
+        assert!(is_backup_file_name(&backup_file_name()));
+        assert!(is_backup_file_name("awareness-backup-20240101-120000.db"));
+        assert!(!is_backup_file_name("awareness-backup-2024010-1120000.zip"));
+        assert!(!is_backup_file_name("awareness-backup-20240101-12000.zip"));
+        assert!(!is_backup_file_name("backup-20240101-120000.zip"));
+    }
+
+    #[test]
+    fn test_backup_directory_stays_in_data_dir() {
+        let data_dir = Path::new("/var/lib/awareness");
+        assert_eq!(
+            resolve_backup_directory("backups", data_dir).unwrap(),
+            data_dir.join("backups")
+        );
+        assert_eq!(
+            resolve_backup_directory(" nightly/db ", data_dir).unwrap(),
+            data_dir.join("nightly/db")
+        );
+        assert!(check_backup_directory("").is_ok());
+
+        for value in ["/etc", "../elsewhere", "backups/../../etc", "/"] {
+            assert!(check_backup_directory(value).is_err(), "{}", value);
+            assert!(resolve_backup_directory(value, data_dir).is_err());
+        }
+    }
+
+    #[test]
+    fn test_backup_archive_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("snapshot.db");
+        let conn = Connection::open(&snapshot).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE endpoints (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT);
+             INSERT INTO settings VALUES ('snmp_communities', 'private');",
+        )
+        .unwrap();
+        drop(conn);
+        let config = dir.path().join("config.toml");
+        fs::write(&config, "[web]\nport = 9000\n").unwrap();
+
+        let archive_path = dir.path().join(backup_file_name());
+        let file = fs::File::create(&archive_path).unwrap();
+        write_backup_archive(file, &snapshot, Some(&config)).unwrap();
+        assert!(is_archive(&archive_path));
+        assert!(!is_archive(&snapshot));
+
+        let mut archive = ZipArchive::new(fs::File::open(&archive_path).unwrap()).unwrap();
+        let mut manifest = String::new();
+        archive
+            .by_name(ARCHIVE_MANIFEST)
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["database"], ARCHIVE_DATABASE);
+        assert_eq!(manifest["config"], ARCHIVE_CONFIG);
+        let mut config_copy = String::new();
+        archive
+            .by_name(ARCHIVE_CONFIG)
+            .unwrap()
+            .read_to_string(&mut config_copy)
+            .unwrap();
+        assert_eq!(config_copy, "[web]\nport = 9000\n");
+
+        let extracted = dir.path().join("extracted.db");
+        extract_backup_database(&archive_path, &extracted, MAX_BACKUP_BYTES).unwrap();
+        validate_backup(&extracted).unwrap();
+        let size = fs::metadata(&extracted).unwrap().len();
+        extract_backup_database(&archive_path, &extracted, size).unwrap();
+        // Extraction stops at the limit rather than trusting the archive
+        let err = extract_backup_database(&archive_path, &extracted, size - 1).unwrap_err();
+        assert!(err.contains("larger than"), "{}", err);
+        let community: String = Connection::open(&extracted)
+            .unwrap()
+            .query_row("SELECT value FROM settings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(community, "private");
+
+        // Without a config file the manifest says so
+        let bytes = write_backup_archive(Cursor::new(Vec::new()), &snapshot, None)
+            .unwrap()
+            .into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert!(archive.by_name(ARCHIVE_CONFIG).is_err());
+    }
+}
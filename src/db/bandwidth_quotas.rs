@@ -0,0 +1,532 @@
+//! Daily internet byte quotas, for devices on metered connections. A quota applies to a
+//! single endpoint, to every endpoint carrying a tag, or to every endpoint of a device
+//! type. An endpoint's own quota takes precedence, then the tightest of its tags' quotas,
+//! then its type's. A background checker compares each endpoint's download
+//! plus upload bytes for the current UTC day, from the hourly usage rollups, against its
+//! quota and raises one `quota_exceeded` notification and webhook event per endpoint and day.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+use tokio::task;
+
+use super::{
+    insert_notification_with_endpoint_id, new_connection_result, parse_tag, queue_webhook_event,
+};
+
+/// How often quotas are checked against the rollups
+const CHECK_INTERVAL_SECS: u64 = 300;
+
+/// Days of exceeded quotas kept, so an endpoint is alerted once per day
+const ALERT_RETENTION_DAYS: i64 = 7;
+
+/// Flag to ensure the checker is only started once
+static QUOTA_CHECKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// A quota as submitted through the API: exactly one of an endpoint name, a device type
+/// or a tag
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NewBandwidthQuota {
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub device_type: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    pub daily_bytes: i64,
+}
+
+impl NewBandwidthQuota {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let targets = [
+            self.endpoint.is_some(),
+            self.device_type.is_some(),
+            self.tag.is_some(),
+        ];
+        match targets.iter().filter(|target| **target).count() {
+            0 => return Err("One of endpoint, device_type or tag is required".into()),
+            1 => {}
+            _ => {
+                return Err(
+                    "A quota applies to one endpoint, device type or tag, not several".into(),
+                );
+            }
+        }
+        if let Some(tag) = &self.tag
+            && parse_tag(tag).is_none()
+        {
+            return Err(format!("Invalid tag '{}'", tag));
+        }
+        if self.daily_bytes <= 0 {
+            return Err("daily_bytes must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BandwidthQuota {
+    pub id: i64,
+    pub endpoint_id: Option<i64>,
+    pub endpoint_name: Option<String>,
+    pub device_type: Option<String>,
+    pub tag: Option<String>,
+    pub daily_bytes: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// An endpoint that went over its quota on a day
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaExceeded {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    /// Unix time of the UTC day
+    pub day_start: i64,
+    /// Internet bytes when the quota was found exceeded
+    pub bytes: i64,
+    pub daily_bytes: i64,
+    pub alerted_at: i64,
+}
+
+pub fn create_bandwidth_quota_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS bandwidth_quotas (
+            id INTEGER PRIMARY KEY,
+            endpoint_id INTEGER,
+            device_type TEXT,
+            daily_bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    // Added after the table; fails harmlessly once the column exists
+    let _ = conn.execute("ALTER TABLE bandwidth_quotas ADD COLUMN tag TEXT", []);
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_bandwidth_quotas_endpoint ON bandwidth_quotas (endpoint_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS bandwidth_quota_alerts (
+            endpoint_id INTEGER NOT NULL,
+            day_start INTEGER NOT NULL,
+            bytes INTEGER NOT NULL,
+            daily_bytes INTEGER NOT NULL,
+            alerted_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, day_start)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Set the quota for an endpoint, a device type or a tag (one of them), replacing any it
+/// already has. Returns the quota id.
+pub fn set_bandwidth_quota(
+    conn: &Connection,
+    endpoint_id: Option<i64>,
+    device_type: Option<&str>,
+    tag: Option<&str>,
+    daily_bytes: i64,
+) -> Result<i64> {
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM bandwidth_quotas
+             WHERE endpoint_id IS ?1 AND device_type IS ?2 AND tag IS ?3",
+            params![endpoint_id, device_type, tag],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match existing {
+        Some(id) => {
+            conn.execute(
+                "UPDATE bandwidth_quotas SET daily_bytes = ?1, updated_at = strftime('%s', 'now')
+                 WHERE id = ?2",
+                params![daily_bytes, id],
+            )?;
+            Ok(id)
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO bandwidth_quotas (endpoint_id, device_type, tag, daily_bytes, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, strftime('%s', 'now'), strftime('%s', 'now'))",
+                params![endpoint_id, device_type, tag, daily_bytes],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
+
+/// Remove a quota. Returns false when it didn't exist.
+pub fn delete_bandwidth_quota(conn: &Connection, id: i64) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM bandwidth_quotas WHERE id = ?1", [id])? > 0)
+}
+
+/// Every quota, endpoint quotas first, then tag quotas
+pub fn get_bandwidth_quotas(conn: &Connection) -> Result<Vec<BandwidthQuota>> {
+    let mut stmt = conn.prepare(
+        "SELECT q.id, q.endpoint_id, e.display_name, q.device_type, q.daily_bytes,
+                q.created_at, q.updated_at, q.tag
+         FROM bandwidth_quotas q
+         LEFT JOIN endpoints e ON e.id = q.endpoint_id
+         ORDER BY q.endpoint_id IS NULL, q.tag IS NULL, e.display_name, q.tag, q.device_type",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(BandwidthQuota {
+            id: row.get(0)?,
+            endpoint_id: row.get(1)?,
+            endpoint_name: row.get(2)?,
+            device_type: row.get(3)?,
+            daily_bytes: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+            tag: row.get(7)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Quotas exceeded in the last days, most recent first
+pub fn get_exceeded_quotas(conn: &Connection) -> Result<Vec<QuotaExceeded>> {
+    let mut stmt = conn.prepare(
+        "SELECT a.endpoint_id, e.display_name, a.day_start, a.bytes, a.daily_bytes, a.alerted_at
+         FROM bandwidth_quota_alerts a
+         LEFT JOIN endpoints e ON e.id = a.endpoint_id
+         ORDER BY a.alerted_at DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(QuotaExceeded {
+            endpoint_id: row.get(0)?,
+            endpoint_name: row.get(1)?,
+            day_start: row.get(2)?,
+            bytes: row.get(3)?,
+            daily_bytes: row.get(4)?,
+            alerted_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn format_bytes(bytes: i64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Endpoint id, display name, daily byte limit, quota device type, quota tag and bytes
+/// today
+type CoveredEndpoint = (
+    i64,
+    Option<String>,
+    i64,
+    Option<String>,
+    Option<String>,
+    i64,
+);
+
+/// Compare every endpoint covered by a quota with its internet bytes so far today, and
+/// alert on those that went over for the first time today. Returns the number alerted.
+pub fn check_bandwidth_quotas(conn: &Connection, now: i64) -> Result<usize> {
+    let day_start = now - now.rem_euclid(86400);
+    conn.execute(
+        "DELETE FROM bandwidth_quota_alerts WHERE day_start < ?1",
+        [day_start - ALERT_RETENTION_DAYS * 86400],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.display_name, q.daily_bytes, q.device_type, q.tag,
+                (SELECT COALESCE(SUM(u.wan_bytes_in + u.wan_bytes_out), 0)
+                 FROM endpoint_usage_hourly u
+                 WHERE u.endpoint_id = e.id AND u.hour_start >= ?1)
+         FROM endpoints e
+         JOIN bandwidth_quotas q ON q.id = (
+             SELECT q2.id FROM bandwidth_quotas q2
+             WHERE q2.endpoint_id = e.id
+                OR q2.tag IN (SELECT et.tag FROM endpoint_tags et WHERE et.endpoint_id = e.id)
+                OR (q2.endpoint_id IS NULL AND q2.tag IS NULL
+                    AND q2.device_type = COALESCE(e.manual_device_type, e.auto_device_type))
+             ORDER BY q2.endpoint_id IS NULL, q2.tag IS NULL, q2.daily_bytes
+             LIMIT 1)
+         WHERE NOT EXISTS (SELECT 1 FROM bandwidth_quota_alerts a
+                           WHERE a.endpoint_id = e.id AND a.day_start = ?1)",
+    )?;
+    let covered: Vec<CoveredEndpoint> = stmt
+        .query_map([day_start], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })?
+        .collect::<Result<_>>()?;
+    let over: Vec<_> = covered
+        .into_iter()
+        .filter(|(_, _, daily_bytes, _, _, bytes)| bytes > daily_bytes)
+        .collect();
+
+    for (endpoint_id, name, daily_bytes, device_type, tag, bytes) in &over {
+        conn.execute(
+            "INSERT OR IGNORE INTO bandwidth_quota_alerts
+                 (endpoint_id, day_start, bytes, daily_bytes, alerted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![endpoint_id, day_start, bytes, daily_bytes, now],
+        )?;
+
+        let name = name
+            .clone()
+            .unwrap_or_else(|| format!("endpoint {}", endpoint_id));
+        let quota = match (tag, device_type) {
+            (Some(tag), _) => format!("the '{}' tag quota", tag),
+            (None, Some(device_type)) => format!("the {} quota", device_type),
+            (None, None) => "its quota".to_string(),
+        };
+        let details = format!(
+            "{} of internet traffic today (UTC), over {} of {} a day.",
+            format_bytes(*bytes),
+            quota,
+            format_bytes(*daily_bytes)
+        );
+        insert_notification_with_endpoint_id(
+            conn,
+            "quota_exceeded",
+            &format!("{} exceeded its daily bandwidth quota", name),
+            Some(&details),
+            Some(&name),
+            Some(*endpoint_id),
+        );
+        let payload = serde_json::json!({
+            "event": "quota_exceeded",
+            "endpoint_id": endpoint_id,
+            "endpoint_name": name,
+            "device_type": device_type,
+            "tag": tag,
+            "day_start": day_start,
+            "bytes": bytes,
+            "daily_bytes": daily_bytes,
+        });
+        queue_webhook_event(conn, "quota_exceeded", *endpoint_id, &payload)?;
+    }
+    Ok(over.len())
+}
+
+/// Point quotas and alerts at the endpoint a merged endpoint was folded into. The
+/// surviving endpoint keeps its own quota if it has one.
+pub fn reassign_bandwidth_quotas(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE bandwidth_quotas SET endpoint_id = ?1 WHERE endpoint_id = ?2
+         AND NOT EXISTS (SELECT 1 FROM bandwidth_quotas WHERE endpoint_id = ?1)",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM bandwidth_quotas WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    conn.execute(
+        "UPDATE OR IGNORE bandwidth_quota_alerts SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM bandwidth_quota_alerts WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+/// Start the task that checks quotas against the usage rollups
+pub fn start_bandwidth_quota_checker() {
+    if QUOTA_CHECKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        loop {
+            let result = task::spawn_blocking(|| {
+                let conn = new_connection_result()?;
+                check_bandwidth_quotas(&conn, chrono::Utc::now().timestamp())
+            })
+            .await;
+
+            match result {
+                Ok(Ok(exceeded)) if exceeded > 0 => {
+                    println!("{} endpoint(s) exceeded their bandwidth quota", exceeded)
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("Bandwidth quota check failed: {}", e),
+                Err(e) => eprintln!("Bandwidth quota task failed: {}", e),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{NewWebhook, create_webhook, new_test_connection, record_wan_bytes};
+    use crate::network::direction::TrafficDirection;
+
+    #[test]
+    fn test_quota_exceeded_alerts_once_per_day() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'tv', 'tv'), (2, 1, 'den-tv', 'tv'), (3, 1, 'laptop', 'computer');",
+        )
+        .unwrap();
+        let webhook = NewWebhook {
+            url: "http://automation.lan/hook".to_string(),
+            events: vec!["quota_exceeded".to_string()],
+            ..Default::default()
+        };
+        assert!(webhook.validate().is_ok());
+        create_webhook(&conn, &webhook, &[]).unwrap();
+
+        set_bandwidth_quota(&conn, None, Some("tv"), None, 1_000_000).unwrap();
+        // The endpoint's own quota wins over its type's
+        let own = set_bandwidth_quota(&conn, Some(2), None, None, 5_000_000).unwrap();
+        assert_eq!(
+            set_bandwidth_quota(&conn, Some(2), None, None, 3_000_000).unwrap(),
+            own
+        );
+        assert_eq!(get_bandwidth_quotas(&conn).unwrap().len(), 2);
+
+        let noon = 1_760_616_000; // 2025-10-16 12:00 UTC
+        // Yesterday's traffic doesn't count toward today's quota
+        record_wan_bytes(&conn, 1, TrafficDirection::Inbound, 900_000, noon - 86400).unwrap();
+        record_wan_bytes(&conn, 1, TrafficDirection::Inbound, 800_000, noon).unwrap();
+        record_wan_bytes(&conn, 1, TrafficDirection::Outbound, 300_000, noon + 60).unwrap();
+        record_wan_bytes(&conn, 2, TrafficDirection::Inbound, 2_000_000, noon).unwrap();
+        record_wan_bytes(&conn, 3, TrafficDirection::Inbound, 9_000_000, noon).unwrap();
+
+        assert_eq!(check_bandwidth_quotas(&conn, noon + 3600).unwrap(), 1);
+        let exceeded = get_exceeded_quotas(&conn).unwrap();
+        assert_eq!(exceeded.len(), 1);
+        assert_eq!(exceeded[0].endpoint_id, 1);
+        assert_eq!(exceeded[0].bytes, 1_100_000);
+
+        let queued: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM webhook_deliveries WHERE event_type = 'quota_exceeded'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(queued, 1);
+        let notified: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = 'quota_exceeded'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(notified, 1);
+
+        // Still over, but already alerted today
+        record_wan_bytes(&conn, 1, TrafficDirection::Inbound, 500_000, noon + 7200).unwrap();
+        assert_eq!(check_bandwidth_quotas(&conn, noon + 7200).unwrap(), 0);
+
+        // A new day starts from zero
+        record_wan_bytes(&conn, 1, TrafficDirection::Inbound, 2_000_000, noon + 86400).unwrap();
+        assert_eq!(check_bandwidth_quotas(&conn, noon + 86400).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_quota_validation_and_reassign() {
+        let quota =
+            |endpoint: Option<&str>, device_type: Option<&str>, daily_bytes| NewBandwidthQuota {
+                endpoint: endpoint.map(str::to_string),
+                device_type: device_type.map(str::to_string),
+                daily_bytes,
+                ..Default::default()
+            };
+        assert!(quota(Some("tv"), None, 1).validate().is_ok());
+        assert!(quota(Some("tv"), Some("tv"), 1).validate().is_err());
+        assert!(quota(None, None, 1).validate().is_err());
+        assert!(quota(None, Some("tv"), 0).validate().is_err());
+        let mut tagged = quota(None, None, 1);
+        tagged.tag = Some("kids".to_string());
+        assert!(tagged.validate().is_ok());
+        tagged.device_type = Some("tv".to_string());
+        assert!(tagged.validate().is_err());
+        tagged.device_type = None;
+        tagged.tag = Some("two words".to_string());
+        assert!(tagged.validate().is_err());
+
+        let conn = new_test_connection();
+        set_bandwidth_quota(&conn, Some(1), None, None, 100).unwrap();
+        set_bandwidth_quota(&conn, Some(2), None, None, 200).unwrap();
+        set_bandwidth_quota(&conn, Some(3), None, None, 300).unwrap();
+        reassign_bandwidth_quotas(&conn, 1, 2).unwrap();
+        reassign_bandwidth_quotas(&conn, 3, 4).unwrap();
+        let quotas: Vec<(Option<i64>, i64)> = get_bandwidth_quotas(&conn)
+            .unwrap()
+            .iter()
+            .map(|q| (q.endpoint_id, q.daily_bytes))
+            .collect();
+        assert_eq!(quotas.len(), 2);
+        assert!(quotas.contains(&(Some(2), 200)));
+        assert!(quotas.contains(&(Some(4), 300)));
+
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(1_500_000), "1.5 MB");
+    }
+
+    #[test]
+    fn test_tag_quota_precedence() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'tablet', 'tv'), (2, 1, 'console', 'gaming'),
+                 (3, 1, 'den-tv', 'tv'), (4, 1, 'phone', 'phone');
+             INSERT INTO endpoint_tags (endpoint_id, tag, created_at) VALUES
+                 (1, 'kids', 1), (2, 'kids', 1), (2, 'metered', 1), (3, 'kids', 1);",
+        )
+        .unwrap();
+        set_bandwidth_quota(&conn, None, Some("tv"), None, 10_000).unwrap();
+        set_bandwidth_quota(&conn, None, None, Some("kids"), 2_000).unwrap();
+        set_bandwidth_quota(&conn, None, None, Some("metered"), 1_000).unwrap();
+        set_bandwidth_quota(&conn, Some(3), None, None, 50_000).unwrap();
+        let quotas = get_bandwidth_quotas(&conn).unwrap();
+        assert_eq!(quotas[0].endpoint_id, Some(3));
+        assert_eq!(quotas[1].tag.as_deref(), Some("kids"));
+
+        let noon = 1_760_616_000;
+        for endpoint_id in 1..=4 {
+            record_wan_bytes(&conn, endpoint_id, TrafficDirection::Inbound, 5_000, noon).unwrap();
+        }
+        assert_eq!(check_bandwidth_quotas(&conn, noon + 60).unwrap(), 2);
+        let mut exceeded: Vec<(i64, i64)> = get_exceeded_quotas(&conn)
+            .unwrap()
+            .iter()
+            .map(|q| (q.endpoint_id, q.daily_bytes))
+            .collect();
+        exceeded.sort_unstable();
+        // The tag quota wins over the tv quota, the tightest of two tags applies, the
+        // endpoint's own quota wins over its tag's, and untagged phones have no quota
+        assert_eq!(exceeded, vec![(1, 2_000), (2, 1_000)]);
+
+        let details: String = conn
+            .query_row(
+                "SELECT details FROM notifications WHERE endpoint_id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(details.contains("the 'kids' tag quota"));
+    }
+}
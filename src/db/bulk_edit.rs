@@ -0,0 +1,340 @@
+//! Bulk endpoint edits. One change set (device type, vendor, model, tags, trust) is applied
+//! to a list of endpoints in a single transaction, so either every endpoint is changed or none is, and
+//! one notification sums up the edit instead of one per endpoint. Each change still feeds
+//! classification feedback, the same as editing the endpoints one at a time.
+
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    FeedbackKind, add_endpoint_tag, insert_notification, is_known_device_type, parse_tags,
+    record_classification_feedback, remove_endpoint_tag, set_endpoint_trusted,
+};
+use crate::network::endpoint::EndPoint;
+
+/// Most endpoints one bulk edit may change
+const MAX_BULK_ENDPOINTS: usize = 500;
+
+/// Endpoint names listed in a notification before the rest are counted
+const NAMES_IN_SUMMARY: usize = 5;
+
+/// A change set for many endpoints. Fields left out are not changed; "auto" or an empty
+/// string clears the override so the detected value is used again. Tags are added and
+/// removed without touching the endpoints' other tags.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BulkEndpointEdit {
+    pub endpoints: Vec<String>,
+    #[serde(default)]
+    pub device_type: Option<String>,
+    #[serde(default)]
+    pub vendor: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+    #[serde(default)]
+    pub trusted: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BulkEditOutcome {
+    pub updated: usize,
+    /// What was changed, e.g. "type set to 'appliance', vendor cleared"
+    pub summary: String,
+}
+
+/// None for "auto" and empty strings, which clear an override
+fn override_value(value: &str) -> Option<&str> {
+    (value != "auto" && !value.is_empty()).then_some(value)
+}
+
+fn describe(field: &str, value: &str) -> String {
+    match override_value(value) {
+        Some(value) => format!("{} set to '{}'", field, value),
+        None => format!("{} cleared", field),
+    }
+}
+
+impl BulkEndpointEdit {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.endpoints.is_empty() {
+            return Err("No endpoints given".to_string());
+        }
+        if self.endpoints.len() > MAX_BULK_ENDPOINTS {
+            return Err(format!(
+                "At most {} endpoints can be edited at once",
+                MAX_BULK_ENDPOINTS
+            ));
+        }
+        if self.device_type.is_none()
+            && self.vendor.is_none()
+            && self.model.is_none()
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+            && self.trusted.is_none()
+        {
+            return Err("Nothing to change".to_string());
+        }
+        let (add, remove) = (parse_tags(&self.add_tags)?, parse_tags(&self.remove_tags)?);
+        if let Some(tag) = add.iter().find(|tag| remove.contains(tag)) {
+            return Err(format!("Tag '{}' is both added and removed", tag));
+        }
+        if let Some(device_type) = self.device_type.as_deref().and_then(override_value)
+            && !is_known_device_type(device_type)
+        {
+            return Err(format!("Unknown device type '{}'", device_type));
+        }
+        Ok(())
+    }
+
+    fn summary(&self, add_tags: &[String], remove_tags: &[String]) -> String {
+        let tags = |verb: &str, tags: &[String]| {
+            (!tags.is_empty()).then(|| format!("tags {} {}", verb, tags.join(", ")))
+        };
+        [
+            self.device_type.as_deref().map(|t| describe("type", t)),
+            self.vendor.as_deref().map(|v| describe("vendor", v)),
+            self.model.as_deref().map(|m| describe("model", m)),
+            tags("added:", add_tags),
+            tags("removed:", remove_tags),
+            self.trusted
+                .map(|trusted| if trusted { "trusted" } else { "untrusted" }.to_string()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+/// Ids of the endpoints a name refers to, matched the same way the override setters
+/// match it
+fn endpoint_ids(conn: &Connection, name: &str) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT e.id FROM endpoints e
+         LEFT JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
+         WHERE LOWER(e.name) = LOWER(?1)
+            OR LOWER(e.custom_name) = LOWER(?1)
+            OR LOWER(ea.hostname) = LOWER(?1)
+            OR LOWER(ea.ip) = LOWER(?1)",
+    )?;
+    let rows = stmt.query_map(params![name], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Apply a validated bulk edit. Nothing is changed when any endpoint is unknown; the
+/// error names them.
+pub fn apply_bulk_edit(
+    conn: &Connection,
+    edit: &BulkEndpointEdit,
+    now: i64,
+) -> std::result::Result<BulkEditOutcome, String> {
+    apply(conn, edit, now).map_err(|e| format!("Database error: {}", e))?
+}
+
+fn apply(
+    conn: &Connection,
+    edit: &BulkEndpointEdit,
+    now: i64,
+) -> Result<std::result::Result<BulkEditOutcome, String>> {
+    let (add_tags, remove_tags) = match (parse_tags(&edit.add_tags), parse_tags(&edit.remove_tags))
+    {
+        (Ok(add), Ok(remove)) => (add, remove),
+        (Err(e), _) | (_, Err(e)) => return Ok(Err(e)),
+    };
+    let tx = conn.unchecked_transaction()?;
+    let mut missing = Vec::new();
+    for name in &edit.endpoints {
+        let mut updated = 0;
+        if !add_tags.is_empty() || !remove_tags.is_empty() || edit.trusted.is_some() {
+            let ids = endpoint_ids(&tx, name)?;
+            for &id in &ids {
+                for tag in &add_tags {
+                    add_endpoint_tag(&tx, id, tag, now)?;
+                }
+                for tag in &remove_tags {
+                    remove_endpoint_tag(&tx, id, tag)?;
+                }
+                if let Some(trusted) = edit.trusted {
+                    set_endpoint_trusted(&tx, id, trusted, now)?;
+                }
+            }
+            updated += ids.len();
+        }
+        if let Some(device_type) = &edit.device_type {
+            let value = override_value(device_type);
+            updated += EndPoint::set_manual_device_type(&tx, name, value)?;
+            record_classification_feedback(&tx, name, FeedbackKind::DeviceType, value, now)?;
+        }
+        if let Some(vendor) = &edit.vendor {
+            let value = override_value(vendor);
+            updated += EndPoint::set_custom_vendor(&tx, name, value)?;
+            record_classification_feedback(&tx, name, FeedbackKind::Vendor, value, now)?;
+        }
+        if let Some(model) = &edit.model {
+            let value = override_value(model);
+            updated += EndPoint::set_custom_model(&tx, name, value)?;
+            record_classification_feedback(&tx, name, FeedbackKind::Model, value, now)?;
+        }
+        if updated == 0 {
+            missing.push(name.as_str());
+        }
+    }
+    if !missing.is_empty() {
+        // Dropping the transaction rolls everything back
+        return Ok(Err(format!("Endpoints not found: {}", missing.join(", "))));
+    }
+
+    let summary = edit.summary(&add_tags, &remove_tags);
+    let mut names = edit
+        .endpoints
+        .iter()
+        .take(NAMES_IN_SUMMARY)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if edit.endpoints.len() > NAMES_IN_SUMMARY {
+        names.push_str(&format!(
+            " and {} more",
+            edit.endpoints.len() - NAMES_IN_SUMMARY
+        ));
+    }
+    insert_notification(
+        &tx,
+        "endpoints_bulk_edited",
+        &format!("{} endpoints edited: {}", edit.endpoints.len(), summary),
+        Some(&names),
+        None,
+    );
+    tx.commit()?;
+    Ok(Ok(BulkEditOutcome {
+        updated: edit.endpoints.len(),
+        summary,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{get_endpoint_tags, is_endpoint_trusted, new_test_connection};
+
+    fn edit(endpoints: &[&str]) -> BulkEndpointEdit {
+        BulkEndpointEdit {
+            endpoints: endpoints.iter().map(|e| e.to_string()).collect(),
+            device_type: Some("appliance".to_string()),
+            vendor: Some("auto".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bulk_edit_validation() {
+        assert!(edit(&["cam-1"]).validate().is_ok());
+        assert!(edit(&[]).validate().is_err());
+        assert!(
+            BulkEndpointEdit {
+                endpoints: vec!["cam-1".to_string()],
+                ..Default::default()
+            }
+            .validate()
+            .is_err()
+        );
+        let mut unknown = edit(&["cam-1"]);
+        unknown.device_type = Some("toaster-oven".to_string());
+        assert!(unknown.validate().is_err());
+    }
+
+    #[test]
+    fn test_bulk_edit_is_all_or_nothing() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, custom_vendor) VALUES
+                 (1, 1, 'cam-1', 'Acme'), (2, 1, 'cam-2', 'Acme');",
+        )
+        .unwrap();
+        let types = || -> Vec<Option<String>> {
+            conn.prepare("SELECT manual_device_type FROM endpoints ORDER BY id")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap()
+        };
+
+        let err = apply_bulk_edit(&conn, &edit(&["cam-1", "cam-9"]), 100).unwrap_err();
+        assert!(err.contains("cam-9"));
+        assert_eq!(types(), vec![None, None]);
+
+        let outcome = apply_bulk_edit(&conn, &edit(&["cam-1", "cam-2"]), 100).unwrap();
+        assert_eq!(outcome.updated, 2);
+        assert_eq!(outcome.summary, "type set to 'appliance', vendor cleared");
+        assert_eq!(
+            types(),
+            vec![Some("appliance".to_string()), Some("appliance".to_string())]
+        );
+        let vendors: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM endpoints WHERE custom_vendor IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(vendors, 0);
+        let notifications: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = 'endpoints_bulk_edited'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(notifications, 1);
+    }
+
+    #[test]
+    fn test_bulk_edit_tags_and_trust() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'cam-1'), (2, 1, 'cam-2');
+             INSERT INTO endpoint_tags (endpoint_id, tag, created_at) VALUES (2, 'guest', 1);",
+        )
+        .unwrap();
+        let tag_edit = BulkEndpointEdit {
+            endpoints: vec!["cam-1".to_string(), "CAM-2".to_string()],
+            add_tags: vec!["IoT".to_string()],
+            remove_tags: vec!["guest".to_string()],
+            trusted: Some(true),
+            ..Default::default()
+        };
+        assert!(tag_edit.validate().is_ok());
+
+        let outcome = apply_bulk_edit(&conn, &tag_edit, 100).unwrap();
+        assert_eq!(outcome.updated, 2);
+        assert_eq!(
+            outcome.summary,
+            "tags added: iot, tags removed: guest, trusted"
+        );
+        assert_eq!(get_endpoint_tags(&conn, &[1]).unwrap(), vec!["iot"]);
+        assert_eq!(get_endpoint_tags(&conn, &[2]).unwrap(), vec!["iot"]);
+        assert!(is_endpoint_trusted(&conn, &[1]).unwrap());
+        assert!(is_endpoint_trusted(&conn, &[2]).unwrap());
+
+        // An unknown endpoint rolls the tags back too
+        let untrust = BulkEndpointEdit {
+            endpoints: vec!["cam-1".to_string(), "cam-9".to_string()],
+            remove_tags: vec!["iot".to_string()],
+            trusted: Some(false),
+            ..Default::default()
+        };
+        assert!(apply_bulk_edit(&conn, &untrust, 200).is_err());
+        assert_eq!(get_endpoint_tags(&conn, &[1]).unwrap(), vec!["iot"]);
+        assert!(is_endpoint_trusted(&conn, &[1]).unwrap());
+
+        let mut invalid = untrust.clone();
+        invalid.add_tags = vec!["not a tag".to_string()];
+        assert!(invalid.validate().is_err());
+        invalid.add_tags = vec!["IOT".to_string()];
+        assert!(invalid.validate().is_err());
+    }
+}
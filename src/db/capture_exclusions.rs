@@ -0,0 +1,183 @@
+//! Capture exclusions ("do not record"). Excluded devices are listed by MAC, so the
+//! capture writer can drop their communications before anything is inserted, whichever
+//! IP they are using. An endpoint is excluded by listing every MAC it is known by; the
+//! endpoint id is kept only to show which device an entry belongs to.
+
+use std::collections::HashSet;
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+use crate::network::communication::Communication;
+
+/// One excluded MAC
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureExclusion {
+    pub mac: String,
+    pub endpoint_id: Option<i64>,
+    pub endpoint_name: Option<String>,
+    pub created_at: i64,
+}
+
+pub fn create_capture_exclusions_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS capture_exclusions (
+            mac TEXT PRIMARY KEY,
+            endpoint_id INTEGER,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Exclude a single MAC, attributed to the endpoint currently known by it (if any)
+pub fn exclude_mac(conn: &Connection, mac: &str) -> Result<()> {
+    let mac = mac.to_lowercase();
+    conn.execute(
+        "INSERT INTO capture_exclusions (mac, endpoint_id, created_at)
+         VALUES (?1, (SELECT endpoint_id FROM endpoint_attributes
+                      WHERE LOWER(mac) = ?1 ORDER BY created_at DESC LIMIT 1),
+                 strftime('%s', 'now'))
+         ON CONFLICT(mac) DO NOTHING",
+        [&mac],
+    )?;
+    Ok(())
+}
+
+/// Exclude every MAC an endpoint is known by. Returns how many MACs are now excluded
+/// for it (0 when the endpoint has no MAC to exclude by).
+pub fn exclude_endpoint(conn: &Connection, endpoint_id: i64) -> Result<usize> {
+    conn.execute(
+        "INSERT INTO capture_exclusions (mac, endpoint_id, created_at)
+         SELECT DISTINCT LOWER(mac), ?1, strftime('%s', 'now') FROM endpoint_attributes
+         WHERE endpoint_id = ?1 AND mac IS NOT NULL AND mac != ''
+         ON CONFLICT(mac) DO UPDATE SET endpoint_id = excluded.endpoint_id",
+        [endpoint_id],
+    )?;
+    conn.query_row(
+        "SELECT COUNT(*) FROM capture_exclusions WHERE endpoint_id = ?1",
+        [endpoint_id],
+        |row| row.get(0),
+    )
+}
+
+/// Stop excluding a MAC. Returns false when it wasn't excluded.
+pub fn include_mac(conn: &Connection, mac: &str) -> Result<bool> {
+    let removed = conn.execute(
+        "DELETE FROM capture_exclusions WHERE mac = ?1",
+        [mac.to_lowercase()],
+    )?;
+    Ok(removed > 0)
+}
+
+/// Stop excluding an endpoint. Returns the number of MACs removed from the list.
+pub fn include_endpoint(conn: &Connection, endpoint_id: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM capture_exclusions WHERE endpoint_id = ?1",
+        [endpoint_id],
+    )
+}
+
+/// The exclusion list, newest first
+pub fn get_capture_exclusions(conn: &Connection) -> Result<Vec<CaptureExclusion>> {
+    let mut stmt = conn.prepare(
+        "SELECT x.mac, x.endpoint_id, e.display_name, x.created_at
+         FROM capture_exclusions x
+         LEFT JOIN endpoints e ON e.id = x.endpoint_id
+         ORDER BY x.created_at DESC, x.mac",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(CaptureExclusion {
+            mac: row.get(0)?,
+            endpoint_id: row.get(1)?,
+            endpoint_name: row.get(2)?,
+            created_at: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Excluded MACs (lowercase), as checked by the capture writer
+pub fn load_excluded_macs(conn: &Connection) -> HashSet<String> {
+    let Ok(mut stmt) = conn.prepare("SELECT mac FROM capture_exclusions") else {
+        return HashSet::new();
+    };
+    stmt.query_map([], |row| row.get(0))
+        .map(|rows| rows.flatten().collect())
+        .unwrap_or_default()
+}
+
+/// Whether any of the endpoints has an excluded MAC
+pub fn is_capture_excluded(conn: &Connection, endpoint_ids: &[i64]) -> bool {
+    endpoint_ids.iter().any(|endpoint_id| {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM capture_exclusions WHERE endpoint_id = ?1)",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(false)
+    })
+}
+
+/// Whether a communication was sent or received by an excluded device
+pub fn is_excluded_communication(
+    excluded: &HashSet<String>,
+    communication: &Communication,
+) -> bool {
+    !excluded.is_empty()
+        && [&communication.source_mac, &communication.destination_mac]
+            .into_iter()
+            .flatten()
+            .any(|mac| excluded.contains(&mac.to_lowercase()))
+}
+
+/// Point exclusions at the endpoint a merged endpoint was folded into
+pub fn reassign_capture_exclusions(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE capture_exclusions SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_capture_exclusions() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'work-laptop'), (2, 1, 'phone');
+             INSERT INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname) VALUES
+                 (1, 1, 'AA:BB:CC:00:00:01', '192.168.1.10', 'work-laptop'),
+                 (2, 1, 'aa:bb:cc:00:00:02', '192.168.1.11', 'work-laptop'),
+                 (3, 2, 'aa:bb:cc:00:00:03', '192.168.1.12', 'phone');",
+        )
+        .unwrap();
+
+        assert_eq!(exclude_endpoint(&conn, 1).unwrap(), 2);
+        let excluded = load_excluded_macs(&conn);
+        assert!(excluded.contains("aa:bb:cc:00:00:01"));
+        assert!(!excluded.contains("aa:bb:cc:00:00:03"));
+        assert!(is_capture_excluded(&conn, &[1]));
+        assert!(!is_capture_excluded(&conn, &[2]));
+
+        let mut communication = Communication::default();
+        communication.destination_mac = Some("aa:bb:cc:00:00:02".to_string());
+        assert!(is_excluded_communication(&excluded, &communication));
+        communication.destination_mac = Some("aa:bb:cc:00:00:03".to_string());
+        assert!(!is_excluded_communication(&excluded, &communication));
+
+        reassign_capture_exclusions(&conn, 1, 2).unwrap();
+        let exclusions = get_capture_exclusions(&conn).unwrap();
+        assert!(exclusions.iter().all(|x| x.endpoint_id == Some(2)));
+        assert_eq!(exclusions[0].endpoint_name.as_deref(), Some("phone"));
+
+        assert!(include_mac(&conn, "AA:BB:CC:00:00:01").unwrap());
+        assert_eq!(include_endpoint(&conn, 2).unwrap(), 1);
+        assert!(load_excluded_macs(&conn).is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! Scheduled capture windows. Live capture can be limited to daily time windows (for
+//! example 08:00-23:00) and held while a backup or restore runs. The schedule is stored
+//! as JSON in the `capture_schedule` setting and re-evaluated by a background task, so
+//! the capture loop only reads an atomic flag per packet.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use tokio::task;
+
+use super::activity::record_activity_event;
+use super::backup::is_backup_in_progress;
+use super::{get_setting, set_setting};
+
+/// Setting key holding the schedule as JSON
+const CAPTURE_SCHEDULE_SETTING: &str = "capture_schedule";
+
+/// How often the schedule is checked against the clock
+const SCHEDULE_CHECK_SECS: u64 = 30;
+
+/// Flag to ensure the capture scheduler is only started once
+static CAPTURE_SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The current local time is outside every configured window
+static OUTSIDE_CAPTURE_WINDOW: AtomicBool = AtomicBool::new(false);
+
+/// Mirrors `CaptureSchedule::pause_during_backups` for the per-packet check
+static PAUSE_DURING_BACKUPS: AtomicBool = AtomicBool::new(false);
+
+/// A daily time range during which live capture runs. A window that ends before it
+/// starts runs past midnight; one that ends when it starts covers the whole day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureWindow {
+    /// Days the window starts on ("mon", "tue", ...); empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Local time, "HH:MM"
+    pub start: NaiveTime,
+    /// Local time, "HH:MM"
+    pub end: NaiveTime,
+}
+
+impl CaptureWindow {
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = now.weekday();
+        if self.start < self.end {
+            self.starts_on(today) && self.start <= time && time < self.end
+        } else if self.start > self.end {
+            (self.starts_on(today) && time >= self.start)
+                || (self.starts_on(today.pred()) && time < self.end)
+        } else {
+            self.starts_on(today)
+        }
+    }
+}
+
+/// When live capture is allowed to run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureSchedule {
+    /// Capture only inside these windows; empty means capture at any time
+    #[serde(default)]
+    pub windows: Vec<CaptureWindow>,
+    /// Hold capture while a backup or restore is running
+    #[serde(default)]
+    pub pause_during_backups: bool,
+}
+
+impl CaptureSchedule {
+    /// Whether the windows allow capture at local time `now`
+    pub fn allows(&self, now: NaiveDateTime) -> bool {
+        self.windows.is_empty() || self.windows.iter().any(|window| window.contains(now))
+    }
+}
+
+/// The stored schedule; a missing or unreadable setting means "always capture"
+pub fn get_capture_schedule() -> CaptureSchedule {
+    get_setting(CAPTURE_SCHEDULE_SETTING)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Store the schedule and apply it to live capture right away
+pub fn set_capture_schedule(schedule: &CaptureSchedule) -> Result<(), String> {
+    let json = serde_json::to_string(schedule).map_err(|e| e.to_string())?;
+    set_setting(CAPTURE_SCHEDULE_SETTING, &json)
+        .map_err(|e| format!("Failed to save capture schedule: {}", e))?;
+    apply_capture_schedule(schedule);
+    Ok(())
+}
+
+/// Update the flags read by the capture loop. Returns true when this changed whether
+/// the current time is inside a window.
+fn apply_capture_schedule(schedule: &CaptureSchedule) -> bool {
+    PAUSE_DURING_BACKUPS.store(schedule.pause_during_backups, Ordering::Relaxed);
+    let outside = !schedule.allows(chrono::Local::now().naive_local());
+    OUTSIDE_CAPTURE_WINDOW.swap(outside, Ordering::Relaxed) != outside
+}
+
+/// Whether the schedule is holding live capture: outside every window, or a backup is
+/// running and the schedule pauses for backups
+pub fn is_capture_scheduled_off() -> bool {
+    OUTSIDE_CAPTURE_WINDOW.load(Ordering::Relaxed)
+        || (PAUSE_DURING_BACKUPS.load(Ordering::Relaxed) && is_backup_in_progress())
+}
+
+/// Start the task that opens and closes capture windows. The schedule is re-read each
+/// check so changes made through the settings table also apply.
+pub fn start_capture_scheduler() {
+    if CAPTURE_SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        loop {
+            let schedule = task::spawn_blocking(get_capture_schedule)
+                .await
+                .unwrap_or_default();
+            if apply_capture_schedule(&schedule) {
+                let kind = if OUTSIDE_CAPTURE_WINDOW.load(Ordering::Relaxed) {
+                    println!("Capture window closed - live traffic will be ignored");
+                    "capture_window_closed"
+                } else {
+                    println!("Capture window opened - live traffic will be processed");
+                    "capture_window_opened"
+                };
+                let _ = task::spawn_blocking(move || {
+                    record_activity_event(kind, serde_json::json!({}))
+                })
+                .await;
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(SCHEDULE_CHECK_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        // 2024-01-01 was a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_time(time.parse().unwrap())
+    }
+
+    #[test]
+    fn test_capture_schedule_windows() {
+        let schedule: CaptureSchedule = serde_json::from_str(
+            r#"{"windows": [
+                {"days": ["mon", "tue", "wed", "thu", "fri"], "start": "08:00", "end": "23:00"},
+                {"days": ["sat"], "start": "22:00", "end": "02:00"}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert!(schedule.allows(at(1, "08:00:00")));
+        assert!(!schedule.allows(at(1, "23:00:00")));
+        assert!(!schedule.allows(at(1, "07:59:59")));
+        // Saturday's window runs into Sunday morning
+        assert!(!schedule.allows(at(6, "12:00:00")));
+        assert!(schedule.allows(at(6, "22:30:00")));
+        assert!(schedule.allows(at(7, "01:59:00")));
+        assert!(!schedule.allows(at(7, "02:00:00")));
+        // Sunday night isn't covered by Saturday's window
+        assert!(!schedule.allows(at(7, "22:30:00")));
+
+        assert!(CaptureSchedule::default().allows(at(3, "03:00:00")));
+    }
+}
@@ -0,0 +1,468 @@
+//! What users teach the classifier. Each manual device type, vendor or model override
+//! is recorded with the signals the endpoint had at the time (hostname, MAC prefix,
+//! SSDP strings), and overrides the built-in rules missed are turned into suggested
+//! additions to tools/device-rules-generator/device_rules.toml and
+//! tools/oui-generator/overrides.toml. Rows are snapshots, so they outlive the endpoint.
+
+use std::collections::BTreeMap;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::network::endpoint::{
+    get_hostname_vendor, get_mac_vendor, is_locally_administered_mac, strip_local_suffix,
+};
+
+pub fn create_classification_feedback_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS classification_feedback (
+            id INTEGER PRIMARY KEY,
+            endpoint_id INTEGER NOT NULL,
+            endpoint_name TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            value TEXT NOT NULL,
+            detected TEXT,
+            hostname TEXT,
+            mac_prefix TEXT,
+            ssdp_model TEXT,
+            ssdp_friendly_name TEXT,
+            created_at INTEGER NOT NULL,
+            UNIQUE(endpoint_id, kind)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Which manual override a feedback row records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackKind {
+    DeviceType,
+    Vendor,
+    Model,
+}
+
+impl FeedbackKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FeedbackKind::DeviceType => "device_type",
+            FeedbackKind::Vendor => "vendor",
+            FeedbackKind::Model => "model",
+        }
+    }
+}
+
+/// A rule that would have produced what a user set by hand
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleSuggestion {
+    /// "device_rules.toml" or "overrides.toml"
+    pub file: &'static str,
+    /// TOML section the entry belongs in, e.g. "patterns.printer" or "overrides"
+    pub section: String,
+    /// The hostname pattern, vendor name or OUI prefix to add
+    pub key: String,
+    /// What the rule maps to (the vendor or model; the class for list sections)
+    pub value: String,
+    /// Endpoints whose overrides support it
+    pub endpoints: Vec<String>,
+    /// Ready-to-paste TOML
+    pub toml: String,
+}
+
+/// device_rules.toml hostname classes by device type; other types have no hostname rules
+fn hostname_class(device_type: &str) -> Option<&'static str> {
+    match device_type {
+        "printer" => Some("printer"),
+        "tv" => Some("tv"),
+        "gaming" => Some("gaming"),
+        "phone" => Some("phone"),
+        "virtualization" => Some("vm"),
+        "soundbar" => Some("soundbar"),
+        "appliance" => Some("appliance"),
+        _ => None,
+    }
+}
+
+/// Device types device_rules.toml can assign by MAC vendor
+const VENDOR_CLASS_TYPES: &[&str] = &["appliance", "gaming", "tv", "gateway"];
+
+/// The part of a hostname that names the kind of device rather than the unit: lowercase,
+/// without the local domain and trailing serial numbers ("Zebra-ZT410-0042.local" ->
+/// "zebra-zt410"). None when nothing distinctive is left.
+pub fn hostname_stem(hostname: &str) -> Option<String> {
+    let lower = strip_local_suffix(hostname).to_lowercase();
+    let stem = match lower.rsplit_once(['-', '_']) {
+        // A trailing hex serial ("printer-a1b2c3")
+        Some((head, tail)) if tail.len() >= 6 && tail.chars().all(|c| c.is_ascii_hexdigit()) => {
+            head
+        }
+        _ => lower.as_str(),
+    };
+    let stem = stem
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .trim_end_matches(['-', '_', '.', ' ']);
+    let letters = stem.chars().filter(|c| c.is_ascii_alphabetic()).count();
+    (letters >= 3 && !stem.contains(':')).then(|| stem.to_string())
+}
+
+/// Record (or with None forget) a manual override along with the endpoint's current
+/// signals. Returns false if there is no such endpoint.
+pub fn record_classification_feedback(
+    conn: &Connection,
+    endpoint_name: &str,
+    kind: FeedbackKind,
+    value: Option<&str>,
+    now: i64,
+) -> Result<bool> {
+    let signals = conn
+        .query_row(
+            "SELECT e.id, e.auto_device_type, e.ssdp_model, e.ssdp_friendly_name,
+                (SELECT hostname FROM endpoint_attributes
+                 WHERE endpoint_id = e.id AND hostname IS NOT NULL AND hostname != ip
+                 ORDER BY created_at DESC LIMIT 1),
+                (SELECT mac FROM endpoint_attributes
+                 WHERE endpoint_id = e.id AND mac IS NOT NULL
+                 ORDER BY created_at DESC LIMIT 1)
+             FROM endpoints e
+             WHERE LOWER(e.name) = LOWER(?1) OR LOWER(e.custom_name) = LOWER(?1)
+             LIMIT 1",
+            [endpoint_name],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((endpoint_id, auto_type, ssdp_model, ssdp_friendly_name, hostname, mac)) = signals
+    else {
+        return Ok(false);
+    };
+
+    let Some(value) = value else {
+        conn.execute(
+            "DELETE FROM classification_feedback WHERE endpoint_id = ?1 AND kind = ?2",
+            params![endpoint_id, kind.as_str()],
+        )?;
+        return Ok(true);
+    };
+
+    // What the built-in rules came up with, so agreeing overrides aren't suggested
+    let detected = match kind {
+        FeedbackKind::DeviceType => auto_type,
+        FeedbackKind::Vendor => mac
+            .as_deref()
+            .and_then(get_mac_vendor)
+            .or_else(|| hostname.as_deref().and_then(get_hostname_vendor))
+            .map(str::to_string),
+        FeedbackKind::Model => None,
+    };
+    // Randomized MACs say nothing about the maker
+    let mac_prefix = mac
+        .filter(|m| m.len() >= 8 && !is_locally_administered_mac(m))
+        .map(|m| m[..8].to_lowercase());
+
+    conn.execute(
+        "INSERT INTO classification_feedback
+            (endpoint_id, endpoint_name, kind, value, detected, hostname, mac_prefix,
+             ssdp_model, ssdp_friendly_name, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT(endpoint_id, kind) DO UPDATE SET
+            endpoint_name = excluded.endpoint_name,
+            value = excluded.value,
+            detected = excluded.detected,
+            hostname = excluded.hostname,
+            mac_prefix = excluded.mac_prefix,
+            ssdp_model = excluded.ssdp_model,
+            ssdp_friendly_name = excluded.ssdp_friendly_name,
+            created_at = excluded.created_at",
+        params![
+            endpoint_id,
+            endpoint_name,
+            kind.as_str(),
+            value,
+            detected,
+            hostname,
+            mac_prefix,
+            ssdp_model,
+            ssdp_friendly_name,
+            now
+        ],
+    )?;
+    Ok(true)
+}
+
+/// Move an endpoint's feedback to the endpoint it is merged into, keeping the survivor's
+/// own rows where both have one
+pub fn reassign_classification_feedback(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE classification_feedback SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM classification_feedback WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+fn toml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Rules that would have produced the recorded overrides, most supported first. Overrides
+/// the built-in rules already agreed with are left out.
+pub fn get_rule_suggestions(conn: &Connection) -> Result<Vec<RuleSuggestion>> {
+    let mut stmt = conn.prepare(
+        "SELECT endpoint_name, kind, value, hostname, mac_prefix
+         FROM classification_feedback
+         WHERE detected IS NULL OR LOWER(detected) != LOWER(value)
+         ORDER BY created_at",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+
+    // (file, section, key, value) -> endpoints
+    let mut grouped: BTreeMap<(&'static str, String, String, String), Vec<String>> =
+        BTreeMap::new();
+    let mut add =
+        |file: &'static str, section: String, key: String, value: String, endpoint: &str| {
+            let endpoints = grouped.entry((file, section, key, value)).or_default();
+            if !endpoints.iter().any(|e| e == endpoint) {
+                endpoints.push(endpoint.to_string());
+            }
+        };
+
+    for row in rows {
+        let (endpoint, kind, value, hostname, mac_prefix) = row?;
+        let stem = hostname.as_deref().and_then(hostname_stem);
+        match kind.as_str() {
+            "device_type" => {
+                if let (Some(class), Some(stem)) = (hostname_class(&value), &stem) {
+                    add(
+                        "device_rules.toml",
+                        format!("patterns.{}", class),
+                        stem.clone(),
+                        class.to_string(),
+                        &endpoint,
+                    );
+                }
+                if VENDOR_CLASS_TYPES.contains(&value.as_str())
+                    && let Some(vendor) = mac_prefix.as_deref().and_then(get_mac_vendor)
+                {
+                    add(
+                        "device_rules.toml",
+                        format!("vendor_classes.{}", value),
+                        vendor.to_string(),
+                        value.clone(),
+                        &endpoint,
+                    );
+                }
+            }
+            "vendor" => {
+                if let Some(prefix) = mac_prefix {
+                    add(
+                        "overrides.toml",
+                        "overrides".to_string(),
+                        prefix,
+                        value,
+                        &endpoint,
+                    );
+                } else if let Some(stem) = stem {
+                    add(
+                        "device_rules.toml",
+                        "hostname_vendors".to_string(),
+                        stem,
+                        value,
+                        &endpoint,
+                    );
+                }
+            }
+            "model" => {
+                if let Some(stem) = stem {
+                    add(
+                        "device_rules.toml",
+                        "hostname_models".to_string(),
+                        stem,
+                        value,
+                        &endpoint,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut suggestions: Vec<RuleSuggestion> = grouped
+        .into_iter()
+        .map(|((file, section, key, value), endpoints)| {
+            let toml = match section.as_str() {
+                "overrides" => format!("{} = {}", toml_string(&key), toml_string(&value)),
+                "hostname_vendors" => format!(
+                    "[[hostname_vendors]]\nmatch_type = \"starts_with\"\npatterns = [{}]\nvendor = {}",
+                    toml_string(&key),
+                    toml_string(&value)
+                ),
+                "hostname_models" => format!(
+                    "[[hostname_models]]\nmatch_type = \"starts_with\"\npattern = {}\nmodel = {}",
+                    toml_string(&key),
+                    toml_string(&value)
+                ),
+                // List sections: add the key to the class's list
+                _ => format!(
+                    "# [{}]\n{} = [{}]",
+                    section.split('.').next().unwrap_or_default(),
+                    value,
+                    toml_string(&key)
+                ),
+            };
+            RuleSuggestion {
+                file,
+                section,
+                key,
+                value,
+                endpoints,
+                toml,
+            }
+        })
+        .collect();
+    suggestions.sort_by_key(|s| std::cmp::Reverse(s.endpoints.len()));
+    Ok(suggestions)
+}
+
+/// The suggestions as one text file, with a block per target file
+pub fn export_rule_suggestions(suggestions: &[RuleSuggestion]) -> String {
+    let mut out = String::from(
+        "# Suggested classification rules from manual overrides.\n\
+         # Review each entry, then copy it into the named file and regenerate.\n",
+    );
+    for file in ["device_rules.toml", "overrides.toml"] {
+        let entries: Vec<&RuleSuggestion> = suggestions.iter().filter(|s| s.file == file).collect();
+        if entries.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n# ===== {} =====\n", file));
+        for suggestion in entries {
+            out.push_str(&format!(
+                "\n# From: {}\n{}\n",
+                suggestion.endpoints.join(", "),
+                suggestion.toml
+            ));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_hostname_stem() {
+        assert_eq!(
+            hostname_stem("Zebra-ZT410-0042.local").as_deref(),
+            Some("zebra-zt410")
+        );
+        assert_eq!(hostname_stem("printer-a1b2c3").as_deref(), Some("printer"));
+        assert_eq!(
+            hostname_stem("kitchen_display").as_deref(),
+            Some("kitchen_display")
+        );
+        assert!(hostname_stem("192.168.1.20").is_none());
+        assert!(hostname_stem("ab-12").is_none());
+    }
+
+    #[test]
+    fn test_rule_suggestions_from_feedback() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type)
+                VALUES (1, 1, 'zebra-zt410-01', NULL), (2, 1, 'zebra-zt410-02', NULL),
+                       (3, 1, 'hp-laserjet', 'printer');
+             INSERT INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname) VALUES
+                (1, 1, '0c:11:22:00:00:01', '10.0.0.1', 'zebra-zt410-01'),
+                (1, 2, '02:00:00:00:00:02', '10.0.0.2', 'zebra-zt410-02'),
+                (1, 3, NULL, '10.0.0.3', 'hp-laserjet');",
+        )
+        .unwrap();
+
+        for name in ["zebra-zt410-01", "zebra-zt410-02", "hp-laserjet"] {
+            assert!(
+                record_classification_feedback(
+                    &conn,
+                    name,
+                    FeedbackKind::DeviceType,
+                    Some("printer"),
+                    10
+                )
+                .unwrap()
+            );
+        }
+        record_classification_feedback(
+            &conn,
+            "zebra-zt410-01",
+            FeedbackKind::Vendor,
+            Some("Zebra"),
+            10,
+        )
+        .unwrap();
+        record_classification_feedback(
+            &conn,
+            "zebra-zt410-02",
+            FeedbackKind::Vendor,
+            Some("Zebra"),
+            10,
+        )
+        .unwrap();
+        assert!(
+            !record_classification_feedback(&conn, "nobody", FeedbackKind::Model, Some("x"), 10)
+                .unwrap()
+        );
+
+        let suggestions = get_rule_suggestions(&conn).unwrap();
+        // hp-laserjet was already a printer, so only the zebras count
+        let pattern = &suggestions[0];
+        assert_eq!(pattern.section, "patterns.printer");
+        assert_eq!(pattern.key, "zebra-zt410");
+        assert_eq!(pattern.endpoints.len(), 2);
+        assert_eq!(pattern.toml, "# [patterns]\nprinter = [\"zebra-zt410\"]");
+
+        // The global MAC gives an OUI override; the randomized one falls back to the hostname
+        assert!(
+            suggestions
+                .iter()
+                .any(|s| s.section == "overrides" && s.key == "0c:11:22" && s.value == "Zebra")
+        );
+        assert!(suggestions.iter().any(|s| s.section == "hostname_vendors"
+            && s.key == "zebra-zt410"
+            && s.endpoints == vec!["zebra-zt410-02".to_string()]));
+
+        let export = export_rule_suggestions(&suggestions);
+        assert!(export.contains(
+            "# ===== overrides.toml =====\n\n# From: zebra-zt410-01\n\"0c:11:22\" = \"Zebra\""
+        ));
+
+        // Clearing an override forgets it
+        record_classification_feedback(&conn, "zebra-zt410-01", FeedbackKind::Vendor, None, 20)
+            .unwrap();
+        assert!(
+            !get_rule_suggestions(&conn)
+                .unwrap()
+                .iter()
+                .any(|s| s.section == "overrides")
+        );
+    }
+}
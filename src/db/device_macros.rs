@@ -0,0 +1,235 @@
+//! Device control macros: named sequences of remote commands ("Movie night": TV on,
+//! HDMI 2, soundbar volume up) run one after another through the device controllers.
+//! Steps name their endpoint rather than an address, so a macro keeps working when a
+//! device's DHCP lease changes.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use crate::network::device_control::MacroStep;
+
+/// Most steps a macro may hold
+const MAX_MACRO_STEPS: usize = 50;
+
+/// Most times one step may be repeated
+const MAX_STEP_REPEAT: u32 = 50;
+
+/// Longest pause after a step
+const MAX_STEP_DELAY_MS: u64 = 60_000;
+
+/// Longest macro name
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceMacro {
+    pub id: i64,
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A macro as submitted through the API; with an id it replaces that macro
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewDeviceMacro {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+}
+
+impl NewDeviceMacro {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(format!(
+                "Macro name must be 1 to {} characters",
+                MAX_NAME_LEN
+            ));
+        }
+        if self.steps.is_empty() || self.steps.len() > MAX_MACRO_STEPS {
+            return Err(format!("A macro needs 1 to {} steps", MAX_MACRO_STEPS));
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            let problem = if step.endpoint.trim().is_empty() {
+                "has no endpoint".to_string()
+            } else if step.device_type.trim().is_empty() {
+                "has no device type".to_string()
+            } else if step.value.is_empty() {
+                "has nothing to send".to_string()
+            } else if !(1..=MAX_STEP_REPEAT).contains(&step.repeat) {
+                format!("must repeat 1 to {} times", MAX_STEP_REPEAT)
+            } else if step.delay_ms > MAX_STEP_DELAY_MS {
+                format!("may pause at most {} ms", MAX_STEP_DELAY_MS)
+            } else {
+                continue;
+            };
+            return Err(format!("Step {} {}", index + 1, problem));
+        }
+        Ok(())
+    }
+}
+
+pub fn create_device_macros_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS device_macros (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            steps TEXT NOT NULL DEFAULT '[]',
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn steps_to_json(steps: &[MacroStep]) -> String {
+    serde_json::to_string(steps).unwrap_or_else(|_| "[]".to_string())
+}
+
+fn macro_from_row(row: &rusqlite::Row) -> Result<DeviceMacro> {
+    let steps: String = row.get(2)?;
+    Ok(DeviceMacro {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        steps: serde_json::from_str(&steps).unwrap_or_default(),
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+/// Create a macro, or replace the one with the given id. Returns the id, or None when
+/// the macro to replace doesn't exist. A duplicate name fails with a constraint
+/// violation.
+pub fn save_device_macro(
+    conn: &Connection,
+    device_macro: &NewDeviceMacro,
+    now: i64,
+) -> Result<Option<i64>> {
+    let name = device_macro.name.trim();
+    let steps = steps_to_json(&device_macro.steps);
+    match device_macro.id {
+        Some(id) => {
+            let updated = conn.execute(
+                "UPDATE device_macros SET name = ?2, steps = ?3, updated_at = ?4 WHERE id = ?1",
+                params![id, name, steps, now],
+            )?;
+            Ok((updated > 0).then_some(id))
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO device_macros (name, steps, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?3)",
+                params![name, steps, now],
+            )?;
+            Ok(Some(conn.last_insert_rowid()))
+        }
+    }
+}
+
+pub fn delete_device_macro(conn: &Connection, id: i64) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM device_macros WHERE id = ?1", [id])? > 0)
+}
+
+pub fn get_device_macros(conn: &Connection) -> Result<Vec<DeviceMacro>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, steps, created_at, updated_at FROM device_macros
+         ORDER BY name COLLATE NOCASE",
+    )?;
+    stmt.query_map([], macro_from_row)?.collect()
+}
+
+pub fn get_device_macro(conn: &Connection, id: i64) -> Result<Option<DeviceMacro>> {
+    conn.query_row(
+        "SELECT id, name, steps, created_at, updated_at FROM device_macros WHERE id = ?1",
+        [id],
+        macro_from_row,
+    )
+    .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn step(endpoint: &str, value: &str) -> MacroStep {
+        serde_json::from_value(serde_json::json!({
+            "endpoint": endpoint,
+            "device_type": "samsung",
+            "value": value,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_device_macros() {
+        let conn = new_test_connection();
+        let mut movie_night = NewDeviceMacro {
+            id: None,
+            name: " Movie night ".to_string(),
+            steps: vec![
+                step("living-room-tv", "KEY_POWER"),
+                step("living-room-tv", "KEY_HDMI2"),
+            ],
+        };
+        assert!(movie_night.validate().is_ok());
+        assert_eq!(movie_night.steps[0].repeat, 1);
+        let id = save_device_macro(&conn, &movie_night, 100)
+            .unwrap()
+            .unwrap();
+
+        // Names are unique regardless of case
+        let duplicate = NewDeviceMacro {
+            name: "MOVIE NIGHT".to_string(),
+            ..movie_night.clone()
+        };
+        assert!(save_device_macro(&conn, &duplicate, 100).is_err());
+
+        movie_night.id = Some(id);
+        movie_night.steps.push(MacroStep {
+            repeat: 8,
+            ..step("soundbar", "VolumeUp")
+        });
+        assert_eq!(
+            save_device_macro(&conn, &movie_night, 200).unwrap(),
+            Some(id)
+        );
+        let saved = get_device_macro(&conn, id).unwrap().unwrap();
+        assert_eq!(saved.name, "Movie night");
+        assert_eq!(saved.steps.len(), 3);
+        assert_eq!(saved.steps[2].repeat, 8);
+        assert_eq!((saved.created_at, saved.updated_at), (100, 200));
+        assert_eq!(get_device_macros(&conn).unwrap().len(), 1);
+
+        movie_night.id = Some(id + 1);
+        assert_eq!(save_device_macro(&conn, &movie_night, 300).unwrap(), None);
+        assert!(delete_device_macro(&conn, id).unwrap());
+        assert!(!delete_device_macro(&conn, id).unwrap());
+    }
+
+    #[test]
+    fn test_validate_device_macro() {
+        let device_macro = |steps: Vec<MacroStep>| NewDeviceMacro {
+            id: None,
+            name: "Bedtime".to_string(),
+            steps,
+        };
+        assert!(device_macro(vec![]).validate().is_err());
+        assert_eq!(
+            device_macro(vec![step("tv", "KEY_POWER"), step("", "KEY_POWER")]).validate(),
+            Err("Step 2 has no endpoint".to_string())
+        );
+        let too_many = MacroStep {
+            repeat: MAX_STEP_REPEAT + 1,
+            ..step("tv", "KEY_VOLDOWN")
+        };
+        assert!(device_macro(vec![too_many]).validate().is_err());
+        let too_slow = MacroStep {
+            delay_ms: MAX_STEP_DELAY_MS + 1,
+            ..step("tv", "KEY_POWER")
+        };
+        assert!(device_macro(vec![too_slow]).validate().is_err());
+    }
+}
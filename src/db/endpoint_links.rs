@@ -0,0 +1,225 @@
+//! Linked interfaces: endpoints that are one physical device, like a laptop seen once on
+//! Ethernet and once on Wi-Fi. Unlike a merge, linking keeps each endpoint and its
+//! per-MAC history intact; the endpoints just share a device id so details and traffic
+//! can be shown for the device as a whole. Links are made by hand, and endpoints that
+//! report the same hostname from different MACs are offered as suggestions.
+
+use std::collections::BTreeMap;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+/// Endpoints reporting the same hostname from different MACs, not yet linked together
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkSuggestion {
+    pub hostname: String,
+    pub endpoint_ids: Vec<i64>,
+}
+
+pub fn create_endpoint_links_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_links (
+            endpoint_id INTEGER PRIMARY KEY,
+            device_id INTEGER NOT NULL,
+            linked_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_endpoint_links_device_id ON endpoint_links (device_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn device_id_of(conn: &Connection, endpoint_id: i64) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT device_id FROM endpoint_links WHERE endpoint_id = ?1",
+        [endpoint_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Drop devices left with a single endpoint
+fn prune_single_links(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "DELETE FROM endpoint_links WHERE device_id IN (
+             SELECT device_id FROM endpoint_links GROUP BY device_id HAVING COUNT(*) < 2
+         )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Link two endpoints as interfaces of one device. Either may already be linked to
+/// others, in which case the two devices become one. Returns the device id.
+pub fn link_endpoints(conn: &Connection, endpoint_id: i64, other_id: i64, now: i64) -> Result<i64> {
+    let device_id = match (
+        device_id_of(conn, endpoint_id)?,
+        device_id_of(conn, other_id)?,
+    ) {
+        (Some(device_id), Some(other_device_id)) => {
+            conn.execute(
+                "UPDATE endpoint_links SET device_id = ?1 WHERE device_id = ?2",
+                params![device_id, other_device_id],
+            )?;
+            device_id
+        }
+        (Some(device_id), None) | (None, Some(device_id)) => device_id,
+        (None, None) => conn.query_row(
+            "SELECT COALESCE(MAX(device_id), 0) + 1 FROM endpoint_links",
+            [],
+            |row| row.get(0),
+        )?,
+    };
+    for id in [endpoint_id, other_id] {
+        conn.execute(
+            "INSERT OR IGNORE INTO endpoint_links (endpoint_id, device_id, linked_at)
+             VALUES (?1, ?2, ?3)",
+            params![id, device_id, now],
+        )?;
+    }
+    Ok(device_id)
+}
+
+/// Detach an endpoint from the device it is linked into. Returns false if it wasn't linked.
+pub fn unlink_endpoint(conn: &Connection, endpoint_id: i64) -> Result<bool> {
+    let removed = conn.execute(
+        "DELETE FROM endpoint_links WHERE endpoint_id = ?1",
+        [endpoint_id],
+    )? > 0;
+    prune_single_links(conn)?;
+    Ok(removed)
+}
+
+/// The other endpoints linked with `endpoint_id`, lowest id first
+pub fn get_linked_endpoint_ids(conn: &Connection, endpoint_id: i64) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT l.endpoint_id FROM endpoint_links l
+         JOIN endpoints e ON e.id = l.endpoint_id
+         WHERE l.device_id = (SELECT device_id FROM endpoint_links WHERE endpoint_id = ?1)
+           AND l.endpoint_id != ?1
+         ORDER BY l.endpoint_id",
+    )?;
+    stmt.query_map([endpoint_id], |row| row.get(0))?.collect()
+}
+
+/// Groups of endpoints that gave the same hostname from different hardware MACs and
+/// aren't all linked together yet
+pub fn get_link_suggestions(conn: &Connection) -> Result<Vec<LinkSuggestion>> {
+    let mut stmt = conn.prepare(
+        "SELECT LOWER(a.hostname), a.endpoint_id, l.device_id
+         FROM endpoint_attributes a
+         JOIN endpoints e ON e.id = a.endpoint_id
+         LEFT JOIN endpoint_links l ON l.endpoint_id = a.endpoint_id
+         WHERE a.mac IS NOT NULL AND a.mac != '' AND a.mac != '00:00:00:00:00:00'
+           AND a.hostname IS NOT NULL AND a.hostname != '' AND a.hostname != a.ip
+           AND a.hostname NOT LIKE '%:%'
+           AND a.hostname NOT GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*'
+         GROUP BY LOWER(a.hostname), a.endpoint_id
+         ORDER BY LOWER(a.hostname), a.endpoint_id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, Option<i64>>(2)?,
+        ))
+    })?;
+
+    let mut by_hostname: BTreeMap<String, Vec<(i64, Option<i64>)>> = BTreeMap::new();
+    for row in rows {
+        let (hostname, endpoint_id, device_id) = row?;
+        by_hostname
+            .entry(hostname)
+            .or_default()
+            .push((endpoint_id, device_id));
+    }
+
+    Ok(by_hostname
+        .into_iter()
+        .filter(|(_, endpoints)| {
+            let first_device = endpoints[0].1;
+            endpoints.len() > 1
+                && (first_device.is_none()
+                    || endpoints.iter().any(|(_, device)| *device != first_device))
+        })
+        .map(|(hostname, endpoints)| LinkSuggestion {
+            hostname,
+            endpoint_ids: endpoints.into_iter().map(|(id, _)| id).collect(),
+        })
+        .collect())
+}
+
+/// Carry an endpoint's links over when it is merged into another
+pub fn reassign_endpoint_links(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    if let Some(from_device) = device_id_of(conn, from_id)? {
+        match device_id_of(conn, to_id)? {
+            Some(to_device) => {
+                conn.execute(
+                    "UPDATE endpoint_links SET device_id = ?1 WHERE device_id = ?2",
+                    params![to_device, from_device],
+                )?;
+                conn.execute(
+                    "DELETE FROM endpoint_links WHERE endpoint_id = ?1",
+                    [from_id],
+                )?;
+            }
+            None => {
+                conn.execute(
+                    "UPDATE endpoint_links SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+                    params![to_id, from_id],
+                )?;
+            }
+        }
+        prune_single_links(conn)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_endpoint_links() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES
+                 (1, 1, 'laptop'), (2, 1, 'laptop'), (3, 1, 'nas'), (4, 1, 'laptop');
+             INSERT INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname) VALUES
+                 (1, 1, 'aa:bb:cc:00:00:01', '192.168.1.20', 'Laptop'),
+                 (1, 2, 'aa:bb:cc:00:00:02', '192.168.1.21', 'laptop'),
+                 (1, 3, 'aa:bb:cc:00:00:03', '192.168.1.30', 'nas'),
+                 (1, 4, NULL, '192.168.1.22', 'laptop');",
+        )
+        .unwrap();
+
+        // The MAC-less sighting isn't a separate interface
+        assert_eq!(
+            get_link_suggestions(&conn).unwrap(),
+            vec![LinkSuggestion {
+                hostname: "laptop".to_string(),
+                endpoint_ids: vec![1, 2],
+            }]
+        );
+
+        let device_id = link_endpoints(&conn, 1, 2, 100).unwrap();
+        assert_eq!(link_endpoints(&conn, 2, 3, 100).unwrap(), device_id);
+        assert_eq!(get_linked_endpoint_ids(&conn, 1).unwrap(), vec![2, 3]);
+        assert!(get_link_suggestions(&conn).unwrap().is_empty());
+
+        // Merging 3 into 4 keeps 4 on the device
+        reassign_endpoint_links(&conn, 3, 4).unwrap();
+        assert_eq!(get_linked_endpoint_ids(&conn, 2).unwrap(), vec![1, 4]);
+
+        assert!(unlink_endpoint(&conn, 4).unwrap());
+        assert_eq!(get_linked_endpoint_ids(&conn, 1).unwrap(), vec![2]);
+        // A device down to one endpoint is no device at all
+        assert!(unlink_endpoint(&conn, 1).unwrap());
+        assert!(get_linked_endpoint_ids(&conn, 2).unwrap().is_empty());
+        assert!(!unlink_endpoint(&conn, 2).unwrap());
+    }
+}
@@ -0,0 +1,232 @@
+//! Materialized endpoint summaries. The endpoint table and export need vendor, model,
+//! type, traffic and address data for every endpoint; computing that fans out into
+//! several full-table scans, so a background materializer keeps one `endpoint_summary`
+//! row per display name instead. The packet write path marks endpoints dirty here and
+//! the materializer recomputes just those rows.
+
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+/// Everything the endpoint table and export show for one endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointSummary {
+    pub endpoint_id: i64,
+    pub name: String,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub device_type: Option<String>,
+    pub bytes: i64,
+    pub last_seen_at: Option<i64>,
+    pub ips: Vec<String>,
+    pub macs: Vec<String>,
+}
+
+/// Endpoints written since the materializer last ran
+static DIRTY_ENDPOINTS: LazyLock<Mutex<HashSet<i64>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Mark endpoints whose summary needs recomputing
+pub fn mark_endpoint_summary_dirty(endpoint_ids: &[i64]) {
+    if let Ok(mut dirty) = DIRTY_ENDPOINTS.lock() {
+        dirty.extend(endpoint_ids);
+    }
+}
+
+/// Take (and clear) the set of endpoints marked dirty
+pub fn take_dirty_endpoint_summaries() -> Vec<i64> {
+    DIRTY_ENDPOINTS
+        .lock()
+        .map(|mut dirty| dirty.drain().collect())
+        .unwrap_or_default()
+}
+
+pub fn create_endpoint_summary_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_summary (
+            name TEXT PRIMARY KEY COLLATE NOCASE,
+            endpoint_id INTEGER NOT NULL,
+            vendor TEXT,
+            model TEXT,
+            device_type TEXT,
+            bytes INTEGER NOT NULL DEFAULT 0,
+            last_seen_at INTEGER,
+            ips TEXT NOT NULL DEFAULT '',
+            macs TEXT NOT NULL DEFAULT '',
+            updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_endpoint_summary_last_seen ON endpoint_summary (last_seen_at)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_endpoint_summary_endpoint_id ON endpoint_summary (endpoint_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn split_list(value: String) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Insert or replace summaries (one per display name)
+pub fn upsert_endpoint_summaries(conn: &Connection, summaries: &[EndpointSummary]) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "INSERT OR REPLACE INTO endpoint_summary
+            (name, endpoint_id, vendor, model, device_type, bytes, last_seen_at, ips, macs, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, strftime('%s', 'now'))",
+    )?;
+    for summary in summaries {
+        stmt.execute(params![
+            summary.name,
+            summary.endpoint_id,
+            summary.vendor,
+            summary.model,
+            summary.device_type,
+            summary.bytes,
+            summary.last_seen_at,
+            summary.ips.join(","),
+            summary.macs.join(","),
+        ])?;
+    }
+    Ok(())
+}
+
+/// Replace the whole table with `summaries` (a full rematerialization)
+pub fn replace_endpoint_summaries(conn: &Connection, summaries: &[EndpointSummary]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM endpoint_summary", [])?;
+    upsert_endpoint_summaries(&tx, summaries)?;
+    tx.commit()
+}
+
+/// Delete the summaries for the given display names
+pub fn delete_endpoint_summaries(conn: &Connection, names: &[String]) -> Result<()> {
+    let mut stmt = conn.prepare("DELETE FROM endpoint_summary WHERE name = ?1")?;
+    for name in names {
+        stmt.execute([name])?;
+    }
+    Ok(())
+}
+
+/// Summaries whose endpoint has since been deleted or renamed. Returns
+/// (stale summary name, endpoint id or None if the endpoint is gone).
+pub fn get_stale_endpoint_summaries(conn: &Connection) -> Result<Vec<(String, Option<i64>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.name, e.id
+         FROM endpoint_summary s
+         LEFT JOIN endpoints e ON e.id = s.endpoint_id
+         WHERE e.id IS NULL OR e.display_name IS NULL OR e.display_name != s.name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// All summaries for endpoints seen at or after `since`, ordered by name
+pub fn get_endpoint_summaries(conn: &Connection, since: i64) -> Result<Vec<EndpointSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT endpoint_id, name, vendor, model, device_type, bytes, last_seen_at, ips, macs
+         FROM endpoint_summary
+         WHERE last_seen_at >= ?1
+         ORDER BY name",
+    )?;
+    let rows = stmt.query_map([since], |row| {
+        Ok(EndpointSummary {
+            endpoint_id: row.get(0)?,
+            name: row.get(1)?,
+            vendor: row.get(2)?,
+            model: row.get(3)?,
+            device_type: row.get(4)?,
+            bytes: row.get(5)?,
+            last_seen_at: row.get(6)?,
+            ips: split_list(row.get(7)?),
+            macs: split_list(row.get(8)?),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Whether the materializer has populated the table yet
+pub fn has_endpoint_summaries(conn: &Connection) -> bool {
+    conn.query_row("SELECT EXISTS(SELECT 1 FROM endpoint_summary)", [], |row| {
+        row.get(0)
+    })
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn summary(endpoint_id: i64, name: &str, last_seen_at: i64) -> EndpointSummary {
+        EndpointSummary {
+            endpoint_id,
+            name: name.to_string(),
+            vendor: Some("Apple".to_string()),
+            model: None,
+            device_type: Some("phone".to_string()),
+            bytes: 1024,
+            last_seen_at: Some(last_seen_at),
+            ips: vec!["10.0.0.2".to_string(), "fe80::2".to_string()],
+            macs: vec!["aa:bb:cc:dd:ee:02".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_endpoint_summary_roundtrip_and_staleness() {
+        let conn = new_test_connection();
+        assert!(!has_endpoint_summaries(&conn));
+
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'phone'), (2, 1, 'tv');",
+        )
+        .unwrap();
+        replace_endpoint_summaries(&conn, &[summary(1, "phone", 500), summary(2, "tv", 100)])
+            .unwrap();
+
+        let recent = get_endpoint_summaries(&conn, 200).unwrap();
+        assert_eq!(recent, vec![summary(1, "phone", 500)]);
+        assert!(get_stale_endpoint_summaries(&conn).unwrap().is_empty());
+
+        // Upserting by name (case-insensitively) replaces the row
+        let mut renamed = summary(1, "PHONE", 600);
+        renamed.bytes = 2048;
+        upsert_endpoint_summaries(&conn, &[renamed.clone()]).unwrap();
+        assert_eq!(get_endpoint_summaries(&conn, 200).unwrap(), vec![renamed]);
+
+        conn.execute_batch(
+            "UPDATE endpoints SET custom_name = 'Living Room TV' WHERE id = 2;
+             DELETE FROM endpoints WHERE id = 1;",
+        )
+        .unwrap();
+        let mut stale = get_stale_endpoint_summaries(&conn).unwrap();
+        stale.sort();
+        assert_eq!(
+            stale,
+            vec![("PHONE".to_string(), None), ("tv".to_string(), Some(2))]
+        );
+
+        delete_endpoint_summaries(&conn, &["phone".to_string(), "tv".to_string()]).unwrap();
+        assert!(!has_endpoint_summaries(&conn));
+    }
+
+    #[test]
+    fn test_dirty_endpoints_are_drained() {
+        mark_endpoint_summary_dirty(&[7, 8, 7]);
+        let mut dirty = take_dirty_endpoint_summaries();
+        dirty.sort();
+        // Other tests may mark endpoints concurrently; ours must be present once each
+        assert!(dirty.contains(&7) && dirty.contains(&8));
+        assert_eq!(dirty.iter().filter(|id| **id == 7).count(), 1);
+    }
+}
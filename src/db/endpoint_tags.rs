@@ -0,0 +1,253 @@
+//! Endpoint tags and trust. Tags are free-form labels ("iot", "guest", "kids") that group
+//! endpoints across device types, so webhooks, graph exports and bandwidth quotas can be
+//! aimed at a group. Trust marks an endpoint as known and expected on the network.
+//! Both are kept by endpoint id and follow it through merges.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, Result, params};
+
+/// Longest tag accepted
+const MAX_TAG_LEN: usize = 32;
+
+pub fn create_endpoint_tag_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_tags (
+            endpoint_id INTEGER NOT NULL,
+            tag TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, tag)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_endpoint_tags_tag ON endpoint_tags (tag)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_trust (
+            endpoint_id INTEGER PRIMARY KEY,
+            trusted_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Normalize a tag for storage: trimmed and lowercase, letters, digits, '-', '_' and
+/// '.' only. None when that leaves nothing or it is too long.
+pub fn parse_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LEN
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid.then_some(tag)
+}
+
+/// Normalize a list of tags, rejecting the first invalid one
+pub fn parse_tags(tags: &[String]) -> std::result::Result<Vec<String>, String> {
+    let mut parsed = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let Some(tag) = parse_tag(tag) else {
+            return Err(format!(
+                "Invalid tag '{}': use up to {} letters, digits, '-', '_' or '.'",
+                tag, MAX_TAG_LEN
+            ));
+        };
+        if !parsed.contains(&tag) {
+            parsed.push(tag);
+        }
+    }
+    Ok(parsed)
+}
+
+pub fn add_endpoint_tag(conn: &Connection, endpoint_id: i64, tag: &str, now: i64) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO endpoint_tags (endpoint_id, tag, created_at) VALUES (?1, ?2, ?3)",
+        params![endpoint_id, tag, now],
+    )?;
+    Ok(())
+}
+
+pub fn remove_endpoint_tag(conn: &Connection, endpoint_id: i64, tag: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM endpoint_tags WHERE endpoint_id = ?1 AND tag = ?2",
+        params![endpoint_id, tag],
+    )?;
+    Ok(())
+}
+
+/// Replace an endpoint's tags with `tags` (already normalized)
+pub fn set_endpoint_tags(
+    conn: &Connection,
+    endpoint_id: i64,
+    tags: &[String],
+    now: i64,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM endpoint_tags WHERE endpoint_id = ?1",
+        [endpoint_id],
+    )?;
+    for tag in tags {
+        add_endpoint_tag(&tx, endpoint_id, tag, now)?;
+    }
+    tx.commit()
+}
+
+/// An endpoint's tags, alphabetically
+pub fn get_endpoint_tags(conn: &Connection, endpoint_ids: &[i64]) -> Result<Vec<String>> {
+    let ids = serde_json::to_string(endpoint_ids).unwrap_or_else(|_| "[]".to_string());
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT tag FROM endpoint_tags
+         WHERE endpoint_id IN (SELECT value FROM json_each(?1))
+         ORDER BY tag",
+    )?;
+    let rows = stmt.query_map([ids], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Every tag in use, with how many endpoints carry it
+pub fn get_tag_counts(conn: &Connection) -> Result<HashMap<String, i64>> {
+    let mut stmt = conn.prepare("SELECT tag, COUNT(*) FROM endpoint_tags GROUP BY tag")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Ids of the endpoints carrying any of `tags`
+pub fn endpoint_ids_with_tags(conn: &Connection, tags: &[String]) -> Result<Vec<i64>> {
+    let tags = serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string());
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT endpoint_id FROM endpoint_tags
+         WHERE tag IN (SELECT value FROM json_each(?1))
+         ORDER BY endpoint_id",
+    )?;
+    let rows = stmt.query_map([tags], |row| row.get(0))?;
+    rows.collect()
+}
+
+pub fn set_endpoint_trusted(
+    conn: &Connection,
+    endpoint_id: i64,
+    trusted: bool,
+    now: i64,
+) -> Result<()> {
+    if trusted {
+        conn.execute(
+            "INSERT OR IGNORE INTO endpoint_trust (endpoint_id, trusted_at) VALUES (?1, ?2)",
+            params![endpoint_id, now],
+        )?;
+    } else {
+        conn.execute(
+            "DELETE FROM endpoint_trust WHERE endpoint_id = ?1",
+            [endpoint_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether any of the endpoint's ids is trusted
+pub fn is_endpoint_trusted(conn: &Connection, endpoint_ids: &[i64]) -> Result<bool> {
+    let ids = serde_json::to_string(endpoint_ids).unwrap_or_else(|_| "[]".to_string());
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM endpoint_trust
+                       WHERE endpoint_id IN (SELECT value FROM json_each(?1)))",
+        [ids],
+        |row| row.get(0),
+    )
+}
+
+/// Move tags and trust from a merged endpoint to the one it was merged into
+pub fn reassign_endpoint_tags(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE endpoint_tags SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_tags WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    conn.execute(
+        "UPDATE OR IGNORE endpoint_trust SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_trust WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(parse_tag(" IoT ").as_deref(), Some("iot"));
+        assert_eq!(parse_tag("guest-wifi").as_deref(), Some("guest-wifi"));
+        assert!(parse_tag("").is_none());
+        assert!(parse_tag("two words").is_none());
+        assert!(parse_tag(&"x".repeat(MAX_TAG_LEN + 1)).is_none());
+
+        assert_eq!(
+            parse_tags(&["Kids".to_string(), "kids".to_string(), "iot".to_string()]).unwrap(),
+            vec!["kids", "iot"]
+        );
+        assert!(parse_tags(&["ok".to_string(), "not ok".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_endpoint_tags_and_trust() {
+        let conn = new_test_connection();
+        set_endpoint_tags(&conn, 1, &["iot".to_string(), "kids".to_string()], 10).unwrap();
+        add_endpoint_tag(&conn, 2, "iot", 10).unwrap();
+        add_endpoint_tag(&conn, 2, "iot", 11).unwrap();
+        add_endpoint_tag(&conn, 3, "guest", 10).unwrap();
+
+        assert_eq!(get_endpoint_tags(&conn, &[1]).unwrap(), vec!["iot", "kids"]);
+        assert_eq!(get_endpoint_tags(&conn, &[1, 3]).unwrap().len(), 3);
+        assert_eq!(get_tag_counts(&conn).unwrap()["iot"], 2);
+        assert_eq!(
+            endpoint_ids_with_tags(&conn, &["iot".to_string()]).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            endpoint_ids_with_tags(&conn, &["kids".to_string(), "guest".to_string()]).unwrap(),
+            vec![1, 3]
+        );
+        assert!(endpoint_ids_with_tags(&conn, &[]).unwrap().is_empty());
+
+        set_endpoint_tags(&conn, 1, &["kids".to_string()], 20).unwrap();
+        remove_endpoint_tag(&conn, 2, "iot").unwrap();
+        assert!(
+            endpoint_ids_with_tags(&conn, &["iot".to_string()])
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(!is_endpoint_trusted(&conn, &[1]).unwrap());
+        set_endpoint_trusted(&conn, 1, true, 10).unwrap();
+        set_endpoint_trusted(&conn, 1, true, 20).unwrap();
+        assert!(is_endpoint_trusted(&conn, &[1, 2]).unwrap());
+        set_endpoint_trusted(&conn, 1, false, 30).unwrap();
+        assert!(!is_endpoint_trusted(&conn, &[1]).unwrap());
+    }
+
+    #[test]
+    fn test_reassign_endpoint_tags() {
+        let conn = new_test_connection();
+        set_endpoint_tags(&conn, 1, &["iot".to_string()], 10).unwrap();
+        set_endpoint_tags(&conn, 2, &["iot".to_string(), "kids".to_string()], 10).unwrap();
+        set_endpoint_trusted(&conn, 2, true, 10).unwrap();
+
+        reassign_endpoint_tags(&conn, 2, 1).unwrap();
+        assert_eq!(get_endpoint_tags(&conn, &[1]).unwrap(), vec!["iot", "kids"]);
+        assert!(get_endpoint_tags(&conn, &[2]).unwrap().is_empty());
+        assert!(is_endpoint_trusted(&conn, &[1]).unwrap());
+        assert!(!is_endpoint_trusted(&conn, &[2]).unwrap());
+    }
+}
@@ -0,0 +1,480 @@
+//! Energy use estimates for the household's devices. Each endpoint is taken to draw a fixed
+//! wattage while online: its own override if set, else its device type's (a built-in
+//! estimate, or the type's override). Online time is the hours with traffic in the hourly
+//! usage rollups. Watt-hours per endpoint and local day are rolled up into `energy_daily`
+//! every hour, so monthly summaries outlive the usage retention; a past day keeps the
+//! wattage it was rolled up with.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{Datelike, Local, NaiveDate, TimeZone, Timelike};
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+use tokio::task;
+
+use super::{get_setting_i64, new_connection_result};
+
+/// Typical draw in watts of every built-in device type while it's on
+pub const DEFAULT_TYPE_WATTS: &[(&str, f64)] = &[
+    ("local", 50.0),
+    ("gateway", 12.0),
+    ("internet", 0.0),
+    ("printer", 20.0),
+    ("tv", 90.0),
+    ("gaming", 120.0),
+    ("phone", 2.0),
+    // Drawn by the host they run on
+    ("virtualization", 0.0),
+    ("soundbar", 15.0),
+    ("appliance", 10.0),
+    ("nas", 30.0),
+    ("ups", 8.0),
+    ("other", 5.0),
+];
+
+/// Highest wattage accepted for a type or endpoint
+pub const MAX_WATTS: f64 = 5000.0;
+
+/// Flag to ensure the rollup is only started once
+static ENERGY_ROLLUP_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn create_energy_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS device_type_power (
+            device_type TEXT PRIMARY KEY,
+            watts REAL NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_power (
+            endpoint_id INTEGER PRIMARY KEY REFERENCES endpoints(id) ON DELETE CASCADE,
+            watts REAL NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS energy_daily (
+            endpoint_id INTEGER NOT NULL,
+            day TEXT NOT NULL,
+            device_type TEXT NOT NULL,
+            online_hours INTEGER NOT NULL,
+            watts REAL NOT NULL,
+            watt_hours REAL NOT NULL,
+            PRIMARY KEY (endpoint_id, day)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A device type's effective wattage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypePower {
+    pub watts: f64,
+    /// Differs from the built-in estimate
+    pub customized: bool,
+}
+
+/// Wattage of every built-in device type, with overrides applied
+pub fn get_type_power(conn: &Connection) -> Result<HashMap<String, TypePower>> {
+    let mut overrides: HashMap<String, f64> = conn
+        .prepare("SELECT device_type, watts FROM device_type_power")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+
+    Ok(DEFAULT_TYPE_WATTS
+        .iter()
+        .map(|(device_type, watts)| {
+            let custom = overrides.remove(*device_type);
+            let power = TypePower {
+                customized: custom.is_some(),
+                watts: custom.unwrap_or(*watts),
+            };
+            (device_type.to_string(), power)
+        })
+        .collect())
+}
+
+/// Override a device type's wattage; None restores the built-in estimate
+pub fn set_type_power(conn: &Connection, device_type: &str, watts: Option<f64>) -> Result<()> {
+    match watts {
+        Some(watts) => conn.execute(
+            "INSERT OR REPLACE INTO device_type_power (device_type, watts) VALUES (?1, ?2)",
+            params![device_type, watts],
+        )?,
+        None => conn.execute(
+            "DELETE FROM device_type_power WHERE device_type = ?1",
+            [device_type],
+        )?,
+    };
+    Ok(())
+}
+
+/// Endpoints with their own wattage, keyed by lowercase display name
+pub fn get_endpoint_power(conn: &Connection) -> Result<HashMap<String, f64>> {
+    let mut stmt = conn.prepare(
+        "SELECT LOWER(e.display_name), p.watts
+         FROM endpoint_power p
+         JOIN endpoints e ON e.id = p.endpoint_id
+         WHERE e.display_name IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Set an endpoint's own wattage; None reverts to its type's
+pub fn set_endpoint_power(conn: &Connection, endpoint_id: i64, watts: Option<f64>) -> Result<()> {
+    match watts {
+        Some(watts) => conn.execute(
+            "INSERT OR REPLACE INTO endpoint_power (endpoint_id, watts) VALUES (?1, ?2)",
+            params![endpoint_id, watts],
+        )?,
+        None => conn.execute(
+            "DELETE FROM endpoint_power WHERE endpoint_id = ?1",
+            [endpoint_id],
+        )?,
+    };
+    Ok(())
+}
+
+/// Move an endpoint's wattage and energy history to the endpoint it is merged into,
+/// unless that one has its own
+pub fn reassign_energy(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    for table in ["endpoint_power", "energy_daily"] {
+        conn.execute(
+            &format!(
+                "UPDATE OR IGNORE {} SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+                table
+            ),
+            params![to_id, from_id],
+        )?;
+        conn.execute(
+            &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+            [from_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Unix time of local midnight starting the day `timestamp` falls in
+fn local_day_start(timestamp: i64) -> i64 {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .and_then(|t| t.date_naive().and_hms_opt(0, 0, 0))
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .map(|t| t.timestamp())
+        .unwrap_or(timestamp - timestamp.rem_euclid(86400))
+}
+
+/// Roll the hourly usage of every local day from the one containing `since` into
+/// `energy_daily`, at today's wattages. Returns the endpoint-days written.
+pub fn rollup_energy(conn: &Connection, since: i64) -> Result<usize> {
+    let type_watts = get_type_power(conn)?;
+    let endpoint_watts: HashMap<i64, f64> = conn
+        .prepare("SELECT endpoint_id, watts FROM endpoint_power")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT u.endpoint_id, u.hour_start,
+                COALESCE(e.manual_device_type, e.auto_device_type, 'other')
+         FROM endpoint_usage_hourly u
+         JOIN endpoints e ON e.id = u.endpoint_id
+         WHERE u.hour_start >= ?1",
+    )?;
+    let rows = stmt.query_map([local_day_start(since)], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    // (endpoint, local date) -> device type and the local hours online
+    let mut days: BTreeMap<(i64, String), (String, BTreeSet<u32>)> = BTreeMap::new();
+    for row in rows {
+        let (endpoint_id, hour_start, device_type) = row?;
+        let Some(local) = Local.timestamp_opt(hour_start, 0).single() else {
+            continue;
+        };
+        days.entry((endpoint_id, local.format("%Y-%m-%d").to_string()))
+            .or_insert_with(|| (device_type, BTreeSet::new()))
+            .1
+            .insert(local.hour());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut insert = tx.prepare(
+        "INSERT OR REPLACE INTO energy_daily
+             (endpoint_id, day, device_type, online_hours, watts, watt_hours)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    let written = days.len();
+    for ((endpoint_id, day), (device_type, hours)) in days {
+        let watts = endpoint_watts
+            .get(&endpoint_id)
+            .copied()
+            .or_else(|| type_watts.get(&device_type).map(|p| p.watts))
+            .or_else(|| type_watts.get("other").map(|p| p.watts))
+            .unwrap_or(0.0);
+        let online_hours = hours.len() as i64;
+        insert.execute(params![
+            endpoint_id,
+            day,
+            device_type,
+            online_hours,
+            watts,
+            watts * online_hours as f64
+        ])?;
+    }
+    drop(insert);
+    tx.commit()?;
+    Ok(written)
+}
+
+/// Energy over one local day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnergyDay {
+    /// Local date, YYYY-MM-DD
+    pub date: String,
+    pub kwh: f64,
+    /// Hours online summed over every endpoint
+    pub online_hours: i64,
+}
+
+/// Energy over one calendar month
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnergyMonth {
+    /// YYYY-MM
+    pub month: String,
+    pub kwh: f64,
+    /// Days in the month with any data
+    pub days: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeEnergy {
+    pub device_type: String,
+    pub kwh: f64,
+    pub endpoints: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EndpointEnergy {
+    pub endpoint_id: i64,
+    pub endpoint: Option<String>,
+    pub device_type: String,
+    pub online_hours: i64,
+    pub kwh: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnergyReport {
+    /// First local date of the daily window
+    pub since: String,
+    /// Total over the daily window
+    pub total_kwh: f64,
+    pub average_daily_kwh: f64,
+    pub daily: Vec<EnergyDay>,
+    pub monthly: Vec<EnergyMonth>,
+    pub by_type: Vec<TypeEnergy>,
+    /// Biggest users over the daily window
+    pub top_endpoints: Vec<EndpointEnergy>,
+}
+
+/// Endpoints listed in a report's top users
+const TOP_ENDPOINTS: usize = 10;
+
+/// Daily summaries for the last `days` days and monthly ones for the last `months`
+/// calendar months, both counting the current one
+pub fn get_energy_report(
+    conn: &Connection,
+    days: i64,
+    months: i64,
+    now: i64,
+) -> Result<EnergyReport> {
+    let today = Local
+        .timestamp_opt(now, 0)
+        .single()
+        .map(|t| t.date_naive())
+        .unwrap_or_default();
+    let since = (today - chrono::Duration::days(days - 1))
+        .format("%Y-%m-%d")
+        .to_string();
+    let first_month = today.year() * 12 + today.month0() as i32 - (months as i32 - 1);
+    let since_month = NaiveDate::from_ymd_opt(
+        first_month.div_euclid(12),
+        first_month.rem_euclid(12) as u32 + 1,
+        1,
+    )
+    .unwrap_or(today)
+    .format("%Y-%m")
+    .to_string();
+
+    let daily: Vec<EnergyDay> = conn
+        .prepare(
+            "SELECT day, SUM(watt_hours) / 1000.0, SUM(online_hours) FROM energy_daily
+             WHERE day >= ?1 GROUP BY day ORDER BY day",
+        )?
+        .query_map([&since], |row| {
+            Ok(EnergyDay {
+                date: row.get(0)?,
+                kwh: row.get(1)?,
+                online_hours: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+    let monthly: Vec<EnergyMonth> = conn
+        .prepare(
+            "SELECT substr(day, 1, 7) AS month, SUM(watt_hours) / 1000.0, COUNT(DISTINCT day)
+             FROM energy_daily
+             WHERE month >= ?1 GROUP BY month ORDER BY month",
+        )?
+        .query_map([&since_month], |row| {
+            Ok(EnergyMonth {
+                month: row.get(0)?,
+                kwh: row.get(1)?,
+                days: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+    let by_type: Vec<TypeEnergy> = conn
+        .prepare(
+            "SELECT device_type, SUM(watt_hours) / 1000.0, COUNT(DISTINCT endpoint_id)
+             FROM energy_daily
+             WHERE day >= ?1 GROUP BY device_type ORDER BY SUM(watt_hours) DESC",
+        )?
+        .query_map([&since], |row| {
+            Ok(TypeEnergy {
+                device_type: row.get(0)?,
+                kwh: row.get(1)?,
+                endpoints: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+    let top_endpoints: Vec<EndpointEnergy> = conn
+        .prepare(
+            "SELECT d.endpoint_id, e.display_name, MAX(d.device_type), SUM(d.online_hours),
+                    SUM(d.watt_hours) / 1000.0
+             FROM energy_daily d
+             LEFT JOIN endpoints e ON e.id = d.endpoint_id
+             WHERE d.day >= ?1
+             GROUP BY d.endpoint_id
+             ORDER BY SUM(d.watt_hours) DESC
+             LIMIT ?2",
+        )?
+        .query_map(params![since, TOP_ENDPOINTS as i64], |row| {
+            Ok(EndpointEnergy {
+                endpoint_id: row.get(0)?,
+                endpoint: row.get(1)?,
+                device_type: row.get(2)?,
+                online_hours: row.get(3)?,
+                kwh: row.get(4)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+
+    let total_kwh: f64 = daily.iter().map(|d| d.kwh).sum();
+    Ok(EnergyReport {
+        since,
+        total_kwh,
+        average_daily_kwh: if daily.is_empty() {
+            0.0
+        } else {
+            total_kwh / daily.len() as f64
+        },
+        daily,
+        monthly,
+        by_type,
+        top_endpoints,
+    })
+}
+
+/// Start the hourly energy rollup. The first run covers every day the usage retention
+/// still holds in full; later runs redo yesterday and today.
+pub fn start_energy_rollup() {
+    if ENERGY_ROLLUP_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        let mut first_run = true;
+        loop {
+            let now = chrono::Utc::now().timestamp();
+            let since = if first_run {
+                let retention_days = get_setting_i64("usage_retention_days", 30);
+                now - (retention_days - 1).max(1) * 24 * 60 * 60
+            } else {
+                now - 24 * 60 * 60
+            };
+            let result = task::spawn_blocking(move || {
+                let conn = new_connection_result()?;
+                rollup_energy(&conn, since)
+            })
+            .await;
+            match result {
+                Ok(Ok(_)) => first_run = false,
+                Ok(Err(e)) => eprintln!("Energy rollup failed: {}", e),
+                Err(e) => eprintln!("Energy rollup task failed: {}", e),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(60 * 60)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::appearance::DEFAULT_TYPE_APPEARANCE;
+    use crate::db::new_test_connection;
+    use crate::db::usage::record_usage;
+
+    #[test]
+    fn test_every_device_type_has_a_wattage() {
+        for (device_type, _, _) in DEFAULT_TYPE_APPEARANCE {
+            assert!(
+                DEFAULT_TYPE_WATTS.iter().any(|(t, _)| t == device_type),
+                "no wattage for {}",
+                device_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_energy_from_online_hours_and_overrides() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'living-room-tv', 'tv'), (2, 1, 'router', 'gateway');",
+        )
+        .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let midnight = local_day_start(now);
+        // The TV online for three hours of today, the router for one
+        for hour in 0..3 {
+            record_usage(&conn, 1, None, 1, 100, midnight + hour * 3600 + 60).unwrap();
+        }
+        record_usage(&conn, 2, None, 1, 100, midnight + 60).unwrap();
+
+        set_type_power(&conn, "gateway", Some(10.0)).unwrap();
+        set_endpoint_power(&conn, 1, Some(100.0)).unwrap();
+        assert_eq!(rollup_energy(&conn, now).unwrap(), 2);
+
+        let report = get_energy_report(&conn, 7, 12, now).unwrap();
+        assert_eq!(report.daily.len(), 1);
+        assert!((report.total_kwh - 0.31).abs() < 1e-9);
+        assert_eq!(report.monthly.len(), 1);
+        assert_eq!(report.top_endpoints[0].endpoint_id, 1);
+        assert_eq!(report.top_endpoints[0].online_hours, 3);
+        assert_eq!(report.by_type[0].device_type, "tv");
+
+        // Clearing the override falls back to the type's estimate
+        set_endpoint_power(&conn, 1, None).unwrap();
+        rollup_energy(&conn, now).unwrap();
+        let report = get_energy_report(&conn, 7, 12, now).unwrap();
+        assert!((report.total_kwh - 0.28).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,554 @@
+//! The communication graph in standard formats, for layout and analysis in Gephi or
+//! Cytoscape: GraphML, Graphviz DOT, and Cytoscape.js JSON. Nodes are endpoints and
+//! each directed edge totals the traffic one endpoint sent another within the window.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use super::get_endpoint_tags;
+use crate::network::broadcast::not_noise_sql;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    GraphMl,
+    Dot,
+    Json,
+}
+
+impl GraphFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "graphml" => Some(GraphFormat::GraphMl),
+            "dot" | "gv" => Some(GraphFormat::Dot),
+            "json" | "cytoscape" | "cyjs" => Some(GraphFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            GraphFormat::GraphMl => "application/graphml+xml",
+            GraphFormat::Dot => "text/vnd.graphviz",
+            GraphFormat::Json => "application/json",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            GraphFormat::GraphMl => "network.graphml",
+            GraphFormat::Dot => "network.dot",
+            GraphFormat::Json => "network.cyjs",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GraphNode {
+    pub id: i64,
+    pub label: String,
+    pub ip: Option<String>,
+    pub mac: Option<String>,
+    pub vendor: Option<String>,
+    pub device_type: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GraphEdge {
+    pub source: i64,
+    pub target: i64,
+    pub protocols: Vec<String>,
+    /// Destination ports, lowest first
+    pub ports: Vec<u16>,
+    pub packets: i64,
+    pub bytes: i64,
+    /// Distinct flows (protocol and port combinations) behind the edge
+    pub flows: i64,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommunicationGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Which communications go into the graph
+#[derive(Debug, Clone, Default)]
+pub struct GraphFilter {
+    pub since: i64,
+    /// Keep only edges with an endpoint of one of these types (all when empty)
+    pub device_types: Vec<String>,
+    /// Keep only edges with an endpoint carrying one of these tags (all when empty). With
+    /// device types too, that endpoint must match both.
+    pub tags: Vec<String>,
+    pub interface: Option<String>,
+    pub exclude_noise: bool,
+}
+
+fn split_list(value: Option<String>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn load_node(conn: &Connection, endpoint_id: i64) -> Result<Option<GraphNode>> {
+    let tags = get_endpoint_tags(conn, &[endpoint_id])?;
+    conn.query_row(
+        "SELECT e.display_name, s.ips, s.macs, s.vendor,
+                COALESCE(s.device_type, e.manual_device_type, e.auto_device_type)
+         FROM endpoints e
+         LEFT JOIN endpoint_summary s ON s.endpoint_id = e.id
+         WHERE e.id = ?1
+         LIMIT 1",
+        [endpoint_id],
+        |row| {
+            Ok(GraphNode {
+                id: endpoint_id,
+                label: row
+                    .get::<_, Option<String>>(0)?
+                    .unwrap_or_else(|| format!("endpoint {}", endpoint_id)),
+                ip: split_list(row.get(1)?).into_iter().next(),
+                mac: split_list(row.get(2)?).into_iter().next(),
+                vendor: row.get(3)?,
+                device_type: row.get(4)?,
+                tags,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Endpoints and the traffic between them, per `filter`
+pub fn get_communication_graph(
+    conn: &Connection,
+    filter: &GraphFilter,
+) -> Result<CommunicationGraph> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT c.src_endpoint_id, c.dst_endpoint_id,
+                GROUP_CONCAT(DISTINCT COALESCE(NULLIF(c.sub_protocol, ''), c.ip_header_protocol)),
+                GROUP_CONCAT(DISTINCT c.destination_port),
+                SUM(c.packet_count), SUM(c.bytes), MIN(c.created_at), MAX(c.last_seen_at),
+                COUNT(*)
+         FROM communications c
+         WHERE c.last_seen_at >= ?1
+           AND (?2 IS NULL OR c.interface = ?2)
+           AND (?3 = 0 OR {})
+           AND c.src_endpoint_id != c.dst_endpoint_id
+         GROUP BY c.src_endpoint_id, c.dst_endpoint_id",
+        not_noise_sql("c")
+    ))?;
+    let mut edges = stmt
+        .query_map(
+            params![filter.since, filter.interface, filter.exclude_noise],
+            |row| {
+                let mut ports: Vec<u16> = split_list(row.get(3)?)
+                    .iter()
+                    .filter_map(|port| port.parse().ok())
+                    .collect();
+                ports.sort_unstable();
+                let mut protocols = split_list(row.get(2)?);
+                protocols.sort();
+                Ok(GraphEdge {
+                    source: row.get(0)?,
+                    target: row.get(1)?,
+                    protocols,
+                    ports,
+                    packets: row.get(4)?,
+                    bytes: row.get(5)?,
+                    flows: row.get(8)?,
+                    first_seen_at: row.get(6)?,
+                    last_seen_at: row.get(7)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut nodes = BTreeMap::new();
+    for endpoint_id in edges.iter().flat_map(|edge| [edge.source, edge.target]) {
+        if !nodes.contains_key(&endpoint_id)
+            && let Some(node) = load_node(conn, endpoint_id)?
+        {
+            nodes.insert(endpoint_id, node);
+        }
+    }
+
+    let matches = |endpoint_id: &i64| {
+        nodes.get(endpoint_id).is_some_and(|node| {
+            (filter.device_types.is_empty()
+                || node.device_type.as_ref().is_some_and(|device_type| {
+                    filter
+                        .device_types
+                        .iter()
+                        .any(|t| t.eq_ignore_ascii_case(device_type))
+                }))
+                && (filter.tags.is_empty() || node.tags.iter().any(|t| filter.tags.contains(t)))
+        })
+    };
+    edges.retain(|edge| {
+        nodes.contains_key(&edge.source)
+            && nodes.contains_key(&edge.target)
+            && (matches(&edge.source) || matches(&edge.target))
+    });
+    edges.sort_by_key(|edge| (edge.source, edge.target));
+
+    let connected: HashSet<i64> = edges
+        .iter()
+        .flat_map(|edge| [edge.source, edge.target])
+        .collect();
+    nodes.retain(|endpoint_id, _| connected.contains(endpoint_id));
+
+    Ok(CommunicationGraph {
+        nodes: nodes.into_values().collect(),
+        edges,
+    })
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn join_ports(ports: &[u16]) -> String {
+    ports
+        .iter()
+        .map(u16::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Node attributes shared by the GraphML and DOT output
+fn node_attributes(node: &GraphNode) -> Vec<(&'static str, String)> {
+    [
+        ("label", Some(node.label.clone())),
+        ("ip", node.ip.clone()),
+        ("mac", node.mac.clone()),
+        ("vendor", node.vendor.clone()),
+        ("device_type", node.device_type.clone()),
+        ("tags", (!node.tags.is_empty()).then(|| node.tags.join(","))),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.map(|value| (key, value)))
+    .collect()
+}
+
+/// Edge attributes shared by the GraphML and DOT output. Weight is the byte count, which
+/// Gephi uses for edge thickness.
+fn edge_attributes(edge: &GraphEdge) -> Vec<(&'static str, String)> {
+    vec![
+        ("protocols", edge.protocols.join(",")),
+        ("ports", join_ports(&edge.ports)),
+        ("packets", edge.packets.to_string()),
+        ("bytes", edge.bytes.to_string()),
+        ("weight", edge.bytes.max(1).to_string()),
+        ("first_seen_at", edge.first_seen_at.to_string()),
+        ("last_seen_at", edge.last_seen_at.to_string()),
+    ]
+}
+
+pub fn to_graphml(graph: &CommunicationGraph) -> String {
+    const NODE_KEYS: &[&str] = &["label", "ip", "mac", "vendor", "device_type", "tags"];
+    const EDGE_KEYS: &[(&str, &str)] = &[
+        ("protocols", "string"),
+        ("ports", "string"),
+        ("packets", "long"),
+        ("bytes", "long"),
+        ("weight", "double"),
+        ("first_seen_at", "long"),
+        ("last_seen_at", "long"),
+    ];
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+    );
+    for key in NODE_KEYS {
+        let _ = writeln!(
+            out,
+            "  <key id=\"{0}\" for=\"node\" attr.name=\"{0}\" attr.type=\"string\"/>",
+            key
+        );
+    }
+    for (key, kind) in EDGE_KEYS {
+        let _ = writeln!(
+            out,
+            "  <key id=\"{0}\" for=\"edge\" attr.name=\"{0}\" attr.type=\"{1}\"/>",
+            key, kind
+        );
+    }
+    out.push_str("  <graph id=\"network\" edgedefault=\"directed\">\n");
+    for node in &graph.nodes {
+        let _ = writeln!(out, "    <node id=\"n{}\">", node.id);
+        for (key, value) in node_attributes(node) {
+            let _ = writeln!(
+                out,
+                "      <data key=\"{}\">{}</data>",
+                key,
+                xml_escape(&value)
+            );
+        }
+        out.push_str("    </node>\n");
+    }
+    for (index, edge) in graph.edges.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">",
+            index, edge.source, edge.target
+        );
+        for (key, value) in edge_attributes(edge) {
+            let _ = writeln!(
+                out,
+                "      <data key=\"{}\">{}</data>",
+                key,
+                xml_escape(&value)
+            );
+        }
+        out.push_str("    </edge>\n");
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+pub fn to_dot(graph: &CommunicationGraph) -> String {
+    let attributes = |pairs: Vec<(&str, String)>| {
+        pairs
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, dot_escape(value)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut out = String::from("digraph network {\n");
+    for node in &graph.nodes {
+        let _ = writeln!(
+            out,
+            "  n{} [{}];",
+            node.id,
+            attributes(node_attributes(node))
+        );
+    }
+    for edge in &graph.edges {
+        let mut pairs = edge_attributes(edge);
+        // Graphviz draws the edge label; the protocols make the most readable one
+        pairs.insert(0, ("label", edge.protocols.join(",")));
+        let _ = writeln!(
+            out,
+            "  n{} -> n{} [{}];",
+            edge.source,
+            edge.target,
+            attributes(pairs)
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Cytoscape.js elements, which Cytoscape desktop imports as a `.cyjs` network
+pub fn to_cytoscape_json(graph: &CommunicationGraph) -> serde_json::Value {
+    let nodes: Vec<serde_json::Value> = graph
+        .nodes
+        .iter()
+        .map(|node| {
+            serde_json::json!({ "data": {
+                "id": format!("n{}", node.id),
+                "name": node.label,
+                "ip": node.ip,
+                "mac": node.mac,
+                "vendor": node.vendor,
+                "device_type": node.device_type,
+                "tags": node.tags,
+            }})
+        })
+        .collect();
+    let edges: Vec<serde_json::Value> = graph
+        .edges
+        .iter()
+        .enumerate()
+        .map(|(index, edge)| {
+            serde_json::json!({ "data": {
+                "id": format!("e{}", index),
+                "source": format!("n{}", edge.source),
+                "target": format!("n{}", edge.target),
+                "protocols": edge.protocols,
+                "ports": edge.ports,
+                "packets": edge.packets,
+                "bytes": edge.bytes,
+                "first_seen_at": edge.first_seen_at,
+                "last_seen_at": edge.last_seen_at,
+            }})
+        })
+        .collect();
+    serde_json::json!({
+        "data": { "name": "network" },
+        "elements": { "nodes": nodes, "edges": edges },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn sample_graph() -> CommunicationGraph {
+        CommunicationGraph {
+            nodes: vec![
+                GraphNode {
+                    id: 1,
+                    label: "Dad's \"work\" laptop".to_string(),
+                    ip: Some("192.168.1.20".to_string()),
+                    device_type: Some("local".to_string()),
+                    ..Default::default()
+                },
+                GraphNode {
+                    id: 2,
+                    label: "laserjet".to_string(),
+                    device_type: Some("printer".to_string()),
+                    tags: vec!["office".to_string(), "shared".to_string()],
+                    ..Default::default()
+                },
+            ],
+            edges: vec![GraphEdge {
+                source: 1,
+                target: 2,
+                protocols: vec!["IPP".to_string()],
+                ports: vec![631],
+                packets: 10,
+                bytes: 4000,
+                flows: 1,
+                first_seen_at: 100,
+                last_seen_at: 200,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_graph_formats() {
+        let graph = sample_graph();
+
+        let graphml = to_graphml(&graph);
+        assert!(graphml.contains("<node id=\"n1\">"));
+        assert!(graphml.contains("Dad's &quot;work&quot; laptop"));
+        assert!(graphml.contains("<edge id=\"e0\" source=\"n1\" target=\"n2\">"));
+        assert!(graphml.contains("<data key=\"ports\">631</data>"));
+        assert!(graphml.contains("<data key=\"tags\">office,shared</data>"));
+
+        let dot = to_dot(&graph);
+        assert!(dot.starts_with("digraph network {"));
+        assert!(dot.contains("n1 [label=\"Dad's \\\"work\\\" laptop\""));
+        assert!(dot.contains("n1 -> n2 [label=\"IPP\""));
+        assert!(dot.contains("tags=\"office,shared\""));
+
+        let json = to_cytoscape_json(&graph);
+        assert_eq!(json["elements"]["nodes"][1]["data"]["name"], "laserjet");
+        assert_eq!(json["elements"]["nodes"][1]["data"]["tags"][1], "shared");
+        assert_eq!(json["elements"]["edges"][0]["data"]["source"], "n1");
+        assert_eq!(json["elements"]["edges"][0]["data"]["bytes"], 4000);
+
+        assert_eq!(GraphFormat::parse("GraphML"), Some(GraphFormat::GraphMl));
+        assert_eq!(GraphFormat::parse("svg"), None);
+    }
+
+    #[test]
+    fn test_communication_graph_filters() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'laptop', NULL), (2, 1, 'laserjet', 'printer'), (3, 1, 'nas', NULL);
+             INSERT INTO communications (src_endpoint_id, dst_endpoint_id, created_at,
+                 last_seen_at, packet_count, bytes, destination_port, ip_header_protocol,
+                 sub_protocol, interface) VALUES
+                 (1, 2, 100, 500, 4, 400, 631, 'Tcp', 'IPP', 'eth0'),
+                 (1, 2, 100, 600, 2, 100, 9100, 'Tcp', '', 'eth0'),
+                 (1, 3, 100, 700, 5, 900, 445, 'Tcp', 'SMB', 'eth0'),
+                 (3, 1, 10, 20, 1, 60, 22, 'Tcp', 'SSH', 'eth0');",
+        )
+        .unwrap();
+
+        let graph = get_communication_graph(
+            &conn,
+            &GraphFilter {
+                since: 400,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // The SSH flow is older than the window
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!(graph.nodes.len(), 3);
+        let printing = &graph.edges[0];
+        assert_eq!(printing.protocols, vec!["IPP", "Tcp"]);
+        assert_eq!(printing.ports, vec![631, 9100]);
+        assert_eq!((printing.packets, printing.bytes), (6, 500));
+        assert_eq!(printing.flows, 2);
+
+        let printers = get_communication_graph(
+            &conn,
+            &GraphFilter {
+                since: 0,
+                device_types: vec!["printer".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(printers.edges.len(), 1);
+        assert_eq!(
+            printers.nodes.iter().map(|n| n.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let other_interface = get_communication_graph(
+            &conn,
+            &GraphFilter {
+                interface: Some("wlan0".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(other_interface.edges.is_empty());
+        assert!(other_interface.nodes.is_empty());
+
+        conn.execute_batch(
+            "INSERT INTO endpoint_tags (endpoint_id, tag, created_at) VALUES
+                 (3, 'backup', 1), (2, 'office', 1);",
+        )
+        .unwrap();
+        let tagged = |device_types: &[&str], tags: &[&str]| {
+            let graph = get_communication_graph(
+                &conn,
+                &GraphFilter {
+                    since: 400,
+                    device_types: device_types.iter().map(|t| t.to_string()).collect(),
+                    tags: tags.iter().map(|t| t.to_string()).collect(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            graph
+                .edges
+                .iter()
+                .map(|edge| (edge.source, edge.target))
+                .collect::<Vec<_>>()
+        };
+        // Edges touching a tagged endpoint, from either end
+        assert_eq!(tagged(&[], &["backup"]), vec![(1, 3)]);
+        assert_eq!(tagged(&[], &["backup", "office"]), vec![(1, 2), (1, 3)]);
+        // Device types and tags narrow each other
+        assert_eq!(tagged(&["printer"], &["office"]), vec![(1, 2)]);
+        assert!(tagged(&["printer"], &["backup"]).is_empty());
+        assert!(tagged(&[], &["guest"]).is_empty());
+    }
+}
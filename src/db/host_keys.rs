@@ -0,0 +1,540 @@
+//! SSH host keys and TLS certificates of endpoint services, collected after port scans.
+//! A changed SSH host key raises host_key_changed, as does a certificate swapped for one
+//! with a different key while the old one still had time to run (renewals close to
+//! expiry are routine). Certificates nearing expiry raise certificate_expiring once each.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use super::insert_notification_with_endpoint_id;
+use crate::network::endpoint::EndPoint;
+use crate::scanner::{SshHostKey, TlsCertificate};
+
+pub fn create_host_key_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_ssh_host_keys (
+            endpoint_id INTEGER NOT NULL,
+            port INTEGER NOT NULL,
+            key_type TEXT NOT NULL,
+            fingerprint TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, port, key_type)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_tls_certificates (
+            endpoint_id INTEGER NOT NULL,
+            port INTEGER NOT NULL,
+            subject_cn TEXT,
+            issuer TEXT,
+            not_before INTEGER,
+            not_after INTEGER,
+            self_signed INTEGER NOT NULL DEFAULT 0,
+            fingerprint TEXT NOT NULL,
+            key_fingerprint TEXT NOT NULL,
+            expiry_notified INTEGER NOT NULL DEFAULT 0,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, port)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_host_key_checks (
+            endpoint_id INTEGER PRIMARY KEY,
+            checked_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// An endpoint due a host key check, with the SSH/TLS ports port scans found open on it
+#[derive(Debug)]
+pub struct HostKeyTarget {
+    pub endpoint_id: i64,
+    pub ip: String,
+    pub ports: Vec<u16>,
+}
+
+/// Endpoints with an open SSH or TLS port that weren't checked since `stale_before`
+pub fn get_host_key_targets(
+    conn: &Connection,
+    ports: &[u16],
+    stale_before: i64,
+) -> Result<Vec<HostKeyTarget>> {
+    let ports = ports
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT op.endpoint_id, GROUP_CONCAT(DISTINCT op.port),
+                (SELECT ea.ip FROM endpoint_attributes ea
+                 WHERE ea.endpoint_id = op.endpoint_id AND ea.ip != ''
+                 ORDER BY ea.created_at DESC LIMIT 1) AS ip
+         FROM open_ports op
+         LEFT JOIN endpoint_host_key_checks c ON c.endpoint_id = op.endpoint_id
+         WHERE op.port IN ({}) AND (c.checked_at IS NULL OR c.checked_at < ?1)
+         GROUP BY op.endpoint_id
+         HAVING ip IS NOT NULL",
+        ports
+    ))?;
+    let rows = stmt.query_map([stale_before], |row| {
+        let ports: String = row.get(1)?;
+        Ok(HostKeyTarget {
+            endpoint_id: row.get(0)?,
+            ports: ports.split(',').filter_map(|p| p.parse().ok()).collect(),
+            ip: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn endpoint_display_name(conn: &Connection, endpoint_id: i64) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten())
+}
+
+/// Store a host key seen at `now`. Returns true when it replaced a different key.
+pub fn store_ssh_host_key(
+    conn: &Connection,
+    endpoint_id: i64,
+    key: &SshHostKey,
+    now: i64,
+) -> Result<bool> {
+    let previous: Option<String> = conn
+        .query_row(
+            "SELECT fingerprint FROM endpoint_ssh_host_keys
+             WHERE endpoint_id = ?1 AND port = ?2 AND key_type = ?3",
+            params![endpoint_id, key.port, key.key_type],
+            |row| row.get(0),
+        )
+        .optional()?;
+    conn.execute(
+        "INSERT INTO endpoint_ssh_host_keys
+            (endpoint_id, port, key_type, fingerprint, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+         ON CONFLICT(endpoint_id, port, key_type) DO UPDATE SET
+            first_seen_at = CASE WHEN fingerprint = excluded.fingerprint
+                                 THEN first_seen_at ELSE excluded.first_seen_at END,
+            fingerprint = excluded.fingerprint,
+            last_seen_at = excluded.last_seen_at",
+        params![endpoint_id, key.port, key.key_type, key.fingerprint, now],
+    )?;
+
+    let Some(previous) = previous.filter(|previous| *previous != key.fingerprint) else {
+        return Ok(false);
+    };
+    let endpoint_name = endpoint_display_name(conn, endpoint_id)?;
+    insert_notification_with_endpoint_id(
+        conn,
+        "host_key_changed",
+        &format!(
+            "SSH host key of {} changed",
+            endpoint_name.as_deref().unwrap_or("an endpoint")
+        ),
+        Some(&format!(
+            "Port {} {}: {} is now {}",
+            key.port, key.key_type, previous, key.fingerprint
+        )),
+        endpoint_name.as_deref(),
+        Some(endpoint_id),
+    );
+    Ok(true)
+}
+
+/// Store a certificate seen at `now`, alerting on an early key change and on expiry
+/// within `warning_days`
+pub fn store_tls_certificate(
+    conn: &Connection,
+    endpoint_id: i64,
+    cert: &TlsCertificate,
+    now: i64,
+    warning_days: i64,
+) -> Result<()> {
+    let warn_after = now + warning_days * 24 * 60 * 60;
+    let previous: Option<(String, String, Option<i64>, bool)> = conn
+        .query_row(
+            "SELECT fingerprint, key_fingerprint, not_after, expiry_notified
+             FROM endpoint_tls_certificates WHERE endpoint_id = ?1 AND port = ?2",
+            params![endpoint_id, cert.port],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+
+    let mut expiry_notified = false;
+    let mut key_replaced_early = false;
+    match &previous {
+        Some((fingerprint, _, _, notified)) if *fingerprint == cert.fingerprint => {
+            expiry_notified = *notified;
+        }
+        Some((_, key_fingerprint, not_after, _)) => {
+            key_replaced_early = *key_fingerprint != cert.key_fingerprint
+                && not_after.is_none_or(|not_after| not_after > warn_after);
+        }
+        None => {}
+    }
+
+    let expiring = cert
+        .not_after
+        .is_some_and(|not_after| not_after <= warn_after);
+    conn.execute(
+        "INSERT INTO endpoint_tls_certificates
+            (endpoint_id, port, subject_cn, issuer, not_before, not_after, self_signed,
+             fingerprint, key_fingerprint, expiry_notified, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?11)
+         ON CONFLICT(endpoint_id, port) DO UPDATE SET
+            first_seen_at = CASE WHEN fingerprint = excluded.fingerprint
+                                 THEN first_seen_at ELSE excluded.first_seen_at END,
+            subject_cn = excluded.subject_cn,
+            issuer = excluded.issuer,
+            not_before = excluded.not_before,
+            not_after = excluded.not_after,
+            self_signed = excluded.self_signed,
+            fingerprint = excluded.fingerprint,
+            key_fingerprint = excluded.key_fingerprint,
+            expiry_notified = excluded.expiry_notified,
+            last_seen_at = excluded.last_seen_at",
+        params![
+            endpoint_id,
+            cert.port,
+            cert.subject_cn,
+            cert.issuer,
+            cert.not_before,
+            cert.not_after,
+            cert.self_signed,
+            cert.fingerprint,
+            cert.key_fingerprint,
+            expiry_notified || expiring,
+            now
+        ],
+    )?;
+    if !key_replaced_early && (expiry_notified || !expiring) {
+        return Ok(());
+    }
+
+    let endpoint_name = endpoint_display_name(conn, endpoint_id)?;
+    let name = endpoint_name.as_deref().unwrap_or("an endpoint");
+    let subject = cert.subject_cn.as_deref().unwrap_or("no common name");
+    if key_replaced_early {
+        insert_notification_with_endpoint_id(
+            conn,
+            "host_key_changed",
+            &format!("TLS certificate key of {} changed", name),
+            Some(&format!(
+                "Port {} ({}) presented a new key before its certificate was due to expire",
+                cert.port, subject
+            )),
+            endpoint_name.as_deref(),
+            Some(endpoint_id),
+        );
+    }
+    if expiring
+        && !expiry_notified
+        && let Some(not_after) = cert.not_after
+    {
+        let expires = chrono::DateTime::from_timestamp(not_after, 0)
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let title = if not_after <= now {
+            format!("TLS certificate of {} has expired", name)
+        } else {
+            format!("TLS certificate of {} expires soon", name)
+        };
+        insert_notification_with_endpoint_id(
+            conn,
+            "certificate_expiring",
+            &title,
+            Some(&format!(
+                "Port {} ({}), expires {}",
+                cert.port, subject, expires
+            )),
+            endpoint_name.as_deref(),
+            Some(endpoint_id),
+        );
+    }
+    Ok(())
+}
+
+/// Store everything one check of an endpoint found and mark it checked. Keys and
+/// certificates of services that didn't answer are kept.
+pub fn store_host_keys(
+    conn: &Connection,
+    endpoint_id: i64,
+    ssh_keys: &[SshHostKey],
+    certificates: &[TlsCertificate],
+    now: i64,
+    warning_days: i64,
+) -> Result<()> {
+    for key in ssh_keys {
+        store_ssh_host_key(conn, endpoint_id, key, now)?;
+    }
+    for cert in certificates {
+        store_tls_certificate(conn, endpoint_id, cert, now, warning_days)?;
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO endpoint_host_key_checks (endpoint_id, checked_at)
+         VALUES (?1, ?2)",
+        params![endpoint_id, now],
+    )?;
+
+    // A device's own certificates anchor its identity, as an EAP-TLS login does
+    for subject in certificates
+        .iter()
+        .filter(|cert| identifies_device(cert))
+        .filter_map(|cert| cert.subject.as_deref())
+    {
+        if EndPoint::anchor_certificate(conn, endpoint_id, subject, None, now)? != endpoint_id {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a certificate names a particular device rather than "localhost" or an address
+fn identifies_device(cert: &TlsCertificate) -> bool {
+    cert.subject_cn.as_deref().is_some_and(|cn| {
+        let cn = cn.trim();
+        !cn.is_empty()
+            && !cn.eq_ignore_ascii_case("localhost")
+            && cn.parse::<std::net::IpAddr>().is_err()
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SshHostKeyRecord {
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+    /// When this key was first seen (reset by a key change)
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TlsCertificateRecord {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub port: u16,
+    pub subject_cn: Option<String>,
+    pub issuer: Option<String>,
+    pub not_before: Option<i64>,
+    pub not_after: Option<i64>,
+    pub self_signed: bool,
+    pub fingerprint: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHostKeys {
+    pub ssh_host_keys: Vec<SshHostKeyRecord>,
+    pub tls_certificates: Vec<TlsCertificateRecord>,
+}
+
+fn certificate_record(row: &rusqlite::Row) -> Result<TlsCertificateRecord> {
+    Ok(TlsCertificateRecord {
+        endpoint_id: row.get(0)?,
+        endpoint_name: row.get(1)?,
+        port: row.get(2)?,
+        subject_cn: row.get(3)?,
+        issuer: row.get(4)?,
+        not_before: row.get(5)?,
+        not_after: row.get(6)?,
+        self_signed: row.get(7)?,
+        fingerprint: row.get(8)?,
+        first_seen_at: row.get(9)?,
+        last_seen_at: row.get(10)?,
+    })
+}
+
+const CERTIFICATE_COLUMNS: &str = "c.endpoint_id, e.display_name, c.port, c.subject_cn, \
+     c.issuer, c.not_before, c.not_after, c.self_signed, c.fingerprint, c.first_seen_at, \
+     c.last_seen_at";
+
+/// Host keys and certificates stored for one endpoint
+pub fn get_endpoint_host_keys(conn: &Connection, endpoint_id: i64) -> Result<EndpointHostKeys> {
+    let mut stmt = conn.prepare(
+        "SELECT port, key_type, fingerprint, first_seen_at, last_seen_at
+         FROM endpoint_ssh_host_keys WHERE endpoint_id = ?1
+         ORDER BY port, key_type",
+    )?;
+    let ssh_host_keys = stmt
+        .query_map([endpoint_id], |row| {
+            Ok(SshHostKeyRecord {
+                port: row.get(0)?,
+                key_type: row.get(1)?,
+                fingerprint: row.get(2)?,
+                first_seen_at: row.get(3)?,
+                last_seen_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM endpoint_tls_certificates c
+         LEFT JOIN endpoints e ON e.id = c.endpoint_id
+         WHERE c.endpoint_id = ?1 ORDER BY c.port",
+        CERTIFICATE_COLUMNS
+    ))?;
+    let tls_certificates = stmt
+        .query_map([endpoint_id], certificate_record)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(EndpointHostKeys {
+        ssh_host_keys,
+        tls_certificates,
+    })
+}
+
+/// Certificates on every endpoint, soonest expiry first. `expiring_before` keeps only
+/// those whose validity ends before that time.
+pub fn get_tls_certificates(
+    conn: &Connection,
+    expiring_before: Option<i64>,
+) -> Result<Vec<TlsCertificateRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM endpoint_tls_certificates c
+         LEFT JOIN endpoints e ON e.id = c.endpoint_id
+         WHERE ?1 IS NULL OR c.not_after < ?1
+         ORDER BY c.not_after IS NULL, c.not_after, c.endpoint_id, c.port",
+        CERTIFICATE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([expiring_before], certificate_record)?;
+    rows.collect()
+}
+
+/// Move an endpoint's host keys and certificates to the endpoint it is merged into,
+/// unless that one has its own for the same port
+pub fn reassign_host_keys(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    for table in [
+        "endpoint_ssh_host_keys",
+        "endpoint_tls_certificates",
+        "endpoint_host_key_checks",
+    ] {
+        conn.execute(
+            &format!(
+                "UPDATE OR IGNORE {} SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+                table
+            ),
+            params![to_id, from_id],
+        )?;
+        conn.execute(
+            &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+            [from_id],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn cert(fingerprint: &str, key_fingerprint: &str, not_after: i64) -> TlsCertificate {
+        TlsCertificate {
+            port: 443,
+            subject_cn: Some("nas.local".to_string()),
+            subject: Some("CN=nas.local".to_string()),
+            issuer: Some("nas.local".to_string()),
+            not_before: Some(0),
+            not_after: Some(not_after),
+            self_signed: true,
+            fingerprint: fingerprint.to_string(),
+            key_fingerprint: key_fingerprint.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_host_key_changes_and_certificate_expiry() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'nas'), (2, 1, 'nas-2');",
+        )
+        .unwrap();
+        let notifications = |event_type: &str| -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = ?1",
+                [event_type],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        let key = |fingerprint: &str| SshHostKey {
+            port: 22,
+            key_type: "ssh-ed25519".to_string(),
+            fingerprint: fingerprint.to_string(),
+        };
+        assert!(!store_ssh_host_key(&conn, 1, &key("SHA256:a"), 100).unwrap());
+        assert!(!store_ssh_host_key(&conn, 1, &key("SHA256:a"), 200).unwrap());
+        assert_eq!(notifications("host_key_changed"), 0);
+        assert!(store_ssh_host_key(&conn, 1, &key("SHA256:b"), 300).unwrap());
+        assert_eq!(notifications("host_key_changed"), 1);
+        let stored = get_endpoint_host_keys(&conn, 1).unwrap().ssh_host_keys;
+        assert_eq!(
+            (stored[0].fingerprint.as_str(), stored[0].first_seen_at),
+            ("SHA256:b", 300)
+        );
+
+        let now = 1000 * DAY;
+        store_host_keys(&conn, 1, &[], &[cert("A", "K1", now + 90 * DAY)], now, 30).unwrap();
+        // Renewed with the same key, then with a new key close to expiry: both routine
+        store_tls_certificate(&conn, 1, &cert("B", "K1", now + 100 * DAY), now, 30).unwrap();
+        let renewal = now + 80 * DAY;
+        store_tls_certificate(&conn, 1, &cert("C", "K2", now + 200 * DAY), renewal, 30).unwrap();
+        assert_eq!(notifications("host_key_changed"), 1);
+        // A new key while the old certificate had months left is unexpected
+        store_tls_certificate(&conn, 1, &cert("D", "K3", now + 400 * DAY), renewal, 30).unwrap();
+        assert_eq!(notifications("host_key_changed"), 2);
+
+        // Expiring soon: notified once per certificate
+        let later = now + 380 * DAY;
+        store_tls_certificate(&conn, 1, &cert("D", "K3", now + 400 * DAY), later, 30).unwrap();
+        store_tls_certificate(&conn, 1, &cert("D", "K3", now + 400 * DAY), later + DAY, 30)
+            .unwrap();
+        assert_eq!(notifications("certificate_expiring"), 1);
+        assert_eq!(notifications("host_key_changed"), 2);
+
+        let expiring = get_tls_certificates(&conn, Some(later + 30 * DAY)).unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].fingerprint, "D");
+        assert!(get_tls_certificates(&conn, Some(now)).unwrap().is_empty());
+
+        reassign_host_keys(&conn, 1, 2).unwrap();
+        let moved = get_endpoint_host_keys(&conn, 2).unwrap();
+        assert_eq!(
+            (moved.ssh_host_keys.len(), moved.tls_certificates.len()),
+            (1, 1)
+        );
+        conn.execute_batch(
+            "INSERT INTO endpoint_attributes (created_at, endpoint_id, ip) VALUES (1, 2, '192.168.1.10');
+             INSERT INTO open_ports (endpoint_id, port, last_seen_at) VALUES (2, 22, 1), (2, 80, 1);",
+        )
+        .unwrap();
+        assert!(
+            get_host_key_targets(&conn, &[22, 443], now)
+                .unwrap()
+                .is_empty()
+        );
+        let targets = get_host_key_targets(&conn, &[22, 443], now + 1).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            (targets[0].endpoint_id, targets[0].ports.clone()),
+            (2, vec![22])
+        );
+    }
+}
@@ -0,0 +1,854 @@
+//! Optional push of confirmed endpoints to an external IPAM (NetBox or phpIPAM). An
+//! endpoint counts as confirmed once its materialized summary has a MAC address and a
+//! private IPv4 address. A scheduled task pushes the endpoints that are new or whose
+//! mapped fields changed since their last push, and records the outcome per endpoint for
+//! the sync-status report. The API token is stored with the target and never read back
+//! out through the API.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use reqwest::blocking::{Client, RequestBuilder};
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::task;
+
+use super::{get_endpoint_summaries, get_setting_i64, new_connection_result};
+
+/// Timeout for each request to the IPAM
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Longest error body kept in the sync state
+const MAX_ERROR_LEN: usize = 300;
+
+/// Flag to ensure the sync scheduler is only started once
+static IPAM_SYNC_SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn create_ipam_sync_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ipam_sync_config (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            target TEXT NOT NULL,
+            url TEXT NOT NULL,
+            token TEXT NOT NULL,
+            app_id TEXT NOT NULL DEFAULT '',
+            subnet_id TEXT NOT NULL DEFAULT '',
+            field_map TEXT NOT NULL DEFAULT '{}',
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ipam_sync_state (
+            endpoint_id INTEGER PRIMARY KEY,
+            target TEXT NOT NULL,
+            ip TEXT NOT NULL,
+            remote_id TEXT,
+            fingerprint TEXT NOT NULL,
+            status TEXT NOT NULL,
+            error TEXT,
+            attempted_at INTEGER NOT NULL,
+            synced_at INTEGER
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpamTarget {
+    NetBox,
+    PhpIpam,
+}
+
+impl IpamTarget {
+    fn as_str(self) -> &'static str {
+        match self {
+            IpamTarget::NetBox => "netbox",
+            IpamTarget::PhpIpam => "phpipam",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "netbox" => Some(IpamTarget::NetBox),
+            "phpipam" => Some(IpamTarget::PhpIpam),
+            _ => None,
+        }
+    }
+
+    /// Fields pushed when no field map is configured. NetBox only accepts DNS-safe
+    /// `dns_name` values, so the display name goes into the description there.
+    fn default_field_map(self) -> BTreeMap<String, String> {
+        let fields: &[(&str, &str)] = match self {
+            IpamTarget::NetBox => &[
+                ("description", "{name}"),
+                ("comments", "{type} {vendor} {model} {mac}"),
+            ],
+            IpamTarget::PhpIpam => &[
+                ("hostname", "{name}"),
+                ("mac", "{mac}"),
+                ("description", "{type} {vendor} {model}"),
+            ],
+        };
+        fields
+            .iter()
+            .map(|(field, template)| (field.to_string(), template.to_string()))
+            .collect()
+    }
+}
+
+/// Where and how endpoints are pushed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpamSyncConfig {
+    pub target: IpamTarget,
+    /// Base URL of the IPAM, e.g. https://netbox.example.com
+    pub url: String,
+    /// NetBox API token or phpIPAM app code. Never serialized; left empty on update to
+    /// keep the stored token.
+    #[serde(default, skip_serializing)]
+    pub token: String,
+    /// phpIPAM API app id
+    #[serde(default)]
+    pub app_id: String,
+    /// phpIPAM subnet new addresses are created in
+    #[serde(default)]
+    pub subnet_id: String,
+    /// Remote field -> template over {name}, {mac}, {ip}, {vendor}, {model} and {type}.
+    /// Dotted fields set nested values (custom_fields.mac). Empty uses the defaults.
+    #[serde(default)]
+    pub field_map: BTreeMap<String, String>,
+}
+
+impl IpamSyncConfig {
+    /// Check the config before saving; `has_token` is whether a token is already stored
+    pub fn validate(&self, has_token: bool) -> std::result::Result<(), String> {
+        let url = self.url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err("URL must start with http:// or https://".to_string());
+        }
+        if self.token.trim().is_empty() && !has_token {
+            return Err("An API token is required".to_string());
+        }
+        if self.target == IpamTarget::PhpIpam
+            && (self.app_id.trim().is_empty() || self.subnet_id.trim().is_empty())
+        {
+            return Err("phpIPAM needs an app id and a subnet id".to_string());
+        }
+        if self
+            .field_map
+            .keys()
+            .any(|field| field.is_empty() || field.split('.').any(str::is_empty))
+        {
+            return Err("Field map contains an empty field name".to_string());
+        }
+        Ok(())
+    }
+
+    fn effective_field_map(&self) -> BTreeMap<String, String> {
+        if self.field_map.is_empty() {
+            self.target.default_field_map()
+        } else {
+            self.field_map.clone()
+        }
+    }
+}
+
+/// The configured target, if sync is enabled
+pub fn get_ipam_sync_config(conn: &Connection) -> Result<Option<IpamSyncConfig>> {
+    let row = conn
+        .query_row(
+            "SELECT target, url, token, app_id, subnet_id, field_map
+             FROM ipam_sync_config WHERE id = 1",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            },
+        )
+        .optional()?;
+    Ok(
+        row.and_then(|(target, url, token, app_id, subnet_id, field_map)| {
+            Some(IpamSyncConfig {
+                target: IpamTarget::parse(&target)?,
+                url,
+                token,
+                app_id,
+                subnet_id,
+                field_map: serde_json::from_str(&field_map).unwrap_or_default(),
+            })
+        }),
+    )
+}
+
+/// Whether a token is stored (the value itself is never read back out)
+pub fn has_ipam_sync_token(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM ipam_sync_config WHERE id = 1 AND token != ''",
+        [],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+/// Whether the stored token would be kept for `config`: one is stored, and it was given
+/// for the same target and URL. A token is never reused for another host, or anyone able
+/// to change the URL could have it sent to them.
+pub fn ipam_sync_token_kept(conn: &Connection, config: &IpamSyncConfig) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM ipam_sync_config
+         WHERE id = 1 AND token != '' AND target = ?1 AND url = ?2",
+        params![config.target.as_str(), normalized_url(&config.url)],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+fn normalized_url(url: &str) -> &str {
+    url.trim().trim_end_matches('/')
+}
+
+/// Save the sync target, keeping the stored token when `config.token` is empty and the
+/// target and URL are unchanged (it is cleared otherwise). Pushes recorded against
+/// another target are forgotten so everything is pushed again.
+pub fn set_ipam_sync_config(conn: &Connection, config: &IpamSyncConfig) -> Result<()> {
+    let field_map = serde_json::to_string(&config.field_map).unwrap_or_else(|_| "{}".into());
+    conn.execute(
+        "INSERT INTO ipam_sync_config (id, target, url, token, app_id, subnet_id, field_map, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, strftime('%s', 'now'))
+         ON CONFLICT(id) DO UPDATE SET
+            target = excluded.target,
+            url = excluded.url,
+            token = CASE
+                WHEN excluded.token = '' AND excluded.target = target AND excluded.url = url
+                THEN token ELSE excluded.token END,
+            app_id = excluded.app_id,
+            subnet_id = excluded.subnet_id,
+            field_map = excluded.field_map,
+            updated_at = excluded.updated_at",
+        params![
+            config.target.as_str(),
+            normalized_url(&config.url),
+            config.token.trim(),
+            config.app_id.trim(),
+            config.subnet_id.trim(),
+            field_map
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM ipam_sync_state WHERE target != ?1",
+        [config.target.as_str()],
+    )?;
+    Ok(())
+}
+
+/// Turn sync off, dropping the stored token and sync state
+pub fn clear_ipam_sync_config(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM ipam_sync_config", [])?;
+    conn.execute("DELETE FROM ipam_sync_state", [])?;
+    Ok(())
+}
+
+/// A confirmed endpoint as pushed to the IPAM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncEndpoint {
+    pub endpoint_id: i64,
+    pub name: String,
+    pub mac: String,
+    pub ip: Ipv4Addr,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub device_type: Option<String>,
+}
+
+/// Endpoints with a MAC address and a private IPv4 address
+fn get_confirmed_endpoints(conn: &Connection) -> Result<Vec<SyncEndpoint>> {
+    Ok(get_endpoint_summaries(conn, 0)?
+        .into_iter()
+        .filter_map(|summary| {
+            let mac = summary.macs.first()?.clone();
+            let ip = summary
+                .ips
+                .iter()
+                .filter_map(|ip| ip.parse::<Ipv4Addr>().ok())
+                .find(Ipv4Addr::is_private)?;
+            Some(SyncEndpoint {
+                endpoint_id: summary.endpoint_id,
+                name: summary.name,
+                mac,
+                ip,
+                vendor: summary.vendor,
+                model: summary.model,
+                device_type: summary.device_type,
+            })
+        })
+        .collect())
+}
+
+/// Fill a field template. None when the template has placeholders and all of them are
+/// empty, so unknown values don't push fields like "MAC ".
+fn render_template(template: &str, endpoint: &SyncEndpoint) -> Option<String> {
+    let ip = endpoint.ip.to_string();
+    let values = [
+        ("{name}", endpoint.name.as_str()),
+        ("{mac}", endpoint.mac.as_str()),
+        ("{ip}", ip.as_str()),
+        ("{vendor}", endpoint.vendor.as_deref().unwrap_or("")),
+        ("{model}", endpoint.model.as_deref().unwrap_or("")),
+        ("{type}", endpoint.device_type.as_deref().unwrap_or("")),
+    ];
+    let mut rendered = template.to_string();
+    let mut placeholders = 0;
+    let mut filled = 0;
+    for (placeholder, value) in values {
+        if rendered.contains(placeholder) {
+            placeholders += 1;
+            filled += usize::from(!value.is_empty());
+            rendered = rendered.replace(placeholder, value);
+        }
+    }
+    if placeholders > 0 && filled == 0 {
+        return None;
+    }
+    let rendered = rendered.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!rendered.is_empty()).then_some(rendered)
+}
+
+fn insert_field(fields: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            let nested = fields
+                .entry(head)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(nested) = nested {
+                insert_field(nested, rest, value);
+            }
+        }
+        None => {
+            fields.insert(path.to_string(), value);
+        }
+    }
+}
+
+/// Request body fields for an endpoint under `field_map`
+pub fn map_fields(
+    endpoint: &SyncEndpoint,
+    field_map: &BTreeMap<String, String>,
+) -> Map<String, Value> {
+    let mut fields = Map::new();
+    for (path, template) in field_map {
+        if let Some(value) = render_template(template, endpoint) {
+            insert_field(&mut fields, path, Value::String(value));
+        }
+    }
+    fields
+}
+
+/// What was last pushed for an endpoint
+struct SyncState {
+    fingerprint: String,
+    status: String,
+}
+
+/// An endpoint to push with its mapped fields and their fingerprint
+struct PendingPush {
+    endpoint: SyncEndpoint,
+    fields: Map<String, Value>,
+    fingerprint: String,
+}
+
+/// Confirmed endpoints that were never pushed, failed last time, or changed since,
+/// along with the number that are up to date
+fn get_pending_pushes(
+    conn: &Connection,
+    config: &IpamSyncConfig,
+) -> Result<(Vec<PendingPush>, usize)> {
+    let mut stmt = conn.prepare("SELECT endpoint_id, fingerprint, status FROM ipam_sync_state")?;
+    let states: HashMap<i64, SyncState> = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                SyncState {
+                    fingerprint: row.get(1)?,
+                    status: row.get(2)?,
+                },
+            ))
+        })?
+        .collect::<Result<_>>()?;
+
+    let field_map = config.effective_field_map();
+    let mut pending = Vec::new();
+    let mut unchanged = 0;
+    for endpoint in get_confirmed_endpoints(conn)? {
+        let fields = map_fields(&endpoint, &field_map);
+        let fingerprint = format!("{}|{}", endpoint.ip, Value::Object(fields.clone()));
+        if states
+            .get(&endpoint.endpoint_id)
+            .is_some_and(|state| state.status == "synced" && state.fingerprint == fingerprint)
+        {
+            unchanged += 1;
+            continue;
+        }
+        pending.push(PendingPush {
+            endpoint,
+            fields,
+            fingerprint,
+        });
+    }
+    Ok((pending, unchanged))
+}
+
+fn record_push(
+    conn: &Connection,
+    target: IpamTarget,
+    push: &PendingPush,
+    result: &std::result::Result<String, String>,
+) -> Result<()> {
+    let (remote_id, status, error) = match result {
+        Ok(remote_id) => (Some(remote_id.as_str()), "synced", None),
+        Err(e) => (None, "failed", Some(e.as_str())),
+    };
+    conn.execute(
+        "INSERT INTO ipam_sync_state
+            (endpoint_id, target, ip, remote_id, fingerprint, status, error, attempted_at, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, strftime('%s', 'now'),
+                 CASE WHEN ?6 = 'synced' THEN strftime('%s', 'now') END)
+         ON CONFLICT(endpoint_id) DO UPDATE SET
+            target = excluded.target,
+            ip = excluded.ip,
+            remote_id = COALESCE(excluded.remote_id, remote_id),
+            fingerprint = excluded.fingerprint,
+            status = excluded.status,
+            error = excluded.error,
+            attempted_at = excluded.attempted_at,
+            synced_at = COALESCE(excluded.synced_at, synced_at)",
+        params![
+            push.endpoint.endpoint_id,
+            target.as_str(),
+            push.endpoint.ip.to_string(),
+            remote_id,
+            push.fingerprint,
+            status,
+            error
+        ],
+    )?;
+    Ok(())
+}
+
+/// Send a request and parse its JSON reply. With `allow_not_found`, a 404 is an empty
+/// (null) reply rather than an error.
+fn send(request: RequestBuilder, allow_not_found: bool) -> std::result::Result<Value, String> {
+    let response = request
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .map_err(|e| format!("Request failed: {}", e))?;
+    let status = response.status();
+    if allow_not_found && status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(Value::Null);
+    }
+    let body = response.text().unwrap_or_default();
+    if !status.is_success() {
+        let body: String = body.trim().chars().take(MAX_ERROR_LEN).collect();
+        return Err(format!("HTTP {}: {}", status.as_u16(), body));
+    }
+    Ok(serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+/// Object ids come back as numbers from NetBox and as strings from phpIPAM
+fn json_id(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(id) => Some(id.to_string()),
+        Value::String(id) if !id.is_empty() => Some(id.clone()),
+        _ => None,
+    }
+}
+
+/// Update the NetBox IP address object for the endpoint's address, or create one
+fn push_to_netbox(
+    client: &Client,
+    config: &IpamSyncConfig,
+    push: &PendingPush,
+) -> std::result::Result<String, String> {
+    let base = format!("{}/api/ipam/ip-addresses/", config.url);
+    let auth = format!("Token {}", config.token);
+    let ip = push.endpoint.ip.to_string();
+
+    let found = send(
+        client
+            .get(&base)
+            .query(&[("address", ip.as_str())])
+            .header(reqwest::header::AUTHORIZATION, &auth),
+        false,
+    )?;
+    if let Some(id) = found["results"].get(0).and_then(|r| json_id(&r["id"])) {
+        send(
+            client
+                .patch(format!("{}{}/", base, id))
+                .header(reqwest::header::AUTHORIZATION, &auth)
+                .json(&push.fields),
+            false,
+        )?;
+        return Ok(id);
+    }
+
+    let mut fields = push.fields.clone();
+    fields.insert("address".to_string(), Value::String(format!("{}/32", ip)));
+    let created = send(
+        client
+            .post(&base)
+            .header(reqwest::header::AUTHORIZATION, &auth)
+            .json(&fields),
+        false,
+    )?;
+    json_id(&created["id"]).ok_or_else(|| "NetBox did not return an id".to_string())
+}
+
+/// Update the phpIPAM address for the endpoint's address, or create it in the
+/// configured subnet
+fn push_to_phpipam(
+    client: &Client,
+    config: &IpamSyncConfig,
+    push: &PendingPush,
+) -> std::result::Result<String, String> {
+    let base = format!("{}/api/{}", config.url, config.app_id);
+    let ip = push.endpoint.ip.to_string();
+
+    let found = send(
+        client
+            .get(format!("{}/addresses/search/{}/", base, ip))
+            .header("token", &config.token),
+        true,
+    )?;
+    if let Some(id) = found["data"].get(0).and_then(|a| json_id(&a["id"])) {
+        send(
+            client
+                .patch(format!("{}/addresses/{}/", base, id))
+                .header("token", &config.token)
+                .json(&push.fields),
+            false,
+        )?;
+        return Ok(id);
+    }
+
+    let mut fields = push.fields.clone();
+    fields.insert("ip".to_string(), Value::String(ip));
+    fields.insert(
+        "subnetId".to_string(),
+        Value::String(config.subnet_id.clone()),
+    );
+    let created = send(
+        client
+            .post(format!("{}/addresses/", base))
+            .header("token", &config.token)
+            .json(&fields),
+        false,
+    )?;
+    json_id(&created["id"]).ok_or_else(|| "phpIPAM did not return an id".to_string())
+}
+
+/// Outcome of one sync run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IpamSyncRun {
+    pub pushed: usize,
+    pub failed: usize,
+    pub unchanged: usize,
+}
+
+/// Push every pending endpoint to the configured IPAM (blocking)
+pub fn run_ipam_sync(conn: &Connection) -> std::result::Result<IpamSyncRun, String> {
+    let config = get_ipam_sync_config(conn)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "IPAM sync is not configured".to_string())?;
+
+    // Endpoints deleted or merged away since their last push
+    conn.execute(
+        "DELETE FROM ipam_sync_state WHERE endpoint_id NOT IN (SELECT id FROM endpoints)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let (pending, unchanged) = get_pending_pushes(conn, &config).map_err(|e| e.to_string())?;
+    let mut run = IpamSyncRun {
+        unchanged,
+        ..Default::default()
+    };
+    if pending.is_empty() {
+        return Ok(run);
+    }
+
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    for push in &pending {
+        let result = match config.target {
+            IpamTarget::NetBox => push_to_netbox(&client, &config, push),
+            IpamTarget::PhpIpam => push_to_phpipam(&client, &config, push),
+        };
+        match &result {
+            Ok(_) => run.pushed += 1,
+            Err(_) => run.failed += 1,
+        }
+        record_push(conn, config.target, push, &result).map_err(|e| e.to_string())?;
+    }
+    Ok(run)
+}
+
+/// Last push of one endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IpamSyncStateRecord {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub ip: String,
+    pub remote_id: Option<String>,
+    pub status: String,
+    pub error: Option<String>,
+    pub attempted_at: i64,
+    pub synced_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IpamSyncStatus {
+    /// None when sync is disabled
+    pub target: Option<IpamTarget>,
+    pub last_attempt_at: Option<i64>,
+    pub synced: usize,
+    pub failed: usize,
+    /// Confirmed endpoints waiting for their first push or an update
+    pub pending: usize,
+    pub endpoints: Vec<IpamSyncStateRecord>,
+}
+
+/// Sync-status report: per-endpoint outcomes, failures first
+pub fn get_ipam_sync_status(conn: &Connection) -> Result<IpamSyncStatus> {
+    let config = get_ipam_sync_config(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT s.endpoint_id, e.display_name, s.ip, s.remote_id, s.status, s.error,
+                s.attempted_at, s.synced_at
+         FROM ipam_sync_state s
+         LEFT JOIN endpoints e ON e.id = s.endpoint_id
+         ORDER BY s.status = 'synced', s.attempted_at DESC",
+    )?;
+    let endpoints = stmt
+        .query_map([], |row| {
+            Ok(IpamSyncStateRecord {
+                endpoint_id: row.get(0)?,
+                endpoint_name: row.get(1)?,
+                ip: row.get(2)?,
+                remote_id: row.get(3)?,
+                status: row.get(4)?,
+                error: row.get(5)?,
+                attempted_at: row.get(6)?,
+                synced_at: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let pending = match &config {
+        Some(config) => {
+            let (pending, _) = get_pending_pushes(conn, config)?;
+            pending
+                .iter()
+                .filter(|push| {
+                    !endpoints.iter().any(|record| {
+                        record.endpoint_id == push.endpoint.endpoint_id && record.status == "failed"
+                    })
+                })
+                .count()
+        }
+        None => 0,
+    };
+    Ok(IpamSyncStatus {
+        target: config.map(|config| config.target),
+        last_attempt_at: endpoints.iter().map(|record| record.attempted_at).max(),
+        synced: endpoints.iter().filter(|r| r.status == "synced").count(),
+        failed: endpoints.iter().filter(|r| r.status == "failed").count(),
+        pending,
+        endpoints,
+    })
+}
+
+/// Start the scheduled sync task. Runs every `ipam_sync_interval_minutes` while a
+/// target is configured; settings are re-read each cycle so changes apply live.
+pub fn start_ipam_sync_scheduler() {
+    if IPAM_SYNC_SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        // Give the endpoint summaries time to materialize before the first run
+        tokio::time::sleep(tokio::time::Duration::from_secs(120)).await;
+
+        loop {
+            let interval_minutes = get_setting_i64("ipam_sync_interval_minutes", 60).max(5) as u64;
+
+            let result = task::spawn_blocking(|| {
+                let conn = new_connection_result().map_err(|e| e.to_string())?;
+                match get_ipam_sync_config(&conn) {
+                    Ok(Some(_)) => run_ipam_sync(&conn).map(Some),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e.to_string()),
+                }
+            })
+            .await;
+
+            match result {
+                Ok(Ok(Some(run))) if run.pushed + run.failed > 0 => println!(
+                    "IPAM sync pushed {} endpoint(s), {} failed",
+                    run.pushed, run.failed
+                ),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("IPAM sync failed: {}", e),
+                Err(e) => eprintln!("IPAM sync task failed: {}", e),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(interval_minutes * 60)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{EndpointSummary, new_test_connection, upsert_endpoint_summaries};
+
+    fn summary(endpoint_id: i64, name: &str, ips: &[&str], macs: &[&str]) -> EndpointSummary {
+        EndpointSummary {
+            endpoint_id,
+            name: name.to_string(),
+            vendor: Some("HP".to_string()),
+            model: None,
+            device_type: Some("printer".to_string()),
+            bytes: 0,
+            last_seen_at: Some(100),
+            ips: ips.iter().map(|ip| ip.to_string()).collect(),
+            macs: macs.iter().map(|mac| mac.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_ipam_sync_pending_and_field_map() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'printer'), (2, 1, 'cloud'), (3, 1, 'tv')",
+            [],
+        )
+        .unwrap();
+        upsert_endpoint_summaries(
+            &conn,
+            &[
+                summary(
+                    1,
+                    "printer",
+                    &["fe80::1", "192.168.1.20"],
+                    &["aa:bb:cc:dd:ee:01"],
+                ),
+                summary(2, "cloud", &["8.8.8.8"], &["aa:bb:cc:dd:ee:02"]),
+                summary(3, "tv", &["192.168.1.30"], &[]),
+            ],
+        )
+        .unwrap();
+
+        let mut config = IpamSyncConfig {
+            target: IpamTarget::PhpIpam,
+            url: "https://ipam.example.com/".to_string(),
+            token: "secret".to_string(),
+            app_id: "awareness".to_string(),
+            subnet_id: "7".to_string(),
+            field_map: BTreeMap::new(),
+        };
+        assert!(config.validate(false).is_ok());
+        set_ipam_sync_config(&conn, &config).unwrap();
+        assert!(has_ipam_sync_token(&conn));
+
+        // Only the printer has both a MAC and a private IPv4 address
+        let (pending, unchanged) = get_pending_pushes(&conn, &config).unwrap();
+        assert_eq!(unchanged, 0);
+        assert_eq!(pending.len(), 1);
+        let fields = &pending[0].fields;
+        assert_eq!(fields["hostname"], "printer");
+        assert_eq!(fields["mac"], "aa:bb:cc:dd:ee:01");
+        assert_eq!(fields["description"], "printer HP");
+
+        record_push(&conn, config.target, &pending[0], &Ok("12".to_string())).unwrap();
+        let (pending, unchanged) = get_pending_pushes(&conn, &config).unwrap();
+        assert!(pending.is_empty());
+        assert_eq!(unchanged, 1);
+
+        // A new field map changes what would be pushed, so the printer is pending again
+        config.token = String::new();
+        config.field_map = BTreeMap::from([
+            ("custom_fields.model".to_string(), "{model}".to_string()),
+            (
+                "custom_fields.vendor".to_string(),
+                "Vendor {vendor}".to_string(),
+            ),
+        ]);
+        set_ipam_sync_config(&conn, &config).unwrap();
+        let stored = get_ipam_sync_config(&conn).unwrap().unwrap();
+        assert_eq!(stored.token, "secret");
+        assert_eq!(stored.url, "https://ipam.example.com");
+        let (pending, _) = get_pending_pushes(&conn, &stored).unwrap();
+        assert_eq!(
+            Value::Object(pending[0].fields.clone()),
+            serde_json::json!({"custom_fields": {"vendor": "Vendor HP"}})
+        );
+
+        let status = get_ipam_sync_status(&conn).unwrap();
+        assert_eq!(status.target, Some(IpamTarget::PhpIpam));
+        assert_eq!((status.synced, status.failed, status.pending), (1, 0, 1));
+        assert_eq!(status.endpoints[0].remote_id.as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn test_ipam_sync_token_not_kept_for_another_host() {
+        let conn = new_test_connection();
+        let mut config = IpamSyncConfig {
+            target: IpamTarget::NetBox,
+            url: "https://netbox.example.com".to_string(),
+            token: "secret".to_string(),
+            app_id: String::new(),
+            subnet_id: String::new(),
+            field_map: BTreeMap::new(),
+        };
+        set_ipam_sync_config(&conn, &config).unwrap();
+
+        config.token = String::new();
+        config.url = "https://netbox.example.com/".to_string();
+        assert!(ipam_sync_token_kept(&conn, &config));
+
+        // Pointing sync elsewhere without a new token drops the old one
+        config.url = "https://attacker.example.net".to_string();
+        assert!(!ipam_sync_token_kept(&conn, &config));
+        assert!(
+            config
+                .validate(ipam_sync_token_kept(&conn, &config))
+                .is_err()
+        );
+        set_ipam_sync_config(&conn, &config).unwrap();
+        let stored = get_ipam_sync_config(&conn).unwrap().unwrap();
+        assert_eq!(stored.token, "");
+        assert!(!has_ipam_sync_token(&conn));
+
+        // Likewise for a change of target at the same URL
+        config.url = "https://netbox.example.com".to_string();
+        config.token = "secret".to_string();
+        set_ipam_sync_config(&conn, &config).unwrap();
+        config.token = String::new();
+        config.target = IpamTarget::PhpIpam;
+        config.app_id = "app".to_string();
+        config.subnet_id = "1".to_string();
+        set_ipam_sync_config(&conn, &config).unwrap();
+        assert!(!has_ipam_sync_token(&conn));
+    }
+}
@@ -0,0 +1,196 @@
+//! Shared in-memory view of endpoint activity. The capture writer records when each
+//! endpoint last had traffic as it inserts communications, so the web layer can answer
+//! "when was this endpoint last seen / is it online" without aggregating the
+//! communications table on every page load. SQLite remains the durable store: the state
+//! is loaded from it on first use and resynced periodically, which also drops endpoints
+//! that were deleted or merged away and picks up traffic written by other processes.
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use rusqlite::{Connection, Result};
+
+/// How long the in-memory state is trusted before it is reconciled with the database
+const LIVE_RESYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct LiveEndpoints {
+    /// endpoint id -> unix time of its latest communication
+    last_seen: HashMap<i64, i64>,
+    synced_at: Option<Instant>,
+}
+
+impl LiveEndpoints {
+    fn record(&mut self, endpoint_ids: &[i64], seen_at: i64) {
+        for &id in endpoint_ids {
+            let last_seen = self.last_seen.entry(id).or_insert(seen_at);
+            *last_seen = (*last_seen).max(seen_at);
+        }
+    }
+
+    fn needs_sync(&self) -> bool {
+        self.synced_at
+            .is_none_or(|synced| synced.elapsed() >= LIVE_RESYNC_INTERVAL)
+    }
+
+    /// Replace the state with `loaded` (read from the database starting at `load_started`,
+    /// a unix time), keeping activity recorded while the load was running
+    fn sync(&mut self, loaded: HashMap<i64, i64>, load_started: i64) {
+        let recorded = std::mem::replace(&mut self.last_seen, loaded);
+        for (id, seen_at) in recorded {
+            if seen_at >= load_started {
+                self.record(&[id], seen_at);
+            } else if let Some(last_seen) = self.last_seen.get_mut(&id) {
+                *last_seen = (*last_seen).max(seen_at);
+            }
+        }
+        self.synced_at = Some(Instant::now());
+    }
+
+    fn seen_since(&self, since: i64) -> HashMap<i64, i64> {
+        self.last_seen
+            .iter()
+            .filter(|(_, seen_at)| **seen_at >= since)
+            .map(|(id, seen_at)| (*id, *seen_at))
+            .collect()
+    }
+}
+
+static LIVE_ENDPOINTS: LazyLock<RwLock<LiveEndpoints>> =
+    LazyLock::new(|| RwLock::new(LiveEndpoints::default()));
+
+/// Record traffic for endpoints (called by the writer for every stored communication)
+pub fn record_endpoint_activity(endpoint_ids: &[i64], seen_at: i64) {
+    if let Ok(mut live) = LIVE_ENDPOINTS.write() {
+        live.record(endpoint_ids, seen_at);
+    }
+}
+
+/// Latest communication time per endpoint, from the communications table
+fn load_endpoint_last_seen(conn: &Connection) -> Result<HashMap<i64, i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT endpoint_id, MAX(last_seen_at) FROM (
+            SELECT src_endpoint_id AS endpoint_id, MAX(last_seen_at) AS last_seen_at
+            FROM communications GROUP BY src_endpoint_id
+            UNION ALL
+            SELECT dst_endpoint_id, MAX(last_seen_at)
+            FROM communications GROUP BY dst_endpoint_id
+         )
+         WHERE endpoint_id IS NOT NULL
+         GROUP BY endpoint_id",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Reconcile the in-memory state with the database if it hasn't been recently
+fn sync_live_endpoints(conn: &Connection) -> Result<()> {
+    if !LIVE_ENDPOINTS
+        .read()
+        .map(|live| live.needs_sync())
+        .unwrap_or(true)
+    {
+        return Ok(());
+    }
+    let load_started = chrono::Utc::now().timestamp();
+    let loaded = load_endpoint_last_seen(conn)?;
+    if let Ok(mut live) = LIVE_ENDPOINTS.write() {
+        live.sync(loaded, load_started);
+    }
+    Ok(())
+}
+
+/// Unix time each endpoint with traffic at or after `since` was last seen, by endpoint id
+pub fn get_endpoint_last_seen(conn: &Connection, since: i64) -> Result<HashMap<i64, i64>> {
+    sync_live_endpoints(conn)?;
+    Ok(LIVE_ENDPOINTS
+        .read()
+        .map(|live| live.seen_since(since))
+        .unwrap_or_default())
+}
+
+/// Display names of endpoints with traffic at or after `since` (unix time), with the
+/// time they were last seen. Served from memory; only the id -> name mapping is read
+/// from the database so renames show up immediately.
+pub fn get_recently_seen_endpoints(conn: &Connection, since: i64) -> Result<Vec<(String, i64)>> {
+    let seen = get_endpoint_last_seen(conn, since)?;
+    if seen.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt =
+        conn.prepare("SELECT id, display_name FROM endpoints WHERE display_name IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+    Ok(rows
+        .flatten()
+        .filter_map(|(id, name)| seen.get(&id).map(|seen_at| (name, *seen_at)))
+        .collect())
+}
+
+/// Addresses of endpoints with traffic at or after `since`, used to point active scans
+/// at hosts passive capture already knows are there
+pub fn get_recently_seen_addresses(conn: &Connection, since: i64) -> Result<HashSet<IpAddr>> {
+    sync_live_endpoints(conn)?;
+    let seen = LIVE_ENDPOINTS
+        .read()
+        .map(|live| live.seen_since(since))
+        .unwrap_or_default();
+    if seen.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let mut stmt = conn.prepare("SELECT DISTINCT endpoint_id, ip FROM endpoint_attributes")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+    Ok(rows
+        .flatten()
+        .filter(|(id, _)| seen.contains_key(id))
+        .filter_map(|(_, ip)| ip.parse().ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_live_endpoints_sync_keeps_newer_activity() {
+        let mut live = LiveEndpoints::default();
+        assert!(live.needs_sync());
+        live.record(&[1, 2, 3], 100);
+        live.record(&[1], 50);
+        assert_eq!(live.seen_since(0).get(&1), Some(&100));
+
+        // Endpoint 2 is gone from the database, 3 was seen again while loading
+        live.record(&[3], 200);
+        live.sync(HashMap::from([(1, 150), (4, 90)]), 180);
+        assert!(!live.needs_sync());
+
+        let seen = live.seen_since(0);
+        assert_eq!(seen.get(&1), Some(&150));
+        assert_eq!(seen.get(&2), None);
+        assert_eq!(seen.get(&3), Some(&200));
+        assert_eq!(seen.get(&4), Some(&90));
+        assert_eq!(live.seen_since(160).len(), 1);
+    }
+
+    #[test]
+    fn test_load_endpoint_last_seen() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'a'), (2, 1, 'b'), (3, 1, 'c');
+             INSERT INTO communications (src_endpoint_id, dst_endpoint_id, created_at, last_seen_at)
+                VALUES (1, 2, 1, 100), (3, 1, 1, 300), (2, 3, 1, 200);",
+        )
+        .unwrap();
+
+        let loaded = load_endpoint_last_seen(&conn).unwrap();
+        assert_eq!(loaded, HashMap::from([(1, 300), (2, 200), (3, 300)]));
+    }
+}
@@ -0,0 +1,281 @@
+//! Index maintenance and query plan review. Secondary indexes for the hot query paths
+//! are declared in one place and created at startup after every table exists; the
+//! analyze report runs ANALYZE and shows where the space goes and whether the hot
+//! queries still hit an index.
+
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+
+/// Indexes for the hot query paths: (name, table, indexed columns or expressions)
+const HOT_PATH_INDEXES: &[(&str, &str, &str)] = &[
+    // Per-endpoint traffic within a time window
+    (
+        "idx_communications_src_last_seen",
+        "communications",
+        "src_endpoint_id, last_seen_at",
+    ),
+    (
+        "idx_communications_dst_last_seen",
+        "communications",
+        "dst_endpoint_id, last_seen_at",
+    ),
+    // MIN(ip)/MIN(mac)/MIN(hostname) per endpoint and identifier lookups
+    (
+        "idx_endpoint_attributes_endpoint_ip",
+        "endpoint_attributes",
+        "endpoint_id, ip",
+    ),
+    (
+        "idx_endpoint_attributes_endpoint_mac",
+        "endpoint_attributes",
+        "endpoint_id, mac",
+    ),
+    (
+        "idx_endpoint_attributes_endpoint_hostname",
+        "endpoint_attributes",
+        "endpoint_id, hostname",
+    ),
+    (
+        "idx_endpoint_attributes_hostname_lower",
+        "endpoint_attributes",
+        "LOWER(hostname)",
+    ),
+    (
+        "idx_endpoint_attributes_ip_lower",
+        "endpoint_attributes",
+        "LOWER(ip)",
+    ),
+    (
+        "idx_endpoints_custom_name_lower",
+        "endpoints",
+        "LOWER(custom_name)",
+    ),
+    // Notification list (undismissed, newest first) and per-endpoint reassignment
+    (
+        "idx_notifications_dismissed_created",
+        "notifications",
+        "dismissed, created_at DESC",
+    ),
+    (
+        "idx_notifications_endpoint_id",
+        "notifications",
+        "endpoint_id",
+    ),
+    // Scan history snapshots and per-endpoint cleanup
+    (
+        "idx_scan_results_endpoint_scanned",
+        "scan_results",
+        "endpoint_id, scanned_at",
+    ),
+    ("idx_scan_results_scanned_at", "scan_results", "scanned_at"),
+    ("idx_hostname_cache_expires", "hostname_cache", "expires_at"),
+];
+
+/// Representative hot queries whose plans the analyze report checks (literal values,
+/// since EXPLAIN needs every parameter bound)
+const HOT_QUERIES: &[(&str, &str)] = &[
+    (
+        "communications in time window",
+        "SELECT src_endpoint_id, dst_endpoint_id, bytes FROM communications
+         WHERE last_seen_at >= 0",
+    ),
+    (
+        "endpoint traffic in time window",
+        "SELECT SUM(bytes) FROM communications
+         WHERE src_endpoint_id = 1 AND last_seen_at >= 0",
+    ),
+    (
+        "endpoint by IP",
+        "SELECT endpoint_id FROM endpoint_attributes WHERE ip = '192.0.2.1'",
+    ),
+    (
+        "endpoint by MAC",
+        "SELECT endpoint_id FROM endpoint_attributes WHERE mac = '00:00:00:00:00:00'",
+    ),
+    (
+        "endpoint by hostname",
+        "SELECT endpoint_id FROM endpoint_attributes WHERE LOWER(hostname) = LOWER('host')",
+    ),
+    (
+        "endpoint first IP",
+        "SELECT MIN(ip) FROM endpoint_attributes WHERE endpoint_id = 1",
+    ),
+    (
+        "recent notifications",
+        "SELECT id FROM notifications WHERE dismissed = 0 ORDER BY created_at DESC LIMIT 50",
+    ),
+    (
+        "endpoint table",
+        "SELECT name FROM endpoint_summary WHERE last_seen_at >= 0",
+    ),
+];
+
+/// Create the hot path indexes. Tables that don't exist (yet) are skipped.
+pub fn create_hot_path_indexes(conn: &Connection) {
+    for (name, table, columns) in HOT_PATH_INDEXES {
+        if let Err(e) = conn.execute(
+            &format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+                name, table, columns
+            ),
+            [],
+        ) {
+            eprintln!("Failed to create index {}: {}", name, e);
+        }
+    }
+}
+
+/// On-disk size of one table or index
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageObject {
+    pub name: String,
+    /// "table" or "index"
+    pub kind: String,
+    pub table: String,
+    pub size_bytes: i64,
+}
+
+/// Query plan of one hot query
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryPlanReport {
+    pub name: String,
+    pub plan: Vec<String>,
+    /// Plan steps that read a whole table without an index
+    pub full_scans: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseReport {
+    pub file_size_bytes: i64,
+    pub free_bytes: i64,
+    pub analyze_ms: u64,
+    /// Tables and indexes, largest first
+    pub objects: Vec<StorageObject>,
+    pub query_plans: Vec<QueryPlanReport>,
+}
+
+fn pragma_i64(conn: &Connection, pragma: &str) -> i64 {
+    conn.query_row(&format!("PRAGMA {}", pragma), [], |row| row.get(0))
+        .unwrap_or(0)
+}
+
+fn storage_objects(conn: &Connection) -> Result<Vec<StorageObject>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.name, m.type, m.tbl_name, COALESCE(SUM(s.pgsize), 0) AS size
+         FROM sqlite_master m
+         LEFT JOIN dbstat s ON s.name = m.name
+         WHERE m.type IN ('table', 'index')
+         GROUP BY m.name
+         ORDER BY size DESC, m.name",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(StorageObject {
+            name: row.get(0)?,
+            kind: row.get(1)?,
+            table: row.get(2)?,
+            size_bytes: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// EXPLAIN QUERY PLAN for `sql`, returning each step's detail text
+pub fn explain_query_plan(conn: &Connection, sql: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(3))?;
+    rows.collect()
+}
+
+/// A plan step that walks a table row by row (SQLite reports index use as
+/// "SEARCH ... USING INDEX" or "SCAN ... USING [COVERING] INDEX")
+fn is_full_scan(detail: &str) -> bool {
+    detail.starts_with("SCAN ") && !detail.contains(" USING ")
+}
+
+/// Run ANALYZE and report storage use and hot query plans
+pub fn analyze_database(conn: &Connection) -> Result<DatabaseReport> {
+    let started = std::time::Instant::now();
+    conn.execute_batch("ANALYZE;")?;
+    let analyze_ms = started.elapsed().as_millis() as u64;
+
+    let page_size = pragma_i64(conn, "page_size");
+    let query_plans = HOT_QUERIES
+        .iter()
+        .filter_map(|(name, sql)| {
+            // Tables created lazily may not exist yet
+            let plan = explain_query_plan(conn, sql).ok()?;
+            let full_scans = plan.iter().filter(|d| is_full_scan(d)).cloned().collect();
+            Some(QueryPlanReport {
+                name: name.to_string(),
+                plan,
+                full_scans,
+            })
+        })
+        .collect();
+
+    Ok(DatabaseReport {
+        file_size_bytes: pragma_i64(conn, "page_count") * page_size,
+        free_bytes: pragma_i64(conn, "freelist_count") * page_size,
+        analyze_ms,
+        objects: storage_objects(conn)?,
+        query_plans,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_hot_queries_use_indexes() {
+        let conn = new_test_connection();
+        create_hot_path_indexes(&conn);
+
+        // Point lookups on the tables the test connection has must not scan
+        for (name, sql) in HOT_QUERIES {
+            if matches!(
+                *name,
+                "endpoint traffic in time window"
+                    | "endpoint by IP"
+                    | "endpoint by MAC"
+                    | "endpoint by hostname"
+                    | "endpoint first IP"
+            ) {
+                let plan = explain_query_plan(&conn, sql).unwrap();
+                assert!(
+                    !plan.iter().any(|d| is_full_scan(d)),
+                    "{}: {:?}",
+                    name,
+                    plan
+                );
+            }
+        }
+
+        let report = analyze_database(&conn).unwrap();
+        assert!(report.file_size_bytes > 0);
+        assert!(
+            report
+                .objects
+                .iter()
+                .any(|o| o.name == "idx_communications_src_last_seen" && o.kind == "index")
+        );
+        assert!(
+            report
+                .query_plans
+                .iter()
+                .any(|p| p.name == "endpoint table")
+        );
+    }
+
+    #[test]
+    fn test_is_full_scan() {
+        assert!(is_full_scan("SCAN communications"));
+        assert!(!is_full_scan(
+            "SCAN notifications USING INDEX idx_notifications_created"
+        ));
+        assert!(!is_full_scan(
+            "SEARCH endpoint_attributes USING INDEX idx_endpoint_attributes_ip (ip=?)"
+        ));
+    }
+}
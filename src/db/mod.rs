@@ -1,24 +1,256 @@
 //! Database module. Manages SQLite connections, schema creation, endpoint and
 //! communication storage, settings persistence, and WAL file cleanup.
 
-use rusqlite::Connection;
+mod activity;
+mod appearance;
+mod backup;
+mod bandwidth_quotas;
+mod bulk_edit;
+mod capture_exclusions;
+mod capture_schedule;
+mod classification_feedback;
+mod device_macros;
+mod endpoint_links;
+mod endpoint_summary;
+mod endpoint_tags;
+mod energy;
+mod graph_export;
+mod host_keys;
+mod ipam_sync;
+mod live_endpoints;
+mod maintenance;
+mod people;
+mod port_policies;
+mod presence;
+mod printer_supplies;
+mod query_console;
+mod saved_views;
+mod scan_results;
+mod scan_runs;
+mod settings_schema;
+mod smb_shares;
+mod snmp_credentials;
+mod storage_health;
+mod subnets;
+mod system_stats;
+mod timeline;
+mod timeseries;
+mod traffic_matrix;
+mod traffic_purge;
+mod type_baselines;
+mod upnp_events;
+mod upnp_mappings;
+mod ups_monitoring;
+mod usage;
+mod users;
+mod web_info;
+mod webhooks;
+
+pub use activity::{ActivityEvent, get_activity, record_activity_event};
+pub use appearance::{
+    EndpointAppearance, TypeAppearance, get_endpoint_appearances, get_type_appearances,
+    is_known_device_type, parse_color, parse_icon, reassign_endpoint_appearance,
+    set_endpoint_appearance, set_type_appearance,
+};
+pub use backup::{
+    MAX_BACKUP_BYTES, backup_file_name, create_backup_bytes, restore_backup, start_backup_scheduler,
+};
+pub use bandwidth_quotas::{
+    NewBandwidthQuota, delete_bandwidth_quota, get_bandwidth_quotas, get_exceeded_quotas,
+    reassign_bandwidth_quotas, set_bandwidth_quota,
+};
+pub use bulk_edit::{BulkEndpointEdit, apply_bulk_edit};
+pub use capture_exclusions::{
+    CaptureExclusion, exclude_endpoint, exclude_mac, get_capture_exclusions, include_endpoint,
+    include_mac, is_capture_excluded, reassign_capture_exclusions,
+};
+pub use capture_schedule::{
+    CaptureSchedule, get_capture_schedule, is_capture_scheduled_off, set_capture_schedule,
+    start_capture_scheduler,
+};
+pub use classification_feedback::{
+    FeedbackKind, export_rule_suggestions, get_rule_suggestions, reassign_classification_feedback,
+    record_classification_feedback,
+};
+pub use device_macros::{
+    NewDeviceMacro, delete_device_macro, get_device_macro, get_device_macros, save_device_macro,
+};
+pub use endpoint_links::{
+    get_link_suggestions, get_linked_endpoint_ids, link_endpoints, reassign_endpoint_links,
+    unlink_endpoint,
+};
+pub use endpoint_summary::{
+    EndpointSummary, create_endpoint_summary_table, delete_endpoint_summaries,
+    get_endpoint_summaries, get_stale_endpoint_summaries, has_endpoint_summaries,
+    mark_endpoint_summary_dirty, replace_endpoint_summaries, take_dirty_endpoint_summaries,
+    upsert_endpoint_summaries,
+};
+pub use endpoint_tags::{
+    add_endpoint_tag, endpoint_ids_with_tags, get_endpoint_tags, get_tag_counts,
+    is_endpoint_trusted, parse_tag, parse_tags, reassign_endpoint_tags, remove_endpoint_tag,
+    set_endpoint_tags, set_endpoint_trusted,
+};
+pub use energy::{
+    EnergyReport, MAX_WATTS, TypePower, get_endpoint_power, get_energy_report, get_type_power,
+    reassign_energy, rollup_energy, set_endpoint_power, set_type_power,
+};
+pub use graph_export::{
+    GraphFilter, GraphFormat, get_communication_graph, to_cytoscape_json, to_dot, to_graphml,
+};
+pub use host_keys::{
+    get_endpoint_host_keys, get_host_key_targets, get_tls_certificates, reassign_host_keys,
+    store_host_keys,
+};
+pub use ipam_sync::{
+    IpamSyncConfig, clear_ipam_sync_config, get_ipam_sync_config, get_ipam_sync_status,
+    has_ipam_sync_token, ipam_sync_token_kept, run_ipam_sync, set_ipam_sync_config,
+};
+pub use live_endpoints::{
+    get_endpoint_last_seen, get_recently_seen_addresses, get_recently_seen_endpoints,
+    record_endpoint_activity,
+};
+pub use maintenance::{DatabaseReport, analyze_database};
+pub use people::{
+    Person, PersonSummary, create_person, delete_person, get_people, get_person_summary,
+    parse_person_name, reassign_endpoint_owner, set_endpoint_owner,
+};
+pub use port_policies::{
+    DriftSource, NewPortPolicy, check_port_policy, delete_port_policy, get_port_drift,
+    get_port_policies, reassign_port_policies, served_port, set_port_policy,
+};
+pub use printer_supplies::{PrinterReport, get_printer_report, reassign_printer_readings};
+pub use query_console::{
+    DEFAULT_QUERY_ROWS, QueryRequest, QueryResult, new_read_only_connection, run_read_only_query,
+};
+pub use saved_views::{
+    SavedView, delete_view, get_default_view_query, get_saved_views, normalize_view_query,
+    parse_view_name, save_view, set_default_view, update_view,
+};
+pub use scan_results::get_scan_results;
+pub use scan_runs::{
+    ScanChangeset, ScanRun, diff_scan_runs, get_previous_scan_run_id, get_scan_run,
+    get_scan_run_id_before, get_scan_runs, record_scan_run,
+};
+pub use settings_schema::{SETTINGS, SettingDefinition, validate_setting};
+pub use smb_shares::{get_smb_shares, reassign_smb_shares, replace_smb_shares};
+pub use snmp_credentials::{
+    has_snmp_credential, load_snmp_credentials, reassign_snmp_credential, set_snmp_credential,
+};
+pub use storage_health::{
+    StorageReport, get_storage_overview, get_storage_report, reassign_storage_health,
+};
+pub use subnets::{MAX_GRID_ADDRESSES, get_subnet_summaries, load_address_holders, subnet_grid};
+pub use system_stats::{SystemStats, get_system_stats};
+pub use timeline::get_endpoint_timeline;
+pub use timeseries::{MAX_TIMESERIES_RANGE_SECS, TimeSeriesMetric, get_time_series};
+pub use traffic_matrix::build_traffic_matrix;
+pub use traffic_purge::purge_endpoint_traffic;
+pub use type_baselines::{
+    TypeBaselineRequest, check_type_baseline, delete_type_baseline, destination_type,
+    get_baseline_behaviors, get_baseline_deviations, get_type_baselines,
+    reassign_type_baseline_deviations, set_type_baseline_mode,
+};
+pub use upnp_events::{
+    UpnpEventSource, UpnpStateVariable, UpnpSubscription, delete_upnp_subscription,
+    find_upnp_subscription, get_expiring_upnp_subscriptions, get_unsubscribed_upnp_devices,
+    get_upnp_device_state, reassign_upnp_events, record_upnp_event, save_upnp_subscription,
+};
+pub use upnp_mappings::{get_port_mappings, record_port_mappings};
+pub use ups_monitoring::{UpsReport, get_endpoint_ups, get_ups_overview, reassign_ups_readings};
+pub use usage::{
+    get_internet_summary, get_tcp_quality_report, get_usage_report, get_wan_bytes, reassign_usage,
+    record_tcp_quality, record_usage, record_wan_bytes,
+};
+pub use users::{
+    Role, User, admins_remain_after, delete_user, get_user_preferences, get_users,
+    is_preference_key, parse_preference, resolve_role, set_user, set_user_preference,
+    set_user_token, user_for_token,
+};
+pub use web_info::{
+    get_web_favicon, get_web_info_summaries, get_web_info_targets, reassign_web_info,
+    store_web_info,
+};
+pub use webhooks::{
+    NewWebhook, WEBHOOK_EVENTS, create_webhook, delete_webhook, get_webhooks, queue_webhook_event,
+    reassign_webhook_endpoints,
+};
+
+use rusqlite::{Connection, OpenFlags, Transaction, TransactionBehavior};
+use serde::Serialize;
 use tokio::{sync::mpsc, task};
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::Path;
 use std::sync::OnceLock;
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::health::{record_activity, record_error};
+use crate::network::blocklist::create_blocklist_tables;
+use crate::network::broadcast::create_broadcast_traffic_table;
 use crate::network::communication::Communication;
-use crate::network::endpoint::EndPoint;
+use crate::network::dns_audit::create_dns_server_usage_table;
+use crate::network::eapol::create_auth_events_table;
+use crate::network::endpoint::{EndPoint, start_runtime_rules_watcher};
 use crate::network::endpoint_attribute::EndPointAttribute;
+use crate::network::flow::{CaptureMode, FlowAggregator};
+use crate::network::hostname_resolver::HostnameResolver;
+use crate::network::name_poisoning::create_name_responses_table;
+use crate::network::passive_dns::create_dns_entries_table;
+use crate::network::privacy::privacy_mode_enabled;
+use crate::network::router_advertisement::create_router_advertisement_tables;
+use crate::network::wireless::create_wireless_stations_table;
+use activity::{create_activity_table, purge_activity_events};
+use appearance::create_appearance_tables;
+use bandwidth_quotas::{create_bandwidth_quota_tables, start_bandwidth_quota_checker};
+use capture_exclusions::{
+    create_capture_exclusions_table, is_excluded_communication, load_excluded_macs,
+};
+use classification_feedback::create_classification_feedback_table;
+use device_macros::create_device_macros_table;
+use endpoint_links::create_endpoint_links_table;
+use endpoint_tags::create_endpoint_tag_tables;
+use energy::{create_energy_tables, start_energy_rollup};
+use host_keys::create_host_key_tables;
+use ipam_sync::{create_ipam_sync_tables, start_ipam_sync_scheduler};
+use people::create_people_tables;
+use port_policies::create_port_policies_tables;
+use presence::{create_presence_table, start_presence_checker};
+use printer_supplies::{create_printer_tables, start_printer_poller};
+use saved_views::create_saved_views_table;
+use settings_schema::insert_default_settings;
+use smb_shares::create_smb_shares_table;
+use snmp_credentials::create_snmp_credentials_table;
+use storage_health::{create_storage_health_tables, start_storage_health_poller};
+use type_baselines::create_type_baseline_tables;
+use upnp_events::create_upnp_events_tables;
+use upnp_mappings::create_upnp_port_mappings_table;
+use ups_monitoring::{create_ups_tables, start_ups_poller};
+use usage::create_usage_tables;
+use users::create_users_tables;
+use web_info::create_web_info_table;
+use webhooks::{create_webhook_tables, start_webhook_delivery};
 
 const MAX_CHANNEL_BUFFER_SIZE: usize = 50_000; // ~25MB at 500 bytes per Communication
 
 /// Flag to ensure WAL cleanup only runs once at startup
 static WAL_CLEANUP_DONE: AtomicBool = AtomicBool::new(false);
 
+/// Set when this process serves a database another process captures into
+static READ_REPLICA: AtomicBool = AtomicBool::new(false);
+
+/// Open every connection read-only, for a web UI running as a separate process beside the
+/// capture writer. Call at startup, before any connection is opened.
+pub fn set_read_replica(enabled: bool) {
+    READ_REPLICA.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether this process is a read-only replica (see `set_read_replica`)
+pub fn is_read_replica() -> bool {
+    READ_REPLICA.load(Ordering::Relaxed)
+}
+
 /// Attempt to clean up stale WAL and SHM files from a previous crash.
 /// This is especially important on Windows where file locking is stricter.
 /// Only runs once at startup - subsequent calls are no-ops.
@@ -161,6 +393,10 @@ pub fn new_connection_result() -> Result<Connection, rusqlite::Error> {
     let db_url = get_database_url();
     let db_path = db_url.strip_prefix("sqlite://").unwrap_or(&db_url);
 
+    if is_read_replica() {
+        return open_replica_connection(db_path);
+    }
+
     // Attempt to clean up stale WAL/SHM files from previous crashes
     // This is especially important on Windows where file locking is stricter
     cleanup_stale_wal_files(db_path);
@@ -203,6 +439,86 @@ pub fn new_connection_result() -> Result<Connection, rusqlite::Error> {
     Err(e)
 }
 
+/// A read-only connection for replica mode. The WAL belongs to the capture process, so it
+/// is neither cleaned up nor switched on here; reads still see every committed write.
+fn open_replica_connection(db_path: &str) -> Result<Connection, rusqlite::Error> {
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(std::time::Duration::from_secs(30))?;
+    Ok(conn)
+}
+
+/// Create the notifications table, adding columns that older databases lack
+fn create_notifications_table(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notifications (
+            id INTEGER PRIMARY KEY,
+            created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+            event_type TEXT NOT NULL,
+            title TEXT NOT NULL,
+            details TEXT,
+            endpoint_name TEXT,
+            endpoint_id INTEGER,
+            dismissed INTEGER NOT NULL DEFAULT 0,
+            severity TEXT NOT NULL DEFAULT 'info',
+            acknowledged_at INTEGER,
+            acknowledged_by TEXT,
+            dedup_key TEXT,
+            occurrences INTEGER NOT NULL DEFAULT 1,
+            last_occurred_at INTEGER
+        )",
+        [],
+    )?;
+
+    // Add endpoint_id column if it doesn't exist (migration for existing databases)
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN endpoint_id INTEGER",
+        [],
+    );
+
+    // Add severity and acknowledgement columns (migration for existing databases)
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN severity TEXT NOT NULL DEFAULT 'info'",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN acknowledged_at INTEGER",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN acknowledged_by TEXT",
+        [],
+    );
+
+    // Add dedup columns: repeats within the cooldown bump occurrences instead of
+    // inserting new rows (migration for existing databases)
+    let _ = conn.execute("ALTER TABLE notifications ADD COLUMN dedup_key TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN occurrences INTEGER NOT NULL DEFAULT 1",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE notifications ADD COLUMN last_occurred_at INTEGER",
+        [],
+    );
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_notifications_severity ON notifications(severity, created_at DESC)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_notifications_created ON notifications(created_at DESC)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_notifications_dedup ON notifications(dedup_key, dismissed)",
+        [],
+    )?;
+    Ok(())
+}
+
 /// Fire-and-forget helper to insert a notification. Errors are logged, never propagated.
 pub fn insert_notification(
     conn: &Connection,
@@ -223,14 +539,270 @@ pub fn insert_notification_with_endpoint_id(
     endpoint_name: Option<&str>,
     endpoint_id: Option<i64>,
 ) {
+    insert_notification_with_severity(
+        conn,
+        event_type,
+        notification_severity(event_type),
+        title,
+        details,
+        endpoint_name,
+        endpoint_id,
+    );
+}
+
+/// Fire-and-forget helper to insert a notification with an explicit severity,
+/// overriding the default derived from the event type.
+pub fn insert_notification_with_severity(
+    conn: &Connection,
+    event_type: &str,
+    severity: &str,
+    title: &str,
+    details: Option<&str>,
+    endpoint_name: Option<&str>,
+    endpoint_id: Option<i64>,
+) {
+    let dedup_key = notification_dedup_key(event_type, endpoint_id, endpoint_name, title, details);
+
+    // Collapse a repeat of a recent, still-unread notification into its occurrence count.
+    // Once acknowledged, a repeat is raised as a new notification so it shows as unread.
+    let cooldown: i64 = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'notification_dedup_cooldown_seconds'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_NOTIFICATION_DEDUP_COOLDOWN_SECS);
+    if cooldown > 0 {
+        match conn.execute(
+            "UPDATE notifications
+             SET occurrences = occurrences + 1, last_occurred_at = strftime('%s', 'now')
+             WHERE id = (
+                 SELECT id FROM notifications
+                 WHERE dedup_key = ?1 AND dismissed = 0 AND acknowledged_at IS NULL
+                   AND COALESCE(last_occurred_at, created_at) >= strftime('%s', 'now') - ?2
+                 ORDER BY id DESC LIMIT 1
+             )",
+            rusqlite::params![dedup_key, cooldown],
+        ) {
+            Ok(0) => {}
+            Ok(_) => return,
+            Err(e) => eprintln!("Failed to collapse notification: {}", e),
+        }
+    }
+
     if let Err(e) = conn.execute(
-        "INSERT INTO notifications (event_type, title, details, endpoint_name, endpoint_id) VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![event_type, title, details, endpoint_name, endpoint_id],
+        "INSERT INTO notifications (event_type, severity, title, details, endpoint_name, endpoint_id, dedup_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            event_type,
+            severity,
+            title,
+            details,
+            endpoint_name,
+            endpoint_id,
+            dedup_key
+        ],
     ) {
         eprintln!("Failed to insert notification: {}", e);
     }
 }
 
+/// Repeats of a notification within this many seconds of the last occurrence are collapsed
+const DEFAULT_NOTIFICATION_DEDUP_COOLDOWN_SECS: i64 = 3600;
+
+/// Key identifying "the same" notification: event type, endpoint (id, else name) and a
+/// hash of the payload (title and details). FNV-1a keeps it stable across builds.
+fn notification_dedup_key(
+    event_type: &str,
+    endpoint_id: Option<i64>,
+    endpoint_name: Option<&str>,
+    title: &str,
+    details: Option<&str>,
+) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in title
+        .bytes()
+        .chain(std::iter::once(0))
+        .chain(details.unwrap_or("").bytes())
+    {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    let endpoint = match endpoint_id {
+        Some(id) => format!("#{}", id),
+        None => endpoint_name.unwrap_or("").to_lowercase(),
+    };
+    format!("{}|{}|{:016x}", event_type, endpoint, hash)
+}
+
+/// Notification severity levels, ordered from least to most severe
+pub const NOTIFICATION_SEVERITIES: &[&str] = &["info", "warning", "critical"];
+
+/// Map a notification event type to its default severity.
+/// Routine identification events stay at "info" so they don't bury anything
+/// that needs attention; a previously unseen device joining the network is a "warning",
+/// as is a share anyone can read without credentials or a port a device forwarded
+/// through the gateway with UPnP, any service answering on the WAN address, a
+/// device sending its DNS queries past the local resolver, a device contacting a
+/// blocklisted destination (raised as "critical" for malware lists), an SSH host key or
+/// TLS certificate key that changed unexpectedly, a certificate close to expiry, a port
+/// open outside the endpoint's expected-port policy, and a gateway reporting over UPnP
+/// that its WAN connection went down, a printer running low on toner or ink, a NAS
+/// array that is degraded or failed, and a UPS switching to battery.
+/// An IPv6 router advertisement from an unexpected router is "critical": it can
+/// redirect every IPv6-capable device's traffic. So is a device still accepting its
+/// factory login, which anyone on the network could use to take it over, a host
+/// answering LLMNR/NBNS queries for names that aren't its own, and a deauthentication
+/// flood, and a UPS running out of battery, since whatever it powers is about to go
+/// down; a station repeatedly failing Wi-Fi/802.1X authentication is a "warning".
+pub fn notification_severity(event_type: &str) -> &'static str {
+    match event_type {
+        "rogue_router"
+        | "default_credentials"
+        | "name_poisoning"
+        | "deauth_flood"
+        | "ups_low_battery" => "critical",
+        "endpoint_discovered"
+        | "port_opened"
+        | "port_drift"
+        | "guest_share"
+        | "upnp_port_mapping"
+        | "wan_exposure"
+        | "dns_bypass"
+        | "blocklist_match"
+        | "host_key_changed"
+        | "certificate_expiring"
+        | "task_failed"
+        | "auth_failures"
+        | "wireless_client_discovered"
+        | "upnp_wan_disconnected"
+        | "quota_exceeded"
+        | "baseline_deviation"
+        | "printer_supply_low"
+        | "storage_degraded"
+        | "ups_on_battery" => "warning",
+        _ => "info",
+    }
+}
+
+/// Unread notification counts, enough to draw the notification badge without fetching
+/// any notifications
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NotificationSummary {
+    /// Latest activity counted, or the caller's `since` when there is none. Passing it
+    /// back as `since` counts only what arrives afterwards.
+    pub watermark: i64,
+    pub unread: i64,
+    /// Every severity level, including those with nothing unread
+    pub by_severity: BTreeMap<String, i64>,
+    pub by_event_type: BTreeMap<String, i64>,
+}
+
+/// Count notifications that are neither dismissed nor acknowledged and were raised, or
+/// repeated, after `since`
+pub fn notification_summary(
+    conn: &Connection,
+    since: i64,
+) -> rusqlite::Result<NotificationSummary> {
+    let mut summary = NotificationSummary {
+        watermark: since,
+        by_severity: NOTIFICATION_SEVERITIES
+            .iter()
+            .map(|severity| (severity.to_string(), 0))
+            .collect(),
+        ..Default::default()
+    };
+    let mut stmt = conn.prepare(
+        "SELECT severity, event_type, COUNT(*), MAX(COALESCE(last_occurred_at, created_at))
+         FROM notifications
+         WHERE dismissed = 0 AND acknowledged_at IS NULL
+           AND COALESCE(last_occurred_at, created_at) > ?1
+         GROUP BY severity, event_type",
+    )?;
+    let rows = stmt.query_map([since], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+    for row in rows {
+        let (severity, event_type, count, latest) = row?;
+        summary.unread += count;
+        summary.watermark = summary.watermark.max(latest);
+        *summary.by_severity.entry(severity).or_default() += count;
+        *summary.by_event_type.entry(event_type).or_default() += count;
+    }
+    Ok(summary)
+}
+
+/// Acknowledge notifications by id, leaving ones already acknowledged as they were.
+/// Returns how many were acknowledged.
+pub fn mark_notifications_acknowledged(
+    conn: &Connection,
+    ids: &[i64],
+    acknowledged_by: &str,
+) -> rusqlite::Result<usize> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let sql = format!(
+        "UPDATE notifications SET acknowledged_at = strftime('%s', 'now'), acknowledged_by = ?
+         WHERE acknowledged_at IS NULL AND id IN ({})",
+        vec!["?"; ids.len()].join(", ")
+    );
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&acknowledged_by];
+    params.extend(ids.iter().map(|id| id as &dyn rusqlite::ToSql));
+    conn.execute(&sql, params.as_slice())
+}
+
+/// Acknowledge every visible, unacknowledged notification, or only those at the given
+/// severities when any are given. Returns how many were acknowledged.
+pub fn mark_all_notifications_acknowledged(
+    conn: &Connection,
+    severities: &[String],
+    acknowledged_by: &str,
+) -> rusqlite::Result<usize> {
+    let mut sql =
+        "UPDATE notifications SET acknowledged_at = strftime('%s', 'now'), acknowledged_by = ?
+         WHERE acknowledged_at IS NULL AND dismissed = 0"
+            .to_string();
+    if !severities.is_empty() {
+        sql.push_str(&format!(
+            " AND severity IN ({})",
+            vec!["?"; severities.len()].join(", ")
+        ));
+    }
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&acknowledged_by];
+    params.extend(severities.iter().map(|s| s as &dyn rusqlite::ToSql));
+    conn.execute(&sql, params.as_slice())
+}
+
+/// Fire-and-forget helper to record a user-initiated action in the audit log.
+/// Errors are logged, never propagated.
+#[allow(clippy::too_many_arguments)]
+pub fn insert_audit_log(
+    conn: &Connection,
+    action: &str,
+    method: &str,
+    path: &str,
+    status: Option<u16>,
+    source_ip: Option<&str>,
+    actor: Option<&str>,
+    details: Option<&str>,
+) {
+    if let Err(e) = conn.execute(
+        "INSERT INTO audit_log (action, method, path, status, source_ip, actor, details)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![action, method, path, status, source_ip, actor, details],
+    ) {
+        eprintln!("Failed to insert audit log entry: {}", e);
+    }
+}
+
 /// Get a setting value from the database
 pub fn get_setting(key: &str) -> Option<String> {
     let conn = new_connection();
@@ -251,8 +823,10 @@ pub fn get_setting_i64(key: &str, default: i64) -> i64 {
 pub fn set_setting(key: &str, value: &str) -> Result<(), rusqlite::Error> {
     let conn = new_connection();
     conn.execute(
-        "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, strftime('%s', 'now'))
-         ON CONFLICT(key) DO UPDATE SET value = ?2, updated_at = strftime('%s', 'now')",
+        "INSERT INTO settings (key, value, updated_at, source)
+         VALUES (?1, ?2, strftime('%s', 'now'), 'database')
+         ON CONFLICT(key) DO UPDATE SET value = ?2, updated_at = strftime('%s', 'now'),
+             source = 'database'",
         rusqlite::params![key, value],
     )?;
     Ok(())
@@ -287,8 +861,63 @@ pub fn new_test_connection() -> Connection {
     EndPoint::create_table_if_not_exists(&conn).expect("Failed to create endpoints table");
     EndPointAttribute::create_table_if_not_exists(&conn)
         .expect("Failed to create endpoint_attributes table");
+    EndPoint::install_display_name_triggers(&conn)
+        .expect("Failed to install display name triggers");
     Communication::create_table_if_not_exists(&conn)
         .expect("Failed to create communications table");
+    create_broadcast_traffic_table(&conn).expect("Failed to create broadcast_traffic table");
+    create_dns_entries_table(&conn).expect("Failed to create dns_entries table");
+    create_name_responses_table(&conn).expect("Failed to create name_responses table");
+    create_dns_server_usage_table(&conn).expect("Failed to create dns_server_usage table");
+    create_blocklist_tables(&conn).expect("Failed to create blocklist tables");
+    create_router_advertisement_tables(&conn).expect("Failed to create IPv6 router tables");
+    create_wireless_stations_table(&conn).expect("Failed to create wireless_stations table");
+    create_auth_events_table(&conn).expect("Failed to create auth_events table");
+    create_snmp_credentials_table(&conn).expect("Failed to create snmp_credentials table");
+    create_capture_exclusions_table(&conn).expect("Failed to create capture_exclusions table");
+    create_smb_shares_table(&conn).expect("Failed to create smb_shares table");
+    create_upnp_port_mappings_table(&conn).expect("Failed to create upnp_port_mappings table");
+    create_web_info_table(&conn).expect("Failed to create endpoint_web_info table");
+    create_host_key_tables(&conn).expect("Failed to create host key tables");
+    create_ipam_sync_tables(&conn).expect("Failed to create IPAM sync tables");
+    create_appearance_tables(&conn).expect("Failed to create appearance tables");
+    create_saved_views_table(&conn).expect("Failed to create saved_views table");
+    create_users_tables(&conn).expect("Failed to create users tables");
+    create_classification_feedback_table(&conn)
+        .expect("Failed to create classification_feedback table");
+    create_usage_tables(&conn).expect("Failed to create usage tables");
+    create_webhook_tables(&conn).expect("Failed to create webhook tables");
+    create_port_policies_tables(&conn).expect("Failed to create port policy tables");
+    create_bandwidth_quota_tables(&conn).expect("Failed to create bandwidth quota tables");
+    create_type_baseline_tables(&conn).expect("Failed to create type baseline tables");
+    create_upnp_events_tables(&conn).expect("Failed to create UPnP event tables");
+    create_device_macros_table(&conn).expect("Failed to create device macros table");
+    create_endpoint_links_table(&conn).expect("Failed to create endpoint links table");
+    create_endpoint_tag_tables(&conn).expect("Failed to create endpoint tag tables");
+    create_printer_tables(&conn).expect("Failed to create printer tables");
+    create_storage_health_tables(&conn).expect("Failed to create storage health tables");
+    create_ups_tables(&conn).expect("Failed to create UPS tables");
+    create_energy_tables(&conn).expect("Failed to create energy tables");
+    create_people_tables(&conn).expect("Failed to create people tables");
+    create_presence_table(&conn).expect("Failed to create person_presence table");
+    create_activity_table(&conn).expect("Failed to create activity_events table");
+    create_notifications_table(&conn).expect("Failed to create notifications table");
+    endpoint_summary::create_endpoint_summary_table(&conn)
+        .expect("Failed to create endpoint_summary table");
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS open_ports (
+            id INTEGER PRIMARY KEY,
+            endpoint_id INTEGER NOT NULL,
+            port INTEGER NOT NULL,
+            protocol TEXT DEFAULT 'tcp',
+            service_name TEXT,
+            last_seen_at INTEGER NOT NULL,
+            FOREIGN KEY (endpoint_id) REFERENCES endpoints(id),
+            UNIQUE(endpoint_id, port, protocol)
+        )",
+        [],
+    )
+    .expect("Failed to create open_ports table");
 
     conn
 }
@@ -300,6 +929,80 @@ fn get_channel_buffer_size() -> usize {
         .unwrap_or(MAX_CHANNEL_BUFFER_SIZE) // Default value if env var is not set or invalid
 }
 
+/// The writer's queue, held weakly so health checks don't keep it open
+static WRITER_QUEUE: OnceLock<mpsc::WeakSender<Communication>> = OnceLock::new();
+
+/// Work run on the SQL writer's connection between communication batches
+type WriterJob = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// Jobs for the SQL writer
+static WRITER_JOBS: OnceLock<mpsc::Sender<WriterJob>> = OnceLock::new();
+
+/// Most attempts at taking the write lock for a writer job
+const WRITER_JOB_ATTEMPTS: u64 = 10;
+
+/// Run `job` in one IMMEDIATE transaction, retrying while another connection holds the
+/// write lock. Nothing is committed when the job fails.
+fn run_in_transaction<T>(
+    conn: &mut Connection,
+    job: impl FnOnce(&Transaction) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    let mut attempt = 1;
+    let tx = loop {
+        match conn.transaction_with_behavior(TransactionBehavior::Immediate) {
+            Ok(tx) => break tx,
+            Err(e) if SQLWriter::is_lock_error(&e) && attempt < WRITER_JOB_ATTEMPTS => {
+                std::thread::sleep(SQLWriter::backoff_delay(attempt));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    let value = job(&tx)?;
+    tx.commit()?;
+    Ok(value)
+}
+
+/// Run `job` on the SQL writer's connection inside one transaction, so bursts of writes
+/// from elsewhere (scan results) take turns with captured traffic instead of competing
+/// for the write lock. Without a running writer the job gets a connection of its own.
+pub async fn run_in_writer<T, F>(job: F) -> rusqlite::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Transaction) -> rusqlite::Result<T> + Send + 'static,
+{
+    let (done, result) = tokio::sync::oneshot::channel();
+    let job: WriterJob = Box::new(move |conn| {
+        let _ = done.send(run_in_transaction(conn, job));
+    });
+    let job = match WRITER_JOBS.get() {
+        Some(jobs) => jobs.send(job).await.err().map(|e| e.0),
+        None => Some(job),
+    };
+    if let Some(job) = job {
+        task::spawn_blocking(move || match new_connection_result() {
+            Ok(mut conn) => job(&mut conn),
+            Err(e) => eprintln!("Failed to open database for a writer job: {}", e),
+        });
+    }
+    result
+        .await
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
+}
+
+/// Communications waiting for the SQL writer and the queue's capacity, or None once the
+/// writer has stopped
+pub fn writer_queue() -> Option<(usize, usize)> {
+    let sender = WRITER_QUEUE.get()?.upgrade()?;
+    if sender.is_closed() {
+        return None;
+    }
+    Some((
+        sender.max_capacity() - sender.capacity(),
+        sender.max_capacity(),
+    ))
+}
+
 static RESOLVED_DB_PATH: OnceLock<String> = OnceLock::new();
 
 fn get_database_url() -> String {
@@ -330,6 +1033,9 @@ pub struct SQLWriter {
 impl SQLWriter {
     pub async fn new() -> Self {
         let (tx, mut rx) = mpsc::channel::<Communication>(get_channel_buffer_size());
+        let _ = WRITER_QUEUE.set(tx.downgrade());
+        let (jobs_tx, mut jobs) = mpsc::channel::<WriterJob>(16);
+        let _ = WRITER_JOBS.set(jobs_tx);
         println!(
             "SQL Writer started, connecting to database at {}",
             get_database_url()
@@ -346,8 +1052,20 @@ impl SQLWriter {
                 .expect("Failed to create table if not exists");
             EndPointAttribute::create_table_if_not_exists(&conn)
                 .expect("Failed to create table if not exists");
+            EndPoint::install_display_name_triggers(&conn)
+                .expect("Failed to install display name triggers");
             Communication::create_table_if_not_exists(&conn)
                 .expect("Failed to create table if not exists");
+            create_broadcast_traffic_table(&conn)
+                .expect("Failed to create broadcast_traffic table");
+            create_dns_entries_table(&conn).expect("Failed to create dns_entries table");
+            create_name_responses_table(&conn).expect("Failed to create name_responses table");
+            create_dns_server_usage_table(&conn).expect("Failed to create dns_server_usage table");
+            create_blocklist_tables(&conn).expect("Failed to create blocklist tables");
+            create_router_advertisement_tables(&conn).expect("Failed to create IPv6 router tables");
+            create_wireless_stations_table(&conn)
+                .expect("Failed to create wireless_stations table");
+            create_auth_events_table(&conn).expect("Failed to create auth_events table");
 
             // Create scanner-related tables at startup to avoid schema locks during scanning
             conn.execute(
@@ -364,6 +1082,52 @@ impl SQLWriter {
             )
             .expect("Failed to create scan_results table");
 
+            // Migration: record the IP each scan result was keyed by, so results can be
+            // reassigned when an address moves to another device
+            let _ = conn.execute("ALTER TABLE scan_results ADD COLUMN ip TEXT", []);
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_scan_results_ip ON scan_results (ip, scanned_at)",
+                [],
+            )
+            .expect("Failed to create scan_results ip index");
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_scan_results_endpoint
+                 ON scan_results (endpoint_id, scanned_at)",
+                [],
+            )
+            .expect("Failed to create scan_results endpoint index");
+
+            create_snmp_credentials_table(&conn).expect("Failed to create snmp_credentials table");
+            create_capture_exclusions_table(&conn)
+                .expect("Failed to create capture_exclusions table");
+            create_smb_shares_table(&conn).expect("Failed to create smb_shares table");
+            create_upnp_port_mappings_table(&conn)
+                .expect("Failed to create upnp_port_mappings table");
+            create_web_info_table(&conn).expect("Failed to create endpoint_web_info table");
+            create_host_key_tables(&conn).expect("Failed to create host key tables");
+            create_ipam_sync_tables(&conn).expect("Failed to create IPAM sync tables");
+            create_appearance_tables(&conn).expect("Failed to create appearance tables");
+            create_saved_views_table(&conn).expect("Failed to create saved_views table");
+            create_users_tables(&conn).expect("Failed to create users tables");
+            create_classification_feedback_table(&conn)
+                .expect("Failed to create classification_feedback table");
+            create_usage_tables(&conn).expect("Failed to create usage tables");
+            create_webhook_tables(&conn).expect("Failed to create webhook tables");
+            create_port_policies_tables(&conn).expect("Failed to create port policy tables");
+            create_bandwidth_quota_tables(&conn).expect("Failed to create bandwidth quota tables");
+            create_type_baseline_tables(&conn).expect("Failed to create type baseline tables");
+            create_upnp_events_tables(&conn).expect("Failed to create UPnP event tables");
+            create_device_macros_table(&conn).expect("Failed to create device macros table");
+            create_endpoint_links_table(&conn).expect("Failed to create endpoint links table");
+            create_endpoint_tag_tables(&conn).expect("Failed to create endpoint tag tables");
+            create_printer_tables(&conn).expect("Failed to create printer tables");
+            create_storage_health_tables(&conn).expect("Failed to create storage health tables");
+            create_ups_tables(&conn).expect("Failed to create UPS tables");
+            create_energy_tables(&conn).expect("Failed to create energy tables");
+            create_people_tables(&conn).expect("Failed to create people tables");
+            create_presence_table(&conn).expect("Failed to create person_presence table");
+            create_activity_table(&conn).expect("Failed to create activity_events table");
+
             conn.execute(
                 "CREATE TABLE IF NOT EXISTS open_ports (
                     id INTEGER PRIMARY KEY,
@@ -379,72 +1143,129 @@ impl SQLWriter {
             )
             .expect("Failed to create open_ports table");
 
+            scan_runs::create_scan_runs_table(&conn).expect("Failed to create scan_runs table");
+            HostnameResolver::create_table_if_not_exists(&conn)
+                .expect("Failed to create hostname_cache table");
+            endpoint_summary::create_endpoint_summary_table(&conn)
+                .expect("Failed to create endpoint_summary table");
+
             // Create settings table for user-configurable options
             conn.execute(
                 "CREATE TABLE IF NOT EXISTS settings (
                     key TEXT PRIMARY KEY,
                     value TEXT NOT NULL,
-                    updated_at INTEGER DEFAULT (strftime('%s', 'now'))
+                    updated_at INTEGER DEFAULT (strftime('%s', 'now')),
+                    source TEXT
                 )",
                 [],
             )
             .expect("Failed to create settings table");
 
             // Create notifications table for event logging
+            create_notifications_table(&conn).expect("Failed to create notifications table");
+
+            // Create audit log table for user-initiated (mutating) API calls
             conn.execute(
-                "CREATE TABLE IF NOT EXISTS notifications (
+                "CREATE TABLE IF NOT EXISTS audit_log (
                     id INTEGER PRIMARY KEY,
                     created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-                    event_type TEXT NOT NULL,
-                    title TEXT NOT NULL,
-                    details TEXT,
-                    endpoint_name TEXT,
-                    endpoint_id INTEGER,
-                    dismissed INTEGER NOT NULL DEFAULT 0
+                    action TEXT NOT NULL,
+                    method TEXT NOT NULL,
+                    path TEXT NOT NULL,
+                    status INTEGER,
+                    source_ip TEXT,
+                    actor TEXT,
+                    details TEXT
                 )",
                 [],
             )
-            .expect("Failed to create notifications table");
-
-            // Add endpoint_id column if it doesn't exist (migration for existing databases)
-            let _ = conn.execute(
-                "ALTER TABLE notifications ADD COLUMN endpoint_id INTEGER",
-                [],
-            );
+            .expect("Failed to create audit_log table");
 
             conn.execute(
-                "CREATE INDEX IF NOT EXISTS idx_notifications_created ON notifications(created_at DESC)",
+                "CREATE INDEX IF NOT EXISTS idx_audit_log_created ON audit_log(created_at DESC)",
                 [],
             )
-            .expect("Failed to create notifications index");
+            .expect("Failed to create audit_log index");
 
-            // Insert default settings if they don't exist
-            conn.execute(
-                "INSERT OR IGNORE INTO settings (key, value) VALUES
-                    ('cleanup_interval_seconds', '30'),
-                    ('data_retention_days', '7')",
-                [],
-            )
-            .expect("Failed to insert default settings");
+            // Secondary indexes for hot query paths, now that every table exists
+            maintenance::create_hot_path_indexes(&conn);
+
+            // Defaults, or values from the config file and environment, for settings not
+            // changed in the UI
+            insert_default_settings(&conn, &crate::config::setting_layers())
+                .expect("Failed to insert default settings");
 
             const BATCH_SIZE: usize = 100; // Smaller batches to reduce lock time
             const BATCH_TIMEOUT_MS: u64 = 500; // Flush every 0.5 seconds
+            const MODE_CHECK_SECS: u64 = 5; // Pick up capture mode, privacy and exclusion changes without a restart
             let mut batch = Vec::with_capacity(BATCH_SIZE);
             let mut last_flush = std::time::Instant::now();
+            let mut capture_mode = CaptureMode::from_settings(&conn);
+            let mut excluded_macs = load_excluded_macs(&conn);
+            let mut privacy_mode = privacy_mode_enabled(&conn);
+            let mut mode_checked = std::time::Instant::now();
+            let mut flows = FlowAggregator::new();
+            let mut checked_in = std::time::Instant::now();
 
             loop {
+                // Let health checks see the writer is alive, busy or idle
+                if checked_in.elapsed().as_secs() >= 1 {
+                    record_activity("sql_writer");
+                    checked_in = std::time::Instant::now();
+                }
+
+                // Jobs queued by other tasks run between batches, on this connection
+                while let Ok(job) = jobs.try_recv() {
+                    let run = std::panic::AssertUnwindSafe(|| job(&mut conn));
+                    if let Err(payload) = std::panic::catch_unwind(run) {
+                        record_error(
+                            "sql_writer",
+                            format!(
+                                "A writer job panicked: {}",
+                                crate::supervisor::panic_message(&*payload)
+                            ),
+                        );
+                    }
+                }
+
                 // Try to receive without blocking
                 match rx.try_recv() {
-                    Ok(communication) => {
-                        batch.push(communication);
-
-                        // Flush if batch is full
-                        if batch.len() >= BATCH_SIZE {
-                            Self::process_batch(&mut conn, &mut batch);
-                            last_flush = std::time::Instant::now();
+                    Ok(mut communication) => {
+                        // Devices marked "do not record" never reach the database
+                        if is_excluded_communication(&excluded_macs, &communication) {
+                            continue;
+                        }
+                        // Before aggregation, so no flow keeps an unredacted packet
+                        if privacy_mode {
+                            communication.apply_privacy_mode();
+                        }
+                        match capture_mode {
+                            CaptureMode::Flow { interval } => {
+                                flows.add(communication);
+                                if flows.should_flush(interval) {
+                                    batch.extend(flows.drain());
+                                    Self::process_batch(&mut conn, &mut batch);
+                                    last_flush = std::time::Instant::now();
+                                }
+                            }
+                            CaptureMode::Packet => {
+                                batch.push(communication);
+
+                                // Flush if batch is full
+                                if batch.len() >= BATCH_SIZE {
+                                    Self::process_batch(&mut conn, &mut batch);
+                                    last_flush = std::time::Instant::now();
+                                }
+                            }
                         }
                     }
                     Err(mpsc::error::TryRecvError::Empty) => {
+                        if let CaptureMode::Flow { interval } = capture_mode
+                            && flows.should_flush(interval)
+                        {
+                            batch.extend(flows.drain());
+                        }
+
                         // Flush batch if timeout reached and we have pending items
                         if !batch.is_empty()
                             && last_flush.elapsed().as_millis() >= BATCH_TIMEOUT_MS as u128
@@ -452,11 +1273,24 @@ impl SQLWriter {
                             Self::process_batch(&mut conn, &mut batch);
                             last_flush = std::time::Instant::now();
                         }
+
+                        if mode_checked.elapsed().as_secs() >= MODE_CHECK_SECS {
+                            capture_mode = CaptureMode::from_settings(&conn);
+                            excluded_macs = load_excluded_macs(&conn);
+                            privacy_mode = privacy_mode_enabled(&conn);
+                            mode_checked = std::time::Instant::now();
+                            // Switching back to per-packet mode writes out the buffered flows
+                            if capture_mode == CaptureMode::Packet && !flows.is_empty() {
+                                batch.extend(flows.drain());
+                            }
+                        }
+
                         // Sleep briefly to avoid busy-waiting
                         std::thread::sleep(std::time::Duration::from_millis(100));
                     }
                     Err(mpsc::error::TryRecvError::Disconnected) => {
                         // Channel closed, process remaining items and exit
+                        batch.extend(flows.drain());
                         if !batch.is_empty() {
                             Self::process_batch(&mut conn, &mut batch);
                         }
@@ -488,6 +1322,34 @@ impl SQLWriter {
             }
         });
 
+        // Scheduled backups (no-op until a backup directory is configured)
+        start_backup_scheduler();
+
+        // Push confirmed endpoints to NetBox/phpIPAM (no-op until a target is configured)
+        start_ipam_sync_scheduler();
+
+        // Send queued webhook deliveries (no-op until a webhook is configured)
+        start_webhook_delivery();
+
+        // Alert on endpoints over their daily bandwidth quota (no-op until one is set)
+        start_bandwidth_quota_checker();
+
+        // Printer supply levels and page counts over SNMP (no-op until a printer answers)
+        start_printer_poller();
+
+        // NAS volume and disk health over SNMP (no-op until a NAS answers)
+        start_storage_health_poller();
+
+        // UPS battery and power events over SNMP and NUT (no-op until a UPS answers)
+        start_ups_poller();
+        start_energy_rollup();
+
+        // Arrive/leave events for people with assigned devices (no-op until one is assigned)
+        start_presence_checker();
+
+        // Device rules and OUI overrides edited without a rebuild
+        start_runtime_rules_watcher();
+
         SQLWriter { sender: tx }
     }
 
@@ -523,6 +1385,7 @@ impl SQLWriter {
         for attempt in 1..=MAX_RETRIES {
             match Self::try_process_batch(conn, batch, attempt, MAX_RETRIES) {
                 BatchResult::Success => {
+                    record_activity("sql_writer");
                     batch.clear();
                     return;
                 }
@@ -531,6 +1394,10 @@ impl SQLWriter {
                     continue;
                 }
                 BatchResult::Failed => {
+                    record_error(
+                        "sql_writer",
+                        format!("Dropped a batch of {} communications", batch.len()),
+                    );
                     batch.clear();
                     return;
                 }
@@ -641,8 +1508,33 @@ impl SQLWriter {
                 "Cleaned up {} old communication records (retention: {} days)",
                 deleted, retention_days
             );
+            // On the activity timeline, so pruned history isn't mistaken for a capture gap
+            let _ = activity::insert_activity_event(
+                conn,
+                "retention_pruned",
+                &serde_json::json!({
+                    "communications": deleted,
+                    "retention_days": retention_days,
+                }),
+                chrono::Utc::now().timestamp(),
+            );
         }
 
+        // Broadcast/multicast tallies, passive DNS answers and the authentication event log
+        // follow the same retention
+        conn.execute(
+            "DELETE FROM broadcast_traffic WHERE last_seen_at < (strftime('%s', 'now') - ?1)",
+            [retention_seconds],
+        )?;
+        conn.execute(
+            "DELETE FROM dns_entries WHERE last_seen_at < (strftime('%s', 'now') - ?1)",
+            [retention_seconds],
+        )?;
+        conn.execute(
+            "DELETE FROM auth_events WHERE created_at < (strftime('%s', 'now') - ?1)",
+            [retention_seconds],
+        )?;
+
         // Clean up orphaned endpoint attributes (but preserve user-identified endpoints)
         conn.execute(
             "DELETE FROM endpoint_attributes WHERE created_at < (strftime('%s', 'now') - ?1)
@@ -716,6 +1608,48 @@ impl SQLWriter {
             );
         }
 
+        // Audit entries are kept longer than traffic data (default 90 days)
+        let audit_retention_seconds = get_setting_i64("audit_retention_days", 90) * 24 * 60 * 60;
+        let audit_cleaned = conn
+            .execute(
+                "DELETE FROM audit_log WHERE created_at < (strftime('%s', 'now') - ?1)",
+                [audit_retention_seconds],
+            )
+            .unwrap_or(0);
+
+        if audit_cleaned > 0 {
+            println!("Cleaned up {} old audit log entries", audit_cleaned);
+        }
+
+        // Stale hostname cache rows are re-resolved on demand anyway
+        let _ = HostnameResolver::purge_expired(conn, retention_seconds);
+
+        // Expired IP bindings only matter for detecting recent address moves
+        let _ = EndPoint::purge_expired_ip_bindings(conn, retention_seconds);
+
+        // Address history answers "who had this IP last month", so it outlives traffic data
+        let _ = EndPoint::purge_ip_history(conn, get_setting_i64("ip_history_retention_days", 90));
+
+        // Usage rollups are small and back "last month" household reports
+        let _ = usage::purge_usage(conn, get_setting_i64("usage_retention_days", 30));
+
+        // Deleted endpoints stay restorable until their archive expires (default 30 days)
+        let archive_purged = EndPoint::purge_archived_endpoints(
+            conn,
+            get_setting_i64("archived_endpoint_retention_days", 30),
+        )
+        .unwrap_or(0);
+
+        if archive_purged > 0 {
+            println!("Purged {} expired archived endpoints", archive_purged);
+        }
+
+        // Scan runs back comparisons against "N days ago", so they outlive traffic data
+        let _ = scan_runs::purge_scan_runs(conn, get_setting_i64("scan_run_retention_days", 90));
+
+        // The activity timeline follows the audit log's retention
+        let _ = purge_activity_events(conn, get_setting_i64("audit_retention_days", 90));
+
         // Vacuum database occasionally to reclaim space
         if deleted > 1000 || deduped > 1000 || merged > 0 || ipv6_merged > 0 || hotspot_merged > 0 {
             println!("Running VACUUM to reclaim disk space...");
@@ -908,6 +1842,7 @@ impl SQLWriter {
                     "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
                     rusqlite::params![keep_id, merge_id],
                 )?;
+                EndPoint::reassign_endpoint_data(conn, merge_id, keep_id)?;
 
                 // Delete the duplicate endpoint
                 conn.execute("DELETE FROM endpoints WHERE id = ?1", [merge_id])?;
@@ -1054,6 +1989,7 @@ impl SQLWriter {
                         "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
                         rusqlite::params![keep_id, merge_id],
                     )?;
+                    EndPoint::reassign_endpoint_data(conn, merge_id, keep_id)?;
 
                     // Delete the duplicate endpoint
                     conn.execute("DELETE FROM endpoints WHERE id = ?1", [merge_id])?;
@@ -1222,6 +2158,7 @@ impl SQLWriter {
                 "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
                 rusqlite::params![phone_id, gateway_id],
             )?;
+            EndPoint::reassign_endpoint_data(conn, gateway_id, phone_id)?;
 
             // Delete the gateway endpoint
             conn.execute("DELETE FROM endpoints WHERE id = ?1", [gateway_id])?;
@@ -1260,3 +2197,289 @@ impl SQLWriter {
         .ok();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_in_transaction_commits_all_or_nothing() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (v INTEGER)", []).unwrap();
+        let rows = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let failed = run_in_transaction(&mut conn, |tx| {
+            tx.execute("INSERT INTO t (v) VALUES (1)", [])?;
+            tx.execute("INSERT INTO missing (v) VALUES (1)", [])
+        });
+        assert!(failed.is_err());
+        assert_eq!(rows(&conn), 0);
+
+        let inserted = run_in_transaction(&mut conn, |tx| {
+            tx.execute("INSERT INTO t (v) VALUES (1)", [])?;
+            tx.execute("INSERT INTO t (v) VALUES (2)", [])
+        });
+        assert_eq!(inserted.unwrap(), 1);
+        assert_eq!(rows(&conn), 2);
+    }
+
+    fn notifications_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+            [],
+        )
+        .unwrap();
+        create_notifications_table(&conn).unwrap();
+        conn
+    }
+
+    fn notification_counts(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare("SELECT occurrences FROM notifications ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_notification_severity_defaults_and_override() {
+        let conn = notifications_connection();
+        insert_notification(&conn, "vendor_identified", "Vendor", None, Some("tv"));
+        insert_notification(&conn, "endpoint_discovered", "New device", None, Some("tv"));
+        insert_notification(&conn, "rogue_router", "Rogue router", None, Some("tv"));
+        insert_notification_with_severity(
+            &conn,
+            "vendor_identified",
+            "critical",
+            "Vendor changed",
+            None,
+            Some("tv"),
+            None,
+        );
+
+        let mut stmt = conn
+            .prepare("SELECT event_type, severity FROM notifications ORDER BY id")
+            .unwrap();
+        let severities: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let expected = [
+            ("vendor_identified", "info"),
+            ("endpoint_discovered", "warning"),
+            ("rogue_router", "critical"),
+            ("vendor_identified", "critical"),
+        ];
+        assert_eq!(
+            severities,
+            expected
+                .iter()
+                .map(|(e, s)| (e.to_string(), s.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_acknowledge_notifications() {
+        let conn = notifications_connection();
+        conn.execute_batch(
+            "INSERT INTO notifications (id, event_type, title, severity, dismissed) VALUES
+                 (1, 'vendor_identified', 'Vendor', 'info', 0),
+                 (2, 'endpoint_discovered', 'New device', 'warning', 0),
+                 (3, 'rogue_router', 'Rogue router', 'critical', 0),
+                 (4, 'port_opened', 'Port opened', 'warning', 1),
+                 (5, 'rogue_router', 'Another rogue router', 'critical', 0);",
+        )
+        .unwrap();
+        let acknowledged = || -> Vec<(i64, Option<String>)> {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, acknowledged_by FROM notifications
+                     WHERE acknowledged_at IS NOT NULL ORDER BY id",
+                )
+                .unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect()
+        };
+
+        assert_eq!(
+            mark_notifications_acknowledged(&conn, &[], "alice").unwrap(),
+            0
+        );
+        assert_eq!(
+            mark_notifications_acknowledged(&conn, &[3, 99], "alice").unwrap(),
+            1
+        );
+        // Already acknowledged notifications keep who handled them first
+        assert_eq!(
+            mark_notifications_acknowledged(&conn, &[3], "bob").unwrap(),
+            0
+        );
+        assert_eq!(acknowledged(), vec![(3, Some("alice".to_string()))]);
+
+        // Acknowledge-all by severity skips dismissed ones and other severities
+        let warning = vec!["warning".to_string()];
+        assert_eq!(
+            mark_all_notifications_acknowledged(&conn, &warning, "bob").unwrap(),
+            1
+        );
+        assert_eq!(
+            acknowledged(),
+            vec![(2, Some("bob".to_string())), (3, Some("alice".to_string()))]
+        );
+
+        assert_eq!(
+            mark_all_notifications_acknowledged(&conn, &[], "carol").unwrap(),
+            2
+        );
+        assert_eq!(
+            acknowledged().iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![1, 2, 3, 5]
+        );
+        let summary = notification_summary(&conn, 0).unwrap();
+        assert_eq!(summary.unread, 0);
+    }
+
+    #[test]
+    fn test_notification_dedup_collapses_repeats() {
+        let conn = notifications_connection();
+        let notify = |title: &str, endpoint_id: i64| {
+            insert_notification_with_endpoint_id(
+                &conn,
+                "vendor_identified",
+                title,
+                Some("Apple"),
+                Some("laptop"),
+                Some(endpoint_id),
+            )
+        };
+
+        notify("Vendor identified: Apple", 1);
+        notify("Vendor identified: Apple", 1);
+        notify("Vendor identified: Apple", 1);
+        // Different payload or endpoint is a separate notification
+        notify("Vendor identified: Apple Inc.", 1);
+        notify("Vendor identified: Apple", 2);
+        assert_eq!(notification_counts(&conn), vec![3, 1, 1]);
+
+        // Dismissed notifications are not revived
+        conn.execute("UPDATE notifications SET dismissed = 1", [])
+            .unwrap();
+        notify("Vendor identified: Apple", 1);
+        assert_eq!(notification_counts(&conn), vec![3, 1, 1, 1]);
+
+        // A cooldown of 0 disables dedup
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('notification_dedup_cooldown_seconds', '0')",
+            [],
+        )
+        .unwrap();
+        notify("Vendor identified: Apple", 1);
+        assert_eq!(notification_counts(&conn), vec![3, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_notification_repeat_after_acknowledge_is_unread() {
+        let conn = notifications_connection();
+        let notify = || {
+            insert_notification_with_endpoint_id(
+                &conn,
+                "rogue_router",
+                "Rogue router",
+                None,
+                Some("router"),
+                Some(1),
+            )
+        };
+
+        notify();
+        notify();
+        assert_eq!(notification_summary(&conn, 0).unwrap().unread, 1);
+        assert_eq!(
+            mark_all_notifications_acknowledged(&conn, &[], "alice").unwrap(),
+            1
+        );
+        assert_eq!(notification_summary(&conn, 0).unwrap().unread, 0);
+
+        // The repeat within the cooldown isn't folded into the acknowledged one
+        notify();
+        let summary = notification_summary(&conn, 0).unwrap();
+        assert_eq!(summary.unread, 1);
+        assert_eq!(summary.by_severity["critical"], 1);
+        assert_eq!(notification_counts(&conn), vec![2, 1]);
+
+        notify();
+        assert_eq!(notification_counts(&conn), vec![2, 2]);
+    }
+
+    #[test]
+    fn test_notification_summary_counts_unread_since() {
+        let conn = notifications_connection();
+        conn.execute_batch(
+            "INSERT INTO notifications (created_at, event_type, title, severity, dismissed, acknowledged_at, last_occurred_at) VALUES
+                 (100, 'endpoint_discovered', 'New device', 'warning', 0, NULL, NULL),
+                 (200, 'rogue_router', 'Rogue router', 'critical', 0, NULL, NULL),
+                 (300, 'endpoint_discovered', 'New device 2', 'warning', 0, NULL, NULL),
+                 (400, 'vendor_identified', 'Vendor', 'info', 1, NULL, NULL),
+                 (500, 'port_opened', 'Port opened', 'warning', 0, 510, NULL),
+                 (150, 'vendor_identified', 'Vendor again', 'info', 0, NULL, 600);",
+        )
+        .unwrap();
+
+        let all = notification_summary(&conn, 0).unwrap();
+        assert_eq!(all.unread, 4);
+        assert_eq!(all.watermark, 600);
+        assert_eq!(all.by_severity["warning"], 2);
+        assert_eq!(all.by_severity["critical"], 1);
+        assert_eq!(all.by_severity["info"], 1);
+        assert_eq!(all.by_event_type["endpoint_discovered"], 2);
+
+        // A repeat collapsed into an older notification counts as new activity
+        let newer = notification_summary(&conn, 250).unwrap();
+        assert_eq!(newer.unread, 2);
+        assert_eq!(newer.by_severity["critical"], 0);
+        assert_eq!(newer.by_event_type.get("vendor_identified"), Some(&1));
+
+        let none = notification_summary(&conn, 600).unwrap();
+        assert_eq!(none.unread, 0);
+        assert_eq!(none.watermark, 600);
+        assert!(none.by_event_type.is_empty());
+    }
+
+    #[test]
+    fn test_replica_connection_reads_writer_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.db");
+        let path = path.to_str().unwrap();
+        let writer = Connection::open(path).unwrap();
+        writer
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 CREATE TABLE endpoints (id INTEGER PRIMARY KEY, name TEXT);
+                 INSERT INTO endpoints (name) VALUES ('nas');",
+            )
+            .unwrap();
+
+        let replica = open_replica_connection(path).unwrap();
+        let count = || -> i64 {
+            replica
+                .query_row("SELECT COUNT(*) FROM endpoints", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count(), 1);
+        writer
+            .execute("INSERT INTO endpoints (name) VALUES ('tv')", [])
+            .unwrap();
+        assert_eq!(count(), 2);
+        assert!(replica.execute("DELETE FROM endpoints", []).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! People in the household and the devices they own, so one person's phone, laptop and
+//! console can be seen together. An endpoint has at most one owner. A person's summary
+//! lists their devices with whether each is online (traffic within the active threshold)
+//! and its traffic from the hourly usage rollups.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+/// Longest person name accepted
+const MAX_PERSON_NAME_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Person {
+    pub id: i64,
+    pub name: String,
+    pub device_count: i64,
+    /// Whether the person is home, once presence has been checked (see presence.rs)
+    pub home: Option<bool>,
+    pub presence_changed_at: Option<i64>,
+    pub created_at: i64,
+}
+
+/// One of a person's devices
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonDevice {
+    pub endpoint_id: i64,
+    pub name: Option<String>,
+    pub device_type: Option<String>,
+    pub online: bool,
+    /// Unix time of the device's latest traffic
+    pub last_seen: Option<i64>,
+    /// All traffic in the summary window
+    pub bytes: i64,
+    /// Internet download and upload in the summary window
+    pub wan_bytes_in: i64,
+    pub wan_bytes_out: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonSummary {
+    pub id: i64,
+    pub name: String,
+    pub home: Option<bool>,
+    /// Unix time the traffic totals start from
+    pub since: i64,
+    pub online_count: usize,
+    pub bytes: i64,
+    pub wan_bytes_in: i64,
+    pub wan_bytes_out: i64,
+    /// Online devices first, then by name
+    pub devices: Vec<PersonDevice>,
+}
+
+pub fn create_people_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS people (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_owners (
+            endpoint_id INTEGER PRIMARY KEY,
+            person_id INTEGER NOT NULL REFERENCES people(id) ON DELETE CASCADE,
+            assigned_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_endpoint_owners_person ON endpoint_owners (person_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Trim a person's name, rejecting empty and overlong ones
+pub fn parse_person_name(name: &str) -> Option<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_PERSON_NAME_CHARS {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Add a person. Returns their id, or None when the name is taken.
+pub fn create_person(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO people (name, created_at) VALUES (?1, strftime('%s', 'now'))",
+        [name],
+    )?;
+    Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+}
+
+/// Remove a person, leaving their devices without an owner. Returns false when they
+/// didn't exist.
+pub fn delete_person(conn: &Connection, id: i64) -> Result<bool> {
+    conn.execute("DELETE FROM endpoint_owners WHERE person_id = ?1", [id])?;
+    conn.execute("DELETE FROM person_presence WHERE person_id = ?1", [id])?;
+    Ok(conn.execute("DELETE FROM people WHERE id = ?1", [id])? > 0)
+}
+
+/// Everyone, alphabetically, with how many devices they own
+pub fn get_people(conn: &Connection) -> Result<Vec<Person>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.name,
+                (SELECT COUNT(*) FROM endpoint_owners o WHERE o.person_id = p.id),
+                s.home, s.changed_at, p.created_at
+         FROM people p
+         LEFT JOIN person_presence s ON s.person_id = p.id
+         ORDER BY p.name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Person {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            device_count: row.get(2)?,
+            home: row.get(3)?,
+            presence_changed_at: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Make a person the owner of an endpoint, replacing its previous owner, or clear its
+/// owner with None. Returns false when the person doesn't exist.
+pub fn set_endpoint_owner(
+    conn: &Connection,
+    endpoint_id: i64,
+    person_id: Option<i64>,
+) -> Result<bool> {
+    let Some(person_id) = person_id else {
+        conn.execute(
+            "DELETE FROM endpoint_owners WHERE endpoint_id = ?1",
+            [endpoint_id],
+        )?;
+        return Ok(true);
+    };
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM people WHERE id = ?1)",
+        [person_id],
+        |row| row.get(0),
+    )?;
+    if exists {
+        conn.execute(
+            "INSERT OR REPLACE INTO endpoint_owners (endpoint_id, person_id, assigned_at)
+             VALUES (?1, ?2, strftime('%s', 'now'))",
+            params![endpoint_id, person_id],
+        )?;
+    }
+    Ok(exists)
+}
+
+/// A person's devices with their traffic from `since` on. `last_seen` maps endpoints to
+/// their latest traffic (see `get_endpoint_last_seen`); a device seen at or after
+/// `online_since` is online. None when the person doesn't exist.
+pub fn get_person_summary(
+    conn: &Connection,
+    person_id: i64,
+    since: i64,
+    last_seen: &HashMap<i64, i64>,
+    online_since: i64,
+) -> Result<Option<PersonSummary>> {
+    let Some((name, home)) = conn
+        .query_row(
+            "SELECT p.name, s.home FROM people p
+             LEFT JOIN person_presence s ON s.person_id = p.id
+             WHERE p.id = ?1",
+            [person_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get(1)?)),
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT o.endpoint_id, e.display_name,
+                COALESCE(e.manual_device_type, e.auto_device_type),
+                COALESCE(SUM(u.bytes), 0), COALESCE(SUM(u.wan_bytes_in), 0),
+                COALESCE(SUM(u.wan_bytes_out), 0)
+         FROM endpoint_owners o
+         LEFT JOIN endpoints e ON e.id = o.endpoint_id
+         LEFT JOIN endpoint_usage_hourly u
+                ON u.endpoint_id = o.endpoint_id AND u.hour_start >= ?2
+         WHERE o.person_id = ?1
+         GROUP BY o.endpoint_id",
+    )?;
+    let mut devices: Vec<PersonDevice> = stmt
+        .query_map(params![person_id, since - since.rem_euclid(3600)], |row| {
+            Ok(PersonDevice {
+                endpoint_id: row.get(0)?,
+                name: row.get(1)?,
+                device_type: row.get(2)?,
+                online: false,
+                last_seen: None,
+                bytes: row.get(3)?,
+                wan_bytes_in: row.get(4)?,
+                wan_bytes_out: row.get(5)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+
+    for device in &mut devices {
+        device.last_seen = last_seen.get(&device.endpoint_id).copied();
+        device.online = device
+            .last_seen
+            .is_some_and(|seen_at| seen_at >= online_since);
+    }
+    devices.sort_by(|a, b| {
+        b.online
+            .cmp(&a.online)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.endpoint_id.cmp(&b.endpoint_id))
+    });
+
+    Ok(Some(PersonSummary {
+        id: person_id,
+        name,
+        home,
+        since,
+        online_count: devices.iter().filter(|d| d.online).count(),
+        bytes: devices.iter().map(|d| d.bytes).sum(),
+        wan_bytes_in: devices.iter().map(|d| d.wan_bytes_in).sum(),
+        wan_bytes_out: devices.iter().map(|d| d.wan_bytes_out).sum(),
+        devices,
+    }))
+}
+
+/// Move ownership to the endpoint a merged endpoint was folded into. The surviving
+/// endpoint keeps its own owner if it has one.
+pub fn reassign_endpoint_owner(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE endpoint_owners SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_owners WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_person_summary() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'alice-phone', 'phone'), (2, 1, 'alice-laptop', 'computer'),
+                 (3, 1, 'console', 'gaming');
+             INSERT INTO endpoint_usage_hourly (endpoint_id, hour_start, bytes, wan_bytes_in, wan_bytes_out) VALUES
+                 (1, 3600, 500, 100, 50),
+                 (1, 7200, 1000, 400, 100),
+                 (2, 7200, 2000, 0, 0),
+                 (3, 7200, 9000, 9000, 0);",
+        )
+        .unwrap();
+        let now = 10_000;
+        let last_seen = HashMap::from([(1, now - 30), (2, now - 3600), (3, now)]);
+
+        assert_eq!(parse_person_name("  Alice "), Some("Alice".to_string()));
+        assert_eq!(parse_person_name(" "), None);
+        let alice = create_person(&conn, "Alice").unwrap().unwrap();
+        assert_eq!(create_person(&conn, "alice").unwrap(), None);
+        let bob = create_person(&conn, "Bob").unwrap().unwrap();
+
+        assert!(set_endpoint_owner(&conn, 1, Some(alice)).unwrap());
+        assert!(set_endpoint_owner(&conn, 2, Some(alice)).unwrap());
+        assert!(set_endpoint_owner(&conn, 3, Some(alice)).unwrap());
+        // Reassigning replaces the owner, and unknown people are refused
+        assert!(set_endpoint_owner(&conn, 3, Some(bob)).unwrap());
+        assert!(!set_endpoint_owner(&conn, 3, Some(bob + 100)).unwrap());
+
+        let summary = get_person_summary(&conn, alice, 7200, &last_seen, now - 120)
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.name, "Alice");
+        assert_eq!(summary.online_count, 1);
+        assert_eq!(summary.bytes, 3000);
+        assert_eq!(summary.wan_bytes_in, 400);
+        let devices: Vec<(i64, bool)> = summary
+            .devices
+            .iter()
+            .map(|d| (d.endpoint_id, d.online))
+            .collect();
+        assert_eq!(devices, vec![(1, true), (2, false)]);
+        assert_eq!(summary.devices[1].last_seen, Some(now - 3600));
+        assert_eq!(summary.devices[0].device_type.as_deref(), Some("phone"));
+
+        let counts: Vec<(String, i64)> = get_people(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.name, p.device_count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("Alice".to_string(), 2), ("Bob".to_string(), 1)]
+        );
+
+        // The surviving endpoint of a merge keeps its own owner
+        reassign_endpoint_owner(&conn, 2, 3).unwrap();
+        reassign_endpoint_owner(&conn, 1, 4).unwrap();
+        let owners: Vec<(i64, i64)> = conn
+            .prepare("SELECT endpoint_id, person_id FROM endpoint_owners ORDER BY endpoint_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(owners, vec![(3, bob), (4, alice)]);
+
+        assert!(delete_person(&conn, bob).unwrap());
+        assert!(!delete_person(&conn, bob).unwrap());
+        assert!(
+            get_person_summary(&conn, bob, 0, &last_seen, now - 120)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! Expected-port policies. A policy lists the ports a single endpoint, or every endpoint
+//! of a device type, is expected to serve (printers: 9100, 631, 80). An endpoint's own
+//! policy takes precedence over its type's. Ports outside the policy found open by the
+//! port scanner, or answering in captured traffic, are recorded as drift and raise one
+//! `port_drift` notification per endpoint and port.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use super::insert_notification_with_endpoint_id;
+
+/// Lowest port treated as a client's ephemeral port. Traffic from a lower port to one of
+/// these is a service replying to a client.
+const EPHEMERAL_PORT_START: u16 = 32768;
+
+/// Longest port list a policy may hold
+const MAX_POLICY_PORTS: usize = 1024;
+
+/// Where a port outside the policy was noticed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftSource {
+    Scan,
+    Traffic,
+}
+
+impl DriftSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            DriftSource::Scan => "scan",
+            DriftSource::Traffic => "traffic",
+        }
+    }
+}
+
+/// A policy as submitted through the API: an endpoint name or a device type, not both
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NewPortPolicy {
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub device_type: Option<String>,
+    pub ports: Vec<u16>,
+}
+
+impl NewPortPolicy {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        match (&self.endpoint, &self.device_type) {
+            (Some(_), Some(_)) => {
+                return Err("A policy applies to an endpoint or a device type, not both".into());
+            }
+            (None, None) => return Err("Either endpoint or device_type is required".into()),
+            _ => {}
+        }
+        if self.ports.contains(&0) {
+            return Err("Port 0 is not a valid port".to_string());
+        }
+        if self.ports.len() > MAX_POLICY_PORTS {
+            return Err(format!("At most {} ports per policy", MAX_POLICY_PORTS));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortPolicy {
+    pub id: i64,
+    pub endpoint_id: Option<i64>,
+    pub endpoint_name: Option<String>,
+    pub device_type: Option<String>,
+    pub ports: Vec<u16>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A port seen outside an endpoint's policy
+#[derive(Debug, Clone, Serialize)]
+pub struct PortDrift {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub port: u16,
+    /// "scan" or "traffic", whichever noticed it first
+    pub source: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+pub fn create_port_policies_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS port_policies (
+            id INTEGER PRIMARY KEY,
+            endpoint_id INTEGER,
+            device_type TEXT,
+            ports TEXT NOT NULL DEFAULT '[]',
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_port_policies_endpoint ON port_policies (endpoint_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS port_policy_drift (
+            endpoint_id INTEGER NOT NULL,
+            port INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, port)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn ports_from_json(json: &str) -> Vec<u16> {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+/// Set the policy for an endpoint or a device type, replacing any it already has.
+/// Returns the policy id.
+pub fn set_port_policy(
+    conn: &Connection,
+    endpoint_id: Option<i64>,
+    device_type: Option<&str>,
+    ports: &[u16],
+) -> Result<i64> {
+    let mut ports = ports.to_vec();
+    ports.sort_unstable();
+    ports.dedup();
+    let ports = serde_json::to_string(&ports).unwrap_or_else(|_| "[]".into());
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM port_policies WHERE endpoint_id IS ?1 AND device_type IS ?2",
+            params![endpoint_id, device_type],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let id = match existing {
+        Some(id) => {
+            conn.execute(
+                "UPDATE port_policies SET ports = ?1, updated_at = strftime('%s', 'now')
+                 WHERE id = ?2",
+                params![ports, id],
+            )?;
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO port_policies (endpoint_id, device_type, ports, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, strftime('%s', 'now'), strftime('%s', 'now'))",
+                params![endpoint_id, device_type, ports],
+            )?;
+            conn.last_insert_rowid()
+        }
+    };
+    prune_port_drift(conn)?;
+    Ok(id)
+}
+
+/// Remove a policy. Returns false when it didn't exist.
+pub fn delete_port_policy(conn: &Connection, id: i64) -> Result<bool> {
+    let removed = conn.execute("DELETE FROM port_policies WHERE id = ?1", [id])? > 0;
+    if removed {
+        prune_port_drift(conn)?;
+    }
+    Ok(removed)
+}
+
+/// Every policy, endpoint policies first
+pub fn get_port_policies(conn: &Connection) -> Result<Vec<PortPolicy>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.endpoint_id, e.display_name, p.device_type, p.ports,
+                p.created_at, p.updated_at
+         FROM port_policies p
+         LEFT JOIN endpoints e ON e.id = p.endpoint_id
+         ORDER BY p.endpoint_id IS NULL, e.display_name, p.device_type",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(PortPolicy {
+            id: row.get(0)?,
+            endpoint_id: row.get(1)?,
+            endpoint_name: row.get(2)?,
+            device_type: row.get(3)?,
+            ports: ports_from_json(&row.get::<_, String>(4)?),
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Ports seen outside their endpoint's policy, most recent first
+pub fn get_port_drift(conn: &Connection) -> Result<Vec<PortDrift>> {
+    let mut stmt = conn.prepare(
+        "SELECT d.endpoint_id, e.display_name, d.port, d.source, d.first_seen_at, d.last_seen_at
+         FROM port_policy_drift d
+         LEFT JOIN endpoints e ON e.id = d.endpoint_id
+         ORDER BY d.last_seen_at DESC, d.port",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(PortDrift {
+            endpoint_id: row.get(0)?,
+            endpoint_name: row.get(1)?,
+            port: row.get(2)?,
+            source: row.get(3)?,
+            first_seen_at: row.get(4)?,
+            last_seen_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// The ports an endpoint is expected to serve, and the device type when that's where the
+/// policy comes from. None when no policy covers the endpoint.
+fn expected_ports(
+    conn: &Connection,
+    endpoint_id: i64,
+) -> Result<Option<(Vec<u16>, Option<String>)>> {
+    conn.query_row(
+        "SELECT p.ports, p.device_type FROM port_policies p
+         WHERE p.endpoint_id = ?1
+            OR (p.endpoint_id IS NULL AND p.device_type =
+                (SELECT COALESCE(e.manual_device_type, e.auto_device_type)
+                 FROM endpoints e WHERE e.id = ?1))
+         ORDER BY p.endpoint_id IS NULL
+         LIMIT 1",
+        [endpoint_id],
+        |row| Ok((ports_from_json(&row.get::<_, String>(0)?), row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Forget drift for ports a policy now allows, or endpoints no policy covers any more
+fn prune_port_drift(conn: &Connection) -> Result<()> {
+    let drift: Vec<(i64, u16)> = conn
+        .prepare("SELECT endpoint_id, port FROM port_policy_drift")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+    for (endpoint_id, port) in drift {
+        let allowed = match expected_ports(conn, endpoint_id)? {
+            Some((ports, _)) => ports.contains(&port),
+            None => true,
+        };
+        if allowed {
+            conn.execute(
+                "DELETE FROM port_policy_drift WHERE endpoint_id = ?1 AND port = ?2",
+                params![endpoint_id, port],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Check a port an endpoint was seen serving against its policy. Drift is recorded and,
+/// the first time for this endpoint and port, raised as a notification. Returns whether
+/// the port is outside the policy.
+pub fn check_port_policy(
+    conn: &Connection,
+    endpoint_id: i64,
+    port: u16,
+    source: DriftSource,
+    now: i64,
+) -> Result<bool> {
+    let Some((expected, device_type)) = expected_ports(conn, endpoint_id)? else {
+        return Ok(false);
+    };
+    if expected.contains(&port) {
+        return Ok(false);
+    }
+
+    let new = conn.execute(
+        "INSERT OR IGNORE INTO port_policy_drift
+             (endpoint_id, port, source, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)",
+        params![endpoint_id, port, source.as_str(), now],
+    )? > 0;
+    if !new {
+        conn.execute(
+            "UPDATE port_policy_drift SET last_seen_at = ?3 WHERE endpoint_id = ?1 AND port = ?2",
+            params![endpoint_id, port, now],
+        )?;
+        return Ok(true);
+    }
+
+    let name: Option<String> = conn
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let name = name.unwrap_or_else(|| format!("endpoint {}", endpoint_id));
+    let policy = match device_type {
+        Some(device_type) => format!("The {} policy", device_type),
+        None => "Its policy".to_string(),
+    };
+    let expected = if expected.is_empty() {
+        "no ports".to_string()
+    } else {
+        expected
+            .iter()
+            .map(u16::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let seen = match source {
+        DriftSource::Scan => "Found open by the port scanner",
+        DriftSource::Traffic => "Seen answering clients in captured traffic",
+    };
+    let details = format!("{}. {} expects {} only.", seen, policy, expected);
+    insert_notification_with_endpoint_id(
+        conn,
+        "port_drift",
+        &format!("Unexpected port {} on {}", port, name),
+        Some(&details),
+        Some(&name),
+        Some(endpoint_id),
+    );
+    Ok(true)
+}
+
+/// The port the sender is serving on, if the traffic looks like a service answering a
+/// client. Requests aren't counted: a port that is probed but never answers isn't open.
+pub fn served_port(source_port: u16, destination_port: u16) -> Option<u16> {
+    (source_port != 0
+        && source_port < EPHEMERAL_PORT_START
+        && destination_port >= EPHEMERAL_PORT_START)
+        .then_some(source_port)
+}
+
+/// Point policies and drift at the endpoint a merged endpoint was folded into. The
+/// surviving endpoint keeps its own policy if it has one.
+pub fn reassign_port_policies(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE port_policies SET endpoint_id = ?1 WHERE endpoint_id = ?2
+         AND NOT EXISTS (SELECT 1 FROM port_policies WHERE endpoint_id = ?1)",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM port_policies WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    conn.execute(
+        "UPDATE OR IGNORE port_policy_drift SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM port_policy_drift WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn drift_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM port_policy_drift", [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_port_policy_drift() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'laserjet', 'printer'), (2, 1, 'office-printer', 'printer'),
+                 (3, 1, 'nas', 'appliance');",
+        )
+        .unwrap();
+        let printers = set_port_policy(&conn, None, Some("printer"), &[631, 9100, 80]).unwrap();
+        set_port_policy(&conn, Some(2), None, &[80, 443]).unwrap();
+
+        // No policy covers the NAS
+        assert!(!check_port_policy(&conn, 3, 22, DriftSource::Scan, 100).unwrap());
+        // The type policy applies to the first printer
+        assert!(!check_port_policy(&conn, 1, 9100, DriftSource::Scan, 100).unwrap());
+        assert!(check_port_policy(&conn, 1, 23, DriftSource::Scan, 100).unwrap());
+        // The second printer's own policy wins over its type's
+        assert!(check_port_policy(&conn, 2, 9100, DriftSource::Traffic, 100).unwrap());
+        assert!(!check_port_policy(&conn, 2, 443, DriftSource::Traffic, 100).unwrap());
+        assert_eq!(drift_count(&conn), 2);
+
+        // Drift is raised once per endpoint and port
+        assert!(check_port_policy(&conn, 1, 23, DriftSource::Traffic, 200).unwrap());
+        let alerts: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = 'port_drift'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(alerts, 2);
+        let drift = get_port_drift(&conn).unwrap();
+        assert_eq!(drift[0].port, 23);
+        assert_eq!(drift[0].source, "scan");
+        assert_eq!(drift[0].last_seen_at, 200);
+
+        // Allowing a port clears its drift; setting a policy again replaces it
+        set_port_policy(&conn, None, Some("printer"), &[23, 631, 9100, 80, 80]).unwrap();
+        assert_eq!(drift_count(&conn), 1);
+        let policies = get_port_policies(&conn).unwrap();
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies[0].endpoint_id, Some(2));
+        assert_eq!(policies[1].id, printers);
+        assert_eq!(policies[1].ports, vec![23, 80, 631, 9100]);
+
+        // Merging the second printer into the first hands over its policy and drift
+        reassign_port_policies(&conn, 2, 1).unwrap();
+        assert!(check_port_policy(&conn, 1, 631, DriftSource::Scan, 300).unwrap());
+        assert_eq!(get_port_drift(&conn).unwrap()[1].endpoint_id, 1);
+
+        assert!(delete_port_policy(&conn, printers).unwrap());
+        assert!(!delete_port_policy(&conn, printers).unwrap());
+    }
+
+    #[test]
+    fn test_served_port_and_validation() {
+        assert_eq!(served_port(9100, 51000), Some(9100));
+        assert_eq!(served_port(51000, 631), None);
+        assert_eq!(served_port(5353, 5353), None);
+        assert_eq!(served_port(50000, 60000), None);
+
+        let policy = |endpoint: Option<&str>, device_type: Option<&str>| NewPortPolicy {
+            endpoint: endpoint.map(str::to_string),
+            device_type: device_type.map(str::to_string),
+            ports: vec![80],
+        };
+        assert!(policy(Some("laserjet"), None).validate().is_ok());
+        assert!(policy(None, Some("printer")).validate().is_ok());
+        assert!(policy(None, None).validate().is_err());
+        assert!(
+            policy(Some("laserjet"), Some("printer"))
+                .validate()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Presence of the people devices are assigned to, for automations like "turn the lights
+//! off when everyone leaves". A person is home while any of their devices is online
+//! (traffic within the active threshold). A change only counts once it has held for the
+//! arrive or leave delay, so a phone dozing off its Wi-Fi for a few minutes isn't a
+//! departure. Each change raises a `person_arrived` or `person_left` webhook event.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+use tokio::task;
+
+use super::{get_endpoint_last_seen, get_setting_i64, new_connection_result, queue_webhook_event};
+
+/// How often presence is checked
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// Flag to ensure the checker is only started once
+static PRESENCE_CHECKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// How long a change has to hold before it counts
+#[derive(Debug, Clone, Copy)]
+pub struct PresenceDelays {
+    pub arrive_secs: i64,
+    pub leave_secs: i64,
+}
+
+/// A person arriving or leaving
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PresenceChange {
+    pub person_id: i64,
+    pub person: String,
+    pub home: bool,
+    /// The device that came online, or the last one seen before leaving
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub changed_at: i64,
+}
+
+impl PresenceChange {
+    pub fn event_type(&self) -> &'static str {
+        if self.home {
+            "person_arrived"
+        } else {
+            "person_left"
+        }
+    }
+}
+
+pub fn create_presence_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS person_presence (
+            person_id INTEGER PRIMARY KEY REFERENCES people(id) ON DELETE CASCADE,
+            home INTEGER NOT NULL,
+            changed_at INTEGER NOT NULL,
+            pending_since INTEGER
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A person's id and name with the endpoints they own (id and display name)
+type OwnedDevices = (i64, String, Vec<(i64, Option<String>)>);
+
+/// Update everyone's presence from their devices' latest traffic (`last_seen`, by
+/// endpoint id) and return the changes that held long enough. A person seen for the
+/// first time starts in their current state without an event.
+pub fn update_presence(
+    conn: &Connection,
+    last_seen: &HashMap<i64, i64>,
+    now: i64,
+    active_threshold_secs: i64,
+    delays: PresenceDelays,
+) -> Result<Vec<PresenceChange>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.name, o.endpoint_id, e.display_name
+         FROM people p
+         JOIN endpoint_owners o ON o.person_id = p.id
+         LEFT JOIN endpoints e ON e.id = o.endpoint_id
+         ORDER BY p.id",
+    )?;
+    let mut devices: Vec<OwnedDevices> = Vec::new();
+    for row in stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, Option<String>>(3)?,
+        ))
+    })? {
+        let (person_id, name, endpoint_id, endpoint_name) = row?;
+        match devices.last_mut() {
+            Some((id, _, owned)) if *id == person_id => owned.push((endpoint_id, endpoint_name)),
+            _ => devices.push((person_id, name, vec![(endpoint_id, endpoint_name)])),
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (person_id, person, owned) in devices {
+        // The most recently active device decides the event's endpoint
+        let Some((endpoint_id, endpoint_name, latest)) = owned
+            .into_iter()
+            .map(|(id, name)| (id, name, last_seen.get(&id).copied().unwrap_or(0)))
+            .max_by_key(|(_, _, seen_at)| *seen_at)
+        else {
+            continue;
+        };
+        let home = latest >= now - active_threshold_secs;
+
+        let state: Option<(bool, Option<i64>)> = conn
+            .query_row(
+                "SELECT home, pending_since FROM person_presence WHERE person_id = ?1",
+                [person_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        match state {
+            None => {
+                conn.execute(
+                    "INSERT INTO person_presence (person_id, home, changed_at) VALUES (?1, ?2, ?3)",
+                    params![person_id, home, now],
+                )?;
+            }
+            Some((was_home, _)) if was_home == home => {
+                conn.execute(
+                    "UPDATE person_presence SET pending_since = NULL WHERE person_id = ?1",
+                    [person_id],
+                )?;
+            }
+            Some((_, pending_since)) => {
+                let pending_since = pending_since.unwrap_or(now);
+                let delay = if home {
+                    delays.arrive_secs
+                } else {
+                    delays.leave_secs
+                };
+                if now - pending_since >= delay {
+                    conn.execute(
+                        "UPDATE person_presence SET home = ?1, changed_at = ?2, pending_since = NULL
+                         WHERE person_id = ?3",
+                        params![home, now, person_id],
+                    )?;
+                    changes.push(PresenceChange {
+                        person_id,
+                        person,
+                        home,
+                        endpoint_id,
+                        endpoint_name,
+                        changed_at: now,
+                    });
+                } else {
+                    conn.execute(
+                        "UPDATE person_presence SET pending_since = ?1 WHERE person_id = ?2",
+                        params![pending_since, person_id],
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Queue a webhook event for each change, with how many people are home afterwards
+pub fn queue_presence_events(conn: &Connection, changes: &[PresenceChange]) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let people_home: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM person_presence WHERE home = 1",
+        [],
+        |row| row.get(0),
+    )?;
+    for change in changes {
+        let payload = serde_json::json!({
+            "event": change.event_type(),
+            "person_id": change.person_id,
+            "person": change.person,
+            "endpoint_id": change.endpoint_id,
+            "endpoint_name": change.endpoint_name,
+            "changed_at": change.changed_at,
+            "people_home": people_home,
+        });
+        queue_webhook_event(conn, change.event_type(), change.endpoint_id, &payload)?;
+    }
+    Ok(())
+}
+
+/// Start the task that tracks presence and raises arrive/leave events
+pub fn start_presence_checker() {
+    if PRESENCE_CHECKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        loop {
+            let result = task::spawn_blocking(|| {
+                let conn = new_connection_result()?;
+                let delays = PresenceDelays {
+                    arrive_secs: get_setting_i64("presence_arrive_delay_minutes", 0) * 60,
+                    leave_secs: get_setting_i64("presence_leave_delay_minutes", 10) * 60,
+                };
+                let changes = update_presence(
+                    &conn,
+                    &get_endpoint_last_seen(&conn, 0)?,
+                    chrono::Utc::now().timestamp(),
+                    get_setting_i64("active_threshold_seconds", 120),
+                    delays,
+                )?;
+                queue_presence_events(&conn, &changes)?;
+                Ok::<_, rusqlite::Error>(changes)
+            })
+            .await;
+
+            match result {
+                Ok(Ok(changes)) => {
+                    for change in changes {
+                        println!(
+                            "{} {}",
+                            change.person,
+                            if change.home { "arrived" } else { "left" }
+                        );
+                    }
+                }
+                Ok(Err(e)) => eprintln!("Presence check failed: {}", e),
+                Err(e) => eprintln!("Presence task failed: {}", e),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        NewWebhook, create_person, create_webhook, new_test_connection, set_endpoint_owner,
+    };
+
+    #[test]
+    fn test_presence_debounces_arrivals_and_departures() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES
+                 (1, 1, 'alice-phone'), (2, 1, 'alice-laptop'), (3, 1, 'bob-phone');",
+        )
+        .unwrap();
+        let webhook = NewWebhook {
+            url: "http://automation.lan/hook".to_string(),
+            events: vec!["person_left".to_string()],
+            ..Default::default()
+        };
+        assert!(webhook.validate().is_ok());
+        create_webhook(&conn, &webhook, &[]).unwrap();
+
+        let alice = create_person(&conn, "Alice").unwrap().unwrap();
+        let bob = create_person(&conn, "Bob").unwrap().unwrap();
+        set_endpoint_owner(&conn, 1, Some(alice)).unwrap();
+        set_endpoint_owner(&conn, 2, Some(alice)).unwrap();
+        set_endpoint_owner(&conn, 3, Some(bob)).unwrap();
+
+        let delays = PresenceDelays {
+            arrive_secs: 0,
+            leave_secs: 600,
+        };
+        let mut last_seen = HashMap::from([(1, 1000), (3, 100)]);
+        let update = |last_seen: &HashMap<i64, i64>, now| {
+            let changes = update_presence(&conn, last_seen, now, 120, delays).unwrap();
+            queue_presence_events(&conn, &changes).unwrap();
+            changes
+                .into_iter()
+                .map(|c| (c.person, c.home, c.endpoint_id))
+                .collect::<Vec<_>>()
+        };
+
+        // The first check only records where everyone is
+        assert!(update(&last_seen, 1000).is_empty());
+
+        // Bob arrives right away, and stays
+        last_seen.insert(3, 1100);
+        assert_eq!(update(&last_seen, 1100), vec![("Bob".to_string(), true, 3)]);
+        last_seen.insert(3, i64::MAX);
+
+        // Alice's phone going quiet isn't a departure yet, and her laptop coming online
+        // cancels it
+        assert!(update(&last_seen, 1500).is_empty());
+        last_seen.insert(2, 1510);
+        assert!(update(&last_seen, 1510).is_empty());
+        assert!(update(&last_seen, 1600).is_empty());
+
+        // Offline for the whole leave delay
+        assert!(update(&last_seen, 1700).is_empty());
+        assert!(update(&last_seen, 2200).is_empty());
+        assert_eq!(
+            update(&last_seen, 2300),
+            vec![("Alice".to_string(), false, 2)]
+        );
+        assert!(update(&last_seen, 2400).is_empty());
+
+        let payloads: Vec<String> = conn
+            .prepare("SELECT payload FROM webhook_deliveries ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(payloads.len(), 1);
+        let payload: serde_json::Value = serde_json::from_str(&payloads[0]).unwrap();
+        assert_eq!(payload["event"], "person_left");
+        assert_eq!(payload["person"], "Alice");
+        // Bob is still home
+        assert_eq!(payload["people_home"], 1);
+    }
+}
@@ -0,0 +1,426 @@
+//! Printer supply and page counter history. A background poller reads the Printer MIB
+//! from every printer that has answered an SNMP scan, every
+//! `printer_poll_interval_minutes`, and keeps each reading so page counts and toner use
+//! can be charted. A supply dropping to `printer_supply_low_percent` or below raises one
+//! `printer_supply_low` notification; replacing it (the level rising above the threshold
+//! again) re-arms the alert.
+
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+use tokio::task;
+
+use super::{
+    get_setting_i64, insert_notification_with_endpoint_id, load_snmp_credentials,
+    new_connection_result,
+};
+use crate::scanner::printer::{PrinterStatus, PrinterSupply, query_printer};
+use crate::scanner::snmp::SnmpScanner;
+
+/// Readings are kept this long; page counts over a year show real usage
+const READING_RETENTION_DAYS: i64 = 365;
+
+/// Flag to ensure the poller is only started once
+static PRINTER_POLLER_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn create_printer_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS printer_readings (
+            endpoint_id INTEGER NOT NULL,
+            polled_at INTEGER NOT NULL,
+            page_count INTEGER,
+            status TEXT,
+            PRIMARY KEY (endpoint_id, polled_at)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS printer_supply_levels (
+            endpoint_id INTEGER NOT NULL,
+            polled_at INTEGER NOT NULL,
+            supply_index INTEGER NOT NULL,
+            description TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            max_capacity INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, polled_at, supply_index)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS printer_supply_alerts (
+            endpoint_id INTEGER NOT NULL,
+            supply_index INTEGER NOT NULL,
+            alerted_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, supply_index)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A printer to poll, at the address its last SNMP scan answered on
+#[derive(Debug)]
+pub struct PrinterTarget {
+    pub endpoint_id: i64,
+    pub ip: String,
+}
+
+/// Endpoints typed as printers that have answered an SNMP scan
+pub fn get_printer_targets(conn: &Connection) -> Result<Vec<PrinterTarget>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, ip FROM (
+             SELECT e.id,
+                    (SELECT sr.ip FROM scan_results sr
+                     WHERE sr.endpoint_id = e.id AND sr.scan_type = 'snmp' AND sr.ip IS NOT NULL
+                     ORDER BY sr.scanned_at DESC LIMIT 1) AS ip
+             FROM endpoints e
+             WHERE COALESCE(e.manual_device_type, e.auto_device_type) = 'printer')
+         WHERE ip IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(PrinterTarget {
+            endpoint_id: row.get(0)?,
+            ip: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Store one poll of a printer and raise low-supply alerts. Returns the alerts raised.
+pub fn record_printer_status(
+    conn: &Connection,
+    endpoint_id: i64,
+    status: &PrinterStatus,
+    now: i64,
+    low_percent: i64,
+) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let cutoff = now - READING_RETENTION_DAYS * 24 * 60 * 60;
+    tx.execute(
+        "DELETE FROM printer_readings WHERE polled_at < ?1",
+        [cutoff],
+    )?;
+    tx.execute(
+        "DELETE FROM printer_supply_levels WHERE polled_at < ?1",
+        [cutoff],
+    )?;
+
+    tx.execute(
+        "INSERT OR REPLACE INTO printer_readings (endpoint_id, polled_at, page_count, status)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![endpoint_id, now, status.page_count, status.status],
+    )?;
+
+    let name: Option<String> = tx
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let name = name.unwrap_or_else(|| format!("endpoint {}", endpoint_id));
+
+    let mut alerts = 0;
+    for supply in &status.supplies {
+        tx.execute(
+            "INSERT OR REPLACE INTO printer_supply_levels
+                 (endpoint_id, polled_at, supply_index, description, level, max_capacity)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                endpoint_id,
+                now,
+                supply.index,
+                supply.description,
+                supply.level,
+                supply.max_capacity
+            ],
+        )?;
+
+        let Some(percent) = supply.percent() else {
+            continue;
+        };
+        if percent > low_percent {
+            // Replaced or refilled; the next drop alerts again
+            tx.execute(
+                "DELETE FROM printer_supply_alerts WHERE endpoint_id = ?1 AND supply_index = ?2",
+                params![endpoint_id, supply.index],
+            )?;
+            continue;
+        }
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO printer_supply_alerts (endpoint_id, supply_index, alerted_at)
+             VALUES (?1, ?2, ?3)",
+            params![endpoint_id, supply.index, now],
+        )?;
+        if inserted > 0 {
+            insert_notification_with_endpoint_id(
+                &tx,
+                "printer_supply_low",
+                &format!("{} is low on {}", name, supply.description),
+                Some(&format!("{}% remaining", percent)),
+                Some(&name),
+                Some(endpoint_id),
+            );
+            alerts += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(alerts)
+}
+
+/// A supply's latest level and its history over the report window
+#[derive(Debug, Clone, Serialize)]
+pub struct SupplyReport {
+    #[serde(flatten)]
+    pub supply: PrinterSupply,
+    pub percent: Option<i64>,
+    /// (polled_at, percent) oldest first
+    pub history: Vec<(i64, Option<i64>)>,
+}
+
+/// A printer's latest reading and its history
+#[derive(Debug, Clone, Serialize)]
+pub struct PrinterReport {
+    pub polled_at: Option<i64>,
+    pub status: Option<String>,
+    pub page_count: Option<i64>,
+    /// Pages printed within the report window
+    pub pages_printed: Option<i64>,
+    /// (polled_at, page_count) oldest first
+    pub page_counts: Vec<(i64, i64)>,
+    pub supplies: Vec<SupplyReport>,
+}
+
+/// Readings for one printer since `since`
+pub fn get_printer_report(
+    conn: &Connection,
+    endpoint_id: i64,
+    since: i64,
+) -> Result<PrinterReport> {
+    let latest: Option<(i64, Option<String>, Option<i64>)> = conn
+        .query_row(
+            "SELECT polled_at, status, page_count FROM printer_readings
+             WHERE endpoint_id = ?1 ORDER BY polled_at DESC LIMIT 1",
+            [endpoint_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+
+    let page_counts: Vec<(i64, i64)> = conn
+        .prepare(
+            "SELECT polled_at, page_count FROM printer_readings
+             WHERE endpoint_id = ?1 AND polled_at >= ?2 AND page_count IS NOT NULL
+             ORDER BY polled_at",
+        )?
+        .query_map(params![endpoint_id, since], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<_>>()?;
+    let pages_printed = match (page_counts.first(), page_counts.last()) {
+        (Some((_, first)), Some((_, last))) => Some((last - first).max(0)),
+        _ => None,
+    };
+
+    let mut supplies = Vec::new();
+    if let Some((polled_at, _, _)) = &latest {
+        let mut stmt = conn.prepare(
+            "SELECT supply_index, description, level, max_capacity FROM printer_supply_levels
+             WHERE endpoint_id = ?1 AND polled_at = ?2 ORDER BY supply_index",
+        )?;
+        let current: Vec<PrinterSupply> = stmt
+            .query_map(params![endpoint_id, polled_at], |row| {
+                Ok(PrinterSupply {
+                    index: row.get(0)?,
+                    description: row.get(1)?,
+                    level: row.get(2)?,
+                    max_capacity: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_>>()?;
+
+        let mut history_stmt = conn.prepare(
+            "SELECT polled_at, level, max_capacity FROM printer_supply_levels
+             WHERE endpoint_id = ?1 AND supply_index = ?2 AND polled_at >= ?3
+             ORDER BY polled_at",
+        )?;
+        for supply in current {
+            let history = history_stmt
+                .query_map(params![endpoint_id, supply.index, since], |row| {
+                    let reading = PrinterSupply {
+                        level: row.get(1)?,
+                        max_capacity: row.get(2)?,
+                        ..supply.clone()
+                    };
+                    Ok((row.get(0)?, reading.percent()))
+                })?
+                .collect::<Result<_>>()?;
+            supplies.push(SupplyReport {
+                percent: supply.percent(),
+                supply,
+                history,
+            });
+        }
+    }
+
+    let (polled_at, status, page_count) = match latest {
+        Some((polled_at, status, page_count)) => (Some(polled_at), status, page_count),
+        None => (None, None, None),
+    };
+    Ok(PrinterReport {
+        polled_at,
+        status,
+        page_count,
+        pages_printed,
+        page_counts,
+        supplies,
+    })
+}
+
+/// Fold a printer's readings into the endpoint it is merged into
+pub fn reassign_printer_readings(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    for table in [
+        "printer_readings",
+        "printer_supply_levels",
+        "printer_supply_alerts",
+    ] {
+        conn.execute(
+            &format!(
+                "UPDATE OR IGNORE {} SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+                table
+            ),
+            params![to_id, from_id],
+        )?;
+        conn.execute(
+            &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+            [from_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Poll every printer once and store what they report
+fn poll_printers() -> std::result::Result<(usize, usize), String> {
+    let conn = new_connection_result().map_err(|e| e.to_string())?;
+    let targets = get_printer_targets(&conn).map_err(|e| e.to_string())?;
+    if targets.is_empty() {
+        return Ok((0, 0));
+    }
+    let credentials = load_snmp_credentials(&conn).map_err(|e| e.to_string())?;
+    let scanner = SnmpScanner::new().with_credentials(credentials);
+    let low_percent = get_setting_i64("printer_supply_low_percent", 10);
+
+    let (mut polled, mut alerts) = (0, 0);
+    for target in targets {
+        let Ok(ip) = target.ip.parse::<Ipv4Addr>() else {
+            continue;
+        };
+        if let Some(status) = query_printer(&scanner, ip) {
+            let now = chrono::Utc::now().timestamp();
+            alerts += record_printer_status(&conn, target.endpoint_id, &status, now, low_percent)
+                .map_err(|e| e.to_string())?;
+            polled += 1;
+        }
+    }
+    Ok((polled, alerts))
+}
+
+/// Start the printer poller. Runs every `printer_poll_interval_minutes` (0 disables it).
+pub fn start_printer_poller() {
+    if PRINTER_POLLER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        // Let the startup scan find printers and their SNMP communities first
+        tokio::time::sleep(tokio::time::Duration::from_secs(120)).await;
+
+        loop {
+            let interval_minutes = get_setting_i64("printer_poll_interval_minutes", 30);
+            if interval_minutes > 0 {
+                match task::spawn_blocking(poll_printers).await {
+                    Ok(Ok((_, alerts))) if alerts > 0 => {
+                        println!("{} printer supply alert(s) raised", alerts)
+                    }
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => eprintln!("Printer poll failed: {}", e),
+                    Err(e) => eprintln!("Printer poll task failed: {}", e),
+                }
+            }
+
+            // Check again for a changed interval even while disabled
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                interval_minutes.max(5) as u64 * 60,
+            ))
+            .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn reading(level: i64, page_count: i64) -> PrinterStatus {
+        PrinterStatus {
+            page_count: Some(page_count),
+            status: Some("idle".to_string()),
+            supplies: vec![PrinterSupply {
+                index: 1,
+                description: "Black Toner".to_string(),
+                level,
+                max_capacity: 100,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_low_supply_alerts_once_until_replaced() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                ip TEXT, scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL, details TEXT);
+             INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'office-printer', 'printer'), (2, 1, 'old-printer', 'printer');
+             INSERT INTO scan_results (endpoint_id, ip, scan_type, scanned_at) VALUES
+                 (1, '192.168.1.40', 'snmp', 10);",
+        )
+        .unwrap();
+        create_printer_tables(&conn).unwrap();
+
+        // Only the printer that answered SNMP is polled
+        let targets = get_printer_targets(&conn).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].ip, "192.168.1.40");
+
+        let record = |level, page_count, now| {
+            record_printer_status(&conn, 1, &reading(level, page_count), now, 10).unwrap()
+        };
+        assert_eq!(record(40, 1000, 100), 0);
+        assert_eq!(record(8, 1200, 200), 1);
+        assert_eq!(record(5, 1300, 300), 0);
+        // A new cartridge re-arms the alert
+        assert_eq!(record(100, 1350, 400), 0);
+        assert_eq!(record(9, 1600, 500), 1);
+
+        let report = get_printer_report(&conn, 1, 150).unwrap();
+        assert_eq!(report.polled_at, Some(500));
+        assert_eq!(report.page_count, Some(1600));
+        assert_eq!(report.page_counts.len(), 4);
+        assert_eq!(report.pages_printed, Some(400));
+        assert_eq!(report.supplies.len(), 1);
+        assert_eq!(report.supplies[0].percent, Some(9));
+        assert_eq!(
+            report.supplies[0].history,
+            vec![
+                (200, Some(8)),
+                (300, Some(5)),
+                (400, Some(100)),
+                (500, Some(9))
+            ]
+        );
+    }
+}
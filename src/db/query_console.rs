@@ -0,0 +1,297 @@
+//! Read-only SQL console for admins. A query has to be a single SELECT (or WITH/VALUES)
+//! statement, and it runs on a connection opened read-only with an authorizer that allows
+//! nothing beyond reading: writes, ATTACH, and most pragmas are refused by SQLite itself,
+//! and credential columns read back as NULL. Queries are cut off after a few seconds and
+//! a bounded number of rows.
+
+use std::time::{Duration, Instant};
+
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::get_database_url;
+
+/// Rows returned when the request doesn't ask for fewer
+pub const DEFAULT_QUERY_ROWS: usize = 500;
+/// Most rows a query can return
+pub const MAX_QUERY_ROWS: usize = 5000;
+/// How long a query may run before it is interrupted
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Statements a query may start with
+const ALLOWED_STATEMENTS: &[&str] = &["SELECT", "WITH", "VALUES"];
+
+/// Columns holding credentials, which read back as NULL
+const SECRET_COLUMNS: &[(&str, &str)] = &[
+    ("ipam_sync_config", "token"),
+    ("lg_thinq_auth", "pat_token"),
+    ("lg_tokens", "client_key"),
+    ("samsung_tokens", "token"),
+    ("snmp_credentials", "community"),
+    ("users", "token_hash"),
+    ("webhooks", "secret"),
+];
+
+/// Schema pragmas usable as table-valued functions, e.g. `pragma_table_info('endpoints')`
+const ALLOWED_PRAGMAS: &[&str] = &[
+    "table_info",
+    "table_xinfo",
+    "table_list",
+    "index_list",
+    "index_info",
+    "foreign_key_list",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueryRequest {
+    pub sql: String,
+    /// Row limit; DEFAULT_QUERY_ROWS when missing
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// The query had more rows than the limit
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Check a query is one statement of an allowed kind
+fn check_statement(sql: &str) -> Result<(), String> {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    if sql.is_empty() {
+        return Err("Query is empty".to_string());
+    }
+    let keyword = sql
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    if !ALLOWED_STATEMENTS.contains(&keyword.as_str()) {
+        return Err(format!(
+            "Only {} queries are allowed",
+            ALLOWED_STATEMENTS.join("/")
+        ));
+    }
+    // SQLite would quietly skip anything after the first statement
+    if has_statement_separator(sql) {
+        return Err("Only one statement can be run".to_string());
+    }
+    Ok(())
+}
+
+/// Whether a `;` appears outside quoted strings and identifiers
+fn has_statement_separator(sql: &str) -> bool {
+    let mut quote = None;
+    for c in sql.chars() {
+        match (quote, c) {
+            (None, ';') => return true,
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Allow reading and nothing else
+fn read_only_authorizer(context: AuthContext<'_>) -> Authorization {
+    match context.action {
+        AuthAction::Select | AuthAction::Recursive => Authorization::Allow,
+        AuthAction::Read {
+            table_name,
+            column_name,
+        } => {
+            if SECRET_COLUMNS.contains(&(table_name, column_name)) {
+                Authorization::Ignore
+            } else {
+                Authorization::Allow
+            }
+        }
+        AuthAction::Function { function_name } => {
+            if function_name.eq_ignore_ascii_case("load_extension") {
+                Authorization::Deny
+            } else {
+                Authorization::Allow
+            }
+        }
+        AuthAction::Pragma { pragma_name, .. }
+            if ALLOWED_PRAGMAS.contains(&pragma_name.to_ascii_lowercase().as_str()) =>
+        {
+            Authorization::Allow
+        }
+        _ => Authorization::Deny,
+    }
+}
+
+fn sql_to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(v) => Value::from(v),
+        ValueRef::Real(v) => Value::from(v),
+        ValueRef::Text(t) => Value::from(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Value::from(
+            b.iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+        ),
+    }
+}
+
+/// Open the database read-only for the query console
+pub fn new_read_only_connection() -> rusqlite::Result<Connection> {
+    let db_url = get_database_url();
+    let db_path = db_url.strip_prefix("sqlite://").unwrap_or(&db_url);
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(Duration::from_secs(5))?;
+    Ok(conn)
+}
+
+/// Run a read-only query, returning at most `limit` rows (capped at MAX_QUERY_ROWS).
+/// Errors are messages fit to show the user.
+pub fn run_read_only_query(
+    conn: &Connection,
+    sql: &str,
+    limit: usize,
+) -> Result<QueryResult, String> {
+    check_statement(sql)?;
+    let limit = limit.clamp(1, MAX_QUERY_ROWS);
+    let started = Instant::now();
+
+    conn.authorizer(Some(read_only_authorizer));
+    conn.progress_handler(10_000, Some(move || started.elapsed() > QUERY_TIMEOUT));
+    let result = collect_rows(conn, sql, limit);
+    conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+    conn.progress_handler(10_000, None::<fn() -> bool>);
+
+    let (columns, rows, truncated) = result.map_err(|e| match e {
+        rusqlite::Error::SqliteFailure(err, _)
+            if err.code == rusqlite::ErrorCode::OperationInterrupted =>
+        {
+            format!("Query took longer than {} seconds", QUERY_TIMEOUT.as_secs())
+        }
+        rusqlite::Error::MultipleStatement => "Only one statement can be run".to_string(),
+        e => e.to_string(),
+    })?;
+    Ok(QueryResult {
+        columns,
+        rows,
+        truncated,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+type Rows = (Vec<String>, Vec<Vec<Value>>, bool);
+
+fn collect_rows(conn: &Connection, sql: &str, limit: usize) -> rusqlite::Result<Rows> {
+    let mut stmt = conn.prepare(sql)?;
+    // The authorizer already refuses writes; this catches anything it doesn't know about
+    if !stmt.readonly() {
+        return Err(rusqlite::Error::InvalidQuery);
+    }
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    let mut query = stmt.query([])?;
+    while let Some(row) = query.next()? {
+        if rows.len() == limit {
+            truncated = true;
+            break;
+        }
+        rows.push(
+            (0..columns.len())
+                .map(|i| row.get_ref(i).map(sql_to_json))
+                .collect::<rusqlite::Result<Vec<_>>>()?,
+        );
+    }
+    Ok((columns, rows, truncated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_check_statement() {
+        assert!(check_statement("  select 1;").is_ok());
+        assert!(check_statement("WITH t AS (SELECT 1) SELECT * FROM t").is_ok());
+        assert!(check_statement("").is_err());
+        assert!(check_statement("DELETE FROM endpoints").is_err());
+        assert!(check_statement("PRAGMA journal_mode = DELETE").is_err());
+        assert!(check_statement("ATTACH 'x.db' AS x").is_err());
+        assert!(check_statement("SELECT 1; DELETE FROM endpoints").is_err());
+        assert!(check_statement("SELECT ';', \"a;b\", [c;d] FROM t;").is_ok());
+    }
+
+    #[test]
+    fn test_read_only_query() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES
+                 (1, 1, 'nas'), (2, 1, 'printer'), (3, 1, 'tv');
+             INSERT INTO snmp_credentials (endpoint_id, community, updated_at)
+                 VALUES (1, 'hunter2', 1);",
+        )
+        .unwrap();
+
+        let result =
+            run_read_only_query(&conn, "SELECT id, name FROM endpoints ORDER BY id", 2).unwrap();
+        assert_eq!(result.columns, vec!["id", "name"]);
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.rows[1], vec![Value::from(2), Value::from("printer")]);
+        assert!(result.truncated);
+
+        // Credentials read back as NULL
+        let result = run_read_only_query(
+            &conn,
+            "SELECT endpoint_id, community FROM snmp_credentials",
+            10,
+        )
+        .unwrap();
+        assert_eq!(result.rows, vec![vec![Value::from(1), Value::Null]]);
+
+        // Writes hidden behind an allowed keyword are refused by the authorizer
+        assert!(
+            run_read_only_query(&conn, "WITH t AS (SELECT 1) DELETE FROM endpoints", 10).is_err()
+        );
+        assert!(run_read_only_query(&conn, "SELECT 1; DELETE FROM endpoints", 10).is_err());
+        assert!(
+            run_read_only_query(&conn, "SELECT * FROM pragma_table_info('endpoints')", 10).is_ok()
+        );
+
+        // The authorizer is removed again afterwards
+        conn.execute("DELETE FROM endpoints WHERE id = 3", [])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_token_hash_reads_as_null() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO users (name, role, created_at, token_hash) VALUES ('alice', 'admin', 1, 'abc123')",
+            [],
+        )
+        .unwrap();
+
+        let result = run_read_only_query(&conn, "SELECT name, token_hash FROM users", 10).unwrap();
+        assert_eq!(result.rows, vec![vec![Value::from("alice"), Value::Null]]);
+        let result = run_read_only_query(&conn, "SELECT * FROM users", 10).unwrap();
+        let column = result
+            .columns
+            .iter()
+            .position(|c| c == "token_hash")
+            .unwrap();
+        assert_eq!(result.rows[0][column], Value::Null);
+    }
+}
@@ -0,0 +1,248 @@
+//! Named endpoint table views (type filters, search, sort, visible columns and time
+//! window) saved per user. A view is stored as the query string of the index page, so
+//! applying one is just navigating to it; the user's default view is where `/` lands.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+/// Index page parameters a view may carry. Anything else in a submitted query (the
+/// selected node, the active tab, the page number) is dropped.
+const VIEW_PARAMS: &[&str] = &[
+    "filter_local",
+    "filter_gateway",
+    "filter_internet",
+    "filter_printer",
+    "filter_tv",
+    "filter_gaming",
+    "filter_phone",
+    "filter_virtualization",
+    "filter_soundbar",
+    "filter_appliance",
+    "filter_other",
+    "filter_vendor",
+    "filter_protocol",
+    "search",
+    "known",
+    "unknown",
+    "active",
+    "inactive",
+    "sort",
+    "sort_dir",
+    "page_size",
+    "hidden_columns",
+    "scan_interval",
+    "interface",
+    "exclude_noise",
+];
+
+const MAX_VIEW_NAME_CHARS: usize = 64;
+
+pub fn create_saved_views_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS saved_views (
+            id INTEGER PRIMARY KEY,
+            owner TEXT NOT NULL,
+            name TEXT NOT NULL,
+            query TEXT NOT NULL,
+            is_default INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            UNIQUE(owner, name)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SavedView {
+    pub id: i64,
+    pub name: String,
+    /// Index page query string, without the leading `?`
+    pub query: String,
+    pub is_default: bool,
+    pub updated_at: i64,
+}
+
+/// Trim a view name, rejecting empty and overlong ones
+pub fn parse_view_name(name: &str) -> Option<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_VIEW_NAME_CHARS {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Keep only the view parameters of an index page query string, dropping empty ones
+pub fn normalize_view_query(query: &str) -> String {
+    let query = query.trim_start_matches('?');
+    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        if VIEW_PARAMS.contains(&key.as_ref()) && !value.is_empty() {
+            serializer.append_pair(&key, &value);
+        }
+    }
+    serializer.finish()
+}
+
+/// A user's views, alphabetically
+pub fn get_saved_views(conn: &Connection, owner: &str) -> Result<Vec<SavedView>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, query, is_default, updated_at FROM saved_views
+         WHERE owner = ?1
+         ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([owner], |row| {
+        Ok(SavedView {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            query: row.get(2)?,
+            is_default: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Save a view under `name`, replacing the user's view of the same name. Returns its id.
+pub fn save_view(conn: &Connection, owner: &str, name: &str, query: &str, now: i64) -> Result<i64> {
+    conn.query_row(
+        "INSERT INTO saved_views (owner, name, query, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT(owner, name) DO UPDATE SET
+            query = excluded.query,
+            updated_at = excluded.updated_at
+         RETURNING id",
+        params![owner, name, query, now],
+        |row| row.get(0),
+    )
+}
+
+/// Rename a view and replace its query. Returns false if the user has no such view;
+/// fails if another of their views already has the name.
+pub fn update_view(
+    conn: &Connection,
+    owner: &str,
+    id: i64,
+    name: &str,
+    query: &str,
+    now: i64,
+) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE saved_views SET name = ?1, query = ?2, updated_at = ?3
+         WHERE id = ?4 AND owner = ?5",
+        params![name, query, now, id, owner],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Returns false if the user has no such view
+pub fn delete_view(conn: &Connection, owner: &str, id: i64) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM saved_views WHERE id = ?1 AND owner = ?2",
+        params![id, owner],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Make a view the user's landing view, or with None go back to the plain index page.
+/// Returns false if the user has no such view.
+pub fn set_default_view(conn: &Connection, owner: &str, id: Option<i64>) -> Result<bool> {
+    if let Some(id) = id {
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM saved_views WHERE id = ?1 AND owner = ?2",
+                params![id, owner],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            return Ok(false);
+        }
+    }
+    conn.execute(
+        "UPDATE saved_views SET is_default = (id IS ?1) WHERE owner = ?2",
+        params![id, owner],
+    )?;
+    Ok(true)
+}
+
+/// Query string of the user's default view, if they have one
+pub fn get_default_view_query(conn: &Connection, owner: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT query FROM saved_views WHERE owner = ?1 AND is_default = 1",
+        [owner],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_normalize_view_query() {
+        assert_eq!(
+            normalize_view_query("?node=nas&filter_local=0&search=&sort=bandwidth&tab=scanner"),
+            "filter_local=0&sort=bandwidth"
+        );
+        assert_eq!(
+            normalize_view_query("search=living+room&hidden_columns=model%2Cvendor"),
+            "search=living+room&hidden_columns=model%2Cvendor"
+        );
+        assert_eq!(normalize_view_query(""), "");
+
+        assert_eq!(parse_view_name("  IoT only ").as_deref(), Some("IoT only"));
+        assert!(parse_view_name(" ").is_none());
+        assert!(parse_view_name(&"v".repeat(MAX_VIEW_NAME_CHARS + 1)).is_none());
+    }
+
+    #[test]
+    fn test_saved_views_per_owner() {
+        let conn = new_test_connection();
+        let iot = save_view(&conn, "alice", "IoT", "filter_local=0", 100).unwrap();
+        save_view(&conn, "alice", "all", "", 100).unwrap();
+        save_view(&conn, "bob", "IoT", "sort=name", 100).unwrap();
+
+        // Saving under an existing name replaces that view
+        assert_eq!(
+            save_view(&conn, "alice", "IoT", "filter_local=0&sort=bandwidth", 200).unwrap(),
+            iot
+        );
+        let views = get_saved_views(&conn, "alice").unwrap();
+        assert_eq!(
+            views.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(),
+            vec!["all", "IoT"]
+        );
+        assert_eq!(views[1].query, "filter_local=0&sort=bandwidth");
+        assert_eq!(views[1].updated_at, 200);
+
+        // Other users' views can't be touched
+        let bob_iot = get_saved_views(&conn, "bob").unwrap()[0].id;
+        assert!(!delete_view(&conn, "alice", bob_iot).unwrap());
+        assert!(!update_view(&conn, "alice", bob_iot, "x", "", 300).unwrap());
+        assert!(!set_default_view(&conn, "alice", Some(bob_iot)).unwrap());
+
+        assert!(update_view(&conn, "alice", iot, "IoT 24h", "scan_interval=1440", 300).unwrap());
+        assert!(
+            update_view(&conn, "alice", iot, "all", "", 300).is_err(),
+            "names are unique per user"
+        );
+
+        assert_eq!(get_default_view_query(&conn, "alice").unwrap(), None);
+        assert!(set_default_view(&conn, "alice", Some(iot)).unwrap());
+        assert_eq!(
+            get_default_view_query(&conn, "alice").unwrap().as_deref(),
+            Some("scan_interval=1440")
+        );
+        assert_eq!(get_default_view_query(&conn, "bob").unwrap(), None);
+        assert!(set_default_view(&conn, "alice", None).unwrap());
+        assert_eq!(get_default_view_query(&conn, "alice").unwrap(), None);
+
+        assert!(delete_view(&conn, "alice", iot).unwrap());
+        assert_eq!(get_saved_views(&conn, "alice").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,136 @@
+//! Browsing stored scan results. ARP, ICMP, NDP, NetBIOS, SNMP and SSDP scans each add a
+//! `scan_results` row per responding endpoint; the details column holds the JSON the
+//! scanner recorded, returned here as stored so users can see exactly what a device sent.
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredScanResult {
+    pub id: i64,
+    pub scan_type: String,
+    /// Address the endpoint answered on
+    pub ip: Option<String>,
+    pub scanned_at: i64,
+    pub response_time_ms: Option<i64>,
+    /// The stored details: parsed JSON, or the raw text if it isn't JSON
+    pub details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanTypeCount {
+    pub scan_type: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanResultsPage {
+    /// Results matching the type filter, across all pages
+    pub total: i64,
+    /// Results per scan type for the endpoint, regardless of the filter
+    pub scan_types: Vec<ScanTypeCount>,
+    pub results: Vec<StoredScanResult>,
+}
+
+fn parse_details(details: Option<String>) -> Option<serde_json::Value> {
+    details.map(|text| serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)))
+}
+
+/// An endpoint's scan results, newest first, optionally of one scan type
+pub fn get_scan_results(
+    conn: &Connection,
+    endpoint_id: i64,
+    scan_type: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<ScanResultsPage> {
+    let scan_types = conn
+        .prepare(
+            "SELECT scan_type, COUNT(*) FROM scan_results
+             WHERE endpoint_id = ?1
+             GROUP BY scan_type
+             ORDER BY scan_type",
+        )?
+        .query_map([endpoint_id], |row| {
+            Ok(ScanTypeCount {
+                scan_type: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    let total = conn.query_row(
+        "SELECT COUNT(*) FROM scan_results
+         WHERE endpoint_id = ?1 AND (?2 IS NULL OR scan_type = ?2)",
+        params![endpoint_id, scan_type],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, scan_type, ip, scanned_at, response_time_ms, details
+         FROM scan_results
+         WHERE endpoint_id = ?1 AND (?2 IS NULL OR scan_type = ?2)
+         ORDER BY scanned_at DESC, id DESC
+         LIMIT ?3 OFFSET ?4",
+    )?;
+    let results = stmt
+        .query_map(params![endpoint_id, scan_type, limit, offset], |row| {
+            Ok(StoredScanResult {
+                id: row.get(0)?,
+                scan_type: row.get(1)?,
+                ip: row.get(2)?,
+                scanned_at: row.get(3)?,
+                response_time_ms: row.get(4)?,
+                details: parse_details(row.get(5)?),
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ScanResultsPage {
+        total,
+        scan_types,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_scan_results() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                 scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL, response_time_ms INTEGER,
+                 details TEXT, ip TEXT);
+             INSERT INTO scan_results (endpoint_id, scan_type, scanned_at, response_time_ms, details, ip)
+             VALUES
+                 (1, 'arp', 100, 2, NULL, '192.168.1.20'),
+                 (1, 'snmp', 200, NULL, '{\"sys_descr\":\"HP LaserJet\"}', '192.168.1.20'),
+                 (1, 'snmp', 300, NULL, 'not json', '192.168.1.20'),
+                 (2, 'ssdp', 300, NULL, '{}', '192.168.1.30');",
+        )
+        .unwrap();
+
+        let all = get_scan_results(&conn, 1, None, 10, 0).unwrap();
+        assert_eq!(all.total, 3);
+        assert_eq!(all.results[0].scanned_at, 300);
+        assert_eq!(
+            all.scan_types
+                .iter()
+                .map(|t| (t.scan_type.as_str(), t.count))
+                .collect::<Vec<_>>(),
+            vec![("arp", 1), ("snmp", 2)]
+        );
+
+        let snmp = get_scan_results(&conn, 1, Some("snmp"), 1, 1).unwrap();
+        assert_eq!(snmp.total, 2);
+        assert_eq!(snmp.results.len(), 1);
+        assert_eq!(
+            snmp.results[0].details,
+            Some(serde_json::json!({ "sys_descr": "HP LaserJet" }))
+        );
+        let raw = get_scan_results(&conn, 1, Some("snmp"), 1, 0).unwrap();
+        assert_eq!(raw.results[0].details, Some("not json".into()));
+    }
+}
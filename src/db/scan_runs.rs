@@ -0,0 +1,468 @@
+//! Scan run history and diffing. Each completed scan records a snapshot of the hosts,
+//! names and open ports it observed, so two runs can be compared for hosts that
+//! appeared or disappeared, ports that opened or closed, and names that changed.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+/// A host as observed by one scan run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanHost {
+    pub endpoint_id: i64,
+    pub name: Option<String>,
+    pub ips: Vec<String>,
+    pub ports: Vec<ScanPort>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScanPort {
+    pub port: u16,
+    pub protocol: String,
+}
+
+/// Everything a scan run saw, keyed by endpoint id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanSnapshot {
+    pub hosts: Vec<ScanHost>,
+}
+
+/// Scan run metadata (without its snapshot)
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanRun {
+    pub id: i64,
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub scan_types: Vec<String>,
+    pub host_count: i64,
+}
+
+impl ScanRun {
+    fn includes(&self, scan_type: &str) -> bool {
+        self.scan_types.iter().any(|t| t == scan_type)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostChange {
+    pub endpoint_id: i64,
+    pub name: Option<String>,
+    pub ips: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortChange {
+    pub endpoint_id: i64,
+    pub name: Option<String>,
+    pub port: u16,
+    pub protocol: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NameChange {
+    pub endpoint_id: i64,
+    pub old_name: Option<String>,
+    pub new_name: Option<String>,
+}
+
+/// Differences between two scan snapshots
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanChangeset {
+    pub appeared: Vec<HostChange>,
+    pub disappeared: Vec<HostChange>,
+    pub ports_opened: Vec<PortChange>,
+    pub ports_closed: Vec<PortChange>,
+    pub renamed: Vec<NameChange>,
+}
+
+impl ScanChangeset {
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty()
+            && self.disappeared.is_empty()
+            && self.ports_opened.is_empty()
+            && self.ports_closed.is_empty()
+            && self.renamed.is_empty()
+    }
+
+    pub fn change_count(&self) -> usize {
+        self.appeared.len()
+            + self.disappeared.len()
+            + self.ports_opened.len()
+            + self.ports_closed.len()
+            + self.renamed.len()
+    }
+
+    /// One-line human readable summary, e.g. "2 hosts appeared, 1 port opened"
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = [
+            (self.appeared.len(), "host", "appeared"),
+            (self.disappeared.len(), "host", "disappeared"),
+            (self.ports_opened.len(), "port", "opened"),
+            (self.ports_closed.len(), "port", "closed"),
+            (self.renamed.len(), "name", "changed"),
+        ]
+        .into_iter()
+        .filter(|(count, _, _)| *count > 0)
+        .map(|(count, noun, verb)| {
+            format!(
+                "{} {}{} {}",
+                count,
+                noun,
+                if count == 1 { "" } else { "s" },
+                verb
+            )
+        })
+        .collect();
+        if parts.is_empty() {
+            "No changes".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+pub fn create_scan_runs_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scan_runs (
+            id INTEGER PRIMARY KEY,
+            started_at INTEGER NOT NULL,
+            finished_at INTEGER NOT NULL,
+            scan_types TEXT NOT NULL,
+            host_count INTEGER NOT NULL DEFAULT 0,
+            snapshot TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_scan_runs_finished ON scan_runs (finished_at DESC)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Build a snapshot of the hosts (and their open ports) that produced scan results
+/// at or after `since`
+pub fn build_scan_snapshot(conn: &Connection, since: i64) -> Result<ScanSnapshot> {
+    let mut hosts: BTreeMap<i64, ScanHost> = BTreeMap::new();
+
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.display_name
+         FROM endpoints e
+         WHERE e.id IN (SELECT endpoint_id FROM scan_results WHERE scanned_at >= ?1)",
+    )?;
+    let rows = stmt.query_map([since], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?;
+    for row in rows {
+        let (endpoint_id, name) = row?;
+        hosts.insert(
+            endpoint_id,
+            ScanHost {
+                endpoint_id,
+                name,
+                ips: Vec::new(),
+                ports: Vec::new(),
+            },
+        );
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT endpoint_id, ip FROM scan_results
+         WHERE scanned_at >= ?1 AND ip IS NOT NULL AND ip != ''
+         ORDER BY ip",
+    )?;
+    let rows = stmt.query_map([since], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in rows {
+        let (endpoint_id, ip) = row?;
+        if let Some(host) = hosts.get_mut(&endpoint_id) {
+            host.ips.push(ip);
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT endpoint_id, port, COALESCE(protocol, 'tcp') FROM open_ports
+         WHERE last_seen_at >= ?1
+         ORDER BY port, protocol",
+    )?;
+    let rows = stmt.query_map([since], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            ScanPort {
+                port: row.get(1)?,
+                protocol: row.get(2)?,
+            },
+        ))
+    })?;
+    for row in rows {
+        let (endpoint_id, port) = row?;
+        if let Some(host) = hosts.get_mut(&endpoint_id) {
+            host.ports.push(port);
+        }
+    }
+
+    Ok(ScanSnapshot {
+        hosts: hosts.into_values().collect(),
+    })
+}
+
+/// Record a completed scan run along with a snapshot of what it observed.
+/// Returns the new run id.
+pub fn record_scan_run(
+    conn: &Connection,
+    started_at: i64,
+    finished_at: i64,
+    scan_types: &[String],
+) -> Result<i64> {
+    let snapshot = build_scan_snapshot(conn, started_at)?;
+    let snapshot_json = serde_json::to_string(&snapshot).unwrap_or_else(|_| "{}".to_string());
+    conn.execute(
+        "INSERT INTO scan_runs (started_at, finished_at, scan_types, host_count, snapshot)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            started_at,
+            finished_at,
+            scan_types.join(","),
+            snapshot.hosts.len() as i64,
+            snapshot_json
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn scan_run_from_row(row: &rusqlite::Row) -> Result<ScanRun> {
+    let scan_types: String = row.get(3)?;
+    Ok(ScanRun {
+        id: row.get(0)?,
+        started_at: row.get(1)?,
+        finished_at: row.get(2)?,
+        scan_types: scan_types
+            .split(',')
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect(),
+        host_count: row.get(4)?,
+    })
+}
+
+/// Most recent scan runs first
+pub fn get_scan_runs(conn: &Connection, limit: i64) -> Result<Vec<ScanRun>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, started_at, finished_at, scan_types, host_count FROM scan_runs
+         ORDER BY finished_at DESC, id DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map([limit], scan_run_from_row)?;
+    rows.collect()
+}
+
+/// Load a scan run and its snapshot
+pub fn get_scan_run(conn: &Connection, id: i64) -> Result<Option<(ScanRun, ScanSnapshot)>> {
+    conn.query_row(
+        "SELECT id, started_at, finished_at, scan_types, host_count, snapshot
+         FROM scan_runs WHERE id = ?1",
+        [id],
+        |row| {
+            let snapshot: String = row.get(5)?;
+            Ok((
+                scan_run_from_row(row)?,
+                serde_json::from_str(&snapshot).unwrap_or_default(),
+            ))
+        },
+    )
+    .optional()
+}
+
+/// The latest run that finished before `run_id`
+pub fn get_previous_scan_run_id(conn: &Connection, run_id: i64) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT r.id FROM scan_runs r, scan_runs current
+         WHERE current.id = ?1
+           AND (r.finished_at < current.finished_at
+                OR (r.finished_at = current.finished_at AND r.id < current.id))
+         ORDER BY r.finished_at DESC, r.id DESC LIMIT 1",
+        [run_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// The latest run that finished at or before `timestamp`
+pub fn get_scan_run_id_before(conn: &Connection, timestamp: i64) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM scan_runs WHERE finished_at <= ?1
+         ORDER BY finished_at DESC, id DESC LIMIT 1",
+        [timestamp],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Compare two scan runs. Port changes are only reported when both runs included a
+/// port scan; otherwise every port would look closed (or opened) by omission.
+pub fn diff_scan_runs(
+    from: (&ScanRun, &ScanSnapshot),
+    to: (&ScanRun, &ScanSnapshot),
+) -> ScanChangeset {
+    let compare_ports = from.0.includes("port") && to.0.includes("port");
+    diff_scan_snapshots(from.1, to.1, compare_ports)
+}
+
+/// Compare two snapshots. Hosts are matched by endpoint id.
+pub fn diff_scan_snapshots(
+    from: &ScanSnapshot,
+    to: &ScanSnapshot,
+    compare_ports: bool,
+) -> ScanChangeset {
+    let before: BTreeMap<i64, &ScanHost> = from.hosts.iter().map(|h| (h.endpoint_id, h)).collect();
+    let after: BTreeMap<i64, &ScanHost> = to.hosts.iter().map(|h| (h.endpoint_id, h)).collect();
+    let host_change = |h: &ScanHost| HostChange {
+        endpoint_id: h.endpoint_id,
+        name: h.name.clone(),
+        ips: h.ips.clone(),
+    };
+
+    let mut changes = ScanChangeset {
+        appeared: after
+            .iter()
+            .filter(|(id, _)| !before.contains_key(id))
+            .map(|(_, h)| host_change(h))
+            .collect(),
+        disappeared: before
+            .iter()
+            .filter(|(id, _)| !after.contains_key(id))
+            .map(|(_, h)| host_change(h))
+            .collect(),
+        ..Default::default()
+    };
+
+    for (id, new) in &after {
+        let Some(old) = before.get(id) else {
+            continue;
+        };
+
+        if old.name != new.name {
+            changes.renamed.push(NameChange {
+                endpoint_id: *id,
+                old_name: old.name.clone(),
+                new_name: new.name.clone(),
+            });
+        }
+
+        if compare_ports {
+            let old_ports: BTreeSet<&ScanPort> = old.ports.iter().collect();
+            let new_ports: BTreeSet<&ScanPort> = new.ports.iter().collect();
+            let port_change = |p: &ScanPort| PortChange {
+                endpoint_id: *id,
+                name: new.name.clone(),
+                port: p.port,
+                protocol: p.protocol.clone(),
+            };
+            changes
+                .ports_opened
+                .extend(new_ports.difference(&old_ports).map(|p| port_change(p)));
+            changes
+                .ports_closed
+                .extend(old_ports.difference(&new_ports).map(|p| port_change(p)));
+        }
+    }
+
+    changes
+}
+
+/// Delete scan runs that finished more than `retention_days` ago
+pub fn purge_scan_runs(conn: &Connection, retention_days: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM scan_runs WHERE finished_at < (strftime('%s', 'now') - ?1)",
+        [retention_days * 24 * 60 * 60],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn host(endpoint_id: i64, name: &str, ports: &[u16]) -> ScanHost {
+        ScanHost {
+            endpoint_id,
+            name: Some(name.to_string()),
+            ips: vec![format!("192.168.1.{}", endpoint_id)],
+            ports: ports
+                .iter()
+                .map(|&port| ScanPort {
+                    port,
+                    protocol: "tcp".to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_scan_snapshots() {
+        let from = ScanSnapshot {
+            hosts: vec![host(1, "nas", &[22, 80]), host(2, "printer", &[631])],
+        };
+        let to = ScanSnapshot {
+            hosts: vec![host(1, "nas.local", &[22, 443]), host(3, "phone", &[])],
+        };
+
+        let changes = diff_scan_snapshots(&from, &to, true);
+        assert_eq!(changes.appeared.len(), 1);
+        assert_eq!(changes.appeared[0].endpoint_id, 3);
+        assert_eq!(changes.disappeared.len(), 1);
+        assert_eq!(changes.disappeared[0].endpoint_id, 2);
+        assert_eq!(changes.renamed.len(), 1);
+        assert_eq!(changes.renamed[0].new_name.as_deref(), Some("nas.local"));
+        assert_eq!(changes.ports_opened.len(), 1);
+        assert_eq!(changes.ports_opened[0].port, 443);
+        assert_eq!(changes.ports_closed.len(), 1);
+        assert_eq!(changes.ports_closed[0].port, 80);
+        assert_eq!(
+            changes.summary(),
+            "1 host appeared, 1 host disappeared, 1 port opened, 1 port closed, 1 name changed"
+        );
+
+        let without_ports = diff_scan_snapshots(&from, &to, false);
+        assert!(without_ports.ports_opened.is_empty());
+        assert!(without_ports.ports_closed.is_empty());
+        assert!(diff_scan_snapshots(&to, &to, true).is_empty());
+    }
+
+    #[test]
+    fn test_record_scan_run_snapshot() {
+        let conn = new_test_connection();
+        create_scan_runs_table(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                 scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL,
+                 response_time_ms INTEGER, details TEXT, ip TEXT);
+             INSERT INTO endpoints (id, created_at, name, custom_name) VALUES
+                 (1, 1, 'nas', NULL), (2, 1, 'printer', 'Office Printer');
+             INSERT INTO scan_results (endpoint_id, scan_type, scanned_at, ip) VALUES
+                 (1, 'arp', 50, '192.168.1.1'),
+                 (2, 'arp', 150, '192.168.1.2');
+             INSERT INTO open_ports (endpoint_id, port, last_seen_at) VALUES
+                 (2, 631, 150), (2, 9100, 50);",
+        )
+        .unwrap();
+
+        let run_id =
+            record_scan_run(&conn, 100, 200, &["arp".to_string(), "port".to_string()]).unwrap();
+        let (run, snapshot) = get_scan_run(&conn, run_id).unwrap().unwrap();
+        assert_eq!(run.host_count, 1);
+        assert_eq!(run.scan_types, vec!["arp", "port"]);
+        assert_eq!(snapshot.hosts.len(), 1);
+        assert_eq!(snapshot.hosts[0].name.as_deref(), Some("Office Printer"));
+        assert_eq!(snapshot.hosts[0].ips, vec!["192.168.1.2"]);
+        assert_eq!(snapshot.hosts[0].ports.len(), 1);
+        assert_eq!(snapshot.hosts[0].ports[0].port, 631);
+
+        let later = record_scan_run(&conn, 300, 400, &["arp".to_string()]).unwrap();
+        assert_eq!(
+            get_previous_scan_run_id(&conn, later).unwrap(),
+            Some(run_id)
+        );
+        assert_eq!(get_scan_run_id_before(&conn, 250).unwrap(), Some(run_id));
+        assert_eq!(get_scan_run_id_before(&conn, 10).unwrap(), None);
+    }
+}
@@ -0,0 +1,560 @@
+//! The settings that can be changed at runtime: each key's type, allowed range, default,
+//! and what it does. Defaults are seeded into the `settings` table on startup, and
+//! `POST /api/settings` checks values against this list before storing them, so a typo or
+//! a negative interval is turned away instead of being read back as the fallback.
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+use super::backup::check_backup_directory;
+use crate::config::ValueSource;
+use crate::scanner::exposure::WanIpSource;
+use crate::scanner::manager::{SCAN_CONFIG_SETTING, ScanConfig};
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Extra check for text settings with their own syntax
+type SettingCheck = fn(&str) -> std::result::Result<(), String>;
+
+/// The kind of value a setting holds
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SettingType {
+    Integer {
+        min: i64,
+        max: i64,
+    },
+    Boolean,
+    Choice {
+        options: &'static [&'static str],
+    },
+    /// Free text; may be empty
+    Text,
+    /// Comma-separated values
+    List,
+    /// An http(s) URL, or empty
+    Url,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SettingDefinition {
+    pub key: &'static str,
+    #[serde(flatten)]
+    pub setting_type: SettingType,
+    pub default: &'static str,
+    pub description: &'static str,
+    #[serde(skip)]
+    check: Option<SettingCheck>,
+}
+
+const fn integer(
+    key: &'static str,
+    min: i64,
+    max: i64,
+    default: &'static str,
+    description: &'static str,
+) -> SettingDefinition {
+    SettingDefinition {
+        key,
+        setting_type: SettingType::Integer { min, max },
+        default,
+        description,
+        check: None,
+    }
+}
+
+const fn other(
+    key: &'static str,
+    setting_type: SettingType,
+    default: &'static str,
+    description: &'static str,
+) -> SettingDefinition {
+    SettingDefinition {
+        key,
+        setting_type,
+        default,
+        description,
+        check: None,
+    }
+}
+
+fn check_wan_ip_source(value: &str) -> std::result::Result<(), String> {
+    WanIpSource::parse(value)
+        .map(|_| ())
+        .ok_or_else(|| "expected \"gateway\", \"stun:host[:port]\", or an http(s) URL".to_string())
+}
+
+fn check_scan_config(value: &str) -> std::result::Result<(), String> {
+    ScanConfig::from_json(value).map(|_| ())
+}
+
+/// Every runtime setting, in the order a settings page would list them
+pub const SETTINGS: &[SettingDefinition] = &[
+    integer(
+        "cleanup_interval_seconds",
+        1,
+        3600,
+        "30",
+        "How often old data is cleaned up, in seconds",
+    ),
+    integer(
+        "data_retention_days",
+        1,
+        3650,
+        "7",
+        "Days of captured communications to keep",
+    ),
+    integer(
+        "active_threshold_seconds",
+        1,
+        DAY_SECS,
+        "120",
+        "Seconds since last traffic for an endpoint to count as online",
+    ),
+    integer(
+        "auto_scan_interval_minutes",
+        0,
+        7 * 24 * 60,
+        "0",
+        "Minutes between automatic scans from the web UI (0 disables)",
+    ),
+    integer(
+        "audit_retention_days",
+        1,
+        3650,
+        "90",
+        "Days of audit log to keep",
+    ),
+    integer(
+        "archived_endpoint_retention_days",
+        1,
+        3650,
+        "30",
+        "Days a deleted endpoint can still be restored",
+    ),
+    integer(
+        "ip_history_retention_days",
+        1,
+        3650,
+        "90",
+        "Days of closed IP address history to keep",
+    ),
+    integer(
+        "usage_retention_days",
+        1,
+        3650,
+        "30",
+        "Days of hourly usage rollups to keep",
+    ),
+    integer(
+        "ip_binding_lease_minutes",
+        1,
+        7 * 24 * 60,
+        "240",
+        "Minutes an IP stays bound to a MAC without being seen again",
+    ),
+    integer(
+        "host_table_interval_seconds",
+        0,
+        DAY_SECS,
+        "300",
+        "Seconds between reads of the host's ARP/NDP tables (0 disables)",
+    ),
+    integer(
+        "scan_run_retention_days",
+        1,
+        3650,
+        "90",
+        "Days of scan history to keep",
+    ),
+    SettingDefinition {
+        key: SCAN_CONFIG_SETTING,
+        setting_type: SettingType::Text,
+        default: "",
+        description: "Scan config as saved by POST /api/scan/config, including per scan type timeouts, retries, parallelism and rate caps (empty uses the defaults)",
+        check: Some(check_scan_config),
+    },
+    integer(
+        "notification_dedup_cooldown_seconds",
+        0,
+        7 * DAY_SECS,
+        "3600",
+        "Repeats of a notification within this many seconds are counted, not listed again (0 disables)",
+    ),
+    other(
+        "capture_mode",
+        SettingType::Choice {
+            options: &["packet", "flow"],
+        },
+        "packet",
+        "Store every packet, or aggregate packets into flows",
+    ),
+    other(
+        "capture_paused",
+        SettingType::Boolean,
+        "false",
+        "Capture is paused",
+    ),
+    integer(
+        "flow_aggregation_interval_seconds",
+        1,
+        3600,
+        "10",
+        "Seconds packets are aggregated for in flow mode",
+    ),
+    other(
+        "store_broadcast_traffic",
+        SettingType::Boolean,
+        "true",
+        "Store broadcast and multicast packets as communications",
+    ),
+    other(
+        "privacy_mode",
+        SettingType::Boolean,
+        "false",
+        "Keep the device inventory only: no visited site names or payload parsing, internet addresses cut to /24",
+    ),
+    other(
+        "snmp_communities",
+        SettingType::List,
+        "public,private",
+        "SNMP communities tried when probing devices",
+    ),
+    other(
+        "dns_allowed_servers",
+        SettingType::List,
+        "",
+        "DNS servers and DoH hosts devices may use without a bypass warning",
+    ),
+    other(
+        "blocklist_bundled_enabled",
+        SettingType::Boolean,
+        "false",
+        "Match traffic against the bundled blocklist",
+    ),
+    other(
+        "default_credentials_check",
+        SettingType::Boolean,
+        "false",
+        "After port scans, try factory logins on routers, printers and cameras (opt-in)",
+    ),
+    other(
+        "upnp_event_subscriptions",
+        SettingType::Boolean,
+        "false",
+        "Subscribe to UPnP device events for live playback and WAN status (needs the dashboard on all interfaces)",
+    ),
+    integer(
+        "baseline_learning_days",
+        1,
+        365,
+        "7",
+        "Days a device type's connections are learned before its baseline can be enforced",
+    ),
+    integer(
+        "certificate_expiry_warning_days",
+        1,
+        365,
+        "30",
+        "Warn about TLS certificates expiring within this many days",
+    ),
+    integer(
+        "printer_poll_interval_minutes",
+        0,
+        24 * 60,
+        "30",
+        "Minutes between polls of printer supply levels and page counts over SNMP (0 disables)",
+    ),
+    integer(
+        "printer_supply_low_percent",
+        1,
+        100,
+        "10",
+        "Warn when a printer supply drops to this percent or below",
+    ),
+    integer(
+        "nas_poll_interval_minutes",
+        0,
+        24 * 60,
+        "15",
+        "Minutes between polls of NAS volume and disk health over SNMP (0 disables)",
+    ),
+    integer(
+        "ups_poll_interval_seconds",
+        0,
+        60 * 60,
+        "60",
+        "Seconds between polls of UPS battery status over SNMP and NUT (0 disables)",
+    ),
+    integer(
+        "ups_low_battery_percent",
+        1,
+        100,
+        "20",
+        "Alert when a UPS on battery drops to this charge percent or below",
+    ),
+    integer(
+        "presence_arrive_delay_minutes",
+        0,
+        60,
+        "0",
+        "Minutes a person's device must be online before they count as arrived",
+    ),
+    integer(
+        "presence_leave_delay_minutes",
+        0,
+        24 * 60,
+        "10",
+        "Minutes all of a person's devices must be offline before they count as gone",
+    ),
+    SettingDefinition {
+        key: "exposure_wan_ip_source",
+        setting_type: SettingType::Text,
+        default: "gateway",
+        description: "Where the WAN address comes from: \"gateway\" (UPnP), \"stun:host[:port]\", or an http(s) URL",
+        check: Some(check_wan_ip_source),
+    },
+    other(
+        "exposure_scanner_url",
+        SettingType::Url,
+        "",
+        "External service that probes the WAN address (empty probes from inside)",
+    ),
+    SettingDefinition {
+        key: "backup_directory",
+        setting_type: SettingType::Text,
+        default: "",
+        description: "Directory for scheduled backups, relative to the database's directory (empty disables them)",
+        check: Some(check_backup_directory),
+    },
+    integer(
+        "backup_interval_hours",
+        1,
+        24 * 365,
+        "24",
+        "Hours between scheduled backups",
+    ),
+    integer(
+        "backup_keep_count",
+        1,
+        1000,
+        "7",
+        "Scheduled backups kept before the oldest is removed",
+    ),
+    integer(
+        "ipam_sync_interval_minutes",
+        5,
+        7 * 24 * 60,
+        "60",
+        "Minutes between pushes to NetBox/phpIPAM",
+    ),
+    other(
+        "device_rules_file",
+        SettingType::Text,
+        "device_rules.toml",
+        "Device rules loaded at runtime, in addition to the built-in ones",
+    ),
+    other(
+        "oui_overrides_file",
+        SettingType::Text,
+        "oui_overrides.toml",
+        "Vendor overrides by MAC prefix, loaded at runtime",
+    ),
+];
+
+fn setting_definition(key: &str) -> Option<&'static SettingDefinition> {
+    SETTINGS.iter().find(|setting| setting.key == key)
+}
+
+/// Check a value for a setting and return it in the form it is stored in. An empty value
+/// restores the default.
+pub fn validate_setting(key: &str, value: &str) -> std::result::Result<String, String> {
+    let setting = setting_definition(key).ok_or_else(|| format!("Unknown setting '{}'", key))?;
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(setting.default.to_string());
+    }
+
+    let invalid = |reason: String| format!("Invalid value for '{}': {}", key, reason);
+    let value = match setting.setting_type {
+        SettingType::Integer { min, max } => {
+            let number: i64 = value
+                .parse()
+                .map_err(|_| invalid(format!("'{}' is not a whole number", value)))?;
+            if !(min..=max).contains(&number) {
+                return Err(invalid(format!("must be between {} and {}", min, max)));
+            }
+            number.to_string()
+        }
+        SettingType::Boolean => match value.to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => "true".to_string(),
+            "false" | "0" | "no" | "off" => "false".to_string(),
+            _ => return Err(invalid("expected true or false".to_string())),
+        },
+        SettingType::Choice { options } => {
+            let choice = value.to_lowercase();
+            if !options.contains(&choice.as_str()) {
+                return Err(invalid(format!("expected one of: {}", options.join(", "))));
+            }
+            choice
+        }
+        SettingType::Text => value.to_string(),
+        SettingType::List => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<_>>()
+            .join(","),
+        SettingType::Url => match url::Url::parse(value) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => value.to_string(),
+            _ => return Err(invalid("expected an http(s) URL".to_string())),
+        },
+    };
+    if let Some(check) = setting.check {
+        check(&value).map_err(invalid)?;
+    }
+    Ok(value)
+}
+
+/// Fill in every setting the user hasn't changed in the UI: the value from the config file
+/// or environment in `layers` when there is one, else the default. The `source` column
+/// tells the two apart; rows written through the settings API are left alone.
+pub fn insert_default_settings(
+    conn: &Connection,
+    layers: &[(&str, String, ValueSource)],
+) -> Result<()> {
+    // Migration: add the source column. Existing values are taken as the user's own
+    // unless they still hold the default.
+    let has_source: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('settings') WHERE name = 'source'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_source {
+        conn.execute("ALTER TABLE settings ADD COLUMN source TEXT", [])?;
+        for setting in SETTINGS {
+            conn.execute(
+                "UPDATE settings SET source = 'default' WHERE key = ?1 AND value = ?2",
+                params![setting.key, setting.default],
+            )?;
+        }
+        conn.execute(
+            "UPDATE settings SET source = 'database' WHERE source IS NULL",
+            [],
+        )?;
+    }
+
+    for setting in SETTINGS {
+        let (value, source) = layers
+            .iter()
+            .find(|(key, _, _)| *key == setting.key)
+            .map(|(_, value, source)| (value.as_str(), *source))
+            .unwrap_or((setting.default, ValueSource::Default));
+        conn.execute(
+            "INSERT INTO settings (key, value, source) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, source = excluded.source
+             WHERE settings.source IS NOT NULL AND settings.source != 'database'",
+            params![setting.key, value, source.as_str()],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_setting() {
+        assert_eq!(
+            validate_setting("data_retention_days", " 14 ").unwrap(),
+            "14"
+        );
+        assert!(
+            validate_setting("data_retention_days", "-1")
+                .unwrap_err()
+                .contains("between 1 and 3650")
+        );
+        assert!(validate_setting("data_retention_days", "week").is_err());
+        assert_eq!(validate_setting("data_retention_days", "").unwrap(), "7");
+        assert_eq!(
+            validate_setting("store_broadcast_traffic", "Off").unwrap(),
+            "false"
+        );
+        assert_eq!(validate_setting("capture_mode", "FLOW").unwrap(), "flow");
+        assert!(validate_setting("capture_mode", "sampled").is_err());
+        assert_eq!(
+            validate_setting("snmp_communities", "public, ,lan ").unwrap(),
+            "public,lan"
+        );
+        assert!(validate_setting("exposure_scanner_url", "ftp://scanner").is_err());
+        assert!(validate_setting("exposure_wan_ip_source", "stun:stun.l.google.com").is_ok());
+        assert!(validate_setting("exposure_wan_ip_source", "upnp").is_err());
+        assert!(validate_setting("no_such_setting", "1").is_err());
+
+        // Every default passes its own validation
+        for setting in SETTINGS {
+            if !setting.default.is_empty() {
+                assert_eq!(
+                    validate_setting(setting.key, setting.default).as_deref(),
+                    Ok(setting.default),
+                    "{}",
+                    setting.key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_config_values_yield_to_settings_changed_in_ui() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL, updated_at INTEGER);
+             INSERT INTO settings (key, value) VALUES
+                 ('data_retention_days', '7'), ('cleanup_interval_seconds', '60');",
+        )
+        .unwrap();
+        let value = |key: &str| -> (String, Option<String>) {
+            conn.query_row(
+                "SELECT value, source FROM settings WHERE key = ?1",
+                [key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+        };
+
+        // An older database: the changed interval counts as the user's own
+        let layers = [
+            (
+                "data_retention_days",
+                "14".to_string(),
+                ValueSource::ConfigFile,
+            ),
+            (
+                "cleanup_interval_seconds",
+                "10".to_string(),
+                ValueSource::ConfigFile,
+            ),
+        ];
+        insert_default_settings(&conn, &layers).unwrap();
+        assert_eq!(
+            value("data_retention_days"),
+            ("14".into(), Some("config_file".into()))
+        );
+        assert_eq!(
+            value("cleanup_interval_seconds"),
+            ("60".into(), Some("database".into()))
+        );
+        assert_eq!(
+            value("capture_mode"),
+            ("packet".into(), Some("default".into()))
+        );
+
+        // Dropping the option from the file brings the default back
+        insert_default_settings(&conn, &[]).unwrap();
+        assert_eq!(
+            value("data_retention_days"),
+            ("7".into(), Some("default".into()))
+        );
+    }
+}
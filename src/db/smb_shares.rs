@@ -0,0 +1,134 @@
+//! SMB shares found by NetBIOS scans, stored per endpoint. Each successful enumeration
+//! replaces the endpoint's list, so shares that were removed on the device disappear.
+
+use rusqlite::{Connection, Result, params};
+
+use crate::scanner::SmbShare;
+
+pub fn create_smb_shares_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS smb_shares (
+            id INTEGER PRIMARY KEY,
+            endpoint_id INTEGER NOT NULL REFERENCES endpoints(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            share_type TEXT NOT NULL,
+            remark TEXT,
+            guest_readable INTEGER NOT NULL DEFAULT 0,
+            last_seen_at INTEGER NOT NULL,
+            UNIQUE(endpoint_id, name)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Replace an endpoint's shares with the ones seen at `now`
+pub fn replace_smb_shares(
+    conn: &Connection,
+    endpoint_id: i64,
+    shares: &[SmbShare],
+    now: i64,
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM smb_shares WHERE endpoint_id = ?1",
+        [endpoint_id],
+    )?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR REPLACE INTO smb_shares
+            (endpoint_id, name, share_type, remark, guest_readable, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for share in shares {
+        stmt.execute(params![
+            endpoint_id,
+            share.name,
+            share.share_type,
+            share.remark,
+            share.guest_readable,
+            now
+        ])?;
+    }
+    Ok(())
+}
+
+/// An endpoint's shares, guest-readable ones first
+pub fn get_smb_shares(conn: &Connection, endpoint_id: i64) -> Result<Vec<SmbShare>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, share_type, remark, guest_readable FROM smb_shares
+         WHERE endpoint_id = ?1
+         ORDER BY guest_readable DESC, name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([endpoint_id], |row| {
+        Ok(SmbShare {
+            name: row.get(0)?,
+            share_type: row.get(1)?,
+            remark: row.get(2)?,
+            guest_readable: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Move an endpoint's shares to the endpoint it is merged into
+pub fn reassign_smb_shares(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE smb_shares SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute("DELETE FROM smb_shares WHERE endpoint_id = ?1", [from_id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn share(name: &str, guest_readable: bool) -> SmbShare {
+        SmbShare {
+            name: name.to_string(),
+            share_type: "disk".to_string(),
+            remark: None,
+            guest_readable,
+        }
+    }
+
+    #[test]
+    fn test_replace_and_reassign_smb_shares() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'nas'), (2, 1, 'nas-old')",
+            [],
+        )
+        .unwrap();
+
+        replace_smb_shares(
+            &conn,
+            1,
+            &[share("media", true), share("backup", false)],
+            10,
+        )
+        .unwrap();
+        replace_smb_shares(
+            &conn,
+            1,
+            &[share("media", true), share("photos", false)],
+            20,
+        )
+        .unwrap();
+        let names: Vec<String> = get_smb_shares(&conn, 1)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["media", "photos"]);
+
+        replace_smb_shares(&conn, 2, &[share("media", false), share("old", false)], 5).unwrap();
+        reassign_smb_shares(&conn, 2, 1).unwrap();
+        let shares = get_smb_shares(&conn, 1).unwrap();
+        assert_eq!(shares.len(), 3);
+        // The target's own row wins over the merged one
+        assert!(shares.iter().any(|s| s.name == "media" && s.guest_readable));
+        assert!(get_smb_shares(&conn, 2).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+//! SNMP credentials. The ordered community list comes from the `snmp_communities`
+//! setting. Per-endpoint overrides live in their own table and are write-only: the API
+//! only reports whether one is set, and they are never logged, archived, or copied into
+//! scan results. The list community that last answered for an address is read back from
+//! `scan_results` so the next probe tries it first.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+use crate::scanner::snmp::{COMMUNITY_STRINGS, SnmpCredentials};
+
+pub fn create_snmp_credentials_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS snmp_credentials (
+            endpoint_id INTEGER PRIMARY KEY REFERENCES endpoints(id) ON DELETE CASCADE,
+            community TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Split the `snmp_communities` setting (comma or newline separated) into an ordered,
+/// de-duplicated list
+pub fn parse_snmp_communities(value: &str) -> Vec<String> {
+    let mut communities: Vec<String> = Vec::new();
+    for community in value.split([',', '\n']).map(str::trim) {
+        if !community.is_empty() && !communities.iter().any(|c| c == community) {
+            communities.push(community.to_string());
+        }
+    }
+    communities
+}
+
+/// Set (or with `None`, clear) an endpoint's own community
+pub fn set_snmp_credential(
+    conn: &Connection,
+    endpoint_id: i64,
+    community: Option<&str>,
+) -> Result<()> {
+    match community {
+        Some(community) => conn.execute(
+            "INSERT INTO snmp_credentials (endpoint_id, community, updated_at)
+             VALUES (?1, ?2, strftime('%s', 'now'))
+             ON CONFLICT(endpoint_id) DO UPDATE SET
+                community = excluded.community,
+                updated_at = excluded.updated_at",
+            params![endpoint_id, community],
+        )?,
+        None => conn.execute(
+            "DELETE FROM snmp_credentials WHERE endpoint_id = ?1",
+            [endpoint_id],
+        )?,
+    };
+    Ok(())
+}
+
+/// Whether an endpoint has its own community (the value itself is never read back out)
+pub fn has_snmp_credential(conn: &Connection, endpoint_id: i64) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM snmp_credentials WHERE endpoint_id = ?1",
+        [endpoint_id],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+/// Move an endpoint's credential to the endpoint it is merged into, unless that one
+/// already has its own
+pub fn reassign_snmp_credential(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE snmp_credentials SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM snmp_credentials WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+/// Everything the SNMP scanner needs to decide which communities to try per device
+pub fn load_snmp_credentials(conn: &Connection) -> Result<SnmpCredentials> {
+    let communities = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'snmp_communities'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .map(|value| parse_snmp_communities(&value))
+        .unwrap_or_else(|| COMMUNITY_STRINGS.iter().map(|s| s.to_string()).collect());
+
+    let mut device = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT ea.ip, c.community
+         FROM snmp_credentials c
+         JOIN endpoint_attributes ea ON ea.endpoint_id = c.endpoint_id
+         WHERE ea.ip IS NOT NULL AND ea.ip != ''",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for (ip, community) in rows.flatten() {
+        if let Ok(ip) = ip.parse::<Ipv4Addr>() {
+            device.insert(ip, community);
+        }
+    }
+
+    // Oldest first, so the latest success for an address wins
+    let mut last_success = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT ip, json_extract(details, '$.community') FROM scan_results
+         WHERE scan_type = 'snmp' AND ip IS NOT NULL
+           AND json_valid(details)
+           AND COALESCE(json_extract(details, '$.credential'), 'list') = 'list'
+           AND COALESCE(json_extract(details, '$.community'), '') != ''
+         ORDER BY scanned_at",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for (ip, community) in rows.flatten() {
+        if let Ok(ip) = ip.parse::<Ipv4Addr>() {
+            last_success.insert(ip, community);
+        }
+    }
+
+    Ok(SnmpCredentials {
+        communities,
+        device,
+        last_success,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_parse_snmp_communities() {
+        assert_eq!(
+            parse_snmp_communities(" monitor, public\nprivate,,public "),
+            vec!["monitor", "public", "private"]
+        );
+        assert!(parse_snmp_communities("").is_empty());
+    }
+
+    #[test]
+    fn test_load_snmp_credentials() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                ip TEXT, scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL, details TEXT);
+             INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'switch'), (2, 1, 'printer');
+             INSERT INTO endpoint_attributes (endpoint_id, created_at, mac, ip, hostname)
+                VALUES (1, 1, 'aa:bb:cc:00:00:01', '10.0.0.1', 'switch'),
+                       (2, 1, 'aa:bb:cc:00:00:02', '10.0.0.2', 'printer');
+             INSERT INTO settings (key, value) VALUES ('snmp_communities', 'monitor,public');
+             INSERT INTO scan_results (endpoint_id, ip, scan_type, scanned_at, details) VALUES
+                (2, '10.0.0.2', 'snmp', 10, '{\"community\":\"public\"}'),
+                (2, '10.0.0.2', 'snmp', 20, '{\"community\":\"monitor\",\"credential\":\"list\"}'),
+                (1, '10.0.0.1', 'snmp', 30, '{\"credential\":\"device\"}');",
+        )
+        .unwrap();
+        set_snmp_credential(&conn, 1, Some("s3cret")).unwrap();
+        assert!(has_snmp_credential(&conn, 1));
+        assert!(!has_snmp_credential(&conn, 2));
+
+        let credentials = load_snmp_credentials(&conn).unwrap();
+        assert_eq!(credentials.communities, vec!["monitor", "public"]);
+        let switch = Ipv4Addr::new(10, 0, 0, 1);
+        let printer = Ipv4Addr::new(10, 0, 0, 2);
+        assert_eq!(
+            credentials.device.get(&switch).map(String::as_str),
+            Some("s3cret")
+        );
+        assert_eq!(
+            credentials.last_success.get(&printer).map(String::as_str),
+            Some("monitor")
+        );
+        assert!(!credentials.last_success.contains_key(&switch));
+
+        set_snmp_credential(&conn, 1, None).unwrap();
+        assert!(!has_snmp_credential(&conn, 1));
+    }
+}
@@ -0,0 +1,480 @@
+//! NAS storage health. A background poller reads the Synology, QNAP or TrueNAS MIB from
+//! every endpoint that looks like a NAS in its SNMP scan (or is already typed as one),
+//! every `nas_poll_interval_minutes`, and keeps the latest volume and disk state. A NAS
+//! that answers is classified as "nas". A volume, RAID group or pool going degraded or
+//! failed raises one `storage_degraded` notification; it is re-armed once the array is
+//! healthy again.
+
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+use tokio::task;
+
+use super::{
+    get_setting_i64, insert_notification_with_endpoint_id, load_snmp_credentials,
+    new_connection_result,
+};
+use crate::scanner::nas::{NasDisk, NasStatus, NasVendor, NasVolume, query_nas};
+use crate::scanner::snmp::SnmpScanner;
+
+/// Flag to ensure the poller is only started once
+static STORAGE_POLLER_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn create_storage_health_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS storage_devices (
+            endpoint_id INTEGER PRIMARY KEY,
+            vendor TEXT NOT NULL,
+            polled_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS storage_volumes (
+            endpoint_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL,
+            total_bytes INTEGER,
+            free_bytes INTEGER,
+            PRIMARY KEY (endpoint_id, name)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS storage_disks (
+            endpoint_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            model TEXT,
+            status TEXT,
+            temperature_c INTEGER,
+            PRIMARY KEY (endpoint_id, name)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS storage_alerts (
+            endpoint_id INTEGER NOT NULL,
+            volume TEXT NOT NULL,
+            alerted_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, volume)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A NAS to poll, at the address its last SNMP scan answered on
+#[derive(Debug)]
+pub struct NasTarget {
+    pub endpoint_id: i64,
+    pub ip: String,
+    /// Vendor suggested by the SNMP scan, tried first
+    pub hint: Option<NasVendor>,
+}
+
+/// Endpoints that answered an SNMP scan and are typed as a NAS or describe themselves as one
+pub fn get_nas_targets(conn: &Connection) -> Result<Vec<NasTarget>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.id, COALESCE(e.manual_device_type, e.auto_device_type), sr.ip, sr.details
+         FROM endpoints e
+         JOIN scan_results sr ON sr.id = (
+             SELECT id FROM scan_results
+             WHERE endpoint_id = e.id AND scan_type = 'snmp' AND ip IS NOT NULL
+             ORDER BY scanned_at DESC LIMIT 1)",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+        ))
+    })?;
+
+    let mut targets = Vec::new();
+    for row in rows {
+        let (endpoint_id, device_type, ip, details) = row?;
+        let hint = details.as_deref().and_then(NasVendor::from_snmp_details);
+        if hint.is_some() || device_type.as_deref() == Some("nas") {
+            targets.push(NasTarget {
+                endpoint_id,
+                ip,
+                hint,
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Store one poll of a NAS, classify it, and raise degraded-array alerts. Returns the
+/// alerts raised.
+pub fn record_nas_status(
+    conn: &Connection,
+    endpoint_id: i64,
+    status: &NasStatus,
+    now: i64,
+) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT OR REPLACE INTO storage_devices (endpoint_id, vendor, polled_at)
+         VALUES (?1, ?2, ?3)",
+        params![endpoint_id, status.vendor.as_str(), now],
+    )?;
+    // Only the latest state is kept; volumes and disks that went away are dropped
+    tx.execute(
+        "DELETE FROM storage_volumes WHERE endpoint_id = ?1",
+        [endpoint_id],
+    )?;
+    tx.execute(
+        "DELETE FROM storage_disks WHERE endpoint_id = ?1",
+        [endpoint_id],
+    )?;
+    for disk in &status.disks {
+        tx.execute(
+            "INSERT OR REPLACE INTO storage_disks
+                 (endpoint_id, name, model, status, temperature_c)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                endpoint_id,
+                disk.name,
+                disk.model,
+                disk.status,
+                disk.temperature_c
+            ],
+        )?;
+    }
+
+    // Answering a NAS MIB settles what the device is; a manual type still wins
+    tx.execute(
+        "UPDATE endpoints SET auto_device_type = 'nas'
+         WHERE id = ?1 AND auto_device_type IS NOT 'nas'",
+        [endpoint_id],
+    )?;
+
+    let name: Option<String> = tx
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let name = name.unwrap_or_else(|| format!("endpoint {}", endpoint_id));
+
+    let mut alerts = 0;
+    for volume in &status.volumes {
+        tx.execute(
+            "INSERT OR REPLACE INTO storage_volumes
+                 (endpoint_id, name, status, total_bytes, free_bytes)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                endpoint_id,
+                volume.name,
+                volume.status,
+                volume.total_bytes,
+                volume.free_bytes
+            ],
+        )?;
+
+        if !volume.is_degraded() {
+            // Rebuilt or repaired; the next failure alerts again
+            tx.execute(
+                "DELETE FROM storage_alerts WHERE endpoint_id = ?1 AND volume = ?2",
+                params![endpoint_id, volume.name],
+            )?;
+            continue;
+        }
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO storage_alerts (endpoint_id, volume, alerted_at)
+             VALUES (?1, ?2, ?3)",
+            params![endpoint_id, volume.name, now],
+        )?;
+        if inserted > 0 {
+            let failed_disks: Vec<&str> = status
+                .disks
+                .iter()
+                .filter(|d| d.status.as_deref() == Some("failed"))
+                .map(|d| d.name.as_str())
+                .collect();
+            let details = if failed_disks.is_empty() {
+                format!("Status: {}", volume.status)
+            } else {
+                format!(
+                    "Status: {}; failed disks: {}",
+                    volume.status,
+                    failed_disks.join(", ")
+                )
+            };
+            insert_notification_with_endpoint_id(
+                &tx,
+                "storage_degraded",
+                &format!("{} on {} is {}", volume.name, name, volume.status),
+                Some(&details),
+                Some(&name),
+                Some(endpoint_id),
+            );
+            alerts += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(alerts)
+}
+
+/// A volume with its usage worked out
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeReport {
+    #[serde(flatten)]
+    pub volume: NasVolume,
+    pub used_percent: Option<i64>,
+    pub degraded: bool,
+}
+
+/// A NAS's latest storage state
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageReport {
+    pub endpoint_id: i64,
+    pub endpoint: Option<String>,
+    pub vendor: String,
+    pub polled_at: i64,
+    pub volumes: Vec<VolumeReport>,
+    pub disks: Vec<NasDisk>,
+    /// Hottest disk, for a quick overview
+    pub max_temperature_c: Option<i64>,
+}
+
+/// Storage state for one NAS; None until it has been polled
+pub fn get_storage_report(conn: &Connection, endpoint_id: i64) -> Result<Option<StorageReport>> {
+    let device: Option<(Option<String>, String, i64)> = conn
+        .query_row(
+            "SELECT e.display_name, s.vendor, s.polled_at FROM storage_devices s
+             LEFT JOIN endpoints e ON e.id = s.endpoint_id
+             WHERE s.endpoint_id = ?1",
+            [endpoint_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let Some((endpoint, vendor, polled_at)) = device else {
+        return Ok(None);
+    };
+
+    let volumes: Vec<VolumeReport> = conn
+        .prepare(
+            "SELECT name, status, total_bytes, free_bytes FROM storage_volumes
+             WHERE endpoint_id = ?1 ORDER BY name",
+        )?
+        .query_map([endpoint_id], |row| {
+            let volume = NasVolume {
+                name: row.get(0)?,
+                status: row.get(1)?,
+                total_bytes: row.get(2)?,
+                free_bytes: row.get(3)?,
+            };
+            Ok(VolumeReport {
+                used_percent: volume.used_percent(),
+                degraded: volume.is_degraded(),
+                volume,
+            })
+        })?
+        .collect::<Result<_>>()?;
+    let disks: Vec<NasDisk> = conn
+        .prepare(
+            "SELECT name, model, status, temperature_c FROM storage_disks
+             WHERE endpoint_id = ?1 ORDER BY name",
+        )?
+        .query_map([endpoint_id], |row| {
+            Ok(NasDisk {
+                name: row.get(0)?,
+                model: row.get(1)?,
+                status: row.get(2)?,
+                temperature_c: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+
+    Ok(Some(StorageReport {
+        endpoint_id,
+        endpoint,
+        vendor,
+        polled_at,
+        max_temperature_c: disks.iter().filter_map(|d| d.temperature_c).max(),
+        volumes,
+        disks,
+    }))
+}
+
+/// Storage state for every polled NAS, those with a degraded array first
+pub fn get_storage_overview(conn: &Connection) -> Result<Vec<StorageReport>> {
+    let ids: Vec<i64> = conn
+        .prepare("SELECT endpoint_id FROM storage_devices")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    let mut reports = Vec::new();
+    for id in ids {
+        reports.extend(get_storage_report(conn, id)?);
+    }
+    reports.sort_by_key(|r| {
+        (
+            !r.volumes.iter().any(|v| v.degraded),
+            r.endpoint.clone().unwrap_or_default().to_lowercase(),
+        )
+    });
+    Ok(reports)
+}
+
+/// Fold a NAS's storage state into the endpoint it is merged into
+pub fn reassign_storage_health(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    for table in [
+        "storage_devices",
+        "storage_volumes",
+        "storage_disks",
+        "storage_alerts",
+    ] {
+        conn.execute(
+            &format!(
+                "UPDATE OR IGNORE {} SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+                table
+            ),
+            params![to_id, from_id],
+        )?;
+        conn.execute(
+            &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+            [from_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Poll every NAS once and store what they report
+fn poll_nas_devices() -> std::result::Result<(usize, usize), String> {
+    let conn = new_connection_result().map_err(|e| e.to_string())?;
+    let targets = get_nas_targets(&conn).map_err(|e| e.to_string())?;
+    if targets.is_empty() {
+        return Ok((0, 0));
+    }
+    let credentials = load_snmp_credentials(&conn).map_err(|e| e.to_string())?;
+    let scanner = SnmpScanner::new().with_credentials(credentials);
+
+    let (mut polled, mut alerts) = (0, 0);
+    for target in targets {
+        let Ok(ip) = target.ip.parse::<Ipv4Addr>() else {
+            continue;
+        };
+        if let Some(status) = query_nas(&scanner, ip, target.hint) {
+            let now = chrono::Utc::now().timestamp();
+            alerts += record_nas_status(&conn, target.endpoint_id, &status, now)
+                .map_err(|e| e.to_string())?;
+            polled += 1;
+        }
+    }
+    Ok((polled, alerts))
+}
+
+/// Start the NAS storage poller. Runs every `nas_poll_interval_minutes` (0 disables it).
+pub fn start_storage_health_poller() {
+    if STORAGE_POLLER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        // Let the startup scan find SNMP devices and their communities first
+        tokio::time::sleep(tokio::time::Duration::from_secs(120)).await;
+
+        loop {
+            let interval_minutes = get_setting_i64("nas_poll_interval_minutes", 15);
+            if interval_minutes > 0 {
+                match task::spawn_blocking(poll_nas_devices).await {
+                    Ok(Ok((_, alerts))) if alerts > 0 => {
+                        println!("{} degraded storage alert(s) raised", alerts)
+                    }
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => eprintln!("NAS storage poll failed: {}", e),
+                    Err(e) => eprintln!("NAS storage poll task failed: {}", e),
+                }
+            }
+
+            // Check again for a changed interval even while disabled
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                interval_minutes.max(5) as u64 * 60,
+            ))
+            .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn poll(volume_status: &str) -> NasStatus {
+        NasStatus {
+            vendor: NasVendor::Synology,
+            volumes: vec![NasVolume {
+                name: "Volume 1".to_string(),
+                status: volume_status.to_string(),
+                total_bytes: Some(4000),
+                free_bytes: Some(1000),
+            }],
+            disks: vec![NasDisk {
+                name: "Disk 2".to_string(),
+                model: Some("WD40EFRX".to_string()),
+                status: Some("failed".to_string()),
+                temperature_c: Some(44),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_degraded_array_alerts_once_until_healthy() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                ip TEXT, scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL, details TEXT);
+             INSERT INTO endpoints (id, created_at, name) VALUES
+                 (1, 1, 'diskstation'), (2, 1, 'router');
+             INSERT INTO scan_results (endpoint_id, ip, scan_type, scanned_at, details) VALUES
+                 (1, '192.168.1.20', 'snmp', 10,
+                  '{\"sys_descr\":\"Linux DiskStation 4.4.302+\"}'),
+                 (2, '192.168.1.1', 'snmp', 10, '{\"sys_descr\":\"RouterOS RB5009\"}');",
+        )
+        .unwrap();
+
+        let targets = get_nas_targets(&conn).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].hint, Some(NasVendor::Synology));
+
+        assert_eq!(
+            record_nas_status(&conn, 1, &poll("degraded"), 100).unwrap(),
+            1
+        );
+        assert_eq!(
+            record_nas_status(&conn, 1, &poll("degraded"), 200).unwrap(),
+            0
+        );
+        assert_eq!(
+            record_nas_status(&conn, 1, &poll("normal"), 300).unwrap(),
+            0
+        );
+        assert_eq!(
+            record_nas_status(&conn, 1, &poll("failed"), 400).unwrap(),
+            1
+        );
+
+        let device_type: String = conn
+            .query_row(
+                "SELECT auto_device_type FROM endpoints WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(device_type, "nas");
+
+        let report = get_storage_report(&conn, 1).unwrap().unwrap();
+        assert_eq!(report.volumes[0].used_percent, Some(75));
+        assert!(report.volumes[0].degraded);
+        assert_eq!(report.max_temperature_c, Some(44));
+        assert!(get_storage_report(&conn, 2).unwrap().is_none());
+    }
+}
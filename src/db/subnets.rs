@@ -0,0 +1,337 @@
+//! Lightweight IPAM over what has already been observed. The IPv4 networks of the local
+//! interfaces are the subnets; who holds each address comes from endpoint_attributes
+//! (traffic, scans) and open ip_history spans (ARP/NDP replies, DHCP leases), whichever
+//! saw the address last.
+
+use std::collections::{BTreeMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+
+use ipnetwork::Ipv4Network;
+use pnet::ipnetwork::IpNetwork;
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+
+use crate::network::endpoint::{EndPoint, get_local_networks};
+
+/// Largest network the per-address grid is built for (a /20)
+pub const MAX_GRID_ADDRESSES: u64 = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressHolder {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub last_seen_at: i64,
+    /// "traffic" for addresses known from endpoint attributes, otherwise the ip_history
+    /// source ("arp", "ndp", "dhcp")
+    pub source: String,
+}
+
+/// A run of consecutive addresses nobody has been seen at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressRange {
+    pub start: String,
+    pub end: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubnetSummary {
+    /// CIDR notation, e.g. "192.168.1.0/24"
+    pub network: String,
+    /// Assignable addresses (excluding network and broadcast addresses)
+    pub total_addresses: u64,
+    pub addresses_seen: u64,
+    pub utilization_percent: f64,
+    pub free_ranges: Vec<AddressRange>,
+    pub gateway: Option<String>,
+    pub dns_servers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressCell {
+    pub ip: String,
+    pub holder: Option<AddressHolder>,
+}
+
+/// First and last assignable address. /31 and /32 networks have no network or broadcast
+/// address to leave out.
+fn host_range(network: Ipv4Network) -> (u32, u32) {
+    let first = u32::from(network.network());
+    let last = u32::from(network.broadcast());
+    if network.prefix() >= 31 {
+        (first, last)
+    } else {
+        (first + 1, last - 1)
+    }
+}
+
+/// The IPv4 networks of the local interfaces, loopback excluded
+pub fn local_ipv4_subnets() -> Vec<Ipv4Network> {
+    let mut subnets: Vec<Ipv4Network> = get_local_networks()
+        .iter()
+        .filter_map(|network| match network {
+            IpNetwork::V4(v4) if !v4.ip().is_loopback() => {
+                Ipv4Network::new(v4.network(), v4.prefix()).ok()
+            }
+            _ => None,
+        })
+        .collect();
+    subnets.sort_by_key(|network| (u32::from(network.network()), network.prefix()));
+    subnets.dedup();
+    subnets
+}
+
+/// Latest holder of every IPv4 address seen
+pub fn load_address_holders(conn: &Connection) -> Result<BTreeMap<Ipv4Addr, AddressHolder>> {
+    let mut holders: BTreeMap<Ipv4Addr, AddressHolder> = BTreeMap::new();
+    let mut keep_latest = |ip: String, holder: AddressHolder| {
+        let Ok(ip) = ip.parse::<Ipv4Addr>() else {
+            return;
+        };
+        if holders
+            .get(&ip)
+            .is_none_or(|current| holder.last_seen_at >= current.last_seen_at)
+        {
+            holders.insert(ip, holder);
+        }
+    };
+
+    // SQLite returns the bare columns from the row holding MAX(created_at)
+    let mut stmt = conn.prepare(
+        "SELECT ea.ip, ea.endpoint_id, e.display_name, MAX(ea.created_at)
+         FROM endpoint_attributes ea
+         JOIN endpoints e ON e.id = ea.endpoint_id
+         GROUP BY ea.ip",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            AddressHolder {
+                endpoint_id: row.get(1)?,
+                endpoint_name: row.get(2)?,
+                last_seen_at: row.get(3)?,
+                source: "traffic".to_string(),
+            },
+        ))
+    })?;
+    for row in rows {
+        let (ip, holder) = row?;
+        keep_latest(ip, holder);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT h.ip, h.endpoint_id, e.display_name, h.last_seen_at, h.source
+         FROM ip_history h
+         JOIN endpoints e ON e.id = h.endpoint_id
+         WHERE h.unbound_at IS NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            AddressHolder {
+                endpoint_id: row.get(1)?,
+                endpoint_name: row.get(2)?,
+                last_seen_at: row.get(3)?,
+                source: row.get(4)?,
+            },
+        ))
+    })?;
+    for row in rows {
+        let (ip, holder) = row?;
+        keep_latest(ip, holder);
+    }
+
+    Ok(holders)
+}
+
+/// Local endpoints that have answered DNS queries (traffic to port 53)
+pub fn load_dns_servers(conn: &Connection) -> Result<Vec<Ipv4Addr>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT ea.ip FROM communications c
+         JOIN endpoint_attributes ea ON ea.endpoint_id = c.dst_endpoint_id
+         WHERE c.destination_port = 53",
+    )?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut servers: Vec<Ipv4Addr> = rows.filter_map(|ip| ip.ok()?.parse().ok()).collect();
+    servers.sort();
+    servers.dedup();
+    Ok(servers)
+}
+
+/// Endpoints classified (or manually marked) as gateways
+pub fn load_gateway_endpoints(conn: &Connection) -> Result<HashSet<i64>> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM endpoints
+         WHERE COALESCE(NULLIF(manual_device_type, ''), auto_device_type) = 'gateway'",
+    )?;
+    let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+    rows.collect()
+}
+
+/// Summarize one subnet. The default gateway is used when it is inside the subnet,
+/// otherwise the lowest address held by a gateway endpoint.
+pub fn summarize_subnet(
+    network: Ipv4Network,
+    holders: &BTreeMap<Ipv4Addr, AddressHolder>,
+    default_gateway: Option<Ipv4Addr>,
+    gateway_endpoints: &HashSet<i64>,
+    dns_servers: &[Ipv4Addr],
+) -> SubnetSummary {
+    let (first, last) = host_range(network);
+    let total_addresses = u64::from(last - first) + 1;
+
+    let mut addresses_seen = 0;
+    let mut free_ranges = Vec::new();
+    let mut next_free = u64::from(first);
+    let mut push_free = |start: u64, end: u64| {
+        if start <= end {
+            free_ranges.push(AddressRange {
+                start: Ipv4Addr::from(start as u32).to_string(),
+                end: Ipv4Addr::from(end as u32).to_string(),
+                count: end - start + 1,
+            });
+        }
+    };
+    for ip in holders
+        .range(Ipv4Addr::from(first)..=Ipv4Addr::from(last))
+        .map(|(ip, _)| ip)
+    {
+        let address = u64::from(u32::from(*ip));
+        addresses_seen += 1;
+        push_free(next_free, address.saturating_sub(1));
+        next_free = address + 1;
+    }
+    push_free(next_free, u64::from(last));
+
+    let gateway = default_gateway
+        .filter(|ip| network.contains(*ip))
+        .or_else(|| {
+            holders
+                .range(Ipv4Addr::from(first)..=Ipv4Addr::from(last))
+                .find(|(_, holder)| gateway_endpoints.contains(&holder.endpoint_id))
+                .map(|(ip, _)| *ip)
+        })
+        .map(|ip| ip.to_string());
+
+    SubnetSummary {
+        network: network.to_string(),
+        total_addresses,
+        addresses_seen,
+        utilization_percent: addresses_seen as f64 * 100.0 / total_addresses as f64,
+        free_ranges,
+        gateway,
+        dns_servers: dns_servers
+            .iter()
+            .filter(|ip| network.contains(**ip))
+            .map(|ip| ip.to_string())
+            .collect(),
+    }
+}
+
+/// Every assignable address of `network` with its holder, if any. Callers keep the
+/// network within MAX_GRID_ADDRESSES.
+pub fn subnet_grid(
+    network: Ipv4Network,
+    holders: &BTreeMap<Ipv4Addr, AddressHolder>,
+) -> Vec<AddressCell> {
+    let (first, last) = host_range(network);
+    (first..=last)
+        .map(|address| {
+            let ip = Ipv4Addr::from(address);
+            AddressCell {
+                ip: ip.to_string(),
+                holder: holders.get(&ip).cloned(),
+            }
+        })
+        .collect()
+}
+
+/// Summaries of every local subnet
+pub fn get_subnet_summaries(conn: &Connection) -> Result<Vec<SubnetSummary>> {
+    let holders = load_address_holders(conn)?;
+    let gateway_endpoints = load_gateway_endpoints(conn)?;
+    let dns_servers = load_dns_servers(conn)?;
+    let default_gateway = EndPoint::get_default_gateway().and_then(|ip| match ip.parse() {
+        Ok(IpAddr::V4(v4)) => Some(v4),
+        _ => None,
+    });
+
+    Ok(local_ipv4_subnets()
+        .into_iter()
+        .map(|network| {
+            summarize_subnet(
+                network,
+                &holders,
+                default_gateway,
+                &gateway_endpoints,
+                &dns_servers,
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holder(endpoint_id: i64) -> AddressHolder {
+        AddressHolder {
+            endpoint_id,
+            endpoint_name: None,
+            last_seen_at: 0,
+            source: "traffic".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_summarize_subnet() {
+        let network: Ipv4Network = "192.168.1.0/29".parse().unwrap();
+        let holders: BTreeMap<Ipv4Addr, AddressHolder> = [
+            ("192.168.1.1", holder(1)),
+            ("192.168.1.2", holder(2)),
+            ("192.168.1.5", holder(3)),
+            // Outside the subnet
+            ("192.168.2.1", holder(4)),
+        ]
+        .into_iter()
+        .map(|(ip, h)| (ip.parse().unwrap(), h))
+        .collect();
+        let dns = ["192.168.1.1".parse().unwrap(), "10.0.0.53".parse().unwrap()];
+
+        let summary = summarize_subnet(network, &holders, None, &HashSet::from([2]), &dns);
+        assert_eq!(summary.network, "192.168.1.0/29");
+        assert_eq!((summary.total_addresses, summary.addresses_seen), (6, 3));
+        assert_eq!(summary.utilization_percent, 50.0);
+        let free: Vec<(&str, &str, u64)> = summary
+            .free_ranges
+            .iter()
+            .map(|r| (r.start.as_str(), r.end.as_str(), r.count))
+            .collect();
+        assert_eq!(
+            free,
+            vec![
+                ("192.168.1.3", "192.168.1.4", 2),
+                ("192.168.1.6", "192.168.1.6", 1)
+            ]
+        );
+        assert_eq!(summary.gateway.as_deref(), Some("192.168.1.2"));
+        assert_eq!(summary.dns_servers, vec!["192.168.1.1"]);
+
+        // The default gateway wins when it is inside the subnet
+        let default_gateway = Some("192.168.1.1".parse().unwrap());
+        let summary = summarize_subnet(
+            network,
+            &holders,
+            default_gateway,
+            &HashSet::from([2]),
+            &dns,
+        );
+        assert_eq!(summary.gateway.as_deref(), Some("192.168.1.1"));
+
+        let grid = subnet_grid(network, &holders);
+        assert_eq!(grid.len(), 6);
+        assert_eq!(grid[0].ip, "192.168.1.1");
+        assert_eq!(grid[0].holder.as_ref().map(|h| h.endpoint_id), Some(1));
+        assert!(grid[2].holder.is_none());
+    }
+}
@@ -0,0 +1,278 @@
+//! Storage and resource figures for the system stats API: database and WAL file size,
+//! rows per table, free disk space, process memory, and the SQL writer backlog. The
+//! database size is projected out to the full retention window so it's clear whether
+//! retention needs tuning before the disk fills.
+
+use std::path::Path;
+
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+
+use super::{get_database_url, writer_queue};
+
+/// Free disk share below which a warning is raised
+const LOW_DISK_PERCENT: u64 = 10;
+
+/// Writer queue share above which a warning is raised
+const WRITER_BACKLOG_PERCENT: usize = 50;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableRows {
+    pub table: String,
+    pub rows: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DiskSpace {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemStats {
+    pub database_path: String,
+    pub database_bytes: u64,
+    /// Write-ahead log not yet checkpointed into the database file
+    pub wal_bytes: u64,
+    pub endpoint_count: i64,
+    /// Row counts, largest table first
+    pub tables: Vec<TableRows>,
+    /// Space on the filesystem holding the database (None where `df` isn't available)
+    pub disk: Option<DiskSpace>,
+    /// Resident memory of this process, packet capture buffers included
+    pub process_memory_bytes: Option<u64>,
+    pub writer_queue_depth: Option<usize>,
+    pub writer_queue_capacity: Option<usize>,
+    pub retention_days: i64,
+    /// Days of communications currently stored
+    pub retained_days: f64,
+    /// Database size once a full retention window is stored, at the current rate
+    pub projected_database_bytes: Option<u64>,
+    pub warnings: Vec<String>,
+}
+
+fn file_size(path: &str) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Parse `df -Pk` output: the second line's total and available 1K-block columns
+#[cfg_attr(not(unix), allow(dead_code))]
+fn parse_df(output: &str) -> Option<DiskSpace> {
+    let fields: Vec<&str> = output.lines().nth(1)?.split_whitespace().collect();
+    Some(DiskSpace {
+        total_bytes: fields.get(1)?.parse::<u64>().ok()? * 1024,
+        available_bytes: fields.get(3)?.parse::<u64>().ok()? * 1024,
+    })
+}
+
+#[cfg(unix)]
+fn disk_space(path: &Path) -> Option<DiskSpace> {
+    let output = std::process::Command::new("df")
+        .arg("-Pk")
+        .arg(path)
+        .output()
+        .ok()?;
+    parse_df(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(unix))]
+fn disk_space(_path: &Path) -> Option<DiskSpace> {
+    None
+}
+
+/// Parse the resident set size from /proc/self/status (`VmRSS:   1234 kB`)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "linux")]
+fn process_memory() -> Option<u64> {
+    parse_vm_rss(&std::fs::read_to_string("/proc/self/status").ok()?)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_memory() -> Option<u64> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "rss=", "-p", &std::process::id().to_string()])
+        .output()
+        .ok()?;
+    let kb: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(not(unix))]
+fn process_memory() -> Option<u64> {
+    None
+}
+
+fn table_rows(conn: &Connection) -> Result<Vec<TableRows>> {
+    let names: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM sqlite_master
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+             ORDER BY name",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+
+    let mut tables = names
+        .into_iter()
+        .map(|table| {
+            let rows =
+                conn.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table), [], |row| {
+                    row.get(0)
+                })?;
+            Ok(TableRows { table, rows })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    tables.sort_by_key(|t| std::cmp::Reverse(t.rows));
+    Ok(tables)
+}
+
+/// Database size once `retention_days` of data is stored, if fewer days are stored now
+fn project_size(bytes: u64, retained_days: f64, retention_days: i64) -> Option<u64> {
+    // Under a day of data says little about the daily rate
+    if retained_days < 1.0 || retained_days >= retention_days as f64 {
+        return None;
+    }
+    Some((bytes as f64 * retention_days as f64 / retained_days) as u64)
+}
+
+/// Collect the system stats for the database `conn` is open on
+pub fn get_system_stats(conn: &Connection) -> Result<SystemStats> {
+    let database_url = get_database_url();
+    let database_path = database_url
+        .strip_prefix("sqlite://")
+        .unwrap_or(&database_url)
+        .to_string();
+    let database_bytes = file_size(&database_path);
+    let wal_bytes = file_size(&format!("{}-wal", database_path));
+
+    let endpoint_count = conn.query_row("SELECT COUNT(*) FROM endpoints", [], |row| row.get(0))?;
+    let tables = table_rows(conn)?;
+
+    let directory = Path::new(&database_path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let disk = disk_space(directory);
+    let queue = writer_queue();
+
+    let retention_days = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'data_retention_days'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(7);
+    let oldest: Option<i64> =
+        conn.query_row("SELECT MIN(created_at) FROM communications", [], |row| {
+            row.get(0)
+        })?;
+    let retained_days = oldest
+        .map(|oldest| (chrono::Utc::now().timestamp() - oldest).max(0) as f64)
+        .unwrap_or(0.0)
+        / SECONDS_PER_DAY as f64;
+    let stored_bytes = database_bytes + wal_bytes;
+    let projected_database_bytes = project_size(stored_bytes, retained_days, retention_days);
+
+    let mut warnings = Vec::new();
+    if let Some(disk) = disk {
+        if disk.available_bytes * 100 < disk.total_bytes * LOW_DISK_PERCENT {
+            warnings.push(format!(
+                "Less than {}% of the disk is free; lower data_retention_days",
+                LOW_DISK_PERCENT
+            ));
+        }
+        if let Some(projected) = projected_database_bytes
+            && projected.saturating_sub(stored_bytes) > disk.available_bytes
+        {
+            warnings.push(format!(
+                "At the current rate {} days of data won't fit on the disk; lower data_retention_days",
+                retention_days
+            ));
+        }
+    }
+    if let Some((depth, capacity)) = queue
+        && depth * 100 > capacity * WRITER_BACKLOG_PERCENT
+    {
+        warnings.push(format!(
+            "The database writer is behind: {} of {} queued",
+            depth, capacity
+        ));
+    }
+
+    Ok(SystemStats {
+        database_path,
+        database_bytes,
+        wal_bytes,
+        endpoint_count,
+        tables,
+        disk,
+        process_memory_bytes: process_memory(),
+        writer_queue_depth: queue.map(|(depth, _)| depth),
+        writer_queue_capacity: queue.map(|(_, capacity)| capacity),
+        retention_days,
+        retained_days,
+        projected_database_bytes,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_system_stats() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (created_at, name) VALUES (0, 'nas')",
+            [],
+        )
+        .unwrap();
+        let stats = get_system_stats(&conn).unwrap();
+        assert_eq!(stats.endpoint_count, 1);
+        assert!(
+            stats
+                .tables
+                .iter()
+                .any(|t| t.table == "endpoints" && t.rows == 1)
+        );
+        assert_eq!(stats.retained_days, 0.0);
+        assert!(stats.projected_database_bytes.is_none());
+    }
+
+    #[test]
+    fn test_parse_system_figures() {
+        let df = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+                  /dev/sda1        102400000 92160000  10240000      90% /\n";
+        assert_eq!(
+            parse_df(df),
+            Some(DiskSpace {
+                total_bytes: 102_400_000 * 1024,
+                available_bytes: 10_240_000 * 1024,
+            })
+        );
+        assert_eq!(parse_df("df: /missing: No such file or directory\n"), None);
+
+        let status = "Name:\tawareness\nVmPeak:\t  90000 kB\nVmRSS:\t   51200 kB\n";
+        assert_eq!(parse_vm_rss(status), Some(51200 * 1024));
+
+        // Two days stored of a seven day window
+        assert_eq!(project_size(1000, 2.0, 7), Some(3500));
+        assert_eq!(project_size(1000, 0.5, 7), None);
+        assert_eq!(project_size(1000, 7.0, 7), None);
+    }
+}
@@ -0,0 +1,256 @@
+//! Chronological history of one endpoint, assembled on request from the tables that
+//! already record its events: when it was first seen, the addresses it held (ip_history),
+//! the notifications raised about it (renames, reclassification, new ports, ...), the
+//! scan runs that reached it, and the stretches it stopped answering scans.
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+use crate::network::endpoint::EndPoint;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimelineEvent {
+    /// Unix time the event happened (or the span started)
+    pub at: i64,
+    /// "first_seen", "ip_bound", "ip_released", "scanned", "offline", or the
+    /// notification event type ("endpoint_renamed", "port_opened", ...)
+    pub kind: String,
+    pub title: String,
+    pub details: Option<String>,
+    /// End of a span: when an offline endpoint answered again. None while still open
+    /// and for point events.
+    pub until: Option<i64>,
+}
+
+impl TimelineEvent {
+    fn new(at: i64, kind: &str, title: String, details: Option<String>) -> Self {
+        Self {
+            at,
+            kind: kind.to_string(),
+            title,
+            details,
+            until: None,
+        }
+    }
+}
+
+/// Every recorded event for an endpoint, oldest first. Notifications stored before they
+/// carried an endpoint id are matched on the endpoint's current names.
+pub fn get_endpoint_timeline(conn: &Connection, endpoint_id: i64) -> Result<Vec<TimelineEvent>> {
+    let (created_at, names): (i64, Vec<String>) = conn.query_row(
+        "SELECT created_at, name, custom_name, display_name FROM endpoints WHERE id = ?1",
+        [endpoint_id],
+        |row| {
+            let names = (1..=3)
+                .filter_map(|i| row.get::<_, Option<String>>(i).transpose())
+                .collect::<Result<Vec<String>>>()?;
+            Ok((row.get(0)?, names))
+        },
+    )?;
+
+    let mut events = vec![TimelineEvent::new(
+        created_at,
+        "first_seen",
+        "First seen".to_string(),
+        None,
+    )];
+
+    for entry in EndPoint::get_ip_history(conn, endpoint_id)? {
+        events.push(TimelineEvent::new(
+            entry.bound_at,
+            "ip_bound",
+            format!("Seen at {}", entry.ip),
+            Some(match &entry.mac {
+                Some(mac) => format!("{} via {}", mac, entry.source),
+                None => format!("via {}", entry.source),
+            }),
+        ));
+        if let Some(unbound_at) = entry.unbound_at {
+            events.push(TimelineEvent::new(
+                unbound_at,
+                "ip_released",
+                format!("No longer at {}", entry.ip),
+                None,
+            ));
+        }
+    }
+
+    let names_json = serde_json::to_string(&names).unwrap_or_else(|_| "[]".to_string());
+    let mut stmt = conn.prepare(
+        "SELECT created_at, event_type, title, details FROM notifications
+         WHERE endpoint_id = ?1
+            OR (endpoint_id IS NULL
+                AND LOWER(endpoint_name) IN (SELECT LOWER(value) FROM json_each(?2)))
+         ORDER BY created_at, id",
+    )?;
+    let notifications = stmt
+        .query_map(params![endpoint_id, names_json], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    for (at, event_type, title, details) in notifications {
+        if event_type == "endpoint_disappeared" {
+            // Offline until the next scan result from the endpoint, if any
+            let until: Option<i64> = conn.query_row(
+                "SELECT MIN(scanned_at) FROM scan_results WHERE endpoint_id = ?1 AND scanned_at > ?2",
+                params![endpoint_id, at],
+                |row| row.get(0),
+            )?;
+            events.push(TimelineEvent {
+                until,
+                ..TimelineEvent::new(
+                    at,
+                    "offline",
+                    "Stopped responding to scans".to_string(),
+                    details,
+                )
+            });
+        } else {
+            events.push(TimelineEvent::new(at, &event_type, title, details));
+        }
+    }
+
+    // One event per scan run that produced results for the endpoint
+    let mut stmt = conn.prepare(
+        "SELECT r.started_at, GROUP_CONCAT(DISTINCT s.scan_type)
+         FROM scan_runs r
+         JOIN scan_results s
+           ON s.endpoint_id = ?1 AND s.scanned_at BETWEEN r.started_at AND r.finished_at
+         GROUP BY r.id
+         ORDER BY r.started_at",
+    )?;
+    let scans = stmt
+        .query_map([endpoint_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    for (at, scan_types) in scans {
+        let scan_types = scan_types.unwrap_or_default().replace(',', ", ");
+        events.push(TimelineEvent::new(
+            at,
+            "scanned",
+            "Answered a scan".to_string(),
+            (!scan_types.is_empty()).then_some(scan_types),
+        ));
+    }
+
+    // Stable, so same-second events keep the order above (first seen before its address)
+    events.sort_by_key(|event| event.at);
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+    use crate::db::scan_runs::create_scan_runs_table;
+
+    #[test]
+    fn test_endpoint_timeline() {
+        let conn = new_test_connection();
+        create_scan_runs_table(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                 scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL,
+                 response_time_ms INTEGER, details TEXT, ip TEXT);
+             INSERT INTO endpoints (id, created_at, name) VALUES (1, 100, 'nas'), (2, 100, 'tv');
+             INSERT INTO scan_runs (started_at, finished_at, scan_types, snapshot)
+                 VALUES (150, 160, 'arp,icmp', '{}'), (500, 510, 'icmp', '{}');
+             INSERT INTO scan_results (endpoint_id, scan_type, scanned_at) VALUES
+                 (1, 'arp', 151), (1, 'icmp', 152), (1, 'icmp', 505), (2, 'icmp', 155);
+             INSERT INTO notifications (created_at, event_type, title, endpoint_name, endpoint_id) VALUES
+                 (200, 'endpoint_renamed', 'Endpoint ''nas'' renamed', 'NAS', NULL),
+                 (300, 'endpoint_disappeared', 'Gone', 'nas', 1),
+                 (310, 'endpoint_disappeared', 'Gone', 'tv', 2);",
+        )
+        .unwrap();
+        EndPoint::record_ip_history(&conn, 1, "192.168.1.5", None, "arp", 120).unwrap();
+        EndPoint::record_ip_history(&conn, 2, "192.168.1.5", None, "arp", 400).unwrap();
+
+        let events = get_endpoint_timeline(&conn, 1).unwrap();
+        let kinds: Vec<(i64, &str)> = events.iter().map(|e| (e.at, e.kind.as_str())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (100, "first_seen"),
+                (120, "ip_bound"),
+                (150, "scanned"),
+                (200, "endpoint_renamed"),
+                (300, "offline"),
+                (400, "ip_released"),
+                (500, "scanned"),
+            ]
+        );
+        assert_eq!(events[2].details.as_deref(), Some("arp, icmp"));
+        assert_eq!(events[4].until, Some(505));
+
+        assert!(get_endpoint_timeline(&conn, 99).is_err());
+    }
+
+    #[test]
+    fn test_timeline_follows_ip_history() {
+        let conn = new_test_connection();
+        create_scan_runs_table(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                 scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL,
+                 response_time_ms INTEGER, details TEXT, ip TEXT);
+             INSERT INTO endpoints (id, created_at, name) VALUES
+                 (1, 100, 'laptop'), (2, 100, 'printer'), (3, 250, 'laptop-wifi');",
+        )
+        .unwrap();
+        let mac = Some("aa:bb:cc:00:00:01");
+        // The laptop moves from .10 to .11, and the printer is handed .10 afterwards
+        EndPoint::record_ip_history(&conn, 1, "192.168.1.10", mac, "dhcp", 110).unwrap();
+        EndPoint::record_ip_history(&conn, 1, "192.168.1.11", mac, "arp", 200).unwrap();
+        EndPoint::record_ip_history(&conn, 2, "192.168.1.10", None, "arp", 210).unwrap();
+        // A second endpoint for the laptop's other interface, merged into it later
+        EndPoint::record_ip_history(&conn, 3, "192.168.1.12", None, "ndp", 260).unwrap();
+
+        let timeline = |endpoint_id| {
+            get_endpoint_timeline(&conn, endpoint_id)
+                .unwrap()
+                .into_iter()
+                // IP changes also raise ip_changed notifications; only the history matters here
+                .filter(|e| matches!(e.kind.as_str(), "ip_bound" | "ip_released"))
+                .map(|e| (e.at, e.kind, e.title, e.details))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            timeline(1),
+            vec![
+                (
+                    110,
+                    "ip_bound".to_string(),
+                    "Seen at 192.168.1.10".to_string(),
+                    Some("aa:bb:cc:00:00:01 via dhcp".to_string())
+                ),
+                (
+                    200,
+                    "ip_bound".to_string(),
+                    "Seen at 192.168.1.11".to_string(),
+                    Some("aa:bb:cc:00:00:01 via arp".to_string())
+                ),
+                (
+                    210,
+                    "ip_released".to_string(),
+                    "No longer at 192.168.1.10".to_string(),
+                    None
+                ),
+            ]
+        );
+        assert_eq!(timeline(2).len(), 1);
+
+        EndPoint::reassign_ip_history(&conn, 3, 1).unwrap();
+        let merged = timeline(1);
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged[3].0, 260);
+        assert_eq!(merged[3].3.as_deref(), Some("via ndp"));
+        assert!(timeline(3).is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+//! Chart-ready time series. A range is split into minute, hour, or day buckets depending
+//! on how long it is, so a chart never gets more than a few hundred points however far
+//! back it goes. Traffic comes from the per-minute usage rollups for short ranges and the
+//! hourly ones otherwise; notifications are counted straight from their timestamps.
+//! Buckets with nothing in them are filled with zeros.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, Result, params_from_iter};
+use serde::Serialize;
+
+use super::usage::MINUTE_USAGE_RETENTION_SECS;
+
+/// Ranges up to this long are shown per minute
+const MINUTE_RANGE_SECS: i64 = 6 * 60 * 60;
+/// Ranges up to this long are shown per hour, longer ones per day
+const HOUR_RANGE_SECS: i64 = 14 * 24 * 60 * 60;
+/// Longest range that can be asked for
+pub const MAX_TIMESERIES_RANGE_SECS: i64 = 400 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSeriesMetric {
+    /// Bytes sent and received by endpoints
+    Bytes,
+    /// Packets sent and received by endpoints
+    Packets,
+    /// Endpoints with any traffic
+    Endpoints,
+    /// Notifications raised
+    Notifications,
+}
+
+impl TimeSeriesMetric {
+    pub fn parse(metric: &str) -> Option<Self> {
+        match metric {
+            "bytes" => Some(Self::Bytes),
+            "packets" => Some(Self::Packets),
+            "endpoints" => Some(Self::Endpoints),
+            "notifications" => Some(Self::Notifications),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    /// The finest resolution that keeps a range of `range_secs` to a chartable size
+    pub fn for_range(range_secs: i64) -> Self {
+        if range_secs <= MINUTE_RANGE_SECS {
+            Self::Minute
+        } else if range_secs <= HOUR_RANGE_SECS {
+            Self::Hour
+        } else {
+            Self::Day
+        }
+    }
+
+    pub fn seconds(self) -> i64 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TimePoint {
+    /// Start of the bucket (UTC)
+    pub t: i64,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeSeries {
+    pub metric: TimeSeriesMetric,
+    pub resolution: Resolution,
+    pub bucket_seconds: i64,
+    pub start: i64,
+    pub end: i64,
+    pub points: Vec<TimePoint>,
+}
+
+/// `metric` over `start..end`, optionally for only some endpoints. Traffic of a flow
+/// between two local endpoints counts for both of them.
+pub fn get_time_series(
+    conn: &Connection,
+    metric: TimeSeriesMetric,
+    start: i64,
+    end: i64,
+    endpoint_ids: Option<&[i64]>,
+    now: i64,
+) -> Result<TimeSeries> {
+    let mut resolution = Resolution::for_range(end - start);
+    // Minute rollups only go back so far
+    if resolution == Resolution::Minute
+        && metric != TimeSeriesMetric::Notifications
+        && start < now - MINUTE_USAGE_RETENTION_SECS
+    {
+        resolution = Resolution::Hour;
+    }
+    let bucket = resolution.seconds();
+    let first = start - start.rem_euclid(bucket);
+
+    let (table, time_column) = match (metric, resolution) {
+        (TimeSeriesMetric::Notifications, _) => ("notifications", "created_at"),
+        (_, Resolution::Minute) => ("endpoint_usage_minutes", "minute_start"),
+        _ => ("endpoint_usage_hourly", "hour_start"),
+    };
+    let value = match metric {
+        TimeSeriesMetric::Bytes => "SUM(bytes)",
+        TimeSeriesMetric::Packets => "SUM(packet_count)",
+        TimeSeriesMetric::Endpoints => "COUNT(DISTINCT endpoint_id)",
+        TimeSeriesMetric::Notifications => "COUNT(*)",
+    };
+    let endpoint_filter = match endpoint_ids {
+        Some(ids) => format!(
+            " AND endpoint_id IN ({})",
+            ids.iter().map(|_| "?").collect::<Vec<_>>().join(", ")
+        ),
+        None => String::new(),
+    };
+    let sql = format!(
+        "SELECT {time} - ({time} % {bucket}) AS bucket, {value}
+         FROM {table}
+         WHERE {time} >= {first} AND {time} < {end}{endpoint_filter}
+         GROUP BY bucket",
+        time = time_column,
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(params_from_iter(endpoint_ids.unwrap_or_default()), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let points = (first..end)
+        .step_by(bucket as usize)
+        .map(|t| TimePoint {
+            t,
+            value: rows.get(&t).copied().unwrap_or(0),
+        })
+        .collect();
+    Ok(TimeSeries {
+        metric,
+        resolution,
+        bucket_seconds: bucket,
+        start,
+        end,
+        points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::usage::record_usage;
+
+    #[test]
+    fn test_resolution_for_range() {
+        assert_eq!(Resolution::for_range(60 * 60), Resolution::Minute);
+        assert_eq!(Resolution::for_range(3 * 24 * 60 * 60), Resolution::Hour);
+        assert_eq!(Resolution::for_range(90 * 24 * 60 * 60), Resolution::Day);
+    }
+
+    #[test]
+    fn test_time_series_downsampling() {
+        let conn = crate::db::new_test_connection();
+        // Aligned to a day so hour and day buckets line up with the test's arithmetic
+        let day = 20_000 * 86400;
+        record_usage(&conn, 1, None, 2, 1000, day + 30).unwrap();
+        record_usage(&conn, 1, None, 1, 500, day + 90).unwrap();
+        record_usage(&conn, 2, None, 1, 250, day + 3 * 3600).unwrap();
+        record_usage(&conn, 2, None, 1, 100, day + 86400 + 10).unwrap();
+
+        // An hour is charted per minute
+        let now = day + 3600;
+        let series =
+            get_time_series(&conn, TimeSeriesMetric::Bytes, day, day + 3600, None, now).unwrap();
+        assert_eq!(series.resolution, Resolution::Minute);
+        assert_eq!(series.points.len(), 60);
+        assert_eq!(
+            series.points[0],
+            TimePoint {
+                t: day,
+                value: 1000
+            }
+        );
+        assert_eq!(series.points[1].value, 500);
+        assert_eq!(series.points[2].value, 0);
+
+        // A week per hour, for one endpoint only
+        let series = get_time_series(
+            &conn,
+            TimeSeriesMetric::Bytes,
+            day,
+            day + 7 * 86400,
+            Some([2].as_slice()),
+            now,
+        )
+        .unwrap();
+        assert_eq!(series.resolution, Resolution::Hour);
+        assert_eq!(series.points.len(), 7 * 24);
+        assert_eq!(series.points[0].value, 0);
+        assert_eq!(series.points[3].value, 250);
+
+        // Two months per day
+        let series = get_time_series(
+            &conn,
+            TimeSeriesMetric::Endpoints,
+            day,
+            day + 60 * 86400,
+            None,
+            now,
+        )
+        .unwrap();
+        assert_eq!(series.resolution, Resolution::Day);
+        assert_eq!(series.points.len(), 60);
+        assert_eq!(series.points[0].value, 2);
+        assert_eq!(series.points[1].value, 1);
+        assert_eq!(series.points[2].value, 0);
+
+        // Minute rollups that have been purged fall back to hours
+        let series = get_time_series(
+            &conn,
+            TimeSeriesMetric::Bytes,
+            day,
+            day + 3600,
+            None,
+            day + 30 * 86400,
+        )
+        .unwrap();
+        assert_eq!(series.resolution, Resolution::Hour);
+        assert_eq!(
+            series.points,
+            vec![TimePoint {
+                t: day,
+                value: 1500
+            }]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Traffic between local endpoints as a matrix, for chord diagrams and for questions like
+//! "does the camera ever talk to the NAS?". Built from the communication graph, leaving
+//! out internet hosts and anything else off the local network.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::graph_export::{CommunicationGraph, GraphNode};
+
+/// Most endpoints in a dense matrix; the busiest are kept
+pub const MAX_MATRIX_ENDPOINTS: usize = 150;
+
+/// Traffic one local endpoint sent another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MatrixLink {
+    pub source: i64,
+    pub target: i64,
+    pub bytes: i64,
+    pub packets: i64,
+    pub flows: i64,
+    pub protocols: Vec<String>,
+    pub last_seen_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TrafficMatrix {
+    /// Row and column order of the matrices
+    pub endpoints: Vec<GraphNode>,
+    /// `bytes[i][j]` is what `endpoints[i]` sent `endpoints[j]`; empty for the sparse form
+    pub bytes: Vec<Vec<i64>>,
+    /// Flow counts, laid out like `bytes`
+    pub flows: Vec<Vec<i64>>,
+    /// Every non-zero pair, busiest first; empty for the dense form
+    pub links: Vec<MatrixLink>,
+    /// Endpoints left out to stay within `MAX_MATRIX_ENDPOINTS`
+    pub omitted_endpoints: usize,
+}
+
+/// The local-to-local part of `graph`, as an N×N matrix or, when `sparse`, a list of pairs.
+/// `is_local` decides which endpoints take part.
+pub fn build_traffic_matrix(
+    graph: &CommunicationGraph,
+    is_local: impl Fn(&GraphNode) -> bool,
+    sparse: bool,
+) -> TrafficMatrix {
+    let local: HashMap<i64, &GraphNode> = graph
+        .nodes
+        .iter()
+        .filter(|node| is_local(node))
+        .map(|node| (node.id, node))
+        .collect();
+
+    let mut links: Vec<MatrixLink> = graph
+        .edges
+        .iter()
+        .filter(|edge| local.contains_key(&edge.source) && local.contains_key(&edge.target))
+        .map(|edge| MatrixLink {
+            source: edge.source,
+            target: edge.target,
+            bytes: edge.bytes,
+            packets: edge.packets,
+            flows: edge.flows,
+            protocols: edge.protocols.clone(),
+            last_seen_at: edge.last_seen_at,
+        })
+        .collect();
+    links.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then((a.source, a.target).cmp(&(b.source, b.target)))
+    });
+
+    // Endpoints with traffic, busiest first
+    let mut totals: HashMap<i64, i64> = HashMap::new();
+    for link in &links {
+        *totals.entry(link.source).or_default() += link.bytes;
+        *totals.entry(link.target).or_default() += link.bytes;
+    }
+    let mut ids: Vec<i64> = totals.keys().copied().collect();
+    ids.sort_by(|a, b| totals[b].cmp(&totals[a]).then(a.cmp(b)));
+
+    if sparse {
+        return TrafficMatrix {
+            endpoints: ids.iter().map(|id| local[id].clone()).collect(),
+            links,
+            ..Default::default()
+        };
+    }
+
+    let omitted_endpoints = ids.len().saturating_sub(MAX_MATRIX_ENDPOINTS);
+    ids.truncate(MAX_MATRIX_ENDPOINTS);
+    // Present the kept endpoints in a stable order, so the diagram doesn't reshuffle
+    ids.sort_unstable();
+    let index: HashMap<i64, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let mut bytes = vec![vec![0; ids.len()]; ids.len()];
+    let mut flows = vec![vec![0; ids.len()]; ids.len()];
+    for link in &links {
+        if let (Some(&row), Some(&column)) = (index.get(&link.source), index.get(&link.target)) {
+            bytes[row][column] = link.bytes;
+            flows[row][column] = link.flows;
+        }
+    }
+
+    TrafficMatrix {
+        endpoints: ids.iter().map(|id| local[id].clone()).collect(),
+        bytes,
+        flows,
+        links: Vec::new(),
+        omitted_endpoints,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::graph_export::GraphEdge;
+
+    fn node(id: i64, ip: &str) -> GraphNode {
+        GraphNode {
+            id,
+            label: format!("endpoint {}", id),
+            ip: Some(ip.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn edge(source: i64, target: i64, bytes: i64) -> GraphEdge {
+        GraphEdge {
+            source,
+            target,
+            bytes,
+            flows: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_traffic_matrix() {
+        let graph = CommunicationGraph {
+            nodes: vec![
+                node(1, "192.168.1.20"),
+                node(2, "192.168.1.30"),
+                node(3, "192.168.1.40"),
+                node(4, "8.8.8.8"),
+            ],
+            edges: vec![
+                edge(1, 2, 500),
+                edge(2, 1, 100),
+                edge(3, 2, 900),
+                // Internet traffic stays out of the matrix
+                edge(1, 4, 10_000),
+            ],
+        };
+        let is_local = |node: &GraphNode| {
+            node.ip
+                .as_deref()
+                .is_some_and(|ip| ip.starts_with("192.168."))
+        };
+
+        let dense = build_traffic_matrix(&graph, is_local, false);
+        assert_eq!(
+            dense.endpoints.iter().map(|n| n.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            dense.bytes,
+            vec![vec![0, 500, 0], vec![100, 0, 0], vec![0, 900, 0]]
+        );
+        assert_eq!(dense.flows[2][1], 1);
+        assert!(dense.links.is_empty());
+
+        let sparse = build_traffic_matrix(&graph, is_local, true);
+        assert!(sparse.bytes.is_empty());
+        assert_eq!(
+            sparse
+                .links
+                .iter()
+                .map(|l| (l.source, l.target, l.bytes))
+                .collect::<Vec<_>>(),
+            vec![(3, 2, 900), (1, 2, 500), (2, 1, 100)]
+        );
+        assert_eq!(sparse.endpoints[0].id, 2);
+    }
+}
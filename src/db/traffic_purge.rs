@@ -0,0 +1,149 @@
+//! Per-endpoint traffic purge, for a shared household where someone wants their device's
+//! history wiped. Everything recorded from the endpoint's traffic goes: communications in
+//! either direction, usage rollups, DNS server use, blocklist hits, broadcast tallies, and
+//! the alerts raised from them. Names in the shared DNS and internet destination history
+//! go too when no other endpoint visited the same site. The endpoint itself, its
+//! addresses, scan results, and anything set by hand are kept.
+
+use std::collections::{BTreeMap, HashSet};
+
+use rusqlite::{Connection, Result, params};
+
+use super::mark_endpoint_summary_dirty;
+use crate::network::domain_categories::site_domain;
+
+/// Tables keyed by the endpoint, with the column holding its id
+const ENDPOINT_TRAFFIC_TABLES: &[(&str, &str)] = &[
+    ("endpoint_usage_hourly", "endpoint_id"),
+    ("endpoint_usage_minutes", "endpoint_id"),
+    ("endpoint_usage_sites", "endpoint_id"),
+    ("dns_server_usage", "endpoint_id"),
+    ("blocklist_hits", "endpoint_id"),
+    ("broadcast_traffic", "src_endpoint_id"),
+    ("type_baseline_deviations", "endpoint_id"),
+    ("bandwidth_quota_alerts", "endpoint_id"),
+    ("port_policy_drift", "endpoint_id"),
+];
+
+/// Sites in an endpoint's usage that no other endpoint visited
+fn exclusive_sites(conn: &Connection, endpoint_id: i64) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT site FROM endpoint_usage_sites s
+         WHERE endpoint_id = ?1
+           AND NOT EXISTS (
+               SELECT 1 FROM endpoint_usage_sites o
+               WHERE o.site = s.site AND o.endpoint_id != ?1
+           )",
+    )?;
+    stmt.query_map([endpoint_id], |row| row.get(0))?.collect()
+}
+
+/// Delete rows of a hostname table whose site is one of `sites`
+fn purge_hostnames(
+    conn: &Connection,
+    table: &str,
+    key: &str,
+    sites: &HashSet<String>,
+) -> Result<usize> {
+    let hostnames: Vec<String> = conn
+        .prepare(&format!("SELECT DISTINCT hostname FROM {}", table))?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    let mut delete = conn.prepare(&format!("DELETE FROM {} WHERE {} = ?1", table, key))?;
+    let mut removed = 0;
+    for hostname in hostnames {
+        if sites.contains(&site_domain(&hostname)) {
+            removed += delete.execute([&hostname])?;
+        }
+    }
+    Ok(removed)
+}
+
+/// Remove an endpoint's traffic history, keeping the endpoint. Returns the rows removed
+/// from each table.
+pub fn purge_endpoint_traffic(
+    conn: &Connection,
+    endpoint_id: i64,
+) -> Result<BTreeMap<&'static str, usize>> {
+    let tx = conn.unchecked_transaction()?;
+    let mut removed = BTreeMap::new();
+
+    // Before the usage rows naming the sites are gone
+    let sites = exclusive_sites(&tx, endpoint_id)?;
+    if !sites.is_empty() {
+        removed.insert(
+            "dns_entries",
+            purge_hostnames(&tx, "dns_entries", "hostname", &sites)?,
+        );
+        removed.insert(
+            "internet_destinations",
+            purge_hostnames(&tx, "internet_destinations", "hostname", &sites)?,
+        );
+    }
+
+    removed.insert(
+        "communications",
+        tx.execute(
+            "DELETE FROM communications WHERE src_endpoint_id = ?1 OR dst_endpoint_id = ?1",
+            params![endpoint_id],
+        )?,
+    );
+    for (table, column) in ENDPOINT_TRAFFIC_TABLES {
+        removed.insert(
+            *table,
+            tx.execute(
+                &format!("DELETE FROM {} WHERE {} = ?1", table, column),
+                params![endpoint_id],
+            )?,
+        );
+    }
+    tx.commit()?;
+
+    // Its summary no longer has traffic to show
+    mark_endpoint_summary_dirty(&[endpoint_id]);
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+    use crate::db::usage::record_usage;
+
+    #[test]
+    fn test_purge_keeps_endpoint_and_shared_history() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'laptop'), (2, 1, 'tv');
+             INSERT INTO endpoint_attributes (endpoint_id, created_at, mac, ip, hostname)
+                 VALUES (1, 1, 'aa:bb:cc:dd:ee:01', '192.168.1.10', 'laptop');
+             INSERT INTO communications (src_endpoint_id, dst_endpoint_id, created_at,
+                 last_seen_at) VALUES (1, 2, 1, 1), (2, 1, 1, 1);
+             INSERT INTO dns_entries (ip, hostname, first_seen_at, last_seen_at) VALUES
+                 ('1.1.1.1', 'www.private.example', 1, 1),
+                 ('2.2.2.2', 'cdn.shared.example', 1, 1);",
+        )
+        .unwrap();
+        record_usage(&conn, 1, Some("www.private.example"), 1, 100, 1000).unwrap();
+        record_usage(&conn, 1, Some("cdn.shared.example"), 1, 100, 1000).unwrap();
+        record_usage(&conn, 2, Some("shared.example"), 1, 100, 1000).unwrap();
+
+        let removed = purge_endpoint_traffic(&conn, 1).unwrap();
+        assert_eq!(removed["communications"], 2);
+        assert_eq!(removed["endpoint_usage_sites"], 2);
+        assert_eq!(removed["dns_entries"], 1);
+
+        let count = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM endpoints"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM endpoint_attributes"), 1);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM endpoint_usage_hourly WHERE endpoint_id = 2"),
+            1
+        );
+        // The site another endpoint also visited stays in the DNS history
+        assert_eq!(
+            count("SELECT COUNT(*) FROM dns_entries WHERE hostname = 'cdn.shared.example'"),
+            1
+        );
+    }
+}
@@ -0,0 +1,473 @@
+//! Behavioral baselines per device type. While a type is learning, every (port, protocol,
+//! destination) its endpoints connect to is recorded, where the destination is the LAN or
+//! the internet. Learning stops when the configured period is over; once the baseline is
+//! switched to enforce, a connection outside it raises one `baseline_deviation`
+//! notification per endpoint and behavior. Unlike port policies, which list what an
+//! endpoint serves, this covers what every device of a type is seen doing.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{Deserialize, Serialize};
+
+use super::insert_notification_with_endpoint_id;
+use crate::network::direction::TrafficDirection;
+
+pub const BASELINE_LEARNING: &str = "learning";
+pub const BASELINE_ENFORCE: &str = "enforce";
+
+/// Longest learning period that can be asked for
+const MAX_LEARNING_DAYS: i64 = 365;
+
+/// Where a connection went
+pub fn destination_type(direction: TrafficDirection) -> &'static str {
+    match direction {
+        TrafficDirection::LanToLan => "lan",
+        TrafficDirection::Inbound | TrafficDirection::Outbound => "internet",
+    }
+}
+
+/// A baseline change as submitted through the API
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TypeBaselineRequest {
+    pub device_type: String,
+    /// "learning" or "enforce"
+    pub mode: String,
+    /// Learning period; the `baseline_learning_days` setting when missing
+    #[serde(default)]
+    pub learning_days: Option<i64>,
+    /// Forget what was learned before learning again
+    #[serde(default)]
+    pub reset: bool,
+}
+
+impl TypeBaselineRequest {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.mode != BASELINE_LEARNING && self.mode != BASELINE_ENFORCE {
+            return Err(format!(
+                "Unknown mode '{}' (expected {} or {})",
+                self.mode, BASELINE_LEARNING, BASELINE_ENFORCE
+            ));
+        }
+        if let Some(days) = self.learning_days
+            && !(1..=MAX_LEARNING_DAYS).contains(&days)
+        {
+            return Err(format!(
+                "learning_days must be between 1 and {}",
+                MAX_LEARNING_DAYS
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeBaseline {
+    pub device_type: String,
+    pub mode: String,
+    pub learning_started_at: i64,
+    pub learning_until: i64,
+    /// Behaviors recorded for the type
+    pub behavior_count: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BaselineBehavior {
+    pub device_type: String,
+    pub port: u16,
+    pub protocol: String,
+    /// "lan" or "internet"
+    pub destination_type: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+/// A connection outside an endpoint's type baseline
+#[derive(Debug, Clone, Serialize)]
+pub struct BaselineDeviation {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub device_type: String,
+    pub port: u16,
+    pub protocol: String,
+    pub destination_type: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+pub fn create_type_baseline_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS type_baselines (
+            device_type TEXT PRIMARY KEY,
+            mode TEXT NOT NULL,
+            learning_started_at INTEGER NOT NULL,
+            learning_until INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS type_baseline_behaviors (
+            device_type TEXT NOT NULL,
+            port INTEGER NOT NULL,
+            protocol TEXT NOT NULL,
+            destination_type TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            PRIMARY KEY (device_type, port, protocol, destination_type)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS type_baseline_deviations (
+            endpoint_id INTEGER NOT NULL,
+            device_type TEXT NOT NULL,
+            port INTEGER NOT NULL,
+            protocol TEXT NOT NULL,
+            destination_type TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, port, protocol, destination_type)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Start learning a device type for `learning_days` from `now`, or switch it to enforce.
+/// Learning again keeps what was learned unless `reset` is set.
+pub fn set_type_baseline_mode(
+    conn: &Connection,
+    device_type: &str,
+    mode: &str,
+    learning_days: i64,
+    reset: bool,
+    now: i64,
+) -> Result<()> {
+    if mode == BASELINE_LEARNING {
+        if reset {
+            conn.execute(
+                "DELETE FROM type_baseline_behaviors WHERE device_type = ?1",
+                [device_type],
+            )?;
+        }
+        conn.execute(
+            "INSERT INTO type_baselines
+                 (device_type, mode, learning_started_at, learning_until, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?3)
+             ON CONFLICT(device_type) DO UPDATE SET
+                mode = excluded.mode,
+                learning_started_at = excluded.learning_started_at,
+                learning_until = excluded.learning_until,
+                updated_at = excluded.updated_at",
+            params![device_type, mode, now, now + learning_days * 24 * 60 * 60],
+        )?;
+    } else {
+        // Enforcing ends any learning still under way
+        conn.execute(
+            "INSERT INTO type_baselines
+                 (device_type, mode, learning_started_at, learning_until, updated_at)
+             VALUES (?1, ?2, ?3, ?3, ?3)
+             ON CONFLICT(device_type) DO UPDATE SET
+                mode = excluded.mode,
+                learning_until = MIN(learning_until, excluded.updated_at),
+                updated_at = excluded.updated_at",
+            params![device_type, mode, now],
+        )?;
+    }
+    // Deviations from the old baseline may be within the new one
+    conn.execute(
+        "DELETE FROM type_baseline_deviations WHERE device_type = ?1",
+        [device_type],
+    )?;
+    Ok(())
+}
+
+/// Remove a type's baseline and everything learned for it. Returns false when it didn't exist.
+pub fn delete_type_baseline(conn: &Connection, device_type: &str) -> Result<bool> {
+    conn.execute(
+        "DELETE FROM type_baseline_behaviors WHERE device_type = ?1",
+        [device_type],
+    )?;
+    conn.execute(
+        "DELETE FROM type_baseline_deviations WHERE device_type = ?1",
+        [device_type],
+    )?;
+    Ok(conn.execute(
+        "DELETE FROM type_baselines WHERE device_type = ?1",
+        [device_type],
+    )? > 0)
+}
+
+pub fn get_type_baselines(conn: &Connection) -> Result<Vec<TypeBaseline>> {
+    let mut stmt = conn.prepare(
+        "SELECT b.device_type, b.mode, b.learning_started_at, b.learning_until,
+                (SELECT COUNT(*) FROM type_baseline_behaviors t
+                 WHERE t.device_type = b.device_type),
+                b.updated_at
+         FROM type_baselines b
+         ORDER BY b.device_type",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TypeBaseline {
+            device_type: row.get(0)?,
+            mode: row.get(1)?,
+            learning_started_at: row.get(2)?,
+            learning_until: row.get(3)?,
+            behavior_count: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// What was learned for a device type, by port
+pub fn get_baseline_behaviors(
+    conn: &Connection,
+    device_type: &str,
+) -> Result<Vec<BaselineBehavior>> {
+    let mut stmt = conn.prepare(
+        "SELECT device_type, port, protocol, destination_type, first_seen_at, last_seen_at
+         FROM type_baseline_behaviors
+         WHERE device_type = ?1
+         ORDER BY port, protocol, destination_type",
+    )?;
+    let rows = stmt.query_map([device_type], |row| {
+        Ok(BaselineBehavior {
+            device_type: row.get(0)?,
+            port: row.get(1)?,
+            protocol: row.get(2)?,
+            destination_type: row.get(3)?,
+            first_seen_at: row.get(4)?,
+            last_seen_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Connections seen outside their type's baseline, most recent first
+pub fn get_baseline_deviations(conn: &Connection) -> Result<Vec<BaselineDeviation>> {
+    let mut stmt = conn.prepare(
+        "SELECT d.endpoint_id, e.display_name, d.device_type, d.port, d.protocol,
+                d.destination_type, d.first_seen_at, d.last_seen_at
+         FROM type_baseline_deviations d
+         LEFT JOIN endpoints e ON e.id = d.endpoint_id
+         ORDER BY d.last_seen_at DESC, d.port",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(BaselineDeviation {
+            endpoint_id: row.get(0)?,
+            endpoint_name: row.get(1)?,
+            device_type: row.get(2)?,
+            port: row.get(3)?,
+            protocol: row.get(4)?,
+            destination_type: row.get(5)?,
+            first_seen_at: row.get(6)?,
+            last_seen_at: row.get(7)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Check a connection an endpoint made against its type's baseline: learned while the
+/// type is learning, and recorded as a deviation (notified the first time) when it is
+/// enforced and the baseline doesn't have it. Returns whether it was a deviation.
+pub fn check_type_baseline(
+    conn: &Connection,
+    endpoint_id: i64,
+    port: u16,
+    protocol: &str,
+    destination_type: &str,
+    now: i64,
+) -> Result<bool> {
+    let Some((device_type, mode, learning_until)) = conn
+        .prepare_cached(
+            "SELECT b.device_type, b.mode, b.learning_until
+             FROM endpoints e
+             JOIN type_baselines b
+               ON b.device_type = COALESCE(e.manual_device_type, e.auto_device_type)
+             WHERE e.id = ?1",
+        )?
+        .query_row([endpoint_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })
+        .optional()?
+    else {
+        return Ok(false);
+    };
+
+    if mode == BASELINE_LEARNING {
+        if now < learning_until {
+            conn.prepare_cached(
+                "INSERT INTO type_baseline_behaviors
+                     (device_type, port, protocol, destination_type, first_seen_at, last_seen_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+                 ON CONFLICT(device_type, port, protocol, destination_type)
+                 DO UPDATE SET last_seen_at = excluded.last_seen_at",
+            )?
+            .execute(params![device_type, port, protocol, destination_type, now])?;
+        }
+        return Ok(false);
+    }
+
+    let known = conn
+        .prepare_cached(
+            "UPDATE type_baseline_behaviors SET last_seen_at = ?5
+             WHERE device_type = ?1 AND port = ?2 AND protocol = ?3 AND destination_type = ?4",
+        )?
+        .execute(params![device_type, port, protocol, destination_type, now])?
+        > 0;
+    if known {
+        return Ok(false);
+    }
+
+    let new =
+        conn.prepare_cached(
+            "INSERT OR IGNORE INTO type_baseline_deviations
+                 (endpoint_id, device_type, port, protocol, destination_type,
+                  first_seen_at, last_seen_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+        )?
+        .execute(params![
+            endpoint_id,
+            device_type,
+            port,
+            protocol,
+            destination_type,
+            now
+        ])? > 0;
+    if !new {
+        conn.execute(
+            "UPDATE type_baseline_deviations SET last_seen_at = ?5
+             WHERE endpoint_id = ?1 AND port = ?2 AND protocol = ?3 AND destination_type = ?4",
+            params![endpoint_id, port, protocol, destination_type, now],
+        )?;
+        return Ok(true);
+    }
+
+    let name: Option<String> = conn
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let name = name.unwrap_or_else(|| format!("endpoint {}", endpoint_id));
+    let details = format!(
+        "{} port {} ({}) wasn't seen while learning {} devices.",
+        protocol, port, destination_type, device_type
+    );
+    insert_notification_with_endpoint_id(
+        conn,
+        "baseline_deviation",
+        &format!(
+            "{} connected to {} port {} outside the {} baseline",
+            name, protocol, port, device_type
+        ),
+        Some(&details),
+        Some(&name),
+        Some(endpoint_id),
+    );
+    Ok(true)
+}
+
+/// Point deviations at the endpoint a merged endpoint was folded into
+pub fn reassign_type_baseline_deviations(
+    conn: &Connection,
+    from_id: i64,
+    to_id: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE type_baseline_deviations SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM type_baseline_deviations WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_type_baseline_learn_then_enforce() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'living-room-tv', 'tv'), (2, 1, 'den-tv', 'tv'),
+                 (3, 1, 'laptop', 'computer');",
+        )
+        .unwrap();
+        set_type_baseline_mode(&conn, "tv", BASELINE_LEARNING, 7, false, 1000).unwrap();
+
+        // Learned from any TV; types without a baseline are ignored
+        assert!(!check_type_baseline(&conn, 1, 443, "TCP", "internet", 1100).unwrap());
+        assert!(!check_type_baseline(&conn, 2, 8009, "TCP", "lan", 1200).unwrap());
+        assert!(!check_type_baseline(&conn, 3, 22, "TCP", "internet", 1200).unwrap());
+        // Nothing is learned once the period is over
+        let after = 1000 + 7 * 24 * 60 * 60;
+        assert!(!check_type_baseline(&conn, 1, 23, "TCP", "internet", after).unwrap());
+        assert_eq!(get_baseline_behaviors(&conn, "tv").unwrap().len(), 2);
+
+        set_type_baseline_mode(&conn, "tv", BASELINE_ENFORCE, 7, false, after).unwrap();
+        let baseline = &get_type_baselines(&conn).unwrap()[0];
+        assert_eq!(baseline.mode, BASELINE_ENFORCE);
+        assert_eq!(baseline.behavior_count, 2);
+
+        // What the other TV was seen doing is fine for this one
+        assert!(!check_type_baseline(&conn, 1, 8009, "TCP", "lan", after + 10).unwrap());
+        assert!(check_type_baseline(&conn, 1, 23, "TCP", "internet", after + 10).unwrap());
+        assert!(check_type_baseline(&conn, 1, 23, "TCP", "internet", after + 20).unwrap());
+        assert!(check_type_baseline(&conn, 2, 443, "UDP", "internet", after + 30).unwrap());
+
+        let deviations = get_baseline_deviations(&conn).unwrap();
+        assert_eq!(deviations.len(), 2);
+        let alerts: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = 'baseline_deviation'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(alerts, 2);
+
+        reassign_type_baseline_deviations(&conn, 2, 1).unwrap();
+        assert!(
+            get_baseline_deviations(&conn)
+                .unwrap()
+                .iter()
+                .all(|d| d.endpoint_id == 1)
+        );
+
+        // Learning again with a reset starts from nothing
+        set_type_baseline_mode(&conn, "tv", BASELINE_LEARNING, 1, true, after + 40).unwrap();
+        assert!(get_baseline_behaviors(&conn, "tv").unwrap().is_empty());
+        assert!(get_baseline_deviations(&conn).unwrap().is_empty());
+        assert!(delete_type_baseline(&conn, "tv").unwrap());
+        assert!(!delete_type_baseline(&conn, "tv").unwrap());
+    }
+
+    #[test]
+    fn test_baseline_request_validation() {
+        let request = |mode: &str, learning_days| TypeBaselineRequest {
+            device_type: "tv".to_string(),
+            mode: mode.to_string(),
+            learning_days,
+            reset: false,
+        };
+        assert!(request("learning", None).validate().is_ok());
+        assert!(request("enforce", Some(30)).validate().is_ok());
+        assert!(request("alert", None).validate().is_err());
+        assert!(request("learning", Some(0)).validate().is_err());
+        assert_eq!(destination_type(TrafficDirection::LanToLan), "lan");
+        assert_eq!(destination_type(TrafficDirection::Outbound), "internet");
+    }
+}
@@ -0,0 +1,388 @@
+//! UPnP event subscriptions and the device state they report. Each accepted GENA
+//! subscription is kept by SID so the NOTIFY callback can tell which endpoint and service
+//! an event belongs to; the latest value of every evented variable is kept per endpoint
+//! and shown with its details. A gateway's external address changing or its WAN
+//! connection dropping is raised as a notification.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use super::insert_notification_with_endpoint_id;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpnpSubscription {
+    pub sid: String,
+    pub endpoint_id: i64,
+    /// Address the device was subscribed at; events must come from it
+    pub ip: String,
+    pub service_type: String,
+    pub event_url: String,
+    pub expires_at: i64,
+}
+
+/// An SSDP device description that can be checked for evented services
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpnpEventSource {
+    pub endpoint_id: i64,
+    pub ip: String,
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpnpStateVariable {
+    /// Short service name, e.g. "AVTransport"
+    pub service: String,
+    pub variable: String,
+    pub value: String,
+    pub updated_at: i64,
+}
+
+pub fn create_upnp_events_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS upnp_subscriptions (
+            sid TEXT PRIMARY KEY,
+            endpoint_id INTEGER NOT NULL,
+            ip TEXT NOT NULL,
+            service_type TEXT NOT NULL,
+            event_url TEXT NOT NULL,
+            expires_at INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS upnp_device_state (
+            endpoint_id INTEGER NOT NULL,
+            service TEXT NOT NULL,
+            variable TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, service, variable)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Store a subscription, or its new expiry after a renewal
+pub fn save_upnp_subscription(
+    conn: &Connection,
+    subscription: &UpnpSubscription,
+    now: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO upnp_subscriptions
+             (sid, endpoint_id, ip, service_type, event_url, expires_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(sid) DO UPDATE SET expires_at = excluded.expires_at",
+        params![
+            subscription.sid,
+            subscription.endpoint_id,
+            subscription.ip,
+            subscription.service_type,
+            subscription.event_url,
+            subscription.expires_at,
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn delete_upnp_subscription(conn: &Connection, sid: &str) -> Result<()> {
+    conn.execute("DELETE FROM upnp_subscriptions WHERE sid = ?1", [sid])?;
+    Ok(())
+}
+
+fn subscription_from_row(row: &rusqlite::Row) -> Result<UpnpSubscription> {
+    Ok(UpnpSubscription {
+        sid: row.get(0)?,
+        endpoint_id: row.get(1)?,
+        ip: row.get(2)?,
+        service_type: row.get(3)?,
+        event_url: row.get(4)?,
+        expires_at: row.get(5)?,
+    })
+}
+
+pub fn find_upnp_subscription(conn: &Connection, sid: &str) -> Result<Option<UpnpSubscription>> {
+    conn.query_row(
+        "SELECT sid, endpoint_id, ip, service_type, event_url, expires_at
+         FROM upnp_subscriptions WHERE sid = ?1",
+        [sid],
+        subscription_from_row,
+    )
+    .optional()
+}
+
+/// Subscriptions that lapse before `before` and need renewing
+pub fn get_expiring_upnp_subscriptions(
+    conn: &Connection,
+    before: i64,
+) -> Result<Vec<UpnpSubscription>> {
+    let mut stmt = conn.prepare(
+        "SELECT sid, endpoint_id, ip, service_type, event_url, expires_at
+         FROM upnp_subscriptions WHERE expires_at < ?1
+         ORDER BY expires_at",
+    )?;
+    stmt.query_map([before], subscription_from_row)?.collect()
+}
+
+/// Device descriptions from SSDP scans of endpoints that have no subscription yet
+pub fn get_unsubscribed_upnp_devices(conn: &Connection) -> Result<Vec<UpnpEventSource>> {
+    let mut stmt = conn.prepare(
+        "SELECT endpoint_id, ip, json_extract(details, '$.location') AS location,
+                MAX(scanned_at)
+         FROM scan_results s
+         WHERE scan_type = 'ssdp' AND ip IS NOT NULL
+           AND json_valid(details)
+           AND COALESCE(json_extract(details, '$.location'), '') != ''
+           AND NOT EXISTS (SELECT 1 FROM upnp_subscriptions u WHERE u.endpoint_id = s.endpoint_id)
+         GROUP BY endpoint_id, location
+         ORDER BY endpoint_id",
+    )?;
+    stmt.query_map([], |row| {
+        Ok(UpnpEventSource {
+            endpoint_id: row.get(0)?,
+            ip: row.get(1)?,
+            location: row.get(2)?,
+        })
+    })?
+    .collect()
+}
+
+fn endpoint_name(conn: &Connection, endpoint_id: i64) -> Result<String> {
+    let name: Option<String> = conn
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    Ok(name.unwrap_or_else(|| format!("endpoint {}", endpoint_id)))
+}
+
+/// Record the variables from one event. Returns how many changed.
+pub fn record_upnp_event(
+    conn: &Connection,
+    subscription: &UpnpSubscription,
+    variables: &[(String, String)],
+    now: i64,
+) -> Result<usize> {
+    let service = crate::scanner::gena::short_service_name(&subscription.service_type);
+    let endpoint_id = subscription.endpoint_id;
+    let mut changed = 0;
+
+    for (variable, value) in variables {
+        let previous: Option<String> = conn
+            .query_row(
+                "SELECT value FROM upnp_device_state
+                 WHERE endpoint_id = ?1 AND service = ?2 AND variable = ?3",
+                params![endpoint_id, service, variable],
+                |row| row.get(0),
+            )
+            .optional()?;
+        conn.execute(
+            "INSERT INTO upnp_device_state (endpoint_id, service, variable, value, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(endpoint_id, service, variable)
+             DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![endpoint_id, service, variable, value, now],
+        )?;
+        if previous.as_deref() == Some(value.as_str()) {
+            continue;
+        }
+        changed += 1;
+
+        // The first event after subscribing carries every variable; only changes from a
+        // known value are news
+        let Some(previous) = previous else {
+            continue;
+        };
+        match variable.as_str() {
+            "ExternalIPAddress" if !value.is_empty() => {
+                let name = endpoint_name(conn, endpoint_id)?;
+                insert_notification_with_endpoint_id(
+                    conn,
+                    "upnp_external_ip_changed",
+                    &format!("External address of {} changed to {}", name, value),
+                    Some(&format!("The gateway reported {} before.", previous)),
+                    Some(&name),
+                    Some(endpoint_id),
+                );
+            }
+            "ConnectionStatus" if previous == "Connected" => {
+                let name = endpoint_name(conn, endpoint_id)?;
+                insert_notification_with_endpoint_id(
+                    conn,
+                    "upnp_wan_disconnected",
+                    &format!("WAN connection on {} is {}", name, value),
+                    Some(&format!(
+                        "{} reported its WAN connection as {}.",
+                        service, value
+                    )),
+                    Some(&name),
+                    Some(endpoint_id),
+                );
+            }
+            _ => {}
+        }
+    }
+    Ok(changed)
+}
+
+/// Latest evented state reported by an endpoint
+pub fn get_upnp_device_state(
+    conn: &Connection,
+    endpoint_id: i64,
+) -> Result<Vec<UpnpStateVariable>> {
+    let mut stmt = conn.prepare(
+        "SELECT service, variable, value, updated_at FROM upnp_device_state
+         WHERE endpoint_id = ?1
+         ORDER BY service, variable",
+    )?;
+    stmt.query_map([endpoint_id], |row| {
+        Ok(UpnpStateVariable {
+            service: row.get(0)?,
+            variable: row.get(1)?,
+            value: row.get(2)?,
+            updated_at: row.get(3)?,
+        })
+    })?
+    .collect()
+}
+
+/// Move subscriptions and device state to the endpoint another was merged into
+pub fn reassign_upnp_events(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE upnp_subscriptions SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "UPDATE OR IGNORE upnp_device_state SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM upnp_device_state WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn notifications(conn: &Connection, event_type: &str) -> i64 {
+        conn.query_row(
+            "SELECT COUNT(*) FROM notifications WHERE event_type = ?1",
+            [event_type],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record_upnp_event() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'gateway'), (2, 1, 'tv')",
+            [],
+        )
+        .unwrap();
+        let subscription = UpnpSubscription {
+            sid: "uuid:1".to_string(),
+            endpoint_id: 1,
+            ip: "192.168.1.1".to_string(),
+            service_type: "urn:schemas-upnp-org:service:WANIPConnection:1".to_string(),
+            event_url: "http://192.168.1.1:5000/evt/IPConn".to_string(),
+            expires_at: 1900,
+        };
+        save_upnp_subscription(&conn, &subscription, 100).unwrap();
+        assert_eq!(
+            find_upnp_subscription(&conn, "uuid:1").unwrap(),
+            Some(subscription.clone())
+        );
+        assert_eq!(
+            get_expiring_upnp_subscriptions(&conn, 1900).unwrap().len(),
+            0
+        );
+        assert_eq!(
+            get_expiring_upnp_subscriptions(&conn, 2000).unwrap().len(),
+            1
+        );
+
+        let event = |ip: &str, status: &str| {
+            vec![
+                ("ExternalIPAddress".to_string(), ip.to_string()),
+                ("ConnectionStatus".to_string(), status.to_string()),
+            ]
+        };
+        // The initial event only records state
+        assert_eq!(
+            record_upnp_event(
+                &conn,
+                &subscription,
+                &event("203.0.113.7", "Connected"),
+                100
+            )
+            .unwrap(),
+            2
+        );
+        assert_eq!(
+            record_upnp_event(
+                &conn,
+                &subscription,
+                &event("203.0.113.7", "Connected"),
+                110
+            )
+            .unwrap(),
+            0
+        );
+        assert_eq!(notifications(&conn, "upnp_external_ip_changed"), 0);
+
+        assert_eq!(
+            record_upnp_event(
+                &conn,
+                &subscription,
+                &event("198.51.100.4", "Disconnected"),
+                120
+            )
+            .unwrap(),
+            2
+        );
+        assert_eq!(notifications(&conn, "upnp_external_ip_changed"), 1);
+        assert_eq!(notifications(&conn, "upnp_wan_disconnected"), 1);
+        // Staying down isn't raised again
+        record_upnp_event(
+            &conn,
+            &subscription,
+            &event("198.51.100.4", "Disconnected"),
+            130,
+        )
+        .unwrap();
+        assert_eq!(notifications(&conn, "upnp_wan_disconnected"), 1);
+
+        let state = get_upnp_device_state(&conn, 1).unwrap();
+        assert_eq!(state.len(), 2);
+        assert_eq!(state[0].service, "WANIPConnection");
+        assert_eq!(state[0].variable, "ConnectionStatus");
+        assert_eq!(state[0].value, "Disconnected");
+        assert_eq!(state[1].updated_at, 130);
+
+        reassign_upnp_events(&conn, 1, 2).unwrap();
+        assert!(get_upnp_device_state(&conn, 1).unwrap().is_empty());
+        assert_eq!(get_upnp_device_state(&conn, 2).unwrap().len(), 2);
+        assert_eq!(
+            find_upnp_subscription(&conn, "uuid:1")
+                .unwrap()
+                .unwrap()
+                .endpoint_id,
+            2
+        );
+
+        delete_upnp_subscription(&conn, "uuid:1").unwrap();
+        assert_eq!(find_upnp_subscription(&conn, "uuid:1").unwrap(), None);
+    }
+}
@@ -0,0 +1,209 @@
+//! Port mappings reported by UPnP gateways, keyed by gateway address. Each audit
+//! replaces a gateway's table; mappings seen for the first time are returned so the
+//! caller can raise notifications for them.
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+use crate::scanner::UpnpPortMapping;
+
+pub fn create_upnp_port_mappings_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS upnp_port_mappings (
+            id INTEGER PRIMARY KEY,
+            gateway_ip TEXT NOT NULL,
+            remote_host TEXT NOT NULL DEFAULT '',
+            external_port INTEGER NOT NULL,
+            protocol TEXT NOT NULL,
+            internal_client TEXT NOT NULL,
+            internal_port INTEGER NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            description TEXT,
+            lease_duration INTEGER NOT NULL DEFAULT 0,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            UNIQUE(gateway_ip, protocol, external_port, remote_host)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A stored mapping with the internal device resolved to an endpoint when known
+#[derive(Debug, Serialize)]
+pub struct UpnpPortMappingRecord {
+    pub gateway_ip: String,
+    pub gateway_name: Option<String>,
+    pub remote_host: String,
+    pub external_port: u16,
+    pub protocol: String,
+    pub internal_client: String,
+    pub internal_name: Option<String>,
+    pub internal_port: u16,
+    pub enabled: bool,
+    pub description: Option<String>,
+    pub lease_duration: u32,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+/// Replace a gateway's mappings with the ones seen at `now`, returning those that were
+/// not there before (or that now point somewhere else)
+pub fn record_port_mappings(
+    conn: &Connection,
+    gateway_ip: &str,
+    mappings: &[UpnpPortMapping],
+    now: i64,
+) -> Result<Vec<UpnpPortMapping>> {
+    let mut new_mappings = Vec::new();
+    let mut existing = conn.prepare_cached(
+        "SELECT internal_client, internal_port FROM upnp_port_mappings
+         WHERE gateway_ip = ?1 AND protocol = ?2 AND external_port = ?3 AND remote_host = ?4",
+    )?;
+    let mut upsert = conn.prepare_cached(
+        "INSERT INTO upnp_port_mappings
+            (gateway_ip, remote_host, external_port, protocol, internal_client, internal_port,
+             enabled, description, lease_duration, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)
+         ON CONFLICT(gateway_ip, protocol, external_port, remote_host) DO UPDATE SET
+            first_seen_at = CASE
+                WHEN internal_client = excluded.internal_client
+                 AND internal_port = excluded.internal_port THEN first_seen_at
+                ELSE excluded.first_seen_at END,
+            internal_client = excluded.internal_client,
+            internal_port = excluded.internal_port,
+            enabled = excluded.enabled,
+            description = excluded.description,
+            lease_duration = excluded.lease_duration,
+            last_seen_at = excluded.last_seen_at",
+    )?;
+
+    for mapping in mappings {
+        let previous: Option<(String, u16)> = existing
+            .query_row(
+                params![
+                    gateway_ip,
+                    mapping.protocol,
+                    mapping.external_port,
+                    mapping.remote_host
+                ],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .ok();
+        if previous.is_none_or(|(client, port)| {
+            client != mapping.internal_client || port != mapping.internal_port
+        }) {
+            new_mappings.push(mapping.clone());
+        }
+
+        upsert.execute(params![
+            gateway_ip,
+            mapping.remote_host,
+            mapping.external_port,
+            mapping.protocol,
+            mapping.internal_client,
+            mapping.internal_port,
+            mapping.enabled,
+            mapping.description,
+            mapping.lease_duration,
+            now
+        ])?;
+    }
+
+    // Anything the gateway no longer reports has been removed or has expired
+    conn.execute(
+        "DELETE FROM upnp_port_mappings WHERE gateway_ip = ?1 AND last_seen_at < ?2",
+        params![gateway_ip, now],
+    )?;
+    Ok(new_mappings)
+}
+
+/// Every known mapping, grouped by gateway
+pub fn get_port_mappings(conn: &Connection) -> Result<Vec<UpnpPortMappingRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.gateway_ip,
+                (SELECT e.display_name FROM endpoint_attributes ea
+                 JOIN endpoints e ON e.id = ea.endpoint_id
+                 WHERE ea.ip = m.gateway_ip LIMIT 1),
+                m.remote_host, m.external_port, m.protocol, m.internal_client,
+                (SELECT e.display_name FROM endpoint_attributes ea
+                 JOIN endpoints e ON e.id = ea.endpoint_id
+                 WHERE ea.ip = m.internal_client LIMIT 1),
+                m.internal_port, m.enabled, m.description, m.lease_duration,
+                m.first_seen_at, m.last_seen_at
+         FROM upnp_port_mappings m
+         ORDER BY m.gateway_ip, m.external_port, m.protocol",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(UpnpPortMappingRecord {
+            gateway_ip: row.get(0)?,
+            gateway_name: row.get(1)?,
+            remote_host: row.get(2)?,
+            external_port: row.get(3)?,
+            protocol: row.get(4)?,
+            internal_client: row.get(5)?,
+            internal_name: row.get(6)?,
+            internal_port: row.get(7)?,
+            enabled: row.get(8)?,
+            description: row.get(9)?,
+            lease_duration: row.get(10)?,
+            first_seen_at: row.get(11)?,
+            last_seen_at: row.get(12)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn mapping(external_port: u16, internal_client: &str) -> UpnpPortMapping {
+        UpnpPortMapping {
+            remote_host: String::new(),
+            external_port,
+            protocol: "TCP".to_string(),
+            internal_client: internal_client.to_string(),
+            internal_port: 80,
+            enabled: true,
+            description: None,
+            lease_duration: 0,
+        }
+    }
+
+    #[test]
+    fn test_record_port_mappings() {
+        let conn = new_test_connection();
+
+        let new = record_port_mappings(
+            &conn,
+            "192.168.1.1",
+            &[mapping(8080, "192.168.1.50"), mapping(8443, "192.168.1.51")],
+            10,
+        )
+        .unwrap();
+        assert_eq!(new.len(), 2);
+
+        // Same table again: nothing new, and first_seen_at is kept
+        let new = record_port_mappings(
+            &conn,
+            "192.168.1.1",
+            &[mapping(8080, "192.168.1.50"), mapping(8443, "192.168.1.51")],
+            20,
+        )
+        .unwrap();
+        assert!(new.is_empty());
+
+        // 8443 removed, 8080 now forwarded to another device
+        let new = record_port_mappings(&conn, "192.168.1.1", &[mapping(8080, "192.168.1.60")], 30)
+            .unwrap();
+        assert_eq!(new, vec![mapping(8080, "192.168.1.60")]);
+
+        let records = get_port_mappings(&conn).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].internal_client, "192.168.1.60");
+        assert_eq!(records[0].first_seen_at, 30);
+        assert_eq!(records[0].last_seen_at, 30);
+    }
+}
@@ -0,0 +1,497 @@
+//! UPS monitoring. A background poller reads every UPS network card that answered an SNMP
+//! scan (found by its system description, or by being typed as a UPS) and every host with
+//! a Network UPS Tools server on port 3493, every `ups_poll_interval_seconds`. Readings
+//! are kept for charting battery charge, runtime and load. An SNMP card that answers is
+//! classified as "ups"; a NUT server keeps its own type, since the UPS hangs off it by USB.
+//!
+//! Power events go through the notification system: `ups_on_battery` when a UPS loses
+//! mains power, `ups_power_restored` when it comes back, and `ups_low_battery` when the UPS
+//! reports a low battery or drops to `ups_low_battery_percent` while on battery.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+use tokio::task;
+
+use super::{
+    get_setting_i64, insert_notification_with_endpoint_id, load_snmp_credentials,
+    new_connection_result,
+};
+use crate::scanner::snmp::SnmpScanner;
+use crate::scanner::ups::{NUT_PORT, UpsSource, UpsStatus, query_nut, query_snmp_ups};
+
+/// Readings are kept this long
+const READING_RETENTION_DAYS: i64 = 30;
+
+/// Connect and read timeout for NUT servers
+const NUT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Flag to ensure the poller is only started once
+static UPS_POLLER_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn create_ups_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ups_readings (
+            endpoint_id INTEGER NOT NULL,
+            ups_name TEXT NOT NULL,
+            polled_at INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            on_battery INTEGER NOT NULL,
+            low_battery INTEGER NOT NULL,
+            charge_percent INTEGER,
+            runtime_seconds INTEGER,
+            load_percent INTEGER,
+            PRIMARY KEY (endpoint_id, ups_name, polled_at)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ups_power_state (
+            endpoint_id INTEGER NOT NULL,
+            ups_name TEXT NOT NULL,
+            on_battery INTEGER NOT NULL,
+            low_battery INTEGER NOT NULL,
+            changed_at INTEGER NOT NULL,
+            PRIMARY KEY (endpoint_id, ups_name)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A UPS card to read over SNMP, at the address its last SNMP scan answered on
+#[derive(Debug)]
+pub struct SnmpUpsTarget {
+    pub endpoint_id: i64,
+    pub ip: String,
+    /// MIB suggested by the SNMP scan, tried first
+    pub hint: Option<UpsSource>,
+}
+
+/// Endpoints that answered an SNMP scan and are typed as a UPS or describe themselves as one
+pub fn get_snmp_ups_targets(conn: &Connection) -> Result<Vec<SnmpUpsTarget>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.id, COALESCE(e.manual_device_type, e.auto_device_type), sr.ip, sr.details
+         FROM endpoints e
+         JOIN scan_results sr ON sr.id = (
+             SELECT id FROM scan_results
+             WHERE endpoint_id = e.id AND scan_type = 'snmp' AND ip IS NOT NULL
+             ORDER BY scanned_at DESC LIMIT 1)",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+        ))
+    })?;
+
+    let mut targets = Vec::new();
+    for row in rows {
+        let (endpoint_id, device_type, ip, details) = row?;
+        let hint = details.as_deref().and_then(UpsSource::from_snmp_details);
+        if hint.is_some() || device_type.as_deref() == Some("ups") {
+            targets.push(SnmpUpsTarget {
+                endpoint_id,
+                ip,
+                hint,
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Endpoints with a NUT server port open, and their latest address
+pub fn get_nut_servers(conn: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT endpoint_id, ip FROM (
+             SELECT op.endpoint_id,
+                    (SELECT ea.ip FROM endpoint_attributes ea
+                     WHERE ea.endpoint_id = op.endpoint_id AND ea.ip != ''
+                     ORDER BY ea.created_at DESC LIMIT 1) AS ip
+             FROM open_ports op
+             WHERE op.port = ?1 AND COALESCE(op.protocol, 'tcp') = 'tcp')
+         WHERE ip IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([NUT_PORT], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+fn format_runtime(seconds: Option<i64>) -> String {
+    match seconds {
+        Some(s) => format!("{} min left", s / 60),
+        None => "runtime unknown".to_string(),
+    }
+}
+
+fn format_charge(percent: Option<i64>) -> String {
+    match percent {
+        Some(p) => format!("{}% charge", p),
+        None => "charge unknown".to_string(),
+    }
+}
+
+/// Store one UPS reading and raise power events against its last known state. Returns
+/// the notifications raised.
+pub fn record_ups_status(
+    conn: &Connection,
+    endpoint_id: i64,
+    status: &UpsStatus,
+    now: i64,
+    low_percent: i64,
+) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM ups_readings WHERE polled_at < ?1",
+        [now - READING_RETENTION_DAYS * 24 * 60 * 60],
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO ups_readings (endpoint_id, ups_name, polled_at, source,
+             on_battery, low_battery, charge_percent, runtime_seconds, load_percent)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            endpoint_id,
+            status.name,
+            now,
+            status.source.as_str(),
+            status.on_battery,
+            status.low_battery,
+            status.charge_percent,
+            status.runtime_seconds,
+            status.load_percent
+        ],
+    )?;
+
+    // A network card is the UPS itself; a manual type still wins
+    if status.source != UpsSource::Nut {
+        tx.execute(
+            "UPDATE endpoints SET auto_device_type = 'ups'
+             WHERE id = ?1 AND auto_device_type IS NOT 'ups'",
+            [endpoint_id],
+        )?;
+    }
+
+    let low = status.low_battery
+        || (status.on_battery && status.charge_percent.is_some_and(|c| c <= low_percent));
+    // A UPS seen for the first time counts as having been on mains with a good battery
+    let (was_on_battery, was_low): (bool, bool) = tx
+        .query_row(
+            "SELECT on_battery, low_battery FROM ups_power_state
+             WHERE endpoint_id = ?1 AND ups_name = ?2",
+            params![endpoint_id, status.name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .unwrap_or((false, false));
+
+    let mut events = 0;
+    if (was_on_battery, was_low) != (status.on_battery, low) {
+        tx.execute(
+            "INSERT OR REPLACE INTO ups_power_state
+                 (endpoint_id, ups_name, on_battery, low_battery, changed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![endpoint_id, status.name, status.on_battery, low, now],
+        )?;
+
+        let endpoint_name: Option<String> = tx
+            .query_row(
+                "SELECT display_name FROM endpoints WHERE id = ?1",
+                [endpoint_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        let endpoint_name = endpoint_name.unwrap_or_else(|| format!("endpoint {}", endpoint_id));
+        let ups = if status.name.is_empty() {
+            endpoint_name.clone()
+        } else {
+            format!("UPS {} on {}", status.name, endpoint_name)
+        };
+        let details = format!(
+            "{}, {}",
+            format_charge(status.charge_percent),
+            format_runtime(status.runtime_seconds)
+        );
+
+        let mut notify = |event: &str, title: String| {
+            insert_notification_with_endpoint_id(
+                &tx,
+                event,
+                &title,
+                Some(&details),
+                Some(&endpoint_name),
+                Some(endpoint_id),
+            );
+            events += 1;
+        };
+        if status.on_battery && !was_on_battery {
+            notify("ups_on_battery", format!("{} is running on battery", ups));
+        } else if !status.on_battery && was_on_battery {
+            notify("ups_power_restored", format!("Power restored to {}", ups));
+        }
+        if low && !was_low {
+            notify("ups_low_battery", format!("{} battery is low", ups));
+        }
+    }
+    tx.commit()?;
+    Ok(events)
+}
+
+/// A UPS's latest reading, with its history when asked for
+#[derive(Debug, Clone, Serialize)]
+pub struct UpsReport {
+    pub endpoint_id: i64,
+    pub endpoint: Option<String>,
+    /// Name on the NUT server; empty for SNMP cards
+    pub ups_name: String,
+    pub source: String,
+    pub polled_at: i64,
+    pub on_battery: bool,
+    pub low_battery: bool,
+    pub charge_percent: Option<i64>,
+    pub runtime_seconds: Option<i64>,
+    pub load_percent: Option<i64>,
+    /// When the UPS last went on or off battery or its battery turned low
+    pub power_changed_at: Option<i64>,
+    /// (polled_at, charge_percent, load_percent, on_battery) oldest first
+    pub history: Vec<(i64, Option<i64>, Option<i64>, bool)>,
+}
+
+/// Latest reading of each UPS, for one endpoint or all of them, with history since `since`
+fn ups_reports(
+    conn: &Connection,
+    endpoint_id: Option<i64>,
+    since: Option<i64>,
+) -> Result<Vec<UpsReport>> {
+    let mut stmt = conn.prepare(
+        "SELECT r.endpoint_id, e.display_name, r.ups_name, r.source, r.polled_at,
+                r.on_battery, r.low_battery, r.charge_percent, r.runtime_seconds,
+                r.load_percent, s.changed_at
+         FROM ups_readings r
+         LEFT JOIN endpoints e ON e.id = r.endpoint_id
+         LEFT JOIN ups_power_state s
+             ON s.endpoint_id = r.endpoint_id AND s.ups_name = r.ups_name
+         WHERE (?1 IS NULL OR r.endpoint_id = ?1)
+           AND r.polled_at = (SELECT MAX(polled_at) FROM ups_readings
+                              WHERE endpoint_id = r.endpoint_id AND ups_name = r.ups_name)
+         ORDER BY r.on_battery DESC, e.display_name, r.ups_name",
+    )?;
+    let mut reports: Vec<UpsReport> = stmt
+        .query_map([endpoint_id], |row| {
+            Ok(UpsReport {
+                endpoint_id: row.get(0)?,
+                endpoint: row.get(1)?,
+                ups_name: row.get(2)?,
+                source: row.get(3)?,
+                polled_at: row.get(4)?,
+                on_battery: row.get(5)?,
+                low_battery: row.get(6)?,
+                charge_percent: row.get(7)?,
+                runtime_seconds: row.get(8)?,
+                load_percent: row.get(9)?,
+                power_changed_at: row.get(10)?,
+                history: Vec::new(),
+            })
+        })?
+        .collect::<Result<_>>()?;
+
+    if let Some(since) = since {
+        let mut history = conn.prepare(
+            "SELECT polled_at, charge_percent, load_percent, on_battery FROM ups_readings
+             WHERE endpoint_id = ?1 AND ups_name = ?2 AND polled_at >= ?3
+             ORDER BY polled_at",
+        )?;
+        for report in &mut reports {
+            report.history = history
+                .query_map(params![report.endpoint_id, report.ups_name, since], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })?
+                .collect::<Result<_>>()?;
+        }
+    }
+    Ok(reports)
+}
+
+/// The UPSes read through one endpoint, with their history since `since`
+pub fn get_endpoint_ups(conn: &Connection, endpoint_id: i64, since: i64) -> Result<Vec<UpsReport>> {
+    ups_reports(conn, Some(endpoint_id), Some(since))
+}
+
+/// Every UPS's latest reading, those on battery first
+pub fn get_ups_overview(conn: &Connection) -> Result<Vec<UpsReport>> {
+    ups_reports(conn, None, None)
+}
+
+/// Fold a UPS's readings into the endpoint it is merged into
+pub fn reassign_ups_readings(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    for table in ["ups_readings", "ups_power_state"] {
+        conn.execute(
+            &format!(
+                "UPDATE OR IGNORE {} SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+                table
+            ),
+            params![to_id, from_id],
+        )?;
+        conn.execute(
+            &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+            [from_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Poll every UPS card and NUT server once and store what they report
+fn poll_ups_devices() -> std::result::Result<(usize, usize), String> {
+    let conn = new_connection_result().map_err(|e| e.to_string())?;
+    let snmp_targets = get_snmp_ups_targets(&conn).map_err(|e| e.to_string())?;
+    let nut_servers = get_nut_servers(&conn).map_err(|e| e.to_string())?;
+    if snmp_targets.is_empty() && nut_servers.is_empty() {
+        return Ok((0, 0));
+    }
+    let low_percent = get_setting_i64("ups_low_battery_percent", 20);
+
+    let mut readings: Vec<(i64, UpsStatus)> = Vec::new();
+    if !snmp_targets.is_empty() {
+        let credentials = load_snmp_credentials(&conn).map_err(|e| e.to_string())?;
+        let scanner = SnmpScanner::new().with_credentials(credentials);
+        for target in snmp_targets {
+            let Ok(ip) = target.ip.parse::<Ipv4Addr>() else {
+                continue;
+            };
+            if let Some(status) = query_snmp_ups(&scanner, ip, target.hint) {
+                readings.push((target.endpoint_id, status));
+            }
+        }
+    }
+    for (endpoint_id, ip) in nut_servers {
+        let Ok(ip) = ip.parse::<IpAddr>() else {
+            continue;
+        };
+        readings.extend(
+            query_nut(ip, NUT_TIMEOUT)
+                .into_iter()
+                .map(|status| (endpoint_id, status)),
+        );
+    }
+
+    let mut events = 0;
+    for (endpoint_id, status) in &readings {
+        let now = chrono::Utc::now().timestamp();
+        events += record_ups_status(&conn, *endpoint_id, status, now, low_percent)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok((readings.len(), events))
+}
+
+/// Start the UPS poller. Runs every `ups_poll_interval_seconds` (0 disables it).
+pub fn start_ups_poller() {
+    if UPS_POLLER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        // Let the startup scan find SNMP cards and open NUT ports first
+        tokio::time::sleep(tokio::time::Duration::from_secs(120)).await;
+
+        loop {
+            let interval_seconds = get_setting_i64("ups_poll_interval_seconds", 60);
+            if interval_seconds > 0 {
+                match task::spawn_blocking(poll_ups_devices).await {
+                    Ok(Ok((_, events))) if events > 0 => {
+                        println!("{} UPS power event(s) raised", events)
+                    }
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => eprintln!("UPS poll failed: {}", e),
+                    Err(e) => eprintln!("UPS poll task failed: {}", e),
+                }
+            }
+
+            // Check again for a changed interval even while disabled
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                interval_seconds.max(15) as u64
+            ))
+            .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn reading(on_battery: bool, charge: i64) -> UpsStatus {
+        UpsStatus {
+            name: String::new(),
+            source: UpsSource::Apc,
+            on_battery,
+            low_battery: false,
+            charge_percent: Some(charge),
+            runtime_seconds: Some(charge * 30),
+            load_percent: Some(25),
+        }
+    }
+
+    #[test]
+    fn test_power_events_follow_state_changes() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'smart-ups');",
+        )
+        .unwrap();
+
+        // On mains: nothing to report
+        assert_eq!(
+            record_ups_status(&conn, 1, &reading(false, 100), 100, 20).unwrap(),
+            0
+        );
+        // Outage, then the battery running down past the threshold
+        assert_eq!(
+            record_ups_status(&conn, 1, &reading(true, 90), 200, 20).unwrap(),
+            1
+        );
+        assert_eq!(
+            record_ups_status(&conn, 1, &reading(true, 60), 300, 20).unwrap(),
+            0
+        );
+        assert_eq!(
+            record_ups_status(&conn, 1, &reading(true, 15), 400, 20).unwrap(),
+            1
+        );
+        // Mains back: restored, and the low charge no longer counts
+        assert_eq!(
+            record_ups_status(&conn, 1, &reading(false, 16), 500, 20).unwrap(),
+            1
+        );
+
+        let events: Vec<String> = conn
+            .prepare("SELECT event_type FROM notifications ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            ["ups_on_battery", "ups_low_battery", "ups_power_restored"]
+        );
+
+        let device_type: String = conn
+            .query_row(
+                "SELECT auto_device_type FROM endpoints WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(device_type, "ups");
+
+        let reports = get_endpoint_ups(&conn, 1, 0).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].charge_percent, Some(16));
+        assert_eq!(reports[0].history.len(), 5);
+    }
+}
@@ -0,0 +1,853 @@
+//! Per-device usage rollups for household usage reports. The capture writer adds every
+//! flow to the hourly activity of the local endpoints involved, and internet flows to the
+//! daily per-site totals of the local endpoint, with the site named from DNS or TLS SNI.
+//! Reports combine both into hours online per day, top sites, and category shares. The
+//! hourly rows also split internet traffic into download and upload bytes, and carry TCP
+//! handshake times and retransmission counts for the quality report. The internet summary
+//! groups the per-site totals of every endpoint by the organization behind each site.
+//! Per-minute packet and byte counts are also kept for a short while, for charts of the
+//! last few hours.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Local, TimeZone, Timelike};
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::network::direction::TrafficDirection;
+use crate::network::domain_categories::{categorize_domain, site_domain, site_owner};
+use crate::network::tcp_quality::TcpQuality;
+
+/// How long per-minute rollups are kept; longer ranges are charted from the hourly ones
+pub const MINUTE_USAGE_RETENTION_SECS: i64 = 2 * 24 * 60 * 60;
+
+pub fn create_usage_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_usage_hourly (
+            endpoint_id INTEGER NOT NULL,
+            hour_start INTEGER NOT NULL,
+            packet_count INTEGER NOT NULL DEFAULT 0,
+            bytes INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (endpoint_id, hour_start)
+        )",
+        [],
+    )?;
+    // Migration: internet bytes by direction, then TCP quality
+    for column in [
+        "wan_bytes_in",
+        "wan_bytes_out",
+        "tcp_segments",
+        "tcp_retransmissions",
+        "rtt_samples",
+        "rtt_total_us",
+        "rtt_max_us",
+    ] {
+        let has_column: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('endpoint_usage_hourly') WHERE name = ?1",
+                [column],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !has_column {
+            conn.execute(
+                &format!(
+                    "ALTER TABLE endpoint_usage_hourly ADD COLUMN {} INTEGER NOT NULL DEFAULT 0",
+                    column
+                ),
+                [],
+            )?;
+        }
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_usage_sites (
+            endpoint_id INTEGER NOT NULL,
+            day_start INTEGER NOT NULL,
+            site TEXT NOT NULL,
+            packet_count INTEGER NOT NULL DEFAULT 0,
+            bytes INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (endpoint_id, day_start, site)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_usage_minutes (
+            endpoint_id INTEGER NOT NULL,
+            minute_start INTEGER NOT NULL,
+            packet_count INTEGER NOT NULL DEFAULT 0,
+            bytes INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (endpoint_id, minute_start)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Add a flow to an endpoint's rollups. `destination` is the internet host it talked to
+/// (a hostname or bare IP), None for local traffic.
+pub fn record_usage(
+    conn: &Connection,
+    endpoint_id: i64,
+    destination: Option<&str>,
+    packets: u32,
+    bytes: u32,
+    at: i64,
+) -> Result<()> {
+    let packets = packets.max(1);
+    conn.prepare_cached(
+        "INSERT INTO endpoint_usage_hourly (endpoint_id, hour_start, packet_count, bytes)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(endpoint_id, hour_start) DO UPDATE SET
+            packet_count = packet_count + excluded.packet_count,
+            bytes = bytes + excluded.bytes",
+    )?
+    .execute(params![
+        endpoint_id,
+        at - at.rem_euclid(3600),
+        packets,
+        bytes
+    ])?;
+    conn.prepare_cached(
+        "INSERT INTO endpoint_usage_minutes (endpoint_id, minute_start, packet_count, bytes)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(endpoint_id, minute_start) DO UPDATE SET
+            packet_count = packet_count + excluded.packet_count,
+            bytes = bytes + excluded.bytes",
+    )?
+    .execute(params![endpoint_id, at - at.rem_euclid(60), packets, bytes])?;
+
+    if let Some(destination) = destination {
+        conn.prepare_cached(
+            "INSERT INTO endpoint_usage_sites (endpoint_id, day_start, site, packet_count, bytes)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(endpoint_id, day_start, site) DO UPDATE SET
+                packet_count = packet_count + excluded.packet_count,
+                bytes = bytes + excluded.bytes",
+        )?
+        .execute(params![
+            endpoint_id,
+            at - at.rem_euclid(86400),
+            site_domain(destination),
+            packets,
+            bytes
+        ])?;
+    }
+    Ok(())
+}
+
+/// Add the internet side of an inbound (download) or outbound (upload) flow to a local
+/// endpoint's hourly WAN bytes. LAN-to-LAN flows are not counted.
+pub fn record_wan_bytes(
+    conn: &Connection,
+    endpoint_id: i64,
+    direction: TrafficDirection,
+    bytes: u32,
+    at: i64,
+) -> Result<()> {
+    let (bytes_in, bytes_out) = match direction {
+        TrafficDirection::Inbound => (bytes, 0),
+        TrafficDirection::Outbound => (0, bytes),
+        TrafficDirection::LanToLan => return Ok(()),
+    };
+    conn.prepare_cached(
+        "INSERT INTO endpoint_usage_hourly (endpoint_id, hour_start, wan_bytes_in, wan_bytes_out)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(endpoint_id, hour_start) DO UPDATE SET
+            wan_bytes_in = wan_bytes_in + excluded.wan_bytes_in,
+            wan_bytes_out = wan_bytes_out + excluded.wan_bytes_out",
+    )?
+    .execute(params![
+        endpoint_id,
+        at - at.rem_euclid(3600),
+        bytes_in,
+        bytes_out
+    ])?;
+    Ok(())
+}
+
+/// Add a flow's TCP quality signals to an endpoint's hourly rollup
+pub fn record_tcp_quality(
+    conn: &Connection,
+    endpoint_id: i64,
+    quality: &TcpQuality,
+    at: i64,
+) -> Result<()> {
+    if quality.is_empty() {
+        return Ok(());
+    }
+    let rtt_total: i64 = quality.rtt_samples.iter().map(|&rtt| i64::from(rtt)).sum();
+    let rtt_max = quality.rtt_samples.iter().copied().max().unwrap_or(0);
+    conn.prepare_cached(
+        "INSERT INTO endpoint_usage_hourly (endpoint_id, hour_start, tcp_segments,
+            tcp_retransmissions, rtt_samples, rtt_total_us, rtt_max_us)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(endpoint_id, hour_start) DO UPDATE SET
+            tcp_segments = tcp_segments + excluded.tcp_segments,
+            tcp_retransmissions = tcp_retransmissions + excluded.tcp_retransmissions,
+            rtt_samples = rtt_samples + excluded.rtt_samples,
+            rtt_total_us = rtt_total_us + excluded.rtt_total_us,
+            rtt_max_us = MAX(rtt_max_us, excluded.rtt_max_us)",
+    )?
+    .execute(params![
+        endpoint_id,
+        at - at.rem_euclid(3600),
+        quality.segments,
+        quality.retransmissions,
+        quality.rtt_samples.len() as i64,
+        rtt_total,
+        rtt_max
+    ])?;
+    Ok(())
+}
+
+/// Internet (download, upload) bytes of the endpoints in the hours from `since` on
+pub fn get_wan_bytes(conn: &Connection, endpoint_ids: &[i64], since: i64) -> (i64, i64) {
+    endpoint_ids
+        .iter()
+        .filter_map(|endpoint_id| {
+            conn.query_row(
+                "SELECT COALESCE(SUM(wan_bytes_in), 0), COALESCE(SUM(wan_bytes_out), 0)
+                 FROM endpoint_usage_hourly WHERE endpoint_id = ?1 AND hour_start >= ?2",
+                params![endpoint_id, since - since.rem_euclid(3600)],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .ok()
+        })
+        .fold((0, 0), |(total_in, total_out), (bytes_in, bytes_out)| {
+            (total_in + bytes_in, total_out + bytes_out)
+        })
+}
+
+/// Drop rollups older than `retention_days`
+pub fn purge_usage(conn: &Connection, retention_days: i64) -> Result<usize> {
+    let cutoff = retention_days * 24 * 60 * 60;
+    let hourly = conn.execute(
+        "DELETE FROM endpoint_usage_hourly WHERE hour_start < (strftime('%s', 'now') - ?1)",
+        [cutoff],
+    )?;
+    let sites = conn.execute(
+        "DELETE FROM endpoint_usage_sites WHERE day_start < (strftime('%s', 'now') - ?1)",
+        [cutoff],
+    )?;
+    let minutes = conn.execute(
+        "DELETE FROM endpoint_usage_minutes WHERE minute_start < (strftime('%s', 'now') - ?1)",
+        [cutoff.min(MINUTE_USAGE_RETENTION_SECS)],
+    )?;
+    Ok(hourly + sites + minutes)
+}
+
+/// Fold an endpoint's rollups into the endpoint it is merged into
+pub fn reassign_usage(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO endpoint_usage_hourly
+            (endpoint_id, hour_start, packet_count, bytes, wan_bytes_in, wan_bytes_out,
+             tcp_segments, tcp_retransmissions, rtt_samples, rtt_total_us, rtt_max_us)
+         SELECT ?1, hour_start, packet_count, bytes, wan_bytes_in, wan_bytes_out,
+             tcp_segments, tcp_retransmissions, rtt_samples, rtt_total_us, rtt_max_us
+         FROM endpoint_usage_hourly
+         WHERE endpoint_id = ?2
+         ON CONFLICT(endpoint_id, hour_start) DO UPDATE SET
+            packet_count = packet_count + excluded.packet_count,
+            bytes = bytes + excluded.bytes,
+            wan_bytes_in = wan_bytes_in + excluded.wan_bytes_in,
+            wan_bytes_out = wan_bytes_out + excluded.wan_bytes_out,
+            tcp_segments = tcp_segments + excluded.tcp_segments,
+            tcp_retransmissions = tcp_retransmissions + excluded.tcp_retransmissions,
+            rtt_samples = rtt_samples + excluded.rtt_samples,
+            rtt_total_us = rtt_total_us + excluded.rtt_total_us,
+            rtt_max_us = MAX(rtt_max_us, excluded.rtt_max_us)",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "INSERT INTO endpoint_usage_sites (endpoint_id, day_start, site, packet_count, bytes)
+         SELECT ?1, day_start, site, packet_count, bytes FROM endpoint_usage_sites
+         WHERE endpoint_id = ?2
+         ON CONFLICT(endpoint_id, day_start, site) DO UPDATE SET
+            packet_count = packet_count + excluded.packet_count,
+            bytes = bytes + excluded.bytes",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "INSERT INTO endpoint_usage_minutes (endpoint_id, minute_start, packet_count, bytes)
+         SELECT ?1, minute_start, packet_count, bytes FROM endpoint_usage_minutes
+         WHERE endpoint_id = ?2
+         ON CONFLICT(endpoint_id, minute_start) DO UPDATE SET
+            packet_count = packet_count + excluded.packet_count,
+            bytes = bytes + excluded.bytes",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_usage_hourly WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_usage_minutes WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_usage_sites WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SiteUsage {
+    pub site: String,
+    pub category: &'static str,
+    pub packet_count: i64,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryUsage {
+    pub category: &'static str,
+    pub bytes: i64,
+    /// Share of the endpoint's internet bytes
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DailyActivity {
+    /// Local date, YYYY-MM-DD
+    pub date: String,
+    pub hours_online: usize,
+    /// Local hours (0-23) with any traffic
+    pub active_hours: Vec<u32>,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    /// Unix time the report starts at
+    pub since: i64,
+    pub daily: Vec<DailyActivity>,
+    pub top_destinations: Vec<SiteUsage>,
+    pub categories: Vec<CategoryUsage>,
+}
+
+/// Usage of one endpoint since `since`, with at most `limit` top destinations. None for
+/// unknown endpoints.
+pub fn get_usage_report(
+    conn: &Connection,
+    endpoint_id: i64,
+    since: i64,
+    limit: usize,
+) -> Result<Option<UsageReport>> {
+    let Some(endpoint_name) = conn
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT hour_start, bytes FROM endpoint_usage_hourly
+         WHERE endpoint_id = ?1 AND hour_start >= ?2
+         ORDER BY hour_start",
+    )?;
+    let hours = stmt
+        .query_map(
+            params![endpoint_id, since - since.rem_euclid(3600)],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+        )?
+        .collect::<Result<Vec<_>>>()?;
+    let mut days: BTreeMap<String, DailyActivity> = BTreeMap::new();
+    for (hour_start, bytes) in hours {
+        let Some(local) = Local.timestamp_opt(hour_start, 0).single() else {
+            continue;
+        };
+        let date = local.format("%Y-%m-%d").to_string();
+        let day = days.entry(date.clone()).or_insert_with(|| DailyActivity {
+            date,
+            hours_online: 0,
+            active_hours: Vec::new(),
+            bytes: 0,
+        });
+        // When clocks go back, two UTC hours map onto the same local hour
+        if day.active_hours.last() != Some(&local.hour()) {
+            day.active_hours.push(local.hour());
+        }
+        day.hours_online = day.active_hours.len();
+        day.bytes += bytes;
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT site, SUM(packet_count), SUM(bytes) FROM endpoint_usage_sites
+         WHERE endpoint_id = ?1 AND day_start >= ?2
+         GROUP BY site
+         ORDER BY SUM(bytes) DESC, SUM(packet_count) DESC",
+    )?;
+    let sites = stmt
+        .query_map(
+            params![endpoint_id, since - since.rem_euclid(86400)],
+            |row| {
+                let site: String = row.get(0)?;
+                Ok(SiteUsage {
+                    category: categorize_domain(&site),
+                    site,
+                    packet_count: row.get(1)?,
+                    bytes: row.get(2)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    let total_bytes: i64 = sites.iter().map(|site| site.bytes).sum();
+    let mut category_bytes: BTreeMap<&'static str, i64> = BTreeMap::new();
+    for site in &sites {
+        *category_bytes.entry(site.category).or_default() += site.bytes;
+    }
+    let mut categories: Vec<CategoryUsage> = category_bytes
+        .into_iter()
+        .map(|(category, bytes)| CategoryUsage {
+            category,
+            bytes,
+            percent: if total_bytes > 0 {
+                bytes as f64 * 100.0 / total_bytes as f64
+            } else {
+                0.0
+            },
+        })
+        .collect();
+    categories.sort_by_key(|c| std::cmp::Reverse(c.bytes));
+
+    Ok(Some(UsageReport {
+        endpoint_id,
+        endpoint_name,
+        since,
+        daily: days.into_values().collect(),
+        top_destinations: sites.into_iter().take(limit).collect(),
+        categories,
+    }))
+}
+
+/// Endpoints listed under each organization in the internet summary
+const TOP_ENDPOINTS_PER_OWNER: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnerEndpoint {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnerUsage {
+    /// Organization behind the sites, or the site itself when its owner isn't known
+    pub owner: String,
+    /// Sites counted for this owner, busiest first
+    pub sites: Vec<String>,
+    pub packet_count: i64,
+    pub bytes: i64,
+    /// Local endpoints that talked to this owner
+    pub endpoint_count: usize,
+    pub top_endpoints: Vec<OwnerEndpoint>,
+    /// Bytes per day, one entry for each of the summary's `day_starts`
+    pub daily_bytes: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InternetSummary {
+    /// Unix time the summary starts at
+    pub since: i64,
+    /// Unix time of each UTC day covered, oldest first
+    pub day_starts: Vec<i64>,
+    pub total_bytes: i64,
+    /// Busiest first
+    pub owners: Vec<OwnerUsage>,
+}
+
+/// Internet traffic of all endpoints from `since` up to `now`, grouped by the organization
+/// behind each site, with at most `limit` organizations
+pub fn get_internet_summary(
+    conn: &Connection,
+    since: i64,
+    now: i64,
+    limit: usize,
+) -> Result<InternetSummary> {
+    let first_day = since - since.rem_euclid(86400);
+    let day_starts: Vec<i64> = (first_day..=now.max(first_day)).step_by(86400).collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT s.endpoint_id, e.display_name, s.day_start, s.site, s.packet_count, s.bytes
+         FROM endpoint_usage_sites s
+         LEFT JOIN endpoints e ON e.id = s.endpoint_id
+         WHERE s.day_start >= ?1",
+    )?;
+    let rows = stmt
+        .query_map([first_day], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    struct OwnerTotals {
+        sites: HashMap<String, i64>,
+        packet_count: i64,
+        bytes: i64,
+        endpoints: HashMap<i64, (Option<String>, i64)>,
+        daily_bytes: Vec<i64>,
+    }
+    let mut owners: HashMap<String, OwnerTotals> = HashMap::new();
+    for (endpoint_id, endpoint_name, day_start, site, packet_count, bytes) in rows {
+        let owner = site_owner(&site).map_or_else(|| site.clone(), str::to_string);
+        let totals = owners.entry(owner).or_insert_with(|| OwnerTotals {
+            sites: HashMap::new(),
+            packet_count: 0,
+            bytes: 0,
+            endpoints: HashMap::new(),
+            daily_bytes: vec![0; day_starts.len()],
+        });
+        *totals.sites.entry(site).or_default() += bytes;
+        totals.packet_count += packet_count;
+        totals.bytes += bytes;
+        totals
+            .endpoints
+            .entry(endpoint_id)
+            .or_insert((endpoint_name, 0))
+            .1 += bytes;
+        if let Some(day) = totals
+            .daily_bytes
+            .get_mut(((day_start - first_day) / 86400) as usize)
+        {
+            *day += bytes;
+        }
+    }
+
+    let mut owners: Vec<OwnerUsage> = owners
+        .into_iter()
+        .map(|(owner, totals)| {
+            let mut sites: Vec<(String, i64)> = totals.sites.into_iter().collect();
+            sites.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let mut endpoints: Vec<OwnerEndpoint> = totals
+                .endpoints
+                .into_iter()
+                .map(|(endpoint_id, (endpoint_name, bytes))| OwnerEndpoint {
+                    endpoint_id,
+                    endpoint_name,
+                    bytes,
+                })
+                .collect();
+            endpoints.sort_by(|a, b| {
+                b.bytes
+                    .cmp(&a.bytes)
+                    .then_with(|| a.endpoint_id.cmp(&b.endpoint_id))
+            });
+            OwnerUsage {
+                owner,
+                sites: sites.into_iter().map(|(site, _)| site).collect(),
+                packet_count: totals.packet_count,
+                bytes: totals.bytes,
+                endpoint_count: endpoints.len(),
+                top_endpoints: endpoints
+                    .into_iter()
+                    .take(TOP_ENDPOINTS_PER_OWNER)
+                    .collect(),
+                daily_bytes: totals.daily_bytes,
+            }
+        })
+        .collect();
+    owners.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.owner.cmp(&b.owner)));
+
+    Ok(InternetSummary {
+        since,
+        day_starts,
+        total_bytes: owners.iter().map(|owner| owner.bytes).sum(),
+        owners: owners.into_iter().take(limit).collect(),
+    })
+}
+
+/// TCP quality over a period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TcpQualityStats {
+    pub segments: i64,
+    pub retransmissions: i64,
+    /// Retransmissions per 100 data segments
+    pub retransmission_percent: f64,
+    pub rtt_samples: i64,
+    pub avg_rtt_ms: Option<f64>,
+    pub max_rtt_ms: Option<f64>,
+}
+
+impl TcpQualityStats {
+    fn new(
+        segments: i64,
+        retransmissions: i64,
+        rtt_samples: i64,
+        rtt_total_us: i64,
+        rtt_max_us: i64,
+    ) -> Self {
+        TcpQualityStats {
+            segments,
+            retransmissions,
+            retransmission_percent: if segments > 0 {
+                retransmissions as f64 * 100.0 / segments as f64
+            } else {
+                0.0
+            },
+            rtt_samples,
+            avg_rtt_ms: (rtt_samples > 0)
+                .then(|| rtt_total_us as f64 / rtt_samples as f64 / 1000.0),
+            max_rtt_ms: (rtt_samples > 0).then(|| rtt_max_us as f64 / 1000.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HourlyTcpQuality {
+    /// Unix time the hour starts at
+    pub hour_start: i64,
+    #[serde(flatten)]
+    pub stats: TcpQualityStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TcpQualityReport {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    /// Unix time the report starts at
+    pub since: i64,
+    #[serde(flatten)]
+    pub totals: TcpQualityStats,
+    /// Hours with TCP traffic, oldest first
+    pub hourly: Vec<HourlyTcpQuality>,
+}
+
+/// Handshake times and retransmissions of one endpoint since `since`. None for unknown
+/// endpoints.
+pub fn get_tcp_quality_report(
+    conn: &Connection,
+    endpoint_id: i64,
+    since: i64,
+) -> Result<Option<TcpQualityReport>> {
+    let Some(endpoint_name) = conn
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT hour_start, tcp_segments, tcp_retransmissions, rtt_samples, rtt_total_us,
+                rtt_max_us
+         FROM endpoint_usage_hourly
+         WHERE endpoint_id = ?1 AND hour_start >= ?2
+           AND (tcp_segments > 0 OR tcp_retransmissions > 0 OR rtt_samples > 0)
+         ORDER BY hour_start",
+    )?;
+    let hours = stmt
+        .query_map(
+            params![endpoint_id, since - since.rem_euclid(3600)],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    [
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, i64>(4)?,
+                        row.get::<_, i64>(5)?,
+                    ],
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut totals = [0i64; 5];
+    let mut hourly = Vec::with_capacity(hours.len());
+    for (hour_start, [segments, retransmissions, samples, rtt_total, rtt_max]) in hours {
+        totals[0] += segments;
+        totals[1] += retransmissions;
+        totals[2] += samples;
+        totals[3] += rtt_total;
+        totals[4] = totals[4].max(rtt_max);
+        hourly.push(HourlyTcpQuality {
+            hour_start,
+            stats: TcpQualityStats::new(segments, retransmissions, samples, rtt_total, rtt_max),
+        });
+    }
+    let [segments, retransmissions, samples, rtt_total, rtt_max] = totals;
+
+    Ok(Some(TcpQualityReport {
+        endpoint_id,
+        endpoint_name,
+        since,
+        totals: TcpQualityStats::new(segments, retransmissions, samples, rtt_total, rtt_max),
+        hourly,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_usage_report() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'tablet'), (2, 1, 'tablet-2')",
+            [],
+        )
+        .unwrap();
+        let noon = 1_760_616_000; // 2025-10-16 12:00 UTC
+        record_usage(&conn, 1, Some("rr1---sn-a.googlevideo.com"), 10, 9000, noon).unwrap();
+        record_usage(
+            &conn,
+            1,
+            Some("rr2---sn-b.googlevideo.com"),
+            5,
+            6000,
+            noon + 60,
+        )
+        .unwrap();
+        record_usage(&conn, 1, Some("en.wikipedia.org"), 2, 1000, noon + 3600).unwrap();
+        record_usage(&conn, 1, None, 1, 100, noon + 3 * 3600).unwrap();
+        record_usage(&conn, 2, Some("store.steampowered.com"), 4, 4000, noon).unwrap();
+        record_wan_bytes(&conn, 1, TrafficDirection::Inbound, 9000, noon).unwrap();
+        record_wan_bytes(&conn, 1, TrafficDirection::LanToLan, 100, noon).unwrap();
+        record_wan_bytes(&conn, 2, TrafficDirection::Outbound, 500, noon + 60).unwrap();
+        reassign_usage(&conn, 2, 1).unwrap();
+        assert_eq!(get_wan_bytes(&conn, &[1], noon), (9000, 500));
+        assert_eq!(get_wan_bytes(&conn, &[1], noon + 3600), (0, 0));
+
+        let report = get_usage_report(&conn, 1, noon - 86400, 2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            report.daily.iter().map(|d| d.hours_online).sum::<usize>(),
+            3
+        );
+        assert_eq!(report.daily.iter().map(|d| d.bytes).sum::<i64>(), 20_100);
+
+        let top: Vec<(&str, &str, i64)> = report
+            .top_destinations
+            .iter()
+            .map(|s| (s.site.as_str(), s.category, s.bytes))
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                ("googlevideo.com", "streaming", 15_000),
+                ("steampowered.com", "gaming", 4000)
+            ]
+        );
+        let categories: Vec<(&str, f64)> = report
+            .categories
+            .iter()
+            .map(|c| (c.category, c.percent))
+            .collect();
+        assert_eq!(
+            categories,
+            vec![("streaming", 75.0), ("gaming", 20.0), ("browsing", 5.0)]
+        );
+
+        // Nothing before `since`
+        let later = get_usage_report(&conn, 1, noon + 2 * 86400, 10)
+            .unwrap()
+            .unwrap();
+        assert!(later.daily.is_empty() && later.top_destinations.is_empty());
+        assert!(get_usage_report(&conn, 99, 0, 10).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_internet_summary() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'tv'), (2, 1, 'phone')",
+            [],
+        )
+        .unwrap();
+        let noon = 1_760_616_000; // 2025-10-16 12:00 UTC
+        record_usage(&conn, 1, Some("rr1---sn-a.googlevideo.com"), 10, 9000, noon).unwrap();
+        record_usage(&conn, 2, Some("www.youtube.com"), 2, 1000, noon + 86400).unwrap();
+        record_usage(&conn, 2, Some("en.wikipedia.org"), 1, 500, noon).unwrap();
+        record_usage(&conn, 2, Some("203.0.113.9"), 1, 200, noon).unwrap();
+        // Before the summary starts
+        record_usage(&conn, 1, Some("netflix.com"), 1, 7000, noon - 2 * 86400).unwrap();
+
+        let summary = get_internet_summary(&conn, noon - 3600, noon + 86400, 10).unwrap();
+        assert_eq!(summary.day_starts, vec![noon - 43200, noon + 43200]);
+        assert_eq!(summary.total_bytes, 10_700);
+        let owners: Vec<(&str, i64)> = summary
+            .owners
+            .iter()
+            .map(|o| (o.owner.as_str(), o.bytes))
+            .collect();
+        assert_eq!(
+            owners,
+            vec![
+                ("Google", 10_000),
+                ("wikipedia.org", 500),
+                ("203.0.113.9", 200)
+            ]
+        );
+
+        let google = &summary.owners[0];
+        assert_eq!(google.sites, vec!["googlevideo.com", "youtube.com"]);
+        assert_eq!(google.daily_bytes, vec![9000, 1000]);
+        assert_eq!(google.endpoint_count, 2);
+        assert_eq!(
+            google
+                .top_endpoints
+                .iter()
+                .map(|e| (e.endpoint_id, e.bytes))
+                .collect::<Vec<_>>(),
+            vec![(1, 9000), (2, 1000)]
+        );
+
+        let top = get_internet_summary(&conn, noon - 3600, noon + 86400, 1).unwrap();
+        assert_eq!(top.owners.len(), 1);
+        assert_eq!(top.total_bytes, 10_700);
+    }
+
+    #[test]
+    fn test_tcp_quality_report() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'laptop'), (2, 1, 'laptop-2')",
+            [],
+        )
+        .unwrap();
+        let noon = 1_760_616_000;
+        let quality = |segments, retransmissions, rtt_samples: &[u32]| TcpQuality {
+            segments,
+            retransmissions,
+            rtt_samples: rtt_samples.to_vec(),
+        };
+        record_tcp_quality(&conn, 1, &quality(40, 2, &[10_000, 30_000]), noon).unwrap();
+        record_tcp_quality(&conn, 1, &quality(0, 0, &[]), noon + 3600).unwrap();
+        record_tcp_quality(&conn, 2, &quality(10, 3, &[80_000]), noon + 3600).unwrap();
+        record_usage(&conn, 1, None, 1, 100, noon + 2 * 3600).unwrap();
+        reassign_usage(&conn, 2, 1).unwrap();
+
+        let report = get_tcp_quality_report(&conn, 1, noon - 60)
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.hourly.len(), 2);
+        assert_eq!(report.hourly[0].stats.avg_rtt_ms, Some(20.0));
+        assert_eq!(report.hourly[1].stats.retransmission_percent, 30.0);
+        assert_eq!(report.totals.segments, 50);
+        assert_eq!(report.totals.retransmission_percent, 10.0);
+        assert_eq!(report.totals.avg_rtt_ms, Some(40.0));
+        assert_eq!(report.totals.max_rtt_ms, Some(80.0));
+        assert!(get_tcp_quality_report(&conn, 99, 0).unwrap().is_none());
+    }
+}
@@ -0,0 +1,328 @@
+//! Users, their roles and per-user preferences. Users are the identities requests
+//! arrive with (see `request_identity`): the name a trusted authenticating proxy passes,
+//! or the user an API token was issued to. Only a SHA-256 hash of each token is kept.
+//! Until the first user is added everyone is an admin, so single-user installs keep
+//! working unchanged. Once users exist, unknown and anonymous callers are viewers.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Preferences a user can set; `parse_preference` checks their values
+const PREFERENCE_KEYS: &[&str] = &["theme", "default_scan_interval"];
+
+pub fn create_users_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            name TEXT PRIMARY KEY COLLATE NOCASE,
+            role TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    // Added after the table; fails harmlessly once the column exists
+    let _ = conn.execute("ALTER TABLE users ADD COLUMN token_hash TEXT", []);
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_users_token_hash ON users (token_hash)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_preferences (
+            owner TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (owner, key)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Everything, including device control, deleting and merging endpoints, and
+    /// managing users
+    Admin,
+    /// Browsing, scanning and classifying
+    Viewer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        match role.trim().to_ascii_lowercase().as_str() {
+            "admin" => Some(Role::Admin),
+            "viewer" => Some(Role::Viewer),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+    pub created_at: i64,
+    /// Whether an API token has been issued (the token itself is never stored)
+    pub has_token: bool,
+}
+
+pub fn get_users(conn: &Connection) -> Result<Vec<User>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, role, created_at, token_hash IS NOT NULL FROM users ORDER BY name",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let role: String = row.get(1)?;
+        Ok(User {
+            name: row.get(0)?,
+            role: Role::parse(&role).unwrap_or(Role::Viewer),
+            created_at: row.get(2)?,
+            has_token: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Hex SHA-256 of an API token, the form tokens are stored and looked up in
+pub fn hash_api_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Issue `token` to a user, replacing any earlier one. Returns false if there is no
+/// such user.
+pub fn set_user_token(conn: &Connection, name: &str, token: &str) -> Result<bool> {
+    Ok(conn.execute(
+        "UPDATE users SET token_hash = ?2 WHERE name = ?1",
+        params![name, hash_api_token(token)],
+    )? > 0)
+}
+
+/// The user a bearer token was issued to, if any
+pub fn user_for_token(conn: &Connection, token: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT name FROM users WHERE token_hash = ?1",
+        [hash_api_token(token)],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// The role of a caller, by identity (None for anonymous requests)
+pub fn resolve_role(conn: &Connection, identity: Option<&str>) -> Result<Role> {
+    let any_users: bool =
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM users)", [], |row| row.get(0))?;
+    if !any_users {
+        return Ok(Role::Admin);
+    }
+    let Some(identity) = identity else {
+        return Ok(Role::Viewer);
+    };
+    let role: Option<String> = conn
+        .query_row(
+            "SELECT role FROM users WHERE name = ?1",
+            [identity],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(role
+        .as_deref()
+        .and_then(Role::parse)
+        .unwrap_or(Role::Viewer))
+}
+
+/// Whether an admin would be left after giving `name` the role `new_role`, or with
+/// None removing them. Users can't be changed in a way that leaves none.
+pub fn admins_remain_after(conn: &Connection, name: &str, new_role: Option<Role>) -> Result<bool> {
+    if new_role == Some(Role::Admin) {
+        return Ok(true);
+    }
+    let (other_users, other_admins): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(role = 'admin'), 0) FROM users WHERE name != ?1",
+        [name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    // Removing the last user goes back to everyone being an admin
+    Ok(other_admins > 0 || (new_role.is_none() && other_users == 0))
+}
+
+/// Add a user or change their role
+pub fn set_user(conn: &Connection, name: &str, role: Role, now: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO users (name, role, created_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(name) DO UPDATE SET role = excluded.role",
+        params![name, role.as_str(), now],
+    )?;
+    Ok(())
+}
+
+/// Returns false if there is no such user
+pub fn delete_user(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM users WHERE name = ?1", [name])? > 0)
+}
+
+/// Validate a preference value, normalizing it for storage
+pub fn parse_preference(key: &str, value: &str) -> Option<String> {
+    let value = value.trim();
+    match key {
+        "theme" => matches!(value, "light" | "dark").then(|| value.to_string()),
+        "default_scan_interval" => value
+            .parse::<u64>()
+            .ok()
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| minutes.to_string()),
+        _ => None,
+    }
+}
+
+pub fn is_preference_key(key: &str) -> bool {
+    PREFERENCE_KEYS.contains(&key)
+}
+
+pub fn get_user_preferences(conn: &Connection, owner: &str) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT key, value FROM user_preferences WHERE owner = ?1")?;
+    let rows = stmt.query_map([owner], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Set a preference, or with None go back to the default
+pub fn set_user_preference(
+    conn: &Connection,
+    owner: &str,
+    key: &str,
+    value: Option<&str>,
+) -> Result<()> {
+    match value {
+        Some(value) => conn.execute(
+            "INSERT OR REPLACE INTO user_preferences (owner, key, value) VALUES (?1, ?2, ?3)",
+            params![owner, key, value],
+        )?,
+        None => conn.execute(
+            "DELETE FROM user_preferences WHERE owner = ?1 AND key = ?2",
+            params![owner, key],
+        )?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_resolve_role() {
+        let conn = new_test_connection();
+        // Nobody configured: everyone is an admin
+        assert_eq!(resolve_role(&conn, None).unwrap(), Role::Admin);
+        assert_eq!(resolve_role(&conn, Some("bob")).unwrap(), Role::Admin);
+
+        set_user(&conn, "alice", Role::Admin, 1).unwrap();
+        set_user(&conn, "bob", Role::Viewer, 1).unwrap();
+        assert_eq!(resolve_role(&conn, Some("ALICE")).unwrap(), Role::Admin);
+        assert_eq!(resolve_role(&conn, Some("bob")).unwrap(), Role::Viewer);
+        assert_eq!(resolve_role(&conn, Some("mallory")).unwrap(), Role::Viewer);
+        assert_eq!(resolve_role(&conn, None).unwrap(), Role::Viewer);
+
+        set_user(&conn, "bob", Role::Admin, 2).unwrap();
+        let users = get_users(&conn).unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[1].role, Role::Admin);
+        assert_eq!(users[1].created_at, 1);
+    }
+
+    #[test]
+    fn test_user_tokens() {
+        let conn = new_test_connection();
+        set_user(&conn, "alice", Role::Admin, 1).unwrap();
+        set_user(&conn, "bob", Role::Viewer, 1).unwrap();
+        assert!(!set_user_token(&conn, "carol", "unused").unwrap());
+
+        assert!(set_user_token(&conn, "bob", "first-token").unwrap());
+        assert_eq!(
+            user_for_token(&conn, "first-token").unwrap().as_deref(),
+            Some("bob")
+        );
+        assert_eq!(user_for_token(&conn, "other-token").unwrap(), None);
+
+        // Only the hash is kept
+        let stored: String = conn
+            .query_row(
+                "SELECT token_hash FROM users WHERE name = 'bob'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, hash_api_token("first-token"));
+        assert_eq!(stored.len(), 64);
+
+        // A new token replaces the old one
+        assert!(set_user_token(&conn, "bob", "second-token").unwrap());
+        assert_eq!(user_for_token(&conn, "first-token").unwrap(), None);
+        let users = get_users(&conn).unwrap();
+        assert!(!users[0].has_token);
+        assert!(users[1].has_token);
+    }
+
+    #[test]
+    fn test_admins_remain_after() {
+        let conn = new_test_connection();
+        set_user(&conn, "alice", Role::Admin, 1).unwrap();
+        set_user(&conn, "bob", Role::Viewer, 1).unwrap();
+
+        assert!(!admins_remain_after(&conn, "alice", Some(Role::Viewer)).unwrap());
+        assert!(!admins_remain_after(&conn, "alice", None).unwrap());
+        assert!(admins_remain_after(&conn, "bob", None).unwrap());
+        assert!(admins_remain_after(&conn, "carol", Some(Role::Admin)).unwrap());
+        assert!(admins_remain_after(&conn, "carol", Some(Role::Viewer)).unwrap());
+
+        assert!(delete_user(&conn, "bob").unwrap());
+        assert!(!delete_user(&conn, "bob").unwrap());
+        // The last user can go, which opens the tool up again
+        assert!(admins_remain_after(&conn, "alice", None).unwrap());
+
+        // The first user must be an admin
+        let empty = new_test_connection();
+        assert!(!admins_remain_after(&empty, "carol", Some(Role::Viewer)).unwrap());
+    }
+
+    #[test]
+    fn test_user_preferences() {
+        assert_eq!(
+            parse_preference("theme", " light ").as_deref(),
+            Some("light")
+        );
+        assert!(parse_preference("theme", "blue").is_none());
+        assert_eq!(
+            parse_preference("default_scan_interval", "1440").as_deref(),
+            Some("1440")
+        );
+        assert!(parse_preference("default_scan_interval", "0").is_none());
+        assert!(parse_preference("font", "serif").is_none());
+        assert!(is_preference_key("theme"));
+        assert!(!is_preference_key("font"));
+
+        let conn = new_test_connection();
+        set_user_preference(&conn, "alice", "theme", Some("light")).unwrap();
+        set_user_preference(&conn, "bob", "theme", Some("dark")).unwrap();
+        assert_eq!(
+            get_user_preferences(&conn, "alice").unwrap()["theme"],
+            "light"
+        );
+
+        set_user_preference(&conn, "alice", "theme", None).unwrap();
+        assert!(get_user_preferences(&conn, "alice").unwrap().is_empty());
+        assert_eq!(get_user_preferences(&conn, "bob").unwrap().len(), 1);
+    }
+}
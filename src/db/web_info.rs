@@ -0,0 +1,230 @@
+//! Title and favicon of each endpoint's web interface, cached so the endpoint table
+//! can show them without contacting the device. Entries are refreshed after port
+//! scans once they are a day old, or on demand.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::scanner::WebPageInfo;
+
+pub fn create_web_info_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS endpoint_web_info (
+            endpoint_id INTEGER PRIMARY KEY REFERENCES endpoints(id) ON DELETE CASCADE,
+            url TEXT,
+            title TEXT,
+            favicon BLOB,
+            favicon_type TEXT,
+            fetched_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// What the endpoint table shows for an endpoint's web interface
+#[derive(Debug, Clone, Serialize)]
+pub struct WebInfoSummary {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub has_favicon: bool,
+}
+
+/// An endpoint due a web info fetch, with the web ports port scans found open on it
+#[derive(Debug)]
+pub struct WebInfoTarget {
+    pub endpoint_id: i64,
+    pub ip: String,
+    pub ports: Vec<u16>,
+}
+
+/// Store the outcome of a fetch at `now`. A failed fetch keeps what was cached
+/// before, since the device may just be offline for the moment.
+pub fn store_web_info(
+    conn: &Connection,
+    endpoint_id: i64,
+    info: Option<&WebPageInfo>,
+    now: i64,
+) -> Result<()> {
+    match info {
+        Some(info) => {
+            conn.execute(
+                "INSERT OR REPLACE INTO endpoint_web_info
+                    (endpoint_id, url, title, favicon, favicon_type, fetched_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    endpoint_id,
+                    info.url,
+                    info.title,
+                    info.favicon.as_ref().map(|f| &f.data),
+                    info.favicon.as_ref().map(|f| &f.content_type),
+                    now
+                ],
+            )?;
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO endpoint_web_info (endpoint_id, fetched_at) VALUES (?1, ?2)
+                 ON CONFLICT(endpoint_id) DO UPDATE SET fetched_at = excluded.fetched_at",
+                params![endpoint_id, now],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Endpoints with an open web port whose web info is missing or older than `stale_before`
+pub fn get_web_info_targets(
+    conn: &Connection,
+    web_ports: &[u16],
+    stale_before: i64,
+) -> Result<Vec<WebInfoTarget>> {
+    let ports = web_ports
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT op.endpoint_id, GROUP_CONCAT(DISTINCT op.port),
+                (SELECT ea.ip FROM endpoint_attributes ea
+                 WHERE ea.endpoint_id = op.endpoint_id AND ea.ip != ''
+                 ORDER BY ea.created_at DESC LIMIT 1) AS ip
+         FROM open_ports op
+         LEFT JOIN endpoint_web_info w ON w.endpoint_id = op.endpoint_id
+         WHERE op.port IN ({}) AND (w.fetched_at IS NULL OR w.fetched_at < ?1)
+         GROUP BY op.endpoint_id
+         HAVING ip IS NOT NULL",
+        ports
+    ))?;
+    let rows = stmt.query_map([stale_before], |row| {
+        let ports: String = row.get(1)?;
+        Ok(WebInfoTarget {
+            endpoint_id: row.get(0)?,
+            ports: ports.split(',').filter_map(|p| p.parse().ok()).collect(),
+            ip: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// An endpoint's cached favicon as (content type, image bytes)
+pub fn get_web_favicon(conn: &Connection, endpoint_id: i64) -> Result<Option<(String, Vec<u8>)>> {
+    conn.query_row(
+        "SELECT favicon_type, favicon FROM endpoint_web_info
+         WHERE endpoint_id = ?1 AND favicon IS NOT NULL",
+        [endpoint_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Web info for every endpoint that served a page, keyed by lowercase display name
+pub fn get_web_info_summaries(conn: &Connection) -> Result<HashMap<String, WebInfoSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT LOWER(e.display_name), w.url, w.title, w.favicon IS NOT NULL
+         FROM endpoint_web_info w
+         JOIN endpoints e ON e.id = w.endpoint_id
+         WHERE w.url IS NOT NULL AND e.display_name IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            WebInfoSummary {
+                url: row.get(1)?,
+                title: row.get(2)?,
+                has_favicon: row.get(3)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Move an endpoint's web info to the endpoint it is merged into, unless that one
+/// has its own
+pub fn reassign_web_info(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE endpoint_web_info SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM endpoint_web_info WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+    use crate::scanner::Favicon;
+
+    fn page(title: &str) -> WebPageInfo {
+        WebPageInfo {
+            url: "http://192.168.1.10/".to_string(),
+            title: Some(title.to_string()),
+            favicon: Some(Favicon {
+                content_type: "image/png".to_string(),
+                data: vec![0x89, b'P', b'N', b'G'],
+            }),
+        }
+    }
+
+    #[test]
+    fn test_store_and_refresh_web_info() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'nas'), (2, 1, 'printer')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO endpoint_attributes (created_at, endpoint_id, ip)
+             VALUES (1, 1, '192.168.1.10'), (1, 2, '192.168.1.20')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO open_ports (endpoint_id, port, last_seen_at)
+             VALUES (1, 443, 1), (1, 5000, 1), (1, 80, 1), (2, 22, 1)",
+            [],
+        )
+        .unwrap();
+
+        let targets = get_web_info_targets(&conn, &[80, 443, 8080, 8443], 100).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].endpoint_id, 1);
+        assert_eq!(targets[0].ip, "192.168.1.10");
+        let mut ports = targets[0].ports.clone();
+        ports.sort();
+        assert_eq!(ports, vec![80, 443]);
+
+        store_web_info(&conn, 1, Some(&page("Synology DSM")), 100).unwrap();
+        assert!(
+            get_web_info_targets(&conn, &[80, 443], 100)
+                .unwrap()
+                .is_empty()
+        );
+
+        // A failed refresh keeps the cached page
+        store_web_info(&conn, 1, None, 200).unwrap();
+        let summaries = get_web_info_summaries(&conn).unwrap();
+        assert_eq!(summaries["nas"].title.as_deref(), Some("Synology DSM"));
+        assert!(summaries["nas"].has_favicon);
+        assert!(
+            get_web_info_targets(&conn, &[80, 443], 150)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            get_web_favicon(&conn, 1).unwrap(),
+            Some(("image/png".to_string(), vec![0x89, b'P', b'N', b'G']))
+        );
+
+        reassign_web_info(&conn, 1, 2).unwrap();
+        assert!(get_web_favicon(&conn, 1).unwrap().is_none());
+        assert!(get_web_favicon(&conn, 2).unwrap().is_some());
+    }
+}
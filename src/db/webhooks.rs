@@ -0,0 +1,530 @@
+//! Outbound webhooks for endpoint events, for automation keyed by device (DNS records,
+//! firewall rules, presence). A webhook lists the events it wants and may be limited to some
+//! endpoints, device types or tags. Raising an event only queues a delivery row; a background
+//! task posts the queued JSON, so capture and scans never wait on the receiving server.
+//! Failed posts are retried with backoff, and a shared secret signs each body.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::task;
+
+use super::{new_connection_result, parse_tags};
+
+/// Events a webhook can subscribe to
+pub const WEBHOOK_EVENTS: &[&str] = &[
+    "ip_changed",
+    "quota_exceeded",
+    "person_arrived",
+    "person_left",
+];
+
+/// Timeout for each delivery
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often queued deliveries are sent
+const DELIVERY_INTERVAL_SECS: u64 = 10;
+
+/// Deliveries given up after this many failed posts
+const MAX_DELIVERY_ATTEMPTS: i64 = 5;
+
+/// Deliveries sent per run, so a long outage drains gradually
+const DELIVERY_BATCH: i64 = 100;
+
+/// Delivered and abandoned deliveries are kept this long for the status report
+const DELIVERY_RETENTION_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Longest error kept for a failed delivery
+const MAX_ERROR_LEN: usize = 300;
+
+/// Flag to ensure the delivery task is only started once
+static WEBHOOK_DELIVERY_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn create_webhook_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhooks (
+            id INTEGER PRIMARY KEY,
+            url TEXT NOT NULL,
+            events TEXT NOT NULL DEFAULT '[]',
+            device_types TEXT NOT NULL DEFAULT '[]',
+            secret TEXT NOT NULL DEFAULT '',
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    // Added after the table; fails harmlessly once the column exists
+    let _ = conn.execute(
+        "ALTER TABLE webhooks ADD COLUMN tags TEXT NOT NULL DEFAULT '[]'",
+        [],
+    );
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhook_endpoints (
+            webhook_id INTEGER NOT NULL,
+            endpoint_id INTEGER NOT NULL,
+            PRIMARY KEY (webhook_id, endpoint_id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id INTEGER PRIMARY KEY,
+            webhook_id INTEGER NOT NULL,
+            event_type TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            next_attempt_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            delivered_at INTEGER,
+            last_error TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_pending
+         ON webhook_deliveries (delivered_at, next_attempt_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A webhook as created through the API. Empty lists mean "every event" and "every
+/// endpoint"; endpoints, device types and tags widen each other (any one matching is
+/// enough).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NewWebhook {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Endpoint names, resolved to ids by the caller
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    #[serde(default)]
+    pub device_types: Vec<String>,
+    /// Endpoint tags; an endpoint carrying any of them matches
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub secret: String,
+}
+
+impl NewWebhook {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let url = url::Url::parse(self.url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err("Webhook URL must be http or https".to_string());
+        }
+        if let Some(event) = self
+            .events
+            .iter()
+            .find(|event| !WEBHOOK_EVENTS.contains(&event.as_str()))
+        {
+            return Err(format!(
+                "Unknown event '{}' (expected one of: {})",
+                event,
+                WEBHOOK_EVENTS.join(", ")
+            ));
+        }
+        parse_tags(&self.tags)?;
+        Ok(())
+    }
+}
+
+/// A configured webhook and how its deliveries are going. The secret is never read back.
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<String>,
+    pub endpoints: Vec<String>,
+    pub device_types: Vec<String>,
+    pub tags: Vec<String>,
+    pub has_secret: bool,
+    pub created_at: i64,
+    pub pending: i64,
+    pub failed: i64,
+    pub last_delivered_at: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+/// Store a webhook limited to `endpoint_ids` (and the request's device types and tags).
+/// Returns its id.
+pub fn create_webhook(
+    conn: &Connection,
+    webhook: &NewWebhook,
+    endpoint_ids: &[i64],
+) -> Result<i64> {
+    let to_json = |list: &[String]| serde_json::to_string(list).unwrap_or_else(|_| "[]".into());
+    conn.execute(
+        "INSERT INTO webhooks (url, events, device_types, tags, secret, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, strftime('%s', 'now'))",
+        params![
+            webhook.url.trim(),
+            to_json(&webhook.events),
+            to_json(&webhook.device_types),
+            to_json(&parse_tags(&webhook.tags).unwrap_or_default()),
+            webhook.secret.trim(),
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    for endpoint_id in endpoint_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO webhook_endpoints (webhook_id, endpoint_id) VALUES (?1, ?2)",
+            params![id, endpoint_id],
+        )?;
+    }
+    Ok(id)
+}
+
+/// Remove a webhook and its queued deliveries. Returns false when it didn't exist.
+pub fn delete_webhook(conn: &Connection, id: i64) -> Result<bool> {
+    conn.execute("DELETE FROM webhook_endpoints WHERE webhook_id = ?1", [id])?;
+    conn.execute("DELETE FROM webhook_deliveries WHERE webhook_id = ?1", [id])?;
+    Ok(conn.execute("DELETE FROM webhooks WHERE id = ?1", [id])? > 0)
+}
+
+pub fn get_webhooks(conn: &Connection) -> Result<Vec<Webhook>> {
+    let mut stmt = conn.prepare(
+        "SELECT w.id, w.url, w.events, w.device_types, w.secret != '', w.created_at,
+                (SELECT COUNT(*) FROM webhook_deliveries d
+                 WHERE d.webhook_id = w.id AND d.delivered_at IS NULL AND d.attempts < ?1),
+                (SELECT COUNT(*) FROM webhook_deliveries d
+                 WHERE d.webhook_id = w.id AND d.delivered_at IS NULL AND d.attempts >= ?1),
+                (SELECT MAX(delivered_at) FROM webhook_deliveries d WHERE d.webhook_id = w.id),
+                (SELECT last_error FROM webhook_deliveries d
+                 WHERE d.webhook_id = w.id AND d.last_error IS NOT NULL
+                 ORDER BY d.id DESC LIMIT 1),
+                (SELECT json_group_array(e.display_name) FROM webhook_endpoints f
+                 JOIN endpoints e ON e.id = f.endpoint_id WHERE f.webhook_id = w.id),
+                w.tags
+         FROM webhooks w
+         ORDER BY w.id",
+    )?;
+    let list = |json: String| serde_json::from_str::<Vec<String>>(&json).unwrap_or_default();
+    let rows = stmt.query_map([MAX_DELIVERY_ATTEMPTS], |row| {
+        Ok(Webhook {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            events: list(row.get(2)?),
+            device_types: list(row.get(3)?),
+            has_secret: row.get(4)?,
+            created_at: row.get(5)?,
+            pending: row.get(6)?,
+            failed: row.get(7)?,
+            last_delivered_at: row.get(8)?,
+            last_error: row.get(9)?,
+            endpoints: list(row.get(10)?),
+            tags: list(row.get(11)?),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Queue an event about `endpoint_id` for every webhook that wants it. Returns the
+/// number of deliveries queued.
+pub fn queue_webhook_event(
+    conn: &Connection,
+    event_type: &str,
+    endpoint_id: i64,
+    payload: &Value,
+) -> Result<usize> {
+    conn.execute(
+        "INSERT INTO webhook_deliveries (webhook_id, event_type, payload, created_at, next_attempt_at)
+         SELECT w.id, ?1, ?2, strftime('%s', 'now'), strftime('%s', 'now')
+         FROM webhooks w
+         WHERE (w.events = '[]' OR EXISTS (SELECT 1 FROM json_each(w.events) WHERE value = ?1))
+           AND ((w.device_types = '[]' AND w.tags = '[]'
+                 AND NOT EXISTS (SELECT 1 FROM webhook_endpoints f WHERE f.webhook_id = w.id))
+                OR EXISTS (SELECT 1 FROM webhook_endpoints f
+                           WHERE f.webhook_id = w.id AND f.endpoint_id = ?3)
+                OR EXISTS (SELECT 1 FROM json_each(w.device_types) t
+                           JOIN endpoints e ON e.id = ?3
+                           WHERE t.value = COALESCE(e.manual_device_type, e.auto_device_type))
+                OR EXISTS (SELECT 1 FROM json_each(w.tags) t
+                           JOIN endpoint_tags et ON et.tag = t.value AND et.endpoint_id = ?3))",
+        params![event_type, payload.to_string(), endpoint_id],
+    )
+}
+
+/// Point endpoint filters at the endpoint a merged endpoint was folded into
+pub fn reassign_webhook_endpoints(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE webhook_endpoints SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM webhook_endpoints WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+/// HMAC-SHA256 of `body` under `secret`, as lowercase hex
+fn sign(secret: &str, body: &str) -> String {
+    const BLOCK: usize = 64;
+    let mut key = secret.as_bytes().to_vec();
+    if key.len() > BLOCK {
+        key = Sha256::digest(&key).to_vec();
+    }
+    key.resize(BLOCK, 0);
+    let pad = |byte: u8| key.iter().map(|k| k ^ byte).collect::<Vec<u8>>();
+
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(body.as_bytes())
+        .finalize();
+    let outer = Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize();
+    outer.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Post one delivery
+fn post(
+    client: &Client,
+    url: &str,
+    secret: &str,
+    event_type: &str,
+    payload: &str,
+) -> std::result::Result<(), String> {
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Event", event_type)
+        .body(payload.to_string());
+    if !secret.is_empty() {
+        request = request.header(
+            "X-Webhook-Signature",
+            format!("sha256={}", sign(secret, payload)),
+        );
+    }
+    let response = request.send().map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status()))
+    }
+}
+
+/// Send the deliveries that are due. Returns (delivered, failed).
+pub fn deliver_pending_webhooks(conn: &Connection) -> Result<(usize, usize)> {
+    let due: Vec<(i64, String, String, String, String, i64)> = {
+        let mut stmt = conn.prepare(
+            "SELECT d.id, w.url, w.secret, d.event_type, d.payload, d.attempts
+             FROM webhook_deliveries d
+             JOIN webhooks w ON w.id = d.webhook_id
+             WHERE d.delivered_at IS NULL AND d.attempts < ?1
+               AND d.next_attempt_at <= strftime('%s', 'now')
+             ORDER BY d.id
+             LIMIT ?2",
+        )?;
+        stmt.query_map(params![MAX_DELIVERY_ATTEMPTS, DELIVERY_BATCH], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })?
+        .collect::<Result<_>>()?
+    };
+    if due.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let (mut delivered, mut failed) = (0, 0);
+    for (id, url, secret, event_type, payload, attempts) in due {
+        match post(&client, &url, &secret, &event_type, &payload) {
+            Ok(()) => {
+                delivered += 1;
+                conn.execute(
+                    "UPDATE webhook_deliveries
+                     SET delivered_at = strftime('%s', 'now'), attempts = attempts + 1, last_error = NULL
+                     WHERE id = ?1",
+                    [id],
+                )?;
+            }
+            Err(e) => {
+                failed += 1;
+                // Back off 30s, 1m, 2m, 4m between attempts
+                let error: String = e.chars().take(MAX_ERROR_LEN).collect();
+                conn.execute(
+                    "UPDATE webhook_deliveries
+                     SET attempts = attempts + 1, last_error = ?1,
+                         next_attempt_at = strftime('%s', 'now') + ?2
+                     WHERE id = ?3",
+                    params![error, 30_i64 << attempts.min(10), id],
+                )?;
+            }
+        }
+    }
+    Ok((delivered, failed))
+}
+
+/// Start the task that sends queued deliveries and prunes old ones
+pub fn start_webhook_delivery() {
+    if WEBHOOK_DELIVERY_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        loop {
+            let result = task::spawn_blocking(|| {
+                let conn = new_connection_result()?;
+                conn.execute(
+                    "DELETE FROM webhook_deliveries
+                     WHERE (delivered_at IS NOT NULL OR attempts >= ?1)
+                       AND created_at < strftime('%s', 'now') - ?2",
+                    params![MAX_DELIVERY_ATTEMPTS, DELIVERY_RETENTION_SECS],
+                )?;
+                deliver_pending_webhooks(&conn)
+            })
+            .await;
+
+            match result {
+                Ok(Ok((delivered, failed))) if failed > 0 => eprintln!(
+                    "Webhooks: {} delivered, {} failed (will retry)",
+                    delivered, failed
+                ),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("Webhook delivery failed: {}", e),
+                Err(e) => eprintln!("Webhook delivery task failed: {}", e),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(DELIVERY_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+    use crate::network::endpoint::EndPoint;
+
+    #[test]
+    fn test_sign_matches_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_ip_change_queues_matching_webhooks() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'nas', 'appliance'), (2, 1, 'tv', 'tv');",
+        )
+        .unwrap();
+        let webhook = |device_types: &[&str]| NewWebhook {
+            url: "http://automation.lan/hook".to_string(),
+            events: vec!["ip_changed".to_string()],
+            device_types: device_types.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let for_nas = create_webhook(&conn, &webhook(&[]), &[1]).unwrap();
+        let for_tvs = create_webhook(&conn, &webhook(&["tv"]), &[]).unwrap();
+        assert!(webhook(&[]).validate().is_ok());
+        let mut bad = webhook(&[]);
+        bad.events = vec!["ip_moved".to_string()];
+        assert!(bad.validate().is_err());
+
+        EndPoint::record_ip_history(&conn, 1, "192.168.1.10", None, "dhcp", 100).unwrap();
+        EndPoint::record_ip_history(
+            &conn,
+            1,
+            "192.168.1.20",
+            Some("aa:aa:aa:aa:aa:01"),
+            "dhcp",
+            200,
+        )
+        .unwrap();
+        // A new IPv6 address isn't a change of the IPv4 one
+        EndPoint::record_ip_history(&conn, 1, "fd00::10", None, "ndp", 300).unwrap();
+
+        let deliveries: Vec<(i64, String)> = conn
+            .prepare("SELECT webhook_id, payload FROM webhook_deliveries")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].0, for_nas);
+        let payload: Value = serde_json::from_str(&deliveries[0].1).unwrap();
+        assert_eq!(payload["old_ip"], "192.168.1.10");
+        assert_eq!(payload["new_ip"], "192.168.1.20");
+        assert_eq!(payload["mac"], "aa:aa:aa:aa:aa:01");
+
+        EndPoint::record_ip_history(&conn, 2, "192.168.1.30", None, "arp", 100).unwrap();
+        EndPoint::record_ip_history(&conn, 2, "192.168.1.31", None, "arp", 200).unwrap();
+        reassign_webhook_endpoints(&conn, 1, 2).unwrap();
+        let webhooks = get_webhooks(&conn).unwrap();
+        assert_eq!(webhooks[0].endpoints, vec!["tv".to_string()]);
+        assert_eq!(webhooks[0].pending, 1);
+        assert_eq!(webhooks[1].id, for_tvs);
+        assert_eq!(webhooks[1].pending, 1);
+
+        assert!(delete_webhook(&conn, for_nas).unwrap());
+        assert_eq!(get_webhooks(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_webhook_filters_widen_each_other() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name, auto_device_type) VALUES
+                 (1, 1, 'nas', 'appliance'), (2, 1, 'tv', 'tv'), (3, 1, 'plug', 'appliance');
+             INSERT INTO endpoint_tags (endpoint_id, tag, created_at) VALUES
+                 (3, 'iot', 1), (2, 'kids', 1);",
+        )
+        .unwrap();
+        let webhook = |device_types: &[&str], tags: &[&str]| NewWebhook {
+            url: "http://automation.lan/hook".to_string(),
+            device_types: device_types.iter().map(|t| t.to_string()).collect(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let every = create_webhook(&conn, &webhook(&[], &[]), &[]).unwrap();
+        let nas_or_iot = create_webhook(&conn, &webhook(&[], &["IoT"]), &[1]).unwrap();
+        let tvs_or_iot = create_webhook(&conn, &webhook(&["tv"], &["iot"]), &[]).unwrap();
+        let unused_tag = create_webhook(&conn, &webhook(&[], &["guest"]), &[]).unwrap();
+        assert!(webhook(&[], &["not a tag"]).validate().is_err());
+        assert_eq!(
+            get_webhooks(&conn).unwrap()[1].tags,
+            vec!["iot".to_string()]
+        );
+
+        let matched = |endpoint_id: i64| -> Vec<i64> {
+            conn.execute("DELETE FROM webhook_deliveries", []).unwrap();
+            queue_webhook_event(&conn, "ip_changed", endpoint_id, &Value::Null).unwrap();
+            conn.prepare("SELECT webhook_id FROM webhook_deliveries ORDER BY webhook_id")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_>>()
+                .unwrap()
+        };
+        // Endpoint filter
+        assert_eq!(matched(1), vec![every, nas_or_iot]);
+        // Device type filter; the 'kids' tag matches nothing
+        assert_eq!(matched(2), vec![every, tvs_or_iot]);
+        // Tag filter, on its own or next to endpoints and device types
+        assert_eq!(matched(3), vec![every, nas_or_iot, tvs_or_iot]);
+        assert!(!matched(3).contains(&unused_tag));
+    }
+}
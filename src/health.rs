@@ -0,0 +1,315 @@
+//! Process health. Components (packet capture per interface, the SQL writer, mDNS, the
+//! scanner) report activity and errors here, and `/api/health` turns that into a status
+//! per component plus uptime. When run under systemd with `WatchdogSec=`, the watchdog is
+//! only pinged while the writer is draining its queue and some interface is capturing,
+//! so systemd restarts a process that is up but no longer recording anything.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::db::writer_queue;
+use crate::network::mdns_lookup::browsing_count;
+use crate::supervisor::{TaskHealth, TaskState, task_health};
+
+/// The SQL writer checks in every second; this long without it means it is stuck
+const WRITER_STALL: Duration = Duration::from_secs(60);
+
+/// Queue share at which the writer counts as falling behind
+const WRITER_BACKLOG_PERCENT: usize = 90;
+
+static STARTED: OnceLock<(Instant, i64)> = OnceLock::new();
+static ACTIVITY: OnceLock<RwLock<HashMap<&'static str, Activity>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Ok,
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: ComponentStatus,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<i64>,
+    /// Component-specific figures such as queue depth or restart count
+    pub details: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Activity {
+    last_active_at: Option<(Instant, i64)>,
+    last_error: Option<String>,
+    last_error_at: Option<i64>,
+}
+
+fn activity() -> &'static RwLock<HashMap<&'static str, Activity>> {
+    ACTIVITY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn now() -> (Instant, i64) {
+    (Instant::now(), chrono::Utc::now().timestamp())
+}
+
+/// Start the uptime clock; call once at startup
+pub fn init() {
+    STARTED.get_or_init(now);
+}
+
+pub fn uptime_secs() -> u64 {
+    STARTED.get_or_init(now).0.elapsed().as_secs()
+}
+
+pub fn started_at() -> i64 {
+    STARTED.get_or_init(now).1
+}
+
+/// Note that a component did its work just now
+pub fn record_activity(component: &'static str) {
+    if let Ok(mut activity) = activity().write() {
+        activity.entry(component).or_default().last_active_at = Some(now());
+    }
+}
+
+/// Remember a component's latest error
+pub fn record_error(component: &'static str, error: impl Into<String>) {
+    if let Ok(mut activity) = activity().write() {
+        let entry = activity.entry(component).or_default();
+        entry.last_error = Some(error.into());
+        entry.last_error_at = Some(chrono::Utc::now().timestamp());
+    }
+}
+
+fn activity_of(component: &str) -> Activity {
+    activity()
+        .read()
+        .ok()
+        .and_then(|activity| activity.get(component).cloned())
+        .unwrap_or_default()
+}
+
+impl Activity {
+    /// Whether the latest error came after the latest activity
+    fn failing(&self) -> bool {
+        match (self.last_error_at, self.last_active_at) {
+            (Some(error_at), Some((_, active_at))) => error_at > active_at,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.last_active_at.map(|(at, _)| at.elapsed())
+    }
+}
+
+fn capture_health(task: &TaskHealth) -> ComponentHealth {
+    ComponentHealth {
+        name: task.name.clone(),
+        status: match task.state {
+            TaskState::Running => ComponentStatus::Ok,
+            TaskState::Restarting => ComponentStatus::Degraded,
+            TaskState::Stopped | TaskState::Failed => ComponentStatus::Down,
+        },
+        last_error: task.last_error.clone(),
+        last_error_at: task.last_error_at,
+        details: serde_json::json!({
+            "state": task.state,
+            "started_at": task.started_at,
+            "restarts": task.restarts,
+        }),
+    }
+}
+
+/// Capture on each interface
+pub fn capture_components() -> Vec<ComponentHealth> {
+    task_health()
+        .iter()
+        .filter(|task| task.name.starts_with("capture:"))
+        .map(capture_health)
+        .collect()
+}
+
+/// The SQL writer: down once its thread has exited or stalled, degraded while its
+/// queue is nearly full or batches are being dropped
+pub fn writer_component() -> ComponentHealth {
+    let activity = activity_of("sql_writer");
+    let queue = writer_queue();
+    let stalled = activity.idle_for().is_some_and(|idle| idle >= WRITER_STALL);
+    let status = match queue {
+        None => ComponentStatus::Down,
+        Some(_) if stalled => ComponentStatus::Down,
+        Some((depth, capacity)) if depth * 100 >= capacity * WRITER_BACKLOG_PERCENT => {
+            ComponentStatus::Degraded
+        }
+        Some(_) if activity.failing() => ComponentStatus::Degraded,
+        Some(_) => ComponentStatus::Ok,
+    };
+    ComponentHealth {
+        name: "sql_writer".to_string(),
+        status,
+        last_error: activity.last_error,
+        last_error_at: activity.last_error_at,
+        details: serde_json::json!({
+            "queue_depth": queue.map(|(depth, _)| depth),
+            "queue_capacity": queue.map(|(_, capacity)| capacity),
+            "last_active_at": activity.last_active_at.map(|(_, at)| at),
+        }),
+    }
+}
+
+/// The mDNS daemon and its service browses
+pub fn mdns_component() -> ComponentHealth {
+    let activity = activity_of("mdns");
+    let browsing = browsing_count();
+    ComponentHealth {
+        name: "mdns".to_string(),
+        status: if browsing == 0 {
+            ComponentStatus::Down
+        } else if activity.failing() {
+            ComponentStatus::Degraded
+        } else {
+            ComponentStatus::Ok
+        },
+        last_error: activity.last_error,
+        last_error_at: activity.last_error_at,
+        details: serde_json::json!({
+            "services_browsed": browsing,
+            "last_resolved_at": activity.last_active_at.map(|(_, at)| at),
+        }),
+    }
+}
+
+/// The worst status of any component
+pub fn overall_status(components: &[ComponentHealth]) -> ComponentStatus {
+    components
+        .iter()
+        .map(|component| component.status)
+        .max()
+        .unwrap_or(ComponentStatus::Ok)
+}
+
+/// Whether the process is still doing its job: the writer is alive and, if any
+/// interface was set up for capture, at least one is capturing or being restarted
+fn is_alive() -> bool {
+    let captures = capture_components();
+    writer_component().status != ComponentStatus::Down
+        && (captures.is_empty()
+            || captures
+                .iter()
+                .any(|capture| capture.status != ComponentStatus::Down))
+}
+
+/// Send a state line to systemd's notification socket
+#[cfg(unix)]
+fn sd_notify(state: &str) -> bool {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return false;
+    };
+    let path = path.to_string_lossy().into_owned();
+    // A leading '@' names a socket in the abstract namespace
+    let sent = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+                .map(|_| ())
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => return false,
+        None => socket.send_to(state.as_bytes(), &path).map(|_| ()),
+    };
+    sent.is_ok()
+}
+
+#[cfg(not(unix))]
+fn sd_notify(_state: &str) -> bool {
+    false
+}
+
+/// How often to ping the systemd watchdog: half of `WATCHDOG_USEC`, if the watchdog is
+/// enabled for this process
+fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse::<u32>().ok() != Some(std::process::id())
+    {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Tell systemd (`Type=notify`) startup is done, and keep its watchdog fed while the
+/// process is healthy. Does nothing outside systemd.
+pub fn start_systemd_watchdog() {
+    if !sd_notify("READY=1") {
+        return;
+    }
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+    println!(
+        "systemd watchdog enabled, pinging every {:?} while healthy",
+        interval
+    );
+    tokio::spawn(async move {
+        loop {
+            if is_alive() {
+                sd_notify("WATCHDOG=1");
+            } else {
+                eprintln!("Not pinging systemd watchdog: capture or the SQL writer is down");
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity_after_error_clears_failure() {
+        record_error("test:component", "timed out");
+        assert!(activity_of("test:component").failing());
+        record_activity("test:component");
+        let activity = activity_of("test:component");
+        assert!(!activity.failing());
+        assert_eq!(activity.last_error.as_deref(), Some("timed out"));
+        assert!(!activity_of("test:unknown").failing());
+
+        let component = |status| ComponentHealth {
+            name: "test".to_string(),
+            status,
+            last_error: None,
+            last_error_at: None,
+            details: serde_json::Value::Null,
+        };
+        assert_eq!(overall_status(&[]), ComponentStatus::Ok);
+        assert_eq!(
+            overall_status(&[
+                component(ComponentStatus::Ok),
+                component(ComponentStatus::Degraded)
+            ]),
+            ComponentStatus::Degraded
+        );
+        assert_eq!(
+            overall_status(&[
+                component(ComponentStatus::Down),
+                component(ComponentStatus::Degraded)
+            ]),
+            ComponentStatus::Down
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Network discovery library: packet capture and decoding, the endpoint database, active
+//! scanners, and the web UI. The `rust_network_discovery_tool` binary runs it as a daemon
+//! or as one-shot companion subcommands (see `cli`).
+
+pub mod cli;
+pub mod config;
+pub mod control;
+pub mod db;
+pub mod health;
+pub mod network;
+pub mod pcap;
+pub mod scanner;
+pub mod supervisor;
+pub mod web;
+
+#[cfg(test)]
+mod test_utils;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Global flag to pause packet capture (allows pcap playback without live interference)
+static CAPTURE_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Check if packet capture is paused
+pub fn is_capture_paused() -> bool {
+    CAPTURE_PAUSED.load(Ordering::Relaxed)
+}
+
+/// Set the capture paused state and save it so it survives a restart (blocking)
+pub fn set_capture_paused(paused: bool) {
+    if CAPTURE_PAUSED.swap(paused, Ordering::Relaxed) != paused {
+        db::record_activity_event(
+            if paused {
+                "capture_paused"
+            } else {
+                "capture_resumed"
+            },
+            serde_json::json!({}),
+        );
+    }
+    if let Err(e) = db::set_setting("capture_paused", if paused { "true" } else { "false" }) {
+        eprintln!("Failed to save capture pause state: {}", e);
+    }
+}
+
+/// Load the pause state saved by `set_capture_paused`
+pub fn restore_capture_paused() {
+    let paused = db::get_setting("capture_paused").as_deref() == Some("true");
+    CAPTURE_PAUSED.store(paused, Ordering::Relaxed);
+    if paused {
+        println!("Capture is paused (resume it from the web UI)");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{self, new_test_connection};
+    use crate::network::communication::Communication;
+    use crate::pcap::process_pcap_file;
+    use crate::test_utils::{PacketBuilder, create_test_pcap};
+    use pnet::packet::ethernet::EthernetPacket;
+
+    #[test]
+    fn test_communication_from_synthetic_packet() {
+        let packet_data = PacketBuilder::https_packet("192.168.1.100", "1.1.1.1");
+        let eth_packet = EthernetPacket::new(&packet_data).unwrap();
+
+        let comm = Communication::new(eth_packet);
+
+        assert_eq!(comm.source_ip, Some("192.168.1.100".to_string()));
+        assert_eq!(comm.destination_ip, Some("1.1.1.1".to_string()));
+        assert_eq!(comm.destination_port, Some(443));
+        assert_eq!(comm.ip_header_protocol, Some("Tcp".to_string()));
+    }
+
+    #[test]
+    fn test_communication_insertion_to_db() {
+        let conn = new_test_connection();
+        // Use loopback IPs which are always considered local
+        let packet_data = PacketBuilder::https_packet("127.0.0.2", "127.0.0.3");
+        let eth_packet = EthernetPacket::new(&packet_data).unwrap();
+
+        let comm = Communication::new_with_source(eth_packet, Some("test".to_string()));
+        let result = comm.insert_communication(&conn);
+
+        assert!(result.is_ok());
+
+        // Verify communication was inserted
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM communications", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // Verify source was stored
+        let source: String = conn
+            .query_row(
+                "SELECT source FROM communications WHERE source IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(source, "test");
+    }
+
+    #[test]
+    fn test_communication_interface_stored() {
+        let conn = new_test_connection();
+        let packet_data = PacketBuilder::https_packet("127.0.0.2", "127.0.0.3");
+        let eth_packet = EthernetPacket::new(&packet_data).unwrap();
+
+        let comm = Communication::new(eth_packet).with_interface("eth0");
+        comm.insert_communication(&conn).unwrap();
+
+        let interface: String = conn
+            .query_row("SELECT interface FROM communications", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(interface, "eth0");
+    }
+
+    #[test]
+    fn test_pcap_file_creation_and_reading() {
+        // Create synthetic packets
+        let packets = vec![
+            PacketBuilder::https_packet("192.168.1.100", "1.1.1.1"),
+            PacketBuilder::http_packet("192.168.1.100", "8.8.8.8"),
+            PacketBuilder::dns_packet("192.168.1.100", "8.8.4.4"),
+        ];
+
+        // Create pcap file
+        let pcap_file = create_test_pcap(packets.clone()).unwrap();
+        let path = pcap_file.path().to_str().unwrap();
+
+        // Verify file exists
+        assert!(std::path::Path::new(path).exists());
+
+        // Read it back using pcap crate
+        let mut cap = ::pcap::Capture::from_file(path).unwrap();
+
+        let mut packet_count = 0;
+        while cap.next_packet().is_ok() {
+            packet_count += 1;
+        }
+
+        assert_eq!(packet_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_integration_pcap_import_to_db() {
+        // Create synthetic packets representing different types of traffic
+        let packets = vec![
+            PacketBuilder::https_packet("192.168.1.100", "1.1.1.1"), // Cloudflare
+            PacketBuilder::https_packet("192.168.1.100", "8.8.8.8"), // Google DNS
+            PacketBuilder::http_packet("192.168.1.100", "142.250.185.46"), // Google
+            PacketBuilder::dns_packet("192.168.1.100", "8.8.4.4"),   // Google DNS
+            PacketBuilder::https_packet("192.168.1.101", "1.1.1.1"), // Different local IP
+        ];
+
+        // Create pcap file
+        let pcap_file = create_test_pcap(packets).unwrap();
+        let path = pcap_file.path().to_str().unwrap().to_string();
+
+        // Use in-memory DB for testing
+        unsafe {
+            std::env::set_var("DATABASE_URL", ":memory:");
+        }
+
+        // Create SQL writer
+        let sql_writer = db::SQLWriter::new().await;
+
+        // Process the pcap file in a blocking task
+        let sender = sql_writer.sender.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            process_pcap_file(&path, Some("integration_test".to_string()), &sender)
+        })
+        .await
+        .unwrap();
+
+        assert!(result.is_ok());
+        let packet_count = result.unwrap();
+        assert_eq!(packet_count, 5);
+
+        // Give the SQL writer time to process
+        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+
+        // Verify data in database (Note: won't work with different in-memory DB)
+        // This test verifies that pcap files can be processed without errors
+        println!(
+            "Successfully processed {} packets from pcap file",
+            packet_count
+        );
+    }
+
+    #[test]
+    fn test_different_protocol_packets() {
+        let conn = new_test_connection();
+
+        // Test TCP packet (use unicast MACs - LSB of first octet must be 0)
+        // Use loopback IPs which are always considered local
+        let tcp_packet = PacketBuilder::tcp_packet(
+            "aa:bb:cc:dd:ee:ff", // 0xAA = 0b10101010, LSB=0, unicast
+            "00:22:33:44:55:66", // 0x00 = 0b00000000, LSB=0, unicast
+            "127.0.0.2",
+            "127.0.0.3",
+            12345,
+            443,
+        );
+        let eth = EthernetPacket::new(&tcp_packet).unwrap();
+        let comm_tcp = Communication::new(eth);
+        assert_eq!(comm_tcp.ip_header_protocol, Some("Tcp".to_string()));
+        assert!(comm_tcp.insert_communication(&conn).is_ok());
+
+        // Test UDP packet
+        let udp_packet = PacketBuilder::udp_packet(
+            "aa:bb:cc:dd:ee:ff",
+            "00:22:33:44:55:66",
+            "127.0.0.2",
+            "127.0.0.3",
+            54321,
+            53,
+        );
+        let eth = EthernetPacket::new(&udp_packet).unwrap();
+        let comm_udp = Communication::new(eth);
+        assert_eq!(comm_udp.ip_header_protocol, Some("Udp".to_string()));
+        assert!(comm_udp.insert_communication(&conn).is_ok());
+
+        // Verify both were inserted
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM communications", [], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(count, 2);
+    }
+}
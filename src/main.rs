@@ -1,39 +1,26 @@
 //! Application entry point. Defines CLI arguments, initializes packet capture,
 //! and orchestrates network monitoring across interfaces.
 
-mod db;
-mod network;
-pub mod pcap;
-mod scanner;
-mod web;
-
-#[cfg(test)]
-mod test_utils;
-
 use clap::Parser;
 use pnet::datalink;
 use pnet::datalink::Channel::Ethernet;
 use pnet::datalink::NetworkInterface;
 use pnet::packet::ethernet::EthernetPacket;
 use std::env;
-use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::{io, task};
-
-use db::SQLWriter;
-use {network::communication::Communication, network::mdns_lookup::MDnsLookup};
-
-/// Global flag to pause packet capture (allows pcap playback without live interference)
-static CAPTURE_PAUSED: AtomicBool = AtomicBool::new(false);
-
-/// Check if packet capture is paused
-pub fn is_capture_paused() -> bool {
-    CAPTURE_PAUSED.load(Ordering::Relaxed)
-}
-
-/// Set the capture paused state
-pub fn set_capture_paused(paused: bool) {
-    CAPTURE_PAUSED.store(paused, Ordering::Relaxed);
-}
+use std::net::{IpAddr, Ipv4Addr};
+use tokio::io;
+
+use rust_network_discovery_tool::db::{
+    SQLWriter, is_capture_scheduled_off, start_capture_scheduler,
+};
+use rust_network_discovery_tool::network::host_tables::start_host_table_collector;
+use rust_network_discovery_tool::network::tcp_quality::TcpQualityTracker;
+use rust_network_discovery_tool::network::wireless::is_radiotap_interface;
+use rust_network_discovery_tool::network::{communication::Communication, mdns_lookup::MDnsLookup};
+use rust_network_discovery_tool::pcap::process_pcap_file;
+use rust_network_discovery_tool::{
+    cli, config, control, db, health, is_capture_paused, restore_capture_paused, supervisor, web,
+};
 
 /// Network discovery tool that monitors network interfaces and captures traffic
 #[derive(Parser, Debug)]
@@ -66,15 +53,173 @@ struct Args {
     /// Batch mode: import pcap and exit (don't start web server)
     #[arg(long, requires = "import")]
     batch: bool,
+
+    /// Address the web server listens on (defaults to WEB_BIND, or 127.0.0.1). The UI has
+    /// no login, so 0.0.0.0 opens it to everyone on the network
+    #[arg(long, value_name = "ADDR")]
+    bind: Option<IpAddr>,
+
+    /// Reverse proxies whose Remote-User and X-Forwarded-* headers are believed (defaults
+    /// to TRUSTED_PROXIES). Without one, callers are identified only by API token
+    #[arg(long, value_name = "ADDR", value_delimiter = ',')]
+    trusted_proxy: Vec<IpAddr>,
+
+    /// Advertise the web UI via mDNS/DNS-SD (only reachable with a non-loopback --bind)
+    #[arg(long)]
+    advertise: bool,
+
+    /// Config file (defaults to CONFIG_FILE, or config.toml when present)
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+
+    /// Serve only the web UI, read-only, from a database another instance is capturing into
+    #[arg(long, conflicts_with_all = ["import", "interface", "all"])]
+    read_replica: bool,
+
+    /// Database file (defaults to DATABASE_URL, or one named after the monitored interface)
+    #[arg(long, value_name = "FILE", global = true)]
+    database: Option<String>,
+
+    /// Run a one-shot command against the database instead of monitoring
+    #[command(subcommand)]
+    command: Option<cli::Command>,
 }
 
-// process_pcap_file is now in the pcap module
-use crate::pcap::process_pcap_file;
+/// Whether to advertise the dashboard over mDNS (CLI flag, or MDNS_ADVERTISE=1/true)
+fn advertise_enabled(args: &Args) -> bool {
+    args.advertise || env_flag("MDNS_ADVERTISE")
+}
+
+/// Web server listen address: the CLI flag, then WEB_BIND, then localhost only
+fn web_bind(args: &Args) -> IpAddr {
+    args.bind
+        .or_else(|| env::var("WEB_BIND").ok().and_then(|a| a.parse().ok()))
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// Proxies trusted to name the caller: the CLI flag, then TRUSTED_PROXIES. Entries that
+/// aren't addresses are reported and skipped.
+fn trusted_proxies(args: &Args) -> Vec<IpAddr> {
+    if !args.trusted_proxy.is_empty() {
+        return args.trusted_proxy.clone();
+    }
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .filter_map(|p| match p.parse() {
+            Ok(addr) => Some(addr),
+            Err(_) => {
+                eprintln!("Ignoring trusted proxy '{}': not an IP address", p);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Whether to run as a read-only web replica (CLI flag, or READ_REPLICA=1/true)
+fn read_replica_enabled(args: &Args) -> bool {
+    args.read_replica || env_flag("READ_REPLICA")
+}
+
+fn env_flag(var: &str) -> bool {
+    env::var(var)
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Web server port: the CLI flag when given, then WEB_PORT, then the default
+fn web_port(args: &Args) -> u16 {
+    if args.port != 8080 {
+        args.port
+    } else {
+        env::var("WEB_PORT")
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok())
+            .unwrap_or(args.port)
+    }
+}
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
+    health::init();
     let args = Args::parse();
 
+    if let Some(ref database) = args.database {
+        // SAFETY: This is called during single-threaded startup before any other
+        // threads access DATABASE_URL
+        unsafe { env::set_var("DATABASE_URL", database) };
+    }
+
+    // Config file values go under environment variables and settings changed in the UI;
+    // flags given here still win
+    let mut command_line = Vec::new();
+    if let Some(ref interfaces) = args.interface {
+        command_line.push(("capture.interfaces", interfaces.join(",")));
+    }
+    if args.port != 8080 {
+        command_line.push(("web.port", args.port.to_string()));
+    }
+    if let Some(bind) = args.bind {
+        command_line.push(("web.bind", bind.to_string()));
+    }
+    if !args.trusted_proxy.is_empty() {
+        let proxies: Vec<String> = args.trusted_proxy.iter().map(IpAddr::to_string).collect();
+        command_line.push(("web.trusted_proxies", proxies.join(",")));
+    }
+    if args.advertise {
+        command_line.push(("web.advertise", "true".to_string()));
+    }
+    if args.read_replica {
+        command_line.push(("web.read_replica", "true".to_string()));
+    }
+    if let Err(e) = config::load(args.config.as_deref(), &command_line) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    // Read once the config file has been applied, before fields are moved out of `args`
+    let port = web_port(&args);
+    let bind = web_bind(&args);
+    let advertise = advertise_enabled(&args);
+    web::set_trusted_proxies(trusted_proxies(&args));
+
+    // Companion subcommands work on an existing database, so one has to be named
+    if let Some(command) = args.command {
+        if env::var("DATABASE_URL").is_err() {
+            eprintln!("No database given: use --database <FILE> or set DATABASE_URL");
+            std::process::exit(2);
+        }
+        if let Err(e) = cli::run(command).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // A replica serves the web UI from another instance's database and captures nothing
+    if read_replica_enabled(&args) {
+        let Ok(database) = env::var("DATABASE_URL") else {
+            eprintln!(
+                "A read replica needs the capture database: use --database <FILE> or set DATABASE_URL"
+            );
+            std::process::exit(2);
+        };
+        db::set_read_replica(true);
+        if let Err(e) = db::new_connection_result() {
+            eprintln!("Cannot open {} read-only: {}", database, e);
+            std::process::exit(1);
+        }
+        println!("Serving {} as a read-only replica", database);
+        web::start(port, bind, advertise);
+
+        // Keep main thread alive indefinitely (Ctrl+C will exit)
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        }
+    }
+
     // Handle pcap import mode
     if let Some(ref pcap_files) = args.import {
         println!("Running in pcap import mode");
@@ -83,7 +228,17 @@ async fn main() -> io::Result<()> {
 
         let mut total_packets = 0;
         for pcap_file in pcap_files {
-            match process_pcap_file(pcap_file, args.label.clone(), &sql_writer.sender) {
+            let result = process_pcap_file(pcap_file, args.label.clone(), &sql_writer.sender);
+            db::record_activity_event(
+                "pcap_imported",
+                serde_json::json!({
+                    "file": pcap_file,
+                    "label": args.label,
+                    "packets": result.as_ref().ok(),
+                    "error": result.as_ref().err().map(|e| e.to_string()),
+                }),
+            );
+            match result {
                 Ok(count) => total_packets += count,
                 Err(e) => {
                     eprintln!("Error processing {}: {}", pcap_file, e);
@@ -109,16 +264,8 @@ async fn main() -> io::Result<()> {
 
         // Otherwise, start web server for analysis
         println!("\nStarting web server for analysis...");
-        let web_port = if args.port != 8080 {
-            args.port
-        } else {
-            env::var("WEB_PORT")
-                .ok()
-                .and_then(|p| p.parse::<u16>().ok())
-                .unwrap_or(args.port)
-        };
-
-        web::start(web_port);
+        web::start(port, bind, advertise);
+        control::start();
 
         // Keep main thread alive indefinitely (Ctrl+C will exit)
         loop {
@@ -374,19 +521,15 @@ async fn main() -> io::Result<()> {
 
     MDnsLookup::start_daemon();
 
-    // Check for port from CLI args, then env variable, then default
-    let web_port = if args.port != 8080 {
-        // CLI arg was explicitly set (not default)
-        args.port
-    } else {
-        // Try env variable, fall back to CLI default (8080)
-        env::var("WEB_PORT")
-            .ok()
-            .and_then(|p| p.parse::<u16>().ok())
-            .unwrap_or(args.port)
-    };
+    // Pause state from the last run, then any scheduled capture windows
+    restore_capture_paused();
+    start_capture_scheduler();
+
+    // Devices from the OS ARP cache and routing table, even without capture privileges
+    start_host_table_collector();
 
-    web::start(web_port);
+    web::start(port, bind, advertise);
+    control::start();
 
     // Warn on Windows if monitoring multiple interfaces (unless explicitly requested with --all)
     #[cfg(target_os = "windows")]
@@ -402,23 +545,45 @@ async fn main() -> io::Result<()> {
 
     for interface in filtered_interfaces.into_iter() {
         let sender = sql_writer.sender.clone();
-        task::spawn_blocking(move || capture_packets(interface, sender));
+        supervisor::spawn_supervised_blocking(format!("capture:{}", interface.name), move || {
+            db::record_activity_event(
+                "capture_started",
+                serde_json::json!({ "interface": interface.name }),
+            );
+            let result = capture_packets(&interface, &sender);
+            db::record_activity_event(
+                "capture_stopped",
+                serde_json::json!({
+                    "interface": interface.name,
+                    "error": result.as_ref().err(),
+                }),
+            );
+            result
+        });
     }
 
+    // Under systemd, report startup and keep the watchdog fed while healthy
+    health::start_systemd_watchdog();
+
     // Keep main thread alive indefinitely (Ctrl+C will exit)
     loop {
         tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
     }
 }
 
+/// Read errors in a row after which capture gives up and lets the supervisor restart it
+const MAX_CONSECUTIVE_READ_ERRORS: u32 = 100;
+
+/// Capture packets on one interface until the writer shuts down. Errors are returned so
+/// the supervisor can restart capture; `Ok` means there is nothing to restart.
 fn capture_packets(
-    interface: NetworkInterface,
-    sender: tokio::sync::mpsc::Sender<Communication>,
-) -> io::Result<()> {
+    interface: &NetworkInterface,
+    sender: &tokio::sync::mpsc::Sender<Communication>,
+) -> Result<(), String> {
     println!("Starting packet capture on interface: {}", interface.name);
 
     // Create a new channel, dealing with layer 2 packets
-    let (_tx, mut rx) = match datalink::channel(&interface, Default::default()) {
+    let (_tx, mut rx) = match datalink::channel(interface, Default::default()) {
         Ok(Ethernet(tx, rx)) => (tx, rx),
         Ok(_) => {
             eprintln!("Unsupported channel type for interface: {}", interface.name);
@@ -430,201 +595,60 @@ fn capture_packets(
                 interface.name, e
             );
             eprintln!("Hint: Try running with sudo/administrator privileges");
-            return Ok(()); // Skip this interface
+            return Err(format!("failed to open capture channel: {}", e));
         }
     };
 
+    // Monitor-mode interfaces deliver 802.11 frames behind a radiotap header
+    let radiotap = is_radiotap_interface(&interface.name);
+    if radiotap {
+        println!(
+            "Interface {} is in monitor mode - recording wireless signal metadata",
+            interface.name
+        );
+    }
+
+    let mut tcp_quality = TcpQualityTracker::default();
+    let mut read_errors = 0;
     loop {
         match rx.next() {
             Ok(packet) => {
+                read_errors = 0;
+
                 // Skip processing if capture is paused (allows pcap playback without interference)
-                if is_capture_paused() {
+                // or outside its scheduled windows
+                if is_capture_paused() || is_capture_scheduled_off() {
                     continue;
                 }
 
-                // Parse ethernet packet
-                let ethernet_packet = match EthernetPacket::new(packet) {
-                    Some(pkt) => pkt,
-                    None => {
-                        // Malformed packet, skip it
-                        continue;
-                    }
+                let communication = if radiotap {
+                    Communication::new_radiotap(packet, None)
+                } else {
+                    // Parse ethernet packet
+                    EthernetPacket::new(packet).map(|ethernet_packet| {
+                        let quality = tcp_quality
+                            .observe(&ethernet_packet, chrono::Utc::now().timestamp_micros());
+                        Communication::new(ethernet_packet).with_tcp_quality(quality)
+                    })
+                };
+                let Some(communication) = communication else {
+                    // Malformed packet, skip it
+                    continue;
                 };
 
-                let communication: Communication = Communication::new(ethernet_packet);
-                if let Err(e) = sender.blocking_send(communication) {
+                if let Err(e) = sender.blocking_send(communication.with_interface(&interface.name))
+                {
                     eprintln!("Failed to send communication to SQL writer: {}", e);
-                    break; // Channel closed, exit loop
+                    return Ok(()); // Channel closed, nothing to capture for
                 }
             }
             Err(e) => {
                 eprintln!("Error reading packet on {}: {}", interface.name, e);
+                read_errors += 1;
+                if read_errors >= MAX_CONSECUTIVE_READ_ERRORS {
+                    return Err(format!("{} read errors in a row, last: {}", read_errors, e));
+                }
             }
         }
     }
-    Ok(())
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::db::new_test_connection;
-    use crate::test_utils::{PacketBuilder, create_test_pcap};
-    use pnet::packet::ethernet::EthernetPacket;
-
-    #[test]
-    fn test_communication_from_synthetic_packet() {
-        let packet_data = PacketBuilder::https_packet("192.168.1.100", "1.1.1.1");
-        let eth_packet = EthernetPacket::new(&packet_data).unwrap();
-
-        let comm = Communication::new(eth_packet);
-
-        assert_eq!(comm.source_ip, Some("192.168.1.100".to_string()));
-        assert_eq!(comm.destination_ip, Some("1.1.1.1".to_string()));
-        assert_eq!(comm.destination_port, Some(443));
-        assert_eq!(comm.ip_header_protocol, Some("Tcp".to_string()));
-    }
-
-    #[test]
-    fn test_communication_insertion_to_db() {
-        let conn = new_test_connection();
-        // Use loopback IPs which are always considered local
-        let packet_data = PacketBuilder::https_packet("127.0.0.2", "127.0.0.3");
-        let eth_packet = EthernetPacket::new(&packet_data).unwrap();
-
-        let comm = Communication::new_with_source(eth_packet, Some("test".to_string()));
-        let result = comm.insert_communication(&conn);
-
-        assert!(result.is_ok());
-
-        // Verify communication was inserted
-        let count: i64 = conn
-            .query_row("SELECT COUNT(*) FROM communications", [], |row| row.get(0))
-            .unwrap();
-        assert_eq!(count, 1);
-
-        // Verify source was stored
-        let source: String = conn
-            .query_row(
-                "SELECT source FROM communications WHERE source IS NOT NULL",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert_eq!(source, "test");
-    }
-
-    #[test]
-    fn test_pcap_file_creation_and_reading() {
-        // Create synthetic packets
-        let packets = vec![
-            PacketBuilder::https_packet("192.168.1.100", "1.1.1.1"),
-            PacketBuilder::http_packet("192.168.1.100", "8.8.8.8"),
-            PacketBuilder::dns_packet("192.168.1.100", "8.8.4.4"),
-        ];
-
-        // Create pcap file
-        let pcap_file = create_test_pcap(packets.clone()).unwrap();
-        let path = pcap_file.path().to_str().unwrap();
-
-        // Verify file exists
-        assert!(std::path::Path::new(path).exists());
-
-        // Read it back using pcap crate
-        let mut cap = ::pcap::Capture::from_file(path).unwrap();
-
-        let mut packet_count = 0;
-        while cap.next_packet().is_ok() {
-            packet_count += 1;
-        }
-
-        assert_eq!(packet_count, 3);
-    }
-
-    #[tokio::test]
-    async fn test_integration_pcap_import_to_db() {
-        // Create synthetic packets representing different types of traffic
-        let packets = vec![
-            PacketBuilder::https_packet("192.168.1.100", "1.1.1.1"), // Cloudflare
-            PacketBuilder::https_packet("192.168.1.100", "8.8.8.8"), // Google DNS
-            PacketBuilder::http_packet("192.168.1.100", "142.250.185.46"), // Google
-            PacketBuilder::dns_packet("192.168.1.100", "8.8.4.4"),   // Google DNS
-            PacketBuilder::https_packet("192.168.1.101", "1.1.1.1"), // Different local IP
-        ];
-
-        // Create pcap file
-        let pcap_file = create_test_pcap(packets).unwrap();
-        let path = pcap_file.path().to_str().unwrap().to_string();
-
-        // Use in-memory DB for testing
-        unsafe {
-            std::env::set_var("DATABASE_URL", ":memory:");
-        }
-
-        // Create SQL writer
-        let sql_writer = db::SQLWriter::new().await;
-
-        // Process the pcap file in a blocking task
-        let sender = sql_writer.sender.clone();
-        let result = tokio::task::spawn_blocking(move || {
-            process_pcap_file(&path, Some("integration_test".to_string()), &sender)
-        })
-        .await
-        .unwrap();
-
-        assert!(result.is_ok());
-        let packet_count = result.unwrap();
-        assert_eq!(packet_count, 5);
-
-        // Give the SQL writer time to process
-        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-
-        // Verify data in database (Note: won't work with different in-memory DB)
-        // This test verifies that pcap files can be processed without errors
-        println!(
-            "Successfully processed {} packets from pcap file",
-            packet_count
-        );
-    }
-
-    #[test]
-    fn test_different_protocol_packets() {
-        let conn = new_test_connection();
-
-        // Test TCP packet (use unicast MACs - LSB of first octet must be 0)
-        // Use loopback IPs which are always considered local
-        let tcp_packet = PacketBuilder::tcp_packet(
-            "aa:bb:cc:dd:ee:ff", // 0xAA = 0b10101010, LSB=0, unicast
-            "00:22:33:44:55:66", // 0x00 = 0b00000000, LSB=0, unicast
-            "127.0.0.2",
-            "127.0.0.3",
-            12345,
-            443,
-        );
-        let eth = EthernetPacket::new(&tcp_packet).unwrap();
-        let comm_tcp = Communication::new(eth);
-        assert_eq!(comm_tcp.ip_header_protocol, Some("Tcp".to_string()));
-        assert!(comm_tcp.insert_communication(&conn).is_ok());
-
-        // Test UDP packet
-        let udp_packet = PacketBuilder::udp_packet(
-            "aa:bb:cc:dd:ee:ff",
-            "00:22:33:44:55:66",
-            "127.0.0.2",
-            "127.0.0.3",
-            54321,
-            53,
-        );
-        let eth = EthernetPacket::new(&udp_packet).unwrap();
-        let comm_udp = Communication::new(eth);
-        assert_eq!(comm_udp.ip_header_protocol, Some("Udp".to_string()));
-        assert!(comm_udp.insert_communication(&conn).is_ok());
-
-        // Verify both were inserted
-        let count: i64 = conn
-            .query_row("SELECT COUNT(*) FROM communications", [], |row| row.get(0))
-            .unwrap();
-
-        assert_eq!(count, 2);
-    }
 }
@@ -0,0 +1,545 @@
+//! Destination blocklists. Domains and IP addresses (or CIDR ranges) of known trackers and
+//! malware come from user-imported lists, plus a small bundled list when the
+//! `blocklist_bundled_enabled` setting is on. Internet destinations and DNS lookups seen in
+//! capture are matched against them; the first hit per endpoint and entry raises a
+//! notification and every hit is tallied in `blocklist_hits`.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use ipnetwork::IpNetwork;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::db::{insert_notification_with_endpoint_id, insert_notification_with_severity};
+
+/// Name of the bundled list in matches and notifications
+pub const BUNDLED_LIST_NAME: &str = "bundled";
+
+/// Bundled entries: (domain, category). Deliberately short; import a full list for
+/// real coverage.
+const BUNDLED_ENTRIES: &[(&str, &str)] = &[
+    ("doubleclick.net", "tracker"),
+    ("google-analytics.com", "tracker"),
+    ("scorecardresearch.com", "tracker"),
+    ("quantserve.com", "tracker"),
+    ("adnxs.com", "tracker"),
+    ("criteo.com", "tracker"),
+    ("taboola.com", "tracker"),
+    ("outbrain.com", "tracker"),
+    ("hotjar.com", "tracker"),
+    ("mixpanel.com", "tracker"),
+    ("app-measurement.com", "tracker"),
+    ("samsungads.com", "tracker"),
+    ("ads.roku.com", "tracker"),
+    ("coinhive.com", "malware"),
+    ("coin-hive.com", "malware"),
+    ("testsafebrowsing.appspot.com", "malware"),
+];
+
+/// How long the in-memory lists are used before being reloaded, so setting changes and
+/// lists imported by another process are picked up
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The list and category an entry came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlocklistSource {
+    pub list_name: String,
+    pub category: String,
+}
+
+/// A destination that matched a blocklist entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlocklistMatch {
+    /// The entry as listed (a parent domain of the host, or a network)
+    pub entry: String,
+    pub source: BlocklistSource,
+}
+
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    domains: HashMap<String, BlocklistSource>,
+    /// Networks with the entry they were listed as
+    networks: Vec<(IpNetwork, String, BlocklistSource)>,
+}
+
+impl Blocklist {
+    fn add(&mut self, entry: &str, source: BlocklistSource) {
+        match entry.parse::<IpNetwork>() {
+            Ok(network) => self.networks.push((network, entry.to_string(), source)),
+            Err(_) => {
+                self.domains
+                    .entry(entry.trim_end_matches('.').to_lowercase())
+                    .or_insert(source);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty() && self.networks.is_empty()
+    }
+
+    /// Match a host name against the listed domains, most specific first. Subdomains of
+    /// a listed domain match.
+    pub fn match_domain(&self, host: &str) -> Option<BlocklistMatch> {
+        let host = host.trim_end_matches('.').to_lowercase();
+        let mut domain = host.as_str();
+        loop {
+            if let Some(source) = self.domains.get(domain) {
+                return Some(BlocklistMatch {
+                    entry: domain.to_string(),
+                    source: source.clone(),
+                });
+            }
+            domain = domain.split_once('.')?.1;
+        }
+    }
+
+    pub fn match_ip(&self, ip: &str) -> Option<BlocklistMatch> {
+        let ip: IpAddr = ip.parse().ok()?;
+        self.networks
+            .iter()
+            .find(|(network, _, _)| network.contains(ip))
+            .map(|(_, entry, source)| BlocklistMatch {
+                entry: entry.clone(),
+                source: source.clone(),
+            })
+    }
+}
+
+pub fn create_blocklist_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS blocklist_entries (
+            id INTEGER PRIMARY KEY,
+            list_name TEXT NOT NULL,
+            entry TEXT NOT NULL,
+            category TEXT NOT NULL,
+            created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+            UNIQUE(list_name, entry)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS blocklist_hits (
+            id INTEGER PRIMARY KEY,
+            endpoint_id INTEGER NOT NULL,
+            list_name TEXT NOT NULL,
+            entry TEXT NOT NULL,
+            category TEXT NOT NULL,
+            host TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            hit_count INTEGER NOT NULL DEFAULT 1,
+            UNIQUE(endpoint_id, list_name, entry)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Entries from a hosts file or a plain list: one domain, IP, or CIDR range per line.
+/// Hosts-file lines ("0.0.0.0 ads.example.com") contribute their host names; comments
+/// and blank lines are skipped.
+pub fn parse_blocklist(content: &str) -> Vec<String> {
+    let mut entries: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let names: &[&str] = match fields.as_slice() {
+            [] => continue,
+            [sink, hosts @ ..] if !hosts.is_empty() && sink.parse::<IpAddr>().is_ok() => hosts,
+            fields => &fields[..1],
+        };
+        for name in names {
+            let name = name.trim_end_matches('.').to_lowercase();
+            let is_network = name.parse::<IpNetwork>().is_ok();
+            let is_domain = name.contains('.')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+            if (is_network || is_domain) && name != "localhost" {
+                entries.push(name);
+            }
+        }
+    }
+    entries.sort();
+    entries.dedup();
+    entries
+}
+
+/// Replace a list's entries. Returns how many entries were stored.
+pub fn import_blocklist(
+    conn: &Connection,
+    list_name: &str,
+    category: &str,
+    entries: &[String],
+) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM blocklist_entries WHERE list_name = ?1",
+        [list_name],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR IGNORE INTO blocklist_entries (list_name, entry, category) VALUES (?1, ?2, ?3)",
+        )?;
+        for entry in entries {
+            stmt.execute(params![list_name, entry, category])?;
+        }
+    }
+    tx.commit()?;
+    invalidate_blocklist();
+    Ok(entries.len())
+}
+
+/// Remove an imported list. Returns false when there was no such list.
+pub fn delete_blocklist(conn: &Connection, list_name: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM blocklist_entries WHERE list_name = ?1",
+        [list_name],
+    )?;
+    invalidate_blocklist();
+    Ok(deleted > 0)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlocklistSummary {
+    pub list_name: String,
+    pub category: String,
+    pub entry_count: i64,
+    pub imported_at: i64,
+}
+
+/// Imported lists with their entry counts
+pub fn get_blocklists(conn: &Connection) -> Result<Vec<BlocklistSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT list_name, MIN(category), COUNT(*), MAX(created_at) FROM blocklist_entries
+         GROUP BY list_name ORDER BY list_name",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(BlocklistSummary {
+            list_name: row.get(0)?,
+            category: row.get(1)?,
+            entry_count: row.get(2)?,
+            imported_at: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Build the lists in effect from the database
+pub fn load_blocklist(conn: &Connection) -> Result<Blocklist> {
+    let mut blocklist = Blocklist::default();
+    let mut stmt = conn.prepare("SELECT list_name, entry, category FROM blocklist_entries")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (list_name, entry, category) = row?;
+        blocklist.add(
+            &entry,
+            BlocklistSource {
+                list_name,
+                category,
+            },
+        );
+    }
+
+    let bundled_enabled = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'blocklist_bundled_enabled'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+        .is_some_and(|value| value == "true");
+    if bundled_enabled {
+        for (entry, category) in BUNDLED_ENTRIES {
+            blocklist.add(
+                entry,
+                BlocklistSource {
+                    list_name: BUNDLED_LIST_NAME.to_string(),
+                    category: category.to_string(),
+                },
+            );
+        }
+    }
+    Ok(blocklist)
+}
+
+/// The loaded lists and when they were loaded
+type CachedBlocklist = Option<(Arc<Blocklist>, Instant)>;
+
+static BLOCKLIST: LazyLock<RwLock<CachedBlocklist>> = LazyLock::new(|| RwLock::new(None));
+
+/// Drop the cached lists so the next lookup reloads them
+pub fn invalidate_blocklist() {
+    if let Ok(mut cached) = BLOCKLIST.write() {
+        *cached = None;
+    }
+}
+
+/// The lists in effect, reloaded from the database at most once per RELOAD_INTERVAL
+fn current_blocklist(conn: &Connection) -> Arc<Blocklist> {
+    if let Ok(cached) = BLOCKLIST.read()
+        && let Some((blocklist, loaded_at)) = cached.as_ref()
+        && loaded_at.elapsed() < RELOAD_INTERVAL
+    {
+        return Arc::clone(blocklist);
+    }
+    let blocklist = Arc::new(load_blocklist(conn).unwrap_or_else(|e| {
+        eprintln!("Failed to load blocklists: {}", e);
+        Blocklist::default()
+    }));
+    if let Ok(mut cached) = BLOCKLIST.write() {
+        *cached = Some((Arc::clone(&blocklist), Instant::now()));
+    }
+    blocklist
+}
+
+/// Tally a hit, raising a notification the first time the endpoint hits the entry.
+/// Returns true for a first hit.
+pub fn record_blocklist_hit(
+    conn: &Connection,
+    endpoint_id: i64,
+    host: &str,
+    matched: &BlocklistMatch,
+    now: i64,
+) -> Result<bool> {
+    let first_seen_at: i64 = conn.query_row(
+        "INSERT INTO blocklist_hits
+            (endpoint_id, list_name, entry, category, host, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+         ON CONFLICT(endpoint_id, list_name, entry) DO UPDATE SET
+            host = excluded.host,
+            last_seen_at = excluded.last_seen_at,
+            hit_count = hit_count + 1
+         RETURNING first_seen_at",
+        params![
+            endpoint_id,
+            matched.source.list_name,
+            matched.entry,
+            matched.source.category,
+            host,
+            now
+        ],
+        |row| row.get(0),
+    )?;
+    if first_seen_at != now {
+        return Ok(false);
+    }
+
+    let endpoint_name: Option<String> = conn
+        .query_row(
+            "SELECT display_name FROM endpoints WHERE id = ?1",
+            [endpoint_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let title = format!(
+        "{} contacted {} ({})",
+        endpoint_name.as_deref().unwrap_or("An endpoint"),
+        host,
+        matched.source.category
+    );
+    let details = format!(
+        "Matched {} on blocklist '{}'",
+        matched.entry, matched.source.list_name
+    );
+    if matched.source.category == "malware" {
+        insert_notification_with_severity(
+            conn,
+            "blocklist_match",
+            "critical",
+            &title,
+            Some(&details),
+            endpoint_name.as_deref(),
+            Some(endpoint_id),
+        );
+    } else {
+        insert_notification_with_endpoint_id(
+            conn,
+            "blocklist_match",
+            &title,
+            Some(&details),
+            endpoint_name.as_deref(),
+            Some(endpoint_id),
+        );
+    }
+    Ok(true)
+}
+
+/// Match an endpoint's internet destination (or DNS lookup) by name and address, and
+/// record any hit
+pub fn check_destination(
+    conn: &Connection,
+    endpoint_id: i64,
+    host: Option<&str>,
+    ip: Option<&str>,
+    now: i64,
+) -> Result<()> {
+    let blocklist = current_blocklist(conn);
+    if blocklist.is_empty() {
+        return Ok(());
+    }
+    let hit = host
+        .and_then(|host| blocklist.match_domain(host))
+        .or_else(|| ip.and_then(|ip| blocklist.match_ip(ip)));
+    if let Some(matched) = hit {
+        let target = host.or(ip).unwrap_or(&matched.entry);
+        record_blocklist_hit(conn, endpoint_id, target, &matched, now)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlocklistHit {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    pub list_name: String,
+    pub entry: String,
+    pub category: String,
+    /// The host or address last seen matching the entry
+    pub host: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+    pub hit_count: i64,
+}
+
+/// Recorded hits, most recent first
+pub fn get_blocklist_hits(conn: &Connection, limit: i64) -> Result<Vec<BlocklistHit>> {
+    let mut stmt = conn.prepare(
+        "SELECT h.endpoint_id, e.display_name, h.list_name, h.entry, h.category, h.host,
+                h.first_seen_at, h.last_seen_at, h.hit_count
+         FROM blocklist_hits h
+         LEFT JOIN endpoints e ON e.id = h.endpoint_id
+         ORDER BY h.last_seen_at DESC
+         LIMIT ?1",
+    )?;
+    let rows = stmt.query_map([limit], |row| {
+        Ok(BlocklistHit {
+            endpoint_id: row.get(0)?,
+            endpoint_name: row.get(1)?,
+            list_name: row.get(2)?,
+            entry: row.get(3)?,
+            category: row.get(4)?,
+            host: row.get(5)?,
+            first_seen_at: row.get(6)?,
+            last_seen_at: row.get(7)?,
+            hit_count: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Move an endpoint's hits to the endpoint it is merged into
+pub fn reassign_blocklist_hits(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE blocklist_hits SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM blocklist_hits WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_parse_blocklist() {
+        let content = "# Tracker list\n\
+                       0.0.0.0 ads.example.com tracker.example.net # inline comment\n\
+                       127.0.0.1 localhost\n\
+                       Malware.Example.org.\n\
+                       203.0.113.0/24\n\
+                       198.51.100.7\n\
+                       not a domain\n";
+        assert_eq!(
+            parse_blocklist(content),
+            vec![
+                "198.51.100.7",
+                "203.0.113.0/24",
+                "ads.example.com",
+                "malware.example.org",
+                "tracker.example.net",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocklist_matching_and_hits() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'camera');",
+        )
+        .unwrap();
+        import_blocklist(
+            &conn,
+            "malware-feed",
+            "malware",
+            &parse_blocklist("evil.example.com\n203.0.113.0/24"),
+        )
+        .unwrap();
+
+        let blocklist = load_blocklist(&conn).unwrap();
+        let matched = blocklist.match_domain("cdn.EVIL.example.com.").unwrap();
+        assert_eq!(matched.entry, "evil.example.com");
+        assert_eq!(matched.source.list_name, "malware-feed");
+        assert!(blocklist.match_domain("example.com").is_none());
+        assert!(blocklist.match_domain("notevil.example.com").is_none());
+        assert_eq!(
+            blocklist.match_ip("203.0.113.9").unwrap().entry,
+            "203.0.113.0/24"
+        );
+        assert!(blocklist.match_ip("198.51.100.1").is_none());
+        // The bundled list is opt-in
+        assert!(blocklist.match_domain("doubleclick.net").is_none());
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('blocklist_bundled_enabled', 'true')",
+            [],
+        )
+        .unwrap();
+        assert!(
+            load_blocklist(&conn)
+                .unwrap()
+                .match_domain("stats.g.doubleclick.net")
+                .is_some()
+        );
+
+        assert!(record_blocklist_hit(&conn, 1, "cdn.evil.example.com", &matched, 100).unwrap());
+        assert!(!record_blocklist_hit(&conn, 1, "evil.example.com", &matched, 200).unwrap());
+        let hits = get_blocklist_hits(&conn, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            (hits[0].hit_count, hits[0].host.as_str()),
+            (2, "evil.example.com")
+        );
+        let severity: String = conn
+            .query_row(
+                "SELECT severity FROM notifications WHERE event_type = 'blocklist_match'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(severity, "critical");
+
+        assert_eq!(get_blocklists(&conn).unwrap()[0].entry_count, 2);
+        assert!(delete_blocklist(&conn, "malware-feed").unwrap());
+        assert!(!delete_blocklist(&conn, "malware-feed").unwrap());
+    }
+}
@@ -0,0 +1,279 @@
+//! Broadcast and multicast traffic. Packets addressed to a group (a broadcast or multicast
+//! MAC or IP) have no destination endpoint, so rather than communications they are tallied
+//! per sender, group address and protocol in `broadcast_traffic`. Discovery chatter (mDNS,
+//! SSDP, LLMNR, NetBIOS, WS-Discovery, ARP, IGMP) is classed as noise, which the graph and
+//! top-talker views can exclude.
+
+use rusqlite::{Connection, Result, params};
+use serde::Serialize;
+
+use super::communication::Communication;
+
+/// Ports of discovery/announcement protocols (NetBIOS-NS/DG, SSDP, WS-Discovery, mDNS, LLMNR)
+const NOISE_PORTS: &[u16] = &[137, 138, 1900, 3702, 5353, 5355];
+
+/// Header protocols that are pure link-level chatter
+const NOISE_HEADER_PROTOCOLS: &[&str] = &["Arp", "Igmp"];
+
+/// How a packet is addressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CastType {
+    #[default]
+    Unicast,
+    Broadcast,
+    Multicast,
+}
+
+impl CastType {
+    /// Classify a packet from its destination MAC and IP
+    pub fn classify(destination_mac: Option<&str>, destination_ip: Option<&str>) -> Self {
+        if let Some(mac) = destination_mac {
+            if mac.eq_ignore_ascii_case("ff:ff:ff:ff:ff:ff") {
+                return CastType::Broadcast;
+            }
+            // Group bit: least significant bit of the first octet
+            if mac
+                .split(':')
+                .next()
+                .and_then(|octet| u8::from_str_radix(octet, 16).ok())
+                .is_some_and(|octet| octet & 0x01 == 0x01)
+            {
+                return CastType::Multicast;
+            }
+        }
+
+        match destination_ip.and_then(|ip| ip.parse::<std::net::IpAddr>().ok()) {
+            Some(std::net::IpAddr::V4(ip)) if ip.is_broadcast() => CastType::Broadcast,
+            Some(ip) if ip.is_multicast() => CastType::Multicast,
+            _ => CastType::Unicast,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CastType::Unicast => "unicast",
+            CastType::Broadcast => "broadcast",
+            CastType::Multicast => "multicast",
+        }
+    }
+}
+
+/// SQL condition that is true for rows (aliased as `alias`) that are NOT noise
+pub fn not_noise_sql(alias: &str) -> String {
+    let ports = NOISE_PORTS
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let protocols = NOISE_HEADER_PROTOCOLS
+        .iter()
+        .map(|p| format!("'{}'", p))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "NOT (COALESCE({alias}.destination_port, 0) IN ({ports})
+              OR COALESCE({alias}.source_port, 0) IN ({ports})
+              OR COALESCE({alias}.ip_header_protocol, '') IN ({protocols}))"
+    )
+}
+
+pub fn create_broadcast_traffic_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS broadcast_traffic (
+            id INTEGER PRIMARY KEY,
+            src_endpoint_id INTEGER NOT NULL,
+            cast_type TEXT NOT NULL,
+            destination TEXT NOT NULL,
+            source_port INTEGER,
+            destination_port INTEGER,
+            ip_header_protocol TEXT,
+            sub_protocol TEXT,
+            interface TEXT,
+            created_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            packet_count INTEGER NOT NULL DEFAULT 0,
+            bytes INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_broadcast_traffic_unique ON broadcast_traffic (
+            src_endpoint_id,
+            destination,
+            COALESCE(destination_port, 0),
+            COALESCE(ip_header_protocol, '')
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_broadcast_traffic_last_seen ON broadcast_traffic (last_seen_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Add a group-addressed packet (or aggregated flow) from `src_endpoint_id` to the tally
+pub fn record_broadcast_traffic(
+    conn: &Connection,
+    src_endpoint_id: i64,
+    communication: &Communication,
+    now: i64,
+) -> Result<()> {
+    // Prefer the group IP; fall back to the MAC for non-IP traffic (e.g. ARP)
+    let destination = communication
+        .destination_ip
+        .as_deref()
+        .or(communication.destination_mac.as_deref())
+        .unwrap_or("unknown");
+    conn.execute(
+        "INSERT INTO broadcast_traffic (
+            src_endpoint_id, cast_type, destination, source_port, destination_port,
+            ip_header_protocol, sub_protocol, interface, created_at, last_seen_at,
+            packet_count, bytes
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9, ?10, ?11)
+        ON CONFLICT(src_endpoint_id, destination, COALESCE(destination_port, 0), COALESCE(ip_header_protocol, ''))
+        DO UPDATE SET
+            last_seen_at = ?9,
+            packet_count = packet_count + ?10,
+            bytes = bytes + ?11,
+            interface = COALESCE(excluded.interface, interface)",
+        params![
+            src_endpoint_id,
+            communication.cast_type.as_str(),
+            destination,
+            communication.source_port,
+            communication.destination_port,
+            communication.ip_header_protocol,
+            communication.sub_protocol,
+            communication.interface,
+            now,
+            communication.packet_count.max(1),
+            communication.packet_size,
+        ],
+    )?;
+    Ok(())
+}
+
+/// One sender's group-addressed traffic to one destination and protocol
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastTalker {
+    pub endpoint: String,
+    pub cast_type: String,
+    pub destination: String,
+    pub destination_port: Option<u16>,
+    pub protocol: Option<String>,
+    pub packets: i64,
+    pub bytes: i64,
+    pub last_seen_at: i64,
+    pub noise: bool,
+}
+
+/// Group-addressed traffic seen at or after `since`, busiest first
+pub fn get_broadcast_talkers(
+    conn: &Connection,
+    since: i64,
+    exclude_noise: bool,
+    limit: i64,
+) -> Result<Vec<BroadcastTalker>> {
+    let not_noise = not_noise_sql("b");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT e.display_name, b.cast_type, b.destination, b.destination_port,
+                COALESCE(NULLIF(b.sub_protocol, ''), b.ip_header_protocol),
+                b.packet_count, b.bytes, b.last_seen_at, NOT ({not_noise})
+         FROM broadcast_traffic b
+         JOIN endpoints e ON e.id = b.src_endpoint_id
+         WHERE b.last_seen_at >= ?1 AND e.display_name IS NOT NULL
+           AND (?2 = 0 OR {not_noise})
+         ORDER BY b.bytes DESC
+         LIMIT ?3"
+    ))?;
+    let rows = stmt.query_map(params![since, exclude_noise, limit], |row| {
+        Ok(BroadcastTalker {
+            endpoint: row.get(0)?,
+            cast_type: row.get(1)?,
+            destination: row.get(2)?,
+            destination_port: row.get(3)?,
+            protocol: row.get(4)?,
+            packets: row.get(5)?,
+            bytes: row.get(6)?,
+            last_seen_at: row.get(7)?,
+            noise: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_classify_cast_type() {
+        assert_eq!(
+            CastType::classify(Some("ff:ff:ff:ff:ff:ff"), Some("192.168.1.255")),
+            CastType::Broadcast
+        );
+        assert_eq!(
+            CastType::classify(Some("01:00:5e:00:00:fb"), Some("224.0.0.251")),
+            CastType::Multicast
+        );
+        assert_eq!(
+            CastType::classify(Some("33:33:00:00:00:fb"), Some("ff02::fb")),
+            CastType::Multicast
+        );
+        assert_eq!(
+            CastType::classify(None, Some("255.255.255.255")),
+            CastType::Broadcast
+        );
+        assert_eq!(
+            CastType::classify(Some("aa:bb:cc:dd:ee:01"), Some("192.168.1.10")),
+            CastType::Unicast
+        );
+    }
+
+    #[test]
+    fn test_broadcast_traffic_tally_and_noise_filter() {
+        let conn = new_test_connection();
+        create_broadcast_traffic_table(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'printer')",
+            [],
+        )
+        .unwrap();
+
+        let mut mdns = Communication::default();
+        mdns.destination_ip = Some("224.0.0.251".to_string());
+        mdns.destination_port = Some(5353);
+        mdns.ip_header_protocol = Some("Udp".to_string());
+        mdns.cast_type = CastType::Multicast;
+        mdns.packet_size = 100;
+        mdns.packet_count = 1;
+
+        let mut game = Communication::default();
+        game.destination_ip = Some("255.255.255.255".to_string());
+        game.destination_port = Some(27036);
+        game.ip_header_protocol = Some("Udp".to_string());
+        game.cast_type = CastType::Broadcast;
+        game.packet_size = 60;
+        game.packet_count = 1;
+
+        record_broadcast_traffic(&conn, 1, &mdns, 10).unwrap();
+        record_broadcast_traffic(&conn, 1, &mdns, 20).unwrap();
+        record_broadcast_traffic(&conn, 1, &game, 20).unwrap();
+
+        let all = get_broadcast_talkers(&conn, 0, false, 10).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(
+            (all[0].destination.as_str(), all[0].packets, all[0].bytes),
+            ("224.0.0.251", 2, 200)
+        );
+        assert!(all[0].noise);
+        assert_eq!(all[0].cast_type, "multicast");
+
+        let filtered = get_broadcast_talkers(&conn, 0, true, 10).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].destination, "255.255.255.255");
+        assert!(!filtered[0].noise);
+    }
+}
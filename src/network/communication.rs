@@ -5,32 +5,55 @@ use pnet::packet::Packet;
 use pnet::packet::ethernet::EthernetPacket;
 use rusqlite::{Connection, Result, params};
 
-use crate::db::insert_notification_with_endpoint_id;
+use crate::db::{
+    DriftSource, check_port_policy, check_type_baseline, destination_type,
+    insert_notification_with_endpoint_id, mark_endpoint_summary_dirty, record_endpoint_activity,
+    record_tcp_quality, record_usage, record_wan_bytes, served_port,
+};
 use crate::network::{
-    endpoint::{EndPoint, EndpointData, InsertEndpointError, get_mac_vendor, get_model_from_mac},
+    blocklist::check_destination,
+    broadcast::{CastType, record_broadcast_traffic},
+    direction::TrafficDirection,
+    dns_audit::{DnsServerQuery, dns_server_query, record_dns_server_query},
+    eapol::{
+        AuthEvent, ClientCertificate, auth_event_from_80211, auth_event_from_ethernet,
+        client_certificate_from_80211, client_certificate_from_ethernet, record_auth_events,
+        record_client_certificates,
+    },
+    endpoint::{
+        DhcpLease, EndPoint, EndpointData, InsertEndpointError, get_mac_vendor, get_model_from_mac,
+    },
+    endpoint_attribute::EndPointAttribute,
+    live_tail::{LiveFlow, has_live_subscribers, publish_live_flow},
+    name_poisoning::{NameResponse, name_responses_from_packet, process_name_responses},
     packet_wrapper::PacketWrapper,
+    passive_dns::{DnsAnswer, dns_answers_from_packet, lookup_dns_hostname, record_dns_answers},
+    privacy::truncate_ip,
+    router_advertisement::{
+        RouterAdvertisement, parse_router_advertisement, process_router_advertisement,
+    },
+    tcp_quality::TcpQuality,
+    wireless::{RadioSignal, ieee80211_frame, parse_radiotap_frame, record_wireless_signal},
 };
 
-/// Parse DHCP options from payload
-/// Returns (Option 61: Client ID, Option 60: Vendor Class, Option 12: Hostname)
-fn parse_dhcp_options(payload: &[u8]) -> (Option<String>, Option<String>, Option<String>) {
+/// Walk the options (TLV format) of a DHCP packet, returning (code, value) pairs.
+/// Empty for payloads that aren't DHCP.
+fn dhcp_options(payload: &[u8]) -> Vec<(u8, &[u8])> {
     // DHCP packet structure:
     // - Bytes 0-235: Fixed header
     // - Bytes 236-239: Magic cookie (0x63825363)
     // - Bytes 240+: Options (TLV format)
 
     if payload.len() < 244 {
-        return (None, None, None); // Too short for DHCP with options
+        return Vec::new(); // Too short for DHCP with options
     }
 
     // Verify magic cookie
     if payload[236..240] != [0x63, 0x82, 0x53, 0x63] {
-        return (None, None, None);
+        return Vec::new();
     }
 
-    let mut client_id = None;
-    let mut vendor_class = None;
-    let mut hostname = None;
+    let mut options = Vec::new();
 
     // Parse options starting at byte 240
     let mut offset = 240;
@@ -60,24 +83,37 @@ fn parse_dhcp_options(payload: &[u8]) -> (Option<String>, Option<String>, Option
             break;
         }
 
-        let option_data = &payload[offset + 2..offset + 2 + option_len];
+        options.push((option_type, &payload[offset + 2..offset + 2 + option_len]));
+        offset += 2 + option_len;
+    }
 
+    options
+}
+
+/// Parse DHCP options from payload
+/// Returns (Option 61: Client ID, Option 60: Vendor Class, Option 12: Hostname)
+fn parse_dhcp_options(payload: &[u8]) -> (Option<String>, Option<String>, Option<String>) {
+    let mut client_id = None;
+    let mut vendor_class = None;
+    let mut hostname = None;
+
+    for (option_type, option_data) in dhcp_options(payload) {
         match option_type {
             // Option 12: Hostname
-            12 if option_len > 0 => {
+            12 if !option_data.is_empty() => {
                 if let Ok(s) = std::str::from_utf8(option_data) {
                     hostname = Some(s.trim_end_matches('\0').to_string());
                 }
             }
             // Option 60: Vendor Class Identifier
             // Examples: "samsung:SM-G998B", "HP LaserJet Pro M404", "android-dhcp-13"
-            60 if option_len > 0 => {
+            60 if !option_data.is_empty() => {
                 if let Ok(s) = std::str::from_utf8(option_data) {
                     vendor_class = Some(s.trim_end_matches('\0').to_string());
                 }
             }
             // Option 61: Client Identifier
-            61 if option_len > 0 => {
+            61 if !option_data.is_empty() => {
                 // Convert to hex string for storage
                 let hex_string: String = option_data
                     .iter()
@@ -88,13 +124,66 @@ fn parse_dhcp_options(payload: &[u8]) -> (Option<String>, Option<String>, Option
             }
             _ => {}
         }
-
-        offset += 2 + option_len;
     }
 
     (client_id, vendor_class, hostname)
 }
 
+/// Parse a lease event from a DHCP packet: a DHCPACK binds the offered address (yiaddr)
+/// to the client's hardware address (chaddr), a DHCPRELEASE gives up the client's
+/// current address (ciaddr). Other message types carry no lease.
+fn parse_dhcp_lease(payload: &[u8]) -> Option<DhcpLease> {
+    let options = dhcp_options(payload);
+    let message_type = options
+        .iter()
+        .find(|(code, data)| *code == 53 && data.len() == 1)
+        .map(|(_, data)| data[0])?;
+
+    // Only Ethernet hardware addresses map to endpoints
+    if payload[1] != 1 || payload[2] != 6 {
+        return None;
+    }
+    let ipv4 = |offset: usize| {
+        std::net::Ipv4Addr::new(
+            payload[offset],
+            payload[offset + 1],
+            payload[offset + 2],
+            payload[offset + 3],
+        )
+    };
+    let (ip, released) = match message_type {
+        // DHCPACK
+        5 => (ipv4(16), false),
+        // DHCPRELEASE
+        7 => (ipv4(12), true),
+        _ => return None,
+    };
+    // An ACK to a DHCPINFORM assigns no address
+    if ip.is_unspecified() {
+        return None;
+    }
+
+    let mac = payload[28..34]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":");
+    // Option 51: IP Address Lease Time (seconds; all ones means infinite)
+    let lease_seconds = options
+        .iter()
+        .find(|(code, data)| *code == 51 && data.len() == 4)
+        .map(|(_, data)| u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+        .filter(|&seconds| seconds != u32::MAX)
+        .map(i64::from);
+
+    Some(DhcpLease {
+        mac,
+        ip: ip.to_string(),
+        lease_seconds,
+        released,
+    })
+}
+
 /// Extract model from DHCP Vendor Class Identifier (Option 60)
 /// Examples:
 /// - "samsung:SM-G998B" -> "SM-G998B" (Galaxy S21 Ultra)
@@ -177,7 +266,13 @@ pub struct Communication {
     pub ip_header_protocol: Option<String>,
     pub sub_protocol: Option<String>,
     pub source: Option<String>, // Source of the capture (e.g., "live", "capture.pcap", or custom label)
-    pub packet_size: u32,       // Size of the packet in bytes
+    pub packet_size: u32,       // Size of the packet in bytes (total bytes when aggregated)
+    // Number of packets this communication represents (more than 1 for aggregated flows)
+    pub packet_count: u32,
+    // Whether the packet was addressed to one host, the broadcast domain or a multicast group
+    pub cast_type: CastType,
+    // Network interface the packet was captured on (live capture only)
+    pub interface: Option<String>,
     // DHCP Client ID (Option 61) for tracking devices with randomized MACs
     pub dhcp_client_id: Option<String>,
     // DHCP Vendor Class (Option 60) for model identification (e.g., "samsung:SM-G998B")
@@ -186,6 +281,33 @@ pub struct Communication {
     pub dhcp_hostname: Option<String>,
     // Note: payload is used only for parsing hostnames (SNI/HTTP), not stored in DB
     payload: Vec<u8>,
+    // Address answers if this packet is a DNS response (stored in dns_entries)
+    dns_answers: Vec<DnsAnswer>,
+    // Names claimed if this packet is an LLMNR or NBNS response
+    name_responses: Vec<NameResponse>,
+    // EAPOL handshake steps and deauthentications, from wired 802.1X or monitor mode
+    auth_events: Vec<AuthEvent>,
+    // Client certificates sent in the clear during EAP-TLS logins
+    client_certificates: Vec<ClientCertificate>,
+    // Prefixes and DNS servers if this packet is an IPv6 router advertisement
+    router_advertisement: Option<RouterAdvertisement>,
+    // Addresses handed out or given up if this flow carried DHCP ACKs or RELEASEs
+    dhcp_leases: Vec<DhcpLease>,
+    // Handshake times and retransmissions seen by the capture's TCP tracker
+    tcp_quality: TcpQuality,
+    // Signal strength and channel if this is an 802.11 frame from a radiotap capture
+    radio_signal: Option<RadioSignal>,
+}
+
+/// Whether group-addressed traffic is kept (the `store_broadcast_traffic` setting, on by default)
+fn store_broadcast_traffic(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = 'store_broadcast_traffic'",
+        [],
+        |row| row.get::<_, String>(0),
+    )
+    .map(|v| v != "false" && v != "0")
+    .unwrap_or(true)
 }
 
 /// Emit vendor_identified / model_identified notifications from MAC OUI lookup
@@ -228,23 +350,71 @@ impl Communication {
         let packet_size = ethernet_packet.packet().len() as u32;
 
         let payload = packet_wrapper.get_payload().unwrap_or_default().to_vec();
+        let destination_mac = ethernet_packet.get_destination().to_string();
+        let destination_ip = packet_wrapper.get_destination_ip();
+        let cast_type = CastType::classify(Some(&destination_mac), destination_ip.as_deref());
+        let header_protocol = packet_wrapper.get_header_protocol();
+        let dns_answers = dns_answers_from_packet(
+            packet_wrapper.get_source_port(),
+            header_protocol.as_deref(),
+            &payload,
+        );
+        let name_responses = name_responses_from_packet(
+            packet_wrapper.get_source_port(),
+            header_protocol.as_deref(),
+            &payload,
+        );
+        let source_mac = ethernet_packet.get_source().to_string();
+        let auth_events = auth_event_from_ethernet(
+            ethernet_packet.get_ethertype().0,
+            &source_mac,
+            &destination_mac,
+            ethernet_packet.payload(),
+        )
+        .into_iter()
+        .collect();
+        let client_certificates = client_certificate_from_ethernet(
+            ethernet_packet.get_ethertype().0,
+            &source_mac,
+            &destination_mac,
+            ethernet_packet.payload(),
+        )
+        .into_iter()
+        .collect();
+        let router_advertisement = packet_wrapper
+            .get_icmpv6_message()
+            .zip(packet_wrapper.get_source_ip())
+            .and_then(|(message, source_ip)| {
+                parse_router_advertisement(&source_ip, Some(&source_mac), message)
+            });
 
         let mut communication = Communication {
-            source_mac: Some(ethernet_packet.get_source().to_string()),
-            destination_mac: Some(ethernet_packet.get_destination().to_string()),
+            source_mac: Some(source_mac),
+            destination_mac: Some(destination_mac),
             source_ip: packet_wrapper.get_source_ip(),
-            destination_ip: packet_wrapper.get_destination_ip(),
+            destination_ip,
             source_port: packet_wrapper.get_source_port(),
             destination_port: packet_wrapper.get_destination_port(),
             ip_version: packet_wrapper.get_ip_version(),
-            ip_header_protocol: packet_wrapper.get_header_protocol(),
+            ip_header_protocol: header_protocol,
             sub_protocol: None,
             source,
+            interface: None,
             packet_size,
+            packet_count: 1,
+            cast_type,
             dhcp_client_id: None,
             dhcp_vendor_class: None,
             dhcp_hostname: None,
             payload,
+            dns_answers,
+            name_responses,
+            auth_events,
+            client_certificates,
+            router_advertisement,
+            dhcp_leases: Vec::new(),
+            tcp_quality: TcpQuality::default(),
+            radio_signal: None,
         };
         if let Some(ip_header_protocol) = &communication.ip_header_protocol
             && (ip_header_protocol == "Tcp" || ip_header_protocol == "Udp")
@@ -281,6 +451,9 @@ impl Communication {
             communication.dhcp_client_id = client_id;
             communication.dhcp_vendor_class = vendor_class;
             communication.dhcp_hostname = hostname;
+            communication
+                .dhcp_leases
+                .extend(parse_dhcp_lease(&communication.payload));
         }
 
         // Clear MAC addresses for internet traffic to prevent grouping remote endpoints under gateway MAC
@@ -300,10 +473,275 @@ impl Communication {
         communication
     }
 
+    /// Build a communication from a monitor-mode frame (radiotap header and 802.11 frame).
+    /// Only the transmitter, receiver and radio details are known.
+    pub fn new_radiotap(frame: &[u8], source: Option<String>) -> Option<Self> {
+        let radio_frame = parse_radiotap_frame(frame)?;
+        Some(Communication {
+            cast_type: CastType::classify(Some(&radio_frame.receiver), None),
+            source_mac: Some(radio_frame.transmitter),
+            destination_mac: Some(radio_frame.receiver),
+            source,
+            packet_size: frame.len() as u32,
+            packet_count: 1,
+            radio_signal: Some(radio_frame.signal),
+            auth_events: ieee80211_frame(frame)
+                .and_then(auth_event_from_80211)
+                .into_iter()
+                .collect(),
+            client_certificates: ieee80211_frame(frame)
+                .and_then(client_certificate_from_80211)
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Tag this communication with the interface it was captured on
+    pub fn with_interface(mut self, interface: &str) -> Self {
+        self.interface = Some(interface.to_string());
+        self
+    }
+
+    /// Attach what the capture's TCP tracker observed for this packet
+    pub fn with_tcp_quality(mut self, tcp_quality: TcpQuality) -> Self {
+        self.tcp_quality = tcp_quality;
+        self
+    }
+
     pub fn get_payload(&self) -> &[u8] {
         &self.payload
     }
 
+    /// Strip what privacy mode doesn't keep: the payload (unread, so no SNI or HTTP
+    /// hostnames), DNS answers, and the host part of internet addresses
+    pub fn apply_privacy_mode(&mut self) {
+        self.payload = Vec::new();
+        self.dns_answers.clear();
+        for ip in [&mut self.source_ip, &mut self.destination_ip]
+            .into_iter()
+            .flatten()
+        {
+            if !EndPoint::is_on_local_network(ip) {
+                *ip = truncate_ip(ip);
+            }
+        }
+    }
+
+    /// Fold another packet of the same flow into this one: counts are summed, and
+    /// DHCP fields and the payload used for hostname parsing are kept from the first
+    /// packet that had them
+    pub fn absorb(&mut self, other: Communication) {
+        self.packet_count = self.packet_count.saturating_add(other.packet_count.max(1));
+        self.packet_size = self.packet_size.saturating_add(other.packet_size);
+        if self.source_port.is_none() {
+            self.source_port = other.source_port;
+        }
+        if self.dhcp_client_id.is_none() {
+            self.dhcp_client_id = other.dhcp_client_id;
+        }
+        if self.dhcp_vendor_class.is_none() {
+            self.dhcp_vendor_class = other.dhcp_vendor_class;
+        }
+        if self.dhcp_hostname.is_none() {
+            self.dhcp_hostname = other.dhcp_hostname;
+        }
+        if self.payload.is_empty() {
+            self.payload = other.payload;
+        }
+        self.dns_answers.extend(other.dns_answers);
+        self.name_responses.extend(other.name_responses);
+        self.auth_events.extend(other.auth_events);
+        self.client_certificates.extend(other.client_certificates);
+        if self.router_advertisement.is_none() {
+            self.router_advertisement = other.router_advertisement;
+        }
+        self.dhcp_leases.extend(other.dhcp_leases);
+        self.tcp_quality.absorb(other.tcp_quality);
+        match (&mut self.radio_signal, other.radio_signal) {
+            (Some(signal), Some(other)) => signal.absorb(other),
+            (None, other) => self.radio_signal = other,
+            _ => {}
+        }
+    }
+
+    /// The DNS server this flow queries, if it is DNS traffic. For HTTPS the SNI, or else
+    /// the name the client resolved the address from, tells DoH apart from other sites.
+    fn dns_server_query(&self, conn: &Connection) -> Option<DnsServerQuery> {
+        let destination_ip = self.destination_ip.as_deref()?;
+        let destination_port = self.destination_port?;
+        let server_name = if destination_port == 443 {
+            EndPoint::find_sni(self.get_payload())
+                .or_else(|| lookup_dns_hostname(conn, destination_ip))
+        } else {
+            None
+        };
+        dns_server_query(destination_ip, destination_port, server_name.as_deref())
+    }
+
+    /// Name of an internet host for usage reports: the TLS SNI of an outbound flow, else
+    /// the name a device resolved the address from, else the address itself
+    fn internet_host_name(&self, conn: &Connection, ip: &str, outbound: bool) -> String {
+        (outbound && self.destination_port == Some(443))
+            .then(|| EndPoint::find_sni(self.get_payload()))
+            .flatten()
+            .or_else(|| lookup_dns_hostname(conn, ip))
+            .unwrap_or_else(|| ip.to_string())
+    }
+
+    /// The already-known endpoint this packet is addressed to. Used for replies, which
+    /// shouldn't create endpoints.
+    fn existing_destination_endpoint(&self, conn: &Connection) -> Option<i64> {
+        EndPointAttribute::find_existing_endpoint_id_with_dhcp(
+            conn,
+            self.destination_mac.clone(),
+            self.destination_ip.clone(),
+            None,
+            None,
+        )
+    }
+
+    /// Add a flow from a local device to an internet host to the device's usage, and
+    /// match the host against the blocklists
+    fn record_outbound_internet(
+        &self,
+        conn: &Connection,
+        endpoint_id: i64,
+        ip: &str,
+        direction: TrafficDirection,
+    ) {
+        let host = self.internet_host_name(conn, ip, true);
+        let now = chrono::Utc::now().timestamp();
+        self.publish_live(
+            Some(endpoint_id),
+            None,
+            self.source_ip.as_deref(),
+            Some(&host),
+            now,
+        );
+        if let Err(e) = record_usage(
+            conn,
+            endpoint_id,
+            Some(&host),
+            self.packet_count,
+            self.packet_size,
+            now,
+        ) {
+            eprintln!("Failed to record usage: {}", e);
+        }
+        if let Err(e) = record_wan_bytes(conn, endpoint_id, direction, self.packet_size, now) {
+            eprintln!("Failed to record WAN bytes: {}", e);
+        }
+        if let Err(e) = record_tcp_quality(conn, endpoint_id, &self.tcp_quality, now) {
+            eprintln!("Failed to record TCP quality: {}", e);
+        }
+        if let Err(e) = check_destination(
+            conn,
+            endpoint_id,
+            (host != ip).then_some(host.as_str()),
+            Some(ip),
+            now,
+        ) {
+            eprintln!("Failed to check blocklists: {}", e);
+        }
+        self.check_baseline(conn, endpoint_id, direction, now);
+    }
+
+    /// Check a connection the endpoint opened against its device type's baseline. Replies
+    /// from a port it serves are left to the port policies.
+    fn check_baseline(
+        &self,
+        conn: &Connection,
+        endpoint_id: i64,
+        direction: TrafficDirection,
+        now: i64,
+    ) {
+        let (Some(source_port), Some(destination_port), Some(protocol)) = (
+            self.source_port,
+            self.destination_port,
+            self.ip_header_protocol.as_deref(),
+        ) else {
+            return;
+        };
+        if served_port(source_port, destination_port).is_some() {
+            return;
+        }
+        if let Err(e) = check_type_baseline(
+            conn,
+            endpoint_id,
+            destination_port,
+            protocol,
+            destination_type(direction),
+            now,
+        ) {
+            eprintln!("Failed to check type baseline: {}", e);
+        }
+    }
+
+    /// Add a flow from an internet host to the usage of the local device it reached.
+    /// Devices not seen yet are skipped rather than created from a reply.
+    fn record_inbound_usage(&self, conn: &Connection, direction: TrafficDirection) {
+        let Some(source_ip) = self.source_ip.as_deref() else {
+            return;
+        };
+        let Some(endpoint_id) = self.existing_destination_endpoint(conn) else {
+            return;
+        };
+        let host = self.internet_host_name(conn, source_ip, false);
+        let now = chrono::Utc::now().timestamp();
+        self.publish_live(
+            None,
+            Some(endpoint_id),
+            Some(&host),
+            self.destination_ip.as_deref(),
+            now,
+        );
+        if let Err(e) = record_usage(
+            conn,
+            endpoint_id,
+            Some(&host),
+            self.packet_count,
+            self.packet_size,
+            now,
+        ) {
+            eprintln!("Failed to record usage: {}", e);
+        }
+        if let Err(e) = record_wan_bytes(conn, endpoint_id, direction, self.packet_size, now) {
+            eprintln!("Failed to record WAN bytes: {}", e);
+        }
+        if let Err(e) = record_tcp_quality(conn, endpoint_id, &self.tcp_quality, now) {
+            eprintln!("Failed to record TCP quality: {}", e);
+        }
+    }
+
+    /// Hand a stored flow to anyone watching it live. `source` and `destination` name
+    /// each side: its address, or the internet host the flow was attributed to.
+    fn publish_live(
+        &self,
+        src_endpoint_id: Option<i64>,
+        dst_endpoint_id: Option<i64>,
+        source: Option<&str>,
+        destination: Option<&str>,
+        at: i64,
+    ) {
+        if !has_live_subscribers() {
+            return;
+        }
+        publish_live_flow(LiveFlow {
+            at,
+            src_endpoint_id,
+            dst_endpoint_id,
+            source: source.map(str::to_string),
+            destination: destination.map(str::to_string),
+            source_port: self.source_port,
+            destination_port: self.destination_port,
+            protocol: self.ip_header_protocol.clone(),
+            sub_protocol: self.sub_protocol.clone(),
+            bytes: self.packet_size,
+            packets: self.packet_count.max(1),
+        });
+    }
+
     pub fn create_table_if_not_exists(conn: &Connection) -> Result<()> {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS communications (
@@ -325,6 +763,32 @@ impl Communication {
             )",
             [],
         )?;
+        // Migration: Add interface column for per-interface views
+        let has_interface: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('communications') WHERE name = 'interface'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !has_interface {
+            conn.execute("ALTER TABLE communications ADD COLUMN interface TEXT", [])?;
+        }
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_communications_interface ON communications (interface);",
+            [],
+        )?;
+        // Migration: Add direction column (inbound/outbound/lan-to-lan), set at insertion
+        let has_direction: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('communications') WHERE name = 'direction'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !has_direction {
+            conn.execute("ALTER TABLE communications ADD COLUMN direction TEXT", [])?;
+        }
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_communications_created_at ON communications (created_at);",
             [],
@@ -367,6 +831,95 @@ impl Communication {
     }
 
     pub fn insert_communication(&self, conn: &Connection) -> Result<()> {
+        if !self.auth_events.is_empty()
+            && let Err(e) = record_auth_events(
+                conn,
+                &self.auth_events,
+                self.interface.as_deref(),
+                chrono::Utc::now().timestamp(),
+            )
+        {
+            eprintln!("Failed to record authentication events: {}", e);
+        }
+        if !self.client_certificates.is_empty()
+            && let Err(e) = record_client_certificates(
+                conn,
+                &self.client_certificates,
+                self.interface.as_deref(),
+                chrono::Utc::now().timestamp(),
+            )
+        {
+            eprintln!("Failed to record client certificates: {}", e);
+        }
+
+        // 802.11 frames only tell us who transmitted them and how strongly
+        if let Some(signal) = &self.radio_signal {
+            if let Some(mac) = &self.source_mac {
+                record_wireless_signal(
+                    conn,
+                    mac,
+                    signal,
+                    self.packet_count,
+                    self.interface.as_deref(),
+                    chrono::Utc::now().timestamp(),
+                )?;
+            }
+            return Ok(());
+        }
+
+        // Learn name -> IP mappings from DNS responses before anything else, so the
+        // destinations the client is about to contact can be labelled
+        if !self.dns_answers.is_empty()
+            && let Err(e) =
+                record_dns_answers(conn, &self.dns_answers, chrono::Utc::now().timestamp())
+        {
+            eprintln!("Failed to record DNS answers: {}", e);
+        }
+        // A lookup of a listed domain counts as a hit even if no connection follows
+        if !self.dns_answers.is_empty()
+            && let Some(endpoint_id) = self.existing_destination_endpoint(conn)
+        {
+            let now = chrono::Utc::now().timestamp();
+            for answer in &self.dns_answers {
+                if let Err(e) = check_destination(
+                    conn,
+                    endpoint_id,
+                    Some(&answer.hostname),
+                    Some(&answer.ip),
+                    now,
+                ) {
+                    eprintln!("Failed to check blocklists: {}", e);
+                }
+            }
+        }
+        if let Some(responder_ip) = &self.source_ip
+            && !self.name_responses.is_empty()
+            && let Err(e) = process_name_responses(
+                conn,
+                responder_ip,
+                self.source_mac.as_deref(),
+                &self.name_responses,
+                chrono::Utc::now().timestamp(),
+            )
+        {
+            eprintln!("Failed to check LLMNR/NBNS responses: {}", e);
+        }
+        if let Some(advertisement) = &self.router_advertisement
+            && let Err(e) =
+                process_router_advertisement(conn, advertisement, chrono::Utc::now().timestamp())
+        {
+            eprintln!("Failed to record router advertisement: {}", e);
+        }
+        for lease in &self.dhcp_leases {
+            if let Err(e) = EndPoint::record_dhcp_lease(conn, lease, chrono::Utc::now().timestamp())
+            {
+                eprintln!("Failed to record DHCP lease for {}: {}", lease.ip, e);
+            }
+        }
+
+        let direction =
+            TrafficDirection::classify(self.source_ip.as_deref(), self.destination_ip.as_deref());
+
         // For DHCP packets, the source is the client - pass DHCP Client ID, Vendor Class, and Hostname for tracking
         let src_endpoint_id = match EndPoint::get_or_insert_endpoint_with_dhcp(
             conn,
@@ -407,12 +960,41 @@ impl Communication {
                 return Ok(()); // Skip insertion on constraint violation
             }
             Err(InsertEndpointError::InternetDestination) => {
+                // Replies from the internet count toward the local device's usage
+                self.record_inbound_usage(conn, direction);
                 return Ok(()); // Skip - internet destinations are tracked separately
             }
             Err(InsertEndpointError::DatabaseError(e)) => {
                 return Err(e);
             }
         };
+        // Group-addressed packets have no destination endpoint; tally them per sender instead
+        if self.cast_type != CastType::Unicast {
+            if store_broadcast_traffic(conn) {
+                record_broadcast_traffic(
+                    conn,
+                    src_endpoint_id,
+                    self,
+                    chrono::Utc::now().timestamp(),
+                )?;
+            }
+            return Ok(());
+        }
+
+        // Before the destination is resolved: queries to public resolvers end there as
+        // internet destinations
+        if let Some(query) = self.dns_server_query(conn)
+            && let Err(e) = record_dns_server_query(
+                conn,
+                src_endpoint_id,
+                &query,
+                self.packet_count,
+                chrono::Utc::now().timestamp(),
+            )
+        {
+            eprintln!("Failed to record DNS server use: {}", e);
+        }
+
         let dst_endpoint_id = match EndPoint::get_or_insert_endpoint_with_dhcp(
             conn,
             EndpointData {
@@ -451,6 +1033,9 @@ impl Communication {
                 return Ok(()); // Skip insertion on constraint violation
             }
             Err(InsertEndpointError::InternetDestination) => {
+                if let Some(ip) = self.destination_ip.as_deref() {
+                    self.record_outbound_internet(conn, src_endpoint_id, ip, direction);
+                }
                 return Ok(()); // Skip - internet destinations are tracked separately
             }
             Err(InsertEndpointError::DatabaseError(e)) => {
@@ -475,14 +1060,18 @@ impl Communication {
                 ip_version,
                 ip_header_protocol,
                 sub_protocol,
-                source
-            ) VALUES (?1, ?2, ?3, ?3, 1, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                source,
+                interface,
+                direction
+            ) VALUES (?1, ?2, ?3, ?3, ?12, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?13)
             ON CONFLICT(src_endpoint_id, dst_endpoint_id, COALESCE(destination_port, 0), COALESCE(ip_header_protocol, ''), COALESCE(sub_protocol, ''))
             DO UPDATE SET
                 last_seen_at = ?3,
-                packet_count = packet_count + 1,
+                packet_count = packet_count + ?12,
                 bytes = bytes + ?4,
-                source_port = COALESCE(source_port, excluded.source_port)",
+                source_port = COALESCE(source_port, excluded.source_port),
+                interface = COALESCE(excluded.interface, interface),
+                direction = excluded.direction",
             params![
                 src_endpoint_id,
                 dst_endpoint_id,
@@ -493,9 +1082,43 @@ impl Communication {
                 self.ip_version,
                 self.ip_header_protocol,
                 self.sub_protocol,
-                self.source
+                self.source,
+                self.interface,
+                self.packet_count.max(1),
+                direction.as_str()
             ],
         )?;
+        mark_endpoint_summary_dirty(&[src_endpoint_id, dst_endpoint_id]);
+        record_endpoint_activity(&[src_endpoint_id, dst_endpoint_id], now);
+        self.publish_live(
+            Some(src_endpoint_id),
+            Some(dst_endpoint_id),
+            self.source_ip.as_deref().or(self.source_mac.as_deref()),
+            self.destination_ip
+                .as_deref()
+                .or(self.destination_mac.as_deref()),
+            now,
+        );
+        for endpoint_id in [src_endpoint_id, dst_endpoint_id] {
+            record_usage(
+                conn,
+                endpoint_id,
+                None,
+                self.packet_count,
+                self.packet_size,
+                now,
+            )?;
+            record_tcp_quality(conn, endpoint_id, &self.tcp_quality, now)?;
+        }
+        if let (Some(source_port), Some(destination_port)) =
+            (self.source_port, self.destination_port)
+            && let Some(port) = served_port(source_port, destination_port)
+            && let Err(e) =
+                check_port_policy(conn, src_endpoint_id, port, DriftSource::Traffic, now)
+        {
+            eprintln!("Failed to check port policy: {}", e);
+        }
+        self.check_baseline(conn, src_endpoint_id, direction, now);
         Ok(())
     }
 }
@@ -1,11 +1,50 @@
 //! Device controller router. Detects device types (LG TV, Samsung TV, Roku, LG ThinQ)
 //! and dispatches control commands to the appropriate protocol-specific controller.
+//! Detection probes can take several seconds per device, so their results are cached
+//! per address for a while and can be dropped by hand.
 
 use super::lg::LgController;
 use super::lg_thinq::{LgThinQController, ThinQDevice};
 use super::roku::RokuController;
 use super::samsung::SamsungController;
-use super::types::{CommandResult, DeviceCapabilities};
+use super::types::{
+    CommandResult, DeviceCapabilities, MacroAction, MacroRunResult, MacroStep, MacroStepResult,
+    PairingStatus,
+};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a detection result is reused before the device is probed again
+const DETECTION_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// A detection result and when it was found
+type CachedDetection = (Option<TvController>, Instant);
+
+/// Detection results by IP address
+static DETECTION_CACHE: LazyLock<Mutex<HashMap<String, CachedDetection>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The controller that answered detection at an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TvController {
+    Roku,
+    Samsung,
+    Lg,
+}
+
+impl TvController {
+    fn device_type(self) -> &'static str {
+        match self {
+            TvController::Roku => "roku",
+            TvController::Samsung => "samsung",
+            TvController::Lg => "lg",
+        }
+    }
+}
+
+/// Pause between repeats of a macro step, so the device registers each press
+const MACRO_REPEAT_GAP: Duration = Duration::from_millis(250);
 
 /// Main device controller that routes to specific implementations
 pub struct DeviceController;
@@ -22,56 +61,110 @@ impl DeviceController {
             return LgThinQController::get_capabilities(hostname.unwrap_or(""));
         }
 
-        // Quick check: if hostname contains "samsung", skip other checks
-        if let Some(name) = hostname
-            && name.to_lowercase().contains("samsung")
+        match Self::detect(ip, hostname) {
+            Some(TvController::Roku) => return RokuController::get_capabilities(ip),
+            Some(TvController::Samsung) => return SamsungController::get_capabilities(ip),
+            Some(TvController::Lg) => return LgController::get_capabilities(ip),
+            None => {}
+        }
+
+        // No controllable device found
+        DeviceCapabilities {
+            device_type: device_type.unwrap_or("unknown").to_string(),
+            can_control: false,
+            commands: Vec::new(),
+            apps: Vec::new(),
+            device_info: None,
+            needs_pairing: false,
+            is_paired: false,
+        }
+    }
+
+    /// Which TV controller answers at `ip`, from the cache when detection ran recently
+    fn detect(ip: &str, hostname: Option<&str>) -> Option<TvController> {
+        if let Ok(cache) = DETECTION_CACHE.lock()
+            && let Some((controller, detected_at)) = cache.get(ip)
+            && detected_at.elapsed() < DETECTION_TTL
         {
-            return SamsungController::get_capabilities(ip);
+            return *controller;
         }
 
-        // Check for LG TV (webOS)
-        if let Some(name) = hostname {
-            let lower = name.to_lowercase();
-            if lower.contains("lgtv") || lower.contains("webos") || lower.contains("lg-tv") {
-                return LgController::get_capabilities(ip);
-            }
+        let controller = Self::probe(ip, hostname);
+        if let Ok(mut cache) = DETECTION_CACHE.lock() {
+            cache.retain(|_, (_, detected_at)| detected_at.elapsed() < DETECTION_TTL);
+            cache.insert(ip.to_string(), (controller, Instant::now()));
         }
+        controller
+    }
 
-        // For TV types, try Roku, Samsung, and LG
-        if device_type == Some("tv") || device_type == Some("streaming") {
-            if RokuController::is_roku(ip) {
-                return RokuController::get_capabilities(ip);
-            }
-            if SamsungController::is_samsung_with_hostname(ip, hostname) {
-                return SamsungController::get_capabilities(ip);
+    fn probe(ip: &str, hostname: Option<&str>) -> Option<TvController> {
+        // Quick check: if hostname contains "samsung", skip other checks
+        if let Some(name) = hostname {
+            let lower = name.to_lowercase();
+            if lower.contains("samsung") {
+                return Some(TvController::Samsung);
             }
-            if LgController::is_lg_tv(ip, hostname) {
-                return LgController::get_capabilities(ip);
+            // LG TV (webOS)
+            if lower.contains("lgtv") || lower.contains("webos") || lower.contains("lg-tv") {
+                return Some(TvController::Lg);
             }
         }
 
-        // Also try detection regardless of type (some might be misclassified)
+        // Probe regardless of device type, since some TVs are misclassified
         if RokuController::is_roku(ip) {
-            return RokuController::get_capabilities(ip);
+            Some(TvController::Roku)
+        } else if SamsungController::is_samsung_with_hostname(ip, hostname) {
+            Some(TvController::Samsung)
+        } else if LgController::is_lg_tv(ip, hostname) {
+            Some(TvController::Lg)
+        } else {
+            None
         }
+    }
 
-        if SamsungController::is_samsung_with_hostname(ip, hostname) {
-            return SamsungController::get_capabilities(ip);
+    /// Forget cached detection results for one address, or all of them. Returns how many
+    /// were dropped.
+    pub fn invalidate_detection(ip: Option<&str>) -> usize {
+        let Ok(mut cache) = DETECTION_CACHE.lock() else {
+            return 0;
+        };
+        match ip {
+            Some(ip) => usize::from(cache.remove(ip).is_some()),
+            None => {
+                let dropped = cache.len();
+                cache.clear();
+                dropped
+            }
         }
+    }
 
-        if LgController::is_lg_tv(ip, hostname) {
-            return LgController::get_capabilities(ip);
+    /// Whether the device at `ip` needs pairing and is paired, without fetching its apps
+    /// or commands
+    pub fn pairing_status(ip: &str, hostname: Option<&str>) -> PairingStatus {
+        if LgThinQController::is_thinq_appliance(hostname) {
+            let paired = LgThinQController::has_credentials();
+            return PairingStatus {
+                ip: ip.to_string(),
+                device_type: Some("lg_thinq".to_string()),
+                can_control: true,
+                needs_pairing: !paired,
+                is_paired: paired,
+            };
         }
 
-        // No controllable device found
-        DeviceCapabilities {
-            device_type: device_type.unwrap_or("unknown").to_string(),
-            can_control: false,
-            commands: Vec::new(),
-            apps: Vec::new(),
-            device_info: None,
-            needs_pairing: false,
-            is_paired: false,
+        let controller = Self::detect(ip, hostname);
+        let is_paired = match controller {
+            Some(TvController::Roku) => true,
+            Some(TvController::Samsung) => SamsungController::get_token(ip).is_some(),
+            Some(TvController::Lg) => LgController::get_client_key(ip).is_some(),
+            None => false,
+        };
+        PairingStatus {
+            ip: ip.to_string(),
+            device_type: controller.map(|c| c.device_type().to_string()),
+            can_control: controller.is_some(),
+            needs_pairing: controller.is_some() && !is_paired,
+            is_paired,
         }
     }
 
@@ -113,6 +206,108 @@ impl DeviceController {
         }
     }
 
+    /// Launch an app straight into a piece of content
+    pub fn deep_link(
+        ip: &str,
+        app_id: &str,
+        content_id: &str,
+        media_type: &str,
+        device_type: &str,
+    ) -> CommandResult {
+        match device_type {
+            "roku" => RokuController::deep_link(ip, app_id, content_id, media_type),
+            _ => CommandResult {
+                success: false,
+                message: format!("Deep linking not supported for: {}", device_type),
+            },
+        }
+    }
+
+    /// Search the device's catalogue for a keyword
+    pub fn search(ip: &str, keyword: &str, device_type: &str) -> CommandResult {
+        match device_type {
+            "roku" => RokuController::search(ip, keyword),
+            _ => CommandResult {
+                success: false,
+                message: format!("Search not supported for: {}", device_type),
+            },
+        }
+    }
+
+    /// Type text into the device's on-screen keyboard
+    pub fn send_text(ip: &str, text: &str, device_type: &str) -> CommandResult {
+        match device_type {
+            "roku" => RokuController::send_text(ip, text),
+            _ => CommandResult {
+                success: false,
+                message: format!("Text entry not supported for: {}", device_type),
+            },
+        }
+    }
+
+    /// Run a macro's steps in order, pausing after each for its delay. `resolve_ip` maps
+    /// a step's endpoint name to the address to control. The run stops at the first step
+    /// that fails, since later steps usually depend on it (no point switching input on a
+    /// TV that didn't turn on).
+    pub fn run_macro(
+        steps: &[MacroStep],
+        resolve_ip: impl Fn(&str) -> Option<String>,
+    ) -> MacroRunResult {
+        let mut results = Vec::new();
+        for (index, step) in steps.iter().enumerate() {
+            let result = match resolve_ip(&step.endpoint) {
+                Some(ip) => Self::run_macro_step(&ip, step),
+                None => CommandResult {
+                    success: false,
+                    message: format!("No address known for '{}'", step.endpoint),
+                },
+            };
+            let success = result.success;
+            results.push(MacroStepResult {
+                step: index + 1,
+                endpoint: step.endpoint.clone(),
+                success,
+                message: result.message,
+            });
+            if !success {
+                return MacroRunResult {
+                    success: false,
+                    message: format!("Stopped at step {} of {}", index + 1, steps.len()),
+                    steps: results,
+                };
+            }
+            if step.delay_ms > 0 && index + 1 < steps.len() {
+                std::thread::sleep(Duration::from_millis(step.delay_ms));
+            }
+        }
+        MacroRunResult {
+            success: true,
+            message: format!("Ran {} steps", steps.len()),
+            steps: results,
+        }
+    }
+
+    fn run_macro_step(ip: &str, step: &MacroStep) -> CommandResult {
+        let mut result = CommandResult {
+            success: false,
+            message: "Step has nothing to repeat".to_string(),
+        };
+        for attempt in 0..step.repeat {
+            if attempt > 0 {
+                std::thread::sleep(MACRO_REPEAT_GAP);
+            }
+            result = match step.action {
+                MacroAction::Command => Self::send_command(ip, &step.value, &step.device_type),
+                MacroAction::Launch => Self::launch_app(ip, &step.value, &step.device_type),
+                MacroAction::Text => Self::send_text(ip, &step.value, &step.device_type),
+            };
+            if !result.success {
+                break;
+            }
+        }
+        result
+    }
+
     /// Pair with a device that requires pairing (e.g., Samsung TV, LG TV)
     pub fn pair(ip: &str, device_type: &str) -> CommandResult {
         match device_type {
@@ -153,3 +348,50 @@ impl DeviceController {
         LgThinQController::clear_credentials()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hostnames that name the TV decide detection without probing the network
+
+    #[test]
+    fn test_detection_is_cached_until_invalidated() {
+        let ip = "192.0.2.10";
+        assert_eq!(
+            DeviceController::detect(ip, Some("samsung-tv")),
+            Some(TvController::Samsung)
+        );
+        // A different answer is only found once the cached one is dropped
+        assert_eq!(
+            DeviceController::detect(ip, Some("lgtv-den")),
+            Some(TvController::Samsung)
+        );
+        assert_eq!(DeviceController::invalidate_detection(Some(ip)), 1);
+        assert_eq!(DeviceController::invalidate_detection(Some(ip)), 0);
+        assert_eq!(
+            DeviceController::detect(ip, Some("lgtv-den")),
+            Some(TvController::Lg)
+        );
+
+        assert!(DeviceController::invalidate_detection(None) >= 1);
+        assert_eq!(DeviceController::invalidate_detection(Some(ip)), 0);
+    }
+
+    #[test]
+    fn test_expired_detection_is_probed_again() {
+        let ip = "192.0.2.11";
+        let expired = Instant::now()
+            .checked_sub(DETECTION_TTL + Duration::from_secs(1))
+            .unwrap();
+        DETECTION_CACHE
+            .lock()
+            .unwrap()
+            .insert(ip.to_string(), (Some(TvController::Roku), expired));
+
+        assert_eq!(
+            DeviceController::detect(ip, Some("samsung-tv")),
+            Some(TvController::Samsung)
+        );
+    }
+}
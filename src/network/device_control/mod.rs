@@ -9,3 +9,4 @@ mod samsung;
 mod types;
 
 pub use controller::DeviceController;
+pub use types::MacroStep;
@@ -1,5 +1,6 @@
 //! Roku ECP controller. Implements the External Control Protocol on port 8060
-//! for device info retrieval, app listing, and remote command execution.
+//! for device info retrieval, app listing, remote command execution, deep links into
+//! app content, search, and typing text into on-screen keyboards.
 
 use super::types::{AppInfo, CommandInfo, CommandResult, DeviceCapabilities, DeviceInfo};
 use std::time::Duration;
@@ -11,6 +12,20 @@ impl RokuController {
     const PORT: u16 = 8060;
     const TIMEOUT: Duration = Duration::from_secs(3);
 
+    /// Longest text typed in one request; each character is a separate keypress
+    pub const MAX_TEXT_LEN: usize = 200;
+
+    /// Media types Roku's deep linking accepts alongside a content ID
+    pub const MEDIA_TYPES: &[&str] = &[
+        "movie",
+        "episode",
+        "season",
+        "series",
+        "shortFormVideo",
+        "special",
+        "live",
+    ];
+
     /// Check if a device is a Roku by querying its ECP endpoint
     pub fn is_roku(ip: &str) -> bool {
         let url = format!("http://{}:{}/query/device-info", ip, Self::PORT);
@@ -160,7 +175,12 @@ impl RokuController {
 
     /// Launch an app on Roku
     pub fn launch_app(ip: &str, app_id: &str) -> CommandResult {
-        let url = format!("http://{}:{}/launch/{}", ip, Self::PORT, app_id);
+        let url = format!(
+            "http://{}:{}/launch/{}",
+            ip,
+            Self::PORT,
+            Self::encode(app_id)
+        );
 
         let client = match reqwest::blocking::Client::builder()
             .timeout(Self::TIMEOUT)
@@ -196,6 +216,138 @@ impl RokuController {
         }
     }
 
+    /// Launch an app straight into a piece of content (a movie, an episode...)
+    pub fn deep_link(ip: &str, app_id: &str, content_id: &str, media_type: &str) -> CommandResult {
+        if content_id.trim().is_empty() {
+            return CommandResult {
+                success: false,
+                message: "A content ID is required".to_string(),
+            };
+        }
+        if !Self::MEDIA_TYPES.contains(&media_type) {
+            return CommandResult {
+                success: false,
+                message: format!(
+                    "A media type is required for deep links: one of {}",
+                    Self::MEDIA_TYPES.join(", ")
+                ),
+            };
+        }
+
+        let path = format!(
+            "launch/{}?contentId={}&mediaType={}",
+            Self::encode(app_id),
+            Self::encode(content_id.trim()),
+            media_type
+        );
+        match Self::post(ip, &path) {
+            Ok(()) => CommandResult {
+                success: true,
+                message: "Content launched".to_string(),
+            },
+            Err(message) => CommandResult {
+                success: false,
+                message,
+            },
+        }
+    }
+
+    /// Open Roku search with a keyword, as if typed into the search screen
+    pub fn search(ip: &str, keyword: &str) -> CommandResult {
+        let keyword = keyword.trim();
+        if keyword.is_empty() {
+            return CommandResult {
+                success: false,
+                message: "Nothing to search for".to_string(),
+            };
+        }
+
+        match Self::post(
+            ip,
+            &format!("search/browse?keyword={}", Self::encode(keyword)),
+        ) {
+            Ok(()) => CommandResult {
+                success: true,
+                message: format!("Searching for \"{}\"", keyword),
+            },
+            Err(message) => CommandResult {
+                success: false,
+                message,
+            },
+        }
+    }
+
+    /// Type text into whatever on-screen keyboard has focus, one `Lit_` keypress per
+    /// character
+    pub fn send_text(ip: &str, text: &str) -> CommandResult {
+        let length = text.chars().count();
+        if length == 0 {
+            return CommandResult {
+                success: false,
+                message: "Nothing to type".to_string(),
+            };
+        }
+        if length > Self::MAX_TEXT_LEN {
+            return CommandResult {
+                success: false,
+                message: format!(
+                    "Text is limited to {} characters per request",
+                    Self::MAX_TEXT_LEN
+                ),
+            };
+        }
+
+        for (typed, c) in text.chars().enumerate() {
+            if let Err(message) = Self::post(ip, &Self::literal_keypress(c)) {
+                return CommandResult {
+                    success: false,
+                    message: format!("Typed {} of {} characters: {}", typed, length, message),
+                };
+            }
+        }
+        CommandResult {
+            success: true,
+            message: format!("Typed {} characters", length),
+        }
+    }
+
+    /// The keypress path that types one character
+    fn literal_keypress(c: char) -> String {
+        format!("keypress/Lit_{}", Self::encode(c.encode_utf8(&mut [0; 4])))
+    }
+
+    /// Percent-encode everything but unreserved characters, as ECP path segments and
+    /// query values expect
+    fn encode(value: &str) -> String {
+        value
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    }
+
+    /// POST to an ECP path, e.g. `keypress/Home`
+    fn post(ip: &str, path: &str) -> Result<(), String> {
+        let url = format!("http://{}:{}/{}", ip, Self::PORT, path);
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Self::TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        let response = client
+            .post(&url)
+            .send()
+            .map_err(|e| format!("Failed to send command: {}", e))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Roku returned status: {}", response.status()))
+        }
+    }
+
     /// Get all available Roku commands
     pub fn get_commands() -> Vec<CommandInfo> {
         vec![
@@ -331,3 +483,20 @@ impl RokuController {
         if value.is_empty() { None } else { Some(value) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_keypress_encoding() {
+        assert_eq!(RokuController::literal_keypress('a'), "keypress/Lit_a");
+        assert_eq!(RokuController::literal_keypress(' '), "keypress/Lit_%20");
+        assert_eq!(RokuController::literal_keypress('&'), "keypress/Lit_%26");
+        assert_eq!(
+            RokuController::literal_keypress('\u{e9}'),
+            "keypress/Lit_%C3%A9"
+        );
+        assert_eq!(RokuController::encode("tt0111161/x"), "tt0111161%2Fx");
+    }
+}
@@ -44,3 +44,67 @@ pub struct CommandResult {
     pub success: bool,
     pub message: String,
 }
+
+/// What a macro step does with its value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MacroAction {
+    /// Send a remote command, e.g. "KEY_HDMI2" or "VolumeUp"
+    #[default]
+    Command,
+    /// Launch an app by ID
+    Launch,
+    /// Type text into the on-screen keyboard
+    Text,
+}
+
+/// One step of a device macro
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MacroStep {
+    /// Endpoint name; its address is looked up each time the macro runs
+    pub endpoint: String,
+    /// Controller to use: "roku", "samsung", "lg" or "lg_thinq:<device id>"
+    pub device_type: String,
+    #[serde(default)]
+    pub action: MacroAction,
+    /// The command, app ID or text, depending on the action
+    pub value: String,
+    /// Times to send the step, e.g. 10 volume-up presses
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    /// Pause after the step, in milliseconds
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroStepResult {
+    /// Position of the step in the macro, from 1
+    pub step: usize,
+    pub endpoint: String,
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroRunResult {
+    pub success: bool,
+    pub message: String,
+    /// Steps run, ending at the first that failed
+    pub steps: Vec<MacroStepResult>,
+}
+
+/// Pairing state of a controllable device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingStatus {
+    pub ip: String,
+    /// Detected controller ("roku", "samsung", "lg", "lg_thinq"), if any
+    pub device_type: Option<String>,
+    pub can_control: bool,
+    pub needs_pairing: bool,
+    pub is_paired: bool,
+}
@@ -0,0 +1,61 @@
+//! Traffic direction. Each flow is classed when it is inserted, against the networks of
+//! the local interfaces: leaving the LAN (outbound), arriving from outside (inbound), or
+//! staying inside it. Communications store the class, and the internet side of inbound
+//! and outbound flows is added to the local device's hourly WAN byte counts, so upload and
+//! download totals are read back rather than guessed per query.
+
+use super::endpoint::EndPoint;
+
+/// Which way a flow crosses the edge of the local network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficDirection {
+    /// From an internet host to a local device
+    Inbound,
+    /// From a local device to an internet host
+    Outbound,
+    /// Between two local devices (or between two hosts that are both remote)
+    LanToLan,
+}
+
+impl TrafficDirection {
+    /// Classify a flow from its addresses. A side without an IP (ARP, raw Ethernet) is
+    /// on the local link.
+    pub fn classify(source_ip: Option<&str>, destination_ip: Option<&str>) -> Self {
+        let is_local = |ip: Option<&str>| ip.is_none_or(EndPoint::is_on_local_network);
+        match (is_local(source_ip), is_local(destination_ip)) {
+            (true, false) => TrafficDirection::Outbound,
+            (false, true) => TrafficDirection::Inbound,
+            _ => TrafficDirection::LanToLan,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrafficDirection::Inbound => "inbound",
+            TrafficDirection::Outbound => "outbound",
+            TrafficDirection::LanToLan => "lan-to-lan",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_traffic_direction() {
+        assert_eq!(
+            TrafficDirection::classify(Some("127.0.0.1"), Some("93.184.216.34")),
+            TrafficDirection::Outbound
+        );
+        assert_eq!(
+            TrafficDirection::classify(Some("93.184.216.34"), Some("127.0.0.1")),
+            TrafficDirection::Inbound
+        );
+        assert_eq!(
+            TrafficDirection::classify(None, Some("127.0.0.1")),
+            TrafficDirection::LanToLan
+        );
+        assert_eq!(TrafficDirection::LanToLan.as_str(), "lan-to-lan");
+    }
+}
@@ -0,0 +1,387 @@
+//! Which DNS servers each endpoint actually uses. Plain DNS (port 53) and DNS over TLS
+//! (port 853) are recognized by port; DNS over HTTPS by the TLS SNI, or the name the
+//! device resolved the address from, matching a well-known DoH provider. A device talking
+//! to a resolver outside the local network bypasses the local resolver and raises a
+//! notification, unless the server is in the `dns_allowed_servers` setting or the device
+//! is itself a local resolver forwarding upstream.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::db::insert_notification_with_endpoint_id;
+use crate::network::endpoint::EndPoint;
+
+/// Hostnames of public DNS-over-HTTPS services; subdomains match too
+const DOH_HOSTS: &[&str] = &[
+    "dns.google",
+    "dns.google.com",
+    "cloudflare-dns.com",
+    "one.one.one.one",
+    "dns.quad9.net",
+    "dns9.quad9.net",
+    "dns10.quad9.net",
+    "dns11.quad9.net",
+    "doh.opendns.com",
+    "dns.nextdns.io",
+    "doh.cleanbrowsing.org",
+    "dns.adguard.com",
+    "dns.adguard-dns.com",
+    "doh.mullvad.net",
+    "dns.controld.com",
+    "doh.dns.sb",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsTransport {
+    Plain,
+    Tls,
+    Https,
+}
+
+impl DnsTransport {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DnsTransport::Plain => "dns",
+            DnsTransport::Tls => "dot",
+            DnsTransport::Https => "doh",
+        }
+    }
+}
+
+/// One packet flow from a client to a DNS server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsServerQuery {
+    pub server_ip: String,
+    /// DoH provider hostname
+    pub server_name: Option<String>,
+    pub transport: DnsTransport,
+}
+
+fn is_doh_host(hostname: &str) -> bool {
+    let hostname = hostname.trim_end_matches('.').to_lowercase();
+    DOH_HOSTS.iter().any(|host| {
+        hostname == *host
+            || hostname
+                .strip_suffix(host)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+/// Classify a flow by destination. `server_name` is the SNI or resolved name of the
+/// destination and only matters for port 443.
+pub fn dns_server_query(
+    destination_ip: &str,
+    destination_port: u16,
+    server_name: Option<&str>,
+) -> Option<DnsServerQuery> {
+    let (transport, server_name) = match destination_port {
+        53 => (DnsTransport::Plain, None),
+        853 => (DnsTransport::Tls, None),
+        443 => (
+            DnsTransport::Https,
+            Some(server_name.filter(|name| is_doh_host(name))?.to_lowercase()),
+        ),
+        _ => return None,
+    };
+    Some(DnsServerQuery {
+        server_ip: destination_ip.to_string(),
+        server_name,
+        transport,
+    })
+}
+
+pub fn create_dns_server_usage_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS dns_server_usage (
+            id INTEGER PRIMARY KEY,
+            endpoint_id INTEGER NOT NULL,
+            server_ip TEXT NOT NULL,
+            server_name TEXT,
+            transport TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            packet_count INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(endpoint_id, server_ip, transport)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Servers and DoH hostnames from the `dns_allowed_servers` setting (comma separated)
+pub fn allowed_dns_servers(conn: &Connection) -> Vec<String> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = 'dns_allowed_servers'",
+        [],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .map(|value| {
+        value
+            .split(',')
+            .map(|server| server.trim().to_lowercase())
+            .filter(|server| !server.is_empty())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Endpoints other devices send DNS queries to
+fn is_local_resolver(conn: &Connection, endpoint_id: i64) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM communications WHERE dst_endpoint_id = ?1 AND destination_port = 53)",
+        [endpoint_id],
+        |row| row.get(0),
+    )
+}
+
+fn is_bypass(server_ip: &str, server_name: Option<&str>, allowed: &[String]) -> bool {
+    !EndPoint::is_on_local_network(server_ip)
+        && !allowed
+            .iter()
+            .any(|server| server == server_ip || Some(server.as_str()) == server_name)
+}
+
+/// Record that `endpoint_id` queried a DNS server. The first time an endpoint is seen
+/// using a server that bypasses the local resolver, a `dns_bypass` notification is raised.
+/// Returns true for a server the endpoint hadn't used before.
+pub fn record_dns_server_query(
+    conn: &Connection,
+    endpoint_id: i64,
+    query: &DnsServerQuery,
+    packets: u32,
+    now: i64,
+) -> Result<bool> {
+    let first_seen_at: i64 = conn.query_row(
+        "INSERT INTO dns_server_usage
+            (endpoint_id, server_ip, server_name, transport, first_seen_at, last_seen_at, packet_count)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6)
+         ON CONFLICT(endpoint_id, server_ip, transport) DO UPDATE SET
+            server_name = COALESCE(excluded.server_name, server_name),
+            last_seen_at = excluded.last_seen_at,
+            packet_count = packet_count + excluded.packet_count
+         RETURNING first_seen_at",
+        params![
+            endpoint_id,
+            query.server_ip,
+            query.server_name,
+            query.transport.as_str(),
+            now,
+            packets.max(1)
+        ],
+        |row| row.get(0),
+    )?;
+    if first_seen_at != now {
+        return Ok(false);
+    }
+
+    if is_bypass(
+        &query.server_ip,
+        query.server_name.as_deref(),
+        &allowed_dns_servers(conn),
+    ) && !is_local_resolver(conn, endpoint_id)?
+    {
+        let endpoint_name: Option<String> = conn
+            .query_row(
+                "SELECT display_name FROM endpoints WHERE id = ?1",
+                [endpoint_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        let server = match &query.server_name {
+            Some(name) => format!("{} ({})", name, query.server_ip),
+            None => query.server_ip.clone(),
+        };
+        insert_notification_with_endpoint_id(
+            conn,
+            "dns_bypass",
+            &format!(
+                "{} is bypassing the local DNS resolver",
+                endpoint_name.as_deref().unwrap_or("An endpoint")
+            ),
+            Some(&format!(
+                "Using {} via {}",
+                server,
+                query.transport.as_str().to_uppercase()
+            )),
+            endpoint_name.as_deref(),
+            Some(endpoint_id),
+        );
+    }
+    Ok(true)
+}
+
+/// Move an endpoint's DNS server usage to the endpoint it is merged into
+pub fn reassign_dns_server_usage(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE OR IGNORE dns_server_usage SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![to_id, from_id],
+    )?;
+    conn.execute(
+        "DELETE FROM dns_server_usage WHERE endpoint_id = ?1",
+        [from_id],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DnsServerUsage {
+    pub server_ip: String,
+    pub server_name: Option<String>,
+    /// "dns", "dot" or "doh"
+    pub transport: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+    pub packet_count: i64,
+    /// Outside the local network and not allowlisted
+    pub bypass: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointDnsServers {
+    pub endpoint_id: i64,
+    pub endpoint_name: Option<String>,
+    /// The endpoint answers DNS itself, so its upstream servers aren't a bypass
+    pub is_resolver: bool,
+    pub servers: Vec<DnsServerUsage>,
+}
+
+/// DNS servers used per endpoint, endpoints bypassing the local resolver first. With
+/// `endpoint_id`, only that endpoint.
+pub fn get_dns_server_usage(
+    conn: &Connection,
+    endpoint_id: Option<i64>,
+) -> Result<Vec<EndpointDnsServers>> {
+    let allowed = allowed_dns_servers(conn);
+    let mut stmt = conn.prepare(
+        "SELECT u.endpoint_id, e.display_name, u.server_ip, u.server_name, u.transport,
+                u.first_seen_at, u.last_seen_at, u.packet_count,
+                EXISTS (SELECT 1 FROM communications c
+                        WHERE c.dst_endpoint_id = u.endpoint_id AND c.destination_port = 53)
+         FROM dns_server_usage u
+         LEFT JOIN endpoints e ON e.id = u.endpoint_id
+         WHERE ?1 IS NULL OR u.endpoint_id = ?1
+         ORDER BY u.endpoint_id, u.packet_count DESC",
+    )?;
+    let rows = stmt.query_map([endpoint_id], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, bool>(8)?,
+            DnsServerUsage {
+                server_ip: row.get(2)?,
+                server_name: row.get(3)?,
+                transport: row.get(4)?,
+                first_seen_at: row.get(5)?,
+                last_seen_at: row.get(6)?,
+                packet_count: row.get(7)?,
+                bypass: false,
+            },
+        ))
+    })?;
+
+    let mut endpoints: Vec<EndpointDnsServers> = Vec::new();
+    for row in rows {
+        let (endpoint_id, endpoint_name, is_resolver, mut usage) = row?;
+        usage.bypass =
+            !is_resolver && is_bypass(&usage.server_ip, usage.server_name.as_deref(), &allowed);
+        match endpoints.last_mut() {
+            Some(last) if last.endpoint_id == endpoint_id => last.servers.push(usage),
+            _ => endpoints.push(EndpointDnsServers {
+                endpoint_id,
+                endpoint_name,
+                is_resolver,
+                servers: vec![usage],
+            }),
+        }
+    }
+    endpoints.sort_by_key(|endpoint| !endpoint.servers.iter().any(|server| server.bypass));
+    Ok(endpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_dns_server_query_classification() {
+        let plain = dns_server_query("8.8.8.8", 53, None).unwrap();
+        assert_eq!(
+            (plain.transport, plain.server_name),
+            (DnsTransport::Plain, None)
+        );
+        assert_eq!(
+            dns_server_query("1.1.1.1", 853, None).unwrap().transport,
+            DnsTransport::Tls
+        );
+
+        let doh = dns_server_query("8.8.4.4", 443, Some("DNS.Google")).unwrap();
+        assert_eq!(doh.transport, DnsTransport::Https);
+        assert_eq!(doh.server_name.as_deref(), Some("dns.google"));
+        assert!(dns_server_query("45.90.28.1", 443, Some("abc123.dns.nextdns.io")).is_some());
+
+        assert!(dns_server_query("142.250.1.1", 443, Some("www.google.com")).is_none());
+        assert!(dns_server_query("142.250.1.1", 443, Some("notdns.google")).is_none());
+        assert!(dns_server_query("142.250.1.1", 443, None).is_none());
+        assert!(dns_server_query("8.8.8.8", 80, None).is_none());
+    }
+
+    #[test]
+    fn test_record_dns_server_usage_and_bypass() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'tv'), (2, 1, 'laptop');",
+        )
+        .unwrap();
+        let bypass_count = || -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = 'dns_bypass'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        let local = dns_server_query("127.0.0.1", 53, None).unwrap();
+        let google = dns_server_query("8.8.8.8", 53, None).unwrap();
+        assert!(record_dns_server_query(&conn, 1, &local, 2, 100).unwrap());
+        assert_eq!(bypass_count(), 0);
+        assert!(record_dns_server_query(&conn, 1, &google, 1, 100).unwrap());
+        assert!(!record_dns_server_query(&conn, 1, &google, 3, 200).unwrap());
+        assert_eq!(bypass_count(), 1);
+
+        // Allowlisted servers are not reported
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('dns_allowed_servers', ' 1.1.1.1, dns.google ')",
+            [],
+        )
+        .unwrap();
+        let doh = dns_server_query("8.8.4.4", 443, Some("dns.google")).unwrap();
+        assert!(record_dns_server_query(&conn, 2, &doh, 1, 300).unwrap());
+        assert_eq!(bypass_count(), 1);
+
+        let usage = get_dns_server_usage(&conn, None).unwrap();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].endpoint_id, 1);
+        let servers: Vec<(&str, i64, bool)> = usage[0]
+            .servers
+            .iter()
+            .map(|s| (s.server_ip.as_str(), s.packet_count, s.bypass))
+            .collect();
+        assert_eq!(servers, vec![("8.8.8.8", 4, true), ("127.0.0.1", 2, false)]);
+        assert!(!usage[1].servers[0].bypass);
+
+        reassign_dns_server_usage(&conn, 2, 1).unwrap();
+        assert_eq!(
+            get_dns_server_usage(&conn, Some(1)).unwrap()[0]
+                .servers
+                .len(),
+            3
+        );
+    }
+}
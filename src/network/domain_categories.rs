@@ -0,0 +1,304 @@
+//! Bundled domain categories for usage reports. Domains are grouped by site (the
+//! registrable domain, so every googlevideo.com CDN host counts once) and categorized by
+//! suffix; named sites that aren't listed count as browsing. Sites of the big providers
+//! also map to the organization that runs them, for the internet summary.
+
+pub const CATEGORY_STREAMING: &str = "streaming";
+pub const CATEGORY_GAMING: &str = "gaming";
+pub const CATEGORY_SOCIAL: &str = "social";
+pub const CATEGORY_BROWSING: &str = "browsing";
+/// Destinations only known by IP address
+pub const CATEGORY_OTHER: &str = "other";
+
+const STREAMING_DOMAINS: &[&str] = &[
+    "netflix.com",
+    "nflxvideo.net",
+    "nflximg.net",
+    "nflxso.net",
+    "nflxext.com",
+    "youtube.com",
+    "googlevideo.com",
+    "ytimg.com",
+    "youtu.be",
+    "twitch.tv",
+    "ttvnw.net",
+    "jtvnw.net",
+    "hulu.com",
+    "hulustream.com",
+    "disneyplus.com",
+    "disney-plus.net",
+    "dssott.com",
+    "bamgrid.com",
+    "primevideo.com",
+    "aiv-cdn.net",
+    "aiv-delivery.net",
+    "amazonvideo.com",
+    "hbomax.com",
+    "max.com",
+    "peacocktv.com",
+    "paramountplus.com",
+    "pluto.tv",
+    "tubi.tv",
+    "tubitv.com",
+    "sling.com",
+    "fubo.tv",
+    "crunchyroll.com",
+    "vimeo.com",
+    "vimeocdn.com",
+    "dazn.com",
+    "plex.tv",
+    "plex.direct",
+    "spotify.com",
+    "scdn.co",
+    "spotifycdn.com",
+    "pandora.com",
+    "deezer.com",
+    "tidal.com",
+    "soundcloud.com",
+    "sndcdn.com",
+];
+
+const GAMING_DOMAINS: &[&str] = &[
+    "steampowered.com",
+    "steamcommunity.com",
+    "steamcontent.com",
+    "steamserver.net",
+    "steamstatic.com",
+    "xboxlive.com",
+    "xbox.com",
+    "playstation.com",
+    "playstation.net",
+    "sonyentertainmentnetwork.com",
+    "nintendo.com",
+    "nintendo.net",
+    "epicgames.com",
+    "epicgames.dev",
+    "fortnite.com",
+    "riotgames.com",
+    "leagueoflegends.com",
+    "blizzard.com",
+    "battle.net",
+    "activision.com",
+    "callofduty.com",
+    "ea.com",
+    "origin.com",
+    "roblox.com",
+    "rbxcdn.com",
+    "minecraft.net",
+    "mojang.com",
+    "ubisoft.com",
+    "ubi.com",
+    "gog.com",
+    "supercell.com",
+    "king.com",
+];
+
+const SOCIAL_DOMAINS: &[&str] = &[
+    "facebook.com",
+    "facebook.net",
+    "fbcdn.net",
+    "instagram.com",
+    "cdninstagram.com",
+    "threads.net",
+    "tiktok.com",
+    "tiktokcdn.com",
+    "tiktokv.com",
+    "byteoversea.com",
+    "snapchat.com",
+    "sc-cdn.net",
+    "twitter.com",
+    "x.com",
+    "twimg.com",
+    "reddit.com",
+    "redd.it",
+    "redditmedia.com",
+    "redditstatic.com",
+    "pinterest.com",
+    "pinimg.com",
+    "tumblr.com",
+    "linkedin.com",
+    "licdn.com",
+    "bsky.app",
+    "discord.com",
+    "discord.gg",
+    "discordapp.com",
+    "discordapp.net",
+    "whatsapp.com",
+    "whatsapp.net",
+];
+
+/// Organizations behind well-known sites, matched by suffix like the categories. CDN and
+/// cloud hosts count for the provider, since that is who the traffic goes to.
+const SITE_OWNERS: &[(&str, &str)] = &[
+    ("google.com", "Google"),
+    ("googlevideo.com", "Google"),
+    ("googleapis.com", "Google"),
+    ("gstatic.com", "Google"),
+    ("googleusercontent.com", "Google"),
+    ("gvt1.com", "Google"),
+    ("1e100.net", "Google"),
+    ("youtube.com", "Google"),
+    ("ytimg.com", "Google"),
+    ("youtu.be", "Google"),
+    ("doubleclick.net", "Google"),
+    ("netflix.com", "Netflix"),
+    ("nflxvideo.net", "Netflix"),
+    ("nflximg.net", "Netflix"),
+    ("nflxso.net", "Netflix"),
+    ("nflxext.com", "Netflix"),
+    ("amazon.com", "Amazon"),
+    ("amazonaws.com", "Amazon"),
+    ("amazonvideo.com", "Amazon"),
+    ("primevideo.com", "Amazon"),
+    ("aiv-cdn.net", "Amazon"),
+    ("aiv-delivery.net", "Amazon"),
+    ("cloudfront.net", "Amazon"),
+    ("twitch.tv", "Amazon"),
+    ("ttvnw.net", "Amazon"),
+    ("jtvnw.net", "Amazon"),
+    ("apple.com", "Apple"),
+    ("icloud.com", "Apple"),
+    ("mzstatic.com", "Apple"),
+    ("apple-dns.net", "Apple"),
+    ("cdn-apple.com", "Apple"),
+    ("microsoft.com", "Microsoft"),
+    ("windows.com", "Microsoft"),
+    ("windowsupdate.com", "Microsoft"),
+    ("live.com", "Microsoft"),
+    ("office.com", "Microsoft"),
+    ("msftconnecttest.com", "Microsoft"),
+    ("azure.com", "Microsoft"),
+    ("azureedge.net", "Microsoft"),
+    ("xboxlive.com", "Microsoft"),
+    ("xbox.com", "Microsoft"),
+    ("linkedin.com", "Microsoft"),
+    ("licdn.com", "Microsoft"),
+    ("minecraft.net", "Microsoft"),
+    ("mojang.com", "Microsoft"),
+    ("facebook.com", "Meta"),
+    ("facebook.net", "Meta"),
+    ("fbcdn.net", "Meta"),
+    ("instagram.com", "Meta"),
+    ("cdninstagram.com", "Meta"),
+    ("threads.net", "Meta"),
+    ("whatsapp.com", "Meta"),
+    ("whatsapp.net", "Meta"),
+    ("tiktok.com", "ByteDance"),
+    ("tiktokcdn.com", "ByteDance"),
+    ("tiktokv.com", "ByteDance"),
+    ("byteoversea.com", "ByteDance"),
+    ("akamai.net", "Akamai"),
+    ("akamaiedge.net", "Akamai"),
+    ("akamaihd.net", "Akamai"),
+    ("akamaized.net", "Akamai"),
+    ("cloudflare.com", "Cloudflare"),
+    ("cloudflare-dns.com", "Cloudflare"),
+    ("fastly.net", "Fastly"),
+    ("fastlylb.net", "Fastly"),
+    ("disneyplus.com", "Disney"),
+    ("disney-plus.net", "Disney"),
+    ("dssott.com", "Disney"),
+    ("bamgrid.com", "Disney"),
+    ("hulu.com", "Disney"),
+    ("hulustream.com", "Disney"),
+    ("spotify.com", "Spotify"),
+    ("scdn.co", "Spotify"),
+    ("spotifycdn.com", "Spotify"),
+    ("steampowered.com", "Valve"),
+    ("steamcommunity.com", "Valve"),
+    ("steamcontent.com", "Valve"),
+    ("steamserver.net", "Valve"),
+    ("steamstatic.com", "Valve"),
+    ("playstation.com", "Sony"),
+    ("playstation.net", "Sony"),
+    ("sonyentertainmentnetwork.com", "Sony"),
+    ("roku.com", "Roku"),
+    ("samsungcloud.com", "Samsung"),
+    ("samsungcloudsolution.com", "Samsung"),
+    ("samsungqbe.com", "Samsung"),
+];
+
+/// Second-level labels under country code TLDs that are part of the suffix (co.uk, com.au)
+const SECOND_LEVEL_SUFFIXES: &[&str] = &["co", "com", "net", "org", "gov", "edu", "ac", "ne", "or"];
+
+fn matches_domain(domain: &str, suffix: &str) -> bool {
+    domain == suffix
+        || domain
+            .strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// The registrable domain of a hostname ("rr3---sn-a.googlevideo.com" -> "googlevideo.com").
+/// IP addresses are returned unchanged.
+pub fn site_domain(hostname: &str) -> String {
+    let hostname = hostname.trim_end_matches('.').to_lowercase();
+    if hostname.parse::<std::net::IpAddr>().is_ok() {
+        return hostname;
+    }
+    let labels: Vec<&str> = hostname.split('.').collect();
+    let keep = match labels.as_slice() {
+        [.., second, tld] if tld.len() == 2 && SECOND_LEVEL_SUFFIXES.contains(second) => 3,
+        _ => 2,
+    };
+    labels[labels.len().saturating_sub(keep)..].join(".")
+}
+
+/// Category of a hostname or site
+pub fn categorize_domain(domain: &str) -> &'static str {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    if domain.parse::<std::net::IpAddr>().is_ok() {
+        return CATEGORY_OTHER;
+    }
+    [
+        (STREAMING_DOMAINS, CATEGORY_STREAMING),
+        (GAMING_DOMAINS, CATEGORY_GAMING),
+        (SOCIAL_DOMAINS, CATEGORY_SOCIAL),
+    ]
+    .iter()
+    .find(|(domains, _)| domains.iter().any(|suffix| matches_domain(&domain, suffix)))
+    .map_or(CATEGORY_BROWSING, |(_, category)| *category)
+}
+
+/// The organization behind a hostname or site, None for IP addresses and sites that
+/// aren't listed
+pub fn site_owner(domain: &str) -> Option<&'static str> {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    SITE_OWNERS
+        .iter()
+        .find(|(suffix, _)| matches_domain(&domain, suffix))
+        .map(|(_, owner)| *owner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_site_domain_and_category() {
+        assert_eq!(
+            site_domain("rr3---sn-abc.googlevideo.com"),
+            "googlevideo.com"
+        );
+        assert_eq!(site_domain("www.bbc.co.uk."), "bbc.co.uk");
+        assert_eq!(site_domain("localhost"), "localhost");
+        assert_eq!(site_domain("142.250.1.1"), "142.250.1.1");
+
+        assert_eq!(categorize_domain("googlevideo.com"), CATEGORY_STREAMING);
+        assert_eq!(categorize_domain("cdn.steamstatic.com"), CATEGORY_GAMING);
+        assert_eq!(categorize_domain("www.reddit.com"), CATEGORY_SOCIAL);
+        assert_eq!(categorize_domain("wikipedia.org"), CATEGORY_BROWSING);
+        // Suffix matching is on label boundaries
+        assert_eq!(categorize_domain("notnetflix.com"), CATEGORY_BROWSING);
+        assert_eq!(categorize_domain("10.0.0.1"), CATEGORY_OTHER);
+    }
+
+    #[test]
+    fn test_site_owner() {
+        assert_eq!(site_owner("googlevideo.com"), Some("Google"));
+        assert_eq!(site_owner("d1234.cloudfront.net."), Some("Amazon"));
+        assert_eq!(site_owner("scontent.cdninstagram.com"), Some("Meta"));
+        assert_eq!(site_owner("wikipedia.org"), None);
+        assert_eq!(site_owner("notnetflix.com"), None);
+        assert_eq!(site_owner("142.250.1.1"), None);
+    }
+}
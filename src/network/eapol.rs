@@ -0,0 +1,854 @@
+//! 802.1X and WPA authentication events. EAPOL frames (wired 802.1X, or the WPA 4-way
+//! handshake on a monitor-mode capture) and 802.11 deauthentication/disassociation frames
+//! are logged per station in `auth_events`. A station failing to authenticate over and
+//! over (a wrong password, or someone guessing one) raises `auth_failures`, and a burst of
+//! deauth frames against one access point, the usual way to knock clients off to capture
+//! their handshakes or push them to an evil twin, raises a critical `deauth_flood`.
+//! EAP-TLS logins up to TLS 1.2 send the client certificate in the clear; its subject
+//! anchors the station's identity across MAC randomization (see endpoint/identity.rs).
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::db::insert_notification_with_endpoint_id;
+use crate::network::endpoint::EndPoint;
+use crate::scanner::host_keys::certificate_subject;
+
+const ETHERTYPE_EAPOL: u16 = 0x888e;
+
+/// LLC/SNAP header announcing an EAPOL payload in an 802.11 data frame
+const LLC_SNAP_EAPOL: [u8; 8] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x88, 0x8e];
+
+const EAPOL_EAP_PACKET: u8 = 0;
+const EAPOL_START: u8 = 1;
+const EAPOL_LOGOFF: u8 = 2;
+const EAPOL_KEY: u8 = 3;
+
+const EAP_RESPONSE: u8 = 2;
+const EAP_SUCCESS: u8 = 3;
+const EAP_FAILURE: u8 = 4;
+const EAP_TYPE_IDENTITY: u8 = 1;
+const EAP_TYPE_TLS: u8 = 13;
+
+/// EAP-TLS flags: the TLS message length follows, and more fragments follow
+const EAP_TLS_LENGTH_INCLUDED: u8 = 0x80;
+const EAP_TLS_MORE_FRAGMENTS: u8 = 0x40;
+
+const TLS_CHANGE_CIPHER_SPEC: u8 = 20;
+const TLS_HANDSHAKE: u8 = 22;
+const TLS_HANDSHAKE_CERTIFICATE: u8 = 11;
+
+/// Largest EAP-TLS message reassembled, and stations reassembled at once
+const MAX_EAP_TLS_BYTES: usize = 64 * 1024;
+const MAX_EAP_TLS_STATIONS: usize = 256;
+
+const KEY_INFO_PAIRWISE: u16 = 0x0008;
+const KEY_INFO_ACK: u16 = 0x0080;
+const KEY_INFO_MIC: u16 = 0x0100;
+const KEY_INFO_SECURE: u16 = 0x0200;
+
+const SUBTYPE_DISASSOCIATION: u8 = 10;
+const SUBTYPE_DEAUTHENTICATION: u8 = 12;
+
+/// Deauth reasons that mean authentication failed: MIC failure, 4-way and group key
+/// handshake timeouts, and 802.1X authentication failed
+const FAILURE_REASONS: [u16; 4] = [14, 15, 16, 23];
+
+/// A handshake restarting this soon after the client's message 2 means its MIC was wrong
+const HANDSHAKE_TIMEOUT_SECS: i64 = 60;
+
+/// Failed attempts by one station within the window before it is reported
+const AUTH_FAILURE_LIMIT: i64 = 5;
+const AUTH_FAILURE_WINDOW_SECS: i64 = 10 * 60;
+
+/// Deauth/disassociation frames for one access point within the window that count as a flood
+const DEAUTH_FLOOD_LIMIT: i64 = 20;
+const DEAUTH_FLOOD_WINDOW_SECS: i64 = 60;
+
+const BROADCAST_MAC: &str = "ff:ff:ff:ff:ff:ff";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthEventKind {
+    EapolStart,
+    EapolLogoff,
+    /// The supplicant answered the identity request; authentication has begun
+    EapIdentity,
+    EapSuccess,
+    EapFailure,
+    /// Message 1-4 of the pairwise (4-way) handshake
+    Handshake(u8),
+    GroupKey,
+    /// With the reason code (0 when the frame is protected and the reason unreadable)
+    Deauthentication(u16),
+    Disassociation(u16),
+}
+
+impl AuthEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventKind::EapolStart => "eapol_start",
+            AuthEventKind::EapolLogoff => "eapol_logoff",
+            AuthEventKind::EapIdentity => "eap_identity",
+            AuthEventKind::EapSuccess => "eap_success",
+            AuthEventKind::EapFailure => "eap_failure",
+            AuthEventKind::Handshake(1) => "handshake_1",
+            AuthEventKind::Handshake(2) => "handshake_2",
+            AuthEventKind::Handshake(3) => "handshake_3",
+            AuthEventKind::Handshake(_) => "handshake_4",
+            AuthEventKind::GroupKey => "group_key",
+            AuthEventKind::Deauthentication(_) => "deauthentication",
+            AuthEventKind::Disassociation(_) => "disassociation",
+        }
+    }
+
+    fn reason(&self) -> Option<u16> {
+        match self {
+            AuthEventKind::Deauthentication(reason) | AuthEventKind::Disassociation(reason) => {
+                Some(*reason)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_failure(&self) -> bool {
+        *self == AuthEventKind::EapFailure
+            || self
+                .reason()
+                .is_some_and(|reason| FAILURE_REASONS.contains(&reason))
+    }
+}
+
+/// One authentication frame between a station (the supplicant) and its authenticator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthEvent {
+    pub kind: AuthEventKind,
+    pub station: String,
+    /// The access point or 802.1X switch port
+    pub peer: String,
+}
+
+/// Parse an EAPOL frame. Returns the event and whether the authenticator sent it.
+/// Only frames marking a step of authentication are returned; the EAP exchange in
+/// between (and any identity it carries) is not.
+fn parse_eapol(eapol: &[u8]) -> Option<(AuthEventKind, bool)> {
+    let packet_type = *eapol.get(1)?;
+    let length = usize::from(u16::from_be_bytes([*eapol.get(2)?, *eapol.get(3)?]));
+    let body = eapol.get(4..4 + length)?;
+    match packet_type {
+        EAPOL_START => Some((AuthEventKind::EapolStart, false)),
+        EAPOL_LOGOFF => Some((AuthEventKind::EapolLogoff, false)),
+        EAPOL_EAP_PACKET => match *body.first()? {
+            EAP_RESPONSE if body.get(4) == Some(&EAP_TYPE_IDENTITY) => {
+                Some((AuthEventKind::EapIdentity, false))
+            }
+            EAP_SUCCESS => Some((AuthEventKind::EapSuccess, true)),
+            EAP_FAILURE => Some((AuthEventKind::EapFailure, true)),
+            _ => None,
+        },
+        EAPOL_KEY => {
+            let info = u16::from_be_bytes([*body.get(1)?, *body.get(2)?]);
+            let ack = info & KEY_INFO_ACK != 0;
+            if info & KEY_INFO_PAIRWISE == 0 {
+                return Some((AuthEventKind::GroupKey, ack));
+            }
+            let message = match (ack, info & KEY_INFO_MIC != 0, info & KEY_INFO_SECURE != 0) {
+                (true, false, _) => 1,
+                (false, true, false) => 2,
+                (true, true, _) => 3,
+                (false, true, true) => 4,
+                (false, false, _) => return None,
+            };
+            Some((AuthEventKind::Handshake(message), ack))
+        }
+        _ => None,
+    }
+}
+
+fn event(
+    kind: AuthEventKind,
+    from_authenticator: bool,
+    source: &str,
+    destination: &str,
+) -> AuthEvent {
+    let (station, peer) = if from_authenticator {
+        (destination, source)
+    } else {
+        (source, destination)
+    };
+    AuthEvent {
+        kind,
+        station: station.to_lowercase(),
+        peer: peer.to_lowercase(),
+    }
+}
+
+/// The authentication event in an Ethernet frame, if it is EAPOL (wired 802.1X, or a
+/// WPA handshake as seen by the host itself)
+pub fn auth_event_from_ethernet(
+    ethertype: u16,
+    source: &str,
+    destination: &str,
+    payload: &[u8],
+) -> Option<AuthEvent> {
+    if ethertype != ETHERTYPE_EAPOL {
+        return None;
+    }
+    let (kind, from_authenticator) = parse_eapol(payload)?;
+    Some(event(kind, from_authenticator, source, destination))
+}
+
+fn mac_at(frame: &[u8], offset: usize) -> Option<String> {
+    let octets = frame.get(offset..offset + 6)?;
+    Some(
+        octets
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+/// Transmitter, receiver and EAPOL payload of an unencrypted 802.11 data frame
+fn eapol_in_80211(frame: &[u8]) -> Option<(String, String, &[u8])> {
+    let frame_type = (*frame.first()? >> 2) & 0x3;
+    let subtype = frame[0] >> 4;
+    let flags = *frame.get(1)?;
+    if frame_type != 2 || flags & 0x40 != 0 {
+        return None;
+    }
+    let to_ds = flags & 0x1 != 0;
+    let from_ds = flags & 0x2 != 0;
+    let qos = subtype & 0x8 != 0;
+    let mut header_len = 24;
+    if to_ds && from_ds {
+        header_len += 6;
+    }
+    if qos {
+        header_len += 2;
+        if flags & 0x80 != 0 {
+            header_len += 4;
+        }
+    }
+    let body = frame.get(header_len..)?;
+    if !body.starts_with(&LLC_SNAP_EAPOL) {
+        return None;
+    }
+    Some((
+        mac_at(frame, 10)?,
+        mac_at(frame, 4)?,
+        &body[LLC_SNAP_EAPOL.len()..],
+    ))
+}
+
+/// The authentication event in an 802.11 frame (without its radiotap header): a
+/// deauthentication or disassociation, or EAPOL carried in an unencrypted data frame
+pub fn auth_event_from_80211(frame: &[u8]) -> Option<AuthEvent> {
+    let frame_type = (*frame.first()? >> 2) & 0x3;
+    let subtype = frame[0] >> 4;
+    let flags = *frame.get(1)?;
+    let protected = flags & 0x40 != 0;
+    let receiver = mac_at(frame, 4)?;
+    let transmitter = mac_at(frame, 10)?;
+    let bssid = mac_at(frame, 16)?;
+
+    match frame_type {
+        0 if matches!(subtype, SUBTYPE_DEAUTHENTICATION | SUBTYPE_DISASSOCIATION) => {
+            let reason = if protected {
+                0
+            } else {
+                u16::from_le_bytes([*frame.get(24)?, *frame.get(25)?])
+            };
+            let kind = if subtype == SUBTYPE_DEAUTHENTICATION {
+                AuthEventKind::Deauthentication(reason)
+            } else {
+                AuthEventKind::Disassociation(reason)
+            };
+            // Sent by the access point to one station (or all of them), or by a station
+            // leaving its access point
+            Some(event(kind, transmitter == bssid, &transmitter, &receiver))
+        }
+        2 => {
+            let (_, _, eapol) = eapol_in_80211(frame)?;
+            let (kind, from_authenticator) = parse_eapol(eapol)?;
+            Some(event(kind, from_authenticator, &transmitter, &receiver))
+        }
+        _ => None,
+    }
+}
+
+/// A client certificate a station presented in an EAP-TLS login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    pub station: String,
+    pub peer: String,
+    pub subject: String,
+}
+
+/// TLS data of an EAP-TLS response and whether more fragments follow
+fn parse_eap_tls_response(eapol: &[u8]) -> Option<(&[u8], bool)> {
+    let length = usize::from(u16::from_be_bytes([*eapol.get(2)?, *eapol.get(3)?]));
+    let eap = eapol.get(4..4 + length)?;
+    if eapol.get(1) != Some(&EAPOL_EAP_PACKET)
+        || eap.first() != Some(&EAP_RESPONSE)
+        || eap.get(4) != Some(&EAP_TYPE_TLS)
+    {
+        return None;
+    }
+    let flags = *eap.get(5)?;
+    let start = if flags & EAP_TLS_LENGTH_INCLUDED != 0 {
+        10
+    } else {
+        6
+    };
+    Some((eap.get(start..)?, flags & EAP_TLS_MORE_FRAGMENTS != 0))
+}
+
+/// The first certificate of a Certificate message in a client's TLS flight. Records
+/// after ChangeCipherSpec are encrypted, as is everything in TLS 1.3.
+fn first_certificate(mut tls: &[u8]) -> Option<Vec<u8>> {
+    let mut handshake = Vec::new();
+    while tls.len() >= 5 {
+        let content_type = tls[0];
+        let length = usize::from(u16::from_be_bytes([tls[3], tls[4]]));
+        let fragment = tls.get(5..5 + length)?;
+        match content_type {
+            TLS_HANDSHAKE => handshake.extend_from_slice(fragment),
+            TLS_CHANGE_CIPHER_SPEC => break,
+            _ => {}
+        }
+        tls = &tls[5 + length..];
+    }
+
+    let u24 = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .fold(0, |length, &b| (length << 8) | usize::from(b))
+    };
+    let mut messages = handshake.as_slice();
+    while messages.len() >= 4 {
+        let length = u24(&messages[1..4]);
+        let body = messages.get(4..4 + length)?;
+        if messages[0] == TLS_HANDSHAKE_CERTIFICATE {
+            // Length of the whole list, then each certificate's length and DER
+            let first_length = u24(body.get(3..6)?);
+            return body.get(6..6 + first_length).map(<[u8]>::to_vec);
+        }
+        messages = &messages[4 + length..];
+    }
+    None
+}
+
+/// EAP-TLS messages being reassembled, by station
+fn eap_tls_buffers() -> &'static Mutex<HashMap<String, Vec<u8>>> {
+    static BUFFERS: OnceLock<Mutex<HashMap<String, Vec<u8>>>> = OnceLock::new();
+    BUFFERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Add one EAP-TLS fragment from `station`; returns the whole message with the last one
+fn reassemble_eap_tls(station: &str, data: &[u8], more: bool) -> Option<Vec<u8>> {
+    let mut buffers = eap_tls_buffers().lock().ok()?;
+    if !more {
+        let mut message = buffers.remove(station).unwrap_or_default();
+        message.extend_from_slice(data);
+        return Some(message);
+    }
+    if buffers.len() >= MAX_EAP_TLS_STATIONS && !buffers.contains_key(station) {
+        buffers.clear();
+    }
+    let buffer = buffers.entry(station.to_string()).or_default();
+    if buffer.len() + data.len() > MAX_EAP_TLS_BYTES {
+        buffers.remove(station);
+        return None;
+    }
+    buffer.extend_from_slice(data);
+    None
+}
+
+/// The client certificate a station sent, once the EAP-TLS message carrying it is complete
+fn client_certificate(eapol: &[u8], station: &str, peer: &str) -> Option<ClientCertificate> {
+    let (data, more) = parse_eap_tls_response(eapol)?;
+    let message = reassemble_eap_tls(station, data, more)?;
+    let subject = certificate_subject(&first_certificate(&message)?)?;
+    Some(ClientCertificate {
+        station: station.to_lowercase(),
+        peer: peer.to_lowercase(),
+        subject,
+    })
+}
+
+/// The client certificate in an Ethernet frame carrying EAP-TLS (wired 802.1X)
+pub fn client_certificate_from_ethernet(
+    ethertype: u16,
+    source: &str,
+    destination: &str,
+    payload: &[u8],
+) -> Option<ClientCertificate> {
+    if ethertype != ETHERTYPE_EAPOL {
+        return None;
+    }
+    client_certificate(payload, source, destination)
+}
+
+/// The client certificate in an unencrypted 802.11 data frame carrying EAP-TLS
+pub fn client_certificate_from_80211(frame: &[u8]) -> Option<ClientCertificate> {
+    let (transmitter, receiver, eapol) = eapol_in_80211(frame)?;
+    client_certificate(eapol, &transmitter, &receiver)
+}
+
+pub fn create_auth_events_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS auth_events (
+            id INTEGER PRIMARY KEY,
+            station_mac TEXT NOT NULL,
+            peer_mac TEXT NOT NULL,
+            event TEXT NOT NULL,
+            reason INTEGER,
+            interface TEXT,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_auth_events_station ON auth_events (station_mac, created_at)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_auth_events_peer ON auth_events (peer_mac, created_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn insert_event(
+    conn: &Connection,
+    station: &str,
+    peer: &str,
+    event: &str,
+    reason: Option<u16>,
+    interface: Option<&str>,
+    now: i64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO auth_events (station_mac, peer_mac, event, reason, interface, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(params![station, peer, event, reason, interface, now])?;
+    Ok(())
+}
+
+/// Whether `event` was already logged for `mac` (as station) since `since`
+fn logged_since(conn: &Connection, mac: &str, event: &str, since: i64) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM auth_events
+                        WHERE station_mac = ?1 AND event = ?2 AND created_at >= ?3)",
+        params![mac, event, since],
+        |row| row.get(0),
+    )
+}
+
+fn endpoint_id_for_mac(conn: &Connection, mac: &str) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT endpoint_id FROM endpoint_attributes WHERE LOWER(mac) = ?1
+         ORDER BY created_at DESC LIMIT 1",
+        [mac],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Report a station once it has failed `AUTH_FAILURE_LIMIT` times within the window
+fn check_repeated_failures(
+    conn: &Connection,
+    station: &str,
+    peer: &str,
+    interface: Option<&str>,
+    now: i64,
+) -> Result<()> {
+    let since = now - AUTH_FAILURE_WINDOW_SECS;
+    let failures: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM auth_events
+         WHERE station_mac = ?1 AND created_at >= ?2
+           AND (event IN ('eap_failure', 'handshake_failed')
+                OR (event IN ('deauthentication', 'disassociation') AND reason IN (14, 15, 16, 23)))",
+        params![station, since],
+        |row| row.get(0),
+    )?;
+    if failures < AUTH_FAILURE_LIMIT || logged_since(conn, station, "repeated_failures", since)? {
+        return Ok(());
+    }
+    insert_event(
+        conn,
+        station,
+        peer,
+        "repeated_failures",
+        None,
+        interface,
+        now,
+    )?;
+    insert_notification_with_endpoint_id(
+        conn,
+        "auth_failures",
+        &format!("Repeated authentication failures from {}", station),
+        Some(&format!(
+            "{} failed attempts to authenticate with {} in {} minutes: a wrong password, or someone guessing one",
+            failures,
+            peer,
+            AUTH_FAILURE_WINDOW_SECS / 60
+        )),
+        None,
+        endpoint_id_for_mac(conn, station)?,
+    );
+    Ok(())
+}
+
+/// Report a burst of deauth/disassociation frames involving access point `peer`
+fn check_deauth_flood(
+    conn: &Connection,
+    peer: &str,
+    interface: Option<&str>,
+    now: i64,
+) -> Result<()> {
+    let since = now - DEAUTH_FLOOD_WINDOW_SECS;
+    let (frames, stations): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COUNT(DISTINCT station_mac) FROM auth_events
+         WHERE peer_mac = ?1 AND created_at >= ?2
+           AND event IN ('deauthentication', 'disassociation')",
+        params![peer, since],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if frames < DEAUTH_FLOOD_LIMIT || logged_since(conn, peer, "deauth_flood", since)? {
+        return Ok(());
+    }
+    insert_event(conn, peer, peer, "deauth_flood", None, interface, now)?;
+    insert_notification_with_endpoint_id(
+        conn,
+        "deauth_flood",
+        &format!("Deauthentication flood on access point {}", peer),
+        Some(&format!(
+            "{} deauthentication/disassociation frames within a minute, affecting {} station(s)",
+            frames, stations
+        )),
+        None,
+        endpoint_id_for_mac(conn, peer)?,
+    );
+    Ok(())
+}
+
+/// Log authentication events seen at `now` and raise notifications for repeated
+/// failures and deauth floods
+pub fn record_auth_events(
+    conn: &Connection,
+    events: &[AuthEvent],
+    interface: Option<&str>,
+    now: i64,
+) -> Result<()> {
+    for event in events {
+        let (station, peer) = (event.station.as_str(), event.peer.as_str());
+        let mut failed = event.kind.is_failure();
+
+        // The access point starting over after the client's message 2 rejected its MIC
+        if event.kind == AuthEventKind::Handshake(1) {
+            let previous: Option<String> = conn
+                .query_row(
+                    "SELECT event FROM auth_events
+                     WHERE station_mac = ?1 AND peer_mac = ?2 AND created_at >= ?3
+                       AND event IN ('handshake_1', 'handshake_2', 'handshake_3', 'handshake_4')
+                     ORDER BY id DESC LIMIT 1",
+                    params![station, peer, now - HANDSHAKE_TIMEOUT_SECS],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if previous.as_deref() == Some("handshake_2") {
+                insert_event(
+                    conn,
+                    station,
+                    peer,
+                    "handshake_failed",
+                    None,
+                    interface,
+                    now,
+                )?;
+                failed = true;
+            }
+        }
+
+        insert_event(
+            conn,
+            station,
+            peer,
+            event.kind.as_str(),
+            event.kind.reason(),
+            interface,
+            now,
+        )?;
+        if failed && station != BROADCAST_MAC {
+            check_repeated_failures(conn, station, peer, interface, now)?;
+        }
+        if event.kind.reason().is_some() {
+            check_deauth_flood(conn, peer, interface, now)?;
+        }
+    }
+    Ok(())
+}
+
+/// Log the client certificates stations logged in with, and anchor each station's
+/// endpoint to its certificate's subject
+pub fn record_client_certificates(
+    conn: &Connection,
+    certificates: &[ClientCertificate],
+    interface: Option<&str>,
+    now: i64,
+) -> Result<()> {
+    for certificate in certificates {
+        insert_event(
+            conn,
+            &certificate.station,
+            &certificate.peer,
+            "client_certificate",
+            None,
+            interface,
+            now,
+        )?;
+        if let Some(endpoint_id) = endpoint_id_for_mac(conn, &certificate.station)? {
+            EndPoint::anchor_certificate(conn, endpoint_id, &certificate.subject, None, now)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthEventRow {
+    pub station_mac: String,
+    pub peer_mac: String,
+    pub event: String,
+    /// 802.11 reason code of a deauthentication or disassociation
+    pub reason: Option<u16>,
+    pub interface: Option<String>,
+    pub created_at: i64,
+}
+
+/// Events at or after `since`, newest first, optionally only those involving `mac`
+pub fn get_auth_events(
+    conn: &Connection,
+    since: i64,
+    mac: Option<&str>,
+    limit: i64,
+) -> Result<Vec<AuthEventRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT station_mac, peer_mac, event, reason, interface, created_at FROM auth_events
+         WHERE created_at >= ?1 AND (?2 IS NULL OR station_mac = ?2 OR peer_mac = ?2)
+         ORDER BY id DESC
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![since, mac.map(str::to_lowercase), limit], |row| {
+        Ok(AuthEventRow {
+            station_mac: row.get(0)?,
+            peer_mac: row.get(1)?,
+            event: row.get(2)?,
+            reason: row.get(3)?,
+            interface: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    const CLIENT: &str = "00:1b:63:00:00:01";
+    const AP: &str = "00:11:22:33:44:55";
+
+    /// EAPOL-Key frame with the given key information
+    fn eapol_key(info: u16) -> Vec<u8> {
+        let mut eapol = vec![2, EAPOL_KEY, 0, 95, 2];
+        eapol.extend_from_slice(&info.to_be_bytes());
+        eapol.resize(4 + 95, 0);
+        eapol
+    }
+
+    /// 802.11 frame: frame control, duration, three addresses, sequence control, body
+    fn frame_80211(frame_control: [u8; 2], addresses: [[u8; 6]; 3], body: &[u8]) -> Vec<u8> {
+        let mut frame = frame_control.to_vec();
+        frame.extend_from_slice(&[0, 0]);
+        for address in addresses {
+            frame.extend_from_slice(&address);
+        }
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    const CLIENT_BYTES: [u8; 6] = [0x00, 0x1b, 0x63, 0x00, 0x00, 0x01];
+    const AP_BYTES: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    #[test]
+    fn test_parse_auth_frames() {
+        let message = |info| parse_eapol(&eapol_key(info)).unwrap();
+        assert_eq!(message(0x008a), (AuthEventKind::Handshake(1), true));
+        assert_eq!(message(0x010a), (AuthEventKind::Handshake(2), false));
+        assert_eq!(message(0x13ca), (AuthEventKind::Handshake(3), true));
+        assert_eq!(message(0x030a), (AuthEventKind::Handshake(4), false));
+        assert_eq!(message(0x0382), (AuthEventKind::GroupKey, true));
+        assert_eq!(
+            parse_eapol(&[1, EAPOL_EAP_PACKET, 0, 4, EAP_FAILURE, 7, 0, 4]),
+            Some((AuthEventKind::EapFailure, true))
+        );
+
+        // Wired 802.1X: EAPOL-Start from the supplicant to the PAE group address
+        let start = auth_event_from_ethernet(
+            ETHERTYPE_EAPOL,
+            CLIENT,
+            "01:80:c2:00:00:03",
+            &[1, EAPOL_START, 0, 0],
+        )
+        .unwrap();
+        assert_eq!(start.kind, AuthEventKind::EapolStart);
+        assert_eq!(start.station, CLIENT);
+        assert!(auth_event_from_ethernet(0x0800, CLIENT, AP, &[1, EAPOL_START, 0, 0]).is_none());
+
+        // Message 1 from the access point, in a from-DS data frame
+        let mut body = LLC_SNAP_EAPOL.to_vec();
+        body.extend_from_slice(&eapol_key(0x008a));
+        let handshake = auth_event_from_80211(&frame_80211(
+            [0x08, 0x02],
+            [CLIENT_BYTES, AP_BYTES, AP_BYTES],
+            &body,
+        ))
+        .unwrap();
+        assert_eq!(handshake.kind, AuthEventKind::Handshake(1));
+        assert_eq!(
+            (handshake.station.as_str(), handshake.peer.as_str()),
+            (CLIENT, AP)
+        );
+
+        // Encrypted data says nothing
+        assert!(
+            auth_event_from_80211(&frame_80211(
+                [0x08, 0x42],
+                [CLIENT_BYTES, AP_BYTES, AP_BYTES],
+                &body
+            ))
+            .is_none()
+        );
+
+        // Broadcast deauthentication from the access point, reason 7
+        let deauth = auth_event_from_80211(&frame_80211(
+            [0xc0, 0],
+            [[0xff; 6], AP_BYTES, AP_BYTES],
+            &[7, 0],
+        ))
+        .unwrap();
+        assert_eq!(deauth.kind, AuthEventKind::Deauthentication(7));
+        assert_eq!(
+            (deauth.station.as_str(), deauth.peer.as_str()),
+            (BROADCAST_MAC, AP)
+        );
+    }
+
+    /// EAPOL packet carrying an EAP-TLS response fragment
+    fn eap_tls_response(flags: u8, tls: &[u8]) -> Vec<u8> {
+        let mut eap = vec![EAP_RESPONSE, 9, 0, 0, EAP_TYPE_TLS, flags];
+        if flags & EAP_TLS_LENGTH_INCLUDED != 0 {
+            eap.extend_from_slice(&[0, 0, 0, 0]);
+        }
+        eap.extend_from_slice(tls);
+        let length = eap.len() as u16;
+        eap[2..4].copy_from_slice(&length.to_be_bytes());
+        let mut eapol = vec![1, EAPOL_EAP_PACKET];
+        eapol.extend_from_slice(&length.to_be_bytes());
+        eapol.extend_from_slice(&eap);
+        eapol
+    }
+
+    #[test]
+    fn test_eap_tls_client_certificate() {
+        let der = [0x30, 0x03, 0x02, 0x01, 0x07];
+        // Certificate message: list length, certificate length, DER
+        let mut certificate = vec![TLS_HANDSHAKE_CERTIFICATE, 0, 0, 11, 0, 0, 8, 0, 0, 5];
+        certificate.extend_from_slice(&der);
+        let mut tls = vec![TLS_HANDSHAKE, 3, 3, 0, certificate.len() as u8];
+        tls.extend_from_slice(&certificate);
+        // Anything after ChangeCipherSpec is encrypted
+        tls.extend_from_slice(&[TLS_CHANGE_CIPHER_SPEC, 3, 3, 0, 1, 1]);
+
+        // Split across two fragments
+        let (first, second) = tls.split_at(8);
+        let first = eap_tls_response(EAP_TLS_LENGTH_INCLUDED | EAP_TLS_MORE_FRAGMENTS, first);
+        let (data, more) = parse_eap_tls_response(&first).unwrap();
+        assert!(more);
+        assert!(reassemble_eap_tls(CLIENT, data, more).is_none());
+        let second = eap_tls_response(0, second);
+        let (data, more) = parse_eap_tls_response(&second).unwrap();
+        let message = reassemble_eap_tls(CLIENT, data, more).unwrap();
+        assert_eq!(message, tls);
+        assert_eq!(first_certificate(&message).as_deref(), Some(&der[..]));
+
+        // The server's flight is a request, not a response
+        let mut request = eap_tls_response(0, &tls);
+        request[4] = 1;
+        assert!(parse_eap_tls_response(&request).is_none());
+    }
+
+    #[test]
+    fn test_repeated_failures_and_deauth_flood() {
+        let conn = new_test_connection();
+        let at = |kind| AuthEvent {
+            kind,
+            station: CLIENT.to_string(),
+            peer: AP.to_string(),
+        };
+        let notifications = |event_type: &str| -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = ?1",
+                [event_type],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        // A wrong password: message 1 and 2, then the access point starts over
+        for attempt in 0..6 {
+            let now = 100 + attempt * 5;
+            let events = [
+                at(AuthEventKind::Handshake(1)),
+                at(AuthEventKind::Handshake(2)),
+            ];
+            record_auth_events(&conn, &events, Some("wlan0mon"), now).unwrap();
+        }
+        let failed = || -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM auth_events WHERE event = 'handshake_failed'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(failed(), 5);
+        assert_eq!(notifications("auth_failures"), 1);
+
+        // A completed handshake is not a failure
+        let events = [
+            at(AuthEventKind::Handshake(3)),
+            at(AuthEventKind::Handshake(4)),
+            at(AuthEventKind::Handshake(1)),
+        ];
+        record_auth_events(&conn, &events, None, 200).unwrap();
+        assert_eq!(failed(), 5);
+
+        let deauths: Vec<AuthEvent> = (0..DEAUTH_FLOOD_LIMIT * 2)
+            .map(|_| at(AuthEventKind::Deauthentication(7)))
+            .collect();
+        record_auth_events(&conn, &deauths, None, 300).unwrap();
+        assert_eq!(notifications("deauth_flood"), 1);
+
+        let events = get_auth_events(&conn, 300, Some("00:1B:63:00:00:01"), 10).unwrap();
+        assert_eq!(events.len(), 10);
+        assert_eq!(events[0].reason, Some(7));
+    }
+}
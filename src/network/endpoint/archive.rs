@@ -0,0 +1,414 @@
+//! Soft-deleted endpoints. Deleting an endpoint archives a snapshot of its rows so it
+//! can be restored until the retention window expires and the archive is purged.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde_json::Value;
+
+use super::EndPoint;
+use super::display_name::display_name;
+use super::snapshot::{
+    JsonRow, insert_json_row, insert_json_row_or_ignore, json_ids, json_rows, query_ids,
+    query_rows_as_json,
+};
+use super::types::ArchivedEndpoint;
+
+/// Collect distinct non-empty values of `column` from snapshotted attribute rows
+fn attribute_values(attributes: &[JsonRow], column: &str) -> Vec<String> {
+    let mut values: Vec<String> = attributes
+        .iter()
+        .filter_map(|row| row.get(column).and_then(|v| v.as_str()))
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect();
+    values.sort();
+    values.dedup();
+    values
+}
+
+/// Display name of a snapshotted endpoint, by the same rule as live endpoints
+fn snapshot_display_name(endpoint: Option<&Value>, attributes: &[JsonRow]) -> Option<String> {
+    let field = |column: &str| {
+        endpoint
+            .and_then(|e| e.get(column))
+            .and_then(|v| v.as_str())
+    };
+    let hostnames = attribute_values(attributes, "hostname");
+    let ips = attribute_values(attributes, "ip");
+    display_name(
+        field("custom_name"),
+        field("name"),
+        &hostnames.iter().map(String::as_str).collect::<Vec<_>>(),
+        &ips.iter().map(String::as_str).collect::<Vec<_>>(),
+    )
+}
+
+/// Re-point a snapshotted child row at `endpoint_id`, dropping its old primary key
+fn reparent_row(mut row: JsonRow, endpoint_id: i64) -> JsonRow {
+    row.remove("id");
+    row.insert("endpoint_id".to_string(), Value::from(endpoint_id));
+    row
+}
+
+impl EndPoint {
+    pub fn create_archived_endpoints_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS archived_endpoints (
+                id INTEGER PRIMARY KEY,
+                endpoint_id INTEGER NOT NULL,
+                name TEXT,
+                deleted_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                snapshot TEXT NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_archived_endpoints_deleted_at ON archived_endpoints (deleted_at);",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Snapshot an endpoint and everything that references it before it is deleted.
+    /// Returns the archive id.
+    pub fn archive_endpoint(conn: &Connection, endpoint_id: i64, name: &str) -> Result<i64> {
+        let endpoint =
+            query_rows_as_json(conn, "SELECT * FROM endpoints WHERE id = ?1", endpoint_id)?
+                .into_iter()
+                .next()
+                .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+
+        let payload = serde_json::json!({
+            "endpoint": endpoint,
+            "attributes": query_rows_as_json(
+                conn,
+                "SELECT * FROM endpoint_attributes WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "open_ports": query_rows_as_json(
+                conn,
+                "SELECT * FROM open_ports WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "scan_results": query_rows_as_json(
+                conn,
+                "SELECT * FROM scan_results WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "tags": query_rows_as_json(
+                conn,
+                "SELECT * FROM endpoint_tags WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "trust": query_rows_as_json(
+                conn,
+                "SELECT * FROM endpoint_trust WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "appearance": query_rows_as_json(
+                conn,
+                "SELECT * FROM endpoint_appearance WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "owners": query_rows_as_json(
+                conn,
+                "SELECT * FROM endpoint_owners WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "snmp_credentials": query_rows_as_json(
+                conn,
+                "SELECT * FROM snmp_credentials WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "power": query_rows_as_json(
+                conn,
+                "SELECT * FROM endpoint_power WHERE endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "communications_src": query_ids(
+                conn,
+                "SELECT id FROM communications WHERE src_endpoint_id = ?1",
+                endpoint_id,
+            )?,
+            "communications_dst": query_ids(
+                conn,
+                "SELECT id FROM communications WHERE dst_endpoint_id = ?1",
+                endpoint_id,
+            )?,
+        });
+
+        conn.execute(
+            "INSERT INTO archived_endpoints (endpoint_id, name, snapshot) VALUES (?1, ?2, ?3)",
+            params![endpoint_id, name, payload.to_string()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// List archived (soft-deleted) endpoints, most recently deleted first
+    pub fn get_archived_endpoints(conn: &Connection) -> Result<Vec<ArchivedEndpoint>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, endpoint_id, name, deleted_at, snapshot
+             FROM archived_endpoints
+             ORDER BY deleted_at DESC, id DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let snapshot: String = row.get(4)?;
+            let snapshot: Value = serde_json::from_str(&snapshot).unwrap_or(Value::Null);
+            let attributes = json_rows(snapshot.get("attributes"));
+            let name = snapshot_display_name(snapshot.get("endpoint"), &attributes);
+            Ok(ArchivedEndpoint {
+                id: row.get(0)?,
+                endpoint_id: row.get(1)?,
+                name: name.or(row.get(2)?),
+                deleted_at: row.get(3)?,
+                ips: attribute_values(&attributes, "ip"),
+                macs: attribute_values(&attributes, "mac"),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Restore an archived endpoint. The original endpoint id is reused when it is still
+    /// free; attributes, ports or settings that now belong to another endpoint are skipped,
+    /// as is an owner who has since been deleted.
+    /// Returns the restored endpoint id, or None if the archive entry does not exist.
+    pub fn restore_archived_endpoint(conn: &Connection, archive_id: i64) -> Result<Option<i64>> {
+        let Some(snapshot) = conn
+            .query_row(
+                "SELECT snapshot FROM archived_endpoints WHERE id = ?1",
+                [archive_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let snapshot: Value = serde_json::from_str(&snapshot).unwrap_or(Value::Null);
+        let Some(mut endpoint) = snapshot
+            .get("endpoint")
+            .and_then(|v| v.as_object())
+            .cloned()
+        else {
+            return Err(rusqlite::Error::InvalidQuery);
+        };
+
+        let tx = conn.unchecked_transaction()?;
+
+        let original_id = endpoint.get("id").and_then(|v| v.as_i64());
+        let id_taken = match original_id {
+            Some(id) => tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM endpoints WHERE id = ?1)",
+                [id],
+                |row| row.get::<_, bool>(0),
+            )?,
+            None => true,
+        };
+        if id_taken {
+            endpoint.remove("id");
+        }
+        insert_json_row(&tx, "endpoints", &endpoint)?;
+        let endpoint_id = match original_id {
+            Some(id) if !id_taken => id,
+            _ => tx.last_insert_rowid(),
+        };
+
+        for row in json_rows(snapshot.get("attributes")) {
+            insert_json_row_or_ignore(&tx, "endpoint_attributes", &reparent_row(row, endpoint_id))?;
+        }
+        for row in json_rows(snapshot.get("open_ports")) {
+            insert_json_row_or_ignore(&tx, "open_ports", &reparent_row(row, endpoint_id))?;
+        }
+        for row in json_rows(snapshot.get("scan_results")) {
+            insert_json_row(&tx, "scan_results", &reparent_row(row, endpoint_id))?;
+        }
+        for (key, table) in [
+            ("tags", "endpoint_tags"),
+            ("trust", "endpoint_trust"),
+            ("appearance", "endpoint_appearance"),
+            ("snmp_credentials", "snmp_credentials"),
+            ("power", "endpoint_power"),
+        ] {
+            for row in json_rows(snapshot.get(key)) {
+                insert_json_row_or_ignore(&tx, table, &reparent_row(row, endpoint_id))?;
+            }
+        }
+        // The owner may have been deleted while the endpoint was archived
+        for row in json_rows(snapshot.get("owners")) {
+            let person_exists = match row.get("person_id").and_then(|v| v.as_i64()) {
+                Some(person_id) => tx.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM people WHERE id = ?1)",
+                    [person_id],
+                    |row| row.get::<_, bool>(0),
+                )?,
+                None => false,
+            };
+            if person_exists {
+                insert_json_row_or_ignore(&tx, "endpoint_owners", &reparent_row(row, endpoint_id))?;
+            }
+        }
+
+        // Delete nulled these references; only reclaim ones nothing else has taken over
+        for id in json_ids(snapshot.get("communications_src")) {
+            tx.execute(
+                "UPDATE OR IGNORE communications SET src_endpoint_id = ?1
+                 WHERE id = ?2 AND src_endpoint_id IS NULL",
+                params![endpoint_id, id],
+            )?;
+        }
+        for id in json_ids(snapshot.get("communications_dst")) {
+            tx.execute(
+                "UPDATE OR IGNORE communications SET dst_endpoint_id = ?1
+                 WHERE id = ?2 AND dst_endpoint_id IS NULL",
+                params![endpoint_id, id],
+            )?;
+        }
+
+        tx.execute("DELETE FROM archived_endpoints WHERE id = ?1", [archive_id])?;
+        tx.commit()?;
+
+        Ok(Some(endpoint_id))
+    }
+
+    /// Permanently remove archived endpoints older than the retention window
+    pub fn purge_archived_endpoints(conn: &Connection, retention_days: i64) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM archived_endpoints WHERE deleted_at < (strftime('%s', 'now') - ?1)",
+            [retention_days * 24 * 60 * 60],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn create_child_tables(conn: &Connection) {
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                 scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL,
+                 response_time_ms INTEGER, details TEXT);",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_archive_and_restore_endpoint() {
+        let conn = new_test_connection();
+        create_child_tables(&conn);
+
+        conn.execute(
+            "INSERT INTO endpoints (created_at, name, custom_name) VALUES (1, 'printer', 'Office Printer')",
+            [],
+        )
+        .unwrap();
+        let endpoint_id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname)
+             VALUES (1, ?1, '00:11:22:33:44:55', '192.168.1.20', 'printer')",
+            [endpoint_id],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO open_ports (endpoint_id, port, last_seen_at) VALUES (?1, 631, 1)",
+            [endpoint_id],
+        )
+        .unwrap();
+
+        conn.execute_batch(&format!(
+            "INSERT INTO endpoint_tags (endpoint_id, tag, created_at) VALUES ({0}, 'office', 1);
+             INSERT INTO endpoint_trust (endpoint_id, trusted_at) VALUES ({0}, 1);
+             INSERT INTO endpoint_appearance (endpoint_id, icon, color) VALUES ({0}, 'printer', '#ff0000');
+             INSERT INTO people (id, name, created_at) VALUES (1, 'Alice', 1);
+             INSERT INTO endpoint_owners (endpoint_id, person_id, assigned_at) VALUES ({0}, 1, 1);
+             INSERT INTO snmp_credentials (endpoint_id, community, updated_at) VALUES ({0}, 'private', 1);
+             INSERT INTO endpoint_power (endpoint_id, watts) VALUES ({0}, 12.5);",
+            endpoint_id
+        ))
+        .unwrap();
+
+        let archive_id = EndPoint::archive_endpoint(&conn, endpoint_id, "Office Printer").unwrap();
+        conn.execute_batch(&format!(
+            "DELETE FROM endpoint_tags WHERE endpoint_id = {0};
+             DELETE FROM endpoint_trust WHERE endpoint_id = {0};
+             DELETE FROM endpoint_appearance WHERE endpoint_id = {0};
+             DELETE FROM endpoint_owners WHERE endpoint_id = {0};
+             DELETE FROM snmp_credentials WHERE endpoint_id = {0};
+             DELETE FROM endpoint_power WHERE endpoint_id = {0};
+             DELETE FROM open_ports WHERE endpoint_id = {0};
+             DELETE FROM endpoint_attributes WHERE endpoint_id = {0};
+             DELETE FROM endpoints WHERE id = {0};",
+            endpoint_id
+        ))
+        .unwrap();
+
+        let archived = EndPoint::get_archived_endpoints(&conn).unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].name.as_deref(), Some("Office Printer"));
+        assert_eq!(archived[0].ips, vec!["192.168.1.20".to_string()]);
+
+        let restored = EndPoint::restore_archived_endpoint(&conn, archive_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored, endpoint_id);
+
+        let custom_name: String = conn
+            .query_row(
+                "SELECT custom_name FROM endpoints WHERE id = ?1",
+                [restored],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(custom_name, "Office Printer");
+
+        let port_count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM open_ports WHERE endpoint_id = ?1",
+                [restored],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(port_count, 1);
+
+        for table in [
+            "endpoint_tags",
+            "endpoint_trust",
+            "endpoint_appearance",
+            "endpoint_owners",
+            "snmp_credentials",
+            "endpoint_power",
+        ] {
+            let count: i64 = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM {} WHERE endpoint_id = ?1", table),
+                    [restored],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(count, 1, "{} not restored", table);
+        }
+
+        assert!(EndPoint::get_archived_endpoints(&conn).unwrap().is_empty());
+        assert!(
+            EndPoint::restore_archived_endpoint(&conn, archive_id)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_purge_archived_endpoints() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO archived_endpoints (endpoint_id, name, deleted_at, snapshot)
+             VALUES (1, 'old', strftime('%s', 'now') - 40 * 86400, '{}'),
+                    (2, 'recent', strftime('%s', 'now') - 86400, '{}')",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(EndPoint::purge_archived_endpoints(&conn, 30).unwrap(), 1);
+        let remaining = EndPoint::get_archived_endpoints(&conn).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name.as_deref(), Some("recent"));
+    }
+}
@@ -0,0 +1,253 @@
+//! IP-to-endpoint identity bindings. ARP/NDP replies pin an IP to a MAC (and therefore
+//! an endpoint) for a lease window, so IP-only scan results (ICMP, SSDP, NetBIOS, SNMP)
+//! attach to whichever device currently holds the address rather than a stale owner.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+use super::EndPoint;
+
+/// Scan types keyed only by IP; these are the ones that can be mis-attributed
+/// when an address moves between devices.
+const IP_KEYED_SCAN_TYPES: &[&str] = &["icmp", "ssdp", "netbios", "snmp"];
+
+/// Outcome of recording an ARP/NDP binding
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BindingChange {
+    /// Endpoint that previously held the IP, if it was a different endpoint
+    pub previous_endpoint_id: Option<i64>,
+    /// Number of IP-keyed scan results moved to the new owner
+    pub reassigned_scan_results: usize,
+}
+
+impl EndPoint {
+    pub fn create_ip_bindings_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ip_bindings (
+                ip TEXT PRIMARY KEY,
+                mac TEXT NOT NULL,
+                endpoint_id INTEGER NOT NULL,
+                source TEXT NOT NULL,
+                first_seen_at INTEGER NOT NULL,
+                last_seen_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_ip_bindings_endpoint ON ip_bindings (endpoint_id);",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Record that `ip` answered ARP/NDP with `mac` (resolved to `endpoint_id`).
+    /// The binding is valid for `lease_seconds` past this sighting. If the IP was
+    /// previously bound to a different endpoint, IP-keyed scan results recorded for
+    /// that endpoint since it was last confirmed at the address are moved to the new owner.
+    pub fn record_ip_binding(
+        conn: &Connection,
+        ip: &str,
+        mac: &str,
+        endpoint_id: i64,
+        source: &str,
+        lease_seconds: i64,
+    ) -> Result<BindingChange> {
+        let now = chrono::Utc::now().timestamp();
+        let previous: Option<(i64, i64)> = conn
+            .query_row(
+                "SELECT endpoint_id, last_seen_at FROM ip_bindings WHERE ip = ?1",
+                [ip],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let mut change = BindingChange::default();
+        match previous {
+            Some((previous_id, _)) if previous_id == endpoint_id => {
+                conn.execute(
+                    "UPDATE ip_bindings SET mac = ?1, source = ?2, last_seen_at = ?3, expires_at = ?4
+                     WHERE ip = ?5",
+                    params![mac, source, now, now + lease_seconds, ip],
+                )?;
+            }
+            _ => {
+                if let Some((previous_id, previous_seen)) = previous {
+                    change.previous_endpoint_id = Some(previous_id);
+                    change.reassigned_scan_results = Self::reassign_ip_scan_results(
+                        conn,
+                        ip,
+                        previous_id,
+                        endpoint_id,
+                        previous_seen,
+                    )?;
+                }
+                conn.execute(
+                    "INSERT OR REPLACE INTO ip_bindings
+                     (ip, mac, endpoint_id, source, first_seen_at, last_seen_at, expires_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6)",
+                    params![ip, mac, endpoint_id, source, now, now + lease_seconds],
+                )?;
+            }
+        }
+        Self::record_ip_history(conn, endpoint_id, ip, Some(mac), source, now)?;
+
+        Ok(change)
+    }
+
+    /// Endpoint currently bound to `ip`, ignoring expired leases
+    pub fn resolve_ip_binding(conn: &Connection, ip: &str) -> Option<i64> {
+        conn.query_row(
+            "SELECT endpoint_id FROM ip_bindings
+             WHERE ip = ?1 AND expires_at > strftime('%s', 'now')",
+            [ip],
+            |row| row.get(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+    }
+
+    /// Endpoint currently bound to any address via `mac`, ignoring expired leases. Lets an
+    /// IPv6 neighbor join the endpoint its IPv4 address was pinned to, even when the MAC
+    /// is randomized and so never used for endpoint lookups.
+    pub fn resolve_mac_binding(conn: &Connection, mac: &str) -> Option<i64> {
+        conn.query_row(
+            "SELECT endpoint_id FROM ip_bindings
+             WHERE mac = ?1 COLLATE NOCASE AND expires_at > strftime('%s', 'now')
+             ORDER BY last_seen_at DESC
+             LIMIT 1",
+            [mac],
+            |row| row.get(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+    }
+
+    /// Move IP-keyed scan results for `ip` from `from_id` to `to_id` when they were
+    /// recorded after `since` (the last time `from_id` was confirmed at the address).
+    fn reassign_ip_scan_results(
+        conn: &Connection,
+        ip: &str,
+        from_id: i64,
+        to_id: i64,
+        since: i64,
+    ) -> Result<usize> {
+        let placeholders = IP_KEYED_SCAN_TYPES
+            .iter()
+            .map(|t| format!("'{}'", t))
+            .collect::<Vec<_>>()
+            .join(",");
+        conn.execute(
+            &format!(
+                "UPDATE scan_results SET endpoint_id = ?1
+                 WHERE endpoint_id = ?2 AND ip = ?3 AND scanned_at > ?4
+                   AND scan_type IN ({})",
+                placeholders
+            ),
+            params![to_id, from_id, ip, since],
+        )
+    }
+
+    /// Drop bindings whose lease expired more than `retention_seconds` ago
+    pub fn purge_expired_ip_bindings(conn: &Connection, retention_seconds: i64) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM ip_bindings WHERE expires_at < (strftime('%s', 'now') - ?1)",
+            [retention_seconds],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn setup() -> Connection {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                 scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL,
+                 response_time_ms INTEGER, details TEXT, ip TEXT);
+             INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'laptop'), (2, 1, 'phone');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_resolve_ip_binding_respects_expiry() {
+        let conn = setup();
+        EndPoint::record_ip_binding(&conn, "192.168.1.10", "aa:aa:aa:aa:aa:01", 1, "arp", 3600)
+            .unwrap();
+        assert_eq!(EndPoint::resolve_ip_binding(&conn, "192.168.1.10"), Some(1));
+
+        conn.execute(
+            "UPDATE ip_bindings SET expires_at = strftime('%s', 'now') - 1",
+            [],
+        )
+        .unwrap();
+        assert_eq!(EndPoint::resolve_ip_binding(&conn, "192.168.1.10"), None);
+    }
+
+    #[test]
+    fn test_resolve_mac_binding() {
+        let conn = setup();
+        EndPoint::record_ip_binding(&conn, "192.168.1.10", "da:aa:aa:aa:aa:01", 1, "arp", 3600)
+            .unwrap();
+        assert_eq!(
+            EndPoint::resolve_mac_binding(&conn, "DA:AA:AA:AA:AA:01"),
+            Some(1)
+        );
+        assert_eq!(
+            EndPoint::resolve_mac_binding(&conn, "da:aa:aa:aa:aa:02"),
+            None
+        );
+
+        conn.execute(
+            "UPDATE ip_bindings SET expires_at = strftime('%s', 'now') - 1",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            EndPoint::resolve_mac_binding(&conn, "da:aa:aa:aa:aa:01"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_binding_change_reassigns_ip_keyed_scan_results() {
+        let conn = setup();
+        EndPoint::record_ip_binding(&conn, "192.168.1.10", "aa:aa:aa:aa:aa:01", 1, "arp", 3600)
+            .unwrap();
+        // Laptop was last confirmed at the address in the past
+        conn.execute("UPDATE ip_bindings SET last_seen_at = 100", [])
+            .unwrap();
+
+        // Pings after the laptop left were recorded against it; the ARP result before it left stays
+        conn.execute_batch(
+            "INSERT INTO scan_results (endpoint_id, scan_type, scanned_at, ip) VALUES
+                 (1, 'icmp', 50, '192.168.1.10'),
+                 (1, 'icmp', 200, '192.168.1.10'),
+                 (1, 'arp', 200, '192.168.1.10'),
+                 (1, 'ssdp', 300, '192.168.1.99');",
+        )
+        .unwrap();
+
+        let change =
+            EndPoint::record_ip_binding(&conn, "192.168.1.10", "bb:bb:bb:bb:bb:02", 2, "arp", 3600)
+                .unwrap();
+        assert_eq!(change.previous_endpoint_id, Some(1));
+        assert_eq!(change.reassigned_scan_results, 1);
+        assert_eq!(EndPoint::resolve_ip_binding(&conn, "192.168.1.10"), Some(2));
+
+        let phone_results: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM scan_results WHERE endpoint_id = 2",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(phone_results, 1);
+    }
+}
@@ -1,202 +1,120 @@
-//! Device classification by MAC and services. Identifies device types (printers, TVs,
-//! phones, gaming consoles, computers) using MAC vendor lookups and open port/service analysis.
+//! Device classification against the compiled-in device rules. `ActiveRules` feeds the
+//! generated data and any runtime rules to the shared checks in `rule_matcher`, which
+//! define the ordered tiers `classify_device_type` consults.
 
-use super::detection::matches_prefix;
 use super::patterns::{
-    APPLIANCE_SERVICES, APPLIANCE_VENDORS, CLASSIFICATION_APPLIANCE, CLASSIFICATION_GAMING,
-    CLASSIFICATION_PHONE, CLASSIFICATION_PRINTER, CLASSIFICATION_SOUNDBAR, CLASSIFICATION_TV,
-    CLASSIFICATION_VIRTUALIZATION, GAMING_VENDORS, GATEWAY_VENDORS, LG_APPLIANCE_PREFIXES,
-    MAC_DESKTOP_SERVICES, PHONE_SERVICES, PRINTER_SERVICES, SOUNDBAR_SERVICES, TV_SERVICES,
-    TV_VENDORS,
+    APPLIANCE_PATTERNS, APPLIANCE_SERVICES, APPLIANCE_VENDORS, GAMING_PATTERNS, GAMING_VENDORS,
+    GATEWAY_VENDORS, LG_APPLIANCE_PREFIXES, MAC_DESKTOP_SERVICES, PHONE_CONDITIONAL,
+    PHONE_PATTERNS, PHONE_PREFIXES, PHONE_SERVICES, PRINTER_PATTERNS, PRINTER_PREFIXES,
+    PRINTER_SERVICES, SMART_HOME_SERVICES, SOUNDBAR_MODEL_PREFIXES, SOUNDBAR_PATTERNS,
+    SOUNDBAR_SERVICES, TV_PATTERNS, TV_PREFIXES, TV_SERVICES, TV_VENDORS, VM_PATTERNS,
 };
+pub(crate) use super::rule_matcher::DeviceTypeTier;
+use super::rule_matcher::{RuleSet, matches_conditional, matches_pattern, matches_prefix};
+use super::runtime_rules::runtime_rules;
 use super::vendor::get_mac_vendor;
 
-/// Check if any MAC address has a vendor in the given list
-fn has_vendor_in_list(macs: &[String], vendors: &[&str]) -> bool {
-    macs.iter()
-        .any(|mac| get_mac_vendor(mac).is_some_and(|v| vendors.contains(&v)))
-}
+/// The generated device rules merged with the runtime rules file
+pub(crate) struct ActiveRules;
 
-/// Check if any MAC address matches known IoT/appliance vendor OUIs
-pub(crate) fn is_appliance_mac(macs: &[String]) -> bool {
-    if has_vendor_in_list(macs, APPLIANCE_VENDORS) {
-        return true;
+fn compiled_patterns(class: &str) -> &'static [&'static str] {
+    match class {
+        "printer" => PRINTER_PATTERNS,
+        "tv" => TV_PATTERNS,
+        "gaming" => GAMING_PATTERNS,
+        "phone" => PHONE_PATTERNS,
+        "vm" => VM_PATTERNS,
+        "soundbar" => SOUNDBAR_PATTERNS,
+        "appliance" => APPLIANCE_PATTERNS,
+        _ => &[],
     }
-    // Check SmartThings sensor MAC prefixes (mapped to Samsung vendor)
-    macs.iter().any(|mac| {
-        let mac_lower = mac.to_lowercase();
-        mac_lower.starts_with("70:2c:1f") || mac_lower.starts_with("28:6d:97")
-    })
-}
-
-/// Check if any MAC address matches known gaming vendor OUIs
-pub(crate) fn is_gaming_mac(macs: &[String]) -> bool {
-    has_vendor_in_list(macs, GAMING_VENDORS)
-}
-
-/// Check if any MAC address matches known TV/streaming vendor OUIs
-pub(crate) fn is_tv_mac(macs: &[String]) -> bool {
-    has_vendor_in_list(macs, TV_VENDORS)
 }
 
-/// Check if any MAC address is from Apple
-pub(crate) fn is_apple_mac(macs: &[String]) -> bool {
-    macs.iter()
-        .any(|mac| get_mac_vendor(mac).is_some_and(|v| v == "Apple"))
+fn compiled_prefixes(class: &str) -> &'static [&'static str] {
+    match class {
+        "printer" => PRINTER_PREFIXES,
+        "tv" => TV_PREFIXES,
+        "phone" => PHONE_PREFIXES,
+        _ => &[],
+    }
 }
 
-/// Check if any MAC address matches known gateway/router vendor OUIs
-pub(crate) fn is_gateway_mac(macs: &[String]) -> bool {
-    has_vendor_in_list(macs, GATEWAY_VENDORS)
+fn compiled_services(class: &str) -> &'static [&'static str] {
+    match class {
+        "appliance" => APPLIANCE_SERVICES,
+        "phone" => PHONE_SERVICES,
+        "soundbar" => SOUNDBAR_SERVICES,
+        "printer" => PRINTER_SERVICES,
+        "tv" => TV_SERVICES,
+        _ => &[],
+    }
 }
 
-/// Check if device is likely a phone based on MAC and services
-/// Apple devices that don't advertise file sharing services are likely iPhones/iPads
-/// Check if hostname indicates a Mac computer (not a phone)
-pub(crate) fn is_mac_computer_hostname(hostname: &str) -> bool {
-    let mac_patterns = [
-        "macbook",
-        "mac-book",
-        "imac",
-        "i-mac",
-        "mac-mini",
-        "macmini",
-        "mac-pro",
-        "macpro",
-        "mac-studio",
-        "macstudio",
-    ];
-    mac_patterns.iter().any(|p| hostname.contains(p))
+fn compiled_vendors(class: &str) -> &'static [&'static str] {
+    match class {
+        "appliance" => APPLIANCE_VENDORS,
+        "gaming" => GAMING_VENDORS,
+        "tv" => TV_VENDORS,
+        "gateway" => GATEWAY_VENDORS,
+        _ => &[],
+    }
 }
 
-pub(crate) fn is_phone_mac(macs: &[String], ips: &[String], hostname: Option<&str>) -> bool {
-    // Only applies to Apple devices (iPhones/iPads)
-    if !is_apple_mac(macs) {
-        return false;
+impl RuleSet for ActiveRules {
+    // Runtime patterns and prefixes of a class add to the compiled patterns
+    fn hostname_pattern(&self, class: &str, hostname: &str) -> bool {
+        matches_pattern(hostname, compiled_patterns(class))
+            || runtime_rules().hostname_matches(class, hostname)
     }
 
-    // Never classify Mac computers as phones based on hostname
-    if let Some(h) = hostname {
-        let lower = h.to_lowercase();
-        if is_mac_computer_hostname(&lower) {
-            return false;
-        }
+    fn hostname_prefix(&self, class: &str, hostname: &str) -> bool {
+        matches_prefix(hostname, compiled_prefixes(class))
     }
 
-    // Check if device advertises any desktop/Mac services
-    for ip_str in ips {
-        let services = crate::network::mdns_lookup::MDnsLookup::get_services(ip_str);
-        for service in &services {
-            if MAC_DESKTOP_SERVICES.contains(&service.as_str()) {
-                // This is a Mac (desktop), not a phone
-                return false;
-            }
-        }
+    fn hostname_conditional(&self, class: &str, hostname: &str) -> bool {
+        class == "phone" && matches_conditional(hostname, PHONE_CONDITIONAL)
     }
 
-    // Apple device without desktop services = likely iPhone/iPad
-    true
-}
-
-/// Check if hostname indicates an LG ThinQ appliance
-pub(crate) fn is_lg_appliance(hostname: &str) -> bool {
-    if matches_prefix(hostname, LG_APPLIANCE_PREFIXES) {
-        return true;
+    fn service_in_class(&self, class: &str, service: &str) -> bool {
+        compiled_services(class).contains(&service)
+            || runtime_rules().service_in_class(class, service)
     }
-    // WM with digit as third character (washer model)
-    if hostname.starts_with("wm")
-        && let Some(c) = hostname.chars().nth(2)
-        && c.is_ascii_digit()
-    {
-        return true;
+
+    fn vendor_in_class(&self, class: &str, vendor: &str) -> bool {
+        compiled_vendors(class).contains(&vendor) || runtime_rules().vendor_in_class(class, vendor)
     }
-    false
-}
 
-/// Service-to-classification mapping, checked in priority order
-const SERVICE_CLASSIFICATIONS: &[(&[&str], &str)] = &[
-    (APPLIANCE_SERVICES, CLASSIFICATION_APPLIANCE),
-    (PHONE_SERVICES, CLASSIFICATION_PHONE),
-    (SOUNDBAR_SERVICES, CLASSIFICATION_SOUNDBAR),
-    (PRINTER_SERVICES, CLASSIFICATION_PRINTER),
-    (TV_SERVICES, CLASSIFICATION_TV),
-];
-
-/// Check mDNS services for device type
-pub(crate) fn classify_by_services(
-    services: &[String],
-    hostname: Option<&str>,
-) -> Option<&'static str> {
-    for service in services {
-        let s = service.as_str();
-        for &(svc_list, classification) in SERVICE_CLASSIFICATIONS {
-            if svc_list.contains(&s) {
-                // Skip phone classification for Mac computers
-                // (they also advertise _companion-link._tcp)
-                if classification == CLASSIFICATION_PHONE
-                    && let Some(h) = hostname
-                    && is_mac_computer_hostname(h)
-                {
-                    continue;
-                }
-                return Some(classification);
-            }
-        }
-    }
-    None
-}
+    fn mac_vendor(&self, mac: &str) -> Option<&str> {
+        get_mac_vendor(mac)
+    }
 
-/// Check if port combination indicates a computer (laptop/desktop)
-/// Computers typically have remote access ports (RDP/VNC) combined with file sharing
-pub(crate) fn is_computer_by_ports(ports: &[u16]) -> bool {
-    let has_remote_access = ports.contains(&3389)  // RDP (Windows Remote Desktop)
-        || ports.contains(&5900)                    // VNC
-        || ports.contains(&22); // SSH
+    fn is_mac_desktop_service(&self, service: &str) -> bool {
+        MAC_DESKTOP_SERVICES.contains(&service)
+    }
 
-    let has_file_sharing = ports.contains(&445)    // SMB (Windows file sharing)
-        || ports.contains(&548)                     // AFP (Apple file sharing)
-        || ports.contains(&139); // NetBIOS
+    fn is_smart_home_service(&self, service: &str) -> bool {
+        SMART_HOME_SERVICES.contains(&service)
+    }
 
-    // Must have both remote access AND file sharing to be classified as computer
-    // This avoids false positives from devices that just have SSH
-    has_remote_access && has_file_sharing
-}
+    fn soundbar_model_prefix(&self, model: &str) -> bool {
+        matches_prefix(model, SOUNDBAR_MODEL_PREFIXES)
+    }
 
-/// Classify by port number
-pub(crate) fn classify_by_port(port: u16) -> Option<&'static str> {
-    match port {
-        // Printer ports
-        9100 | 631 | 515 => Some(CLASSIFICATION_PRINTER),
-        // Gaming console ports (check BEFORE TV ports)
-        9295..=9297 => Some(CLASSIFICATION_GAMING), // PlayStation Remote Play
-        3478..=3480 => Some(CLASSIFICATION_GAMING), // PlayStation Network
-        3074 => Some(CLASSIFICATION_GAMING),        // Xbox Live
-        // TV/Streaming ports
-        8008 | 8009 => Some(CLASSIFICATION_TV), // Chromecast
-        7000 | 7001 | 8001 | 8002 => Some(CLASSIFICATION_TV), // Samsung TV
-        3000 | 3001 => Some(CLASSIFICATION_TV), // LG WebOS
-        6467 | 6466 => Some(CLASSIFICATION_TV), // Roku
-        // VM/Container ports
-        902 | 903 => Some(CLASSIFICATION_VIRTUALIZATION), // VMware ESXi
-        8006 => Some(CLASSIFICATION_VIRTUALIZATION),      // Proxmox
-        2179 => Some(CLASSIFICATION_VIRTUALIZATION),      // Hyper-V
-        2375 | 2376 => Some(CLASSIFICATION_VIRTUALIZATION), // Docker API
-        6443 => Some(CLASSIFICATION_VIRTUALIZATION),      // Kubernetes API
-        10250 => Some(CLASSIFICATION_VIRTUALIZATION),     // Kubelet
-        9000 => Some(CLASSIFICATION_VIRTUALIZATION),      // Portainer
-        _ => None,
+    fn lg_appliance_prefix(&self, hostname: &str) -> bool {
+        matches_prefix(hostname, LG_APPLIANCE_PREFIXES)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::network::endpoint::rule_matcher::*;
 
     #[test]
     fn test_lg_appliance() {
-        assert_eq!(is_lg_appliance("ldf7774st"), true);
-        assert_eq!(is_lg_appliance("wm3900hwa"), true);
-        assert_eq!(is_lg_appliance("dlex3900w"), true);
+        assert!(is_lg_appliance(&ActiveRules, "ldf7774st"));
+        assert!(is_lg_appliance(&ActiveRules, "wm3900hwa"));
+        assert!(is_lg_appliance(&ActiveRules, "dlex3900w"));
     }
 
     #[test]
@@ -218,4 +136,190 @@ mod tests {
         assert_eq!(classify_by_port(80), None);
         assert_eq!(classify_by_port(443), None);
     }
+
+    #[test]
+    fn test_classify_printer() {
+        // Hostname patterns
+        assert!(is_printer_hostname(&ActiveRules, "hp-laserjet-pro"));
+        assert!(is_printer_hostname(&ActiveRules, "canon-mx920"));
+        assert!(is_printer_hostname(&ActiveRules, "epson-wf-7720"));
+        assert!(is_printer_hostname(&ActiveRules, "brother-mfc-9340cdw"));
+        assert!(is_printer_hostname(&ActiveRules, "npi123456"));
+        assert!(is_printer_hostname(&ActiveRules, "brn001122334455"));
+
+        // Non-printers
+        assert!(!is_printer_hostname(&ActiveRules, "my-laptop"));
+        assert!(!is_printer_hostname(&ActiveRules, "iphone"));
+    }
+
+    #[test]
+    fn test_classify_tv() {
+        // Hostname patterns
+        assert!(is_tv_hostname(&ActiveRules, "samsung-tv"));
+        assert!(is_tv_hostname(&ActiveRules, "roku-ultra"));
+        assert!(is_tv_hostname(&ActiveRules, "chromecast-living-room"));
+        assert!(is_tv_hostname(&ActiveRules, "appletv"));
+        assert!(is_tv_hostname(&ActiveRules, "firetv-stick"));
+        assert!(is_tv_hostname(&ActiveRules, "the-frame"));
+
+        // Roku serial number hostnames (e.g., YN00NJ468680)
+        assert!(is_tv_hostname(&ActiveRules, "YN00NJ468680"));
+        assert!(is_tv_hostname(&ActiveRules, "yn00nj468680")); // lowercase
+        assert!(is_tv_hostname(&ActiveRules, "YK00KM123456"));
+
+        // Non-TVs (lg-* removed - too generic, matches soundbars)
+        assert!(!is_tv_hostname(&ActiveRules, "lg-oled55")); // Use SSDP model instead
+        assert!(!is_tv_hostname(&ActiveRules, "my-laptop"));
+        assert!(!is_tv_hostname(&ActiveRules, "printer"));
+    }
+
+    #[test]
+    fn test_roku_serial_number_detection() {
+        // Valid Roku serial numbers: 2 letters + 2 digits + 2 letters + 6 digits (12 chars)
+        assert!(is_roku_serial_number("YN00NJ468680"));
+        assert!(is_roku_serial_number("YK00KM123456"));
+        assert!(is_roku_serial_number("AB12CD345678"));
+
+        // Valid Roku serial numbers: 2 letters + 2 digits + 2 letters + 4 digits (10 chars)
+        assert!(is_roku_serial_number("BR23AM1691"));
+        assert!(is_roku_serial_number("AB12CD3456"));
+        assert!(is_roku_serial_number("XY99ZZ0000"));
+
+        // Invalid patterns
+        assert!(!is_roku_serial_number("YN00NJ46868")); // 11 chars - invalid length
+        assert!(!is_roku_serial_number("YN00NJ4686801")); // Too long (13 chars)
+        assert!(!is_roku_serial_number("1N00NJ468680")); // First char not letter
+        assert!(!is_roku_serial_number("YNA0NJ468680")); // Third char not digit
+        assert!(!is_roku_serial_number("YN0ANJ468680")); // Fourth char not digit
+        assert!(!is_roku_serial_number("YN001J468680")); // Fifth char not letter
+        assert!(!is_roku_serial_number("YN00N1468680")); // Sixth char not letter
+        assert!(!is_roku_serial_number("YN00NJA68680")); // Seventh char not digit
+        assert!(!is_roku_serial_number("samsung-tv")); // Wrong format
+        assert!(!is_roku_serial_number("7105X")); // Roku model, not serial
+        assert!(!is_roku_serial_number("BR23AM169")); // 9 chars - too short
+    }
+
+    #[test]
+    fn test_roku_tv_model_detection() {
+        // Roku TV platform identifiers (TCL, Hisense TVs running Roku OS)
+        assert!(is_roku_tv_model("7105X"));
+        assert!(is_roku_tv_model("7000X"));
+        assert!(is_roku_tv_model("6500X"));
+        assert!(is_roku_tv_model("3800X"));
+        assert!(is_roku_tv_model("4200")); // Without X suffix
+        assert!(is_roku_tv_model("8500X"));
+
+        // Should be recognized as TV
+        assert!(is_tv_model("7105X"));
+        assert!(is_tv_model("7000X"));
+
+        // Non-Roku TV models
+        assert!(!is_roku_tv_model("HW-MS750")); // Samsung soundbar
+        assert!(!is_roku_tv_model("OLED55C3")); // LG TV (different format)
+        assert!(!is_roku_tv_model("12345X")); // Too many digits
+        assert!(!is_roku_tv_model("710X")); // Only 3 digits
+        assert!(!is_roku_tv_model("7105Y")); // Wrong suffix
+
+        // Roku serial numbers should also be detected as Roku TV models
+        assert!(is_roku_tv_model("YN00NJ468680"));
+        assert!(is_roku_tv_model("yn00nj468680")); // lowercase
+    }
+
+    #[test]
+    fn test_classify_gaming() {
+        assert!(is_gaming_hostname(&ActiveRules, "xbox-series-x"));
+        assert!(is_gaming_hostname(&ActiveRules, "playstation-5"));
+        assert!(is_gaming_hostname(&ActiveRules, "nintendo-switch"));
+        assert!(is_gaming_hostname(&ActiveRules, "steamdeck"));
+
+        assert!(!is_gaming_hostname(&ActiveRules, "my-pc"));
+    }
+
+    #[test]
+    fn test_classify_phone() {
+        assert!(is_phone_hostname(&ActiveRules, "iphone-14-pro"));
+        assert!(is_phone_hostname(&ActiveRules, "ipad-mini"));
+        assert!(is_phone_hostname(&ActiveRules, "galaxy-s23"));
+        assert!(is_phone_hostname(&ActiveRules, "pixel-7"));
+        assert!(is_phone_hostname(&ActiveRules, "sm-g991u"));
+        assert!(is_phone_hostname(&ActiveRules, "oneplus-11"));
+        assert!(is_phone_hostname(&ActiveRules, "moto-g-power"));
+
+        // Should NOT match TV variants
+        assert!(!is_phone_hostname(&ActiveRules, "galaxy-tv"));
+        assert!(!is_phone_hostname(&ActiveRules, "androidtv"));
+    }
+
+    #[test]
+    fn test_classify_vm() {
+        assert!(is_vm_hostname(&ActiveRules, "vmware-esxi-01"));
+        assert!(is_vm_hostname(&ActiveRules, "proxmox-server"));
+        assert!(is_vm_hostname(&ActiveRules, "docker-host"));
+        assert!(is_vm_hostname(&ActiveRules, "kubernetes-node-1"));
+        assert!(is_vm_hostname(&ActiveRules, "vm-ubuntu-22"));
+        assert!(is_vm_hostname(&ActiveRules, "webserver-vm"));
+
+        assert!(!is_vm_hostname(&ActiveRules, "my-laptop"));
+    }
+
+    #[test]
+    fn test_classify_soundbar() {
+        assert!(is_soundbar_hostname(&ActiveRules, "sonos-beam"));
+        assert!(is_soundbar_hostname(&ActiveRules, "bose-soundbar-700"));
+        assert!(is_soundbar_hostname(&ActiveRules, "samsung-sound-plus"));
+        assert!(is_soundbar_hostname(&ActiveRules, "jbl-bar-5.1"));
+
+        assert!(!is_soundbar_hostname(&ActiveRules, "samsung-tv"));
+    }
+
+    #[test]
+    fn test_is_tv_model() {
+        // Samsung QLED TVs
+        assert!(is_tv_model("QN43LS03TAFXZA")); // The Frame
+        assert!(is_tv_model("QN65Q80AAFXZA")); // QLED Q80A
+        assert!(is_tv_model("QN55QN90AAFXZA")); // Neo QLED
+
+        // Samsung LED TVs
+        assert!(is_tv_model("UN55TU8000FXZA"));
+        assert!(is_tv_model("UA43AU7000KXXS"));
+
+        // Samsung The Frame specific
+        assert!(is_tv_model("LS03T"));
+        assert!(is_tv_model("QN43LS01TAFXZA")); // The Serif
+
+        // LG OLED TVs
+        assert!(is_tv_model("OLED55C3PUA"));
+        assert!(is_tv_model("OLED65G3PUA"));
+
+        // LG NanoCell TVs
+        assert!(is_tv_model("NANO75UPA"));
+
+        // Sony Bravia
+        assert!(is_tv_model("XR-55A80J"));
+        assert!(is_tv_model("KD-55X80K"));
+        assert!(is_tv_model("Sony Bravia"));
+
+        // Vizio
+        assert!(is_tv_model("Vizio M-Series"));
+
+        // Generic patterns
+        assert!(is_tv_model("Samsung The Frame"));
+        assert!(is_tv_model("Samsung TV"));
+
+        // Should NOT match
+        assert!(!is_tv_model("HW-MS750")); // Soundbar
+        assert!(!is_tv_model("Galaxy S23")); // Phone
+        assert!(!is_tv_model("MacBook Pro")); // Computer
+        assert!(!is_tv_model("random-device"));
+    }
+
+    #[test]
+    fn test_classify_appliance() {
+        assert!(is_appliance_hostname(&ActiveRules, "lg-dishwasher"));
+        assert!(is_appliance_hostname(&ActiveRules, "samsung-washer"));
+        assert!(is_appliance_hostname(&ActiveRules, "whirlpool-dryer"));
+        assert!(is_appliance_hostname(&ActiveRules, "bosch-dishwasher-500"));
+
+        assert!(!is_appliance_hostname(&ActiveRules, "my-laptop"));
+    }
 }
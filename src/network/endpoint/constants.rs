@@ -1,16 +1,12 @@
-//! Shared constants and caches. Provides DNS and gateway caches, hostname suffix stripping,
+//! Shared constants and caches. Provides the gateway cache, hostname suffix stripping,
 //! UUID detection, MAC address analysis, and local network utility functions.
 
 use pnet::datalink::interfaces;
 use pnet::ipnetwork::IpNetwork;
-use std::collections::HashMap;
 use std::net::IpAddr;
 use std::sync::{LazyLock, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
-// Simple DNS cache to avoid repeated slow lookups
-pub(crate) static DNS_CACHE: LazyLock<Mutex<HashMap<String, (String, Instant)>>> =
-    LazyLock::new(|| Mutex::new(HashMap::new()));
 pub(crate) static GATEWAY_INFO: LazyLock<Mutex<Option<(String, Instant)>>> =
     LazyLock::new(|| Mutex::new(None));
 
@@ -104,7 +100,6 @@ pub(crate) fn get_local_networks() -> &'static Vec<IpNetwork> {
     })
 }
 
-pub(crate) const DNS_CACHE_TTL: Duration = Duration::from_secs(300); // 5 minutes
 pub(crate) const GATEWAY_CACHE_TTL: Duration = Duration::from_secs(60); // 1 minute
 
 /// Check if a MAC address is locally administered (randomized/private)
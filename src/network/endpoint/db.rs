@@ -153,6 +153,47 @@ impl EndPoint {
             conn.execute("ALTER TABLE endpoints ADD COLUMN netbios_name TEXT", [])?;
         }
 
+        // Migration: Add workgroup column for the NetBIOS workgroup or domain
+        let has_workgroup: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('endpoints') WHERE name = 'workgroup'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !has_workgroup {
+            conn.execute("ALTER TABLE endpoints ADD COLUMN workgroup TEXT", [])?;
+        }
+
+        // Migration: Add display_name column, kept current by triggers (see display_name.rs)
+        let has_display_name: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('endpoints') WHERE name = 'display_name'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !has_display_name {
+            conn.execute("ALTER TABLE endpoints ADD COLUMN display_name TEXT", [])?;
+        }
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_endpoints_display_name ON endpoints (display_name COLLATE NOCASE);",
+            [],
+        )?;
+
+        // Migration: Add name_source column recording which protocol supplied the name
+        // (see naming.rs)
+        let has_name_source: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('endpoints') WHERE name = 'name_source'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(false);
+        if !has_name_source {
+            conn.execute("ALTER TABLE endpoints ADD COLUMN name_source TEXT", [])?;
+        }
+
         // Create internet_destinations table for tracking external hosts
         conn.execute(
             "CREATE TABLE IF NOT EXISTS internet_destinations (
@@ -175,6 +216,12 @@ impl EndPoint {
             [],
         )?;
 
+        Self::create_merge_history_table(conn)?;
+        Self::create_archived_endpoints_table(conn)?;
+        Self::create_ip_bindings_table(conn)?;
+        Self::create_ip_history_table(conn)?;
+        Self::create_device_identities_table(conn)?;
+
         Ok(())
     }
 
@@ -289,7 +336,7 @@ impl EndPoint {
     pub fn get_all_auto_device_types(conn: &Connection) -> HashMap<String, String> {
         let mut map = HashMap::new();
         if let Ok(mut stmt) = conn.prepare(
-            "SELECT COALESCE(custom_name, name), auto_device_type FROM endpoints WHERE auto_device_type IS NOT NULL AND auto_device_type != '' AND (name IS NOT NULL OR custom_name IS NOT NULL)",
+            "SELECT display_name, auto_device_type FROM endpoints WHERE auto_device_type IS NOT NULL AND auto_device_type != '' AND display_name IS NOT NULL",
         ) && let Ok(rows) = stmt.query_map([], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
         }) {
@@ -382,11 +429,11 @@ impl EndPoint {
         .ok()
     }
 
-    /// Get all manual device types as a HashMap
+    /// Get all manual device types as a HashMap keyed by display_name
     pub fn get_all_manual_device_types(conn: &Connection) -> HashMap<String, String> {
         let mut map = HashMap::new();
         if let Ok(mut stmt) = conn.prepare(
-            "SELECT COALESCE(custom_name, name), manual_device_type FROM endpoints WHERE manual_device_type IS NOT NULL AND (name IS NOT NULL OR custom_name IS NOT NULL)",
+            "SELECT display_name, manual_device_type FROM endpoints WHERE manual_device_type IS NOT NULL AND display_name IS NOT NULL",
         ) && let Ok(rows) = stmt.query_map([], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
         }) {
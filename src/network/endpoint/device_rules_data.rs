@@ -276,6 +276,12 @@ pub(crate) const MAC_DESKTOP_SERVICES: &[&str] = &[
     "_sftp-ssh._tcp",
 ];
 
+pub(crate) const SMART_HOME_SERVICES: &[&str] = &[
+    "_matter._tcp",
+    "_matterc._udp",
+    "_meshcop._udp",
+];
+
 pub(crate) const SOUNDBAR_MODEL_PREFIXES: &[&str] = &[
     "hw-",
     "spk-",
@@ -306,6 +312,20 @@ pub(crate) const LG_APPLIANCE_PREFIXES: &[&str] = &[
     "lrmv",
 ];
 
+pub(crate) const COMPONENT_VENDORS: &[&str] = &[
+    "Espressif",
+    "Tuya",
+    "Realtek",
+    "MediaTek",
+    "Qualcomm",
+    "Broadcom",
+    "Marvell",
+    "USI",
+    "Wisol",
+    "Murata",
+    "AzureWave",
+];
+
 pub(crate) const SAMSUNG_TV_SERIES: &[(&str, &str)] = &[
     ("ls03", "The Frame"),
     ("ls01", "The Serif"),
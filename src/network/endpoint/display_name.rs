@@ -0,0 +1,374 @@
+//! Endpoint display names. One rule decides what an endpoint is called everywhere: its
+//! custom name, else a usable discovered name, else its lowest usable hostname, else its
+//! lowest IP. `display_name` is the rule in Rust and `display_name_sql` the same rule in
+//! SQL; triggers use the latter to keep the cached `endpoints.display_name` column current,
+//! so queries and exports read that column instead of re-deriving names.
+
+use rusqlite::{Connection, Result};
+
+use super::EndPoint;
+
+const IPV4_GLOB: &str = "[0-9]*.[0-9]*.[0-9]*.[0-9]*";
+const UUID_GLOB: &str = "[0-9a-fA-F]*-[0-9a-fA-F]*-[0-9a-fA-F]*-[0-9a-fA-F]*-[0-9a-fA-F]*";
+
+/// Triggers that keep `endpoints.display_name` in sync, as (name, event, endpoint ids to refresh)
+const DISPLAY_NAME_TRIGGERS: &[(&str, &str, &str)] = &[
+    (
+        "trg_endpoints_display_name_insert",
+        "AFTER INSERT ON endpoints",
+        "NEW.id",
+    ),
+    (
+        "trg_endpoints_display_name_update",
+        "AFTER UPDATE OF name, custom_name ON endpoints",
+        "NEW.id",
+    ),
+    (
+        "trg_attributes_display_name_insert",
+        "AFTER INSERT ON endpoint_attributes",
+        "NEW.endpoint_id",
+    ),
+    (
+        "trg_attributes_display_name_update",
+        "AFTER UPDATE OF endpoint_id, hostname, ip ON endpoint_attributes",
+        "OLD.endpoint_id, NEW.endpoint_id",
+    ),
+    (
+        "trg_attributes_display_name_delete",
+        "AFTER DELETE ON endpoint_attributes",
+        "OLD.endpoint_id",
+    ),
+];
+
+/// SQLite GLOB subset used by the naming rule: `*` and `[...]` classes with ranges
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        Some(('[', rest)) => {
+            let Some(end) = rest.iter().position(|&c| c == ']') else {
+                return false;
+            };
+            let Some((&c, text_rest)) = text.split_first() else {
+                return false;
+            };
+            let class = &rest[..end];
+            let mut i = 0;
+            let mut matched = false;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched && glob_match(&rest[end + 1..], text_rest)
+        }
+        Some((p, rest)) => text.first() == Some(p) && glob_match(rest, &text[1..]),
+    }
+}
+
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match(&pattern, &text)
+}
+
+/// Whether a discovered name or hostname can be shown as-is
+/// (not empty, not an IPv4/IPv6 address, not a UUID)
+fn is_usable_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(':')
+        && !glob(IPV4_GLOB, name)
+        && (name.chars().count() != 36 || !glob(UUID_GLOB, name))
+}
+
+/// SQL form of `is_usable_name` for `column`
+fn usable_name_sql(column: &str) -> String {
+    format!(
+        "{column} IS NOT NULL AND {column} != '' AND {column} NOT LIKE '%:%' \
+         AND {column} NOT GLOB '{IPV4_GLOB}' \
+         AND NOT (LENGTH({column}) = 36 AND {column} GLOB '{UUID_GLOB}')"
+    )
+}
+
+/// Resolve an endpoint's display name from its fields and attributes
+pub fn display_name(
+    custom_name: Option<&str>,
+    name: Option<&str>,
+    hostnames: &[&str],
+    ips: &[&str],
+) -> Option<String> {
+    custom_name
+        .or(name.filter(|n| is_usable_name(n)))
+        .or(hostnames
+            .iter()
+            .copied()
+            .filter(|h| is_usable_name(h))
+            .min())
+        .or(ips.iter().copied().filter(|ip| !ip.is_empty()).min())
+        .map(String::from)
+}
+
+/// SQL expression computing `display_name` for the endpoints row aliased as `alias`
+pub fn display_name_sql(alias: &str) -> String {
+    format!(
+        "COALESCE({alias}.custom_name,
+            CASE WHEN {name_ok} THEN {alias}.name END,
+            (SELECT MIN(hostname) FROM endpoint_attributes
+             WHERE endpoint_id = {alias}.id AND {hostname_ok}),
+            (SELECT MIN(ip) FROM endpoint_attributes
+             WHERE endpoint_id = {alias}.id AND ip IS NOT NULL AND ip != ''))",
+        name_ok = usable_name_sql(&format!("{alias}.name")),
+        hostname_ok = usable_name_sql("hostname"),
+    )
+}
+
+impl EndPoint {
+    /// (Re)create the triggers maintaining `endpoints.display_name` and recompute it for
+    /// every endpoint, so rows written before a rule change pick up the current rule.
+    /// Requires the endpoints and endpoint_attributes tables.
+    pub fn install_display_name_triggers(conn: &Connection) -> Result<()> {
+        let refresh = format!(
+            "UPDATE endpoints SET display_name =
+                (SELECT {} FROM endpoints e WHERE e.id = endpoints.id)",
+            display_name_sql("e")
+        );
+
+        for (trigger, event, ids) in DISPLAY_NAME_TRIGGERS {
+            conn.execute(&format!("DROP TRIGGER IF EXISTS {}", trigger), [])?;
+            conn.execute(
+                &format!(
+                    "CREATE TRIGGER {trigger} {event} BEGIN
+                        {refresh} WHERE id IN ({ids});
+                    END"
+                ),
+                [],
+            )?;
+        }
+
+        conn.execute(&refresh, [])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_usable_name() {
+        assert!(is_usable_name("office-printer"));
+        assert!(is_usable_name("1password-host"));
+        assert!(!is_usable_name(""));
+        assert!(!is_usable_name("192.168.1.10"));
+        assert!(!is_usable_name("fe80::1"));
+        assert!(!is_usable_name("34887b21-9413-022c-352a-67966809b46c"));
+    }
+
+    #[test]
+    fn test_display_name_priority() {
+        assert_eq!(
+            display_name(
+                Some("Den TV"),
+                Some("lgwebostv"),
+                &["tv.local"],
+                &["10.0.0.5"]
+            ),
+            Some("Den TV".to_string())
+        );
+        assert_eq!(
+            display_name(None, Some("10.0.0.5"), &["zeta", "alpha", "10.0.0.5"], &[]),
+            Some("alpha".to_string())
+        );
+        assert_eq!(
+            display_name(None, None, &[], &["10.0.0.9", "10.0.0.5"]),
+            Some("10.0.0.5".to_string())
+        );
+        assert_eq!(display_name(None, Some(""), &[], &[]), None);
+    }
+
+    /// The cached column (SQL rule) must agree with the Rust rule
+    #[test]
+    fn test_display_name_column_matches_rust_rule() {
+        let conn = new_test_connection();
+        // (custom_name, name, hostnames, ips)
+        type Case<'a> = (
+            Option<&'a str>,
+            Option<&'a str>,
+            &'a [&'a str],
+            &'a [&'a str],
+        );
+        let cases: &[Case] = &[
+            (
+                Some("Den TV"),
+                Some("lgwebostv"),
+                &["tv.local"],
+                &["10.0.0.5"],
+            ),
+            (None, Some("nas"), &["nas.local"], &["10.0.0.6"]),
+            (
+                None,
+                Some("34887b21-9413-022c-352a-67966809b46c"),
+                &["zeta", "alpha"],
+                &["10.0.0.7"],
+            ),
+            (
+                None,
+                Some("10.0.0.8"),
+                &["10.0.0.8", "fe80::1"],
+                &["10.0.0.8"],
+            ),
+            (None, None, &[], &[]),
+        ];
+
+        for (i, (custom_name, name, hostnames, ips)) in cases.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO endpoints (created_at, name, custom_name) VALUES (?1, ?2, ?3)",
+                rusqlite::params![i as i64, name, custom_name],
+            )
+            .unwrap();
+            let id = conn.last_insert_rowid();
+            for hostname in hostnames.iter() {
+                conn.execute(
+                    "INSERT INTO endpoint_attributes (created_at, endpoint_id, hostname, ip)
+                     VALUES (1, ?1, ?2, '')",
+                    rusqlite::params![id, hostname],
+                )
+                .unwrap();
+            }
+            for ip in ips.iter() {
+                conn.execute(
+                    "INSERT INTO endpoint_attributes (created_at, endpoint_id, ip) VALUES (1, ?1, ?2)",
+                    rusqlite::params![id, ip],
+                )
+                .unwrap();
+            }
+
+            let cached: Option<String> = conn
+                .query_row(
+                    "SELECT display_name FROM endpoints WHERE id = ?1",
+                    [id],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(
+                cached,
+                display_name(*custom_name, *name, hostnames, ips),
+                "case {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_name_follows_renames() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (created_at, name) VALUES (1, '10.0.0.5')",
+            [],
+        )
+        .unwrap();
+        let id = conn.last_insert_rowid();
+        let cached = |conn: &Connection| -> Option<String> {
+            conn.query_row(
+                "SELECT display_name FROM endpoints WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(cached(&conn), None);
+
+        conn.execute(
+            "INSERT INTO endpoint_attributes (created_at, endpoint_id, ip, hostname)
+             VALUES (1, ?1, '10.0.0.5', 'printer')",
+            [id],
+        )
+        .unwrap();
+        assert_eq!(cached(&conn).as_deref(), Some("printer"));
+
+        conn.execute(
+            "UPDATE endpoints SET custom_name = 'Office Printer' WHERE id = ?1",
+            [id],
+        )
+        .unwrap();
+        assert_eq!(cached(&conn).as_deref(), Some("Office Printer"));
+
+        conn.execute(
+            "UPDATE endpoints SET custom_name = NULL WHERE id = ?1",
+            [id],
+        )
+        .unwrap();
+        conn.execute(
+            "DELETE FROM endpoint_attributes WHERE endpoint_id = ?1",
+            [id],
+        )
+        .unwrap();
+        assert_eq!(cached(&conn), None);
+    }
+
+    #[test]
+    fn test_display_name_follows_moved_attributes() {
+        let conn = new_test_connection();
+        for name in ["10.0.0.5", "10.0.0.6"] {
+            conn.execute(
+                "INSERT INTO endpoints (created_at, name) VALUES (1, ?1)",
+                [name],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO endpoint_attributes (created_at, endpoint_id, ip, hostname)
+             VALUES (1, 1, '10.0.0.5', 'nas'), (1, 2, '10.0.0.6', '')",
+            [],
+        )
+        .unwrap();
+        let cached = |id: i64| -> Option<String> {
+            conn.query_row(
+                "SELECT display_name FROM endpoints WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(cached(1).as_deref(), Some("nas"));
+        assert_eq!(cached(2).as_deref(), Some("10.0.0.6"));
+
+        // Merging moves attributes: both the old and the new owner are refreshed
+        conn.execute(
+            "UPDATE endpoint_attributes SET endpoint_id = 2 WHERE endpoint_id = 1",
+            [],
+        )
+        .unwrap();
+        assert_eq!(cached(1), None);
+        assert_eq!(cached(2).as_deref(), Some("nas"));
+
+        conn.execute("UPDATE endpoints SET name = 'storage' WHERE id = 2", [])
+            .unwrap();
+        assert_eq!(cached(2).as_deref(), Some("storage"));
+    }
+
+    #[test]
+    fn test_install_display_name_triggers_backfills() {
+        let conn = new_test_connection();
+        conn.execute(
+            "INSERT INTO endpoints (created_at, name) VALUES (1, 'nas')",
+            [],
+        )
+        .unwrap();
+        // A row written under an older rule, or before the triggers existed
+        conn.execute("UPDATE endpoints SET display_name = 'stale'", [])
+            .unwrap();
+
+        EndPoint::install_display_name_triggers(&conn).unwrap();
+        EndPoint::install_display_name_triggers(&conn).unwrap();
+        let cached: Option<String> = conn
+            .query_row("SELECT display_name FROM endpoints", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(cached.as_deref(), Some("nas"));
+    }
+}
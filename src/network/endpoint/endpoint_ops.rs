@@ -1,34 +1,33 @@
 //! Core endpoint operations. Handles endpoint CRUD in SQLite, device type classification,
 //! DNS hostname resolution, and network locality checks.
 
-use dns_lookup::{get_hostname, lookup_addr};
+use dns_lookup::get_hostname;
 use pnet::datalink::interfaces;
 use rusqlite::{Connection, Result, params};
 use std::net::IpAddr;
-use std::time::Instant;
 
+use crate::db::{
+    reassign_bandwidth_quotas, reassign_capture_exclusions, reassign_classification_feedback,
+    reassign_endpoint_appearance, reassign_endpoint_links, reassign_endpoint_owner,
+    reassign_endpoint_tags, reassign_energy, reassign_host_keys, reassign_port_policies,
+    reassign_printer_readings, reassign_smb_shares, reassign_snmp_credential,
+    reassign_storage_health, reassign_type_baseline_deviations, reassign_upnp_events,
+    reassign_ups_readings, reassign_usage, reassign_web_info, reassign_webhook_endpoints,
+};
+use crate::network::blocklist::reassign_blocklist_hits;
+use crate::network::dns_audit::reassign_dns_server_usage;
 use crate::network::endpoint_attribute::EndPointAttribute;
-use crate::network::mdns_lookup::MDnsLookup;
+use crate::network::hostname_resolver::HostnameResolver;
+use crate::network::passive_dns::lookup_dns_hostname;
 
 use super::EndPoint;
-use super::classification::{
-    classify_by_port, classify_by_services, is_appliance_mac, is_computer_by_ports, is_gaming_mac,
-    is_gateway_mac, is_lg_appliance, is_phone_mac, is_tv_mac,
-};
+use super::classification::{ActiveRules, DeviceTypeTier};
 use super::constants::{
-    DNS_CACHE, DNS_CACHE_TTL, extract_mac_from_ipv6_eui64, get_local_networks, is_ipv6_link_local,
+    extract_mac_from_ipv6_eui64, get_local_networks, is_ipv6_link_local,
     is_locally_administered_mac, is_valid_display_name, strip_local_suffix,
 };
 use super::model::get_model_from_mac;
-use super::detection::{
-    is_appliance_hostname, is_gaming_hostname, is_phone_hostname, is_printer_hostname,
-    is_soundbar_hostname, is_soundbar_model, is_tv_hostname, is_tv_model, is_vm_hostname,
-};
-use super::patterns::{
-    CLASSIFICATION_APPLIANCE, CLASSIFICATION_COMPUTER, CLASSIFICATION_GAMING,
-    CLASSIFICATION_GATEWAY, CLASSIFICATION_PHONE, CLASSIFICATION_PRINTER, CLASSIFICATION_SOUNDBAR,
-    CLASSIFICATION_TV, CLASSIFICATION_VIRTUALIZATION,
-};
+use super::naming::NameSource;
 use super::types::{EndpointData, InsertEndpointError};
 
 impl EndPoint {
@@ -42,100 +41,21 @@ impl EndPoint {
         macs: &[String],
         model: Option<&str>,
     ) -> Option<&'static str> {
+        // mDNS service advertisements from ALL IPs
+        let services: Vec<String> = ips
+            .iter()
+            .flat_map(|ip| crate::network::mdns_lookup::MDnsLookup::get_services(ip))
+            .collect();
         // Pre-compute lowercase hostname once
         let lower_hostname = hostname.map(|h| h.to_lowercase());
-        let lower = lower_hostname.as_deref();
-
-        // Check SSDP/UPnP model first - most reliable for identifying device type
-        if let Some(m) = model
-            && is_soundbar_model(m)
-        {
-            return Some(CLASSIFICATION_SOUNDBAR);
-        }
-
-        // Check for TV models (Samsung Frame, QLED, LG OLED, etc.)
-        if let Some(m) = model
-            && is_tv_model(m)
-        {
-            return Some(CLASSIFICATION_TV);
-        }
-
-        // Check for LG ThinQ appliances FIRST (they advertise AirPlay but aren't TVs)
-        if let Some(h) = lower
-            && is_lg_appliance(h)
-        {
-            return Some(CLASSIFICATION_APPLIANCE);
-        }
-
-        // Check hostname patterns FIRST - most reliable for user devices
-        // This prevents mDNS services from misclassifying computers/phones as TVs
-        if let Some(h) = lower {
-            // Order matters: check more specific patterns first
-            if is_printer_hostname(h) {
-                return Some(CLASSIFICATION_PRINTER);
-            }
-            if is_phone_hostname(h) {
-                return Some(CLASSIFICATION_PHONE);
-            }
-            if is_gaming_hostname(h) {
-                return Some(CLASSIFICATION_GAMING);
-            }
-            if is_tv_hostname(h) {
-                return Some(CLASSIFICATION_TV);
-            }
-            if is_vm_hostname(h) {
-                return Some(CLASSIFICATION_VIRTUALIZATION);
-            }
-            if is_soundbar_hostname(h) {
-                return Some(CLASSIFICATION_SOUNDBAR);
-            }
-            if is_appliance_hostname(h) {
-                return Some(CLASSIFICATION_APPLIANCE);
-            }
-        }
-
-        // Check mDNS service advertisements for ALL IPs
-        // This catches smart devices that don't have distinctive hostnames
-        for ip_str in ips {
-            let services = crate::network::mdns_lookup::MDnsLookup::get_services(ip_str);
-            if let Some(classification) = classify_by_services(&services, lower) {
-                return Some(classification);
-            }
-        }
-
-        // MAC-based detection (identifies devices by vendor OUI)
-        // Check gateway first - networking equipment vendors
-        if is_gateway_mac(macs) {
-            return Some(CLASSIFICATION_GATEWAY);
-        }
-        // Check phone - Apple devices without desktop services are likely iPhones/iPads
-        if is_phone_mac(macs, ips, lower) {
-            return Some(CLASSIFICATION_PHONE);
-        }
-        if is_gaming_mac(macs) {
-            return Some(CLASSIFICATION_GAMING);
-        }
-        if is_tv_mac(macs) {
-            return Some(CLASSIFICATION_TV);
-        }
-        if is_appliance_mac(macs) {
-            return Some(CLASSIFICATION_APPLIANCE);
-        }
-
-        // Computer detection based on port combinations
-        // RDP (3389) or VNC (5900) combined with file sharing ports indicates a computer
-        if is_computer_by_ports(ports) {
-            return Some(CLASSIFICATION_COMPUTER);
-        }
-
-        // Port-based detection (less reliable, fallback)
-        for &port in ports {
-            if let Some(classification) = classify_by_port(port) {
-                return Some(classification);
-            }
-        }
-
-        None
+        DeviceTypeTier::classify_all(
+            &ActiveRules,
+            lower_hostname.as_deref(),
+            &services,
+            ports,
+            macs,
+            model,
+        )
     }
 
     fn insert_endpoint_with_dhcp(
@@ -257,9 +177,11 @@ impl EndPoint {
         if let Some(ref ip_str) = ip
             && !Self::is_on_local_network(ip_str)
         {
-            // Use hostname if we have one, otherwise use the IP address
+            // Use hostname if we have one, preferring the name a device looked up via DNS,
+            // otherwise use the IP address
             let dest_name = dhcp_hostname
                 .clone()
+                .or_else(|| lookup_dns_hostname(conn, ip_str))
                 .or_else(|| {
                     Self::lookup_hostname(ip.clone(), mac.clone(), protocol.clone(), payload)
                 })
@@ -331,10 +253,16 @@ impl EndPoint {
                 (id, true)
             }
         };
+        let name_source = if dhcp_hostname.is_some() {
+            NameSource::Dhcp
+        } else {
+            NameSource::Other
+        };
         Self::check_and_update_endpoint_name(
             conn,
             endpoint_id,
             hostname.clone().unwrap_or_default(),
+            name_source,
         )?;
 
         // If endpoint still has no valid name, try to derive one from MAC vendor/model rules
@@ -354,10 +282,7 @@ impl EndPoint {
             && Self::is_on_local_network(ip_addr)
         {
             // Only probe for local IPs (remote servers probably won't respond to our mDNS)
-            crate::network::mdns_lookup::MDnsLookup::probe_hostname_async(
-                ip_addr.clone(),
-                endpoint_id,
-            );
+            HostnameResolver::resolve_async(ip_addr.clone());
         }
 
         Ok((endpoint_id, is_new))
@@ -367,31 +292,13 @@ impl EndPoint {
         conn: &Connection,
         endpoint_id: i64,
         hostname: String,
+        source: NameSource,
     ) -> Result<(), InsertEndpointError> {
         // Strip local suffixes like .local, .lan, .home and normalize to lowercase
         let hostname = strip_local_suffix(&hostname).to_lowercase();
 
-        // Only accept valid display names (not empty, not UUID, not IP)
-        if !is_valid_display_name(&hostname) {
-            return Ok(());
-        }
-
-        // Get current name
-        let current_name: String = conn.query_row(
-            "SELECT COALESCE(name, '') FROM endpoints WHERE id = ?",
-            params![endpoint_id],
-            |row| row.get(0),
-        )?;
-
-        // Update if current name is invalid (empty, UUID, IP) and new name is valid
-        let current_is_valid = is_valid_display_name(&current_name);
-        let should_update = !current_is_valid;
-
-        if should_update {
-            conn.execute(
-                "UPDATE endpoints SET name = ? WHERE id = ?",
-                params![hostname, endpoint_id],
-            )?;
+        // Takes the name only if it's valid and outranks where the current name came from
+        if Self::set_discovered_name(conn, endpoint_id, &hostname, source)? {
             // When updating to a valid hostname, try to merge other IPv6 endpoints on same prefix
             Self::merge_ipv6_siblings_into_endpoint(conn, endpoint_id);
             // Try to merge this endpoint into an existing one with the same hostname
@@ -556,6 +463,7 @@ impl EndPoint {
                     "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
                     params![target_endpoint_id, sibling_id],
                 );
+                let _ = Self::reassign_endpoint_data(conn, sibling_id, target_endpoint_id);
                 let _ = conn.execute("DELETE FROM endpoints WHERE id = ?1", [sibling_id]);
                 println!(
                     "Merged IPv6 endpoint {} into {} (same /64 prefix: {})",
@@ -569,21 +477,7 @@ impl EndPoint {
     /// Only merges if the current endpoint has no real (non-locally-administered) MAC,
     /// to avoid accidentally merging two well-identified devices.
     fn try_merge_by_hostname(conn: &Connection, endpoint_id: i64, hostname: &str) {
-        // Check if this endpoint has any real (non-randomized) MAC
-        let has_real_mac: bool = conn
-            .query_row(
-                "SELECT EXISTS(
-                    SELECT 1 FROM endpoint_attributes
-                    WHERE endpoint_id = ?1
-                    AND mac IS NOT NULL AND mac != ''
-                    AND UPPER(SUBSTR(mac, 2, 1)) NOT IN ('2', '6', 'A', 'E')
-                )",
-                params![endpoint_id],
-                |row| row.get(0),
-            )
-            .unwrap_or(true); // Default to true (don't merge) on error
-
-        if has_real_mac {
+        if Self::has_real_mac(conn, endpoint_id) {
             return; // Only merge bare-IP or randomized-MAC endpoints
         }
 
@@ -603,6 +497,32 @@ impl EndPoint {
             return;
         };
 
+        Self::merge_endpoint_into(conn, endpoint_id, target_id);
+        println!(
+            "Merged endpoint {} into {} (same hostname: {})",
+            endpoint_id, target_id, hostname
+        );
+    }
+
+    /// Whether the endpoint has a burned-in (not locally administered) MAC. True on
+    /// error, so callers err on the side of not merging.
+    fn has_real_mac(conn: &Connection, endpoint_id: i64) -> bool {
+        conn.query_row(
+            "SELECT EXISTS(
+                SELECT 1 FROM endpoint_attributes
+                WHERE endpoint_id = ?1
+                AND mac IS NOT NULL AND mac != ''
+                AND UPPER(SUBSTR(mac, 2, 1)) NOT IN ('2', '6', 'A', 'E')
+            )",
+            params![endpoint_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(true)
+    }
+
+    /// Fold `endpoint_id` and everything recorded for it into `target_id`, keeping the
+    /// target's user-set fields where it has them, then delete it
+    pub(super) fn merge_endpoint_into(conn: &Connection, endpoint_id: i64, target_id: i64) {
         // Preserve user fields (custom_name, custom_vendor, manual_device_type) before merge
         let _ = conn.execute(
             "UPDATE endpoints SET
@@ -646,11 +566,38 @@ impl EndPoint {
             "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
             params![target_id, endpoint_id],
         );
+        let _ = Self::reassign_endpoint_data(conn, endpoint_id, target_id);
         let _ = conn.execute("DELETE FROM endpoints WHERE id = ?1", [endpoint_id]);
-        println!(
-            "Merged endpoint {} into {} (same hostname: {})",
-            endpoint_id, target_id, hostname
-        );
+    }
+
+    /// Move every per-endpoint table that hangs off `from_id` onto `to_id`. Shared by all
+    /// merge paths (automatic and manual) so a new table only needs adding here.
+    pub fn reassign_endpoint_data(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+        reassign_snmp_credential(conn, from_id, to_id)?;
+        reassign_smb_shares(conn, from_id, to_id)?;
+        reassign_web_info(conn, from_id, to_id)?;
+        reassign_endpoint_appearance(conn, from_id, to_id)?;
+        reassign_classification_feedback(conn, from_id, to_id)?;
+        Self::reassign_ip_history(conn, from_id, to_id)?;
+        Self::reassign_identities(conn, from_id, to_id)?;
+        reassign_dns_server_usage(conn, from_id, to_id)?;
+        reassign_usage(conn, from_id, to_id)?;
+        reassign_blocklist_hits(conn, from_id, to_id)?;
+        reassign_host_keys(conn, from_id, to_id)?;
+        reassign_capture_exclusions(conn, from_id, to_id)?;
+        reassign_webhook_endpoints(conn, from_id, to_id)?;
+        reassign_port_policies(conn, from_id, to_id)?;
+        reassign_bandwidth_quotas(conn, from_id, to_id)?;
+        reassign_type_baseline_deviations(conn, from_id, to_id)?;
+        reassign_upnp_events(conn, from_id, to_id)?;
+        reassign_endpoint_links(conn, from_id, to_id)?;
+        reassign_endpoint_tags(conn, from_id, to_id)?;
+        reassign_printer_readings(conn, from_id, to_id)?;
+        reassign_storage_health(conn, from_id, to_id)?;
+        reassign_ups_readings(conn, from_id, to_id)?;
+        reassign_energy(conn, from_id, to_id)?;
+        reassign_endpoint_owner(conn, from_id, to_id)?;
+        Ok(())
     }
 
     pub fn is_on_local_network(ip: &str) -> bool {
@@ -678,46 +625,14 @@ impl EndPoint {
     fn lookup_dns(ip: Option<String>, mac: Option<String>) -> Option<String> {
         let ip_str = ip?;
         let mac_str = mac?;
-        let ip_addr = ip_str.parse().ok()?;
-        let is_local = Self::is_local(ip_str.clone(), mac_str.clone());
-        let local_hostname = get_hostname().unwrap_or_default();
-
-        // Check cache first to avoid slow DNS lookups
-        if let Ok(cache) = DNS_CACHE.lock()
-            && let Some((cached_name, cached_time)) = cache.get(&ip_str)
-            && cached_time.elapsed() < DNS_CACHE_TTL
-        {
-            return Some(cached_name.clone());
-        }
-
-        // Get hostname via DNS or fallback to mDNS/IP
-        let hostname = match lookup_addr(&ip_addr) {
-            Ok(name) if name != ip_str && !is_local => name,
-            _ => MDnsLookup::lookup(&ip_str).unwrap_or(ip_str.clone()),
-        };
-
-        // Use local hostname for local IPs with different names
-        let final_hostname = if is_local && !hostname.eq_ignore_ascii_case(&local_hostname) {
-            local_hostname
-        } else {
-            hostname
-        };
+        ip_str.parse::<IpAddr>().ok()?;
 
-        // Cache the result
-        if let Ok(mut cache) = DNS_CACHE.lock() {
-            cache.insert(ip_str, (final_hostname.clone(), Instant::now()));
-            // LRU-style eviction: remove oldest entries instead of clearing all
-            if cache.len() > 10000 {
-                // Find and remove the 1000 oldest entries
-                let mut entries: Vec<_> = cache.iter().map(|(k, (_, t))| (k.clone(), *t)).collect();
-                entries.sort_by_key(|(_, t)| *t);
-                for (key, _) in entries.into_iter().take(1000) {
-                    cache.remove(&key);
-                }
-            }
+        // The local machine is always named by its own hostname
+        if Self::is_local(ip_str.clone(), mac_str) {
+            return Some(get_hostname().unwrap_or_default());
         }
 
-        Some(final_hostname)
+        Some(HostnameResolver::resolve(&ip_str).unwrap_or(ip_str))
     }
 
     fn lookup_hostname(
@@ -771,7 +686,7 @@ impl EndPoint {
     }
 
     // Parse TLS ClientHello to extract SNI (Server Name Indication)
-    fn find_sni(payload: &[u8]) -> Option<String> {
+    pub(crate) fn find_sni(payload: &[u8]) -> Option<String> {
         // Minimum TLS ClientHello size
         if payload.len() < 44 {
             return None;
@@ -1151,4 +1066,45 @@ mod tests {
             Some("appliance")
         );
     }
+
+    #[test]
+    fn test_merge_endpoint_into_moves_tags_and_trust() {
+        use crate::db::{
+            get_endpoint_tags, is_endpoint_trusted, set_endpoint_tags, set_endpoint_trusted,
+        };
+
+        let conn = new_test_connection();
+        let (target, _) = EndPoint::get_or_insert_endpoint(
+            &conn,
+            Some("00:11:22:33:44:55".to_string()),
+            Some("127.0.0.2".to_string()),
+            None,
+            &[],
+        )
+        .unwrap();
+        let (source, _) = EndPoint::get_or_insert_endpoint(
+            &conn,
+            Some("02:11:22:33:44:66".to_string()),
+            Some("127.0.0.3".to_string()),
+            None,
+            &[],
+        )
+        .unwrap();
+        set_endpoint_tags(&conn, source, &["iot".to_string()], 10).unwrap();
+        set_endpoint_trusted(&conn, source, true, 10).unwrap();
+
+        EndPoint::merge_endpoint_into(&conn, source, target);
+
+        assert_eq!(get_endpoint_tags(&conn, &[target]).unwrap(), vec!["iot"]);
+        assert!(is_endpoint_trusted(&conn, &[target]).unwrap());
+        let orphaned: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM endpoint_tags WHERE endpoint_id = ?1)
+                      + (SELECT COUNT(*) FROM endpoint_trust WHERE endpoint_id = ?1)",
+                [source],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(orphaned, 0);
+    }
 }
@@ -0,0 +1,220 @@
+//! Run a device through the characterization pipeline and report what every tier made of
+//! it, not only the answer that won. Backs `/api/classification/evaluate` for debugging
+//! rules against a hypothetical device, and the golden corpus in
+//! characterization_corpus.toml that pins the pipeline's answers for real devices.
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::communication::extract_model_from_vendor_class;
+
+use super::classification::{ActiveRules, DeviceTypeTier};
+use super::model::model_verdicts;
+use super::types::{Characterized, DataSource, pick_best};
+use super::vendor::{product_vendor_macs, vendor_verdicts};
+
+/// What is known about a device, as the capture and probes would have recorded it
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DeviceSample {
+    pub hostname: Option<String>,
+    pub macs: Vec<String>,
+    /// mDNS service types advertised, e.g. "_googlecast._tcp"
+    pub services: Vec<String>,
+    pub ports: Vec<u16>,
+    pub ssdp_model: Option<String>,
+    pub ssdp_friendly_name: Option<String>,
+    pub snmp_vendor: Option<String>,
+    pub snmp_model: Option<String>,
+    pub dhcp_vendor_class: Option<String>,
+    pub custom_vendor: Option<String>,
+    pub custom_model: Option<String>,
+}
+
+/// One tier's answer, None when it had nothing to say
+#[derive(Debug, Clone, Serialize)]
+pub struct TierVerdict {
+    pub tier: &'static str,
+    pub value: Option<String>,
+    pub source: Option<DataSource>,
+}
+
+impl TierVerdict {
+    fn from_characterized(tier: &'static str, verdict: Option<Characterized<String>>) -> Self {
+        Self {
+            tier,
+            source: verdict.as_ref().map(|v| v.source),
+            value: verdict.map(|v| v.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Evaluation {
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub device_type: Option<String>,
+    /// Every tier in the order it is consulted
+    pub vendor_tiers: Vec<TierVerdict>,
+    pub model_tiers: Vec<TierVerdict>,
+    pub device_type_tiers: Vec<TierVerdict>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.is_empty())
+}
+
+/// Characterize `sample` the way the endpoint table and details panel would: device type
+/// first (the model's vendor-and-type fallback needs it), then the vendor from MACs that
+/// aren't component makers', then the model, with the DHCP vendor class only filling in
+/// when no other tier names one.
+pub fn evaluate(sample: &DeviceSample) -> Evaluation {
+    let hostname = non_empty(&sample.hostname);
+    let ssdp_model = non_empty(&sample.ssdp_model);
+    let lower_hostname = hostname.map(|h| h.to_lowercase());
+
+    let device_type_tiers: Vec<TierVerdict> = DeviceTypeTier::ALL
+        .into_iter()
+        .map(|tier| TierVerdict {
+            tier: tier.as_str(),
+            value: tier
+                .classify(
+                    &ActiveRules,
+                    lower_hostname.as_deref(),
+                    &sample.services,
+                    &sample.ports,
+                    &sample.macs,
+                    ssdp_model,
+                )
+                .map(str::to_string),
+            source: None,
+        })
+        .collect();
+    let device_type = device_type_tiers.iter().find_map(|t| t.value.clone());
+
+    let vendor_verdicts = vendor_verdicts(
+        non_empty(&sample.custom_vendor),
+        non_empty(&sample.ssdp_friendly_name),
+        non_empty(&sample.snmp_vendor),
+        hostname,
+        &product_vendor_macs(&sample.macs),
+        ssdp_model,
+    );
+    let vendor = pick_best(&vendor_verdicts.clone().map(|(_, v)| v)).map(|v| v.value);
+
+    let model_verdicts = model_verdicts(
+        non_empty(&sample.custom_model),
+        ssdp_model,
+        non_empty(&sample.snmp_model),
+        hostname,
+        &sample.macs,
+        vendor.as_deref(),
+        device_type.as_deref(),
+    );
+    let dhcp_model = non_empty(&sample.dhcp_vendor_class)
+        .and_then(extract_model_from_vendor_class)
+        .map(Characterized::network_inferred);
+    let model = pick_best(&model_verdicts.clone().map(|(_, v)| v))
+        .or_else(|| dhcp_model.clone())
+        .map(|v| v.value);
+
+    Evaluation {
+        vendor,
+        model,
+        device_type,
+        vendor_tiers: vendor_verdicts
+            .into_iter()
+            .map(|(tier, v)| TierVerdict::from_characterized(tier, v))
+            .collect(),
+        model_tiers: model_verdicts
+            .into_iter()
+            .chain([("dhcp", dhcp_model)])
+            .map(|(tier, v)| TierVerdict::from_characterized(tier, v))
+            .collect(),
+        device_type_tiers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A corpus device and what the pipeline should make of it. Expectations left out
+    /// aren't checked; "" expects no answer at all.
+    #[derive(Deserialize)]
+    struct CorpusDevice {
+        description: String,
+        #[serde(flatten)]
+        sample: DeviceSample,
+        expected_vendor: Option<String>,
+        expected_model: Option<String>,
+        expected_type: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Corpus {
+        devices: Vec<CorpusDevice>,
+    }
+
+    #[test]
+    fn test_characterization_corpus() {
+        let corpus: Corpus = toml::from_str(include_str!("characterization_corpus.toml")).unwrap();
+        assert!(!corpus.devices.is_empty());
+
+        let mut mismatches = Vec::new();
+        for device in &corpus.devices {
+            let evaluation = evaluate(&device.sample);
+            for (field, expected, actual) in [
+                ("vendor", &device.expected_vendor, &evaluation.vendor),
+                ("model", &device.expected_model, &evaluation.model),
+                ("type", &device.expected_type, &evaluation.device_type),
+            ] {
+                let Some(expected) = expected else {
+                    continue;
+                };
+                if actual.as_deref().unwrap_or("") != expected {
+                    mismatches.push(format!(
+                        "{}: {} is {:?}, expected {:?}",
+                        device.description, field, actual, expected
+                    ));
+                }
+            }
+        }
+        assert!(mismatches.is_empty(), "\n{}", mismatches.join("\n"));
+    }
+
+    #[test]
+    fn test_evaluate_reports_every_tier() {
+        let evaluation = evaluate(&DeviceSample {
+            hostname: Some("HP-LaserJet-Pro".to_string()),
+            custom_vendor: Some("Acme".to_string()),
+            ports: vec![9100],
+            ..Default::default()
+        });
+        assert_eq!(evaluation.vendor.as_deref(), Some("Acme"));
+        assert_eq!(evaluation.device_type.as_deref(), Some("printer"));
+        assert_eq!(evaluation.vendor_tiers.len(), 6);
+        assert_eq!(evaluation.model_tiers.last().unwrap().tier, "dhcp");
+
+        // The hostname tier won, but the ports tier's own answer is still reported
+        let tier = |name: &str| {
+            evaluation
+                .device_type_tiers
+                .iter()
+                .find(|t| t.tier == name)
+                .and_then(|t| t.value.as_deref())
+        };
+        assert_eq!(tier("hostname"), Some("printer"));
+        assert_eq!(tier("ports"), Some("printer"));
+        assert_eq!(tier("model"), None);
+
+        let vendor_tier = |name: &str| {
+            evaluation
+                .vendor_tiers
+                .iter()
+                .find(|t| t.tier == name)
+                .unwrap()
+        };
+        assert_eq!(vendor_tier("custom").source, Some(DataSource::UserSet));
+        assert!(vendor_tier("hostname").value.is_some());
+    }
+}
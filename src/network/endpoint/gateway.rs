@@ -5,7 +5,7 @@ use std::time::Instant;
 
 use super::EndPoint;
 use super::constants::{GATEWAY_CACHE_TTL, GATEWAY_INFO};
-use super::patterns::{CLASSIFICATION_GATEWAY, CLASSIFICATION_INTERNET};
+use super::rule_matcher::{CLASSIFICATION_GATEWAY, CLASSIFICATION_INTERNET};
 
 impl EndPoint {
     /// Classify an endpoint as Gateway, Internet, or LocalNetwork based on IP address and hostname
@@ -130,7 +130,7 @@ impl EndPoint {
             || lower.starts_with("rt-") // Asus RT- series routers
     }
 
-    fn get_default_gateway() -> Option<String> {
+    pub fn get_default_gateway() -> Option<String> {
         // Check cache first
         if let Ok(cache) = GATEWAY_INFO.lock()
             && let Some((gateway_ip, cached_time)) = cache.as_ref()
@@ -233,7 +233,7 @@ impl EndPoint {
 #[cfg(test)]
 mod tests {
     use super::super::EndPoint;
-    use super::super::patterns::{CLASSIFICATION_GATEWAY, CLASSIFICATION_INTERNET};
+    use super::super::rule_matcher::{CLASSIFICATION_GATEWAY, CLASSIFICATION_INTERNET};
 
     #[test]
     fn test_classify_common_router_ip() {
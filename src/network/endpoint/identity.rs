@@ -0,0 +1,295 @@
+//! Device identities that outlive IP addresses. A UPnP device announces a UDN (a uuid
+//! in its SSDP USN and description) and a DNS-SD instance is named uniquely on the link,
+//! often with a device id in its TXT record. Once an identifier is bound to an endpoint,
+//! data discovered under it follows that endpoint wherever its address moves; a claim
+//! made from an address another device provably holds (see bindings.rs) is refused.
+//! Certificate subjects, from EAP-TLS logins or the certificates of a device's own
+//! services, anchor a device across MAC randomization: a randomized-MAC endpoint
+//! presenting a subject another randomized-MAC endpoint already holds is merged into it.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+use super::EndPoint;
+
+/// Where an identifier comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityKind {
+    /// UPnP Unique Device Name, without the "uuid:" prefix
+    UpnpUdn,
+    /// DNS-SD device id from the TXT record, or the instance's full name
+    MdnsInstance,
+    /// Subject of a client or service certificate, lowercase
+    Certificate,
+}
+
+impl IdentityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdentityKind::UpnpUdn => "upnp_udn",
+            IdentityKind::MdnsInstance => "mdns_instance",
+            IdentityKind::Certificate => "certificate",
+        }
+    }
+}
+
+/// Which endpoint an identifier seen at an address belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityResolution {
+    /// Bound to this endpoint, now or earlier
+    Verified(i64),
+    /// The endpoint at the address already has another identifier of this kind and the
+    /// address isn't pinned to it, so the claim can't be checked
+    Unverified(i64),
+    /// Bound to `bound_to`, but the address is pinned to `holder`
+    Conflict { bound_to: i64, holder: i64 },
+    /// New identifier and no endpoint at the address
+    Unknown,
+}
+
+impl EndPoint {
+    pub fn create_device_identities_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS device_identities (
+                kind TEXT NOT NULL,
+                identifier TEXT NOT NULL,
+                endpoint_id INTEGER NOT NULL,
+                last_ip TEXT NOT NULL,
+                first_seen_at INTEGER NOT NULL,
+                last_seen_at INTEGER NOT NULL,
+                PRIMARY KEY (kind, identifier)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_device_identities_endpoint ON device_identities (endpoint_id);",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Resolve the endpoint an identifier announced from `ip` belongs to. `ip_endpoint` is
+    /// the endpoint the address resolves to. A known identifier keeps its endpoint unless
+    /// an ARP/NDP binding pins the address to a different one. A new identifier is bound
+    /// to `ip_endpoint` if that endpoint has none of this kind yet or holds the address.
+    pub fn resolve_identity(
+        conn: &Connection,
+        kind: IdentityKind,
+        identifier: &str,
+        ip: &str,
+        ip_endpoint: Option<i64>,
+        now: i64,
+    ) -> Result<IdentityResolution> {
+        let holder = Self::resolve_ip_binding(conn, ip);
+        let bound: Option<i64> = conn
+            .query_row(
+                "SELECT endpoint_id FROM device_identities WHERE kind = ?1 AND identifier = ?2",
+                params![kind.as_str(), identifier],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        if let Some(bound_to) = bound {
+            if let Some(holder) = holder.filter(|&holder| holder != bound_to) {
+                return Ok(IdentityResolution::Conflict { bound_to, holder });
+            }
+            conn.execute(
+                "UPDATE device_identities SET last_ip = ?1, last_seen_at = ?2
+                 WHERE kind = ?3 AND identifier = ?4",
+                params![ip, now, kind.as_str(), identifier],
+            )?;
+            return Ok(IdentityResolution::Verified(bound_to));
+        }
+
+        let Some(endpoint_id) = ip_endpoint else {
+            return Ok(IdentityResolution::Unknown);
+        };
+        if Self::has_identity(conn, kind, endpoint_id)? && holder != Some(endpoint_id) {
+            return Ok(IdentityResolution::Unverified(endpoint_id));
+        }
+        conn.execute(
+            "INSERT INTO device_identities
+             (kind, identifier, endpoint_id, last_ip, first_seen_at, last_seen_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![kind.as_str(), identifier, endpoint_id, ip, now],
+        )?;
+        Ok(IdentityResolution::Verified(endpoint_id))
+    }
+
+    /// Anchor an endpoint to a certificate subject. If another endpoint holds the subject
+    /// and both were only ever seen with randomized MACs, they are one device and
+    /// `endpoint_id` is merged into the holder. Devices with real MACs (or none) may share
+    /// a user certificate or a firmware default, so they are left apart and the first
+    /// keeps the anchor. Returns the endpoint the device is known as afterwards.
+    pub fn anchor_certificate(
+        conn: &Connection,
+        endpoint_id: i64,
+        subject: &str,
+        ip: Option<&str>,
+        now: i64,
+    ) -> Result<i64> {
+        let identifier = subject.trim().to_lowercase();
+        let kind = IdentityKind::Certificate.as_str();
+        let holder: Option<i64> = conn
+            .query_row(
+                "SELECT endpoint_id FROM device_identities WHERE kind = ?1 AND identifier = ?2",
+                params![kind, identifier],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let endpoint_id = match holder {
+            None => {
+                conn.execute(
+                    "INSERT INTO device_identities
+                     (kind, identifier, endpoint_id, last_ip, first_seen_at, last_seen_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![kind, identifier, endpoint_id, ip.unwrap_or_default(), now],
+                )?;
+                return Ok(endpoint_id);
+            }
+            Some(holder) if holder == endpoint_id => endpoint_id,
+            Some(holder)
+                if has_only_random_macs(conn, endpoint_id)?
+                    && has_only_random_macs(conn, holder)? =>
+            {
+                Self::merge_endpoint_into(conn, endpoint_id, holder);
+                println!(
+                    "Merged endpoint {} into {} (same certificate: {})",
+                    endpoint_id, holder, subject
+                );
+                holder
+            }
+            Some(_) => return Ok(endpoint_id),
+        };
+        conn.execute(
+            "UPDATE device_identities SET last_ip = COALESCE(?1, last_ip), last_seen_at = ?2
+             WHERE kind = ?3 AND identifier = ?4",
+            params![ip, now, kind, identifier],
+        )?;
+        Ok(endpoint_id)
+    }
+
+    /// Whether the endpoint has any identifier of this kind
+    pub fn has_identity(conn: &Connection, kind: IdentityKind, endpoint_id: i64) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM device_identities WHERE kind = ?1 AND endpoint_id = ?2)",
+            params![kind.as_str(), endpoint_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Move an endpoint's identifiers to the endpoint it is merged into
+    pub fn reassign_identities(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+        conn.execute(
+            "UPDATE device_identities SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+            params![to_id, from_id],
+        )?;
+        Ok(())
+    }
+}
+
+/// Whether the endpoint was seen with a MAC, and only ever with locally administered ones
+fn has_only_random_macs(conn: &Connection, endpoint_id: i64) -> Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0
+                AND COALESCE(SUM(UPPER(SUBSTR(mac, 2, 1)) NOT IN ('2', '6', 'A', 'E')), 0) = 0
+         FROM endpoint_attributes
+         WHERE endpoint_id = ?1 AND mac IS NOT NULL AND mac != ''",
+        [endpoint_id],
+        |row| row.get(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    const UDN: &str = "2f402f80-da50-11e1-9b23-00178812d6f4";
+
+    #[test]
+    fn test_certificate_anchors_randomized_macs() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES
+                 (1, 1, 'phone'), (2, 1, 'phone-2'), (3, 1, 'laptop'), (4, 1, 'desktop');
+             INSERT INTO endpoint_attributes (created_at, endpoint_id, mac, ip) VALUES
+                 (1, 1, '5a:00:00:00:00:01', '192.168.1.20'),
+                 (1, 2, '7e:00:00:00:00:02', '192.168.1.21'),
+                 (1, 3, '00:1b:63:00:00:03', '192.168.1.22'),
+                 (1, 4, '00:1b:63:00:00:04', '192.168.1.23');",
+        )
+        .unwrap();
+        let subject = "CN=phone-7F3A, OU=Devices, O=Acme";
+        let anchor = |endpoint_id| {
+            EndPoint::anchor_certificate(&conn, endpoint_id, subject, None, 100).unwrap()
+        };
+
+        assert_eq!(anchor(1), 1);
+        // The phone came back with a new random MAC
+        assert_eq!(anchor(2), 1);
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM endpoints WHERE id = 2", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(remaining, 0);
+
+        // A user certificate on two machines with real MACs doesn't merge them
+        let shared = "CN=alice, O=Acme";
+        let anchor = |endpoint_id| {
+            EndPoint::anchor_certificate(&conn, endpoint_id, shared, None, 100).unwrap()
+        };
+        assert_eq!(anchor(3), 3);
+        assert_eq!(anchor(4), 4);
+    }
+
+    #[test]
+    fn test_identity_follows_device_across_addresses() {
+        let conn = new_test_connection();
+        let resolve = |ip: &str, ip_endpoint: Option<i64>| {
+            EndPoint::resolve_identity(&conn, IdentityKind::UpnpUdn, UDN, ip, ip_endpoint, 100)
+                .unwrap()
+        };
+
+        assert_eq!(resolve("192.168.1.20", None), IdentityResolution::Unknown);
+        assert_eq!(
+            resolve("192.168.1.20", Some(1)),
+            IdentityResolution::Verified(1)
+        );
+        // Moved to an address last recorded for another endpoint
+        assert_eq!(
+            resolve("192.168.1.30", Some(2)),
+            IdentityResolution::Verified(1)
+        );
+
+        // Another device answered ARP for the address: the claim is refused
+        EndPoint::record_ip_binding(&conn, "192.168.1.30", "aa:bb:cc:00:00:02", 2, "arp", 600)
+            .unwrap();
+        assert_eq!(
+            resolve("192.168.1.30", Some(2)),
+            IdentityResolution::Conflict {
+                bound_to: 1,
+                holder: 2
+            }
+        );
+
+        // A second UDN can't be pinned on an endpoint that has one without proof
+        let other = EndPoint::resolve_identity(
+            &conn,
+            IdentityKind::UpnpUdn,
+            "0cd1b9fa-0000-1000-8000-f4ccb6e1a2b3",
+            "192.168.1.20",
+            Some(1),
+            100,
+        )
+        .unwrap();
+        assert_eq!(other, IdentityResolution::Unverified(1));
+
+        EndPoint::reassign_identities(&conn, 1, 3).unwrap();
+        assert_eq!(
+            resolve("192.168.1.20", Some(1)),
+            IdentityResolution::Verified(3)
+        );
+    }
+}
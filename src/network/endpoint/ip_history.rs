@@ -0,0 +1,351 @@
+//! Which endpoint held which IP address, and when. `ip_bindings` only knows the current
+//! owner of each address; here every stay of an endpoint at an address is kept as a span
+//! that opens when it is first seen there (ARP/NDP replies, DHCP ACKs) and closes when
+//! another endpoint takes over or the client releases its DHCP lease.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+use super::EndPoint;
+use super::types::{DhcpLease, IpHistoryEntry};
+
+const IP_HISTORY_COLUMNS: &str = "h.endpoint_id, e.display_name, h.ip, h.mac, h.source,
+    h.bound_at, h.last_seen_at, h.unbound_at, h.lease_expires_at";
+
+impl IpHistoryEntry {
+    fn from_row(row: &rusqlite::Row<'_>) -> Result<Self> {
+        Ok(Self {
+            endpoint_id: row.get(0)?,
+            endpoint_name: row.get(1)?,
+            ip: row.get(2)?,
+            mac: row.get(3)?,
+            source: row.get(4)?,
+            bound_at: row.get(5)?,
+            last_seen_at: row.get(6)?,
+            unbound_at: row.get(7)?,
+            lease_expires_at: row.get(8)?,
+        })
+    }
+}
+
+impl EndPoint {
+    pub fn create_ip_history_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ip_history (
+                id INTEGER PRIMARY KEY,
+                endpoint_id INTEGER NOT NULL,
+                ip TEXT NOT NULL,
+                mac TEXT,
+                source TEXT NOT NULL,
+                bound_at INTEGER NOT NULL,
+                last_seen_at INTEGER NOT NULL,
+                unbound_at INTEGER,
+                lease_expires_at INTEGER
+            )",
+            [],
+        )?;
+        // Add lease column if it doesn't exist (migration for existing databases)
+        let _ = conn.execute(
+            "ALTER TABLE ip_history ADD COLUMN lease_expires_at INTEGER",
+            [],
+        );
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_ip_history_endpoint ON ip_history (endpoint_id, bound_at);",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_ip_history_ip ON ip_history (ip, bound_at);",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Record that `endpoint_id` was seen at `ip`. Extends its open span for the address,
+    /// or opens a new one and closes whichever other endpoint's span was open. Returns
+    /// true when a new span was opened.
+    pub fn record_ip_history(
+        conn: &Connection,
+        endpoint_id: i64,
+        ip: &str,
+        mac: Option<&str>,
+        source: &str,
+        seen_at: i64,
+    ) -> Result<bool> {
+        let extended = conn.execute(
+            "UPDATE ip_history SET last_seen_at = MAX(last_seen_at, ?1), mac = COALESCE(?2, mac)
+             WHERE endpoint_id = ?3 AND ip = ?4 AND unbound_at IS NULL",
+            params![seen_at, mac, endpoint_id, ip],
+        )?;
+        if extended > 0 {
+            return Ok(false);
+        }
+
+        Self::notify_ip_change(conn, endpoint_id, ip, mac, source, seen_at)?;
+        conn.execute(
+            "UPDATE ip_history SET unbound_at = ?1 WHERE ip = ?2 AND unbound_at IS NULL",
+            params![seen_at, ip],
+        )?;
+        conn.execute(
+            "INSERT INTO ip_history (endpoint_id, ip, mac, source, bound_at, last_seen_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![endpoint_id, ip, mac, source, seen_at],
+        )?;
+        Ok(true)
+    }
+
+    /// Raise an `ip_changed` event (notification and webhooks) when an endpoint shows up
+    /// at a new IPv4 address. Only IPv4 is followed: IPv6 privacy addresses rotate daily.
+    fn notify_ip_change(
+        conn: &Connection,
+        endpoint_id: i64,
+        ip: &str,
+        mac: Option<&str>,
+        source: &str,
+        seen_at: i64,
+    ) -> Result<()> {
+        if !ip.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_ipv4()) {
+            return Ok(());
+        }
+        let previous: Option<(String, Option<String>)> = conn
+            .query_row(
+                "SELECT ip, mac FROM ip_history
+                 WHERE endpoint_id = ?1 AND ip != ?2 AND ip NOT LIKE '%:%'
+                 ORDER BY bound_at DESC, id DESC
+                 LIMIT 1",
+                params![endpoint_id, ip],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((old_ip, old_mac)) = previous else {
+            return Ok(());
+        };
+
+        let (name, device_type): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT display_name, COALESCE(manual_device_type, auto_device_type)
+                 FROM endpoints WHERE id = ?1",
+                [endpoint_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .unwrap_or_default();
+        let mac = mac.map(str::to_string).or(old_mac.clone());
+
+        let details = match &mac {
+            Some(mac) => format!("MAC {}, seen via {}", mac, source),
+            None => format!("Seen via {}", source),
+        };
+        crate::db::insert_notification_with_endpoint_id(
+            conn,
+            "ip_changed",
+            &format!("IP address changed from {} to {}", old_ip, ip),
+            Some(&details),
+            name.as_deref(),
+            Some(endpoint_id),
+        );
+
+        let payload = serde_json::json!({
+            "event": "ip_changed",
+            "endpoint_id": endpoint_id,
+            "endpoint": name,
+            "device_type": device_type,
+            "old_ip": old_ip,
+            "new_ip": ip,
+            "old_mac": old_mac,
+            "mac": mac,
+            "source": source,
+            "changed_at": seen_at,
+        });
+        crate::db::queue_webhook_event(conn, "ip_changed", endpoint_id, &payload)?;
+        Ok(())
+    }
+
+    /// Apply a DHCP ACK or RELEASE to the history of the client's endpoint. Clients that
+    /// haven't been seen as an endpoint yet are skipped. Returns whether the lease was
+    /// recorded.
+    pub fn record_dhcp_lease(conn: &Connection, lease: &DhcpLease, seen_at: i64) -> Result<bool> {
+        let endpoint_id: Option<i64> = conn
+            .query_row(
+                "SELECT endpoint_id FROM endpoint_attributes
+                 WHERE LOWER(mac) = LOWER(?1)
+                 ORDER BY created_at DESC
+                 LIMIT 1",
+                [&lease.mac],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(endpoint_id) = endpoint_id else {
+            return Ok(false);
+        };
+
+        if lease.released {
+            conn.execute(
+                "UPDATE ip_history SET unbound_at = ?1, lease_expires_at = MIN(COALESCE(lease_expires_at, ?1), ?1)
+                 WHERE endpoint_id = ?2 AND ip = ?3 AND unbound_at IS NULL",
+                params![seen_at, endpoint_id, lease.ip],
+            )?;
+            return Ok(true);
+        }
+
+        Self::record_ip_history(
+            conn,
+            endpoint_id,
+            &lease.ip,
+            Some(&lease.mac),
+            "dhcp",
+            seen_at,
+        )?;
+        conn.execute(
+            "UPDATE ip_history SET lease_expires_at = ?1
+             WHERE endpoint_id = ?2 AND ip = ?3 AND unbound_at IS NULL",
+            params![
+                lease.lease_seconds.map(|seconds| seen_at + seconds),
+                endpoint_id,
+                lease.ip
+            ],
+        )?;
+        Ok(true)
+    }
+
+    /// Every address an endpoint has held, oldest first
+    pub fn get_ip_history(conn: &Connection, endpoint_id: i64) -> Result<Vec<IpHistoryEntry>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM ip_history h
+             LEFT JOIN endpoints e ON e.id = h.endpoint_id
+             WHERE h.endpoint_id = ?1
+             ORDER BY h.bound_at, h.id",
+            IP_HISTORY_COLUMNS
+        ))?;
+        let rows = stmt.query_map([endpoint_id], IpHistoryEntry::from_row)?;
+        rows.collect()
+    }
+
+    /// Who held `ip` at unix time `at`: the spans that had started by then and weren't yet
+    /// closed, newest first. Without `at`, every span recorded for the address.
+    pub fn get_ip_holders(
+        conn: &Connection,
+        ip: &str,
+        at: Option<i64>,
+    ) -> Result<Vec<IpHistoryEntry>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM ip_history h
+             LEFT JOIN endpoints e ON e.id = h.endpoint_id
+             WHERE h.ip = ?1
+               AND (?2 IS NULL OR (h.bound_at <= ?2 AND (h.unbound_at IS NULL OR h.unbound_at > ?2)))
+             ORDER BY h.bound_at DESC, h.id DESC",
+            IP_HISTORY_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![ip, at], IpHistoryEntry::from_row)?;
+        rows.collect()
+    }
+
+    /// Drop spans that closed more than `retention_days` ago
+    pub fn purge_ip_history(conn: &Connection, retention_days: i64) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM ip_history WHERE unbound_at < (strftime('%s', 'now') - ?1)",
+            [retention_days * 24 * 60 * 60],
+        )
+    }
+
+    /// Move an endpoint's address history to the endpoint it is merged into
+    pub fn reassign_ip_history(conn: &Connection, from_id: i64, to_id: i64) -> Result<()> {
+        conn.execute(
+            "UPDATE ip_history SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+            params![to_id, from_id],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_ip_history_spans() {
+        let conn = new_test_connection();
+        let ip = "192.168.1.50";
+
+        assert!(
+            EndPoint::record_ip_history(&conn, 1, ip, Some("aa:aa:aa:aa:aa:01"), "arp", 100)
+                .unwrap()
+        );
+        assert!(!EndPoint::record_ip_history(&conn, 1, ip, None, "arp", 200).unwrap());
+        // Another device takes the address over, then the first one gets it back
+        assert!(EndPoint::record_ip_history(&conn, 2, ip, None, "ndp", 300).unwrap());
+        assert!(EndPoint::record_ip_history(&conn, 1, ip, None, "arp", 400).unwrap());
+
+        let first = EndPoint::get_ip_history(&conn, 1).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(
+            (
+                first[0].bound_at,
+                first[0].last_seen_at,
+                first[0].unbound_at
+            ),
+            (100, 200, Some(300))
+        );
+        assert_eq!(first[0].mac.as_deref(), Some("aa:aa:aa:aa:aa:01"));
+        assert_eq!((first[1].bound_at, first[1].unbound_at), (400, None));
+
+        let second = EndPoint::get_ip_history(&conn, 2).unwrap();
+        assert_eq!((second[0].bound_at, second[0].unbound_at), (300, Some(400)));
+
+        let holders = |at| {
+            EndPoint::get_ip_holders(&conn, ip, at)
+                .unwrap()
+                .iter()
+                .map(|entry| entry.endpoint_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(holders(Some(50)), Vec::<i64>::new());
+        assert_eq!(holders(Some(250)), vec![1]);
+        assert_eq!(holders(Some(300)), vec![2]);
+        assert_eq!(holders(Some(1000)), vec![1]);
+        assert_eq!(holders(None), vec![1, 2, 1]);
+
+        EndPoint::reassign_ip_history(&conn, 2, 1).unwrap();
+        assert_eq!(EndPoint::get_ip_history(&conn, 1).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_dhcp_lease_and_release() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, 'phone');
+             INSERT INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname)
+                 VALUES (1, 1, 'aa:bb:cc:dd:ee:01', '0.0.0.0', 'phone');",
+        )
+        .unwrap();
+        let lease = DhcpLease {
+            mac: "AA:BB:CC:DD:EE:01".to_string(),
+            ip: "192.168.1.60".to_string(),
+            lease_seconds: Some(3600),
+            released: false,
+        };
+
+        assert!(EndPoint::record_dhcp_lease(&conn, &lease, 1000).unwrap());
+        let history = EndPoint::get_ip_history(&conn, 1).unwrap();
+        assert_eq!(history[0].source, "dhcp");
+        assert_eq!(history[0].endpoint_name.as_deref(), Some("phone"));
+        assert_eq!(history[0].lease_expires_at, Some(4600));
+
+        let release = DhcpLease {
+            released: true,
+            lease_seconds: None,
+            ..lease.clone()
+        };
+        EndPoint::record_dhcp_lease(&conn, &release, 2000).unwrap();
+        let history = EndPoint::get_ip_history(&conn, 1).unwrap();
+        assert_eq!(
+            (history[0].unbound_at, history[0].lease_expires_at),
+            (Some(2000), Some(2000))
+        );
+
+        let stranger = DhcpLease {
+            mac: "aa:bb:cc:dd:ee:99".to_string(),
+            ..lease
+        };
+        assert!(!EndPoint::record_dhcp_lease(&conn, &stranger, 3000).unwrap());
+    }
+}
@@ -0,0 +1,388 @@
+//! Merge history for manually merged endpoints. Snapshots the source endpoint row and
+//! every row a merge reassigns so the most recent merge into an endpoint can be undone.
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde_json::Value;
+
+use super::EndPoint;
+use super::snapshot::{
+    JsonRow, insert_json_row, json_ids, json_rows, json_to_sql, move_rows, query_ids,
+    query_rows_as_json,
+};
+use super::types::MergeHistoryEntry;
+
+/// Pre-merge state captured before `merge_endpoints` mutates anything
+pub struct MergeSnapshot {
+    target_id: i64,
+    source_id: i64,
+    source_endpoint: JsonRow,
+    target_fields: JsonRow,
+    communications_src: Vec<i64>,
+    communications_dst: Vec<i64>,
+    attributes: Vec<JsonRow>,
+    open_ports: Vec<JsonRow>,
+    scan_results: Vec<i64>,
+    notifications: Vec<i64>,
+    max_attribute_id: i64,
+}
+
+impl MergeHistoryEntry {
+    fn from_row(row: &rusqlite::Row<'_>) -> Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            created_at: row.get(1)?,
+            target_id: row.get(2)?,
+            source_id: row.get(3)?,
+            source_name: row.get(4)?,
+            undone_at: row.get(5)?,
+        })
+    }
+}
+
+impl EndPoint {
+    pub fn create_merge_history_table(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS merge_history (
+                id INTEGER PRIMARY KEY,
+                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                target_id INTEGER NOT NULL,
+                source_id INTEGER NOT NULL,
+                source_name TEXT,
+                snapshot TEXT NOT NULL,
+                undone_at INTEGER
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_merge_history_target ON merge_history (target_id, created_at DESC);",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Capture everything a merge of `source_id` into `target_id` is about to change
+    pub fn capture_merge_snapshot(
+        conn: &Connection,
+        target_id: i64,
+        source_id: i64,
+    ) -> Result<MergeSnapshot> {
+        let source_endpoint =
+            query_rows_as_json(conn, "SELECT * FROM endpoints WHERE id = ?1", source_id)?
+                .into_iter()
+                .next()
+                .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let target_fields = query_rows_as_json(
+            conn,
+            "SELECT ssdp_model, ssdp_friendly_name, netbios_name, auto_device_type
+             FROM endpoints WHERE id = ?1",
+            target_id,
+        )?
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+
+        Ok(MergeSnapshot {
+            target_id,
+            source_id,
+            source_endpoint,
+            target_fields,
+            communications_src: query_ids(
+                conn,
+                "SELECT id FROM communications WHERE src_endpoint_id = ?1",
+                source_id,
+            )?,
+            communications_dst: query_ids(
+                conn,
+                "SELECT id FROM communications WHERE dst_endpoint_id = ?1",
+                source_id,
+            )?,
+            attributes: query_rows_as_json(
+                conn,
+                "SELECT * FROM endpoint_attributes WHERE endpoint_id = ?1",
+                source_id,
+            )?,
+            open_ports: query_rows_as_json(
+                conn,
+                "SELECT * FROM open_ports WHERE endpoint_id = ?1",
+                source_id,
+            )?,
+            scan_results: query_ids(
+                conn,
+                "SELECT id FROM scan_results WHERE endpoint_id = ?1",
+                source_id,
+            )?,
+            notifications: query_ids(
+                conn,
+                "SELECT id FROM notifications WHERE endpoint_id = ?1",
+                source_id,
+            )?,
+            max_attribute_id: conn.query_row(
+                "SELECT COALESCE(MAX(id), 0) FROM endpoint_attributes",
+                [],
+                |row| row.get(0),
+            )?,
+        })
+    }
+
+    /// Persist a completed merge. Attribute rows copied into the target during the
+    /// merge are identified by ids above the pre-merge maximum.
+    pub fn record_merge(
+        conn: &Connection,
+        snapshot: MergeSnapshot,
+        source_name: &str,
+    ) -> Result<i64> {
+        let inserted_attributes = conn
+            .prepare("SELECT id FROM endpoint_attributes WHERE endpoint_id = ?1 AND id > ?2")?
+            .query_map(
+                params![snapshot.target_id, snapshot.max_attribute_id],
+                |row| row.get::<_, i64>(0),
+            )?
+            .collect::<Result<Vec<i64>>>()?;
+
+        let payload = serde_json::json!({
+            "source_endpoint": snapshot.source_endpoint,
+            "target_fields": snapshot.target_fields,
+            "communications_src": snapshot.communications_src,
+            "communications_dst": snapshot.communications_dst,
+            "attributes": snapshot.attributes,
+            "inserted_attributes": inserted_attributes,
+            "open_ports": snapshot.open_ports,
+            "scan_results": snapshot.scan_results,
+            "notifications": snapshot.notifications,
+        });
+
+        conn.execute(
+            "INSERT INTO merge_history (target_id, source_id, source_name, snapshot)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                snapshot.target_id,
+                snapshot.source_id,
+                source_name,
+                payload.to_string()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Split the most recent (not yet undone) merge into `target_id` back out.
+    /// Returns the undone history entry, or None if there is nothing to undo.
+    pub fn undo_last_merge(conn: &Connection, target_id: i64) -> Result<Option<MergeHistoryEntry>> {
+        let Some(entry) = Self::get_merge_history(conn, target_id)?
+            .into_iter()
+            .find(|e| e.target_id == target_id && e.undone_at.is_none())
+        else {
+            return Ok(None);
+        };
+
+        let snapshot: Value = conn
+            .query_row(
+                "SELECT snapshot FROM merge_history WHERE id = ?1",
+                [entry.id],
+                |row| row.get::<_, String>(0),
+            )
+            .map(|s| serde_json::from_str(&s).unwrap_or(Value::Null))?;
+        let source_id = entry.source_id;
+
+        let tx = conn.unchecked_transaction()?;
+
+        // Recreate the source endpoint with its original id so references line up again
+        if let Some(source_endpoint) = snapshot.get("source_endpoint").and_then(|v| v.as_object()) {
+            insert_json_row(&tx, "endpoints", source_endpoint)?;
+        } else {
+            return Err(rusqlite::Error::InvalidQuery);
+        }
+
+        // Restore target metadata that the merge may have filled in from the source
+        if let Some(fields) = snapshot.get("target_fields").and_then(|v| v.as_object()) {
+            for (column, value) in fields {
+                tx.execute(
+                    &format!("UPDATE endpoints SET {} = ?1 WHERE id = ?2", column),
+                    params![json_to_sql(value), target_id],
+                )?;
+            }
+        }
+
+        // Communications, scan results, and notifications kept their ids; move them back
+        move_rows(
+            &tx,
+            "communications",
+            "src_endpoint_id",
+            &json_ids(snapshot.get("communications_src")),
+            target_id,
+            source_id,
+        )?;
+        move_rows(
+            &tx,
+            "communications",
+            "dst_endpoint_id",
+            &json_ids(snapshot.get("communications_dst")),
+            target_id,
+            source_id,
+        )?;
+        move_rows(
+            &tx,
+            "scan_results",
+            "endpoint_id",
+            &json_ids(snapshot.get("scan_results")),
+            target_id,
+            source_id,
+        )?;
+        move_rows(
+            &tx,
+            "notifications",
+            "endpoint_id",
+            &json_ids(snapshot.get("notifications")),
+            target_id,
+            source_id,
+        )?;
+
+        // Attributes were copied (new ids) and deleted: drop the copies, restore originals
+        for id in json_ids(snapshot.get("inserted_attributes")) {
+            tx.execute(
+                "DELETE FROM endpoint_attributes WHERE id = ?1 AND endpoint_id = ?2",
+                params![id, target_id],
+            )?;
+        }
+        for row in json_rows(snapshot.get("attributes")) {
+            tx.execute(
+                "DELETE FROM endpoint_attributes WHERE id = ?1",
+                [row.get("id").and_then(|v| v.as_i64()).unwrap_or(-1)],
+            )?;
+            insert_json_row(&tx, "endpoint_attributes", &row)?;
+        }
+
+        // Open ports were moved (same id) or deleted as duplicates: restore all originals
+        for row in json_rows(snapshot.get("open_ports")) {
+            tx.execute(
+                "DELETE FROM open_ports WHERE id = ?1",
+                [row.get("id").and_then(|v| v.as_i64()).unwrap_or(-1)],
+            )?;
+            insert_json_row(&tx, "open_ports", &row)?;
+        }
+
+        tx.execute(
+            "UPDATE merge_history SET undone_at = strftime('%s', 'now') WHERE id = ?1",
+            [entry.id],
+        )?;
+        tx.commit()?;
+
+        Ok(conn
+            .query_row(
+                "SELECT id, created_at, target_id, source_id, source_name, undone_at
+                 FROM merge_history WHERE id = ?1",
+                [entry.id],
+                MergeHistoryEntry::from_row,
+            )
+            .optional()?
+            .or(Some(entry)))
+    }
+
+    /// List merges where the endpoint was the target or the (since restored) source, newest first
+    pub fn get_merge_history(
+        conn: &Connection,
+        endpoint_id: i64,
+    ) -> Result<Vec<MergeHistoryEntry>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, target_id, source_id, source_name, undone_at
+             FROM merge_history
+             WHERE target_id = ?1 OR source_id = ?1
+             ORDER BY created_at DESC, id DESC",
+        )?;
+        let rows = stmt.query_map([endpoint_id], MergeHistoryEntry::from_row)?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    fn insert_endpoint(conn: &Connection, name: &str) -> i64 {
+        conn.execute(
+            "INSERT INTO endpoints (created_at, name) VALUES (strftime('%s', 'now'), ?1)",
+            [name],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn create_merge_side_tables(conn: &Connection) {
+        conn.execute_batch(
+            "CREATE TABLE scan_results (id INTEGER PRIMARY KEY, endpoint_id INTEGER NOT NULL,
+                 scan_type TEXT NOT NULL, scanned_at INTEGER NOT NULL,
+                 response_time_ms INTEGER, details TEXT);",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_merge_snapshot_roundtrip_restores_source() {
+        let conn = new_test_connection();
+        create_merge_side_tables(&conn);
+
+        let target = insert_endpoint(&conn, "target-host");
+        let source = insert_endpoint(&conn, "source-host");
+        conn.execute(
+            "INSERT INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname)
+             VALUES (1, ?1, 'aa:bb:cc:dd:ee:ff', '192.168.1.50', 'source-host')",
+            [source],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO open_ports (endpoint_id, port, last_seen_at) VALUES (?1, 22, 1)",
+            [source],
+        )
+        .unwrap();
+
+        // Simulate the merge performed by the API handler
+        let snapshot = EndPoint::capture_merge_snapshot(&conn, target, source).unwrap();
+        conn.execute(
+            "UPDATE open_ports SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+            [target, source],
+        )
+        .unwrap();
+        conn.execute(
+            "DELETE FROM endpoint_attributes WHERE endpoint_id = ?1",
+            [source],
+        )
+        .unwrap();
+        conn.execute("DELETE FROM endpoints WHERE id = ?1", [source])
+            .unwrap();
+        EndPoint::record_merge(&conn, snapshot, "source-host").unwrap();
+
+        let undone = EndPoint::undo_last_merge(&conn, target).unwrap().unwrap();
+        assert_eq!(undone.source_id, source);
+        assert!(undone.undone_at.is_some());
+
+        let name: String = conn
+            .query_row(
+                "SELECT name FROM endpoints WHERE id = ?1",
+                [source],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(name, "source-host");
+
+        let attr_count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM endpoint_attributes WHERE endpoint_id = ?1",
+                [source],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(attr_count, 1);
+
+        let port_owner: i64 = conn
+            .query_row(
+                "SELECT endpoint_id FROM open_ports WHERE port = 22",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(port_owner, source);
+
+        // Nothing left to undo
+        assert!(EndPoint::undo_last_merge(&conn, target).unwrap().is_none());
+    }
+}
@@ -1,14 +1,24 @@
 //! Endpoint module. Re-exports public APIs for endpoint classification,
 //! vendor characterization, and model identification.
 
+mod archive;
+mod bindings;
 mod classification;
 mod constants;
 mod db;
-mod detection;
+mod display_name;
 mod endpoint_ops;
+mod evaluate;
 mod gateway;
+mod identity;
+mod ip_history;
+mod merge_history;
 mod model;
+mod naming;
 mod patterns;
+mod rule_matcher;
+mod runtime_rules;
+mod snapshot;
 mod types;
 mod vendor;
 
@@ -16,10 +26,18 @@ mod vendor;
 pub struct EndPoint;
 
 // Re-exports to preserve public API
-pub use constants::{is_valid_display_name, strip_local_suffix};
+pub(crate) use constants::get_local_networks;
+pub use constants::{is_locally_administered_mac, is_valid_display_name, strip_local_suffix};
+pub use evaluate::{DeviceSample, evaluate};
+pub use identity::{IdentityKind, IdentityResolution};
 pub use model::{
     characterize_model, get_model_from_hostname, get_model_from_mac,
     get_model_from_vendor_and_type, infer_model_with_context, normalize_model_name,
 };
-pub use types::{EndpointData, InsertEndpointError, InternetDestination};
-pub use vendor::{characterize_vendor, get_hostname_vendor, get_mac_vendor, get_vendor_from_model};
+pub use naming::NameSource;
+pub use runtime_rules::{reload_runtime_rules, start_runtime_rules_watcher};
+pub use types::{DhcpLease, EndpointData, InsertEndpointError, InternetDestination};
+pub use vendor::{
+    characterize_vendor, component_vendors, get_hostname_vendor, get_mac_vendor,
+    get_vendor_from_model, is_component_vendor, product_vendor_macs,
+};
@@ -1,11 +1,11 @@
 //! Device model identification. Normalizes raw model numbers to friendly display names
 //! and infers models from hostnames, MAC addresses, and vendor context.
 
-use super::detection::{is_roku_serial_number, is_roku_tv_model};
 use super::patterns::{
     HOSTNAME_MODEL_RULES, LG_TV_SERIES, MAC_VENDOR_MODEL_RULES, SAMSUNG_TV_SERIES, SONY_TV_SERIES,
     VENDOR_TYPE_MODEL_RULES,
 };
+use super::rule_matcher::{is_roku_serial_number, is_roku_tv_model};
 use super::types::{Characterized, pick_best};
 use super::vendor::get_mac_vendor;
 
@@ -171,6 +171,28 @@ pub fn characterize_model(
     vendor: Option<&str>,
     device_type: Option<&str>,
 ) -> Option<Characterized<String>> {
+    let verdicts = model_verdicts(
+        custom_model,
+        ssdp_model,
+        snmp_model,
+        hostname,
+        macs,
+        vendor,
+        device_type,
+    );
+    pick_best(&verdicts.map(|(_, verdict)| verdict))
+}
+
+/// What each source says the model is, by tier name, before `characterize_model` picks one
+pub(crate) fn model_verdicts(
+    custom_model: Option<&str>,
+    ssdp_model: Option<&str>,
+    snmp_model: Option<&str>,
+    hostname: Option<&str>,
+    macs: &[String],
+    vendor: Option<&str>,
+    device_type: Option<&str>,
+) -> [(&'static str, Option<Characterized<String>>); 6] {
     // User-set model has highest priority
     let user = custom_model
         .filter(|m| !m.is_empty())
@@ -209,15 +231,14 @@ pub fn characterize_model(
         })
         .map(Characterized::pattern_matched);
 
-    // Pick the best one (highest priority source)
-    pick_best(&[
-        user,
-        ssdp,
-        from_snmp,
-        from_hostname,
-        from_mac,
-        from_vendor_type,
-    ])
+    [
+        ("custom", user),
+        ("ssdp", ssdp),
+        ("snmp", from_snmp),
+        ("hostname", from_hostname),
+        ("mac", from_mac),
+        ("vendor_type", from_vendor_type),
+    ]
 }
 
 /// Extract model name from hostname patterns
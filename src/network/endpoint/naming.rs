@@ -0,0 +1,202 @@
+//! Which discovered name an endpoint goes by. Several protocols announce names for the same
+//! device, so `endpoints.name_source` records where the current name came from and a new
+//! name only replaces it when its source ranks higher: mDNS, then the DHCP hostname
+//! (option 12), then NetBIOS, then anything else (DNS, TLS SNI, SNMP, model rules). A
+//! custom name still overrides all of them, and the IP is the fallback when none is usable
+//! (see display_name.rs).
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+use super::EndPoint;
+use super::constants::is_valid_display_name;
+
+/// Where an endpoint's name came from. Declaration order is precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NameSource {
+    Mdns,
+    Dhcp,
+    NetBios,
+    /// Hostnames from DNS, PTR, HTTP and TLS, SNMP sysName, and model-derived names
+    Other,
+}
+
+impl NameSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NameSource::Mdns => "mdns",
+            NameSource::Dhcp => "dhcp",
+            NameSource::NetBios => "netbios",
+            NameSource::Other => "other",
+        }
+    }
+
+    /// Names stored before sources were recorded count as `Other`
+    fn parse(s: Option<&str>) -> Self {
+        match s {
+            Some("mdns") => NameSource::Mdns,
+            Some("dhcp") => NameSource::Dhcp,
+            Some("netbios") => NameSource::NetBios,
+            _ => NameSource::Other,
+        }
+    }
+}
+
+/// Whether `source` may rename an endpoint currently called `current` by `current_source`
+fn should_replace(current: &str, current_source: NameSource, source: NameSource) -> bool {
+    !is_valid_display_name(current) || source < current_source
+}
+
+impl EndPoint {
+    /// Offer a discovered name for an endpoint. It is taken when the endpoint has no usable
+    /// name yet or its name came from a lower-ranked source. Returns whether the name
+    /// changed.
+    pub fn set_discovered_name(
+        conn: &Connection,
+        endpoint_id: i64,
+        name: &str,
+        source: NameSource,
+    ) -> Result<bool> {
+        if !is_valid_display_name(name) {
+            return Ok(false);
+        }
+        let Some((current, current_source)) = conn
+            .query_row(
+                "SELECT COALESCE(name, ''), name_source FROM endpoints WHERE id = ?1",
+                [endpoint_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .optional()?
+        else {
+            return Ok(false);
+        };
+        let current_source = NameSource::parse(current_source.as_deref());
+        if !should_replace(&current, current_source, source) {
+            return Ok(false);
+        }
+        conn.execute(
+            "UPDATE endpoints SET name = ?1, name_source = ?2 WHERE id = ?3",
+            params![name, source.as_str(), endpoint_id],
+        )?;
+        Ok(current != name)
+    }
+
+    /// `set_discovered_name` for every endpoint seen at `ip`. Returns the endpoints renamed.
+    pub fn set_discovered_name_for_ip(
+        conn: &Connection,
+        ip: &str,
+        name: &str,
+        source: NameSource,
+    ) -> Result<Vec<i64>> {
+        let endpoint_ids = conn
+            .prepare("SELECT DISTINCT endpoint_id FROM endpoint_attributes WHERE ip = ?1")?
+            .query_map([ip], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>>>()?;
+        let mut renamed = Vec::new();
+        for endpoint_id in endpoint_ids {
+            if Self::set_discovered_name(conn, endpoint_id, name, source)? {
+                renamed.push(endpoint_id);
+            }
+        }
+        Ok(renamed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_should_replace() {
+        // Anything beats an address or an empty name
+        assert!(should_replace(
+            "192.168.1.20",
+            NameSource::Mdns,
+            NameSource::Other
+        ));
+        assert!(should_replace("", NameSource::Other, NameSource::NetBios));
+        assert!(should_replace(
+            "lgwebostv",
+            NameSource::NetBios,
+            NameSource::Dhcp
+        ));
+        assert!(should_replace(
+            "lgwebostv",
+            NameSource::Dhcp,
+            NameSource::Mdns
+        ));
+        assert!(!should_replace(
+            "lgwebostv",
+            NameSource::Dhcp,
+            NameSource::NetBios
+        ));
+        // Equal sources keep the first name, so a device with two names doesn't flap
+        assert!(!should_replace(
+            "lgwebostv",
+            NameSource::Dhcp,
+            NameSource::Dhcp
+        ));
+        assert_eq!(NameSource::parse(None), NameSource::Other);
+    }
+
+    #[test]
+    fn test_set_discovered_name() {
+        let conn = new_test_connection();
+        conn.execute_batch(
+            "INSERT INTO endpoints (id, created_at, name) VALUES (1, 1, '192.168.1.20');
+             INSERT INTO endpoint_attributes (created_at, endpoint_id, ip, hostname)
+             VALUES (1, 1, '192.168.1.20', '192.168.1.20');",
+        )
+        .unwrap();
+        let name = |conn: &Connection| -> (String, Option<String>) {
+            conn.query_row(
+                "SELECT name, name_source FROM endpoints WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+        };
+
+        assert!(
+            EndPoint::set_discovered_name(&conn, 1, "DESKTOP-7F3K", NameSource::NetBios).unwrap()
+        );
+        assert!(
+            EndPoint::set_discovered_name(&conn, 1, "kitchen-laptop", NameSource::Dhcp).unwrap()
+        );
+        assert!(
+            !EndPoint::set_discovered_name(&conn, 1, "DESKTOP-7F3K", NameSource::NetBios).unwrap()
+        );
+        assert!(!EndPoint::set_discovered_name(&conn, 1, "10.0.0.1", NameSource::Mdns).unwrap());
+        assert_eq!(
+            name(&conn),
+            ("kitchen-laptop".to_string(), Some("dhcp".to_string()))
+        );
+
+        assert_eq!(
+            EndPoint::set_discovered_name_for_ip(
+                &conn,
+                "192.168.1.20",
+                "kitchen-laptop-2",
+                NameSource::Mdns
+            )
+            .unwrap(),
+            vec![1]
+        );
+        assert_eq!(name(&conn).1.as_deref(), Some("mdns"));
+
+        // A custom name still wins in the display name
+        conn.execute(
+            "UPDATE endpoints SET custom_name = 'Kitchen' WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        let display_name: String = conn
+            .query_row(
+                "SELECT display_name FROM endpoints WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(display_name, "Kitchen");
+    }
+}
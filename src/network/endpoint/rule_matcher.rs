@@ -0,0 +1,554 @@
+//! The rule-driven part of device type classification: the tier order, the hostname,
+//! mDNS service, MAC vendor and port checks, and their hard-coded special cases, over any
+//! `RuleSet`. Uses only std so tools/device-rules-generator can include this file by path
+//! and classify its fixtures against device_rules.toml itself, without building this
+//! crate or the generated data. Keep it free of `crate::`/`super::` imports.
+
+// Classification type constants
+pub(crate) const CLASSIFICATION_GATEWAY: &str = "gateway";
+pub(crate) const CLASSIFICATION_INTERNET: &str = "internet";
+pub(crate) const CLASSIFICATION_PRINTER: &str = "printer";
+pub(crate) const CLASSIFICATION_TV: &str = "tv";
+pub(crate) const CLASSIFICATION_GAMING: &str = "gaming";
+pub(crate) const CLASSIFICATION_VIRTUALIZATION: &str = "virtualization";
+pub(crate) const CLASSIFICATION_SOUNDBAR: &str = "soundbar";
+pub(crate) const CLASSIFICATION_APPLIANCE: &str = "appliance";
+pub(crate) const CLASSIFICATION_PHONE: &str = "phone";
+pub(crate) const CLASSIFICATION_COMPUTER: &str = "computer";
+
+/// The device rules a classification runs against. `class` is a device_rules.toml key
+/// ("printer", "vm", ...); hostnames and models are already lowercased.
+pub(crate) trait RuleSet {
+    /// Hostname contains one of the class's `[patterns]`
+    fn hostname_pattern(&self, class: &str, hostname: &str) -> bool;
+    /// Hostname starts with one of the class's `[prefixes]`
+    fn hostname_prefix(&self, class: &str, hostname: &str) -> bool;
+    /// Hostname matches one of the class's `[[conditionals]]`
+    fn hostname_conditional(&self, class: &str, hostname: &str) -> bool;
+    /// Service is listed under the class in `[services]`
+    fn service_in_class(&self, class: &str, service: &str) -> bool;
+    /// Vendor is listed under the class in `[vendor_classes]`
+    fn vendor_in_class(&self, class: &str, vendor: &str) -> bool;
+    /// Vendor registered for the MAC's OUI
+    fn mac_vendor(&self, mac: &str) -> Option<&str>;
+    fn is_mac_desktop_service(&self, service: &str) -> bool;
+    fn is_smart_home_service(&self, service: &str) -> bool;
+    /// Model starts with one of `standalone.soundbar_model_prefixes`
+    fn soundbar_model_prefix(&self, model: &str) -> bool;
+    /// Hostname starts with one of `standalone.lg_appliance_prefixes`
+    fn lg_appliance_prefix(&self, hostname: &str) -> bool;
+}
+
+/// Check if hostname matches any pattern in list
+pub(crate) fn matches_pattern(hostname: &str, patterns: &[&str]) -> bool {
+    patterns.iter().any(|p| hostname.contains(p))
+}
+
+/// Check if hostname starts with any prefix in list
+pub(crate) fn matches_prefix(hostname: &str, prefixes: &[&str]) -> bool {
+    prefixes.iter().any(|p| hostname.starts_with(p))
+}
+
+/// Check if hostname matches pattern but not exclusion
+pub(crate) fn matches_conditional(hostname: &str, conditionals: &[(&str, &str)]) -> bool {
+    conditionals
+        .iter()
+        .any(|(pattern, exclude)| hostname.contains(pattern) && !hostname.contains(exclude))
+}
+
+/// Check if hostname indicates a printer
+pub(crate) fn is_printer_hostname<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    rules.hostname_pattern("printer", hostname) || rules.hostname_prefix("printer", hostname)
+}
+
+/// Check if hostname indicates a TV/streaming device
+pub(crate) fn is_tv_hostname<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    if rules.hostname_pattern("tv", hostname) || rules.hostname_prefix("tv", hostname) {
+        return true;
+    }
+    // Roku serial number as hostname (e.g., YN00NJ468680)
+    let hostname_upper = hostname.to_uppercase();
+    is_roku_serial_number(&hostname_upper)
+}
+
+/// Check if hostname indicates a gaming console
+pub(crate) fn is_gaming_hostname<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    rules.hostname_pattern("gaming", hostname)
+}
+
+/// Check if hostname indicates a phone/tablet
+pub(crate) fn is_phone_hostname<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    if rules.hostname_pattern("phone", hostname) || rules.hostname_prefix("phone", hostname) {
+        return true;
+    }
+    if rules.hostname_conditional("phone", hostname) {
+        return true;
+    }
+    // Special case: android but not androidtv
+    if hostname.contains("android") && !hostname.contains("androidtv") && !hostname.contains("tv") {
+        return true;
+    }
+    // Special case: asus phone
+    if hostname.contains("asus") && (hostname.contains("phone") || hostname.contains("zenfone")) {
+        return true;
+    }
+    false
+}
+
+/// Check if hostname indicates a VM/container
+pub(crate) fn is_vm_hostname<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    rules.hostname_pattern("vm", hostname)
+        || hostname.starts_with("vm-")
+        || hostname.ends_with("-vm")
+}
+
+/// Check if hostname indicates a soundbar
+pub(crate) fn is_soundbar_hostname<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    if rules.hostname_pattern("soundbar", hostname) {
+        return true;
+    }
+    // Sonos Arc special case
+    if hostname.contains("arc") && (hostname.contains("sonos") || hostname.contains("sound")) {
+        return true;
+    }
+    // Brand + sound combinations
+    let sound_brands = ["yamaha", "samsung", "lg", "vizio"];
+    if sound_brands.iter().any(|b| hostname.contains(b)) && hostname.contains("sound") {
+        return true;
+    }
+    // JBL bar
+    if hostname.contains("jbl") && hostname.contains("bar") {
+        return true;
+    }
+    false
+}
+
+/// Check if hostname indicates an appliance
+pub(crate) fn is_appliance_hostname<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    if rules.hostname_pattern("appliance", hostname) {
+        return true;
+    }
+    // Whirlpool (but not router)
+    if hostname.contains("whirlpool") && !hostname.contains("router") {
+        return true;
+    }
+    // GE appliance
+    if hostname.contains("ge-") && hostname.contains("appliance") {
+        return true;
+    }
+    // Bosch washer/dishwasher
+    if hostname.contains("bosch") && (hostname.contains("wash") || hostname.contains("dish")) {
+        return true;
+    }
+    false
+}
+
+/// Check if hostname indicates an LG ThinQ appliance
+pub(crate) fn is_lg_appliance<R: RuleSet + ?Sized>(rules: &R, hostname: &str) -> bool {
+    if rules.lg_appliance_prefix(hostname) {
+        return true;
+    }
+    // WM with digit as third character (washer model)
+    hostname.starts_with("wm") && hostname.chars().nth(2).is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Check if SSDP/UPnP model indicates a soundbar
+pub(crate) fn is_soundbar_model<R: RuleSet + ?Sized>(rules: &R, model: &str) -> bool {
+    rules.soundbar_model_prefix(&model.to_lowercase())
+}
+
+/// Check if a model name indicates a TV
+pub(crate) fn is_tv_model(model: &str) -> bool {
+    let model_lower = model.to_lowercase();
+    let model_upper = model.to_uppercase();
+
+    // Check for known TV model patterns
+    // Samsung TV model patterns
+    if model_upper.starts_with("QN")
+        || model_upper.starts_with("UN")
+        || model_upper.starts_with("UA")
+    {
+        // QN = QLED, UN/UA = LED TVs
+        // e.g., QN43LS03TAFXZA (The Frame), UN55TU8000FXZA
+        return true;
+    }
+
+    // Samsung Frame TVs (LS series)
+    if model_upper.contains("LS03") || model_upper.contains("LS01") {
+        return true;
+    }
+
+    // LG TV model patterns
+    if model_upper.starts_with("OLED") || model_upper.starts_with("NANO") {
+        return true;
+    }
+
+    // Sony Bravia
+    if model_lower.contains("bravia")
+        || model_upper.starts_with("XR")
+        || model_upper.starts_with("KD-")
+    {
+        return true;
+    }
+
+    // Vizio
+    if model_lower.contains("vizio") {
+        return true;
+    }
+
+    // Roku TV platform identifiers (TCL, Hisense, etc. running Roku OS)
+    // Format: 4 digits followed by optional X (e.g., 7105X, 7000X, 6500X, 3800X)
+    // 7XXX series = TCL TVs, 6XXX = mid-range, 3XXX = budget models
+    if is_roku_tv_model(&model_upper) {
+        return true;
+    }
+
+    // Check for generic TV indicators in model name
+    if model_lower.contains("the frame") || model_lower.contains("samsung tv") {
+        return true;
+    }
+
+    false
+}
+
+/// Check if a string is a Roku serial number
+/// Roku serial numbers follow the pattern: 2 letters + 2 digits + 2 letters + N digits
+/// - 12 chars total: 2 letters + 2 digits + 2 letters + 6 digits (e.g., YN00NJ468680)
+/// - 10 chars total: 2 letters + 2 digits + 2 letters + 4 digits (e.g., BR23AM1691)
+pub(crate) fn is_roku_serial_number(s: &str) -> bool {
+    // Must be 10 or 12 ASCII characters
+    if !s.is_ascii() || (s.len() != 10 && s.len() != 12) {
+        return false;
+    }
+    let chars: Vec<char> = s.chars().collect();
+    // First 2 chars: letters
+    chars[0].is_ascii_alphabetic()
+        && chars[1].is_ascii_alphabetic()
+        // Next 2 chars: digits
+        && chars[2].is_ascii_digit()
+        && chars[3].is_ascii_digit()
+        // Next 2 chars: letters
+        && chars[4].is_ascii_alphabetic()
+        && chars[5].is_ascii_alphabetic()
+        // Remaining chars (4 or 6): all digits
+        && chars[6..].iter().all(|c| c.is_ascii_digit())
+}
+
+/// Check if model is a Roku TV platform identifier
+/// Roku TV models follow patterns like 7105X, 7000X, 6500X, 3800X
+pub(crate) fn is_roku_tv_model(model: &str) -> bool {
+    let model_upper = model.to_uppercase();
+    // Pattern: 4 digits, optionally followed by X
+    if model_upper.len() >= 4 && model_upper.len() <= 5 {
+        let chars: Vec<char> = model_upper.chars().collect();
+        // First 4 chars must be digits
+        if chars.len() >= 4 && chars[0..4].iter().all(|c| c.is_ascii_digit()) {
+            // 5th char (if present) must be X
+            if chars.len() == 4 || chars[4] == 'X' {
+                // Roku TV models typically start with 3, 4, 5, 6, or 7
+                let first_digit = chars[0];
+                return matches!(first_digit, '3' | '4' | '5' | '6' | '7' | '8' | '9');
+            }
+        }
+    }
+
+    // Roku serial number format used as hostname (e.g., YN00NJ468680)
+    // Pattern: 2 letters + 2 digits + 2 letters + 6 digits (12 chars total)
+    if is_roku_serial_number(&model_upper) {
+        return true;
+    }
+    false
+}
+
+/// Check if any MAC address has a vendor in the given `[vendor_classes]` group
+fn has_vendor_in_class<R: RuleSet + ?Sized>(rules: &R, macs: &[String], class: &str) -> bool {
+    macs.iter().any(|mac| {
+        rules
+            .mac_vendor(mac)
+            .is_some_and(|v| rules.vendor_in_class(class, v))
+    })
+}
+
+/// Check if any MAC address matches known IoT/appliance vendor OUIs
+pub(crate) fn is_appliance_mac<R: RuleSet + ?Sized>(rules: &R, macs: &[String]) -> bool {
+    if has_vendor_in_class(rules, macs, "appliance") {
+        return true;
+    }
+    // Check SmartThings sensor MAC prefixes (mapped to Samsung vendor)
+    macs.iter().any(|mac| {
+        let mac_lower = mac.to_lowercase();
+        mac_lower.starts_with("70:2c:1f") || mac_lower.starts_with("28:6d:97")
+    })
+}
+
+/// Check if any MAC address matches known gaming vendor OUIs
+pub(crate) fn is_gaming_mac<R: RuleSet + ?Sized>(rules: &R, macs: &[String]) -> bool {
+    has_vendor_in_class(rules, macs, "gaming")
+}
+
+/// Check if any MAC address matches known TV/streaming vendor OUIs
+pub(crate) fn is_tv_mac<R: RuleSet + ?Sized>(rules: &R, macs: &[String]) -> bool {
+    has_vendor_in_class(rules, macs, "tv")
+}
+
+/// Check if any MAC address is from Apple
+pub(crate) fn is_apple_mac<R: RuleSet + ?Sized>(rules: &R, macs: &[String]) -> bool {
+    macs.iter()
+        .any(|mac| rules.mac_vendor(mac).is_some_and(|v| v == "Apple"))
+}
+
+/// Check if any MAC address matches known gateway/router vendor OUIs
+pub(crate) fn is_gateway_mac<R: RuleSet + ?Sized>(rules: &R, macs: &[String]) -> bool {
+    has_vendor_in_class(rules, macs, "gateway")
+}
+
+/// Check if hostname indicates a Mac computer (not a phone)
+pub(crate) fn is_mac_computer_hostname(hostname: &str) -> bool {
+    let mac_patterns = [
+        "macbook",
+        "mac-book",
+        "imac",
+        "i-mac",
+        "mac-mini",
+        "macmini",
+        "mac-pro",
+        "macpro",
+        "mac-studio",
+        "macstudio",
+    ];
+    mac_patterns.iter().any(|p| hostname.contains(p))
+}
+
+/// Check if device is likely a phone based on MAC and services
+/// Apple devices that don't advertise file sharing services are likely iPhones/iPads
+pub(crate) fn is_phone_mac<R: RuleSet + ?Sized>(
+    rules: &R,
+    macs: &[String],
+    services: &[String],
+    hostname: Option<&str>,
+) -> bool {
+    // Only applies to Apple devices (iPhones/iPads)
+    if !is_apple_mac(rules, macs) {
+        return false;
+    }
+
+    // Never classify Mac computers as phones based on hostname
+    if hostname.is_some_and(|h| is_mac_computer_hostname(&h.to_lowercase())) {
+        return false;
+    }
+
+    // Check if device advertises any desktop/Mac services
+    if services.iter().any(|s| rules.is_mac_desktop_service(s)) {
+        // This is a Mac (desktop), not a phone
+        return false;
+    }
+
+    // Apple device without desktop services = likely iPhone/iPad
+    true
+}
+
+/// `[services]` groups, checked in priority order
+const SERVICE_CLASSIFICATIONS: &[&str] = &[
+    CLASSIFICATION_APPLIANCE,
+    CLASSIFICATION_PHONE,
+    CLASSIFICATION_SOUNDBAR,
+    CLASSIFICATION_PRINTER,
+    CLASSIFICATION_TV,
+];
+
+/// Check mDNS services for device type
+pub(crate) fn classify_by_services<R: RuleSet + ?Sized>(
+    rules: &R,
+    services: &[String],
+    hostname: Option<&str>,
+) -> Option<&'static str> {
+    for service in services {
+        for &classification in SERVICE_CLASSIFICATIONS {
+            if !rules.service_in_class(classification, service) {
+                continue;
+            }
+            // Skip phone classification for Mac computers
+            // (they also advertise _companion-link._tcp)
+            if classification == CLASSIFICATION_PHONE
+                && hostname.is_some_and(is_mac_computer_hostname)
+            {
+                continue;
+            }
+            return Some(classification);
+        }
+    }
+    // Matter and Thread only tell us it's smart-home gear; hubs are often TVs or speakers,
+    // so any more specific service above wins
+    if services.iter().any(|s| rules.is_smart_home_service(s)) {
+        return Some(CLASSIFICATION_APPLIANCE);
+    }
+    None
+}
+
+/// Check if port combination indicates a computer (laptop/desktop)
+/// Computers typically have remote access ports (RDP/VNC) combined with file sharing
+pub(crate) fn is_computer_by_ports(ports: &[u16]) -> bool {
+    let has_remote_access = ports.contains(&3389)  // RDP (Windows Remote Desktop)
+        || ports.contains(&5900)                    // VNC
+        || ports.contains(&22); // SSH
+
+    let has_file_sharing = ports.contains(&445)    // SMB (Windows file sharing)
+        || ports.contains(&548)                     // AFP (Apple file sharing)
+        || ports.contains(&139); // NetBIOS
+
+    // Must have both remote access AND file sharing to be classified as computer
+    // This avoids false positives from devices that just have SSH
+    has_remote_access && has_file_sharing
+}
+
+/// Classify by port number
+pub(crate) fn classify_by_port(port: u16) -> Option<&'static str> {
+    match port {
+        // Printer ports
+        9100 | 631 | 515 => Some(CLASSIFICATION_PRINTER),
+        // Gaming console ports (check BEFORE TV ports)
+        9295..=9297 => Some(CLASSIFICATION_GAMING), // PlayStation Remote Play
+        3478..=3480 => Some(CLASSIFICATION_GAMING), // PlayStation Network
+        3074 => Some(CLASSIFICATION_GAMING),        // Xbox Live
+        // TV/Streaming ports
+        8008 | 8009 => Some(CLASSIFICATION_TV), // Chromecast
+        7000 | 7001 | 8001 | 8002 => Some(CLASSIFICATION_TV), // Samsung TV
+        3000 | 3001 => Some(CLASSIFICATION_TV), // LG WebOS
+        6467 | 6466 => Some(CLASSIFICATION_TV), // Roku
+        // VM/Container ports
+        902 | 903 => Some(CLASSIFICATION_VIRTUALIZATION), // VMware ESXi
+        8006 => Some(CLASSIFICATION_VIRTUALIZATION),      // Proxmox
+        2179 => Some(CLASSIFICATION_VIRTUALIZATION),      // Hyper-V
+        2375 | 2376 => Some(CLASSIFICATION_VIRTUALIZATION), // Docker API
+        6443 => Some(CLASSIFICATION_VIRTUALIZATION),      // Kubernetes API
+        10250 => Some(CLASSIFICATION_VIRTUALIZATION),     // Kubelet
+        9000 => Some(CLASSIFICATION_VIRTUALIZATION),      // Portainer
+        _ => None,
+    }
+}
+
+/// The stages of device type classification, in the order they are consulted; the first
+/// with an answer decides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DeviceTypeTier {
+    /// SSDP/UPnP model (soundbars, TVs)
+    Model,
+    Hostname,
+    /// mDNS service advertisements
+    Mdns,
+    /// MAC vendor
+    Mac,
+    /// Open ports
+    Ports,
+}
+
+impl DeviceTypeTier {
+    pub(crate) const ALL: [DeviceTypeTier; 5] = [
+        DeviceTypeTier::Model,
+        DeviceTypeTier::Hostname,
+        DeviceTypeTier::Mdns,
+        DeviceTypeTier::Mac,
+        DeviceTypeTier::Ports,
+    ];
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            DeviceTypeTier::Model => "model",
+            DeviceTypeTier::Hostname => "hostname",
+            DeviceTypeTier::Mdns => "mdns",
+            DeviceTypeTier::Mac => "mac",
+            DeviceTypeTier::Ports => "ports",
+        }
+    }
+
+    /// This tier's classification on its own. `lower` is the lowercased hostname.
+    pub(crate) fn classify<R: RuleSet + ?Sized>(
+        &self,
+        rules: &R,
+        lower: Option<&str>,
+        services: &[String],
+        ports: &[u16],
+        macs: &[String],
+        model: Option<&str>,
+    ) -> Option<&'static str> {
+        match self {
+            DeviceTypeTier::Model => {
+                // Most reliable for identifying device type
+                let m = model?;
+                if is_soundbar_model(rules, m) {
+                    return Some(CLASSIFICATION_SOUNDBAR);
+                }
+                // TV models (Samsung Frame, QLED, LG OLED, etc.)
+                is_tv_model(m).then_some(CLASSIFICATION_TV)
+            }
+            DeviceTypeTier::Hostname => {
+                // Checked before mDNS services - most reliable for user devices, and
+                // prevents services from misclassifying computers/phones as TVs
+                let h = lower?;
+                // LG ThinQ appliances FIRST (they advertise AirPlay but aren't TVs)
+                if is_lg_appliance(rules, h) {
+                    return Some(CLASSIFICATION_APPLIANCE);
+                }
+                // Order matters: check more specific patterns first
+                if is_printer_hostname(rules, h) {
+                    Some(CLASSIFICATION_PRINTER)
+                } else if is_phone_hostname(rules, h) {
+                    Some(CLASSIFICATION_PHONE)
+                } else if is_gaming_hostname(rules, h) {
+                    Some(CLASSIFICATION_GAMING)
+                } else if is_tv_hostname(rules, h) {
+                    Some(CLASSIFICATION_TV)
+                } else if is_vm_hostname(rules, h) {
+                    Some(CLASSIFICATION_VIRTUALIZATION)
+                } else if is_soundbar_hostname(rules, h) {
+                    Some(CLASSIFICATION_SOUNDBAR)
+                } else if is_appliance_hostname(rules, h) {
+                    Some(CLASSIFICATION_APPLIANCE)
+                } else {
+                    None
+                }
+            }
+            // Catches smart devices that don't have distinctive hostnames
+            DeviceTypeTier::Mdns => classify_by_services(rules, services, lower),
+            DeviceTypeTier::Mac => {
+                // Gateway first - networking equipment vendors; then Apple devices
+                // without desktop services, which are likely iPhones/iPads
+                if is_gateway_mac(rules, macs) {
+                    Some(CLASSIFICATION_GATEWAY)
+                } else if is_phone_mac(rules, macs, services, lower) {
+                    Some(CLASSIFICATION_PHONE)
+                } else if is_gaming_mac(rules, macs) {
+                    Some(CLASSIFICATION_GAMING)
+                } else if is_tv_mac(rules, macs) {
+                    Some(CLASSIFICATION_TV)
+                } else if is_appliance_mac(rules, macs) {
+                    Some(CLASSIFICATION_APPLIANCE)
+                } else {
+                    None
+                }
+            }
+            DeviceTypeTier::Ports => {
+                // RDP (3389) or VNC (5900) combined with file sharing ports indicates a computer
+                if is_computer_by_ports(ports) {
+                    return Some(CLASSIFICATION_COMPUTER);
+                }
+                // Single ports are less reliable, hence last
+                ports.iter().find_map(|&port| classify_by_port(port))
+            }
+        }
+    }
+
+    /// The first tier's classification, as `classify_device_type` reports it
+    pub(crate) fn classify_all<R: RuleSet + ?Sized>(
+        rules: &R,
+        lower: Option<&str>,
+        services: &[String],
+        ports: &[u16],
+        macs: &[String],
+        model: Option<&str>,
+    ) -> Option<&'static str> {
+        Self::ALL
+            .into_iter()
+            .find_map(|tier| tier.classify(rules, lower, services, ports, macs, model))
+    }
+}
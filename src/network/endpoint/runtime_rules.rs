@@ -0,0 +1,424 @@
+//! Device rules and OUI overrides read at runtime and merged over the compiled-in data,
+//! so rule changes can be tried without a rebuild. The rules file uses the format of
+//! tools/device-rules-generator/device_rules.toml (only the sections listed in
+//! `RulesFile` apply; runtime patterns add to the compiled ones), and the overrides file
+//! that of tools/oui-generator/overrides.toml (runtime overrides win). Both files are
+//! optional and re-read when they change on disk or on `/api/classification/reload`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tokio::task;
+
+use crate::db::get_setting;
+
+/// Hostname groups `is_*_hostname` consult, by device_rules.toml key
+const HOSTNAME_CLASSES: &[&str] = &[
+    "printer",
+    "tv",
+    "gaming",
+    "phone",
+    "vm",
+    "soundbar",
+    "appliance",
+];
+/// MAC vendor groups the `is_*_mac` checks consult
+const VENDOR_CLASSES: &[&str] = &["appliance", "gaming", "tv", "gateway"];
+/// mDNS service groups `classify_by_services` consults
+const SERVICE_CLASSES: &[&str] = &["appliance", "phone", "soundbar", "printer", "tv"];
+
+/// How often the watcher checks the files for changes
+const WATCH_INTERVAL_SECS: u64 = 5;
+
+static RUNTIME_RULES: LazyLock<RwLock<Arc<RuntimeRules>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(RuntimeRules::default())));
+
+static WATCHER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Vendor names handed out as `&'static str`, like the compiled-in ones. Each distinct
+/// name is leaked once, however often the files are reloaded.
+static INTERNED: LazyLock<Mutex<HashSet<&'static str>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+fn intern(name: &str) -> &'static str {
+    let mut interned = INTERNED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(existing) = interned.get(name) {
+        return existing;
+    }
+    let leaked: &'static str = Box::leak(name.to_string().into_boxed_str());
+    interned.insert(leaked);
+    leaked
+}
+
+#[derive(Deserialize, Default)]
+struct RulesFile {
+    #[serde(default)]
+    patterns: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    prefixes: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    vendor_classes: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    services: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    hostname_vendors: Vec<HostnameVendorRule>,
+    #[serde(default)]
+    standalone: StandaloneLists,
+}
+
+/// The `[standalone]` lists that can be extended at runtime
+#[derive(Deserialize, Default)]
+struct StandaloneLists {
+    #[serde(default)]
+    component_vendors: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct HostnameVendorRule {
+    match_type: String,
+    patterns: Vec<String>,
+    vendor: String,
+}
+
+#[derive(Deserialize, Default)]
+struct OverridesFile {
+    #[serde(default)]
+    overrides: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub(crate) struct RuntimeRules {
+    /// Lowercase hostname substrings by device_rules.toml class
+    patterns: HashMap<String, Vec<String>>,
+    /// Lowercase hostname prefixes by class
+    prefixes: HashMap<String, Vec<String>>,
+    vendor_classes: HashMap<String, Vec<String>>,
+    services: HashMap<String, Vec<String>>,
+    /// (starts_with, lowercase pattern, vendor), checked in file order
+    hostname_vendors: Vec<(bool, String, &'static str)>,
+    /// Chip makers to add to the compiled-in COMPONENT_VENDORS
+    component_vendors: Vec<String>,
+    /// Lowercase "aa:bb:cc" OUI -> vendor
+    mac_vendors: HashMap<String, &'static str>,
+}
+
+/// What the last reload picked up
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RuntimeRulesSummary {
+    pub rules_file: String,
+    pub rules_file_found: bool,
+    pub overrides_file: String,
+    pub overrides_file_found: bool,
+    pub hostname_patterns: usize,
+    pub vendor_class_entries: usize,
+    pub service_entries: usize,
+    pub hostname_vendor_rules: usize,
+    pub component_vendors: usize,
+    pub oui_overrides: usize,
+}
+
+fn check_classes(
+    section: &str,
+    map: &HashMap<String, Vec<String>>,
+    allowed: &[&str],
+) -> Result<(), String> {
+    match map.keys().find(|key| !allowed.contains(&key.as_str())) {
+        Some(key) => Err(format!(
+            "Unknown class \"{}\" in [{}] (expected one of: {})",
+            key,
+            section,
+            allowed.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+fn lowercase_lists(map: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+    map.into_iter()
+        .map(|(class, items)| (class, items.iter().map(|i| i.to_lowercase()).collect()))
+        .collect()
+}
+
+/// Normalize "AA:BB:CC", "aa-bb-cc" or "aabbcc" to "aa:bb:cc"
+fn normalize_oui(oui: &str) -> Option<String> {
+    let hex: String = oui
+        .trim()
+        .chars()
+        .filter(|c| *c != ':' && *c != '-')
+        .collect();
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = hex.to_ascii_lowercase();
+    Some(format!("{}:{}:{}", &hex[0..2], &hex[2..4], &hex[4..6]))
+}
+
+impl RuntimeRules {
+    /// Build from the contents of the rules and overrides files (None when missing)
+    pub(crate) fn parse(rules: Option<&str>, overrides: Option<&str>) -> Result<Self, String> {
+        let file: RulesFile = match rules {
+            Some(content) => {
+                toml::from_str(content).map_err(|e| format!("Invalid device rules: {}", e))?
+            }
+            None => RulesFile::default(),
+        };
+        check_classes("patterns", &file.patterns, HOSTNAME_CLASSES)?;
+        check_classes("prefixes", &file.prefixes, HOSTNAME_CLASSES)?;
+        check_classes("vendor_classes", &file.vendor_classes, VENDOR_CLASSES)?;
+        check_classes("services", &file.services, SERVICE_CLASSES)?;
+
+        let mut hostname_vendors = Vec::new();
+        for rule in &file.hostname_vendors {
+            let starts_with = match rule.match_type.as_str() {
+                "contains" => false,
+                "starts_with" => true,
+                other => return Err(format!("Invalid match_type in hostname_vendors: {}", other)),
+            };
+            let vendor = intern(&rule.vendor);
+            hostname_vendors.extend(
+                rule.patterns
+                    .iter()
+                    .map(|p| (starts_with, p.to_lowercase(), vendor)),
+            );
+        }
+
+        let overrides: OverridesFile = match overrides {
+            Some(content) => {
+                toml::from_str(content).map_err(|e| format!("Invalid OUI overrides: {}", e))?
+            }
+            None => OverridesFile::default(),
+        };
+        let mut mac_vendors = HashMap::new();
+        for (oui, vendor) in &overrides.overrides {
+            let prefix = normalize_oui(oui)
+                .ok_or_else(|| format!("Invalid OUI prefix in overrides: {}", oui))?;
+            mac_vendors.insert(prefix, intern(vendor));
+        }
+
+        Ok(Self {
+            patterns: lowercase_lists(file.patterns),
+            prefixes: lowercase_lists(file.prefixes),
+            vendor_classes: file.vendor_classes,
+            services: file.services,
+            hostname_vendors,
+            component_vendors: file.standalone.component_vendors,
+            mac_vendors,
+        })
+    }
+
+    /// Whether a lowercase hostname matches the runtime patterns or prefixes of a class
+    pub(crate) fn hostname_matches(&self, class: &str, hostname: &str) -> bool {
+        self.patterns
+            .get(class)
+            .is_some_and(|patterns| patterns.iter().any(|p| hostname.contains(p.as_str())))
+            || self
+                .prefixes
+                .get(class)
+                .is_some_and(|prefixes| prefixes.iter().any(|p| hostname.starts_with(p.as_str())))
+    }
+
+    pub(crate) fn vendor_in_class(&self, class: &str, vendor: &str) -> bool {
+        self.vendor_classes
+            .get(class)
+            .is_some_and(|vendors| vendors.iter().any(|v| v == vendor))
+    }
+
+    pub(crate) fn service_in_class(&self, class: &str, service: &str) -> bool {
+        self.services
+            .get(class)
+            .is_some_and(|services| services.iter().any(|s| s == service))
+    }
+
+    /// Vendor for a lowercase hostname from the runtime hostname_vendors rules
+    pub(crate) fn hostname_vendor(&self, hostname: &str) -> Option<&'static str> {
+        self.hostname_vendors
+            .iter()
+            .find(|(starts_with, pattern, _)| {
+                if *starts_with {
+                    hostname.starts_with(pattern.as_str())
+                } else {
+                    hostname.contains(pattern.as_str())
+                }
+            })
+            .map(|(_, _, vendor)| *vendor)
+    }
+
+    pub(crate) fn component_vendors(&self) -> &[String] {
+        &self.component_vendors
+    }
+
+    /// Vendor for a lowercase "aa:bb:cc" OUI from the runtime overrides
+    pub(crate) fn mac_vendor(&self, oui: &str) -> Option<&'static str> {
+        self.mac_vendors.get(oui).copied()
+    }
+
+    fn summary(&self) -> RuntimeRulesSummary {
+        let count = |map: &HashMap<String, Vec<String>>| map.values().map(Vec::len).sum::<usize>();
+        RuntimeRulesSummary {
+            hostname_patterns: count(&self.patterns) + count(&self.prefixes),
+            vendor_class_entries: count(&self.vendor_classes),
+            service_entries: count(&self.services),
+            hostname_vendor_rules: self.hostname_vendors.len(),
+            component_vendors: self.component_vendors.len(),
+            oui_overrides: self.mac_vendors.len(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The rules currently in effect
+pub(crate) fn runtime_rules() -> Arc<RuntimeRules> {
+    RUNTIME_RULES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Paths of the rules and overrides files, from settings
+fn runtime_rule_paths() -> (String, String) {
+    let path = |key: &str, default: &str| {
+        get_setting(key)
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| default.to_string())
+    };
+    (
+        path("device_rules_file", "device_rules.toml"),
+        path("oui_overrides_file", "oui_overrides.toml"),
+    )
+}
+
+fn read_optional(path: &str) -> Result<Option<String>, String> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path)
+        .map(Some)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+/// Re-read both files and swap them in. On error the previous rules stay in effect.
+pub fn reload_runtime_rules() -> Result<RuntimeRulesSummary, String> {
+    let (rules_path, overrides_path) = runtime_rule_paths();
+    let rules = read_optional(&rules_path)?;
+    let overrides = read_optional(&overrides_path)?;
+    let parsed = RuntimeRules::parse(rules.as_deref(), overrides.as_deref())?;
+
+    let summary = RuntimeRulesSummary {
+        rules_file: rules_path,
+        rules_file_found: rules.is_some(),
+        overrides_file: overrides_path,
+        overrides_file_found: overrides.is_some(),
+        ..parsed.summary()
+    };
+    *RUNTIME_RULES.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(parsed);
+    Ok(summary)
+}
+
+/// Paths and modification times of both files, to notice edits, renames and deletions
+fn file_signature() -> Vec<(String, Option<SystemTime>)> {
+    let (rules_path, overrides_path) = runtime_rule_paths();
+    [rules_path, overrides_path]
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Load the runtime rules and reload them whenever either file changes
+pub fn start_runtime_rules_watcher() {
+    if WATCHER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        let mut last_signature = None;
+        loop {
+            let signature = task::spawn_blocking(file_signature).await.ok();
+            if signature.is_some() && signature != last_signature {
+                match task::spawn_blocking(reload_runtime_rules).await {
+                    Ok(Ok(summary)) if summary.rules_file_found || summary.overrides_file_found => {
+                        println!(
+                            "Loaded runtime device rules: {} hostname patterns, {} OUI overrides",
+                            summary.hostname_patterns, summary.oui_overrides
+                        )
+                    }
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => eprintln!("Runtime device rules not reloaded: {}", e),
+                    Err(e) => eprintln!("Runtime device rules reload task failed: {}", e),
+                }
+                last_signature = signature;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(WATCH_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_runtime_rules() {
+        let rules = RuntimeRules::parse(
+            Some(
+                r#"
+                [patterns]
+                printer = ["Zebra-"]
+                [prefixes]
+                vm = ["lab"]
+                [vendor_classes]
+                gateway = ["Acme Networks"]
+                [services]
+                tv = ["_acmetv._tcp"]
+                [standalone]
+                component_vendors = ["Acme Silicon"]
+                [[hostname_vendors]]
+                match_type = "starts_with"
+                patterns = ["acme"]
+                vendor = "Acme"
+                "#,
+            ),
+            Some("[overrides]\n\"AA-BB-CC\" = \"Acme Networks\"\n"),
+        )
+        .unwrap();
+
+        assert!(rules.hostname_matches("printer", "warehouse-zebra-01"));
+        assert!(rules.hostname_matches("vm", "lab-runner"));
+        assert!(!rules.hostname_matches("vm", "my-lab"));
+        assert!(!rules.hostname_matches("tv", "warehouse-zebra-01"));
+        assert!(rules.vendor_in_class("gateway", "Acme Networks"));
+        assert!(rules.service_in_class("tv", "_acmetv._tcp"));
+        assert_eq!(rules.hostname_vendor("acme-cam"), Some("Acme"));
+        assert_eq!(rules.hostname_vendor("cam-acme"), None);
+        assert_eq!(rules.mac_vendor("aa:bb:cc"), Some("Acme Networks"));
+        assert_eq!(rules.component_vendors(), ["Acme Silicon".to_string()]);
+        assert_eq!(rules.summary().hostname_patterns, 2);
+
+        let empty = RuntimeRules::parse(None, None).unwrap();
+        assert_eq!(empty.summary().oui_overrides, 0);
+    }
+
+    #[test]
+    fn test_parse_runtime_rules_errors() {
+        assert!(RuntimeRules::parse(Some("[patterns]\ntoaster = [\"x\"]"), None).is_err());
+        assert!(RuntimeRules::parse(Some("[vendor_classes]\nprinter = [\"x\"]"), None).is_err());
+        assert!(RuntimeRules::parse(Some("[patterns"), None).is_err());
+        assert!(
+            RuntimeRules::parse(
+                Some("[[hostname_vendors]]\nmatch_type = \"regex\"\npatterns = []\nvendor = \"x\""),
+                None
+            )
+            .is_err()
+        );
+        assert!(RuntimeRules::parse(None, Some("[overrides]\n\"aa:bb\" = \"x\"")).is_err());
+        assert_eq!(normalize_oui("AABBCC").as_deref(), Some("aa:bb:cc"));
+        assert!(std::ptr::eq(intern("Acme"), intern("Acme")));
+    }
+}
@@ -0,0 +1,122 @@
+//! JSON row snapshots used to undo destructive endpoint operations (merges, deletes).
+//! Rows are captured as column-name keyed JSON objects and re-inserted verbatim.
+
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, Result, params, params_from_iter};
+use serde_json::{Map, Value};
+
+/// A JSON object holding one table row keyed by column name
+pub(super) type JsonRow = Map<String, Value>;
+
+/// Convert a SQLite value to JSON (BLOBs are not used by the snapshotted tables)
+fn sql_to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => Value::Null,
+        ValueRef::Integer(v) => Value::from(v),
+        ValueRef::Real(v) => Value::from(v),
+        ValueRef::Text(t) => Value::from(String::from_utf8_lossy(t).into_owned()),
+    }
+}
+
+/// Convert a JSON value back to a SQLite value for re-insertion
+pub(super) fn json_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+pub(super) fn json_ids(value: Option<&Value>) -> Vec<i64> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|ids| ids.iter().filter_map(|id| id.as_i64()).collect())
+        .unwrap_or_default()
+}
+
+pub(super) fn json_rows(value: Option<&Value>) -> Vec<JsonRow> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|rows| {
+            rows.iter()
+                .filter_map(|row| row.as_object().cloned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Select full rows as JSON objects for a query taking a single id parameter
+pub(super) fn query_rows_as_json(conn: &Connection, sql: &str, id: i64) -> Result<Vec<JsonRow>> {
+    let mut stmt = conn.prepare(sql)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let rows = stmt.query_map([id], |row| {
+        let mut map = JsonRow::new();
+        for (i, column) in columns.iter().enumerate() {
+            map.insert(column.clone(), sql_to_json(row.get_ref(i)?));
+        }
+        Ok(map)
+    })?;
+    rows.collect()
+}
+
+pub(super) fn query_ids(conn: &Connection, sql: &str, id: i64) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([id], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Re-insert a snapshotted row with its original column values (including id)
+pub(super) fn insert_json_row(conn: &Connection, table: &str, row: &JsonRow) -> Result<usize> {
+    insert_json_row_with(conn, "INSERT", table, row)
+}
+
+/// Like `insert_json_row`, but skips rows that conflict with a unique constraint
+pub(super) fn insert_json_row_or_ignore(
+    conn: &Connection,
+    table: &str,
+    row: &JsonRow,
+) -> Result<usize> {
+    insert_json_row_with(conn, "INSERT OR IGNORE", table, row)
+}
+
+fn insert_json_row_with(
+    conn: &Connection,
+    verb: &str,
+    table: &str,
+    row: &JsonRow,
+) -> Result<usize> {
+    let columns: Vec<&str> = row.keys().map(String::as_str).collect();
+    let placeholders = vec!["?"; columns.len()].join(",");
+    let sql = format!(
+        "{} INTO {} ({}) VALUES ({})",
+        verb,
+        table,
+        columns.join(","),
+        placeholders
+    );
+    conn.execute(&sql, params_from_iter(row.values().map(json_to_sql)))
+}
+
+/// Update an id-list of rows in `table`, moving `column` from one endpoint to another
+pub(super) fn move_rows(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    ids: &[i64],
+    from_id: i64,
+    to_id: i64,
+) -> Result<usize> {
+    let mut moved = 0;
+    for id in ids {
+        moved += conn.execute(
+            &format!("UPDATE OR IGNORE {table} SET {column} = ?1 WHERE id = ?2 AND {column} = ?3"),
+            params![to_id, id, from_id],
+        )?;
+    }
+    Ok(moved)
+}
@@ -117,3 +117,55 @@ pub struct InternetDestination {
     pub bytes_in: i64,
     pub bytes_out: i64,
 }
+
+/// A recorded endpoint merge, as listed in merge history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeHistoryEntry {
+    pub id: i64,
+    pub created_at: i64,
+    pub target_id: i64,
+    pub source_id: i64,
+    pub source_name: Option<String>,
+    pub undone_at: Option<i64>,
+}
+
+/// A DHCP server handing out (or a client giving up) an address, seen in passing traffic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpLease {
+    /// Client hardware address
+    pub mac: String,
+    pub ip: String,
+    /// Lease time from the ACK; None for infinite leases and releases
+    pub lease_seconds: Option<i64>,
+    pub released: bool,
+}
+
+/// A span during which an endpoint held an IP address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpHistoryEntry {
+    pub endpoint_id: i64,
+    /// Current display name of the endpoint
+    pub endpoint_name: Option<String>,
+    pub ip: String,
+    pub mac: Option<String>,
+    /// How the address was observed ("arp", "ndp", ...)
+    pub source: String,
+    pub bound_at: i64,
+    pub last_seen_at: i64,
+    /// When another endpoint took the address over or the lease was released; None while
+    /// still held
+    pub unbound_at: Option<i64>,
+    /// End of the DHCP lease, for addresses seen being handed out
+    pub lease_expires_at: Option<i64>,
+}
+
+/// A soft-deleted endpoint awaiting restore or purge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedEndpoint {
+    pub id: i64,
+    pub endpoint_id: i64,
+    pub name: Option<String>,
+    pub deleted_at: i64,
+    pub ips: Vec<String>,
+    pub macs: Vec<String>,
+}
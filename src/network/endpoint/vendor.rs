@@ -1,12 +1,13 @@
 //! Vendor identification. Extracts vendor names from MAC OUI prefixes, hostname patterns,
 //! and model numbers with priority-based selection across multiple data sources.
 
-use super::detection::{is_roku_serial_number, is_roku_tv_model, matches_prefix};
+use super::rule_matcher::{is_roku_serial_number, is_roku_tv_model, matches_prefix};
 // Sorted lexicographically by prefix for binary search lookup.
 // Data file generated by oui-generator — do not edit mac_vendor_data.rs directly.
 // To regenerate: `cd tools/oui-generator && cargo run --release -- --verify`
 const MAC_VENDOR_MAP: &[(&str, &str)] = include!("mac_vendor_data.rs");
-use super::patterns::{HOSTNAME_VENDOR_RULES, LG_APPLIANCE_PREFIXES};
+use super::patterns::{COMPONENT_VENDORS, HOSTNAME_VENDOR_RULES, LG_APPLIANCE_PREFIXES};
+use super::runtime_rules::runtime_rules;
 use super::types::{Characterized, pick_best};
 
 /// Get vendor name from MAC address OUI (binary search on sorted map)
@@ -14,6 +15,9 @@ pub fn get_mac_vendor(mac: &str) -> Option<&'static str> {
     let mac_lower = mac.to_lowercase();
     if mac_lower.len() >= 8 {
         let oui = &mac_lower[..8];
+        if let Some(vendor) = runtime_rules().mac_vendor(oui) {
+            return Some(vendor);
+        }
         return MAC_VENDOR_MAP
             .binary_search_by_key(&oui, |(prefix, _)| prefix)
             .ok()
@@ -22,6 +26,36 @@ pub fn get_mac_vendor(mac: &str) -> Option<&'static str> {
     None
 }
 
+/// Component vendors in effect: the compiled-in list followed by any added at runtime
+pub fn component_vendors() -> Vec<String> {
+    let mut vendors: Vec<String> = COMPONENT_VENDORS.iter().map(|v| v.to_string()).collect();
+    for vendor in runtime_rules().component_vendors() {
+        if !vendors.contains(vendor) {
+            vendors.push(vendor.clone());
+        }
+    }
+    vendors
+}
+
+/// Whether a MAC vendor makes chips or modules for other manufacturers (Espressif,
+/// Realtek, ...), so its name says nothing about who made the device
+pub fn is_component_vendor(vendor: &str) -> bool {
+    COMPONENT_VENDORS.contains(&vendor)
+        || runtime_rules()
+            .component_vendors()
+            .iter()
+            .any(|v| v == vendor)
+}
+
+/// The MACs worth identifying a device's vendor by: those whose OUI isn't a component
+/// vendor's
+pub fn product_vendor_macs(macs: &[String]) -> Vec<String> {
+    macs.iter()
+        .filter(|mac| get_mac_vendor(mac).is_none_or(|v| !is_component_vendor(v)))
+        .cloned()
+        .collect()
+}
+
 /// Get vendor name from hostname patterns (fallback when MAC is locally administered)
 pub fn get_hostname_vendor(hostname: &str) -> Option<&'static str> {
     let lower = hostname.to_lowercase();
@@ -43,6 +77,11 @@ pub fn get_hostname_vendor(hostname: &str) -> Option<&'static str> {
         return Some("TCL");
     }
 
+    // Rules loaded at runtime take priority over the compiled-in ones
+    if let Some(vendor) = runtime_rules().hostname_vendor(&lower) {
+        return Some(vendor);
+    }
+
     // Simple rules from generated TOML data
     for &(match_type, pattern, vendor) in HOSTNAME_VENDOR_RULES {
         let matched = match match_type {
@@ -68,6 +107,26 @@ pub fn characterize_vendor(
     macs: &[String],
     model: Option<&str>,
 ) -> Option<Characterized<String>> {
+    let verdicts = vendor_verdicts(
+        custom_vendor,
+        ssdp_friendly_name,
+        snmp_vendor,
+        hostname,
+        macs,
+        model,
+    );
+    pick_best(&verdicts.map(|(_, verdict)| verdict))
+}
+
+/// What each source says the vendor is, by tier name, before `characterize_vendor` picks one
+pub(crate) fn vendor_verdicts(
+    custom_vendor: Option<&str>,
+    ssdp_friendly_name: Option<&str>,
+    snmp_vendor: Option<&str>,
+    hostname: Option<&str>,
+    macs: &[String],
+    model: Option<&str>,
+) -> [(&'static str, Option<Characterized<String>>); 6] {
     // Gather all sources
     let user = custom_vendor
         .filter(|v| !v.is_empty())
@@ -118,8 +177,14 @@ pub fn characterize_vendor(
         .find_map(|mac| get_mac_vendor(mac))
         .map(|v| Characterized::network_inferred(v.to_string()));
 
-    // Pick the best one (highest priority source)
-    pick_best(&[user, ssdp, from_snmp, from_hostname, from_model, from_mac])
+    [
+        ("custom", user),
+        ("ssdp", ssdp),
+        ("snmp", from_snmp),
+        ("hostname", from_hostname),
+        ("model", from_model),
+        ("mac", from_mac),
+    ]
 }
 
 pub fn get_vendor_from_model(model: &str) -> Option<&'static str> {
@@ -253,4 +318,18 @@ mod tests {
         assert_eq!(get_mac_vendor("fc:f1:52:aa:bb:cc"), Some("Sony"));
         assert_eq!(get_mac_vendor("ff:ff:ff:ff:ff:ff"), None);
     }
+
+    #[test]
+    fn test_product_vendor_macs() {
+        assert!(is_component_vendor("Espressif"));
+        assert!(!is_component_vendor("Apple"));
+        // The Espressif module's MAC is dropped; unknown OUIs are kept
+        let macs = [
+            "00:4b:12:aa:bb:cc".to_string(),
+            "00:03:93:ab:cd:ef".to_string(),
+            "ff:ff:ff:ff:ff:ff".to_string(),
+        ];
+        assert_eq!(product_vendor_macs(&macs), macs[1..].to_vec());
+        assert!(component_vendors().contains(&"AzureWave".to_string()));
+    }
 }
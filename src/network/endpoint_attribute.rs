@@ -5,7 +5,7 @@ use std::net::Ipv4Addr;
 
 use rusqlite::{Connection, OptionalExtension, Result, params};
 
-use super::endpoint::{get_mac_vendor, is_valid_display_name, strip_local_suffix};
+use super::endpoint::{EndPoint, get_mac_vendor, is_valid_display_name, strip_local_suffix};
 
 /// Check if MAC is from a gateway/router vendor (for similar-MAC merging)
 /// These vendors often have multiple NICs with sequential MACs on the same device
@@ -324,7 +324,7 @@ impl EndPointAttribute {
         }
 
         // Copy unique attributes from duplicates to kept endpoint
-        for merge_id in &merge_ids {
+        for &merge_id in &merge_ids {
             // Use INSERT OR IGNORE to skip duplicates
             conn.execute(
                 "INSERT OR IGNORE INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname)
@@ -355,6 +355,7 @@ impl EndPointAttribute {
                 "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
                 params![keep_id, merge_id],
             )?;
+            EndPoint::reassign_endpoint_data(conn, merge_id, keep_id)?;
 
             // Reassign notifications so they point to the surviving endpoint
             conn.execute(
@@ -0,0 +1,183 @@
+//! Flow aggregation for busy networks. In flow capture mode the writer coalesces packets
+//! sharing a 5-tuple (plus MACs, interface and capture source) into one communication per
+//! aggregation interval carrying the packet and byte counts, so SQLite sees one upsert per
+//! flow per interval instead of one per packet.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+
+use super::communication::Communication;
+
+const DEFAULT_FLOW_AGGREGATION_INTERVAL_SECS: u64 = 10;
+
+/// Flows buffered before an early flush, bounding memory on very busy links
+const MAX_BUFFERED_FLOWS: usize = 50_000;
+
+/// How the writer stores captured packets (the `capture_mode` setting)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// One upsert per packet (`packet`, the default)
+    Packet,
+    /// One upsert per flow per interval (`flow`)
+    Flow { interval: Duration },
+}
+
+impl CaptureMode {
+    /// Read the mode from the `capture_mode` and `flow_aggregation_interval_seconds` settings
+    pub fn from_settings(conn: &Connection) -> Self {
+        let setting = |key: &str| -> Option<String> {
+            conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .ok()
+        };
+
+        match setting("capture_mode").as_deref() {
+            Some("flow") => {
+                let secs = setting("flow_aggregation_interval_seconds")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(DEFAULT_FLOW_AGGREGATION_INTERVAL_SECS)
+                    .max(1);
+                CaptureMode::Flow {
+                    interval: Duration::from_secs(secs),
+                }
+            }
+            _ => CaptureMode::Packet,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FlowKey {
+    source_mac: Option<String>,
+    destination_mac: Option<String>,
+    source_ip: Option<String>,
+    destination_ip: Option<String>,
+    source_port: Option<u16>,
+    destination_port: Option<u16>,
+    ip_header_protocol: Option<String>,
+    sub_protocol: Option<String>,
+    interface: Option<String>,
+    source: Option<String>,
+}
+
+impl FlowKey {
+    fn of(communication: &Communication) -> Self {
+        FlowKey {
+            source_mac: communication.source_mac.clone(),
+            destination_mac: communication.destination_mac.clone(),
+            source_ip: communication.source_ip.clone(),
+            destination_ip: communication.destination_ip.clone(),
+            source_port: communication.source_port,
+            destination_port: communication.destination_port,
+            ip_header_protocol: communication.ip_header_protocol.clone(),
+            sub_protocol: communication.sub_protocol.clone(),
+            interface: communication.interface.clone(),
+            source: communication.source.clone(),
+        }
+    }
+}
+
+/// Packets coalesced into flows for the current aggregation window
+pub struct FlowAggregator {
+    flows: HashMap<FlowKey, Communication>,
+    window_started: Instant,
+}
+
+impl Default for FlowAggregator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlowAggregator {
+    pub fn new() -> Self {
+        FlowAggregator {
+            flows: HashMap::new(),
+            window_started: Instant::now(),
+        }
+    }
+
+    pub fn add(&mut self, communication: Communication) {
+        match self.flows.entry(FlowKey::of(&communication)) {
+            Entry::Occupied(mut flow) => flow.get_mut().absorb(communication),
+            Entry::Vacant(slot) => {
+                slot.insert(communication);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Whether the window is over (or the buffer is full) and the flows should be written
+    pub fn should_flush(&self, interval: Duration) -> bool {
+        !self.flows.is_empty()
+            && (self.window_started.elapsed() >= interval || self.flows.len() >= MAX_BUFFERED_FLOWS)
+    }
+
+    /// Take the aggregated flows and start a new window
+    pub fn drain(&mut self) -> Vec<Communication> {
+        self.window_started = Instant::now();
+        self.flows.drain().map(|(_, flow)| flow).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(destination_port: u16, size: u32) -> Communication {
+        let mut communication = Communication::default();
+        communication.source_ip = Some("10.0.0.2".to_string());
+        communication.destination_ip = Some("10.0.0.3".to_string());
+        communication.source_port = Some(50000);
+        communication.destination_port = Some(destination_port);
+        communication.ip_header_protocol = Some("Tcp".to_string());
+        communication.packet_size = size;
+        communication.packet_count = 1;
+        communication
+    }
+
+    #[test]
+    fn test_packets_coalesce_per_flow() {
+        let mut flows = FlowAggregator::new();
+        assert!(!flows.should_flush(Duration::ZERO));
+
+        flows.add(packet(443, 100));
+        flows.add(packet(443, 1500));
+        flows.add(packet(80, 60));
+        assert!(flows.should_flush(Duration::ZERO));
+        assert!(!flows.should_flush(Duration::from_secs(3600)));
+
+        let mut drained = flows.drain();
+        drained.sort_by_key(|c| c.destination_port);
+        assert!(flows.is_empty());
+        assert_eq!(drained.len(), 2);
+        assert_eq!((drained[0].packet_count, drained[0].packet_size), (1, 60));
+        assert_eq!((drained[1].packet_count, drained[1].packet_size), (2, 1600));
+    }
+
+    #[test]
+    fn test_capture_mode_from_settings() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);")
+            .unwrap();
+        assert_eq!(CaptureMode::from_settings(&conn), CaptureMode::Packet);
+
+        conn.execute_batch(
+            "INSERT INTO settings VALUES ('capture_mode', 'flow'), ('flow_aggregation_interval_seconds', '30');",
+        )
+        .unwrap();
+        assert_eq!(
+            CaptureMode::from_settings(&conn),
+            CaptureMode::Flow {
+                interval: Duration::from_secs(30)
+            }
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Host table collector. Periodically reads the OS ARP cache and routing table and feeds
+//! each neighbor and next-hop gateway into endpoint creation, so devices appear even
+//! when capture has no raw-socket privileges and no scan has run. Neighbors also pin
+//! their IP to the endpoint like an ARP reply would.
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use pnet::util::MacAddr;
+use rusqlite::Connection;
+use tokio::task;
+
+use crate::db::{get_setting_i64, insert_notification_with_endpoint_id, new_connection};
+use crate::network::endpoint::EndPoint;
+use crate::scanner::neighbor_table::{read_ipv4_neighbors, read_route_gateways};
+
+static HOST_TABLE_COLLECTOR_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Outcome of one pass over the host tables
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HostTableRun {
+    pub neighbors: usize,
+    pub gateways: usize,
+    /// Endpoints created by this pass
+    pub discovered: usize,
+}
+
+/// Read the host tables every `host_table_interval_seconds` (0 disables collection)
+pub fn start_host_table_collector() {
+    if HOST_TABLE_COLLECTOR_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async {
+        // Let tables be created before the first read
+        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+
+        loop {
+            let interval_secs = get_setting_i64("host_table_interval_seconds", 300);
+            if interval_secs > 0 {
+                let result = task::spawn_blocking(|| {
+                    let neighbors = read_ipv4_neighbors();
+                    let gateways = read_route_gateways();
+                    let lease_seconds =
+                        get_setting_i64("ip_binding_lease_minutes", 240).max(1) * 60;
+                    collect_host_tables(&new_connection(), &neighbors, &gateways, lease_seconds)
+                })
+                .await;
+
+                match result {
+                    Ok(run) if run.discovered > 0 => println!(
+                        "Host tables: {} new endpoint(s) from {} ARP entries and {} gateway(s)",
+                        run.discovered, run.neighbors, run.gateways
+                    ),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Host table collection task failed: {}", e),
+                }
+            }
+
+            // Check again for a changed interval even while disabled
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                interval_secs.max(60) as u64
+            ))
+            .await;
+        }
+    });
+}
+
+/// Create or refresh endpoints for ARP cache entries and for route gateways the ARP
+/// cache doesn't already cover. Neighbors are bound to their IP for `lease_seconds`.
+fn collect_host_tables(
+    conn: &Connection,
+    neighbors: &[(IpAddr, MacAddr)],
+    gateways: &[IpAddr],
+    lease_seconds: i64,
+) -> HostTableRun {
+    let mut run = HostTableRun::default();
+
+    for (ip, mac) in neighbors {
+        let ip_str = ip.to_string();
+        let mac_str = mac.to_string();
+        let Ok((endpoint_id, is_new)) = EndPoint::get_or_insert_endpoint(
+            conn,
+            Some(mac_str.clone()),
+            Some(ip_str.clone()),
+            None,
+            &[],
+        ) else {
+            continue;
+        };
+        run.neighbors += 1;
+        if is_new {
+            run.discovered += 1;
+            insert_notification_with_endpoint_id(
+                conn,
+                "endpoint_discovered",
+                &format!("New device discovered: {}", ip_str),
+                Some(&format!("MAC: {} (host ARP table)", mac_str)),
+                Some(&ip_str),
+                Some(endpoint_id),
+            );
+        }
+        if let Err(e) = EndPoint::record_ip_binding(
+            conn,
+            &ip_str,
+            &mac_str,
+            endpoint_id,
+            "arp_table",
+            lease_seconds,
+        ) {
+            eprintln!("Failed to record IP binding for {}: {}", ip_str, e);
+        }
+    }
+
+    for gateway in gateways {
+        if neighbors.iter().any(|(ip, _)| ip == gateway) {
+            continue;
+        }
+        let ip_str = gateway.to_string();
+        let Ok((endpoint_id, is_new)) =
+            EndPoint::get_or_insert_endpoint(conn, None, Some(ip_str.clone()), None, &[])
+        else {
+            continue;
+        };
+        run.gateways += 1;
+        if is_new {
+            run.discovered += 1;
+            insert_notification_with_endpoint_id(
+                conn,
+                "endpoint_discovered",
+                &format!("New device discovered: {}", ip_str),
+                Some("Gateway in host routing table"),
+                Some(&ip_str),
+                Some(endpoint_id),
+            );
+        }
+    }
+
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    #[test]
+    fn test_collect_host_tables() {
+        let conn = new_test_connection();
+        // Loopback addresses are local on any machine, unlike a LAN range the test host
+        // may not be on
+        let neighbors = vec![(
+            "127.0.0.20".parse().unwrap(),
+            "00:11:22:33:44:20".parse().unwrap(),
+        )];
+        let gateways = vec!["127.0.0.20".parse().unwrap(), "127.0.0.1".parse().unwrap()];
+
+        let run = collect_host_tables(&conn, &neighbors, &gateways, 3600);
+        assert_eq!(
+            run,
+            HostTableRun {
+                neighbors: 1,
+                gateways: 1,
+                discovered: 2,
+            }
+        );
+        assert!(EndPoint::resolve_ip_binding(&conn, "127.0.0.20").is_some());
+
+        // Entries already known don't create endpoints again
+        let run = collect_host_tables(&conn, &neighbors, &gateways, 3600);
+        assert_eq!(run.discovered, 0);
+    }
+}
@@ -0,0 +1,353 @@
+//! Hostname resolution. Names observed by mDNS, NetBIOS and SNMP are recorded per source,
+//! PTR lookups are made on demand, and everything is cached in memory and in the
+//! `hostname_cache` table (including failed lookups) so each IP resolves the same way
+//! wherever a hostname is needed.
+
+use dns_lookup::lookup_addr;
+use rusqlite::{Connection, Result, params};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{LazyLock, Mutex, OnceLock};
+use tokio::task;
+
+/// How long names reported by mDNS, NetBIOS and SNMP stay valid without being seen again
+const OBSERVATION_TTL_SECS: i64 = 60 * 60;
+/// How long a successful reverse DNS answer is trusted
+const PTR_TTL_SECS: i64 = 10 * 60;
+/// How long to wait before retrying a reverse DNS lookup that returned nothing
+const NEGATIVE_TTL_SECS: i64 = 5 * 60;
+/// Upper bound on IPs held in memory; the database keeps the rest
+const MAX_CACHED_IPS: usize = 10000;
+
+/// Where a hostname came from. Declaration order is resolution priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HostnameSource {
+    Mdns,
+    NetBios,
+    Ptr,
+    Snmp,
+}
+
+impl HostnameSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HostnameSource::Mdns => "mdns",
+            HostnameSource::NetBios => "netbios",
+            HostnameSource::Ptr => "ptr",
+            HostnameSource::Snmp => "snmp",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "mdns" => Some(HostnameSource::Mdns),
+            "netbios" => Some(HostnameSource::NetBios),
+            "ptr" => Some(HostnameSource::Ptr),
+            "snmp" => Some(HostnameSource::Snmp),
+            _ => None,
+        }
+    }
+
+    fn ttl_secs(&self) -> i64 {
+        match self {
+            HostnameSource::Ptr => PTR_TTL_SECS,
+            _ => OBSERVATION_TTL_SECS,
+        }
+    }
+}
+
+/// A cached answer from one source. `hostname` is None for a negative (failed) lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CachedName {
+    hostname: Option<String>,
+    expires_at: i64,
+}
+
+type Observations = HashMap<HostnameSource, CachedName>;
+
+static CACHE: LazyLock<Mutex<HashMap<String, Observations>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Pick the highest-priority unexpired hostname. Returns the hostname (if any) and
+/// whether a PTR answer - positive or negative - is still fresh.
+fn best_name(observations: &Observations, now: i64) -> (Option<(String, HostnameSource)>, bool) {
+    let mut fresh: Vec<(&HostnameSource, &CachedName)> = observations
+        .iter()
+        .filter(|(_, cached)| cached.expires_at > now)
+        .collect();
+    fresh.sort_by_key(|(source, _)| **source);
+
+    let ptr_fresh = fresh.iter().any(|(s, _)| **s == HostnameSource::Ptr);
+    let best = fresh
+        .into_iter()
+        .find_map(|(source, cached)| cached.hostname.clone().map(|h| (h, *source)));
+    (best, ptr_fresh)
+}
+
+fn with_db<T>(f: impl FnOnce(&Connection) -> Result<T>) -> Option<T> {
+    // One long-lived connection; resolution can happen on the packet path
+    static DB_CONN: OnceLock<Mutex<Option<Connection>>> = OnceLock::new();
+    let conn_mutex = DB_CONN.get_or_init(|| Mutex::new(crate::db::new_connection_result().ok()));
+    let mut guard = conn_mutex.lock().ok()?;
+    if guard.is_none() {
+        *guard = crate::db::new_connection_result().ok();
+    }
+    match f(guard.as_ref()?) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("Hostname cache error: {}", e);
+            None
+        }
+    }
+}
+
+pub struct HostnameResolver;
+
+impl HostnameResolver {
+    pub fn create_table_if_not_exists(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS hostname_cache (
+                ip TEXT NOT NULL,
+                source TEXT NOT NULL,
+                hostname TEXT,
+                resolved_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                PRIMARY KEY (ip, source)
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn load(conn: &Connection, ip: &str, now: i64) -> Result<Observations> {
+        let mut stmt = conn.prepare(
+            "SELECT source, hostname, expires_at FROM hostname_cache
+             WHERE ip = ?1 AND expires_at > ?2",
+        )?;
+        let rows = stmt.query_map(params![ip, now], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                CachedName {
+                    hostname: row.get(1)?,
+                    expires_at: row.get(2)?,
+                },
+            ))
+        })?;
+
+        let mut observations = Observations::new();
+        for row in rows {
+            let (source, cached) = row?;
+            if let Some(source) = HostnameSource::parse(&source) {
+                observations.insert(source, cached);
+            }
+        }
+        Ok(observations)
+    }
+
+    fn store(
+        conn: &Connection,
+        ip: &str,
+        source: HostnameSource,
+        cached: &CachedName,
+        now: i64,
+    ) -> Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO hostname_cache (ip, source, hostname, resolved_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![ip, source.as_str(), cached.hostname, now, cached.expires_at],
+        )?;
+        Ok(())
+    }
+
+    fn remember(ip: &str, source: HostnameSource, cached: CachedName) {
+        if let Ok(mut cache) = CACHE.lock() {
+            if cache.len() >= MAX_CACHED_IPS && !cache.contains_key(ip) {
+                // Drop the IPs whose freshest answer expires soonest
+                let mut by_expiry: Vec<(String, i64)> = cache
+                    .iter()
+                    .map(|(ip, obs)| {
+                        let latest = obs.values().map(|c| c.expires_at).max().unwrap_or(0);
+                        (ip.clone(), latest)
+                    })
+                    .collect();
+                by_expiry.sort_by_key(|(_, expires)| *expires);
+                for (ip, _) in by_expiry.into_iter().take(MAX_CACHED_IPS / 10) {
+                    cache.remove(&ip);
+                }
+            }
+            cache
+                .entry(ip.to_string())
+                .or_default()
+                .insert(source, cached);
+        }
+    }
+
+    /// Record a hostname reported for `ip` by a discovery protocol
+    pub fn record(ip: &str, hostname: &str, source: HostnameSource) {
+        let hostname = hostname.trim().trim_end_matches('.');
+        if hostname.is_empty() || hostname == ip {
+            return;
+        }
+        let now = chrono::Utc::now().timestamp();
+        let cached = CachedName {
+            hostname: Some(hostname.to_string()),
+            expires_at: now + source.ttl_secs(),
+        };
+        with_db(|conn| Self::store(conn, ip, source, &cached, now));
+        Self::remember(ip, source, cached);
+    }
+
+    /// Resolve the best hostname for `ip` (mDNS > NetBIOS > PTR > SNMP sysName).
+    /// A reverse DNS lookup is only made when neither mDNS nor NetBIOS has named the IP
+    /// and no recent PTR answer (including a failed one) is cached. May block on DNS.
+    pub fn resolve(ip: &str) -> Option<String> {
+        let addr: IpAddr = ip.parse().ok()?;
+        let now = chrono::Utc::now().timestamp();
+
+        let cached = CACHE.lock().ok().and_then(|cache| cache.get(ip).cloned());
+        let observations = match cached {
+            Some(observations) => observations,
+            None => {
+                let loaded = with_db(|conn| Self::load(conn, ip, now)).unwrap_or_default();
+                if let Ok(mut cache) = CACHE.lock() {
+                    cache
+                        .entry(ip.to_string())
+                        .or_insert_with(|| loaded.clone());
+                }
+                loaded
+            }
+        };
+
+        let (best, ptr_fresh) = best_name(&observations, now);
+        if ptr_fresh || best.as_ref().is_some_and(|(_, s)| *s < HostnameSource::Ptr) {
+            return best.map(|(hostname, _)| hostname);
+        }
+
+        let ptr = lookup_addr(&addr)
+            .ok()
+            .map(|h| h.trim_end_matches('.').to_string())
+            .filter(|h| !h.is_empty() && h != ip);
+        let cached = CachedName {
+            hostname: ptr.clone(),
+            expires_at: now
+                + if ptr.is_some() {
+                    PTR_TTL_SECS
+                } else {
+                    NEGATIVE_TTL_SECS
+                },
+        };
+        with_db(|conn| Self::store(conn, ip, HostnameSource::Ptr, &cached, now));
+        Self::remember(ip, HostnameSource::Ptr, cached);
+
+        ptr.or(best.map(|(hostname, _)| hostname))
+    }
+
+    /// Resolve in the background so the cache is warm for later lookups
+    pub fn resolve_async(ip: String) {
+        // Check if there's a Tokio runtime available before spawning
+        // This prevents panics when called from non-async tests
+        if tokio::runtime::Handle::try_current().is_ok() {
+            task::spawn_blocking(move || {
+                // Small delay to avoid hammering the network
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                let _ = Self::resolve(&ip);
+            });
+        }
+    }
+
+    /// Delete cache rows that expired more than `retention_seconds` ago
+    pub fn purge_expired(conn: &Connection, retention_seconds: i64) -> Result<usize> {
+        conn.execute(
+            "DELETE FROM hostname_cache WHERE expires_at < (strftime('%s', 'now') - ?1)",
+            [retention_seconds],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(hostname: &str, expires_at: i64) -> CachedName {
+        CachedName {
+            hostname: Some(hostname.to_string()),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_best_name_priority_and_expiry() {
+        let mut observations = Observations::new();
+        observations.insert(HostnameSource::Snmp, named("switch-sysname", 200));
+        observations.insert(HostnameSource::Ptr, named("switch.lan", 200));
+        assert_eq!(
+            best_name(&observations, 100),
+            (Some(("switch.lan".to_string(), HostnameSource::Ptr)), true)
+        );
+
+        observations.insert(HostnameSource::NetBios, named("SWITCH", 200));
+        observations.insert(HostnameSource::Mdns, named("switch.local", 50));
+        // Expired mDNS loses to NetBIOS
+        assert_eq!(
+            best_name(&observations, 100).0,
+            Some(("SWITCH".to_string(), HostnameSource::NetBios))
+        );
+
+        // A failed PTR lookup is fresh but falls through to SNMP
+        let mut observations = Observations::new();
+        observations.insert(
+            HostnameSource::Ptr,
+            CachedName {
+                hostname: None,
+                expires_at: 200,
+            },
+        );
+        observations.insert(HostnameSource::Snmp, named("switch-sysname", 200));
+        assert_eq!(
+            best_name(&observations, 100),
+            (
+                Some(("switch-sysname".to_string(), HostnameSource::Snmp)),
+                true
+            )
+        );
+        assert_eq!(best_name(&observations, 300), (None, false));
+    }
+
+    #[test]
+    fn test_hostname_cache_roundtrip() {
+        let conn = Connection::open_in_memory().unwrap();
+        HostnameResolver::create_table_if_not_exists(&conn).unwrap();
+
+        HostnameResolver::store(
+            &conn,
+            "192.168.1.5",
+            HostnameSource::NetBios,
+            &named("DESKTOP-1", 500),
+            100,
+        )
+        .unwrap();
+        HostnameResolver::store(
+            &conn,
+            "192.168.1.5",
+            HostnameSource::Ptr,
+            &CachedName {
+                hostname: None,
+                expires_at: 150,
+            },
+            100,
+        )
+        .unwrap();
+
+        let loaded = HostnameResolver::load(&conn, "192.168.1.5", 120).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[&HostnameSource::Ptr].hostname, None);
+
+        // Expired rows are not loaded
+        let loaded = HostnameResolver::load(&conn, "192.168.1.5", 200).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(
+            loaded[&HostnameSource::NetBios].hostname.as_deref(),
+            Some("DESKTOP-1")
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Live tail of communications. The capture writer publishes every flow it stores on a
+//! broadcast channel, which `/api/endpoint/{name}/live` subscribers read to watch what one
+//! endpoint is doing as it happens. Nothing is published while nobody is watching, and a
+//! subscriber that falls behind loses the oldest flows rather than slowing the writer.
+
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Flows buffered for each subscriber before the oldest are dropped
+const LIVE_TAIL_CAPACITY: usize = 1024;
+
+static LIVE_FLOWS: LazyLock<broadcast::Sender<LiveFlow>> =
+    LazyLock::new(|| broadcast::channel(LIVE_TAIL_CAPACITY).0);
+
+/// A stored flow as the writer saw it. Internet hosts have no endpoint id; their side is
+/// named by the hostname the flow was attributed to, or the bare IP.
+#[derive(Debug, Clone)]
+pub struct LiveFlow {
+    pub at: i64,
+    pub src_endpoint_id: Option<i64>,
+    pub dst_endpoint_id: Option<i64>,
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub source_port: Option<u16>,
+    pub destination_port: Option<u16>,
+    pub protocol: Option<String>,
+    pub sub_protocol: Option<String>,
+    pub bytes: u32,
+    pub packets: u32,
+}
+
+/// A flow from the point of view of the endpoint being watched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LiveCommunication {
+    pub at: i64,
+    /// "outbound" when the watched endpoint sent it, "inbound" when it received it
+    pub direction: &'static str,
+    /// The other side's address, or internet hostname
+    pub peer: Option<String>,
+    /// None for internet hosts
+    pub peer_endpoint_id: Option<i64>,
+    pub local_port: Option<u16>,
+    pub peer_port: Option<u16>,
+    pub protocol: Option<String>,
+    pub sub_protocol: Option<String>,
+    pub bytes: u32,
+    pub packets: u32,
+}
+
+impl LiveFlow {
+    /// This flow as seen by the endpoint(s) `endpoint_ids`, None when it doesn't involve them
+    pub fn view_for(&self, endpoint_ids: &[i64]) -> Option<LiveCommunication> {
+        let involves = |id: Option<i64>| id.is_some_and(|id| endpoint_ids.contains(&id));
+        let (direction, peer, peer_endpoint_id, local_port, peer_port) =
+            if involves(self.src_endpoint_id) {
+                (
+                    "outbound",
+                    &self.destination,
+                    self.dst_endpoint_id,
+                    self.source_port,
+                    self.destination_port,
+                )
+            } else if involves(self.dst_endpoint_id) {
+                (
+                    "inbound",
+                    &self.source,
+                    self.src_endpoint_id,
+                    self.destination_port,
+                    self.source_port,
+                )
+            } else {
+                return None;
+            };
+        Some(LiveCommunication {
+            at: self.at,
+            direction,
+            peer: peer.clone(),
+            peer_endpoint_id,
+            local_port,
+            peer_port,
+            protocol: self.protocol.clone(),
+            sub_protocol: self.sub_protocol.clone(),
+            bytes: self.bytes,
+            packets: self.packets,
+        })
+    }
+}
+
+/// Whether anyone is watching, so the writer can skip building flows nobody reads
+pub fn has_live_subscribers() -> bool {
+    LIVE_FLOWS.receiver_count() > 0
+}
+
+/// Hand a stored flow to every subscriber
+pub fn publish_live_flow(flow: LiveFlow) {
+    // Fails only when nobody is subscribed
+    let _ = LIVE_FLOWS.send(flow);
+}
+
+/// Receive every flow published from now on
+pub fn subscribe_live_flows() -> broadcast::Receiver<LiveFlow> {
+    LIVE_FLOWS.subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(src: Option<i64>, dst: Option<i64>) -> LiveFlow {
+        LiveFlow {
+            at: 100,
+            src_endpoint_id: src,
+            dst_endpoint_id: dst,
+            source: Some("192.168.1.20".to_string()),
+            destination: Some("example.com".to_string()),
+            source_port: Some(51000),
+            destination_port: Some(443),
+            protocol: Some("TCP".to_string()),
+            sub_protocol: Some("HTTPS".to_string()),
+            bytes: 1500,
+            packets: 2,
+        }
+    }
+
+    #[test]
+    fn test_view_for_watched_endpoint() {
+        let outbound = flow(Some(7), None).view_for(&[7]).unwrap();
+        assert_eq!(outbound.direction, "outbound");
+        assert_eq!(outbound.peer.as_deref(), Some("example.com"));
+        assert_eq!(outbound.peer_endpoint_id, None);
+        assert_eq!(
+            (outbound.local_port, outbound.peer_port),
+            (Some(51000), Some(443))
+        );
+
+        // Any of a device's linked endpoints counts
+        let inbound = flow(Some(3), Some(8)).view_for(&[7, 8]).unwrap();
+        assert_eq!(inbound.direction, "inbound");
+        assert_eq!(inbound.peer.as_deref(), Some("192.168.1.20"));
+        assert_eq!(inbound.peer_endpoint_id, Some(3));
+        assert_eq!(
+            (inbound.local_port, inbound.peer_port),
+            (Some(443), Some(51000))
+        );
+
+        assert!(flow(Some(3), Some(4)).view_for(&[7]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_published_flows_reach_subscribers() {
+        let mut receiver = subscribe_live_flows();
+        assert!(has_live_subscribers());
+        publish_live_flow(flow(Some(42), None));
+        // Other tests may be storing flows at the same time
+        loop {
+            let received = receiver.recv().await.unwrap();
+            if received.src_endpoint_id == Some(42) {
+                assert_eq!(received.view_for(&[42]).unwrap().bytes, 1500);
+                break;
+            }
+        }
+    }
+}
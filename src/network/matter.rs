@@ -0,0 +1,257 @@
+//! Matter and Thread recognition. Matter devices announce themselves over mDNS: an
+//! operational `_matter._tcp` record per fabric they belong to (named after the compressed
+//! fabric ID and node ID), and a `_matterc._udp` record with vendor, product and device type
+//! while they are open for commissioning. Thread border routers announce `_meshcop._udp`
+//! with their network name and extended PAN ID. The parsed details are kept per IP, like
+//! the other mDNS results, and shown with the endpoint.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use serde::Serialize;
+
+/// mDNS service types that carry Matter or Thread details
+pub const MATTER_OPERATIONAL_SERVICE: &str = "_matter._tcp";
+pub const MATTER_COMMISSIONABLE_SERVICE: &str = "_matterc._udp";
+pub const THREAD_BORDER_ROUTER_SERVICE: &str = "_meshcop._udp";
+
+static MATTER_RECORDS: OnceLock<RwLock<HashMap<String, MatterInfo>>> = OnceLock::new();
+
+/// A fabric (controller ecosystem) a device has been commissioned into
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MatterFabric {
+    /// Compressed fabric ID, 16 hex digits
+    pub compressed_fabric_id: String,
+    /// The device's node ID on that fabric, 16 hex digits
+    pub node_id: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ThreadBorderRouter {
+    pub network_name: Option<String>,
+    /// Extended PAN ID of the Thread network, as hex
+    pub extended_pan_id: Option<String>,
+    pub vendor_name: Option<String>,
+    pub model_name: Option<String>,
+    pub thread_version: Option<String>,
+}
+
+/// What a device's Matter and Thread records say about it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MatterInfo {
+    pub fabrics: Vec<MatterFabric>,
+    /// Advertising `_matterc._udp`, i.e. waiting to be commissioned
+    pub commissionable: bool,
+    /// CSA-assigned vendor ID
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub device_type: Option<u32>,
+    pub device_type_name: Option<&'static str>,
+    pub device_name: Option<String>,
+    pub discriminator: Option<u16>,
+    pub thread_border_router: Option<ThreadBorderRouter>,
+}
+
+impl MatterInfo {
+    /// Fold in details from another record (or another IP of the same device)
+    fn merge(&mut self, other: MatterInfo) {
+        for fabric in other.fabrics {
+            if !self.fabrics.contains(&fabric) {
+                self.fabrics.push(fabric);
+            }
+        }
+        self.commissionable |= other.commissionable;
+        self.vendor_id = other.vendor_id.or(self.vendor_id);
+        self.product_id = other.product_id.or(self.product_id);
+        self.device_type = other.device_type.or(self.device_type);
+        self.device_type_name = other.device_type_name.or(self.device_type_name);
+        self.device_name = other.device_name.or(self.device_name.take());
+        self.discriminator = other.discriminator.or(self.discriminator);
+        if other.thread_border_router.is_some() {
+            self.thread_border_router = other.thread_border_router;
+        }
+    }
+}
+
+/// Names of common Matter device types
+fn device_type_name(device_type: u32) -> Option<&'static str> {
+    Some(match device_type {
+        0x000A => "Door Lock",
+        0x000E => "Bridge",
+        0x0015 => "Contact Sensor",
+        0x002B => "Fan",
+        0x0100 => "On/Off Light",
+        0x0101 => "Dimmable Light",
+        0x0107 => "Occupancy Sensor",
+        0x010A => "On/Off Plug-in Unit",
+        0x010B => "Dimmable Plug-in Unit",
+        0x010C => "Color Temperature Light",
+        0x010D => "Extended Color Light",
+        0x0202 => "Window Covering",
+        0x0301 => "Thermostat",
+        0x0302 => "Temperature Sensor",
+        _ => return None,
+    })
+}
+
+/// Parse one resolved record. `instance` is the service instance name (without the
+/// service type) and `property` looks up a TXT value. Returns None for other services.
+pub fn parse_matter_record(
+    service: &str,
+    instance: &str,
+    property: impl Fn(&str) -> Option<Vec<u8>>,
+) -> Option<MatterInfo> {
+    let text = |key: &str| {
+        property(key)
+            .and_then(|value| String::from_utf8(value).ok())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    match service {
+        MATTER_OPERATIONAL_SERVICE => {
+            let (fabric, node) = instance.split_once('-')?;
+            let is_id = |id: &str| id.len() == 16 && id.chars().all(|c| c.is_ascii_hexdigit());
+            if !is_id(fabric) || !is_id(node) {
+                return None;
+            }
+            Some(MatterInfo {
+                fabrics: vec![MatterFabric {
+                    compressed_fabric_id: fabric.to_uppercase(),
+                    node_id: node.to_uppercase(),
+                }],
+                ..Default::default()
+            })
+        }
+        MATTER_COMMISSIONABLE_SERVICE => {
+            // VP is "<vendor>+<product>", or just the vendor
+            let vendor_product = text("VP");
+            let (vendor, product) = match vendor_product.as_deref().map(|vp| vp.split_once('+')) {
+                Some(Some((vendor, product))) => (Some(vendor), Some(product)),
+                Some(None) => (vendor_product.as_deref(), None),
+                None => (None, None),
+            };
+            let device_type = text("DT").and_then(|dt| dt.parse().ok());
+            Some(MatterInfo {
+                commissionable: true,
+                vendor_id: vendor.and_then(|v| v.parse().ok()),
+                product_id: product.and_then(|p| p.parse().ok()),
+                device_type,
+                device_type_name: device_type.and_then(device_type_name),
+                device_name: text("DN"),
+                discriminator: text("D").and_then(|d| d.parse().ok()),
+                ..Default::default()
+            })
+        }
+        THREAD_BORDER_ROUTER_SERVICE => {
+            let extended_pan_id = property("xp")
+                .filter(|xp| xp.len() == 8)
+                .map(|xp| xp.iter().map(|b| format!("{:02X}", b)).collect());
+            Some(MatterInfo {
+                thread_border_router: Some(ThreadBorderRouter {
+                    network_name: text("nn"),
+                    extended_pan_id,
+                    vendor_name: text("vn"),
+                    model_name: text("mn"),
+                    thread_version: text("tv"),
+                }),
+                ..Default::default()
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Remember a parsed record for an IP
+pub fn record_matter_info(ip: &str, info: MatterInfo) {
+    if let Ok(mut records) = MATTER_RECORDS
+        .get_or_init(|| RwLock::new(HashMap::new()))
+        .write()
+    {
+        records.entry(ip.to_string()).or_default().merge(info);
+    }
+}
+
+/// Matter and Thread details seen for any of a device's IPs
+pub fn get_matter_info(ips: &[String]) -> Option<MatterInfo> {
+    let records = MATTER_RECORDS.get_or_init(|| RwLock::new(HashMap::new()));
+    let records = records.read().ok()?;
+    ips.iter()
+        .filter_map(|ip| records.get(ip).cloned())
+        .reduce(|mut info, other| {
+            info.merge(other);
+            info
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txt<'a>(pairs: &'a [(&'a str, &'a [u8])]) -> impl Fn(&str) -> Option<Vec<u8>> + 'a {
+        move |key| {
+            pairs
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_vec())
+        }
+    }
+
+    #[test]
+    fn test_parse_matter_records() {
+        let operational = parse_matter_record(
+            MATTER_OPERATIONAL_SERVICE,
+            "2906c908d115d362-8FC7772401CD0696",
+            txt(&[]),
+        )
+        .unwrap();
+        assert_eq!(
+            operational.fabrics[0].compressed_fabric_id,
+            "2906C908D115D362"
+        );
+        assert_eq!(operational.fabrics[0].node_id, "8FC7772401CD0696");
+        assert!(
+            parse_matter_record(MATTER_OPERATIONAL_SERVICE, "kitchen-light", txt(&[])).is_none()
+        );
+
+        let commissionable = parse_matter_record(
+            MATTER_COMMISSIONABLE_SERVICE,
+            "DD200C20D25AE5F7",
+            txt(&[
+                ("VP", b"4447+8194"),
+                ("DT", b"266"),
+                ("D", b"3840"),
+                ("CM", b"1"),
+            ]),
+        )
+        .unwrap();
+        assert!(commissionable.commissionable);
+        assert_eq!(commissionable.vendor_id, Some(4447));
+        assert_eq!(commissionable.product_id, Some(8194));
+        assert_eq!(commissionable.device_type_name, Some("On/Off Plug-in Unit"));
+        assert_eq!(commissionable.discriminator, Some(3840));
+
+        let border_router = parse_matter_record(
+            THREAD_BORDER_ROUTER_SERVICE,
+            "HomePod",
+            txt(&[
+                ("nn", b"MyHome42"),
+                ("xp", &[0xde, 0xad, 0x00, 0xbe, 0xef, 0x00, 0xca, 0xfe]),
+                ("vn", b"Apple Inc."),
+            ]),
+        )
+        .unwrap();
+        let thread = border_router.thread_border_router.clone().unwrap();
+        assert_eq!(thread.network_name.as_deref(), Some("MyHome42"));
+        assert_eq!(thread.extended_pan_id.as_deref(), Some("DEAD00BEEF00CAFE"));
+
+        // Records for two IPs of one device combine
+        record_matter_info("fd00::10", operational.clone());
+        record_matter_info("fd00::10", operational);
+        record_matter_info("192.168.1.50", commissionable);
+        let info = get_matter_info(&["192.168.1.50".to_string(), "fd00::10".to_string()]).unwrap();
+        assert_eq!(info.fabrics.len(), 1);
+        assert_eq!(info.vendor_id, Some(4447));
+        assert!(get_matter_info(&["192.168.1.51".to_string()]).is_none());
+    }
+}
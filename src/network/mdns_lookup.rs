@@ -1,16 +1,24 @@
 //! mDNS service discovery. Handles multicast DNS hostname resolution, service browsing,
-//! and result caching with local machine detection to avoid self-discovery.
+//! and result caching with local machine detection to avoid self-discovery. Can also
+//! advertise the dashboard itself over DNS-SD.
 
-use dns_lookup::{get_hostname, lookup_addr};
-use mdns_sd::{ServiceDaemon, ServiceEvent};
+use dns_lookup::get_hostname;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
 use pnet::datalink;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{OnceLock, RwLock};
 use std::time::SystemTime;
 use tokio::task;
 
-use super::endpoint::is_valid_display_name;
+use crate::health::{record_activity, record_error};
+
+use super::endpoint::{
+    EndPoint, IdentityKind, IdentityResolution, NameSource, is_valid_display_name,
+};
+use super::hostname_resolver::{HostnameResolver, HostnameSource};
+use super::matter::{parse_matter_record, record_matter_info};
 
 static MDNS_LOOKUPS: OnceLock<std::sync::RwLock<HashMap<String, String>>> = OnceLock::new();
 static MDNS_SERVICES: OnceLock<std::sync::RwLock<HashMap<String, HashSet<String>>>> =
@@ -19,6 +27,34 @@ static MDNS_SERVICES: OnceLock<std::sync::RwLock<HashMap<String, HashSet<String>
 // If dropped, all mDNS browses stop receiving events
 static MDNS_DAEMON: OnceLock<ServiceDaemon> = OnceLock::new();
 
+/// Service types being browsed, for health checks
+static MDNS_BROWSING: AtomicUsize = AtomicUsize::new(0);
+
+/// DNS-SD service type the dashboard is advertised under
+const DASHBOARD_SERVICE_TYPE: &str = "_http._tcp.local.";
+
+/// How many service types are being browsed
+pub fn browsing_count() -> usize {
+    MDNS_BROWSING.load(Ordering::Relaxed)
+}
+
+fn get_daemon() -> &'static ServiceDaemon {
+    MDNS_DAEMON.get_or_init(|| ServiceDaemon::new().expect("Failed to create mDNS daemon"))
+}
+
+/// Instance name shown to DNS-SD browsers, e.g. "Network Discovery on office-mac"
+fn dashboard_instance_name(hostname: &str) -> String {
+    format!("Network Discovery on {}", hostname)
+}
+
+/// mDNS host name for the local machine, e.g. "office-mac.local."
+fn dashboard_host_name(hostname: &str) -> String {
+    format!(
+        "{}.local.",
+        hostname.trim_end_matches('.').trim_end_matches(".local")
+    )
+}
+
 /// Get the local machine's hostname (cached)
 fn get_local_hostname() -> Option<String> {
     static LOCAL_HOSTNAME: OnceLock<Option<String>> = OnceLock::new();
@@ -84,8 +120,7 @@ pub struct MDnsLookup;
 impl MDnsLookup {
     pub fn start_daemon() {
         // Store the daemon in a static to keep it alive - dropping it stops all browses
-        let mdns =
-            MDNS_DAEMON.get_or_init(|| ServiceDaemon::new().expect("Failed to create mDNS daemon"));
+        let mdns = get_daemon();
 
         let services_to_browse = [
             "_afpovertcp._tcp.local.",
@@ -125,6 +160,8 @@ impl MDnsLookup {
             "_webos._tcp.local.", // LG WebOS TVs
             // Additional smart home and IoT services
             "_matter._tcp.local.",      // Matter smart home protocol
+            "_matterc._udp.local.",     // Matter devices open for commissioning
+            "_meshcop._udp.local.",     // Thread border routers
             "_amzn-alexa._tcp.local.",  // Amazon Alexa devices
             "_device-info._tcp.local.", // Device info (many Apple/smart devices)
             "_dyson_mqtt._tcp.local.",  // Dyson devices (fans, purifiers)
@@ -161,9 +198,18 @@ impl MDnsLookup {
 
         for service in services_to_browse.iter() {
             let service_type = service.to_string();
-            let receiver = mdns
-                .browse(&service_type)
-                .expect("Failed to browse for services");
+            let receiver = match mdns.browse(&service_type) {
+                Ok(receiver) => receiver,
+                Err(e) => {
+                    eprintln!("Failed to browse for {}: {}", service_type, e);
+                    record_error(
+                        "mdns",
+                        format!("Failed to browse for {}: {}", service_type, e),
+                    );
+                    continue;
+                }
+            };
+            MDNS_BROWSING.fetch_add(1, Ordering::Relaxed);
 
             task::spawn_blocking(move || {
                 loop {
@@ -171,6 +217,7 @@ impl MDnsLookup {
                         match event {
                             ServiceEvent::ServiceFound(_service, _full) => (),
                             ServiceEvent::ServiceResolved(service_info) => {
+                                record_activity("mdns");
                                 let mut host = service_info.get_hostname().to_string();
                                 if host.ends_with('.') {
                                     host.pop();
@@ -184,6 +231,25 @@ impl MDnsLookup {
                                     .unwrap_or(&service_type)
                                     .to_string();
 
+                                // Matter and Thread records carry fabric, vendor and network details
+                                let instance = service_info
+                                    .get_fullname()
+                                    .strip_suffix(service_type.as_str())
+                                    .unwrap_or_default()
+                                    .trim_end_matches('.');
+                                let matter_info =
+                                    parse_matter_record(&service_name, instance, |key| {
+                                        service_info
+                                            .get_properties()
+                                            .get_property_val(key)
+                                            .flatten()
+                                            .map(<[u8]>::to_vec)
+                                    });
+                                let instance_id =
+                                    mdns_instance_id(service_info.get_fullname(), |key| {
+                                        service_info.get_property_val_str(key)
+                                    });
+
                                 for addr in service_info.get_addresses() {
                                     let ip_addr = addr.to_ip_addr();
 
@@ -194,6 +260,10 @@ impl MDnsLookup {
 
                                     let addr = ip_addr.to_string();
 
+                                    if let Some(info) = &matter_info {
+                                        record_matter_info(&addr, info.clone());
+                                    }
+
                                     // Store hostname lookup (always store for name resolution)
                                     let is_new_hostname = if let Ok(mut lookups) = MDNS_LOOKUPS
                                         .get_or_init(|| RwLock::new(HashMap::new()))
@@ -206,6 +276,7 @@ impl MDnsLookup {
                                     } else {
                                         false
                                     };
+                                    HostnameResolver::record(&addr, &host, HostnameSource::Mdns);
 
                                     // Persist hostname to database if it's a new discovery
                                     // Only update if hostname is a valid display name
@@ -237,7 +308,25 @@ impl MDnsLookup {
                                                     )
                                                     .unwrap_or(false);
 
-                                                if endpoint_exists {
+                                                let identity = endpoint_exists.then(|| {
+                                                    Self::resolve_mdns_identity(
+                                                        conn,
+                                                        &instance_id,
+                                                        &addr,
+                                                    )
+                                                });
+                                                if let Some(Some(IdentityResolution::Conflict {
+                                                    bound_to,
+                                                    holder,
+                                                })) = identity
+                                                {
+                                                    // Another device holds the address; the
+                                                    // announcement can't be trusted
+                                                    eprintln!(
+                                                        "mDNS: {} announced {} of endpoint {} but is bound to endpoint {}; ignoring",
+                                                        addr, instance_id, bound_to, holder
+                                                    );
+                                                } else if endpoint_exists {
                                                     // Update existing endpoint_attributes
                                                     let _ = conn.execute(
                                                         "UPDATE endpoint_attributes SET hostname = ?1
@@ -246,17 +335,28 @@ impl MDnsLookup {
                                                         rusqlite::params![host, addr],
                                                     );
 
-                                                    // Also update endpoints.name if it's currently just an IP
-                                                    let _ = conn.execute(
-                                                        "UPDATE endpoints SET name = ?1
-                                                         WHERE id IN (
-                                                             SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?2
-                                                         )
-                                                         AND (name IS NULL OR name = ?2 OR name LIKE '%:%'
-                                                              OR name GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*')
-                                                         AND custom_name IS NULL",
-                                                        rusqlite::params![host, addr],
-                                                    );
+                                                    // mDNS names outrank every other discovered name.
+                                                    // A verified instance names its own endpoint
+                                                    // even if the address last belonged to another.
+                                                    if let Some(Some(
+                                                        IdentityResolution::Verified(endpoint_id),
+                                                    )) = identity
+                                                    {
+                                                        let _ = EndPoint::set_discovered_name(
+                                                            conn,
+                                                            endpoint_id,
+                                                            &host,
+                                                            NameSource::Mdns,
+                                                        );
+                                                    } else {
+                                                        let _ =
+                                                            EndPoint::set_discovered_name_for_ip(
+                                                                conn,
+                                                                &addr,
+                                                                &host,
+                                                                NameSource::Mdns,
+                                                            );
+                                                    }
 
                                                     // Try to merge: if this IP's endpoint has only randomized MACs,
                                                     // and another endpoint already has this hostname, merge into it
@@ -267,7 +367,7 @@ impl MDnsLookup {
                                                     // Create new endpoint from mDNS discovery
                                                     let now = chrono::Utc::now().timestamp();
                                                     if conn.execute(
-                                                        "INSERT INTO endpoints (created_at, name) VALUES (?1, ?2)",
+                                                        "INSERT INTO endpoints (created_at, name, name_source) VALUES (?1, ?2, 'mdns')",
                                                         rusqlite::params![now, host],
                                                     ).is_ok() {
                                                         let endpoint_id = conn.last_insert_rowid();
@@ -332,10 +432,36 @@ impl MDnsLookup {
         }
     }
 
-    pub fn lookup(ip: &str) -> Option<String> {
-        let lookups = MDNS_LOOKUPS.get_or_init(|| RwLock::new(HashMap::new()));
-        let map = lookups.read().ok()?;
-        map.get(ip).cloned()
+    /// Advertise the web dashboard as an `_http._tcp` service so it can be reached
+    /// by browsing or at `<hostname>.local`. Returns the registered service's full name.
+    pub fn advertise_dashboard(port: u16) -> Result<String, String> {
+        let hostname = get_local_hostname().ok_or("Could not determine local hostname")?;
+        let hostname = hostname.strip_suffix(".local").unwrap_or(&hostname);
+
+        let addrs: Vec<IpAddr> = get_local_ips()
+            .iter()
+            .filter_map(|ip| ip.parse::<IpAddr>().ok())
+            .filter(|ip| !ip.is_loopback())
+            .collect();
+        let properties = [("path", "/"), ("version", env!("CARGO_PKG_VERSION"))];
+
+        let info = ServiceInfo::new(
+            DASHBOARD_SERVICE_TYPE,
+            &dashboard_instance_name(hostname),
+            &dashboard_host_name(hostname),
+            &addrs[..],
+            port,
+            &properties[..],
+        )
+        .map_err(|e| format!("Invalid mDNS service info: {}", e))?
+        // Track interface address changes instead of the addresses seen at startup
+        .enable_addr_auto();
+
+        let fullname = info.get_fullname().to_string();
+        get_daemon()
+            .register(info)
+            .map_err(|e| format!("Failed to register mDNS service: {}", e))?;
+        Ok(fullname)
     }
 
     pub fn get_services(ip: &str) -> Vec<String> {
@@ -360,29 +486,33 @@ impl MDnsLookup {
         }
     }
 
-    /// Actively probe for a device's hostname using reverse DNS lookup
-    /// This works for devices that register with local DNS or respond to PTR queries
-    pub fn probe_hostname(ip: &str) -> Option<String> {
-        // First check our mDNS cache
-        if let Some(hostname) = Self::lookup(ip) {
-            return Some(hostname);
-        }
-
-        // Try reverse DNS lookup (works for mDNS .local addresses too)
-        if let Ok(addr) = ip.parse::<IpAddr>()
-            && let Ok(hostname) = lookup_addr(&addr)
-        {
-            // Cache the result
-            if let Ok(mut lookups) = MDNS_LOOKUPS
-                .get_or_init(|| RwLock::new(HashMap::new()))
-                .write()
-            {
-                lookups.insert(ip.to_string(), hostname.clone());
-            }
-            return Some(hostname);
-        }
-
-        None
+    /// Bind a DNS-SD instance to the endpoint announcing it, or find the endpoint it was
+    /// bound to before. None if the lookup fails.
+    fn resolve_mdns_identity(
+        conn: &rusqlite::Connection,
+        instance_id: &str,
+        ip: &str,
+    ) -> Option<IdentityResolution> {
+        let ip_endpoint = EndPoint::resolve_ip_binding(conn, ip).or_else(|| {
+            conn.query_row(
+                "SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?1
+                 ORDER BY created_at DESC LIMIT 1",
+                [ip],
+                |row| row.get(0),
+            )
+            .ok()
+        });
+        let now = chrono::Utc::now().timestamp();
+        EndPoint::resolve_identity(
+            conn,
+            IdentityKind::MdnsInstance,
+            instance_id,
+            ip,
+            ip_endpoint,
+            now,
+        )
+        .map_err(|e| eprintln!("Failed to resolve mDNS identity: {}", e))
+        .ok()
     }
 
     /// Merge a bare-IP/randomized-MAC endpoint into an existing endpoint with the same hostname.
@@ -475,28 +605,58 @@ impl MDnsLookup {
             "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
             rusqlite::params![target_id, source_id],
         );
+        let _ = EndPoint::reassign_endpoint_data(conn, source_id, target_id);
         let _ = conn.execute("DELETE FROM endpoints WHERE id = ?1", [source_id]);
         eprintln!(
             "mDNS: Merged endpoint {} into {} (same hostname: {})",
             source_id, target_id, hostname
         );
     }
+}
 
-    /// Spawn a background task to probe for hostname and cache it
-    /// This is non-blocking and runs in the background
-    pub fn probe_hostname_async(ip: String, _endpoint_id: i64) {
-        // Check if there's a Tokio runtime available before spawning
-        // This prevents panics when called from non-async tests
-        if tokio::runtime::Handle::try_current().is_ok() {
-            task::spawn_blocking(move || {
-                // Small delay to avoid hammering the network
-                std::thread::sleep(std::time::Duration::from_millis(100));
+/// Identifier of a DNS-SD instance that survives address changes: the device id in the
+/// TXT record (`id` for Google Cast and HomeKit, `deviceid` for AirPlay), else the
+/// instance's full name, which mDNS keeps unique on the link
+fn mdns_instance_id<'a>(fullname: &str, property: impl Fn(&str) -> Option<&'a str>) -> String {
+    ["id", "deviceid"]
+        .iter()
+        .filter_map(|key| property(key))
+        .map(|id| id.trim().to_lowercase())
+        .find(|id| !id.is_empty())
+        .unwrap_or_else(|| fullname.to_lowercase())
+}
 
-                // Just probe and cache - the result is stored in MDNS_LOOKUPS by probe_hostname()
-                // Database updates removed to avoid lock contention with SQLWriter
-                let _ = Self::probe_hostname(&ip);
-            });
-        }
-        // If no runtime available, silently skip the async probe
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_names() {
+        assert_eq!(
+            dashboard_instance_name("office-mac"),
+            "Network Discovery on office-mac"
+        );
+        assert_eq!(dashboard_host_name("office-mac"), "office-mac.local.");
+        assert_eq!(dashboard_host_name("office-mac.local"), "office-mac.local.");
+        assert_eq!(
+            dashboard_host_name("office-mac.local."),
+            "office-mac.local."
+        );
+    }
+
+    #[test]
+    fn test_mdns_instance_id() {
+        let txt = |key: &str| match key {
+            "id" => Some("1D5C6F2B8E7A4C0F9B3A2E1D0C9B8A7F"),
+            _ => None,
+        };
+        assert_eq!(
+            mdns_instance_id("Living-Room-TV._googlecast._tcp.local.", txt),
+            "1d5c6f2b8e7a4c0f9b3a2e1d0c9b8a7f"
+        );
+        assert_eq!(
+            mdns_instance_id("Office Printer._ipp._tcp.local.", |_| None),
+            "office printer._ipp._tcp.local."
+        );
     }
 }
@@ -1,10 +1,27 @@
 //! Network module. Exports submodules for packet processing, endpoint management,
 //! device control, and protocol handling.
 
+pub mod blocklist;
+pub mod broadcast;
 pub mod communication;
 pub mod device_control;
+pub mod direction;
+pub mod dns_audit;
+pub mod domain_categories;
+pub mod eapol;
 pub mod endpoint;
 pub mod endpoint_attribute;
+pub mod flow;
+pub mod host_tables;
+pub mod hostname_resolver;
+pub mod live_tail;
+pub mod matter;
 pub mod mdns_lookup;
+pub mod name_poisoning;
 pub mod packet_wrapper;
+pub mod passive_dns;
+pub mod privacy;
 pub mod protocol;
+pub mod router_advertisement;
+pub mod tcp_quality;
+pub mod wireless;
@@ -0,0 +1,490 @@
+//! LLMNR and NBNS poisoning detection. Windows hosts fall back to these broadcast name
+//! protocols when DNS has no answer, and any host on the link may reply, which is how
+//! responder-style attacks collect credentials. Positive responses seen in the capture
+//! stream are checked against what mDNS and DNS say the name belongs to; an answer pointing
+//! somewhere else, or one host claiming many different names, raises a critical
+//! `name_poisoning` notification.
+
+use std::net::Ipv4Addr;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+
+use crate::db::insert_notification_with_endpoint_id;
+use crate::network::endpoint::EndPoint;
+use crate::network::passive_dns::parse_dns_response;
+
+const LLMNR_PORT: u16 = 5355;
+const NBNS_PORT: u16 = 137;
+const NBNS_HEADER_LEN: usize = 12;
+/// An encoded NetBIOS name: length byte 0x20 and 32 half-byte characters
+const NETBIOS_ENCODED_LEN: usize = 32;
+const NBNS_TYPE_NB: u16 = 0x0020;
+
+/// Distinct names one host may claim for itself before it looks like it answers everything
+const MAX_CLAIMED_NAMES: i64 = 3;
+
+/// How long a responder's claimed names count towards `MAX_CLAIMED_NAMES`
+const CLAIM_WINDOW_SECS: i64 = 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameProtocol {
+    Llmnr,
+    Nbns,
+}
+
+impl NameProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NameProtocol::Llmnr => "LLMNR",
+            NameProtocol::Nbns => "NBNS",
+        }
+    }
+}
+
+/// A positive answer to an LLMNR or NBNS name query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameResponse {
+    pub protocol: NameProtocol,
+    /// Lowercased, without any domain or NetBIOS suffix
+    pub name: String,
+    pub answered_ip: String,
+}
+
+/// The single-label form names are compared in: "NAS.lan." and "nas" are the same host
+fn short_name(name: &str) -> String {
+    name.trim_end_matches('.')
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *message.get(offset)?,
+        *message.get(offset + 1)?,
+    ]))
+}
+
+/// Decode a first-level encoded NetBIOS name (RFC 1001 §14.1): each byte is two
+/// characters 'A'..='P' holding a nibble. The 16th byte is the service suffix.
+fn decode_netbios_name(encoded: &[u8]) -> Option<String> {
+    let bytes = encoded
+        .chunks_exact(2)
+        .map(|pair| {
+            let high = pair[0].checked_sub(b'A').filter(|n| *n < 16)?;
+            let low = pair[1].checked_sub(b'A').filter(|n| *n < 16)?;
+            Some((high << 4) | low)
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    let name = String::from_utf8_lossy(bytes.get(..15)?).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Parse a positive NBNS name query response. Registrations, node status answers and
+/// negative responses yield nothing.
+fn parse_nbns_response(message: &[u8]) -> Option<Vec<NameResponse>> {
+    let flags = read_u16(message, 2)?;
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0x0F;
+    let rcode = flags & 0x000F;
+    if !is_response || opcode != 0 || rcode != 0 || read_u16(message, 6)? == 0 {
+        return None;
+    }
+
+    // The answer repeats the queried name; questions are not echoed
+    let mut offset = NBNS_HEADER_LEN;
+    if usize::from(*message.get(offset)?) != NETBIOS_ENCODED_LEN {
+        return None;
+    }
+    let name = decode_netbios_name(message.get(offset + 1..offset + 1 + NETBIOS_ENCODED_LEN)?)?;
+    offset += 1 + NETBIOS_ENCODED_LEN;
+    // Skip any scope labels
+    while *message.get(offset)? != 0 {
+        offset += 1 + usize::from(message[offset]);
+    }
+    offset += 1;
+
+    if read_u16(message, offset)? != NBNS_TYPE_NB {
+        return None;
+    }
+    let data_len = usize::from(read_u16(message, offset + 8)?);
+    let data = message.get(offset + 10..offset + 10 + data_len)?;
+    // Each address entry is two bytes of flags and an IPv4 address
+    Some(
+        data.chunks_exact(6)
+            .map(|entry| NameResponse {
+                protocol: NameProtocol::Nbns,
+                name: short_name(&name),
+                answered_ip: Ipv4Addr::new(entry[2], entry[3], entry[4], entry[5]).to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// Name answers carried by a captured packet, if it is an LLMNR or NBNS response
+pub fn name_responses_from_packet(
+    source_port: Option<u16>,
+    header_protocol: Option<&str>,
+    payload: &[u8],
+) -> Vec<NameResponse> {
+    if header_protocol != Some("Udp") {
+        return Vec::new();
+    }
+    match source_port {
+        // LLMNR uses the DNS message format
+        Some(LLMNR_PORT) => parse_dns_response(payload)
+            .into_iter()
+            .map(|answer| NameResponse {
+                protocol: NameProtocol::Llmnr,
+                name: short_name(&answer.hostname),
+                answered_ip: answer.ip,
+            })
+            .filter(|response| !response.name.is_empty())
+            .collect(),
+        Some(NBNS_PORT) => parse_nbns_response(payload).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn create_name_responses_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS name_responses (
+            responder_ip TEXT NOT NULL,
+            protocol TEXT NOT NULL,
+            name TEXT NOT NULL,
+            answered_ip TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            response_count INTEGER NOT NULL DEFAULT 1,
+            PRIMARY KEY (responder_ip, protocol, name, answered_ip)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Local addresses mDNS or DNS say `name` belongs to, with where that came from
+fn authoritative_owners(conn: &Connection, name: &str) -> Result<Vec<(String, &'static str)>> {
+    let mut owners: Vec<(String, &'static str)> = Vec::new();
+    let mut stmt = conn.prepare_cached(
+        "SELECT ip, hostname, source FROM hostname_cache
+         WHERE source IN ('mdns', 'ptr') AND hostname IS NOT NULL
+           AND (lower(hostname) = ?1 OR lower(hostname) LIKE ?1 || '.%')",
+    )?;
+    let cached = stmt.query_map([name], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    for row in cached {
+        let (ip, hostname, source) = row?;
+        if short_name(&hostname) == name {
+            let source = if source == "mdns" {
+                "mDNS"
+            } else {
+                "reverse DNS"
+            };
+            owners.push((ip, source));
+        }
+    }
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT ip, hostname FROM dns_entries
+         WHERE lower(hostname) = ?1 OR lower(hostname) LIKE ?1 || '.%'",
+    )?;
+    let answers = stmt.query_map([name], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in answers {
+        let (ip, hostname) = row?;
+        // Internet names sharing a first label ("mail.example.com") say nothing about the LAN
+        if short_name(&hostname) == name && EndPoint::is_on_local_network(&ip) {
+            owners.push((ip, "DNS"));
+        }
+    }
+    Ok(owners)
+}
+
+/// Whether two addresses have been seen on the same endpoint, e.g. a host's IPv4 and
+/// IPv6 addresses
+fn same_endpoint(conn: &Connection, ip: &str, other_ip: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (
+             SELECT 1 FROM endpoint_attributes a
+             JOIN endpoint_attributes b ON b.endpoint_id = a.endpoint_id
+             WHERE a.ip = ?1 AND b.ip = ?2
+         )",
+        params![ip, other_ip],
+        |row| row.get(0),
+    )
+}
+
+/// Store a response seen at `now`. Returns true the first time this responder gave
+/// this answer for this name.
+fn record_name_response(
+    conn: &Connection,
+    responder_ip: &str,
+    response: &NameResponse,
+    now: i64,
+) -> Result<bool> {
+    let first_seen_at: i64 = conn
+        .prepare_cached(
+            "INSERT INTO name_responses
+                (responder_ip, protocol, name, answered_ip, first_seen_at, last_seen_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+             ON CONFLICT(responder_ip, protocol, name, answered_ip) DO UPDATE SET
+                last_seen_at = excluded.last_seen_at,
+                response_count = response_count + 1
+             RETURNING first_seen_at",
+        )?
+        .query_row(
+            params![
+                responder_ip,
+                response.protocol.as_str(),
+                response.name,
+                response.answered_ip,
+                now
+            ],
+            |row| row.get(0),
+        )?;
+    Ok(first_seen_at == now)
+}
+
+/// Distinct names `responder_ip` answered with its own address since `since`
+fn names_claimed(conn: &Connection, responder_ip: &str, since: i64) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(DISTINCT name) FROM name_responses
+         WHERE responder_ip = ?1 AND answered_ip = ?1 AND last_seen_at >= ?2",
+        params![responder_ip, since],
+        |row| row.get(0),
+    )
+}
+
+/// Why a response looks poisoned, if it does
+#[derive(Debug, PartialEq, Eq)]
+enum Suspicion {
+    /// The name belongs to other addresses per mDNS/DNS
+    OwnedElsewhere(Vec<(String, &'static str)>),
+    /// The responder has claimed this many names for itself
+    ClaimsManyNames(i64),
+}
+
+fn assess_response(
+    conn: &Connection,
+    responder_ip: &str,
+    response: &NameResponse,
+    now: i64,
+) -> Result<Option<Suspicion>> {
+    if !record_name_response(conn, responder_ip, response, now)? {
+        return Ok(None);
+    }
+    let owners = authoritative_owners(conn, &response.name)?;
+    if !owners.is_empty() {
+        let mut owned = false;
+        for (ip, _) in &owners {
+            if *ip == response.answered_ip || same_endpoint(conn, ip, &response.answered_ip)? {
+                owned = true;
+                break;
+            }
+        }
+        if !owned {
+            return Ok(Some(Suspicion::OwnedElsewhere(owners)));
+        }
+    }
+    if response.answered_ip == responder_ip {
+        // Reported once, when the count first reaches the limit
+        let claimed = names_claimed(conn, responder_ip, now - CLAIM_WINDOW_SECS)?;
+        if claimed == MAX_CLAIMED_NAMES {
+            return Ok(Some(Suspicion::ClaimsManyNames(claimed)));
+        }
+    }
+    Ok(None)
+}
+
+/// Record LLMNR/NBNS answers from `responder_ip` and raise a notification for any that
+/// contradict mDNS/DNS or show the responder claiming many names
+pub fn process_name_responses(
+    conn: &Connection,
+    responder_ip: &str,
+    responder_mac: Option<&str>,
+    responses: &[NameResponse],
+    now: i64,
+) -> Result<()> {
+    for response in responses {
+        let Some(suspicion) = assess_response(conn, responder_ip, response, now)? else {
+            continue;
+        };
+        let protocol = response.protocol.as_str();
+        let details = match suspicion {
+            Suspicion::OwnedElsewhere(owners) => format!(
+                "Answered '{}' with {}, but {}. MAC: {}",
+                response.name,
+                response.answered_ip,
+                owners
+                    .iter()
+                    .map(|(ip, source)| format!("{} says it is {}", source, ip))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                responder_mac.unwrap_or("unknown")
+            ),
+            Suspicion::ClaimsManyNames(claimed) => format!(
+                "Claimed {} different names for itself within an hour, most recently '{}'. MAC: {}",
+                claimed,
+                response.name,
+                responder_mac.unwrap_or("unknown")
+            ),
+        };
+        let endpoint_id: Option<i64> = conn
+            .query_row(
+                "SELECT endpoint_id FROM endpoint_attributes
+                 WHERE ip = ?1 OR mac = ?2
+                 ORDER BY mac = ?2 DESC LIMIT 1",
+                params![responder_ip, responder_mac],
+                |row| row.get(0),
+            )
+            .optional()?;
+        insert_notification_with_endpoint_id(
+            conn,
+            "name_poisoning",
+            &format!("Possible {} poisoning by {}", protocol, responder_ip),
+            Some(&details),
+            None,
+            endpoint_id,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// NBNS positive query response for "FILESERVER<20>" -> 192.168.1.66
+    fn nbns_response() -> Vec<u8> {
+        let mut message = vec![
+            0x12, 0x34, // transaction id
+            0x85, 0x00, // response, authoritative, no error
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        ];
+        message.push(0x20);
+        let mut raw = *b"FILESERVER      ";
+        raw[15] = 0x20;
+        for byte in raw {
+            message.push(b'A' + (byte >> 4));
+            message.push(b'A' + (byte & 0x0F));
+        }
+        message.push(0x00);
+        message.extend_from_slice(&[0x00, 0x20, 0x00, 0x01]);
+        message.extend_from_slice(&300_000u32.to_be_bytes());
+        message.extend_from_slice(&[0x00, 0x06, 0x00, 0x00, 192, 168, 1, 66]);
+        message
+    }
+
+    /// LLMNR response for "fileserver A" -> 192.168.1.66
+    fn llmnr_response() -> Vec<u8> {
+        let mut message = vec![
+            0xab, 0xcd, 0x80, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        ];
+        message.extend_from_slice(b"\x0afileserver\x00");
+        message.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        message.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01]);
+        message.extend_from_slice(&30u32.to_be_bytes());
+        message.extend_from_slice(&[0x00, 0x04, 192, 168, 1, 66]);
+        message
+    }
+
+    #[test]
+    fn test_parse_name_responses() {
+        let expected = |protocol| {
+            vec![NameResponse {
+                protocol,
+                name: "fileserver".to_string(),
+                answered_ip: "192.168.1.66".to_string(),
+            }]
+        };
+        assert_eq!(
+            name_responses_from_packet(Some(137), Some("Udp"), &nbns_response()),
+            expected(NameProtocol::Nbns)
+        );
+        assert_eq!(
+            name_responses_from_packet(Some(5355), Some("Udp"), &llmnr_response()),
+            expected(NameProtocol::Llmnr)
+        );
+
+        // Queries, registrations and other ports yield nothing
+        let mut query = nbns_response();
+        query[2] = 0x01;
+        assert!(parse_nbns_response(&query).is_none());
+        let mut registration = nbns_response();
+        registration[2] = 0x80 | (5 << 3);
+        assert!(parse_nbns_response(&registration).is_none());
+        assert!(name_responses_from_packet(Some(53), Some("Udp"), &llmnr_response()).is_empty());
+        assert!(parse_nbns_response(&nbns_response()[..30]).is_none());
+    }
+
+    #[test]
+    fn test_responses_checked_against_mdns_and_dns() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_name_responses_table(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE hostname_cache (ip TEXT, source TEXT, hostname TEXT,
+                                          resolved_at INTEGER, expires_at INTEGER);
+             CREATE TABLE dns_entries (ip TEXT, hostname TEXT);
+             CREATE TABLE endpoint_attributes (endpoint_id INTEGER, ip TEXT, mac TEXT);
+             INSERT INTO endpoint_attributes VALUES (1, '192.168.1.10', NULL), (1, 'fe80::10', NULL);
+             INSERT INTO hostname_cache VALUES ('fe80::10', 'mdns', 'nas.local', 0, 0);
+             INSERT INTO hostname_cache VALUES ('192.168.1.10', 'mdns', 'FileServer.local', 0, 0);
+             INSERT INTO dns_entries VALUES ('93.184.216.34', 'printer.example.com');",
+        )
+        .unwrap();
+        let response = |name: &str, ip: &str| NameResponse {
+            protocol: NameProtocol::Llmnr,
+            name: name.to_string(),
+            answered_ip: ip.to_string(),
+        };
+
+        // The real owner answering is fine
+        let owner = response("fileserver", "192.168.1.10");
+        assert_eq!(
+            assess_response(&conn, "192.168.1.10", &owner, 100).unwrap(),
+            None
+        );
+
+        // As is answering with another of the owner's addresses
+        let other_address = response("nas", "192.168.1.10");
+        assert_eq!(
+            assess_response(&conn, "192.168.1.10", &other_address, 100).unwrap(),
+            None
+        );
+
+        // Another host claiming it is flagged, once
+        let poisoned = response("fileserver", "192.168.1.66");
+        assert_eq!(
+            assess_response(&conn, "192.168.1.66", &poisoned, 100).unwrap(),
+            Some(Suspicion::OwnedElsewhere(vec![(
+                "192.168.1.10".to_string(),
+                "mDNS"
+            )]))
+        );
+        assert_eq!(
+            assess_response(&conn, "192.168.1.66", &poisoned, 200).unwrap(),
+            None
+        );
+
+        // Internet DNS names don't count as owners; claiming a third name does
+        let printer = response("printer", "192.168.1.66");
+        assert_eq!(
+            assess_response(&conn, "192.168.1.66", &printer, 300).unwrap(),
+            None
+        );
+        let wpad = response("wpad", "192.168.1.66");
+        assert_eq!(
+            assess_response(&conn, "192.168.1.66", &wpad, 400).unwrap(),
+            Some(Suspicion::ClaimsManyNames(3))
+        );
+    }
+}
@@ -155,6 +155,35 @@ impl<'a> PacketWrapper<'a> {
         }
     }
 
+    /// The TCP segment carried by an IP packet
+    pub fn get_tcp_packet(&self) -> Option<TcpPacket<'_>> {
+        match self {
+            PacketWrapper::Ipv4(packet)
+                if packet.get_next_level_protocol() == IpNextHeaderProtocols::Tcp =>
+            {
+                TcpPacket::new(packet.payload())
+            }
+            PacketWrapper::Ipv6(packet)
+                if packet.get_next_header() == IpNextHeaderProtocols::Tcp =>
+            {
+                TcpPacket::new(packet.payload())
+            }
+            _ => None,
+        }
+    }
+
+    /// The ICMPv6 message carried by an IPv6 packet (type byte onwards)
+    pub fn get_icmpv6_message(&self) -> Option<&[u8]> {
+        match self {
+            PacketWrapper::Ipv6(packet)
+                if packet.get_next_header() == IpNextHeaderProtocols::Icmpv6 =>
+            {
+                Some(packet.payload())
+            }
+            _ => None,
+        }
+    }
+
     pub fn get_sub_protocol(&self, port: u16) -> Option<String> {
         ProtocolPort::from(port).to_string().into()
     }
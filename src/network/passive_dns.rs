@@ -0,0 +1,275 @@
+//! Passive DNS. DNS responses seen in the capture stream are parsed for A/AAAA answers and
+//! stored in `dns_entries` as IP -> queried name, so internet destinations can be labelled
+//! with the domain a device actually looked up instead of a bare IP or a PTR name.
+
+use rusqlite::{Connection, Result, params};
+
+const DNS_PORT: u16 = 53;
+const DNS_HEADER_LEN: usize = 12;
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+/// Compression pointers followed per name before giving up (guards against loops)
+const MAX_NAME_POINTERS: usize = 16;
+
+/// One address answer from a DNS response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsAnswer {
+    /// The name the client asked for (not the end of a CNAME chain)
+    pub hostname: String,
+    pub ip: String,
+    pub ttl: u32,
+}
+
+/// Read a (possibly compressed) name at `offset`. Returns the name and the offset just
+/// past it in the original position.
+fn read_name(message: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut pointers = 0;
+
+    loop {
+        let len = *message.get(offset)? as usize;
+        if len == 0 {
+            end.get_or_insert(offset + 1);
+            break;
+        }
+        if len & 0xC0 == 0xC0 {
+            let target = ((len & 0x3F) << 8) | *message.get(offset + 1)? as usize;
+            end.get_or_insert(offset + 2);
+            pointers += 1;
+            if pointers > MAX_NAME_POINTERS {
+                return None;
+            }
+            offset = target;
+            continue;
+        }
+        let label = message.get(offset + 1..offset + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_lowercase());
+        offset += 1 + len;
+    }
+
+    Some((labels.join("."), end?))
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *message.get(offset)?,
+        *message.get(offset + 1)?,
+    ]))
+}
+
+fn read_u32(message: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes([
+        *message.get(offset)?,
+        *message.get(offset + 1)?,
+        *message.get(offset + 2)?,
+        *message.get(offset + 3)?,
+    ]))
+}
+
+/// Parse the A/AAAA answers out of a DNS response message. Queries, errors and
+/// malformed messages yield no answers.
+pub fn parse_dns_response(message: &[u8]) -> Vec<DnsAnswer> {
+    parse_answers(message).unwrap_or_default()
+}
+
+fn parse_answers(message: &[u8]) -> Option<Vec<DnsAnswer>> {
+    let flags = read_u16(message, 2)?;
+    let is_response = flags & 0x8000 != 0;
+    let rcode = flags & 0x000F;
+    if !is_response || rcode != 0 {
+        return None;
+    }
+    let question_count = read_u16(message, 4)?;
+    let answer_count = read_u16(message, 6)?;
+
+    let mut offset = DNS_HEADER_LEN;
+    let mut queried_name = None;
+    for _ in 0..question_count {
+        let (name, next) = read_name(message, offset)?;
+        queried_name.get_or_insert(name);
+        offset = next + 4; // type + class
+    }
+
+    let mut answers = Vec::new();
+    for _ in 0..answer_count {
+        let (owner, next) = read_name(message, offset)?;
+        let record_type = read_u16(message, next)?;
+        let ttl = read_u32(message, next + 4)?;
+        let data_len = read_u16(message, next + 8)? as usize;
+        let data = message.get(next + 10..next + 10 + data_len)?;
+        offset = next + 10 + data_len;
+
+        let ip = match (record_type, data_len) {
+            (TYPE_A, 4) => std::net::Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string(),
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().ok()?;
+                std::net::Ipv6Addr::from(octets).to_string()
+            }
+            _ => continue,
+        };
+        let hostname = queried_name.clone().unwrap_or(owner);
+        if !hostname.is_empty() {
+            answers.push(DnsAnswer { hostname, ip, ttl });
+        }
+    }
+    Some(answers)
+}
+
+/// Answers carried by a captured packet, if it is a DNS response (source port 53).
+/// DNS over TCP prefixes each message with its length.
+pub fn dns_answers_from_packet(
+    source_port: Option<u16>,
+    header_protocol: Option<&str>,
+    payload: &[u8],
+) -> Vec<DnsAnswer> {
+    if source_port != Some(DNS_PORT) || payload.len() < DNS_HEADER_LEN {
+        return Vec::new();
+    }
+    match header_protocol {
+        Some("Udp") => parse_dns_response(payload),
+        Some("Tcp") => parse_dns_response(payload.get(2..).unwrap_or_default()),
+        _ => Vec::new(),
+    }
+}
+
+pub fn create_dns_entries_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS dns_entries (
+            ip TEXT NOT NULL,
+            hostname TEXT NOT NULL,
+            ttl INTEGER,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            answer_count INTEGER NOT NULL DEFAULT 1,
+            PRIMARY KEY (ip, hostname)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_dns_entries_last_seen ON dns_entries (last_seen_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Store answers seen at `now`
+pub fn record_dns_answers(conn: &Connection, answers: &[DnsAnswer], now: i64) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO dns_entries (ip, hostname, ttl, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT(ip, hostname) DO UPDATE SET
+            ttl = excluded.ttl,
+            last_seen_at = excluded.last_seen_at,
+            answer_count = answer_count + 1",
+    )?;
+    for answer in answers {
+        stmt.execute(params![answer.ip, answer.hostname, answer.ttl, now])?;
+    }
+    Ok(())
+}
+
+/// The name most recently resolved to `ip`, if any
+pub fn lookup_dns_hostname(conn: &Connection, ip: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT hostname FROM dns_entries WHERE ip = ?1 ORDER BY last_seen_at DESC LIMIT 1",
+        [ip],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+/// Most recently seen entries as (ip, hostname, last_seen_at)
+pub fn get_recent_dns_entries(conn: &Connection, limit: i64) -> Result<Vec<(String, String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT ip, hostname, last_seen_at FROM dns_entries
+         ORDER BY last_seen_at DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map([limit], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response to "www.example.com A" answered through a CNAME, using name compression
+    fn example_response() -> Vec<u8> {
+        let mut message = vec![
+            0x12, 0x34, // id
+            0x81, 0x80, // standard response, no error
+            0x00, 0x01, // 1 question
+            0x00, 0x02, // 2 answers
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        // Question at offset 12: www.example.com IN A
+        message.extend_from_slice(b"\x03www\x07example\x03com\x00");
+        message.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        // CNAME www.example.com -> edge.example.com (target compressed against "example.com")
+        message.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x05, 0x00, 0x01]);
+        message.extend_from_slice(&300u32.to_be_bytes());
+        message.extend_from_slice(&[0x00, 0x07]);
+        message.extend_from_slice(b"\x04edge\xC0\x10");
+        let edge_offset = message.len() - 7;
+        // A edge.example.com -> 93.184.216.34
+        message.extend_from_slice(&[0xC0, edge_offset as u8, 0x00, 0x01, 0x00, 0x01]);
+        message.extend_from_slice(&60u32.to_be_bytes());
+        message.extend_from_slice(&[0x00, 0x04, 93, 184, 216, 34]);
+        message
+    }
+
+    #[test]
+    fn test_parse_dns_response_maps_ip_to_queried_name() {
+        assert_eq!(
+            parse_dns_response(&example_response()),
+            vec![DnsAnswer {
+                hostname: "www.example.com".to_string(),
+                ip: "93.184.216.34".to_string(),
+                ttl: 60,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_queries_and_garbage_yield_nothing() {
+        let mut query = example_response();
+        query[2] = 0x01; // clear the response bit
+        assert!(parse_dns_response(&query).is_empty());
+
+        let truncated = &example_response()[..40];
+        assert!(parse_dns_response(truncated).is_empty());
+
+        // A pointer to itself must not loop forever
+        let mut looping = vec![0x00, 0x00, 0x81, 0x80, 0x00, 0x01, 0x00, 0x00, 0, 0, 0, 0];
+        looping.extend_from_slice(&[0xC0, 0x0C]);
+        assert!(parse_dns_response(&looping).is_empty());
+
+        assert!(dns_answers_from_packet(Some(5353), Some("Udp"), &example_response()).is_empty());
+    }
+
+    #[test]
+    fn test_record_and_lookup_dns_entries() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_dns_entries_table(&conn).unwrap();
+
+        let answers = dns_answers_from_packet(Some(53), Some("Udp"), &example_response());
+        record_dns_answers(&conn, &answers, 100).unwrap();
+        record_dns_answers(
+            &conn,
+            &[DnsAnswer {
+                hostname: "cdn.example.net".to_string(),
+                ip: "93.184.216.34".to_string(),
+                ttl: 30,
+            }],
+            200,
+        )
+        .unwrap();
+
+        assert_eq!(
+            lookup_dns_hostname(&conn, "93.184.216.34").as_deref(),
+            Some("cdn.example.net")
+        );
+        assert_eq!(lookup_dns_hostname(&conn, "10.0.0.1"), None);
+        assert_eq!(get_recent_dns_entries(&conn, 10).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,68 @@
+//! Privacy mode (the `privacy_mode` setting) keeps the device inventory without keeping a
+//! record of where devices go on the internet. The SQL writer applies it to every
+//! communication as it arrives, before flow aggregation and before anything is stored or
+//! parsed for names, so live capture and pcap imports are covered alike:
+//!
+//! - payloads are dropped unread, so no TLS SNI or HTTP Host names are taken from them
+//! - DNS answers are discarded, so visited site names never reach the DNS history
+//! - internet addresses are cut to their /24 (/48 for IPv6)
+//!
+//! Local addresses and local discovery protocols (DHCP, LLMNR/NBNS, router advertisements)
+//! are kept, since they describe the devices themselves.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use rusqlite::Connection;
+
+/// Whether privacy mode is on (off by default)
+pub fn privacy_mode_enabled(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = 'privacy_mode'",
+        [],
+        |row| row.get::<_, String>(0),
+    )
+    .map(|v| v == "true" || v == "1")
+    .unwrap_or(false)
+}
+
+/// The network an address belongs to, as its first address: a.b.c.0 for IPv4, the /48 for
+/// IPv6. Strings that aren't addresses are returned unchanged.
+pub fn truncate_ip(ip: &str) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => {
+            let [a, b, c, _] = v4.octets();
+            Ipv4Addr::new(a, b, c, 0).to_string()
+        }
+        Ok(IpAddr::V6(v6)) => {
+            let s = v6.segments();
+            Ipv6Addr::new(s[0], s[1], s[2], 0, 0, 0, 0, 0).to_string()
+        }
+        Err(_) => ip.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::communication::Communication;
+    use crate::test_utils::PacketBuilder;
+    use pnet::packet::ethernet::EthernetPacket;
+
+    #[test]
+    fn test_truncate_ip() {
+        assert_eq!(truncate_ip("93.184.216.34"), "93.184.216.0");
+        assert_eq!(truncate_ip("2606:4700:4700::1111"), "2606:4700:4700::");
+        assert_eq!(truncate_ip("not-an-ip"), "not-an-ip");
+    }
+
+    #[test]
+    fn test_privacy_mode_redacts_internet_traffic() {
+        let packet = PacketBuilder::https_packet("127.0.0.2", "93.184.216.34");
+        let mut comm = Communication::new(EthernetPacket::new(&packet).unwrap());
+        comm.apply_privacy_mode();
+        // The local side is kept as is, the internet side cut to its network
+        assert_eq!(comm.source_ip.as_deref(), Some("127.0.0.2"));
+        assert_eq!(comm.destination_ip.as_deref(), Some("93.184.216.0"));
+        assert!(comm.get_payload().is_empty());
+    }
+}
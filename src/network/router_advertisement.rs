@@ -0,0 +1,522 @@
+//! IPv6 router advertisements. RAs seen in the capture stream are parsed for the prefixes,
+//! DNS servers (RDNSS) and router lifetime they announce and stored per router. The first
+//! router seen is trusted; an RA from any other router, or from a trusted router's address
+//! with a different MAC, is reported as a possible rogue router until it is marked trusted.
+
+use std::net::Ipv6Addr;
+
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::db::insert_notification_with_endpoint_id;
+
+const ICMPV6_ROUTER_ADVERTISEMENT: u8 = 134;
+const RA_HEADER_LEN: usize = 16;
+const OPTION_SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+const OPTION_PREFIX_INFORMATION: u8 = 3;
+const OPTION_MTU: u8 = 5;
+const OPTION_RECURSIVE_DNS_SERVER: u8 = 25;
+
+/// A prefix announced in a Prefix Information option
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AdvertisedPrefix {
+    pub prefix: String,
+    pub prefix_len: u8,
+    /// Hosts may configure addresses in the prefix themselves (SLAAC)
+    pub autonomous: bool,
+    pub on_link: bool,
+    pub valid_lifetime: u32,
+    pub preferred_lifetime: u32,
+}
+
+/// What a router announced in one RA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouterAdvertisement {
+    pub router_ip: String,
+    /// From the source link-layer address option, else the Ethernet source
+    pub router_mac: Option<String>,
+    pub hop_limit: u8,
+    /// DHCPv6 assigns addresses (M flag)
+    pub managed: bool,
+    /// DHCPv6 provides other configuration (O flag)
+    pub other_config: bool,
+    /// Seconds hosts may use the router as default gateway; 0 means "not a default router"
+    pub router_lifetime: u16,
+    pub mtu: Option<u32>,
+    pub prefixes: Vec<AdvertisedPrefix>,
+    pub dns_servers: Vec<String>,
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        message.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(message: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        message.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_ipv6(message: &[u8], offset: usize) -> Option<Ipv6Addr> {
+    let octets: [u8; 16] = message.get(offset..offset + 16)?.try_into().ok()?;
+    Some(Ipv6Addr::from(octets))
+}
+
+/// Parse an ICMPv6 message as a router advertisement. Other messages and malformed
+/// options yield None.
+pub fn parse_router_advertisement(
+    router_ip: &str,
+    router_mac: Option<&str>,
+    message: &[u8],
+) -> Option<RouterAdvertisement> {
+    if message.len() < RA_HEADER_LEN || message[0] != ICMPV6_ROUTER_ADVERTISEMENT {
+        return None;
+    }
+    let flags = message[5];
+    let mut advertisement = RouterAdvertisement {
+        router_ip: router_ip.to_string(),
+        router_mac: router_mac.map(str::to_string),
+        hop_limit: message[4],
+        managed: flags & 0x80 != 0,
+        other_config: flags & 0x40 != 0,
+        router_lifetime: read_u16(message, 6)?,
+        mtu: None,
+        prefixes: Vec::new(),
+        dns_servers: Vec::new(),
+    };
+
+    let mut offset = RA_HEADER_LEN;
+    while offset + 2 <= message.len() {
+        let kind = message[offset];
+        // Option length is in units of 8 bytes; zero is invalid and would never advance
+        let len = usize::from(message[offset + 1]) * 8;
+        if len == 0 {
+            return None;
+        }
+        let option = message.get(offset..offset + len)?;
+        match kind {
+            OPTION_SOURCE_LINK_LAYER_ADDRESS if option.len() >= 8 => {
+                let mac = &option[2..8];
+                advertisement.router_mac = Some(
+                    mac.iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect::<Vec<_>>()
+                        .join(":"),
+                );
+            }
+            OPTION_PREFIX_INFORMATION if option.len() >= 32 => {
+                advertisement.prefixes.push(AdvertisedPrefix {
+                    prefix: read_ipv6(option, 16)?.to_string(),
+                    prefix_len: option[2],
+                    on_link: option[3] & 0x80 != 0,
+                    autonomous: option[3] & 0x40 != 0,
+                    valid_lifetime: read_u32(option, 4)?,
+                    preferred_lifetime: read_u32(option, 8)?,
+                });
+            }
+            OPTION_MTU if option.len() >= 8 => advertisement.mtu = read_u32(option, 4),
+            OPTION_RECURSIVE_DNS_SERVER => {
+                let mut server = 8;
+                while server + 16 <= option.len() {
+                    advertisement
+                        .dns_servers
+                        .push(read_ipv6(option, server)?.to_string());
+                    server += 16;
+                }
+            }
+            _ => {}
+        }
+        offset += len;
+    }
+    Some(advertisement)
+}
+
+pub fn create_router_advertisement_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ipv6_routers (
+            router_ip TEXT PRIMARY KEY,
+            router_mac TEXT,
+            trusted INTEGER NOT NULL DEFAULT 0,
+            hop_limit INTEGER,
+            managed INTEGER NOT NULL DEFAULT 0,
+            other_config INTEGER NOT NULL DEFAULT 0,
+            router_lifetime INTEGER NOT NULL,
+            mtu INTEGER,
+            dns_servers TEXT,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            advertisement_count INTEGER NOT NULL DEFAULT 1
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ipv6_prefixes (
+            prefix TEXT NOT NULL,
+            prefix_len INTEGER NOT NULL,
+            router_ip TEXT NOT NULL REFERENCES ipv6_routers(router_ip) ON DELETE CASCADE,
+            autonomous INTEGER NOT NULL DEFAULT 0,
+            on_link INTEGER NOT NULL DEFAULT 0,
+            valid_lifetime INTEGER NOT NULL,
+            preferred_lifetime INTEGER NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            PRIMARY KEY (prefix, prefix_len, router_ip)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// What changed when an RA was recorded
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AdvertisementChanges {
+    /// The router had not advertised before
+    pub new_router: bool,
+    pub trusted: bool,
+    /// The MAC the router's address was previously seen from, when it differs now
+    pub previous_mac: Option<String>,
+    pub new_prefixes: Vec<AdvertisedPrefix>,
+}
+
+/// Store an RA seen at `now`. The first router ever recorded is trusted.
+pub fn record_router_advertisement(
+    conn: &Connection,
+    advertisement: &RouterAdvertisement,
+    now: i64,
+) -> Result<AdvertisementChanges> {
+    let existing: Option<(Option<String>, bool)> = conn
+        .query_row(
+            "SELECT router_mac, trusted FROM ipv6_routers WHERE router_ip = ?1",
+            [&advertisement.router_ip],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    let mut changes = AdvertisementChanges::default();
+    match existing {
+        Some((previous_mac, trusted)) => {
+            changes.trusted = trusted;
+            if previous_mac.is_some()
+                && advertisement.router_mac.is_some()
+                && previous_mac != advertisement.router_mac
+            {
+                changes.previous_mac = previous_mac;
+            }
+        }
+        None => {
+            changes.new_router = true;
+            changes.trusted =
+                conn.query_row("SELECT COUNT(*) = 0 FROM ipv6_routers", [], |row| {
+                    row.get(0)
+                })?;
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO ipv6_routers
+            (router_ip, router_mac, trusted, hop_limit, managed, other_config, router_lifetime,
+             mtu, dns_servers, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)
+         ON CONFLICT(router_ip) DO UPDATE SET
+            router_mac = COALESCE(excluded.router_mac, router_mac),
+            hop_limit = excluded.hop_limit,
+            managed = excluded.managed,
+            other_config = excluded.other_config,
+            router_lifetime = excluded.router_lifetime,
+            mtu = COALESCE(excluded.mtu, mtu),
+            dns_servers = COALESCE(excluded.dns_servers, dns_servers),
+            last_seen_at = excluded.last_seen_at,
+            advertisement_count = advertisement_count + 1",
+        params![
+            advertisement.router_ip,
+            advertisement.router_mac,
+            changes.trusted,
+            advertisement.hop_limit,
+            advertisement.managed,
+            advertisement.other_config,
+            advertisement.router_lifetime,
+            advertisement.mtu,
+            (!advertisement.dns_servers.is_empty()).then(|| advertisement.dns_servers.join(",")),
+            now
+        ],
+    )?;
+
+    let mut upsert = conn.prepare_cached(
+        "INSERT INTO ipv6_prefixes
+            (prefix, prefix_len, router_ip, autonomous, on_link, valid_lifetime,
+             preferred_lifetime, first_seen_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
+         ON CONFLICT(prefix, prefix_len, router_ip) DO UPDATE SET
+            autonomous = excluded.autonomous,
+            on_link = excluded.on_link,
+            valid_lifetime = excluded.valid_lifetime,
+            preferred_lifetime = excluded.preferred_lifetime,
+            last_seen_at = excluded.last_seen_at
+         RETURNING first_seen_at",
+    )?;
+    for prefix in &advertisement.prefixes {
+        let first_seen_at: i64 = upsert.query_row(
+            params![
+                prefix.prefix,
+                prefix.prefix_len,
+                advertisement.router_ip,
+                prefix.autonomous,
+                prefix.on_link,
+                prefix.valid_lifetime,
+                prefix.preferred_lifetime,
+                now
+            ],
+            |row| row.get(0),
+        )?;
+        if first_seen_at == now {
+            changes.new_prefixes.push(prefix.clone());
+        }
+    }
+    Ok(changes)
+}
+
+/// Record an RA and raise notifications for rogue routers and newly announced prefixes
+pub fn process_router_advertisement(
+    conn: &Connection,
+    advertisement: &RouterAdvertisement,
+    now: i64,
+) -> Result<()> {
+    let changes = record_router_advertisement(conn, advertisement, now)?;
+    let router = &advertisement.router_ip;
+    let mac = advertisement.router_mac.as_deref().unwrap_or("unknown MAC");
+    let endpoint_id: Option<i64> = conn
+        .query_row(
+            "SELECT endpoint_id FROM endpoint_attributes WHERE mac = ?1 LIMIT 1",
+            [mac],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if changes.new_router && !changes.trusted {
+        let prefixes: Vec<String> = advertisement
+            .prefixes
+            .iter()
+            .map(|p| format!("{}/{}", p.prefix, p.prefix_len))
+            .collect();
+        insert_notification_with_endpoint_id(
+            conn,
+            "rogue_router",
+            &format!("Unexpected IPv6 router advertisement from {}", router),
+            Some(&format!(
+                "MAC: {}, lifetime {}s, prefixes: {}, DNS: {}",
+                mac,
+                advertisement.router_lifetime,
+                if prefixes.is_empty() {
+                    "none".to_string()
+                } else {
+                    prefixes.join(", ")
+                },
+                if advertisement.dns_servers.is_empty() {
+                    "none".to_string()
+                } else {
+                    advertisement.dns_servers.join(", ")
+                }
+            )),
+            None,
+            endpoint_id,
+        );
+    }
+    if let Some(previous_mac) = changes.previous_mac.as_deref()
+        && changes.trusted
+    {
+        insert_notification_with_endpoint_id(
+            conn,
+            "rogue_router",
+            &format!("IPv6 router {} is advertising from a different MAC", router),
+            Some(&format!("Was {}, now {}", previous_mac, mac)),
+            None,
+            endpoint_id,
+        );
+    }
+    if changes.trusted {
+        for prefix in &changes.new_prefixes {
+            insert_notification_with_endpoint_id(
+                conn,
+                "ipv6_prefix",
+                &format!(
+                    "IPv6 prefix {}/{} advertised",
+                    prefix.prefix, prefix.prefix_len
+                ),
+                Some(&format!("Router {} ({})", router, mac)),
+                None,
+                endpoint_id,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Mark a router as expected (or not)
+pub fn set_router_trusted(conn: &Connection, router_ip: &str, trusted: bool) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE ipv6_routers SET trusted = ?1 WHERE router_ip = ?2",
+        params![trusted, router_ip],
+    )?;
+    Ok(updated > 0)
+}
+
+/// A router and the prefixes it currently announces
+#[derive(Debug, Serialize)]
+pub struct Ipv6Router {
+    pub router_ip: String,
+    pub router_mac: Option<String>,
+    pub trusted: bool,
+    pub managed: bool,
+    pub other_config: bool,
+    pub router_lifetime: u16,
+    pub mtu: Option<u32>,
+    pub dns_servers: Vec<String>,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+    pub advertisement_count: i64,
+    pub prefixes: Vec<AdvertisedPrefix>,
+}
+
+/// Every router seen, with the prefixes whose valid lifetime has not run out
+pub fn get_ipv6_routers(conn: &Connection, now: i64) -> Result<Vec<Ipv6Router>> {
+    let mut stmt = conn.prepare(
+        "SELECT router_ip, router_mac, trusted, managed, other_config, router_lifetime, mtu,
+                dns_servers, first_seen_at, last_seen_at, advertisement_count
+         FROM ipv6_routers ORDER BY trusted DESC, last_seen_at DESC",
+    )?;
+    let mut routers = stmt
+        .query_map([], |row| {
+            let dns_servers: Option<String> = row.get(7)?;
+            Ok(Ipv6Router {
+                router_ip: row.get(0)?,
+                router_mac: row.get(1)?,
+                trusted: row.get(2)?,
+                managed: row.get(3)?,
+                other_config: row.get(4)?,
+                router_lifetime: row.get(5)?,
+                mtu: row.get(6)?,
+                dns_servers: dns_servers
+                    .map(|s| s.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+                first_seen_at: row.get(8)?,
+                last_seen_at: row.get(9)?,
+                advertisement_count: row.get(10)?,
+                prefixes: Vec::new(),
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    // 0xffffffff is an infinite lifetime
+    let mut stmt = conn.prepare(
+        "SELECT prefix, prefix_len, autonomous, on_link, valid_lifetime, preferred_lifetime
+         FROM ipv6_prefixes
+         WHERE router_ip = ?1
+           AND (valid_lifetime = 4294967295 OR last_seen_at + valid_lifetime >= ?2)
+         ORDER BY prefix, prefix_len",
+    )?;
+    for router in &mut routers {
+        router.prefixes = stmt
+            .query_map(params![router.router_ip, now], |row| {
+                Ok(AdvertisedPrefix {
+                    prefix: row.get(0)?,
+                    prefix_len: row.get(1)?,
+                    autonomous: row.get(2)?,
+                    on_link: row.get(3)?,
+                    valid_lifetime: row.get(4)?,
+                    preferred_lifetime: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+    }
+    Ok(routers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RA with a source link-layer address, one SLAAC prefix and one RDNSS server
+    fn example_advertisement() -> Vec<u8> {
+        let mut message = vec![
+            134, 0, 0x00, 0x00, // type, code, checksum
+            64, 0x40, // hop limit, O flag
+            0x07, 0x08, // router lifetime 1800
+            0, 0, 0, 0, 0, 0, 0, 0, // reachable time, retrans timer
+        ];
+        message.extend_from_slice(&[1, 1, 0xaa, 0xbb, 0xcc, 0x00, 0x00, 0x01]);
+        message.extend_from_slice(&[3, 4, 64, 0xc0]);
+        message.extend_from_slice(&86400u32.to_be_bytes());
+        message.extend_from_slice(&14400u32.to_be_bytes());
+        message.extend_from_slice(&[0; 4]);
+        message.extend_from_slice(&"2001:db8:1::".parse::<Ipv6Addr>().unwrap().octets());
+        message.extend_from_slice(&[25, 3, 0, 0]);
+        message.extend_from_slice(&600u32.to_be_bytes());
+        message.extend_from_slice(&"2001:db8:1::53".parse::<Ipv6Addr>().unwrap().octets());
+        message
+    }
+
+    #[test]
+    fn test_parse_router_advertisement() {
+        let ra = parse_router_advertisement("fe80::1", None, &example_advertisement()).unwrap();
+        assert_eq!(ra.router_mac.as_deref(), Some("aa:bb:cc:00:00:01"));
+        assert_eq!(ra.router_lifetime, 1800);
+        assert!(!ra.managed);
+        assert!(ra.other_config);
+        assert_eq!(
+            ra.prefixes,
+            vec![AdvertisedPrefix {
+                prefix: "2001:db8:1::".to_string(),
+                prefix_len: 64,
+                autonomous: true,
+                on_link: true,
+                valid_lifetime: 86400,
+                preferred_lifetime: 14400,
+            }]
+        );
+        assert_eq!(ra.dns_servers, vec!["2001:db8:1::53"]);
+
+        // Neighbor solicitations and zero-length options are rejected
+        let mut other = example_advertisement();
+        other[0] = 135;
+        assert!(parse_router_advertisement("fe80::1", None, &other).is_none());
+        let mut broken = example_advertisement();
+        broken[17] = 0;
+        assert!(parse_router_advertisement("fe80::1", None, &broken).is_none());
+    }
+
+    #[test]
+    fn test_record_router_advertisement() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_router_advertisement_tables(&conn).unwrap();
+        let ra = parse_router_advertisement("fe80::1", None, &example_advertisement()).unwrap();
+
+        let changes = record_router_advertisement(&conn, &ra, 100).unwrap();
+        assert!(changes.new_router && changes.trusted);
+        assert_eq!(changes.new_prefixes.len(), 1);
+
+        let changes = record_router_advertisement(&conn, &ra, 200).unwrap();
+        assert!(!changes.new_router && changes.new_prefixes.is_empty());
+
+        // A second router is not trusted until marked so
+        let mut rogue = ra.clone();
+        rogue.router_ip = "fe80::bad".to_string();
+        rogue.router_mac = Some("de:ad:be:ef:00:01".to_string());
+        let changes = record_router_advertisement(&conn, &rogue, 300).unwrap();
+        assert!(changes.new_router && !changes.trusted);
+        assert!(set_router_trusted(&conn, "fe80::bad", true).unwrap());
+
+        // The trusted router's address from another MAC
+        let mut spoofed = ra.clone();
+        spoofed.router_mac = Some("de:ad:be:ef:00:02".to_string());
+        let changes = record_router_advertisement(&conn, &spoofed, 400).unwrap();
+        assert_eq!(changes.previous_mac.as_deref(), Some("aa:bb:cc:00:00:01"));
+
+        let routers = get_ipv6_routers(&conn, 400).unwrap();
+        assert_eq!(routers.len(), 2);
+        assert!(routers.iter().all(|r| r.trusted));
+        assert_eq!(routers[0].prefixes.len(), 1);
+        assert_eq!(routers[0].dns_servers, vec!["2001:db8:1::53"]);
+    }
+}
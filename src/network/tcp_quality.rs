@@ -0,0 +1,215 @@
+//! Lightweight TCP quality signals. A tracker per capture follows connections just far
+//! enough to time handshakes (SYN to the matching SYN/ACK) and to spot data segments
+//! that are sent again. The results ride along on each communication and are added to
+//! the hourly rollups of the local endpoints involved, which is enough to tell a flaky
+//! Wi-Fi device from a healthy one without keeping per-connection history.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+use pnet::packet::Packet;
+use pnet::packet::ethernet::EthernetPacket;
+use pnet::packet::tcp::TcpFlags;
+
+use super::packet_wrapper::PacketWrapper;
+
+/// Connections tracked before idle state is pruned
+const MAX_TRACKED: usize = 20_000;
+
+/// State idle this long is dropped when pruning
+const IDLE_MICROS: i64 = 120 * 1_000_000;
+
+/// Handshakes slower than this are not timed (the SYN/ACK answers something else)
+const MAX_RTT_MICROS: i64 = 30 * 1_000_000;
+
+/// TCP quality signals of one packet, or summed over the packets of a flow
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TcpQuality {
+    /// Segments carrying data (or a FIN)
+    pub segments: u32,
+    /// Segments whose sequence range had already been sent
+    pub retransmissions: u32,
+    /// Handshake round trips, in microseconds
+    pub rtt_samples: Vec<u32>,
+}
+
+impl TcpQuality {
+    pub fn is_empty(&self) -> bool {
+        self.segments == 0 && self.retransmissions == 0 && self.rtt_samples.is_empty()
+    }
+
+    pub fn absorb(&mut self, other: TcpQuality) {
+        self.segments = self.segments.saturating_add(other.segments);
+        self.retransmissions = self.retransmissions.saturating_add(other.retransmissions);
+        self.rtt_samples.extend(other.rtt_samples);
+    }
+}
+
+/// One direction of a connection: (sender, receiver)
+type FlowKey = (SocketAddr, SocketAddr);
+
+#[derive(Debug, Default)]
+pub struct TcpQualityTracker {
+    /// SYNs waiting for their SYN/ACK: initial sequence number and when it was sent
+    pending_syns: HashMap<FlowKey, (u32, i64)>,
+    /// End of the highest sequence range sent in each direction, and when
+    next_seq: HashMap<FlowKey, (u32, i64)>,
+}
+
+impl TcpQualityTracker {
+    /// Follow one captured frame (`at_micros` is its capture time)
+    pub fn observe(&mut self, ethernet_packet: &EthernetPacket, at_micros: i64) -> TcpQuality {
+        let wrapper = PacketWrapper::new(ethernet_packet);
+        let (Some(source_ip), Some(destination_ip), Some(tcp)) = (
+            wrapper
+                .get_source_ip()
+                .and_then(|ip| ip.parse::<IpAddr>().ok()),
+            wrapper
+                .get_destination_ip()
+                .and_then(|ip| ip.parse::<IpAddr>().ok()),
+            wrapper.get_tcp_packet(),
+        ) else {
+            return TcpQuality::default();
+        };
+        let key = (
+            SocketAddr::new(source_ip, tcp.get_source()),
+            SocketAddr::new(destination_ip, tcp.get_destination()),
+        );
+        let flags = tcp.get_flags();
+        let has_flag = |flag: u8| flags & flag == flag;
+        self.observe_segment(
+            key,
+            tcp.get_sequence(),
+            tcp.get_acknowledgement(),
+            flags,
+            tcp.payload().len() as u32 + u32::from(has_flag(TcpFlags::FIN)),
+            at_micros,
+        )
+    }
+
+    fn observe_segment(
+        &mut self,
+        key: FlowKey,
+        seq: u32,
+        ack: u32,
+        flags: u8,
+        length: u32,
+        at_micros: i64,
+    ) -> TcpQuality {
+        let mut quality = TcpQuality::default();
+        let has_flag = |flag: u8| flags & flag == flag;
+
+        if has_flag(TcpFlags::SYN) && !has_flag(TcpFlags::ACK) {
+            // A repeated SYN is a retransmission, and its handshake can't be timed
+            if self
+                .pending_syns
+                .get(&key)
+                .is_some_and(|&(isn, _)| isn == seq)
+            {
+                self.pending_syns.remove(&key);
+                quality.retransmissions += 1;
+            } else {
+                self.pending_syns.insert(key, (seq, at_micros));
+            }
+        } else if has_flag(TcpFlags::SYN | TcpFlags::ACK) {
+            let reverse = (key.1, key.0);
+            if let Some((isn, sent_at)) = self.pending_syns.remove(&reverse)
+                && ack == isn.wrapping_add(1)
+                && (0..MAX_RTT_MICROS).contains(&(at_micros - sent_at))
+            {
+                quality.rtt_samples.push((at_micros - sent_at) as u32);
+            }
+        }
+
+        if length > 0 {
+            quality.segments += 1;
+            let end = seq.wrapping_add(length);
+            match self.next_seq.get_mut(&key) {
+                // Keep-alive probes resend the last byte on purpose
+                Some((next, seen_at)) if length == 1 && end == *next => *seen_at = at_micros,
+                // Ends at or before data already sent (allowing for wraparound)
+                Some((next, seen_at)) if (next.wrapping_sub(end) as i32) >= 0 => {
+                    quality.retransmissions += 1;
+                    *seen_at = at_micros;
+                }
+                Some(state) => *state = (end, at_micros),
+                None => {
+                    self.next_seq.insert(key, (end, at_micros));
+                }
+            }
+        }
+
+        if has_flag(TcpFlags::RST) {
+            self.next_seq.remove(&key);
+            self.next_seq.remove(&(key.1, key.0));
+        }
+        if self.pending_syns.len() + self.next_seq.len() > MAX_TRACKED {
+            self.prune(at_micros);
+        }
+        quality
+    }
+
+    /// Drop idle connections, and everything if that isn't enough
+    fn prune(&mut self, now_micros: i64) {
+        let active = |&mut (_, at): &mut (u32, i64)| now_micros - at < IDLE_MICROS;
+        self.pending_syns.retain(|_, state| active(state));
+        self.next_seq.retain(|_, state| active(state));
+        if self.pending_syns.len() + self.next_seq.len() > MAX_TRACKED {
+            self.pending_syns.clear();
+            self.next_seq.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tcp_quality_tracker() {
+        let client: SocketAddr = "192.168.1.20:50000".parse().unwrap();
+        let server: SocketAddr = "192.168.1.5:443".parse().unwrap();
+        let mut tracker = TcpQualityTracker::default();
+
+        // Handshake: SYN at 0, SYN/ACK 12.5 ms later
+        let syn = tracker.observe_segment((client, server), 1000, 0, TcpFlags::SYN, 0, 0);
+        assert!(syn.is_empty());
+        let syn_ack = tracker.observe_segment(
+            (server, client),
+            5000,
+            1001,
+            TcpFlags::SYN | TcpFlags::ACK,
+            0,
+            12_500,
+        );
+        assert_eq!(syn_ack.rtt_samples, vec![12_500]);
+
+        // Data, the same range sent again, then the next range
+        let data = |tracker: &mut TcpQualityTracker, seq: u32| {
+            tracker.observe_segment((client, server), seq, 5001, TcpFlags::ACK, 100, 20_000)
+        };
+        assert_eq!(data(&mut tracker, 1001).retransmissions, 0);
+        assert_eq!(data(&mut tracker, 1001).retransmissions, 1);
+        assert_eq!(data(&mut tracker, 1101).retransmissions, 0);
+
+        let mut total = TcpQuality::default();
+        total.absorb(syn_ack);
+        total.absorb(data(&mut tracker, 1101));
+        assert_eq!(total.segments, 1);
+        assert_eq!(total.retransmissions, 1);
+
+        // A repeated SYN is counted and not timed
+        tracker.observe_segment((client, server), 7000, 0, TcpFlags::SYN, 0, 0);
+        let again = tracker.observe_segment((client, server), 7000, 0, TcpFlags::SYN, 0, 1_000_000);
+        assert_eq!(again.retransmissions, 1);
+        let late = tracker.observe_segment(
+            (server, client),
+            9000,
+            7001,
+            TcpFlags::SYN | TcpFlags::ACK,
+            0,
+            1_010_000,
+        );
+        assert!(late.rtt_samples.is_empty());
+    }
+}
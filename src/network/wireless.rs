@@ -0,0 +1,490 @@
+//! Wireless signal metadata. On a monitor-mode interface (or a pcap recorded from one)
+//! each frame starts with a radiotap header giving the received signal strength and the
+//! channel, followed by the 802.11 header. Those frames carry no usable IP traffic, so
+//! instead of communications they are tallied per transmitting MAC in `wireless_stations`.
+//! Clients show up there from their probe requests and association traffic, before they
+//! ever get an address.
+
+use pnet::util::MacAddr;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+
+use crate::db::insert_notification;
+use crate::network::endpoint::{get_mac_vendor, is_locally_administered_mac};
+
+/// pcap link type of frames with a radiotap header (DLT_IEEE802_11_RADIO)
+pub const LINKTYPE_IEEE802_11_RADIOTAP: i32 = 127;
+
+/// Linux ARPHRD type of monitor-mode interfaces that deliver radiotap headers
+#[cfg(target_os = "linux")]
+const ARPHRD_IEEE80211_RADIOTAP: &str = "803";
+
+/// Radiotap fields up to the antenna signal, in bit order: (bit, alignment, size)
+const RADIOTAP_FIELDS: [(u32, usize, usize); 6] = [
+    (0, 8, 8), // TSFT
+    (1, 1, 1), // Flags
+    (2, 1, 1), // Rate
+    (3, 2, 4), // Channel: frequency and flags
+    (4, 1, 2), // FHSS
+    (5, 1, 1), // Antenna signal, dBm
+];
+
+const RADIOTAP_CHANNEL: u32 = 3;
+const RADIOTAP_ANTENNA_SIGNAL: u32 = 5;
+
+/// Whether live capture on this interface delivers radiotap frames
+#[cfg(target_os = "linux")]
+pub fn is_radiotap_interface(name: &str) -> bool {
+    std::fs::read_to_string(format!("/sys/class/net/{}/type", name))
+        .is_ok_and(|kind| kind.trim() == ARPHRD_IEEE80211_RADIOTAP)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_radiotap_interface(_name: &str) -> bool {
+    false
+}
+
+/// What a transmitting station is, as far as its frames tell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StationRole {
+    Client,
+    AccessPoint,
+    #[default]
+    Unknown,
+}
+
+impl StationRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StationRole::Client => "client",
+            StationRole::AccessPoint => "access_point",
+            StationRole::Unknown => "unknown",
+        }
+    }
+}
+
+/// Radio details of one frame, or of the frames of one transmitter in a flow
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RadioSignal {
+    pub role: StationRole,
+    /// Access point the station talks to (its own BSSID for an access point)
+    pub bssid: Option<String>,
+    pub channel_mhz: Option<u16>,
+    /// Received signal strengths, in dBm
+    pub rssi_samples: Vec<i8>,
+}
+
+impl RadioSignal {
+    /// Fold in the signal of later frames: samples are added and the latest details win
+    pub fn absorb(&mut self, other: RadioSignal) {
+        if other.role != StationRole::Unknown {
+            self.role = other.role;
+        }
+        if other.bssid.is_some() {
+            self.bssid = other.bssid;
+        }
+        if other.channel_mhz.is_some() {
+            self.channel_mhz = other.channel_mhz;
+        }
+        self.rssi_samples.extend(other.rssi_samples);
+    }
+}
+
+/// A parsed 802.11 frame with its radiotap metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadioFrame {
+    pub transmitter: String,
+    pub receiver: String,
+    pub signal: RadioSignal,
+}
+
+/// Signal strength and channel from a radiotap header, plus the header length
+fn parse_radiotap_header(frame: &[u8]) -> Option<(usize, Option<i8>, Option<u16>)> {
+    if frame.len() < 8 || frame[0] != 0 {
+        return None;
+    }
+    let length = u16::from_le_bytes([frame[2], frame[3]]) as usize;
+    if length < 8 || length > frame.len() {
+        return None;
+    }
+    let word = |offset: usize| {
+        u32::from_le_bytes([
+            frame[offset],
+            frame[offset + 1],
+            frame[offset + 2],
+            frame[offset + 3],
+        ])
+    };
+    let present = word(4);
+
+    // Extended presence bitmaps follow while bit 31 is set; the fields come after them
+    let mut offset = 8;
+    let mut bitmap = present;
+    while bitmap & (1 << 31) != 0 {
+        if offset + 4 > length {
+            return None;
+        }
+        bitmap = word(offset);
+        offset += 4;
+    }
+
+    let mut signal = None;
+    let mut channel = None;
+    for (bit, align, size) in RADIOTAP_FIELDS {
+        if present & (1 << bit) == 0 {
+            continue;
+        }
+        // Fields are aligned to their natural size from the start of the header
+        offset = offset.next_multiple_of(align);
+        if offset + size > length {
+            return None;
+        }
+        match bit {
+            RADIOTAP_CHANNEL => {
+                channel = Some(u16::from_le_bytes([frame[offset], frame[offset + 1]]))
+            }
+            RADIOTAP_ANTENNA_SIGNAL => signal = Some(frame[offset] as i8),
+            _ => {}
+        }
+        offset += size;
+    }
+    Some((length, signal, channel.filter(|&mhz| mhz > 0)))
+}
+
+/// The 802.11 frame behind a radiotap header
+pub fn ieee80211_frame(frame: &[u8]) -> Option<&[u8]> {
+    let (length, _, _) = parse_radiotap_header(frame)?;
+    frame.get(length..)
+}
+
+/// Parse a radiotap frame. Control frames (ACK, CTS, ...) don't name their sender and
+/// are skipped.
+pub fn parse_radiotap_frame(frame: &[u8]) -> Option<RadioFrame> {
+    let (length, rssi, channel_mhz) = parse_radiotap_header(frame)?;
+    let header = &frame[length..];
+    if header.len() < 22 {
+        return None;
+    }
+    let frame_type = (header[0] >> 2) & 0x3;
+    let subtype = header[0] >> 4;
+    let to_ds = header[1] & 0x1 != 0;
+    let from_ds = header[1] & 0x2 != 0;
+
+    let address = |offset: usize| {
+        MacAddr::new(
+            header[offset],
+            header[offset + 1],
+            header[offset + 2],
+            header[offset + 3],
+            header[offset + 4],
+            header[offset + 5],
+        )
+    };
+    let (receiver, transmitter, address3) = (address(4), address(10), address(16));
+    if transmitter.is_multicast() || transmitter.is_zero() {
+        return None;
+    }
+
+    let (role, bssid) = match (frame_type, subtype) {
+        // Association and reassociation requests, probe requests
+        (0, 0 | 2 | 4) => (StationRole::Client, address3),
+        // Association and reassociation responses, probe responses, beacons
+        (0, 1 | 3 | 5 | 8) => (StationRole::AccessPoint, transmitter),
+        (0, _) => (StationRole::Unknown, address3),
+        (2, _) if to_ds && !from_ds => (StationRole::Client, receiver),
+        (2, _) if from_ds && !to_ds => (StationRole::AccessPoint, transmitter),
+        (2, _) => (StationRole::Unknown, address3),
+        _ => return None,
+    };
+
+    Some(RadioFrame {
+        transmitter: transmitter.to_string(),
+        receiver: receiver.to_string(),
+        signal: RadioSignal {
+            role,
+            // Probe requests go to the broadcast BSSID
+            bssid: (!bssid.is_broadcast()).then(|| bssid.to_string()),
+            channel_mhz,
+            rssi_samples: rssi.into_iter().collect(),
+        },
+    })
+}
+
+pub fn create_wireless_stations_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS wireless_stations (
+            mac TEXT PRIMARY KEY,
+            role TEXT NOT NULL,
+            bssid TEXT,
+            channel_mhz INTEGER,
+            rssi_dbm INTEGER,
+            rssi_min INTEGER,
+            rssi_max INTEGER,
+            rssi_total INTEGER NOT NULL DEFAULT 0,
+            rssi_samples INTEGER NOT NULL DEFAULT 0,
+            frames INTEGER NOT NULL DEFAULT 0,
+            interface TEXT,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_wireless_stations_last_seen ON wireless_stations (last_seen_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Add the frames of one transmitter to its station row. A client seen for the first
+/// time talking to an access point, under a fixed MAC no endpoint is known by, raises
+/// a notification. Returns whether the station is new.
+pub fn record_wireless_signal(
+    conn: &Connection,
+    mac: &str,
+    signal: &RadioSignal,
+    frames: u32,
+    interface: Option<&str>,
+    now: i64,
+) -> Result<bool> {
+    let mac = mac.to_lowercase();
+    let is_new = conn
+        .query_row(
+            "SELECT 1 FROM wireless_stations WHERE mac = ?1",
+            [&mac],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .is_none();
+
+    let samples = &signal.rssi_samples;
+    let total: i64 = samples.iter().map(|&rssi| i64::from(rssi)).sum();
+    // SQLite's MIN()/MAX() with several arguments are NULL if any argument is
+    conn.execute(
+        "INSERT INTO wireless_stations (
+            mac, role, bssid, channel_mhz, rssi_dbm, rssi_min, rssi_max, rssi_total,
+            rssi_samples, frames, interface, first_seen_at, last_seen_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)
+        ON CONFLICT(mac) DO UPDATE SET
+            role = CASE WHEN excluded.role = 'unknown' THEN role ELSE excluded.role END,
+            bssid = COALESCE(excluded.bssid, bssid),
+            channel_mhz = COALESCE(excluded.channel_mhz, channel_mhz),
+            rssi_dbm = COALESCE(excluded.rssi_dbm, rssi_dbm),
+            rssi_min = MIN(COALESCE(rssi_min, excluded.rssi_min), COALESCE(excluded.rssi_min, rssi_min)),
+            rssi_max = MAX(COALESCE(rssi_max, excluded.rssi_max), COALESCE(excluded.rssi_max, rssi_max)),
+            rssi_total = rssi_total + excluded.rssi_total,
+            rssi_samples = rssi_samples + excluded.rssi_samples,
+            frames = frames + excluded.frames,
+            interface = COALESCE(excluded.interface, interface),
+            last_seen_at = excluded.last_seen_at",
+        params![
+            mac,
+            signal.role.as_str(),
+            signal.bssid,
+            signal.channel_mhz,
+            samples.last(),
+            samples.iter().min(),
+            samples.iter().max(),
+            total,
+            samples.len() as i64,
+            frames.max(1),
+            interface,
+            now,
+        ],
+    )?;
+
+    if is_new
+        && signal.role == StationRole::Client
+        && signal.bssid.is_some()
+        && !is_locally_administered_mac(&mac)
+        && !is_known_mac(conn, &mac)
+    {
+        let vendor = get_mac_vendor(&mac).unwrap_or("unknown vendor");
+        let details = match samples.last() {
+            Some(rssi) => format!("{} - {} dBm", vendor, rssi),
+            None => vendor.to_string(),
+        };
+        insert_notification(
+            conn,
+            "wireless_client_discovered",
+            &format!("Unknown Wi-Fi client: {}", mac),
+            Some(&details),
+            None,
+        );
+    }
+    Ok(is_new)
+}
+
+/// Whether an endpoint is known by this MAC
+fn is_known_mac(conn: &Connection, mac: &str) -> bool {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM endpoint_attributes WHERE LOWER(mac) = ?1)",
+        [mac],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
+
+/// A transmitting station and the signal it was heard with
+#[derive(Debug, Clone, Serialize)]
+pub struct WirelessStation {
+    pub mac: String,
+    pub role: String,
+    pub bssid: Option<String>,
+    pub channel_mhz: Option<u16>,
+    /// Latest signal strength, in dBm
+    pub rssi_dbm: Option<i64>,
+    pub rssi_avg: Option<f64>,
+    pub rssi_min: Option<i64>,
+    pub rssi_max: Option<i64>,
+    pub frames: i64,
+    pub interface: Option<String>,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+    /// Endpoint known by this MAC, if any
+    pub endpoint: Option<String>,
+    pub vendor: Option<String>,
+}
+
+/// Stations heard at or after `since`, strongest signal first
+pub fn get_wireless_stations(
+    conn: &Connection,
+    since: i64,
+    role: Option<&str>,
+    limit: i64,
+) -> Result<Vec<WirelessStation>> {
+    let mut stmt = conn.prepare(
+        "SELECT w.mac, w.role, w.bssid, w.channel_mhz, w.rssi_dbm,
+                CAST(w.rssi_total AS REAL) / NULLIF(w.rssi_samples, 0) AS rssi_avg,
+                w.rssi_min, w.rssi_max, w.frames, w.interface, w.first_seen_at, w.last_seen_at,
+                (SELECT e.display_name FROM endpoint_attributes a
+                 JOIN endpoints e ON e.id = a.endpoint_id
+                 WHERE LOWER(a.mac) = w.mac
+                 ORDER BY a.created_at DESC LIMIT 1)
+         FROM wireless_stations w
+         WHERE w.last_seen_at >= ?1 AND (?2 IS NULL OR w.role = ?2)
+         ORDER BY rssi_avg IS NULL, rssi_avg DESC, w.frames DESC
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![since, role, limit], |row| {
+        let mac: String = row.get(0)?;
+        Ok(WirelessStation {
+            vendor: get_mac_vendor(&mac).map(str::to_string),
+            mac,
+            role: row.get(1)?,
+            bssid: row.get(2)?,
+            channel_mhz: row.get(3)?,
+            rssi_dbm: row.get(4)?,
+            rssi_avg: row.get(5)?,
+            rssi_min: row.get(6)?,
+            rssi_max: row.get(7)?,
+            frames: row.get(8)?,
+            interface: row.get(9)?,
+            first_seen_at: row.get(10)?,
+            last_seen_at: row.get(11)?,
+            endpoint: row.get(12)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+
+    /// A radiotap header with flags, channel and antenna signal, then an 802.11 header
+    fn radiotap_frame(frame_control: [u8; 2], addresses: [[u8; 6]; 3], rssi: i8) -> Vec<u8> {
+        let mut frame = vec![0, 0, 15, 0];
+        frame.extend_from_slice(&0b10_1010u32.to_le_bytes()); // flags, channel, signal
+        frame.push(0x10); // flags
+        frame.push(0); // padding to align the channel
+        frame.extend_from_slice(&2437u16.to_le_bytes());
+        frame.extend_from_slice(&0x00a0u16.to_le_bytes());
+        frame.push(rssi as u8);
+        frame.extend_from_slice(&frame_control);
+        frame.extend_from_slice(&[0, 0]); // duration
+        for address in addresses {
+            frame.extend_from_slice(&address);
+        }
+        frame.extend_from_slice(&[0, 0]); // sequence control
+        frame
+    }
+
+    const CLIENT: [u8; 6] = [0x00, 0x1b, 0x63, 0x00, 0x00, 0x01];
+    const AP: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const BROADCAST: [u8; 6] = [0xff; 6];
+
+    #[test]
+    fn test_parse_radiotap_frame() {
+        // Probe request: from a client, to the broadcast BSSID
+        let probe = parse_radiotap_frame(&radiotap_frame(
+            [0x40, 0],
+            [BROADCAST, CLIENT, BROADCAST],
+            -61,
+        ))
+        .unwrap();
+        assert_eq!(probe.transmitter, "00:1b:63:00:00:01");
+        assert_eq!(probe.signal.role, StationRole::Client);
+        assert_eq!(probe.signal.bssid, None);
+        assert_eq!(probe.signal.channel_mhz, Some(2437));
+        assert_eq!(probe.signal.rssi_samples, vec![-61]);
+
+        // Data to the distribution system: the receiver is the access point
+        let data =
+            parse_radiotap_frame(&radiotap_frame([0x08, 0x01], [AP, CLIENT, AP], -55)).unwrap();
+        assert_eq!(data.signal.role, StationRole::Client);
+        assert_eq!(data.signal.bssid.as_deref(), Some("00:11:22:33:44:55"));
+
+        let beacon =
+            parse_radiotap_frame(&radiotap_frame([0x80, 0], [BROADCAST, AP, AP], -40)).unwrap();
+        assert_eq!(beacon.signal.role, StationRole::AccessPoint);
+
+        // ACKs don't name their sender
+        let mut ack = radiotap_frame([0xd4, 0], [CLIENT, [0; 6], [0; 6]], -50);
+        assert!(parse_radiotap_frame(&ack).is_none());
+        ack.truncate(10);
+        assert!(parse_radiotap_frame(&ack).is_none());
+    }
+
+    #[test]
+    fn test_record_wireless_signal() {
+        let conn = new_test_connection();
+        let client = "00:1b:63:00:00:01";
+        let mut signal = RadioSignal {
+            role: StationRole::Client,
+            bssid: Some("00:11:22:33:44:55".to_string()),
+            channel_mhz: Some(5180),
+            rssi_samples: vec![-60, -70],
+        };
+        assert!(record_wireless_signal(&conn, client, &signal, 2, Some("wlan0mon"), 100).unwrap());
+        let notifications: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM notifications WHERE event_type = 'wireless_client_discovered'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(notifications, 1);
+
+        signal.role = StationRole::Unknown;
+        signal.rssi_samples = vec![-50];
+        assert!(!record_wireless_signal(&conn, client, &signal, 1, None, 200).unwrap());
+
+        let stations = get_wireless_stations(&conn, 0, Some("client"), 10).unwrap();
+        assert_eq!(stations.len(), 1);
+        let station = &stations[0];
+        assert_eq!(station.frames, 3);
+        assert_eq!(station.rssi_dbm, Some(-50));
+        assert_eq!(station.rssi_min, Some(-70));
+        assert_eq!(station.rssi_max, Some(-50));
+        assert_eq!(station.rssi_avg, Some(-60.0));
+        assert_eq!(station.interface.as_deref(), Some("wlan0mon"));
+        assert_eq!(station.last_seen_at, 200);
+        assert!(
+            get_wireless_stations(&conn, 300, None, 10)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,12 +1,20 @@
 //! PCAP file processing. Reads captured packet files, extracts Ethernet frames,
 //! and sends them to the database writer with progress tracking.
 
-use pcap::Capture;
+use pcap::{Capture, PacketHeader};
 use pnet::packet::ethernet::EthernetPacket;
 use std::io::{self, Write};
 use std::path::Path;
 
 use crate::network::communication::Communication;
+use crate::network::tcp_quality::TcpQualityTracker;
+use crate::network::wireless::LINKTYPE_IEEE802_11_RADIOTAP;
+
+/// When a packet was captured, in microseconds. The timeval field types vary by platform.
+#[allow(clippy::unnecessary_cast)]
+fn capture_micros(header: &PacketHeader) -> i64 {
+    header.ts.tv_sec as i64 * 1_000_000 + header.ts.tv_usec as i64
+}
 
 /// Process a PCAP file and send packets to the database writer.
 ///
@@ -29,22 +37,35 @@ pub fn process_pcap_file(
     let mut cap = Capture::from_file(file_path)
         .map_err(|e| io::Error::other(format!("Failed to open pcap file {}: {}", file_path, e)))?;
 
+    // Captures from a monitor-mode interface hold 802.11 frames behind a radiotap header
+    let radiotap = cap.get_datalink().0 == LINKTYPE_IEEE802_11_RADIOTAP;
+
     let mut packet_count = 0;
+    let mut tcp_quality = TcpQualityTracker::default();
     while let Ok(packet) = cap.next_packet() {
-        if let Some(ethernet_packet) = EthernetPacket::new(packet.data) {
-            let communication =
-                Communication::new_with_source(ethernet_packet, Some(source_label.clone()));
-
-            if sender.blocking_send(communication).is_err() {
-                eprintln!("Warning: Failed to send packet to database writer");
-                break;
-            }
-            packet_count += 1;
-
-            if packet_count % 1000 == 0 {
-                print!("\rProcessed {} packets...", packet_count);
-                io::stdout().flush().ok();
-            }
+        let communication = if radiotap {
+            Communication::new_radiotap(packet.data, Some(source_label.clone()))
+        } else {
+            EthernetPacket::new(packet.data).map(|ethernet_packet| {
+                // Handshakes are timed from the capture timestamps, not from when we read them
+                let quality = tcp_quality.observe(&ethernet_packet, capture_micros(packet.header));
+                Communication::new_with_source(ethernet_packet, Some(source_label.clone()))
+                    .with_tcp_quality(quality)
+            })
+        };
+        let Some(communication) = communication else {
+            continue;
+        };
+
+        if sender.blocking_send(communication).is_err() {
+            eprintln!("Warning: Failed to send packet to database writer");
+            break;
+        }
+        packet_count += 1;
+
+        if packet_count % 1000 == 0 {
+            print!("\rProcessed {} packets...", packet_count);
+            io::stdout().flush().ok();
         }
     }
 
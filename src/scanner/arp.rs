@@ -1,24 +1,42 @@
 //! ARP scanner. Discovers devices on local subnets by constructing and sending
 //! ARP request packets and collecting MAC/IP address responses.
 
+use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use futures::future::BoxFuture;
 use ipnetwork::Ipv4Network;
 use pnet::datalink::{self, Channel, NetworkInterface};
 use pnet::packet::Packet;
 use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
 use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
 use pnet::util::MacAddr;
-use tokio::sync::mpsc;
-use tokio::time::timeout;
 
-use super::ArpResult;
+use super::manager::ScanRun;
+use super::registry::{ConfigField, Scanner, Tunables};
+use super::{ArpResult, Privileges, ScanProgress, ScanResult, ScanType};
+
+/// Default number of ARP requests sent back-to-back before pausing
+pub const DEFAULT_ARP_PARALLELISM: usize = 64;
+
+/// Default number of extra rounds for hosts that did not answer
+pub const DEFAULT_ARP_RETRIES: u8 = 2;
+
+/// Lower bound for the adaptive wait after each round
+const MIN_ROUND_WAIT_MS: u64 = 50;
 
 /// ARP scanner for local subnet device discovery
 pub struct ArpScanner {
     timeout_ms: u64,
+    /// Pause between batches
     delay_ms: u64,
+    /// Requests sent per batch
+    parallelism: usize,
+    /// Retry rounds for non-responders
+    retries: u8,
 }
 
 impl ArpScanner {
@@ -26,6 +44,8 @@ impl ArpScanner {
         Self {
             timeout_ms: 1000,
             delay_ms: 10,
+            parallelism: DEFAULT_ARP_PARALLELISM,
+            retries: DEFAULT_ARP_RETRIES,
         }
     }
 
@@ -34,6 +54,41 @@ impl ArpScanner {
         self
     }
 
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u8) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Host addresses in a subnet (excluding network and broadcast addresses)
+    fn host_addresses(network: &Ipv4Network) -> impl Iterator<Item = Ipv4Addr> + '_ {
+        network
+            .iter()
+            .filter(move |ip| *ip != network.network() && *ip != network.broadcast())
+    }
+
+    /// Progress units a scan of `network` will report: one per host per round
+    pub fn planned_units(&self, network: &Ipv4Network) -> u64 {
+        let hosts = (network.size() as u64).saturating_sub(2);
+        hosts * (self.retries as u64 + 1)
+    }
+
+    /// How long to wait for stragglers after a round, based on observed reply latency.
+    /// With no replies yet we wait the full timeout.
+    fn adaptive_wait(&self, max_rtt: Option<Duration>) -> Duration {
+        let timeout = Duration::from_millis(self.timeout_ms);
+        match max_rtt {
+            Some(rtt) => (rtt * 3)
+                .max(Duration::from_millis(MIN_ROUND_WAIT_MS))
+                .min(timeout),
+            None => timeout,
+        }
+    }
+
     /// Get the best interface for scanning a target network
     fn find_interface_for_network(&self, network: &Ipv4Network) -> Option<NetworkInterface> {
         datalink::interfaces()
@@ -90,8 +145,30 @@ impl ArpScanner {
         Some(ethernet_buffer)
     }
 
-    /// Scan a subnet for devices using ARP
-    pub async fn scan_subnet(&self, network: Ipv4Network) -> Vec<ArpResult> {
+    /// Scan a subnet, advancing `progress` by `planned_units(network)` in total.
+    /// Requests go out in batches of `parallelism`; hosts that stay silent are retried
+    /// up to `retries` times, waiting after each round in proportion to observed latency.
+    pub async fn scan_subnet_with_progress(
+        &self,
+        network: Ipv4Network,
+        progress: &ScanProgress,
+    ) -> Vec<ArpResult> {
+        let planned = self.planned_units(&network);
+        let mut reported = 0u64;
+
+        let results = self.sweep(network, progress, &mut reported).await;
+
+        // Account for skipped hosts (our own IP) and retry rounds that were not needed
+        progress.advance(planned.saturating_sub(reported));
+        results
+    }
+
+    async fn sweep(
+        &self,
+        network: Ipv4Network,
+        progress: &ScanProgress,
+        reported: &mut u64,
+    ) -> Vec<ArpResult> {
         let Some(interface) = self.find_interface_for_network(&network) else {
             eprintln!("No interface found for network {}", network);
             return Vec::new();
@@ -102,81 +179,128 @@ impl ArpScanner {
             return Vec::new();
         };
 
-        // Create channel
-        let Ok(Channel::Ethernet(mut tx, mut rx)) =
-            datalink::channel(&interface, Default::default())
+        // Short read timeout so the receiver notices when sending has finished
+        let channel_config = datalink::Config {
+            read_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let Ok(Channel::Ethernet(mut tx, mut rx)) = datalink::channel(&interface, channel_config)
         else {
             eprintln!("Failed to create datalink channel");
             return Vec::new();
         };
 
-        let (result_tx, mut result_rx) = mpsc::channel::<ArpResult>(256);
-        let timeout_ms = self.timeout_ms;
-
-        // Spawn receiver task
-        let receiver_handle = tokio::task::spawn_blocking(move || {
-            let start = Instant::now();
-            let timeout_duration = Duration::from_millis(timeout_ms);
-
-            while start.elapsed() < timeout_duration {
-                match rx.next() {
-                    Ok(packet) => {
-                        if let Some(ethernet) = EthernetPacket::new(packet)
-                            && ethernet.get_ethertype() == EtherTypes::Arp
-                            && let Some(arp) = ArpPacket::new(ethernet.payload())
-                            && arp.get_operation() == ArpOperations::Reply
-                        {
-                            let ip = IpAddr::V4(arp.get_sender_proto_addr());
-                            let mac = arp.get_sender_hw_addr();
-                            let response_time_ms =
-                                u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
-
-                            let _ = result_tx.blocking_send(ArpResult {
-                                ip,
-                                mac,
-                                response_time_ms,
-                            });
-                        }
+        let targets: Vec<Ipv4Addr> = Self::host_addresses(&network)
+            .filter(|ip| *ip != src_ip)
+            .collect();
+        if targets.is_empty() {
+            return Vec::new();
+        }
+
+        // Send times per target, so replies get a true round-trip time
+        let sent_at: Arc<Mutex<HashMap<Ipv4Addr, Instant>>> = Arc::new(Mutex::new(HashMap::new()));
+        let responses: Arc<Mutex<HashMap<Ipv4Addr, ArpResult>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let max_rtt: Arc<Mutex<Option<Duration>>> = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let receiver_handle = {
+            let sent_at = sent_at.clone();
+            let responses = responses.clone();
+            let max_rtt = max_rtt.clone();
+            let stop = stop.clone();
+            tokio::task::spawn_blocking(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let Ok(packet) = rx.next() else {
+                        continue; // read timeout
+                    };
+                    let Some(ethernet) = EthernetPacket::new(packet) else {
+                        continue;
+                    };
+                    if ethernet.get_ethertype() != EtherTypes::Arp {
+                        continue;
+                    }
+                    let Some(arp) = ArpPacket::new(ethernet.payload()) else {
+                        continue;
+                    };
+                    if arp.get_operation() != ArpOperations::Reply {
+                        continue;
+                    }
+
+                    let ip = arp.get_sender_proto_addr();
+                    let Some(sent) = sent_at.lock().ok().and_then(|s| s.get(&ip).copied()) else {
+                        continue; // Not one of our targets
+                    };
+                    let rtt = sent.elapsed();
+                    if let Ok(mut max) = max_rtt.lock() {
+                        *max = Some(max.map_or(rtt, |m| m.max(rtt)));
+                    }
+                    if let Ok(mut responses) = responses.lock() {
+                        responses.entry(ip).or_insert(ArpResult {
+                            ip: IpAddr::V4(ip),
+                            mac: arp.get_sender_hw_addr(),
+                            response_time_ms: u64::try_from(rtt.as_millis()).unwrap_or(u64::MAX),
+                        });
+                    }
+                }
+            })
+        };
+
+        let total_targets = targets.len() as u64;
+        let mut pending = targets;
+        let mut delay = Duration::from_millis(self.delay_ms);
+
+        for round in 0..=self.retries {
+            let round_len = pending.len() as u64;
+            let mut sent = 0u64;
+            let mut round_reported = 0u64;
+
+            for batch in pending.chunks(self.parallelism) {
+                let now = Instant::now();
+                if let Ok(mut sent_at) = sent_at.lock() {
+                    for ip in batch {
+                        sent_at.insert(*ip, now);
                     }
-                    Err(_) => {
-                        std::thread::sleep(Duration::from_millis(1));
+                }
+                for ip in batch {
+                    if let Some(packet) = Self::build_arp_request(src_mac, src_ip, *ip) {
+                        let _ = tx.send_to(&packet, None);
                     }
                 }
+
+                // Each round is worth one unit per target, spread over the packets it sends
+                sent += batch.len() as u64;
+                let round_units = sent * total_targets / round_len;
+                progress.advance(round_units - round_reported);
+                *reported += round_units - round_reported;
+                round_reported = round_units;
+
+                tokio::time::sleep(delay).await;
             }
-        });
-
-        // Send ARP requests
-        let delay = Duration::from_millis(self.delay_ms);
-        for ip in network.iter() {
-            // Skip network and broadcast addresses
-            if ip == network.network() || ip == network.broadcast() {
-                continue;
+
+            let observed = max_rtt.lock().ok().and_then(|m| *m);
+            tokio::time::sleep(self.adaptive_wait(observed)).await;
+
+            if let Ok(responses) = responses.lock() {
+                pending.retain(|ip| !responses.contains_key(ip));
             }
-            // Skip our own IP
-            if ip == src_ip {
-                continue;
+            if pending.is_empty() {
+                break;
             }
-
-            if let Some(packet) = Self::build_arp_request(src_mac, src_ip, ip) {
-                let _ = tx.send_to(&packet, None);
+            if round < self.retries {
+                // Back off between batches; silent hosts may be a sign of dropped frames
+                delay *= 2;
             }
-
-            tokio::time::sleep(delay).await;
         }
 
-        // Wait for receiver to finish
-        let _ = timeout(Duration::from_millis(self.timeout_ms), receiver_handle).await;
-
-        // Collect results
-        let mut results = Vec::new();
-        while let Ok(result) = result_rx.try_recv() {
-            results.push(result);
-        }
+        stop.store(true, Ordering::Relaxed);
+        let _ = receiver_handle.await;
 
-        // Deduplicate
+        let mut results: Vec<ArpResult> = responses
+            .lock()
+            .map(|r| r.values().cloned().collect())
+            .unwrap_or_default();
         results.sort_by(|a, b| a.ip.cmp(&b.ip));
-        results.dedup_by(|a, b| a.ip == b.ip);
-
         results
     }
 }
@@ -186,3 +310,127 @@ impl Default for ArpScanner {
         Self::new()
     }
 }
+
+/// The ARP sweep of the local subnets as a scan type
+pub struct ArpScan;
+
+impl Scanner for ArpScan {
+    fn scan_type(&self) -> ScanType {
+        ScanType::ARP
+    }
+
+    fn label(&self) -> &'static str {
+        "ARP"
+    }
+
+    fn description(&self) -> &'static str {
+        "Find devices by MAC"
+    }
+
+    fn is_available(&self, privileges: &Privileges) -> bool {
+        privileges.raw_socket
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![
+            ConfigField {
+                key: "arp_parallelism",
+                label: "Parallelism",
+                kind: "integer",
+                description: "ARP requests sent per batch",
+            },
+            ConfigField {
+                key: "arp_retries",
+                label: "Retries",
+                kind: "integer",
+                description: "Retry rounds for hosts that did not answer",
+            },
+        ]
+    }
+
+    fn tunables(&self) -> Tunables {
+        Tunables {
+            retries: true,
+            parallelism: true,
+        }
+    }
+
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let (cfg, tuning) = (run.config(), run.tuning());
+            let scanner = ArpScanner::new()
+                .with_timeout(run.timeout_ms())
+                .with_parallelism(tuning.parallelism.unwrap_or(cfg.arp_parallelism))
+                .with_retries(tuning.retries.unwrap_or(cfg.arp_retries));
+            let mut hosts = 0;
+            for subnet in run.subnets() {
+                run.progress().add_total(scanner.planned_units(subnet));
+                hosts += (subnet.size() as u64).saturating_sub(2);
+            }
+            run.set_targets(hosts);
+
+            for subnet in run.subnets() {
+                run.acquire((subnet.size() as u64).saturating_sub(2)).await;
+                let results = scanner
+                    .scan_subnet_with_progress(*subnet, run.progress())
+                    .await;
+                run.report(results.into_iter().map(ScanResult::Arp).collect())
+                    .await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planned_units_includes_retry_rounds() {
+        let scanner = ArpScanner::new().with_retries(2);
+        let network: Ipv4Network = "192.168.1.0/24".parse().unwrap();
+        assert_eq!(scanner.planned_units(&network), 254 * 3);
+
+        let scanner = ArpScanner::new().with_retries(0);
+        let network: Ipv4Network = "10.0.0.0/16".parse().unwrap();
+        assert_eq!(scanner.planned_units(&network), 65534);
+    }
+
+    #[test]
+    fn test_host_addresses_skip_network_and_broadcast() {
+        let network: Ipv4Network = "192.168.1.0/30".parse().unwrap();
+        let hosts: Vec<Ipv4Addr> = ArpScanner::host_addresses(&network).collect();
+        assert_eq!(
+            hosts,
+            vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_adaptive_wait_bounds() {
+        let scanner = ArpScanner::new().with_timeout(1000);
+        assert_eq!(scanner.adaptive_wait(None), Duration::from_millis(1000));
+        assert_eq!(
+            scanner.adaptive_wait(Some(Duration::from_millis(5))),
+            Duration::from_millis(MIN_ROUND_WAIT_MS)
+        );
+        assert_eq!(
+            scanner.adaptive_wait(Some(Duration::from_millis(100))),
+            Duration::from_millis(300)
+        );
+        assert_eq!(
+            scanner.adaptive_wait(Some(Duration::from_millis(900))),
+            Duration::from_millis(1000)
+        );
+    }
+
+    #[test]
+    fn test_parallelism_minimum() {
+        let scanner = ArpScanner::new().with_parallelism(0);
+        assert_eq!(scanner.parallelism, 1);
+    }
+}
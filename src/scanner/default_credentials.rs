@@ -0,0 +1,390 @@
+//! Opt-in default credentials check. Routers, printers and cameras with an open admin
+//! port are tried against a short list of factory logins over HTTP basic auth and
+//! telnet. Only the fact that a login was accepted is reported (with the list entry's
+//! label); the credentials are never stored, and nothing is done with an accepted login
+//! beyond closing the connection.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use serde::Serialize;
+
+/// Admin ports tried: telnet and plain HTTP web interfaces
+pub const CREDENTIAL_CHECK_PORTS: &[u16] = &[23, 80, 8080];
+
+const TELNET_PORT: u16 = 23;
+
+/// Device types checked; cameras have no device type of their own, so go by vendor
+const CHECKED_DEVICE_TYPES: &[&str] = &["gateway", "printer"];
+const CAMERA_VENDORS: &[&str] = &["hikvision", "dahua", "axis", "reolink", "amcrest"];
+
+/// Most logins tried per port, so a device's lockout policy isn't tripped
+const MAX_ATTEMPTS_PER_PORT: usize = 5;
+
+const MAX_RESPONSE_BYTES: usize = 16 * 1024;
+
+const TELNET_IAC: u8 = 255;
+const TELNET_DONT: u8 = 254;
+const TELNET_DO: u8 = 253;
+const TELNET_WONT: u8 = 252;
+const TELNET_WILL: u8 = 251;
+const TELNET_SB: u8 = 250;
+const TELNET_SE: u8 = 240;
+
+/// A factory login; `vendors` empty means it is tried on any device
+struct DefaultCredential {
+    label: &'static str,
+    vendors: &'static [&'static str],
+    username: &'static str,
+    password: &'static str,
+}
+
+const fn credential(
+    label: &'static str,
+    vendors: &'static [&'static str],
+    username: &'static str,
+    password: &'static str,
+) -> DefaultCredential {
+    DefaultCredential {
+        label,
+        vendors,
+        username,
+        password,
+    }
+}
+
+/// Vendor-specific logins come first, then the ones many vendors ship with
+const DEFAULT_CREDENTIALS: &[DefaultCredential] = &[
+    credential("Hikvision factory login", &["hikvision"], "admin", "12345"),
+    credential("Dahua factory login", &["dahua"], "admin", "888888"),
+    credential("Axis factory login", &["axis"], "root", "pass"),
+    credential("Ubiquiti factory login", &["ubiquiti"], "ubnt", "ubnt"),
+    credential("Zyxel factory login", &["zyxel"], "admin", "1234"),
+    credential("Brother factory login", &["brother"], "admin", "access"),
+    credential("Xerox factory login", &["xerox"], "admin", "1111"),
+    credential("Common factory login", &[], "admin", "admin"),
+    credential("Common factory login", &[], "admin", "password"),
+    credential("Common factory login (no password)", &[], "admin", ""),
+];
+
+/// A port that accepted a factory login
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DefaultCredentialFinding {
+    pub port: u16,
+    /// "HTTP basic auth" or "telnet"
+    pub protocol: &'static str,
+    /// Which list entry was accepted, e.g. "Hikvision factory login"
+    pub label: &'static str,
+}
+
+/// Whether a device is a router, printer or camera, the kinds that ship with factory
+/// logins on their admin ports
+pub fn is_credential_check_target(device_type: Option<&str>, vendor: Option<&str>) -> bool {
+    let vendor = vendor.unwrap_or_default().to_lowercase();
+    device_type.is_some_and(|t| CHECKED_DEVICE_TYPES.contains(&t))
+        || CAMERA_VENDORS.iter().any(|v| vendor.contains(v))
+}
+
+/// Logins to try on a device from `vendor`, vendor-specific first
+fn candidates(vendor: Option<&str>) -> impl Iterator<Item = &'static DefaultCredential> {
+    let vendor = vendor.unwrap_or_default().to_lowercase();
+    let (specific, common): (Vec<_>, Vec<_>) = DEFAULT_CREDENTIALS
+        .iter()
+        .filter(move |c| c.vendors.is_empty() || c.vendors.iter().any(|v| vendor.contains(v)))
+        .partition(|c| !c.vendors.is_empty());
+    specific
+        .into_iter()
+        .chain(common)
+        .take(MAX_ATTEMPTS_PER_PORT)
+}
+
+/// Tries factory logins on device admin ports
+pub struct DefaultCredentialChecker {
+    timeout_ms: u64,
+}
+
+impl DefaultCredentialChecker {
+    pub fn new() -> Self {
+        Self { timeout_ms: 3000 }
+    }
+
+    /// Ports among `ports` that accept a factory login, trying entries for `vendor`
+    pub async fn check(
+        &self,
+        ip: IpAddr,
+        ports: &[u16],
+        vendor: Option<String>,
+    ) -> Vec<DefaultCredentialFinding> {
+        let timeout = Duration::from_millis(self.timeout_ms);
+        let ports = ports.to_vec();
+        tokio::task::spawn_blocking(move || {
+            ports
+                .iter()
+                .filter(|port| CREDENTIAL_CHECK_PORTS.contains(port))
+                .filter_map(|&port| {
+                    check_port(SocketAddr::new(ip, port), timeout, vendor.as_deref())
+                })
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+impl Default for DefaultCredentialChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_port(
+    addr: SocketAddr,
+    timeout: Duration,
+    vendor: Option<&str>,
+) -> Option<DefaultCredentialFinding> {
+    if addr.port() == TELNET_PORT {
+        let accepted = candidates(vendor)
+            .find(|c| telnet_login(addr, timeout, c.username, c.password).unwrap_or(false))?;
+        return Some(DefaultCredentialFinding {
+            port: addr.port(),
+            protocol: "telnet",
+            label: accepted.label,
+        });
+    }
+
+    // Only pages that ask for basic auth are tried; form logins are left alone
+    let (status, basic) = http_get(addr, timeout, None)?;
+    if status != 401 || !basic {
+        return None;
+    }
+    let accepted = candidates(vendor).find(|c| {
+        let token = STANDARD.encode(format!("{}:{}", c.username, c.password));
+        matches!(http_get(addr, timeout, Some(&token)), Some((200..=399, _)))
+    })?;
+    Some(DefaultCredentialFinding {
+        port: addr.port(),
+        protocol: "HTTP basic auth",
+        label: accepted.label,
+    })
+}
+
+fn connect(addr: SocketAddr, timeout: Duration) -> Option<TcpStream> {
+    let stream = TcpStream::connect_timeout(&addr, timeout).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    Some(stream)
+}
+
+/// GET / and return the status and whether a basic auth challenge came back
+fn http_get(addr: SocketAddr, timeout: Duration, basic_token: Option<&str>) -> Option<(u16, bool)> {
+    let mut stream = connect(addr, timeout)?;
+    let authorization = basic_token
+        .map(|token| format!("Authorization: Basic {}\r\n", token))
+        .unwrap_or_default();
+    let request = format!(
+        "GET / HTTP/1.0\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+        addr.ip(),
+        authorization
+    );
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut response = Vec::new();
+    let _ = stream
+        .take(MAX_RESPONSE_BYTES as u64)
+        .read_to_end(&mut response);
+    parse_http_response(&String::from_utf8_lossy(&response))
+}
+
+/// Status code and whether a `WWW-Authenticate: Basic` challenge is present
+fn parse_http_response(response: &str) -> Option<(u16, bool)> {
+    let mut lines = response.lines();
+    let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+    let basic = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .any(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("www-authenticate")
+                && value.trim().to_lowercase().starts_with("basic")
+        });
+    Some((status, basic))
+}
+
+/// Strip telnet negotiation from `data`, refusing every option in `replies`
+fn strip_telnet(data: &[u8], replies: &mut Vec<u8>) -> String {
+    let mut text = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != TELNET_IAC {
+            text.push(data[i]);
+            i += 1;
+            continue;
+        }
+        match (data.get(i + 1), data.get(i + 2)) {
+            (Some(&TELNET_DO), Some(&option)) => {
+                replies.extend([TELNET_IAC, TELNET_WONT, option]);
+                i += 3;
+            }
+            (Some(&TELNET_WILL), Some(&option)) => {
+                replies.extend([TELNET_IAC, TELNET_DONT, option]);
+                i += 3;
+            }
+            (Some(&TELNET_DONT | &TELNET_WONT), Some(_)) => i += 3,
+            (Some(&TELNET_SB), _) => {
+                // Skip to IAC SE
+                i += 2;
+                while i + 1 < data.len() && !(data[i] == TELNET_IAC && data[i + 1] == TELNET_SE) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            (Some(&TELNET_IAC), _) => {
+                text.push(TELNET_IAC);
+                i += 2;
+            }
+            _ => i += 2,
+        }
+    }
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+/// Read until the lowercased output ends with one of `prompts`, answering negotiation.
+/// Returns everything read, or None on timeout or a closed connection.
+fn read_until_prompt(
+    stream: &mut TcpStream,
+    timeout: Duration,
+    prompts: &[&str],
+) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+    let mut output = String::new();
+    let mut buf = [0; 1024];
+    while Instant::now() < deadline && output.len() < MAX_RESPONSE_BYTES {
+        let n = stream.read(&mut buf).ok()?;
+        if n == 0 {
+            return None;
+        }
+        let mut replies = Vec::new();
+        output.push_str(&strip_telnet(&buf[..n], &mut replies));
+        if !replies.is_empty() {
+            stream.write_all(&replies).ok()?;
+        }
+        let tail = output.trim_end().to_lowercase();
+        if prompts.iter().any(|prompt| tail.ends_with(prompt)) {
+            return Some(output);
+        }
+    }
+    None
+}
+
+/// Whether what a device printed after the password looks like a shell
+fn telnet_login_succeeded(output: &str) -> bool {
+    let lower = output.to_lowercase();
+    let rejected = [
+        "incorrect",
+        "invalid",
+        "failed",
+        "denied",
+        "login:",
+        "password:",
+    ]
+    .iter()
+    .any(|word| lower.contains(word));
+    !rejected && output.trim_end().ends_with(['#', '$', '>'])
+}
+
+fn telnet_login(
+    addr: SocketAddr,
+    timeout: Duration,
+    username: &str,
+    password: &str,
+) -> Option<bool> {
+    let mut stream = connect(addr, timeout)?;
+    read_until_prompt(&mut stream, timeout, &["login:", "username:", "user name:"])?;
+    stream
+        .write_all(format!("{}\r\n", username).as_bytes())
+        .ok()?;
+    read_until_prompt(&mut stream, timeout, &["password:"])?;
+    stream
+        .write_all(format!("{}\r\n", password).as_bytes())
+        .ok()?;
+    let output = read_until_prompt(
+        &mut stream,
+        timeout,
+        &["#", "$", ">", "login:", "username:", "password:"],
+    )
+    .unwrap_or_default();
+    Some(telnet_login_succeeded(&output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_put_vendor_logins_first() {
+        let labels: Vec<_> = candidates(Some("Hangzhou Hikvision Digital Technology"))
+            .map(|c| c.label)
+            .collect();
+        assert_eq!(labels[0], "Hikvision factory login");
+        assert_eq!(labels.len(), 4);
+        assert!(candidates(None).all(|c| c.vendors.is_empty()));
+
+        assert!(is_credential_check_target(Some("gateway"), None));
+        assert!(is_credential_check_target(
+            Some("other"),
+            Some("Dahua Technology")
+        ));
+        assert!(!is_credential_check_target(Some("tv"), Some("Samsung")));
+    }
+
+    #[test]
+    fn test_parse_http_response() {
+        let challenge = "HTTP/1.1 401 Unauthorized\r\nServer: lighttpd\r\n\
+                         WWW-Authenticate: Basic realm=\"Router\"\r\n\r\n";
+        assert_eq!(parse_http_response(challenge), Some((401, true)));
+        let digest = "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Digest realm=\"cam\"\r\n\r\n";
+        assert_eq!(parse_http_response(digest), Some((401, false)));
+        assert_eq!(
+            parse_http_response("HTTP/1.0 200 OK\r\n\r\n<html>"),
+            Some((200, false))
+        );
+        assert_eq!(parse_http_response(""), None);
+    }
+
+    #[test]
+    fn test_telnet_negotiation_and_login_result() {
+        let mut replies = Vec::new();
+        let data = [
+            TELNET_IAC,
+            TELNET_DO,
+            24,
+            TELNET_IAC,
+            TELNET_WILL,
+            1,
+            TELNET_IAC,
+            TELNET_SB,
+            31,
+            0,
+            80,
+            TELNET_IAC,
+            TELNET_SE,
+            b'l',
+            b'o',
+            b'g',
+            b'i',
+            b'n',
+            b':',
+            b' ',
+        ];
+        assert_eq!(strip_telnet(&data, &mut replies), "login: ");
+        assert_eq!(
+            replies,
+            [TELNET_IAC, TELNET_WONT, 24, TELNET_IAC, TELNET_DONT, 1]
+        );
+
+        assert!(telnet_login_succeeded("\r\nBusyBox v1.19.4\r\n# "));
+        assert!(!telnet_login_succeeded("\r\nLogin incorrect\r\nlogin: "));
+        assert!(!telnet_login_succeeded(""));
+    }
+}
@@ -0,0 +1,388 @@
+//! WAN exposure check. Finds the network's public address (from the UPnP gateway, a STUN
+//! server or an HTTP echo service) and looks for open ports on it, either by connecting
+//! from inside the network or by asking a user-provided external scanner.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use super::igd::{discover_gateway, query_external_ip};
+use super::port::{PortScanner, port_to_service_name};
+use super::{ExposureResult, PortResult};
+
+/// Where the WAN address comes from unless configured otherwise
+pub const DEFAULT_WAN_IP_SOURCE: &str = "gateway";
+
+const STUN_DEFAULT_PORT: u16 = 3478;
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_RESPONSE: u16 = 0x0101;
+const STUN_MAPPED_ADDRESS: u16 = 0x0001;
+const STUN_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+/// How the WAN address is determined (the `exposure_wan_ip_source` setting)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WanIpSource {
+    /// GetExternalIPAddress on the UPnP gateway
+    Gateway,
+    /// A STUN binding request to `host:port`
+    Stun(String),
+    /// An HTTP service that answers with the caller's address as plain text
+    Http(String),
+}
+
+impl WanIpSource {
+    /// Parse "gateway", "stun:host[:port]" or an http(s) URL
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case(DEFAULT_WAN_IP_SOURCE) {
+            return Some(WanIpSource::Gateway);
+        }
+        if let Some(server) = value.strip_prefix("stun:") {
+            let server = server.trim_start_matches("//");
+            if server.is_empty() {
+                return None;
+            }
+            return Some(if server.contains(':') {
+                WanIpSource::Stun(server.to_string())
+            } else {
+                WanIpSource::Stun(format!("{}:{}", server, STUN_DEFAULT_PORT))
+            });
+        }
+        match url::Url::parse(value) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                Some(WanIpSource::Http(value.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for WanIpSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WanIpSource::Gateway => write!(f, "gateway"),
+            WanIpSource::Stun(server) => write!(f, "stun:{}", server),
+            WanIpSource::Http(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+fn stun_binding_request(transaction_id: &[u8; 12]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(transaction_id);
+    request
+}
+
+/// Read the mapped address out of a STUN binding response, preferring XOR-MAPPED-ADDRESS
+fn parse_stun_response(response: &[u8], transaction_id: &[u8; 12]) -> Option<IpAddr> {
+    if response.len() < 20
+        || u16::from_be_bytes([response[0], response[1]]) != STUN_BINDING_RESPONSE
+        || u32::from_be_bytes(response[4..8].try_into().ok()?) != STUN_MAGIC_COOKIE
+        || response[8..20] != transaction_id[..]
+    {
+        return None;
+    }
+    let length = usize::from(u16::from_be_bytes([response[2], response[3]]));
+    let attributes = response.get(20..20 + length)?;
+
+    let mut mapped = None;
+    let mut offset = 0;
+    while offset + 4 <= attributes.len() {
+        let kind = u16::from_be_bytes([attributes[offset], attributes[offset + 1]]);
+        let len = usize::from(u16::from_be_bytes([
+            attributes[offset + 2],
+            attributes[offset + 3],
+        ]));
+        let value = attributes.get(offset + 4..offset + 4 + len)?;
+        match kind {
+            STUN_XOR_MAPPED_ADDRESS => return parse_stun_address(value, Some(&response[4..20])),
+            STUN_MAPPED_ADDRESS => mapped = parse_stun_address(value, None),
+            _ => {}
+        }
+        // Attributes are padded to a multiple of four bytes
+        offset += 4 + len.div_ceil(4) * 4;
+    }
+    mapped
+}
+
+/// Decode a (XOR-)MAPPED-ADDRESS value; `xor` is the magic cookie and transaction ID
+fn parse_stun_address(value: &[u8], xor: Option<&[u8]>) -> Option<IpAddr> {
+    let unmask = |bytes: &[u8]| -> Vec<u8> {
+        match xor {
+            Some(key) => bytes.iter().zip(key).map(|(b, k)| b ^ k).collect(),
+            None => bytes.to_vec(),
+        }
+    };
+    match *value.get(1)? {
+        0x01 => {
+            let octets: [u8; 4] = unmask(value.get(4..8)?).try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        0x02 => {
+            let octets: [u8; 16] = unmask(value.get(4..20)?).try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// Open ports reported by an external scanner: a JSON array of port numbers, or an
+/// object with an `open_ports` array
+fn parse_external_scanner_response(body: &str) -> Option<Vec<u16>> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    let ports = match &value {
+        serde_json::Value::Array(ports) => ports,
+        serde_json::Value::Object(object) => object.get("open_ports")?.as_array()?,
+        _ => return None,
+    };
+    Some(
+        ports
+            .iter()
+            .filter_map(|port| u16::try_from(port.as_u64()?).ok())
+            .collect(),
+    )
+}
+
+/// Whether a WAN address is itself private, i.e. the gateway sits behind another NAT
+/// (a second router or carrier-grade NAT) and nothing is directly reachable
+fn is_behind_nat(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(v6) => v6.is_loopback() || (v6.segments()[0] & 0xfe00) == 0xfc00,
+    }
+}
+
+/// Checks which ports on the network's WAN address answer
+pub struct ExposureScanner {
+    timeout_ms: u64,
+    wan_ip_source: WanIpSource,
+    /// URL with an `{ip}` placeholder; when set, ports are checked by this service instead
+    /// of connecting from inside the network
+    external_scanner_url: Option<String>,
+}
+
+impl ExposureScanner {
+    pub fn new() -> Self {
+        Self {
+            timeout_ms: 3000,
+            wan_ip_source: WanIpSource::Gateway,
+            external_scanner_url: None,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn with_wan_ip_source(mut self, source: WanIpSource) -> Self {
+        self.wan_ip_source = source;
+        self
+    }
+
+    pub fn with_external_scanner(mut self, url: Option<String>) -> Self {
+        self.external_scanner_url = url.filter(|url| !url.trim().is_empty());
+        self
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Determine the WAN address from the configured source
+    pub async fn wan_ip(&self) -> Option<IpAddr> {
+        match &self.wan_ip_source {
+            WanIpSource::Gateway => {
+                let service = discover_gateway(self.timeout()).await?;
+                query_external_ip(&service, self.timeout()).await
+            }
+            WanIpSource::Stun(server) => self.stun_wan_ip(server).await,
+            WanIpSource::Http(url) => {
+                let client = reqwest::Client::builder()
+                    .timeout(self.timeout())
+                    .build()
+                    .ok()?;
+                let body = client.get(url).send().await.ok()?.text().await.ok()?;
+                body.trim().parse().ok()
+            }
+        }
+    }
+
+    async fn stun_wan_ip(&self, server: &str) -> Option<IpAddr> {
+        let server: SocketAddr = tokio::net::lookup_host(server).await.ok()?.next()?;
+        let bind: SocketAddr = if server.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(bind).await.ok()?;
+        let transaction_id: [u8; 12] = uuid::Uuid::new_v4().into_bytes()[..12].try_into().ok()?;
+        socket
+            .send_to(&stun_binding_request(&transaction_id), server)
+            .await
+            .ok()?;
+
+        let mut buf = [0u8; 512];
+        let (len, _) = tokio::time::timeout(self.timeout(), socket.recv_from(&mut buf))
+            .await
+            .ok()?
+            .ok()?;
+        parse_stun_response(&buf[..len], &transaction_id)
+    }
+
+    async fn external_open_ports(&self, url: &str, wan_ip: IpAddr) -> Result<Vec<u16>, String> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let url = url.replace("{ip}", &wan_ip.to_string());
+        let body = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("External scanner request failed: {}", e))?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?;
+        parse_external_scanner_response(&body)
+            .ok_or_else(|| "External scanner returned an unrecognised response".to_string())
+    }
+
+    /// Find the WAN address and check `ports` on it
+    pub async fn scan(&self, ports: &[u16]) -> Result<ExposureResult, String> {
+        let wan_ip = self.wan_ip().await.ok_or_else(|| {
+            format!(
+                "Could not determine the WAN address from {}",
+                self.wan_ip_source
+            )
+        })?;
+
+        let (external_scanner, open_ports) = match &self.external_scanner_url {
+            Some(url) => {
+                let open_ports = self
+                    .external_open_ports(url, wan_ip)
+                    .await?
+                    .into_iter()
+                    .map(|port| PortResult {
+                        ip: wan_ip,
+                        port,
+                        open: true,
+                        service_name: port_to_service_name(port),
+                    })
+                    .collect();
+                (true, open_ports)
+            }
+            None => {
+                let scanner = PortScanner::new().with_timeout(self.timeout_ms);
+                (false, scanner.scan_ips(&[wan_ip], ports).await)
+            }
+        };
+
+        Ok(ExposureResult {
+            wan_ip,
+            wan_ip_source: self.wan_ip_source.to_string(),
+            behind_nat: is_behind_nat(wan_ip),
+            external_scanner,
+            open_ports,
+        })
+    }
+}
+
+impl Default for ExposureScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wan_ip_source_parse() {
+        assert_eq!(WanIpSource::parse(""), Some(WanIpSource::Gateway));
+        assert_eq!(WanIpSource::parse("Gateway"), Some(WanIpSource::Gateway));
+        assert_eq!(
+            WanIpSource::parse("stun:stun.example.org"),
+            Some(WanIpSource::Stun("stun.example.org:3478".to_string()))
+        );
+        assert_eq!(
+            WanIpSource::parse("stun:stun.example.org:19302"),
+            Some(WanIpSource::Stun("stun.example.org:19302".to_string()))
+        );
+        assert_eq!(
+            WanIpSource::parse("https://ip.example.org/"),
+            Some(WanIpSource::Http("https://ip.example.org/".to_string()))
+        );
+        assert_eq!(WanIpSource::parse("ftp://ip.example.org/"), None);
+        assert_eq!(WanIpSource::parse("stun:"), None);
+    }
+
+    #[test]
+    fn test_parse_stun_response() {
+        let transaction_id = [7u8; 12];
+        let request = stun_binding_request(&transaction_id);
+        assert_eq!(request.len(), 20);
+        assert_eq!(&request[..2], &[0x00, 0x01]);
+
+        // XOR-MAPPED-ADDRESS for 203.0.113.5:54321
+        let port = 54321u16 ^ (STUN_MAGIC_COOKIE >> 16) as u16;
+        let addr = u32::from(Ipv4Addr::new(203, 0, 113, 5)) ^ STUN_MAGIC_COOKIE;
+        let mut response = vec![0x01, 0x01, 0x00, 0x0c];
+        response.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        response.extend_from_slice(&transaction_id);
+        response.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01]);
+        response.extend_from_slice(&port.to_be_bytes());
+        response.extend_from_slice(&addr.to_be_bytes());
+
+        assert_eq!(
+            parse_stun_response(&response, &transaction_id),
+            Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 5)))
+        );
+        // A response to someone else's request is ignored
+        assert_eq!(parse_stun_response(&response, &[8u8; 12]), None);
+    }
+
+    #[test]
+    fn test_parse_external_scanner_response() {
+        assert_eq!(
+            parse_external_scanner_response("[22, 443]"),
+            Some(vec![22, 443])
+        );
+        assert_eq!(
+            parse_external_scanner_response(r#"{"ip": "203.0.113.5", "open_ports": [8080]}"#),
+            Some(vec![8080])
+        );
+        assert_eq!(parse_external_scanner_response("open: 22"), None);
+    }
+
+    #[test]
+    fn test_is_behind_nat() {
+        assert!(is_behind_nat("192.168.0.10".parse().unwrap()));
+        assert!(is_behind_nat("100.72.1.1".parse().unwrap()));
+        assert!(!is_behind_nat("203.0.113.5".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_scanner_default() {
+        let scanner = ExposureScanner::default();
+        assert_eq!(scanner.timeout_ms, 3000);
+        assert_eq!(scanner.wan_ip_source, WanIpSource::Gateway);
+        assert!(scanner.external_scanner_url.is_none());
+
+        let scanner = ExposureScanner::new().with_external_scanner(Some(" ".to_string()));
+        assert!(scanner.external_scanner_url.is_none());
+    }
+}
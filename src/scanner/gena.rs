@@ -0,0 +1,328 @@
+//! UPnP eventing (GENA). Devices found by SSDP list an event subscription URL per
+//! service; subscribing with a callback URL makes the device send NOTIFY requests with
+//! its state variables whenever they change, so a media renderer starting playback or a
+//! gateway's WAN address changing arrives as it happens instead of on the next scan.
+//! This module finds the evented services, subscribes and renews, and parses the
+//! property sets the device sends back.
+
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use super::ssdp::SsdpScanner;
+
+/// Services whose events are subscribed to: playback and volume on media renderers,
+/// WAN address and connection status on gateways
+const EVENTED_SERVICES: &[&str] = &[
+    "AVTransport",
+    "RenderingControl",
+    "WANIPConnection",
+    "WANPPPConnection",
+];
+
+/// Subscription length asked for; devices may grant less
+pub const REQUESTED_TIMEOUT_SECS: u64 = 1800;
+
+/// State variables longer than this (track metadata, mostly) are cut short
+const MAX_VALUE_LEN: usize = 256;
+
+/// A service that can be subscribed to for events
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventService {
+    pub service_type: String,
+    pub event_url: String,
+}
+
+impl EventService {
+    /// The service's short name, e.g. "AVTransport" for
+    /// `urn:schemas-upnp-org:service:AVTransport:1`
+    pub fn short_name(&self) -> &str {
+        short_service_name(&self.service_type)
+    }
+}
+
+pub fn short_service_name(service_type: &str) -> &str {
+    service_type
+        .rsplit(':')
+        .nth(1)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(service_type)
+}
+
+/// A subscription the device accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    pub sid: String,
+    pub timeout_secs: u64,
+}
+
+/// Evented services of interest in a device description fetched from `location`
+pub fn find_event_services(description: &str, location: &str) -> Vec<EventService> {
+    let base = SsdpScanner::extract_xml_element(description, "URLBase")
+        .unwrap_or_else(|| location.to_string());
+    let Ok(base) = url::Url::parse(&base) else {
+        return Vec::new();
+    };
+
+    description
+        .split("<service>")
+        .skip(1)
+        .filter_map(|block| {
+            let block = block.split("</service>").next()?;
+            let service_type = SsdpScanner::extract_xml_element(block, "serviceType")?;
+            if !EVENTED_SERVICES
+                .iter()
+                .any(|service| short_service_name(&service_type) == *service)
+            {
+                return None;
+            }
+            let event_url = SsdpScanner::extract_xml_element(block, "eventSubURL")?;
+            Some(EventService {
+                service_type,
+                event_url: base.join(&event_url).ok()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Fetch the device description at `location` and list its evented services of interest
+pub async fn fetch_event_services(location: &str) -> Vec<EventService> {
+    match SsdpScanner::fetch_device_description(location).await {
+        Some(description) => find_event_services(&description, location),
+        None => Vec::new(),
+    }
+}
+
+/// The local address a device at `device` would reach us on
+pub fn local_address_for(device: IpAddr) -> Option<IpAddr> {
+    let bind: SocketAddr = match device {
+        IpAddr::V4(_) => "0.0.0.0:0".parse().ok()?,
+        IpAddr::V6(_) => "[::]:0".parse().ok()?,
+    };
+    let socket = UdpSocket::bind(bind).ok()?;
+    // Connecting a UDP socket only picks the route; nothing is sent
+    socket.connect((device, 1900)).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Parse a GENA TIMEOUT header ("Second-1800" or "Second-infinite")
+fn parse_timeout(value: &str) -> Option<u64> {
+    let seconds = value.trim().strip_prefix("Second-")?;
+    if seconds.eq_ignore_ascii_case("infinite") {
+        return Some(REQUESTED_TIMEOUT_SECS);
+    }
+    seconds.parse().ok()
+}
+
+async fn send_subscribe(
+    event_url: &str,
+    headers: &[(&str, String)],
+    timeout: Duration,
+) -> Result<Subscription, String> {
+    let method = reqwest::Method::from_bytes(b"SUBSCRIBE").map_err(|e| e.to_string())?;
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut request = client
+        .request(method, event_url)
+        .header("TIMEOUT", format!("Second-{}", REQUESTED_TIMEOUT_SECS));
+    for (name, value) in headers {
+        request = request.header(*name, value);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let sid = header("SID").ok_or("Device accepted the subscription without a SID")?;
+    Ok(Subscription {
+        sid,
+        timeout_secs: header("TIMEOUT")
+            .and_then(|value| parse_timeout(&value))
+            .unwrap_or(REQUESTED_TIMEOUT_SECS),
+    })
+}
+
+/// Subscribe to a service's events, to be delivered to `callback_url`
+pub async fn subscribe(
+    event_url: &str,
+    callback_url: &str,
+    timeout: Duration,
+) -> Result<Subscription, String> {
+    send_subscribe(
+        event_url,
+        &[
+            ("CALLBACK", format!("<{}>", callback_url)),
+            ("NT", "upnp:event".to_string()),
+        ],
+        timeout,
+    )
+    .await
+}
+
+/// Renew a subscription before it lapses
+pub async fn renew(event_url: &str, sid: &str, timeout: Duration) -> Result<Subscription, String> {
+    send_subscribe(event_url, &[("SID", sid.to_string())], timeout).await
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn truncate(value: String) -> String {
+    match value.char_indices().nth(MAX_VALUE_LEN) {
+        Some((end, _)) => value[..end].to_string(),
+        None => value,
+    }
+}
+
+/// The name of the tag starting at `tag` (just after its '<'), without prefix or attributes
+fn tag_name(tag: &str) -> &str {
+    let name = tag
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or("");
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = tag[start..].find('"')? + start;
+    Some(&tag[start..end])
+}
+
+/// Variables in an AVTransport/RenderingControl LastChange document:
+/// `<Event><InstanceID val="0"><TransportState val="PLAYING"/>...`
+fn parse_last_change(xml: &str) -> Vec<(String, String)> {
+    xml.split('<')
+        .skip(1)
+        .filter(|tag| !tag.starts_with('/') && !tag.starts_with('?'))
+        .filter_map(|tag| {
+            let tag = tag.split('>').next()?;
+            let name = tag_name(tag);
+            if name == "Event" || name == "InstanceID" {
+                return None;
+            }
+            // Per-channel variables are kept for the master channel only
+            if attribute(tag, "channel").is_some_and(|channel| channel != "Master") {
+                return None;
+            }
+            let value = attribute(tag, "val")?;
+            Some((name.to_string(), truncate(xml_unescape(value))))
+        })
+        .collect()
+}
+
+/// State variables from a NOTIFY body
+/// (`<e:propertyset><e:property><Name>value</Name></e:property>...`), with a LastChange
+/// document expanded into the variables it carries
+pub fn parse_property_set(body: &str) -> Vec<(String, String)> {
+    let mut variables = Vec::new();
+    for (start, _) in body.match_indices('<') {
+        let tag = &body[start + 1..];
+        if tag_name(tag) != "property" {
+            continue;
+        }
+        let Some(content) = tag.split_once('>').map(|(_, rest)| rest) else {
+            continue;
+        };
+        // The property holds one element: <Name>value</Name>
+        let Some(variable) = content.trim_start().strip_prefix('<') else {
+            continue;
+        };
+        let name = tag_name(variable);
+        let Some((_, rest)) = variable.split_once('>') else {
+            continue;
+        };
+        let Some(end) = rest.find(&format!("</{}", name)) else {
+            continue;
+        };
+        let value = xml_unescape(rest[..end].trim());
+        if name == "LastChange" {
+            variables.extend(parse_last_change(&value));
+        } else {
+            variables.push((name.to_string(), truncate(value)));
+        }
+    }
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENDERER_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:MediaRenderer:1</deviceType>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:AVTransport:1</serviceType>
+        <controlURL>/AVTransport/control</controlURL>
+        <eventSubURL>/AVTransport/event</eventSubURL>
+      </service>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:ConnectionManager:1</serviceType>
+        <eventSubURL>/ConnectionManager/event</eventSubURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#;
+
+    #[test]
+    fn test_find_event_services() {
+        let services = find_event_services(RENDERER_DESCRIPTION, "http://192.168.1.40:1400/xml");
+        assert_eq!(
+            services,
+            vec![EventService {
+                service_type: "urn:schemas-upnp-org:service:AVTransport:1".to_string(),
+                event_url: "http://192.168.1.40:1400/AVTransport/event".to_string(),
+            }]
+        );
+        assert_eq!(services[0].short_name(), "AVTransport");
+        assert_eq!(parse_timeout("Second-1800"), Some(1800));
+        assert_eq!(
+            parse_timeout("Second-infinite"),
+            Some(REQUESTED_TIMEOUT_SECS)
+        );
+        assert_eq!(parse_timeout("1800"), None);
+    }
+
+    #[test]
+    fn test_parse_property_set() {
+        let gateway = r#"<?xml version="1.0"?>
+<e:propertyset xmlns:e="urn:schemas-upnp-org:event-1-0">
+  <e:property><ExternalIPAddress>203.0.113.7</ExternalIPAddress></e:property>
+  <e:property><ConnectionStatus>Connected</ConnectionStatus></e:property>
+</e:propertyset>"#;
+        assert_eq!(
+            parse_property_set(gateway),
+            vec![
+                ("ExternalIPAddress".to_string(), "203.0.113.7".to_string()),
+                ("ConnectionStatus".to_string(), "Connected".to_string()),
+            ]
+        );
+
+        let renderer = r#"<e:propertyset xmlns:e="urn:schemas-upnp-org:event-1-0"><e:property><LastChange>&lt;Event xmlns="urn:schemas-upnp-org:metadata-1-0/AVT/"&gt;&lt;InstanceID val="0"&gt;&lt;TransportState val="PLAYING"/&gt;&lt;Volume channel="LF" val="10"/&gt;&lt;Volume channel="Master" val="32"/&gt;&lt;/InstanceID&gt;&lt;/Event&gt;</LastChange></e:property></e:propertyset>"#;
+        assert_eq!(
+            parse_property_set(renderer),
+            vec![
+                ("TransportState".to_string(), "PLAYING".to_string()),
+                ("Volume".to_string(), "32".to_string()),
+            ]
+        );
+    }
+}
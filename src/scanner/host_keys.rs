@@ -0,0 +1,513 @@
+//! SSH host key and TLS certificate collection. The SSH key exchange is carried only as
+//! far as the server's reply, which holds its host key; TLS handshakes accept any
+//! certificate, since LAN devices mostly present self-signed ones.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD_NO_PAD};
+use sha2::{Digest, Sha256};
+
+use super::{SshHostKey, TlsCertificate};
+
+/// Ports an SSH server is expected on
+pub const SSH_PORTS: &[u16] = &[22, 2222];
+
+/// Ports speaking TLS from the first byte (HTTPS, SMTPS, LDAPS, IMAPS, POP3S, and
+/// NAS/controller web interfaces)
+pub const TLS_PORTS: &[u16] = &[443, 465, 636, 993, 995, 5001, 8443, 9443];
+
+const CLIENT_VERSION: &[u8] = b"SSH-2.0-NetworkDiscovery_1.0\r\n";
+
+/// Only curve25519 is offered, so the client's ephemeral key can be any 32 bytes
+const KEX_ALGORITHMS: &str = "curve25519-sha256,curve25519-sha256@libssh.org";
+const HOST_KEY_ALGORITHMS: &str = "ssh-ed25519,ecdsa-sha2-nistp256,ecdsa-sha2-nistp384,\
+                                   ecdsa-sha2-nistp521,rsa-sha2-512,rsa-sha2-256,ssh-rsa";
+const CIPHERS: &str = "chacha20-poly1305@openssh.com,aes128-ctr,aes256-ctr,\
+                       aes128-gcm@openssh.com,aes256-gcm@openssh.com";
+const MACS: &str = "hmac-sha2-256,hmac-sha2-512,hmac-sha1";
+
+const SSH_MSG_DISCONNECT: u8 = 1;
+const SSH_MSG_KEXINIT: u8 = 20;
+const SSH_MSG_KEX_ECDH_INIT: u8 = 30;
+const SSH_MSG_KEX_ECDH_REPLY: u8 = 31;
+
+/// Lines a server may send before its version string
+const MAX_BANNER_LINES: usize = 32;
+const MAX_PACKET_BYTES: usize = 256 * 1024;
+/// Packets read while waiting for a message (servers may send IGNORE/DEBUG first)
+const MAX_PACKETS: usize = 16;
+
+const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_PRINTABLE_STRING: u8 = 0x13;
+const TAG_TELETEX_STRING: u8 = 0x14;
+const TAG_IA5_STRING: u8 = 0x16;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_BMP_STRING: u8 = 0x1e;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_VERSION: u8 = 0xa0;
+
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+
+/// Distinguished name attributes rendered in a subject, with their usual short names
+const NAME_ATTRIBUTES: &[(&[u8], &str)] = &[
+    (OID_COMMON_NAME, "CN"),
+    (&[0x55, 0x04, 0x05], "serialNumber"),
+    (&[0x55, 0x04, 0x06], "C"),
+    (&[0x55, 0x04, 0x07], "L"),
+    (&[0x55, 0x04, 0x08], "ST"),
+    (OID_ORGANIZATION, "O"),
+    (&[0x55, 0x04, 0x0b], "OU"),
+    (
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01],
+        "emailAddress",
+    ),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01],
+        "UID",
+    ),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19],
+        "DC",
+    ),
+];
+
+/// Collects SSH host keys and TLS certificates from device services
+pub struct HostKeyCollector {
+    timeout_ms: u64,
+}
+
+impl HostKeyCollector {
+    pub fn new() -> Self {
+        Self { timeout_ms: 3000 }
+    }
+
+    /// Host keys from the SSH ports and certificates from the TLS ports among `ports`.
+    /// Services that don't complete the exchange are left out.
+    pub async fn collect(
+        &self,
+        ip: IpAddr,
+        ports: &[u16],
+    ) -> (Vec<SshHostKey>, Vec<TlsCertificate>) {
+        let timeout = Duration::from_millis(self.timeout_ms);
+        let ports = ports.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let ssh_keys = ports
+                .iter()
+                .filter(|port| SSH_PORTS.contains(port))
+                .filter_map(|&port| fetch_ssh_host_key(SocketAddr::new(ip, port), timeout))
+                .collect();
+            let certificates = ports
+                .iter()
+                .filter(|port| TLS_PORTS.contains(port))
+                .filter_map(|&port| fetch_tls_certificate(SocketAddr::new(ip, port), timeout))
+                .collect();
+            (ssh_keys, certificates)
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+impl Default for HostKeyCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn connect(addr: SocketAddr, timeout: Duration) -> Option<TcpStream> {
+    let stream = TcpStream::connect_timeout(&addr, timeout).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    Some(stream)
+}
+
+/// Run an SSH key exchange up to the server's KEX_ECDH_REPLY and return its host key
+pub fn fetch_ssh_host_key(addr: SocketAddr, timeout: Duration) -> Option<SshHostKey> {
+    let mut reader = BufReader::new(connect(addr, timeout)?);
+
+    let mut line = Vec::new();
+    for _ in 0..MAX_BANNER_LINES {
+        line.clear();
+        if (&mut reader).take(1024).read_until(b'\n', &mut line).ok()? == 0 {
+            return None;
+        }
+        if line.starts_with(b"SSH-") {
+            break;
+        }
+    }
+    if !line.starts_with(b"SSH-2.0-") && !line.starts_with(b"SSH-1.99-") {
+        return None;
+    }
+
+    let stream = reader.get_mut();
+    stream.write_all(CLIENT_VERSION).ok()?;
+    stream.write_all(&ssh_packet(&kexinit_payload())).ok()?;
+    read_ssh_message(&mut reader, SSH_MSG_KEXINIT)?;
+
+    let mut ecdh_init = vec![SSH_MSG_KEX_ECDH_INIT];
+    put_ssh_string(&mut ecdh_init, &random_bytes());
+    reader.get_mut().write_all(&ssh_packet(&ecdh_init)).ok()?;
+    let reply = read_ssh_message(&mut reader, SSH_MSG_KEX_ECDH_REPLY)?;
+    parse_ecdh_reply(&reply, addr.port())
+}
+
+/// 32 random bytes, for the KEXINIT cookie and the throwaway ephemeral key
+fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0; 32];
+    bytes[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    bytes[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    bytes
+}
+
+fn put_ssh_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// Split an SSH string off the front of `data`, returning (value, rest)
+fn read_ssh_string(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let value = data.get(4..4 + len)?;
+    Some((value, &data[4 + len..]))
+}
+
+fn kexinit_payload() -> Vec<u8> {
+    let mut payload = vec![SSH_MSG_KEXINIT];
+    payload.extend_from_slice(&random_bytes()[..16]);
+    for name_list in [
+        KEX_ALGORITHMS,
+        HOST_KEY_ALGORITHMS,
+        CIPHERS,
+        CIPHERS,
+        MACS,
+        MACS,
+        "none",
+        "none",
+        "",
+        "",
+    ] {
+        put_ssh_string(&mut payload, name_list.as_bytes());
+    }
+    // first_kex_packet_follows, then the reserved uint32
+    payload.push(0);
+    payload.extend_from_slice(&[0; 4]);
+    payload
+}
+
+/// Frame a payload as an unencrypted binary packet: padded to the 8-byte block size with
+/// at least 4 bytes of padding, no MAC
+fn ssh_packet(payload: &[u8]) -> Vec<u8> {
+    let mut padding = 8 - (5 + payload.len()) % 8;
+    if padding < 4 {
+        padding += 8;
+    }
+    let mut packet = Vec::with_capacity(5 + payload.len() + padding);
+    packet.extend_from_slice(&((1 + payload.len() + padding) as u32).to_be_bytes());
+    packet.push(padding as u8);
+    packet.extend_from_slice(payload);
+    packet.resize(packet.len() + padding, 0);
+    packet
+}
+
+fn read_ssh_packet(reader: &mut impl Read) -> Option<Vec<u8>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len).ok()?;
+    let len = u32::from_be_bytes(len) as usize;
+    if !(5..=MAX_PACKET_BYTES).contains(&len) {
+        return None;
+    }
+    let mut packet = vec![0; len];
+    reader.read_exact(&mut packet).ok()?;
+    let padding = usize::from(packet[0]);
+    packet.get(1..len.checked_sub(padding)?).map(<[u8]>::to_vec)
+}
+
+/// Read packets until one carries `message`, giving up on a disconnect
+fn read_ssh_message(reader: &mut impl Read, message: u8) -> Option<Vec<u8>> {
+    for _ in 0..MAX_PACKETS {
+        let payload = read_ssh_packet(reader)?;
+        match payload.first() {
+            Some(&m) if m == message => return Some(payload),
+            Some(&SSH_MSG_DISCONNECT) | None => return None,
+            Some(_) => {}
+        }
+    }
+    None
+}
+
+/// The host key in a KEX_ECDH_REPLY payload
+fn parse_ecdh_reply(payload: &[u8], port: u16) -> Option<SshHostKey> {
+    let (host_key, _) = read_ssh_string(payload.get(1..)?)?;
+    let (key_type, _) = read_ssh_string(host_key)?;
+    Some(SshHostKey {
+        port,
+        key_type: String::from_utf8(key_type.to_vec()).ok()?,
+        fingerprint: ssh_fingerprint(host_key),
+    })
+}
+
+/// OpenSSH-style fingerprint of a public key blob, as `ssh-keygen -l` prints it
+pub fn ssh_fingerprint(host_key: &[u8]) -> String {
+    format!(
+        "SHA256:{}",
+        STANDARD_NO_PAD.encode(Sha256::digest(host_key))
+    )
+}
+
+/// Complete a TLS handshake, accepting any certificate, and read the server's certificate
+pub fn fetch_tls_certificate(addr: SocketAddr, timeout: Duration) -> Option<TlsCertificate> {
+    let stream = connect(addr, timeout)?;
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .ok()?;
+    let tls = connector.connect(&addr.ip().to_string(), stream).ok()?;
+    let der = tls.peer_certificate().ok()??.to_der().ok()?;
+    parse_certificate(&der, addr.port())
+}
+
+/// SHA-256 as colon-separated uppercase hex, the way browsers and openssl show it
+fn hex_fingerprint(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+struct DerElement<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    /// Tag, length and contents together
+    encoded: &'a [u8],
+}
+
+/// Split one DER element off the front of `data`
+fn der_element(data: &[u8]) -> Option<(DerElement<'_>, &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)?;
+    let (len, header) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + count)?
+            .iter()
+            .fold(0, |len, &b| (len << 8) | usize::from(b));
+        (len, 2 + count)
+    };
+    let end = header.checked_add(len)?;
+    let element = DerElement {
+        tag,
+        contents: data.get(header..end)?,
+        encoded: &data[..end],
+    };
+    Some((element, &data[end..]))
+}
+
+fn der_children(mut data: &[u8]) -> impl Iterator<Item = DerElement<'_>> {
+    std::iter::from_fn(move || {
+        let (element, rest) = der_element(data)?;
+        data = rest;
+        Some(element)
+    })
+}
+
+fn der_string(element: &DerElement) -> Option<String> {
+    match element.tag {
+        TAG_BMP_STRING => {
+            let units: Vec<u16> = element
+                .contents
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            String::from_utf16(&units).ok()
+        }
+        // Teletex strings are ASCII in practice
+        TAG_UTF8_STRING | TAG_PRINTABLE_STRING | TAG_TELETEX_STRING | TAG_IA5_STRING => {
+            Some(String::from_utf8_lossy(element.contents).into_owned())
+        }
+        _ => None,
+    }
+}
+
+/// First value of an attribute in a distinguished name
+fn name_attribute(name: &[u8], oid: &[u8]) -> Option<String> {
+    der_children(name)
+        .filter(|rdn| rdn.tag == TAG_SET)
+        .flat_map(|rdn| der_children(rdn.contents))
+        .find_map(|attribute| {
+            let mut parts = der_children(attribute.contents);
+            let id = parts.next()?;
+            let value = parts.next()?;
+            if id.tag == TAG_OID && id.contents == oid {
+                der_string(&value)
+            } else {
+                None
+            }
+        })
+}
+
+/// A distinguished name as "CN=laptop-42, OU=Devices, O=Acme", in encoded order.
+/// Attributes without a short name are left out.
+fn render_name(name: &[u8]) -> Option<String> {
+    let parts: Vec<String> = der_children(name)
+        .filter(|rdn| rdn.tag == TAG_SET)
+        .flat_map(|rdn| der_children(rdn.contents))
+        .filter_map(|attribute| {
+            let mut parts = der_children(attribute.contents);
+            let id = parts.next()?;
+            let (_, short_name) = NAME_ATTRIBUTES
+                .iter()
+                .find(|(oid, _)| id.tag == TAG_OID && id.contents == *oid)?;
+            let value = der_string(&parts.next()?)?;
+            Some(format!("{}={}", short_name, value.trim()))
+        })
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(", "))
+    }
+}
+
+/// UTCTime or GeneralizedTime as Unix seconds
+fn parse_der_time(element: &DerElement) -> Option<i64> {
+    let text = std::str::from_utf8(element.contents).ok()?;
+    let text = match element.tag {
+        // Two-digit years from 50 on are 19xx (RFC 5280)
+        TAG_UTC_TIME if text.get(..2)? >= "50" => format!("19{}", text),
+        TAG_UTC_TIME => format!("20{}", text),
+        TAG_GENERALIZED_TIME => text.to_string(),
+        _ => return None,
+    };
+    chrono::NaiveDateTime::parse_from_str(&text, "%Y%m%d%H%M%SZ")
+        .ok()
+        .map(|time| time.and_utc().timestamp())
+}
+
+/// Pull the subject, issuer and validity out of a DER-encoded X.509 certificate
+pub fn parse_certificate(der: &[u8], port: u16) -> Option<TlsCertificate> {
+    let (certificate, _) = der_element(der)?;
+    let (tbs, _) = der_element(certificate.contents)?;
+    let mut fields = der_children(tbs.contents).skip_while(|field| field.tag == TAG_VERSION);
+    let _serial = fields.next()?;
+    let _signature_algorithm = fields.next()?;
+    let issuer = fields.next()?;
+    let validity = fields.next()?;
+    let subject = fields.next()?;
+    let public_key = fields.next()?;
+    if [
+        certificate.tag,
+        tbs.tag,
+        issuer.tag,
+        validity.tag,
+        subject.tag,
+        public_key.tag,
+    ] != [TAG_SEQUENCE; 6]
+    {
+        return None;
+    }
+
+    let mut times = der_children(validity.contents).map(|time| parse_der_time(&time));
+    let not_before = times.next().flatten();
+    let not_after = times.next().flatten();
+    Some(TlsCertificate {
+        port,
+        subject_cn: name_attribute(subject.contents, OID_COMMON_NAME),
+        subject: render_name(subject.contents),
+        issuer: name_attribute(issuer.contents, OID_COMMON_NAME)
+            .or_else(|| name_attribute(issuer.contents, OID_ORGANIZATION)),
+        not_before,
+        not_after,
+        // No signature check: a certificate naming itself as issuer is self-signed
+        // for every device this tool will meet
+        self_signed: issuer.encoded == subject.encoded,
+        fingerprint: hex_fingerprint(certificate.encoded),
+        key_fingerprint: hex_fingerprint(public_key.encoded),
+    })
+}
+
+/// Subject of a DER-encoded X.509 certificate, as in `TlsCertificate::subject`
+pub fn certificate_subject(der: &[u8]) -> Option<String> {
+    parse_certificate(der, 0)?.subject
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed P-256 certificate for "O=Acme, CN=nas.local", valid 2025-01-01 to
+    /// 2055-01-01 (a GeneralizedTime, being past 2049)
+    const TEST_CERTIFICATE: &str = "\
+        MIIBnjCCAUOgAwIBAgIUPiUQXebuToGi9TjkTckwx3z1xN4wCgYIKoZIzj0EAwIwIzENMAsGA1UECgwEQWNtZTESMBAGA1UEAwwJ\
+        bmFzLmxvY2FsMCAXDTI1MDEwMTAwMDAwMFoYDzIwNTUwMTAxMDAwMDAwWjAjMQ0wCwYDVQQKDARBY21lMRIwEAYDVQQDDAluYXMu\
+        bG9jYWwwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASOuHYXqD7rf8pXsTmap3slp/PbvqPzU0lzVhgLTd/bJwMd59+kjZawWJKl\
+        KgzRcv7t+iK1VbMrLEYYil/Ywmgpo1MwUTAdBgNVHQ4EFgQUrTveMT5/Q4Qd6NbqNrQojNFCIVcwHwYDVR0jBBgwFoAUrTveMT5/\
+        Q4Qd6NbqNrQojNFCIVcwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNJADBGAiEAixx3BOG3ZCGvXrD5bAUvsH34HQzLiV4P\
+        hcziOXlgHBYCIQDwSNOCuUzm6FX4QkC5sFMWHr7u9S5Ej1fd2OAhtgsEFw==";
+
+    #[test]
+    fn test_parse_certificate() {
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(TEST_CERTIFICATE)
+            .unwrap();
+        let cert = parse_certificate(&der, 5001).unwrap();
+        assert_eq!(cert.port, 5001);
+        assert_eq!(cert.subject_cn.as_deref(), Some("nas.local"));
+        assert_eq!(cert.subject.as_deref(), Some("O=Acme, CN=nas.local"));
+        assert_eq!(cert.issuer.as_deref(), Some("nas.local"));
+        assert_eq!(cert.not_before, Some(1_735_689_600));
+        assert_eq!(cert.not_after, Some(2_682_374_400));
+        assert!(cert.self_signed);
+        assert_eq!(
+            cert.fingerprint,
+            "75:0C:62:ED:76:2B:2A:96:82:50:6E:28:57:23:DF:40:D7:A5:E0:22:C3:CF:EA:E8:1A:27:E9:B8:A2:F3:21:2F"
+        );
+        assert!(cert.key_fingerprint.starts_with("E1:7C:A1:2C:D5:C8"));
+
+        assert!(parse_certificate(&der[..100], 443).is_none());
+        assert!(parse_certificate(b"not a certificate", 443).is_none());
+    }
+
+    #[test]
+    fn test_ssh_packets_and_host_key() {
+        let payload = kexinit_payload();
+        let packet = ssh_packet(&payload);
+        assert_eq!(packet.len() % 8, 0);
+        assert!(packet[4] >= 4);
+        assert_eq!(read_ssh_packet(&mut packet.as_slice()).unwrap(), payload);
+
+        // KEX_ECDH_REPLY carrying an ed25519 key, as `ssh-keygen -l` fingerprints it
+        let host_key = base64::engine::general_purpose::STANDARD
+            .decode("AAAAC3NzaC1lZDI1NTE5AAAAIF1m7DrSN7BE1omg4i4vpQOj1MfE+n3irVZs/2+KVpwo")
+            .unwrap();
+        let mut reply = vec![SSH_MSG_KEX_ECDH_REPLY];
+        put_ssh_string(&mut reply, &host_key);
+        put_ssh_string(&mut reply, &[0; 32]);
+        put_ssh_string(&mut reply, b"signature");
+        let mut stream = [ssh_packet(&[4, 0]), ssh_packet(&reply)].concat();
+        let reply = read_ssh_message(&mut stream.as_slice(), SSH_MSG_KEX_ECDH_REPLY).unwrap();
+        assert_eq!(
+            parse_ecdh_reply(&reply, 22),
+            Some(SshHostKey {
+                port: 22,
+                key_type: "ssh-ed25519".to_string(),
+                fingerprint: "SHA256:xd+CTrdBF13RFng46/KSIc0NKxuVwOG7LiDeW8dRxNI".to_string(),
+            })
+        );
+
+        stream = ssh_packet(&[SSH_MSG_DISCONNECT]);
+        assert!(read_ssh_message(&mut stream.as_slice(), SSH_MSG_KEX_ECDH_REPLY).is_none());
+    }
+}
@@ -1,20 +1,65 @@
 //! ICMP ping scanner. Performs concurrent ping sweeps using raw sockets with
 //! semaphore-based concurrency limiting to discover live hosts on the network.
 
+use std::fmt;
+use std::io;
 use std::mem::MaybeUninit;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use futures::future::BoxFuture;
 use socket2::{Domain, Protocol, Socket, Type};
 use tokio::sync::Semaphore;
 
-use super::IcmpResult;
+use super::manager::ScanRun;
+use super::registry::{Scanner, Tunables};
+use super::{IcmpResult, Privileges, ScanResult, ScanType};
+
+/// Kind of socket used to send the echo request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SocketKind {
+    /// Raw ICMP socket (requires root/CAP_NET_RAW)
+    Raw,
+    /// Unprivileged ICMP datagram socket
+    Datagram,
+}
+
+/// A received echo reply
+#[derive(Debug, Clone, Copy)]
+pub struct EchoReply {
+    pub rtt: Duration,
+    /// TTL from the reply's IP header, when the socket exposes it
+    pub ttl: Option<u8>,
+}
+
+/// Why an echo request did not get a reply
+#[derive(Debug)]
+pub enum EchoError {
+    /// Neither a raw nor a datagram ICMP socket could be opened
+    Socket(io::Error),
+    Send(io::Error),
+    Receive(io::Error),
+    Timeout,
+}
+
+impl fmt::Display for EchoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EchoError::Socket(e) => write!(f, "Cannot open ICMP socket: {}", e),
+            EchoError::Send(e) => write!(f, "Failed to send echo request: {}", e),
+            EchoError::Receive(e) => write!(f, "Failed to receive echo reply: {}", e),
+            EchoError::Timeout => write!(f, "Host unreachable"),
+        }
+    }
+}
 
 /// ICMP echo (ping) scanner
 pub struct IcmpScanner {
     timeout_ms: u64,
     max_concurrent: usize,
+    /// Extra echoes for hosts that did not answer
+    retries: u8,
 }
 
 impl IcmpScanner {
@@ -22,6 +67,7 @@ impl IcmpScanner {
         Self {
             timeout_ms: 1000,
             max_concurrent: 50,
+            retries: 0,
         }
     }
 
@@ -30,6 +76,16 @@ impl IcmpScanner {
         self
     }
 
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.max_concurrent = parallelism.max(1);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u8) -> Self {
+        self.retries = retries;
+        self
+    }
+
     /// Build an ICMP echo request packet
     fn build_echo_request(identifier: u16, sequence: u16) -> Vec<u8> {
         let mut packet = vec![0u8; 8];
@@ -78,64 +134,143 @@ impl IcmpScanner {
         !sum as u16
     }
 
-    /// Create a failed result for the given IP
-    fn failed_result(ip: Ipv4Addr) -> IcmpResult {
-        IcmpResult {
-            ip: IpAddr::V4(ip),
-            alive: false,
-            rtt_ms: None,
-            ttl: None,
-        }
+    /// Build an ICMPv6 echo request. The kernel fills in the checksum for ICMPv6
+    /// sockets since it depends on the IPv6 pseudo-header.
+    fn build_echo_request_v6(identifier: u16, sequence: u16) -> Vec<u8> {
+        let mut packet = vec![0u8; 8];
+        // Type: Echo Request (128)
+        packet[0] = 128;
+        packet[4..6].copy_from_slice(&identifier.to_be_bytes());
+        packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+        packet
     }
 
-    /// Ping a single IP address
-    fn ping_ip(&self, ip: Ipv4Addr, sequence: u16) -> IcmpResult {
-        let start = Instant::now();
+    /// Open an ICMP socket for the address family. Raw sockets need root/CAP_NET_RAW;
+    /// otherwise fall back to unprivileged ICMP datagram ("ping") sockets, which
+    /// Linux (per net.ipv4.ping_group_range) and macOS allow for regular users.
+    fn open_socket(ip: IpAddr) -> io::Result<(Socket, SocketKind)> {
+        let (domain, protocol) = match ip {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+        };
+        Socket::new(domain, Type::RAW, Some(protocol))
+            .map(|socket| (socket, SocketKind::Raw))
+            .or_else(|_| {
+                Socket::new(domain, Type::DGRAM, Some(protocol))
+                    .map(|socket| (socket, SocketKind::Datagram))
+            })
+    }
 
-        // Create raw ICMP socket
-        let Ok(socket) = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)) else {
-            return Self::failed_result(ip);
+    /// Check whether a received packet is the echo reply we are waiting for.
+    /// Returns the reply TTL (when the IP header is available) on a match.
+    fn match_echo_reply(
+        data: &[u8],
+        ip: IpAddr,
+        kind: SocketKind,
+        identifier: u16,
+        sequence: u16,
+    ) -> Option<Option<u8>> {
+        // IPv4 raw sockets (and datagram sockets on macOS) include the IP header;
+        // Linux datagram sockets and all ICMPv6 sockets deliver just the ICMP message
+        let (icmp, ttl) = match ip {
+            IpAddr::V4(_) if data.first().is_some_and(|b| b >> 4 == 4) => {
+                let header_len = ((data[0] & 0x0f) as usize) * 4;
+                (data.get(header_len..)?, data.get(8).copied())
+            }
+            _ => (data, None),
         };
+        if icmp.len() < 8 {
+            return None;
+        }
 
-        // Set timeout
-        let _ = socket.set_read_timeout(Some(Duration::from_millis(self.timeout_ms)));
-        let _ = socket.set_write_timeout(Some(Duration::from_millis(self.timeout_ms)));
+        let reply_type = if ip.is_ipv4() { 0 } else { 129 };
+        if icmp[0] != reply_type || icmp[1] != 0 {
+            return None;
+        }
+        if u16::from_be_bytes([icmp[6], icmp[7]]) != sequence {
+            return None;
+        }
+        // The kernel rewrites the identifier on datagram sockets, so only raw sockets can check it
+        if kind == SocketKind::Raw && u16::from_be_bytes([icmp[4], icmp[5]]) != identifier {
+            return None;
+        }
 
-        // Build and send echo request
-        let identifier = std::process::id() as u16;
-        let packet = Self::build_echo_request(identifier, sequence);
-        let addr = SocketAddr::new(IpAddr::V4(ip), 0);
+        Some(ttl)
+    }
 
-        if socket.send_to(&packet, &addr.into()).is_err() {
-            return Self::failed_result(ip);
-        }
+    /// Send one echo request to `ip` and wait for the matching reply
+    pub fn echo(&self, ip: IpAddr, sequence: u16) -> Result<EchoReply, EchoError> {
+        let (socket, kind) = Self::open_socket(ip).map_err(EchoError::Socket)?;
+        let timeout = Duration::from_millis(self.timeout_ms);
+        let _ = socket.set_write_timeout(Some(timeout));
 
-        // Wait for reply - use MaybeUninit buffer as required by socket2
-        let mut buffer: [MaybeUninit<u8>; 1024] = unsafe { MaybeUninit::uninit().assume_init() };
-        match socket.recv(&mut buffer) {
-            Ok(len) if len >= 28 => {
-                // Safety: we know `len` bytes are initialized
-                let buffer: &[u8] =
-                    unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, len) };
-                // IP header (20 bytes) + ICMP header (8 bytes minimum)
-                let icmp_type = buffer[20];
-                let ttl = buffer[8];
-
-                if icmp_type == 0 {
-                    // Echo Reply
-                    let rtt = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
-                    return IcmpResult {
-                        ip: IpAddr::V4(ip),
-                        alive: true,
-                        rtt_ms: Some(rtt),
-                        ttl: Some(ttl),
-                    };
+        let identifier = std::process::id() as u16;
+        let packet = match ip {
+            IpAddr::V4(_) => Self::build_echo_request(identifier, sequence),
+            IpAddr::V6(_) => Self::build_echo_request_v6(identifier, sequence),
+        };
+
+        let start = Instant::now();
+        let addr = SocketAddr::new(ip, 0);
+        socket
+            .send_to(&packet, &addr.into())
+            .map_err(EchoError::Send)?;
+
+        // Raw sockets see every ICMP packet on the host, so keep reading until our
+        // reply arrives or the timeout expires
+        let deadline = start + timeout;
+        let mut buffer = [MaybeUninit::<u8>::uninit(); 1024];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(EchoError::Timeout);
+            }
+            let _ = socket.set_read_timeout(Some(remaining));
+
+            let (len, from) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err(EchoError::Timeout);
                 }
+                Err(e) => return Err(EchoError::Receive(e)),
+            };
+            if from.as_socket().map(|a| a.ip()) != Some(ip) {
+                continue;
+            }
+
+            // Safety: recv_from initialized the first `len` bytes
+            let data: &[u8] =
+                unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, len) };
+            if let Some(ttl) = Self::match_echo_reply(data, ip, kind, identifier, sequence) {
+                return Ok(EchoReply {
+                    rtt: start.elapsed(),
+                    ttl,
+                });
             }
-            _ => {}
         }
+    }
 
-        Self::failed_result(ip)
+    /// Ping a single IP address
+    fn ping_ip(&self, ip: IpAddr, sequence: u16) -> IcmpResult {
+        match self.echo(ip, sequence) {
+            Ok(reply) => IcmpResult {
+                ip,
+                alive: true,
+                rtt_ms: Some(u64::try_from(reply.rtt.as_millis()).unwrap_or(u64::MAX)),
+                ttl: reply.ttl,
+            },
+            Err(_) => IcmpResult {
+                ip,
+                alive: false,
+                rtt_ms: None,
+                ttl: None,
+            },
+        }
     }
 
     /// Ping sweep multiple IP addresses
@@ -147,6 +282,7 @@ impl IcmpScanner {
             if let IpAddr::V4(ipv4) = ip {
                 let sem = semaphore.clone();
                 let timeout_ms = self.timeout_ms;
+                let retries = self.retries;
                 // Wrap sequence number at u16::MAX (65535) - this is fine for ICMP identification
                 let seq = (sequence % (u16::MAX as usize + 1)) as u16;
 
@@ -155,7 +291,14 @@ impl IcmpScanner {
                     let rt = tokio::runtime::Handle::current();
                     let _permit = rt.block_on(sem.acquire());
                     let scanner = IcmpScanner::new().with_timeout(timeout_ms);
-                    scanner.ping_ip(ipv4, seq)
+                    let mut result = scanner.ping_ip(IpAddr::V4(ipv4), seq);
+                    for _ in 0..retries {
+                        if result.alive {
+                            break;
+                        }
+                        result = scanner.ping_ip(IpAddr::V4(ipv4), seq);
+                    }
+                    result
                 }));
             }
         }
@@ -179,6 +322,57 @@ impl Default for IcmpScanner {
     }
 }
 
+/// The ping sweep as a scan type
+pub struct IcmpScan;
+
+impl Scanner for IcmpScan {
+    fn scan_type(&self) -> ScanType {
+        ScanType::ICMP
+    }
+
+    fn label(&self) -> &'static str {
+        "Ping"
+    }
+
+    fn description(&self) -> &'static str {
+        "ICMP echo sweep"
+    }
+
+    fn is_available(&self, privileges: &Privileges) -> bool {
+        privileges.raw_socket || privileges.icmp_datagram
+    }
+
+    fn tunables(&self) -> Tunables {
+        Tunables {
+            retries: true,
+            parallelism: true,
+        }
+    }
+
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let tuning = run.tuning();
+            let mut scanner = IcmpScanner::new().with_timeout(run.timeout_ms());
+            if let Some(parallelism) = tuning.parallelism {
+                scanner = scanner.with_parallelism(parallelism);
+            }
+            if let Some(retries) = tuning.retries {
+                scanner = scanner.with_retries(retries);
+            }
+            let scanner = &scanner;
+            run.scan_targets(1, move |ips| async move {
+                scanner
+                    .ping_sweep(ips)
+                    .await
+                    .into_iter()
+                    .map(ScanResult::Icmp)
+                    .collect::<Vec<_>>()
+            })
+            .await;
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,6 +417,59 @@ mod tests {
         assert!(checksum > 0);
     }
 
+    #[test]
+    fn test_echo_request_v6_building() {
+        let packet = IcmpScanner::build_echo_request_v6(1234, 5678);
+        assert_eq!(packet.len(), 8);
+        assert_eq!(packet[0], 128); // Type: Echo Request
+        assert_eq!(&packet[4..6], &1234u16.to_be_bytes());
+        assert_eq!(&packet[6..8], &5678u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_match_echo_reply_with_ip_header() {
+        let ip: IpAddr = "192.168.1.1".parse().unwrap();
+        let mut data = vec![0u8; 28];
+        data[0] = 0x45; // IPv4, 20-byte header
+        data[8] = 64; // TTL
+        data[20] = 0; // Echo Reply
+        data[24..26].copy_from_slice(&42u16.to_be_bytes());
+        data[26..28].copy_from_slice(&7u16.to_be_bytes());
+
+        assert_eq!(
+            IcmpScanner::match_echo_reply(&data, ip, SocketKind::Raw, 42, 7),
+            Some(Some(64))
+        );
+        // Wrong identifier or sequence is someone else's reply
+        assert_eq!(
+            IcmpScanner::match_echo_reply(&data, ip, SocketKind::Raw, 43, 7),
+            None
+        );
+        assert_eq!(
+            IcmpScanner::match_echo_reply(&data, ip, SocketKind::Raw, 42, 8),
+            None
+        );
+    }
+
+    #[test]
+    fn test_match_echo_reply_datagram_without_header() {
+        let ip: IpAddr = "192.168.1.1".parse().unwrap();
+        let mut data = vec![0u8; 8];
+        data[4..6].copy_from_slice(&999u16.to_be_bytes()); // kernel-assigned identifier
+        data[6..8].copy_from_slice(&7u16.to_be_bytes());
+
+        assert_eq!(
+            IcmpScanner::match_echo_reply(&data, ip, SocketKind::Datagram, 42, 7),
+            Some(None)
+        );
+        // An echo request (type 8) is not a reply
+        data[0] = 8;
+        assert_eq!(
+            IcmpScanner::match_echo_reply(&data, ip, SocketKind::Datagram, 42, 7),
+            None
+        );
+    }
+
     #[test]
     fn test_scanner_default() {
         let scanner = IcmpScanner::default();
@@ -0,0 +1,273 @@
+//! UPnP Internet Gateway Device port-mapping audit. Finds the WAN connection service in
+//! a gateway's device description and walks GetGenericPortMappingEntry to list the ports
+//! devices on the network have opened to the internet through it. Also asks the gateway
+//! for its WAN address.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use futures::StreamExt;
+
+use super::UpnpPortMapping;
+use super::ssdp::SsdpScanner;
+
+/// Services that hold a gateway's port mapping table
+const WAN_CONNECTION_SERVICES: &[&str] = &["WANIPConnection", "WANPPPConnection"];
+
+/// Entries read per gateway (guards against gateways that never report the end)
+const MAX_PORT_MAPPINGS: u32 = 256;
+
+/// UPnP error for an index past the last entry
+const ERROR_SPECIFIED_ARRAY_INDEX_INVALID: &str = "713";
+
+/// A gateway's WAN IP/PPP connection service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WanConnectionService {
+    pub service_type: String,
+    pub control_url: String,
+}
+
+/// Find the WAN connection service in a device description fetched from `location`
+pub fn find_wan_connection_service(
+    description: &str,
+    location: &str,
+) -> Option<WanConnectionService> {
+    let base = SsdpScanner::extract_xml_element(description, "URLBase")
+        .unwrap_or_else(|| location.to_string());
+    let base = url::Url::parse(&base).ok()?;
+
+    description.split("<service>").skip(1).find_map(|block| {
+        let block = block.split("</service>").next()?;
+        let service_type = SsdpScanner::extract_xml_element(block, "serviceType")?;
+        if !WAN_CONNECTION_SERVICES
+            .iter()
+            .any(|service| service_type.contains(service))
+        {
+            return None;
+        }
+        let control_url = SsdpScanner::extract_xml_element(block, "controlURL")?;
+        Some(WanConnectionService {
+            service_type,
+            control_url: base.join(&control_url).ok()?.to_string(),
+        })
+    })
+}
+
+/// Find the first gateway on the network that answers an SSDP search for an
+/// Internet Gateway Device and exposes a WAN connection service
+pub async fn discover_gateway(timeout: Duration) -> Option<WanConnectionService> {
+    let search_target = ssdp_client::SearchTarget::URN(ssdp_client::URN::device(
+        "schemas-upnp-org",
+        "InternetGatewayDevice",
+        1,
+    ));
+    let mut responses = ssdp_client::search(&search_target, timeout, 2, None)
+        .await
+        .ok()?;
+    while let Some(response) = responses.next().await {
+        let Ok(response) = response else {
+            continue;
+        };
+        let location = response.location().to_string();
+        if let Some(description) = SsdpScanner::fetch_device_description(&location).await
+            && let Some(service) = find_wan_connection_service(&description, &location)
+        {
+            return Some(service);
+        }
+    }
+    None
+}
+
+fn soap_request(service_type: &str, action: &str, arguments: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{action} xmlns:u=\"{service_type}\">{arguments}</u:{action}>\
+         </s:Body></s:Envelope>"
+    )
+}
+
+fn port_mapping_request(service_type: &str, index: u32) -> String {
+    soap_request(
+        service_type,
+        "GetGenericPortMappingEntry",
+        &format!("<NewPortMappingIndex>{}</NewPortMappingIndex>", index),
+    )
+}
+
+/// Parse a GetGenericPortMappingEntry response
+fn parse_port_mapping_entry(xml: &str) -> Option<UpnpPortMapping> {
+    let field = |name: &str| SsdpScanner::extract_xml_element(xml, name);
+    Some(UpnpPortMapping {
+        remote_host: field("NewRemoteHost").unwrap_or_default(),
+        external_port: field("NewExternalPort")?.parse().ok()?,
+        protocol: field("NewProtocol")?.to_uppercase(),
+        internal_client: field("NewInternalClient")?,
+        internal_port: field("NewInternalPort")?.parse().ok()?,
+        enabled: field("NewEnabled").is_none_or(|v| v == "1" || v.eq_ignore_ascii_case("true")),
+        description: field("NewPortMappingDescription"),
+        lease_duration: field("NewLeaseDuration")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0),
+    })
+}
+
+fn is_end_of_table(xml: &str) -> bool {
+    SsdpScanner::extract_xml_element(xml, "errorCode").as_deref()
+        == Some(ERROR_SPECIFIED_ARRAY_INDEX_INVALID)
+}
+
+/// Ask a gateway for the address its WAN connection was given
+pub async fn query_external_ip(
+    service: &WanConnectionService,
+    timeout: Duration,
+) -> Option<IpAddr> {
+    let client = reqwest::Client::builder().timeout(timeout).build().ok()?;
+    let body = client
+        .post(&service.control_url)
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header(
+            "SOAPAction",
+            format!("\"{}#GetExternalIPAddress\"", service.service_type),
+        )
+        .body(soap_request(
+            &service.service_type,
+            "GetExternalIPAddress",
+            "",
+        ))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .text()
+        .await
+        .ok()?;
+    SsdpScanner::extract_xml_element(&body, "NewExternalIPAddress")?
+        .parse()
+        .ok()
+}
+
+/// List a gateway's active port mappings. None when the gateway refuses the query;
+/// an empty list when it has none.
+pub async fn query_port_mappings(
+    service: &WanConnectionService,
+    timeout: Duration,
+) -> Option<Vec<UpnpPortMapping>> {
+    let client = reqwest::Client::builder().timeout(timeout).build().ok()?;
+    let soap_action = format!("\"{}#GetGenericPortMappingEntry\"", service.service_type);
+
+    let mut mappings = Vec::new();
+    for index in 0..MAX_PORT_MAPPINGS {
+        let response = client
+            .post(&service.control_url)
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header("SOAPAction", &soap_action)
+            .body(port_mapping_request(&service.service_type, index))
+            .send()
+            .await;
+        let body = match response {
+            Ok(response) if response.status().is_success() => response.text().await.ok(),
+            // Faults come back as HTTP 500 with a SOAP body
+            Ok(response) => response.text().await.ok().filter(|b| is_end_of_table(b)),
+            Err(_) => None,
+        };
+
+        match body.as_deref().map(|b| (b, parse_port_mapping_entry(b))) {
+            Some((_, Some(mapping))) => mappings.push(mapping),
+            Some((b, None)) if is_end_of_table(b) => break,
+            _ if index > 0 => break,
+            _ => return None,
+        }
+    }
+    Some(mappings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GATEWAY_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>
+        <controlURL>/ctl/L3F</controlURL>
+      </service>
+    </serviceList>
+    <deviceList><device><deviceList><device>
+      <serviceList>
+        <service>
+          <serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+          <controlURL>/ctl/IPConn</controlURL>
+        </service>
+      </serviceList>
+    </device></deviceList></device></deviceList>
+  </device>
+</root>"#;
+
+    #[test]
+    fn test_find_wan_connection_service() {
+        assert_eq!(
+            find_wan_connection_service(
+                GATEWAY_DESCRIPTION,
+                "http://192.168.1.1:5000/rootDesc.xml"
+            ),
+            Some(WanConnectionService {
+                service_type: "urn:schemas-upnp-org:service:WANIPConnection:1".to_string(),
+                control_url: "http://192.168.1.1:5000/ctl/IPConn".to_string(),
+            })
+        );
+
+        let media_server = GATEWAY_DESCRIPTION.replace("WANIPConnection", "ContentDirectory");
+        assert!(find_wan_connection_service(&media_server, "http://192.168.1.1:5000/").is_none());
+    }
+
+    #[test]
+    fn test_parse_port_mapping_entry() {
+        let response = r#"<s:Envelope><s:Body>
+<u:GetGenericPortMappingEntryResponse xmlns:u="urn:schemas-upnp-org:service:WANIPConnection:1">
+<NewRemoteHost></NewRemoteHost>
+<NewExternalPort>8080</NewExternalPort>
+<NewProtocol>tcp</NewProtocol>
+<NewInternalPort>80</NewInternalPort>
+<NewInternalClient>192.168.1.50</NewInternalClient>
+<NewEnabled>1</NewEnabled>
+<NewPortMappingDescription>IP Camera</NewPortMappingDescription>
+<NewLeaseDuration>0</NewLeaseDuration>
+</u:GetGenericPortMappingEntryResponse></s:Body></s:Envelope>"#;
+
+        let mapping = parse_port_mapping_entry(response).unwrap();
+        assert_eq!(mapping.remote_host, "");
+        assert_eq!(mapping.external_port, 8080);
+        assert_eq!(mapping.protocol, "TCP");
+        assert_eq!(mapping.internal_client, "192.168.1.50");
+        assert_eq!(mapping.internal_port, 80);
+        assert!(mapping.enabled);
+        assert_eq!(mapping.description.as_deref(), Some("IP Camera"));
+        assert!(!is_end_of_table(response));
+    }
+
+    #[test]
+    fn test_port_mapping_request() {
+        let request = port_mapping_request("urn:schemas-upnp-org:service:WANIPConnection:1", 3);
+        assert!(request.contains(
+            "<u:GetGenericPortMappingEntry xmlns:u=\"urn:schemas-upnp-org:service:WANIPConnection:1\">"
+        ));
+        assert!(request.contains("<NewPortMappingIndex>3</NewPortMappingIndex>"));
+        assert!(request.contains("</u:GetGenericPortMappingEntry>"));
+    }
+
+    #[test]
+    fn test_end_of_table_fault() {
+        let fault = r#"<s:Envelope><s:Body><s:Fault><faultcode>s:Client</faultcode>
+<detail><UPnPError><errorCode>713</errorCode>
+<errorDescription>SpecifiedArrayIndexInvalid</errorDescription></UPnPError></detail>
+</s:Fault></s:Body></s:Envelope>"#;
+        assert!(is_end_of_table(fault));
+        assert!(parse_port_mapping_entry(fault).is_none());
+    }
+}
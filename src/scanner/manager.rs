@@ -1,23 +1,133 @@
-//! Scan orchestration. Manages concurrent scanner execution with configurable scan types,
-//! timeout settings, progress tracking, and stop signaling.
+//! Scan orchestration. Runs the registered scanners (see `registry`) of the requested
+//! scan types concurrently (bounded by `max_concurrent_scan_types`) under a shared
+//! probe-rate budget, tracks progress per scan type, and lets individual scan types or
+//! the whole scan be cancelled. Scan types that probe addresses one by one can be
+//! pointed at hosts passive capture has recently seen instead of sweeping whole subnets
+//! (see `TargetStrategy`).
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::net::IpAddr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use ipnetwork::Ipv4Network;
 use pnet::datalink;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, Semaphore, mpsc};
 
-use super::arp::ArpScanner;
-use super::icmp::IcmpScanner;
-use super::ndp::NdpScanner;
-use super::netbios::NetBiosScanner;
-use super::port::{DEFAULT_PORTS, PortScanner};
-use super::snmp::SnmpScanner;
-use super::ssdp::SsdpScanner;
-use super::{ScanResult, ScanType, check_scan_privileges};
+use crate::supervisor::panic_message;
+
+use super::arp::{DEFAULT_ARP_PARALLELISM, DEFAULT_ARP_RETRIES};
+use super::port::DEFAULT_PORTS;
+use super::registry::{self, Scanner};
+use super::snmp::SnmpCredentials;
+use super::{RateBudget, ScanProgress, ScanResult, ScanType, check_privileges};
+
+/// Reads the SNMP community list and per-device credentials at the start of an SNMP scan
+pub type SnmpCredentialsLoader = Arc<dyn Fn() -> Option<SnmpCredentials> + Send + Sync>;
+
+/// Reads the addresses seen in passive capture since a unix time, at the start of a scan
+/// that has a scan type using a non-sweep `TargetStrategy`
+pub type ObservedAddressesLoader = Arc<dyn Fn(i64) -> Option<HashSet<IpAddr>> + Send + Sync>;
+
+/// Scan types run at the same time by default
+pub const DEFAULT_MAX_CONCURRENT_SCAN_TYPES: usize = 3;
+
+/// Probes per second shared by all running scan types by default
+pub const DEFAULT_MAX_PROBES_PER_SEC: u32 = 1000;
+
+/// Targets handed to a scanner at a time; progress, results and the rate budget
+/// advance per chunk
+const TARGET_CHUNK: usize = 64;
+
+/// How often a running scan type checks whether it was cancelled
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How far back passive capture counts an address as recently seen by default
+pub const DEFAULT_OBSERVED_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Share of unseen addresses the observed strategy still probes by default
+pub const DEFAULT_UNSEEN_SAMPLE_PERCENT: u8 = 5;
+
+/// Settings key the scan config is saved under, as JSON
+pub const SCAN_CONFIG_SETTING: &str = "scan_config";
+
+/// Bounds checked by `ScanConfig::validate`
+const TIMEOUT_MS_RANGE: std::ops::RangeInclusive<u64> = 50..=60_000;
+const MAX_RETRIES: u8 = 10;
+const MAX_PARALLELISM: usize = 1024;
+const PROBES_PER_SEC_RANGE: std::ops::RangeInclusive<u32> = 1..=100_000;
+
+/// Which addresses a scan type probes. Applies to the scan types that probe addresses one
+/// by one through `ScanRun::scan_targets` (ICMP, port, NetBIOS, SNMP); ARP and the
+/// discovery scans always cover everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetStrategy {
+    /// Every address of the local subnets, in order
+    #[default]
+    Sweep,
+    /// Every address, starting with those recently seen in passive capture
+    Prioritized,
+    /// Recently seen addresses plus a sample of `unseen_sample_percent` of the rest
+    Observed,
+}
+
+/// Events emitted by a running scan, in order
+#[derive(Debug, Clone)]
+pub enum ScanEvent {
+    Result(ScanResult),
+    /// Sent after the last result of a run
+    Completed {
+        started_at: i64,
+        finished_at: i64,
+        scan_types: Vec<ScanType>,
+        /// The run was stopped (or a scan type cancelled) before every phase finished
+        stopped: bool,
+    },
+    /// A scan type panicked; sent before the run's `Completed`
+    Failed {
+        scan_type: ScanType,
+        error: String,
+    },
+}
+
+/// Where a scan type is in its run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PhaseState {
+    /// Waiting for a concurrency slot
+    Pending,
+    Running,
+    Completed,
+    Cancelled,
+    /// Not permitted with the current privileges, or no scanner is registered for it
+    Skipped,
+    /// The scanner panicked
+    Failed,
+}
+
+impl PhaseState {
+    fn is_finished(self) -> bool {
+        !matches!(self, PhaseState::Pending | PhaseState::Running)
+    }
+}
+
+/// Progress of one scan type for API responses
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseStatus {
+    pub scan_type: ScanType,
+    pub state: PhaseState,
+    /// Targets probed so far (0 for discovery scans without a target list)
+    pub targets_scanned: u64,
+    pub targets_total: u64,
+    pub progress_percent: u8,
+    pub discovered_count: u32,
+    pub elapsed_secs: u64,
+    pub estimated_remaining_secs: Option<u64>,
+}
 
 /// Scan status for API responses
 #[derive(Debug, Clone, Serialize)]
@@ -28,6 +138,330 @@ pub struct ScanStatus {
     pub discovered_count: u32,
     pub last_scan_time: Option<i64>,
     pub current_phase: Option<String>,
+    /// Unix timestamp the running scan started at
+    pub started_at: Option<i64>,
+    /// Estimated seconds until the running scan completes
+    pub estimated_remaining_secs: Option<u64>,
+    /// Per scan type progress of the running (or last) scan
+    pub phases: Vec<PhaseStatus>,
+}
+
+/// Seconds left if `fraction` of the work took `elapsed_secs`
+fn estimate_remaining(elapsed_secs: f64, fraction: f64) -> Option<u64> {
+    if fraction <= 0.0 {
+        return None;
+    }
+    Some((elapsed_secs / fraction * (1.0 - fraction)).round() as u64)
+}
+
+impl ScanStatus {
+    /// Set overall progress from a fraction in 0..=1 and re-estimate time remaining
+    fn set_progress(&mut self, fraction: f64) {
+        let fraction = fraction.clamp(0.0, 1.0);
+        self.progress_percent = (fraction * 100.0) as u8;
+        self.estimated_remaining_secs = self.started_at.and_then(|started| {
+            let elapsed = (chrono::Utc::now().timestamp() - started).max(0) as f64;
+            estimate_remaining(elapsed, fraction)
+        });
+    }
+}
+
+/// Live state of one scan type in a scan, shared between its task and status readers
+struct Phase {
+    scan_type: ScanType,
+    state: Mutex<PhaseState>,
+    progress: ScanProgress,
+    targets: AtomicU64,
+    discovered: AtomicU32,
+    cancelled: AtomicBool,
+    /// Unix timestamps, 0 until set
+    started_at: AtomicI64,
+    finished_at: AtomicI64,
+}
+
+impl Phase {
+    fn new(scan_type: ScanType) -> Self {
+        Self {
+            scan_type,
+            state: Mutex::new(PhaseState::Pending),
+            progress: ScanProgress::default(),
+            targets: AtomicU64::new(0),
+            discovered: AtomicU32::new(0),
+            cancelled: AtomicBool::new(false),
+            started_at: AtomicI64::new(0),
+            finished_at: AtomicI64::new(0),
+        }
+    }
+
+    fn state(&self) -> PhaseState {
+        self.state
+            .lock()
+            .map(|s| *s)
+            .unwrap_or(PhaseState::Cancelled)
+    }
+
+    fn set_state(&self, state: PhaseState) {
+        if let Ok(mut s) = self.state.lock() {
+            *s = state;
+        }
+        let now = chrono::Utc::now().timestamp();
+        match state {
+            PhaseState::Running => self.started_at.store(now, Ordering::Relaxed),
+            PhaseState::Pending => {}
+            _ => self.finished_at.store(now, Ordering::Relaxed),
+        }
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    async fn wait_cancelled(&self) {
+        while !self.is_cancelled() {
+            tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Share of the scan type's work that is done; finished phases count as done
+    fn fraction(&self) -> f64 {
+        match self.state() {
+            PhaseState::Pending => 0.0,
+            PhaseState::Running => self.progress.fraction(),
+            _ => 1.0,
+        }
+    }
+
+    fn status(&self, now: i64) -> PhaseStatus {
+        let state = self.state();
+        // Unlike `fraction`, report how far a cancelled scan type actually got
+        let fraction = match state {
+            PhaseState::Completed => 1.0,
+            PhaseState::Pending | PhaseState::Skipped => 0.0,
+            PhaseState::Running | PhaseState::Cancelled | PhaseState::Failed => {
+                self.progress.fraction()
+            }
+        };
+        let targets_total = self.targets.load(Ordering::Relaxed);
+        let started_at = self.started_at.load(Ordering::Relaxed);
+        let finished_at = self.finished_at.load(Ordering::Relaxed);
+        let elapsed_secs = match (started_at, finished_at) {
+            (0, _) => 0,
+            (started, 0) => (now - started).max(0) as u64,
+            (started, finished) => (finished - started).max(0) as u64,
+        };
+        PhaseStatus {
+            scan_type: self.scan_type,
+            state,
+            targets_scanned: (fraction * targets_total as f64).round() as u64,
+            targets_total,
+            progress_percent: (fraction * 100.0) as u8,
+            discovered_count: self.discovered.load(Ordering::Relaxed),
+            elapsed_secs,
+            estimated_remaining_secs: match state {
+                PhaseState::Running => estimate_remaining(elapsed_secs as f64, fraction),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Everything a scan type needs while it runs, handed to its `Scanner`
+pub struct ScanRun {
+    scanner: Arc<dyn Scanner>,
+    phase: Arc<Phase>,
+    config: ScanConfig,
+    subnets: Arc<Vec<Ipv4Network>>,
+    budget: Arc<RateBudget>,
+    /// The scan type's own rate cap, within the shared budget
+    type_budget: Option<RateBudget>,
+    event_tx: mpsc::Sender<ScanEvent>,
+    status: Arc<RwLock<ScanStatus>>,
+    snmp_credentials: Option<SnmpCredentialsLoader>,
+    /// Addresses seen in passive capture within the observed window
+    observed: Arc<HashSet<IpAddr>>,
+    /// Unique IPs found by any scan type in this scan
+    discovered_ips: Arc<Mutex<HashSet<IpAddr>>>,
+}
+
+impl ScanRun {
+    /// Run the scan type until it finishes or is cancelled. A cancelled scanner is
+    /// dropped mid-flight; probes already handed to blocking threads finish unreported.
+    async fn run(self) {
+        if self.phase.is_cancelled() {
+            self.phase.set_state(PhaseState::Cancelled);
+            return;
+        }
+        self.phase.set_state(PhaseState::Running);
+
+        tokio::select! {
+            _ = self.scanner.run(&self) => {}
+            _ = self.phase.wait_cancelled() => {}
+        }
+
+        self.phase.set_state(if self.phase.is_cancelled() {
+            PhaseState::Cancelled
+        } else {
+            PhaseState::Completed
+        });
+    }
+
+    /// Scan config as of the start of the scan
+    pub fn config(&self) -> &ScanConfig {
+        &self.config
+    }
+
+    /// Local IPv4 subnets
+    pub fn subnets(&self) -> &[Ipv4Network] {
+        &self.subnets
+    }
+
+    /// Work counter for the scan type's progress
+    pub fn progress(&self) -> &ScanProgress {
+        &self.phase.progress
+    }
+
+    /// Record how many hosts the scan type covers
+    pub fn set_targets(&self, targets: u64) {
+        self.phase.targets.store(targets, Ordering::Relaxed);
+    }
+
+    /// The scan type's tunables, falling back to the scan-wide timeout
+    pub fn tuning(&self) -> ScanTuning {
+        self.config.tuning(self.phase.scan_type)
+    }
+
+    /// Timeout for the scan type's probes
+    pub fn timeout_ms(&self) -> u64 {
+        self.config.timeout_ms_for(self.phase.scan_type)
+    }
+
+    /// Wait for room in the scan type's rate cap and the scan's shared rate budget, then
+    /// reserve `probes` probes
+    pub async fn acquire(&self, probes: u64) {
+        if let Some(budget) = &self.type_budget {
+            budget.acquire(probes).await;
+        }
+        self.budget.acquire(probes).await;
+    }
+
+    /// SNMP communities and per-device credentials, when a loader is configured
+    pub async fn snmp_credentials(&self) -> Option<SnmpCredentials> {
+        let load = self.snmp_credentials.clone()?;
+        tokio::task::spawn_blocking(move || load()).await.ok()?
+    }
+
+    /// Run a discovery scan that has no target list, as one unit of progress
+    pub async fn discover<Fut>(&self, scan: Fut)
+    where
+        Fut: Future<Output = Vec<ScanResult>>,
+    {
+        self.phase.progress.add_total(1);
+        self.acquire(1).await;
+        let results = scan.await;
+        self.phase.progress.advance(1);
+        self.report(results).await;
+    }
+
+    /// Probe the addresses of the local subnets picked by the scan type's target strategy
+    /// in chunks, reserving `probes_per_target` probes per address from the rate budget
+    pub async fn scan_targets<F, Fut>(&self, probes_per_target: u64, scan_chunk: F)
+    where
+        F: Fn(Vec<IpAddr>) -> Fut,
+        Fut: Future<Output = Vec<ScanResult>>,
+    {
+        let all: Vec<IpAddr> = self
+            .subnets
+            .iter()
+            .flat_map(|subnet| subnet.iter().map(IpAddr::V4))
+            .collect();
+        let strategy = self
+            .config
+            .target_strategies
+            .get(&self.phase.scan_type)
+            .copied()
+            .unwrap_or_default();
+        let offset = uuid::Uuid::new_v4().as_u128() as usize;
+        let targets = select_targets(
+            all,
+            strategy,
+            &self.observed,
+            self.config.unseen_sample_percent,
+            offset,
+        );
+        self.phase
+            .targets
+            .store(targets.len() as u64, Ordering::Relaxed);
+        self.phase
+            .progress
+            .add_total(targets.len() as u64 * probes_per_target);
+
+        for chunk in targets.chunks(TARGET_CHUNK) {
+            let probes = chunk.len() as u64 * probes_per_target;
+            self.acquire(probes).await;
+            let results = scan_chunk(chunk.to_vec()).await;
+            self.phase.progress.advance(probes);
+            self.report(results).await;
+        }
+    }
+
+    /// Send results and update discovered counts
+    pub async fn report(&self, results: Vec<ScanResult>) {
+        let mut found = HashSet::new();
+        for result in results {
+            found.insert(result.ip());
+            let _ = self.event_tx.send(ScanEvent::Result(result)).await;
+        }
+        if found.is_empty() {
+            return;
+        }
+
+        self.phase
+            .discovered
+            .fetch_add(found.len().min(u32::MAX as usize) as u32, Ordering::Relaxed);
+        let total = match self.discovered_ips.lock() {
+            Ok(mut ips) => {
+                ips.extend(found);
+                ips.len()
+            }
+            Err(_) => return,
+        };
+        self.status.write().await.discovered_count = total.min(u32::MAX as usize) as u32;
+    }
+}
+
+/// Order `all` by `strategy`, and for `Observed` drop all but a sample of the addresses
+/// not in `observed`. The sample takes every n-th unseen address starting at `offset`, so
+/// successive scans with random offsets cover different parts of the unseen space.
+fn select_targets(
+    all: Vec<IpAddr>,
+    strategy: TargetStrategy,
+    observed: &HashSet<IpAddr>,
+    sample_percent: u8,
+    offset: usize,
+) -> Vec<IpAddr> {
+    if strategy == TargetStrategy::Sweep {
+        return all;
+    }
+    let (seen, unseen): (Vec<IpAddr>, Vec<IpAddr>) =
+        all.into_iter().partition(|ip| observed.contains(ip));
+    let unseen = match strategy {
+        TargetStrategy::Observed if sample_percent == 0 => Vec::new(),
+        TargetStrategy::Observed => {
+            let stride = 100 / usize::from(sample_percent.min(100));
+            unseen
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| (i + offset % stride).is_multiple_of(stride))
+                .map(|(_, ip)| ip)
+                .collect()
+        }
+        _ => unseen,
+    };
+    seen.into_iter().chain(unseen).collect()
 }
 
 /// Scan configuration
@@ -37,22 +471,211 @@ pub struct ScanConfig {
     pub enabled_scanners: HashSet<ScanType>,
     pub ports: Vec<u16>,
     pub timeout_ms: u64,
+    /// ARP requests sent per batch
+    #[serde(default = "default_arp_parallelism")]
+    pub arp_parallelism: usize,
+    /// Retry rounds for hosts that did not answer ARP
+    #[serde(default = "default_arp_retries")]
+    pub arp_retries: u8,
+    /// Scan types run at the same time
+    #[serde(default = "default_max_concurrent_scan_types")]
+    pub max_concurrent_scan_types: usize,
+    /// Probes per second shared by all running scan types
+    #[serde(default = "default_max_probes_per_sec")]
+    pub max_probes_per_sec: u32,
+    /// NetBIOS scans also list shares over an anonymous SMB session on port 445
+    #[serde(default = "default_enumerate_smb_shares")]
+    pub enumerate_smb_shares: bool,
+    /// Target strategy per scan type; scan types not listed sweep
+    #[serde(default)]
+    pub target_strategies: HashMap<ScanType, TargetStrategy>,
+    /// How far back passive capture counts an address as recently seen
+    #[serde(default = "default_observed_window_secs")]
+    pub observed_window_secs: u64,
+    /// Percent of unseen addresses still probed by the observed strategy
+    #[serde(default = "default_unseen_sample_percent")]
+    pub unseen_sample_percent: u8,
+    /// Timeout, retry, parallelism and rate overrides per scan type
+    #[serde(default)]
+    pub tuning: HashMap<ScanType, ScanTuning>,
+}
+
+/// Overrides for one scan type. Unset fields fall back to the scan-wide timeout and rate
+/// budget, or to the scanner's own retries and parallelism. Retries and parallelism only
+/// apply to scanners that take them (see `Scanner::tunables`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanTuning {
+    pub timeout_ms: Option<u64>,
+    /// Extra attempts for hosts that did not answer
+    pub retries: Option<u8>,
+    /// Probes in flight at once
+    pub parallelism: Option<usize>,
+    /// Probes per second for this scan type, within `max_probes_per_sec`
+    pub max_probes_per_sec: Option<u32>,
+}
+
+impl ScanConfig {
+    /// A scan type's tunables (all unset when it has none)
+    pub fn tuning(&self, scan_type: ScanType) -> ScanTuning {
+        self.tuning.get(&scan_type).copied().unwrap_or_default()
+    }
+
+    /// Probe timeout for a scan type during a scan
+    pub fn timeout_ms_for(&self, scan_type: ScanType) -> u64 {
+        self.tuning(scan_type).timeout_ms.unwrap_or(self.timeout_ms)
+    }
+
+    /// Timeout for an on-demand probe of a single host: the scan type's own timeout when
+    /// one is set, else `default_ms`
+    pub fn probe_timeout_ms(&self, scan_type: ScanType, default_ms: u64) -> u64 {
+        self.tuning(scan_type).timeout_ms.unwrap_or(default_ms)
+    }
+
+    /// Check every value is in range and every tuned scan type takes its tunables
+    pub fn validate(&self) -> Result<(), String> {
+        let check_timeout = |name: &str, timeout_ms: u64| {
+            if TIMEOUT_MS_RANGE.contains(&timeout_ms) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} must be between {} and {} ms",
+                    name,
+                    TIMEOUT_MS_RANGE.start(),
+                    TIMEOUT_MS_RANGE.end()
+                ))
+            }
+        };
+        let check_rate = |name: &str, probes_per_sec: u32| {
+            if PROBES_PER_SEC_RANGE.contains(&probes_per_sec) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} must be between {} and {} probes per second",
+                    name,
+                    PROBES_PER_SEC_RANGE.start(),
+                    PROBES_PER_SEC_RANGE.end()
+                ))
+            }
+        };
+
+        check_timeout("timeout_ms", self.timeout_ms)?;
+        check_rate("max_probes_per_sec", self.max_probes_per_sec)?;
+        if !(1..=MAX_PARALLELISM).contains(&self.arp_parallelism) {
+            return Err(format!(
+                "arp_parallelism must be between 1 and {}",
+                MAX_PARALLELISM
+            ));
+        }
+        if self.arp_retries > MAX_RETRIES {
+            return Err(format!("arp_retries must be at most {}", MAX_RETRIES));
+        }
+        if self.max_concurrent_scan_types == 0 {
+            return Err("max_concurrent_scan_types must be at least 1".to_string());
+        }
+        if self.unseen_sample_percent > 100 {
+            return Err("unseen_sample_percent must be at most 100".to_string());
+        }
+        if self.ports.contains(&0) {
+            return Err("ports must be between 1 and 65535".to_string());
+        }
+
+        for (scan_type, tuning) in &self.tuning {
+            let scanner = registry::get(*scan_type)
+                .ok_or_else(|| format!("no scanner is registered for {}", scan_type))?;
+            let tunables = scanner.tunables();
+            if let Some(timeout_ms) = tuning.timeout_ms {
+                check_timeout(&format!("{} timeout_ms", scan_type), timeout_ms)?;
+            }
+            if let Some(probes_per_sec) = tuning.max_probes_per_sec {
+                check_rate(&format!("{} max_probes_per_sec", scan_type), probes_per_sec)?;
+            }
+            match tuning.retries {
+                Some(_) if !tunables.retries => {
+                    return Err(format!("{} scans don't take retries", scan_type));
+                }
+                Some(retries) if retries > MAX_RETRIES => {
+                    return Err(format!(
+                        "{} retries must be at most {}",
+                        scan_type, MAX_RETRIES
+                    ));
+                }
+                _ => {}
+            }
+            match tuning.parallelism {
+                Some(_) if !tunables.parallelism => {
+                    return Err(format!("{} scans don't take a parallelism", scan_type));
+                }
+                Some(parallelism) if !(1..=MAX_PARALLELISM).contains(&parallelism) => {
+                    return Err(format!(
+                        "{} parallelism must be between 1 and {}",
+                        scan_type, MAX_PARALLELISM
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a scan config saved as JSON, checking it as `validate` does
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let config: ScanConfig = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn default_arp_parallelism() -> usize {
+    DEFAULT_ARP_PARALLELISM
+}
+
+fn default_arp_retries() -> u8 {
+    DEFAULT_ARP_RETRIES
+}
+
+fn default_max_concurrent_scan_types() -> usize {
+    DEFAULT_MAX_CONCURRENT_SCAN_TYPES
+}
+
+fn default_max_probes_per_sec() -> u32 {
+    DEFAULT_MAX_PROBES_PER_SEC
+}
+
+fn default_enumerate_smb_shares() -> bool {
+    true
+}
+
+fn default_observed_window_secs() -> u64 {
+    DEFAULT_OBSERVED_WINDOW_SECS
+}
+
+fn default_unseen_sample_percent() -> u8 {
+    DEFAULT_UNSEEN_SAMPLE_PERCENT
 }
 
 impl Default for ScanConfig {
     fn default() -> Self {
-        let mut enabled = HashSet::new();
-        enabled.insert(ScanType::Arp);
-        enabled.insert(ScanType::Ndp); // IPv6 neighbor discovery
-        enabled.insert(ScanType::NetBios); // NetBIOS name discovery
-        enabled.insert(ScanType::Ssdp);
-        enabled.insert(ScanType::Snmp); // SNMP device discovery
+        let enabled = registry::scanners()
+            .iter()
+            .filter(|scanner| scanner.enabled_by_default())
+            .map(|scanner| scanner.scan_type())
+            .collect();
 
         Self {
             scan_interval_secs: None,
             enabled_scanners: enabled,
             ports: DEFAULT_PORTS.to_vec(),
             timeout_ms: 1000,
+            arp_parallelism: DEFAULT_ARP_PARALLELISM,
+            arp_retries: DEFAULT_ARP_RETRIES,
+            max_concurrent_scan_types: DEFAULT_MAX_CONCURRENT_SCAN_TYPES,
+            max_probes_per_sec: DEFAULT_MAX_PROBES_PER_SEC,
+            enumerate_smb_shares: default_enumerate_smb_shares(),
+            target_strategies: HashMap::new(),
+            observed_window_secs: DEFAULT_OBSERVED_WINDOW_SECS,
+            unseen_sample_percent: DEFAULT_UNSEEN_SAMPLE_PERCENT,
+            tuning: HashMap::new(),
         }
     }
 }
@@ -61,12 +684,15 @@ impl Default for ScanConfig {
 pub struct ScanManager {
     status: Arc<RwLock<ScanStatus>>,
     config: Arc<RwLock<ScanConfig>>,
-    result_tx: mpsc::Sender<ScanResult>,
-    stop_signal: Arc<RwLock<bool>>,
+    event_tx: mpsc::Sender<ScanEvent>,
+    /// Scan types of the running (or last) scan
+    phases: Arc<RwLock<Vec<Arc<Phase>>>>,
+    snmp_credentials: Option<SnmpCredentialsLoader>,
+    observed_addresses: Option<ObservedAddressesLoader>,
 }
 
 impl ScanManager {
-    pub fn new(result_tx: mpsc::Sender<ScanResult>) -> Self {
+    pub fn new(event_tx: mpsc::Sender<ScanEvent>) -> Self {
         Self {
             status: Arc::new(RwLock::new(ScanStatus {
                 running: false,
@@ -75,16 +701,61 @@ impl ScanManager {
                 discovered_count: 0,
                 last_scan_time: None,
                 current_phase: None,
+                started_at: None,
+                estimated_remaining_secs: None,
+                phases: Vec::new(),
             })),
             config: Arc::new(RwLock::new(ScanConfig::default())),
-            result_tx,
-            stop_signal: Arc::new(RwLock::new(false)),
+            event_tx,
+            phases: Arc::new(RwLock::new(Vec::new())),
+            snmp_credentials: None,
+            observed_addresses: None,
         }
     }
 
-    /// Get current scan status
+    /// Load SNMP communities with `loader` for each SNMP scan instead of using the defaults
+    pub fn with_snmp_credentials(mut self, loader: SnmpCredentialsLoader) -> Self {
+        self.snmp_credentials = Some(loader);
+        self
+    }
+
+    /// Look up recently seen addresses with `loader` for scan types that don't sweep
+    pub fn with_observed_addresses(mut self, loader: ObservedAddressesLoader) -> Self {
+        self.observed_addresses = Some(loader);
+        self
+    }
+
+    /// Start from a saved config instead of the defaults
+    pub fn with_config(mut self, config: ScanConfig) -> Self {
+        self.config = Arc::new(RwLock::new(config));
+        self
+    }
+
+    /// Get current scan status, with progress computed from the scan types' counters
     pub async fn get_status(&self) -> ScanStatus {
-        self.status.read().await.clone()
+        let mut status = self.status.read().await.clone();
+        let phases = self.phases.read().await;
+        if phases.is_empty() {
+            return status;
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        status.phases = phases.iter().map(|phase| phase.status(now)).collect();
+        if status.running {
+            let fraction =
+                phases.iter().map(|phase| phase.fraction()).sum::<f64>() / phases.len() as f64;
+            status.set_progress(fraction);
+
+            let running: Vec<String> = phases
+                .iter()
+                .filter(|phase| phase.state() == PhaseState::Running)
+                .map(|phase| format!("{} scan", phase.scan_type))
+                .collect();
+            if !running.is_empty() {
+                status.current_phase = Some(running.join(", "));
+            }
+        }
+        status
     }
 
     /// Get current config
@@ -118,189 +789,160 @@ impl ScanManager {
 
     /// Start a manual scan
     pub async fn start_scan(&self, scan_types: Vec<ScanType>) -> Result<(), String> {
-        // Check if already running
+        let started_at = chrono::Utc::now().timestamp();
+
+        // Check if already running, and claim the run in the same step
         {
-            let status = self.status.read().await;
+            let mut status = self.status.write().await;
             if status.running {
                 return Err("Scan already in progress".to_string());
             }
-        }
-
-        // Reset stop signal
-        *self.stop_signal.write().await = false;
-
-        // Update status
-        {
-            let mut status = self.status.write().await;
             status.running = true;
             status.scan_types = scan_types.clone();
             status.progress_percent = 0;
             status.discovered_count = 0;
             status.current_phase = Some("Starting".to_string());
+            status.started_at = Some(started_at);
+            status.estimated_remaining_secs = None;
+            status.phases = Vec::new();
         }
 
+        let mut seen = HashSet::new();
+        let phases: Vec<Arc<Phase>> = scan_types
+            .iter()
+            .filter(|scan_type| seen.insert(**scan_type))
+            .map(|scan_type| Arc::new(Phase::new(*scan_type)))
+            .collect();
+        *self.phases.write().await = phases.clone();
+
         let status = self.status.clone();
         let config = self.config.clone();
-        let result_tx = self.result_tx.clone();
-        let stop_signal = self.stop_signal.clone();
+        let event_tx = self.event_tx.clone();
+        let snmp_credentials = self.snmp_credentials.clone();
+        let observed_addresses = self.observed_addresses.clone();
 
         // Spawn the scan task
         tokio::spawn(async move {
             let cfg = config.read().await.clone();
-            let subnets = Self::get_local_subnets();
-            let capabilities = check_scan_privileges();
-
-            let total_phases: usize = scan_types.len();
-            let mut completed_phases: usize = 0;
-            let mut discovered_ips: HashSet<IpAddr> = HashSet::new();
+            let subnets = Arc::new(Self::get_local_subnets());
+            let privileges = check_privileges();
+            let budget = Arc::new(RateBudget::new(cfg.max_probes_per_sec));
+            let slots = Arc::new(Semaphore::new(cfg.max_concurrent_scan_types.max(1)));
+            let discovered_ips = Arc::new(Mutex::new(HashSet::new()));
 
-            for scan_type in &scan_types {
-                // Check stop signal
-                if *stop_signal.read().await {
-                    break;
+            let wants_observed = phases.iter().any(|phase| {
+                cfg.target_strategies
+                    .get(&phase.scan_type)
+                    .is_some_and(|strategy| *strategy != TargetStrategy::Sweep)
+            });
+            let observed = match observed_addresses {
+                Some(load) if wants_observed => {
+                    let since = started_at - cfg.observed_window_secs as i64;
+                    tokio::task::spawn_blocking(move || load(since))
+                        .await
+                        .ok()
+                        .flatten()
+                        .unwrap_or_default()
                 }
+                _ => HashSet::new(),
+            };
+            let observed = Arc::new(observed);
 
-                // Update phase
-                {
-                    let mut s = status.write().await;
-                    s.current_phase = Some(format!("{} scan", scan_type));
-                }
-
-                let results: Vec<ScanResult> = match scan_type {
-                    ScanType::Arp if capabilities.can_arp => {
-                        let mut all_results = Vec::new();
-                        for subnet in &subnets {
-                            if *stop_signal.read().await {
-                                break;
-                            }
-                            let scanner = ArpScanner::new().with_timeout(cfg.timeout_ms);
-                            let results = scanner.scan_subnet(*subnet).await;
-                            all_results.extend(results.into_iter().map(ScanResult::Arp));
-                        }
-                        all_results
-                    }
-                    ScanType::Icmp if capabilities.can_icmp => {
-                        let mut all_ips = Vec::new();
-                        for subnet in &subnets {
-                            all_ips.extend(subnet.iter().map(IpAddr::V4));
-                        }
-                        let scanner = IcmpScanner::new().with_timeout(cfg.timeout_ms);
-                        scanner
-                            .ping_sweep(all_ips)
-                            .await
-                            .into_iter()
-                            .map(ScanResult::Icmp)
-                            .collect()
-                    }
-                    ScanType::Port => {
-                        // Port scan known IPs from previous scans
-                        // For now, scan subnet
-                        let mut all_ips = Vec::new();
-                        for subnet in &subnets {
-                            all_ips.extend(subnet.iter().map(IpAddr::V4));
-                        }
-                        let scanner = PortScanner::new().with_timeout(cfg.timeout_ms);
-                        scanner
-                            .scan_ips(&all_ips, &cfg.ports)
-                            .await
-                            .into_iter()
-                            .map(ScanResult::Port)
-                            .collect()
-                    }
-                    ScanType::Ndp if capabilities.can_ndp => {
-                        let scanner = NdpScanner::new().with_timeout(cfg.timeout_ms);
-                        scanner
-                            .scan()
-                            .await
-                            .into_iter()
-                            .map(ScanResult::Ndp)
-                            .collect()
-                    }
-                    ScanType::Ssdp => {
-                        let scanner = SsdpScanner::new();
-                        scanner
-                            .discover()
-                            .await
-                            .into_iter()
-                            .map(ScanResult::Ssdp)
-                            .collect()
-                    }
-                    ScanType::NetBios if capabilities.can_netbios => {
-                        let mut all_ips = Vec::new();
-                        for subnet in &subnets {
-                            all_ips.extend(subnet.iter().map(IpAddr::V4));
-                        }
-                        let scanner = NetBiosScanner::new().with_timeout(cfg.timeout_ms);
-                        scanner
-                            .scan_ips(&all_ips)
-                            .await
-                            .into_iter()
-                            .map(ScanResult::NetBios)
-                            .collect()
-                    }
-                    ScanType::Snmp if capabilities.can_snmp => {
-                        let mut all_ips = Vec::new();
-                        for subnet in &subnets {
-                            all_ips.extend(subnet.iter().map(IpAddr::V4));
-                        }
-                        let scanner = SnmpScanner::new().with_timeout(cfg.timeout_ms);
-                        scanner
-                            .scan_ips(&all_ips)
-                            .await
-                            .into_iter()
-                            .map(ScanResult::Snmp)
-                            .collect()
-                    }
-                    _ => Vec::new(), // Skip if no privilege
+            let mut handles = Vec::new();
+            for phase in &phases {
+                let Some(scanner) = registry::get(phase.scan_type)
+                    .filter(|scanner| scanner.is_available(&privileges))
+                else {
+                    phase.set_state(PhaseState::Skipped);
+                    continue;
                 };
-
-                // Send results and track unique IPs
-                for result in &results {
-                    let _ = result_tx.send(result.clone()).await;
-                    // Extract IP from result for unique device counting
-                    let ip = match result {
-                        ScanResult::Arp(r) => r.ip,
-                        ScanResult::Icmp(r) => r.ip,
-                        ScanResult::Ndp(r) => r.ip,
-                        ScanResult::NetBios(r) => r.ip,
-                        ScanResult::Port(r) => r.ip,
-                        ScanResult::Snmp(r) => r.ip,
-                        ScanResult::Ssdp(r) => r.ip,
+                let type_budget = cfg
+                    .tuning(phase.scan_type)
+                    .max_probes_per_sec
+                    .map(RateBudget::new);
+                let run = ScanRun {
+                    scanner,
+                    type_budget,
+                    phase: phase.clone(),
+                    config: cfg.clone(),
+                    subnets: subnets.clone(),
+                    budget: budget.clone(),
+                    event_tx: event_tx.clone(),
+                    status: status.clone(),
+                    snmp_credentials: snmp_credentials.clone(),
+                    observed: observed.clone(),
+                    discovered_ips: discovered_ips.clone(),
+                };
+                let slots = slots.clone();
+                let handle = tokio::spawn(async move {
+                    // Hold a slot for the whole run so at most `max_concurrent_scan_types` run
+                    let Ok(_slot) = slots.acquire_owned().await else {
+                        return;
                     };
-                    discovered_ips.insert(ip);
-                }
-
-                completed_phases += 1;
-
-                // Update progress (using saturating arithmetic to prevent overflow)
+                    run.run().await;
+                });
+                handles.push((phase.clone(), handle));
+            }
+            // A panicking scanner ends only its own scan type
+            for (phase, handle) in handles {
+                if let Err(e) = handle.await
+                    && e.is_panic()
                 {
-                    let mut s = status.write().await;
-                    let percent = completed_phases
-                        .saturating_mul(100)
-                        .checked_div(total_phases.max(1))
-                        .unwrap_or(0)
-                        .min(100);
-                    s.progress_percent = percent as u8;
-                    s.discovered_count = discovered_ips.len().min(u32::MAX as usize) as u32;
+                    phase.set_state(PhaseState::Failed);
+                    let error = format!("panicked: {}", panic_message(&*e.into_panic()));
+                    let _ = event_tx
+                        .send(ScanEvent::Failed {
+                            scan_type: phase.scan_type,
+                            error,
+                        })
+                        .await;
                 }
             }
 
+            let finished_at = chrono::Utc::now().timestamp();
+            let stopped = phases
+                .iter()
+                .any(|phase| phase.state() == PhaseState::Cancelled);
+            let _ = event_tx
+                .send(ScanEvent::Completed {
+                    started_at,
+                    finished_at,
+                    scan_types,
+                    stopped,
+                })
+                .await;
+
             // Mark as complete
             {
                 let mut s = status.write().await;
                 s.running = false;
                 s.progress_percent = 100;
-                s.last_scan_time = Some(chrono::Utc::now().timestamp());
+                s.last_scan_time = Some(finished_at);
                 s.current_phase = None;
+                s.estimated_remaining_secs = None;
             }
         });
 
         Ok(())
     }
 
-    /// Stop the current scan
+    /// Cancel one scan type of the running scan; the other types carry on
+    pub async fn cancel_scan_type(&self, scan_type: ScanType) -> Result<(), String> {
+        let phases = self.phases.read().await;
+        match phases.iter().find(|phase| phase.scan_type == scan_type) {
+            Some(phase) if !phase.state().is_finished() => {
+                phase.cancel();
+                Ok(())
+            }
+            _ => Err(format!("No {} scan in progress", scan_type)),
+        }
+    }
+
+    /// Stop the current scan (every scan type)
     pub async fn stop_scan(&self) {
-        *self.stop_signal.write().await = true;
+        for phase in self.phases.read().await.iter() {
+            phase.cancel();
+        }
     }
 }
 
@@ -318,10 +960,10 @@ mod tests {
         assert!(!config.ports.is_empty());
 
         // Check default enabled scanners
-        assert!(config.enabled_scanners.contains(&ScanType::Arp));
-        assert!(config.enabled_scanners.contains(&ScanType::Ssdp));
-        assert!(!config.enabled_scanners.contains(&ScanType::Icmp));
-        assert!(!config.enabled_scanners.contains(&ScanType::Port));
+        assert!(config.enabled_scanners.contains(&ScanType::ARP));
+        assert!(config.enabled_scanners.contains(&ScanType::SSDP));
+        assert!(!config.enabled_scanners.contains(&ScanType::ICMP));
+        assert!(!config.enabled_scanners.contains(&ScanType::PORT));
     }
 
     #[test]
@@ -334,6 +976,98 @@ mod tests {
         assert!(config.ports.contains(&443)); // HTTPS
     }
 
+    #[test]
+    fn test_scan_config_target_strategies() {
+        // Configs saved before target strategies existed still load, and sweep
+        let config: ScanConfig = serde_json::from_str(
+            r#"{"scan_interval_secs":null,"enabled_scanners":["arp"],"ports":[22],"timeout_ms":500}"#,
+        )
+        .unwrap();
+        assert!(config.target_strategies.is_empty());
+        assert_eq!(config.unseen_sample_percent, DEFAULT_UNSEEN_SAMPLE_PERCENT);
+
+        let config: ScanConfig = serde_json::from_str(
+            r#"{"scan_interval_secs":null,"enabled_scanners":["port"],"ports":[22],"timeout_ms":500,
+                "target_strategies":{"port":"observed","icmp":"prioritized"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.target_strategies.get(&ScanType::PORT),
+            Some(&TargetStrategy::Observed)
+        );
+        assert_eq!(
+            config.target_strategies.get(&ScanType::ICMP),
+            Some(&TargetStrategy::Prioritized)
+        );
+    }
+
+    #[test]
+    fn test_scan_config_tuning() {
+        let config = ScanConfig::from_json(
+            r#"{"scan_interval_secs":null,"enabled_scanners":["arp"],"ports":[22],"timeout_ms":500,
+                "tuning":{"icmp":{"timeout_ms":2000,"retries":1},"ssdp":{"max_probes_per_sec":10}}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.timeout_ms_for(ScanType::ICMP), 2000);
+        assert_eq!(config.timeout_ms_for(ScanType::PORT), 500);
+        assert_eq!(config.tuning(ScanType::ICMP).retries, Some(1));
+        assert_eq!(config.tuning(ScanType::SSDP).max_probes_per_sec, Some(10));
+        // On-demand probes keep their own default unless the scan type sets one
+        assert_eq!(config.probe_timeout_ms(ScanType::NETBIOS, 2000), 2000);
+        assert_eq!(config.probe_timeout_ms(ScanType::ICMP, 3000), 2000);
+
+        // SSDP takes no retries
+        let mut invalid = config.clone();
+        invalid.tuning.insert(
+            ScanType::SSDP,
+            ScanTuning {
+                retries: Some(2),
+                ..ScanTuning::default()
+            },
+        );
+        assert!(invalid.validate().is_err());
+        let mut invalid = config.clone();
+        invalid.tuning.insert(
+            ScanType::ARP,
+            ScanTuning {
+                timeout_ms: Some(10),
+                ..ScanTuning::default()
+            },
+        );
+        assert!(invalid.validate().is_err());
+        let mut invalid = config;
+        invalid.max_probes_per_sec = 0;
+        assert!(invalid.validate().is_err());
+
+        assert!(ScanConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_select_targets() {
+        let ip = |last: u8| IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, last));
+        let all: Vec<IpAddr> = (0..=99).map(ip).collect();
+        let observed: HashSet<IpAddr> = [ip(50), ip(7)].into_iter().collect();
+
+        let sweep = select_targets(all.clone(), TargetStrategy::Sweep, &observed, 5, 0);
+        assert_eq!(sweep, all);
+
+        let prioritized = select_targets(all.clone(), TargetStrategy::Prioritized, &observed, 5, 0);
+        assert_eq!(prioritized.len(), 100);
+        assert_eq!(&prioritized[..2], &[ip(7), ip(50)]);
+
+        // Observed addresses plus every 20th unseen one
+        let limited = select_targets(all.clone(), TargetStrategy::Observed, &observed, 5, 0);
+        assert_eq!(&limited[..2], &[ip(7), ip(50)]);
+        assert_eq!(limited.len(), 2 + 5);
+        // A different offset samples different unseen addresses
+        let shifted = select_targets(all.clone(), TargetStrategy::Observed, &observed, 5, 3);
+        assert_eq!(shifted.len(), 2 + 5);
+        assert_ne!(limited[2..], shifted[2..]);
+
+        let observed_only = select_targets(all, TargetStrategy::Observed, &observed, 0, 0);
+        assert_eq!(observed_only, vec![ip(7), ip(50)]);
+    }
+
     #[test]
     fn test_get_local_subnets() {
         let subnets = ScanManager::get_local_subnets();
@@ -349,11 +1083,11 @@ mod tests {
 
     #[test]
     fn test_scan_type_display() {
-        assert_eq!(format!("{}", ScanType::Arp), "arp");
-        assert_eq!(format!("{}", ScanType::Icmp), "icmp");
-        assert_eq!(format!("{}", ScanType::Port), "port");
-        assert_eq!(format!("{}", ScanType::Snmp), "snmp");
-        assert_eq!(format!("{}", ScanType::Ssdp), "ssdp");
+        assert_eq!(format!("{}", ScanType::ARP), "arp");
+        assert_eq!(format!("{}", ScanType::ICMP), "icmp");
+        assert_eq!(format!("{}", ScanType::PORT), "port");
+        assert_eq!(format!("{}", ScanType::SNMP), "snmp");
+        assert_eq!(format!("{}", ScanType::SSDP), "ssdp");
     }
 
     #[tokio::test]
@@ -368,6 +1102,105 @@ mod tests {
         assert!(status.scan_types.is_empty());
         assert!(status.current_phase.is_none());
         assert!(status.last_scan_time.is_none());
+        assert!(status.started_at.is_none());
+        assert!(status.estimated_remaining_secs.is_none());
+    }
+
+    #[test]
+    fn test_scan_status_progress_estimate() {
+        let mut status = ScanStatus {
+            running: true,
+            scan_types: vec![ScanType::ARP],
+            progress_percent: 0,
+            discovered_count: 0,
+            last_scan_time: None,
+            current_phase: None,
+            started_at: Some(chrono::Utc::now().timestamp() - 30),
+            estimated_remaining_secs: None,
+            phases: Vec::new(),
+        };
+
+        status.set_progress(0.0);
+        assert_eq!(status.progress_percent, 0);
+        assert!(status.estimated_remaining_secs.is_none());
+
+        status.set_progress(0.25);
+        assert_eq!(status.progress_percent, 25);
+        // 30s for the first quarter leaves roughly 90s
+        let remaining = status.estimated_remaining_secs.unwrap();
+        assert!((88..=92).contains(&remaining));
+
+        status.set_progress(1.5);
+        assert_eq!(status.progress_percent, 100);
+        assert_eq!(status.estimated_remaining_secs, Some(0));
+    }
+
+    #[test]
+    fn test_scan_config_arp_defaults_when_missing() {
+        let config: ScanConfig = serde_json::from_str(
+            r#"{"scan_interval_secs":null,"enabled_scanners":["arp"],"ports":[80],"timeout_ms":500}"#,
+        )
+        .unwrap();
+        assert_eq!(config.arp_parallelism, DEFAULT_ARP_PARALLELISM);
+        assert_eq!(config.arp_retries, DEFAULT_ARP_RETRIES);
+        assert_eq!(
+            config.max_concurrent_scan_types,
+            DEFAULT_MAX_CONCURRENT_SCAN_TYPES
+        );
+        assert_eq!(config.max_probes_per_sec, DEFAULT_MAX_PROBES_PER_SEC);
+    }
+
+    #[test]
+    fn test_phase_status_tracks_targets() {
+        let phase = Phase::new(ScanType::ICMP);
+        phase.targets.store(200, Ordering::Relaxed);
+        phase.progress.add_total(200);
+        assert_eq!(phase.status(0).state, PhaseState::Pending);
+        assert_eq!(phase.fraction(), 0.0);
+
+        phase.set_state(PhaseState::Running);
+        phase.progress.advance(50);
+        let status = phase.status(chrono::Utc::now().timestamp());
+        assert_eq!((status.targets_scanned, status.targets_total), (50, 200));
+        assert_eq!(status.progress_percent, 25);
+
+        // A cancelled phase is finished for the overall bar but keeps its partial count
+        phase.cancel();
+        phase.set_state(PhaseState::Cancelled);
+        let status = phase.status(chrono::Utc::now().timestamp());
+        assert_eq!(status.targets_scanned, 50);
+        assert!(status.estimated_remaining_secs.is_none());
+        assert_eq!(phase.fraction(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_scan_type_requires_running_phase() {
+        let (tx, _rx) = mpsc::channel(100);
+        let manager = ScanManager::new(tx);
+        assert!(manager.cancel_scan_type(ScanType::ARP).await.is_err());
+
+        let running = Arc::new(Phase::new(ScanType::ARP));
+        running.set_state(PhaseState::Running);
+        let done = Arc::new(Phase::new(ScanType::SSDP));
+        done.set_state(PhaseState::Completed);
+        *manager.phases.write().await = vec![running.clone(), done.clone()];
+
+        assert!(manager.cancel_scan_type(ScanType::ARP).await.is_ok());
+        assert!(running.is_cancelled());
+        assert!(manager.cancel_scan_type(ScanType::SSDP).await.is_err());
+        assert!(!done.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_rate_budget_spaces_reservations() {
+        let budget = RateBudget::new(100);
+        let started = std::time::Instant::now();
+        budget.acquire(10).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        // The first ten probes occupy the next 100ms of the budget
+        budget.acquire(1).await;
+        assert!(started.elapsed() >= Duration::from_millis(90));
     }
 
     #[tokio::test]
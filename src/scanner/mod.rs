@@ -1,44 +1,100 @@
-//! Scanner module. Defines ScanType and ScanResult enums and exports all scanner
-//! implementations (ARP, ICMP, NDP, NetBIOS, Port, SNMP, SSDP).
+//! Scanner module. Defines ScanType and the ScanResult enum and exports all scanner
+//! implementations (ARP, ICMP, NDP, NetBIOS/SMB, Port, SNMP, SSDP), each registered as
+//! a scan type in `registry`, along with the on-demand WAN exposure check, web interface
+//! fingerprinting, SSH host key and TLS certificate collection, and printer supply, NAS
+//! storage health and UPS polling.
 
 pub mod arp;
+pub mod default_credentials;
+pub mod exposure;
+pub mod gena;
+pub mod host_keys;
 pub mod icmp;
+pub mod igd;
 pub mod manager;
+pub mod nas;
 pub mod ndp;
+pub mod neighbor_table;
 pub mod netbios;
 pub mod port;
+pub mod printer;
+pub mod registry;
+pub mod smb;
 pub mod snmp;
 pub mod ssdp;
+pub mod ups;
+pub mod web_page;
 
+use std::collections::BTreeMap;
 use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 use pnet::util::MacAddr;
 use serde::{Deserialize, Serialize};
 
-/// Types of scanners available
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum ScanType {
-    Arp,
-    Icmp,
-    Ndp,
-    NetBios,
-    Port,
-    Snmp,
-    Ssdp,
+/// A scan type, named after the scanner that runs it (see `registry`). Only the names of
+/// registered scanners parse; a new scanner names its type with `ScanType::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScanType(&'static str);
+
+impl ScanType {
+    pub const ARP: ScanType = ScanType::new("arp");
+    pub const ICMP: ScanType = ScanType::new("icmp");
+    pub const NDP: ScanType = ScanType::new("ndp");
+    pub const NETBIOS: ScanType = ScanType::new("netbios");
+    pub const PORT: ScanType = ScanType::new("port");
+    pub const SNMP: ScanType = ScanType::new("snmp");
+    pub const SSDP: ScanType = ScanType::new("ssdp");
+
+    /// Lowercase name used in the API, config and CLI
+    pub const fn new(name: &'static str) -> Self {
+        Self(name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
 }
 
 impl std::fmt::Display for ScanType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ScanType::Arp => write!(f, "arp"),
-            ScanType::Icmp => write!(f, "icmp"),
-            ScanType::Ndp => write!(f, "ndp"),
-            ScanType::NetBios => write!(f, "netbios"),
-            ScanType::Port => write!(f, "port"),
-            ScanType::Snmp => write!(f, "snmp"),
-            ScanType::Ssdp => write!(f, "ssdp"),
+        f.write_str(self.0)
+    }
+}
+
+impl std::str::FromStr for ScanType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(scanner) = registry::find(s) {
+            return Ok(scanner.scan_type());
         }
+        let mut names: Vec<&str> = registry::scanners()
+            .iter()
+            .map(|scanner| scanner.scan_type().as_str())
+            .collect();
+        names.sort_unstable();
+        let expected = match names.split_last() {
+            Some((last, [])) => last.to_string(),
+            Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+            None => "a registered scanner".to_string(),
+        };
+        Err(format!("unknown scan type '{}' (expected {})", s, expected))
+    }
+}
+
+impl Serialize for ScanType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ScanType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
     }
 }
 
@@ -54,6 +110,44 @@ pub enum ScanResult {
     Ssdp(SsdpResult),
 }
 
+impl ScanResult {
+    /// Address the result is about
+    pub fn ip(&self) -> IpAddr {
+        match self {
+            ScanResult::Arp(r) => r.ip,
+            ScanResult::Icmp(r) => r.ip,
+            ScanResult::Ndp(r) => r.ip,
+            ScanResult::NetBios(r) => r.ip,
+            ScanResult::Port(r) => r.ip,
+            ScanResult::Snmp(r) => r.ip,
+            ScanResult::Ssdp(r) => r.ip,
+        }
+    }
+}
+
+/// What makes a result a repeat of an earlier one in the same scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScanResultKey {
+    Arp(IpAddr, MacAddr),
+    Ndp(IpAddr, MacAddr),
+    Icmp(IpAddr),
+    Port(IpAddr, u16),
+}
+
+impl ScanResult {
+    /// Identity for skipping repeats within a run, such as ARP retries answered twice.
+    /// Results whose details can differ between answers (SSDP, SNMP, NetBIOS) have none.
+    pub fn repeat_key(&self) -> Option<ScanResultKey> {
+        match self {
+            ScanResult::Arp(arp) => Some(ScanResultKey::Arp(arp.ip, arp.mac)),
+            ScanResult::Ndp(ndp) => Some(ScanResultKey::Ndp(ndp.ip, ndp.mac)),
+            ScanResult::Icmp(icmp) if icmp.alive => Some(ScanResultKey::Icmp(icmp.ip)),
+            ScanResult::Port(port) if port.open => Some(ScanResultKey::Port(port.ip, port.port)),
+            _ => None,
+        }
+    }
+}
+
 /// ARP scan result
 #[derive(Debug, Clone)]
 pub struct ArpResult {
@@ -67,7 +161,8 @@ pub struct ArpResult {
 pub struct NdpResult {
     pub ip: IpAddr,
     pub mac: MacAddr,
-    pub response_time_ms: u64,
+    /// None for entries read from the OS neighbor table
+    pub response_time_ms: Option<u64>,
 }
 
 /// ICMP ping result
@@ -97,6 +192,39 @@ pub struct SsdpResult {
     pub device_type: Option<String>,
     pub friendly_name: Option<String>,
     pub model_name: Option<String>,
+    /// Unique Device Name of the root device, lowercase without the "uuid:" prefix
+    pub udn: Option<String>,
+    /// Active port mappings when the device is an Internet Gateway Device that answered
+    pub port_mappings: Option<Vec<UpnpPortMapping>>,
+}
+
+/// A port forwarded from the internet by a UPnP gateway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpnpPortMapping {
+    /// Empty when any remote host may connect
+    pub remote_host: String,
+    pub external_port: u16,
+    /// "TCP" or "UDP"
+    pub protocol: String,
+    pub internal_client: String,
+    pub internal_port: u16,
+    pub enabled: bool,
+    pub description: Option<String>,
+    /// Seconds left, 0 for a permanent mapping
+    pub lease_duration: u32,
+}
+
+/// Outcome of a WAN exposure check
+#[derive(Debug, Clone)]
+pub struct ExposureResult {
+    pub wan_ip: IpAddr,
+    /// Where the address came from ("gateway", "stun:host:port" or a URL)
+    pub wan_ip_source: String,
+    /// The WAN address is private or carrier-grade NAT, so another NAT sits in front
+    pub behind_nat: bool,
+    /// Ports were checked by the external scanner rather than from inside the network
+    pub external_scanner: bool,
+    pub open_ports: Vec<PortResult>,
 }
 
 /// NetBIOS Name Service result
@@ -106,6 +234,76 @@ pub struct NetBiosResult {
     pub netbios_name: String,
     pub group_name: Option<String>,
     pub mac: Option<String>,
+    /// Names and shares from SMB on port 445, when share enumeration is enabled
+    pub smb: Option<SmbInfo>,
+}
+
+/// What an anonymous SMB session on port 445 revealed
+#[derive(Debug, Clone, Default)]
+pub struct SmbInfo {
+    pub computer_name: Option<String>,
+    /// NetBIOS domain or workgroup
+    pub domain: Option<String>,
+    pub dns_domain: Option<String>,
+    /// Whether the server accepted an anonymous (null) logon
+    pub anonymous_session: bool,
+    pub shares: Vec<SmbShare>,
+}
+
+/// A share visible to an anonymous SMB session
+#[derive(Debug, Clone, Serialize)]
+pub struct SmbShare {
+    pub name: String,
+    /// "disk", "printer", "device", "ipc" or "other"
+    pub share_type: String,
+    pub remark: Option<String>,
+    /// The share root could be listed without credentials
+    pub guest_readable: bool,
+}
+
+/// Landing page of a device's web interface
+#[derive(Debug, Clone)]
+pub struct WebPageInfo {
+    /// Final URL after redirects
+    pub url: String,
+    pub title: Option<String>,
+    pub favicon: Option<Favicon>,
+}
+
+/// A favicon image, typed by its content rather than the server's header
+#[derive(Debug, Clone)]
+pub struct Favicon {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Host key of an SSH server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshHostKey {
+    pub port: u16,
+    /// Key algorithm, e.g. "ssh-ed25519" or "ssh-rsa"
+    pub key_type: String,
+    /// OpenSSH-style fingerprint ("SHA256:" and unpadded base64)
+    pub fingerprint: String,
+}
+
+/// Certificate presented by a TLS service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsCertificate {
+    pub port: u16,
+    pub subject_cn: Option<String>,
+    /// Full subject, e.g. "O=Acme, CN=nas.local"
+    pub subject: Option<String>,
+    /// Issuer common name, or organization when it has none
+    pub issuer: Option<String>,
+    /// Unix seconds
+    pub not_before: Option<i64>,
+    pub not_after: Option<i64>,
+    pub self_signed: bool,
+    /// SHA-256 of the certificate, colon-separated hex
+    pub fingerprint: String,
+    /// SHA-256 of the public key, unchanged by renewals that keep the key
+    pub key_fingerprint: String,
 }
 
 /// SNMP scan result
@@ -116,34 +314,104 @@ pub struct SnmpResult {
     pub sys_object_id: Option<String>,
     pub sys_name: Option<String>,
     pub sys_location: Option<String>,
+    /// Community that answered; empty when the device's own credential did
     pub community: String,
+    pub per_device_credential: bool,
 }
 
-/// Scan capabilities based on privileges
-#[derive(Debug, Clone, Serialize)]
-pub struct ScanCapabilities {
-    pub can_arp: bool,
-    pub can_icmp: bool,
-    pub can_ndp: bool,
-    pub can_netbios: bool,
-    pub can_port: bool,
-    pub can_snmp: bool,
-    pub can_ssdp: bool,
+/// Shared work counter a scanner advances while running, so the manager can report
+/// progress within a phase. Units are scanner-defined (e.g. ARP requests planned).
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    completed: AtomicU64,
+    total: AtomicU64,
+}
+
+impl ScanProgress {
+    pub fn add_total(&self, units: u64) {
+        self.total.fetch_add(units, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, units: u64) {
+        self.completed.fetch_add(units, Ordering::Relaxed);
+    }
+
+    /// Completed fraction in 0.0..=1.0 (0.0 when nothing is planned yet)
+    pub fn fraction(&self) -> f64 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        (self.completed.load(Ordering::Relaxed) as f64 / total as f64).min(1.0)
+    }
 }
 
+/// Probe rate shared by every scan type running in a scan, so running types side by side
+/// does not multiply the load put on the network. Callers reserve probes before sending.
+#[derive(Debug)]
+pub struct RateBudget {
+    per_probe: Duration,
+    next_slot: tokio::sync::Mutex<tokio::time::Instant>,
+}
+
+impl RateBudget {
+    pub fn new(probes_per_sec: u32) -> Self {
+        Self {
+            per_probe: Duration::from_secs(1) / probes_per_sec.max(1),
+            next_slot: tokio::sync::Mutex::new(tokio::time::Instant::now()),
+        }
+    }
+
+    /// Wait until the budget has room, then reserve `probes` probes
+    pub async fn acquire(&self, probes: u64) {
+        let start = {
+            let mut next_slot = self.next_slot.lock().await;
+            let start = (*next_slot).max(tokio::time::Instant::now());
+            let reserved = u32::try_from(probes).unwrap_or(u32::MAX);
+            *next_slot = start + self.per_probe.saturating_mul(reserved);
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
+
+/// What the process may do, which decides the scan types that can run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Privileges {
+    /// Raw sockets (ARP, NDP and raw ICMP)
+    pub raw_socket: bool,
+    /// Unprivileged ICMP datagram ("ping") sockets
+    pub icmp_datagram: bool,
+}
+
+/// Check what the process may do
+pub fn check_privileges() -> Privileges {
+    let raw_socket = check_raw_socket_access();
+    Privileges {
+        raw_socket,
+        icmp_datagram: raw_socket || check_icmp_datagram_access(),
+    }
+}
+
+/// Whether each registered scan type can run, serialized as `{"can_arp": true, ...}`
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct ScanCapabilities(BTreeMap<String, bool>);
+
 /// Check what scan types are available based on privileges
 pub fn check_scan_privileges() -> ScanCapabilities {
-    let can_raw_socket = check_raw_socket_access();
-
-    ScanCapabilities {
-        can_arp: can_raw_socket,
-        can_icmp: can_raw_socket,
-        can_ndp: can_raw_socket, // NDP also requires raw sockets
-        can_netbios: true,       // UDP always works
-        can_port: true,          // TCP connect always works
-        can_snmp: true,          // UDP always works
-        can_ssdp: true,          // UDP multicast always works
-    }
+    let privileges = check_privileges();
+    ScanCapabilities(
+        registry::scanners()
+            .iter()
+            .map(|scanner| {
+                (
+                    format!("can_{}", scanner.scan_type()),
+                    scanner.is_available(&privileges),
+                )
+            })
+            .collect(),
+    )
 }
 
 /// Check if we have raw socket access (needed for ARP and ICMP)
@@ -152,3 +420,9 @@ fn check_raw_socket_access() -> bool {
     use socket2::{Domain, Protocol, Socket, Type};
     Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).is_ok()
 }
+
+/// Check if unprivileged ICMP datagram ("ping") sockets are allowed (Linux, macOS)
+fn check_icmp_datagram_access() -> bool {
+    use socket2::{Domain, Protocol, Socket, Type};
+    Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)).is_ok()
+}
@@ -0,0 +1,424 @@
+//! NAS storage health over SNMP, from the vendor MIBs of Synology (SYNOLOGY-DISK-MIB and
+//! SYNOLOGY-RAID-MIB), QNAP (NAS-MIB) and TrueNAS (FREENAS-MIB): volume and pool status
+//! and capacity, and per-disk status and temperature. Tables are read by index with plain
+//! GETs, the same way the printer supplies are.
+
+use std::net::Ipv4Addr;
+
+use serde::Serialize;
+
+use super::snmp::SnmpScanner;
+
+/// Disk rows read; covers desktop units and most rackmounts
+const MAX_DISKS: u32 = 16;
+/// Volume, RAID group or pool rows read
+const MAX_VOLUMES: u32 = 8;
+/// Table rows per GET request, keeping responses well under small SNMP message limits
+const ROWS_PER_REQUEST: u32 = 4;
+
+/// synoDisk diskEntry: diskID, diskModel, diskStatus, diskTemperature
+const SYNOLOGY_DISK_ENTRY: &[u32] = &[1, 3, 6, 1, 4, 1, 6574, 2, 1, 1];
+const SYNOLOGY_DISK_COLUMNS: &[u32] = &[2, 3, 5, 6];
+/// synoRaid raidEntry: raidName, raidStatus, raidFreeSize, raidTotalSize
+const SYNOLOGY_RAID_ENTRY: &[u32] = &[1, 3, 6, 1, 4, 1, 6574, 3, 1, 1];
+const SYNOLOGY_RAID_COLUMNS: &[u32] = &[2, 3, 4, 5];
+
+/// NAS-MIB hdEntry: HdDescr, HdTemperature, HdStatus, HdModel, HdSmartInfo
+const QNAP_DISK_ENTRY: &[u32] = &[1, 3, 6, 1, 4, 1, 24681, 1, 2, 11, 1];
+const QNAP_DISK_COLUMNS: &[u32] = &[2, 3, 4, 5, 7];
+/// NAS-MIB sysVolumeEntry: SysVolumeDescr, SysVolumeTotalSize, SysVolumeFreeSize,
+/// SysVolumeStatus
+const QNAP_VOLUME_ENTRY: &[u32] = &[1, 3, 6, 1, 4, 1, 24681, 1, 2, 17, 1];
+const QNAP_VOLUME_COLUMNS: &[u32] = &[2, 4, 5, 6];
+
+/// FREENAS-MIB hddTempEntry: hddTempDevice, hddTempValue (millidegrees)
+const TRUENAS_DISK_ENTRY: &[u32] = &[1, 3, 6, 1, 4, 1, 50536, 3, 1];
+const TRUENAS_DISK_COLUMNS: &[u32] = &[2, 3];
+/// FREENAS-MIB zpoolEntry: zpoolName, zpoolHealth
+const TRUENAS_POOL_ENTRY: &[u32] = &[1, 3, 6, 1, 4, 1, 50536, 1, 1, 1];
+const TRUENAS_POOL_COLUMNS: &[u32] = &[2, 3];
+
+/// Which vendor MIB a NAS answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NasVendor {
+    Synology,
+    Qnap,
+    TrueNas,
+}
+
+impl NasVendor {
+    pub const ALL: [NasVendor; 3] = [NasVendor::Synology, NasVendor::Qnap, NasVendor::TrueNas];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NasVendor::Synology => "synology",
+            NasVendor::Qnap => "qnap",
+            NasVendor::TrueNas => "truenas",
+        }
+    }
+
+    /// Guess the vendor from an SNMP scan's system description or object id
+    pub fn from_snmp_details(details: &str) -> Option<NasVendor> {
+        let lower = details.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+        if has(&["synology", "diskstation", "rackstation", ".6574."]) {
+            Some(NasVendor::Synology)
+        } else if has(&["qnap", ".24681."]) {
+            Some(NasVendor::Qnap)
+        } else if has(&["truenas", "freenas", ".50536."]) {
+            Some(NasVendor::TrueNas)
+        } else {
+            None
+        }
+    }
+
+    /// First table index; Synology counts from 0
+    fn first_index(&self) -> u32 {
+        match self {
+            NasVendor::Synology => 0,
+            NasVendor::Qnap | NasVendor::TrueNas => 1,
+        }
+    }
+}
+
+/// A volume, RAID group or ZFS pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NasVolume {
+    pub name: String,
+    /// normal, degraded, failed, or the vendor's word for anything else (e.g. repairing)
+    pub status: String,
+    pub total_bytes: Option<i64>,
+    pub free_bytes: Option<i64>,
+}
+
+impl NasVolume {
+    /// Whether redundancy is lost or the volume is gone
+    pub fn is_degraded(&self) -> bool {
+        matches!(self.status.as_str(), "degraded" | "failed")
+    }
+
+    pub fn used_percent(&self) -> Option<i64> {
+        match (self.total_bytes, self.free_bytes) {
+            (Some(total), Some(free)) if total > 0 => {
+                Some(((total - free.min(total)) * 100 / total).clamp(0, 100))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// One drive bay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NasDisk {
+    pub name: String,
+    pub model: Option<String>,
+    /// normal, warning, failed, or None when the MIB has no status for disks
+    pub status: Option<String>,
+    pub temperature_c: Option<i64>,
+}
+
+/// What one poll of a NAS returned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NasStatus {
+    pub vendor: NasVendor,
+    pub volumes: Vec<NasVolume>,
+    pub disks: Vec<NasDisk>,
+}
+
+fn table_oid(entry: &[u32], column: u32, index: u32) -> Vec<u32> {
+    let mut oid = entry.to_vec();
+    oid.extend([column, index]);
+    oid
+}
+
+/// GET requests covering `rows` rows of a table, a few rows per request
+fn table_requests(entry: &[u32], columns: &[u32], first: u32, rows: u32) -> Vec<Vec<Vec<u32>>> {
+    (first..first + rows)
+        .step_by(ROWS_PER_REQUEST as usize)
+        .map(|start| {
+            (start..(start + ROWS_PER_REQUEST).min(first + rows))
+                .flat_map(|index| columns.iter().map(move |&c| table_oid(entry, c, index)))
+                .collect()
+        })
+        .collect()
+}
+
+fn requests_for(vendor: NasVendor) -> Vec<Vec<Vec<u32>>> {
+    let first = vendor.first_index();
+    let (disk_entry, disk_columns, volume_entry, volume_columns) = match vendor {
+        NasVendor::Synology => (
+            SYNOLOGY_DISK_ENTRY,
+            SYNOLOGY_DISK_COLUMNS,
+            SYNOLOGY_RAID_ENTRY,
+            SYNOLOGY_RAID_COLUMNS,
+        ),
+        NasVendor::Qnap => (
+            QNAP_DISK_ENTRY,
+            QNAP_DISK_COLUMNS,
+            QNAP_VOLUME_ENTRY,
+            QNAP_VOLUME_COLUMNS,
+        ),
+        NasVendor::TrueNas => (
+            TRUENAS_DISK_ENTRY,
+            TRUENAS_DISK_COLUMNS,
+            TRUENAS_POOL_ENTRY,
+            TRUENAS_POOL_COLUMNS,
+        ),
+    };
+    // Volumes first: the first request decides whether the device speaks this MIB
+    let mut requests = table_requests(volume_entry, volume_columns, first, MAX_VOLUMES);
+    requests.extend(table_requests(disk_entry, disk_columns, first, MAX_DISKS));
+    requests
+}
+
+/// Read storage health from a NAS, trying `hint`'s MIB first and then the others. None
+/// when no vendor MIB answers.
+pub fn query_nas(
+    scanner: &SnmpScanner,
+    ip: Ipv4Addr,
+    hint: Option<NasVendor>,
+) -> Option<NasStatus> {
+    let mut vendors: Vec<NasVendor> = hint.into_iter().collect();
+    vendors.extend(NasVendor::ALL.into_iter().filter(|v| Some(*v) != hint));
+    vendors.into_iter().find_map(|vendor| {
+        let status = parse_nas_status(vendor, &scanner.get(ip, &requests_for(vendor)));
+        (!status.volumes.is_empty() || !status.disks.is_empty()).then_some(status)
+    })
+}
+
+/// A QNAP size string such as "1.79 TB" in bytes
+fn parse_size(text: &str) -> Option<i64> {
+    let mut parts = text.split_whitespace();
+    let number: f64 = parts.next()?.parse().ok()?;
+    let multiplier = match parts.next().unwrap_or("B").to_uppercase().as_str() {
+        "B" | "BYTES" => 1u64,
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        "TB" => 1 << 40,
+        "PB" => 1 << 50,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as i64)
+}
+
+/// Build a NAS status from the values of a poll
+fn parse_nas_status(vendor: NasVendor, values: &[(Vec<u32>, String)]) -> NasStatus {
+    let value = |entry: &[u32], column: u32, index: u32| {
+        let oid = table_oid(entry, column, index);
+        values
+            .iter()
+            .find(|(o, _)| *o == oid)
+            .map(|(_, v)| v.trim_end_matches('\0').trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let number = |entry: &[u32], column: u32, index: u32| {
+        value(entry, column, index).and_then(|v| v.parse::<i64>().ok())
+    };
+    let first = vendor.first_index();
+    let volume_rows = first..first + MAX_VOLUMES;
+    let disk_rows = first..first + MAX_DISKS;
+
+    let (volumes, disks) = match vendor {
+        NasVendor::Synology => {
+            let e = SYNOLOGY_RAID_ENTRY;
+            let volumes = volume_rows
+                .filter_map(|i| {
+                    Some(NasVolume {
+                        name: value(e, 2, i)?,
+                        status: match number(e, 3, i) {
+                            Some(1) => "normal".to_string(),
+                            Some(11) => "degraded".to_string(),
+                            Some(12) => "failed".to_string(),
+                            Some(2) => "repairing".to_string(),
+                            Some(7) => "syncing".to_string(),
+                            Some(13) => "scrubbing".to_string(),
+                            Some(other) => other.to_string(),
+                            None => "unknown".to_string(),
+                        },
+                        total_bytes: number(e, 5, i),
+                        free_bytes: number(e, 4, i),
+                    })
+                })
+                .collect();
+            let e = SYNOLOGY_DISK_ENTRY;
+            let disks = disk_rows
+                .filter_map(|i| {
+                    Some(NasDisk {
+                        name: value(e, 2, i)?,
+                        model: value(e, 3, i),
+                        status: number(e, 5, i).map(|s| {
+                            match s {
+                                1..=3 => "normal",
+                                4 => "warning",
+                                _ => "failed",
+                            }
+                            .to_string()
+                        }),
+                        temperature_c: number(e, 6, i),
+                    })
+                })
+                .collect();
+            (volumes, disks)
+        }
+        NasVendor::Qnap => {
+            let e = QNAP_VOLUME_ENTRY;
+            let volumes = volume_rows
+                .filter_map(|i| {
+                    let status = value(e, 6, i).unwrap_or_default().to_lowercase();
+                    Some(NasVolume {
+                        name: value(e, 2, i)?,
+                        status: if status == "ready" || status == "good" {
+                            "normal".to_string()
+                        } else if status.contains("degrad") {
+                            "degraded".to_string()
+                        } else if status.contains("fail") || status.contains("not active") {
+                            "failed".to_string()
+                        } else if status.is_empty() {
+                            "unknown".to_string()
+                        } else {
+                            status
+                        },
+                        total_bytes: value(e, 4, i).and_then(|v| parse_size(&v)),
+                        free_bytes: value(e, 5, i).and_then(|v| parse_size(&v)),
+                    })
+                })
+                .collect();
+            let e = QNAP_DISK_ENTRY;
+            let disks = disk_rows
+                .filter_map(|i| {
+                    // No disk in the bay
+                    if number(e, 4, i) == Some(-5) {
+                        return None;
+                    }
+                    let smart = value(e, 7, i).unwrap_or_default().to_lowercase();
+                    Some(NasDisk {
+                        name: value(e, 2, i)?,
+                        model: value(e, 5, i),
+                        status: Some(
+                            match (number(e, 4, i), smart.as_str()) {
+                                (Some(-9) | Some(-6), _) => "failed",
+                                (_, "abnormal") => "failed",
+                                (_, "warning") => "warning",
+                                _ => "normal",
+                            }
+                            .to_string(),
+                        ),
+                        // "36 C/96 F"
+                        temperature_c: value(e, 3, i).and_then(|v| {
+                            v.split(|c: char| !c.is_ascii_digit())
+                                .next()
+                                .and_then(|n| n.parse().ok())
+                        }),
+                    })
+                })
+                .collect();
+            (volumes, disks)
+        }
+        NasVendor::TrueNas => {
+            let e = TRUENAS_POOL_ENTRY;
+            let volumes = volume_rows
+                .filter_map(|i| {
+                    Some(NasVolume {
+                        name: value(e, 2, i)?,
+                        status: match number(e, 3, i) {
+                            Some(0) => "normal",
+                            Some(1) => "degraded",
+                            Some(2) | Some(4) => "failed",
+                            Some(3) => "offline",
+                            Some(5) => "removed",
+                            _ => "unknown",
+                        }
+                        .to_string(),
+                        total_bytes: None,
+                        free_bytes: None,
+                    })
+                })
+                .collect();
+            let e = TRUENAS_DISK_ENTRY;
+            let disks = disk_rows
+                .filter_map(|i| {
+                    Some(NasDisk {
+                        name: value(e, 2, i)?,
+                        model: None,
+                        status: None,
+                        temperature_c: number(e, 3, i).map(|t| t / 1000),
+                    })
+                })
+                .collect();
+            (volumes, disks)
+        }
+    };
+
+    NasStatus {
+        vendor,
+        volumes,
+        disks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_synology_status() {
+        let values = vec![
+            (table_oid(SYNOLOGY_RAID_ENTRY, 2, 0), "Volume 1".to_string()),
+            (table_oid(SYNOLOGY_RAID_ENTRY, 3, 0), "11".to_string()),
+            (table_oid(SYNOLOGY_RAID_ENTRY, 4, 0), "250".to_string()),
+            (table_oid(SYNOLOGY_RAID_ENTRY, 5, 0), "1000".to_string()),
+            (table_oid(SYNOLOGY_DISK_ENTRY, 2, 0), "Disk 1".to_string()),
+            (table_oid(SYNOLOGY_DISK_ENTRY, 3, 0), "WD40EFRX".to_string()),
+            (table_oid(SYNOLOGY_DISK_ENTRY, 5, 0), "5".to_string()),
+            (table_oid(SYNOLOGY_DISK_ENTRY, 6, 0), "41".to_string()),
+        ];
+        let status = parse_nas_status(NasVendor::Synology, &values);
+        assert_eq!(status.volumes.len(), 1);
+        assert!(status.volumes[0].is_degraded());
+        assert_eq!(status.volumes[0].used_percent(), Some(75));
+        assert_eq!(status.disks[0].status.as_deref(), Some("failed"));
+        assert_eq!(status.disks[0].temperature_c, Some(41));
+    }
+
+    #[test]
+    fn test_parse_qnap_status() {
+        let values = vec![
+            (
+                table_oid(QNAP_VOLUME_ENTRY, 2, 1),
+                "[Mirror Disk Volume: Drive 1 2]".to_string(),
+            ),
+            (table_oid(QNAP_VOLUME_ENTRY, 4, 1), "2 TB".to_string()),
+            (table_oid(QNAP_VOLUME_ENTRY, 5, 1), "1.5 TB".to_string()),
+            (table_oid(QNAP_VOLUME_ENTRY, 6, 1), "Ready".to_string()),
+            (table_oid(QNAP_DISK_ENTRY, 2, 1), "HDD1".to_string()),
+            (table_oid(QNAP_DISK_ENTRY, 3, 1), "36 C/96 F".to_string()),
+            (table_oid(QNAP_DISK_ENTRY, 4, 1), "0".to_string()),
+            (table_oid(QNAP_DISK_ENTRY, 2, 2), "HDD2".to_string()),
+            (table_oid(QNAP_DISK_ENTRY, 4, 2), "-5".to_string()),
+        ];
+        let status = parse_nas_status(NasVendor::Qnap, &values);
+        assert_eq!(status.volumes[0].status, "normal");
+        assert_eq!(status.volumes[0].used_percent(), Some(25));
+        // The empty bay is left out
+        assert_eq!(status.disks.len(), 1);
+        assert_eq!(status.disks[0].temperature_c, Some(36));
+    }
+
+    #[test]
+    fn test_vendor_from_snmp_details() {
+        assert_eq!(
+            NasVendor::from_snmp_details(r#"{"sys_descr":"Linux DiskStation 4.4.302+"}"#),
+            Some(NasVendor::Synology)
+        );
+        assert_eq!(
+            NasVendor::from_snmp_details(r#"{"sys_descr":"TrueNAS-13.0-U6"}"#),
+            Some(NasVendor::TrueNas)
+        );
+        assert_eq!(
+            NasVendor::from_snmp_details(r#"{"sys_descr":"RouterOS"}"#),
+            None
+        );
+    }
+}
@@ -1,9 +1,11 @@
 //! NDP scanner. Uses IPv6 Neighbor Discovery Protocol to find devices on
-//! link-local networks via multicast neighbor solicitation messages.
+//! link-local networks via multicast neighbor solicitation messages and an all-nodes
+//! echo request, then adds the neighbors from the host OS neighbor table.
 
 use std::net::{IpAddr, Ipv6Addr};
 use std::time::{Duration, Instant};
 
+use futures::future::BoxFuture;
 use pnet::datalink::{self, Channel, NetworkInterface};
 use pnet::packet::MutablePacket;
 use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
@@ -14,7 +16,16 @@ use pnet::util::MacAddr;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
 
-use super::NdpResult;
+use super::manager::ScanRun;
+use super::neighbor_table::read_ipv6_neighbors;
+use super::registry::Scanner;
+use super::{NdpResult, Privileges, ScanResult, ScanType};
+
+/// All-nodes multicast address ff02::1
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Ethernet multicast MAC for ff02::1
+const ALL_NODES_MAC: MacAddr = MacAddr(0x33, 0x33, 0x00, 0x00, 0x00, 0x01);
 
 /// NDP scanner for IPv6 neighbor discovery
 pub struct NdpScanner {
@@ -89,15 +100,10 @@ impl NdpScanner {
 
     /// Build a Neighbor Solicitation packet for all-nodes multicast
     fn build_neighbor_solicitation(src_mac: MacAddr, src_ip: Ipv6Addr) -> Option<Vec<u8>> {
-        // Target: all-nodes multicast ff02::1
-        let all_nodes_multicast = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
-
         // Solicited-node multicast address for our source IP (used as target for NS)
         // For neighbor discovery, we send to ff02::1 to discover all neighbors
-        let dst_ip = all_nodes_multicast;
-
-        // Multicast MAC for ff02::1 is 33:33:00:00:00:01
-        let dst_mac = MacAddr::new(0x33, 0x33, 0x00, 0x00, 0x00, 0x01);
+        let dst_ip = ALL_NODES;
+        let dst_mac = ALL_NODES_MAC;
 
         // Ethernet (14) + IPv6 (40) + ICMPv6 NS (24) + Source Link-Layer Option (8) = 86 bytes
         let total_len = 14 + 40 + 24 + 8;
@@ -157,10 +163,50 @@ impl NdpScanner {
         Some(buffer)
     }
 
-    /// Parse Neighbor Advertisement to extract IP and MAC
-    fn parse_neighbor_advertisement(packet: &[u8]) -> Option<(Ipv6Addr, MacAddr)> {
-        // Need at least Ethernet (14) + IPv6 (40) + ICMPv6 NA (24)
-        if packet.len() < 78 {
+    /// Build an ICMPv6 Echo Request to all-nodes multicast. Hosts that ignore a
+    /// solicitation without a target still answer this with an Echo Reply.
+    fn build_echo_request(src_mac: MacAddr, src_ip: Ipv6Addr) -> Option<Vec<u8>> {
+        // Ethernet (14) + IPv6 (40) + ICMPv6 echo header (8) + payload (8) = 70 bytes
+        let mut buffer = vec![0u8; 70];
+
+        {
+            let mut eth = MutableEthernetPacket::new(&mut buffer[0..14])?;
+            eth.set_destination(ALL_NODES_MAC);
+            eth.set_source(src_mac);
+            eth.set_ethertype(EtherTypes::Ipv6);
+        }
+
+        {
+            let mut ipv6 = MutableIpv6Packet::new(&mut buffer[14..54])?;
+            ipv6.set_version(6);
+            ipv6.set_payload_length(16);
+            ipv6.set_next_header(IpNextHeaderProtocols::Icmpv6);
+            ipv6.set_hop_limit(1);
+            ipv6.set_source(src_ip);
+            ipv6.set_destination(ALL_NODES);
+        }
+
+        {
+            let mut icmpv6 = MutableIcmpv6Packet::new(&mut buffer[54..70])?;
+            icmpv6.set_icmpv6_type(Icmpv6Types::EchoRequest);
+            icmpv6.set_icmpv6_code(pnet::packet::icmpv6::Icmpv6Code(0));
+            // Identifier and sequence number, then a fixed payload
+            let echo = icmpv6.payload_mut();
+            echo[0..2].copy_from_slice(&(std::process::id() as u16).to_be_bytes());
+            echo[2..4].copy_from_slice(&1u16.to_be_bytes());
+            echo[4..12].copy_from_slice(b"netdisco");
+        }
+
+        let checksum = Self::icmpv6_checksum(&src_ip, &ALL_NODES, &buffer[54..70]);
+        buffer[56..58].copy_from_slice(&checksum.to_be_bytes());
+
+        Some(buffer)
+    }
+
+    /// Parse a Neighbor Advertisement or Echo Reply to extract IP and MAC
+    fn parse_neighbor_reply(packet: &[u8]) -> Option<(Ipv6Addr, MacAddr)> {
+        // Need at least Ethernet (14) + IPv6 (40) + ICMPv6 echo header (8)
+        if packet.len() < 62 {
             return None;
         }
 
@@ -196,8 +242,8 @@ impl NdpScanner {
             return None;
         }
 
-        // Check for Neighbor Advertisement (type 136)
-        if icmpv6_data[0] != 136 {
+        // Neighbor Advertisement (type 136) or Echo Reply (type 129)
+        if icmpv6_data[0] != 136 && icmpv6_data[0] != 129 {
             return None;
         }
 
@@ -212,12 +258,11 @@ impl NdpScanner {
         Some((src_ip, src_mac))
     }
 
-    /// Scan for IPv6 neighbors using NDP
+    /// Scan for IPv6 neighbors using NDP, then merge in the OS neighbor table
     pub async fn scan(&self) -> Vec<NdpResult> {
         let interfaces = Self::find_ipv6_interfaces();
         if interfaces.is_empty() {
             eprintln!("No IPv6 interfaces found for NDP scan");
-            return Vec::new();
         }
 
         let mut all_results = Vec::new();
@@ -246,13 +291,16 @@ impl NdpScanner {
                 while start.elapsed() < timeout_duration {
                     match rx.next() {
                         Ok(packet) => {
-                            if let Some((ip, mac)) = Self::parse_neighbor_advertisement(packet) {
+                            // Our own stack answers the multicast echo too
+                            if let Some((ip, mac)) = Self::parse_neighbor_reply(packet)
+                                && mac != src_mac
+                            {
                                 let response_time_ms =
                                     u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
                                 let _ = result_tx.blocking_send(NdpResult {
                                     ip: IpAddr::V6(ip),
                                     mac,
-                                    response_time_ms,
+                                    response_time_ms: Some(response_time_ms),
                                 });
                             }
                         }
@@ -270,6 +318,9 @@ impl NdpScanner {
 
             // Also send an ICMPv6 Echo Request to ff02::1 to trigger responses
             // This often gets more responses than NS alone
+            if let Some(packet) = Self::build_echo_request(src_mac, src_ip) {
+                let _ = tx.send_to(&packet, None);
+            }
 
             // Wait for receiver
             let _ = timeout(
@@ -284,7 +335,15 @@ impl NdpScanner {
             }
         }
 
-        // Deduplicate by IP
+        // The kernel table also holds neighbors that only talk to this host, and is read
+        // last so it includes what the probes above taught it
+        all_results.extend(
+            tokio::task::spawn_blocking(read_ipv6_neighbors)
+                .await
+                .unwrap_or_default(),
+        );
+
+        // Deduplicate by IP, keeping probe replies (with a response time) over table entries
         all_results.sort_by(|a, b| a.ip.cmp(&b.ip));
         all_results.dedup_by(|a, b| a.ip == b.ip);
 
@@ -297,3 +356,39 @@ impl Default for NdpScanner {
         Self::new()
     }
 }
+
+/// IPv6 neighbor discovery as a scan type
+pub struct NdpScan;
+
+impl Scanner for NdpScan {
+    fn scan_type(&self) -> ScanType {
+        ScanType::NDP
+    }
+
+    fn label(&self) -> &'static str {
+        "NDP"
+    }
+
+    fn description(&self) -> &'static str {
+        "IPv6 neighbors"
+    }
+
+    fn is_available(&self, privileges: &Privileges) -> bool {
+        privileges.raw_socket
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let scanner = NdpScanner::new().with_timeout(run.timeout_ms());
+            run.discover(async {
+                let results = scanner.scan().await;
+                results.into_iter().map(ScanResult::Ndp).collect::<Vec<_>>()
+            })
+            .await;
+        })
+    }
+}
@@ -0,0 +1,343 @@
+//! Host OS neighbor and routing tables. The kernel already knows every neighbor that has
+//! talked to this host (or answered its multicast echo), so reading the ARP cache and
+//! IPv6 neighbor table (`ip neigh`, `arp -an`/`ndp -an`, `netsh ... show neighbors`)
+//! finds devices without sending anything. The routing table adds next-hop gateways,
+//! including ones for static routes that never show up in captured traffic.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::process::Command;
+
+use pnet::util::MacAddr;
+
+use super::NdpResult;
+
+/// Address family of a neighbor table read
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Family {
+    V4,
+    V6,
+}
+
+/// Neighbors in the host's IPv6 neighbor table (blocking: runs a system command)
+pub fn read_ipv6_neighbors() -> Vec<NdpResult> {
+    read_neighbors(Family::V6)
+        .into_iter()
+        .map(|(ip, mac)| NdpResult {
+            ip,
+            mac,
+            response_time_ms: None,
+        })
+        .collect()
+}
+
+/// Neighbors in the host's ARP cache (blocking: runs a system command)
+pub fn read_ipv4_neighbors() -> Vec<(IpAddr, MacAddr)> {
+    read_neighbors(Family::V4)
+}
+
+/// Next-hop gateways in the host's IPv4 routing table (blocking: runs a system command)
+pub fn read_route_gateways() -> Vec<IpAddr> {
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "windows") {
+        ("route", &["print", "-4"])
+    } else if cfg!(target_os = "macos") {
+        ("netstat", &["-rn", "-f", "inet"])
+    } else {
+        ("ip", &["-4", "route", "show"])
+    };
+    let Some(output) = run(program, args) else {
+        return Vec::new();
+    };
+
+    let mut gateways = if cfg!(target_os = "windows") {
+        parse_windows_routes(&output)
+    } else if cfg!(target_os = "macos") {
+        parse_macos_routes(&output)
+    } else {
+        parse_linux_routes(&output)
+    };
+    gateways.sort();
+    gateways.dedup();
+    gateways.into_iter().map(IpAddr::V4).collect()
+}
+
+fn read_neighbors(family: Family) -> Vec<(IpAddr, MacAddr)> {
+    let v4 = family == Family::V4;
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "windows") {
+        let family = if v4 { "ipv4" } else { "ipv6" };
+        ("netsh", &["interface", family, "show", "neighbors"])
+    } else if cfg!(target_os = "macos") {
+        (if v4 { "arp" } else { "ndp" }, &["-an"])
+    } else {
+        ("ip", &[if v4 { "-4" } else { "-6" }, "neigh", "show"])
+    };
+    let Some(output) = run(program, args) else {
+        return Vec::new();
+    };
+
+    let neighbors = if cfg!(target_os = "windows") {
+        parse_windows_neighbors(&output)
+    } else if cfg!(target_os = "macos") && v4 {
+        parse_macos_arp(&output)
+    } else if cfg!(target_os = "macos") {
+        parse_macos_neighbors(&output)
+    } else {
+        parse_linux_neighbors(&output)
+    };
+    neighbors
+        .into_iter()
+        .filter(|(ip, _)| ip.is_ipv4() == v4)
+        .collect()
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Unicast neighbors with a real hardware address
+fn is_usable(ip: &IpAddr, mac: &MacAddr) -> bool {
+    let unicast = match ip {
+        IpAddr::V4(v4) => !v4.is_broadcast() && !v4.is_multicast(),
+        IpAddr::V6(v6) => !v6.is_multicast(),
+    };
+    unicast
+        && !ip.is_unspecified()
+        && !ip.is_loopback()
+        && *mac != MacAddr::zero()
+        && mac.0 & 0x01 == 0
+}
+
+/// An address as printed by the OS, without a "%en0" zone suffix or surrounding parentheses
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    value
+        .trim_matches(|c| c == '(' || c == ')')
+        .split('%')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// `fe80::1 dev eth0 lladdr aa:bb:cc:dd:ee:ff router REACHABLE`; entries without a
+/// link-layer address or that failed resolution are skipped
+fn parse_linux_neighbors(output: &str) -> Vec<(IpAddr, MacAddr)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields
+                .last()
+                .is_some_and(|state| matches!(*state, "FAILED" | "INCOMPLETE"))
+            {
+                return None;
+            }
+            let ip = parse_ip(fields.first()?)?;
+            let lladdr = fields.iter().position(|field| *field == "lladdr")?;
+            let mac: MacAddr = fields.get(lladdr + 1)?.parse().ok()?;
+            is_usable(&ip, &mac).then_some((ip, mac))
+        })
+        .collect()
+}
+
+/// `fe80::1%en0  a:bb:cc:d:ee:ff  en0 23h59m58s S R`; macOS drops leading zeros from
+/// each octet, which `MacAddr` parsing accepts
+fn parse_macos_neighbors(output: &str) -> Vec<(IpAddr, MacAddr)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let ip = parse_ip(fields.next()?)?;
+            let mac: MacAddr = fields.next()?.parse().ok()?;
+            is_usable(&ip, &mac).then_some((ip, mac))
+        })
+        .collect()
+}
+
+/// `? (192.168.1.1) at a:bb:cc:d:ee:ff on en0 ifscope [ethernet]`; unresolved entries
+/// print "(incomplete)" in place of the address
+fn parse_macos_arp(output: &str) -> Vec<(IpAddr, MacAddr)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let at = fields.iter().position(|field| *field == "at")?;
+            let ip = parse_ip(fields.get(at.checked_sub(1)?)?)?;
+            let mac: MacAddr = fields.get(at + 1)?.parse().ok()?;
+            is_usable(&ip, &mac).then_some((ip, mac))
+        })
+        .collect()
+}
+
+/// `fe80::1    aa-bb-cc-dd-ee-ff  Reachable (Router)`, grouped under per-interface
+/// headings; unreachable entries are skipped
+fn parse_windows_neighbors(output: &str) -> Vec<(IpAddr, MacAddr)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let ip = parse_ip(fields.next()?)?;
+            let mac: MacAddr = fields.next()?.replace('-', ":").parse().ok()?;
+            if fields.next() == Some("Unreachable") {
+                return None;
+            }
+            is_usable(&ip, &mac).then_some((ip, mac))
+        })
+        .collect()
+}
+
+/// `default via 192.168.1.1 dev eth0 proto dhcp metric 100`
+fn parse_linux_routes(output: &str) -> Vec<Ipv4Addr> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let via = fields.iter().position(|field| *field == "via")?;
+            fields.get(via + 1)?.parse().ok()
+        })
+        .collect()
+}
+
+/// `default  192.168.1.1  UGScg  en0`; only rows flagged G route through a gateway
+fn parse_macos_routes(output: &str) -> Vec<Ipv4Addr> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if !fields.get(2)?.contains('G') {
+                return None;
+            }
+            fields.get(1)?.parse().ok()
+        })
+        .collect()
+}
+
+/// `0.0.0.0  0.0.0.0  192.168.1.1  192.168.1.50  25`; directly connected routes show
+/// "On-link" as the gateway
+fn parse_windows_routes(output: &str) -> Vec<Ipv4Addr> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 5
+                || fields[0].parse::<Ipv4Addr>().is_err()
+                || fields[1].parse::<Ipv4Addr>().is_err()
+            {
+                return None;
+            }
+            fields[2].parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbor(ip: &str, mac: &str) -> (IpAddr, MacAddr) {
+        (ip.parse().unwrap(), mac.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_neighbor_tables() {
+        let linux = "fe80::1 dev eth0 lladdr aa:bb:cc:dd:ee:01 router REACHABLE
+2001:db8::5 dev eth0 lladdr 00:11:22:33:44:55 STALE
+fe80::9 dev eth0 FAILED
+2001:db8::6 dev eth0 lladdr 00:11:22:33:44:66 INCOMPLETE
+ff02::1 dev eth0 lladdr 33:33:00:00:00:01 NOARP
+192.168.1.20 dev eth0 lladdr 00:11:22:33:44:20 DELAY";
+        assert_eq!(
+            parse_linux_neighbors(linux),
+            vec![
+                neighbor("fe80::1", "aa:bb:cc:dd:ee:01"),
+                neighbor("2001:db8::5", "00:11:22:33:44:55"),
+                neighbor("192.168.1.20", "00:11:22:33:44:20"),
+            ]
+        );
+
+        let macos =
+            "Neighbor                        Linklayer Address  Netif Expire    St Flgs Prbs
+fe80::1%en0                     a:bb:cc:d:ee:1     en0 23h59m58s S  R
+2601:647:4d00::5                0:11:22:33:44:55   en0 permanent R
+fe80::1234%en0                  (incomplete)       en0 expired   N";
+        assert_eq!(
+            parse_macos_neighbors(macos),
+            vec![
+                neighbor("fe80::1", "0a:bb:cc:0d:ee:01"),
+                neighbor("2601:647:4d00::5", "00:11:22:33:44:55"),
+            ]
+        );
+
+        let macos_arp = "? (192.168.1.1) at a:bb:cc:d:ee:1 on en0 ifscope [ethernet]
+? (192.168.1.7) at (incomplete) on en0 ifscope [ethernet]
+? (192.168.1.255) at ff:ff:ff:ff:ff:ff on en0 ifscope [ethernet]
+printer.lan (192.168.1.30) at 0:11:22:33:44:30 on en0 ifscope [ethernet]";
+        assert_eq!(
+            parse_macos_arp(macos_arp),
+            vec![
+                neighbor("192.168.1.1", "0a:bb:cc:0d:ee:01"),
+                neighbor("192.168.1.30", "00:11:22:33:44:30"),
+            ]
+        );
+
+        let windows = "
+Interface 12: Ethernet
+
+Internet Address                              Physical Address   Type
+--------------------------------------------  -----------------  -----------
+fe80::1                                       aa-bb-cc-dd-ee-01  Reachable (Router)
+2001:db8::7                                   00-11-22-33-44-77  Stale
+2001:db8::8                                   00-00-00-00-00-00  Unreachable
+ff02::1                                       33-33-00-00-00-01  Permanent
+192.168.1.40                                  00-11-22-33-44-40  Stale
+224.0.0.22                                    01-00-5e-00-00-16  Permanent";
+        assert_eq!(
+            parse_windows_neighbors(windows),
+            vec![
+                neighbor("fe80::1", "aa:bb:cc:dd:ee:01"),
+                neighbor("2001:db8::7", "00:11:22:33:44:77"),
+                neighbor("192.168.1.40", "00:11:22:33:44:40"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_route_tables() {
+        let gateway = |ip: &str| ip.parse::<Ipv4Addr>().unwrap();
+
+        let linux = "default via 192.168.1.1 dev eth0 proto dhcp metric 100
+10.20.0.0/16 via 192.168.1.254 dev eth0
+192.168.1.0/24 dev eth0 proto kernel scope link src 192.168.1.50";
+        assert_eq!(
+            parse_linux_routes(linux),
+            vec![gateway("192.168.1.1"), gateway("192.168.1.254")]
+        );
+
+        let macos = "Routing tables
+
+Internet:
+Destination        Gateway            Flags               Netif Expire
+default            192.168.1.1        UGScg                 en0
+10.20/16           192.168.1.254      UGSc                  en0
+192.168.1          link#6             UCS                   en0      !
+192.168.1.30       0:11:22:33:44:30   UHLWIi                en0   1162";
+        assert_eq!(
+            parse_macos_routes(macos),
+            vec![gateway("192.168.1.1"), gateway("192.168.1.254")]
+        );
+
+        let windows = "IPv4 Route Table
+===========================================================================
+Active Routes:
+Network Destination        Netmask          Gateway       Interface  Metric
+          0.0.0.0          0.0.0.0      192.168.1.1    192.168.1.50     25
+        10.20.0.0      255.255.0.0    192.168.1.254    192.168.1.50     26
+      192.168.1.0    255.255.255.0         On-link     192.168.1.50    281
+===========================================================================
+Persistent Routes:
+  Network Address          Netmask  Gateway Address  Metric
+        10.20.0.0      255.255.0.0    192.168.1.254       1";
+        assert_eq!(
+            parse_windows_routes(windows),
+            vec![gateway("192.168.1.1"), gateway("192.168.1.254")]
+        );
+    }
+}
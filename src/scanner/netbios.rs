@@ -1,11 +1,17 @@
 //! NetBIOS scanner. Queries UDP port 137 to discover Windows device names
-//! and workgroup information via NetBIOS Name Service (NBNS) requests.
+//! and workgroup information via NetBIOS Name Service (NBNS) requests, and
+//! optionally enumerates SMB shares on port 445.
 
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::sync::atomic::{AtomicU16, Ordering};
 use std::time::Duration;
 
-use super::NetBiosResult;
+use futures::future::BoxFuture;
+
+use super::manager::ScanRun;
+use super::registry::{ConfigField, Scanner, Tunables};
+use super::smb::SmbScanner;
+use super::{NetBiosResult, ScanResult, ScanType};
 
 /// Transaction ID counter for NetBIOS requests
 static TRANSACTION_ID: AtomicU16 = AtomicU16::new(1);
@@ -16,11 +22,18 @@ const NETBIOS_PORT: u16 = 137;
 /// Queries devices on UDP port 137 for their NetBIOS names
 pub struct NetBiosScanner {
     timeout_ms: u64,
+    enumerate_shares: bool,
+    /// Extra queries for hosts that did not answer
+    retries: u8,
 }
 
 impl NetBiosScanner {
     pub fn new() -> Self {
-        Self { timeout_ms: 1000 }
+        Self {
+            timeout_ms: 1000,
+            enumerate_shares: false,
+            retries: 0,
+        }
     }
 
     pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
@@ -28,6 +41,17 @@ impl NetBiosScanner {
         self
     }
 
+    pub fn with_retries(mut self, retries: u8) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Also probe SMB on port 445 for the workgroup/domain and anonymously visible shares
+    pub fn with_share_enumeration(mut self, enabled: bool) -> Self {
+        self.enumerate_shares = enabled;
+        self
+    }
+
     /// Build a NetBIOS Node Status Request packet
     /// This queries for the "*" name to get the full name table
     fn build_nbstat_request(transaction_id: u16) -> Vec<u8> {
@@ -176,8 +200,34 @@ impl NetBiosScanner {
         computer_name.map(|name| (name, group_name, mac))
     }
 
-    /// Query a single IP for NetBIOS name
+    /// Query a single IP for NetBIOS name. With share enumeration, a host that only
+    /// answers SMB is still reported under the computer name from its NTLM challenge.
     pub fn query_ip(&self, ip: Ipv4Addr) -> Option<NetBiosResult> {
+        let name_table = self.query_name_table(ip);
+        let smb = self
+            .enumerate_shares
+            .then(|| SmbScanner::new().with_timeout(self.timeout_ms).query_ip(ip))
+            .flatten();
+
+        let (netbios_name, group_name, mac) = match name_table {
+            Some(names) => names,
+            None => {
+                let smb = smb.as_ref()?;
+                (smb.computer_name.clone()?, smb.domain.clone(), None)
+            }
+        };
+
+        Some(NetBiosResult {
+            ip: IpAddr::V4(ip),
+            netbios_name,
+            group_name,
+            mac,
+            smb,
+        })
+    }
+
+    /// Send a Node Status Request and parse the name table
+    fn query_name_table(&self, ip: Ipv4Addr) -> Option<(String, Option<String>, Option<String>)> {
         let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
         socket
             .set_read_timeout(Some(Duration::from_millis(self.timeout_ms)))
@@ -191,19 +241,14 @@ impl NetBiosScanner {
         let mut buf = [0u8; 512];
         let (len, _) = socket.recv_from(&mut buf).ok()?;
 
-        let (netbios_name, group_name, mac) = Self::parse_nbstat_response(&buf[..len])?;
-
-        Some(NetBiosResult {
-            ip: IpAddr::V4(ip),
-            netbios_name,
-            group_name,
-            mac,
-        })
+        Self::parse_nbstat_response(&buf[..len])
     }
 
     /// Scan a list of IPs for NetBIOS names
     pub async fn scan_ips(&self, ips: &[IpAddr]) -> Vec<NetBiosResult> {
         let timeout_ms = self.timeout_ms;
+        let enumerate_shares = self.enumerate_shares;
+        let retries = self.retries;
         let ips: Vec<Ipv4Addr> = ips
             .iter()
             .filter_map(|ip| match ip {
@@ -218,8 +263,10 @@ impl NetBiosScanner {
         for ip in ips {
             let timeout = timeout_ms;
             handles.push(tokio::task::spawn_blocking(move || {
-                let scanner = NetBiosScanner::new().with_timeout(timeout);
-                scanner.query_ip(ip)
+                let scanner = NetBiosScanner::new()
+                    .with_timeout(timeout)
+                    .with_share_enumeration(enumerate_shares);
+                (0..=retries).find_map(|_| scanner.query_ip(ip))
             }));
         }
 
@@ -240,6 +287,62 @@ impl Default for NetBiosScanner {
     }
 }
 
+/// NetBIOS name queries, and SMB share listing, as a scan type
+pub struct NetBiosScan;
+
+impl Scanner for NetBiosScan {
+    fn scan_type(&self) -> ScanType {
+        ScanType::NETBIOS
+    }
+
+    fn label(&self) -> &'static str {
+        "NetBIOS"
+    }
+
+    fn description(&self) -> &'static str {
+        "Windows names"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![ConfigField {
+            key: "enumerate_smb_shares",
+            label: "List SMB shares",
+            kind: "boolean",
+            description: "Also list shares over an anonymous SMB session on port 445",
+        }]
+    }
+
+    fn tunables(&self) -> Tunables {
+        Tunables {
+            retries: true,
+            parallelism: false,
+        }
+    }
+
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let scanner = NetBiosScanner::new()
+                .with_timeout(run.timeout_ms())
+                .with_share_enumeration(run.config().enumerate_smb_shares)
+                .with_retries(run.tuning().retries.unwrap_or(0));
+            let scanner = &scanner;
+            run.scan_targets(1, move |ips| async move {
+                scanner
+                    .scan_ips(&ips)
+                    .await
+                    .into_iter()
+                    .map(ScanResult::NetBios)
+                    .collect::<Vec<_>>()
+            })
+            .await;
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -272,6 +375,7 @@ mod tests {
     fn test_scanner_default() {
         let scanner = NetBiosScanner::default();
         assert_eq!(scanner.timeout_ms, 1000);
+        assert!(!scanner.enumerate_shares);
     }
 
     #[test]
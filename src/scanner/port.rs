@@ -4,14 +4,17 @@
 use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
+use futures::future::BoxFuture;
 use tokio::net::TcpStream;
 use tokio::sync::Semaphore;
 use tokio::time::timeout;
 
-use super::PortResult;
+use super::manager::ScanRun;
+use super::registry::{ConfigField, Scanner, Tunables};
+use super::{PortResult, ScanResult, ScanType};
 
 /// Map well-known port numbers to service names
-fn port_to_service_name(port: u16) -> Option<String> {
+pub(super) fn port_to_service_name(port: u16) -> Option<String> {
     match port {
         22 => Some("SSH".to_string()),
         80 => Some("HTTP".to_string()),
@@ -23,6 +26,7 @@ fn port_to_service_name(port: u16) -> Option<String> {
         8080 => Some("HTTP-Alt".to_string()),
         8443 => Some("HTTPS-Alt".to_string()),
         9100 => Some("Printer".to_string()),
+        3493 => Some("NUT".to_string()),
         21 => Some("FTP".to_string()),
         23 => Some("Telnet".to_string()),
         25 => Some("SMTP".to_string()),
@@ -47,6 +51,7 @@ pub const DEFAULT_PORTS: &[u16] = &[
     8080, // HTTP Alt
     8443, // HTTPS Alt
     9100, // Printer
+    3493, // Network UPS Tools
 ];
 
 /// TCP port scanner using async connect
@@ -68,6 +73,11 @@ impl PortScanner {
         self
     }
 
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.max_concurrent = parallelism.max(1);
+        self
+    }
+
     /// Scan a single port on an IP
     async fn scan_port(&self, ip: IpAddr, port: u16) -> PortResult {
         let addr = SocketAddr::new(ip, port);
@@ -130,6 +140,58 @@ impl Default for PortScanner {
     }
 }
 
+/// The TCP connect scan as a scan type
+pub struct PortScan;
+
+impl Scanner for PortScan {
+    fn scan_type(&self) -> ScanType {
+        ScanType::PORT
+    }
+
+    fn label(&self) -> &'static str {
+        "Ports"
+    }
+
+    fn description(&self) -> &'static str {
+        "TCP port probe"
+    }
+
+    fn config_schema(&self) -> Vec<ConfigField> {
+        vec![ConfigField {
+            key: "ports",
+            label: "Ports",
+            kind: "ports",
+            description: "TCP ports probed on each host",
+        }]
+    }
+
+    fn tunables(&self) -> Tunables {
+        Tunables {
+            retries: false,
+            parallelism: true,
+        }
+    }
+
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let mut scanner = PortScanner::new().with_timeout(run.timeout_ms());
+            if let Some(parallelism) = run.tuning().parallelism {
+                scanner = scanner.with_parallelism(parallelism);
+            }
+            let (scanner, ports) = (&scanner, &run.config().ports);
+            run.scan_targets(ports.len() as u64, move |ips| async move {
+                scanner
+                    .scan_ips(&ips, ports)
+                    .await
+                    .into_iter()
+                    .map(ScanResult::Port)
+                    .collect::<Vec<_>>()
+            })
+            .await;
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,163 @@
+//! Printer MIB (RFC 3805) polling over SNMP: marker supply levels (toner, ink, drums,
+//! waste containers), the lifetime page counter, and the Host Resources printer status.
+//! Supplies are read by index on the first marker device, which is where every printer
+//! seen so far keeps them.
+
+use std::net::Ipv4Addr;
+
+use serde::Serialize;
+
+use super::snmp::SnmpScanner;
+
+/// prtMarkerLifeCount for marker 1 of device 1
+const OID_PAGE_COUNT: &[u32] = &[1, 3, 6, 1, 2, 1, 43, 10, 2, 1, 4, 1, 1];
+/// hrPrinterStatus for device 1
+const OID_PRINTER_STATUS: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 3, 5, 1, 1, 1];
+/// hrDeviceStatus for device 1
+const OID_DEVICE_STATUS: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 3, 2, 1, 5, 1];
+
+/// prtMarkerSuppliesEntry columns, followed by device 1 and the supply index
+const SUPPLIES_ENTRY: &[u32] = &[1, 3, 6, 1, 2, 1, 43, 11, 1, 1];
+const SUPPLY_DESCRIPTION: u32 = 6;
+const SUPPLY_MAX_CAPACITY: u32 = 8;
+const SUPPLY_LEVEL: u32 = 9;
+
+/// Supply indexes read; colour lasers with drums and a waste bottle use up to about ten
+const MAX_SUPPLIES: u32 = 12;
+
+/// Supplies per GET request, keeping responses well under small SNMP message limits
+const SUPPLIES_PER_REQUEST: u32 = 4;
+
+/// One marker supply and how much of it is left
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrinterSupply {
+    pub index: u32,
+    pub description: String,
+    /// Current level in the supply's own units; -1 other, -2 unknown, -3 some remaining
+    pub level: i64,
+    /// Full level in the same units; -1 and -2 when the printer doesn't say
+    pub max_capacity: i64,
+}
+
+impl PrinterSupply {
+    /// Percent remaining, when the printer reports both a level and a capacity
+    pub fn percent(&self) -> Option<i64> {
+        (self.level >= 0 && self.max_capacity > 0)
+            .then(|| (self.level * 100 / self.max_capacity).min(100))
+    }
+}
+
+/// What one poll of a printer returned
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PrinterStatus {
+    /// Pages printed over the printer's life
+    pub page_count: Option<i64>,
+    /// idle, printing, warmup, warning, down, or other
+    pub status: Option<String>,
+    pub supplies: Vec<PrinterSupply>,
+}
+
+fn supply_oid(column: u32, index: u32) -> Vec<u32> {
+    let mut oid = SUPPLIES_ENTRY.to_vec();
+    oid.extend([column, 1, index]);
+    oid
+}
+
+/// Read supplies, page count and status from a printer. None when it doesn't answer or
+/// doesn't implement the Printer MIB.
+pub fn query_printer(scanner: &SnmpScanner, ip: Ipv4Addr) -> Option<PrinterStatus> {
+    let mut requests = vec![vec![
+        OID_PAGE_COUNT.to_vec(),
+        OID_PRINTER_STATUS.to_vec(),
+        OID_DEVICE_STATUS.to_vec(),
+    ]];
+    for first in (1..=MAX_SUPPLIES).step_by(SUPPLIES_PER_REQUEST as usize) {
+        let last = (first + SUPPLIES_PER_REQUEST - 1).min(MAX_SUPPLIES);
+        requests.push(
+            (first..=last)
+                .flat_map(|index| {
+                    [SUPPLY_DESCRIPTION, SUPPLY_MAX_CAPACITY, SUPPLY_LEVEL]
+                        .map(|column| supply_oid(column, index))
+                })
+                .collect(),
+        );
+    }
+
+    let status = parse_printer_status(&scanner.get(ip, &requests));
+    (status.page_count.is_some() || !status.supplies.is_empty()).then_some(status)
+}
+
+/// Build a printer status from the values of a poll
+fn parse_printer_status(values: &[(Vec<u32>, String)]) -> PrinterStatus {
+    let value = |oid: &[u32]| {
+        values
+            .iter()
+            .find(|(o, _)| o == oid)
+            .map(|(_, value)| value.as_str())
+    };
+    let number = |oid: &[u32]| value(oid).and_then(|v| v.parse::<i64>().ok());
+
+    // A warning or fault on the device outranks what the printer is doing
+    let status = match number(OID_DEVICE_STATUS) {
+        Some(3) => Some("warning"),
+        Some(5) => Some("down"),
+        _ => match number(OID_PRINTER_STATUS) {
+            Some(3) => Some("idle"),
+            Some(4) => Some("printing"),
+            Some(5) => Some("warmup"),
+            Some(_) => Some("other"),
+            None => None,
+        },
+    };
+
+    let supplies = (1..=MAX_SUPPLIES)
+        .filter_map(|index| {
+            let description = value(&supply_oid(SUPPLY_DESCRIPTION, index))?;
+            Some(PrinterSupply {
+                index,
+                description: description.trim_end_matches('\0').trim().to_string(),
+                level: number(&supply_oid(SUPPLY_LEVEL, index)).unwrap_or(-2),
+                max_capacity: number(&supply_oid(SUPPLY_MAX_CAPACITY, index)).unwrap_or(-2),
+            })
+        })
+        .collect();
+
+    PrinterStatus {
+        page_count: number(OID_PAGE_COUNT),
+        status: status.map(String::from),
+        supplies,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_printer_status() {
+        let values = vec![
+            (OID_PAGE_COUNT.to_vec(), "48213".to_string()),
+            (OID_PRINTER_STATUS.to_vec(), "3".to_string()),
+            (OID_DEVICE_STATUS.to_vec(), "3".to_string()),
+            (
+                supply_oid(SUPPLY_DESCRIPTION, 1),
+                "Black Toner Cartridge HP CF410A".to_string(),
+            ),
+            (supply_oid(SUPPLY_MAX_CAPACITY, 1), "100".to_string()),
+            (supply_oid(SUPPLY_LEVEL, 1), "7".to_string()),
+            (
+                supply_oid(SUPPLY_DESCRIPTION, 2),
+                "Imaging Drum".to_string(),
+            ),
+            (supply_oid(SUPPLY_MAX_CAPACITY, 2), "-2".to_string()),
+            (supply_oid(SUPPLY_LEVEL, 2), "-3".to_string()),
+        ];
+        let status = parse_printer_status(&values);
+        assert_eq!(status.page_count, Some(48213));
+        assert_eq!(status.status.as_deref(), Some("warning"));
+        assert_eq!(status.supplies.len(), 2);
+        assert_eq!(status.supplies[0].percent(), Some(7));
+        // "Some remaining" has no percentage
+        assert_eq!(status.supplies[1].percent(), None);
+    }
+}
@@ -0,0 +1,222 @@
+//! Scanner registry. A scan type is a `Scanner` implementation in its own module: once
+//! registered, the manager can run it, `ScanType` parses its name, and the API and UI
+//! list it with its availability and settings. Scanners report through the typed
+//! results in `ScanResult`, which the result pipeline stores.
+
+use std::sync::{Arc, OnceLock, RwLock};
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use super::arp::ArpScan;
+use super::icmp::IcmpScan;
+use super::manager::ScanRun;
+use super::ndp::NdpScan;
+use super::netbios::NetBiosScan;
+use super::port::PortScan;
+use super::snmp::SnmpScan;
+use super::ssdp::SsdpScan;
+use super::{Privileges, ScanType};
+
+/// A scan config setting a scanner reads
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigField {
+    /// Field name in `ScanConfig`
+    pub key: &'static str,
+    pub label: &'static str,
+    /// "integer", "boolean" or "ports"
+    pub kind: &'static str,
+    pub description: &'static str,
+}
+
+/// Which per scan type overrides in `ScanTuning` a scanner takes besides the timeout and
+/// rate cap, which every scanner honours
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Tunables {
+    pub retries: bool,
+    pub parallelism: bool,
+}
+
+/// A scan type the manager can run
+pub trait Scanner: Send + Sync {
+    fn scan_type(&self) -> ScanType;
+
+    /// Short name for the UI, e.g. "ARP"
+    fn label(&self) -> &'static str;
+
+    /// One line on what the scan finds
+    fn description(&self) -> &'static str;
+
+    /// Whether the scan can run with these privileges
+    fn is_available(&self, _privileges: &Privileges) -> bool {
+        true
+    }
+
+    /// Part of the default scan config
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    /// Scan config settings the scanner reads besides the shared timeout
+    fn config_schema(&self) -> Vec<ConfigField> {
+        Vec::new()
+    }
+
+    /// Per scan type overrides the scanner takes
+    fn tunables(&self) -> Tunables {
+        Tunables::default()
+    }
+
+    /// Scan until done, planning progress and reporting results through `run`. The
+    /// future is dropped when the scan type is cancelled.
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()>;
+}
+
+/// A registered scanner as the API lists it
+#[derive(Debug, Clone, Serialize)]
+pub struct ScannerInfo {
+    pub scan_type: ScanType,
+    pub label: &'static str,
+    pub description: &'static str,
+    pub available: bool,
+    pub enabled_by_default: bool,
+    pub config: Vec<ConfigField>,
+    pub tunables: Tunables,
+}
+
+fn registry() -> &'static RwLock<Vec<Arc<dyn Scanner>>> {
+    static REGISTRY: OnceLock<RwLock<Vec<Arc<dyn Scanner>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        RwLock::new(vec![
+            Arc::new(ArpScan),
+            Arc::new(NdpScan),
+            Arc::new(IcmpScan),
+            Arc::new(PortScan),
+            Arc::new(SsdpScan),
+            Arc::new(NetBiosScan),
+            Arc::new(SnmpScan),
+        ])
+    })
+}
+
+/// Add a scanner, replacing one already registered under the same scan type
+pub fn register(scanner: Arc<dyn Scanner>) {
+    if let Ok(mut scanners) = registry().write() {
+        scanners.retain(|s| s.scan_type() != scanner.scan_type());
+        scanners.push(scanner);
+    }
+}
+
+/// Registered scanners, in registration order
+pub fn scanners() -> Vec<Arc<dyn Scanner>> {
+    registry()
+        .read()
+        .map(|scanners| scanners.clone())
+        .unwrap_or_default()
+}
+
+/// The scanner registered under a scan type name, ignoring case
+pub fn find(name: &str) -> Option<Arc<dyn Scanner>> {
+    scanners()
+        .into_iter()
+        .find(|scanner| scanner.scan_type().as_str().eq_ignore_ascii_case(name))
+}
+
+/// The scanner that runs a scan type
+pub fn get(scan_type: ScanType) -> Option<Arc<dyn Scanner>> {
+    scanners()
+        .into_iter()
+        .find(|scanner| scanner.scan_type() == scan_type)
+}
+
+/// Describe every registered scanner for the API
+pub fn scanner_info(privileges: &Privileges) -> Vec<ScannerInfo> {
+    scanners()
+        .iter()
+        .map(|scanner| ScannerInfo {
+            scan_type: scanner.scan_type(),
+            label: scanner.label(),
+            description: scanner.description(),
+            available: scanner.is_available(privileges),
+            enabled_by_default: scanner.enabled_by_default(),
+            config: scanner.config_schema(),
+            tunables: scanner.tunables(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BANNER: ScanType = ScanType::new("test-banner");
+
+    struct BannerScan;
+
+    impl Scanner for BannerScan {
+        fn scan_type(&self) -> ScanType {
+            BANNER
+        }
+
+        fn label(&self) -> &'static str {
+            "Banner"
+        }
+
+        fn description(&self) -> &'static str {
+            "Service banners"
+        }
+
+        fn run<'a>(&'a self, _run: &'a ScanRun) -> BoxFuture<'a, ()> {
+            Box::pin(async {})
+        }
+    }
+
+    #[test]
+    fn test_builtin_scanners_registered() {
+        let names: Vec<ScanType> = scanners().iter().map(|s| s.scan_type()).collect();
+        for scan_type in [
+            ScanType::ARP,
+            ScanType::ICMP,
+            ScanType::NDP,
+            ScanType::NETBIOS,
+            ScanType::PORT,
+            ScanType::SNMP,
+            ScanType::SSDP,
+        ] {
+            assert_eq!(names.iter().filter(|n| **n == scan_type).count(), 1);
+        }
+
+        let privileges = Privileges {
+            raw_socket: false,
+            icmp_datagram: true,
+        };
+        let info = scanner_info(&privileges);
+        let available = |scan_type| {
+            info.iter()
+                .find(|i| i.scan_type == scan_type)
+                .is_some_and(|i| i.available)
+        };
+        assert!(!available(ScanType::ARP));
+        assert!(available(ScanType::ICMP));
+        assert!(available(ScanType::SSDP));
+    }
+
+    #[test]
+    fn test_register_scanner() {
+        assert!("test-banner".parse::<ScanType>().is_err());
+
+        register(Arc::new(BannerScan));
+        register(Arc::new(BannerScan));
+        assert_eq!("Test-Banner".parse::<ScanType>(), Ok(BANNER));
+        assert_eq!(
+            scanners()
+                .iter()
+                .filter(|s| s.scan_type() == BANNER)
+                .count(),
+            1
+        );
+        let types: Vec<ScanType> = serde_json::from_str(r#"["arp","test-banner"]"#).unwrap();
+        assert_eq!(types, vec![ScanType::ARP, BANNER]);
+        assert!(get(BANNER).is_some());
+    }
+}
@@ -0,0 +1,878 @@
+//! SMB share enumeration. Connects to TCP port 445, reads the computer and domain names
+//! from the NTLM challenge, then attempts an anonymous (null) session to list shares over
+//! the srvsvc pipe and check which disk shares can be opened without credentials.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use super::{SmbInfo, SmbShare};
+
+const SMB_PORT: u16 = 445;
+
+const SMB2_NEGOTIATE: u16 = 0x0000;
+const SMB2_SESSION_SETUP: u16 = 0x0001;
+const SMB2_TREE_CONNECT: u16 = 0x0003;
+const SMB2_TREE_DISCONNECT: u16 = 0x0004;
+const SMB2_CREATE: u16 = 0x0005;
+const SMB2_CLOSE: u16 = 0x0006;
+const SMB2_READ: u16 = 0x0008;
+const SMB2_IOCTL: u16 = 0x000B;
+
+const SMB2_HEADER_LEN: usize = 64;
+/// Dialects offered: 2.0.2, 2.1, 3.0, 3.0.2 (3.1.1 needs negotiate contexts)
+const DIALECTS: &[u16] = &[0x0202, 0x0210, 0x0300, 0x0302];
+
+const STATUS_SUCCESS: u32 = 0x0000_0000;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xC000_0016;
+
+const FSCTL_PIPE_TRANSCEIVE: u32 = 0x0011_C017;
+/// Largest single pipe read/transceive (one credit)
+const MAX_PIPE_RESPONSE: u32 = 65_535;
+/// Disk shares checked for guest access per host
+const MAX_SHARES_CHECKED: usize = 32;
+
+const NTLMSSP_SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+/// UNICODE | REQUEST_TARGET | NTLM | ALWAYS_SIGN | EXTENDED_SESSIONSECURITY | 128 | 56
+const NTLMSSP_NEGOTIATE_FLAGS: u32 = 0xA008_8205;
+const NTLMSSP_NEGOTIATE_ANONYMOUS: u32 = 0x0000_0800;
+
+/// SPNEGO and NTLMSSP mechanism OIDs (DER encoded, with tag and length)
+const SPNEGO_OID: &[u8] = &[0x06, 0x06, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x02];
+const NTLMSSP_OID: &[u8] = &[
+    0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02, 0x0a,
+];
+
+/// srvsvc interface 4b324fc8-1670-01d3-1278-5a47bf6ee188 v3.0
+const SRVSVC_UUID: [u8; 16] = [
+    0xc8, 0x4f, 0x32, 0x4b, 0x70, 0x16, 0xd3, 0x01, 0x12, 0x78, 0x5a, 0x47, 0xbf, 0x6e, 0xe1, 0x88,
+];
+/// NDR transfer syntax 8a885d04-1ceb-11c9-9fe8-08002b104860 v2
+const NDR_UUID: [u8; 16] = [
+    0x04, 0x5d, 0x88, 0x8a, 0xeb, 0x1c, 0xc9, 0x11, 0x9f, 0xe8, 0x08, 0x00, 0x2b, 0x10, 0x48, 0x60,
+];
+const DCERPC_BIND: u8 = 11;
+const DCERPC_BIND_ACK: u8 = 12;
+const DCERPC_REQUEST: u8 = 0;
+const DCERPC_RESPONSE: u8 = 2;
+const DCERPC_LAST_FRAG: u8 = 0x02;
+const DCERPC_MAX_FRAG: u16 = 4280;
+const OPNUM_NETR_SHARE_ENUM: u16 = 15;
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn get_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn get_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn get_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn from_utf16le(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// DER tag + length + content
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else if len <= 0xFF {
+        out.extend_from_slice(&[0x81, len as u8]);
+    } else {
+        out.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Wrap the first NTLMSSP token in a SPNEGO NegTokenInit
+fn spnego_init(token: &[u8]) -> Vec<u8> {
+    let mech_types = der(0xa0, &der(0x30, NTLMSSP_OID));
+    let mech_token = der(0xa2, &der(0x04, token));
+    let neg_token_init = der(0xa0, &der(0x30, &[mech_types, mech_token].concat()));
+    der(0x60, &[SPNEGO_OID, &neg_token_init].concat())
+}
+
+/// Wrap a follow-up NTLMSSP token in a SPNEGO NegTokenResp
+fn spnego_response(token: &[u8]) -> Vec<u8> {
+    der(0xa1, &der(0x30, &der(0xa2, &der(0x04, token))))
+}
+
+fn ntlm_negotiate() -> Vec<u8> {
+    let mut msg = NTLMSSP_SIGNATURE.to_vec();
+    put_u32(&mut msg, 1);
+    put_u32(&mut msg, NTLMSSP_NEGOTIATE_FLAGS);
+    msg.extend_from_slice(&[0u8; 16]); // empty domain and workstation fields
+    msg
+}
+
+/// Anonymous AUTHENTICATE: no user, empty NT response and a single zero byte LM response
+fn ntlm_anonymous_authenticate(challenge_flags: u32) -> Vec<u8> {
+    const PAYLOAD_OFFSET: u32 = 64;
+    let mut msg = NTLMSSP_SIGNATURE.to_vec();
+    put_u32(&mut msg, 3);
+    // LmChallengeResponse: Z(1)
+    put_u16(&mut msg, 1);
+    put_u16(&mut msg, 1);
+    put_u32(&mut msg, PAYLOAD_OFFSET);
+    // NtChallengeResponse, DomainName, UserName, Workstation, EncryptedRandomSessionKey
+    for _ in 0..5 {
+        put_u16(&mut msg, 0);
+        put_u16(&mut msg, 0);
+        put_u32(&mut msg, PAYLOAD_OFFSET + 1);
+    }
+    put_u32(
+        &mut msg,
+        (challenge_flags & NTLMSSP_NEGOTIATE_FLAGS) | NTLMSSP_NEGOTIATE_ANONYMOUS,
+    );
+    msg.push(0);
+    msg
+}
+
+/// Names from the NTLM CHALLENGE message's target info
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ChallengeNames {
+    flags: u32,
+    computer_name: Option<String>,
+    domain: Option<String>,
+    dns_computer_name: Option<String>,
+    dns_domain: Option<String>,
+}
+
+/// Parse the NTLM CHALLENGE message out of a (possibly SPNEGO wrapped) security blob
+fn parse_ntlm_challenge(blob: &[u8]) -> Option<ChallengeNames> {
+    let start = blob
+        .windows(NTLMSSP_SIGNATURE.len())
+        .position(|w| w == NTLMSSP_SIGNATURE)?;
+    let msg = &blob[start..];
+    if get_u32(msg, 8)? != 2 {
+        return None;
+    }
+    let mut names = ChallengeNames {
+        flags: get_u32(msg, 20)?,
+        ..ChallengeNames::default()
+    };
+
+    let info_len = get_u16(msg, 40)? as usize;
+    let info_offset = get_u32(msg, 44)? as usize;
+    let info = msg.get(info_offset..info_offset + info_len)?;
+    let mut pos = 0;
+    while let (Some(id), Some(len)) = (get_u16(info, pos), get_u16(info, pos + 2)) {
+        let Some(value) = info.get(pos + 4..pos + 4 + len as usize) else {
+            break;
+        };
+        let value = Some(from_utf16le(value)).filter(|v| !v.is_empty());
+        match id {
+            0 => break,
+            1 => names.computer_name = value,
+            2 => names.domain = value,
+            3 => names.dns_computer_name = value,
+            4 => names.dns_domain = value,
+            _ => {}
+        }
+        pos += 4 + len as usize;
+    }
+    Some(names)
+}
+
+/// Reads NDR data from a srvsvc response stub
+struct NdrReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl NdrReader<'_> {
+    fn u32(&mut self) -> Option<u32> {
+        let value = get_u32(self.data, self.pos)?;
+        self.pos += 4;
+        Some(value)
+    }
+
+    /// Conformant varying UTF-16 string, padded to 4 bytes
+    fn string(&mut self) -> Option<String> {
+        let _max_count = self.u32()?;
+        let _offset = self.u32()?;
+        let count = self.u32()? as usize;
+        let bytes = self.data.get(self.pos..self.pos + count * 2)?;
+        self.pos += (count * 2).next_multiple_of(4);
+        Some(from_utf16le(bytes))
+    }
+}
+
+/// Parse a NetrShareEnum level 1 response stub into (name, type, remark) entries
+fn parse_share_enum(stub: &[u8]) -> Option<Vec<(String, u32, Option<String>)>> {
+    let mut r = NdrReader { data: stub, pos: 0 };
+    let _level = r.u32()?;
+    let _switch = r.u32()?;
+    if r.u32()? == 0 {
+        return Some(Vec::new()); // no container
+    }
+    let entries_read = r.u32()? as usize;
+    if r.u32()? == 0 {
+        return Some(Vec::new()); // no buffer
+    }
+    let count = r.u32()? as usize;
+    if count != entries_read || count > stub.len() / 12 {
+        return None;
+    }
+
+    let mut headers = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name_ptr = r.u32()?;
+        let share_type = r.u32()?;
+        let remark_ptr = r.u32()?;
+        headers.push((name_ptr, share_type, remark_ptr));
+    }
+
+    let mut shares = Vec::with_capacity(count);
+    for (name_ptr, share_type, remark_ptr) in headers {
+        let name = if name_ptr != 0 {
+            r.string()?
+        } else {
+            String::new()
+        };
+        let remark = if remark_ptr != 0 {
+            Some(r.string()?)
+        } else {
+            None
+        };
+        if !name.is_empty() {
+            shares.push((name, share_type, remark.filter(|r| !r.is_empty())));
+        }
+    }
+    Some(shares)
+}
+
+fn share_type_name(share_type: u32) -> &'static str {
+    match share_type & 0xFF {
+        0 => "disk",
+        1 => "printer",
+        2 => "device",
+        3 => "ipc",
+        _ => "other",
+    }
+}
+
+fn dcerpc_header(ptype: u8, frag_length: u16, call_id: u32) -> Vec<u8> {
+    let mut pdu = vec![5, 0, ptype, 0x03, 0x10, 0, 0, 0];
+    put_u16(&mut pdu, frag_length);
+    put_u16(&mut pdu, 0); // auth_length
+    put_u32(&mut pdu, call_id);
+    pdu
+}
+
+fn dcerpc_bind() -> Vec<u8> {
+    let mut pdu = dcerpc_header(DCERPC_BIND, 72, 1);
+    put_u16(&mut pdu, DCERPC_MAX_FRAG);
+    put_u16(&mut pdu, DCERPC_MAX_FRAG);
+    put_u32(&mut pdu, 0); // assoc_group_id
+    pdu.extend_from_slice(&[1, 0, 0, 0]); // one context
+    put_u16(&mut pdu, 0); // context id
+    pdu.extend_from_slice(&[1, 0]); // one transfer syntax
+    pdu.extend_from_slice(&SRVSVC_UUID);
+    put_u32(&mut pdu, 3);
+    pdu.extend_from_slice(&NDR_UUID);
+    put_u32(&mut pdu, 2);
+    pdu
+}
+
+/// NetrShareEnum(ServerName = NULL, Level = 1, PreferedMaximumLength = MAX, ResumeHandle = NULL)
+fn dcerpc_share_enum_request() -> Vec<u8> {
+    let mut stub = Vec::new();
+    put_u32(&mut stub, 0); // ServerName
+    put_u32(&mut stub, 1); // Level
+    put_u32(&mut stub, 1); // union switch
+    put_u32(&mut stub, 0x0002_0000); // SHARE_INFO_1_CONTAINER referent
+    put_u32(&mut stub, 0); // EntriesRead
+    put_u32(&mut stub, 0); // Buffer
+    put_u32(&mut stub, u32::MAX); // PreferedMaximumLength
+    put_u32(&mut stub, 0); // ResumeHandle
+
+    let mut pdu = dcerpc_header(DCERPC_REQUEST, (24 + stub.len()) as u16, 2);
+    put_u32(&mut pdu, stub.len() as u32); // alloc_hint
+    put_u16(&mut pdu, 0); // context id
+    put_u16(&mut pdu, OPNUM_NETR_SHARE_ENUM);
+    pdu.extend_from_slice(&stub);
+    pdu
+}
+
+/// Append the stub data of the response PDUs in `data` to `stub`. Returns whether the
+/// last fragment has been seen, or None for a fault.
+fn dcerpc_response_stub(data: &[u8], stub: &mut Vec<u8>) -> Option<bool> {
+    let mut pos = 0;
+    let mut last = false;
+    while pos < data.len() {
+        let pdu = &data[pos..];
+        if *pdu.get(2)? != DCERPC_RESPONSE {
+            return None; // fault or unexpected PDU
+        }
+        let frag_length = get_u16(pdu, 8)? as usize;
+        let auth_length = get_u16(pdu, 10)? as usize;
+        let body_end =
+            frag_length.checked_sub(auth_length + if auth_length > 0 { 8 } else { 0 })?;
+        stub.extend_from_slice(pdu.get(24..body_end)?);
+        last = pdu[3] & DCERPC_LAST_FRAG != 0;
+        pos += frag_length.max(1);
+    }
+    Some(last)
+}
+
+/// One SMB2 session over a TCP connection
+struct SmbSession {
+    stream: TcpStream,
+    message_id: u64,
+    session_id: u64,
+}
+
+struct SmbResponse {
+    status: u32,
+    session_id: u64,
+    tree_id: u32,
+    /// The whole SMB2 message; offsets in bodies are relative to its start
+    message: Vec<u8>,
+}
+
+impl SmbResponse {
+    fn body(&self) -> &[u8] {
+        &self.message[SMB2_HEADER_LEN..]
+    }
+}
+
+impl SmbSession {
+    fn connect(ip: Ipv4Addr, timeout: Duration) -> Option<Self> {
+        let stream =
+            TcpStream::connect_timeout(&SocketAddr::new(IpAddr::V4(ip), SMB_PORT), timeout).ok()?;
+        stream.set_read_timeout(Some(timeout)).ok()?;
+        stream.set_write_timeout(Some(timeout)).ok()?;
+        Some(Self {
+            stream,
+            message_id: 0,
+            session_id: 0,
+        })
+    }
+
+    /// Send one request and wait for its (non-pending) response
+    fn request(&mut self, command: u16, tree_id: u32, body: &[u8]) -> Option<SmbResponse> {
+        let mut header = Vec::with_capacity(SMB2_HEADER_LEN + body.len());
+        header.extend_from_slice(&[0xFE, b'S', b'M', b'B']);
+        put_u16(&mut header, SMB2_HEADER_LEN as u16);
+        put_u16(&mut header, 0); // credit charge
+        put_u32(&mut header, 0); // status
+        put_u16(&mut header, command);
+        put_u16(&mut header, 64); // credits requested
+        put_u32(&mut header, 0); // flags
+        put_u32(&mut header, 0); // next command
+        put_u64(&mut header, self.message_id);
+        put_u32(&mut header, 0); // reserved (process id)
+        put_u32(&mut header, tree_id);
+        put_u64(&mut header, self.session_id);
+        header.extend_from_slice(&[0u8; 16]); // signature
+        header.extend_from_slice(body);
+        self.message_id += 1;
+
+        let len = header.len() as u32;
+        let mut frame = vec![0, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        frame.extend_from_slice(&header);
+        self.stream.write_all(&frame).ok()?;
+
+        loop {
+            let mut prefix = [0u8; 4];
+            self.stream.read_exact(&mut prefix).ok()?;
+            let len = u32::from_be_bytes([0, prefix[1], prefix[2], prefix[3]]) as usize;
+            if len < SMB2_HEADER_LEN {
+                return None;
+            }
+            let mut message = vec![0u8; len];
+            self.stream.read_exact(&mut message).ok()?;
+            if message[..4] != [0xFE, b'S', b'M', b'B'] {
+                return None;
+            }
+            let status = get_u32(&message, 8)?;
+            let flags = get_u32(&message, 16)?;
+            // Interim STATUS_PENDING response (async flag set); the real one follows
+            if status == 0x0000_0103 && flags & 0x2 != 0 {
+                continue;
+            }
+            return Some(SmbResponse {
+                status,
+                tree_id: get_u32(&message, 36)?,
+                session_id: get_u64(&message, 40)?,
+                message,
+            });
+        }
+    }
+
+    fn negotiate(&mut self) -> Option<()> {
+        let mut body = Vec::new();
+        put_u16(&mut body, 36);
+        put_u16(&mut body, DIALECTS.len() as u16);
+        put_u16(&mut body, 1); // signing enabled
+        put_u16(&mut body, 0);
+        put_u32(&mut body, 0); // capabilities
+        body.extend_from_slice(&uuid::Uuid::new_v4().into_bytes()); // client guid
+        put_u64(&mut body, 0); // client start time
+        for dialect in DIALECTS {
+            put_u16(&mut body, *dialect);
+        }
+        let response = self.request(SMB2_NEGOTIATE, 0, &body)?;
+        (response.status == STATUS_SUCCESS).then_some(())
+    }
+
+    fn session_setup(&mut self, token: &[u8]) -> Option<SmbResponse> {
+        let mut body = Vec::new();
+        put_u16(&mut body, 25);
+        body.push(0); // flags
+        body.push(1); // signing enabled
+        put_u32(&mut body, 0); // capabilities
+        put_u32(&mut body, 0); // channel
+        put_u16(&mut body, (SMB2_HEADER_LEN + 24) as u16);
+        put_u16(&mut body, token.len() as u16);
+        put_u64(&mut body, 0); // previous session id
+        body.extend_from_slice(token);
+        self.request(SMB2_SESSION_SETUP, 0, &body)
+    }
+
+    /// Security buffer of a SESSION_SETUP response
+    fn security_buffer(response: &SmbResponse) -> Option<&[u8]> {
+        let offset = get_u16(response.body(), 4)? as usize;
+        let len = get_u16(response.body(), 6)? as usize;
+        response.message.get(offset..offset + len)
+    }
+
+    fn tree_connect(&mut self, path: &str) -> Option<u32> {
+        let path = utf16le(path);
+        let mut body = Vec::new();
+        put_u16(&mut body, 9);
+        put_u16(&mut body, 0);
+        put_u16(&mut body, (SMB2_HEADER_LEN + 8) as u16);
+        put_u16(&mut body, path.len() as u16);
+        body.extend_from_slice(&path);
+        let response = self.request(SMB2_TREE_CONNECT, 0, &body)?;
+        (response.status == STATUS_SUCCESS).then_some(response.tree_id)
+    }
+
+    fn tree_disconnect(&mut self, tree_id: u32) {
+        let _ = self.request(SMB2_TREE_DISCONNECT, tree_id, &[4, 0, 0, 0]);
+    }
+
+    /// Open `name` (empty for the share root) and return its file id
+    fn create(
+        &mut self,
+        tree_id: u32,
+        name: &str,
+        desired_access: u32,
+        create_options: u32,
+    ) -> Option<[u8; 16]> {
+        let mut name = utf16le(name);
+        let name_len = name.len();
+        if name.is_empty() {
+            name.extend_from_slice(&[0, 0]); // the buffer may not be empty
+        }
+        let mut body = Vec::new();
+        put_u16(&mut body, 57);
+        body.push(0); // security flags
+        body.push(0); // oplock level
+        put_u32(&mut body, 2); // impersonation
+        put_u64(&mut body, 0); // create flags
+        put_u64(&mut body, 0); // reserved
+        put_u32(&mut body, desired_access);
+        put_u32(&mut body, 0); // file attributes
+        put_u32(&mut body, 0x7); // share read | write | delete
+        put_u32(&mut body, 1); // FILE_OPEN
+        put_u32(&mut body, create_options);
+        put_u16(&mut body, (SMB2_HEADER_LEN + 56) as u16);
+        put_u16(&mut body, name_len as u16);
+        put_u32(&mut body, 0); // create contexts offset
+        put_u32(&mut body, 0); // create contexts length
+        body.extend_from_slice(&name);
+        let response = self.request(SMB2_CREATE, tree_id, &body)?;
+        if response.status != STATUS_SUCCESS {
+            return None;
+        }
+        response.body().get(64..80)?.try_into().ok()
+    }
+
+    fn close(&mut self, tree_id: u32, file_id: &[u8; 16]) {
+        let mut body = Vec::new();
+        put_u16(&mut body, 24);
+        put_u16(&mut body, 0);
+        put_u32(&mut body, 0);
+        body.extend_from_slice(file_id);
+        let _ = self.request(SMB2_CLOSE, tree_id, &body);
+    }
+
+    /// Write `input` to a named pipe and read its reply in one round trip
+    fn transceive(&mut self, tree_id: u32, file_id: &[u8; 16], input: &[u8]) -> Option<Vec<u8>> {
+        let mut body = Vec::new();
+        put_u16(&mut body, 57);
+        put_u16(&mut body, 0);
+        put_u32(&mut body, FSCTL_PIPE_TRANSCEIVE);
+        body.extend_from_slice(file_id);
+        put_u32(&mut body, (SMB2_HEADER_LEN + 56) as u32); // input offset
+        put_u32(&mut body, input.len() as u32);
+        put_u32(&mut body, 0); // max input response
+        put_u32(&mut body, 0); // output offset
+        put_u32(&mut body, 0); // output count
+        put_u32(&mut body, MAX_PIPE_RESPONSE);
+        put_u32(&mut body, 1); // SMB2_0_IOCTL_IS_FSCTL
+        put_u32(&mut body, 0);
+        body.extend_from_slice(input);
+        let response = self.request(SMB2_IOCTL, tree_id, &body)?;
+        if response.status != STATUS_SUCCESS {
+            return None;
+        }
+        let offset = get_u32(response.body(), 32)? as usize;
+        let len = get_u32(response.body(), 36)? as usize;
+        response
+            .message
+            .get(offset..offset + len)
+            .map(<[u8]>::to_vec)
+    }
+
+    /// Read the next message from a named pipe
+    fn read_pipe(&mut self, tree_id: u32, file_id: &[u8; 16]) -> Option<Vec<u8>> {
+        let mut body = Vec::new();
+        put_u16(&mut body, 49);
+        body.push(0x50); // padding: data right after the response header
+        body.push(0);
+        put_u32(&mut body, MAX_PIPE_RESPONSE);
+        put_u64(&mut body, 0); // offset
+        body.extend_from_slice(file_id);
+        put_u32(&mut body, 0); // minimum count
+        put_u32(&mut body, 0); // channel
+        put_u32(&mut body, 0); // remaining bytes
+        put_u16(&mut body, 0);
+        put_u16(&mut body, 0);
+        body.push(0);
+        let response = self.request(SMB2_READ, tree_id, &body)?;
+        if response.status != STATUS_SUCCESS {
+            return None;
+        }
+        let offset = *response.body().get(2)? as usize;
+        let len = get_u32(response.body(), 4)? as usize;
+        response
+            .message
+            .get(offset..offset + len)
+            .map(<[u8]>::to_vec)
+    }
+}
+
+/// SMB scanner for workgroup/domain names and anonymously visible shares
+pub struct SmbScanner {
+    timeout_ms: u64,
+}
+
+impl SmbScanner {
+    pub fn new() -> Self {
+        Self { timeout_ms: 2000 }
+    }
+
+    pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Query a single IP. Returns None when nothing answers SMB2 on port 445.
+    pub fn query_ip(&self, ip: Ipv4Addr) -> Option<SmbInfo> {
+        let mut session = SmbSession::connect(ip, Duration::from_millis(self.timeout_ms))?;
+        session.negotiate()?;
+
+        let challenge = session.session_setup(&spnego_init(&ntlm_negotiate()))?;
+        if challenge.status != STATUS_MORE_PROCESSING_REQUIRED {
+            return None;
+        }
+        let names = parse_ntlm_challenge(SmbSession::security_buffer(&challenge)?)?;
+        session.session_id = challenge.session_id;
+
+        let mut info = SmbInfo {
+            computer_name: names.computer_name.clone(),
+            // Standalone Windows hosts report their own name as the domain
+            domain: names.domain.clone().filter(|d| {
+                names
+                    .computer_name
+                    .as_ref()
+                    .is_none_or(|c| !c.eq_ignore_ascii_case(d))
+            }),
+            dns_domain: names.dns_domain.clone().filter(|d| {
+                names
+                    .dns_computer_name
+                    .as_ref()
+                    .is_none_or(|c| !c.eq_ignore_ascii_case(d))
+            }),
+            anonymous_session: false,
+            shares: Vec::new(),
+        };
+
+        let auth =
+            session.session_setup(&spnego_response(&ntlm_anonymous_authenticate(names.flags)));
+        if auth.is_none_or(|r| r.status != STATUS_SUCCESS) {
+            return Some(info); // anonymous logon refused
+        }
+        info.anonymous_session = true;
+
+        let host = ip.to_string();
+        if let Some(shares) = Self::enumerate_shares(&mut session, &host) {
+            info.shares = shares;
+        }
+        Self::check_guest_access(&mut session, &host, &mut info.shares);
+        Some(info)
+    }
+
+    /// List shares through srvsvc on IPC$
+    fn enumerate_shares(session: &mut SmbSession, host: &str) -> Option<Vec<SmbShare>> {
+        // Read/write data and attributes on the pipe
+        const PIPE_ACCESS: u32 = 0x0012_019F;
+
+        let tree_id = session.tree_connect(&format!("\\\\{}\\IPC$", host))?;
+        let shares = session
+            .create(tree_id, "srvsvc", PIPE_ACCESS, 0)
+            .and_then(|pipe| {
+                let shares = Self::net_share_enum(session, tree_id, &pipe);
+                session.close(tree_id, &pipe);
+                shares
+            });
+        session.tree_disconnect(tree_id);
+
+        Some(
+            shares?
+                .into_iter()
+                .map(|(name, share_type, remark)| SmbShare {
+                    name,
+                    share_type: share_type_name(share_type).to_string(),
+                    remark,
+                    guest_readable: false,
+                })
+                .collect(),
+        )
+    }
+
+    /// Bind to srvsvc on an open pipe and call NetrShareEnum
+    fn net_share_enum(
+        session: &mut SmbSession,
+        tree_id: u32,
+        pipe: &[u8; 16],
+    ) -> Option<Vec<(String, u32, Option<String>)>> {
+        let bind_ack = session.transceive(tree_id, pipe, &dcerpc_bind())?;
+        if *bind_ack.get(2)? != DCERPC_BIND_ACK {
+            return None;
+        }
+        let mut response = session.transceive(tree_id, pipe, &dcerpc_share_enum_request())?;
+        let mut stub = Vec::new();
+        while !dcerpc_response_stub(&response, &mut stub)? {
+            response = session.read_pipe(tree_id, pipe)?;
+        }
+        parse_share_enum(&stub)
+    }
+
+    /// Mark disk shares whose root can be listed by the anonymous session
+    fn check_guest_access(session: &mut SmbSession, host: &str, shares: &mut [SmbShare]) {
+        // FILE_LIST_DIRECTORY | FILE_READ_ATTRIBUTES | SYNCHRONIZE, as a directory
+        const LIST_ACCESS: u32 = 0x0010_0081;
+        const FILE_DIRECTORY_FILE: u32 = 0x0000_0001;
+
+        for share in shares
+            .iter_mut()
+            .filter(|s| s.share_type == "disk")
+            .take(MAX_SHARES_CHECKED)
+        {
+            let Some(tree_id) = session.tree_connect(&format!("\\\\{}\\{}", host, share.name))
+            else {
+                continue;
+            };
+            if let Some(root) = session.create(tree_id, "", LIST_ACCESS, FILE_DIRECTORY_FILE) {
+                share.guest_readable = true;
+                session.close(tree_id, &root);
+            }
+            session.tree_disconnect(tree_id);
+        }
+    }
+}
+
+impl Default for SmbScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntlm_challenge(target_info: &[(u16, &str)]) -> Vec<u8> {
+        let mut info = Vec::new();
+        for (id, value) in target_info {
+            let value = utf16le(value);
+            put_u16(&mut info, *id);
+            put_u16(&mut info, value.len() as u16);
+            info.extend_from_slice(&value);
+        }
+        info.extend_from_slice(&[0, 0, 0, 0]);
+
+        let mut msg = NTLMSSP_SIGNATURE.to_vec();
+        put_u32(&mut msg, 2);
+        msg.extend_from_slice(&[0u8; 8]); // target name
+        put_u32(&mut msg, 0x0082_8215);
+        msg.extend_from_slice(&[0x11; 8]); // server challenge
+        msg.extend_from_slice(&[0u8; 8]);
+        put_u16(&mut msg, info.len() as u16);
+        put_u16(&mut msg, info.len() as u16);
+        put_u32(&mut msg, 48);
+        msg.extend_from_slice(&info);
+        msg
+    }
+
+    #[test]
+    fn test_parse_ntlm_challenge_inside_spnego() {
+        let challenge = ntlm_challenge(&[
+            (2, "CORP"),
+            (1, "FILESRV"),
+            (4, "corp.example.com"),
+            (3, "filesrv.corp.example.com"),
+        ]);
+        let names = parse_ntlm_challenge(&spnego_response(&challenge)).unwrap();
+        assert_eq!(names.flags, 0x0082_8215);
+        assert_eq!(names.computer_name.as_deref(), Some("FILESRV"));
+        assert_eq!(names.domain.as_deref(), Some("CORP"));
+        assert_eq!(names.dns_domain.as_deref(), Some("corp.example.com"));
+
+        assert!(parse_ntlm_challenge(b"not a challenge").is_none());
+    }
+
+    #[test]
+    fn test_anonymous_authenticate_has_no_credentials() {
+        let msg = ntlm_anonymous_authenticate(0xE288_8215);
+        assert_eq!(&msg[..8], NTLMSSP_SIGNATURE);
+        assert_eq!(get_u32(&msg, 8), Some(3));
+        assert_eq!(get_u16(&msg, 12), Some(1)); // LM response Z(1)
+        for field in [20, 28, 36, 44, 52] {
+            assert_eq!(get_u16(&msg, field), Some(0));
+        }
+        assert_ne!(get_u32(&msg, 60).unwrap() & NTLMSSP_NEGOTIATE_ANONYMOUS, 0);
+        assert_eq!(msg.len(), 65);
+    }
+
+    #[test]
+    fn test_spnego_long_lengths() {
+        let wrapped = der(0x04, &[0u8; 300]);
+        assert_eq!(&wrapped[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        assert_eq!(spnego_init(&ntlm_negotiate())[0], 0x60);
+    }
+
+    fn ndr_string(stub: &mut Vec<u8>, text: &str) {
+        let units = text.encode_utf16().count() as u32 + 1;
+        put_u32(stub, units);
+        put_u32(stub, 0);
+        put_u32(stub, units);
+        stub.extend_from_slice(&utf16le(text));
+        stub.extend_from_slice(&[0, 0]);
+        while !stub.len().is_multiple_of(4) {
+            stub.push(0);
+        }
+    }
+
+    #[test]
+    fn test_parse_share_enum_response() {
+        let mut stub = Vec::new();
+        for value in [1, 1, 0x0002_0000, 3, 0x0002_0004, 3] {
+            put_u32(&mut stub, value);
+        }
+        for (name_ptr, share_type, remark_ptr) in [
+            (0x0002_0008, 0u32, 0x0002_000c),
+            (0x0002_0010, 0x8000_0003, 0x0002_0014),
+            (0x0002_0018, 1, 0),
+        ] {
+            put_u32(&mut stub, name_ptr);
+            put_u32(&mut stub, share_type);
+            put_u32(&mut stub, remark_ptr);
+        }
+        ndr_string(&mut stub, "public");
+        ndr_string(&mut stub, "Shared files");
+        ndr_string(&mut stub, "IPC$");
+        ndr_string(&mut stub, "");
+        ndr_string(&mut stub, "laser");
+        put_u32(&mut stub, 3); // total entries
+        put_u32(&mut stub, 0); // resume handle
+        put_u32(&mut stub, 0); // WERROR
+
+        let shares = parse_share_enum(&stub).unwrap();
+        assert_eq!(
+            shares,
+            vec![
+                ("public".to_string(), 0, Some("Shared files".to_string())),
+                ("IPC$".to_string(), 0x8000_0003, None),
+                ("laser".to_string(), 1, None),
+            ]
+        );
+        assert_eq!(share_type_name(0x8000_0003), "ipc");
+        assert!(parse_share_enum(&stub[..40]).is_none());
+    }
+
+    #[test]
+    fn test_dcerpc_fragments_are_joined() {
+        let fragment = |flags: u8, stub: &[u8]| {
+            let mut pdu = dcerpc_header(DCERPC_RESPONSE, (24 + stub.len()) as u16, 2);
+            pdu[3] = flags;
+            put_u32(&mut pdu, stub.len() as u32);
+            put_u32(&mut pdu, 0);
+            pdu.extend_from_slice(stub);
+            pdu
+        };
+        let mut stub = Vec::new();
+        assert_eq!(
+            dcerpc_response_stub(&fragment(0x01, &[1, 2, 3, 4]), &mut stub),
+            Some(false)
+        );
+        assert_eq!(
+            dcerpc_response_stub(&fragment(0x02, &[5, 6, 7, 8]), &mut stub),
+            Some(true)
+        );
+        assert_eq!(stub, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let mut fault = fragment(0x03, &[0; 8]);
+        fault[2] = 3;
+        assert_eq!(dcerpc_response_stub(&fault, &mut Vec::new()), None);
+    }
+
+    #[test]
+    fn test_bind_pdu_length_matches_header() {
+        let bind = dcerpc_bind();
+        assert_eq!(bind.len(), 72);
+        assert_eq!(get_u16(&bind, 8), Some(72));
+        let request = dcerpc_share_enum_request();
+        assert_eq!(get_u16(&request, 8), Some(request.len() as u16));
+        assert_eq!(get_u16(&request, 22), Some(OPNUM_NETR_SHARE_ENUM));
+    }
+}
@@ -1,11 +1,16 @@
 //! SNMP scanner. Queries devices on UDP port 161 using SNMPv2c GET requests
 //! to retrieve system description, name, location, and object ID.
 
+use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 
-use super::SnmpResult;
+use futures::future::BoxFuture;
+
+use super::manager::ScanRun;
+use super::registry::{Scanner, Tunables};
+use super::{ScanResult, ScanType, SnmpResult};
 
 /// Request ID counter for SNMP requests
 static REQUEST_ID: AtomicU32 = AtomicU32::new(1);
@@ -19,20 +24,58 @@ const OID_SYS_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 5, 0]; // sysName
 const OID_SYS_LOCATION: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 6, 0]; // sysLocation
 
 /// Common SNMP community strings to try
-const COMMUNITY_STRINGS: &[&str] = &["public", "private"];
+pub const COMMUNITY_STRINGS: &[&str] = &["public", "private"];
+
+/// Communities to try when probing, resolved per device
+#[derive(Debug, Clone, Default)]
+pub struct SnmpCredentials {
+    /// Communities tried for every device, in order
+    pub communities: Vec<String>,
+    /// Per-device credential, tried before the list
+    pub device: HashMap<Ipv4Addr, String>,
+    /// Community from the list that last worked for an address, tried first of the list
+    pub last_success: HashMap<Ipv4Addr, String>,
+}
+
+impl SnmpCredentials {
+    /// Communities to try for `ip`, in order, flagged when they are the device's own
+    /// credential (which is never reported back in results)
+    fn attempts_for(&self, ip: Ipv4Addr) -> Vec<(String, bool)> {
+        let mut attempts: Vec<(String, bool)> = Vec::new();
+        if let Some(community) = self.device.get(&ip) {
+            attempts.push((community.clone(), true));
+        }
+        let preferred = self
+            .last_success
+            .get(&ip)
+            .filter(|community| self.communities.contains(community));
+        for community in preferred.into_iter().chain(&self.communities) {
+            if !attempts.iter().any(|(tried, _)| tried == community) {
+                attempts.push((community.clone(), false));
+            }
+        }
+        attempts
+    }
+}
 
 /// SNMP scanner for device identification
 /// Queries SNMP-enabled devices for system information (sysDescr, sysName, etc.)
 pub struct SnmpScanner {
     timeout_ms: u64,
-    communities: Vec<String>,
+    credentials: SnmpCredentials,
+    /// Extra rounds through the credentials for hosts that did not answer
+    retries: u8,
 }
 
 impl SnmpScanner {
     pub fn new() -> Self {
         Self {
             timeout_ms: 2000,
-            communities: COMMUNITY_STRINGS.iter().map(|s| s.to_string()).collect(),
+            credentials: SnmpCredentials {
+                communities: COMMUNITY_STRINGS.iter().map(|s| s.to_string()).collect(),
+                ..SnmpCredentials::default()
+            },
+            retries: 0,
         }
     }
 
@@ -41,6 +84,17 @@ impl SnmpScanner {
         self
     }
 
+    pub fn with_retries(mut self, retries: u8) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Use a configured community list and per-device credentials instead of the defaults
+    pub fn with_credentials(mut self, credentials: SnmpCredentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
     /// Encode an OID in BER format
     fn encode_oid(oid: &[u32]) -> Vec<u8> {
         let mut encoded = Vec::new();
@@ -355,6 +409,14 @@ impl SnmpScanner {
                         val.to_string()
                     }
                 }
+                0x41 | 0x42 | 0x43 | 0x46 => {
+                    // Counter32, Gauge32, TimeTicks, Counter64 - unsigned
+                    let mut val = 0u64;
+                    for b in &data[pos..pos + val_len] {
+                        val = (val << 8) | *b as u64;
+                    }
+                    val.to_string()
+                }
                 0x40 => {
                     // IpAddress
                     if val_len == 4 {
@@ -396,9 +458,18 @@ impl SnmpScanner {
 
     /// Query a single IP for SNMP information
     pub fn query_ip(&self, ip: Ipv4Addr) -> Option<SnmpResult> {
+        Self::query_with(ip, self.timeout_ms, self.credentials.attempts_for(ip))
+    }
+
+    /// Query `ip` trying each (community, per-device) attempt in order
+    fn query_with(
+        ip: Ipv4Addr,
+        timeout_ms: u64,
+        attempts: Vec<(String, bool)>,
+    ) -> Option<SnmpResult> {
         let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
         socket
-            .set_read_timeout(Some(Duration::from_millis(self.timeout_ms)))
+            .set_read_timeout(Some(Duration::from_millis(timeout_ms)))
             .ok()?;
 
         let target = SocketAddr::new(IpAddr::V4(ip), SNMP_PORT);
@@ -410,9 +481,9 @@ impl SnmpScanner {
         ];
 
         // Try each community string
-        for community in &self.communities {
+        for (community, per_device) in attempts {
             let request_id = REQUEST_ID.fetch_add(1, Ordering::Relaxed);
-            let request = Self::build_get_request(community, request_id, &oids);
+            let request = Self::build_get_request(&community, request_id, &oids);
 
             if socket.send_to(&request, target).is_err() {
                 continue;
@@ -444,7 +515,9 @@ impl SnmpScanner {
                 if sys_descr.is_some() || sys_name.is_some() {
                     return Some(SnmpResult {
                         ip: IpAddr::V4(ip),
-                        community: community.clone(),
+                        // A device's own credential is never echoed into results
+                        community: if per_device { String::new() } else { community },
+                        per_device_credential: per_device,
                         sys_descr,
                         sys_object_id,
                         sys_name,
@@ -457,10 +530,61 @@ impl SnmpScanner {
         None
     }
 
+    /// Send several GET requests to `ip` and collect every value returned. Communities are
+    /// tried on the first request; the rest go out with whichever one answered it. OIDs the
+    /// device doesn't have are left out.
+    pub fn get(&self, ip: Ipv4Addr, requests: &[Vec<Vec<u32>>]) -> Vec<(Vec<u32>, String)> {
+        let Some((first, rest)) = requests.split_first() else {
+            return Vec::new();
+        };
+        let Ok(socket) = UdpSocket::bind("0.0.0.0:0") else {
+            return Vec::new();
+        };
+        if socket
+            .set_read_timeout(Some(Duration::from_millis(self.timeout_ms)))
+            .is_err()
+        {
+            return Vec::new();
+        }
+
+        let target = SocketAddr::new(IpAddr::V4(ip), SNMP_PORT);
+        for (community, _) in self.credentials.attempts_for(ip) {
+            if let Some(mut values) = Self::exchange(&socket, target, &community, first) {
+                for oids in rest {
+                    values.extend(
+                        Self::exchange(&socket, target, &community, oids).unwrap_or_default(),
+                    );
+                }
+                return values;
+            }
+        }
+        Vec::new()
+    }
+
+    /// One GET request and its response's values
+    fn exchange(
+        socket: &UdpSocket,
+        target: SocketAddr,
+        community: &str,
+        oids: &[Vec<u32>],
+    ) -> Option<Vec<(Vec<u32>, String)>> {
+        let oids: Vec<&[u32]> = oids.iter().map(Vec::as_slice).collect();
+        let request_id = REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+        let request = Self::build_get_request(community, request_id, &oids);
+        socket.send_to(&request, target).ok()?;
+
+        let mut buf = [0u8; 4096];
+        let (len, src) = socket.recv_from(&mut buf).ok()?;
+        if src.ip() != target.ip() {
+            return None;
+        }
+        Self::parse_response(&buf[..len])
+    }
+
     /// Scan a list of IPs for SNMP information
     pub async fn scan_ips(&self, ips: &[IpAddr]) -> Vec<SnmpResult> {
         let timeout_ms = self.timeout_ms;
-        let communities = self.communities.clone();
+        let retries = self.retries;
         let ips: Vec<Ipv4Addr> = ips
             .iter()
             .filter_map(|ip| match ip {
@@ -472,14 +596,10 @@ impl SnmpScanner {
         let mut handles = Vec::new();
 
         for ip in ips {
-            let timeout = timeout_ms;
-            let comms = communities.clone();
+            let attempts = self.credentials.attempts_for(ip);
             handles.push(tokio::task::spawn_blocking(move || {
-                let scanner = SnmpScanner {
-                    timeout_ms: timeout,
-                    communities: comms,
-                };
-                scanner.query_ip(ip)
+                (0..=retries)
+                    .find_map(|_| SnmpScanner::query_with(ip, timeout_ms, attempts.clone()))
             }));
         }
 
@@ -500,6 +620,55 @@ impl Default for SnmpScanner {
     }
 }
 
+/// SNMP system queries as a scan type
+pub struct SnmpScan;
+
+impl Scanner for SnmpScan {
+    fn scan_type(&self) -> ScanType {
+        ScanType::SNMP
+    }
+
+    fn label(&self) -> &'static str {
+        "SNMP"
+    }
+
+    fn description(&self) -> &'static str {
+        "Device info"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn tunables(&self) -> Tunables {
+        Tunables {
+            retries: true,
+            parallelism: false,
+        }
+    }
+
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let mut scanner = SnmpScanner::new()
+                .with_timeout(run.timeout_ms())
+                .with_retries(run.tuning().retries.unwrap_or(0));
+            if let Some(credentials) = run.snmp_credentials().await {
+                scanner = scanner.with_credentials(credentials);
+            }
+            let scanner = &scanner;
+            run.scan_targets(1, move |ips| async move {
+                scanner
+                    .scan_ips(&ips)
+                    .await
+                    .into_iter()
+                    .map(ScanResult::Snmp)
+                    .collect::<Vec<_>>()
+            })
+            .await;
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -546,7 +715,40 @@ mod tests {
     fn test_scanner_default() {
         let scanner = SnmpScanner::default();
         assert_eq!(scanner.timeout_ms, 2000);
-        assert_eq!(scanner.communities.len(), 2);
+        assert_eq!(scanner.credentials.communities.len(), 2);
+    }
+
+    #[test]
+    fn test_attempt_order_prefers_device_then_last_success() {
+        let ip = Ipv4Addr::new(192, 168, 1, 20);
+        let other = Ipv4Addr::new(192, 168, 1, 21);
+        let credentials = SnmpCredentials {
+            communities: vec!["public".into(), "private".into(), "monitor".into()],
+            device: HashMap::from([(ip, "s3cret".to_string())]),
+            last_success: HashMap::from([
+                (ip, "monitor".to_string()),
+                (other, "removed".to_string()),
+            ]),
+        };
+
+        assert_eq!(
+            credentials.attempts_for(ip),
+            vec![
+                ("s3cret".to_string(), true),
+                ("monitor".to_string(), false),
+                ("public".to_string(), false),
+                ("private".to_string(), false),
+            ]
+        );
+        // A remembered community that is no longer configured is not tried
+        assert_eq!(
+            credentials
+                .attempts_for(other)
+                .into_iter()
+                .map(|(community, _)| community)
+                .collect::<Vec<_>>(),
+            vec!["public", "private", "monitor"]
+        );
     }
 
     #[test]
@@ -1,43 +1,48 @@
 //! SSDP/UPnP scanner. Discovers network devices via multicast M-SEARCH requests
-//! and fetches device description XML from UPnP location URLs.
+//! and fetches device description XML from UPnP location URLs. Gateways found this
+//! way are also asked for their port mappings (see igd.rs).
 
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::time::Duration;
 
 use futures::StreamExt;
+use futures::future::BoxFuture;
 
-use super::SsdpResult;
+use super::igd::{find_wan_connection_service, query_port_mappings};
+use super::manager::ScanRun;
+use super::registry::Scanner;
+use super::{ScanResult, ScanType, SsdpResult};
 
 /// SSDP/UPnP device discovery scanner
 pub struct SsdpScanner {
-    timeout_secs: u64,
+    timeout_ms: u64,
 }
 
 impl SsdpScanner {
     pub fn new() -> Self {
-        Self { timeout_secs: 3 }
+        Self { timeout_ms: 3000 }
     }
 
-    /// Fetch and parse UPnP device description XML to get friendly name and model
-    async fn fetch_device_info(location: &str) -> Option<(Option<String>, Option<String>)> {
+    /// How long to collect answers to the M-SEARCH
+    pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Fetch UPnP device description XML
+    pub(super) async fn fetch_device_description(location: &str) -> Option<String> {
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(3))
             .build()
             .ok()?;
 
         let response = client.get(location).send().await.ok()?;
-        let xml = response.text().await.ok()?;
-
-        // Parse XML to extract friendlyName and modelName
-        let friendly_name = Self::extract_xml_element(&xml, "friendlyName");
-        let model_name = Self::extract_xml_element(&xml, "modelName");
-
-        Some((friendly_name, model_name))
+        response.text().await.ok()
     }
 
     /// Extract a simple XML element value (basic parsing without full XML parser)
-    fn extract_xml_element(xml: &str, element: &str) -> Option<String> {
+    pub(super) fn extract_xml_element(xml: &str, element: &str) -> Option<String> {
         let start_tag = format!("<{}>", element);
         let end_tag = format!("</{}>", element);
 
@@ -48,6 +53,18 @@ impl SsdpScanner {
         if value.is_empty() { None } else { Some(value) }
     }
 
+    /// Device uuid from a USN ("uuid:<udn>::upnp:rootdevice") or a description's UDN
+    pub(super) fn parse_udn(usn: &str) -> Option<String> {
+        let udn = usn.split("::").next()?.trim();
+        let udn = udn
+            .get(..5)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("uuid:"))
+            .map_or(udn, |_| &udn[5..])
+            .trim()
+            .to_lowercase();
+        if udn.is_empty() { None } else { Some(udn) }
+    }
+
     /// Discover SSDP/UPnP devices on the network
     pub async fn discover(&self) -> Vec<SsdpResult> {
         let mut results = Vec::new();
@@ -56,7 +73,7 @@ impl SsdpScanner {
 
         match ssdp_client::search(
             &search_target,
-            Duration::from_secs(self.timeout_secs),
+            Duration::from_millis(self.timeout_ms),
             2,
             None,
         )
@@ -84,6 +101,8 @@ impl SsdpScanner {
                             device_type: Some(response.search_target().to_string()),
                             friendly_name: None,
                             model_name: None,
+                            udn: Self::parse_udn(response.usn()),
+                            port_mappings: None,
                         });
                     }
                 }
@@ -100,10 +119,21 @@ impl SsdpScanner {
         // Fetch device descriptions to get friendly names and models
         for result in &mut results {
             if let Some(location) = location_map.get(&result.ip)
-                && let Some((friendly_name, model_name)) = Self::fetch_device_info(location).await
+                && let Some(xml) = Self::fetch_device_description(location).await
             {
-                result.friendly_name = friendly_name;
-                result.model_name = model_name;
+                result.friendly_name = Self::extract_xml_element(&xml, "friendlyName");
+                result.model_name = Self::extract_xml_element(&xml, "modelName");
+                if let Some(udn) =
+                    Self::extract_xml_element(&xml, "UDN").and_then(|udn| Self::parse_udn(&udn))
+                {
+                    result.udn = Some(udn);
+                }
+
+                // Gateways also report the ports opened to the internet through them
+                if let Some(service) = find_wan_connection_service(&xml, location) {
+                    result.port_mappings =
+                        query_port_mappings(&service, Duration::from_secs(3)).await;
+                }
             }
         }
 
@@ -117,6 +147,46 @@ impl Default for SsdpScanner {
     }
 }
 
+/// SSDP/UPnP discovery as a scan type
+pub struct SsdpScan;
+
+impl Scanner for SsdpScan {
+    fn scan_type(&self) -> ScanType {
+        ScanType::SSDP
+    }
+
+    fn label(&self) -> &'static str {
+        "SSDP"
+    }
+
+    fn description(&self) -> &'static str {
+        "UPnP discovery"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(&'a self, run: &'a ScanRun) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            // Answers trickle in for seconds, so only an explicit override replaces the
+            // scanner's own wait
+            let mut scanner = SsdpScanner::new();
+            if let Some(timeout_ms) = run.tuning().timeout_ms {
+                scanner = scanner.with_timeout(timeout_ms);
+            }
+            run.discover(async {
+                let results = scanner.discover().await;
+                results
+                    .into_iter()
+                    .map(ScanResult::Ssdp)
+                    .collect::<Vec<_>>()
+            })
+            .await;
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,7 +194,22 @@ mod tests {
     #[test]
     fn test_scanner_default() {
         let scanner = SsdpScanner::default();
-        assert_eq!(scanner.timeout_secs, 3);
+        assert_eq!(scanner.timeout_ms, 3000);
+    }
+
+    #[test]
+    fn test_parse_udn() {
+        assert_eq!(
+            SsdpScanner::parse_udn("uuid:2F402F80-DA50-11E1-9B23-00178812D6F4::upnp:rootdevice")
+                .as_deref(),
+            Some("2f402f80-da50-11e1-9b23-00178812d6f4")
+        );
+        assert_eq!(
+            SsdpScanner::parse_udn(" UUID:roku-1234 ").as_deref(),
+            Some("roku-1234")
+        );
+        assert_eq!(SsdpScanner::parse_udn("uuid:"), None);
+        assert_eq!(SsdpScanner::parse_udn(""), None);
     }
 
     #[test]
@@ -139,6 +224,8 @@ mod tests {
                 device_type: Some("upnp:rootdevice".to_string()),
                 friendly_name: None,
                 model_name: None,
+                udn: None,
+                port_mappings: None,
             },
             SsdpResult {
                 ip: "192.168.1.100".parse().unwrap(),
@@ -147,6 +234,8 @@ mod tests {
                 device_type: Some("urn:schemas-upnp-org:device:MediaRenderer:1".to_string()),
                 friendly_name: None,
                 model_name: None,
+                udn: None,
+                port_mappings: None,
             },
             SsdpResult {
                 ip: "192.168.1.101".parse().unwrap(),
@@ -155,6 +244,8 @@ mod tests {
                 device_type: None,
                 friendly_name: None,
                 model_name: None,
+                udn: None,
+                port_mappings: None,
             },
         ];
 
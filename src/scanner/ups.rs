@@ -0,0 +1,325 @@
+//! UPS status over the network: battery charge, runtime, load and whether the UPS is on
+//! battery. Network management cards are read over SNMP through the APC PowerNet or
+//! CyberPower MIB, or the standard UPS-MIB (RFC 1628) most other cards implement. UPSes
+//! attached to a host by USB are read from its Network UPS Tools server (upsd, port 3493),
+//! which can serve several.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::snmp::SnmpScanner;
+
+/// Network UPS Tools server port
+pub const NUT_PORT: u16 = 3493;
+
+/// Longest NUT reply read; a UPS with every variable has a couple of hundred
+const MAX_NUT_LINES: usize = 1000;
+
+/// Where a UPS's status came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpsSource {
+    Apc,
+    CyberPower,
+    UpsMib,
+    Nut,
+}
+
+impl UpsSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpsSource::Apc => "apc",
+            UpsSource::CyberPower => "cyberpower",
+            UpsSource::UpsMib => "upsmib",
+            UpsSource::Nut => "nut",
+        }
+    }
+
+    /// Guess the SNMP layout from a scan's system description or object id
+    pub fn from_snmp_details(details: &str) -> Option<UpsSource> {
+        let lower = details.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+        if has(&["apc ", "american power conversion", "smart-ups", ".318."]) {
+            Some(UpsSource::Apc)
+        } else if has(&["cyberpower", ".3808."]) {
+            Some(UpsSource::CyberPower)
+        } else if has(&["eaton", "powerware", ".534.", ".33.1."]) {
+            Some(UpsSource::UpsMib)
+        } else {
+            None
+        }
+    }
+}
+
+/// One UPS's state at a poll
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UpsStatus {
+    /// The UPS's name on a NUT server; empty for SNMP cards, which serve one UPS
+    pub name: String,
+    pub source: UpsSource,
+    pub on_battery: bool,
+    /// The UPS itself reports its battery as low or depleted
+    pub low_battery: bool,
+    pub charge_percent: Option<i64>,
+    pub runtime_seconds: Option<i64>,
+    pub load_percent: Option<i64>,
+}
+
+/// Where one SNMP layout keeps each value, all scalars
+struct SnmpLayout {
+    source: UpsSource,
+    battery_status: &'static [u32],
+    /// Battery status values meaning low or depleted
+    low_values: &'static [i64],
+    charge: &'static [u32],
+    runtime: &'static [u32],
+    runtime_to_seconds: fn(i64) -> i64,
+    output_status: &'static [u32],
+    on_battery_value: i64,
+    load: &'static [u32],
+}
+
+/// TimeTicks are hundredths of a second
+fn ticks_to_seconds(ticks: i64) -> i64 {
+    ticks / 100
+}
+
+fn minutes_to_seconds(minutes: i64) -> i64 {
+    minutes * 60
+}
+
+/// PowerNet-MIB upsBasicBatteryStatus, upsAdvBatteryCapacity,
+/// upsAdvBatteryRunTimeRemaining, upsBasicOutputStatus, upsAdvOutputLoad
+const APC_LAYOUT: SnmpLayout = SnmpLayout {
+    source: UpsSource::Apc,
+    battery_status: &[1, 3, 6, 1, 4, 1, 318, 1, 1, 1, 2, 1, 1, 0],
+    low_values: &[3],
+    charge: &[1, 3, 6, 1, 4, 1, 318, 1, 1, 1, 2, 2, 1, 0],
+    runtime: &[1, 3, 6, 1, 4, 1, 318, 1, 1, 1, 2, 2, 3, 0],
+    runtime_to_seconds: ticks_to_seconds,
+    output_status: &[1, 3, 6, 1, 4, 1, 318, 1, 1, 1, 4, 1, 1, 0],
+    on_battery_value: 3,
+    load: &[1, 3, 6, 1, 4, 1, 318, 1, 1, 1, 4, 2, 3, 0],
+};
+
+/// CPS-MIB upsBaseBatteryStatus, upsAdvanceBatteryCapacity,
+/// upsAdvanceBatteryRunTimeRemaining, upsBaseOutputStatus, upsAdvanceOutputLoad
+const CYBERPOWER_LAYOUT: SnmpLayout = SnmpLayout {
+    source: UpsSource::CyberPower,
+    battery_status: &[1, 3, 6, 1, 4, 1, 3808, 1, 1, 1, 2, 1, 1, 0],
+    low_values: &[3],
+    charge: &[1, 3, 6, 1, 4, 1, 3808, 1, 1, 1, 2, 2, 1, 0],
+    runtime: &[1, 3, 6, 1, 4, 1, 3808, 1, 1, 1, 2, 2, 4, 0],
+    runtime_to_seconds: ticks_to_seconds,
+    output_status: &[1, 3, 6, 1, 4, 1, 3808, 1, 1, 1, 4, 1, 1, 0],
+    on_battery_value: 3,
+    load: &[1, 3, 6, 1, 4, 1, 3808, 1, 1, 1, 4, 2, 3, 0],
+};
+
+/// UPS-MIB upsBatteryStatus, upsEstimatedChargeRemaining, upsEstimatedMinutesRemaining,
+/// upsOutputSource, and upsOutputPercentLoad of the first output line
+const UPS_MIB_LAYOUT: SnmpLayout = SnmpLayout {
+    source: UpsSource::UpsMib,
+    battery_status: &[1, 3, 6, 1, 2, 1, 33, 1, 2, 1, 0],
+    low_values: &[3, 4],
+    charge: &[1, 3, 6, 1, 2, 1, 33, 1, 2, 4, 0],
+    runtime: &[1, 3, 6, 1, 2, 1, 33, 1, 2, 3, 0],
+    runtime_to_seconds: minutes_to_seconds,
+    output_status: &[1, 3, 6, 1, 2, 1, 33, 1, 4, 1, 0],
+    on_battery_value: 5,
+    load: &[1, 3, 6, 1, 2, 1, 33, 1, 4, 4, 1, 5, 1],
+};
+
+const SNMP_LAYOUTS: [&SnmpLayout; 3] = [&APC_LAYOUT, &CYBERPOWER_LAYOUT, &UPS_MIB_LAYOUT];
+
+/// Read a UPS network card over SNMP, trying `hint`'s MIB first and then the others.
+/// None when no UPS MIB answers.
+pub fn query_snmp_ups(
+    scanner: &SnmpScanner,
+    ip: Ipv4Addr,
+    hint: Option<UpsSource>,
+) -> Option<UpsStatus> {
+    let mut layouts: Vec<&SnmpLayout> = SNMP_LAYOUTS
+        .into_iter()
+        .filter(|l| Some(l.source) == hint)
+        .collect();
+    layouts.extend(SNMP_LAYOUTS.into_iter().filter(|l| Some(l.source) != hint));
+
+    layouts.into_iter().find_map(|layout| {
+        let request = [
+            layout.battery_status,
+            layout.charge,
+            layout.runtime,
+            layout.output_status,
+            layout.load,
+        ]
+        .map(<[u32]>::to_vec)
+        .to_vec();
+        parse_snmp_status(layout, &scanner.get(ip, &[request]))
+    })
+}
+
+/// Build a UPS status from an SNMP layout's values. None without a charge or output status.
+fn parse_snmp_status(layout: &SnmpLayout, values: &[(Vec<u32>, String)]) -> Option<UpsStatus> {
+    let number = |oid: &[u32]| {
+        values
+            .iter()
+            .find(|(o, _)| o == oid)
+            .and_then(|(_, v)| v.parse::<i64>().ok())
+    };
+    let charge = number(layout.charge);
+    let output_status = number(layout.output_status);
+    if charge.is_none() && output_status.is_none() {
+        return None;
+    }
+    Some(UpsStatus {
+        name: String::new(),
+        source: layout.source,
+        on_battery: output_status == Some(layout.on_battery_value),
+        low_battery: number(layout.battery_status).is_some_and(|s| layout.low_values.contains(&s)),
+        charge_percent: charge,
+        runtime_seconds: number(layout.runtime).map(layout.runtime_to_seconds),
+        load_percent: number(layout.load),
+    })
+}
+
+/// Send one NUT command and read its reply up to `end`. None on an error reply.
+fn nut_command(
+    stream: &mut TcpStream,
+    reader: &mut BufReader<TcpStream>,
+    command: &str,
+    end: &str,
+) -> Option<Vec<String>> {
+    stream.write_all(format!("{}\n", command).as_bytes()).ok()?;
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 || line.starts_with("ERR") {
+            return None;
+        }
+        let line = line.trim_end();
+        if line == end {
+            return Some(lines);
+        }
+        if !line.starts_with("BEGIN ") {
+            lines.push(line.to_string());
+        }
+        if lines.len() > MAX_NUT_LINES {
+            return None;
+        }
+    }
+}
+
+/// A quoted NUT value, unescaped
+fn nut_value(quoted: &str) -> String {
+    let inner = quoted.trim().trim_start_matches('"').trim_end_matches('"');
+    inner.replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+/// Build a UPS status from a `LIST VAR` reply
+fn parse_nut_vars(name: &str, lines: &[String]) -> UpsStatus {
+    let prefix = format!("VAR {} ", name);
+    let var = |key: &str| {
+        lines.iter().find_map(|line| {
+            let rest = line.strip_prefix(&prefix)?;
+            let (k, value) = rest.split_once(' ')?;
+            (k == key).then(|| nut_value(value))
+        })
+    };
+    let number = |key: &str| {
+        var(key)
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(|v| v as i64)
+    };
+    // e.g. "OL CHRG", "OB DISCHRG", "OB LB"
+    let flags = var("ups.status").unwrap_or_default();
+    let has_flag = |flag: &str| flags.split_whitespace().any(|f| f == flag);
+
+    UpsStatus {
+        name: name.to_string(),
+        source: UpsSource::Nut,
+        on_battery: has_flag("OB"),
+        low_battery: has_flag("LB"),
+        charge_percent: number("battery.charge"),
+        runtime_seconds: number("battery.runtime"),
+        load_percent: number("ups.load"),
+    }
+}
+
+/// Every UPS a NUT server serves. Empty when it doesn't answer or requires a login for
+/// reading (upsd allows anonymous reads by default).
+pub fn query_nut(ip: IpAddr, timeout: Duration) -> Vec<UpsStatus> {
+    let query = || -> Option<Vec<UpsStatus>> {
+        let mut stream =
+            TcpStream::connect_timeout(&SocketAddr::new(ip, NUT_PORT), timeout).ok()?;
+        stream.set_read_timeout(Some(timeout)).ok()?;
+        stream.set_write_timeout(Some(timeout)).ok()?;
+        let mut reader = BufReader::new(stream.try_clone().ok()?);
+
+        // "UPS <name> "<description>""
+        let names: Vec<String> = nut_command(&mut stream, &mut reader, "LIST UPS", "END LIST UPS")?
+            .iter()
+            .filter_map(|line| line.strip_prefix("UPS ")?.split_whitespace().next())
+            .map(String::from)
+            .collect();
+        let statuses = names
+            .iter()
+            .filter_map(|name| {
+                let vars = nut_command(
+                    &mut stream,
+                    &mut reader,
+                    &format!("LIST VAR {}", name),
+                    &format!("END LIST VAR {}", name),
+                )?;
+                Some(parse_nut_vars(name, &vars))
+            })
+            .collect();
+        let _ = stream.write_all(b"LOGOUT\n");
+        Some(statuses)
+    };
+    query().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_apc_status() {
+        let values = vec![
+            (APC_LAYOUT.battery_status.to_vec(), "2".to_string()),
+            (APC_LAYOUT.charge.to_vec(), "87".to_string()),
+            (APC_LAYOUT.runtime.to_vec(), "252000".to_string()),
+            (APC_LAYOUT.output_status.to_vec(), "3".to_string()),
+            (APC_LAYOUT.load.to_vec(), "24".to_string()),
+        ];
+        let status = parse_snmp_status(&APC_LAYOUT, &values).unwrap();
+        assert!(status.on_battery);
+        assert!(!status.low_battery);
+        assert_eq!(status.runtime_seconds, Some(2520));
+        // Another vendor's MIB doesn't answer for these values
+        assert!(parse_snmp_status(&CYBERPOWER_LAYOUT, &values).is_none());
+    }
+
+    #[test]
+    fn test_parse_nut_vars() {
+        let lines: Vec<String> = [
+            r#"VAR rack battery.charge "18""#,
+            r#"VAR rack battery.runtime "300""#,
+            r#"VAR rack device.mfr "CPS""#,
+            r#"VAR rack ups.load "31""#,
+            r#"VAR rack ups.status "OB DISCHRG LB""#,
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let status = parse_nut_vars("rack", &lines);
+        assert!(status.on_battery && status.low_battery);
+        assert_eq!(status.charge_percent, Some(18));
+        assert_eq!(status.runtime_seconds, Some(300));
+        assert_eq!(status.load_percent, Some(31));
+    }
+}
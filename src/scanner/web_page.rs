@@ -0,0 +1,351 @@
+//! Web interface fingerprinting. Fetches the landing page served on a device's
+//! HTTP(S) port and keeps its title and favicon, which usually name the product
+//! outright ("TP-Link login page", "Synology DSM").
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use super::{Favicon, WebPageInfo};
+
+/// Ports checked for a web interface, in order of preference
+pub const WEB_PORTS: &[u16] = &[80, 443, 8080, 8443];
+
+/// Ports spoken to over TLS
+const HTTPS_PORTS: &[u16] = &[443, 8443];
+
+/// Larger pages are cut off; the title sits in the head anyway
+const MAX_PAGE_BYTES: usize = 256 * 1024;
+
+const MAX_FAVICON_BYTES: usize = 64 * 1024;
+
+const MAX_TITLE_CHARS: usize = 200;
+
+/// Fetches page titles and favicons from device web interfaces
+pub struct WebPageFetcher {
+    timeout_ms: u64,
+}
+
+impl WebPageFetcher {
+    pub fn new() -> Self {
+        Self { timeout_ms: 3000 }
+    }
+
+    /// Fetch the landing page on the first of `ports` that serves one, preferring a
+    /// page with a title. Embedded devices rarely have valid certificates, so TLS
+    /// errors are ignored.
+    pub async fn fetch(&self, ip: IpAddr, ports: &[u16]) -> Option<WebPageInfo> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(self.timeout_ms))
+            .danger_accept_invalid_certs(true)
+            .redirect(reqwest::redirect::Policy::limited(5))
+            .build()
+            .ok()?;
+
+        let mut fallback: Option<(reqwest::Url, String)> = None;
+        for &port in WEB_PORTS.iter().filter(|p| ports.contains(p)) {
+            let Some((url, html)) = Self::fetch_page(&client, &page_url(ip, port)).await else {
+                continue;
+            };
+            if parse_title(&html).is_some() {
+                return Some(Self::page_info(&client, url, &html).await);
+            }
+            if fallback.is_none() {
+                fallback = Some((url, html));
+            }
+        }
+
+        let (url, html) = fallback?;
+        Some(Self::page_info(&client, url, &html).await)
+    }
+
+    async fn fetch_page(client: &reqwest::Client, url: &str) -> Option<(reqwest::Url, String)> {
+        // Login pages often answer 401/403 but still carry a useful title
+        let response = client.get(url).send().await.ok()?;
+        let url = response.url().clone();
+        let mut body = response.bytes().await.ok()?.to_vec();
+        body.truncate(MAX_PAGE_BYTES);
+        Some((url, String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    async fn page_info(client: &reqwest::Client, url: reqwest::Url, html: &str) -> WebPageInfo {
+        let href = find_favicon_href(html).unwrap_or_else(|| "/favicon.ico".to_string());
+        let favicon = match url.join(&href) {
+            Ok(favicon_url) => Self::fetch_favicon(client, favicon_url).await,
+            Err(_) => None,
+        };
+        WebPageInfo {
+            url: url.to_string(),
+            title: parse_title(html),
+            favicon,
+        }
+    }
+
+    async fn fetch_favicon(client: &reqwest::Client, url: reqwest::Url) -> Option<Favicon> {
+        let response = client.get(url).send().await.ok()?.error_for_status().ok()?;
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > MAX_FAVICON_BYTES)
+        {
+            return None;
+        }
+        let data = response.bytes().await.ok()?;
+        if data.len() > MAX_FAVICON_BYTES {
+            return None;
+        }
+        // Trust the bytes rather than the header: devices label icons as text/html,
+        // and SVG is left out as it could carry script
+        let content_type = sniff_image_type(&data)?;
+        Some(Favicon {
+            content_type: content_type.to_string(),
+            data: data.to_vec(),
+        })
+    }
+}
+
+impl Default for WebPageFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn page_url(ip: IpAddr, port: u16) -> String {
+    let scheme = if HTTPS_PORTS.contains(&port) {
+        "https"
+    } else {
+        "http"
+    };
+    match ip {
+        IpAddr::V4(v4) => format!("{}://{}:{}/", scheme, v4, port),
+        IpAddr::V6(v6) => format!("{}://[{}]:{}/", scheme, v6, port),
+    }
+}
+
+/// The page's `<title>`, with entities decoded and whitespace collapsed
+pub fn parse_title(html: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets valid for slicing `html`
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+
+    let title = decode_entities(&html[start..end])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if title.is_empty() {
+        return None;
+    }
+    Some(title.chars().take(MAX_TITLE_CHARS).collect())
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&len| len <= 8)
+            .map(|len| &rest[1..=len]);
+        let ch = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = entity.strip_prefix('#')?;
+                let code = match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => code.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (ch, entity) {
+            (Some(ch), Some(entity)) => {
+                decoded.push(ch);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The `href` of the page's icon `<link>`, preferring `rel="icon"` (or
+/// "shortcut icon") over apple-touch-icon
+pub fn find_favicon_href(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut touch_icon = None;
+    for (start, _) in lower.match_indices("<link") {
+        let tag_start = start + "<link".len();
+        let Some(tag_len) = lower[tag_start..].find('>') else {
+            break;
+        };
+        let attributes = tag_attributes(&html[tag_start..tag_start + tag_len]);
+        let attribute = |name: &str| {
+            attributes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        };
+        let (Some(rel), Some(href)) = (attribute("rel"), attribute("href")) else {
+            continue;
+        };
+        if href.is_empty() {
+            continue;
+        }
+        let rel = rel.to_ascii_lowercase();
+        if rel.split_whitespace().any(|token| token == "icon") {
+            return Some(href.to_string());
+        }
+        if touch_icon.is_none() && rel.split_whitespace().any(|t| t == "apple-touch-icon") {
+            touch_icon = Some(href.to_string());
+        }
+    }
+    touch_icon
+}
+
+/// Attribute names (lowercased) and values from the inside of an HTML tag
+fn tag_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            break;
+        }
+        let name = rest[..name_len].to_ascii_lowercase();
+        rest = rest[name_len..].trim_start();
+
+        let Some(value_start) = rest.strip_prefix('=') else {
+            attributes.push((name, String::new()));
+            continue;
+        };
+        let value_start = value_start.trim_start();
+        let (value, remaining) = match value_start.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &value_start[1..];
+                let end = inner.find(quote).unwrap_or(inner.len());
+                (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = value_start
+                    .find(char::is_whitespace)
+                    .unwrap_or(value_start.len());
+                (&value_start[..end], &value_start[end..])
+            }
+        };
+        attributes.push((name, decode_entities(value)));
+        rest = remaining;
+    }
+    attributes
+}
+
+/// Content type of a raster image, recognised by its leading bytes
+fn sniff_image_type(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0x00, 0x00, 0x01, 0x00, ..] => Some("image/x-icon"),
+        [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => Some("image/webp"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_title() {
+        assert_eq!(
+            parse_title("<html><HEAD><Title>\n  Synology&nbsp;DSM &amp; more\n</TITLE>"),
+            Some("Synology DSM & more".to_string())
+        );
+        assert_eq!(
+            parse_title("<title lang=\"en\">TP-Link &#8211; Login</title>"),
+            Some("TP-Link \u{2013} Login".to_string())
+        );
+        assert_eq!(parse_title("<title>  </title>"), None);
+        assert_eq!(parse_title("<html><body>No title</body></html>"), None);
+        assert_eq!(
+            parse_title("<title>R&D &unknown; &</title>").unwrap(),
+            "R&D &unknown; &"
+        );
+    }
+
+    #[test]
+    fn test_find_favicon_href() {
+        let html = r#"<head>
+<link rel="stylesheet" href="/style.css">
+<link rel="apple-touch-icon" href="/touch.png">
+<LINK REL='shortcut icon' HREF='/img/favicon.ico?v=2' />
+</head>"#;
+        assert_eq!(
+            find_favicon_href(html).as_deref(),
+            Some("/img/favicon.ico?v=2")
+        );
+
+        let html =
+            r#"<link href=/touch.png rel=apple-touch-icon><link rel="stylesheet" href="a.css">"#;
+        assert_eq!(find_favicon_href(html).as_deref(), Some("/touch.png"));
+
+        assert!(find_favicon_href("<link rel=\"icon\" data-href=\"x.png\">").is_none());
+    }
+
+    #[test]
+    fn test_sniff_image_type() {
+        assert_eq!(sniff_image_type(&[0, 0, 1, 0, 1, 0]), Some("image/x-icon"));
+        assert_eq!(sniff_image_type(b"\x89PNG\r\n\x1a\n"), Some("image/png"));
+        assert_eq!(
+            sniff_image_type(b"<svg xmlns=\"http://www.w3.org/2000/svg\">"),
+            None
+        );
+        assert_eq!(sniff_image_type(b"<!DOCTYPE html>"), None);
+    }
+
+    #[test]
+    fn test_page_url() {
+        assert_eq!(
+            page_url("192.168.1.1".parse().unwrap(), 8443),
+            "https://192.168.1.1:8443/"
+        );
+        assert_eq!(
+            page_url("fe80::1".parse().unwrap(), 80),
+            "http://[fe80::1]:80/"
+        );
+    }
+
+    #[test]
+    fn test_fetcher_default() {
+        let fetcher = WebPageFetcher::default();
+        assert_eq!(fetcher.timeout_ms, 3000);
+    }
+}
@@ -0,0 +1,199 @@
+//! Supervised background tasks. Long-running blocking work such as per-interface capture
+//! runs under a supervisor that catches both errors and panics, logs them, raises a
+//! `task_failed` notification, and starts the task again with exponential backoff, so a
+//! bad packet or a flapping interface doesn't silently end capture. One-shot work (scan
+//! types) reports its failures here too. `/api/health` reads the resulting task states.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::db::{insert_notification, new_connection_result};
+
+/// Wait before the first restart; doubled after each failure in a row
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A run lasting this long resets the backoff
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+
+static TASKS: OnceLock<RwLock<BTreeMap<String, TaskHealth>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskState {
+    Running,
+    /// Failed and waiting out its backoff
+    Restarting,
+    /// Ended on its own (e.g. capture stopped because the writer shut down)
+    Stopped,
+    /// Failed and not restarted
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub name: String,
+    pub state: TaskState,
+    /// Unix time the current run started
+    pub started_at: i64,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<i64>,
+}
+
+fn tasks() -> &'static RwLock<BTreeMap<String, TaskHealth>> {
+    TASKS.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+fn update(name: &str, change: impl FnOnce(&mut TaskHealth)) {
+    if let Ok(mut tasks) = tasks().write() {
+        let task = tasks.entry(name.to_string()).or_insert_with(|| TaskHealth {
+            name: name.to_string(),
+            state: TaskState::Running,
+            started_at: chrono::Utc::now().timestamp(),
+            restarts: 0,
+            last_error: None,
+            last_error_at: None,
+        });
+        change(task);
+    }
+}
+
+/// Every task that has run, by name
+pub fn task_health() -> Vec<TaskHealth> {
+    tasks()
+        .read()
+        .map(|tasks| tasks.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The message a panic was raised with
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Record that a task failed: log it and raise a notification (blocking)
+pub fn record_failure(name: &str, error: &str) {
+    eprintln!("Task {} failed: {}", name, error);
+    update(name, |task| {
+        task.state = TaskState::Failed;
+        task.last_error = Some(error.to_string());
+        task.last_error_at = Some(chrono::Utc::now().timestamp());
+    });
+    match new_connection_result() {
+        Ok(conn) => insert_notification(
+            &conn,
+            "task_failed",
+            &format!("Background task {} failed", name),
+            Some(error),
+            None,
+        ),
+        Err(e) => eprintln!("Failed to record failure of {}: {}", name, e),
+    }
+}
+
+/// Mark a one-shot task as finished, unless it failed after `since`
+pub fn record_finished(name: &str, since: i64) {
+    if let Ok(mut tasks) = tasks().write()
+        && let Some(task) = tasks.get_mut(name)
+        && task.last_error_at.is_none_or(|at| at < since)
+    {
+        task.state = TaskState::Stopped;
+    }
+}
+
+/// Run `task` on the blocking pool, starting it again with backoff whenever it returns
+/// an error or panics. Returning `Ok(())` ends supervision.
+pub fn spawn_supervised_blocking<F>(name: String, task: F)
+where
+    F: Fn() -> Result<(), String> + Send + Sync + 'static,
+{
+    let task = Arc::new(task);
+    tokio::spawn(async move {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            update(&name, |health| {
+                health.state = TaskState::Running;
+                health.started_at = chrono::Utc::now().timestamp();
+            });
+            let started = Instant::now();
+            let run = task.clone();
+            let error = match tokio::task::spawn_blocking(move || run()).await {
+                Ok(Ok(())) => {
+                    update(&name, |health| health.state = TaskState::Stopped);
+                    return;
+                }
+                Ok(Err(e)) => e,
+                Err(e) => match e.try_into_panic() {
+                    Ok(payload) => format!("panicked: {}", panic_message(&*payload)),
+                    Err(e) => e.to_string(),
+                },
+            };
+
+            let (failed, message) = (name.clone(), error);
+            let _ = tokio::task::spawn_blocking(move || record_failure(&failed, &message)).await;
+            if started.elapsed() >= HEALTHY_RUN {
+                backoff = MIN_BACKOFF;
+            }
+            update(&name, |health| {
+                health.state = TaskState::Restarting;
+                health.restarts += 1;
+            });
+            eprintln!("Restarting {} in {}s", name, backoff.as_secs());
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message_and_finished_state() {
+        let payload = std::panic::catch_unwind(|| panic!("bad frame {}", 7)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "bad frame 7");
+        let payload = std::panic::catch_unwind(|| panic!("index out of bounds")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "index out of bounds");
+
+        let fail_at = |at: i64| {
+            update("test:scan", |task| {
+                task.state = TaskState::Failed;
+                task.last_error_at = Some(at);
+            })
+        };
+        let state = || {
+            task_health()
+                .into_iter()
+                .find(|task| task.name == "test:scan")
+                .map(|task| task.state)
+        };
+
+        // A later run that finishes cleanly clears an earlier failure
+        fail_at(100);
+        record_finished("test:scan", 200);
+        assert_eq!(state(), Some(TaskState::Stopped));
+
+        // But not a failure from the run that just finished
+        fail_at(300);
+        record_finished("test:scan", 200);
+        assert_eq!(state(), Some(TaskState::Failed));
+
+        // Tasks that never failed aren't tracked
+        record_finished("test:never-failed", 0);
+        assert!(
+            task_health()
+                .iter()
+                .all(|task| task.name != "test:never-failed")
+        );
+    }
+}
@@ -2,42 +2,121 @@
 //! Extracted from `mod.rs` to reduce file size and improve maintainability.
 
 use actix_multipart::Multipart;
+use actix_web::http::StatusCode;
 use actix_web::web::{Json, Query};
-use actix_web::{HttpResponse, Responder, get, post};
+use actix_web::{HttpRequest, HttpResponse, Responder, get, post};
 use futures_util::StreamExt;
 use rusqlite::{Connection, OptionalExtension, params};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::io::Write;
 use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::mpsc;
 
+use crate::config::effective_config;
 use crate::db::{
-    SQLWriter, get_all_settings, get_setting_i64, insert_notification,
-    insert_notification_with_endpoint_id, new_connection, new_connection_result, set_setting,
+    BulkEndpointEdit, CaptureExclusion, CaptureSchedule, DEFAULT_QUERY_ROWS, DatabaseReport,
+    DriftSource, EndpointAppearance, EndpointSummary, EnergyReport, FeedbackKind, GraphFilter,
+    GraphFormat, IpamSyncConfig, MAX_BACKUP_BYTES, MAX_GRID_ADDRESSES, MAX_TIMESERIES_RANGE_SECS,
+    MAX_WATTS, NOTIFICATION_SEVERITIES, NewBandwidthQuota, NewDeviceMacro, NewPortPolicy,
+    NewWebhook, QueryRequest, QueryResult, Role, SETTINGS, SQLWriter, SavedView, ScanChangeset,
+    ScanRun, SettingDefinition, SystemStats, TimeSeriesMetric, TypeAppearance, TypeBaselineRequest,
+    TypePower, UpnpEventSource, UpnpSubscription, User, WEBHOOK_EVENTS, admins_remain_after,
+    analyze_database, apply_bulk_edit, backup_file_name, build_traffic_matrix, check_port_policy,
+    clear_ipam_sync_config, create_backup_bytes, create_person, create_webhook,
+    delete_bandwidth_quota, delete_device_macro, delete_endpoint_summaries, delete_person,
+    delete_port_policy, delete_type_baseline, delete_upnp_subscription, delete_user, delete_view,
+    delete_webhook, diff_scan_runs, endpoint_ids_with_tags, exclude_endpoint, exclude_mac,
+    export_rule_suggestions, find_upnp_subscription, get_activity, get_all_settings,
+    get_bandwidth_quotas, get_baseline_behaviors, get_baseline_deviations, get_capture_exclusions,
+    get_capture_schedule, get_communication_graph, get_device_macro, get_device_macros,
+    get_endpoint_appearances, get_endpoint_host_keys, get_endpoint_last_seen, get_endpoint_power,
+    get_endpoint_summaries, get_endpoint_tags, get_endpoint_timeline, get_endpoint_ups,
+    get_energy_report, get_exceeded_quotas, get_expiring_upnp_subscriptions, get_host_key_targets,
+    get_internet_summary, get_ipam_sync_config, get_ipam_sync_status, get_link_suggestions,
+    get_linked_endpoint_ids, get_people, get_person_summary, get_port_drift, get_port_mappings,
+    get_port_policies, get_previous_scan_run_id, get_printer_report, get_recently_seen_addresses,
+    get_rule_suggestions, get_saved_views, get_scan_results, get_scan_run, get_scan_run_id_before,
+    get_scan_runs, get_setting, get_setting_i64, get_smb_shares, get_stale_endpoint_summaries,
+    get_storage_overview, get_storage_report, get_subnet_summaries, get_system_stats,
+    get_tag_counts, get_tcp_quality_report, get_time_series, get_tls_certificates,
+    get_type_appearances, get_type_baselines, get_type_power, get_unsubscribed_upnp_devices,
+    get_upnp_device_state, get_ups_overview, get_usage_report, get_user_preferences, get_users,
+    get_web_favicon, get_web_info_summaries, get_web_info_targets, get_webhooks,
+    has_endpoint_summaries, has_ipam_sync_token, has_snmp_credential, include_endpoint,
+    include_mac, insert_notification, insert_notification_with_endpoint_id, ipam_sync_token_kept,
+    is_capture_excluded, is_capture_scheduled_off, is_endpoint_trusted, is_known_device_type,
+    is_preference_key, link_endpoints, load_address_holders, load_snmp_credentials,
+    mark_all_notifications_acknowledged, mark_notifications_acknowledged, new_connection,
+    new_connection_result, new_read_only_connection, normalize_view_query, notification_summary,
+    parse_color, parse_icon, parse_person_name, parse_preference, parse_tag, parse_tags,
+    parse_view_name, purge_endpoint_traffic, record_activity_event, record_classification_feedback,
+    record_port_mappings, record_scan_run, record_upnp_event, replace_endpoint_summaries,
+    replace_smb_shares, restore_backup, rollup_energy, run_in_writer, run_ipam_sync,
+    run_read_only_query, save_device_macro, save_upnp_subscription, save_view, set_bandwidth_quota,
+    set_capture_schedule, set_default_view, set_endpoint_appearance, set_endpoint_owner,
+    set_endpoint_power, set_endpoint_tags, set_endpoint_trusted, set_ipam_sync_config,
+    set_port_policy, set_setting, set_snmp_credential, set_type_appearance, set_type_baseline_mode,
+    set_type_power, set_user, set_user_preference, set_user_token, store_host_keys, store_web_info,
+    subnet_grid, take_dirty_endpoint_summaries, to_cytoscape_json, to_dot, to_graphml,
+    unlink_endpoint, update_view, upsert_endpoint_summaries, validate_setting,
 };
+use crate::health::{
+    self, ComponentHealth, ComponentStatus, capture_components, mdns_component, overall_status,
+    writer_component,
+};
+use crate::network::blocklist::{
+    BUNDLED_LIST_NAME, delete_blocklist, get_blocklist_hits, get_blocklists, import_blocklist,
+    parse_blocklist,
+};
+use crate::network::broadcast::{BroadcastTalker, get_broadcast_talkers};
 use crate::network::communication::extract_model_from_vendor_class;
 use crate::network::device_control::DeviceController;
+use crate::network::dns_audit::get_dns_server_usage;
+use crate::network::eapol::{AuthEventRow, get_auth_events};
 use crate::network::endpoint::{
-    EndPoint, characterize_model, characterize_vendor, get_hostname_vendor, get_mac_vendor,
+    DeviceSample, EndPoint, IdentityKind, IdentityResolution, NameSource, characterize_model,
+    characterize_vendor, component_vendors, evaluate, get_hostname_vendor, get_mac_vendor,
     get_model_from_hostname, get_model_from_mac, get_model_from_vendor_and_type,
-    get_vendor_from_model, infer_model_with_context, is_valid_display_name, normalize_model_name,
-    strip_local_suffix,
+    get_vendor_from_model, infer_model_with_context, is_component_vendor, is_valid_display_name,
+    normalize_model_name, product_vendor_macs, reload_runtime_rules, strip_local_suffix,
+};
+use crate::network::endpoint_attribute::EndPointAttribute;
+use crate::network::hostname_resolver::{HostnameResolver, HostnameSource};
+use crate::network::live_tail::subscribe_live_flows;
+use crate::network::matter::get_matter_info;
+use crate::network::router_advertisement::{Ipv6Router, get_ipv6_routers, set_router_trusted};
+use crate::network::wireless::{WirelessStation, get_wireless_stations};
+use crate::scanner::default_credentials::{
+    CREDENTIAL_CHECK_PORTS, DefaultCredentialChecker, DefaultCredentialFinding,
+    is_credential_check_target,
+};
+use crate::scanner::gena;
+use crate::scanner::host_keys::{HostKeyCollector, SSH_PORTS, TLS_PORTS};
+use crate::scanner::manager::{SCAN_CONFIG_SETTING, ScanConfig, ScanEvent, ScanManager};
+use crate::scanner::registry::scanner_info;
+use crate::scanner::web_page::{WEB_PORTS, WebPageFetcher};
+use crate::scanner::{
+    NetBiosResult, ScanResult, ScanResultKey, ScanType, SmbShare, SnmpResult, SsdpResult,
+    SshHostKey, TlsCertificate, UpnpPortMapping, WebPageInfo, check_privileges,
+    check_scan_privileges,
 };
-use crate::scanner::manager::{ScanConfig, ScanManager};
-use crate::scanner::{ScanResult, ScanType, check_scan_privileges};
+use crate::supervisor::{self, TaskState, task_health};
 
 use rust_xlsxwriter::{Format, Workbook};
 
 // Shared items from parent (mod.rs)
+use super::auth::{Caller, RequireAdmin, request_identity};
 use super::{
-    DISPLAY_NAME_SQL, EndpointDetailsResponse, NodeQuery, dropdown_endpoints,
-    get_all_endpoint_types, get_all_endpoints_last_seen, get_all_endpoints_online_status,
+    DISPLAY_NAME_SQL, EndpointDetailsResponse, LinkedEndpointView, NodeQuery, box_i64_params,
+    build_in_placeholders, dropdown_endpoints, format_last_seen, get_all_endpoint_types,
     get_all_ips_macs_and_hostnames_from_single_hostname, get_all_protocols, get_bytes_for_endpoint,
-    get_combined_endpoint_stats, get_dns_entries, get_endpoint_ips_and_macs,
-    get_endpoint_ssdp_models, get_endpoints_for_protocol, get_ports_for_endpoint,
-    get_protocols_for_endpoint, looks_like_ip, probe_and_save_hp_printer_model_blocking,
-    probe_hp_printer_model_blocking,
+    get_bytes_for_endpoint_ids, get_combined_endpoint_stats, get_dns_entries,
+    get_endpoint_ips_and_macs, get_endpoint_ssdp_models, get_endpoints_for_protocol,
+    get_ports_for_endpoint, get_protocols_for_endpoint, looks_like_ip, params_to_refs,
+    probe_and_save_hp_printer_model_blocking, probe_hp_printer_model_blocking,
+    resolve_identifier_to_endpoint_ids,
 };
 
 // ============================================================================
@@ -54,36 +133,34 @@ fn get_probing_endpoints() -> &'static Mutex<HashSet<i64>> {
 // Cache for endpoint table data to avoid repeated DB queries
 static ENDPOINT_TABLE_CACHE: OnceLock<Mutex<EndpointTableCache>> = OnceLock::new();
 
+/// Full (unsorted, unpaged) endpoint tables keyed by capture interface ("" = all).
+/// Sorting, filtering and paging are applied per request on top of a cached table.
 struct EndpointTableCache {
-    data: Option<Vec<EndpointTableRow>>,
-    last_updated: std::time::Instant,
+    entries: HashMap<String, (std::time::Instant, Vec<EndpointTableRow>)>,
     ttl_seconds: u64,
 }
 
 impl EndpointTableCache {
     fn new() -> Self {
         Self {
-            data: None,
-            last_updated: std::time::Instant::now(),
+            entries: HashMap::new(),
             ttl_seconds: 3, // Cache for 3 seconds
         }
     }
 
-    fn is_valid(&self) -> bool {
-        self.data.is_some() && self.last_updated.elapsed().as_secs() < self.ttl_seconds
-    }
-
-    fn get(&self) -> Option<Vec<EndpointTableRow>> {
-        if self.is_valid() {
-            self.data.clone()
-        } else {
-            None
-        }
+    fn get(&self, key: &str) -> Option<Vec<EndpointTableRow>> {
+        self.entries
+            .get(key)
+            .filter(|(updated, _)| updated.elapsed().as_secs() < self.ttl_seconds)
+            .map(|(_, data)| data.clone())
     }
 
-    fn set(&mut self, data: Vec<EndpointTableRow>) {
-        self.data = Some(data);
-        self.last_updated = std::time::Instant::now();
+    fn set(&mut self, key: &str, data: Vec<EndpointTableRow>) {
+        let ttl = self.ttl_seconds;
+        self.entries
+            .retain(|_, (updated, _)| updated.elapsed().as_secs() < ttl);
+        self.entries
+            .insert(key.to_string(), (std::time::Instant::now(), data));
     }
 }
 
@@ -91,29 +168,147 @@ fn get_endpoint_table_cache() -> &'static Mutex<EndpointTableCache> {
     ENDPOINT_TABLE_CACHE.get_or_init(|| Mutex::new(EndpointTableCache::new()))
 }
 
+/// Most scan results recorded in one transaction
+const SCAN_RESULT_BATCH_SIZE: usize = 200;
+
+/// Gather `first` and the results already queued behind it into one batch, skipping
+/// repeats of results seen earlier in the run. Stops at a full batch or at the first
+/// event that isn't a result, which is returned to be handled next.
+fn gather_scan_batch(
+    first: ScanResult,
+    rx: &mut mpsc::Receiver<ScanEvent>,
+    seen: &mut HashSet<ScanResultKey>,
+) -> (Vec<ScanResult>, Option<ScanEvent>) {
+    let mut batch = Vec::new();
+    let mut pending = Some(first);
+    while let Some(result) = pending.take() {
+        if result.repeat_key().is_none_or(|key| seen.insert(key)) {
+            batch.push(result);
+        }
+        if batch.len() >= SCAN_RESULT_BATCH_SIZE {
+            break;
+        }
+        match rx.try_recv() {
+            Ok(ScanEvent::Result(result)) => pending = Some(result),
+            Ok(event) => return (batch, Some(event)),
+            Err(_) => {}
+        }
+    }
+    (batch, None)
+}
+
 /// Global scan manager instance
 static SCAN_MANAGER: OnceLock<std::sync::Arc<ScanManager>> = OnceLock::new();
 
 pub fn get_scan_manager() -> std::sync::Arc<ScanManager> {
     SCAN_MANAGER
         .get_or_init(|| {
-            let (tx, mut rx) = mpsc::channel::<ScanResult>(1000);
+            let (tx, mut rx) = mpsc::channel::<ScanEvent>(1000);
 
-            // Spawn a task to process scan results
+            // Spawn a task to process scan events
             tokio::spawn(async move {
-                while let Some(result) = rx.recv().await {
-                    // Process scan result - create/update endpoint in database
-                    if let Err(e) = process_scan_result(&result) {
-                        eprintln!("Error processing scan result: {}", e);
+                // Results already recorded this run; ARP and NDP retries answer again
+                let mut seen = HashSet::new();
+                // An event read while gathering a batch, handled next
+                let mut next = None;
+                loop {
+                    let event = match next.take() {
+                        Some(event) => event,
+                        None => match rx.recv().await {
+                            Some(event) => event,
+                            None => break,
+                        },
+                    };
+                    match event {
+                        // Sweeps answer in bursts: everything already queued is recorded
+                        // in one transaction on the SQL writer, with repeats within the
+                        // run skipped
+                        ScanEvent::Result(result) => {
+                            let (batch, following) = gather_scan_batch(result, &mut rx, &mut seen);
+                            next = following;
+                            if !batch.is_empty()
+                                && let Err(e) = run_in_writer(move |tx| {
+                                    record_scan_results(tx, &batch);
+                                    Ok(())
+                                })
+                                .await
+                            {
+                                eprintln!("Failed to record scan results: {}", e);
+                            }
+                        }
+                        ScanEvent::Failed { scan_type, error } => {
+                            let name = format!("scan:{}", scan_type);
+                            let _ = tokio::task::spawn_blocking(move || {
+                                supervisor::record_failure(&name, &error)
+                            })
+                            .await;
+                        }
+                        // Results arrive before completion, so the run snapshot is complete
+                        ScanEvent::Completed {
+                            started_at,
+                            finished_at,
+                            scan_types,
+                            stopped,
+                        } => {
+                            if !stopped {
+                                record_completed_scan(started_at, finished_at, &scan_types);
+                            }
+                            for scan_type in &scan_types {
+                                supervisor::record_finished(
+                                    &format!("scan:{}", scan_type),
+                                    started_at,
+                                );
+                            }
+                            supervisor::record_finished("scan-results", started_at);
+                            seen.clear();
+                            // Newly found web ports get their title and favicon fetched,
+                            // SSH and TLS ports their host keys and certificates
+                            if scan_types.contains(&ScanType::PORT) {
+                                tokio::spawn(refresh_stale_web_info());
+                                tokio::spawn(refresh_stale_host_keys());
+                                if get_setting("default_credentials_check").as_deref()
+                                    == Some("true")
+                                {
+                                    tokio::spawn(check_default_credentials());
+                                }
+                            }
+                        }
                     }
                 }
             });
 
-            std::sync::Arc::new(ScanManager::new(tx))
+            std::sync::Arc::new(
+                ScanManager::new(tx)
+                    .with_config(saved_scan_config())
+                    .with_snmp_credentials(std::sync::Arc::new(|| {
+                        let conn = new_connection_result().ok()?;
+                        load_snmp_credentials(&conn)
+                            .map_err(|e| eprintln!("Failed to load SNMP credentials: {}", e))
+                            .ok()
+                    }))
+                    .with_observed_addresses(std::sync::Arc::new(|since| {
+                        let conn = new_connection_result().ok()?;
+                        get_recently_seen_addresses(&conn, since)
+                            .map_err(|e| eprintln!("Failed to load observed addresses: {}", e))
+                            .ok()
+                    })),
+            )
         })
         .clone()
 }
 
+/// The scan config saved through the API, or the defaults when there is none or it no
+/// longer checks out (e.g. a scanner it tunes was removed)
+fn saved_scan_config() -> ScanConfig {
+    match get_setting(SCAN_CONFIG_SETTING).filter(|json| !json.is_empty()) {
+        Some(json) => ScanConfig::from_json(&json).unwrap_or_else(|e| {
+            eprintln!("Ignoring saved scan config: {}", e);
+            ScanConfig::default()
+        }),
+        None => ScanConfig::default(),
+    }
+}
+
 // ============================================================================
 // DNS API Endpoints
 // ============================================================================
@@ -172,13 +367,15 @@ pub struct ProbeResponse {
     success: bool,
 }
 
-/// Probe a device for its hostname using reverse DNS/mDNS lookup
+/// Probe a device for its hostname via the hostname resolver (mDNS, NetBIOS, PTR, SNMP)
 /// Also persists the hostname to the database if found
 #[post("/api/probe-hostname")]
-pub async fn probe_hostname(body: Json<ProbeRequest>) -> impl Responder {
-    use crate::network::mdns_lookup::MDnsLookup;
-
-    let hostname = MDnsLookup::probe_hostname(&body.ip);
+pub async fn probe_hostname(_admin: RequireAdmin, body: Json<ProbeRequest>) -> impl Responder {
+    let ip = body.ip.clone();
+    let hostname = tokio::task::spawn_blocking(move || HostnameResolver::resolve(&ip))
+        .await
+        .ok()
+        .flatten();
 
     // If we found a real hostname (not just the IP back), save it to the database
     if let Some(ref h) = hostname
@@ -211,12 +408,14 @@ pub struct NetBiosProbeResponse {
     netbios_name: Option<String>,
     group_name: Option<String>,
     mac: Option<String>,
+    /// Shares visible to an anonymous SMB session; None when SMB did not answer
+    smb_shares: Option<Vec<SmbShare>>,
     success: bool,
 }
 
-/// Probe a device for its NetBIOS name
+/// Probe a device for its NetBIOS name and anonymously visible SMB shares
 #[post("/api/probe-netbios")]
-pub async fn probe_netbios(body: Json<ProbeRequest>) -> impl Responder {
+pub async fn probe_netbios(_admin: RequireAdmin, body: Json<ProbeRequest>) -> impl Responder {
     use crate::scanner::netbios::NetBiosScanner;
     use std::net::Ipv4Addr;
 
@@ -231,14 +430,19 @@ pub async fn probe_netbios(body: Json<ProbeRequest>) -> impl Responder {
                 netbios_name: None,
                 group_name: None,
                 mac: None,
+                smb_shares: None,
                 success: false,
             });
         }
     };
 
     // Run the NetBIOS query in a blocking task
+    let config = get_scan_manager().get_config().await;
+    let timeout_ms = config.probe_timeout_ms(ScanType::NETBIOS, 2000);
     let result = tokio::task::spawn_blocking(move || {
-        let scanner = NetBiosScanner::new().with_timeout(2000);
+        let scanner = NetBiosScanner::new()
+            .with_timeout(timeout_ms)
+            .with_share_enumeration(true);
         scanner.query_ip(ip)
     })
     .await;
@@ -247,18 +451,25 @@ pub async fn probe_netbios(body: Json<ProbeRequest>) -> impl Responder {
         Ok(Some(netbios)) => {
             // Save NetBIOS name to endpoint if found
             let netbios_name = netbios.netbios_name.clone();
+            HostnameResolver::record(&ip_str, &netbios_name, HostnameSource::NetBios);
             let ip_for_db = ip_str.clone();
+            let netbios_for_db = netbios.clone();
             tokio::task::spawn_blocking(move || {
                 if let Ok(conn) = new_connection_result() {
+                    if let Some(endpoint_id) = find_existing_endpoint_by_ip(&conn, &ip_for_db) {
+                        record_netbios_smb_info(&conn, endpoint_id, &netbios_for_db);
+                    }
                     // Find endpoint by IP and update netbios_name
                     let _ = conn.execute(
                         "UPDATE endpoints SET netbios_name = ?1 WHERE id IN (SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?2) AND (netbios_name IS NULL OR netbios_name = '')",
                         rusqlite::params![netbios_name, ip_for_db],
                     );
-                    // Also update endpoint name if it's currently just an IP address
-                    let _ = conn.execute(
-                        "UPDATE endpoints SET name = ?1 WHERE id IN (SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?2) AND (name = ?2 OR name GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*')",
-                        rusqlite::params![netbios_name, ip_for_db],
+                    // Also name the endpoint, unless mDNS or DHCP already has
+                    let _ = EndPoint::set_discovered_name_for_ip(
+                        &conn,
+                        &ip_for_db,
+                        &netbios_name,
+                        NameSource::NetBios,
                     );
                 }
             });
@@ -268,6 +479,7 @@ pub async fn probe_netbios(body: Json<ProbeRequest>) -> impl Responder {
                 netbios_name: Some(netbios.netbios_name),
                 group_name: netbios.group_name,
                 mac: netbios.mac,
+                smb_shares: netbios.smb.map(|smb| smb.shares),
                 success: true,
             })
         }
@@ -276,6 +488,7 @@ pub async fn probe_netbios(body: Json<ProbeRequest>) -> impl Responder {
             netbios_name: None,
             group_name: None,
             mac: None,
+            smb_shares: None,
             success: false,
         }),
     }
@@ -295,54 +508,37 @@ pub struct PingResponse {
 
 /// Ping a device using ICMP echo
 #[post("/api/ping")]
-pub async fn ping_endpoint(body: Json<PingRequest>) -> impl Responder {
+pub async fn ping_endpoint(_admin: RequireAdmin, body: Json<PingRequest>) -> impl Responder {
+    use crate::scanner::icmp::IcmpScanner;
     use std::net::IpAddr;
-    use std::process::Command;
-    use std::time::Instant;
-
-    let ip = body.ip.clone();
 
     // Validate IP address
-    if ip.parse::<IpAddr>().is_err() {
+    let Ok(ip) = body.ip.parse::<IpAddr>() else {
         return HttpResponse::BadRequest().json(PingResponse {
             success: false,
             latency_ms: None,
             message: Some("Invalid IP address".to_string()),
         });
-    }
+    };
 
-    // Use system ping command (works without root on most systems)
-    // macOS uses -t for timeout, Linux uses -W
-    let start = Instant::now();
-    #[cfg(target_os = "macos")]
-    let output = Command::new("ping")
-        .args(["-c", "1", "-t", "2", &ip])
-        .output();
-    #[cfg(not(target_os = "macos"))]
-    let output = Command::new("ping")
-        .args(["-c", "1", "-W", "2", &ip])
-        .output();
-
-    match output {
-        Ok(result) => {
-            let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-            if result.status.success() {
-                // Try to parse actual latency from ping output
-                let stdout = String::from_utf8_lossy(&result.stdout);
-                let latency = parse_ping_latency(&stdout).unwrap_or(elapsed);
-                HttpResponse::Ok().json(PingResponse {
-                    success: true,
-                    latency_ms: Some(latency),
-                    message: None,
-                })
-            } else {
-                HttpResponse::Ok().json(PingResponse {
-                    success: false,
-                    latency_ms: None,
-                    message: Some("Host unreachable".to_string()),
-                })
-            }
-        }
+    let config = get_scan_manager().get_config().await;
+    let timeout_ms = config.probe_timeout_ms(ScanType::ICMP, 2000);
+    let result = tokio::task::spawn_blocking(move || {
+        IcmpScanner::new().with_timeout(timeout_ms).echo(ip, 1)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(reply)) => HttpResponse::Ok().json(PingResponse {
+            success: true,
+            latency_ms: Some(reply.rtt.as_secs_f64() * 1000.0),
+            message: None,
+        }),
+        Ok(Err(e)) => HttpResponse::Ok().json(PingResponse {
+            success: false,
+            latency_ms: None,
+            message: Some(e.to_string()),
+        }),
         Err(e) => HttpResponse::Ok().json(PingResponse {
             success: false,
             latency_ms: None,
@@ -351,29 +547,6 @@ pub async fn ping_endpoint(body: Json<PingRequest>) -> impl Responder {
     }
 }
 
-/// Parse latency from ping output (e.g., "time=1.23 ms")
-fn parse_ping_latency(output: &str) -> Option<f64> {
-    for line in output.lines() {
-        if let Some(time_idx) = line.find("time=") {
-            let after_time = &line[time_idx + 5..];
-            if let Some(latency) = after_time
-                .find(" ms")
-                .and_then(|idx| after_time[..idx].parse::<f64>().ok())
-            {
-                return Some(latency);
-            }
-            // Also try without space (time=1.23ms)
-            if let Some(latency) = after_time
-                .find("ms")
-                .and_then(|idx| after_time[..idx].parse::<f64>().ok())
-            {
-                return Some(latency);
-            }
-        }
-    }
-    None
-}
-
 #[derive(Deserialize)]
 pub struct PortScanRequest {
     ip: String,
@@ -394,7 +567,10 @@ pub struct PortScanResponse {
 
 /// Scan common ports on a device
 #[post("/api/port-scan")]
-pub async fn port_scan_endpoint(body: Json<PortScanRequest>) -> impl Responder {
+pub async fn port_scan_endpoint(
+    _admin: RequireAdmin,
+    body: Json<PortScanRequest>,
+) -> impl Responder {
     use std::net::{IpAddr, SocketAddr, TcpStream};
     use std::time::Duration;
 
@@ -582,20 +758,6 @@ fn get_endpoint_details_blocking(
     let mac_vendor = macs.iter().find_map(|mac| get_mac_vendor(mac));
     let hostname_vendor = get_hostname_vendor(&endpoint_name);
 
-    // Component vendors - these make chips/modules used by other manufacturers
-    const COMPONENT_VENDORS: &[&str] = &[
-        "Espressif",
-        "Tuya",
-        "Realtek",
-        "MediaTek",
-        "Qualcomm",
-        "Broadcom",
-        "Marvell",
-        "USI",
-        "Wisol",
-        "Murata",
-    ];
-
     // Get SSDP model early so we can use it for vendor detection
     let ssdp_model_for_vendor: Option<String> = conn
         .query_row(
@@ -657,7 +819,7 @@ fn get_endpoint_details_blocking(
             // Model vendor identified (e.g., TCL from "7105X") - use it before MAC
             (None, _, Some(mv)) => mv.to_string(),
             // MAC vendor is a component manufacturer - don't show it
-            (None, Some(mv), None) if COMPONENT_VENDORS.contains(&mv) => String::new(),
+            (None, Some(mv), None) if is_component_vendor(mv) => String::new(),
             // MAC vendor is a product manufacturer - show it
             (None, Some(mv), None) => mv.to_string(),
             // No vendor identified
@@ -751,6 +913,26 @@ fn get_endpoint_details_blocking(
     // Get bytes stats
     let bytes_stats = get_bytes_for_endpoint(endpoint_name.clone(), internal_minutes);
 
+    let endpoint_ids = resolve_identifier_to_endpoint_ids(&conn, &endpoint_name);
+    let capture_excluded = is_capture_excluded(&conn, &endpoint_ids);
+    let tags = get_endpoint_tags(&conn, &endpoint_ids).unwrap_or_default();
+    let trusted = is_endpoint_trusted(&conn, &endpoint_ids).unwrap_or(false);
+
+    let matter = get_matter_info(&ips);
+
+    let upnp_state = endpoint_ids
+        .iter()
+        .flat_map(|id| get_upnp_device_state(&conn, *id).unwrap_or_default())
+        .collect();
+
+    let linked_endpoints: Vec<LinkedEndpointView> = endpoint_ids
+        .first()
+        .and_then(|id| get_linked_endpoint_ids(&conn, *id).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|id| linked_endpoint_view(&conn, id, internal_minutes))
+        .collect();
+
     EndpointDetailsResponse {
         endpoint_name,
         device_type,
@@ -764,6 +946,189 @@ fn get_endpoint_details_blocking(
         protocols,
         bytes_in: bytes_stats.bytes_in,
         bytes_out: bytes_stats.bytes_out,
+        wan_bytes_in: bytes_stats.wan_bytes_in,
+        wan_bytes_out: bytes_stats.wan_bytes_out,
+        capture_excluded,
+        tags,
+        trusted,
+        matter,
+        upnp_state,
+        device_bytes_in: bytes_stats.bytes_in
+            + linked_endpoints.iter().map(|l| l.bytes_in).sum::<i64>(),
+        device_bytes_out: bytes_stats.bytes_out
+            + linked_endpoints.iter().map(|l| l.bytes_out).sum::<i64>(),
+        linked_endpoints,
+    }
+}
+
+/// Addresses and traffic of one linked interface, looked up by id since linked
+/// endpoints often share a name
+fn linked_endpoint_view(
+    conn: &rusqlite::Connection,
+    endpoint_id: i64,
+    internal_minutes: u64,
+) -> Option<LinkedEndpointView> {
+    let name: String = conn
+        .query_row(
+            &format!("SELECT {DISPLAY_NAME_SQL} FROM endpoints e WHERE e.id = ?1"),
+            [endpoint_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .ok()??;
+    let (mut ips, mut macs) = (Vec::new(), Vec::new());
+    if let Ok(mut stmt) =
+        conn.prepare("SELECT DISTINCT ip, mac FROM endpoint_attributes WHERE endpoint_id = ?1")
+        && let Ok(rows) = stmt.query_map([endpoint_id], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+            ))
+        })
+    {
+        for (ip, mac) in rows.flatten() {
+            ips.extend(ip.filter(|ip| !ip.is_empty()));
+            macs.extend(mac.filter(|mac| !mac.is_empty()));
+        }
+    }
+    ips.sort();
+    ips.dedup();
+    macs.sort();
+    macs.dedup();
+    let bytes = get_bytes_for_endpoint_ids(conn, &[endpoint_id], internal_minutes);
+    Some(LinkedEndpointView {
+        endpoint_id,
+        name,
+        ips,
+        macs,
+        bytes_in: bytes.bytes_in,
+        bytes_out: bytes.bytes_out,
+    })
+}
+
+/// Fields of `EndpointDetailsResponse` the bulk endpoint API can be limited to
+const ENDPOINT_DETAIL_FIELDS: &[&str] = &[
+    "endpoint_name",
+    "device_type",
+    "is_manual_override",
+    "device_vendor",
+    "device_model",
+    "ips",
+    "macs",
+    "hostnames",
+    "ports",
+    "protocols",
+    "bytes_in",
+    "bytes_out",
+    "wan_bytes_in",
+    "wan_bytes_out",
+    "capture_excluded",
+    "tags",
+    "trusted",
+    "matter",
+    "upnp_state",
+    "linked_endpoints",
+    "device_bytes_in",
+    "device_bytes_out",
+];
+
+/// Most endpoints returned per page of the bulk endpoint API
+const BULK_ENDPOINTS_MAX_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+pub struct BulkEndpointsQuery {
+    scan_interval: Option<u64>,
+    /// Comma-separated detail fields to return (all when omitted). The endpoint's id and
+    /// name are always included.
+    fields: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+/// Split a comma-separated `fields` parameter, rejecting names not in
+/// `ENDPOINT_DETAIL_FIELDS`
+fn parse_detail_fields(fields: &str) -> Result<Vec<String>, String> {
+    let fields: Vec<String> = fields
+        .split(',')
+        .map(|field| field.trim().to_string())
+        .filter(|field| !field.is_empty())
+        .collect();
+    match fields
+        .iter()
+        .find(|field| !ENDPOINT_DETAIL_FIELDS.contains(&field.as_str()))
+    {
+        Some(unknown) => Err(format!(
+            "Unknown field '{}' (expected any of: {})",
+            unknown,
+            ENDPOINT_DETAIL_FIELDS.join(", ")
+        )),
+        None => Ok(fields),
+    }
+}
+
+/// An endpoint's details as a bulk API object: limited to `fields` when given (the name
+/// is always kept) and carrying the endpoint id
+fn bulk_endpoint_object(
+    endpoint_id: i64,
+    details: &EndpointDetailsResponse,
+    fields: Option<&[String]>,
+) -> serde_json::Value {
+    let mut value = serde_json::to_value(details).unwrap_or_default();
+    if let Some(object) = value.as_object_mut() {
+        if let Some(fields) = fields {
+            object.retain(|key, _| key == "endpoint_name" || fields.contains(key));
+        }
+        object.insert("id".to_string(), endpoint_id.into());
+    }
+    value
+}
+
+/// Every endpoint's details in one call, ordered by name and paged, so integrations don't
+/// have to fetch `/api/endpoint/{name}/details` once per device. Each object carries the
+/// same fields as that route plus the endpoint id.
+#[get("/api/v1/endpoints")]
+pub async fn get_endpoints_v1(query: Query<BulkEndpointsQuery>) -> impl Responder {
+    let internal_minutes = query.scan_interval.unwrap_or(525600);
+    let limit = query
+        .limit
+        .unwrap_or(BULK_ENDPOINTS_MAX_LIMIT)
+        .clamp(1, BULK_ENDPOINTS_MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    let fields = match query.fields.as_deref().map(parse_detail_fields).transpose() {
+        Ok(fields) => fields,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+
+    let mut summaries = load_endpoint_summaries(None).await;
+    summaries.sort_by_key(|summary| summary.name.to_lowercase());
+    let total = summaries.len();
+    let page: Vec<(i64, String)> = summaries
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|summary| (summary.endpoint_id, summary.name))
+        .collect();
+
+    let result = tokio::task::spawn_blocking(move || {
+        page.into_iter()
+            .map(|(endpoint_id, name)| {
+                let details = get_endpoint_details_blocking(name, internal_minutes);
+                bulk_endpoint_object(endpoint_id, &details, fields.as_deref())
+            })
+            .collect::<Vec<_>>()
+    })
+    .await;
+
+    match result {
+        Ok(endpoints) => HttpResponse::Ok().json(serde_json::json!({
+            "version": 1,
+            "total": total,
+            "offset": offset,
+            "limit": limit,
+            "endpoints": endpoints,
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch endpoints"
+        })),
     }
 }
 
@@ -812,6 +1177,111 @@ pub async fn get_all_protocols_api(query: actix_web::web::Query<NodeQuery>) -> i
     HttpResponse::Ok().json(AllProtocolsResponse { protocols })
 }
 
+#[derive(Deserialize)]
+pub struct BroadcastTrafficQuery {
+    scan_interval: Option<u64>,
+    /// Hide discovery/announcement chatter (mDNS, SSDP, LLMNR, NetBIOS, ARP, ...)
+    exclude_noise: Option<bool>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct BroadcastTrafficResponse {
+    talkers: Vec<BroadcastTalker>,
+}
+
+/// Senders of broadcast/multicast traffic, busiest first
+#[get("/api/traffic/broadcast")]
+pub async fn get_broadcast_traffic(query: Query<BroadcastTrafficQuery>) -> impl Responder {
+    let internal_minutes = query.scan_interval.unwrap_or(60) as i64;
+    let exclude_noise = query.exclude_noise.unwrap_or(false);
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        let since = chrono::Utc::now().timestamp() - internal_minutes * 60;
+        get_broadcast_talkers(&conn, since, exclude_noise, limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(talkers)) => HttpResponse::Ok().json(BroadcastTrafficResponse { talkers }),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch broadcast traffic"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WirelessStationsQuery {
+    scan_interval: Option<u64>,
+    /// "client", "access_point" or "unknown"
+    role: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct WirelessStationsResponse {
+    stations: Vec<WirelessStation>,
+}
+
+/// Wi-Fi stations heard on monitor-mode interfaces, strongest signal first
+#[get("/api/wireless/stations")]
+pub async fn get_wireless_stations_api(query: Query<WirelessStationsQuery>) -> impl Responder {
+    let internal_minutes = query.scan_interval.unwrap_or(60) as i64;
+    let role = query.role.clone().filter(|role| !role.is_empty());
+    let limit = query.limit.unwrap_or(200).clamp(1, 1000);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        let since = chrono::Utc::now().timestamp() - internal_minutes * 60;
+        get_wireless_stations(&conn, since, role.as_deref(), limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(stations)) => HttpResponse::Ok().json(WirelessStationsResponse { stations }),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch wireless stations"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuthEventsQuery {
+    scan_interval: Option<u64>,
+    /// Only events for this station or access point
+    mac: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct AuthEventsResponse {
+    events: Vec<AuthEventRow>,
+}
+
+/// EAPOL handshake steps and deauthentications, newest first
+#[get("/api/wireless/auth-events")]
+pub async fn get_auth_events_api(query: Query<AuthEventsQuery>) -> impl Responder {
+    let internal_minutes = query.scan_interval.unwrap_or(60) as i64;
+    let mac = query.mac.clone().filter(|mac| !mac.is_empty());
+    let limit = query.limit.unwrap_or(200).clamp(1, 1000);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        let since = chrono::Utc::now().timestamp() - internal_minutes * 60;
+        get_auth_events(&conn, since, mac.as_deref(), limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(events)) => HttpResponse::Ok().json(AuthEventsResponse { events }),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch authentication events"
+        })),
+    }
+}
+
 // ============================================================================
 // Endpoint Management API Endpoints
 // ============================================================================
@@ -829,7 +1299,10 @@ pub struct ClassifyResponse {
 }
 
 #[post("/api/endpoint/classify")]
-pub async fn set_endpoint_type(body: Json<ClassifyRequest>) -> impl Responder {
+pub async fn set_endpoint_type(
+    _admin: RequireAdmin,
+    body: Json<ClassifyRequest>,
+) -> impl Responder {
     let conn = new_connection();
 
     // If device_type is "auto" or empty, clear the manual override
@@ -842,6 +1315,13 @@ pub async fn set_endpoint_type(body: Json<ClassifyRequest>) -> impl Responder {
     match EndPoint::set_manual_device_type(&conn, &body.endpoint_name, device_type) {
         Ok(rows_updated) => {
             if rows_updated > 0 {
+                let _ = record_classification_feedback(
+                    &conn,
+                    &body.endpoint_name,
+                    FeedbackKind::DeviceType,
+                    device_type,
+                    chrono::Utc::now().timestamp(),
+                );
                 insert_notification(
                     &conn,
                     "endpoint_reclassified",
@@ -894,7 +1374,7 @@ pub struct RenameResponse {
 }
 
 #[post("/api/endpoint/rename")]
-pub async fn rename_endpoint(body: Json<RenameRequest>) -> impl Responder {
+pub async fn rename_endpoint(_admin: RequireAdmin, body: Json<RenameRequest>) -> impl Responder {
     let conn = new_connection();
 
     // If custom_name is empty string, treat as None (clear the custom name)
@@ -966,7 +1446,10 @@ pub struct SetModelResponse {
 }
 
 #[post("/api/endpoint/model")]
-pub async fn set_endpoint_model(body: Json<SetModelRequest>) -> impl Responder {
+pub async fn set_endpoint_model(
+    _admin: RequireAdmin,
+    body: Json<SetModelRequest>,
+) -> impl Responder {
     let conn = new_connection();
 
     // If model is "auto" or empty, clear the custom model
@@ -979,6 +1462,13 @@ pub async fn set_endpoint_model(body: Json<SetModelRequest>) -> impl Responder {
     match EndPoint::set_custom_model(&conn, &body.endpoint_name, model) {
         Ok(rows_updated) => {
             if rows_updated > 0 {
+                let _ = record_classification_feedback(
+                    &conn,
+                    &body.endpoint_name,
+                    FeedbackKind::Model,
+                    model,
+                    chrono::Utc::now().timestamp(),
+                );
                 let (event, title) = if let Some(m) = model {
                     (
                         "model_changed",
@@ -1028,7 +1518,10 @@ pub struct SetVendorResponse {
 }
 
 #[post("/api/endpoint/vendor")]
-pub async fn set_endpoint_vendor(body: Json<SetVendorRequest>) -> impl Responder {
+pub async fn set_endpoint_vendor(
+    _admin: RequireAdmin,
+    body: Json<SetVendorRequest>,
+) -> impl Responder {
     let conn = new_connection();
 
     // If vendor is "auto" or empty, clear the custom vendor
@@ -1041,6 +1534,13 @@ pub async fn set_endpoint_vendor(body: Json<SetVendorRequest>) -> impl Responder
     match EndPoint::set_custom_vendor(&conn, &body.endpoint_name, vendor) {
         Ok(rows_updated) => {
             if rows_updated > 0 {
+                let _ = record_classification_feedback(
+                    &conn,
+                    &body.endpoint_name,
+                    FeedbackKind::Vendor,
+                    vendor,
+                    chrono::Utc::now().timestamp(),
+                );
                 let (event, title) = if let Some(v) = vendor {
                     (
                         "vendor_changed",
@@ -1077,2264 +1577,7272 @@ pub async fn set_endpoint_vendor(body: Json<SetVendorRequest>) -> impl Responder
     }
 }
 
-#[derive(Deserialize)]
-pub struct ProbeEndpointRequest {
-    endpoint_name: String,
+/// Apply one change set (device type, vendor, model, tags, trust) to many endpoints at
+/// once. Either every endpoint is changed or, when any is unknown, none is.
+#[post("/api/endpoints/bulk")]
+pub async fn bulk_edit_endpoints(
+    _admin: RequireAdmin,
+    body: Json<BulkEndpointEdit>,
+) -> impl Responder {
+    let edit = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        edit.validate()?;
+        apply_bulk_edit(&new_connection(), &edit, chrono::Utc::now().timestamp())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(outcome)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("{} endpoints edited: {}", outcome.updated, outcome.summary),
+            "updated": outcome.updated,
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
 }
 
 #[derive(Serialize)]
-pub struct ProbeEndpointResponse {
+pub struct AppearanceResponse {
+    /// Icon and color of each built-in device type
+    types: HashMap<String, TypeAppearance>,
+    /// Endpoints with their own icon or color, by lowercase name
+    endpoints: HashMap<String, EndpointAppearance>,
+}
+
+/// Icons and colors used for device types and individual endpoints
+#[get("/api/appearance")]
+pub async fn get_appearance() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result()?;
+        Ok::<_, rusqlite::Error>(AppearanceResponse {
+            types: get_type_appearances(&conn)?,
+            endpoints: get_endpoint_appearances(&conn)?,
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(response)) => HttpResponse::Ok().json(response),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load appearance"
+        })),
+    }
+}
+
+#[derive(Serialize)]
+pub struct SetAppearanceResponse {
     success: bool,
     message: String,
-    snmp_info: Option<SnmpProbeInfo>,
-    netbios_name: Option<String>,
 }
 
-#[derive(Serialize)]
-pub struct SnmpProbeInfo {
-    sys_descr: Option<String>,
-    sys_name: Option<String>,
-    sys_location: Option<String>,
+impl SetAppearanceResponse {
+    fn error(message: String) -> Self {
+        SetAppearanceResponse {
+            success: false,
+            message,
+        }
+    }
 }
 
-/// Probe an endpoint for device information (SNMP, NetBIOS)
-#[post("/api/endpoint/probe")]
-pub async fn probe_endpoint(body: Json<ProbeEndpointRequest>) -> impl Responder {
-    use crate::scanner::netbios::NetBiosScanner;
-    use crate::scanner::snmp::SnmpScanner;
+/// Validate an icon and color from a request; missing or empty values are unset
+fn parse_appearance(
+    icon: Option<&str>,
+    color: Option<&str>,
+) -> Result<(Option<String>, Option<String>), String> {
+    let icon = match icon.map(str::trim).filter(|i| !i.is_empty()) {
+        Some(icon) => Some(parse_icon(icon).ok_or("Icon must be at most 16 characters")?),
+        None => None,
+    };
+    let color = match color.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(color) => Some(
+            parse_color(color)
+                .ok_or_else(|| format!("Invalid color '{}', expected #rrggbb", color))?,
+        ),
+        None => None,
+    };
+    Ok((icon, color))
+}
 
-    let conn = new_connection();
+/// A device type's icon and color; leaving both empty restores the default
+#[derive(Deserialize)]
+pub struct SetTypeAppearanceRequest {
+    device_type: String,
+    icon: Option<String>,
+    color: Option<String>,
+}
 
-    // Get IPs for this endpoint
-    let ips: Vec<String> = conn
-        .prepare(&format!(
-            "SELECT DISTINCT ea.ip FROM endpoints e
-             JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
-             WHERE {} = ?1 COLLATE NOCASE
-             AND ea.ip IS NOT NULL AND ea.ip != ''",
-            DISPLAY_NAME_SQL
-        ))
-        .and_then(|mut stmt| {
-            stmt.query_map([&body.endpoint_name], |row| row.get(0))
-                .map(|rows| rows.filter_map(|r| r.ok()).collect())
-        })
-        .unwrap_or_default();
-
-    if ips.is_empty() {
-        return HttpResponse::NotFound().json(ProbeEndpointResponse {
-            success: false,
-            message: format!("No IPs found for endpoint '{}'", body.endpoint_name),
-            snmp_info: None,
-            netbios_name: None,
-        });
+#[post("/api/appearance/type")]
+pub async fn update_device_type_appearance(
+    _admin: RequireAdmin,
+    body: Json<SetTypeAppearanceRequest>,
+) -> impl Responder {
+    if !is_known_device_type(&body.device_type) {
+        return HttpResponse::BadRequest().json(SetAppearanceResponse::error(format!(
+            "Unknown device type '{}'",
+            body.device_type
+        )));
     }
+    let (icon, color) = match parse_appearance(body.icon.as_deref(), body.color.as_deref()) {
+        Ok(appearance) => appearance,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(SetAppearanceResponse::error(message));
+        }
+    };
 
-    // Get endpoint ID for saving results
-    let endpoint_id: Option<i64> = conn
-        .query_row(
-            &format!(
-                "SELECT e.id FROM endpoints e WHERE {} = ?1 COLLATE NOCASE LIMIT 1",
-                DISPLAY_NAME_SQL
-            ),
-            [&body.endpoint_name],
-            |row| row.get(0),
-        )
-        .ok();
+    let conn = new_connection();
+    match set_type_appearance(&conn, &body.device_type, icon.as_deref(), color.as_deref()) {
+        Ok(()) => HttpResponse::Ok().json(SetAppearanceResponse {
+            success: true,
+            message: format!("Appearance updated for {}", body.device_type),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(SetAppearanceResponse::error(format!(
+            "Database error: {}",
+            e
+        ))),
+    }
+}
 
-    let mut snmp_info = None;
-    let mut netbios_name = None;
+/// An endpoint's own icon and color; leaving both empty reverts to its type's
+#[derive(Deserialize)]
+pub struct SetEndpointAppearanceRequest {
+    endpoint_name: String,
+    icon: Option<String>,
+    color: Option<String>,
+}
 
-    // Probe each IP
-    for ip_str in &ips {
-        if let Ok(ip) = ip_str.parse::<std::net::Ipv4Addr>() {
-            // SNMP probe
-            if snmp_info.is_none() {
-                let snmp_scanner = SnmpScanner::new().with_timeout(3000);
-                if let Some(result) = snmp_scanner.query_ip(ip) {
-                    // Save to database
-                    if let Some(eid) = endpoint_id {
-                        let details = serde_json::json!({
-                            "sys_descr": result.sys_descr,
-                            "sys_object_id": result.sys_object_id,
-                            "sys_name": result.sys_name,
-                            "sys_location": result.sys_location,
-                            "community": result.community,
-                        });
-                        let _ = insert_scan_result(
-                            &conn,
-                            eid,
-                            "snmp",
-                            None,
-                            Some(&details.to_string()),
-                        );
+#[post("/api/endpoint/appearance")]
+pub async fn update_endpoint_appearance(
+    _admin: RequireAdmin,
+    body: Json<SetEndpointAppearanceRequest>,
+) -> impl Responder {
+    let (icon, color) = match parse_appearance(body.icon.as_deref(), body.color.as_deref()) {
+        Ok(appearance) => appearance,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(SetAppearanceResponse::error(message));
+        }
+    };
 
-                        // Extract and save vendor/model from sysDescr
-                        if let Some(ref sys_descr) = result.sys_descr {
-                            let (vendor, model) = parse_snmp_sys_descr(sys_descr);
-                            if let Some(v) = &vendor {
-                                match conn.execute(
-                                    "UPDATE endpoints SET snmp_vendor = ?1 WHERE id = ?2 AND (snmp_vendor IS NULL OR snmp_vendor = '')",
-                                    params![v, eid],
-                                ) {
-                                    Ok(rows) if rows > 0 => {
-                                        insert_notification_with_endpoint_id(
-                                            &conn, "vendor_identified",
-                                            &format!("Vendor identified: {}", v),
-                                            None, None, Some(eid),
-                                        );
-                                    }
-                                    Err(e) => eprintln!("Failed to save SNMP vendor: {}", e),
-                                    _ => {}
-                                }
-                            }
-                            if let Some(m) = &model {
-                                match conn.execute(
-                                    "UPDATE endpoints SET snmp_model = ?1 WHERE id = ?2 AND (snmp_model IS NULL OR snmp_model = '')",
-                                    params![m, eid],
-                                ) {
-                                    Ok(rows) if rows > 0 => {
-                                        insert_notification_with_endpoint_id(
-                                            &conn, "model_identified",
-                                            &format!("Device model identified: {}", m),
-                                            None, None, Some(eid),
-                                        );
-                                    }
-                                    Err(e) => eprintln!("Failed to save SNMP model: {}", e),
-                                    _ => {}
-                                }
-                            }
-                        }
+    let conn = new_connection();
+    let Some(endpoint_id) = find_endpoint_id_by_name(&conn, &body.endpoint_name) else {
+        return HttpResponse::NotFound().json(SetAppearanceResponse::error(format!(
+            "Endpoint '{}' not found",
+            body.endpoint_name
+        )));
+    };
+    match set_endpoint_appearance(&conn, endpoint_id, icon.as_deref(), color.as_deref()) {
+        Ok(()) => HttpResponse::Ok().json(SetAppearanceResponse {
+            success: true,
+            message: format!("Appearance updated for {}", body.endpoint_name),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(SetAppearanceResponse::error(format!(
+            "Database error: {}",
+            e
+        ))),
+    }
+}
 
-                        // Update endpoint name from sysName if current name is just an IP
-                        if let Some(ref sys_name) = result.sys_name
-                            && !sys_name.is_empty()
-                        {
-                            let _ = conn.execute(
-                                "UPDATE endpoints SET name = ?1 WHERE id = ?2 AND (name = ?3 OR name GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*')",
-                                params![sys_name, eid, ip_str],
-                            );
-                        }
-                    }
+/// An endpoint's tags, replacing the ones it had, and optionally its trust
+#[derive(Deserialize)]
+pub struct SetEndpointTagsRequest {
+    endpoint_name: String,
+    tags: Vec<String>,
+    trusted: Option<bool>,
+}
 
-                    snmp_info = Some(SnmpProbeInfo {
-                        sys_descr: result.sys_descr,
-                        sys_name: result.sys_name,
-                        sys_location: result.sys_location,
-                    });
-                }
-            }
+#[post("/api/endpoint/tags")]
+pub async fn update_endpoint_tags(
+    _admin: RequireAdmin,
+    body: Json<SetEndpointTagsRequest>,
+) -> impl Responder {
+    let tags = match parse_tags(&body.tags) {
+        Ok(tags) => tags,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "success": false,
+                "message": message
+            }));
+        }
+    };
 
-            // NetBIOS probe
-            if netbios_name.is_none() {
-                let netbios_scanner = NetBiosScanner::new().with_timeout(2000);
-                if let Some(result) = netbios_scanner.query_ip(ip) {
-                    if let Some(eid) = endpoint_id {
-                        // Save hostname
-                        let _ = conn.execute(
-                            "INSERT OR IGNORE INTO endpoint_attributes (endpoint_id, ip, hostname)
-                             VALUES (?1, ?2, ?3)
-                             ON CONFLICT(endpoint_id, ip, mac) DO UPDATE SET hostname = ?3
-                             WHERE hostname IS NULL OR hostname = ''",
-                            params![eid, ip_str, result.netbios_name],
-                        );
-                    }
-                    netbios_name = Some(result.netbios_name);
-                }
-            }
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        let Some(endpoint_id) = find_endpoint_id_by_name(&conn, &body.endpoint_name) else {
+            return Ok(None);
+        };
+        let now = chrono::Utc::now().timestamp();
+        set_endpoint_tags(&conn, endpoint_id, &tags, now)?;
+        if let Some(trusted) = body.trusted {
+            set_endpoint_trusted(&conn, endpoint_id, trusted, now)?;
         }
+        Ok::<_, rusqlite::Error>(Some(body.endpoint_name.clone()))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(name))) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("Tags updated for {}", name)
+        })),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
     }
+}
 
-    let found_something = snmp_info.is_some() || netbios_name.is_some();
-    HttpResponse::Ok().json(ProbeEndpointResponse {
-        success: found_something,
-        message: if found_something {
-            "Probe completed".to_string()
-        } else {
-            "No device info discovered".to_string()
-        },
-        snmp_info,
-        netbios_name,
-    })
+/// Every tag in use and how many endpoints carry it
+#[get("/api/tags")]
+pub async fn list_tags() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| get_tag_counts(&new_connection_result()?)).await;
+
+    match result {
+        Ok(Ok(tags)) => HttpResponse::Ok().json(serde_json::json!({ "tags": tags })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load tags"
+        })),
+    }
 }
 
-#[derive(Deserialize)]
-pub struct DeleteEndpointRequest {
-    endpoint_name: String,
+/// Who saved views belong to. Requests without an identity share one set of views.
+fn view_owner(req: &HttpRequest) -> String {
+    request_identity(req.headers(), req.peer_addr()).unwrap_or_default()
 }
 
 #[derive(Serialize)]
-pub struct DeleteEndpointResponse {
+pub struct SavedViewsResponse {
+    views: Vec<SavedView>,
+}
+
+/// The caller's saved endpoint table views
+#[get("/api/views")]
+pub async fn list_saved_views(req: HttpRequest) -> impl Responder {
+    let owner = view_owner(&req);
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        get_saved_views(&conn, &owner)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(views)) => HttpResponse::Ok().json(SavedViewsResponse { views }),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load saved views"
+        })),
+    }
+}
+
+#[derive(Serialize)]
+pub struct SavedViewResponse {
     success: bool,
     message: String,
+    id: Option<i64>,
 }
 
-/// Delete an endpoint and all associated data (communications, attributes, scan results)
-#[post("/api/endpoint/delete")]
-pub async fn delete_endpoint(body: Json<DeleteEndpointRequest>) -> impl Responder {
-    let conn = new_connection();
+impl SavedViewResponse {
+    fn error(message: String) -> Self {
+        SavedViewResponse {
+            success: false,
+            message,
+            id: None,
+        }
+    }
+}
 
-    // First, find the endpoint ID(s) matching the name
-    let endpoint_ids: Vec<i64> = match conn.prepare(&format!(
-        "SELECT DISTINCT e.id FROM endpoints e
-         LEFT JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
-         WHERE {} = ?1 COLLATE NOCASE
-            OR LOWER(ea.hostname) = LOWER(?1)
-            OR LOWER(ea.ip) = LOWER(?1)",
-        DISPLAY_NAME_SQL
-    )) {
-        Ok(mut stmt) => match stmt.query_map([&body.endpoint_name], |row| row.get(0)) {
-            Ok(rows) => rows.filter_map(|r| r.ok()).collect(),
-            Err(e) => {
-                eprintln!("Error querying for endpoint to delete: {}", e);
-                return HttpResponse::InternalServerError().json(DeleteEndpointResponse {
-                    success: false,
-                    message: format!("Database query error: {}", e),
-                });
-            }
-        },
-        Err(e) => {
-            eprintln!("Error preparing delete query: {}", e);
-            return HttpResponse::InternalServerError().json(DeleteEndpointResponse {
-                success: false,
-                message: format!("Database error: {}", e),
-            });
+/// Save the index page query as a named view. With an `id` that view is renamed and
+/// updated; otherwise a view of the same name is replaced.
+#[derive(Deserialize)]
+pub struct SaveViewRequest {
+    id: Option<i64>,
+    name: String,
+    query: String,
+}
+
+#[post("/api/views/save")]
+pub async fn save_saved_view(req: HttpRequest, body: Json<SaveViewRequest>) -> impl Responder {
+    let Some(name) = parse_view_name(&body.name) else {
+        return HttpResponse::BadRequest().json(SavedViewResponse::error(
+            "View name must be 1 to 64 characters".to_string(),
+        ));
+    };
+    let owner = view_owner(&req);
+    let query = normalize_view_query(&body.query);
+    let now = chrono::Utc::now().timestamp();
+
+    let conn = new_connection();
+    let result = match body.id {
+        Some(id) => {
+            update_view(&conn, &owner, id, &name, &query, now).map(|updated| updated.then_some(id))
         }
+        None => save_view(&conn, &owner, &name, &query, now).map(Some),
     };
+    match result {
+        Ok(Some(id)) => HttpResponse::Ok().json(SavedViewResponse {
+            success: true,
+            message: format!("Saved view '{}'", name),
+            id: Some(id),
+        }),
+        Ok(None) => {
+            HttpResponse::NotFound().json(SavedViewResponse::error("View not found".to_string()))
+        }
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            HttpResponse::Conflict().json(SavedViewResponse::error(format!(
+                "A view named '{}' already exists",
+                name
+            )))
+        }
+        Err(e) => HttpResponse::InternalServerError()
+            .json(SavedViewResponse::error(format!("Database error: {}", e))),
+    }
+}
 
-    if endpoint_ids.is_empty() {
-        return HttpResponse::NotFound().json(DeleteEndpointResponse {
-            success: false,
-            message: format!("Endpoint '{}' not found", body.endpoint_name),
-        });
+#[derive(Deserialize)]
+pub struct DeleteViewRequest {
+    id: i64,
+}
+
+#[post("/api/views/delete")]
+pub async fn delete_saved_view(req: HttpRequest, body: Json<DeleteViewRequest>) -> impl Responder {
+    let owner = view_owner(&req);
+    let conn = new_connection();
+    match delete_view(&conn, &owner, body.id) {
+        Ok(true) => HttpResponse::Ok().json(SavedViewResponse {
+            success: true,
+            message: "View deleted".to_string(),
+            id: Some(body.id),
+        }),
+        Ok(false) => {
+            HttpResponse::NotFound().json(SavedViewResponse::error("View not found".to_string()))
+        }
+        Err(e) => HttpResponse::InternalServerError()
+            .json(SavedViewResponse::error(format!("Database error: {}", e))),
     }
+}
 
-    // Delete in order to respect foreign key constraints
-    let mut updated_comms = 0;
-    let mut deleted_attrs = 0;
-    let mut deleted_scans = 0;
-    let mut deleted_endpoints = 0;
+/// The view `/` lands on; a null id goes back to the unfiltered table
+#[derive(Deserialize)]
+pub struct DefaultViewRequest {
+    id: Option<i64>,
+}
 
-    for endpoint_id in &endpoint_ids {
-        // Nullify this endpoint's ID in communications instead of deleting them
-        // This preserves communication history for other endpoints
-        updated_comms += conn
-            .execute(
-                "UPDATE communications SET src_endpoint_id = NULL WHERE src_endpoint_id = ?1",
-                params![endpoint_id],
-            )
-            .unwrap_or(0);
-        updated_comms += conn
-            .execute(
-                "UPDATE communications SET dst_endpoint_id = NULL WHERE dst_endpoint_id = ?1",
-                params![endpoint_id],
-            )
-            .unwrap_or(0);
+#[post("/api/views/default")]
+pub async fn set_default_saved_view(
+    req: HttpRequest,
+    body: Json<DefaultViewRequest>,
+) -> impl Responder {
+    let owner = view_owner(&req);
+    let conn = new_connection();
+    match set_default_view(&conn, &owner, body.id) {
+        Ok(true) => HttpResponse::Ok().json(SavedViewResponse {
+            success: true,
+            message: if body.id.is_some() {
+                "Default view set".to_string()
+            } else {
+                "Default view cleared".to_string()
+            },
+            id: body.id,
+        }),
+        Ok(false) => {
+            HttpResponse::NotFound().json(SavedViewResponse::error("View not found".to_string()))
+        }
+        Err(e) => HttpResponse::InternalServerError()
+            .json(SavedViewResponse::error(format!("Database error: {}", e))),
+    }
+}
 
-        // Delete scan results
-        deleted_scans += conn
-            .execute(
-                "DELETE FROM scan_results WHERE endpoint_id = ?1",
-                params![endpoint_id],
-            )
-            .unwrap_or(0);
+#[derive(Serialize)]
+pub struct CurrentUserResponse {
+    /// None for anonymous requests
+    identity: Option<String>,
+    role: Role,
+    preferences: HashMap<String, String>,
+    /// False while everyone is an admin because no users have been added
+    users_configured: bool,
+}
 
-        // Delete endpoint attributes
-        deleted_attrs += conn
-            .execute(
-                "DELETE FROM endpoint_attributes WHERE endpoint_id = ?1",
-                params![endpoint_id],
-            )
-            .unwrap_or(0);
+/// Who the caller is, their role and their preferences
+#[get("/api/me")]
+pub async fn get_current_user(caller: Caller) -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        let owner = caller.identity.as_deref().unwrap_or_default();
+        Ok::<_, rusqlite::Error>(CurrentUserResponse {
+            preferences: get_user_preferences(&conn, owner)?,
+            users_configured: !get_users(&conn)?.is_empty(),
+            identity: caller.identity,
+            role: caller.role,
+        })
+    })
+    .await;
 
-        // Delete open ports
-        conn.execute(
-            "DELETE FROM open_ports WHERE endpoint_id = ?1",
-            params![endpoint_id],
-        )
-        .unwrap_or(0);
+    match result {
+        Ok(Ok(response)) => HttpResponse::Ok().json(response),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load user"
+        })),
+    }
+}
 
-        // Delete scan results
-        conn.execute(
-            "DELETE FROM scan_results WHERE endpoint_id = ?1",
-            params![endpoint_id],
-        )
-        .unwrap_or(0);
+/// Preferences to change; an empty value restores the default
+#[derive(Deserialize)]
+pub struct SetPreferencesRequest {
+    preferences: HashMap<String, String>,
+}
 
-        // Delete the endpoint itself
-        deleted_endpoints += conn
-            .execute("DELETE FROM endpoints WHERE id = ?1", params![endpoint_id])
-            .unwrap_or(0);
+#[post("/api/me/preferences")]
+pub async fn set_current_user_preferences(
+    req: HttpRequest,
+    body: Json<SetPreferencesRequest>,
+) -> impl Responder {
+    let mut changes = Vec::new();
+    for (key, value) in &body.preferences {
+        if !is_preference_key(key) {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "success": false,
+                "message": format!("Unknown preference '{}'", key)
+            }));
+        }
+        if value.trim().is_empty() {
+            changes.push((key.clone(), None));
+            continue;
+        }
+        match parse_preference(key, value) {
+            Some(value) => changes.push((key.clone(), Some(value))),
+            None => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "success": false,
+                    "message": format!("Invalid value '{}' for {}", value, key)
+                }));
+            }
+        }
     }
 
-    insert_notification(
-        &conn,
-        "endpoint_deleted",
-        &format!("Endpoint '{}' deleted", body.endpoint_name),
-        Some(&format!(
-            "{} endpoint(s), {} attribute(s), {} scan result(s) removed",
-            deleted_endpoints, deleted_attrs, deleted_scans
-        )),
-        Some(&body.endpoint_name),
-    );
+    let owner = view_owner(&req);
+    let conn = new_connection();
+    for (key, value) in &changes {
+        if let Err(e) = set_user_preference(&conn, &owner, key, value.as_deref()) {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "success": false,
+                "message": format!("Database error: {}", e)
+            }));
+        }
+    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Preferences saved"
+    }))
+}
 
-    HttpResponse::Ok().json(DeleteEndpointResponse {
-        success: true,
-        message: format!(
-            "Deleted endpoint '{}': {} endpoint(s), {} attribute(s), {} scan result(s) (preserved {} communication records)",
-            body.endpoint_name, deleted_endpoints, deleted_attrs, deleted_scans, updated_comms
-        ),
+#[derive(Serialize)]
+pub struct UsersResponse {
+    users: Vec<User>,
+}
+
+/// Everyone with a role. Admin only, like the other /api/users calls.
+#[get("/api/users")]
+pub async fn list_users(_admin: RequireAdmin) -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result()?;
+        get_users(&conn)
     })
+    .await;
+
+    match result {
+        Ok(Ok(users)) => HttpResponse::Ok().json(UsersResponse { users }),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load users"
+        })),
+    }
 }
 
+/// Add a user, or change their role. The name is the identity their requests carry:
+/// the user name from a trusted authenticating proxy, or the user an API token is then
+/// issued to (see `issue_user_token`).
 #[derive(Deserialize)]
-pub struct MergeEndpointsRequest {
-    /// The endpoint to keep (target) - can be name, custom_name, hostname, or IP
-    target: String,
-    /// The endpoint to merge and delete (source) - can be name, custom_name, hostname, or IP
-    source: String,
+pub struct SaveUserRequest {
+    name: String,
+    role: String,
 }
 
-#[derive(Serialize)]
-pub struct MergeEndpointsResponse {
-    success: bool,
-    message: String,
-}
+#[post("/api/users/save")]
+pub async fn save_user(_admin: RequireAdmin, body: Json<SaveUserRequest>) -> impl Responder {
+    let name = body.name.trim();
+    if name.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": "User name is required"
+        }));
+    }
+    let Some(role) = Role::parse(&body.role) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": format!("Unknown role '{}', expected admin or viewer", body.role)
+        }));
+    };
 
-/// Merge two endpoints into one, keeping the target and deleting the source
-/// All communications, attributes, scan results, and ports from source are moved to target
-#[post("/api/endpoint/merge")]
-pub async fn merge_endpoints(body: Json<MergeEndpointsRequest>) -> impl Responder {
     let conn = new_connection();
-
-    // Find the target endpoint ID
-    let target_id: Option<i64> = match conn.prepare(&format!(
-        "SELECT DISTINCT e.id FROM endpoints e
-         LEFT JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
-         WHERE {} = ?1 COLLATE NOCASE
-            OR LOWER(ea.hostname) = LOWER(?1)
-            OR LOWER(ea.ip) = LOWER(?1)
-         LIMIT 1",
-        DISPLAY_NAME_SQL
-    )) {
-        Ok(mut stmt) => stmt.query_row([&body.target], |row| row.get(0)).ok(),
+    match admins_remain_after(&conn, name, Some(role)) {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "success": false,
+                "message": "There must be at least one admin"
+            }));
+        }
         Err(e) => {
-            eprintln!("Error preparing target query: {}", e);
-            return HttpResponse::InternalServerError().json(MergeEndpointsResponse {
-                success: false,
-                message: format!("Database error: {}", e),
-            });
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "success": false,
+                "message": format!("Database error: {}", e)
+            }));
         }
-    };
+    }
+    match set_user(&conn, name, role, chrono::Utc::now().timestamp()) {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("{} is now {}", name, role.as_str())
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
+        })),
+    }
+}
 
-    let target_id = match target_id {
-        Some(id) => id,
-        None => {
-            return HttpResponse::NotFound().json(MergeEndpointsResponse {
-                success: false,
-                message: format!("Target endpoint '{}' not found", body.target),
-            });
-        }
-    };
+#[derive(Deserialize)]
+pub struct UserNameRequest {
+    name: String,
+}
 
-    // Find the source endpoint ID
-    let source_id: Option<i64> = match conn.prepare(&format!(
-        "SELECT DISTINCT e.id FROM endpoints e
-         LEFT JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
-         WHERE {} = ?1 COLLATE NOCASE
-            OR LOWER(ea.hostname) = LOWER(?1)
-            OR LOWER(ea.ip) = LOWER(?1)
-         LIMIT 1",
-        DISPLAY_NAME_SQL
-    )) {
-        Ok(mut stmt) => stmt.query_row([&body.source], |row| row.get(0)).ok(),
+#[post("/api/users/delete")]
+pub async fn remove_user(_admin: RequireAdmin, body: Json<UserNameRequest>) -> impl Responder {
+    let conn = new_connection();
+    match admins_remain_after(&conn, &body.name, None) {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "success": false,
+                "message": "There must be at least one admin"
+            }));
+        }
         Err(e) => {
-            eprintln!("Error preparing source query: {}", e);
-            return HttpResponse::InternalServerError().json(MergeEndpointsResponse {
-                success: false,
-                message: format!("Database error: {}", e),
-            });
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "success": false,
+                "message": format!("Database error: {}", e)
+            }));
         }
-    };
+    }
+    match delete_user(&conn, &body.name) {
+        Ok(true) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("Removed {}", body.name)
+        })),
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": format!("User '{}' not found", body.name)
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
+        })),
+    }
+}
 
-    let source_id = match source_id {
-        Some(id) => id,
-        None => {
-            return HttpResponse::NotFound().json(MergeEndpointsResponse {
-                success: false,
-                message: format!("Source endpoint '{}' not found", body.source),
-            });
-        }
-    };
+#[derive(Serialize)]
+pub struct IssueTokenResponse {
+    success: bool,
+    message: String,
+    /// Shown once; only its hash is stored
+    token: Option<String>,
+}
 
-    // Check they're not the same endpoint
-    if target_id == source_id {
-        return HttpResponse::BadRequest().json(MergeEndpointsResponse {
+/// Issue a user a new API token, replacing any earlier one. They send it as
+/// `Authorization: Bearer <token>`.
+#[post("/api/users/token")]
+pub async fn issue_user_token(_admin: RequireAdmin, body: Json<UserNameRequest>) -> impl Responder {
+    let token = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let conn = new_connection();
+    match set_user_token(&conn, &body.name, &token) {
+        Ok(true) => HttpResponse::Ok().json(IssueTokenResponse {
+            success: true,
+            message: format!("New token for {}; it won't be shown again", body.name),
+            token: Some(token),
+        }),
+        Ok(false) => HttpResponse::NotFound().json(IssueTokenResponse {
             success: false,
-            message: "Cannot merge an endpoint with itself".to_string(),
-        });
+            message: format!("User '{}' not found", body.name),
+            token: None,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(IssueTokenResponse {
+            success: false,
+            message: format!("Database error: {}", e),
+            token: None,
+        }),
     }
+}
 
-    // Perform the merge
-    let mut merged_comms = 0;
-    let mut merged_attrs = 0;
-    let mut merged_ports = 0;
-    let mut merged_scans = 0;
+/// Re-read the runtime device rules and OUI overrides now, rather than waiting for the
+/// file watcher. Endpoints are classified with the new rules from their next update.
+#[post("/api/classification/reload")]
+pub async fn reload_classification_rules(_admin: RequireAdmin) -> impl Responder {
+    match tokio::task::spawn_blocking(reload_runtime_rules).await {
+        Ok(Ok(summary)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Loaded {} hostname patterns and {} OUI overrides",
+                summary.hostname_patterns, summary.oui_overrides
+            ),
+            "summary": summary,
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e,
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Reload task failed",
+        })),
+    }
+}
 
-    // Merge communications
-    merged_comms += conn
-        .execute(
-            "UPDATE communications SET src_endpoint_id = ?1 WHERE src_endpoint_id = ?2",
-            params![target_id, source_id],
-        )
-        .unwrap_or(0);
-    merged_comms += conn
-        .execute(
-            "UPDATE communications SET dst_endpoint_id = ?1 WHERE dst_endpoint_id = ?2",
-            params![target_id, source_id],
-        )
-        .unwrap_or(0);
+/// Chip and module makers never shown as a device's vendor from its MAC alone: the
+/// compiled-in list plus any `component_vendors` in the runtime rules file's `[standalone]`
+#[get("/api/classification/component-vendors")]
+pub async fn get_component_vendors() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "component_vendors": component_vendors(),
+    }))
+}
 
-    // Merge endpoint attributes (INSERT OR IGNORE to skip duplicates)
-    merged_attrs += conn
-        .execute(
-            "INSERT OR IGNORE INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname, dhcp_client_id, dhcp_vendor_class)
-             SELECT created_at, ?1, mac, ip, hostname, dhcp_client_id, dhcp_vendor_class
-             FROM endpoint_attributes
-             WHERE endpoint_id = ?2",
-            params![target_id, source_id],
-        )
-        .unwrap_or(0);
+/// Run a hypothetical device through the characterization pipeline, returning the final
+/// vendor, model and device type along with every tier's own verdict
+#[post("/api/classification/evaluate")]
+pub async fn evaluate_classification(body: Json<DeviceSample>) -> impl Responder {
+    HttpResponse::Ok().json(evaluate(&body))
+}
 
-    // Delete source attributes after copying
-    conn.execute(
-        "DELETE FROM endpoint_attributes WHERE endpoint_id = ?1",
-        params![source_id],
-    )
-    .unwrap_or(0);
-
-    // Merge open ports (UPDATE OR IGNORE to skip duplicates)
-    merged_ports += conn
-        .execute(
-            "UPDATE OR IGNORE open_ports SET endpoint_id = ?1 WHERE endpoint_id = ?2",
-            params![target_id, source_id],
-        )
-        .unwrap_or(0);
-
-    // Delete any remaining source ports (duplicates)
-    conn.execute(
-        "DELETE FROM open_ports WHERE endpoint_id = ?1",
-        params![source_id],
-    )
-    .unwrap_or(0);
-
-    // Merge scan results
-    merged_scans += conn
-        .execute(
-            "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
-            params![target_id, source_id],
-        )
-        .unwrap_or(0);
-
-    // Copy over any useful metadata from source that target doesn't have
-    let _ = conn.execute(
-        "UPDATE endpoints SET
-            ssdp_model = COALESCE((SELECT ssdp_model FROM endpoints WHERE id = ?1), (SELECT ssdp_model FROM endpoints WHERE id = ?2)),
-            ssdp_friendly_name = COALESCE((SELECT ssdp_friendly_name FROM endpoints WHERE id = ?1), (SELECT ssdp_friendly_name FROM endpoints WHERE id = ?2)),
-            netbios_name = COALESCE((SELECT netbios_name FROM endpoints WHERE id = ?1), (SELECT netbios_name FROM endpoints WHERE id = ?2)),
-            auto_device_type = COALESCE((SELECT auto_device_type FROM endpoints WHERE id = ?1), (SELECT auto_device_type FROM endpoints WHERE id = ?2))
-         WHERE id = ?1",
-        params![target_id, source_id],
-    );
-
-    // Reassign notifications so they point to the surviving endpoint
-    let _ = conn.execute(
-        "UPDATE notifications SET endpoint_id = ?1 WHERE endpoint_id = ?2",
-        params![target_id, source_id],
-    );
-
-    // Delete the source endpoint
-    let deleted = conn
-        .execute("DELETE FROM endpoints WHERE id = ?1", params![source_id])
-        .unwrap_or(0);
-
-    if deleted > 0 {
-        insert_notification(
-            &conn,
-            "endpoints_merged",
-            &format!("Merged '{}' into '{}'", body.source, body.target),
-            Some(&format!(
-                "{} communication(s), {} attribute(s), {} port(s), {} scan result(s)",
-                merged_comms, merged_attrs, merged_ports, merged_scans
-            )),
-            Some(&body.target),
-        );
+/// Rules that would have classified manually overridden endpoints correctly, for adding
+/// to the device rules and OUI overrides
+#[get("/api/classification/suggestions")]
+pub async fn get_classification_suggestions() -> impl Responder {
+    match tokio::task::spawn_blocking(|| get_rule_suggestions(&new_connection())).await {
+        Ok(Ok(suggestions)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "suggestions": suggestions,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e),
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Suggestions task failed",
+        })),
+    }
+}
 
-        HttpResponse::Ok().json(MergeEndpointsResponse {
-            success: true,
-            message: format!(
-                "Merged '{}' into '{}': {} communication(s), {} attribute(s), {} port(s), {} scan result(s)",
-                body.source, body.target, merged_comms, merged_attrs, merged_ports, merged_scans
-            ),
-        })
-    } else {
-        HttpResponse::InternalServerError().json(MergeEndpointsResponse {
-            success: false,
-            message: "Failed to delete source endpoint after merge".to_string(),
-        })
+/// The same suggestions as TOML snippets, grouped by the file they belong in
+#[get("/api/classification/suggestions/export")]
+pub async fn export_classification_suggestions() -> impl Responder {
+    match tokio::task::spawn_blocking(|| get_rule_suggestions(&new_connection())).await {
+        Ok(Ok(suggestions)) => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .insert_header((
+                "Content-Disposition",
+                "attachment; filename=\"suggested_rules.toml\"",
+            ))
+            .body(export_rule_suggestions(&suggestions)),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+        Err(_) => HttpResponse::InternalServerError().body("Export task failed"),
     }
 }
 
+/// An endpoint's own SNMP community. Sent in the body so it never reaches the audit log;
+/// an empty or missing community clears it.
 #[derive(Deserialize)]
-pub struct ProbeModelRequest {
-    ip: String,
+pub struct SetSnmpCredentialRequest {
+    endpoint_name: String,
+    community: Option<String>,
 }
 
 #[derive(Serialize)]
-pub struct ProbeModelResponse {
+pub struct SnmpCredentialResponse {
     success: bool,
     message: String,
-    model: Option<String>,
+    has_credential: bool,
 }
 
-/// Probe a device's web interface to detect its model
-#[post("/api/endpoint/probe/model")]
-pub async fn probe_endpoint_model(body: Json<ProbeModelRequest>) -> impl Responder {
-    let ip = body.ip.clone();
-
-    // Try to probe the device for its model (run in blocking thread for immediate execution)
-    let ip_clone = ip.clone();
-    let model = tokio::task::spawn_blocking(move || probe_hp_printer_model_blocking(&ip_clone))
-        .await
-        .ok()
-        .flatten();
-
-    if let Some(model) = model {
-        // Find the endpoint and save the model
-        let conn = match new_connection_result() {
-            Ok(c) => c,
-            Err(e) => {
-                return HttpResponse::InternalServerError().json(ProbeModelResponse {
-                    success: false,
-                    message: format!("Database error: {}", e),
-                    model: None,
-                });
-            }
-        };
+fn find_endpoint_id_by_name(conn: &Connection, endpoint_name: &str) -> Option<i64> {
+    conn.query_row(
+        &format!(
+            "SELECT e.id FROM endpoints e WHERE {} = ?1 COLLATE NOCASE LIMIT 1",
+            DISPLAY_NAME_SQL
+        ),
+        [endpoint_name],
+        |row| row.get(0),
+    )
+    .ok()
+}
 
-        // Find endpoint by IP and update the ssdp_model
-        let update_result = conn.execute(
-            "UPDATE endpoints SET ssdp_model = ?1
-             WHERE id IN (SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?2)
-             AND (ssdp_model IS NULL OR ssdp_model = '')",
-            params![model, ip],
-        );
+/// Whether an endpoint has its own SNMP community (the value is never returned)
+#[get("/api/endpoint/{name}/snmp-credential")]
+pub async fn get_snmp_credential(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        let endpoint_id = find_endpoint_id_by_name(&conn, &endpoint_name)?;
+        Some(has_snmp_credential(&conn, endpoint_id))
+    })
+    .await;
 
-        match update_result {
-            Ok(rows) => {
-                if rows > 0 {
-                    let eid: Option<i64> = conn
-                        .query_row(
-                            "SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?1 LIMIT 1",
-                            params![ip],
-                            |row| row.get(0),
-                        )
-                        .ok();
-                    insert_notification_with_endpoint_id(
-                        &conn,
-                        "model_identified",
-                        &format!("Device model identified: {}", model),
-                        None,
-                        None,
-                        eid,
-                    );
-                }
-                HttpResponse::Ok().json(ProbeModelResponse {
-                    success: true,
-                    message: format!("Found model '{}', updated {} endpoint(s)", model, rows),
-                    model: Some(model),
-                })
-            }
-            Err(e) => HttpResponse::InternalServerError().json(ProbeModelResponse {
-                success: false,
-                message: format!("Found model '{}' but failed to save: {}", model, e),
-                model: Some(model),
-            }),
-        }
-    } else {
-        HttpResponse::Ok().json(ProbeModelResponse {
+    match result {
+        Ok(Some(has_credential)) => HttpResponse::Ok().json(SnmpCredentialResponse {
+            success: true,
+            message: String::new(),
+            has_credential,
+        }),
+        Ok(None) => HttpResponse::NotFound().json(SnmpCredentialResponse {
             success: false,
-            message: "Could not detect model from device web interface".to_string(),
-            model: None,
-        })
+            message: "Endpoint not found".to_string(),
+            has_credential: false,
+        }),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to read SNMP credential"),
     }
 }
 
-// ============================================================================
-// Device Control API Endpoints
-// ============================================================================
-
-#[derive(Deserialize)]
-pub struct DeviceQuery {
-    ip: String,
-    device_type: Option<String>,
-    hostname: Option<String>,
+#[derive(Serialize)]
+pub struct SmbSharesResponse {
+    workgroup: Option<String>,
+    shares: Vec<SmbShare>,
 }
 
-#[derive(Deserialize)]
-pub struct DeviceCommandRequest {
-    ip: String,
-    command: String,
-    device_type: String,
-}
+/// Workgroup and SMB shares stored for an endpoint by NetBIOS scans
+#[get("/api/endpoint/{name}/smb-shares")]
+pub async fn get_endpoint_smb_shares(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        let endpoint_id = find_endpoint_id_by_name(&conn, &endpoint_name)?;
+        let workgroup: Option<String> = conn
+            .query_row(
+                "SELECT workgroup FROM endpoints WHERE id = ?1",
+                [endpoint_id],
+                |row| row.get(0),
+            )
+            .ok()
+            .flatten();
+        let shares = get_smb_shares(&conn, endpoint_id).unwrap_or_default();
+        Some(SmbSharesResponse { workgroup, shares })
+    })
+    .await;
 
-#[derive(Deserialize)]
-pub struct LaunchAppRequest {
-    ip: String,
-    app_id: String,
-    device_type: String,
+    match result {
+        Ok(Some(response)) => HttpResponse::Ok().json(response),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch SMB shares"
+        })),
+    }
 }
 
-#[get("/api/device/capabilities")]
-pub async fn get_device_capabilities(query: Query<DeviceQuery>) -> impl Responder {
-    let ip = query.ip.clone();
-    let device_type = query.device_type.clone();
-    let hostname = query.hostname.clone();
+/// Cached web info is fetched again after a port scan once it is this old
+const WEB_INFO_MAX_AGE_SECS: i64 = 24 * 60 * 60;
+/// Devices whose web interface is fetched at the same time
+const WEB_INFO_CONCURRENCY: usize = 8;
 
-    // Run blocking device detection in a separate thread
-    let capabilities = actix_web::web::block(move || {
-        DeviceController::get_capabilities(&ip, device_type.as_deref(), hostname.as_deref())
+/// Fetch the page title and favicon of every endpoint with an open web port whose
+/// cached web info is missing or out of date
+async fn refresh_stale_web_info() {
+    let now = chrono::Utc::now().timestamp();
+    let targets = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        get_web_info_targets(&conn, WEB_PORTS, now - WEB_INFO_MAX_AGE_SECS).ok()
     })
-    .await;
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+    if targets.is_empty() {
+        return;
+    }
 
-    match capabilities {
-        Ok(caps) => HttpResponse::Ok().json(caps),
-        Err(_) => HttpResponse::InternalServerError().body("Failed to get device capabilities"),
+    let fetcher = WebPageFetcher::new();
+    let results: Vec<(i64, Option<WebPageInfo>)> = futures_util::stream::iter(targets)
+        .map(|target| {
+            let fetcher = &fetcher;
+            async move {
+                let info = match target.ip.parse() {
+                    Ok(ip) => fetcher.fetch(ip, &target.ports).await,
+                    Err(_) => None,
+                };
+                (target.endpoint_id, info)
+            }
+        })
+        .buffer_unordered(WEB_INFO_CONCURRENCY)
+        .collect()
+        .await;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        for (endpoint_id, info) in &results {
+            store_web_info(&conn, *endpoint_id, info.as_ref(), now)?;
+        }
+        Ok::<_, rusqlite::Error>(())
+    })
+    .await;
+    if let Ok(Err(e)) = result {
+        eprintln!("Failed to store web info: {}", e);
     }
 }
 
-#[post("/api/device/command")]
-pub async fn send_device_command(body: Json<DeviceCommandRequest>) -> impl Responder {
-    let ip = body.ip.clone();
-    let command = body.command.clone();
-    let device_type = body.device_type.clone();
+/// Host keys are collected again after a port scan once this old, so a changed key is
+/// noticed within hours
+const HOST_KEY_MAX_AGE_SECS: i64 = 6 * 60 * 60;
+/// Devices whose SSH and TLS services are contacted at the same time
+const HOST_KEY_CONCURRENCY: usize = 8;
 
-    let result =
-        actix_web::web::block(move || DeviceController::send_command(&ip, &command, &device_type))
+/// Collect SSH host keys and TLS certificates from every endpoint with an open SSH or
+/// TLS port that wasn't checked recently
+async fn refresh_stale_host_keys() {
+    let now = chrono::Utc::now().timestamp();
+    let ports: Vec<u16> = SSH_PORTS.iter().chain(TLS_PORTS).copied().collect();
+    let targets = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        get_host_key_targets(&conn, &ports, now - HOST_KEY_MAX_AGE_SECS).ok()
+    })
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+    if targets.is_empty() {
+        return;
+    }
+
+    let collector = HostKeyCollector::new();
+    let results: Vec<(i64, Vec<SshHostKey>, Vec<TlsCertificate>)> =
+        futures_util::stream::iter(targets)
+            .map(|target| {
+                let collector = &collector;
+                async move {
+                    let (ssh_keys, certificates) = match target.ip.parse() {
+                        Ok(ip) => collector.collect(ip, &target.ports).await,
+                        Err(_) => Default::default(),
+                    };
+                    (target.endpoint_id, ssh_keys, certificates)
+                }
+            })
+            .buffer_unordered(HOST_KEY_CONCURRENCY)
+            .collect()
             .await;
 
-    match result {
-        Ok(r) if r.success => HttpResponse::Ok().json(r),
-        Ok(r) => HttpResponse::BadRequest().json(r),
-        Err(_) => HttpResponse::InternalServerError().body("Command failed"),
+    let result = tokio::task::spawn_blocking(move || {
+        let warning_days = get_setting_i64("certificate_expiry_warning_days", 30);
+        let conn = new_connection_result()?;
+        for (endpoint_id, ssh_keys, certificates) in &results {
+            store_host_keys(
+                &conn,
+                *endpoint_id,
+                ssh_keys,
+                certificates,
+                now,
+                warning_days,
+            )?;
+        }
+        Ok::<_, rusqlite::Error>(())
+    })
+    .await;
+    if let Ok(Err(e)) = result {
+        eprintln!("Failed to store host keys: {}", e);
     }
 }
 
-#[post("/api/device/launch")]
-pub async fn launch_device_app(body: Json<LaunchAppRequest>) -> impl Responder {
-    let ip = body.ip.clone();
-    let app_id = body.app_id.clone();
-    let device_type = body.device_type.clone();
+/// A device's factory logins are tried again after this long, not on every port scan
+const DEFAULT_CREDENTIALS_RECHECK_SECS: i64 = 7 * 24 * 60 * 60;
 
-    let result =
-        actix_web::web::block(move || DeviceController::launch_app(&ip, &app_id, &device_type))
-            .await;
-
-    match result {
-        Ok(r) if r.success => HttpResponse::Ok().json(r),
-        Ok(r) => HttpResponse::BadRequest().json(r),
-        Err(_) => HttpResponse::InternalServerError().body("Launch failed"),
-    }
-}
+/// When each endpoint's admin ports were last tried against factory logins
+static DEFAULT_CREDENTIALS_CHECKED: OnceLock<Mutex<HashMap<i64, i64>>> = OnceLock::new();
 
-#[derive(Deserialize)]
-pub struct PairRequest {
+/// A router, printer or camera with open admin ports
+struct CredentialCheckTarget {
+    endpoint_id: i64,
+    name: String,
     ip: String,
-    device_type: String,
+    vendor: Option<String>,
+    ports: Vec<u16>,
 }
 
-#[post("/api/device/pair")]
-pub async fn pair_device(body: Json<PairRequest>) -> impl Responder {
-    let ip = body.ip.clone();
-    let device_type = body.device_type.clone();
+fn get_credential_check_targets(conn: &Connection) -> rusqlite::Result<Vec<CredentialCheckTarget>> {
+    let ports = CREDENTIAL_CHECK_PORTS
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT op.endpoint_id, GROUP_CONCAT(DISTINCT op.port), s.name, s.vendor, s.device_type,
+                (SELECT ea.ip FROM endpoint_attributes ea
+                 WHERE ea.endpoint_id = op.endpoint_id AND ea.ip != ''
+                 ORDER BY ea.created_at DESC LIMIT 1) AS ip
+         FROM open_ports op
+         JOIN endpoint_summary s ON s.endpoint_id = op.endpoint_id
+         WHERE op.port IN ({})
+         GROUP BY op.endpoint_id
+         HAVING ip IS NOT NULL",
+        ports
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        let ports: String = row.get(1)?;
+        let device_type: Option<String> = row.get(4)?;
+        let target = CredentialCheckTarget {
+            endpoint_id: row.get(0)?,
+            name: row.get(2)?,
+            ip: row.get(5)?,
+            vendor: row.get(3)?,
+            ports: ports.split(',').filter_map(|p| p.parse().ok()).collect(),
+        };
+        Ok(
+            is_credential_check_target(device_type.as_deref(), target.vendor.as_deref())
+                .then_some(target),
+        )
+    })?;
+    rows.filter_map(Result::transpose).collect()
+}
 
-    let result = actix_web::web::block(move || DeviceController::pair(&ip, &device_type)).await;
+/// Try factory logins on the admin ports of routers, printers and cameras not checked
+/// within a week. Runs only with the `default_credentials_check` setting on; an
+/// accepted login raises a critical notification and nothing else.
+async fn check_default_credentials() {
+    let now = chrono::Utc::now().timestamp();
+    let targets = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result().ok()?;
+        get_credential_check_targets(&conn).ok()
+    })
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or_default();
 
-    match result {
-        Ok(r) if r.success => HttpResponse::Ok().json(r),
-        Ok(r) => HttpResponse::BadRequest().json(r),
-        Err(_) => HttpResponse::InternalServerError().body("Pairing failed"),
+    let targets: Vec<CredentialCheckTarget> = {
+        let Ok(mut checked) = DEFAULT_CREDENTIALS_CHECKED
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+        else {
+            return;
+        };
+        targets
+            .into_iter()
+            .filter(|target| {
+                let due = checked
+                    .get(&target.endpoint_id)
+                    .is_none_or(|&at| at < now - DEFAULT_CREDENTIALS_RECHECK_SECS);
+                if due {
+                    checked.insert(target.endpoint_id, now);
+                }
+                due
+            })
+            .collect()
+    };
+    if targets.is_empty() {
+        return;
     }
-}
 
-// ============================================================================
-// LG ThinQ API Endpoints
-// ============================================================================
+    let checker = DefaultCredentialChecker::new();
+    let results: Vec<(CredentialCheckTarget, Vec<DefaultCredentialFinding>)> =
+        futures_util::stream::iter(targets)
+            .map(|target| {
+                let checker = &checker;
+                async move {
+                    let findings = match target.ip.parse() {
+                        Ok(ip) => {
+                            checker
+                                .check(ip, &target.ports, target.vendor.clone())
+                                .await
+                        }
+                        Err(_) => Vec::new(),
+                    };
+                    (target, findings)
+                }
+            })
+            .buffer_unordered(HOST_KEY_CONCURRENCY)
+            .collect()
+            .await;
 
-#[derive(Deserialize)]
-pub struct ThinQSetupRequest {
-    pat_token: String,
-    country_code: String,
+    let _ = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        for (target, findings) in &results {
+            for finding in findings {
+                insert_notification_with_endpoint_id(
+                    &conn,
+                    "default_credentials",
+                    &format!("Default credentials accepted on {}", target.name),
+                    Some(&format!(
+                        "{} on port {} accepted a {}; change the password",
+                        finding.protocol, finding.port, finding.label
+                    )),
+                    Some(&target.name),
+                    Some(target.endpoint_id),
+                );
+            }
+        }
+        Ok::<_, rusqlite::Error>(())
+    })
+    .await;
 }
 
 #[derive(Serialize)]
-pub struct ThinQStatusResponse {
-    configured: bool,
-    devices: Vec<ThinQDeviceInfo>,
+pub struct WebInfoRefreshResponse {
+    success: bool,
+    message: String,
+    url: Option<String>,
+    title: Option<String>,
+    has_favicon: bool,
 }
 
-#[derive(Serialize)]
-pub struct ThinQDeviceInfo {
-    device_id: String,
-    device_type: String,
-    name: String,
-    model: Option<String>,
-    online: bool,
+impl WebInfoRefreshResponse {
+    fn error(message: &str) -> Self {
+        WebInfoRefreshResponse {
+            success: false,
+            message: message.to_string(),
+            url: None,
+            title: None,
+            has_favicon: false,
+        }
+    }
 }
 
-#[post("/api/thinq/setup")]
-pub async fn setup_thinq(body: Json<ThinQSetupRequest>) -> impl Responder {
-    let pat_token = body.pat_token.clone();
-    let country_code = body.country_code.clone();
+/// Fetch an endpoint's web page title and favicon now, trying every web port
+#[post("/api/endpoint/{name}/web-info/refresh")]
+pub async fn refresh_endpoint_web_info(
+    _admin: RequireAdmin,
+    path: actix_web::web::Path<String>,
+) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let target = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        let endpoint_id = find_endpoint_id_by_name(&conn, &endpoint_name)?;
+        let ip: Option<String> = conn
+            .query_row(
+                "SELECT ip FROM endpoint_attributes WHERE endpoint_id = ?1 AND ip != ''
+                 ORDER BY created_at DESC LIMIT 1",
+                [endpoint_id],
+                |row| row.get(0),
+            )
+            .ok();
+        Some((endpoint_id, ip))
+    })
+    .await
+    .ok()
+    .flatten();
 
-    let result =
-        actix_web::web::block(move || DeviceController::setup_thinq(&pat_token, &country_code))
-            .await;
+    let Some((endpoint_id, ip)) = target else {
+        return HttpResponse::NotFound().json(WebInfoRefreshResponse::error("Endpoint not found"));
+    };
+    let Some(ip) = ip.and_then(|ip| ip.parse::<std::net::IpAddr>().ok()) else {
+        return HttpResponse::BadRequest()
+            .json(WebInfoRefreshResponse::error("Endpoint has no IP address"));
+    };
 
-    match result {
-        Ok(r) if r.success => HttpResponse::Ok().json(r),
-        Ok(r) => HttpResponse::BadRequest().json(r),
-        Err(_) => HttpResponse::InternalServerError().body("ThinQ setup failed"),
+    let info = WebPageFetcher::new().fetch(ip, WEB_PORTS).await;
+    let now = chrono::Utc::now().timestamp();
+    let stored = {
+        let info = info.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = new_connection_result()?;
+            store_web_info(&conn, endpoint_id, info.as_ref(), now)
+        })
+        .await
+    };
+    if !matches!(stored, Ok(Ok(()))) {
+        return HttpResponse::InternalServerError()
+            .json(WebInfoRefreshResponse::error("Failed to store web info"));
     }
-}
 
-#[get("/api/thinq/status")]
-pub async fn get_thinq_status() -> impl Responder {
-    let result = actix_web::web::block(move || {
-        let configured = DeviceController::is_thinq_configured();
-        let devices = if configured {
-            DeviceController::list_thinq_devices()
-                .unwrap_or_default()
-                .into_iter()
-                .map(|d| ThinQDeviceInfo {
-                    device_id: d.device_id,
-                    device_type: d.device_type,
-                    name: d.device_alias,
-                    model: d.model_name,
-                    online: d.online,
-                })
-                .collect()
-        } else {
-            Vec::new()
-        };
+    match info {
+        Some(info) => HttpResponse::Ok().json(WebInfoRefreshResponse {
+            success: true,
+            message: format!("Fetched {}", info.url),
+            has_favicon: info.favicon.is_some(),
+            url: Some(info.url),
+            title: info.title,
+        }),
+        None => HttpResponse::Ok().json(WebInfoRefreshResponse::error(
+            "No web interface responded on ports 80, 443, 8080 or 8443",
+        )),
+    }
+}
 
-        ThinQStatusResponse {
-            configured,
-            devices,
-        }
+/// An endpoint's cached favicon, as served by the device
+#[get("/api/endpoint/{name}/favicon")]
+pub async fn get_endpoint_favicon(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        let endpoint_id = find_endpoint_id_by_name(&conn, &endpoint_name)?;
+        get_web_favicon(&conn, endpoint_id).ok().flatten()
     })
     .await;
 
     match result {
-        Ok(status) => HttpResponse::Ok().json(status),
-        Err(_) => HttpResponse::InternalServerError().body("Failed to get ThinQ status"),
+        Ok(Some((content_type, data))) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header(("Cache-Control", "private, max-age=3600"))
+            .insert_header(("X-Content-Type-Options", "nosniff"))
+            .body(data),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
 
-#[get("/api/thinq/devices")]
-pub async fn list_thinq_devices() -> impl Responder {
-    let result = actix_web::web::block(move || {
-        DeviceController::list_thinq_devices().map(|devices| {
-            devices
-                .into_iter()
-                .map(|d| ThinQDeviceInfo {
-                    device_id: d.device_id,
-                    device_type: d.device_type,
-                    name: d.device_alias,
-                    model: d.model_name,
-                    online: d.online,
-                })
-                .collect::<Vec<_>>()
-        })
+/// SSH host key fingerprints and TLS certificates collected from an endpoint's services
+#[get("/api/endpoint/{name}/host-keys")]
+pub async fn get_endpoint_host_keys_api(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        match find_endpoint_id_by_name(&conn, &endpoint_name) {
+            Some(endpoint_id) => get_endpoint_host_keys(&conn, endpoint_id).map(Some),
+            None => Ok(None),
+        }
     })
     .await;
 
     match result {
-        Ok(Ok(devices)) => HttpResponse::Ok().json(devices),
-        Ok(Err(e)) => HttpResponse::BadRequest().body(e),
-        Err(_) => HttpResponse::InternalServerError().body("Failed to list ThinQ devices"),
+        Ok(Ok(Some(host_keys))) => HttpResponse::Ok().json(host_keys),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch host keys"
+        })),
     }
 }
 
-#[post("/api/thinq/disconnect")]
-pub async fn disconnect_thinq() -> impl Responder {
-    let result = actix_web::web::block(DeviceController::disconnect_thinq).await;
+#[derive(Deserialize)]
+pub struct ScanResultsQuery {
+    /// Only results of this scan type ("arp", "icmp", "ndp", "netbios", "snmp", "ssdp")
+    #[serde(rename = "type")]
+    scan_type: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
 
-    match result {
-        Ok(success) => {
-            if success {
-                HttpResponse::Ok().json(serde_json::json!({
-                    "success": true,
-                    "message": "Disconnected from LG ThinQ"
-                }))
-            } else {
-                HttpResponse::BadRequest().json(serde_json::json!({
-                    "success": false,
-                    "message": "Failed to disconnect"
-                }))
+/// An endpoint's stored scan results, newest first, with the details JSON as recorded
+#[get("/api/endpoint/{name}/scan-results")]
+pub async fn get_endpoint_scan_results(
+    path: actix_web::web::Path<String>,
+    query: Query<ScanResultsQuery>,
+) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let scan_type = query.scan_type.clone().filter(|t| !t.is_empty());
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        match find_endpoint_id_by_name(&conn, &endpoint_name) {
+            Some(endpoint_id) => {
+                get_scan_results(&conn, endpoint_id, scan_type.as_deref(), limit, offset).map(Some)
             }
+            None => Ok(None),
         }
-        Err(_) => HttpResponse::InternalServerError().body("Failed to disconnect ThinQ"),
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(page))) => HttpResponse::Ok().json(page),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch scan results"
+        })),
     }
 }
 
-// ============================================================================
-// Network Scanner API Endpoints
-// ============================================================================
-
 #[derive(Deserialize)]
-pub struct StartScanRequest {
-    scan_types: Vec<ScanType>,
-}
-
-#[derive(Serialize)]
-pub struct StartScanResponse {
-    success: bool,
-    message: String,
+pub struct CertificatesQuery {
+    /// Only certificates expiring within this many days (expired ones included)
+    expiring_within_days: Option<i64>,
 }
 
-#[post("/api/scan/start")]
-pub async fn start_scan(body: Json<StartScanRequest>) -> impl Responder {
-    let manager = get_scan_manager();
-    let scan_types = body.scan_types.clone();
-
-    match manager.start_scan(scan_types.clone()).await {
-        Ok(()) => {
-            let type_names: Vec<String> = scan_types.iter().map(|t| t.to_string()).collect();
-            let details = format!("Scan types: {}", type_names.join(", "));
-            tokio::task::spawn_blocking(move || {
-                let conn = new_connection();
-                insert_notification(
-                    &conn,
-                    "scan_started",
-                    "Network scan started",
-                    Some(&details),
-                    None,
-                );
-            });
+/// TLS certificates seen on the LAN, soonest expiry first
+#[get("/api/certificates")]
+pub async fn get_certificates(query: Query<CertificatesQuery>) -> impl Responder {
+    let expiring_before = query
+        .expiring_within_days
+        .map(|days| chrono::Utc::now().timestamp() + days.clamp(0, 3650) * 24 * 60 * 60);
+    let result = tokio::task::spawn_blocking(move || {
+        get_tls_certificates(&new_connection(), expiring_before)
+    })
+    .await;
 
-            HttpResponse::Ok().json(StartScanResponse {
-                success: true,
-                message: "Scan started".to_string(),
-            })
+    match result {
+        Ok(Ok(certificates)) => {
+            HttpResponse::Ok().json(serde_json::json!({ "certificates": certificates }))
         }
-        Err(e) => HttpResponse::BadRequest().json(StartScanResponse {
-            success: false,
-            message: e,
-        }),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch certificates"
+        })),
     }
 }
 
-#[post("/api/scan/stop")]
-pub async fn stop_scan() -> impl Responder {
-    let manager = get_scan_manager();
-    manager.stop_scan().await;
-
-    tokio::task::spawn_blocking(|| {
-        let conn = new_connection();
-        insert_notification(&conn, "scan_stopped", "Network scan stopped", None, None);
-    });
-
-    HttpResponse::Ok().json(StartScanResponse {
-        success: true,
-        message: "Scan stopped".to_string(),
+/// Port mappings reported by UPnP gateways, i.e. which devices exposed ports to the internet
+#[get("/api/upnp/port-mappings")]
+pub async fn get_upnp_port_mappings() -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        get_port_mappings(&conn).map_err(|e| e.to_string())
     })
-}
+    .await;
 
-#[get("/api/scan/status")]
-pub async fn get_scan_status() -> impl Responder {
-    let manager = get_scan_manager();
-    let status = manager.get_status().await;
+    match result {
+        Ok(Ok(mappings)) => HttpResponse::Ok().json(mappings),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch UPnP port mappings"
+        })),
+    }
+}
 
-    HttpResponse::Ok().json(status)
+#[derive(Serialize)]
+pub struct Ipv6PrefixesResponse {
+    routers: Vec<Ipv6Router>,
 }
 
-#[get("/api/scan/capabilities")]
-pub async fn get_scan_capabilities() -> impl Responder {
-    let capabilities = check_scan_privileges();
-    HttpResponse::Ok().json(capabilities)
-}
-
-#[get("/api/scan/config")]
-pub async fn get_scan_config() -> impl Responder {
-    let manager = get_scan_manager();
-    let config = manager.get_config().await;
+/// Routers seen sending IPv6 router advertisements, with the prefixes, DNS servers and
+/// lifetimes they announce
+#[get("/api/ipv6/prefixes")]
+pub async fn get_ipv6_prefixes() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        get_ipv6_routers(&conn, chrono::Utc::now().timestamp()).map_err(|e| e.to_string())
+    })
+    .await;
 
-    HttpResponse::Ok().json(config)
+    match result {
+        Ok(Ok(routers)) => HttpResponse::Ok().json(Ipv6PrefixesResponse { routers }),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch IPv6 prefixes"
+        })),
+    }
 }
 
-#[post("/api/scan/config")]
-pub async fn set_scan_config(body: Json<ScanConfig>) -> impl Responder {
-    let manager = get_scan_manager();
-    manager.set_config(body.into_inner()).await;
+#[derive(Deserialize)]
+pub struct TrustRouterRequest {
+    router_ip: String,
+    trusted: bool,
+}
 
-    HttpResponse::Ok().json(StartScanResponse {
-        success: true,
-        message: "Config updated".to_string(),
+/// Mark an IPv6 router as expected, so its advertisements stop raising rogue router alerts
+#[post("/api/ipv6/routers/trust")]
+pub async fn set_ipv6_router_trust(
+    _admin: RequireAdmin,
+    body: Json<TrustRouterRequest>,
+) -> impl Responder {
+    let TrustRouterRequest { router_ip, trusted } = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        set_router_trusted(&conn, &router_ip, trusted).map_err(|e| e.to_string())
     })
+    .await;
+
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Router not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to update router"
+        })),
+    }
 }
 
-// ============================================================================
-// Scan Result Processing
-// ============================================================================
+/// A port that answered on the WAN address, and where the gateway sends it
+#[derive(Serialize)]
+pub struct ExposedService {
+    port: u16,
+    service_name: Option<String>,
+    /// None when no UPnP mapping covers the port (the gateway itself, or a manual forward)
+    internal_client: Option<String>,
+    internal_port: Option<u16>,
+    internal_name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ExposureReport {
+    wan_ip: String,
+    wan_ip_source: String,
+    behind_nat: bool,
+    external_scanner: bool,
+    services: Vec<ExposedService>,
+}
 
-/// Process a scan result and store in database with retry logic
-fn process_scan_result(result: &ScanResult) -> Result<(), String> {
-    const MAX_RETRIES: u32 = 5;
+/// Find the WAN address and report which services answer on it
+#[post("/api/exposure/scan")]
+pub async fn run_exposure_scan(_admin: RequireAdmin) -> impl Responder {
+    use crate::scanner::exposure::{DEFAULT_WAN_IP_SOURCE, ExposureScanner, WanIpSource};
+    use crate::scanner::port::DEFAULT_PORTS;
+
+    let settings = tokio::task::spawn_blocking(|| {
+        let settings = get_all_settings();
+        let mappings = new_connection_result()
+            .ok()
+            .and_then(|conn| get_port_mappings(&conn).ok())
+            .unwrap_or_default();
+        (settings, mappings)
+    })
+    .await;
+    let Ok((settings, mappings)) = settings else {
+        return HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load exposure settings"
+        }));
+    };
 
-    for attempt in 1..=MAX_RETRIES {
-        match process_scan_result_inner(result) {
-            Ok(()) => return Ok(()),
-            Err(e) if e.contains("database is locked") && attempt < MAX_RETRIES => {
-                // Exponential backoff: 50ms, 100ms, 200ms, 400ms
-                std::thread::sleep(std::time::Duration::from_millis(50 * (1 << (attempt - 1))));
-                continue;
-            }
-            Err(e) => return Err(e),
+    let source_setting = settings
+        .get("exposure_wan_ip_source")
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_WAN_IP_SOURCE);
+    let Some(source) = WanIpSource::parse(source_setting) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unrecognised WAN address source: {}", source_setting)
+        }));
+    };
+    let config = get_scan_manager().get_config().await;
+    let scanner = ExposureScanner::new()
+        .with_timeout(config.probe_timeout_ms(ScanType::PORT, 3000))
+        .with_wan_ip_source(source)
+        .with_external_scanner(settings.get("exposure_scanner_url").cloned());
+
+    // Forwarded ports are the likeliest to be open, so check them alongside the defaults
+    let mut ports: Vec<u16> = DEFAULT_PORTS.to_vec();
+    for mapping in mappings.iter().filter(|m| m.protocol == "TCP") {
+        if !ports.contains(&mapping.external_port) {
+            ports.push(mapping.external_port);
         }
     }
 
-    Err("Max retries exceeded".to_string())
-}
+    let result = match scanner.scan(&ports).await {
+        Ok(result) => result,
+        Err(e) => {
+            return HttpResponse::BadGateway().json(serde_json::json!({ "error": e }));
+        }
+    };
 
-/// Inner function that does the actual work
-fn process_scan_result_inner(result: &ScanResult) -> Result<(), String> {
-    let conn = new_connection();
+    let services: Vec<ExposedService> = result
+        .open_ports
+        .iter()
+        .map(|open| {
+            let mapping = mappings
+                .iter()
+                .find(|m| m.protocol == "TCP" && m.external_port == open.port && m.enabled);
+            ExposedService {
+                port: open.port,
+                service_name: open.service_name.clone(),
+                internal_client: mapping.map(|m| m.internal_client.clone()),
+                internal_port: mapping.map(|m| m.internal_port),
+                internal_name: mapping.and_then(|m| m.internal_name.clone()),
+            }
+        })
+        .collect();
 
-    match result {
-        ScanResult::Arp(arp) => {
-            let ip_str = arp.ip.to_string();
-            let mac_str = arp.mac.to_string();
-            if let Ok((endpoint_id, is_new)) = EndPoint::get_or_insert_endpoint(
-                &conn,
-                Some(mac_str.clone()),
-                Some(ip_str.clone()),
-                None,
-                &[],
-            ) {
-                if is_new {
-                    insert_notification_with_endpoint_id(
-                        &conn,
-                        "endpoint_discovered",
-                        &format!("New device discovered: {}", ip_str),
-                        Some(&format!("MAC: {}", mac_str)),
-                        Some(&ip_str),
-                        Some(endpoint_id),
-                    );
-                }
+    let report = ExposureReport {
+        wan_ip: result.wan_ip.to_string(),
+        wan_ip_source: result.wan_ip_source,
+        behind_nat: result.behind_nat,
+        external_scanner: result.external_scanner,
+        services,
+    };
 
-                insert_scan_result(
+    if !report.services.is_empty() {
+        let listing: Vec<String> = report
+            .services
+            .iter()
+            .map(|s| match (&s.internal_client, s.internal_port) {
+                (Some(client), Some(port)) => format!(
+                    "{} -> {}:{}",
+                    s.port,
+                    s.internal_name.as_deref().unwrap_or(client),
+                    port
+                ),
+                _ => s.port.to_string(),
+            })
+            .collect();
+        let title = format!(
+            "{} port(s) reachable on WAN address {}",
+            report.services.len(),
+            report.wan_ip
+        );
+        let details = format!("Open: {}", listing.join(", "));
+        let _ = tokio::task::spawn_blocking(move || {
+            if let Ok(conn) = new_connection_result() {
+                insert_notification_with_endpoint_id(
                     &conn,
-                    endpoint_id,
-                    "arp",
-                    Some(arp.response_time_ms as i64),
+                    "wan_exposure",
+                    &title,
+                    Some(&details),
                     None,
-                )?;
-
-                // If this is an HP device, probe for printer model
-                if get_mac_vendor(&mac_str).is_some_and(|v| v == "HP") {
-                    let ip_for_probe = ip_str.clone();
-                    tokio::task::spawn_blocking(move || {
-                        probe_and_save_hp_printer_model_blocking(&ip_for_probe, endpoint_id);
-                    });
-                }
-            }
-        }
-        ScanResult::Icmp(icmp) => {
-            if icmp.alive {
-                let ip_str = icmp.ip.to_string();
-                // For ICMP (no MAC), only record if endpoint already exists
-                // This prevents creating ghost entries for false positive pings
-                if let Some(endpoint_id) = find_existing_endpoint_by_ip(&conn, &ip_str) {
-                    let details = serde_json::json!({
-                        "ttl": icmp.ttl,
-                        "rtt_ms": icmp.rtt_ms,
-                    });
-                    insert_scan_result(
-                        &conn,
-                        endpoint_id,
-                        "icmp",
-                        icmp.rtt_ms.map(|r| r as i64),
-                        Some(&details.to_string()),
-                    )?;
-                }
-            }
-        }
-        ScanResult::Port(port) => {
-            if port.open {
-                let ip_str = port.ip.to_string();
-                // For port scans (no MAC), only record if endpoint already exists
-                if let Some(endpoint_id) = find_existing_endpoint_by_ip(&conn, &ip_str) {
-                    insert_open_port(&conn, endpoint_id, port.port, port.service_name.as_deref())?;
-                }
+                    None,
+                );
             }
-        }
-        ScanResult::Ssdp(ssdp) => {
-            let ip_str = ssdp.ip.to_string();
-            // For SSDP (no MAC), only record if endpoint already exists
-            if let Some(endpoint_id) = find_existing_endpoint_by_ip(&conn, &ip_str) {
-                let details = serde_json::json!({
-                    "location": ssdp.location,
-                    "server": ssdp.server,
-                    "device_type": ssdp.device_type,
-                    "friendly_name": ssdp.friendly_name,
-                    "model_name": ssdp.model_name,
-                });
-                insert_scan_result(&conn, endpoint_id, "ssdp", None, Some(&details.to_string()))?;
-
-                // If we got a model name from SSDP, save it to the endpoint
-                // But first verify it's consistent with the endpoint's MAC vendor
-                // to prevent mismatched data from IP address reassignments
-                if let Some(ref model) = ssdp.model_name
-                    && is_ssdp_model_consistent_with_endpoint(&conn, endpoint_id, model)
-                {
-                    // Update if empty OR if new model is more specific than current
-                    let current_model: Option<String> = conn
-                        .query_row(
-                            "SELECT ssdp_model FROM endpoints WHERE id = ?1",
-                            params![endpoint_id],
-                            |row| row.get(0),
-                        )
-                        .ok()
-                        .flatten();
-
-                    let should_update = match &current_model {
-                        None => true,
-                        Some(current) if current.is_empty() => true,
-                        Some(current) => is_more_specific_model(model, current),
-                    };
+        })
+        .await;
+    }
 
-                    if should_update {
-                        let _ = conn.execute(
-                            "UPDATE endpoints SET ssdp_model = ?1 WHERE id = ?2",
-                            params![model, endpoint_id],
-                        );
+    HttpResponse::Ok().json(report)
+}
 
-                        if current_model.as_ref().is_none_or(|m| m.is_empty()) {
-                            insert_notification_with_endpoint_id(
-                                &conn,
-                                "model_identified",
-                                &format!("Device model identified: {}", model),
-                                None,
-                                None,
-                                Some(endpoint_id),
-                            );
-                        } else if let Some(ref old) = current_model {
-                            insert_notification_with_endpoint_id(
-                                &conn,
-                                "model_changed",
-                                &format!("Device model updated: {}", model),
-                                Some(&format!("Previous: {}", old)),
-                                None,
-                                Some(endpoint_id),
-                            );
-                        }
-                    }
-                }
-                // If we got a friendly name from SSDP, save it
-                // Update if empty OR if new name is more specific
-                if let Some(ref friendly) = ssdp.friendly_name {
-                    let current_friendly: Option<String> = conn
-                        .query_row(
-                            "SELECT ssdp_friendly_name FROM endpoints WHERE id = ?1",
-                            params![endpoint_id],
-                            |row| row.get(0),
-                        )
-                        .ok()
-                        .flatten();
+#[post("/api/endpoint/snmp-credential")]
+pub async fn set_endpoint_snmp_credential(
+    _admin: RequireAdmin,
+    body: Json<SetSnmpCredentialRequest>,
+) -> impl Responder {
+    let SetSnmpCredentialRequest {
+        endpoint_name,
+        community,
+    } = body.into_inner();
+    let community = community.filter(|c| !c.trim().is_empty());
 
-                    let should_update = match &current_friendly {
-                        None => true,
-                        Some(current) if current.is_empty() => true,
-                        Some(current) => is_more_specific_model(friendly, current),
-                    };
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        let Some(endpoint_id) = find_endpoint_id_by_name(&conn, &endpoint_name) else {
+            return Ok(None);
+        };
+        set_snmp_credential(&conn, endpoint_id, community.as_deref()).map_err(|e| e.to_string())?;
 
-                    if should_update {
-                        let _ = conn.execute(
-                            "UPDATE endpoints SET ssdp_friendly_name = ?1 WHERE id = ?2",
-                            params![friendly, endpoint_id],
-                        );
-                    }
-                }
+        // The notification says what changed, never the community itself
+        let title = if community.is_some() {
+            format!("SNMP credential set for {}", endpoint_name)
+        } else {
+            format!("SNMP credential cleared for {}", endpoint_name)
+        };
+        insert_notification_with_endpoint_id(
+            &conn,
+            "snmp_credential_changed",
+            &title,
+            None,
+            Some(&endpoint_name),
+            Some(endpoint_id),
+        );
+        Ok::<_, String>(Some((title, community.is_some())))
+    })
+    .await;
 
-                // If endpoint still has no valid name, set it from SSDP friendly name or model
-                try_set_endpoint_name_from_discovery(&conn, endpoint_id,
-                    ssdp.friendly_name.as_deref().or(ssdp.model_name.as_deref()));
-            }
+    match result {
+        Ok(Ok(Some((message, has_credential)))) => {
+            HttpResponse::Ok().json(SnmpCredentialResponse {
+                success: true,
+                message,
+                has_credential,
+            })
         }
-        ScanResult::Ndp(ndp) => {
-            let ip_str = ndp.ip.to_string();
-            let mac_str = ndp.mac.to_string();
-            if let Ok((endpoint_id, is_new)) = EndPoint::get_or_insert_endpoint(
-                &conn,
-                Some(mac_str.clone()),
-                Some(ip_str.clone()),
-                None,
-                &[],
-            ) {
-                if is_new {
-                    insert_notification_with_endpoint_id(
-                        &conn,
-                        "endpoint_discovered",
-                        &format!("New device discovered: {}", ip_str),
-                        Some(&format!("MAC: {} (NDP)", mac_str)),
-                        Some(&ip_str),
-                        Some(endpoint_id),
-                    );
-                }
+        Ok(Ok(None)) => HttpResponse::NotFound().json(SnmpCredentialResponse {
+            success: false,
+            message: "Endpoint not found".to_string(),
+            has_credential: false,
+        }),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(SnmpCredentialResponse {
+            success: false,
+            message: format!("Database error: {}", e),
+            has_credential: false,
+        }),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to save SNMP credential"),
+    }
+}
 
-                insert_scan_result(
-                    &conn,
-                    endpoint_id,
-                    "ndp",
-                    Some(ndp.response_time_ms as i64),
-                    None,
-                )?;
-            }
-        }
-        ScanResult::NetBios(netbios) => {
-            let ip_str = netbios.ip.to_string();
-            // For NetBIOS (no MAC from packet), only record if endpoint already exists
-            if let Some(endpoint_id) = find_existing_endpoint_by_ip(&conn, &ip_str) {
-                let details = serde_json::json!({
-                    "netbios_name": netbios.netbios_name,
-                    "group_name": netbios.group_name,
-                    "mac": netbios.mac,
+#[derive(Deserialize)]
+pub struct ProbeEndpointRequest {
+    endpoint_name: String,
+}
+
+#[derive(Serialize)]
+pub struct ProbeEndpointResponse {
+    success: bool,
+    message: String,
+    snmp_info: Option<SnmpProbeInfo>,
+    netbios_name: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SnmpProbeInfo {
+    sys_descr: Option<String>,
+    sys_name: Option<String>,
+    sys_location: Option<String>,
+}
+
+/// Probe an endpoint for device information (SNMP, NetBIOS)
+#[post("/api/endpoint/probe")]
+pub async fn probe_endpoint(
+    _admin: RequireAdmin,
+    body: Json<ProbeEndpointRequest>,
+) -> impl Responder {
+    use crate::scanner::netbios::NetBiosScanner;
+    use crate::scanner::snmp::SnmpScanner;
+
+    let config = get_scan_manager().get_config().await;
+    let conn = new_connection();
+
+    // Get IPs for this endpoint
+    let ips: Vec<String> = conn
+        .prepare(&format!(
+            "SELECT DISTINCT ea.ip FROM endpoints e
+             JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
+             WHERE {} = ?1 COLLATE NOCASE
+             AND ea.ip IS NOT NULL AND ea.ip != ''",
+            DISPLAY_NAME_SQL
+        ))
+        .and_then(|mut stmt| {
+            stmt.query_map([&body.endpoint_name], |row| row.get(0))
+                .map(|rows| rows.filter_map(|r| r.ok()).collect())
+        })
+        .unwrap_or_default();
+
+    if ips.is_empty() {
+        return HttpResponse::NotFound().json(ProbeEndpointResponse {
+            success: false,
+            message: format!("No IPs found for endpoint '{}'", body.endpoint_name),
+            snmp_info: None,
+            netbios_name: None,
+        });
+    }
+
+    // Get endpoint ID for saving results
+    let endpoint_id = find_endpoint_id_by_name(&conn, &body.endpoint_name);
+
+    let mut snmp_info = None;
+    let mut netbios_name = None;
+
+    // Probe each IP
+    for ip_str in &ips {
+        if let Ok(ip) = ip_str.parse::<std::net::Ipv4Addr>() {
+            // SNMP probe
+            if snmp_info.is_none() {
+                let mut snmp_scanner =
+                    SnmpScanner::new().with_timeout(config.probe_timeout_ms(ScanType::SNMP, 3000));
+                if let Ok(credentials) = load_snmp_credentials(&conn) {
+                    snmp_scanner = snmp_scanner.with_credentials(credentials);
+                }
+                if let Some(result) = snmp_scanner.query_ip(ip) {
+                    // Save to database
+                    if let Some(eid) = endpoint_id {
+                        let details = snmp_result_details(&result);
+                        let _ = insert_scan_result(
+                            &conn,
+                            eid,
+                            ip_str,
+                            "snmp",
+                            None,
+                            Some(&details.to_string()),
+                        );
+
+                        // Extract and save vendor/model from sysDescr
+                        if let Some(ref sys_descr) = result.sys_descr {
+                            let (vendor, model) = parse_snmp_sys_descr(sys_descr);
+                            if let Some(v) = &vendor {
+                                match conn.execute(
+                                    "UPDATE endpoints SET snmp_vendor = ?1 WHERE id = ?2 AND (snmp_vendor IS NULL OR snmp_vendor = '')",
+                                    params![v, eid],
+                                ) {
+                                    Ok(rows) if rows > 0 => {
+                                        insert_notification_with_endpoint_id(
+                                            &conn, "vendor_identified",
+                                            &format!("Vendor identified: {}", v),
+                                            None, None, Some(eid),
+                                        );
+                                    }
+                                    Err(e) => eprintln!("Failed to save SNMP vendor: {}", e),
+                                    _ => {}
+                                }
+                            }
+                            if let Some(m) = &model {
+                                match conn.execute(
+                                    "UPDATE endpoints SET snmp_model = ?1 WHERE id = ?2 AND (snmp_model IS NULL OR snmp_model = '')",
+                                    params![m, eid],
+                                ) {
+                                    Ok(rows) if rows > 0 => {
+                                        insert_notification_with_endpoint_id(
+                                            &conn, "model_identified",
+                                            &format!("Device model identified: {}", m),
+                                            None, None, Some(eid),
+                                        );
+                                    }
+                                    Err(e) => eprintln!("Failed to save SNMP model: {}", e),
+                                    _ => {}
+                                }
+                            }
+                        }
+
+                        // Update endpoint name from sysName if current name is just an IP
+                        if let Some(ref sys_name) = result.sys_name
+                            && !sys_name.is_empty()
+                        {
+                            HostnameResolver::record(ip_str, sys_name, HostnameSource::Snmp);
+                            let _ = conn.execute(
+                                "UPDATE endpoints SET name = ?1 WHERE id = ?2 AND (name = ?3 OR name GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*')",
+                                params![sys_name, eid, ip_str],
+                            );
+                        }
+                    }
+
+                    snmp_info = Some(SnmpProbeInfo {
+                        sys_descr: result.sys_descr,
+                        sys_name: result.sys_name,
+                        sys_location: result.sys_location,
+                    });
+                }
+            }
+
+            // NetBIOS probe
+            if netbios_name.is_none() {
+                let netbios_scanner = NetBiosScanner::new()
+                    .with_timeout(config.probe_timeout_ms(ScanType::NETBIOS, 2000));
+                if let Some(result) = netbios_scanner.query_ip(ip) {
+                    HostnameResolver::record(ip_str, &result.netbios_name, HostnameSource::NetBios);
+                    if let Some(eid) = endpoint_id {
+                        // Save hostname
+                        let _ = conn.execute(
+                            "INSERT OR IGNORE INTO endpoint_attributes (endpoint_id, ip, hostname)
+                             VALUES (?1, ?2, ?3)
+                             ON CONFLICT(endpoint_id, ip, mac) DO UPDATE SET hostname = ?3
+                             WHERE hostname IS NULL OR hostname = ''",
+                            params![eid, ip_str, result.netbios_name],
+                        );
+                    }
+                    netbios_name = Some(result.netbios_name);
+                }
+            }
+        }
+    }
+
+    let found_something = snmp_info.is_some() || netbios_name.is_some();
+    HttpResponse::Ok().json(ProbeEndpointResponse {
+        success: found_something,
+        message: if found_something {
+            "Probe completed".to_string()
+        } else {
+            "No device info discovered".to_string()
+        },
+        snmp_info,
+        netbios_name,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct DeleteEndpointRequest {
+    endpoint_name: String,
+    /// Skip the archive and remove the endpoint immediately (not restorable)
+    #[serde(default)]
+    permanent: bool,
+}
+
+#[derive(Serialize)]
+pub struct DeleteEndpointResponse {
+    success: bool,
+    message: String,
+}
+
+/// Delete an endpoint and all associated data (communications, attributes, scan results).
+/// Unless `permanent` is set, the endpoint is archived first and can be restored
+/// until `archived_endpoint_retention_days` passes.
+#[post("/api/endpoint/delete")]
+pub async fn delete_endpoint(
+    _admin: RequireAdmin,
+    body: Json<DeleteEndpointRequest>,
+) -> impl Responder {
+    let conn = new_connection();
+
+    // First, find the endpoint ID(s) matching the name
+    let endpoint_ids: Vec<i64> = match conn.prepare(&format!(
+        "SELECT DISTINCT e.id FROM endpoints e
+         LEFT JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
+         WHERE {} = ?1 COLLATE NOCASE
+            OR LOWER(ea.hostname) = LOWER(?1)
+            OR LOWER(ea.ip) = LOWER(?1)",
+        DISPLAY_NAME_SQL
+    )) {
+        Ok(mut stmt) => match stmt.query_map([&body.endpoint_name], |row| row.get(0)) {
+            Ok(rows) => rows.filter_map(|r| r.ok()).collect(),
+            Err(e) => {
+                eprintln!("Error querying for endpoint to delete: {}", e);
+                return HttpResponse::InternalServerError().json(DeleteEndpointResponse {
+                    success: false,
+                    message: format!("Database query error: {}", e),
                 });
-                insert_scan_result(
-                    &conn,
-                    endpoint_id,
-                    "netbios",
-                    None,
-                    Some(&details.to_string()),
-                )?;
+            }
+        },
+        Err(e) => {
+            eprintln!("Error preparing delete query: {}", e);
+            return HttpResponse::InternalServerError().json(DeleteEndpointResponse {
+                success: false,
+                message: format!("Database error: {}", e),
+            });
+        }
+    };
 
-                // Save NetBIOS name to endpoint if not already set
-                let _ = conn.execute(
-                    "UPDATE endpoints SET netbios_name = ?1 WHERE id = ?2 AND (netbios_name IS NULL OR netbios_name = '')",
-                    params![netbios.netbios_name, endpoint_id],
-                );
+    if endpoint_ids.is_empty() {
+        return HttpResponse::NotFound().json(DeleteEndpointResponse {
+            success: false,
+            message: format!("Endpoint '{}' not found", body.endpoint_name),
+        });
+    }
 
-                // Also update endpoint name if it's currently just an IP address
-                let _ = conn.execute(
-                    "UPDATE endpoints SET name = ?1 WHERE id = ?2 AND (name = ?3 OR name GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*')",
-                    params![netbios.netbios_name, endpoint_id, ip_str],
-                );
+    // Archive everything first so a failed snapshot never leaves a partial delete
+    let mut archived = 0;
+    if !body.permanent {
+        for endpoint_id in &endpoint_ids {
+            if let Err(e) = EndPoint::archive_endpoint(&conn, *endpoint_id, &body.endpoint_name) {
+                eprintln!("Error archiving endpoint {}: {}", endpoint_id, e);
+                return HttpResponse::InternalServerError().json(DeleteEndpointResponse {
+                    success: false,
+                    message: format!("Failed to archive endpoint before delete: {}", e),
+                });
             }
+            archived += 1;
+        }
+    }
+
+    // Delete in order to respect foreign key constraints
+    let mut updated_comms = 0;
+    let mut deleted_attrs = 0;
+    let mut deleted_scans = 0;
+    let mut deleted_endpoints = 0;
+
+    for endpoint_id in &endpoint_ids {
+        // Nullify this endpoint's ID in communications instead of deleting them
+        // This preserves communication history for other endpoints
+        updated_comms += conn
+            .execute(
+                "UPDATE communications SET src_endpoint_id = NULL WHERE src_endpoint_id = ?1",
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+        updated_comms += conn
+            .execute(
+                "UPDATE communications SET dst_endpoint_id = NULL WHERE dst_endpoint_id = ?1",
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+
+        // Delete scan results
+        deleted_scans += conn
+            .execute(
+                "DELETE FROM scan_results WHERE endpoint_id = ?1",
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+
+        // Delete endpoint attributes
+        deleted_attrs += conn
+            .execute(
+                "DELETE FROM endpoint_attributes WHERE endpoint_id = ?1",
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+
+        // Delete open ports
+        conn.execute(
+            "DELETE FROM open_ports WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete scan results
+        conn.execute(
+            "DELETE FROM scan_results WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete the endpoint's own SNMP credential
+        conn.execute(
+            "DELETE FROM snmp_credentials WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete SMB shares
+        conn.execute(
+            "DELETE FROM smb_shares WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete the cached web page title and favicon
+        conn.execute(
+            "DELETE FROM endpoint_web_info WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete SSH host keys and TLS certificates
+        for table in [
+            "endpoint_ssh_host_keys",
+            "endpoint_tls_certificates",
+            "endpoint_host_key_checks",
+        ] {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+        }
+
+        // Delete printer supply and page count history
+        for table in [
+            "printer_readings",
+            "printer_supply_levels",
+            "printer_supply_alerts",
+        ] {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+        }
+
+        // Delete NAS volume and disk health
+        for table in [
+            "storage_devices",
+            "storage_volumes",
+            "storage_disks",
+            "storage_alerts",
+        ] {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+        }
+
+        // Delete UPS readings and power state
+        for table in ["ups_readings", "ups_power_state"] {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+        }
+
+        // Delete the UPnP and mDNS identifiers bound to the endpoint
+        conn.execute(
+            "DELETE FROM device_identities WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete the endpoint's own wattage and energy history
+        for table in ["endpoint_power", "energy_daily"] {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+        }
+
+        // Delete the endpoint's own icon and color
+        conn.execute(
+            "DELETE FROM endpoint_appearance WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete the endpoint's tags and trust
+        for table in ["endpoint_tags", "endpoint_trust"] {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE endpoint_id = ?1", table),
+                params![endpoint_id],
+            )
+            .unwrap_or(0);
+        }
+
+        // Unassign the endpoint from its owner
+        conn.execute(
+            "DELETE FROM endpoint_owners WHERE endpoint_id = ?1",
+            params![endpoint_id],
+        )
+        .unwrap_or(0);
+
+        // Delete the endpoint itself
+        deleted_endpoints += conn
+            .execute("DELETE FROM endpoints WHERE id = ?1", params![endpoint_id])
+            .unwrap_or(0);
+    }
+
+    insert_notification(
+        &conn,
+        "endpoint_deleted",
+        &format!("Endpoint '{}' deleted", body.endpoint_name),
+        Some(&format!(
+            "{} endpoint(s), {} attribute(s), {} scan result(s) removed",
+            deleted_endpoints, deleted_attrs, deleted_scans
+        )),
+        Some(&body.endpoint_name),
+    );
+
+    HttpResponse::Ok().json(DeleteEndpointResponse {
+        success: true,
+        message: format!(
+            "Deleted endpoint '{}': {} endpoint(s), {} attribute(s), {} scan result(s) (preserved {} communication records, {} archived for restore)",
+            body.endpoint_name, deleted_endpoints, deleted_attrs, deleted_scans, updated_comms, archived
+        ),
+    })
+}
+
+/// Remove an endpoint's traffic history (communications, usage rollups, DNS names only it
+/// looked up) while keeping the endpoint, its addresses and anything set by hand
+#[post("/api/endpoint/{name}/purge-traffic")]
+pub async fn purge_endpoint_traffic_api(
+    _admin: RequireAdmin,
+    path: actix_web::web::Path<String>,
+) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let name = endpoint_name.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        let Some(endpoint_id) = find_endpoint_id_by_name(&conn, &name) else {
+            return Ok(None);
+        };
+        let removed = purge_endpoint_traffic(&conn, endpoint_id).map_err(|e| e.to_string())?;
+        let total: usize = removed.values().sum();
+        insert_notification_with_endpoint_id(
+            &conn,
+            "endpoint_traffic_purged",
+            &format!("Traffic history for '{}' purged", name),
+            Some(&format!("{} rows removed", total)),
+            Some(&name),
+            Some(endpoint_id),
+        );
+        Ok::<_, String>(Some(removed))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(removed))) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "endpoint": endpoint_name,
+            "removed": removed,
+        })),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": format!("Endpoint '{}' not found", endpoint_name)
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Failed to purge traffic"
+        })),
+    }
+}
+
+/// List soft-deleted endpoints that can still be restored
+#[get("/api/endpoints/archived")]
+pub async fn get_archived_endpoints() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection();
+        let retention_days = get_setting_i64("archived_endpoint_retention_days", 30);
+        EndPoint::get_archived_endpoints(&conn).map(|archived| (archived, retention_days))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((archived, retention_days))) => HttpResponse::Ok().json(serde_json::json!({
+            "archived": archived,
+            "retention_days": retention_days,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch archived endpoints"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RestoreEndpointRequest {
+    archive_id: i64,
+}
+
+/// Restore a soft-deleted endpoint from the archive
+#[post("/api/endpoint/restore")]
+pub async fn restore_endpoint(
+    _admin: RequireAdmin,
+    body: Json<RestoreEndpointRequest>,
+) -> impl Responder {
+    let archive_id = body.archive_id;
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let name: Option<String> = conn
+            .query_row(
+                "SELECT name FROM archived_endpoints WHERE id = ?1",
+                [archive_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .flatten();
+        let restored = EndPoint::restore_archived_endpoint(&conn, archive_id);
+        if let Ok(Some(endpoint_id)) = restored {
+            insert_notification_with_endpoint_id(
+                &conn,
+                "endpoint_restored",
+                &format!(
+                    "Endpoint '{}' restored",
+                    name.as_deref().unwrap_or("unknown")
+                ),
+                None,
+                name.as_deref(),
+                Some(endpoint_id),
+            );
+        }
+        restored
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(endpoint_id))) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "endpoint_id": endpoint_id,
+            "message": "Endpoint restored",
+        })),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": format!("Archived endpoint {} not found", archive_id),
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Failed to restore endpoint: {}", e),
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Restore task failed",
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MergeEndpointsRequest {
+    /// The endpoint to keep (target) - can be name, custom_name, hostname, or IP
+    target: String,
+    /// The endpoint to merge and delete (source) - can be name, custom_name, hostname, or IP
+    source: String,
+}
+
+#[derive(Serialize)]
+pub struct MergeEndpointsResponse {
+    success: bool,
+    message: String,
+}
+
+/// Merge two endpoints into one, keeping the target and deleting the source
+/// All communications, attributes, scan results, and ports from source are moved to target
+#[post("/api/endpoint/merge")]
+pub async fn merge_endpoints(
+    _admin: RequireAdmin,
+    body: Json<MergeEndpointsRequest>,
+) -> impl Responder {
+    let conn = new_connection();
+
+    // Find the target endpoint ID
+    let target_id: Option<i64> = match conn.prepare(&format!(
+        "SELECT DISTINCT e.id FROM endpoints e
+         LEFT JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
+         WHERE {} = ?1 COLLATE NOCASE
+            OR LOWER(ea.hostname) = LOWER(?1)
+            OR LOWER(ea.ip) = LOWER(?1)
+         LIMIT 1",
+        DISPLAY_NAME_SQL
+    )) {
+        Ok(mut stmt) => stmt.query_row([&body.target], |row| row.get(0)).ok(),
+        Err(e) => {
+            eprintln!("Error preparing target query: {}", e);
+            return HttpResponse::InternalServerError().json(MergeEndpointsResponse {
+                success: false,
+                message: format!("Database error: {}", e),
+            });
+        }
+    };
+
+    let target_id = match target_id {
+        Some(id) => id,
+        None => {
+            return HttpResponse::NotFound().json(MergeEndpointsResponse {
+                success: false,
+                message: format!("Target endpoint '{}' not found", body.target),
+            });
+        }
+    };
+
+    // Find the source endpoint ID
+    let source_id: Option<i64> = match conn.prepare(&format!(
+        "SELECT DISTINCT e.id FROM endpoints e
+         LEFT JOIN endpoint_attributes ea ON e.id = ea.endpoint_id
+         WHERE {} = ?1 COLLATE NOCASE
+            OR LOWER(ea.hostname) = LOWER(?1)
+            OR LOWER(ea.ip) = LOWER(?1)
+         LIMIT 1",
+        DISPLAY_NAME_SQL
+    )) {
+        Ok(mut stmt) => stmt.query_row([&body.source], |row| row.get(0)).ok(),
+        Err(e) => {
+            eprintln!("Error preparing source query: {}", e);
+            return HttpResponse::InternalServerError().json(MergeEndpointsResponse {
+                success: false,
+                message: format!("Database error: {}", e),
+            });
+        }
+    };
+
+    let source_id = match source_id {
+        Some(id) => id,
+        None => {
+            return HttpResponse::NotFound().json(MergeEndpointsResponse {
+                success: false,
+                message: format!("Source endpoint '{}' not found", body.source),
+            });
+        }
+    };
+
+    // Check they're not the same endpoint
+    if target_id == source_id {
+        return HttpResponse::BadRequest().json(MergeEndpointsResponse {
+            success: false,
+            message: "Cannot merge an endpoint with itself".to_string(),
+        });
+    }
+
+    // Snapshot pre-merge state so the merge can be undone later
+    let snapshot = match EndPoint::capture_merge_snapshot(&conn, target_id, source_id) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            eprintln!("Failed to capture merge snapshot: {}", e);
+            None
+        }
+    };
+
+    // Perform the merge
+    let mut merged_comms = 0;
+    let mut merged_attrs = 0;
+    let mut merged_ports = 0;
+    let mut merged_scans = 0;
+
+    // Merge communications
+    merged_comms += conn
+        .execute(
+            "UPDATE communications SET src_endpoint_id = ?1 WHERE src_endpoint_id = ?2",
+            params![target_id, source_id],
+        )
+        .unwrap_or(0);
+    merged_comms += conn
+        .execute(
+            "UPDATE communications SET dst_endpoint_id = ?1 WHERE dst_endpoint_id = ?2",
+            params![target_id, source_id],
+        )
+        .unwrap_or(0);
+
+    // Merge endpoint attributes (INSERT OR IGNORE to skip duplicates)
+    merged_attrs += conn
+        .execute(
+            "INSERT OR IGNORE INTO endpoint_attributes (created_at, endpoint_id, mac, ip, hostname, dhcp_client_id, dhcp_vendor_class)
+             SELECT created_at, ?1, mac, ip, hostname, dhcp_client_id, dhcp_vendor_class
+             FROM endpoint_attributes
+             WHERE endpoint_id = ?2",
+            params![target_id, source_id],
+        )
+        .unwrap_or(0);
+
+    // Delete source attributes after copying
+    conn.execute(
+        "DELETE FROM endpoint_attributes WHERE endpoint_id = ?1",
+        params![source_id],
+    )
+    .unwrap_or(0);
+
+    // Merge open ports (UPDATE OR IGNORE to skip duplicates)
+    merged_ports += conn
+        .execute(
+            "UPDATE OR IGNORE open_ports SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+            params![target_id, source_id],
+        )
+        .unwrap_or(0);
+
+    // Delete any remaining source ports (duplicates)
+    conn.execute(
+        "DELETE FROM open_ports WHERE endpoint_id = ?1",
+        params![source_id],
+    )
+    .unwrap_or(0);
+
+    // Merge scan results
+    merged_scans += conn
+        .execute(
+            "UPDATE scan_results SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+            params![target_id, source_id],
+        )
+        .unwrap_or(0);
+    let _ = EndPoint::reassign_endpoint_data(&conn, source_id, target_id);
+
+    // Copy over any useful metadata from source that target doesn't have
+    let _ = conn.execute(
+        "UPDATE endpoints SET
+            ssdp_model = COALESCE((SELECT ssdp_model FROM endpoints WHERE id = ?1), (SELECT ssdp_model FROM endpoints WHERE id = ?2)),
+            ssdp_friendly_name = COALESCE((SELECT ssdp_friendly_name FROM endpoints WHERE id = ?1), (SELECT ssdp_friendly_name FROM endpoints WHERE id = ?2)),
+            netbios_name = COALESCE((SELECT netbios_name FROM endpoints WHERE id = ?1), (SELECT netbios_name FROM endpoints WHERE id = ?2)),
+            workgroup = COALESCE((SELECT workgroup FROM endpoints WHERE id = ?1), (SELECT workgroup FROM endpoints WHERE id = ?2)),
+            auto_device_type = COALESCE((SELECT auto_device_type FROM endpoints WHERE id = ?1), (SELECT auto_device_type FROM endpoints WHERE id = ?2))
+         WHERE id = ?1",
+        params![target_id, source_id],
+    );
+
+    // Reassign notifications so they point to the surviving endpoint
+    let _ = conn.execute(
+        "UPDATE notifications SET endpoint_id = ?1 WHERE endpoint_id = ?2",
+        params![target_id, source_id],
+    );
+
+    // Delete the source endpoint
+    let deleted = conn
+        .execute("DELETE FROM endpoints WHERE id = ?1", params![source_id])
+        .unwrap_or(0);
+
+    if deleted > 0 {
+        if let Some(snapshot) = snapshot
+            && let Err(e) = EndPoint::record_merge(&conn, snapshot, &body.source)
+        {
+            eprintln!("Failed to record merge history: {}", e);
+        }
+
+        insert_notification(
+            &conn,
+            "endpoints_merged",
+            &format!("Merged '{}' into '{}'", body.source, body.target),
+            Some(&format!(
+                "{} communication(s), {} attribute(s), {} port(s), {} scan result(s)",
+                merged_comms, merged_attrs, merged_ports, merged_scans
+            )),
+            Some(&body.target),
+        );
+
+        HttpResponse::Ok().json(MergeEndpointsResponse {
+            success: true,
+            message: format!(
+                "Merged '{}' into '{}': {} communication(s), {} attribute(s), {} port(s), {} scan result(s)",
+                body.source, body.target, merged_comms, merged_attrs, merged_ports, merged_scans
+            ),
+        })
+    } else {
+        HttpResponse::InternalServerError().json(MergeEndpointsResponse {
+            success: false,
+            message: "Failed to delete source endpoint after merge".to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UnmergeEndpointRequest {
+    /// The endpoint a merge was performed into - can be name, custom_name, hostname, or IP
+    endpoint: String,
+    /// Optional merge history id; must be the most recent merge into the endpoint
+    merge_id: Option<i64>,
+}
+
+/// Undo the most recent merge into an endpoint, restoring the source endpoint
+/// and moving its communications, attributes, ports, and scan results back
+#[post("/api/endpoint/unmerge")]
+pub async fn unmerge_endpoint(
+    _admin: RequireAdmin,
+    body: Json<UnmergeEndpointRequest>,
+) -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().map_err(|e| format!("Database error: {}", e))?;
+
+        let target_id = resolve_identifier_to_endpoint_ids(&conn, &body.endpoint)
+            .first()
+            .copied()
+            .ok_or_else(|| format!("Endpoint '{}' not found", body.endpoint))?;
+
+        if let Some(merge_id) = body.merge_id {
+            let latest = EndPoint::get_merge_history(&conn, target_id)
+                .map_err(|e| format!("Database error: {}", e))?
+                .into_iter()
+                .find(|m| m.target_id == target_id && m.undone_at.is_none());
+            if latest.as_ref().map(|m| m.id) != Some(merge_id) {
+                return Err(format!(
+                    "Merge {} is not the most recent merge into '{}'",
+                    merge_id, body.endpoint
+                ));
+            }
+        }
+
+        let undone = EndPoint::undo_last_merge(&conn, target_id)
+            .map_err(|e| format!("Failed to undo merge: {}", e))?
+            .ok_or_else(|| format!("No merge to undo for '{}'", body.endpoint))?;
+
+        let source_name = undone.source_name.clone().unwrap_or_default();
+        insert_notification_with_endpoint_id(
+            &conn,
+            "endpoints_unmerged",
+            &format!("Split '{}' back out of '{}'", source_name, body.endpoint),
+            None,
+            Some(&source_name),
+            Some(undone.source_id),
+        );
+
+        Ok(undone)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(undone)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Restored '{}' (undid merge #{})",
+                undone.source_name.as_deref().unwrap_or("endpoint"),
+                undone.id
+            ),
+            "merge": undone,
+        })),
+        Ok(Err(message)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": message,
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Unmerge task failed",
+        })),
+    }
+}
+
+/// List merges into (or out of) an endpoint, newest first
+#[get("/api/endpoint/{name}/merge-history")]
+pub async fn get_endpoint_merge_history(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let endpoint_id = resolve_identifier_to_endpoint_ids(&conn, &endpoint_name)
+            .first()
+            .copied()?;
+        EndPoint::get_merge_history(&conn, endpoint_id).ok()
+    })
+    .await;
+
+    match result {
+        Ok(Some(history)) => HttpResponse::Ok().json(serde_json::json!({ "history": history })),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch merge history"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LinkEndpointsRequest {
+    /// Name, IP or MAC of each endpoint; MACs pick out interfaces that share a name
+    endpoint: String,
+    other: String,
+}
+
+#[derive(Deserialize)]
+pub struct UnlinkEndpointRequest {
+    endpoint: String,
+}
+
+/// Link two endpoints as interfaces of one device (wired and wireless). Both keep their
+/// own history; details and traffic are shown for the device as a whole.
+#[post("/api/endpoint/link")]
+pub async fn link_endpoints_api(
+    _admin: RequireAdmin,
+    body: Json<LinkEndpointsRequest>,
+) -> impl Responder {
+    let LinkEndpointsRequest { endpoint, other } = body.into_inner();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let resolve = |identifier: &str| {
+            resolve_identifier_to_endpoint_ids(&conn, identifier)
+                .first()
+                .copied()
+                .ok_or_else(|| format!("Endpoint '{}' not found", identifier))
+        };
+        let endpoint_id = resolve(&endpoint)?;
+        let other_id = resolve(&other)?;
+        if endpoint_id == other_id {
+            return Err("An endpoint can't be linked to itself".to_string());
+        }
+        link_endpoints(&conn, endpoint_id, other_id, chrono::Utc::now().timestamp())
+            .map_err(|e| format!("Database error: {}", e))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(device_id)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Endpoints linked",
+            "id": device_id,
+        })),
+        Ok(Err(message)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": message,
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Failed to link endpoints",
+        })),
+    }
+}
+
+#[post("/api/endpoint/unlink")]
+pub async fn unlink_endpoint_api(
+    _admin: RequireAdmin,
+    body: Json<UnlinkEndpointRequest>,
+) -> impl Responder {
+    let endpoint = body.into_inner().endpoint;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let endpoint_id = resolve_identifier_to_endpoint_ids(&conn, &endpoint)
+            .first()
+            .copied()?;
+        unlink_endpoint(&conn, endpoint_id).ok()
+    })
+    .await;
+
+    match result {
+        Ok(Some(true)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Endpoint unlinked",
+        })),
+        Ok(_) => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": "Endpoint is not linked",
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Failed to unlink endpoint",
+        })),
+    }
+}
+
+/// Endpoints that report the same hostname from different MACs and might be one device
+#[get("/api/endpoint/link-suggestions")]
+pub async fn get_endpoint_link_suggestions() -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        let suggestions = get_link_suggestions(&conn)?;
+        Ok::<_, rusqlite::Error>(
+            suggestions
+                .into_iter()
+                .map(|suggestion| {
+                    let endpoints: Vec<LinkedEndpointView> = suggestion
+                        .endpoint_ids
+                        .iter()
+                        .filter_map(|id| linked_endpoint_view(&conn, *id, 525600))
+                        .collect();
+                    serde_json::json!({
+                        "hostname": suggestion.hostname,
+                        "endpoints": endpoints,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        )
+    })
+    .await;
+
+    match result {
+        Ok(Ok(suggestions)) => {
+            HttpResponse::Ok().json(serde_json::json!({ "suggestions": suggestions }))
+        }
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to find link suggestions"
+        })),
+    }
+}
+
+/// Everything recorded about an endpoint, oldest first
+#[get("/api/endpoint/{name}/timeline")]
+pub async fn get_endpoint_timeline_events(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let endpoint_id = resolve_identifier_to_endpoint_ids(&conn, &endpoint_name)
+            .first()
+            .copied()?;
+        get_endpoint_timeline(&conn, endpoint_id).ok()
+    })
+    .await;
+
+    match result {
+        Ok(Some(events)) => HttpResponse::Ok().json(serde_json::json!({ "events": events })),
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build timeline"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    /// Days to report on, counting back from now (default 7)
+    days: Option<i64>,
+    /// Number of top destinations (default 20)
+    limit: Option<usize>,
+}
+
+/// Household usage report for an endpoint: hours online per day, top internet
+/// destinations, and streaming/gaming/social/browsing shares
+#[get("/api/endpoint/{name}/usage")]
+pub async fn get_endpoint_usage(
+    path: actix_web::web::Path<String>,
+    query: Query<UsageQuery>,
+) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let days = query.days.unwrap_or(7).clamp(1, 90);
+    let limit = query.limit.unwrap_or(20).clamp(1, 200);
+    let since = chrono::Utc::now().timestamp() - days * 24 * 60 * 60;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        match resolve_identifier_to_endpoint_ids(&conn, &endpoint_name).first() {
+            Some(&endpoint_id) => get_usage_report(&conn, endpoint_id, since, limit),
+            None => Ok(None),
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(report))) => HttpResponse::Ok().json(report),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build usage report"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PrinterQuery {
+    /// Days of history to return, counting back from now (default 30)
+    days: Option<i64>,
+}
+
+/// A printer's supply levels, page count and status from SNMP polling, with their history
+#[get("/api/endpoint/{name}/printer")]
+pub async fn get_endpoint_printer(
+    path: actix_web::web::Path<String>,
+    query: Query<PrinterQuery>,
+) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let days = query.days.unwrap_or(30).clamp(1, 365);
+    let since = chrono::Utc::now().timestamp() - days * 24 * 60 * 60;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        match find_endpoint_id_by_name(&conn, &endpoint_name) {
+            Some(endpoint_id) => get_printer_report(&conn, endpoint_id, since).map(Some),
+            None => Ok(None),
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(report))) => HttpResponse::Ok().json(report),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build printer report"
+        })),
+    }
+}
+
+/// A NAS's volume, RAID and pool status and its disks' health from SNMP polling
+#[get("/api/endpoint/{name}/storage")]
+pub async fn get_endpoint_storage(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        match find_endpoint_id_by_name(&conn, &endpoint_name) {
+            Some(endpoint_id) => get_storage_report(&conn, endpoint_id).map(Some),
+            None => Ok(None),
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(Some(report)))) => HttpResponse::Ok().json(report),
+        Ok(Ok(Some(None))) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No storage health recorded for this endpoint"
+        })),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build storage report"
+        })),
+    }
+}
+
+/// Storage health of every polled NAS, those with a degraded array first
+#[get("/api/storage")]
+pub async fn get_storage_health() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result()?;
+        get_storage_overview(&conn)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(devices)) => HttpResponse::Ok().json(serde_json::json!({
+            "degraded": devices
+                .iter()
+                .filter(|d| d.volumes.iter().any(|v| v.degraded))
+                .count(),
+            "devices": devices,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build storage overview"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpsQuery {
+    /// Hours of history to return, counting back from now (default 24)
+    hours: Option<i64>,
+}
+
+/// The UPSes read through an endpoint (its own network card, or those its NUT server
+/// serves) with battery charge, runtime, load and power state, and their history
+#[get("/api/endpoint/{name}/ups")]
+pub async fn get_endpoint_ups_api(
+    path: actix_web::web::Path<String>,
+    query: Query<UpsQuery>,
+) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let hours = query.hours.unwrap_or(24).clamp(1, 30 * 24);
+    let since = chrono::Utc::now().timestamp() - hours * 60 * 60;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        match find_endpoint_id_by_name(&conn, &endpoint_name) {
+            Some(endpoint_id) => get_endpoint_ups(&conn, endpoint_id, since).map(Some),
+            None => Ok(None),
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(ups))) => HttpResponse::Ok().json(serde_json::json!({ "ups": ups })),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build UPS report"
+        })),
+    }
+}
+
+/// Every monitored UPS's latest reading, those on battery first
+#[get("/api/ups")]
+pub async fn get_ups_overview_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result()?;
+        get_ups_overview(&conn)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(ups)) => HttpResponse::Ok().json(serde_json::json!({
+            "on_battery": ups.iter().filter(|u| u.on_battery).count(),
+            "ups": ups,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build UPS overview"
+        })),
+    }
+}
+/// Seconds between keep-alive comments on an idle live tail, so proxies don't time it out
+const LIVE_TAIL_KEEPALIVE_SECS: u64 = 15;
+
+/// Stream each new communication involving an endpoint as server-sent events: one JSON
+/// `data:` line per flow with its direction, peer, ports, protocol and bytes. A `lagged`
+/// event reports flows dropped because the client fell behind.
+#[get("/api/endpoint/{name}/live")]
+pub async fn stream_endpoint_live(path: actix_web::web::Path<String>) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let endpoint_ids = tokio::task::spawn_blocking(move || {
+        resolve_identifier_to_endpoint_ids(&new_connection(), &endpoint_name)
+    })
+    .await
+    .unwrap_or_default();
+    if endpoint_ids.is_empty() {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        }));
+    }
+
+    let events = futures_util::stream::unfold(
+        (subscribe_live_flows(), endpoint_ids),
+        |(mut receiver, endpoint_ids)| async move {
+            let keepalive = std::time::Duration::from_secs(LIVE_TAIL_KEEPALIVE_SECS);
+            loop {
+                let event = match tokio::time::timeout(keepalive, receiver.recv()).await {
+                    Err(_) => ": keep-alive\n\n".to_string(),
+                    Ok(Ok(flow)) => match flow.view_for(&endpoint_ids) {
+                        Some(communication) => format!(
+                            "data: {}\n\n",
+                            serde_json::to_string(&communication).unwrap_or_default()
+                        ),
+                        None => continue,
+                    },
+                    Ok(Err(RecvError::Lagged(skipped))) => {
+                        format!("event: lagged\ndata: {}\n\n", skipped)
+                    }
+                    Ok(Err(RecvError::Closed)) => return None,
+                };
+                return Some((
+                    Ok::<_, std::convert::Infallible>(actix_web::web::Bytes::from(event)),
+                    (receiver, endpoint_ids),
+                ));
+            }
+        },
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+/// Internet traffic of every endpoint over the last `days`, grouped by the organization
+/// behind each site, busiest first, with daily series for sparklines
+#[get("/api/internet/summary")]
+pub async fn get_internet_summary_api(query: Query<UsageQuery>) -> impl Responder {
+    let days = query.days.unwrap_or(7).clamp(1, 90);
+    let limit = query.limit.unwrap_or(20).clamp(1, 200);
+    let now = chrono::Utc::now().timestamp();
+    let since = now - days * 24 * 60 * 60;
+
+    let result = tokio::task::spawn_blocking(move || {
+        get_internet_summary(&new_connection(), since, now, limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(summary)) => HttpResponse::Ok().json(summary),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build internet summary"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TimeSeriesQuery {
+    /// bytes, packets, endpoints, or notifications
+    metric: String,
+    /// Range start (unix seconds); `hours` before `end` when missing
+    start: Option<i64>,
+    /// Range end (unix seconds, default now)
+    end: Option<i64>,
+    /// Hours to chart when `start` is missing (default 24)
+    hours: Option<i64>,
+    /// Only this endpoint's traffic and notifications
+    endpoint: Option<String>,
+}
+
+/// A metric over time for charts, per minute, hour, or day depending on the range
+#[get("/api/timeseries")]
+pub async fn get_timeseries_api(query: Query<TimeSeriesQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let Some(metric) = TimeSeriesMetric::parse(&query.metric) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unknown metric '{}'", query.metric)
+        }));
+    };
+    let now = chrono::Utc::now().timestamp();
+    let end = query.end.unwrap_or(now);
+    let start = query
+        .start
+        .unwrap_or_else(|| end - query.hours.unwrap_or(24).max(1) * 60 * 60);
+    if start >= end || end - start > MAX_TIMESERIES_RANGE_SECS {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "The range must be positive and at most {} days",
+                MAX_TIMESERIES_RANGE_SECS / (24 * 60 * 60)
+            )
+        }));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let endpoint_ids = match &query.endpoint {
+            Some(name) => {
+                let ids = resolve_identifier_to_endpoint_ids(&conn, name);
+                if ids.is_empty() {
+                    return Ok(None);
+                }
+                Some(ids)
+            }
+            None => None,
+        };
+        get_time_series(&conn, metric, start, end, endpoint_ids.as_deref(), now).map(Some)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(series))) => HttpResponse::Ok().json(series),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build time series"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TcpQualityQuery {
+    /// Hours to report on, counting back from now (default 24)
+    hours: Option<i64>,
+}
+
+/// TCP handshake round trips and retransmission rate for an endpoint, in total and per
+/// hour, for telling flaky (e.g. poor Wi-Fi) devices apart
+#[get("/api/endpoint/{name}/quality")]
+pub async fn get_endpoint_quality(
+    path: actix_web::web::Path<String>,
+    query: Query<TcpQualityQuery>,
+) -> impl Responder {
+    let endpoint_name = path.into_inner();
+    let hours = query.hours.unwrap_or(24).clamp(1, 24 * 30);
+    let since = chrono::Utc::now().timestamp() - hours * 60 * 60;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        match resolve_identifier_to_endpoint_ids(&conn, &endpoint_name).first() {
+            Some(&endpoint_id) => get_tcp_quality_report(&conn, endpoint_id, since),
+            None => Ok(None),
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(report))) => HttpResponse::Ok().json(report),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build quality report"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct IpHistoryQuery {
+    ip: String,
+    /// Unix seconds or an RFC 3339 timestamp; omit for the address's whole history
+    at: Option<String>,
+}
+
+/// Which endpoint held an IP address at a given time, from ARP/NDP replies and DHCP leases
+#[get("/api/ip-history")]
+pub async fn get_ip_history_api(query: Query<IpHistoryQuery>) -> impl Responder {
+    let ip = query.ip.trim().to_string();
+    if ip.parse::<std::net::IpAddr>().is_err() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Invalid IP address: {}", ip)
+        }));
+    }
+    let at = match query.at.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+        None => None,
+        Some(at) => match at.parse::<i64>().ok().or_else(|| {
+            chrono::DateTime::parse_from_rfc3339(at)
+                .ok()
+                .map(|t| t.timestamp())
+        }) {
+            Some(at) => Some(at),
+            None => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": format!("Invalid time: {}", at)
+                }));
+            }
+        },
+    };
+
+    let lookup_ip = ip.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        EndPoint::get_ip_holders(&new_connection(), &lookup_ip, at)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(holders)) => HttpResponse::Ok().json(serde_json::json!({
+            "ip": ip,
+            "at": at,
+            "holders": holders,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to look up IP history"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DnsServersQuery {
+    /// Limit the report to one endpoint (name, IP, or MAC)
+    endpoint: Option<String>,
+}
+
+/// Which DNS servers each endpoint uses, endpoints bypassing the local resolver first
+#[get("/api/dns-servers")]
+pub async fn get_dns_servers(query: Query<DnsServersQuery>) -> impl Responder {
+    let endpoint = query.endpoint.clone().filter(|e| !e.trim().is_empty());
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let endpoint_id = match &endpoint {
+            Some(name) => match resolve_identifier_to_endpoint_ids(&conn, name).first() {
+                Some(id) => Some(*id),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        get_dns_server_usage(&conn, endpoint_id).map(Some)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(endpoints))) => {
+            HttpResponse::Ok().json(serde_json::json!({ "endpoints": endpoints }))
+        }
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Endpoint not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load DNS server usage"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BlocklistHitsQuery {
+    /// Number of hits to return (default 200)
+    limit: Option<i64>,
+}
+
+/// Imported blocklists, and whether the bundled tracker/malware list is enabled
+#[get("/api/blocklists")]
+pub async fn get_blocklists_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let bundled_enabled = get_setting("blocklist_bundled_enabled").as_deref() == Some("true");
+        get_blocklists(&new_connection()).map(|lists| (lists, bundled_enabled))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((lists, bundled_enabled))) => HttpResponse::Ok().json(serde_json::json!({
+            "lists": lists,
+            "bundled_enabled": bundled_enabled,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load blocklists"
+        })),
+    }
+}
+
+/// Endpoints seen contacting blocklisted domains or addresses, most recent first
+#[get("/api/blocklists/hits")]
+pub async fn get_blocklist_hits_api(query: Query<BlocklistHitsQuery>) -> impl Responder {
+    let limit = query.limit.unwrap_or(200).clamp(1, 5000);
+    let result =
+        tokio::task::spawn_blocking(move || get_blocklist_hits(&new_connection(), limit)).await;
+
+    match result {
+        Ok(Ok(hits)) => HttpResponse::Ok().json(serde_json::json!({ "hits": hits })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load blocklist hits"
+        })),
+    }
+}
+
+/// Import a hosts file or plain domain/IP list (multipart fields "file", "name" and
+/// optionally "category", default "tracker"). Re-importing a name replaces its entries.
+#[post("/api/blocklists/import")]
+pub async fn import_blocklist_api(_admin: RequireAdmin, mut payload: Multipart) -> impl Responder {
+    let mut file_data: Option<Vec<u8>> = None;
+    let mut list_name = String::new();
+    let mut category = String::new();
+
+    while let Some(item) = payload.next().await {
+        let mut field = match item {
+            Ok(f) => f,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "success": false,
+                    "message": format!("Error reading multipart field: {}", e)
+                }));
+            }
+        };
+        let field_name = field.name().unwrap_or("").to_string();
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            match chunk {
+                Ok(bytes) => data.extend_from_slice(&bytes),
+                Err(e) => {
+                    return HttpResponse::BadRequest().json(serde_json::json!({
+                        "success": false,
+                        "message": format!("Error reading upload: {}", e)
+                    }));
+                }
+            }
+        }
+        match field_name.as_str() {
+            "file" => file_data = Some(data),
+            "name" => list_name = String::from_utf8_lossy(&data).trim().to_string(),
+            "category" => category = String::from_utf8_lossy(&data).trim().to_lowercase(),
+            _ => {}
+        }
+    }
+
+    if list_name.is_empty() || list_name == BUNDLED_LIST_NAME {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": "A list name is required and may not be 'bundled'"
+        }));
+    }
+    if category.is_empty() {
+        category = "tracker".to_string();
+    }
+    let entries = match file_data {
+        Some(data) => parse_blocklist(&String::from_utf8_lossy(&data)),
+        None => Vec::new(),
+    };
+    if entries.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": "No domains or addresses found in the uploaded list"
+        }));
+    }
+
+    let name = list_name.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        import_blocklist(&new_connection(), &name, &category, &entries)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(count)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("Imported {} entries into '{}'", count, list_name),
+            "entry_count": count,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DeleteBlocklistRequest {
+    name: String,
+}
+
+/// Remove an imported blocklist
+#[post("/api/blocklists/delete")]
+pub async fn delete_blocklist_api(
+    _admin: RequireAdmin,
+    body: Json<DeleteBlocklistRequest>,
+) -> impl Responder {
+    let list_name = body.into_inner().name;
+    let result =
+        tokio::task::spawn_blocking(move || delete_blocklist(&new_connection(), &list_name)).await;
+
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Blocklist not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete blocklist"
+        })),
+    }
+}
+
+/// Address usage of every local subnet: seen vs. free addresses, gateway, DNS servers
+#[get("/api/subnets")]
+pub async fn get_subnets() -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || get_subnet_summaries(&new_connection())).await;
+
+    match result {
+        Ok(Ok(subnets)) => HttpResponse::Ok().json(serde_json::json!({ "subnets": subnets })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to summarize subnets"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SubnetGridQuery {
+    network: String,
+}
+
+/// Every address of a subnet with the endpoint holding it
+#[get("/api/subnets/grid")]
+pub async fn get_subnet_grid(query: Query<SubnetGridQuery>) -> impl Responder {
+    let network = match query.network.trim().parse::<ipnetwork::Ipv4Network>() {
+        Ok(network) => network,
+        Err(_) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Invalid IPv4 network: {}", query.network)
+            }));
+        }
+    };
+    // Normalize "192.168.1.7/24" to the network itself
+    let Ok(network) = ipnetwork::Ipv4Network::new(network.network(), network.prefix()) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Invalid IPv4 network: {}", query.network)
+        }));
+    };
+    if network.prefix() < 32 && 1u64 << (32 - network.prefix()) > MAX_GRID_ADDRESSES {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "{} is too large for a grid (at most {} addresses)",
+                network, MAX_GRID_ADDRESSES
+            )
+        }));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        load_address_holders(&new_connection()).map(|holders| subnet_grid(network, &holders))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(addresses)) => HttpResponse::Ok().json(serde_json::json!({
+            "network": network.to_string(),
+            "addresses": addresses,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build subnet grid"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProbeModelRequest {
+    ip: String,
+}
+
+#[derive(Serialize)]
+pub struct ProbeModelResponse {
+    success: bool,
+    message: String,
+    model: Option<String>,
+}
+
+/// Probe a device's web interface to detect its model
+#[post("/api/endpoint/probe/model")]
+pub async fn probe_endpoint_model(
+    _admin: RequireAdmin,
+    body: Json<ProbeModelRequest>,
+) -> impl Responder {
+    let ip = body.ip.clone();
+
+    // Try to probe the device for its model (run in blocking thread for immediate execution)
+    let ip_clone = ip.clone();
+    let model = tokio::task::spawn_blocking(move || probe_hp_printer_model_blocking(&ip_clone))
+        .await
+        .ok()
+        .flatten();
+
+    if let Some(model) = model {
+        // Find the endpoint and save the model
+        let conn = match new_connection_result() {
+            Ok(c) => c,
+            Err(e) => {
+                return HttpResponse::InternalServerError().json(ProbeModelResponse {
+                    success: false,
+                    message: format!("Database error: {}", e),
+                    model: None,
+                });
+            }
+        };
+
+        // Find endpoint by IP and update the ssdp_model
+        let update_result = conn.execute(
+            "UPDATE endpoints SET ssdp_model = ?1
+             WHERE id IN (SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?2)
+             AND (ssdp_model IS NULL OR ssdp_model = '')",
+            params![model, ip],
+        );
+
+        match update_result {
+            Ok(rows) => {
+                if rows > 0 {
+                    let eid: Option<i64> = conn
+                        .query_row(
+                            "SELECT endpoint_id FROM endpoint_attributes WHERE ip = ?1 LIMIT 1",
+                            params![ip],
+                            |row| row.get(0),
+                        )
+                        .ok();
+                    insert_notification_with_endpoint_id(
+                        &conn,
+                        "model_identified",
+                        &format!("Device model identified: {}", model),
+                        None,
+                        None,
+                        eid,
+                    );
+                }
+                HttpResponse::Ok().json(ProbeModelResponse {
+                    success: true,
+                    message: format!("Found model '{}', updated {} endpoint(s)", model, rows),
+                    model: Some(model),
+                })
+            }
+            Err(e) => HttpResponse::InternalServerError().json(ProbeModelResponse {
+                success: false,
+                message: format!("Found model '{}' but failed to save: {}", model, e),
+                model: Some(model),
+            }),
+        }
+    } else {
+        HttpResponse::Ok().json(ProbeModelResponse {
+            success: false,
+            message: "Could not detect model from device web interface".to_string(),
+            model: None,
+        })
+    }
+}
+
+// ============================================================================
+// Device Control API Endpoints
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct DeviceQuery {
+    ip: String,
+    device_type: Option<String>,
+    hostname: Option<String>,
+    /// Probe the device again instead of using a cached detection result
+    #[serde(default)]
+    refresh: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PairingStatusQuery {
+    hostname: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct InvalidateDetectionRequest {
+    /// Forget this address only; every cached result when absent
+    ip: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceCommandRequest {
+    ip: String,
+    command: String,
+    device_type: String,
+}
+
+#[derive(Deserialize)]
+pub struct LaunchAppRequest {
+    ip: String,
+    app_id: String,
+    device_type: String,
+    /// Deep link into this content instead of the app's home screen
+    content_id: Option<String>,
+    /// Kind of content `content_id` names (Roku: movie, episode, series...)
+    media_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceTextRequest {
+    ip: String,
+    text: String,
+    device_type: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceSearchRequest {
+    ip: String,
+    keyword: String,
+    device_type: String,
+}
+
+#[get("/api/device/capabilities")]
+pub async fn get_device_capabilities(query: Query<DeviceQuery>) -> impl Responder {
+    let ip = query.ip.clone();
+    let device_type = query.device_type.clone();
+    let hostname = query.hostname.clone();
+    let refresh = query.refresh;
+
+    // Run blocking device detection in a separate thread
+    let capabilities = actix_web::web::block(move || {
+        if refresh {
+            DeviceController::invalidate_detection(Some(&ip));
+        }
+        DeviceController::get_capabilities(&ip, device_type.as_deref(), hostname.as_deref())
+    })
+    .await;
+
+    match capabilities {
+        Ok(caps) => HttpResponse::Ok().json(caps),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to get device capabilities"),
+    }
+}
+
+#[get("/api/device/{ip}/pairing-status")]
+pub async fn get_device_pairing_status(
+    path: actix_web::web::Path<String>,
+    query: Query<PairingStatusQuery>,
+) -> impl Responder {
+    let ip = path.into_inner();
+    if ip.parse::<std::net::IpAddr>().is_err() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Invalid IP address"
+        }));
+    }
+    let hostname = query.into_inner().hostname;
+
+    let status =
+        actix_web::web::block(move || DeviceController::pairing_status(&ip, hostname.as_deref()))
+            .await;
+
+    match status {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to get pairing status"),
+    }
+}
+
+#[post("/api/device/capabilities/invalidate")]
+pub async fn invalidate_device_capabilities(
+    _admin: RequireAdmin,
+    body: Json<InvalidateDetectionRequest>,
+) -> impl Responder {
+    let dropped = DeviceController::invalidate_detection(body.ip.as_deref());
+    HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("Cleared {} cached detection result(s)", dropped),
+        "cleared": dropped,
+    }))
+}
+
+#[post("/api/device/command")]
+pub async fn send_device_command(
+    _admin: RequireAdmin,
+    body: Json<DeviceCommandRequest>,
+) -> impl Responder {
+    let ip = body.ip.clone();
+    let command = body.command.clone();
+    let device_type = body.device_type.clone();
+
+    let result =
+        actix_web::web::block(move || DeviceController::send_command(&ip, &command, &device_type))
+            .await;
+
+    match result {
+        Ok(r) if r.success => HttpResponse::Ok().json(r),
+        Ok(r) => HttpResponse::BadRequest().json(r),
+        Err(_) => HttpResponse::InternalServerError().body("Command failed"),
+    }
+}
+
+#[post("/api/device/launch")]
+pub async fn launch_device_app(
+    _admin: RequireAdmin,
+    body: Json<LaunchAppRequest>,
+) -> impl Responder {
+    let LaunchAppRequest {
+        ip,
+        app_id,
+        device_type,
+        content_id,
+        media_type,
+    } = body.into_inner();
+
+    let result = actix_web::web::block(move || match content_id {
+        Some(content_id) => DeviceController::deep_link(
+            &ip,
+            &app_id,
+            &content_id,
+            media_type.as_deref().unwrap_or_default(),
+            &device_type,
+        ),
+        None => DeviceController::launch_app(&ip, &app_id, &device_type),
+    })
+    .await;
+
+    match result {
+        Ok(r) if r.success => HttpResponse::Ok().json(r),
+        Ok(r) => HttpResponse::BadRequest().json(r),
+        Err(_) => HttpResponse::InternalServerError().body("Launch failed"),
+    }
+}
+
+#[post("/api/device/text")]
+pub async fn send_device_text(
+    _admin: RequireAdmin,
+    body: Json<DeviceTextRequest>,
+) -> impl Responder {
+    let DeviceTextRequest {
+        ip,
+        text,
+        device_type,
+    } = body.into_inner();
+
+    let result =
+        actix_web::web::block(move || DeviceController::send_text(&ip, &text, &device_type)).await;
+
+    match result {
+        Ok(r) if r.success => HttpResponse::Ok().json(r),
+        Ok(r) => HttpResponse::BadRequest().json(r),
+        Err(_) => HttpResponse::InternalServerError().body("Text entry failed"),
+    }
+}
+
+#[post("/api/device/search")]
+pub async fn search_device(
+    _admin: RequireAdmin,
+    body: Json<DeviceSearchRequest>,
+) -> impl Responder {
+    let DeviceSearchRequest {
+        ip,
+        keyword,
+        device_type,
+    } = body.into_inner();
+
+    let result =
+        actix_web::web::block(move || DeviceController::search(&ip, &keyword, &device_type)).await;
+
+    match result {
+        Ok(r) if r.success => HttpResponse::Ok().json(r),
+        Ok(r) => HttpResponse::BadRequest().json(r),
+        Err(_) => HttpResponse::InternalServerError().body("Search failed"),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PairRequest {
+    ip: String,
+    device_type: String,
+}
+
+#[post("/api/device/pair")]
+pub async fn pair_device(_admin: RequireAdmin, body: Json<PairRequest>) -> impl Responder {
+    let ip = body.ip.clone();
+    let device_type = body.device_type.clone();
+
+    let result = actix_web::web::block(move || DeviceController::pair(&ip, &device_type)).await;
+
+    match result {
+        Ok(r) if r.success => HttpResponse::Ok().json(r),
+        Ok(r) => HttpResponse::BadRequest().json(r),
+        Err(_) => HttpResponse::InternalServerError().body("Pairing failed"),
+    }
+}
+
+// ============================================================================
+// Device Control Macros
+// ============================================================================
+
+/// Saved device macros
+#[get("/api/device/macros")]
+pub async fn get_device_macros_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| get_device_macros(&new_connection())).await;
+
+    match result {
+        Ok(Ok(macros)) => HttpResponse::Ok().json(serde_json::json!({ "macros": macros })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch device macros"
+        })),
+    }
+}
+
+/// Create a macro, or edit the one with the given id
+#[post("/api/device/macros")]
+pub async fn save_device_macro_api(
+    _admin: RequireAdmin,
+    body: Json<NewDeviceMacro>,
+) -> impl Responder {
+    let device_macro = body.into_inner();
+    if let Err(e) = device_macro.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        }));
+    }
+    let name = device_macro.name.trim().to_string();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        if let Some(step) = device_macro
+            .steps
+            .iter()
+            .find(|step| find_endpoint_id_by_name(&conn, &step.endpoint).is_none())
+        {
+            return Ok(Err(format!("Endpoint '{}' not found", step.endpoint)));
+        }
+        save_device_macro(&conn, &device_macro, chrono::Utc::now().timestamp()).map(Ok)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Ok(Some(id)))) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("Saved macro '{}'", name),
+            "id": id,
+        })),
+        Ok(Ok(Ok(None))) => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": "Macro not found"
+        })),
+        Ok(Ok(Err(e))) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Ok(Err(rusqlite::Error::SqliteFailure(e, _)))
+            if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            HttpResponse::Conflict().json(serde_json::json!({
+                "success": false,
+                "message": format!("A macro named '{}' already exists", name)
+            }))
+        }
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DeviceMacroIdRequest {
+    id: i64,
+}
+
+#[post("/api/device/macros/delete")]
+pub async fn delete_device_macro_api(
+    _admin: RequireAdmin,
+    body: Json<DeviceMacroIdRequest>,
+) -> impl Responder {
+    let id = body.into_inner().id;
+    let result =
+        tokio::task::spawn_blocking(move || delete_device_macro(&new_connection(), id)).await;
+
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Macro not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete macro"
+        })),
+    }
+}
+
+/// Run a macro's steps in order and report each one. Endpoints are resolved to their
+/// current address (IPv4 preferred) when the run starts.
+#[post("/api/device/macros/run")]
+pub async fn run_device_macro(
+    _admin: RequireAdmin,
+    body: Json<DeviceMacroIdRequest>,
+) -> impl Responder {
+    let id = body.into_inner().id;
+    let result = actix_web::web::block(move || {
+        let Some(device_macro) = get_device_macro(&new_connection(), id)? else {
+            return Ok(None);
+        };
+        let names: Vec<String> = device_macro
+            .steps
+            .iter()
+            .map(|step| step.endpoint.clone())
+            .collect();
+        let addresses = get_endpoint_ips_and_macs(&names);
+        let resolve_ip = |endpoint: &str| {
+            let (ips, _) = addresses.get(&endpoint.to_lowercase())?;
+            ips.iter()
+                .find(|ip| ip.parse::<std::net::Ipv4Addr>().is_ok())
+                .or_else(|| ips.first())
+                .cloned()
+        };
+        Ok::<_, rusqlite::Error>(Some(DeviceController::run_macro(
+            &device_macro.steps,
+            resolve_ip,
+        )))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(run))) if run.success => HttpResponse::Ok().json(run),
+        Ok(Ok(Some(run))) => HttpResponse::BadRequest().json(run),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": "Macro not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Failed to run macro"
+        })),
+    }
+}
+
+// ============================================================================
+// LG ThinQ API Endpoints
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct ThinQSetupRequest {
+    pat_token: String,
+    country_code: String,
+}
+
+#[derive(Serialize)]
+pub struct ThinQStatusResponse {
+    configured: bool,
+    devices: Vec<ThinQDeviceInfo>,
+}
+
+#[derive(Serialize)]
+pub struct ThinQDeviceInfo {
+    device_id: String,
+    device_type: String,
+    name: String,
+    model: Option<String>,
+    online: bool,
+}
+
+#[post("/api/thinq/setup")]
+pub async fn setup_thinq(_admin: RequireAdmin, body: Json<ThinQSetupRequest>) -> impl Responder {
+    let pat_token = body.pat_token.clone();
+    let country_code = body.country_code.clone();
+
+    let result =
+        actix_web::web::block(move || DeviceController::setup_thinq(&pat_token, &country_code))
+            .await;
+
+    match result {
+        Ok(r) if r.success => HttpResponse::Ok().json(r),
+        Ok(r) => HttpResponse::BadRequest().json(r),
+        Err(_) => HttpResponse::InternalServerError().body("ThinQ setup failed"),
+    }
+}
+
+#[get("/api/thinq/status")]
+pub async fn get_thinq_status() -> impl Responder {
+    let result = actix_web::web::block(move || {
+        let configured = DeviceController::is_thinq_configured();
+        let devices = if configured {
+            DeviceController::list_thinq_devices()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|d| ThinQDeviceInfo {
+                    device_id: d.device_id,
+                    device_type: d.device_type,
+                    name: d.device_alias,
+                    model: d.model_name,
+                    online: d.online,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        ThinQStatusResponse {
+            configured,
+            devices,
+        }
+    })
+    .await;
+
+    match result {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to get ThinQ status"),
+    }
+}
+
+#[get("/api/thinq/devices")]
+pub async fn list_thinq_devices() -> impl Responder {
+    let result = actix_web::web::block(move || {
+        DeviceController::list_thinq_devices().map(|devices| {
+            devices
+                .into_iter()
+                .map(|d| ThinQDeviceInfo {
+                    device_id: d.device_id,
+                    device_type: d.device_type,
+                    name: d.device_alias,
+                    model: d.model_name,
+                    online: d.online,
+                })
+                .collect::<Vec<_>>()
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(devices)) => HttpResponse::Ok().json(devices),
+        Ok(Err(e)) => HttpResponse::BadRequest().body(e),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to list ThinQ devices"),
+    }
+}
+
+#[post("/api/thinq/disconnect")]
+pub async fn disconnect_thinq(_admin: RequireAdmin) -> impl Responder {
+    let result = actix_web::web::block(DeviceController::disconnect_thinq).await;
+
+    match result {
+        Ok(success) => {
+            if success {
+                HttpResponse::Ok().json(serde_json::json!({
+                    "success": true,
+                    "message": "Disconnected from LG ThinQ"
+                }))
+            } else {
+                HttpResponse::BadRequest().json(serde_json::json!({
+                    "success": false,
+                    "message": "Failed to disconnect"
+                }))
+            }
+        }
+        Err(_) => HttpResponse::InternalServerError().body("Failed to disconnect ThinQ"),
+    }
+}
+
+// ============================================================================
+// Network Scanner API Endpoints
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct StartScanRequest {
+    scan_types: Vec<ScanType>,
+}
+
+#[derive(Serialize)]
+pub struct StartScanResponse {
+    success: bool,
+    message: String,
+}
+
+#[post("/api/scan/start")]
+pub async fn start_scan(_admin: RequireAdmin, body: Json<StartScanRequest>) -> impl Responder {
+    let manager = get_scan_manager();
+    let scan_types = body.scan_types.clone();
+
+    match manager.start_scan(scan_types.clone()).await {
+        Ok(()) => {
+            let type_names: Vec<String> = scan_types.iter().map(|t| t.to_string()).collect();
+            let details = format!("Scan types: {}", type_names.join(", "));
+            tokio::task::spawn_blocking(move || {
+                let conn = new_connection();
+                insert_notification(
+                    &conn,
+                    "scan_started",
+                    "Network scan started",
+                    Some(&details),
+                    None,
+                );
+            });
+
+            HttpResponse::Ok().json(StartScanResponse {
+                success: true,
+                message: "Scan started".to_string(),
+            })
+        }
+        Err(e) => HttpResponse::BadRequest().json(StartScanResponse {
+            success: false,
+            message: e,
+        }),
+    }
+}
+
+#[post("/api/scan/stop")]
+pub async fn stop_scan(_admin: RequireAdmin) -> impl Responder {
+    let manager = get_scan_manager();
+    manager.stop_scan().await;
+
+    tokio::task::spawn_blocking(|| {
+        let conn = new_connection();
+        insert_notification(&conn, "scan_stopped", "Network scan stopped", None, None);
+    });
+
+    HttpResponse::Ok().json(StartScanResponse {
+        success: true,
+        message: "Scan stopped".to_string(),
+    })
+}
+
+/// Cancel one scan type of the running scan, leaving the others running
+#[post("/api/scan/cancel/{scan_type}")]
+pub async fn cancel_scan_type(
+    _admin: RequireAdmin,
+    path: actix_web::web::Path<ScanType>,
+) -> impl Responder {
+    let scan_type = path.into_inner();
+    let manager = get_scan_manager();
+
+    match manager.cancel_scan_type(scan_type).await {
+        Ok(()) => {
+            let title = format!("{} scan cancelled", scan_type.to_string().to_uppercase());
+            tokio::task::spawn_blocking(move || {
+                let conn = new_connection();
+                insert_notification(&conn, "scan_stopped", &title, None, None);
+            });
+
+            HttpResponse::Ok().json(StartScanResponse {
+                success: true,
+                message: format!("{} scan cancelled", scan_type),
+            })
+        }
+        Err(e) => HttpResponse::BadRequest().json(StartScanResponse {
+            success: false,
+            message: e,
+        }),
+    }
+}
+
+#[get("/api/scan/status")]
+pub async fn get_scan_status() -> impl Responder {
+    let manager = get_scan_manager();
+    let status = manager.get_status().await;
+
+    HttpResponse::Ok().json(status)
+}
+
+#[get("/api/scan/capabilities")]
+pub async fn get_scan_capabilities() -> impl Responder {
+    let capabilities = check_scan_privileges();
+    HttpResponse::Ok().json(capabilities)
+}
+
+/// Registered scan types with their availability and the scan config settings they read
+#[get("/api/scan/scanners")]
+pub async fn get_scanners() -> impl Responder {
+    let scanners = scanner_info(&check_privileges());
+    HttpResponse::Ok().json(scanners)
+}
+
+#[get("/api/scan/config")]
+pub async fn get_scan_config() -> impl Responder {
+    let manager = get_scan_manager();
+    let config = manager.get_config().await;
+
+    HttpResponse::Ok().json(config)
+}
+
+/// Check and save the scan config; it is kept in the `scan_config` setting across restarts
+#[post("/api/scan/config")]
+pub async fn set_scan_config(_admin: RequireAdmin, body: Json<ScanConfig>) -> impl Responder {
+    let config = body.into_inner();
+    if let Err(message) = config.validate() {
+        return HttpResponse::BadRequest().json(StartScanResponse {
+            success: false,
+            message,
+        });
+    }
+
+    let saved = config.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let json = serde_json::to_string(&saved).map_err(|e| e.to_string())?;
+        set_setting(SCAN_CONFIG_SETTING, &json).map_err(|e| e.to_string())
+    })
+    .await;
+    let error = match result {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e),
+        Err(e) => Some(e.to_string()),
+    };
+    if let Some(e) = error {
+        eprintln!("Failed to save scan config: {}", e);
+        return HttpResponse::InternalServerError().json(StartScanResponse {
+            success: false,
+            message: "Failed to save config".to_string(),
+        });
+    }
+
+    let manager = get_scan_manager();
+    manager.set_config(config).await;
+
+    HttpResponse::Ok().json(StartScanResponse {
+        success: true,
+        message: "Config updated".to_string(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ScanRunsQuery {
+    limit: Option<i64>,
+}
+
+#[get("/api/scan/runs")]
+pub async fn get_scan_runs_api(query: Query<ScanRunsQuery>) -> impl Responder {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        get_scan_runs(&conn, limit).map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(runs)) => HttpResponse::Ok().json(runs),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(e),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to load scan runs"),
+    }
+}
+
+/// Compare two scan runs. Without parameters the latest run is compared with the one
+/// before it; `days` compares the latest run with the last run at least that old.
+#[derive(Deserialize)]
+pub struct ScanDiffQuery {
+    from: Option<i64>,
+    to: Option<i64>,
+    days: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ScanDiffResponse {
+    from: ScanRun,
+    to: ScanRun,
+    summary: String,
+    changes: ScanChangeset,
+}
+
+#[get("/api/scan/diff")]
+pub async fn get_scan_diff(query: Query<ScanDiffQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let result =
+        tokio::task::spawn_blocking(move || -> Result<Option<ScanDiffResponse>, String> {
+            let conn = new_connection_result().map_err(|e| e.to_string())?;
+
+            let to_id = match query.to {
+                Some(id) => Some(id),
+                None => get_scan_runs(&conn, 1)
+                    .map_err(|e| e.to_string())?
+                    .first()
+                    .map(|r| r.id),
+            };
+            let Some(to_id) = to_id else {
+                return Ok(None);
+            };
+            let Some((to_run, to_snapshot)) =
+                get_scan_run(&conn, to_id).map_err(|e| e.to_string())?
+            else {
+                return Ok(None);
+            };
+
+            let from_id = match (query.from, query.days) {
+                (Some(id), _) => Some(id),
+                (None, Some(days)) => {
+                    let cutoff = chrono::Utc::now().timestamp() - days.max(0) * 24 * 60 * 60;
+                    get_scan_run_id_before(&conn, cutoff.min(to_run.finished_at - 1))
+                        .map_err(|e| e.to_string())?
+                }
+                (None, None) => {
+                    get_previous_scan_run_id(&conn, to_id).map_err(|e| e.to_string())?
+                }
+            };
+            let Some(from_id) = from_id else {
+                return Ok(None);
+            };
+            let Some((from_run, from_snapshot)) =
+                get_scan_run(&conn, from_id).map_err(|e| e.to_string())?
+            else {
+                return Ok(None);
+            };
+
+            let changes = diff_scan_runs((&from_run, &from_snapshot), (&to_run, &to_snapshot));
+            Ok(Some(ScanDiffResponse {
+                from: from_run,
+                to: to_run,
+                summary: changes.summary(),
+                changes,
+            }))
+        })
+        .await;
+
+    match result {
+        Ok(Ok(Some(diff))) => HttpResponse::Ok().json(diff),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Not enough scan runs to compare"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(e),
+        Err(_) => HttpResponse::InternalServerError().body("Failed to compare scan runs"),
+    }
+}
+
+// ============================================================================
+// Scan Result Processing
+// ============================================================================
+
+/// Record a finished scan run and notify about differences from the previous run
+fn record_completed_scan(started_at: i64, finished_at: i64, scan_types: &[ScanType]) {
+    let Ok(conn) = new_connection_result() else {
+        return;
+    };
+    let scan_types: Vec<String> = scan_types.iter().map(|t| t.to_string()).collect();
+    let run_id = match record_scan_run(&conn, started_at, finished_at, &scan_types) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Failed to record scan run: {}", e);
+            return;
+        }
+    };
+
+    let Ok(Some(previous_id)) = get_previous_scan_run_id(&conn, run_id) else {
+        return;
+    };
+    let (Ok(Some(previous)), Ok(Some(current))) = (
+        get_scan_run(&conn, previous_id),
+        get_scan_run(&conn, run_id),
+    ) else {
+        return;
+    };
+    let changes = diff_scan_runs((&previous.0, &previous.1), (&current.0, &current.1));
+    if changes.is_empty() {
+        return;
+    }
+
+    insert_notification(
+        &conn,
+        "scan_changes",
+        &format!(
+            "Scan found {} change{} since the previous scan",
+            changes.change_count(),
+            if changes.change_count() == 1 { "" } else { "s" }
+        ),
+        Some(&changes.summary()),
+        None,
+    );
+
+    // A newly listening service is worth flagging on the device itself
+    for opened in &changes.ports_opened {
+        insert_notification_with_endpoint_id(
+            &conn,
+            "port_opened",
+            &format!(
+                "Port {}/{} opened on {}",
+                opened.port,
+                opened.protocol,
+                opened.name.as_deref().unwrap_or("unknown device")
+            ),
+            None,
+            opened.name.as_deref(),
+            Some(opened.endpoint_id),
+        );
+    }
+    for gone in &changes.disappeared {
+        insert_notification_with_endpoint_id(
+            &conn,
+            "endpoint_disappeared",
+            &format!(
+                "Device no longer responding to scans: {}",
+                gone.name.as_deref().unwrap_or("unknown device")
+            ),
+            (!gone.ips.is_empty())
+                .then(|| gone.ips.join(", "))
+                .as_deref(),
+            gone.name.as_deref(),
+            Some(gone.endpoint_id),
+        );
+    }
+}
+
+/// Record a batch of scan results on `conn`, normally the SQL writer's open transaction.
+/// A result that fails or panics is reported and the rest are still recorded.
+fn record_scan_results(conn: &Connection, results: &[ScanResult]) {
+    for result in results {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            process_scan_result(conn, result)
+        })) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Error processing scan result: {}", e),
+            Err(payload) => supervisor::record_failure(
+                "scan-results",
+                &format!("panicked: {}", supervisor::panic_message(&*payload)),
+            ),
+        }
+    }
+}
+
+/// Store one scan result
+fn process_scan_result(conn: &Connection, result: &ScanResult) -> Result<(), String> {
+    match result {
+        ScanResult::Arp(arp) => {
+            let ip_str = arp.ip.to_string();
+            let mac_str = arp.mac.to_string();
+            if let Ok((endpoint_id, is_new)) = EndPoint::get_or_insert_endpoint(
+                conn,
+                Some(mac_str.clone()),
+                Some(ip_str.clone()),
+                None,
+                &[],
+            ) {
+                if is_new {
+                    insert_notification_with_endpoint_id(
+                        conn,
+                        "endpoint_discovered",
+                        &format!("New device discovered: {}", ip_str),
+                        Some(&format!("MAC: {}", mac_str)),
+                        Some(&ip_str),
+                        Some(endpoint_id),
+                    );
+                }
+
+                record_scan_binding(conn, &ip_str, &mac_str, endpoint_id, "arp");
+
+                insert_scan_result(
+                    conn,
+                    endpoint_id,
+                    &ip_str,
+                    "arp",
+                    Some(arp.response_time_ms as i64),
+                    None,
+                )?;
+
+                // If this is an HP device, probe for printer model
+                if get_mac_vendor(&mac_str).is_some_and(|v| v == "HP") {
+                    let ip_for_probe = ip_str.clone();
+                    tokio::task::spawn_blocking(move || {
+                        probe_and_save_hp_printer_model_blocking(&ip_for_probe, endpoint_id);
+                    });
+                }
+            }
+        }
+        ScanResult::Icmp(icmp) => {
+            if icmp.alive {
+                let ip_str = icmp.ip.to_string();
+                // For ICMP (no MAC), only record if endpoint already exists
+                // This prevents creating ghost entries for false positive pings
+                if let Some(endpoint_id) = find_existing_endpoint_by_ip(conn, &ip_str) {
+                    let details = serde_json::json!({
+                        "ttl": icmp.ttl,
+                        "rtt_ms": icmp.rtt_ms,
+                    });
+                    insert_scan_result(
+                        conn,
+                        endpoint_id,
+                        &ip_str,
+                        "icmp",
+                        icmp.rtt_ms.map(|r| r as i64),
+                        Some(&details.to_string()),
+                    )?;
+                }
+            }
+        }
+        ScanResult::Port(port) => {
+            if port.open {
+                let ip_str = port.ip.to_string();
+                // For port scans (no MAC), only record if endpoint already exists
+                if let Some(endpoint_id) = find_existing_endpoint_by_ip(conn, &ip_str) {
+                    insert_open_port(conn, endpoint_id, port.port, port.service_name.as_deref())?;
+                    if let Err(e) = check_port_policy(
+                        conn,
+                        endpoint_id,
+                        port.port,
+                        DriftSource::Scan,
+                        chrono::Utc::now().timestamp(),
+                    ) {
+                        eprintln!("Failed to check port policy: {}", e);
+                    }
+                }
+            }
+        }
+        ScanResult::Ssdp(ssdp) => {
+            let ip_str = ssdp.ip.to_string();
+            if let Some(ref mappings) = ssdp.port_mappings {
+                record_upnp_port_mappings(conn, &ip_str, mappings);
+            }
+            // For SSDP (no MAC), only record if endpoint already exists. The UDN says which
+            // device answered, so its data follows the device when addresses are reassigned.
+            if let Some((endpoint_id, verified)) = resolve_ssdp_endpoint(conn, &ip_str, ssdp) {
+                let details = serde_json::json!({
+                    "location": ssdp.location,
+                    "server": ssdp.server,
+                    "device_type": ssdp.device_type,
+                    "friendly_name": ssdp.friendly_name,
+                    "model_name": ssdp.model_name,
+                    "udn": ssdp.udn,
+                    "port_mappings": ssdp.port_mappings.as_ref().map(Vec::len),
+                });
+                insert_scan_result(
+                    conn,
+                    endpoint_id,
+                    &ip_str,
+                    "ssdp",
+                    None,
+                    Some(&details.to_string()),
+                )?;
+
+                // Model and names are only taken from a device whose identity checks out
+                if !verified {
+                    return Ok(());
+                }
+
+                // If we got a model name from SSDP, save it to the endpoint
+                if let Some(ref model) = ssdp.model_name {
+                    // Update if empty OR if new model is more specific than current
+                    let current_model: Option<String> = conn
+                        .query_row(
+                            "SELECT ssdp_model FROM endpoints WHERE id = ?1",
+                            params![endpoint_id],
+                            |row| row.get(0),
+                        )
+                        .ok()
+                        .flatten();
+
+                    let should_update = match &current_model {
+                        None => true,
+                        Some(current) if current.is_empty() => true,
+                        Some(current) => is_more_specific_model(model, current),
+                    };
+
+                    if should_update {
+                        let _ = conn.execute(
+                            "UPDATE endpoints SET ssdp_model = ?1 WHERE id = ?2",
+                            params![model, endpoint_id],
+                        );
+
+                        if current_model.as_ref().is_none_or(|m| m.is_empty()) {
+                            insert_notification_with_endpoint_id(
+                                conn,
+                                "model_identified",
+                                &format!("Device model identified: {}", model),
+                                None,
+                                None,
+                                Some(endpoint_id),
+                            );
+                        } else if let Some(ref old) = current_model {
+                            insert_notification_with_endpoint_id(
+                                conn,
+                                "model_changed",
+                                &format!("Device model updated: {}", model),
+                                Some(&format!("Previous: {}", old)),
+                                None,
+                                Some(endpoint_id),
+                            );
+                        }
+                    }
+                }
+                // If we got a friendly name from SSDP, save it
+                // Update if empty OR if new name is more specific
+                if let Some(ref friendly) = ssdp.friendly_name {
+                    let current_friendly: Option<String> = conn
+                        .query_row(
+                            "SELECT ssdp_friendly_name FROM endpoints WHERE id = ?1",
+                            params![endpoint_id],
+                            |row| row.get(0),
+                        )
+                        .ok()
+                        .flatten();
+
+                    let should_update = match &current_friendly {
+                        None => true,
+                        Some(current) if current.is_empty() => true,
+                        Some(current) => is_more_specific_model(friendly, current),
+                    };
+
+                    if should_update {
+                        let _ = conn.execute(
+                            "UPDATE endpoints SET ssdp_friendly_name = ?1 WHERE id = ?2",
+                            params![friendly, endpoint_id],
+                        );
+                    }
+                }
+
+                // If endpoint still has no valid name, set it from SSDP friendly name or model
+                try_set_endpoint_name_from_discovery(
+                    conn,
+                    endpoint_id,
+                    ssdp.friendly_name.as_deref().or(ssdp.model_name.as_deref()),
+                );
+            }
+        }
+        ScanResult::Ndp(ndp) => {
+            let ip_str = ndp.ip.to_string();
+            let mac_str = ndp.mac.to_string();
+            // An IPv6-only address joins the device whose IPv4 address was last bound with
+            // the same MAC; this covers randomized MACs, which endpoint lookup ignores
+            let endpoint = match EndPoint::resolve_mac_binding(conn, &mac_str) {
+                Some(endpoint_id) => EndPointAttribute::insert_endpoint_attribute_with_dhcp(
+                    conn,
+                    endpoint_id,
+                    Some(mac_str.clone()),
+                    Some(ip_str.clone()),
+                    ip_str.clone(),
+                    None,
+                    None,
+                )
+                .ok()
+                .map(|_| (endpoint_id, false)),
+                None => EndPoint::get_or_insert_endpoint(
+                    conn,
+                    Some(mac_str.clone()),
+                    Some(ip_str.clone()),
+                    None,
+                    &[],
+                )
+                .ok(),
+            };
+            if let Some((endpoint_id, is_new)) = endpoint {
+                if is_new {
+                    insert_notification_with_endpoint_id(
+                        conn,
+                        "endpoint_discovered",
+                        &format!("New device discovered: {}", ip_str),
+                        Some(&format!("MAC: {} (NDP)", mac_str)),
+                        Some(&ip_str),
+                        Some(endpoint_id),
+                    );
+                }
+
+                record_scan_binding(conn, &ip_str, &mac_str, endpoint_id, "ndp");
+
+                insert_scan_result(
+                    conn,
+                    endpoint_id,
+                    &ip_str,
+                    "ndp",
+                    ndp.response_time_ms.map(|ms| ms as i64),
+                    None,
+                )?;
+            }
+        }
+        ScanResult::NetBios(netbios) => {
+            let ip_str = netbios.ip.to_string();
+            HostnameResolver::record(&ip_str, &netbios.netbios_name, HostnameSource::NetBios);
+            // For NetBIOS (no MAC from packet), only record if endpoint already exists
+            if let Some(endpoint_id) = find_existing_endpoint_by_ip(conn, &ip_str) {
+                let details = serde_json::json!({
+                    "netbios_name": netbios.netbios_name,
+                    "group_name": netbios.group_name,
+                    "mac": netbios.mac,
+                    "smb_shares": netbios.smb.as_ref().map(|smb| smb.shares.len()),
+                });
+                insert_scan_result(
+                    conn,
+                    endpoint_id,
+                    &ip_str,
+                    "netbios",
+                    None,
+                    Some(&details.to_string()),
+                )?;
+
+                // Save NetBIOS name to endpoint if not already set
+                let _ = conn.execute(
+                    "UPDATE endpoints SET netbios_name = ?1 WHERE id = ?2 AND (netbios_name IS NULL OR netbios_name = '')",
+                    params![netbios.netbios_name, endpoint_id],
+                );
+
+                // Also name the endpoint, unless mDNS or DHCP already has
+                let _ = EndPoint::set_discovered_name(
+                    conn,
+                    endpoint_id,
+                    &netbios.netbios_name,
+                    NameSource::NetBios,
+                );
+
+                record_netbios_smb_info(conn, endpoint_id, netbios);
+            }
+        }
+        ScanResult::Snmp(snmp) => {
+            let ip_str = snmp.ip.to_string();
+            if let Some(ref sys_name) = snmp.sys_name {
+                HostnameResolver::record(&ip_str, sys_name, HostnameSource::Snmp);
+            }
+            // For SNMP (no MAC from packet), only record if endpoint already exists
+            if let Some(endpoint_id) = find_existing_endpoint_by_ip(conn, &ip_str) {
+                let details = snmp_result_details(snmp);
+                insert_scan_result(
+                    conn,
+                    endpoint_id,
+                    &ip_str,
+                    "snmp",
+                    None,
+                    Some(&details.to_string()),
+                )?;
+
+                // Extract vendor/model info from sysDescr if available
+                if let Some(ref sys_descr) = snmp.sys_descr {
+                    let (vendor, model) = parse_snmp_sys_descr(sys_descr);
+
+                    // Update vendor if we found one and endpoint doesn't have one
+                    if let Some(v) = &vendor {
+                        match conn.execute(
+                            "UPDATE endpoints SET snmp_vendor = ?1 WHERE id = ?2 AND (snmp_vendor IS NULL OR snmp_vendor = '')",
+                            params![v, endpoint_id],
+                        ) {
+                            Ok(rows) if rows > 0 => {
+                                insert_notification_with_endpoint_id(
+                                    conn, "vendor_identified",
+                                    &format!("Vendor identified: {}", v),
+                                    None, None, Some(endpoint_id),
+                                );
+                            }
+                            Err(e) => eprintln!("Failed to save SNMP vendor: {}", e),
+                            _ => {}
+                        }
+                    }
+
+                    // Update model if we found one and endpoint doesn't have one
+                    if let Some(m) = &model {
+                        match conn.execute(
+                            "UPDATE endpoints SET snmp_model = ?1 WHERE id = ?2 AND (snmp_model IS NULL OR snmp_model = '')",
+                            params![m, endpoint_id],
+                        ) {
+                            Ok(rows) if rows > 0 => {
+                                insert_notification_with_endpoint_id(
+                                    conn, "model_identified",
+                                    &format!("Device model identified: {}", m),
+                                    None, None, Some(endpoint_id),
+                                );
+                            }
+                            Err(e) => eprintln!("Failed to save SNMP model: {}", e),
+                            _ => {}
+                        }
+                    }
+                }
+
+                // Update endpoint name from sysName if name is just an IP
+                if let Some(ref sys_name) = snmp.sys_name
+                    && !sys_name.is_empty()
+                {
+                    let _ = conn.execute(
+                        "UPDATE endpoints SET name = ?1 WHERE id = ?2 AND (name = ?3 OR name GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*')",
+                        params![sys_name, endpoint_id, ip_str],
+                    );
+                }
+
+                // If name still not set, try vendor+model from SNMP sysDescr
+                if let Some(ref sys_descr) = snmp.sys_descr {
+                    let (vendor, model) = parse_snmp_sys_descr(sys_descr);
+                    let name = model.or(vendor);
+                    try_set_endpoint_name_from_discovery(conn, endpoint_id, name.as_deref());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Try to set endpoint name from a discovered name (SSDP friendly name, SNMP model, etc.)
+/// Only updates if the endpoint currently has no valid display name.
+/// Appends (2), (3), etc. if another endpoint already has the same name.
+fn try_set_endpoint_name_from_discovery(conn: &Connection, endpoint_id: i64, name: Option<&str>) {
+    let name = match name {
+        Some(n) if !n.is_empty() && is_valid_display_name(n) => n,
+        _ => return,
+    };
+
+    let current_name: String = conn
+        .query_row(
+            "SELECT COALESCE(name, '') FROM endpoints WHERE id = ?",
+            params![endpoint_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_default();
+
+    if !is_valid_display_name(&current_name) {
+        let unique = EndPoint::make_unique_endpoint_name(conn, name, endpoint_id);
+        let _ = conn.execute(
+            "UPDATE endpoints SET name = ?1 WHERE id = ?2",
+            params![unique, endpoint_id],
+        );
+    }
+}
+
+/// Find an existing endpoint by IP address (must have a MAC to be considered valid)
+/// An unexpired ARP/NDP binding wins; otherwise the most recent attribute row is used.
+/// Returns None if no endpoint with a MAC exists for this IP
+fn find_existing_endpoint_by_ip(conn: &Connection, ip: &str) -> Option<i64> {
+    if let Some(endpoint_id) = EndPoint::resolve_ip_binding(conn, ip) {
+        return Some(endpoint_id);
+    }
+
+    conn.query_row(
+        "SELECT ea.endpoint_id FROM endpoint_attributes ea
+         WHERE ea.ip = ?1 AND ea.mac IS NOT NULL AND ea.mac != ''
+         ORDER BY ea.created_at DESC
+         LIMIT 1",
+        params![ip],
+        |row| row.get(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+}
+
+/// Pin an IP to the endpoint that answered ARP/NDP for it, for the configured lease
+fn record_scan_binding(conn: &Connection, ip: &str, mac: &str, endpoint_id: i64, source: &str) {
+    let lease_seconds = get_setting_i64("ip_binding_lease_minutes", 240).max(1) * 60;
+    match EndPoint::record_ip_binding(conn, ip, mac, endpoint_id, source, lease_seconds) {
+        Ok(change) if change.reassigned_scan_results > 0 => {
+            println!(
+                "{} moved to endpoint {}: reassigned {} scan result(s) from endpoint {}",
+                ip,
+                endpoint_id,
+                change.reassigned_scan_results,
+                change.previous_endpoint_id.unwrap_or_default()
+            );
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to record IP binding for {}: {}", ip, e),
+    }
+}
+
+/// Parse SNMP sysDescr to extract vendor and model information
+/// Returns (vendor, model) as Option strings
+fn parse_snmp_sys_descr(sys_descr: &str) -> (Option<String>, Option<String>) {
+    let descr_lower = sys_descr.to_lowercase();
+
+    // Common vendor patterns in sysDescr
+    let vendor_patterns: &[(&str, &str)] = &[
+        ("hewlett-packard", "HP"),
+        ("hp ", "HP"),
+        ("cisco", "Cisco"),
+        ("synology", "Synology"),
+        ("qnap", "QNAP"),
+        ("netgear", "NETGEAR"),
+        ("linksys", "Linksys"),
+        ("ubiquiti", "Ubiquiti"),
+        ("unifi", "Ubiquiti"),
+        ("mikrotik", "MikroTik"),
+        ("tp-link", "TP-Link"),
+        ("asus", "ASUS"),
+        ("d-link", "D-Link"),
+        ("buffalo", "Buffalo"),
+        ("brother", "Brother"),
+        ("canon", "Canon"),
+        ("epson", "Epson"),
+        ("xerox", "Xerox"),
+        ("ricoh", "Ricoh"),
+        ("dell", "Dell"),
+        ("lenovo", "Lenovo"),
+        ("apple", "Apple"),
+        ("asustor", "ASUSTOR"),
+        ("drobo", "Drobo"),
+        ("western digital", "Western Digital"),
+        ("seagate", "Seagate"),
+        ("aruba", "Aruba"),
+        ("juniper", "Juniper"),
+        ("fortinet", "Fortinet"),
+        ("paloalto", "Palo Alto"),
+        ("sonicwall", "SonicWall"),
+    ];
+
+    let mut vendor: Option<String> = None;
+    for (pattern, name) in vendor_patterns {
+        // "hp " needs word boundary check to avoid matching "chapter", "graph ", etc.
+        if *pattern == "hp " {
+            if descr_lower.starts_with("hp ")
+                || descr_lower.contains(" hp ")
+                || descr_lower.contains("\nhp ")
+            {
+                vendor = Some(name.to_string());
+                break;
+            }
+        } else if descr_lower.contains(pattern) {
+            vendor = Some(name.to_string());
+            break;
+        }
+    }
+
+    // Try to extract model - look for common patterns
+    let mut model: Option<String> = None;
+
+    // HP printer pattern: "PID:HP Color LaserJet..." - common in HP printer SNMP
+    if let Some(idx) = descr_lower.find("pid:hp") {
+        let after_pid = &sys_descr[idx + 4..]; // Skip "PID:"
+        // Take the HP model name - everything after "HP " until end or comma
+        let trimmed = after_pid.trim();
+        // HP models typically end at the end of string or before a comma
+        let model_str = if let Some(end) = trimmed.find(',') {
+            trimmed[..end].trim()
+        } else {
+            trimmed
+        };
+        if model_str.len() > 2 {
+            model = Some(model_str.to_string());
+            // Also set vendor to HP if not already set
+            if vendor.is_none() {
+                vendor = Some("HP".to_string());
+            }
+        }
+    }
+
+    // Pattern: "Model: XYZ" or "Model XYZ"
+    if model.is_none()
+        && let Some(idx) = descr_lower.find("model")
+    {
+        let after_model = &sys_descr[idx + 5..];
+        let trimmed = after_model.trim_start_matches([':', ' ']);
+        if let Some(end) = trimmed.find([',', ';', '\n', '\r']) {
+            let m = trimmed[..end].trim();
+            if !m.is_empty() {
+                model = Some(m.to_string());
+            }
+        } else if !trimmed.is_empty() {
+            // Take first word/phrase
+            let m = trimmed
+                .split_whitespace()
+                .take(3)
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !m.is_empty() {
+                model = Some(m);
+            }
+        }
+    }
+
+    // For HP printers, look for "HP XXXX" pattern
+    if model.is_none()
+        && vendor.as_deref() == Some("HP")
+        && let Some(idx) = descr_lower.find("hp ")
+    {
+        let after_hp = &sys_descr[idx + 3..];
+        // Take first word(s) that look like a model
+        let parts: Vec<&str> = after_hp.split_whitespace().take(3).collect();
+        if !parts.is_empty() {
+            let m = parts.join(" ");
+            if m.len() > 2 {
+                model = Some(m);
+            }
+        }
+    }
+
+    // For Synology NAS, extract model from pattern like "DS920+"
+    if model.is_none() && vendor.as_deref() == Some("Synology") {
+        // Look for DS/RS followed by numbers
+        for word in sys_descr.split_whitespace() {
+            let w = word.to_uppercase();
+            if (w.starts_with("DS") || w.starts_with("RS")) && w.len() > 2 {
+                let rest = &w[2..];
+                if rest
+                    .chars()
+                    .next()
+                    .map(|c| c.is_ascii_digit())
+                    .unwrap_or(false)
+                {
+                    model = Some(word.to_string());
+                    break;
+                }
+            }
+        }
+    }
+
+    (vendor, model)
+}
+
+/// The endpoint an SSDP response belongs to, and whether its identity is verified. With a
+/// UDN, an endpoint the UDN is already bound to wins over the address's current owner, and
+/// a UDN claimed from an address another device holds is refused. Without one, the data
+/// is only trusted on an endpoint that has never announced a UDN.
+fn resolve_ssdp_endpoint(conn: &Connection, ip: &str, ssdp: &SsdpResult) -> Option<(i64, bool)> {
+    let ip_endpoint = find_existing_endpoint_by_ip(conn, ip);
+    let Some(udn) = &ssdp.udn else {
+        let endpoint_id = ip_endpoint?;
+        let has_udn =
+            EndPoint::has_identity(conn, IdentityKind::UpnpUdn, endpoint_id).unwrap_or(true);
+        return Some((endpoint_id, !has_udn));
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    match EndPoint::resolve_identity(conn, IdentityKind::UpnpUdn, udn, ip, ip_endpoint, now) {
+        Ok(IdentityResolution::Verified(endpoint_id)) => Some((endpoint_id, true)),
+        Ok(IdentityResolution::Unverified(endpoint_id)) => Some((endpoint_id, false)),
+        Ok(IdentityResolution::Conflict { bound_to, holder }) => {
+            eprintln!(
+                "SSDP: {} claimed UDN {} of endpoint {} but is bound to endpoint {}; ignoring",
+                ip, udn, bound_to, holder
+            );
+            None
+        }
+        Ok(IdentityResolution::Unknown) => None,
+        Err(e) => {
+            eprintln!("Failed to resolve SSDP identity: {}", e);
+            None
+        }
+    }
+}
+
+/// Store the workgroup and SMB shares from a NetBIOS result, and warn when shares can be
+/// read without credentials
+fn record_netbios_smb_info(conn: &Connection, endpoint_id: i64, netbios: &NetBiosResult) {
+    // Domain members may only report their AD DNS domain
+    let workgroup = netbios.group_name.as_ref().or_else(|| {
+        let smb = netbios.smb.as_ref()?;
+        smb.domain.as_ref().or(smb.dns_domain.as_ref())
+    });
+    if let Some(workgroup) = workgroup {
+        let _ = conn.execute(
+            "UPDATE endpoints SET workgroup = ?1 WHERE id = ?2",
+            params![workgroup, endpoint_id],
+        );
+    }
+
+    let Some(smb) = &netbios.smb else {
+        return;
+    };
+    let now = chrono::Utc::now().timestamp();
+    if let Err(e) = replace_smb_shares(conn, endpoint_id, &smb.shares, now) {
+        eprintln!("Failed to store SMB shares: {}", e);
+    }
+
+    let readable: Vec<&str> = smb
+        .shares
+        .iter()
+        .filter(|share| share.guest_readable)
+        .map(|share| share.name.as_str())
+        .collect();
+    if !readable.is_empty() {
+        insert_notification_with_endpoint_id(
+            conn,
+            "guest_share",
+            &format!("Guest-readable SMB shares on {}", netbios.netbios_name),
+            Some(&format!(
+                "Readable without credentials: {}",
+                readable.join(", ")
+            )),
+            Some(&netbios.netbios_name),
+            Some(endpoint_id),
+        );
+    }
+}
+
+/// Store a gateway's port mappings and raise a notification for each one not seen before
+fn record_upnp_port_mappings(conn: &Connection, gateway_ip: &str, mappings: &[UpnpPortMapping]) {
+    let now = chrono::Utc::now().timestamp();
+    let new_mappings = match record_port_mappings(conn, gateway_ip, mappings, now) {
+        Ok(new_mappings) => new_mappings,
+        Err(e) => {
+            eprintln!("Failed to store UPnP port mappings: {}", e);
+            return;
+        }
+    };
+
+    for mapping in new_mappings {
+        let endpoint_id = find_existing_endpoint_by_ip(conn, &mapping.internal_client);
+        let device_name: Option<String> = endpoint_id.and_then(|id| {
+            conn.query_row(
+                "SELECT display_name FROM endpoints WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .ok()
+            .flatten()
+        });
+        let device = device_name.as_deref().unwrap_or(&mapping.internal_client);
+        let mut details = format!(
+            "Gateway {} forwards {} port {} to {}:{}",
+            gateway_ip,
+            mapping.protocol,
+            mapping.external_port,
+            mapping.internal_client,
+            mapping.internal_port
+        );
+        if let Some(ref description) = mapping.description {
+            details.push_str(&format!(" ({})", description));
+        }
+        if !mapping.remote_host.is_empty() {
+            details.push_str(&format!(", only from {}", mapping.remote_host));
+        }
+        insert_notification_with_endpoint_id(
+            conn,
+            "upnp_port_mapping",
+            &format!(
+                "{} exposed {} port {} to the internet",
+                device, mapping.protocol, mapping.external_port
+            ),
+            Some(&details),
+            device_name.as_deref(),
+            endpoint_id,
+        );
+    }
+}
+
+/// Check if new_model is more specific than current_model.
+/// Used to allow updating stored SSDP data when better info is discovered.
+fn is_more_specific_model(new_model: &str, current_model: &str) -> bool {
+    let new_lower = new_model.to_lowercase();
+    let current_lower = current_model.to_lowercase();
+
+    // If they're the same, no need to update
+    if new_lower == current_lower {
+        return false;
+    }
+
+    // New model is longer and contains the current model - likely more specific
+    // e.g., "Samsung The Frame 65" is more specific than "Samsung"
+    if new_model.len() > current_model.len() && new_lower.contains(&current_lower) {
+        return true;
+    }
+
+    // Current model is very generic (just a brand name)
+    let generic_names = [
+        "samsung", "lg", "sony", "tcl", "hisense", "vizio", "roku", "apple", "google", "amazon",
+    ];
+    let current_is_generic = generic_names.iter().any(|g| current_lower == *g);
+    if current_is_generic && new_model.len() > current_model.len() {
+        return true;
+    }
+
+    // New model contains specific product identifiers that current lacks
+    let specific_indicators = [
+        "the frame",
+        "the serif",
+        "the sero",
+        "qled",
+        "oled",
+        "neo qled",
+        "nanocell",
+        "bravia",
+        "roku ultra",
+        "roku express",
+        "chromecast",
+        "fire tv",
+        "echo",
+        "homepod",
+    ];
+    let new_has_specific = specific_indicators.iter().any(|s| new_lower.contains(s));
+    let current_has_specific = specific_indicators
+        .iter()
+        .any(|s| current_lower.contains(s));
+    if new_has_specific && !current_has_specific {
+        return true;
+    }
+
+    false
+}
+
+/// Scan result details for an SNMP answer. Records which credential worked so the next
+/// probe tries it first: the community itself for list entries, only "device" for a
+/// device's own credential.
+fn snmp_result_details(snmp: &SnmpResult) -> serde_json::Value {
+    if snmp.per_device_credential {
+        serde_json::json!({
+            "sys_descr": snmp.sys_descr,
+            "sys_object_id": snmp.sys_object_id,
+            "sys_name": snmp.sys_name,
+            "sys_location": snmp.sys_location,
+            "credential": "device",
+        })
+    } else {
+        serde_json::json!({
+            "sys_descr": snmp.sys_descr,
+            "sys_object_id": snmp.sys_object_id,
+            "sys_name": snmp.sys_name,
+            "sys_location": snmp.sys_location,
+            "credential": "list",
+            "community": snmp.community,
+        })
+    }
+}
+
+/// Insert a scan result into the database
+/// Note: Table is created at startup in SQLWriter to avoid schema locks
+fn insert_scan_result(
+    conn: &Connection,
+    endpoint_id: i64,
+    ip: &str,
+    scan_type: &str,
+    response_time_ms: Option<i64>,
+    details: Option<&str>,
+) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT INTO scan_results (endpoint_id, ip, scan_type, scanned_at, response_time_ms, details) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![endpoint_id, ip, scan_type, now, response_time_ms, details],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Insert an open port into the database
+/// Note: Table is created at startup in SQLWriter to avoid schema locks
+fn insert_open_port(
+    conn: &Connection,
+    endpoint_id: i64,
+    port: u16,
+    service_name: Option<&str>,
+) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT OR REPLACE INTO open_ports (endpoint_id, port, protocol, service_name, last_seen_at) VALUES (?1, ?2, 'tcp', ?3, ?4)",
+        params![endpoint_id, port as i64, service_name, now],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Endpoints Table API (for AJAX refresh without full page reload)
+// ============================================================================
+
+#[derive(Clone, Serialize)]
+pub struct EndpointTableRow {
+    #[serde(skip)]
+    endpoint_id: i64,
+    name: String,
+    vendor: Option<String>,
+    model: Option<String>,
+    device_type: Option<String>,
+    bytes: i64,
+    last_seen: String,
+    last_seen_at: Option<i64>,
+    online: bool,
+    /// Title of the device's web interface, when one was fetched
+    web_title: Option<String>,
+    has_favicon: bool,
+}
+
+#[derive(Serialize)]
+pub struct EndpointsTableResponse {
+    endpoints: Vec<EndpointTableRow>,
+    /// Number of endpoints matching the filters, before paging
+    total: usize,
+}
+
+#[derive(Deserialize, Default)]
+pub struct EndpointsTableQuery {
+    /// Only include endpoints seen on this capture interface
+    interface: Option<String>,
+    /// Sort column: name (default), bytes, last_seen or vendor
+    sort: Option<String>,
+    /// "asc" or "desc"; defaults to desc for bytes/last_seen and asc otherwise
+    order: Option<String>,
+    /// Page size; omitted returns every matching endpoint
+    limit: Option<usize>,
+    offset: Option<usize>,
+    /// Exact device type (case-insensitive)
+    device_type: Option<String>,
+    online: Option<bool>,
+    /// Vendor substring (case-insensitive)
+    vendor: Option<String>,
+    /// Only endpoints carrying this tag
+    tag: Option<String>,
+}
+
+/// Filter, sort and page a full endpoint table. Returns the page and the filtered total.
+/// `tagged` holds the ids of the endpoints carrying the requested tag, if one was given.
+fn apply_endpoints_table_query(
+    rows: Vec<EndpointTableRow>,
+    query: &EndpointsTableQuery,
+    tagged: Option<&HashSet<i64>>,
+) -> (Vec<EndpointTableRow>, usize) {
+    let device_type = query
+        .device_type
+        .as_deref()
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase);
+    let vendor = query
+        .vendor
+        .as_deref()
+        .filter(|v| !v.is_empty())
+        .map(str::to_lowercase);
+
+    let mut rows: Vec<EndpointTableRow> = rows
+        .into_iter()
+        .filter(|row| {
+            device_type.as_ref().is_none_or(|t| {
+                row.device_type
+                    .as_deref()
+                    .is_some_and(|d| d.eq_ignore_ascii_case(t))
+            })
+        })
+        .filter(|row| query.online.is_none_or(|online| row.online == online))
+        .filter(|row| {
+            vendor.as_ref().is_none_or(|v| {
+                row.vendor
+                    .as_deref()
+                    .is_some_and(|rv| rv.to_lowercase().contains(v))
+            })
+        })
+        .filter(|row| tagged.is_none_or(|ids| ids.contains(&row.endpoint_id)))
+        .collect();
+
+    let sort = query.sort.as_deref().unwrap_or("name");
+    let descending = match query.order.as_deref() {
+        Some("asc") => false,
+        Some("desc") => true,
+        _ => matches!(sort, "bytes" | "last_seen"),
+    };
+    let by_name = |a: &EndpointTableRow, b: &EndpointTableRow| {
+        a.name.to_lowercase().cmp(&b.name.to_lowercase())
+    };
+    match sort {
+        "bytes" => rows.sort_by(|a, b| a.bytes.cmp(&b.bytes).then_with(|| by_name(a, b))),
+        "last_seen" => rows.sort_by(|a, b| {
+            a.last_seen_at
+                .cmp(&b.last_seen_at)
+                .then_with(|| by_name(a, b))
+        }),
+        "vendor" => rows.sort_by(|a, b| {
+            // Endpoints without a vendor sort after those with one
+            let key = |r: &EndpointTableRow| {
+                let vendor = r.vendor.as_deref().map(str::to_lowercase);
+                (vendor.is_none(), vendor)
+            };
+            key(a).cmp(&key(b)).then_with(|| by_name(a, b))
+        }),
+        _ => rows.sort_by(by_name),
+    }
+    if descending {
+        rows.reverse();
+    }
+
+    let total = rows.len();
+    let offset = query.offset.unwrap_or(0).min(total);
+    let limit = query.limit.unwrap_or(total);
+    let page = rows.into_iter().skip(offset).take(limit).collect();
+    (page, total)
+}
+
+/// Get endpoint table data for AJAX refresh (doesn't reload full page).
+/// Supports server-side sorting, filtering and paging via query parameters.
+#[get("/api/endpoints/table")]
+pub async fn get_endpoints_table(query: Query<EndpointsTableQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let interface = query.interface.clone().filter(|i| !i.is_empty());
+    let cache_key = interface.clone().unwrap_or_default();
+
+    let tag = match query
+        .tag
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        Some(tag) => match parse_tag(tag) {
+            Some(tag) => Some(tag),
+            None => {
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "success": false,
+                    "message": format!("Invalid tag '{}'", tag)
+                }));
+            }
+        },
+        None => None,
+    };
+
+    // Check cache first (3-second TTL), keyed by interface
+    let cached = get_endpoint_table_cache()
+        .lock()
+        .ok()
+        .and_then(|cache_guard| cache_guard.get(&cache_key));
+    let rows = match cached {
+        Some(rows) => rows,
+        None => {
+            let now = chrono::Utc::now().timestamp();
+            let active_threshold = get_setting_i64("active_threshold_seconds", 120);
+            let rows: Vec<EndpointTableRow> = load_endpoint_summaries(interface)
+                .await
+                .into_iter()
+                .map(|summary| EndpointTableRow::from_summary(summary, now, active_threshold))
+                .collect();
+            if let Ok(mut cache_guard) = get_endpoint_table_cache().lock() {
+                cache_guard.set(&cache_key, rows.clone());
+            }
+            rows
+        }
+    };
+
+    // Tags change independently of the summary too, so they're looked up per request
+    let tagged = match tag {
+        Some(tag) => Some(
+            tokio::task::spawn_blocking(move || {
+                let conn = new_connection_result().ok()?;
+                endpoint_ids_with_tags(&conn, &[tag]).ok()
+            })
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<HashSet<i64>>(),
+        ),
+        None => None,
+    };
+    let (mut endpoints, total) = apply_endpoints_table_query(rows, &query, tagged.as_ref());
+
+    // Web info changes independently of the summary, so it isn't cached with the rows
+    let web_info = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result().ok()?;
+        get_web_info_summaries(&conn).ok()
+    })
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+    for row in &mut endpoints {
+        if let Some(info) = web_info.get(&row.name.to_lowercase()) {
+            row.web_title = info.title.clone();
+            row.has_favicon = info.has_favicon;
+        }
+    }
+
+    HttpResponse::Ok().json(EndpointsTableResponse { endpoints, total })
+}
+
+/// Look-back window for the endpoint table and export (same default as index route)
+const ENDPOINT_TABLE_WINDOW_MINUTES: u64 = 525600;
+/// How often the materializer folds dirty endpoints into endpoint_summary
+const SUMMARY_INCREMENTAL_INTERVAL_SECS: u64 = 5;
+/// How often endpoint_summary is rebuilt from scratch, picking up changes (renames,
+/// probes, manual overrides) that don't pass through the packet write path
+const SUMMARY_FULL_REFRESH_SECS: u64 = 60;
+
+impl EndpointTableRow {
+    fn from_summary(summary: EndpointSummary, now: i64, active_threshold: i64) -> Self {
+        EndpointTableRow {
+            last_seen: summary
+                .last_seen_at
+                .map(|ts| format_last_seen(now - ts))
+                .unwrap_or_else(|| "-".to_string()),
+            online: summary
+                .last_seen_at
+                .is_some_and(|ts| ts >= now - active_threshold),
+            last_seen_at: summary.last_seen_at,
+            endpoint_id: summary.endpoint_id,
+            name: summary.name,
+            vendor: summary.vendor,
+            model: summary.model,
+            device_type: summary.device_type,
+            bytes: summary.bytes,
+            web_title: None,
+            has_favicon: false,
+        }
+    }
+}
+
+/// Endpoints listed in the endpoint table, optionally only those seen on `interface`.
+/// The unfiltered list is read from the materialized endpoint_summary table; filtered
+/// lists (and the first requests after startup, before materialization) are computed.
+async fn load_endpoint_summaries(interface: Option<String>) -> Vec<EndpointSummary> {
+    if interface.is_none() {
+        let materialized = tokio::task::spawn_blocking(|| {
+            let conn = new_connection_result().ok()?;
+            if !has_endpoint_summaries(&conn) {
+                return None;
+            }
+            let since =
+                chrono::Utc::now().timestamp() - (ENDPOINT_TABLE_WINDOW_MINUTES * 60) as i64;
+            get_endpoint_summaries(&conn, since).ok()
+        })
+        .await
+        .ok()
+        .flatten();
+        if let Some(summaries) = materialized {
+            return summaries;
+        }
+    }
+
+    let names = tokio::task::spawn_blocking(move || {
+        dropdown_endpoints(ENDPOINT_TABLE_WINDOW_MINUTES, interface.as_deref())
+    })
+    .await
+    .unwrap_or_default();
+    summarize_endpoints(names).await
+}
+
+/// Start the background task that keeps endpoint_summary current
+pub(super) fn start_endpoint_summary_materializer() {
+    tokio::spawn(async {
+        let mut last_full_refresh: Option<std::time::Instant> = None;
+        loop {
+            if last_full_refresh.is_none_or(|t| t.elapsed().as_secs() >= SUMMARY_FULL_REFRESH_SECS)
+            {
+                materialize_all_endpoint_summaries().await;
+                last_full_refresh = Some(std::time::Instant::now());
+            } else {
+                materialize_dirty_endpoint_summaries().await;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                SUMMARY_INCREMENTAL_INTERVAL_SECS,
+            ))
+            .await;
+        }
+    });
+}
+
+async fn materialize_all_endpoint_summaries() {
+    // Anything marked from here on is picked up by the next incremental pass
+    take_dirty_endpoint_summaries();
+
+    let names =
+        tokio::task::spawn_blocking(|| dropdown_endpoints(ENDPOINT_TABLE_WINDOW_MINUTES, None))
+            .await
+            .unwrap_or_default();
+    let summaries = summarize_endpoints(names).await;
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        replace_endpoint_summaries(&conn, &summaries)
+    })
+    .await;
+    if let Ok(Err(e)) = result {
+        eprintln!("Failed to materialize endpoint summaries: {}", e);
+    }
+}
+
+async fn materialize_dirty_endpoint_summaries() {
+    let dirty = take_dirty_endpoint_summaries();
+
+    // Work out which display names to recompute and which rows to drop
+    let plan = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        let stale = get_stale_endpoint_summaries(&conn).unwrap_or_default();
+        let mut ids: Vec<i64> = dirty;
+        ids.extend(stale.iter().filter_map(|(_, id)| *id));
+        if ids.is_empty() && stale.is_empty() {
+            return None;
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        if !ids.is_empty() {
+            let sql = format!(
+                "SELECT DISTINCT display_name FROM endpoints
+                 WHERE id IN ({}) AND display_name IS NOT NULL",
+                build_in_placeholders(ids.len())
+            );
+            let params = box_i64_params(&ids);
+            if let Ok(mut stmt) = conn.prepare(&sql)
+                && let Ok(rows) = stmt.query_map(params_to_refs(&params).as_slice(), |row| {
+                    row.get::<_, String>(0)
+                })
+            {
+                names.extend(rows.flatten());
+            }
+        }
+
+        // Only endpoints the table lists get a summary row
+        let listed: HashSet<String> = dropdown_endpoints(ENDPOINT_TABLE_WINDOW_MINUTES, None)
+            .iter()
+            .map(|n| n.to_lowercase())
+            .collect();
+        let (refresh, unlisted): (Vec<String>, Vec<String>) = names
+            .into_iter()
+            .partition(|n| listed.contains(&n.to_lowercase()));
+        let mut removed: Vec<String> = stale.into_iter().map(|(name, _)| name).collect();
+        removed.extend(unlisted);
+        Some((refresh, removed))
+    })
+    .await
+    .ok()
+    .flatten();
+
+    let Some((refresh, removed)) = plan else {
+        return;
+    };
+    let summaries = summarize_endpoints(refresh).await;
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        delete_endpoint_summaries(&conn, &removed)?;
+        upsert_endpoint_summaries(&conn, &summaries)
+    })
+    .await;
+    if let Ok(Err(e)) = result {
+        eprintln!("Failed to update endpoint summaries: {}", e);
+    }
+}
+
+/// Compute summaries (vendor, model, type, traffic and addresses) for the given
+/// display names. This is the expensive fan-out the materializer runs in the background.
+async fn summarize_endpoints(dropdown_endpoints_list: Vec<String>) -> Vec<EndpointSummary> {
+    let scan_interval = ENDPOINT_TABLE_WINDOW_MINUTES;
+
+    if dropdown_endpoints_list.is_empty() {
+        return Vec::new();
+    }
+
+    // Prepare for parallel queries
+    let dropdown_for_ids = dropdown_endpoints_list.clone();
+    let dropdown_for_stats = dropdown_endpoints_list.clone();
+    let dropdown_for_types = dropdown_endpoints_list.clone();
+    let dropdown_for_ips = dropdown_endpoints_list.clone();
+    let dropdown_for_ssdp = dropdown_endpoints_list.clone();
+
+    // OPTIMIZATION: Combined stats query (replaces separate queries for bytes and last_seen)
+    let stats_future = tokio::task::spawn_blocking(move || {
+        get_combined_endpoint_stats(&dropdown_for_stats, scan_interval)
+    });
+
+    let all_types_future =
+        tokio::task::spawn_blocking(move || get_all_endpoint_types(&dropdown_for_types));
+
+    // Fetch vendor/model data (always fresh - removed cache that caused stale data issues)
+    let ips_macs_future =
+        tokio::task::spawn_blocking(move || get_endpoint_ips_and_macs(&dropdown_for_ips));
+    let ssdp_models_future =
+        tokio::task::spawn_blocking(move || get_endpoint_ssdp_models(&dropdown_for_ssdp));
+
+    let ids_future =
+        tokio::task::spawn_blocking(move || get_endpoint_ids_by_name(&dropdown_for_ids));
+
+    // Run all queries in parallel
+    let (stats_result, all_types_result, ips_macs_result, ssdp_models_result, ids_result) = tokio::join!(
+        stats_future,
+        all_types_future,
+        ips_macs_future,
+        ssdp_models_future,
+        ids_future
+    );
+
+    let endpoint_stats = stats_result.unwrap_or_default();
+    let endpoint_ids = ids_result.unwrap_or_default();
+    let (dropdown_types, _manual_overrides) = all_types_result.unwrap_or_default();
+    let endpoint_ips_macs = ips_macs_result.unwrap_or_default();
+    let endpoint_ssdp_models = ssdp_models_result.unwrap_or_default();
+
+    // Build vendor lookup
+    let endpoint_vendors: HashMap<String, String> =
+        dropdown_endpoints_list
+            .iter()
+            .filter_map(|endpoint| {
+                let endpoint_lower = endpoint.to_lowercase();
+                let (
+                    _custom_model,
+                    ssdp_model,
+                    ssdp_friendly,
+                    custom_vendor,
+                    snmp_vendor,
+                    _snmp_model,
+                ) = endpoint_ssdp_models
+                    .get(&endpoint_lower)
+                    .map(|(cm, sm, sf, cv, sv, snm)| {
+                        (
+                            cm.as_deref(),
+                            sm.as_deref(),
+                            sf.as_deref(),
+                            cv.as_deref(),
+                            sv.as_deref(),
+                            snm.as_deref(),
+                        )
+                    })
+                    .unwrap_or((None, None, None, None, None, None));
+
+                let macs = endpoint_ips_macs
+                    .get(&endpoint_lower)
+                    .map(|(_, m)| product_vendor_macs(m))
+                    .unwrap_or_default();
+
+                characterize_vendor(
+                    custom_vendor,
+                    ssdp_friendly,
+                    snmp_vendor,
+                    Some(endpoint.as_str()),
+                    &macs,
+                    ssdp_model,
+                )
+                .map(|c| (endpoint_lower, c.value))
+            })
+            .collect();
+
+    // Build model lookup (first pass without device_type)
+    let mut endpoint_models: HashMap<String, String> = dropdown_endpoints_list
+        .iter()
+        .filter_map(|endpoint| {
+            let endpoint_lower = endpoint.to_lowercase();
+            let (custom_model, ssdp_model, _, _, _, snmp_model) = endpoint_ssdp_models
+                .get(&endpoint_lower)
+                .map(|(cm, sm, sf, cv, sv, snm)| {
+                    (
+                        cm.as_deref(),
+                        sm.as_deref(),
+                        sf.as_deref(),
+                        cv.as_deref(),
+                        sv.as_deref(),
+                        snm.as_deref(),
+                    )
+                })
+                .unwrap_or((None, None, None, None, None, None));
+
+            let macs: Vec<String> = endpoint_ips_macs
+                .get(&endpoint_lower)
+                .map(|(_, m)| m.clone())
+                .unwrap_or_default();
+
+            let vendor = endpoint_vendors.get(&endpoint_lower).map(|v| v.as_str());
+
+            characterize_model(
+                custom_model,
+                ssdp_model,
+                snmp_model,
+                Some(endpoint.as_str()),
+                &macs,
+                vendor,
+                None,
+            )
+            .map(|c| (endpoint_lower, c.value))
+        })
+        .collect();
+
+    // Second pass: enhance models using vendor + device type for endpoints without models
+    for endpoint in &dropdown_endpoints_list {
+        let endpoint_lower = endpoint.to_lowercase();
+        if !endpoint_models.contains_key(&endpoint_lower)
+            && let Some(vendor) = endpoint_vendors.get(&endpoint_lower)
+            && let Some(device_type) = dropdown_types.get(&endpoint_lower)
+            && let Some(model) = get_model_from_vendor_and_type(vendor, device_type)
+        {
+            endpoint_models.insert(endpoint_lower, model);
+        }
+    }
+
+    dropdown_endpoints_list
+        .iter()
+        .filter_map(|endpoint| {
+            let endpoint_lower = endpoint.to_lowercase();
+            let stats = endpoint_stats.get(&endpoint_lower);
+            let (ips, macs) = endpoint_ips_macs
+                .get(&endpoint_lower)
+                .cloned()
+                .unwrap_or_default();
+            Some(EndpointSummary {
+                endpoint_id: *endpoint_ids.get(&endpoint_lower)?,
+                name: endpoint.clone(),
+                vendor: endpoint_vendors.get(&endpoint_lower).cloned(),
+                model: endpoint_models.get(&endpoint_lower).cloned(),
+                device_type: dropdown_types.get(&endpoint_lower).map(|s| s.to_string()),
+                bytes: stats.map(|s| s.bytes).unwrap_or(0),
+                last_seen_at: stats.and_then(|s| s.last_seen_at),
+                ips,
+                macs,
+            })
+        })
+        .collect()
+}
+
+/// Map lowercase display names to an endpoint id (the lowest, if several share a name)
+fn get_endpoint_ids_by_name(names: &[String]) -> HashMap<String, i64> {
+    let mut ids = HashMap::new();
+    let Ok(conn) = new_connection_result() else {
+        return ids;
+    };
+    let sql = format!(
+        "SELECT LOWER(display_name), MIN(id) FROM endpoints
+         WHERE display_name COLLATE NOCASE IN ({})
+         GROUP BY LOWER(display_name)",
+        build_in_placeholders(names.len())
+    );
+    if let Ok(mut stmt) = conn.prepare(&sql)
+        && let Ok(rows) = stmt.query_map(rusqlite::params_from_iter(names), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+    {
+        ids.extend(rows.flatten());
+    }
+    ids
+}
+
+// ============================================================================
+// Interface Statistics
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct InterfaceStatsQuery {
+    /// Look-back window in minutes (defaults to one year, like the main view)
+    scan_interval: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct InterfaceStats {
+    interface: String,
+    endpoints: i64,
+    communications: i64,
+    packets: i64,
+    bytes: i64,
+    last_seen_at: Option<i64>,
+}
+
+/// Per-interface traffic and endpoint counts for live-captured communications
+#[get("/api/interfaces")]
+pub async fn get_interface_stats(query: Query<InterfaceStatsQuery>) -> impl Responder {
+    let scan_interval = query.scan_interval.unwrap_or(525600);
+
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<InterfaceStats>> {
+        let conn = new_connection_result()?;
+        let mut stmt = conn.prepare(
+            "SELECT c.interface,
+                    COUNT(*),
+                    COALESCE(SUM(c.packet_count), 0),
+                    COALESCE(SUM(c.bytes), 0),
+                    MAX(c.last_seen_at),
+                    COALESCE(ep.endpoints, 0)
+             FROM communications c
+             LEFT JOIN (
+                 SELECT interface, COUNT(DISTINCT endpoint_id) AS endpoints
+                 FROM (
+                     SELECT interface, src_endpoint_id AS endpoint_id, last_seen_at FROM communications
+                     UNION ALL
+                     SELECT interface, dst_endpoint_id AS endpoint_id, last_seen_at FROM communications
+                 )
+                 WHERE endpoint_id IS NOT NULL
+                   AND last_seen_at >= (strftime('%s', 'now') - (?1 * 60))
+                 GROUP BY interface
+             ) ep ON ep.interface = c.interface
+             WHERE c.interface IS NOT NULL
+               AND c.last_seen_at >= (strftime('%s', 'now') - (?1 * 60))
+             GROUP BY c.interface
+             ORDER BY 4 DESC",
+        )?;
+        let rows = stmt.query_map([scan_interval], |row| {
+            Ok(InterfaceStats {
+                interface: row.get(0)?,
+                communications: row.get(1)?,
+                packets: row.get(2)?,
+                bytes: row.get(3)?,
+                last_seen_at: row.get(4)?,
+                endpoints: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    })
+    .await;
+
+    match result {
+        Ok(Ok(interfaces)) => {
+            HttpResponse::Ok().json(serde_json::json!({ "interfaces": interfaces }))
+        }
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch interface statistics"
+        })),
+    }
+}
+
+// ============================================================================
+// Export Endpoints
+// ============================================================================
+
+#[get("/api/export/endpoints.xlsx")]
+pub async fn export_endpoints_xlsx() -> impl Responder {
+    let summaries = load_endpoint_summaries(None).await;
+
+    if summaries.is_empty() {
+        return HttpResponse::Ok()
+            .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+            .insert_header((
+                "Content-Disposition",
+                "attachment; filename=\"endpoints.xlsx\"",
+            ))
+            .body(Vec::new());
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let active_threshold = get_setting_i64("active_threshold_seconds", 120);
+
+    // Create Excel workbook
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Endpoints").ok();
+
+    // Header format
+    let header_format = Format::new().set_bold();
+
+    // Write headers
+    let headers = [
+        "Name",
+        "IP",
+        "MAC",
+        "Vendor",
+        "Model",
+        "Device Type",
+        "Last Seen",
+        "Online",
+    ];
+    for (col, header) in headers.iter().enumerate() {
+        worksheet
+            .write_string_with_format(0, col as u16, *header, &header_format)
+            .ok();
+    }
+
+    // Write data rows
+    for (row_idx, summary) in summaries.into_iter().enumerate() {
+        let row = (row_idx + 1) as u32;
+        let ips = summary.ips.join(", ");
+        let macs = summary.macs.join(", ");
+        let endpoint = EndpointTableRow::from_summary(summary, now, active_threshold);
+
+        worksheet.write_string(row, 0, &endpoint.name).ok();
+        worksheet.write_string(row, 1, ips).ok();
+        worksheet.write_string(row, 2, macs).ok();
+        worksheet
+            .write_string(row, 3, endpoint.vendor.as_deref().unwrap_or(""))
+            .ok();
+        worksheet
+            .write_string(row, 4, endpoint.model.as_deref().unwrap_or(""))
+            .ok();
+        worksheet
+            .write_string(row, 5, endpoint.device_type.as_deref().unwrap_or(""))
+            .ok();
+        worksheet.write_string(row, 6, &endpoint.last_seen).ok();
+        worksheet
+            .write_string(row, 7, if endpoint.online { "Yes" } else { "No" })
+            .ok();
+    }
+
+    // Set column widths for readability
+    worksheet.set_column_width(0, 30).ok(); // Name
+    worksheet.set_column_width(1, 15).ok(); // IP
+    worksheet.set_column_width(2, 20).ok(); // MAC
+    worksheet.set_column_width(3, 15).ok(); // Vendor
+    worksheet.set_column_width(4, 20).ok(); // Model
+    worksheet.set_column_width(5, 15).ok(); // Device Type
+    worksheet.set_column_width(6, 20).ok(); // Last Seen
+    worksheet.set_column_width(7, 8).ok(); // Online
+
+    // Save to buffer
+    let buffer = match workbook.save_to_buffer() {
+        Ok(buf) => buf,
+        Err(e) => {
+            eprintln!("Failed to create Excel file: {}", e);
+            return HttpResponse::InternalServerError().body("Failed to create Excel file");
         }
-        ScanResult::Snmp(snmp) => {
-            let ip_str = snmp.ip.to_string();
-            // For SNMP (no MAC from packet), only record if endpoint already exists
-            if let Some(endpoint_id) = find_existing_endpoint_by_ip(&conn, &ip_str) {
-                let details = serde_json::json!({
-                    "sys_descr": snmp.sys_descr,
-                    "sys_object_id": snmp.sys_object_id,
-                    "sys_name": snmp.sys_name,
-                    "sys_location": snmp.sys_location,
-                    "community": snmp.community,
-                });
-                insert_scan_result(&conn, endpoint_id, "snmp", None, Some(&details.to_string()))?;
+    };
 
-                // Extract vendor/model info from sysDescr if available
-                if let Some(ref sys_descr) = snmp.sys_descr {
-                    let (vendor, model) = parse_snmp_sys_descr(sys_descr);
+    HttpResponse::Ok()
+        .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+        .insert_header((
+            "Content-Disposition",
+            "attachment; filename=\"endpoints.xlsx\"",
+        ))
+        .body(buffer)
+}
 
-                    // Update vendor if we found one and endpoint doesn't have one
-                    if let Some(v) = &vendor {
-                        match conn.execute(
-                            "UPDATE endpoints SET snmp_vendor = ?1 WHERE id = ?2 AND (snmp_vendor IS NULL OR snmp_vendor = '')",
-                            params![v, endpoint_id],
-                        ) {
-                            Ok(rows) if rows > 0 => {
-                                insert_notification_with_endpoint_id(
-                                    &conn, "vendor_identified",
-                                    &format!("Vendor identified: {}", v),
-                                    None, None, Some(endpoint_id),
-                                );
-                            }
-                            Err(e) => eprintln!("Failed to save SNMP vendor: {}", e),
-                            _ => {}
-                        }
-                    }
+#[derive(Deserialize)]
+pub struct GraphExportQuery {
+    /// "graphml", "dot" or "json" (Cytoscape.js elements)
+    format: Option<String>,
+    scan_interval: Option<u64>,
+    /// Comma-separated device types; only traffic to or from these is exported
+    device_types: Option<String>,
+    /// Comma-separated endpoint tags; only traffic to or from endpoints carrying one is
+    /// exported
+    tags: Option<String>,
+    interface: Option<String>,
+    exclude_noise: Option<bool>,
+}
 
-                    // Update model if we found one and endpoint doesn't have one
-                    if let Some(m) = &model {
-                        match conn.execute(
-                            "UPDATE endpoints SET snmp_model = ?1 WHERE id = ?2 AND (snmp_model IS NULL OR snmp_model = '')",
-                            params![m, endpoint_id],
-                        ) {
-                            Ok(rows) if rows > 0 => {
-                                insert_notification_with_endpoint_id(
-                                    &conn, "model_identified",
-                                    &format!("Device model identified: {}", m),
-                                    None, None, Some(endpoint_id),
-                                );
-                            }
-                            Err(e) => eprintln!("Failed to save SNMP model: {}", e),
-                            _ => {}
-                        }
-                    }
-                }
+/// The communication graph as GraphML, Graphviz DOT or Cytoscape JSON, for Gephi and
+/// Cytoscape
+#[get("/api/export/graph")]
+pub async fn export_graph(query: Query<GraphExportQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let format_name = query.format.unwrap_or_else(|| "json".to_string());
+    let Some(format) = GraphFormat::parse(&format_name) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unknown format '{}' (expected graphml, dot or json)", format_name)
+        }));
+    };
+    let tags: Vec<String> = query
+        .tags
+        .unwrap_or_default()
+        .split(',')
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    let tags = match parse_tags(&tags) {
+        Ok(tags) => tags,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    let internal_minutes = query.scan_interval.unwrap_or(60) as i64;
+    let filter = GraphFilter {
+        since: chrono::Utc::now().timestamp() - internal_minutes * 60,
+        tags,
+        device_types: query
+            .device_types
+            .unwrap_or_default()
+            .split(',')
+            .map(|device_type| device_type.trim().to_string())
+            .filter(|device_type| !device_type.is_empty())
+            .collect(),
+        interface: query.interface.filter(|interface| !interface.is_empty()),
+        exclude_noise: query.exclude_noise.unwrap_or(false),
+    };
 
-                // Update endpoint name from sysName if name is just an IP
-                if let Some(ref sys_name) = snmp.sys_name
-                    && !sys_name.is_empty()
-                {
-                    let _ = conn.execute(
-                        "UPDATE endpoints SET name = ?1 WHERE id = ?2 AND (name = ?3 OR name GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*')",
-                        params![sys_name, endpoint_id, ip_str],
-                    );
-                }
+    let result = tokio::task::spawn_blocking(move || {
+        get_communication_graph(&new_connection_result()?, &filter)
+    })
+    .await;
 
-                // If name still not set, try vendor+model from SNMP sysDescr
-                if let Some(ref sys_descr) = snmp.sys_descr {
-                    let (vendor, model) = parse_snmp_sys_descr(sys_descr);
-                    let name = model.or(vendor);
-                    try_set_endpoint_name_from_discovery(&conn, endpoint_id, name.as_deref());
-                }
-            }
+    let graph = match result {
+        Ok(Ok(graph)) => graph,
+        _ => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to build communication graph"
+            }));
         }
-    }
+    };
+    let body = match format {
+        GraphFormat::GraphMl => to_graphml(&graph),
+        GraphFormat::Dot => to_dot(&graph),
+        GraphFormat::Json => to_cytoscape_json(&graph).to_string(),
+    };
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", format.file_name()),
+        ))
+        .body(body)
+}
 
-    Ok(())
+#[derive(Deserialize)]
+pub struct TrafficMatrixQuery {
+    scan_interval: Option<u64>,
+    interface: Option<String>,
+    exclude_noise: Option<bool>,
+    /// A list of endpoint pairs instead of the N×N matrix
+    sparse: Option<bool>,
 }
 
-/// Try to set endpoint name from a discovered name (SSDP friendly name, SNMP model, etc.)
-/// Only updates if the endpoint currently has no valid display name.
-/// Appends (2), (3), etc. if another endpoint already has the same name.
-fn try_set_endpoint_name_from_discovery(conn: &Connection, endpoint_id: i64, name: Option<&str>) {
-    let name = match name {
-        Some(n) if !n.is_empty() && is_valid_display_name(n) => n,
-        _ => return,
+/// Bytes and flows between every pair of local endpoints over the window
+#[get("/api/matrix")]
+pub async fn get_traffic_matrix(query: Query<TrafficMatrixQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let internal_minutes = query.scan_interval.unwrap_or(60) as i64;
+    let filter = GraphFilter {
+        since: chrono::Utc::now().timestamp() - internal_minutes * 60,
+        interface: query.interface.filter(|interface| !interface.is_empty()),
+        exclude_noise: query.exclude_noise.unwrap_or(false),
+        ..Default::default()
     };
+    let sparse = query.sparse.unwrap_or(false);
 
-    let current_name: String = conn
-        .query_row(
-            "SELECT COALESCE(name, '') FROM endpoints WHERE id = ?",
-            params![endpoint_id],
-            |row| row.get(0),
-        )
-        .unwrap_or_default();
+    let result = tokio::task::spawn_blocking(move || {
+        get_communication_graph(&new_connection_result()?, &filter)
+    })
+    .await;
 
-    if !is_valid_display_name(&current_name) {
-        let unique = EndPoint::make_unique_endpoint_name(conn, name, endpoint_id);
-        let _ = conn.execute(
-            "UPDATE endpoints SET name = ?1 WHERE id = ?2",
-            params![unique, endpoint_id],
-        );
+    match result {
+        Ok(Ok(graph)) => HttpResponse::Ok().json(build_traffic_matrix(
+            &graph,
+            |node| {
+                node.ip
+                    .as_deref()
+                    .is_some_and(EndPoint::is_on_local_network)
+            },
+            sparse,
+        )),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to build traffic matrix"
+        })),
     }
 }
 
-/// Find an existing endpoint by IP address (must have a MAC to be considered valid)
-/// Returns None if no endpoint with a MAC exists for this IP
-fn find_existing_endpoint_by_ip(conn: &Connection, ip: &str) -> Option<i64> {
-    conn.query_row(
-        "SELECT ea.endpoint_id FROM endpoint_attributes ea
-         WHERE ea.ip = ?1 AND ea.mac IS NOT NULL AND ea.mac != ''
-         LIMIT 1",
-        params![ip],
-        |row| row.get(0),
-    )
-    .optional()
-    .ok()
-    .flatten()
+// ============================================================================
+// Settings Endpoints
+// ============================================================================
+
+#[derive(Serialize)]
+pub struct SettingsResponse {
+    settings: std::collections::HashMap<String, String>,
+    /// Type, range, default, and description of every setting that can be changed
+    schema: &'static [SettingDefinition],
 }
 
-/// Parse SNMP sysDescr to extract vendor and model information
-/// Returns (vendor, model) as Option strings
-fn parse_snmp_sys_descr(sys_descr: &str) -> (Option<String>, Option<String>) {
-    let descr_lower = sys_descr.to_lowercase();
+#[derive(Deserialize)]
+pub struct UpdateSettingRequest {
+    key: String,
+    value: String,
+}
 
-    // Common vendor patterns in sysDescr
-    let vendor_patterns: &[(&str, &str)] = &[
-        ("hewlett-packard", "HP"),
-        ("hp ", "HP"),
-        ("cisco", "Cisco"),
-        ("synology", "Synology"),
-        ("qnap", "QNAP"),
-        ("netgear", "NETGEAR"),
-        ("linksys", "Linksys"),
-        ("ubiquiti", "Ubiquiti"),
-        ("unifi", "Ubiquiti"),
-        ("mikrotik", "MikroTik"),
-        ("tp-link", "TP-Link"),
-        ("asus", "ASUS"),
-        ("d-link", "D-Link"),
-        ("buffalo", "Buffalo"),
-        ("brother", "Brother"),
-        ("canon", "Canon"),
-        ("epson", "Epson"),
-        ("xerox", "Xerox"),
-        ("ricoh", "Ricoh"),
-        ("dell", "Dell"),
-        ("lenovo", "Lenovo"),
-        ("apple", "Apple"),
-        ("asustor", "ASUSTOR"),
-        ("drobo", "Drobo"),
-        ("western digital", "Western Digital"),
-        ("seagate", "Seagate"),
-        ("aruba", "Aruba"),
-        ("juniper", "Juniper"),
-        ("fortinet", "Fortinet"),
-        ("paloalto", "Palo Alto"),
-        ("sonicwall", "SonicWall"),
-    ];
+#[derive(Serialize)]
+pub struct UpdateSettingResponse {
+    success: bool,
+    message: String,
+}
 
-    let mut vendor: Option<String> = None;
-    for (pattern, name) in vendor_patterns {
-        // "hp " needs word boundary check to avoid matching "chapter", "graph ", etc.
-        if *pattern == "hp " {
-            if descr_lower.starts_with("hp ")
-                || descr_lower.contains(" hp ")
-                || descr_lower.contains("\nhp ")
-            {
-                vendor = Some(name.to_string());
-                break;
-            }
-        } else if descr_lower.contains(pattern) {
-            vendor = Some(name.to_string());
-            break;
-        }
-    }
+#[get("/api/settings")]
+pub async fn get_settings() -> impl Responder {
+    let settings = tokio::task::spawn_blocking(get_all_settings)
+        .await
+        .unwrap_or_default();
 
-    // Try to extract model - look for common patterns
-    let mut model: Option<String> = None;
+    HttpResponse::Ok().json(SettingsResponse {
+        settings,
+        schema: SETTINGS,
+    })
+}
 
-    // HP printer pattern: "PID:HP Color LaserJet..." - common in HP printer SNMP
-    if let Some(idx) = descr_lower.find("pid:hp") {
-        let after_pid = &sys_descr[idx + 4..]; // Skip "PID:"
-        // Take the HP model name - everything after "HP " until end or comma
-        let trimmed = after_pid.trim();
-        // HP models typically end at the end of string or before a comma
-        let model_str = if let Some(end) = trimmed.find(',') {
-            trimmed[..end].trim()
-        } else {
-            trimmed
-        };
-        if model_str.len() > 2 {
-            model = Some(model_str.to_string());
-            // Also set vendor to HP if not already set
-            if vendor.is_none() {
-                vendor = Some("HP".to_string());
-            }
+#[post("/api/settings")]
+pub async fn update_setting(
+    _admin: RequireAdmin,
+    body: Json<UpdateSettingRequest>,
+) -> impl Responder {
+    let key = body.key.clone();
+    let value = match validate_setting(&key, &body.value) {
+        Ok(value) => value,
+        Err(message) => {
+            return HttpResponse::BadRequest().json(UpdateSettingResponse {
+                success: false,
+                message,
+            });
         }
-    }
-
-    // Pattern: "Model: XYZ" or "Model XYZ"
-    if model.is_none()
-        && let Some(idx) = descr_lower.find("model")
-    {
-        let after_model = &sys_descr[idx + 5..];
-        let trimmed = after_model.trim_start_matches([':', ' ']);
-        if let Some(end) = trimmed.find([',', ';', '\n', '\r']) {
-            let m = trimmed[..end].trim();
-            if !m.is_empty() {
-                model = Some(m.to_string());
-            }
-        } else if !trimmed.is_empty() {
-            // Take first word/phrase
-            let m = trimmed
-                .split_whitespace()
-                .take(3)
-                .collect::<Vec<_>>()
-                .join(" ");
-            if !m.is_empty() {
-                model = Some(m);
+    };
+
+    let stored = value.clone();
+    let result = tokio::task::spawn_blocking(move || set_setting(&key, &value)).await;
+
+    match result {
+        Ok(Ok(())) => {
+            // The scanner only reads its saved config at startup
+            if body.key == SCAN_CONFIG_SETTING {
+                let config = ScanConfig::from_json(&stored).unwrap_or_default();
+                get_scan_manager().set_config(config).await;
             }
+            HttpResponse::Ok().json(UpdateSettingResponse {
+                success: true,
+                message: format!("Setting '{}' updated", body.key),
+            })
         }
+        _ => HttpResponse::InternalServerError().json(UpdateSettingResponse {
+            success: false,
+            message: "Failed to update setting".to_string(),
+        }),
     }
+}
 
-    // For HP printers, look for "HP XXXX" pattern
-    if model.is_none()
-        && vendor.as_deref() == Some("HP")
-        && let Some(idx) = descr_lower.find("hp ")
-    {
-        let after_hp = &sys_descr[idx + 3..];
-        // Take first word(s) that look like a model
-        let parts: Vec<&str> = after_hp.split_whitespace().take(3).collect();
-        if !parts.is_empty() {
-            let m = parts.join(" ");
-            if m.len() > 2 {
-                model = Some(m);
-            }
-        }
+/// Each config option's effective value and whether it came from the config file, the
+/// environment, the settings table, a command-line flag, or the built-in default
+#[get("/api/config/effective")]
+pub async fn get_effective_config() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| effective_config(&new_connection())).await;
+
+    match result {
+        Ok(Ok(config)) => HttpResponse::Ok().json(config),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to read effective config"
+        })),
     }
+}
 
-    // For Synology NAS, extract model from pattern like "DS920+"
-    if model.is_none() && vendor.as_deref() == Some("Synology") {
-        // Look for DS/RS followed by numbers
-        for word in sys_descr.split_whitespace() {
-            let w = word.to_uppercase();
-            if (w.starts_with("DS") || w.starts_with("RS")) && w.len() > 2 {
-                let rest = &w[2..];
-                if rest
-                    .chars()
-                    .next()
-                    .map(|c| c.is_ascii_digit())
-                    .unwrap_or(false)
-                {
-                    model = Some(word.to_string());
-                    break;
-                }
-            }
-        }
+/// Status of each component (capture per interface, SQL writer, scanner, mDNS, web)
+/// with its last error, plus uptime and the supervised task list. Answers 503 unless
+/// every component is ok, so monitors can alert on it.
+#[get("/api/health")]
+pub async fn get_health() -> impl Responder {
+    let tasks = task_health();
+
+    // A scan type that panicked leaves the scanner degraded until it next completes
+    let scan_status = get_scan_manager().get_status().await;
+    let scan_failure = tasks
+        .iter()
+        .filter(|task| task.name.starts_with("scan") && task.state == TaskState::Failed)
+        .max_by_key(|task| task.last_error_at);
+    let scanner = ComponentHealth {
+        name: "scanner".to_string(),
+        status: if scan_failure.is_some() {
+            ComponentStatus::Degraded
+        } else {
+            ComponentStatus::Ok
+        },
+        last_error: scan_failure.and_then(|task| task.last_error.clone()),
+        last_error_at: scan_failure.and_then(|task| task.last_error_at),
+        details: serde_json::json!({
+            "running": scan_status.running,
+            "last_scan_time": scan_status.last_scan_time,
+        }),
+    };
+    // Answering this request is proof enough
+    let web = ComponentHealth {
+        name: "web".to_string(),
+        status: ComponentStatus::Ok,
+        last_error: None,
+        last_error_at: None,
+        details: serde_json::Value::Null,
+    };
+
+    let mut components = capture_components();
+    components.extend([writer_component(), scanner, mdns_component(), web]);
+    let status = overall_status(&components);
+    let body = serde_json::json!({
+        "status": status,
+        "uptime_secs": health::uptime_secs(),
+        "started_at": health::started_at(),
+        "components": components,
+        "tasks": tasks,
+    });
+    if status == ComponentStatus::Ok {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
     }
+}
 
-    (vendor, model)
+// ============================================================================
+// Capture Pause Endpoint
+// ============================================================================
+
+#[derive(Serialize)]
+pub struct CapturePauseResponse {
+    success: bool,
+    paused: bool,
+    message: String,
 }
 
-/// Check if SSDP model is consistent with endpoint's MAC vendor.
-/// Prevents saving mismatched SSDP data when IP addresses get reassigned.
-fn is_ssdp_model_consistent_with_endpoint(
-    conn: &Connection,
-    endpoint_id: i64,
-    ssdp_model: &str,
-) -> bool {
-    // Get MAC addresses for this endpoint
-    let macs: Vec<String> = conn
-        .prepare("SELECT DISTINCT mac FROM endpoint_attributes WHERE endpoint_id = ?1 AND mac IS NOT NULL AND mac != ''")
-        .and_then(|mut stmt| {
-            stmt.query_map(params![endpoint_id], |row| row.get(0))
-                .map(|rows| rows.filter_map(|r| r.ok()).collect())
-        })
-        .unwrap_or_default();
+#[derive(Serialize)]
+pub struct CaptureStatusResponse {
+    success: bool,
+    paused: bool,
+    /// The capture schedule is holding capture (outside its windows, or during a backup)
+    scheduled_pause: bool,
+    message: String,
+}
 
-    if macs.is_empty() {
-        return true; // No MAC to validate against, allow it
-    }
-
-    let model_lower = ssdp_model.to_lowercase();
-
-    // Extract brand names from the SSDP model
-    // Common streaming device brands that we want to match
-    let ssdp_brands: Vec<&str> = [
-        "roku",
-        "onn",
-        "tcl",
-        "hisense",
-        "samsung",
-        "lg",
-        "sony",
-        "vizio",
-        "apple",
-        "amazon",
-        "fire",
-        "chromecast",
-        "google",
-        "nvidia",
-        "xbox",
-        "playstation",
-        "hp",
-        "epson",
-        "canon",
-        "brother",
-    ]
-    .iter()
-    .filter(|brand| model_lower.contains(*brand))
-    .copied()
-    .collect();
-
-    // If no recognizable brand in SSDP model, allow it
-    if ssdp_brands.is_empty() {
-        return true;
-    }
+/// Get capture pause status
+#[get("/api/capture/status")]
+pub async fn get_capture_status() -> impl Responder {
+    let paused = crate::is_capture_paused();
+    let scheduled_pause = is_capture_scheduled_off();
+    HttpResponse::Ok().json(CaptureStatusResponse {
+        success: true,
+        paused,
+        scheduled_pause,
+        message: if paused {
+            "Capture is paused".to_string()
+        } else if scheduled_pause {
+            "Capture is paused by its schedule".to_string()
+        } else {
+            "Capture is running".to_string()
+        },
+    })
+}
 
-    // Check each MAC's vendor against the SSDP brands
-    for mac in &macs {
-        if let Some(mac_vendor) = get_mac_vendor(mac) {
-            let vendor_lower = mac_vendor.to_lowercase();
+/// Toggle capture pause state
+#[post("/api/capture/pause")]
+pub async fn toggle_capture_pause(_admin: RequireAdmin) -> impl Responder {
+    let currently_paused = crate::is_capture_paused();
+    let new_state = !currently_paused;
+    let _ = tokio::task::spawn_blocking(move || crate::set_capture_paused(new_state)).await;
 
-            // If MAC vendor matches any SSDP brand, it's consistent
-            for brand in &ssdp_brands {
-                if vendor_lower.contains(brand) || brand.contains(vendor_lower.as_str()) {
-                    return true;
-                }
-            }
+    HttpResponse::Ok().json(CapturePauseResponse {
+        success: true,
+        paused: new_state,
+        message: if new_state {
+            "Capture paused - live traffic will be ignored".to_string()
+        } else {
+            "Capture resumed - live traffic will be processed".to_string()
+        },
+    })
+}
 
-            // Special case: TCL/Hisense/Philips can run Roku OS
-            // If MAC is TCL/Hisense/Philips and SSDP says Roku, that's OK
-            if (vendor_lower.contains("tcl")
-                || vendor_lower.contains("hisense")
-                || vendor_lower.contains("philips"))
-                && ssdp_brands.contains(&"roku")
-            {
-                return true;
-            }
+/// Set capture pause state explicitly
+#[derive(Deserialize)]
+pub struct SetCapturePauseRequest {
+    paused: bool,
+}
 
-            // Special case: Earda is OEM for TCL Roku TVs
-            if vendor_lower.contains("earda")
-                && (ssdp_brands.contains(&"tcl") || ssdp_brands.contains(&"roku"))
-            {
-                return true;
-            }
-
-            // If we have a known vendor and SSDP brand doesn't match, reject
-            // This prevents "onn." SSDP data from being saved to a TCL device
-            if !vendor_lower.is_empty() && !ssdp_brands.is_empty() {
-                // Check for conflicting brands (onn vs tcl, samsung vs lg, etc.)
-                let conflicting_pairs = [
-                    ("onn", "tcl"),
-                    ("onn", "hisense"),
-                    ("onn", "samsung"),
-                    ("onn", "lg"),
-                    ("onn", "sony"),
-                    ("samsung", "lg"),
-                    ("samsung", "sony"),
-                    ("samsung", "tcl"),
-                    ("lg", "sony"),
-                    ("lg", "tcl"),
-                    ("lg", "samsung"),
-                    ("hp", "epson"),
-                    ("hp", "canon"),
-                    ("hp", "brother"),
-                    ("epson", "canon"),
-                    ("epson", "brother"),
-                    ("canon", "brother"),
-                ];
-
-                for (brand_a, brand_b) in conflicting_pairs {
-                    // If vendor is brand_a and ssdp is brand_b (or vice versa), it's a conflict
-                    if (vendor_lower.contains(brand_a) && ssdp_brands.contains(&brand_b))
-                        || (vendor_lower.contains(brand_b) && ssdp_brands.contains(&brand_a))
-                    {
-                        return false;
-                    }
-                }
-            }
-        }
-    }
+#[post("/api/capture/set-pause")]
+pub async fn set_capture_pause(
+    _admin: RequireAdmin,
+    body: Json<SetCapturePauseRequest>,
+) -> impl Responder {
+    let paused = body.paused;
+    let _ = tokio::task::spawn_blocking(move || crate::set_capture_paused(paused)).await;
 
-    true // Default to allowing if no clear conflict
+    HttpResponse::Ok().json(CapturePauseResponse {
+        success: true,
+        paused: body.paused,
+        message: if body.paused {
+            "Capture paused - live traffic will be ignored".to_string()
+        } else {
+            "Capture resumed - live traffic will be processed".to_string()
+        },
+    })
 }
 
-/// Check if new_model is more specific than current_model.
-/// Used to allow updating stored SSDP data when better info is discovered.
-fn is_more_specific_model(new_model: &str, current_model: &str) -> bool {
-    let new_lower = new_model.to_lowercase();
-    let current_lower = current_model.to_lowercase();
+/// Get the capture windows and whether they are holding capture right now
+#[get("/api/capture/schedule")]
+pub async fn get_capture_schedule_api() -> impl Responder {
+    let schedule = tokio::task::spawn_blocking(get_capture_schedule)
+        .await
+        .unwrap_or_default();
+    HttpResponse::Ok().json(serde_json::json!({
+        "schedule": schedule,
+        "scheduled_pause": is_capture_scheduled_off(),
+    }))
+}
 
-    // If they're the same, no need to update
-    if new_lower == current_lower {
-        return false;
+/// Replace the capture windows; an empty window list captures at any time
+#[post("/api/capture/schedule")]
+pub async fn set_capture_schedule_api(
+    _admin: RequireAdmin,
+    body: Json<CaptureSchedule>,
+) -> impl Responder {
+    let schedule = body.into_inner();
+    let windows = schedule.windows.len();
+    match tokio::task::spawn_blocking(move || set_capture_schedule(&schedule)).await {
+        Ok(Ok(())) => HttpResponse::Ok().json(UpdateSettingResponse {
+            success: true,
+            message: if windows == 0 {
+                "Capture schedule cleared".to_string()
+            } else {
+                format!("Capture schedule saved with {} window(s)", windows)
+            },
+        }),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(UpdateSettingResponse {
+            success: false,
+            message: e,
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(UpdateSettingResponse {
+            success: false,
+            message: "Failed to save capture schedule".to_string(),
+        }),
     }
+}
 
-    // New model is longer and contains the current model - likely more specific
-    // e.g., "Samsung The Frame 65" is more specific than "Samsung"
-    if new_model.len() > current_model.len() && new_lower.contains(&current_lower) {
-        return true;
-    }
+#[derive(Serialize)]
+pub struct CaptureExclusionsResponse {
+    exclusions: Vec<CaptureExclusion>,
+}
 
-    // Current model is very generic (just a brand name)
-    let generic_names = [
-        "samsung", "lg", "sony", "tcl", "hisense", "vizio", "roku", "apple", "google", "amazon",
-    ];
-    let current_is_generic = generic_names.iter().any(|g| current_lower == *g);
-    if current_is_generic && new_model.len() > current_model.len() {
-        return true;
-    }
+/// Devices whose traffic is dropped before it is recorded
+#[get("/api/capture/exclusions")]
+pub async fn get_capture_exclusions_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        get_capture_exclusions(&conn).map_err(|e| e.to_string())
+    })
+    .await;
 
-    // New model contains specific product identifiers that current lacks
-    let specific_indicators = [
-        "the frame",
-        "the serif",
-        "the sero",
-        "qled",
-        "oled",
-        "neo qled",
-        "nanocell",
-        "bravia",
-        "roku ultra",
-        "roku express",
-        "chromecast",
-        "fire tv",
-        "echo",
-        "homepod",
-    ];
-    let new_has_specific = specific_indicators.iter().any(|s| new_lower.contains(s));
-    let current_has_specific = specific_indicators
-        .iter()
-        .any(|s| current_lower.contains(s));
-    if new_has_specific && !current_has_specific {
-        return true;
+    match result {
+        Ok(Ok(exclusions)) => HttpResponse::Ok().json(CaptureExclusionsResponse { exclusions }),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to read capture exclusions: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to read capture exclusions"
+        })),
     }
+}
 
-    false
+/// An endpoint (by name) or a single MAC to exclude from, or return to, capture
+#[derive(Deserialize)]
+pub struct CaptureExclusionRequest {
+    endpoint: Option<String>,
+    mac: Option<String>,
 }
 
-/// Insert a scan result into the database
-/// Note: Table is created at startup in SQLWriter to avoid schema locks
-fn insert_scan_result(
+enum ExclusionTarget {
+    Endpoint(i64),
+    Mac(String),
+}
+
+fn resolve_exclusion_target(
     conn: &Connection,
-    endpoint_id: i64,
-    scan_type: &str,
-    response_time_ms: Option<i64>,
-    details: Option<&str>,
-) -> Result<(), String> {
-    let now = chrono::Utc::now().timestamp();
-    conn.execute(
-        "INSERT INTO scan_results (endpoint_id, scan_type, scanned_at, response_time_ms, details) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![endpoint_id, scan_type, now, response_time_ms, details],
-    ).map_err(|e| e.to_string())?;
+    request: &CaptureExclusionRequest,
+) -> Result<ExclusionTarget, (StatusCode, String)> {
+    if let Some(name) = request.endpoint.as_deref().filter(|n| !n.trim().is_empty()) {
+        return find_endpoint_id_by_name(conn, name.trim())
+            .map(ExclusionTarget::Endpoint)
+            .ok_or((StatusCode::NOT_FOUND, "Endpoint not found".to_string()));
+    }
+    match request.mac.as_deref().map(str::trim) {
+        Some(mac) if !mac.is_empty() => mac
+            .parse::<pnet::util::MacAddr>()
+            .map(|mac| ExclusionTarget::Mac(mac.to_string()))
+            .map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid MAC address '{}'", mac),
+                )
+            }),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "Specify an endpoint or a MAC address".to_string(),
+        )),
+    }
+}
 
-    Ok(())
+/// Mark an endpoint (all of its MACs) or a MAC as "do not record"
+#[post("/api/capture/exclusions")]
+pub async fn add_capture_exclusion(
+    _admin: RequireAdmin,
+    body: Json<CaptureExclusionRequest>,
+) -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let db_error = |e: rusqlite::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        match resolve_exclusion_target(&conn, &body)? {
+            ExclusionTarget::Endpoint(endpoint_id) => {
+                match exclude_endpoint(&conn, endpoint_id).map_err(db_error)? {
+                    0 => Err((
+                        StatusCode::BAD_REQUEST,
+                        "Endpoint has no known MAC address to exclude by".to_string(),
+                    )),
+                    count => Ok(format!(
+                        "Traffic from {} MAC address(es) will no longer be recorded",
+                        count
+                    )),
+                }
+            }
+            ExclusionTarget::Mac(mac) => {
+                exclude_mac(&conn, &mac).map_err(db_error)?;
+                Ok(format!("Traffic from {} will no longer be recorded", mac))
+            }
+        }
+    })
+    .await;
+
+    capture_exclusion_response(result)
 }
 
-/// Insert an open port into the database
-/// Note: Table is created at startup in SQLWriter to avoid schema locks
-fn insert_open_port(
-    conn: &Connection,
-    endpoint_id: i64,
-    port: u16,
-    service_name: Option<&str>,
-) -> Result<(), String> {
-    let now = chrono::Utc::now().timestamp();
-    conn.execute(
-        "INSERT OR REPLACE INTO open_ports (endpoint_id, port, protocol, service_name, last_seen_at) VALUES (?1, ?2, 'tcp', ?3, ?4)",
-        params![endpoint_id, port as i64, service_name, now],
-    ).map_err(|e| e.to_string())?;
+/// Record an endpoint's or a MAC's traffic again
+#[post("/api/capture/exclusions/remove")]
+pub async fn remove_capture_exclusion(
+    _admin: RequireAdmin,
+    body: Json<CaptureExclusionRequest>,
+) -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let db_error = |e: rusqlite::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        let removed = match resolve_exclusion_target(&conn, &body)? {
+            ExclusionTarget::Endpoint(endpoint_id) => {
+                include_endpoint(&conn, endpoint_id).map_err(db_error)? > 0
+            }
+            ExclusionTarget::Mac(mac) => include_mac(&conn, &mac).map_err(db_error)?,
+        };
+        if removed {
+            Ok("Traffic will be recorded again".to_string())
+        } else {
+            Err((
+                StatusCode::NOT_FOUND,
+                "Not on the exclusion list".to_string(),
+            ))
+        }
+    })
+    .await;
 
-    Ok(())
+    capture_exclusion_response(result)
+}
+
+fn capture_exclusion_response(
+    result: Result<Result<String, (StatusCode, String)>, tokio::task::JoinError>,
+) -> HttpResponse {
+    match result {
+        Ok(Ok(message)) => HttpResponse::Ok().json(UpdateSettingResponse {
+            success: true,
+            message,
+        }),
+        Ok(Err((status, message))) => HttpResponse::build(status).json(UpdateSettingResponse {
+            success: false,
+            message,
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(UpdateSettingResponse {
+            success: false,
+            message: "Failed to update capture exclusions".to_string(),
+        }),
+    }
 }
 
 // ============================================================================
-// Endpoints Table API (for AJAX refresh without full page reload)
+// Backup & Restore
 // ============================================================================
 
-#[derive(Clone, Serialize)]
-pub struct EndpointTableRow {
-    name: String,
-    vendor: Option<String>,
-    model: Option<String>,
-    device_type: Option<String>,
-    bytes: i64,
-    last_seen: String,
-    online: bool,
-}
-
 #[derive(Serialize)]
-pub struct EndpointsTableResponse {
-    endpoints: Vec<EndpointTableRow>,
+pub struct RestoreResponse {
+    success: bool,
+    message: String,
 }
 
-/// Get endpoint table data for AJAX refresh (doesn't reload full page)
-#[get("/api/endpoints/table")]
-pub async fn get_endpoints_table() -> impl Responder {
-    // Check cache first (3-second TTL)
-    {
-        let cache = get_endpoint_table_cache();
-        if let Ok(cache_guard) = cache.lock()
-            && let Some(cached_data) = cache_guard.get()
-        {
-            return HttpResponse::Ok().json(EndpointsTableResponse {
-                endpoints: cached_data,
-            });
-        }
+/// Download a backup archive: a consistent snapshot of the database (endpoints, history,
+/// settings, and device credentials), the startup config file, and a manifest.
+#[get("/api/backup")]
+pub async fn download_backup(_admin: RequireAdmin) -> impl Responder {
+    match tokio::task::spawn_blocking(create_backup_bytes).await {
+        Ok(Ok(data)) => HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", backup_file_name()),
+            ))
+            .body(data),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Task execution error: {}", e)
+        })),
     }
+}
 
-    let scan_interval: u64 = 525600; // Same default as index route
-    let active_threshold = get_setting_i64("active_threshold_seconds", 120) as u64;
-
-    // Get endpoint list
-    let dropdown_future = tokio::task::spawn_blocking(move || dropdown_endpoints(scan_interval));
-    let dropdown_endpoints_list = dropdown_future.await.unwrap_or_default();
-
-    if dropdown_endpoints_list.is_empty() {
-        return HttpResponse::Ok().json(EndpointsTableResponse {
-            endpoints: Vec::new(),
-        });
-    }
+/// Restore the database from an uploaded backup archive, or a bare database file from
+/// an older backup (multipart field "file"), of at most `MAX_BACKUP_BYTES`. The upload is
+/// validated before anything is overwritten; a config file in the archive is left for the
+/// user to put back.
+#[post("/api/restore")]
+pub async fn restore_from_backup(_admin: RequireAdmin, mut payload: Multipart) -> impl Responder {
+    let mut file_data: Option<Vec<u8>> = None;
 
-    // Prepare for parallel queries
-    let dropdown_for_stats = dropdown_endpoints_list.clone();
-    let dropdown_for_types = dropdown_endpoints_list.clone();
-    let dropdown_for_ips = dropdown_endpoints_list.clone();
-    let dropdown_for_ssdp = dropdown_endpoints_list.clone();
+    while let Some(item) = payload.next().await {
+        let mut field = match item {
+            Ok(f) => f,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(RestoreResponse {
+                    success: false,
+                    message: format!("Error reading multipart field: {}", e),
+                });
+            }
+        };
 
-    // OPTIMIZATION: Combined stats query (replaces 3 separate queries for bytes, last_seen, online)
-    let stats_future = tokio::task::spawn_blocking(move || {
-        get_combined_endpoint_stats(&dropdown_for_stats, scan_interval, active_threshold)
-    });
+        if field.name() != Some("file") {
+            continue;
+        }
 
-    let all_types_future =
-        tokio::task::spawn_blocking(move || get_all_endpoint_types(&dropdown_for_types));
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            match chunk {
+                Ok(bytes) if (data.len() + bytes.len()) as u64 > MAX_BACKUP_BYTES => {
+                    return HttpResponse::PayloadTooLarge().json(RestoreResponse {
+                        success: false,
+                        message: format!(
+                            "Backup file is larger than {} MB",
+                            MAX_BACKUP_BYTES / (1024 * 1024)
+                        ),
+                    });
+                }
+                Ok(bytes) => data.extend_from_slice(&bytes),
+                Err(e) => {
+                    return HttpResponse::BadRequest().json(RestoreResponse {
+                        success: false,
+                        message: format!("Error reading file data: {}", e),
+                    });
+                }
+            }
+        }
+        file_data = Some(data);
+    }
 
-    // Fetch vendor/model data (always fresh - removed cache that caused stale data issues)
-    let ips_macs_future =
-        tokio::task::spawn_blocking(move || get_endpoint_ips_and_macs(&dropdown_for_ips));
-    let ssdp_models_future =
-        tokio::task::spawn_blocking(move || get_endpoint_ssdp_models(&dropdown_for_ssdp));
+    let data = match file_data {
+        Some(d) if !d.is_empty() => d,
+        _ => {
+            return HttpResponse::BadRequest().json(RestoreResponse {
+                success: false,
+                message: "No backup file uploaded or file is empty".to_string(),
+            });
+        }
+    };
 
-    // Run all queries in parallel
-    let (stats_result, all_types_result, ips_macs_result, ssdp_models_result) = tokio::join!(
-        stats_future,
-        all_types_future,
-        ips_macs_future,
-        ssdp_models_future
-    );
+    let result = tokio::task::spawn_blocking(move || {
+        let temp_path =
+            std::env::temp_dir().join(format!("awareness_upload_{}", uuid::Uuid::new_v4()));
+        std::fs::write(&temp_path, &data)
+            .map_err(|e| format!("Failed to write temp file: {}", e))?;
+        let result = restore_backup(&temp_path);
+        let _ = std::fs::remove_file(&temp_path);
+        result
+    })
+    .await;
 
-    let endpoint_stats = stats_result.unwrap_or_default();
-    let (dropdown_types, _manual_overrides) = all_types_result.unwrap_or_default();
-    let endpoint_ips_macs = ips_macs_result.unwrap_or_default();
-    let endpoint_ssdp_models = ssdp_models_result.unwrap_or_default();
+    match result {
+        Ok(Ok(())) => HttpResponse::Ok().json(RestoreResponse {
+            success: true,
+            message: "Database restored from backup".to_string(),
+        }),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(RestoreResponse {
+            success: false,
+            message: e,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(RestoreResponse {
+            success: false,
+            message: format!("Task execution error: {}", e),
+        }),
+    }
+}
 
-    // Build vendor lookup
-    // Build vendor lookup
-    let component_vendors = [
-        "Espressif",
-        "Tuya",
-        "Realtek",
-        "MediaTek",
-        "Qualcomm",
-        "Broadcom",
-        "Marvell",
-        "USI",
-        "Wisol",
-        "Murata",
-        "AzureWave",
-    ];
+// ============================================================================
+// IPAM Sync
+// ============================================================================
 
-    let endpoint_vendors: HashMap<String, String> =
-        dropdown_endpoints_list
-            .iter()
-            .filter_map(|endpoint| {
-                let endpoint_lower = endpoint.to_lowercase();
-                let (
-                    _custom_model,
-                    ssdp_model,
-                    ssdp_friendly,
-                    custom_vendor,
-                    snmp_vendor,
-                    _snmp_model,
-                ) = endpoint_ssdp_models
-                    .get(&endpoint_lower)
-                    .map(|(cm, sm, sf, cv, sv, snm)| {
-                        (
-                            cm.as_deref(),
-                            sm.as_deref(),
-                            sf.as_deref(),
-                            cv.as_deref(),
-                            sv.as_deref(),
-                            snm.as_deref(),
-                        )
-                    })
-                    .unwrap_or((None, None, None, None, None, None));
+/// The NetBox/phpIPAM sync target (null when disabled). The token is never returned,
+/// only whether one is stored.
+#[get("/api/ipam-sync/config")]
+pub async fn get_ipam_sync_config_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection();
+        get_ipam_sync_config(&conn).map(|config| (config, has_ipam_sync_token(&conn)))
+    })
+    .await;
 
-                let macs: Vec<String> = endpoint_ips_macs
-                    .get(&endpoint_lower)
-                    .map(|(_, m)| m.clone())
-                    .unwrap_or_default()
-                    .into_iter()
-                    .filter(|mac| {
-                        get_mac_vendor(mac)
-                            .map(|v| !component_vendors.contains(&v))
-                            .unwrap_or(true)
-                    })
-                    .collect();
+    match result {
+        Ok(Ok((config, has_token))) => HttpResponse::Ok().json(serde_json::json!({
+            "config": config,
+            "has_token": has_token,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch IPAM sync config"
+        })),
+    }
+}
 
-                characterize_vendor(
-                    custom_vendor,
-                    ssdp_friendly,
-                    snmp_vendor,
-                    Some(endpoint.as_str()),
-                    &macs,
-                    ssdp_model,
-                )
-                .map(|c| (endpoint_lower, c.value))
-            })
-            .collect();
+/// Enable sync or change its target; an empty token keeps the stored one as long as the
+/// target and URL stay the same
+#[post("/api/ipam-sync/config")]
+pub async fn set_ipam_sync_config_api(
+    _admin: RequireAdmin,
+    body: Json<IpamSyncConfig>,
+) -> impl Responder {
+    let config = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        config.validate(ipam_sync_token_kept(&conn, &config))?;
+        set_ipam_sync_config(&conn, &config).map_err(|e| format!("Database error: {}", e))
+    })
+    .await;
 
-    // Build model lookup (first pass without device_type)
-    let mut endpoint_models: HashMap<String, String> = dropdown_endpoints_list
-        .iter()
-        .filter_map(|endpoint| {
-            let endpoint_lower = endpoint.to_lowercase();
-            let (custom_model, ssdp_model, _, _, _, snmp_model) = endpoint_ssdp_models
-                .get(&endpoint_lower)
-                .map(|(cm, sm, sf, cv, sv, snm)| {
-                    (
-                        cm.as_deref(),
-                        sm.as_deref(),
-                        sf.as_deref(),
-                        cv.as_deref(),
-                        sv.as_deref(),
-                        snm.as_deref(),
-                    )
-                })
-                .unwrap_or((None, None, None, None, None, None));
+    match result {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "IPAM sync configured"
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
+}
 
-            let macs: Vec<String> = endpoint_ips_macs
-                .get(&endpoint_lower)
-                .map(|(_, m)| m.clone())
-                .unwrap_or_default();
+/// Turn sync off and forget the stored token
+#[post("/api/ipam-sync/disable")]
+pub async fn disable_ipam_sync(_admin: RequireAdmin) -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| clear_ipam_sync_config(&new_connection())).await;
 
-            let vendor = endpoint_vendors.get(&endpoint_lower).map(|v| v.as_str());
+    match result {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "IPAM sync disabled"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Failed to disable IPAM sync"
+        })),
+    }
+}
 
-            characterize_model(
-                custom_model,
-                ssdp_model,
-                snmp_model,
-                Some(endpoint.as_str()),
-                &macs,
-                vendor,
-                None,
-            )
-            .map(|c| (endpoint_lower, c.value))
-        })
-        .collect();
+/// Push pending endpoints now instead of waiting for the next scheduled run
+#[post("/api/ipam-sync/run")]
+pub async fn run_ipam_sync_api(_admin: RequireAdmin) -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| run_ipam_sync(&new_connection())).await;
 
-    // Second pass: enhance models using vendor + device type for endpoints without models
-    for endpoint in &dropdown_endpoints_list {
-        let endpoint_lower = endpoint.to_lowercase();
-        if !endpoint_models.contains_key(&endpoint_lower)
-            && let Some(vendor) = endpoint_vendors.get(&endpoint_lower)
-            && let Some(device_type) = dropdown_types.get(&endpoint_lower)
-            && let Some(model) = get_model_from_vendor_and_type(vendor, device_type)
-        {
-            endpoint_models.insert(endpoint_lower, model);
-        }
+    match result {
+        Ok(Ok(run)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!(
+                "Pushed {} endpoint(s), {} failed, {} unchanged",
+                run.pushed, run.failed, run.unchanged
+            ),
+            "run": run,
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
     }
+}
 
-    // Build response
-    let endpoints: Vec<EndpointTableRow> = dropdown_endpoints_list
-        .iter()
-        .map(|endpoint| {
-            let endpoint_lower = endpoint.to_lowercase();
-            let stats = endpoint_stats.get(&endpoint_lower);
-            EndpointTableRow {
-                name: endpoint.clone(),
-                vendor: endpoint_vendors.get(&endpoint_lower).cloned(),
-                model: endpoint_models.get(&endpoint_lower).cloned(),
-                device_type: dropdown_types.get(&endpoint_lower).map(|s| s.to_string()),
-                bytes: stats.map(|s| s.bytes).unwrap_or(0),
-                last_seen: stats
-                    .map(|s| s.last_seen.clone())
-                    .unwrap_or_else(|| "-".to_string()),
-                online: stats.map(|s| s.online).unwrap_or(false),
-            }
-        })
-        .collect();
+/// Sync-status report: the last push of every endpoint, with failures first
+#[get("/api/ipam-sync/status")]
+pub async fn get_ipam_sync_status_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| get_ipam_sync_status(&new_connection())).await;
 
-    // Update cache
-    {
-        let cache = get_endpoint_table_cache();
-        if let Ok(mut cache_guard) = cache.lock() {
-            cache_guard.set(endpoints.clone());
-        }
+    match result {
+        Ok(Ok(status)) => HttpResponse::Ok().json(status),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch IPAM sync status"
+        })),
     }
-
-    HttpResponse::Ok().json(EndpointsTableResponse { endpoints })
 }
 
 // ============================================================================
-// Export Endpoints
+// Webhooks
 // ============================================================================
 
-#[get("/api/export/endpoints.xlsx")]
-pub async fn export_endpoints_xlsx() -> impl Responder {
-    let scan_interval: u64 = 525600;
+/// Configured webhooks with their delivery status. Secrets are never returned.
+#[get("/api/webhooks")]
+pub async fn get_webhooks_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| get_webhooks(&new_connection())).await;
 
-    // Get endpoint list
-    let dropdown_future = tokio::task::spawn_blocking(move || dropdown_endpoints(scan_interval));
-    let dropdown_endpoints_list = dropdown_future.await.unwrap_or_default();
-
-    if dropdown_endpoints_list.is_empty() {
-        return HttpResponse::Ok()
-            .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
-            .insert_header((
-                "Content-Disposition",
-                "attachment; filename=\"endpoints.xlsx\"",
-            ))
-            .body(Vec::new());
+    match result {
+        Ok(Ok(webhooks)) => HttpResponse::Ok().json(serde_json::json!({
+            "webhooks": webhooks,
+            "events": WEBHOOK_EVENTS,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch webhooks"
+        })),
     }
+}
 
-    // Run queries in parallel (same as get_endpoints_table)
-    let dropdown_for_ips = dropdown_endpoints_list.clone();
-    let dropdown_for_seen = dropdown_endpoints_list.clone();
-    let dropdown_for_online = dropdown_endpoints_list.clone();
-    let dropdown_for_types = dropdown_endpoints_list.clone();
-    let dropdown_for_ssdp = dropdown_endpoints_list.clone();
+/// Add a webhook, optionally limited to some endpoints (by name), device types or tags
+#[post("/api/webhooks")]
+pub async fn create_webhook_api(_admin: RequireAdmin, body: Json<NewWebhook>) -> impl Responder {
+    let webhook = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        webhook.validate()?;
+        if let Some(device_type) = webhook
+            .device_types
+            .iter()
+            .find(|device_type| !is_known_device_type(device_type))
+        {
+            return Err(format!("Unknown device type '{}'", device_type));
+        }
+        let conn = new_connection();
+        let endpoint_ids = webhook
+            .endpoints
+            .iter()
+            .map(|name| {
+                find_endpoint_id_by_name(&conn, name)
+                    .ok_or_else(|| format!("Endpoint '{}' not found", name))
+            })
+            .collect::<Result<Vec<i64>, String>>()?;
+        create_webhook(&conn, &webhook, &endpoint_ids).map_err(|e| format!("Database error: {}", e))
+    })
+    .await;
 
-    let ips_macs_future =
-        tokio::task::spawn_blocking(move || get_endpoint_ips_and_macs(&dropdown_for_ips));
-    let last_seen_future = tokio::task::spawn_blocking(move || {
-        get_all_endpoints_last_seen(&dropdown_for_seen, scan_interval)
-    });
-    let online_status_future = tokio::task::spawn_blocking(move || {
-        let active_threshold = get_setting_i64("active_threshold_seconds", 120) as u64;
-        get_all_endpoints_online_status(&dropdown_for_online, active_threshold)
-    });
-    let all_types_future =
-        tokio::task::spawn_blocking(move || get_all_endpoint_types(&dropdown_for_types));
-    let ssdp_models_future =
-        tokio::task::spawn_blocking(move || get_endpoint_ssdp_models(&dropdown_for_ssdp));
+    match result {
+        Ok(Ok(id)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Webhook added",
+            "id": id,
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
+}
 
-    let (
-        ips_macs_result,
-        last_seen_result,
-        online_status_result,
-        all_types_result,
-        ssdp_models_result,
-    ) = tokio::join!(
-        ips_macs_future,
-        last_seen_future,
-        online_status_future,
-        all_types_future,
-        ssdp_models_future
-    );
+#[derive(Deserialize)]
+pub struct DeleteWebhookRequest {
+    id: i64,
+}
 
-    let endpoint_ips_macs = ips_macs_result.unwrap_or_default();
-    let endpoint_last_seen = last_seen_result.unwrap_or_default();
-    let endpoint_online_status = online_status_result.unwrap_or_default();
-    let (dropdown_types, _manual_overrides) = all_types_result.unwrap_or_default();
-    let endpoint_ssdp_models = ssdp_models_result.unwrap_or_default();
+/// Remove a webhook along with its queued deliveries
+#[post("/api/webhooks/delete")]
+pub async fn delete_webhook_api(
+    _admin: RequireAdmin,
+    body: Json<DeleteWebhookRequest>,
+) -> impl Responder {
+    let id = body.into_inner().id;
+    let result = tokio::task::spawn_blocking(move || delete_webhook(&new_connection(), id)).await;
 
-    // Build vendor lookup
-    let component_vendors = [
-        "Espressif",
-        "Tuya",
-        "Realtek",
-        "MediaTek",
-        "Qualcomm",
-        "Broadcom",
-        "Marvell",
-        "USI",
-        "Wisol",
-        "Murata",
-        "AzureWave",
-    ];
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Webhook not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete webhook"
+        })),
+    }
+}
 
-    let endpoint_vendors: HashMap<String, String> =
-        dropdown_endpoints_list
-            .iter()
-            .filter_map(|endpoint| {
-                let endpoint_lower = endpoint.to_lowercase();
-                let (
-                    _custom_model,
-                    ssdp_model,
-                    ssdp_friendly,
-                    custom_vendor,
-                    snmp_vendor,
-                    _snmp_model,
-                ) = endpoint_ssdp_models
-                    .get(&endpoint_lower)
-                    .map(|(cm, sm, sf, cv, sv, snm)| {
-                        (
-                            cm.as_deref(),
-                            sm.as_deref(),
-                            sf.as_deref(),
-                            cv.as_deref(),
-                            sv.as_deref(),
-                            snm.as_deref(),
-                        )
-                    })
-                    .unwrap_or((None, None, None, None, None, None));
+// ============================================================================
+// Expected-Port Policies
+// ============================================================================
 
-                let macs: Vec<String> = endpoint_ips_macs
-                    .get(&endpoint_lower)
-                    .map(|(_, m)| m.clone())
-                    .unwrap_or_default()
-                    .into_iter()
-                    .filter(|mac| {
-                        get_mac_vendor(mac)
-                            .map(|v| !component_vendors.contains(&v))
-                            .unwrap_or(true)
-                    })
-                    .collect();
+/// Expected-port policies and the ports currently seen outside them
+#[get("/api/port-policies")]
+pub async fn get_port_policies_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection();
+        Ok::<_, rusqlite::Error>((get_port_policies(&conn)?, get_port_drift(&conn)?))
+    })
+    .await;
 
-                characterize_vendor(
-                    custom_vendor,
-                    ssdp_friendly,
-                    snmp_vendor,
-                    Some(endpoint.as_str()),
-                    &macs,
-                    ssdp_model,
-                )
-                .map(|c| (endpoint_lower, c.value))
-            })
-            .collect();
+    match result {
+        Ok(Ok((policies, drift))) => HttpResponse::Ok().json(serde_json::json!({
+            "policies": policies,
+            "drift": drift,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch port policies"
+        })),
+    }
+}
 
-    // Build model lookup
-    let endpoint_models: HashMap<String, String> = dropdown_endpoints_list
-        .iter()
-        .filter_map(|endpoint| {
-            let endpoint_lower = endpoint.to_lowercase();
-            let (custom_model, ssdp_model, _, _, _, snmp_model) = endpoint_ssdp_models
-                .get(&endpoint_lower)
-                .map(|(cm, sm, sf, cv, sv, snm)| {
-                    (
-                        cm.as_deref(),
-                        sm.as_deref(),
-                        sf.as_deref(),
-                        cv.as_deref(),
-                        sv.as_deref(),
-                        snm.as_deref(),
-                    )
-                })
-                .unwrap_or((None, None, None, None, None, None));
+/// Set the expected ports for an endpoint (by name) or a device type, replacing any
+/// policy it already has
+#[post("/api/port-policies")]
+pub async fn set_port_policy_api(
+    _admin: RequireAdmin,
+    body: Json<NewPortPolicy>,
+) -> impl Responder {
+    let policy = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        policy.validate()?;
+        if let Some(device_type) = &policy.device_type
+            && !is_known_device_type(device_type)
+        {
+            return Err(format!("Unknown device type '{}'", device_type));
+        }
+        let conn = new_connection();
+        let endpoint_id = match &policy.endpoint {
+            Some(name) => Some(
+                find_endpoint_id_by_name(&conn, name)
+                    .ok_or_else(|| format!("Endpoint '{}' not found", name))?,
+            ),
+            None => None,
+        };
+        set_port_policy(
+            &conn,
+            endpoint_id,
+            policy.device_type.as_deref(),
+            &policy.ports,
+        )
+        .map_err(|e| format!("Database error: {}", e))
+    })
+    .await;
 
-            let macs: Vec<String> = endpoint_ips_macs
-                .get(&endpoint_lower)
-                .map(|(_, m)| m.clone())
-                .unwrap_or_default();
+    match result {
+        Ok(Ok(id)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Port policy saved",
+            "id": id,
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
+}
 
-            let vendor = endpoint_vendors.get(&endpoint_lower).map(|v| v.as_str());
+#[derive(Deserialize)]
+pub struct DeletePortPolicyRequest {
+    id: i64,
+}
 
-            characterize_model(
-                custom_model,
-                ssdp_model,
-                snmp_model,
-                Some(endpoint.as_str()),
-                &macs,
-                vendor,
-                None,
-            )
-            .map(|c| (endpoint_lower, c.value))
-        })
-        .collect();
+/// Remove an expected-port policy, clearing the drift it was reporting
+#[post("/api/port-policies/delete")]
+pub async fn delete_port_policy_api(
+    _admin: RequireAdmin,
+    body: Json<DeletePortPolicyRequest>,
+) -> impl Responder {
+    let id = body.into_inner().id;
+    let result =
+        tokio::task::spawn_blocking(move || delete_port_policy(&new_connection(), id)).await;
 
-    // Create Excel workbook
-    let mut workbook = Workbook::new();
-    let worksheet = workbook.add_worksheet();
-    worksheet.set_name("Endpoints").ok();
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Port policy not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete port policy"
+        })),
+    }
+}
 
-    // Header format
-    let header_format = Format::new().set_bold();
+// ============================================================================
+// Bandwidth Quotas
+// ============================================================================
 
-    // Write headers
-    let headers = [
-        "Name",
-        "IP",
-        "MAC",
-        "Vendor",
-        "Model",
-        "Device Type",
-        "Last Seen",
-        "Online",
-    ];
-    for (col, header) in headers.iter().enumerate() {
-        worksheet
-            .write_string_with_format(0, col as u16, *header, &header_format)
-            .ok();
-    }
+/// Daily bandwidth quotas and the endpoints that went over them recently
+#[get("/api/bandwidth-quotas")]
+pub async fn get_bandwidth_quotas_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection();
+        Ok::<_, rusqlite::Error>((get_bandwidth_quotas(&conn)?, get_exceeded_quotas(&conn)?))
+    })
+    .await;
 
-    // Write data rows
-    for (row_idx, endpoint) in dropdown_endpoints_list.iter().enumerate() {
-        let row = (row_idx + 1) as u32;
-        let endpoint_lower = endpoint.to_lowercase();
+    match result {
+        Ok(Ok((quotas, exceeded))) => HttpResponse::Ok().json(serde_json::json!({
+            "quotas": quotas,
+            "exceeded": exceeded,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch bandwidth quotas"
+        })),
+    }
+}
 
-        let (ips, macs) = endpoint_ips_macs
-            .get(&endpoint_lower)
-            .cloned()
-            .unwrap_or_default();
+/// Set the daily internet byte quota for an endpoint (by name), a device type or a tag,
+/// replacing any quota it already has
+#[post("/api/bandwidth-quotas")]
+pub async fn set_bandwidth_quota_api(
+    _admin: RequireAdmin,
+    body: Json<NewBandwidthQuota>,
+) -> impl Responder {
+    let quota = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        quota.validate()?;
+        if let Some(device_type) = &quota.device_type
+            && !is_known_device_type(device_type)
+        {
+            return Err(format!("Unknown device type '{}'", device_type));
+        }
+        let conn = new_connection();
+        let endpoint_id = match &quota.endpoint {
+            Some(name) => Some(
+                find_endpoint_id_by_name(&conn, name)
+                    .ok_or_else(|| format!("Endpoint '{}' not found", name))?,
+            ),
+            None => None,
+        };
+        let tag = quota.tag.as_deref().and_then(parse_tag);
+        set_bandwidth_quota(
+            &conn,
+            endpoint_id,
+            quota.device_type.as_deref(),
+            tag.as_deref(),
+            quota.daily_bytes,
+        )
+        .map_err(|e| format!("Database error: {}", e))
+    })
+    .await;
 
-        worksheet.write_string(row, 0, endpoint).ok();
-        worksheet.write_string(row, 1, ips.join(", ")).ok();
-        worksheet.write_string(row, 2, macs.join(", ")).ok();
-        worksheet
-            .write_string(
-                row,
-                3,
-                endpoint_vendors
-                    .get(&endpoint_lower)
-                    .map(|s| &**s)
-                    .unwrap_or(""),
-            )
-            .ok();
-        worksheet
-            .write_string(
-                row,
-                4,
-                endpoint_models
-                    .get(&endpoint_lower)
-                    .map(|s| &**s)
-                    .unwrap_or(""),
-            )
-            .ok();
-        worksheet
-            .write_string(
-                row,
-                5,
-                dropdown_types
-                    .get(&endpoint_lower)
-                    .map(|s| &**s)
-                    .unwrap_or(""),
-            )
-            .ok();
-        worksheet
-            .write_string(
-                row,
-                6,
-                endpoint_last_seen
-                    .get(&endpoint_lower)
-                    .map(|s| &**s)
-                    .unwrap_or("-"),
-            )
-            .ok();
-        worksheet
-            .write_string(
-                row,
-                7,
-                if *endpoint_online_status
-                    .get(&endpoint_lower)
-                    .unwrap_or(&false)
-                {
-                    "Yes"
-                } else {
-                    "No"
-                },
-            )
-            .ok();
+    match result {
+        Ok(Ok(id)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Bandwidth quota saved",
+            "id": id,
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
     }
+}
 
-    // Set column widths for readability
-    worksheet.set_column_width(0, 30).ok(); // Name
-    worksheet.set_column_width(1, 15).ok(); // IP
-    worksheet.set_column_width(2, 20).ok(); // MAC
-    worksheet.set_column_width(3, 15).ok(); // Vendor
-    worksheet.set_column_width(4, 20).ok(); // Model
-    worksheet.set_column_width(5, 15).ok(); // Device Type
-    worksheet.set_column_width(6, 20).ok(); // Last Seen
-    worksheet.set_column_width(7, 8).ok(); // Online
+#[derive(Deserialize)]
+pub struct DeleteBandwidthQuotaRequest {
+    id: i64,
+}
 
-    // Save to buffer
-    let buffer = match workbook.save_to_buffer() {
-        Ok(buf) => buf,
-        Err(e) => {
-            eprintln!("Failed to create Excel file: {}", e);
-            return HttpResponse::InternalServerError().body("Failed to create Excel file");
-        }
-    };
+/// Remove a bandwidth quota
+#[post("/api/bandwidth-quotas/delete")]
+pub async fn delete_bandwidth_quota_api(
+    _admin: RequireAdmin,
+    body: Json<DeleteBandwidthQuotaRequest>,
+) -> impl Responder {
+    let id = body.into_inner().id;
+    let result =
+        tokio::task::spawn_blocking(move || delete_bandwidth_quota(&new_connection(), id)).await;
 
-    HttpResponse::Ok()
-        .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
-        .insert_header((
-            "Content-Disposition",
-            "attachment; filename=\"endpoints.xlsx\"",
-        ))
-        .body(buffer)
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Bandwidth quota not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete bandwidth quota"
+        })),
+    }
 }
 
 // ============================================================================
-// Settings Endpoints
+// People
 // ============================================================================
 
-#[derive(Serialize)]
-pub struct SettingsResponse {
-    settings: std::collections::HashMap<String, String>,
+/// Everyone in the household, with how many devices each owns
+#[get("/api/people")]
+pub async fn get_people_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| get_people(&new_connection())).await;
+
+    match result {
+        Ok(Ok(people)) => HttpResponse::Ok().json(serde_json::json!({ "people": people })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch people"
+        })),
+    }
 }
 
 #[derive(Deserialize)]
-pub struct UpdateSettingRequest {
-    key: String,
-    value: String,
+pub struct CreatePersonRequest {
+    name: String,
 }
 
-#[derive(Serialize)]
-pub struct UpdateSettingResponse {
-    success: bool,
-    message: String,
+/// Add a person devices can be assigned to
+#[post("/api/people")]
+pub async fn create_person_api(
+    _admin: RequireAdmin,
+    body: Json<CreatePersonRequest>,
+) -> impl Responder {
+    let Some(name) = parse_person_name(&body.name) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": "Name must be 1-64 characters"
+        }));
+    };
+    let result = {
+        let name = name.clone();
+        tokio::task::spawn_blocking(move || create_person(&new_connection(), &name)).await
+    };
+
+    match result {
+        Ok(Ok(Some(id))) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "id": id,
+        })),
+        Ok(Ok(None)) => HttpResponse::Conflict().json(serde_json::json!({
+            "success": false,
+            "message": format!("A person named '{}' already exists", name)
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": "Failed to add person"
+        })),
+    }
 }
 
-#[get("/api/settings")]
-pub async fn get_settings() -> impl Responder {
-    let settings = tokio::task::spawn_blocking(get_all_settings)
-        .await
-        .unwrap_or_default();
+#[derive(Deserialize)]
+pub struct DeletePersonRequest {
+    id: i64,
+}
+
+/// Remove a person; their devices are left without an owner
+#[post("/api/people/delete")]
+pub async fn delete_person_api(
+    _admin: RequireAdmin,
+    body: Json<DeletePersonRequest>,
+) -> impl Responder {
+    let id = body.into_inner().id;
+    let result = tokio::task::spawn_blocking(move || delete_person(&new_connection(), id)).await;
 
-    HttpResponse::Ok().json(SettingsResponse { settings })
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Person not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete person"
+        })),
+    }
 }
 
-#[post("/api/settings")]
-pub async fn update_setting(body: Json<UpdateSettingRequest>) -> impl Responder {
-    let key = body.key.clone();
-    let value = body.value.clone();
+#[derive(Deserialize)]
+pub struct AssignEndpointOwnerRequest {
+    endpoint: String,
+    /// The new owner, or null to clear the endpoint's owner
+    person_id: Option<i64>,
+}
 
-    let result = tokio::task::spawn_blocking(move || set_setting(&key, &value)).await;
+/// Assign an endpoint (by name) to a person, replacing its previous owner
+#[post("/api/people/assign")]
+pub async fn assign_endpoint_owner_api(
+    _admin: RequireAdmin,
+    body: Json<AssignEndpointOwnerRequest>,
+) -> impl Responder {
+    let request = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let endpoint_id = find_endpoint_id_by_name(&conn, &request.endpoint)
+            .ok_or_else(|| format!("Endpoint '{}' not found", request.endpoint))?;
+        match set_endpoint_owner(&conn, endpoint_id, request.person_id) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("Person not found".to_string()),
+            Err(e) => Err(format!("Database error: {}", e)),
+        }
+    })
+    .await;
 
     match result {
-        Ok(Ok(())) => HttpResponse::Ok().json(UpdateSettingResponse {
-            success: true,
-            message: format!("Setting '{}' updated", body.key),
-        }),
-        _ => HttpResponse::InternalServerError().json(UpdateSettingResponse {
-            success: false,
-            message: "Failed to update setting".to_string(),
-        }),
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PersonSummaryQuery {
+    /// Hours of traffic to total (default 24, at most 31 days)
+    hours: Option<i64>,
+}
+
+/// A person's devices with their online status and traffic
+#[get("/api/people/{id}/summary")]
+pub async fn get_person_summary_api(
+    path: actix_web::web::Path<i64>,
+    query: Query<PersonSummaryQuery>,
+) -> impl Responder {
+    let person_id = path.into_inner();
+    let hours = query.hours.unwrap_or(24).clamp(1, 31 * 24);
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let now = chrono::Utc::now().timestamp();
+        let active_threshold = get_setting_i64("active_threshold_seconds", 120);
+        let last_seen = get_endpoint_last_seen(&conn, 0)?;
+        get_person_summary(
+            &conn,
+            person_id,
+            now - hours * 3600,
+            &last_seen,
+            now - active_threshold,
+        )
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(summary))) => HttpResponse::Ok().json(summary),
+        Ok(Ok(None)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Person not found"
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to summarize person"
+        })),
     }
 }
 
 // ============================================================================
-// Capture Pause Endpoint
+// Device Type Baselines
 // ============================================================================
 
-#[derive(Serialize)]
-pub struct CapturePauseResponse {
-    success: bool,
-    paused: bool,
-    message: String,
+/// Device type baselines with what was learned for each, and the connections seen
+/// outside them
+#[get("/api/type-baselines")]
+pub async fn get_type_baselines_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection();
+        let baselines = get_type_baselines(&conn)?
+            .into_iter()
+            .map(|baseline| {
+                let behaviors = get_baseline_behaviors(&conn, &baseline.device_type)?;
+                Ok(serde_json::json!({
+                    "baseline": baseline,
+                    "behaviors": behaviors,
+                }))
+            })
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        Ok::<_, rusqlite::Error>((baselines, get_baseline_deviations(&conn)?))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((baselines, deviations))) => HttpResponse::Ok().json(serde_json::json!({
+            "baselines": baselines,
+            "deviations": deviations,
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Database error: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch type baselines"
+        })),
+    }
 }
 
-/// Get capture pause status
-#[get("/api/capture/status")]
-pub async fn get_capture_status() -> impl Responder {
-    let paused = crate::is_capture_paused();
-    HttpResponse::Ok().json(CapturePauseResponse {
-        success: true,
-        paused,
-        message: if paused {
-            "Capture is paused".to_string()
-        } else {
-            "Capture is running".to_string()
-        },
+/// Start learning a device type's connections, or switch its baseline to enforce
+#[post("/api/type-baselines")]
+pub async fn set_type_baseline_api(
+    _admin: RequireAdmin,
+    body: Json<TypeBaselineRequest>,
+) -> impl Responder {
+    let request = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        request.validate()?;
+        if !is_known_device_type(&request.device_type) {
+            return Err(format!("Unknown device type '{}'", request.device_type));
+        }
+        let learning_days = request
+            .learning_days
+            .unwrap_or_else(|| get_setting_i64("baseline_learning_days", 7));
+        set_type_baseline_mode(
+            &new_connection(),
+            &request.device_type,
+            &request.mode,
+            learning_days,
+            request.reset,
+            chrono::Utc::now().timestamp(),
+        )
+        .map_err(|e| format!("Database error: {}", e))
     })
+    .await;
+
+    match result {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": "Type baseline saved",
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Task execution error: {}", e)
+        })),
+    }
 }
 
-/// Toggle capture pause state
-#[post("/api/capture/pause")]
-pub async fn toggle_capture_pause() -> impl Responder {
-    let currently_paused = crate::is_capture_paused();
-    let new_state = !currently_paused;
-    crate::set_capture_paused(new_state);
+#[derive(Deserialize)]
+pub struct DeleteTypeBaselineRequest {
+    device_type: String,
+}
 
-    HttpResponse::Ok().json(CapturePauseResponse {
-        success: true,
-        paused: new_state,
-        message: if new_state {
-            "Capture paused - live traffic will be ignored".to_string()
-        } else {
-            "Capture resumed - live traffic will be processed".to_string()
-        },
+/// Remove a device type's baseline along with what was learned and its deviations
+#[post("/api/type-baselines/delete")]
+pub async fn delete_type_baseline_api(
+    _admin: RequireAdmin,
+    body: Json<DeleteTypeBaselineRequest>,
+) -> impl Responder {
+    let device_type = body.into_inner().device_type;
+    let result =
+        tokio::task::spawn_blocking(move || delete_type_baseline(&new_connection(), &device_type))
+            .await;
+
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().json(serde_json::json!({ "success": true })),
+        Ok(Ok(false)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Type baseline not found"
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to delete type baseline"
+        })),
+    }
+}
+
+// ============================================================================
+// UPnP Event Subscriptions
+// ============================================================================
+
+/// Path UPnP devices deliver subscribed events to
+pub(super) const UPNP_EVENT_CALLBACK_PATH: &str = "/upnp/events";
+
+/// Seconds between passes that renew and add subscriptions
+const UPNP_SUBSCRIPTION_INTERVAL_SECS: u64 = 60;
+
+/// Subscriptions this close to lapsing are renewed
+const UPNP_RENEW_MARGIN_SECS: i64 = 300;
+
+/// A device description that yielded no subscription isn't tried again for this long
+const UPNP_SUBSCRIBE_RETRY_SECS: i64 = 3600;
+
+const UPNP_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// When each device description was last tried for subscriptions
+static UPNP_SUBSCRIBE_ATTEMPTS: OnceLock<Mutex<HashMap<String, i64>>> = OnceLock::new();
+
+/// NOTIFY callback for UPnP event subscriptions. An event is only accepted for a SID
+/// this server subscribed, sent from the address it subscribed at; anything else gets
+/// 412 Precondition Failed, which tells the device to drop the subscription.
+pub(super) async fn receive_upnp_event(
+    req: HttpRequest,
+    body: actix_web::web::Bytes,
+) -> impl Responder {
+    let Some(sid) = req
+        .headers()
+        .get("SID")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+    else {
+        return HttpResponse::PreconditionFailed().finish();
+    };
+    let peer = req.peer_addr().map(|addr| addr.ip().to_string());
+    let body = String::from_utf8_lossy(&body).into_owned();
+
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<bool> {
+        let conn = new_connection_result()?;
+        let mut subscription = find_upnp_subscription(&conn, &sid)?;
+        if subscription.is_none() {
+            // The initial event can beat the new subscription being stored
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            subscription = find_upnp_subscription(&conn, &sid)?;
+        }
+        let Some(subscription) = subscription else {
+            return Ok(false);
+        };
+        if peer.as_deref() != Some(subscription.ip.as_str()) {
+            return Ok(false);
+        }
+        let variables = gena::parse_property_set(&body);
+        record_upnp_event(
+            &conn,
+            &subscription,
+            &variables,
+            chrono::Utc::now().timestamp(),
+        )?;
+        Ok(true)
     })
+    .await;
+
+    match result {
+        Ok(Ok(true)) => HttpResponse::Ok().finish(),
+        Ok(Ok(false)) => HttpResponse::PreconditionFailed().finish(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to record UPnP event: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
 }
 
-/// Set capture pause state explicitly
-#[derive(Deserialize)]
-pub struct SetCapturePauseRequest {
-    paused: bool,
+/// Start the task that keeps UPnP event subscriptions alive while the
+/// `upnp_event_subscriptions` setting is on. Devices call back on `port`, so this is only
+/// started when the dashboard listens on all interfaces.
+pub(super) fn start_upnp_event_subscriptions(port: u16) {
+    tokio::spawn(async move {
+        loop {
+            if get_setting("upnp_event_subscriptions").as_deref() == Some("true") {
+                maintain_upnp_subscriptions(port).await;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                UPNP_SUBSCRIPTION_INTERVAL_SECS,
+            ))
+            .await;
+        }
+    });
 }
 
-#[post("/api/capture/set-pause")]
-pub async fn set_capture_pause(body: Json<SetCapturePauseRequest>) -> impl Responder {
-    crate::set_capture_paused(body.paused);
+/// Renew subscriptions about to lapse, dropping those the device no longer honours, then
+/// subscribe to SSDP devices that have evented services and no subscription yet
+async fn maintain_upnp_subscriptions(port: u16) {
+    let now = chrono::Utc::now().timestamp();
+    let pending = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result().ok()?;
+        Some((
+            get_expiring_upnp_subscriptions(&conn, now + UPNP_RENEW_MARGIN_SECS).ok()?,
+            get_unsubscribed_upnp_devices(&conn).ok()?,
+        ))
+    })
+    .await
+    .ok()
+    .flatten();
+    let Some((expiring, devices)) = pending else {
+        return;
+    };
 
-    HttpResponse::Ok().json(CapturePauseResponse {
-        success: true,
-        paused: body.paused,
-        message: if body.paused {
-            "Capture paused - live traffic will be ignored".to_string()
-        } else {
-            "Capture resumed - live traffic will be processed".to_string()
-        },
+    let mut renewed = Vec::new();
+    let mut lapsed = Vec::new();
+    for mut subscription in expiring {
+        match gena::renew(
+            &subscription.event_url,
+            &subscription.sid,
+            UPNP_REQUEST_TIMEOUT,
+        )
+        .await
+        {
+            Ok(renewal) => {
+                subscription.expires_at = now + renewal.timeout_secs as i64;
+                renewed.push(subscription);
+            }
+            Err(_) => lapsed.push(subscription.sid),
+        }
+    }
+    let _ = tokio::task::spawn_blocking(move || {
+        let conn = new_connection_result()?;
+        for subscription in &renewed {
+            save_upnp_subscription(&conn, subscription, now)?;
+        }
+        for sid in &lapsed {
+            delete_upnp_subscription(&conn, sid)?;
+        }
+        Ok::<_, rusqlite::Error>(())
     })
+    .await;
+
+    let devices: Vec<UpnpEventSource> = {
+        let Ok(mut attempts) = UPNP_SUBSCRIBE_ATTEMPTS
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+        else {
+            return;
+        };
+        devices
+            .into_iter()
+            .filter(|device| {
+                let due = attempts
+                    .get(&device.location)
+                    .is_none_or(|&at| at < now - UPNP_SUBSCRIBE_RETRY_SECS);
+                if due {
+                    attempts.insert(device.location.clone(), now);
+                }
+                due
+            })
+            .collect()
+    };
+
+    for device in devices {
+        let Some(local) = device.ip.parse().ok().and_then(gena::local_address_for) else {
+            continue;
+        };
+        let callback = format!(
+            "http://{}{}",
+            std::net::SocketAddr::new(local, port),
+            UPNP_EVENT_CALLBACK_PATH
+        );
+        for service in gena::fetch_event_services(&device.location).await {
+            let subscription =
+                match gena::subscribe(&service.event_url, &callback, UPNP_REQUEST_TIMEOUT).await {
+                    Ok(subscription) => subscription,
+                    Err(e) => {
+                        eprintln!(
+                            "UPnP subscription to {} on {} failed: {}",
+                            service.short_name(),
+                            device.ip,
+                            e
+                        );
+                        continue;
+                    }
+                };
+            let subscription = UpnpSubscription {
+                sid: subscription.sid,
+                endpoint_id: device.endpoint_id,
+                ip: device.ip.clone(),
+                service_type: service.service_type,
+                event_url: service.event_url,
+                expires_at: now + subscription.timeout_secs as i64,
+            };
+            // Stored straight away: the device sends its initial event right after
+            // accepting, and the callback only accepts known SIDs
+            let _ = tokio::task::spawn_blocking(move || {
+                let conn = new_connection_result()?;
+                save_upnp_subscription(&conn, &subscription, now)
+            })
+            .await;
+        }
+    }
 }
 
 // ============================================================================
@@ -3350,7 +8858,7 @@ pub struct PcapUploadResponse {
 }
 
 #[post("/api/pcap/upload")]
-pub async fn upload_pcap(mut payload: Multipart) -> impl Responder {
+pub async fn upload_pcap(_admin: RequireAdmin, mut payload: Multipart) -> impl Responder {
     let mut file_data: Option<Vec<u8>> = None;
     let mut filename: Option<String> = None;
     let mut label: Option<String> = None;
@@ -3468,6 +8976,19 @@ pub async fn upload_pcap(mut payload: Multipart) -> impl Responder {
     // Clean up temp file
     let _ = std::fs::remove_file(&temp_path);
 
+    let activity = serde_json::json!({
+        "file": original_filename,
+        "label": source_label,
+        "packets": result.as_ref().ok().and_then(|r| r.as_ref().ok()),
+        "error": match &result {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(e) => Some(e.to_string()),
+        },
+    });
+    let _ =
+        tokio::task::spawn_blocking(move || record_activity_event("pcap_imported", activity)).await;
+
     // Give the SQL writer time to flush remaining packets
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
@@ -3507,6 +9028,18 @@ pub struct NotificationsQuery {
     offset: Option<i64>,
     search: Option<String>,
     include_dismissed: Option<bool>,
+    /// Comma-separated list of severities to include (e.g. "warning,critical")
+    severity: Option<String>,
+    /// Filter by acknowledgement state (true = acknowledged only, false = unacknowledged only)
+    acknowledged: Option<bool>,
+}
+
+/// Parse a comma-separated severity filter, keeping only known severity levels
+fn parse_severity_filter(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| NOTIFICATION_SEVERITIES.contains(&s.as_str()))
+        .collect()
 }
 
 #[derive(Serialize)]
@@ -3520,194 +9053,716 @@ pub struct NotificationItem {
     dismissed: bool,
     endpoint_ip: Option<String>,
     endpoint_mac: Option<String>,
+    severity: String,
+    acknowledged_at: Option<i64>,
+    acknowledged_by: Option<String>,
+    /// How many times this notification fired within the dedup cooldown (1 = once)
+    occurrences: i64,
+    last_occurred_at: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct NotificationsResponse {
+    notifications: Vec<NotificationItem>,
+    total: i64,
+}
+
+#[get("/api/notifications")]
+pub async fn get_notifications(query: Query<NotificationsQuery>) -> impl Responder {
+    let since = query.since.unwrap_or(0);
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+    let include_dismissed = query.include_dismissed.unwrap_or(false);
+    let search = query.search.clone().unwrap_or_default();
+    let severities = query
+        .severity
+        .as_deref()
+        .map(parse_severity_filter)
+        .unwrap_or_default();
+    let acknowledged = query.acknowledged;
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+
+        // Build WHERE clause and parameters together (use n. prefix since we JOIN with endpoints)
+        let mut conditions = vec!["n.created_at > ?".to_string()];
+        let mut filter_params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(since)];
+        if !include_dismissed {
+            conditions.push("n.dismissed = 0".to_string());
+        }
+        if !search.is_empty() {
+            conditions.push("(n.title LIKE ? OR COALESCE(n.details, '') LIKE ? OR COALESCE(n.endpoint_name, '') LIKE ? OR n.event_type LIKE ?)".to_string());
+            let search_pattern = format!("%{}%", search);
+            for _ in 0..4 {
+                filter_params.push(Box::new(search_pattern.clone()));
+            }
+        }
+        if !severities.is_empty() {
+            conditions.push(format!(
+                "n.severity IN ({})",
+                build_in_placeholders(severities.len())
+            ));
+            for severity in &severities {
+                filter_params.push(Box::new(severity.clone()));
+            }
+        }
+        match acknowledged {
+            Some(true) => conditions.push("n.acknowledged_at IS NOT NULL".to_string()),
+            Some(false) => conditions.push("n.acknowledged_at IS NULL".to_string()),
+            None => {}
+        }
+        let where_clause = conditions.join(" AND ");
+
+        // Get total count
+        let count_sql = format!("SELECT COUNT(*) FROM notifications n WHERE {}", where_clause);
+        let total: i64 = conn
+            .query_row(&count_sql, params_to_refs(&filter_params).as_slice(), |row| {
+                row.get(0)
+            })
+            .unwrap_or(0);
+
+        // Resolve current endpoint display name via LEFT JOIN when endpoint_id is available.
+        // This fixes stale names (e.g. "unknown" or bare IPs) in notifications created before
+        // the endpoint received a proper name via mDNS, DHCP, SNMP, etc.
+        let resolve_name_sql = format!("COALESCE({DISPLAY_NAME_SQL}, n.endpoint_name)");
+
+        // Get page of results with resolved endpoint names
+        let sql = format!(
+            "SELECT n.id, n.created_at, n.event_type, n.title, n.details, n.endpoint_name, n.dismissed,
+                    {resolve_name} AS resolved_name,
+                    (SELECT MIN(ip) FROM endpoint_attributes WHERE endpoint_id = e.id
+                     AND ip IS NOT NULL AND ip != '') AS endpoint_ip,
+                    (SELECT MIN(mac) FROM endpoint_attributes WHERE endpoint_id = e.id
+                     AND mac IS NOT NULL AND mac != '') AS endpoint_mac,
+                    n.severity, n.acknowledged_at, n.acknowledged_by,
+                    n.occurrences, n.last_occurred_at
+             FROM notifications n
+             LEFT JOIN endpoints e ON n.endpoint_id = e.id
+             WHERE {where_clause}
+             ORDER BY n.created_at DESC LIMIT ? OFFSET ?",
+            resolve_name = resolve_name_sql,
+            where_clause = where_clause
+        );
+
+        let map_row = |row: &rusqlite::Row| -> rusqlite::Result<NotificationItem> {
+            let original_title: String = row.get(3)?;
+            let original_endpoint_name: Option<String> = row.get(5)?;
+            let resolved_name: Option<String> = row.get(7)?;
+
+            // Rewrite the title if we have a resolved name that differs from the original
+            let title = match (&resolved_name, &original_endpoint_name) {
+                (Some(resolved), Some(original)) if resolved != original && !resolved.is_empty() => {
+                    original_title.replace(original, resolved)
+                }
+                _ => original_title,
+            };
+
+            Ok(NotificationItem {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                event_type: row.get(2)?,
+                title,
+                details: row.get(4)?,
+                endpoint_name: resolved_name.or(original_endpoint_name),
+                dismissed: row.get::<_, i64>(6)? != 0,
+                endpoint_ip: row.get(8)?,
+                endpoint_mac: row.get(9)?,
+                severity: row.get(10)?,
+                acknowledged_at: row.get(11)?,
+                acknowledged_by: row.get(12)?,
+                occurrences: row.get(13)?,
+                last_occurred_at: row.get(14)?,
+            })
+        };
+
+        let mut page_params = filter_params;
+        page_params.push(Box::new(limit));
+        page_params.push(Box::new(offset));
+
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params_to_refs(&page_params).as_slice(), map_row)
+            .map_err(|e| e.to_string())?;
+        let notifications: Vec<NotificationItem> = rows.filter_map(|r| r.ok()).collect();
+
+        Ok::<_, String>((notifications, total))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((notifications, total))) => HttpResponse::Ok().json(NotificationsResponse {
+            notifications,
+            total,
+        }),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch notifications"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NotificationSummaryQuery {
+    /// Only count notifications with activity after this time (the `watermark` of an
+    /// earlier summary, or when the user last opened the notification list)
+    since: Option<i64>,
+}
+
+/// Unread counts per severity and event type, for the notification badge
+#[get("/api/notifications/summary")]
+pub async fn get_notification_summary(query: Query<NotificationSummaryQuery>) -> impl Responder {
+    let since = query.since.unwrap_or(0);
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        notification_summary(&conn, since).map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(summary)) => HttpResponse::Ok().json(summary),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to summarize notifications"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DismissRequest {
+    ids: Vec<i64>,
+}
+
+#[post("/api/notifications/dismiss")]
+pub async fn dismiss_notifications(
+    _admin: RequireAdmin,
+    body: Json<DismissRequest>,
+) -> impl Responder {
+    let ids = body.ids.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        let placeholders: Vec<String> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, _)| format!("?{}", i + 1))
+            .collect();
+        let sql = format!(
+            "UPDATE notifications SET dismissed = 1 WHERE id IN ({})",
+            placeholders.join(",")
+        );
+        let params: Vec<Box<dyn rusqlite::ToSql>> = ids
+            .iter()
+            .map(|id| Box::new(*id) as Box<dyn rusqlite::ToSql>)
+            .collect();
+        let refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        conn.execute(&sql, refs.as_slice())
+            .map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(count)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "dismissed": count
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": "Failed to dismiss notifications"
+        })),
+    }
+}
+
+#[post("/api/notifications/clear")]
+pub async fn clear_notifications(_admin: RequireAdmin) -> impl Responder {
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        conn.execute(
+            "UPDATE notifications SET dismissed = 1 WHERE dismissed = 0",
+            [],
+        )
+        .map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(count)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "dismissed": count
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": "Failed to clear notifications"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AcknowledgeRequest {
+    ids: Vec<i64>,
+    acknowledged_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AcknowledgeAllRequest {
+    /// Comma-separated list of severities to acknowledge (all when omitted)
+    severity: Option<String>,
+    acknowledged_by: Option<String>,
+}
+
+/// Identify who acknowledged a notification: explicit name from the request body,
+/// falling back to the client address.
+fn acknowledger_identity(req: &HttpRequest, explicit: Option<&str>) -> String {
+    explicit
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .or_else(|| {
+            req.connection_info()
+                .realip_remote_addr()
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Acknowledge specific notifications. Unlike dismissal, acknowledged notifications
+/// stay visible in the list but record who handled them and when.
+#[post("/api/notifications/acknowledge")]
+pub async fn acknowledge_notifications(
+    _admin: RequireAdmin,
+    req: HttpRequest,
+    body: Json<AcknowledgeRequest>,
+) -> impl Responder {
+    let ids = body.ids.clone();
+    let acknowledged_by = acknowledger_identity(&req, body.acknowledged_by.as_deref());
+
+    if ids.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "error": "No notification ids provided"
+        }));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        mark_notifications_acknowledged(&conn, &ids, &acknowledged_by).map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(count)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "acknowledged": count
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": "Failed to acknowledge notifications"
+        })),
+    }
+}
+
+/// Acknowledge all unacknowledged notifications, optionally limited to given severities
+#[post("/api/notifications/acknowledge-all")]
+pub async fn acknowledge_all_notifications(
+    _admin: RequireAdmin,
+    req: HttpRequest,
+    body: Json<AcknowledgeAllRequest>,
+) -> impl Responder {
+    let acknowledged_by = acknowledger_identity(&req, body.acknowledged_by.as_deref());
+    let severities = body
+        .severity
+        .as_deref()
+        .map(parse_severity_filter)
+        .unwrap_or_default();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = new_connection();
+        mark_all_notifications_acknowledged(&conn, &severities, &acknowledged_by)
+            .map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(count)) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "acknowledged": count
+        })),
+        _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": "Failed to acknowledge notifications"
+        })),
+    }
+}
+
+// ============================================================================
+// Audit Log
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    since: Option<i64>,
+    until: Option<i64>,
+    action: Option<String>,
+    actor: Option<String>,
+    source_ip: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct AuditEntryItem {
+    id: i64,
+    created_at: i64,
+    action: String,
+    method: String,
+    path: String,
+    status: Option<i64>,
+    source_ip: Option<String>,
+    actor: Option<String>,
+    details: Option<String>,
 }
 
 #[derive(Serialize)]
-pub struct NotificationsResponse {
-    notifications: Vec<NotificationItem>,
+pub struct AuditLogResponse {
+    entries: Vec<AuditEntryItem>,
     total: i64,
 }
 
-#[get("/api/notifications")]
-pub async fn get_notifications(query: Query<NotificationsQuery>) -> impl Responder {
+/// List audit log entries for user-initiated actions, newest first
+#[get("/api/audit")]
+pub async fn get_audit_log(query: Query<AuditQuery>) -> impl Responder {
     let since = query.since.unwrap_or(0);
-    let limit = query.limit.unwrap_or(50);
-    let offset = query.offset.unwrap_or(0);
-    let include_dismissed = query.include_dismissed.unwrap_or(false);
-    let search = query.search.clone().unwrap_or_default();
+    let until = query.until;
+    let action = query.action.clone().filter(|s| !s.is_empty());
+    let actor = query.actor.clone().filter(|s| !s.is_empty());
+    let source_ip = query.source_ip.clone().filter(|s| !s.is_empty());
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = query.offset.unwrap_or(0).max(0);
 
     let result = tokio::task::spawn_blocking(move || {
         let conn = new_connection();
 
-        let has_search = !search.is_empty();
-        let search_pattern = format!("%{}%", search);
-
-        // Build WHERE clause (use n. prefix since we JOIN with endpoints)
-        let mut conditions = vec!["n.created_at > ?1"];
-        if !include_dismissed {
-            conditions.push("n.dismissed = 0");
+        let mut conditions = vec!["created_at >= ?"];
+        let mut filter_params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(since)];
+        if let Some(until) = until {
+            conditions.push("created_at <= ?");
+            filter_params.push(Box::new(until));
+        }
+        if let Some(action) = action {
+            // Allow prefix matching so "endpoint" matches all endpoint_* actions
+            conditions.push("action LIKE ?");
+            filter_params.push(Box::new(format!("{}%", action)));
         }
-        if has_search {
-            conditions.push("(n.title LIKE ?4 OR COALESCE(n.details, '') LIKE ?4 OR COALESCE(n.endpoint_name, '') LIKE ?4 OR n.event_type LIKE ?4)");
+        if let Some(actor) = actor {
+            conditions.push("actor = ?");
+            filter_params.push(Box::new(actor));
+        }
+        if let Some(source_ip) = source_ip {
+            conditions.push("source_ip = ?");
+            filter_params.push(Box::new(source_ip));
         }
         let where_clause = conditions.join(" AND ");
 
-        // Get total count
-        let count_sql = format!("SELECT COUNT(*) FROM notifications n WHERE {}", where_clause);
-        let total: i64 = if has_search {
-            conn.query_row(&count_sql, params![since, limit, offset, search_pattern], |row| row.get(0))
-        } else {
-            conn.query_row(&count_sql, params![since], |row| row.get(0))
-        }.unwrap_or(0);
-
-        // Resolve current endpoint display name via LEFT JOIN when endpoint_id is available.
-        // This fixes stale names (e.g. "unknown" or bare IPs) in notifications created before
-        // the endpoint received a proper name via mDNS, DHCP, SNMP, etc.
-        let resolve_name_sql = "COALESCE(
-                e.custom_name,
-                CASE WHEN e.name IS NOT NULL AND e.name != '' AND e.name NOT LIKE '%:%'
-                     AND e.name NOT GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*' THEN e.name END,
-                (SELECT MIN(hostname) FROM endpoint_attributes WHERE endpoint_id = e.id
-                 AND hostname IS NOT NULL AND hostname != ''
-                 AND hostname NOT LIKE '%:%' AND hostname NOT GLOB '[0-9]*.[0-9]*.[0-9]*.[0-9]*'),
-                (SELECT MIN(ip) FROM endpoint_attributes WHERE endpoint_id = e.id
-                 AND ip IS NOT NULL AND ip != ''),
-                n.endpoint_name
-            )"
-        .to_string();
-
-        // Get page of results with resolved endpoint names
-        let sql = format!(
-            "SELECT n.id, n.created_at, n.event_type, n.title, n.details, n.endpoint_name, n.dismissed,
-                    {resolve_name} AS resolved_name,
-                    (SELECT MIN(ip) FROM endpoint_attributes WHERE endpoint_id = e.id
-                     AND ip IS NOT NULL AND ip != '') AS endpoint_ip,
-                    (SELECT MIN(mac) FROM endpoint_attributes WHERE endpoint_id = e.id
-                     AND mac IS NOT NULL AND mac != '') AS endpoint_mac
-             FROM notifications n
-             LEFT JOIN endpoints e ON n.endpoint_id = e.id
-             WHERE {where_clause}
-             ORDER BY n.created_at DESC LIMIT ?2 OFFSET ?3",
-            resolve_name = resolve_name_sql,
-            where_clause = where_clause
-        );
-
-        let map_row = |row: &rusqlite::Row| -> rusqlite::Result<NotificationItem> {
-            let original_title: String = row.get(3)?;
-            let original_endpoint_name: Option<String> = row.get(5)?;
-            let resolved_name: Option<String> = row.get(7)?;
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM audit_log WHERE {}", where_clause),
+                params_to_refs(&filter_params).as_slice(),
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
 
-            // Rewrite the title if we have a resolved name that differs from the original
-            let title = match (&resolved_name, &original_endpoint_name) {
-                (Some(resolved), Some(original)) if resolved != original && !resolved.is_empty() => {
-                    original_title.replace(original, resolved)
-                }
-                _ => original_title,
-            };
+        let mut page_params = filter_params;
+        page_params.push(Box::new(limit));
+        page_params.push(Box::new(offset));
 
-            Ok(NotificationItem {
-                id: row.get(0)?,
-                created_at: row.get(1)?,
-                event_type: row.get(2)?,
-                title,
-                details: row.get(4)?,
-                endpoint_name: resolved_name.or(original_endpoint_name),
-                dismissed: row.get::<_, i64>(6)? != 0,
-                endpoint_ip: row.get(8)?,
-                endpoint_mac: row.get(9)?,
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT id, created_at, action, method, path, status, source_ip, actor, details
+                 FROM audit_log WHERE {}
+                 ORDER BY created_at DESC, id DESC LIMIT ? OFFSET ?",
+                where_clause
+            ))
+            .map_err(|e| e.to_string())?;
+        let entries: Vec<AuditEntryItem> = stmt
+            .query_map(params_to_refs(&page_params).as_slice(), |row| {
+                Ok(AuditEntryItem {
+                    id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    action: row.get(2)?,
+                    method: row.get(3)?,
+                    path: row.get(4)?,
+                    status: row.get(5)?,
+                    source_ip: row.get(6)?,
+                    actor: row.get(7)?,
+                    details: row.get(8)?,
+                })
             })
-        };
-
-        let notifications: Vec<NotificationItem> = if has_search {
-            let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
-            let rows = stmt
-                .query_map(params![since, limit, offset, search_pattern], map_row)
-                .map_err(|e| e.to_string())?;
-            rows.filter_map(|r| r.ok()).collect()
-        } else {
-            let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
-            let rows = stmt
-                .query_map(params![since, limit, offset], map_row)
-                .map_err(|e| e.to_string())?;
-            rows.filter_map(|r| r.ok()).collect()
-        };
+            .map_err(|e| e.to_string())?
+            .filter_map(|r| r.ok())
+            .collect();
 
-        Ok::<_, String>((notifications, total))
+        Ok::<_, String>((entries, total))
     })
     .await;
 
     match result {
-        Ok(Ok((notifications, total))) => HttpResponse::Ok().json(NotificationsResponse {
-            notifications,
-            total,
-        }),
+        Ok(Ok((entries, total))) => HttpResponse::Ok().json(AuditLogResponse { entries, total }),
         _ => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Failed to fetch notifications"
+            "error": "Failed to fetch audit log"
         })),
     }
 }
 
 #[derive(Deserialize)]
-pub struct DismissRequest {
-    ids: Vec<i64>,
+pub struct ActivityQuery {
+    since: Option<i64>,
+    until: Option<i64>,
+    /// Only this kind of event, e.g. "scan" or "capture_stopped"
+    kind: Option<String>,
+    limit: Option<usize>,
 }
 
-#[post("/api/notifications/dismiss")]
-pub async fn dismiss_notifications(body: Json<DismissRequest>) -> impl Responder {
-    let ids = body.ids.clone();
+/// When capture started, stopped or paused, scans ran, pcaps were imported and old data
+/// was pruned, newest first, to explain gaps in the collected data
+#[get("/api/activity")]
+pub async fn get_activity_timeline(query: Query<ActivityQuery>) -> impl Responder {
+    let since = query.since.unwrap_or(0);
+    let until = query.until.unwrap_or(i64::MAX);
+    let kind = query.kind.clone().filter(|s| !s.is_empty());
+    let limit = query.limit.unwrap_or(200).clamp(1, 1000);
+
     let result = tokio::task::spawn_blocking(move || {
         let conn = new_connection();
-        let placeholders: Vec<String> = ids
-            .iter()
-            .enumerate()
-            .map(|(i, _)| format!("?{}", i + 1))
-            .collect();
-        let sql = format!(
-            "UPDATE notifications SET dismissed = 1 WHERE id IN ({})",
-            placeholders.join(",")
-        );
-        let params: Vec<Box<dyn rusqlite::ToSql>> = ids
-            .iter()
-            .map(|id| Box::new(*id) as Box<dyn rusqlite::ToSql>)
-            .collect();
-        let refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-        conn.execute(&sql, refs.as_slice())
-            .map_err(|e| e.to_string())
+        get_activity(&conn, since, until, kind.as_deref(), limit).map_err(|e| e.to_string())
     })
     .await;
 
     match result {
-        Ok(Ok(count)) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true,
-            "dismissed": count
-        })),
+        Ok(Ok(events)) => HttpResponse::Ok().json(serde_json::json!({ "events": events })),
         _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to fetch activity"
+        })),
+    }
+}
+
+// ============================================================================
+// Database Maintenance
+// ============================================================================
+
+/// Run ANALYZE and report table/index sizes and the query plans of hot queries
+#[post("/api/maintenance/analyze")]
+pub async fn run_maintenance_analyze(_admin: RequireAdmin) -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| -> Result<DatabaseReport, String> {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        analyze_database(&conn).map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(report)) => HttpResponse::Ok().json(report),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
             "success": false,
-            "error": "Failed to dismiss notifications"
+            "error": format!("Failed to analyze database: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": "Failed to analyze database"
         })),
     }
 }
 
-#[post("/api/notifications/clear")]
-pub async fn clear_notifications() -> impl Responder {
-    let result = tokio::task::spawn_blocking(move || {
-        let conn = new_connection();
-        conn.execute(
-            "UPDATE notifications SET dismissed = 1 WHERE dismissed = 0",
-            [],
+/// Run a read-only SQL query (admin only) and return its rows as JSON
+#[post("/api/query")]
+pub async fn run_query_api(_admin: RequireAdmin, body: Json<QueryRequest>) -> impl Responder {
+    let request = body.into_inner();
+    let result = tokio::task::spawn_blocking(move || -> Result<QueryResult, String> {
+        let conn = new_read_only_connection().map_err(|e| e.to_string())?;
+        run_read_only_query(
+            &conn,
+            &request.sql,
+            request.limit.unwrap_or(DEFAULT_QUERY_ROWS),
         )
-        .map_err(|e| e.to_string())
     })
     .await;
 
     match result {
-        Ok(Ok(count)) => HttpResponse::Ok().json(serde_json::json!({
-            "success": true,
-            "dismissed": count
+        Ok(Ok(rows)) => HttpResponse::Ok().json(rows),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "error": e
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "error": "Failed to run query"
+        })),
+    }
+}
+
+/// Database and WAL size, rows per table, free disk, process memory and writer backlog,
+/// with warnings when retention should be lowered
+#[get("/api/stats/system")]
+pub async fn get_system_stats_api() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| -> Result<SystemStats, String> {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        get_system_stats(&conn).map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(stats)) => HttpResponse::Ok().json(stats),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to collect system stats: {}", e)
+        })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to collect system stats"
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EnergyQuery {
+    /// Days of daily summaries, counting today (default 30)
+    days: Option<i64>,
+    /// Calendar months of monthly summaries, counting this one (default 12)
+    months: Option<i64>,
+}
+
+/// Estimated energy use of the household's devices from their wattage and online hours,
+/// as daily and monthly kWh, per device type and for the biggest users
+#[get("/api/stats/energy")]
+pub async fn get_energy_stats_api(query: Query<EnergyQuery>) -> impl Responder {
+    let days = query.days.unwrap_or(30).clamp(1, 366);
+    let months = query.months.unwrap_or(12).clamp(1, 120);
+    let result = tokio::task::spawn_blocking(move || -> Result<EnergyReport, String> {
+        let conn = new_connection_result().map_err(|e| e.to_string())?;
+        let now = chrono::Utc::now().timestamp();
+        // Bring yesterday and today up to date; the hourly rollup lags behind
+        if !crate::db::is_read_replica() {
+            rollup_energy(&conn, now - 24 * 60 * 60).map_err(|e| e.to_string())?;
+        }
+        get_energy_report(&conn, days, months, now).map_err(|e| e.to_string())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(report)) => HttpResponse::Ok().json(report),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to estimate energy use: {}", e)
         })),
+        Err(_) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to estimate energy use"
+        })),
+    }
+}
+
+#[derive(Serialize)]
+pub struct EnergyModelResponse {
+    /// Watts per device type
+    types: HashMap<String, TypePower>,
+    /// Endpoints with their own wattage, by lowercase name
+    endpoints: HashMap<String, f64>,
+}
+
+/// The wattages energy estimates are based on
+#[get("/api/energy/model")]
+pub async fn get_energy_model() -> impl Responder {
+    let result = tokio::task::spawn_blocking(|| {
+        let conn = new_connection_result()?;
+        Ok::<_, rusqlite::Error>(EnergyModelResponse {
+            types: get_type_power(&conn)?,
+            endpoints: get_endpoint_power(&conn)?,
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(response)) => HttpResponse::Ok().json(response),
         _ => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": "Failed to load energy model"
+        })),
+    }
+}
+
+/// Validate a wattage from a request
+fn parse_watts(watts: Option<f64>) -> Result<Option<f64>, String> {
+    match watts {
+        Some(w) if !(0.0..=MAX_WATTS).contains(&w) => {
+            Err(format!("Watts must be between 0 and {}", MAX_WATTS))
+        }
+        watts => Ok(watts),
+    }
+}
+
+/// A device type's wattage; leaving it out restores the built-in estimate
+#[derive(Deserialize)]
+pub struct SetTypePowerRequest {
+    device_type: String,
+    watts: Option<f64>,
+}
+
+#[post("/api/energy/model/type")]
+pub async fn update_device_type_power(
+    _admin: RequireAdmin,
+    body: Json<SetTypePowerRequest>,
+) -> impl Responder {
+    if !is_known_device_type(&body.device_type) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
             "success": false,
-            "error": "Failed to clear notifications"
+            "message": format!("Unknown device type '{}'", body.device_type)
+        }));
+    }
+    let watts = match parse_watts(body.watts) {
+        Ok(watts) => watts,
+        Err(message) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "success": false, "message": message }));
+        }
+    };
+
+    let conn = new_connection();
+    match set_type_power(&conn, &body.device_type, watts) {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("Power updated for {}", body.device_type)
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
+        })),
+    }
+}
+
+/// An endpoint's own wattage; leaving it out reverts to its type's
+#[derive(Deserialize)]
+pub struct SetEndpointPowerRequest {
+    endpoint_name: String,
+    watts: Option<f64>,
+}
+
+#[post("/api/endpoint/power")]
+pub async fn update_endpoint_power(
+    _admin: RequireAdmin,
+    body: Json<SetEndpointPowerRequest>,
+) -> impl Responder {
+    let watts = match parse_watts(body.watts) {
+        Ok(watts) => watts,
+        Err(message) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "success": false, "message": message }));
+        }
+    };
+
+    let conn = new_connection();
+    let Some(endpoint_id) = find_endpoint_id_by_name(&conn, &body.endpoint_name) else {
+        return HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "message": format!("Endpoint '{}' not found", body.endpoint_name)
+        }));
+    };
+    match set_endpoint_power(&conn, endpoint_id, watts) {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "message": format!("Power updated for {}", body.endpoint_name)
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "success": false,
+            "message": format!("Database error: {}", e)
         })),
     }
 }
@@ -3719,5 +9774,251 @@ pub async fn get_instance() -> impl Responder {
         "app": "awareness",
         "version": env!("CARGO_PKG_VERSION"),
         "pid": std::process::id(),
+        "read_replica": crate::db::is_read_replica(),
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::new_test_connection;
+    use crate::scanner::{ArpResult, IcmpResult, PortResult};
+
+    fn arp(ip: &str, mac: &str) -> ScanResult {
+        ScanResult::Arp(ArpResult {
+            ip: ip.parse().unwrap(),
+            mac: mac.parse().unwrap(),
+            response_time_ms: 3,
+        })
+    }
+
+    fn port(ip: &str, port: u16) -> ScanResult {
+        ScanResult::Port(PortResult {
+            ip: ip.parse().unwrap(),
+            port,
+            open: true,
+            service_name: None,
+        })
+    }
+
+    #[test]
+    fn test_gather_scan_batch_skips_repeats() {
+        let (tx, mut rx) = mpsc::channel(1000);
+        let mut seen = HashSet::new();
+        let dead = |ip: &str| {
+            ScanResult::Icmp(IcmpResult {
+                ip: ip.parse().unwrap(),
+                alive: false,
+                rtt_ms: None,
+                ttl: None,
+            })
+        };
+        for event in [
+            // An ARP retry answered twice
+            arp("10.0.0.2", "02:00:00:00:00:02"),
+            // Results without a repeat key are always kept
+            dead("10.0.0.9"),
+            dead("10.0.0.9"),
+            port("10.0.0.2", 22),
+        ] {
+            tx.try_send(ScanEvent::Result(event)).unwrap();
+        }
+        tx.try_send(ScanEvent::Failed {
+            scan_type: ScanType::PORT,
+            error: "boom".to_string(),
+        })
+        .unwrap();
+        tx.try_send(ScanEvent::Result(port("10.0.0.3", 22)))
+            .unwrap();
+
+        let (batch, next) =
+            gather_scan_batch(arp("10.0.0.2", "02:00:00:00:00:02"), &mut rx, &mut seen);
+        assert_eq!(batch.len(), 4);
+        // The batch stops at the failure, leaving later results queued
+        assert!(matches!(next, Some(ScanEvent::Failed { .. })));
+        assert!(matches!(rx.try_recv(), Ok(ScanEvent::Result(_))));
+
+        // Repeats are remembered across batches in the same run
+        let (batch, next) = gather_scan_batch(port("10.0.0.2", 22), &mut rx, &mut seen);
+        assert!(batch.is_empty());
+        assert!(next.is_none());
+
+        // A long burst is split into full batches
+        for n in 0..250 {
+            tx.try_send(ScanEvent::Result(port("10.0.0.4", 1000 + n)))
+                .unwrap();
+        }
+        let first = match rx.try_recv() {
+            Ok(ScanEvent::Result(result)) => result,
+            _ => unreachable!(),
+        };
+        let (batch, _) = gather_scan_batch(first, &mut rx, &mut seen);
+        assert_eq!(batch.len(), SCAN_RESULT_BATCH_SIZE);
+        let (batch, _) = gather_scan_batch(port("10.0.0.5", 80), &mut rx, &mut seen);
+        assert_eq!(batch.len(), 250 - SCAN_RESULT_BATCH_SIZE + 1);
+    }
+
+    #[test]
+    fn test_record_scan_results() {
+        let conn = new_test_connection();
+        conn.execute(
+            "CREATE TABLE scan_results (
+                id INTEGER PRIMARY KEY,
+                endpoint_id INTEGER NOT NULL,
+                scan_type TEXT NOT NULL,
+                scanned_at INTEGER NOT NULL,
+                response_time_ms INTEGER,
+                details TEXT,
+                ip TEXT
+            )",
+            [],
+        )
+        .unwrap();
+
+        // Loopback addresses count as local, so the ARP answer creates an endpoint
+        record_scan_results(
+            &conn,
+            &[
+                arp("127.0.0.2", "02:00:00:00:00:02"),
+                port("127.0.0.2", 22),
+                // No endpoint holds this address, so the open port isn't recorded
+                port("127.0.0.99", 22),
+            ],
+        );
+        let count = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM endpoints"), 1);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM scan_results WHERE scan_type = 'arp'"),
+            1
+        );
+        assert_eq!(count("SELECT COUNT(*) FROM open_ports WHERE port = 22"), 1);
+    }
+
+    #[test]
+    fn test_apply_endpoints_table_query() {
+        let row = |endpoint_id: i64, name: &str, vendor: Option<&str>, bytes: i64, seen: i64| {
+            EndpointTableRow {
+                endpoint_id,
+                name: name.to_string(),
+                vendor: vendor.map(String::from),
+                model: None,
+                device_type: Some(if bytes > 100 { "TV" } else { "phone" }.to_string()),
+                bytes,
+                last_seen: "-".to_string(),
+                last_seen_at: Some(seen),
+                online: seen >= 50,
+                web_title: None,
+                has_favicon: false,
+            }
+        };
+        let rows = vec![
+            row(1, "den-tv", Some("LG"), 500, 10),
+            row(2, "Alices-phone", Some("Apple"), 50, 90),
+            row(3, "bobs-phone", None, 80, 60),
+            row(4, "kitchen-tv", Some("Samsung"), 300, 70),
+        ];
+        let names = |query: EndpointsTableQuery, tagged: Option<&HashSet<i64>>| {
+            let (page, total) = apply_endpoints_table_query(rows.clone(), &query, tagged);
+            let names: Vec<String> = page.into_iter().map(|r| r.name).collect();
+            (names, total)
+        };
+
+        // Name sorts ascending and case-insensitively by default
+        assert_eq!(
+            names(EndpointsTableQuery::default(), None).0,
+            ["Alices-phone", "bobs-phone", "den-tv", "kitchen-tv"]
+        );
+        // Bytes and last_seen default to descending
+        let query = EndpointsTableQuery {
+            sort: Some("bytes".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query, None).0[0], "den-tv");
+        let query = EndpointsTableQuery {
+            sort: Some("last_seen".to_string()),
+            order: Some("asc".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query, None).0[0], "den-tv");
+        // Endpoints without a vendor sort last
+        let query = EndpointsTableQuery {
+            sort: Some("vendor".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query, None).0[3], "bobs-phone");
+
+        let query = EndpointsTableQuery {
+            device_type: Some("tv".to_string()),
+            online: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(names(query, None), (vec!["kitchen-tv".to_string()], 1));
+        let query = EndpointsTableQuery {
+            vendor: Some("apP".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query, None).1, 1);
+        let tagged: HashSet<i64> = [1, 3].into_iter().collect();
+        assert_eq!(
+            names(EndpointsTableQuery::default(), Some(&tagged)).0,
+            ["bobs-phone", "den-tv"]
+        );
+
+        // The total counts every match; the page is cut from the sorted list
+        let query = EndpointsTableQuery {
+            limit: Some(2),
+            offset: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(query, None),
+            (vec!["bobs-phone".to_string(), "den-tv".to_string()], 4)
+        );
+        let query = EndpointsTableQuery {
+            offset: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(names(query, None), (vec![], 4));
+    }
+
+    #[test]
+    fn test_bulk_endpoint_fields() {
+        let details = EndpointDetailsResponse {
+            endpoint_name: "den-tv".to_string(),
+            device_type: "tv".to_string(),
+            tags: vec!["media".to_string()],
+            ..Default::default()
+        };
+
+        // Every selectable field is one the details route returns, and vice versa, so
+        // v1 consumers see the same shape as the per-endpoint route
+        let full = bulk_endpoint_object(7, &details, None);
+        let mut keys: Vec<&str> = full
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .filter(|key| *key != "id")
+            .collect();
+        let mut expected = ENDPOINT_DETAIL_FIELDS.to_vec();
+        keys.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
+        assert_eq!(full["id"], 7);
+
+        let fields = parse_detail_fields(" tags, ,device_type").unwrap();
+        assert_eq!(fields, ["tags", "device_type"]);
+        assert_eq!(
+            bulk_endpoint_object(7, &details, Some(&fields)),
+            serde_json::json!({
+                "id": 7,
+                "endpoint_name": "den-tv",
+                "device_type": "tv",
+                "tags": ["media"],
+            })
+        );
+        assert!(parse_detail_fields("").unwrap().is_empty());
+        let error = parse_detail_fields("ips,password").unwrap_err();
+        assert!(error.contains("'password'"), "{}", error);
+    }
+}
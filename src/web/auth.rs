@@ -0,0 +1,206 @@
+//! Who is calling and what they may do. The tool has no login of its own: callers are
+//! named by an authenticating reverse proxy, trusted only when the request comes from an
+//! address given with `--trusted-proxy`, or by an API token issued to a user. Every
+//! handler that changes something takes a [`RequireAdmin`] argument, except a viewer's own
+//! saved views and preferences and the classification dry run.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+use actix_web::dev::Payload;
+use actix_web::http::header::{AUTHORIZATION, HeaderMap};
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use tokio::task;
+
+use crate::db::{Role, new_connection_result, resolve_role, user_for_token};
+
+/// Headers an authenticating reverse proxy puts the signed-in user name in
+const PROXY_USER_HEADERS: &[&str] = &["Remote-User", "X-Forwarded-User", "X-Remote-User"];
+
+static TRUSTED_PROXIES: OnceLock<Vec<IpAddr>> = OnceLock::new();
+
+/// Addresses of reverse proxies whose user and forwarding headers are believed. Call at
+/// startup, before the web server starts; without it those headers are ignored.
+pub fn set_trusted_proxies(proxies: Vec<IpAddr>) {
+    let _ = TRUSTED_PROXIES.set(proxies);
+}
+
+fn trusted_proxies() -> &'static [IpAddr] {
+    TRUSTED_PROXIES.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+fn is_trusted_proxy(peer: Option<SocketAddr>, trusted: &[IpAddr]) -> bool {
+    peer.is_some_and(|peer| trusted.contains(&peer.ip()))
+}
+
+/// The user name a proxy passed, when the request came straight from a trusted one
+fn proxy_user(headers: &HeaderMap, peer: Option<SocketAddr>, trusted: &[IpAddr]) -> Option<String> {
+    if !is_trusted_proxy(peer, trusted) {
+        return None;
+    }
+    PROXY_USER_HEADERS.iter().find_map(|header| {
+        headers
+            .get(*header)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+    })
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+/// Identify the caller of a request: the user name set by a trusted authenticating
+/// proxy, then the user their bearer token was issued to. Unknown tokens are anonymous.
+pub(super) fn request_identity(headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<String> {
+    if let Some(user) = proxy_user(headers, peer, trusted_proxies()) {
+        return Some(user);
+    }
+    let token = bearer_token(headers)?;
+    let conn = new_connection_result().ok()?;
+    user_for_token(&conn, token).unwrap_or_else(|e| {
+        eprintln!("Failed to look up API token: {}", e);
+        None
+    })
+}
+
+/// The client address for the audit log. Forwarding headers only count when the
+/// request came from a trusted proxy.
+pub(super) fn client_ip(req: &HttpRequest) -> Option<String> {
+    if is_trusted_proxy(req.peer_addr(), trusted_proxies()) {
+        req.connection_info()
+            .realip_remote_addr()
+            .map(|s| s.to_string())
+    } else {
+        req.peer_addr().map(|peer| peer.ip().to_string())
+    }
+}
+
+/// The caller of a request and their role, resolved once per request
+#[derive(Debug, Clone)]
+pub(super) struct Caller {
+    /// None for anonymous requests
+    pub identity: Option<String>,
+    pub role: Role,
+}
+
+impl FromRequest for Caller {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(caller) = req.extensions().get::<Caller>().cloned() {
+            return Box::pin(async move { Ok(caller) });
+        }
+        let req = req.clone();
+        let headers = req.headers().clone();
+        let peer = req.peer_addr();
+        Box::pin(async move {
+            let resolved = task::spawn_blocking(move || {
+                let identity = request_identity(&headers, peer);
+                let conn = new_connection_result()?;
+                let role = resolve_role(&conn, identity.as_deref())?;
+                Ok::<_, rusqlite::Error>(Caller { identity, role })
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()));
+            match resolved {
+                Ok(caller) => {
+                    req.extensions_mut().insert(caller.clone());
+                    Ok(caller)
+                }
+                Err(e) => {
+                    eprintln!("Failed to check role for {}: {}", req.path(), e);
+                    Err(permission_error(
+                        HttpResponse::InternalServerError(),
+                        "Failed to check permissions",
+                    ))
+                }
+            }
+        })
+    }
+}
+
+/// Taken by handlers only admins may call; viewers are turned away with 403
+pub(super) struct RequireAdmin;
+
+impl FromRequest for RequireAdmin {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let caller = Caller::from_request(req, payload);
+        Box::pin(async move {
+            match caller.await?.role {
+                Role::Admin => Ok(RequireAdmin),
+                Role::Viewer => Err(permission_error(
+                    HttpResponse::Forbidden(),
+                    "This action requires the admin role",
+                )),
+            }
+        })
+    }
+}
+
+fn permission_error(
+    mut response: actix_web::HttpResponseBuilder,
+    message: &'static str,
+) -> actix_web::Error {
+    let response = response.json(serde_json::json!({
+        "success": false,
+        "message": message
+    }));
+    actix_web::error::InternalError::from_response(message, response).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    #[test]
+    fn test_proxy_user_needs_trusted_peer() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("remote-user"),
+            HeaderValue::from_static("alice"),
+        );
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let from = |ip: &str| Some(SocketAddr::new(ip.parse().unwrap(), 40000));
+
+        assert_eq!(
+            proxy_user(&headers, from("10.0.0.2"), &[proxy]).as_deref(),
+            Some("alice")
+        );
+        // Anyone else could set the header themselves
+        assert_eq!(proxy_user(&headers, from("10.0.0.3"), &[proxy]), None);
+        assert_eq!(proxy_user(&headers, from("10.0.0.2"), &[]), None);
+        assert_eq!(proxy_user(&headers, None, &[proxy]), None);
+        assert_eq!(
+            proxy_user(&HeaderMap::new(), from("10.0.0.2"), &[proxy]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret-token "),
+        );
+        assert_eq!(bearer_token(&headers), Some("secret-token"));
+    }
+}
@@ -71,6 +71,14 @@
             }
         }
 
+        ['wan-bytes-in', 'wan-bytes-out'].forEach(function(id) {
+            var elem = document.getElementById(id);
+            var bytes = elem ? parseInt(elem.textContent) : NaN;
+            if (!isNaN(bytes) && App.Formatting) {
+                elem.textContent = App.Formatting.formatBytes(bytes);
+            }
+        });
+
         // Format endpoint bytes in the list
         document.querySelectorAll('.bandwidth-value').forEach(function(elem) {
             var bytes = parseInt(elem.textContent);
@@ -92,6 +100,8 @@
         var filterVirtualization = urlParams.get('filter_virtualization');
         var filterSoundbar = urlParams.get('filter_soundbar');
         var filterAppliance = urlParams.get('filter_appliance');
+        var filterNas = urlParams.get('filter_nas');
+        var filterUps = urlParams.get('filter_ups');
         var filterOther = urlParams.get('filter_other');
 
         // Set checkbox states (default to checked if not specified)
@@ -106,6 +116,8 @@
             'filterVirtualization': filterVirtualization,
             'filterSoundbar': filterSoundbar,
             'filterAppliance': filterAppliance,
+            'filterNas': filterNas,
+            'filterUps': filterUps,
             'filterOther': filterOther
         };
 
@@ -240,6 +252,11 @@
             App.restorePaginationState();
         }
 
+        // Apply hidden columns and time window, and list saved views
+        if (App.Views) {
+            App.Views.init();
+        }
+
         // Initialize refresh interval
         if (App.Refresh) {
             App.Refresh.init();
@@ -360,8 +377,9 @@
                     bVal = b.dataset.endpointType || '';
                     break;
                 case 'name':
-                    aVal = (a.querySelector('.endpoint-name-cell')?.textContent || '').toLowerCase();
-                    bVal = (b.querySelector('.endpoint-name-cell')?.textContent || '').toLowerCase();
+                    // The name cell also holds the web page title
+                    aVal = (a.dataset.endpointName || '').toLowerCase();
+                    bVal = (b.dataset.endpointName || '').toLowerCase();
                     break;
                 case 'vendor':
                     aVal = (a.querySelector('.vendor-cell')?.textContent || '').toLowerCase();
@@ -93,6 +93,8 @@
                         'virtualization': '🖥 VM',
                         'soundbar': '🔊 Soundbar',
                         'appliance': '🏠 Appliance',
+                        'nas': '🗄️ NAS',
+                        'ups': '🔋 UPS',
                         'local': '🖥️ Local',
                         'other': '❓ Other'
                     };
@@ -474,6 +476,79 @@
             .catch(function(error) {
                 alert('Failed to reset model. Please try again.');
             });
+        },
+
+        /**
+         * Show icon/color edit inputs
+         */
+        showAppearanceEdit: function() {
+            // Pause auto-refresh while the inputs are open
+            if (App.state.refreshIntervalId) {
+                App.state.savedRefreshInterval = document.getElementById('refreshInterval').value;
+                clearInterval(App.state.refreshIntervalId);
+                App.state.refreshIntervalId = null;
+            }
+            document.getElementById('appearance-edit-input').style.display = 'block';
+            document.getElementById('custom-icon-input').focus();
+        },
+
+        /**
+         * Cancel icon/color edit operation
+         */
+        cancelAppearanceEdit: function() {
+            document.getElementById('appearance-edit-input').style.display = 'none';
+            // Resume auto-refresh if it was paused
+            if (App.state.savedRefreshInterval && App.state.savedRefreshInterval !== '0') {
+                if (App.Refresh) {
+                    App.Refresh.updateInterval(App.state.savedRefreshInterval);
+                }
+                App.state.savedRefreshInterval = null;
+            }
+        },
+
+        /**
+         * Save the endpoint's own icon and color. The color is only sent once it
+         * has been picked, so an untouched picker keeps the type's color.
+         */
+        saveEndpointAppearance: function() {
+            var colorInput = document.getElementById('custom-color-input');
+            App.Classification.setEndpointAppearance(
+                document.getElementById('custom-icon-input').value.trim(),
+                colorInput.dataset.changed ? colorInput.value : ''
+            );
+        },
+
+        /**
+         * Clear the endpoint's own icon and color, reverting to its type's
+         */
+        clearEndpointAppearance: function() {
+            App.Classification.setEndpointAppearance('', '');
+        },
+
+        setEndpointAppearance: function(icon, color) {
+            fetch('/api/endpoint/appearance', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({
+                    endpoint_name: currentEndpointName,
+                    icon: icon,
+                    color: color
+                })
+            })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                if (result.success) {
+                    // Icons and colors are rendered into the table server-side
+                    if (App.Refresh) {
+                        App.Refresh.reloadWithState();
+                    }
+                } else {
+                    alert('Failed to set icon and color: ' + result.message);
+                }
+            })
+            .catch(function(error) {
+                alert('Failed to set icon and color. Please try again.');
+            });
         }
     };
 
@@ -492,5 +567,9 @@
     window.cancelModelEdit = App.Classification.cancelModelEdit;
     window.saveCustomModel = App.Classification.saveCustomModel;
     window.clearCustomModel = App.Classification.clearCustomModel;
+    window.showAppearanceEdit = App.Classification.showAppearanceEdit;
+    window.cancelAppearanceEdit = App.Classification.cancelAppearanceEdit;
+    window.saveEndpointAppearance = App.Classification.saveEndpointAppearance;
+    window.clearEndpointAppearance = App.Classification.clearEndpointAppearance;
 
 })(window.App);
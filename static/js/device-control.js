@@ -29,8 +29,9 @@
 
         /**
          * Load device capabilities from the API
+         * @param {boolean} refresh - Probe the device again instead of using cached detection
          */
-        loadCapabilities: function() {
+        loadCapabilities: function(refresh) {
             // Mark as loading to prevent refresh interruption
             App.state.deviceCapabilitiesLoaded = false;
 
@@ -82,6 +83,7 @@
             var url = '/api/device/capabilities?ip=' + encodeURIComponent(App.state.currentDeviceIp);
             if (deviceType) url += '&device_type=' + encodeURIComponent(deviceType);
             if (endpointName) url += '&hostname=' + encodeURIComponent(endpointName);
+            if (refresh === true) url += '&refresh=true';
 
             // Add timeout for slow/unreachable devices
             var controller = new AbortController();
@@ -127,6 +129,16 @@
                             if (capabilities.apps && capabilities.apps.length > 0 && appsSectionEl) {
                                 appsSectionEl.style.display = 'block';
                                 var appsGrid = document.getElementById('apps-grid');
+                                var deepLinkApp = document.getElementById('roku-deep-link-app');
+                                if (deepLinkApp) {
+                                    deepLinkApp.innerHTML = '';
+                                    capabilities.apps.forEach(function(app) {
+                                        var option = document.createElement('option');
+                                        option.value = app.id;
+                                        option.textContent = app.name;
+                                        deepLinkApp.appendChild(option);
+                                    });
+                                }
                                 if (appsGrid) {
                                     appsGrid.innerHTML = '';
 
@@ -248,6 +260,80 @@
             });
         },
 
+        /**
+         * POST a device action and show its result in a status line
+         */
+        postWithStatus: function(url, payload, statusId) {
+            var statusEl = document.getElementById(statusId);
+            payload.ip = App.state.currentDeviceIp;
+            payload.device_type = App.state.currentDeviceType;
+            if (statusEl) {
+                statusEl.textContent = 'Sending...';
+                statusEl.style.color = 'var(--text-secondary)';
+            }
+
+            return fetch(url, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(payload)
+            })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                if (statusEl) {
+                    statusEl.textContent = result.message;
+                    statusEl.style.color = result.success ? 'var(--accent-success)' : '#ef4444';
+                }
+                return result;
+            })
+            .catch(function(error) {
+                console.error('Device request failed:', error);
+                if (statusEl) {
+                    statusEl.textContent = 'Request failed';
+                    statusEl.style.color = '#ef4444';
+                }
+                return { success: false };
+            });
+        },
+
+        /**
+         * Type the keyboard box's text into the device's on-screen keyboard
+         */
+        sendText: function() {
+            var input = document.getElementById('roku-text-input');
+            if (!input || !input.value || !App.state.currentDeviceIp) return;
+
+            App.DeviceControl.postWithStatus('/api/device/text', { text: input.value }, 'roku-text-status')
+                .then(function(result) {
+                    if (result.success) input.value = '';
+                });
+        },
+
+        /**
+         * Open the device's search screen for the keyboard box's text
+         */
+        search: function() {
+            var input = document.getElementById('roku-text-input');
+            if (!input || !input.value.trim() || !App.state.currentDeviceIp) return;
+
+            App.DeviceControl.postWithStatus('/api/device/search', { keyword: input.value }, 'roku-text-status');
+        },
+
+        /**
+         * Launch the selected app straight into a piece of content
+         */
+        launchContent: function() {
+            var app = document.getElementById('roku-deep-link-app');
+            var contentId = document.getElementById('roku-content-id');
+            var mediaType = document.getElementById('roku-media-type');
+            if (!app || !app.value || !contentId || !contentId.value.trim() || !App.state.currentDeviceIp) return;
+
+            App.DeviceControl.postWithStatus('/api/device/launch', {
+                app_id: app.value,
+                content_id: contentId.value.trim(),
+                media_type: mediaType ? mediaType.value : 'movie'
+            }, 'roku-content-status');
+        },
+
         /**
          * Pair with a device (Samsung TVs)
          */
@@ -303,6 +389,9 @@
     window.loadDeviceCapabilities = App.DeviceControl.loadCapabilities;
     window.sendCommand = App.DeviceControl.sendCommand;
     window.launchApp = App.DeviceControl.launchApp;
+    window.sendDeviceText = App.DeviceControl.sendText;
+    window.searchDevice = App.DeviceControl.search;
+    window.launchContent = App.DeviceControl.launchContent;
     window.pairDevice = App.DeviceControl.pairDevice;
 
 })(window.App);
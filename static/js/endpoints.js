@@ -96,6 +96,8 @@
                 'virtualization': { emoji: '', label: 'VM' },
                 'soundbar': { emoji: '', label: 'Soundbar' },
                 'appliance': { emoji: '', label: 'Appliance' },
+                'nas': { emoji: '', label: 'NAS' },
+                'ups': { emoji: '', label: 'UPS' },
                 'local': { emoji: '', label: 'Local' }
             };
             return types[deviceType] || { emoji: '?', label: 'Other' };
@@ -161,6 +163,12 @@
                 manualOverrideIndicator.style.display = data.is_manual_override ? '' : 'none';
             }
 
+            // Update capture exclusion badge
+            var captureExcludedIndicator = document.getElementById('capture-excluded-indicator');
+            if (captureExcludedIndicator) {
+                captureExcludedIndicator.style.display = data.capture_excluded ? '' : 'none';
+            }
+
             // Update vendor badge and add button
             var vendorBadge = document.getElementById('device-vendor-badge');
             var addVendorBtn = document.getElementById('add-vendor-btn');
@@ -217,6 +225,12 @@
             var bytesOut = document.getElementById('bytes-out');
             if (bytesIn) bytesIn.textContent = App.Formatting.formatBytes(data.bytes_in);
             if (bytesOut) bytesOut.textContent = App.Formatting.formatBytes(data.bytes_out);
+            var wanBytesIn = document.getElementById('wan-bytes-in');
+            var wanBytesOut = document.getElementById('wan-bytes-out');
+            if (wanBytesIn) wanBytesIn.textContent = App.Formatting.formatBytes(data.wan_bytes_in);
+            if (wanBytesOut) wanBytesOut.textContent = App.Formatting.formatBytes(data.wan_bytes_out);
+
+            App.Endpoints.renderLinkedEndpoints(data);
 
             // Update protocols container
             var protocolsContainer = document.getElementById('protocols-container');
@@ -307,6 +321,10 @@
                 actionsContainer.style.display = '';
                 var mergeBtn = document.getElementById('merge-endpoint-btn');
                 var deleteBtn = document.getElementById('delete-endpoint-btn');
+                var linkBtn = document.getElementById('link-endpoint-btn');
+                if (linkBtn) {
+                    linkBtn.onclick = function() { App.Endpoints.linkEndpoint(data.endpoint_name); };
+                }
                 if (mergeBtn) {
                     mergeBtn.onclick = function() { App.Endpoints.mergeEndpoint(data.endpoint_name); };
                 }
@@ -356,6 +374,10 @@
             var bytesOut = document.getElementById('bytes-out');
             if (bytesIn) bytesIn.textContent = '-';
             if (bytesOut) bytesOut.textContent = '-';
+            var wanBytesIn = document.getElementById('wan-bytes-in');
+            var wanBytesOut = document.getElementById('wan-bytes-out');
+            if (wanBytesIn) wanBytesIn.textContent = '-';
+            if (wanBytesOut) wanBytesOut.textContent = '-';
         },
 
         /**
@@ -499,6 +521,8 @@
             // Hide manual override indicator
             var manualOverride = document.getElementById('manual-override-indicator');
             if (manualOverride) manualOverride.style.display = 'none';
+            var captureExcluded = document.getElementById('capture-excluded-indicator');
+            if (captureExcluded) captureExcluded.style.display = 'none';
 
             // Hide vendor and model badges and add buttons
             var vendorBadge = document.getElementById('device-vendor-badge');
@@ -529,6 +553,10 @@
             var bytesOut = document.getElementById('bytes-out');
             if (bytesIn) bytesIn.textContent = '0 B';
             if (bytesOut) bytesOut.textContent = '0 B';
+            var wanBytesIn = document.getElementById('wan-bytes-in');
+            var wanBytesOut = document.getElementById('wan-bytes-out');
+            if (wanBytesIn) wanBytesIn.textContent = '0 B';
+            if (wanBytesOut) wanBytesOut.textContent = '0 B';
 
             // Hide endpoint actions container
             var actionsContainer = document.getElementById('endpoint-actions-container');
@@ -754,8 +782,119 @@
         /**
          * Delete an endpoint and all associated data
          */
+        /**
+         * Show the endpoints linked with this one as interfaces of the same device
+         */
+        renderLinkedEndpoints: function(data) {
+            var section = document.getElementById('linked-endpoints-section');
+            var container = document.getElementById('linked-endpoints-container');
+            if (!section || !container) return;
+
+            var linked = data.linked_endpoints || [];
+            if (linked.length === 0) {
+                section.style.display = 'none';
+                container.innerHTML = '';
+                return;
+            }
+
+            section.style.display = '';
+            var total = document.getElementById('device-bytes-total');
+            if (total) {
+                total.textContent = 'Whole device: ' + App.Formatting.formatBytes(data.device_bytes_in) +
+                    ' in / ' + App.Formatting.formatBytes(data.device_bytes_out) + ' out';
+            }
+
+            var escape = App.Endpoints.escapeHtml;
+            container.innerHTML = linked.map(function(ep) {
+                var details = ep.macs.concat(ep.ips).join(' • ');
+                return '<div class="hostname-item" style="display: flex; justify-content: space-between; align-items: center; gap: 0.5rem;">' +
+                       '<div>' +
+                           '<div>' + escape(ep.name) + '</div>' +
+                           (details ? '<div style="font-size: 0.7rem; color: var(--text-secondary);">' + escape(details) + '</div>' : '') +
+                           '<div style="font-size: 0.7rem; color: var(--text-secondary);">' +
+                               App.Formatting.formatBytes(ep.bytes_in) + ' in / ' + App.Formatting.formatBytes(ep.bytes_out) + ' out' +
+                           '</div>' +
+                       '</div>' +
+                       '<button class="admin-only" data-endpoint-id="' + ep.endpoint_id + '" ' +
+                               'style="background: none; border: 1px solid var(--border-color); color: var(--text-secondary); padding: 0.25rem 0.5rem; border-radius: 0.375rem; cursor: pointer; font-size: 0.7rem;">' +
+                           'Unlink' +
+                       '</button>' +
+                       '</div>';
+            }).join('');
+
+            container.querySelectorAll('button[data-endpoint-id]').forEach(function(btn, i) {
+                btn.onclick = function() {
+                    App.Endpoints.unlinkEndpoint(linked[i], data.endpoint_name);
+                };
+            });
+        },
+
+        /**
+         * Re-fetch the details panel for the selected endpoint
+         */
+        reloadDetails: function(endpointName) {
+            fetch('/api/endpoint/' + encodeURIComponent(endpointName) + '/details')
+                .then(function(response) { return response.json(); })
+                .then(function(data) { App.Endpoints.updateDetails(data); })
+                .catch(function(error) {
+                    console.error('Error fetching endpoint details:', error);
+                });
+        },
+
+        /**
+         * Link another endpoint with this one as an interface of the same device
+         */
+        linkEndpoint: function(endpointName) {
+            var other = prompt('Link "' + endpointName + '" with which endpoint?\n\nEnter its name, IP or MAC address.');
+            if (!other || !other.trim()) return;
+
+            fetch('/api/endpoint/link', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ endpoint: endpointName, other: other.trim() })
+            })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                if (result.success) {
+                    App.Endpoints.reloadDetails(endpointName);
+                } else {
+                    alert('Failed to link endpoints: ' + result.message);
+                }
+            })
+            .catch(function(error) {
+                alert('Error linking endpoints: ' + error);
+            });
+        },
+
+        /**
+         * Detach a linked endpoint from the device
+         */
+        unlinkEndpoint: function(linked, endpointName) {
+            if (!confirm('Unlink "' + linked.name + '" from this device?\n\nBoth endpoints and their history are kept.')) {
+                return;
+            }
+
+            fetch('/api/endpoint/unlink', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                // Linked interfaces usually share a name, so pick this one out by address
+                body: JSON.stringify({ endpoint: linked.macs[0] || linked.ips[0] || linked.name })
+            })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                if (result.success) {
+                    App.Endpoints.reloadDetails(endpointName);
+                } else {
+                    alert('Failed to unlink endpoint: ' + result.message);
+                }
+            })
+            .catch(function(error) {
+                alert('Error unlinking endpoint: ' + error);
+            });
+        },
+
         deleteEndpoint: function(endpointName) {
-            if (!confirm('Are you sure you want to delete "' + endpointName + '"?\n\nThis will permanently remove the endpoint and all associated communications.')) {
+            if (!confirm('Are you sure you want to delete "' + endpointName + '"?\n\nThe endpoint will be archived and can be restored from /api/endpoints/archived until the retention period expires.')) {
                 return;
             }
 
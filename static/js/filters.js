@@ -66,6 +66,8 @@
             document.getElementById('filterVirtualization').checked = true;
             document.getElementById('filterSoundbar').checked = true;
             document.getElementById('filterAppliance').checked = true;
+            document.getElementById('filterNas').checked = true;
+            document.getElementById('filterUps').checked = true;
             document.getElementById('filterOther').checked = true;
 
             // Clear known/unknown/active/inactive filter states
@@ -166,6 +168,8 @@
             document.getElementById('filterVirtualization').checked = false;
             document.getElementById('filterSoundbar').checked = false;
             document.getElementById('filterAppliance').checked = false;
+            document.getElementById('filterNas').checked = false;
+            document.getElementById('filterUps').checked = false;
             document.getElementById('filterOther').checked = false;
             App.Filters.apply();
         },
@@ -184,6 +188,8 @@
             document.getElementById('filterVirtualization').checked = false;
             document.getElementById('filterSoundbar').checked = false;
             document.getElementById('filterAppliance').checked = false;
+            document.getElementById('filterNas').checked = false;
+            document.getElementById('filterUps').checked = false;
             document.getElementById('filterOther').checked = false;
 
             // Check only the specified filter
@@ -221,6 +227,8 @@
             var showVirtualization = document.getElementById('filterVirtualization')?.checked ?? true;
             var showSoundbar = document.getElementById('filterSoundbar')?.checked ?? true;
             var showAppliance = document.getElementById('filterAppliance')?.checked ?? true;
+            var showNas = document.getElementById('filterNas')?.checked ?? true;
+            var showUps = document.getElementById('filterUps')?.checked ?? true;
             var showOther = document.getElementById('filterOther')?.checked ?? true;
 
             // Get search term
@@ -262,6 +270,8 @@
                 url.searchParams.set('filter_virtualization', showVirtualization ? '1' : '0');
                 url.searchParams.set('filter_soundbar', showSoundbar ? '1' : '0');
                 url.searchParams.set('filter_appliance', showAppliance ? '1' : '0');
+                url.searchParams.set('filter_nas', showNas ? '1' : '0');
+                url.searchParams.set('filter_ups', showUps ? '1' : '0');
                 url.searchParams.set('filter_other', showOther ? '1' : '0');
                 if (searchTerm) {
                     url.searchParams.set('search', searchTerm);
@@ -286,6 +296,8 @@
                 else if (rowType === 'virtualization' && showVirtualization) shouldShowByType = true;
                 else if (rowType === 'soundbar' && showSoundbar) shouldShowByType = true;
                 else if (rowType === 'appliance' && showAppliance) shouldShowByType = true;
+                else if (rowType === 'nas' && showNas) shouldShowByType = true;
+                else if (rowType === 'ups' && showUps) shouldShowByType = true;
                 else if (rowType === 'other' && showOther) shouldShowByType = true;
 
                 // Apply search filter if search term exists
@@ -240,6 +240,16 @@
                         if (result.mac) {
                             html += '<br><span style="color: rgba(255,255,255,0.7);">MAC: ' + App.Utils.escapeHtml(result.mac) + '</span>';
                         }
+                        if (result.smb_shares && result.smb_shares.length > 0) {
+                            html += '<br><span style="color: rgba(255,255,255,0.7);">SMB shares: </span>' +
+                                result.smb_shares.map(function(share) {
+                                    var label = App.Utils.escapeHtml(share.name);
+                                    if (share.guest_readable) {
+                                        return '<span style="color: #f59e0b;" title="Readable without credentials">' + label + ' (guest)</span>';
+                                    }
+                                    return '<span style="color: white;">' + label + '</span>';
+                                }).join(', ');
+                        }
                         resultEl.innerHTML = html;
                     } else {
                         resultEl.innerHTML = '<span style="color: #f59e0b;">No NetBIOS response</span><br>' +
@@ -254,6 +264,56 @@
             });
         },
 
+        /**
+         * Fetch the page title and favicon of the device's web interface
+         */
+        refreshWebInfo: function() {
+            var endpointName = App.NetworkActions.getCurrentEndpointName();
+            if (!endpointName) {
+                alert('Could not determine current endpoint name');
+                return;
+            }
+
+            var resultEl = document.getElementById('refresh-web-info-result');
+            if (resultEl) {
+                resultEl.style.display = 'block';
+                resultEl.innerHTML = '<span style="color: rgba(255,255,255,0.7);">Fetching web interface...</span>';
+            }
+
+            fetch('/api/endpoint/' + encodeURIComponent(endpointName) + '/web-info/refresh', {
+                method: 'POST'
+            })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                if (resultEl) {
+                    if (result.success) {
+                        var html = '<span style="color: #22c55e;">&#10003; ' +
+                            App.Utils.escapeHtml(result.title || 'Page has no title') + '</span>' +
+                            '<br><span style="color: rgba(255,255,255,0.7);">' + App.Utils.escapeHtml(result.url) + '</span>';
+                        if (!result.has_favicon) {
+                            html += '<br><span style="color: rgba(255,255,255,0.7);">No favicon found</span>';
+                        }
+                        resultEl.innerHTML = html;
+                    } else {
+                        resultEl.innerHTML = '<span style="color: #f59e0b;">' + App.Utils.escapeHtml(result.message) + '</span>';
+                    }
+                }
+
+                if (result.success && App.Refresh) {
+                    document.querySelectorAll('.endpoint-row').forEach(function(row) {
+                        if ((row.dataset.endpoint || '').toLowerCase() === endpointName.toLowerCase()) {
+                            App.Refresh.updateWebInfoCell(row, result.title, result.has_favicon, true);
+                        }
+                    });
+                }
+            })
+            .catch(function(error) {
+                if (resultEl) {
+                    resultEl.innerHTML = '<span style="color: #ef4444;">Error: ' + error.message + '</span>';
+                }
+            });
+        },
+
         /**
          * Apply a discovered hostname as the endpoint name
          */
@@ -104,7 +104,8 @@
 
             items.forEach(function(item) {
                 var div = document.createElement('div');
-                div.className = 'notification-item';
+                div.className = 'notification-item severity-' + (item.severity || 'info');
+                if (item.acknowledged_at) div.className += ' acknowledged';
                 div.dataset.id = item.id;
 
                 var icon = document.createElement('span');
@@ -142,6 +143,21 @@
                 var time = document.createElement('div');
                 time.className = 'notification-time';
                 time.textContent = App.Notifications.formatTime(item.created_at);
+                if (item.occurrences > 1) {
+                    var count = document.createElement('span');
+                    count.className = 'notification-count';
+                    count.textContent = '\u00d7' + item.occurrences;
+                    count.title = 'Repeated ' + item.occurrences + ' times' +
+                        (item.last_occurred_at
+                            ? ', last ' + App.Notifications.formatTime(item.last_occurred_at)
+                            : '');
+                    title.appendChild(document.createTextNode(' '));
+                    title.appendChild(count);
+                }
+                if (item.acknowledged_at) {
+                    time.textContent += ' \u00b7 Acknowledged' +
+                        (item.acknowledged_by ? ' by ' + item.acknowledged_by : '');
+                }
                 body.appendChild(time);
 
                 var dismiss = document.createElement('button');
@@ -154,6 +170,16 @@
 
                 div.appendChild(icon);
                 div.appendChild(body);
+                if (!item.acknowledged_at && item.severity && item.severity !== 'info') {
+                    var ack = document.createElement('button');
+                    ack.className = 'notification-ack';
+                    ack.title = 'Acknowledge';
+                    ack.textContent = 'Ack';
+                    ack.onclick = function() {
+                        App.Notifications.acknowledge(item.id);
+                    };
+                    div.appendChild(ack);
+                }
                 div.appendChild(dismiss);
                 fragment.appendChild(div);
             });
@@ -218,12 +244,36 @@
         iconFor: function(eventType) {
             var icons = {
                 'endpoint_discovered': '\uD83D\uDD0D',
+                'wireless_client_discovered': '\uD83D\uDCF6',
                 'endpoint_deleted': '\uD83D\uDDD1\uFE0F',
+                'endpoint_restored': '\u267B\uFE0F',
                 'endpoints_merged': '\uD83D\uDD17',
+                'endpoints_unmerged': '\u2702\uFE0F',
                 'endpoint_renamed': '\u270F\uFE0F',
                 'endpoint_reclassified': '\uD83C\uDFF7\uFE0F',
                 'scan_started': '\u25B6\uFE0F',
                 'scan_stopped': '\u23F9\uFE0F',
+                'scan_changes': '\uD83D\uDD00',
+                'port_opened': '\uD83D\uDEAA',
+                'port_drift': '\uD83D\uDCCF',
+                'guest_share': '\uD83D\uDCC2',
+                'upnp_port_mapping': '\uD83C\uDF10',
+                'upnp_external_ip_changed': '\uD83D\uDD04',
+                'upnp_wan_disconnected': '\uD83D\uDD0C',
+                'wan_exposure': '\uD83D\uDEA8',
+                'rogue_router': '\u2620\uFE0F',
+                'name_poisoning': '\uD83E\uDD78',
+                'deauth_flood': '\uD83D\uDCF5',
+                'auth_failures': '\uD83D\uDD10',
+                'dns_bypass': '\uD83D\uDEE1\uFE0F',
+                'blocklist_match': '\u26D4',
+                'host_key_changed': '\uD83D\uDD11',
+                'default_credentials': '\uD83D\uDD13',
+                'certificate_expiring': '\uD83D\uDCDC',
+                'ipv6_prefix': '\uD83E\uDDED',
+                'ip_changed': '\uD83D\uDD04',
+                'task_failed': '\u26A0\uFE0F',
+                'endpoint_disappeared': '\uD83D\uDC7B',
                 'model_identified': '\uD83D\uDCF1',
                 'model_changed': '\uD83D\uDCF1',
                 'vendor_identified': '\uD83C\uDFED',
@@ -277,6 +327,24 @@
             .catch(function() {});
         },
 
+        /**
+         * Acknowledge a single notification (keeps it visible, records who handled it)
+         */
+        acknowledge: function(id) {
+            fetch('/api/notifications/acknowledge', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ ids: [id] })
+            })
+            .then(function(response) { return response.json(); })
+            .then(function(data) {
+                if (data.success) {
+                    App.Notifications.refresh();
+                }
+            })
+            .catch(function() {});
+        },
+
         /**
          * Dismiss all notifications
          */
@@ -305,9 +373,9 @@
          * Update badge count from notification items
          */
         updateBadge: function(items) {
-            // Use total from server when available (more accurate than page count)
+            // The badge counts unread notifications, which the page doesn't tell apart
             if (!paging.search && paging.page === 1) {
-                App.Notifications.setBadge(paging.total);
+                App.Notifications.refreshBadge();
             }
         },
 
@@ -355,27 +423,33 @@
             if (badgeIntervalId) return;
 
             // Initial badge count fetch
-            fetch('/api/notifications?limit=1')
-                .then(function(r) { return r.json(); })
-                .then(function(data) {
-                    if (data.total !== undefined) {
-                        App.Notifications.setBadge(data.total);
-                    }
-                })
-                .catch(function() {});
+            App.Notifications.refreshBadge();
 
             badgeIntervalId = setInterval(function() {
                 if (App.state.activeTab !== 'notifications') {
-                    fetch('/api/notifications?limit=1')
-                        .then(function(r) { return r.json(); })
-                        .then(function(data) {
-                            if (data.total !== undefined) {
-                                App.Notifications.setBadge(data.total);
-                            }
-                        })
-                        .catch(function() {});
+                    App.Notifications.refreshBadge();
                 }
             }, 30000);
+        },
+
+        /**
+         * Set the badge from the unread summary, with a per-severity breakdown as its tooltip
+         */
+        refreshBadge: function() {
+            fetch('/api/notifications/summary')
+                .then(function(r) { return r.json(); })
+                .then(function(data) {
+                    if (data.unread === undefined) return;
+                    App.Notifications.setBadge(data.unread);
+                    var badge = document.getElementById('notification-badge');
+                    if (badge && data.by_severity) {
+                        badge.title = Object.keys(data.by_severity)
+                            .filter(function(severity) { return data.by_severity[severity] > 0; })
+                            .map(function(severity) { return data.by_severity[severity] + ' ' + severity; })
+                            .join(', ');
+                    }
+                })
+                .catch(function() {});
         }
     };
 
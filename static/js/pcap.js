@@ -27,7 +27,7 @@
             fetch('/api/capture/status')
                 .then(function(response) { return response.json(); })
                 .then(function(result) {
-                    App.Pcap.updateCaptureUI(result.paused);
+                    App.Pcap.updateCaptureUI(result.paused, result.scheduled_pause);
                 })
                 .catch(function(error) {
                     console.error('Failed to get capture status:', error);
@@ -45,8 +45,9 @@
 
             fetch('/api/capture/pause', { method: 'POST' })
                 .then(function(response) { return response.json(); })
-                .then(function(result) {
-                    App.Pcap.updateCaptureUI(result.paused);
+                .then(function() {
+                    // Re-read the status so a schedule holding capture is shown too
+                    App.Pcap.refreshCaptureStatus();
                     if (btn) btn.disabled = false;
                 })
                 .catch(function(error) {
@@ -56,9 +57,10 @@
         },
 
         /**
-         * Update the capture UI based on paused state
+         * Update the capture UI based on paused state. scheduledPause means the capture
+         * schedule is holding capture even though it isn't paused by hand.
          */
-        updateCaptureUI: function(isPaused) {
+        updateCaptureUI: function(isPaused, scheduledPause) {
             var indicator = document.getElementById('capture-status-indicator');
             var statusText = document.getElementById('capture-status-text');
             var btn = document.getElementById('capture-toggle-btn');
@@ -73,8 +75,12 @@
                     btn.style.color = '#22c55e';
                 }
             } else {
-                if (indicator) indicator.style.background = '#22c55e';
-                if (statusText) statusText.textContent = 'Active - receiving packets';
+                if (indicator) indicator.style.background = scheduledPause ? '#f59e0b' : '#22c55e';
+                if (statusText) {
+                    statusText.textContent = scheduledPause
+                        ? 'Outside capture schedule - live packets ignored'
+                        : 'Active - receiving packets';
+                }
                 if (btn) {
                     btn.textContent = 'Pause Capture';
                     btn.style.background = 'rgba(245, 158, 11, 0.2)';
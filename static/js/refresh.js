@@ -254,6 +254,8 @@
                         if (ep.device_type) {
                             row.dataset.endpointType = ep.device_type;
                         }
+
+                        App.Refresh.updateWebInfoCell(row, ep.web_title, ep.has_favicon, false);
                     });
 
                     // Re-apply filters after update
@@ -266,6 +268,36 @@
                 });
         },
 
+        /**
+         * Show the web page title and favicon in an endpoint row's name cell.
+         * reloadFavicon bypasses the browser cache after a manual refresh.
+         */
+        updateWebInfoCell: function(row, title, hasFavicon, reloadFavicon) {
+            var nameCell = row.querySelector('.endpoint-name-cell');
+            if (!nameCell) return;
+
+            var titleEl = nameCell.querySelector('.endpoint-web-title');
+            if (titleEl) {
+                titleEl.textContent = title || '';
+            }
+
+            var img = nameCell.querySelector('.endpoint-favicon');
+            if (!hasFavicon) {
+                if (img) img.remove();
+                return;
+            }
+            var src = '/api/endpoint/' + encodeURIComponent(row.dataset.endpoint) + '/favicon';
+            if (!img) {
+                img = document.createElement('img');
+                img.className = 'endpoint-favicon';
+                img.alt = '';
+                nameCell.insertBefore(img, nameCell.firstChild);
+            } else if (!reloadFavicon) {
+                return;
+            }
+            img.src = reloadFavicon ? src + '?t=' + Date.now() : src;
+        },
+
         /**
          * Reload page while preserving sort and selection state
          */
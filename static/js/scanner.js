@@ -12,13 +12,9 @@
          * Start network scan with selected scan types
          */
         start: function() {
-            var scanTypes = [];
-            if (document.getElementById('scan-arp').checked) scanTypes.push('arp');
-            if (document.getElementById('scan-icmp').checked) scanTypes.push('icmp');
-            if (document.getElementById('scan-port').checked) scanTypes.push('port');
-            if (document.getElementById('scan-ssdp').checked) scanTypes.push('ssdp');
-            if (document.getElementById('scan-netbios').checked) scanTypes.push('netbios');
-            if (document.getElementById('scan-snmp').checked) scanTypes.push('snmp');
+            var scanTypes = App.Scanner.scanTypeInputs()
+                .filter(function(input) { return input.checked; })
+                .map(function(input) { return input.dataset.scanType; });
 
             if (scanTypes.length === 0) {
                 alert('Please select at least one scan type');
@@ -78,9 +74,15 @@
                 })
                 .then(function(status) {
                     document.getElementById('scan-progress-fill').style.width = status.progress_percent + '%';
-                    document.getElementById('scan-progress-text').textContent = status.progress_percent + '%';
+                    var progressText = status.progress_percent + '%';
+                    if (status.running && status.estimated_remaining_secs != null) {
+                        var secs = status.estimated_remaining_secs;
+                        progressText += ' (~' + (secs >= 60 ? Math.ceil(secs / 60) + 'm' : secs + 's') + ' left)';
+                    }
+                    document.getElementById('scan-progress-text').textContent = progressText;
                     document.getElementById('scan-phase').textContent = status.current_phase || 'Scanning...';
                     document.getElementById('discovered-count').textContent = status.discovered_count;
+                    App.Scanner.renderPhases(status.phases || [], status.running);
 
                     if (status.last_scan_time) {
                         var date = new Date(status.last_scan_time * 1000);
@@ -107,6 +109,48 @@
                 });
         },
 
+        /**
+         * Render per scan type progress rows, with a cancel button for unfinished types
+         */
+        renderPhases: function(phases, running) {
+            var list = document.getElementById('scan-phase-list');
+            if (!list) return;
+            var formatSecs = function(secs) {
+                return secs >= 60 ? Math.floor(secs / 60) + 'm ' + (secs % 60) + 's' : secs + 's';
+            };
+
+            list.innerHTML = phases.map(function(phase) {
+                var detail = phase.state;
+                if (phase.targets_total > 0) {
+                    detail += ' · ' + phase.targets_scanned + '/' + phase.targets_total;
+                }
+                if (phase.elapsed_secs > 0) {
+                    detail += ' · ' + formatSecs(phase.elapsed_secs);
+                }
+                if (phase.estimated_remaining_secs != null) {
+                    detail += ' (~' + formatSecs(phase.estimated_remaining_secs) + ' left)';
+                }
+                var cancellable = running && (phase.state === 'pending' || phase.state === 'running');
+                return '<div style="display: flex; justify-content: space-between; align-items: center; gap: 0.5rem; padding: 0.125rem 0;">' +
+                    '<span style="font-weight: 500; text-transform: uppercase; min-width: 4rem;">' + phase.scan_type + '</span>' +
+                    '<span style="flex: 1;">' + detail + '</span>' +
+                    (cancellable ?
+                        '<button onclick="App.Scanner.cancelType(\'' + phase.scan_type + '\')" title="Cancel this scan type" ' +
+                        'style="background: none; border: none; color: #f87171; cursor: pointer; font-size: 0.75rem;">✕</button>' : '') +
+                    '</div>';
+            }).join('');
+        },
+
+        /**
+         * Cancel a single scan type; the rest of the scan continues
+         */
+        cancelType: function(scanType) {
+            fetch('/api/scan/cancel/' + encodeURIComponent(scanType), { method: 'POST' })
+                .catch(function(e) {
+                    console.error('Error cancelling ' + scanType + ' scan:', e);
+                });
+        },
+
         /**
          * Reset scan button to initial state
          */
@@ -129,36 +173,77 @@
         },
 
         /**
-         * Check scan capabilities on load
+         * Scan type checkboxes, in registration order
+         */
+        scanTypeInputs: function() {
+            return Array.prototype.slice.call(
+                document.querySelectorAll('#scan-type-options input[data-scan-type]')
+            );
+        },
+
+        /**
+         * Add a checkbox for a registered scanner
+         */
+        addScanTypeOption: function(container, scanner) {
+            var label = document.createElement('label');
+            label.style.cssText = 'display: flex; align-items: center; gap: 0.5rem; font-size: 0.875rem; cursor: pointer; padding: 0.5rem 0.75rem; background: var(--bg-tertiary); border-radius: 0.5rem; border: 1px solid var(--border-color); flex: 1; min-width: 120px;';
+
+            var input = document.createElement('input');
+            input.type = 'checkbox';
+            input.id = 'scan-type-' + scanner.scan_type;
+            input.dataset.scanType = scanner.scan_type;
+            input.checked = scanner.enabled_by_default;
+            input.style.cssText = 'cursor: pointer; width: 1rem; height: 1rem;';
+
+            var text = document.createElement('div');
+            var name = document.createElement('div');
+            name.style.fontWeight = '500';
+            name.textContent = scanner.label;
+            var description = document.createElement('div');
+            description.style.cssText = 'font-size: 0.7rem; color: var(--text-secondary);';
+            description.textContent = scanner.description;
+            text.appendChild(name);
+            text.appendChild(description);
+
+            label.appendChild(input);
+            label.appendChild(text);
+            container.appendChild(label);
+            return input;
+        },
+
+        /**
+         * List the registered scan types on load, disabling those the privileges rule out
          */
         checkCapabilities: function() {
-            fetch('/api/scan/capabilities')
+            fetch('/api/scan/scanners')
                 .then(function(response) {
                     return response.json();
                 })
-                .then(function(caps) {
-                    var needsPrivileges = false;
-
-                    // Disable checkboxes for unavailable scan types
-                    if (!caps.can_arp) {
-                        document.getElementById('scan-arp').disabled = true;
-                        document.getElementById('scan-arp').checked = false;
-                        document.getElementById('scan-arp').parentElement.style.opacity = '0.5';
-                        document.getElementById('scan-arp').parentElement.title = 'Requires root/admin privileges';
-                        needsPrivileges = true;
-                    }
-                    if (!caps.can_icmp) {
-                        document.getElementById('scan-icmp').disabled = true;
-                        document.getElementById('scan-icmp').checked = false;
-                        document.getElementById('scan-icmp').parentElement.style.opacity = '0.5';
-                        document.getElementById('scan-icmp').parentElement.title = 'Requires root/admin privileges';
-                        needsPrivileges = true;
-                    }
+                .then(function(scanners) {
+                    var container = document.getElementById('scan-type-options');
+                    if (!container) return;
+                    var unavailable = [];
+
+                    scanners.forEach(function(scanner) {
+                        var input = document.getElementById('scan-type-' + scanner.scan_type) ||
+                            App.Scanner.addScanTypeOption(container, scanner);
+                        if (!scanner.available) {
+                            input.disabled = true;
+                            input.checked = false;
+                            input.parentElement.style.opacity = '0.5';
+                            input.parentElement.title = 'Requires root/admin privileges';
+                            unavailable.push(scanner.label);
+                        }
+                    });
 
                     // Show privilege warning if needed
                     var warning = document.getElementById('privilege-warning');
                     if (warning) {
-                        warning.style.display = needsPrivileges ? 'block' : 'none';
+                        warning.style.display = unavailable.length > 0 ? 'block' : 'none';
+                    }
+                    var types = document.getElementById('privilege-warning-types');
+                    if (types) {
+                        types.textContent = unavailable.join(', ');
                     }
                 })
                 .catch(function(e) {
@@ -212,21 +297,10 @@
          */
         runAutoScan: function() {
             // Get all enabled scan types
-            var scanTypes = [];
-            var arpCheck = document.getElementById('scan-arp');
-            var icmpCheck = document.getElementById('scan-icmp');
-            var portCheck = document.getElementById('scan-port');
-            var ssdpCheck = document.getElementById('scan-ssdp');
-            var netbiosCheck = document.getElementById('scan-netbios');
-            var snmpCheck = document.getElementById('scan-snmp');
-
-            // Use checked state, or default to enabled if not disabled
-            if (arpCheck && !arpCheck.disabled) scanTypes.push('arp');
-            if (icmpCheck && !icmpCheck.disabled) scanTypes.push('icmp');
-            if (portCheck && !portCheck.disabled) scanTypes.push('port');
-            if (ssdpCheck && !ssdpCheck.disabled) scanTypes.push('ssdp');
-            if (netbiosCheck && !netbiosCheck.disabled) scanTypes.push('netbios');
-            if (snmpCheck && !snmpCheck.disabled) scanTypes.push('snmp');
+            // Every scan type that isn't ruled out by privileges
+            var scanTypes = App.Scanner.scanTypeInputs()
+                .filter(function(input) { return !input.disabled; })
+                .map(function(input) { return input.dataset.scanType; });
 
             if (scanTypes.length === 0) {
                 console.log('Auto-scan: no scan types available');
@@ -34,6 +34,31 @@
                     autoScanInterval.value = settings.auto_scan_interval_minutes;
                 }
 
+                var snmpCommunities = document.getElementById('setting-snmp-communities');
+                if (snmpCommunities && settings.snmp_communities !== undefined) {
+                    snmpCommunities.value = settings.snmp_communities;
+                }
+
+                var wanIpSource = document.getElementById('setting-exposure-wan-ip-source');
+                if (wanIpSource && settings.exposure_wan_ip_source !== undefined) {
+                    wanIpSource.value = settings.exposure_wan_ip_source;
+                }
+
+                var exposureScannerUrl = document.getElementById('setting-exposure-scanner-url');
+                if (exposureScannerUrl && settings.exposure_scanner_url !== undefined) {
+                    exposureScannerUrl.value = settings.exposure_scanner_url;
+                }
+
+                var captureMode = document.getElementById('setting-capture-mode');
+                if (captureMode && settings.capture_mode) {
+                    captureMode.value = settings.capture_mode;
+                }
+
+                var flowInterval = document.getElementById('setting-flow-interval');
+                if (flowInterval && settings.flow_aggregation_interval_seconds) {
+                    flowInterval.value = settings.flow_aggregation_interval_seconds;
+                }
+
                 showStatus('Settings loaded', 'success');
             })
             .catch(function(error) {
@@ -105,6 +130,76 @@
             );
         }
 
+        var snmpCommunities = document.getElementById('setting-snmp-communities');
+        if (snmpCommunities) {
+            promises.push(
+                fetch('/api/settings', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({
+                        key: 'snmp_communities',
+                        value: snmpCommunities.value
+                    })
+                })
+            );
+        }
+
+        var wanIpSource = document.getElementById('setting-exposure-wan-ip-source');
+        if (wanIpSource) {
+            promises.push(
+                fetch('/api/settings', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({
+                        key: 'exposure_wan_ip_source',
+                        value: wanIpSource.value
+                    })
+                })
+            );
+        }
+
+        var exposureScannerUrl = document.getElementById('setting-exposure-scanner-url');
+        if (exposureScannerUrl) {
+            promises.push(
+                fetch('/api/settings', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({
+                        key: 'exposure_scanner_url',
+                        value: exposureScannerUrl.value
+                    })
+                })
+            );
+        }
+
+        var captureMode = document.getElementById('setting-capture-mode');
+        if (captureMode) {
+            promises.push(
+                fetch('/api/settings', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({
+                        key: 'capture_mode',
+                        value: captureMode.value
+                    })
+                })
+            );
+        }
+
+        var flowInterval = document.getElementById('setting-flow-interval');
+        if (flowInterval) {
+            promises.push(
+                fetch('/api/settings', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({
+                        key: 'flow_aggregation_interval_seconds',
+                        value: flowInterval.value
+                    })
+                })
+            );
+        }
+
         Promise.all(promises)
             .then(function(responses) {
                 return Promise.all(responses.map(function(r) { return r.json(); }));
@@ -118,7 +213,10 @@
                         window.startAutoScan();
                     }
                 } else {
-                    showStatus('Some settings failed to save', 'error');
+                    var errors = results
+                        .filter(function(r) { return !r.success; })
+                        .map(function(r) { return r.message; });
+                    showStatus('Some settings failed to save: ' + errors.join('; '), 'error');
                 }
             })
             .catch(function(error) {
@@ -153,6 +251,321 @@
         }, 5000);
     }
 
+    /**
+     * Run the WAN exposure check and list the services that answered
+     */
+    function runExposureCheck() {
+        var button = document.getElementById('exposure-check-btn');
+        var resultsEl = document.getElementById('exposure-results');
+        if (!resultsEl) return;
+
+        if (button) button.disabled = true;
+        resultsEl.textContent = 'Checking WAN address...';
+
+        fetch('/api/exposure/scan', { method: 'POST' })
+            .then(function(response) { return response.json(); })
+            .then(function(report) {
+                resultsEl.innerHTML = '';
+                if (report.error) {
+                    resultsEl.textContent = report.error;
+                    return;
+                }
+
+                var summary = document.createElement('div');
+                summary.textContent = 'WAN address ' + report.wan_ip + ' (from ' + report.wan_ip_source + ', checked ' +
+                    (report.external_scanner ? 'by the external scanner' : 'from inside the network') + ')';
+                resultsEl.appendChild(summary);
+
+                if (report.behind_nat) {
+                    var nat = document.createElement('div');
+                    nat.style.color = 'var(--text-secondary)';
+                    nat.textContent = 'This is a private address: another router or carrier-grade NAT sits in front of the gateway.';
+                    resultsEl.appendChild(nat);
+                }
+
+                if (report.services.length === 0) {
+                    var none = document.createElement('div');
+                    none.style.color = '#22c55e';
+                    none.textContent = 'No services appear reachable from the internet.';
+                    resultsEl.appendChild(none);
+                    return;
+                }
+
+                var list = document.createElement('ul');
+                list.style.margin = '0.5rem 0 0 1.25rem';
+                report.services.forEach(function(service) {
+                    var item = document.createElement('li');
+                    var label = 'Port ' + service.port + (service.service_name ? ' (' + service.service_name + ')' : '');
+                    if (service.internal_client) {
+                        label += ' \u2192 ' + (service.internal_name || service.internal_client) + ':' + service.internal_port;
+                    } else {
+                        label += ' \u2192 gateway or manual forward';
+                    }
+                    item.textContent = label;
+                    item.style.color = '#ef4444';
+                    list.appendChild(item);
+                });
+                resultsEl.appendChild(list);
+            })
+            .catch(function(error) {
+                console.error('Error running exposure check:', error);
+                resultsEl.textContent = 'Exposure check failed';
+            })
+            .finally(function() {
+                if (button) button.disabled = false;
+            });
+    }
+
+    /**
+     * List each device type with inputs for its icon and color
+     */
+    function loadTypeAppearance() {
+        var listEl = document.getElementById('type-appearance-list');
+        if (!listEl) return;
+
+        fetch('/api/appearance')
+            .then(function(response) { return response.json(); })
+            .then(function(data) {
+                listEl.innerHTML = '';
+                Object.keys(data.types || {}).sort().forEach(function(deviceType) {
+                    var look = data.types[deviceType];
+                    var row = document.createElement('div');
+                    row.style.cssText = 'display: flex; align-items: center; gap: 0.5rem;';
+
+                    var label = document.createElement('span');
+                    label.textContent = deviceType;
+                    label.style.cssText = 'width: 7rem; color: var(--text-secondary); font-size: 0.875rem;';
+
+                    var icon = document.createElement('input');
+                    icon.type = 'text';
+                    icon.maxLength = 16;
+                    icon.value = look.icon;
+                    icon.style.cssText = 'width: 4rem; padding: 0.375rem; border: 1px solid var(--border-color); border-radius: 0.25rem; background: var(--bg-secondary); color: var(--text-primary);';
+
+                    var color = document.createElement('input');
+                    color.type = 'color';
+                    color.value = look.color;
+                    color.style.cssText = 'width: 2.5rem; height: 2rem; padding: 0; border: 1px solid var(--border-color); border-radius: 0.25rem; background: var(--bg-secondary); cursor: pointer;';
+
+                    var save = document.createElement('button');
+                    save.textContent = 'Save';
+                    save.onclick = function() { saveTypeAppearance(deviceType, icon.value, color.value); };
+
+                    var reset = document.createElement('button');
+                    reset.textContent = 'Reset';
+                    reset.disabled = !look.customized;
+                    reset.onclick = function() { saveTypeAppearance(deviceType, '', ''); };
+
+                    [save, reset].forEach(function(button) {
+                        button.style.cssText = 'padding: 0.375rem 0.75rem; background: var(--card-bg); color: var(--text-primary); border: 1px solid var(--border-color); border-radius: 0.25rem; cursor: pointer; font-size: 0.75rem;';
+                    });
+
+                    row.appendChild(label);
+                    row.appendChild(icon);
+                    row.appendChild(color);
+                    row.appendChild(save);
+                    row.appendChild(reset);
+                    listEl.appendChild(row);
+                });
+            })
+            .catch(function(error) {
+                console.error('Error loading device appearance:', error);
+            });
+    }
+
+    /**
+     * Save a device type's icon and color; empty values restore the defaults
+     */
+    function saveTypeAppearance(deviceType, icon, color) {
+        fetch('/api/appearance/type', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ device_type: deviceType, icon: icon, color: color })
+        })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                showStatus(result.message, result.success ? 'success' : 'error');
+                if (result.success) {
+                    loadTypeAppearance();
+                }
+            })
+            .catch(function(error) {
+                console.error('Error saving device appearance:', error);
+                showStatus('Failed to save icon and color', 'error');
+            });
+    }
+
+    /**
+     * Show who the caller is and load their preferences, plus the user list for admins
+     */
+    function loadCurrentUser() {
+        fetch('/api/me')
+            .then(function(response) { return response.json(); })
+            .then(function(me) {
+                var info = document.getElementById('current-user-info');
+                if (info) {
+                    var who = me.identity ? 'Signed in as ' + me.identity : 'Not signed in';
+                    info.textContent = me.users_configured
+                        ? who + ' (' + me.role + ').'
+                        : who + '. No users have been added, so everyone is an admin.';
+                }
+
+                var timeWindow = document.getElementById('pref-default-scan-interval');
+                if (timeWindow) {
+                    timeWindow.value = (me.preferences || {}).default_scan_interval || '';
+                }
+
+                if (me.role === 'admin') {
+                    loadUsers();
+                }
+            })
+            .catch(function(error) {
+                console.error('Error loading user:', error);
+            });
+    }
+
+    function saveDefaultTimeWindow() {
+        var timeWindow = document.getElementById('pref-default-scan-interval');
+        if (!timeWindow) return;
+
+        fetch('/api/me/preferences', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ preferences: { default_scan_interval: timeWindow.value } })
+        })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                showStatus(result.message, result.success ? 'success' : 'error');
+            })
+            .catch(function(error) {
+                console.error('Error saving preferences:', error);
+                showStatus('Failed to save preferences', 'error');
+            });
+    }
+
+    function loadUsers() {
+        var listEl = document.getElementById('users-list');
+        if (!listEl) return;
+
+        fetch('/api/users')
+            .then(function(response) { return response.json(); })
+            .then(function(data) {
+                listEl.innerHTML = '';
+                (data.users || []).forEach(function(user) {
+                    var row = document.createElement('div');
+                    row.style.cssText = 'display: flex; align-items: center; gap: 0.5rem;';
+
+                    var name = document.createElement('span');
+                    name.textContent = user.name;
+                    name.style.cssText = 'min-width: 10rem; color: var(--text-primary); font-size: 0.875rem;';
+
+                    var role = document.createElement('select');
+                    ['viewer', 'admin'].forEach(function(value) {
+                        var option = document.createElement('option');
+                        option.value = value;
+                        option.textContent = value === 'admin' ? 'Admin' : 'Viewer';
+                        role.appendChild(option);
+                    });
+                    role.value = user.role;
+                    role.style.cssText = 'padding: 0.25rem; border: 1px solid var(--border-color); border-radius: 0.25rem; background: var(--bg-secondary); color: var(--text-primary);';
+                    role.onchange = function() { saveUser(user.name, role.value); };
+
+                    var remove = document.createElement('button');
+                    remove.textContent = 'Remove';
+                    remove.style.cssText = 'padding: 0.25rem 0.75rem; background: var(--card-bg); color: var(--text-primary); border: 1px solid var(--border-color); border-radius: 0.25rem; cursor: pointer; font-size: 0.75rem;';
+                    remove.onclick = function() { removeUser(user.name); };
+
+                    var token = document.createElement('button');
+                    token.textContent = user.has_token ? 'Replace Token' : 'Issue Token';
+                    token.style.cssText = remove.style.cssText;
+                    token.onclick = function() { issueToken(user.name, user.has_token); };
+
+                    row.appendChild(name);
+                    row.appendChild(role);
+                    row.appendChild(token);
+                    row.appendChild(remove);
+                    listEl.appendChild(row);
+                });
+            })
+            .catch(function(error) {
+                console.error('Error loading users:', error);
+            });
+    }
+
+    /**
+     * Add a user or change their role
+     */
+    function saveUser(name, role) {
+        fetch('/api/users/save', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ name: name, role: role })
+        })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                showStatus(result.message, result.success ? 'success' : 'error');
+                // Reload either way so a refused change shows the role still in effect
+                loadCurrentUser();
+            })
+            .catch(function(error) {
+                console.error('Error saving user:', error);
+                showStatus('Failed to save user', 'error');
+            });
+    }
+
+    function addUser() {
+        var nameInput = document.getElementById('new-user-name');
+        var roleSelect = document.getElementById('new-user-role');
+        if (!nameInput || !roleSelect || !nameInput.value.trim()) return;
+
+        saveUser(nameInput.value.trim(), roleSelect.value);
+        nameInput.value = '';
+    }
+
+    function removeUser(name) {
+        if (!confirm('Remove user "' + name + '"?')) return;
+
+        fetch('/api/users/delete', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ name: name })
+        })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                showStatus(result.message, result.success ? 'success' : 'error');
+                loadCurrentUser();
+            })
+            .catch(function(error) {
+                console.error('Error removing user:', error);
+                showStatus('Failed to remove user', 'error');
+            });
+    }
+
+    /**
+     * Issue a user an API token. It is shown once; only its hash is kept.
+     */
+    function issueToken(name, replacing) {
+        if (replacing && !confirm('Replace the API token for "' + name + '"? The old one stops working.')) return;
+
+        fetch('/api/users/token', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ name: name })
+        })
+            .then(function(response) { return response.json(); })
+            .then(function(result) {
+                showStatus(result.message, result.success ? 'success' : 'error');
+                if (result.token) {
+                    prompt('API token for ' + name + ' (send as "Authorization: Bearer <token>"):', result.token);
+                }
+                loadUsers();
+            })
+            .catch(function(error) {
+                console.error('Error issuing token:', error);
+                showStatus('Failed to issue token', 'error');
+            });
+    }
+
     // Load settings when switching to the settings tab
     var originalSwitchTab = window.switchTab;
     window.switchTab = function(tabName) {
@@ -161,11 +574,16 @@
         }
         if (tabName === 'settings') {
             loadSettings();
+            loadTypeAppearance();
+            loadCurrentUser();
         }
     };
 
     // Expose functions globally
     window.loadSettings = loadSettings;
     window.saveSettings = saveSettings;
+    window.runExposureCheck = runExposureCheck;
+    window.saveDefaultTimeWindow = saveDefaultTimeWindow;
+    window.addUser = addUser;
 
 })(window);
@@ -1,6 +1,7 @@
 /**
  * Theme Module - Light/Dark mode switching
- * Respects browser preference and persists user choice in localStorage
+ * Respects browser preference and persists user choice in localStorage, and with
+ * the user's server-side preferences so it follows them between browsers
  */
 (function(App) {
     'use strict';
@@ -32,6 +33,8 @@
                 }
             });
 
+            this.loadUserPreference();
+
             // Setup buttons when DOM is ready
             if (document.readyState === 'loading') {
                 document.addEventListener('DOMContentLoaded', function() {
@@ -78,13 +81,40 @@
         },
 
         /**
-         * Set theme (user action) - saves to localStorage
+         * Apply the theme saved in the user's preferences, if they have one
+         */
+        loadUserPreference: function() {
+            fetch('/api/me')
+                .then(function(response) { return response.json(); })
+                .then(function(me) {
+                    var theme = (me.preferences || {}).theme;
+                    if (theme) {
+                        App.Theme.applyTheme(theme);
+                        localStorage.setItem(STORAGE_KEY, theme);
+                        App.Theme.updateButtons();
+                    }
+                })
+                .catch(function(error) {
+                    console.error('Error loading theme preference:', error);
+                });
+        },
+
+        /**
+         * Set theme (user action) - saves to localStorage and the user's preferences
          * @param {string} theme - 'light' or 'dark'
          */
         set: function(theme) {
             this.applyTheme(theme);
             localStorage.setItem(STORAGE_KEY, theme);
             this.updateButtons();
+
+            fetch('/api/me/preferences', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ preferences: { theme: theme } })
+            }).catch(function(error) {
+                console.error('Error saving theme preference:', error);
+            });
         },
 
         /**
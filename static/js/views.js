@@ -0,0 +1,294 @@
+/**
+ * Views Module - Saved endpoint table views
+ * A view is the page's query string (type filters, search, sort, hidden columns and
+ * time window), stored per user on the server. The default view is where "/" lands.
+ */
+(function(App) {
+    'use strict';
+
+    // Columns that can be hidden, by their header's data-sort key
+    var HIDEABLE_COLUMNS = ['type', 'vendor', 'model', 'bandwidth', 'last_seen'];
+
+    var views = [];
+
+    function selectedView() {
+        var select = document.getElementById('savedViewSelect');
+        var id = select ? parseInt(select.value, 10) : NaN;
+        return views.find(function(view) { return view.id === id; }) || null;
+    }
+
+    /**
+     * The current URL with the table's sort and page size folded in; the server
+     * keeps only the parameters that belong to a view
+     */
+    function currentQuery() {
+        var url = new URL(window.location.href);
+
+        var sortedHeader = document.querySelector('.endpoints-table th.sorted-asc, .endpoints-table th.sorted-desc');
+        if (sortedHeader) {
+            url.searchParams.set('sort', sortedHeader.dataset.sort);
+            url.searchParams.set('sort_dir', sortedHeader.classList.contains('sorted-asc') ? 'asc' : 'desc');
+        }
+
+        if (App.Pagination) {
+            var pageState = App.Pagination.getState('endpoints');
+            if (pageState && pageState.pageSize !== 25) {
+                url.searchParams.set('page_size', pageState.pageSize);
+            }
+        }
+
+        return url.search;
+    }
+
+    function hiddenColumns() {
+        var param = new URLSearchParams(window.location.search).get('hidden_columns') || '';
+        return param.split(',').filter(function(col) {
+            return HIDEABLE_COLUMNS.indexOf(col) !== -1;
+        });
+    }
+
+    function applyHiddenColumns() {
+        var table = document.getElementById('endpoints-table');
+        var hidden = hiddenColumns();
+        HIDEABLE_COLUMNS.forEach(function(col) {
+            var isHidden = hidden.indexOf(col) !== -1;
+            if (table) {
+                table.classList.toggle('hide-col-' + col, isHidden);
+            }
+            var checkbox = document.getElementById('column-toggle-' + col);
+            if (checkbox) {
+                checkbox.checked = !isHidden;
+            }
+        });
+    }
+
+    function postJson(path, body) {
+        return fetch(path, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(body)
+        }).then(function(response) { return response.json(); });
+    }
+
+    function render(selectedId) {
+        var select = document.getElementById('savedViewSelect');
+        if (!select) return;
+
+        select.innerHTML = '';
+        var placeholder = document.createElement('option');
+        placeholder.value = '';
+        placeholder.textContent = views.length ? 'Saved views…' : 'No saved views';
+        select.appendChild(placeholder);
+
+        var all = document.createElement('option');
+        all.value = 'all';
+        all.textContent = 'All endpoints';
+        select.appendChild(all);
+
+        views.forEach(function(view) {
+            var option = document.createElement('option');
+            option.value = view.id;
+            option.textContent = (view.is_default ? '★ ' : '') + view.name;
+            select.appendChild(option);
+        });
+
+        select.value = selectedId ? String(selectedId) : '';
+        updateButtons();
+    }
+
+    function updateButtons() {
+        var view = selectedView();
+        var defaultBtn = document.getElementById('defaultViewBtn');
+        if (defaultBtn) {
+            defaultBtn.disabled = !view;
+            defaultBtn.textContent = view && view.is_default ? '★' : '☆';
+            defaultBtn.title = view && view.is_default
+                ? 'Stop landing on this view'
+                : 'Land on this view when opening the page';
+        }
+        ['renameViewBtn', 'deleteViewBtn'].forEach(function(id) {
+            var btn = document.getElementById(id);
+            if (btn) btn.disabled = !view;
+        });
+    }
+
+    App.Views = {
+        init: function() {
+            applyHiddenColumns();
+
+            var timeWindow = document.getElementById('timeWindowSelect');
+            if (timeWindow) {
+                // The window in effect may come from the user's default rather than the URL
+                timeWindow.value = timeWindow.dataset.current;
+            }
+
+            App.Views.load();
+        },
+
+        /**
+         * Fetch the user's views. The view last applied in this tab stays selected,
+         * as does one the page landed on by default.
+         */
+        load: function(selectedId) {
+            fetch('/api/views')
+                .then(function(response) { return response.json(); })
+                .then(function(data) {
+                    views = data.views || [];
+                    if (!selectedId) {
+                        var applied = parseInt(sessionStorage.getItem('savedViewId'), 10);
+                        var search = window.location.search.replace(/^\?/, '');
+                        var match = views.find(function(view) { return view.id === applied; })
+                            || views.find(function(view) { return view.is_default && view.query === search; });
+                        selectedId = match ? match.id : null;
+                    }
+                    render(selectedId);
+                })
+                .catch(function(error) {
+                    console.error('Error loading saved views:', error);
+                });
+        },
+
+        apply: function(value) {
+            if (value === 'all') {
+                sessionStorage.removeItem('savedViewId');
+                // Any query keeps "/" from redirecting to the default view
+                window.location.href = '/?view=all';
+                return;
+            }
+            var view = selectedView();
+            if (!view) {
+                updateButtons();
+                return;
+            }
+            sessionStorage.setItem('savedViewId', view.id);
+            window.location.href = view.query ? '/?' + view.query : '/?view=all';
+        },
+
+        /**
+         * Save the current filters, sort, columns and time window under a name,
+         * replacing the view of that name if there is one
+         */
+        saveCurrent: function() {
+            var view = selectedView();
+            var name = prompt('Save current view as:', view ? view.name : '');
+            if (name === null) return;
+            if (!name.trim()) {
+                alert('Please enter a name');
+                return;
+            }
+
+            postJson('/api/views/save', { name: name, query: currentQuery() })
+                .then(function(result) {
+                    if (!result.success) {
+                        alert('Failed to save view: ' + result.message);
+                        return;
+                    }
+                    sessionStorage.setItem('savedViewId', result.id);
+                    App.Views.load(result.id);
+                })
+                .catch(function(error) {
+                    console.error('Error saving view:', error);
+                    alert('Failed to save view. Please try again.');
+                });
+        },
+
+        renameSelected: function() {
+            var view = selectedView();
+            if (!view) return;
+            var name = prompt('Rename view:', view.name);
+            if (name === null || !name.trim() || name.trim() === view.name) return;
+
+            postJson('/api/views/save', { id: view.id, name: name, query: view.query })
+                .then(function(result) {
+                    if (!result.success) {
+                        alert('Failed to rename view: ' + result.message);
+                        return;
+                    }
+                    App.Views.load(view.id);
+                })
+                .catch(function(error) {
+                    console.error('Error renaming view:', error);
+                    alert('Failed to rename view. Please try again.');
+                });
+        },
+
+        toggleDefault: function() {
+            var view = selectedView();
+            if (!view) return;
+
+            postJson('/api/views/default', { id: view.is_default ? null : view.id })
+                .then(function(result) {
+                    if (!result.success) {
+                        alert('Failed to set default view: ' + result.message);
+                        return;
+                    }
+                    App.Views.load(view.id);
+                })
+                .catch(function(error) {
+                    console.error('Error setting default view:', error);
+                    alert('Failed to set default view. Please try again.');
+                });
+        },
+
+        deleteSelected: function() {
+            var view = selectedView();
+            if (!view || !confirm('Delete view "' + view.name + '"?')) return;
+
+            postJson('/api/views/delete', { id: view.id })
+                .then(function(result) {
+                    if (!result.success) {
+                        alert('Failed to delete view: ' + result.message);
+                        return;
+                    }
+                    sessionStorage.removeItem('savedViewId');
+                    App.Views.load();
+                })
+                .catch(function(error) {
+                    console.error('Error deleting view:', error);
+                    alert('Failed to delete view. Please try again.');
+                });
+        },
+
+        /**
+         * Only list endpoints seen in the last `minutes`
+         */
+        setTimeWindow: function(minutes) {
+            var url = new URL(window.location.href);
+            url.searchParams.set('scan_interval', minutes);
+            url.searchParams.delete('page');
+            window.location.href = url.toString();
+        },
+
+        toggleColumnMenu: function() {
+            var menu = document.getElementById('column-menu');
+            if (menu) {
+                menu.classList.toggle('show');
+            }
+        },
+
+        setColumnVisible: function(col, visible) {
+            var hidden = hiddenColumns().filter(function(c) { return c !== col; });
+            if (!visible) {
+                hidden.push(col);
+            }
+
+            var url = new URL(window.location.href);
+            if (hidden.length) {
+                url.searchParams.set('hidden_columns', hidden.join(','));
+            } else {
+                url.searchParams.delete('hidden_columns');
+            }
+            window.history.replaceState({}, '', url);
+            applyHiddenColumns();
+        }
+    };
+
+    // Close the column menu when clicking elsewhere
+    document.addEventListener('click', function(e) {
+        var menu = document.getElementById('column-menu');
+        if (menu && menu.classList.contains('show') && !e.target.closest('.column-menu-wrapper')) {
+            menu.classList.remove('show');
+        }
+    });
+
+})(window.App);
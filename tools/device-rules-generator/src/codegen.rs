@@ -74,6 +74,7 @@ pub fn generate(rules: &DeviceRules, output: &Path) -> Result<(), Box<dyn std::e
 
     // --- Standalone lists ---
     write_str_array(&mut out, "MAC_DESKTOP_SERVICES", &rules.standalone.mac_desktop_services)?;
+    write_str_array(&mut out, "SMART_HOME_SERVICES", &rules.standalone.smart_home_services)?;
     write_str_array(
         &mut out,
         "SOUNDBAR_MODEL_PREFIXES",
@@ -84,6 +85,11 @@ pub fn generate(rules: &DeviceRules, output: &Path) -> Result<(), Box<dyn std::e
         "LG_APPLIANCE_PREFIXES",
         &rules.standalone.lg_appliance_prefixes,
     )?;
+    write_str_array(
+        &mut out,
+        "COMPONENT_VENDORS",
+        &rules.standalone.component_vendors,
+    )?;
 
     // --- TV series ---
     for series in &rules.tv_series {
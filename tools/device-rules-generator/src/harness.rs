@@ -0,0 +1,255 @@
+//! Dry-run classification of sample devices against the rules in device_rules.toml.
+//! `TomlRules` answers the crate's `RuleSet` queries from the TOML alone, and each fixture
+//! goes through `DeviceTypeTier::classify_all` in the crate's own rule_matcher.rs
+//! (included by path), so the tier order and special cases can't drift and a pattern
+//! removed or narrowed in the TOML stops matching before codegen runs. SSDP models
+//! aren't simulated.
+
+use crate::rule_matcher::{DeviceTypeTier, RuleSet};
+use crate::schema::DeviceRules;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Deserialize)]
+pub struct Fixtures {
+    pub cases: Vec<Fixture>,
+}
+
+#[derive(Deserialize)]
+pub struct Fixture {
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub mac: Option<String>,
+    /// mDNS services the device advertises
+    #[serde(default)]
+    pub services: Vec<String>,
+    /// Open ports found by a scan
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// Classification the crate reports ("printer", "virtualization", ...); omit for
+    /// devices that should stay unclassified
+    #[serde(default)]
+    pub expected_type: Option<String>,
+}
+
+pub struct Mismatch<'a> {
+    pub fixture: &'a Fixture,
+    pub actual: Option<&'static str>,
+}
+
+pub struct TomlRules<'a> {
+    rules: &'a DeviceRules,
+    /// OUI prefix ("aa:bb:cc") -> vendor
+    mac_vendors: HashMap<String, String>,
+}
+
+impl<'a> TomlRules<'a> {
+    pub fn new(rules: &'a DeviceRules, mac_vendors: HashMap<String, String>) -> Self {
+        Self { rules, mac_vendors }
+    }
+
+    /// Run every fixture, returning the ones classified differently than expected.
+    pub fn check<'f>(&self, fixtures: &'f Fixtures) -> Vec<Mismatch<'f>> {
+        fixtures
+            .cases
+            .iter()
+            .filter_map(|fixture| {
+                let actual = self.classify(fixture);
+                (actual != fixture.expected_type.as_deref()).then_some(Mismatch { fixture, actual })
+            })
+            .collect()
+    }
+
+    pub fn classify(&self, fixture: &Fixture) -> Option<&'static str> {
+        let lower = fixture.hostname.as_ref().map(|h| h.to_lowercase());
+        let macs: Vec<String> = fixture.mac.iter().cloned().collect();
+        DeviceTypeTier::classify_all(
+            self,
+            lower.as_deref(),
+            &fixture.services,
+            &fixture.ports,
+            &macs,
+            None,
+        )
+    }
+}
+
+fn list<'r>(lists: &'r BTreeMap<String, Vec<String>>, class: &str) -> &'r [String] {
+    lists.get(class).map_or(&[], Vec::as_slice)
+}
+
+impl RuleSet for TomlRules<'_> {
+    fn hostname_pattern(&self, class: &str, hostname: &str) -> bool {
+        list(&self.rules.patterns, class)
+            .iter()
+            .any(|p| hostname.contains(p.as_str()))
+    }
+
+    fn hostname_prefix(&self, class: &str, hostname: &str) -> bool {
+        list(&self.rules.prefixes, class)
+            .iter()
+            .any(|p| hostname.starts_with(p.as_str()))
+    }
+
+    fn hostname_conditional(&self, class: &str, hostname: &str) -> bool {
+        self.rules.conditionals.iter().any(|c| {
+            c.classification == class
+                && hostname.contains(c.pattern.as_str())
+                && !hostname.contains(c.exclude.as_str())
+        })
+    }
+
+    fn service_in_class(&self, class: &str, service: &str) -> bool {
+        list(&self.rules.services, class)
+            .iter()
+            .any(|s| s == service)
+    }
+
+    fn vendor_in_class(&self, class: &str, vendor: &str) -> bool {
+        list(&self.rules.vendor_classes, class)
+            .iter()
+            .any(|v| v == vendor)
+    }
+
+    /// `get_mac_vendor`
+    fn mac_vendor(&self, mac: &str) -> Option<&str> {
+        let mac_lower = mac.to_lowercase();
+        let oui = mac_lower.get(..8)?;
+        self.mac_vendors.get(oui).map(String::as_str)
+    }
+
+    fn is_mac_desktop_service(&self, service: &str) -> bool {
+        self.rules
+            .standalone
+            .mac_desktop_services
+            .iter()
+            .any(|s| s == service)
+    }
+
+    fn is_smart_home_service(&self, service: &str) -> bool {
+        self.rules
+            .standalone
+            .smart_home_services
+            .iter()
+            .any(|s| s == service)
+    }
+
+    fn soundbar_model_prefix(&self, model: &str) -> bool {
+        self.rules
+            .standalone
+            .soundbar_model_prefixes
+            .iter()
+            .any(|p| model.starts_with(p.as_str()))
+    }
+
+    fn lg_appliance_prefix(&self, hostname: &str) -> bool {
+        self.rules
+            .standalone
+            .lg_appliance_prefixes
+            .iter()
+            .any(|p| hostname.starts_with(p.as_str()))
+    }
+}
+
+/// Read the OUI -> vendor entries of the generated mac_vendor_data.rs
+pub fn parse_mac_vendors(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix("(\"")?;
+            let (prefix, rest) = rest.split_once("\", \"")?;
+            let escaped = rest.strip_suffix("\"),")?;
+            Some((prefix.to_string(), unescape(escaped)))
+        })
+        .collect()
+}
+
+/// Undo the backslash escaping of generated string literals
+fn unescape(escaped: &str) -> String {
+    let mut result = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> DeviceRules {
+        toml::from_str(include_str!("../device_rules.toml")).unwrap()
+    }
+
+    fn mac_vendors() -> HashMap<String, String> {
+        parse_mac_vendors(include_str!(
+            "../../../src/network/endpoint/mac_vendor_data.rs"
+        ))
+    }
+
+    fn fixtures(content: &str) -> Fixtures {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_bundled_fixtures_pass() {
+        let rules = rules();
+        let bundled = fixtures(include_str!("../fixtures.toml"));
+        let report: Vec<String> = TomlRules::new(&rules, mac_vendors())
+            .check(&bundled)
+            .iter()
+            .map(|m| format!("{:?} -> {:?}", m.fixture.hostname, m.actual))
+            .collect();
+        assert!(report.is_empty(), "{}", report.join("\n"));
+    }
+
+    #[test]
+    fn test_rule_edits_apply_without_codegen() {
+        let cases = fixtures(
+            r#"
+            [[cases]]
+            hostname = "zzprobe-label-01"
+            expected_type = "printer"
+
+            [[cases]]
+            hostname = "xbox-series-x"
+            expected_type = "gaming"
+            "#,
+        );
+
+        let mut rules = rules();
+        rules
+            .patterns
+            .get_mut("printer")
+            .unwrap()
+            .push("zzprobe-".to_string());
+        rules
+            .patterns
+            .get_mut("gaming")
+            .unwrap()
+            .retain(|p| !"xbox-series-x".contains(p.as_str()));
+
+        // The added pattern matches, the removed one no longer does
+        let mismatches = TomlRules::new(&rules, HashMap::new()).check(&cases);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(
+            mismatches[0].fixture.hostname.as_deref(),
+            Some("xbox-series-x")
+        );
+        assert_eq!(mismatches[0].actual, None);
+    }
+
+    #[test]
+    fn test_parse_mac_vendors() {
+        let vendors = parse_mac_vendors(
+            "&[\n    (\"00:03:93\", \"Apple\"),\n    (\"00:00:01\", \"Say \\\"Hi\\\"\"),\n]",
+        );
+        assert_eq!(vendors["00:03:93"], "Apple");
+        assert_eq!(vendors["00:00:01"], "Say \"Hi\"");
+    }
+}
@@ -1,10 +1,16 @@
 mod codegen;
+mod harness;
+// The crate's classification checks, shared by path so fixtures run the same tiers and
+// special cases without this tool depending on the crate (and its generated data)
+#[allow(dead_code)]
+#[path = "../../../src/network/endpoint/rule_matcher.rs"]
+mod rule_matcher;
 mod schema;
 
 use clap::Parser;
 use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "device-rules-generator")]
@@ -21,6 +27,15 @@ struct Cli {
     /// Validate TOML only (no output)
     #[arg(long)]
     verify: bool,
+
+    /// Classify the sample devices in this fixtures file with the rules and report
+    /// mismatches (no output)
+    #[arg(long, value_name = "FIXTURES")]
+    test: Option<PathBuf>,
+
+    /// Generated MAC vendor data used for MAC-based classification in --test mode
+    #[arg(long, default_value = "../../src/network/endpoint/mac_vendor_data.rs")]
+    mac_vendors: PathBuf,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -37,10 +52,53 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    if let Some(ref fixtures_path) = cli.test {
+        return run_fixtures(&rules, fixtures_path, &cli.mac_vendors);
+    }
+
     codegen::generate(&rules, &cli.output)?;
     Ok(())
 }
 
+/// Dry-run the rules against a corpus of sample devices.
+fn run_fixtures(
+    rules: &schema::DeviceRules,
+    fixtures_path: &Path,
+    mac_vendors_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fixtures: harness::Fixtures = toml::from_str(&fs::read_to_string(fixtures_path)?)?;
+    let mac_vendors = harness::parse_mac_vendors(&fs::read_to_string(mac_vendors_path)?);
+    eprintln!(
+        "Loaded {} fixtures from {} ({} MAC vendor prefixes)",
+        fixtures.cases.len(),
+        fixtures_path.display(),
+        mac_vendors.len()
+    );
+
+    let mismatches = harness::TomlRules::new(rules, mac_vendors).check(&fixtures);
+    if mismatches.is_empty() {
+        eprintln!("All {} fixtures PASSED", fixtures.cases.len());
+        return Ok(());
+    }
+
+    eprintln!("MISMATCHED fixtures ({}):", mismatches.len());
+    for m in &mismatches {
+        eprintln!(
+            "  hostname={:?} mac={:?} -> expected {}, got {}",
+            m.fixture.hostname.as_deref().unwrap_or(""),
+            m.fixture.mac.as_deref().unwrap_or(""),
+            m.fixture.expected_type.as_deref().unwrap_or("none"),
+            m.actual.unwrap_or("none")
+        );
+    }
+    Err(format!(
+        "Fixture test FAILED: {} of {} mismatched",
+        mismatches.len(),
+        fixtures.cases.len()
+    )
+    .into())
+}
+
 fn validate(rules: &schema::DeviceRules) -> Result<(), Box<dyn std::error::Error>> {
     let valid_classifications: HashSet<&str> = [
         "printer",
@@ -48,8 +48,11 @@ pub struct DeviceRules {
 #[derive(Deserialize)]
 pub struct StandaloneLists {
     pub mac_desktop_services: Vec<String>,
+    pub smart_home_services: Vec<String>,
     pub soundbar_model_prefixes: Vec<String>,
     pub lg_appliance_prefixes: Vec<String>,
+    #[serde(default)]
+    pub component_vendors: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+/// Changes between an existing generated data file and a fresh merge.
+#[derive(Debug, Default, PartialEq)]
+pub struct VendorDiff {
+    pub added: Vec<(String, String)>,
+    pub removed: Vec<(String, String)>,
+    /// (prefix, old vendor, new vendor)
+    pub renamed: Vec<(String, String, String)>,
+    /// Number of entries in the existing file
+    pub previous_len: usize,
+}
+
+impl VendorDiff {
+    pub fn changed(&self) -> usize {
+        self.added.len() + self.removed.len() + self.renamed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed() == 0
+    }
+
+    /// Changed entries as a percentage of the existing file. Everything counts as
+    /// changed when there was no existing data.
+    pub fn change_percent(&self) -> f64 {
+        if self.previous_len == 0 {
+            return if self.is_empty() { 0.0 } else { 100.0 };
+        }
+        self.changed() as f64 * 100.0 / self.previous_len as f64
+    }
+
+    /// Human-readable changelog in Markdown.
+    pub fn changelog(&self) -> String {
+        let mut lines = vec![
+            "# MAC vendor database changes".to_string(),
+            String::new(),
+            format!(
+                "{} added, {} removed, {} renamed ({:.2}% of {} entries)",
+                self.added.len(),
+                self.removed.len(),
+                self.renamed.len(),
+                self.change_percent(),
+                self.previous_len
+            ),
+        ];
+
+        if !self.added.is_empty() {
+            lines.push(String::new());
+            lines.push("## Added".to_string());
+            for (prefix, vendor) in &self.added {
+                lines.push(format!("- {} {}", prefix, vendor));
+            }
+        }
+        if !self.removed.is_empty() {
+            lines.push(String::new());
+            lines.push("## Removed".to_string());
+            for (prefix, vendor) in &self.removed {
+                lines.push(format!("- {} {}", prefix, vendor));
+            }
+        }
+        if !self.renamed.is_empty() {
+            lines.push(String::new());
+            lines.push("## Renamed".to_string());
+            for (prefix, old, new) in &self.renamed {
+                lines.push(format!("- {} {} -> {}", prefix, old, new));
+            }
+        }
+
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// Parse the entries back out of a data file written by `codegen::generate_mac_vendors_rs`.
+pub fn parse_generated(content: &str) -> BTreeMap<String, String> {
+    let mut entries = BTreeMap::new();
+
+    for line in content.lines() {
+        let Some(rest) = line.trim().strip_prefix("(\"") else {
+            continue;
+        };
+        let Some((prefix, rest)) = rest.split_once("\", \"") else {
+            continue;
+        };
+        let Some(escaped) = rest.strip_suffix("\"),") else {
+            continue;
+        };
+        entries.insert(prefix.to_string(), unescape(escaped));
+    }
+
+    entries
+}
+
+/// Undo the escaping codegen applies to vendor names (backslashes and quotes).
+fn unescape(escaped: &str) -> String {
+    let mut result = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                result.push(next);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Compare the existing entries with a fresh merge.
+pub fn diff_vendors(previous: &BTreeMap<String, String>, current: &BTreeMap<String, String>) -> VendorDiff {
+    let mut diff = VendorDiff {
+        previous_len: previous.len(),
+        ..Default::default()
+    };
+
+    for (prefix, vendor) in current {
+        match previous.get(prefix) {
+            None => diff.added.push((prefix.clone(), vendor.clone())),
+            Some(old) if old != vendor => diff.renamed.push((prefix.clone(), old.clone(), vendor.clone())),
+            Some(_) => {}
+        }
+    }
+    for (prefix, vendor) in previous {
+        if !current.contains_key(prefix) {
+            diff.removed.push((prefix.clone(), vendor.clone()));
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(p, v)| (p.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_generated() {
+        let content = "// Auto-generated by oui-generator — 2 entries. Do not edit.\n&[\n    (\"00:03:93\", \"Apple\"),\n    (\"00:11:22\", \"Foo \\\"Bar\\\" \\\\ Baz\"),\n]\n";
+        assert_eq!(
+            parse_generated(content),
+            entries(&[("00:03:93", "Apple"), ("00:11:22", "Foo \"Bar\" \\ Baz")])
+        );
+        assert!(parse_generated("").is_empty());
+    }
+
+    #[test]
+    fn test_diff_vendors() {
+        let previous = entries(&[("00:00:01", "Acme"), ("00:00:02", "Old Name"), ("00:00:03", "Gone")]);
+        let current = entries(&[("00:00:01", "Acme"), ("00:00:02", "New Name"), ("00:00:04", "Fresh")]);
+
+        let diff = diff_vendors(&previous, &current);
+        assert_eq!(diff.added, vec![("00:00:04".to_string(), "Fresh".to_string())]);
+        assert_eq!(diff.removed, vec![("00:00:03".to_string(), "Gone".to_string())]);
+        assert_eq!(
+            diff.renamed,
+            vec![("00:00:02".to_string(), "Old Name".to_string(), "New Name".to_string())]
+        );
+        assert_eq!(diff.change_percent(), 100.0);
+        assert!(diff.changelog().contains("- 00:00:02 Old Name -> New Name"));
+
+        assert!(diff_vendors(&previous, &previous).is_empty());
+        assert_eq!(diff_vendors(&BTreeMap::new(), &current).change_percent(), 100.0);
+        assert_eq!(diff_vendors(&BTreeMap::new(), &BTreeMap::new()).change_percent(), 0.0);
+    }
+}
@@ -1,12 +1,14 @@
 mod codegen;
+mod diff;
 mod ieee;
 mod macaddress_io;
 mod normalize;
 
 use clap::Parser;
+use normalize::{Normalizer, Transliteration};
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "oui-generator")]
@@ -20,6 +22,18 @@ struct Cli {
     #[arg(long, default_value = "overrides.toml")]
     overrides: PathBuf,
 
+    /// Path to the organization name -> vendor name map (optional file)
+    #[arg(long, default_value = "canonical_names.toml")]
+    canonical_names: PathBuf,
+
+    /// How to write non-ASCII characters in vendor names not otherwise mapped
+    #[arg(long, value_enum, default_value = "keep")]
+    transliterate: Transliteration,
+
+    /// Write every generated name that still looks un-normalized to this file
+    #[arg(long)]
+    report: Option<PathBuf>,
+
     /// macaddress.io API key for optional enrichment
     #[arg(long)]
     macaddress_io_key: Option<String>,
@@ -35,6 +49,20 @@ struct Cli {
     /// Only verify existing output without regenerating
     #[arg(long)]
     verify_only: bool,
+
+    /// Only diff a fresh merge against the existing output and print the changelog,
+    /// without writing anything else
+    #[arg(long)]
+    diff_only: bool,
+
+    /// Write a changelog of added, removed and renamed prefixes to this file
+    #[arg(long)]
+    changelog: Option<PathBuf>,
+
+    /// Fail without writing output if more than this percentage of existing entries
+    /// would change (guards against truncated or corrupted downloads)
+    #[arg(long)]
+    max_change_percent: Option<f64>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -58,8 +86,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Enrichment data is cached for future use but not directly used in vendor names yet
     }
 
+    let canonical_names = load_canonical_names(&cli.canonical_names)?;
+    eprintln!("Loaded {} canonical name entries", canonical_names.len());
+    let normalizer = Normalizer::new(&canonical_names, cli.transliterate);
+
     // Merge: overrides take priority, then normalize IEEE names
-    let merged = merge_entries(&overrides, &ieee_entries);
+    let merged = merge_entries(&overrides, &ieee_entries, &normalizer);
     eprintln!(
         "Merged database: {} entries ({} from overrides, {} new from IEEE)",
         merged.len(),
@@ -67,9 +99,27 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         merged.len() - overrides.len()
     );
 
+    // Compare with the existing output before replacing it
+    let changes = diff_existing_output(&cli.output, &merged)?;
+    if let Some(ref path) = cli.changelog {
+        fs::write(path, changes.changelog())?;
+        eprintln!("Wrote changelog to {}", path.display());
+    }
+    if cli.diff_only {
+        print!("{}", changes.changelog());
+    }
+    if let Some(max_percent) = cli.max_change_percent {
+        check_change_percent(&changes, max_percent)?;
+    }
+    if cli.diff_only {
+        return Ok(());
+    }
+
     // Generate output
     codegen::generate_mac_vendors_rs(&merged, &cli.output)?;
 
+    report_unnormalized(&merged, &overrides, cli.report.as_deref())?;
+
     // Verify if requested
     if cli.verify {
         verify_output(&cli.output, &overrides)?;
@@ -96,21 +146,41 @@ fn load_overrides(path: &PathBuf) -> Result<HashMap<String, String>, Box<dyn std
     Ok(overrides)
 }
 
+/// Load the organization name -> vendor name map. Unlike overrides, which pin single
+/// OUI prefixes, these apply to every prefix registered under the organization name.
+/// The file is optional.
+fn load_canonical_names(path: &Path) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        eprintln!("No canonical name map at {}, skipping", path.display());
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(path)?;
+    let parsed: toml::Value = content.parse()?;
+
+    let mut names = HashMap::new();
+
+    if let Some(table) = parsed.get("names").and_then(|v| v.as_table()) {
+        for (org_name, value) in table {
+            if let Some(vendor) = value.as_str() {
+                names.insert(org_name.clone(), vendor.to_string());
+            }
+        }
+    }
+
+    Ok(names)
+}
+
 /// Merge override entries with IEEE entries, applying normalization.
 fn merge_entries(
     overrides: &HashMap<String, String>,
     ieee_entries: &HashMap<String, String>,
+    normalizer: &Normalizer,
 ) -> BTreeMap<String, String> {
     let mut merged = BTreeMap::new();
 
     // First, add all IEEE entries with normalization (Tier 2 + Tier 3)
     for (prefix, org_name) in ieee_entries {
-        let vendor = if let Some(canonical) = normalize::map_organization_name(org_name) {
-            canonical.to_string()
-        } else {
-            normalize::strip_corporate_suffixes(org_name)
-        };
-        merged.insert(prefix.clone(), vendor);
+        merged.insert(prefix.clone(), normalizer.normalize(org_name));
     }
 
     // Then, override with our curated entries (Tier 1 - highest priority)
@@ -121,6 +191,82 @@ fn merge_entries(
     merged
 }
 
+/// Diff a fresh merge against the data file currently at `output_path`, if there is one.
+fn diff_existing_output(
+    output_path: &Path,
+    merged: &BTreeMap<String, String>,
+) -> Result<diff::VendorDiff, Box<dyn std::error::Error>> {
+    let previous = if output_path.exists() {
+        diff::parse_generated(&fs::read_to_string(output_path)?)
+    } else {
+        BTreeMap::new()
+    };
+    let changes = diff::diff_vendors(&previous, merged);
+    eprintln!(
+        "Changes since last generation: {} added, {} removed, {} renamed ({:.2}%)",
+        changes.added.len(),
+        changes.removed.len(),
+        changes.renamed.len(),
+        changes.change_percent()
+    );
+    Ok(changes)
+}
+
+/// Refuse an update that changes too much of the existing data. The first generation
+/// (no existing entries) is always allowed.
+fn check_change_percent(changes: &diff::VendorDiff, max_percent: f64) -> Result<(), Box<dyn std::error::Error>> {
+    if changes.previous_len == 0 || changes.change_percent() <= max_percent {
+        return Ok(());
+    }
+    Err(format!(
+        "{:.2}% of entries would change, more than the allowed {}%; not writing output",
+        changes.change_percent(),
+        max_percent
+    )
+    .into())
+}
+
+/// Summarize generated vendor names that still look like raw organization names, most
+/// widely used first, optionally writing the full list to `report_path`. Names from
+/// overrides are curated and left out.
+fn report_unnormalized(
+    merged: &BTreeMap<String, String>,
+    overrides: &HashMap<String, String>,
+    report_path: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (prefix, vendor) in merged {
+        if !overrides.contains_key(prefix) {
+            *counts.entry(vendor.as_str()).or_default() += 1;
+        }
+    }
+
+    let mut flagged: Vec<(&str, usize, &str)> = counts
+        .into_iter()
+        .filter_map(|(vendor, count)| Some((vendor, count, normalize::unnormalized_reason(vendor)?)))
+        .collect();
+    flagged.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    eprintln!("{} vendor names still look un-normalized", flagged.len());
+    for (vendor, count, reason) in flagged.iter().take(10) {
+        eprintln!("  {:>4} prefixes  {:<16}  {}", count, reason, vendor);
+    }
+
+    if let Some(path) = report_path {
+        let mut lines = vec!["# prefixes\treason\tvendor".to_string()];
+        lines.extend(
+            flagged
+                .iter()
+                .map(|(vendor, count, reason)| format!("{}\t{}\t{}", count, reason, vendor)),
+        );
+        lines.push(String::new());
+        fs::write(path, lines.join("\n"))?;
+        eprintln!("Wrote un-normalized name report to {}", path.display());
+    }
+
+    Ok(())
+}
+
 /// Verify that all original override entries are present in the generated output.
 fn verify_output(
     output_path: &PathBuf,
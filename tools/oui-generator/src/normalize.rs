@@ -1,13 +1,121 @@
-/// 3-tier vendor name normalization pipeline.
-///
-/// Tier 1: Exact OUI overrides (handled externally - checked before calling normalize)
-/// Tier 2: Organization name mapping via substring rules
-/// Tier 3: Corporate suffix stripping for unknown vendors
+//! 3-tier vendor name normalization pipeline.
+//!
+//! Tier 1: Exact OUI overrides (handled externally - checked before calling normalize)
+//! Tier 2: Organization name mapping, first exact names from the canonical-name map
+//!         file, then the built-in substring rules
+//! Tier 3: Corporate suffix stripping for unknown vendors
+//!
+//! Names are folded first (full-width forms, ideographic spaces) and matched ignoring
+//! case and accents, so "ＳＯＮＹ" and "Sony" are the same organization.
+
+use clap::ValueEnum;
+use deunicode::{deunicode, deunicode_char};
+use std::collections::HashMap;
+
+/// How non-ASCII characters are written in names that reach Tier 3
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Transliteration {
+    /// Keep every script as is ("Société Générale", "深圳市")
+    Keep,
+    /// Strip accents from Latin letters but keep other scripts ("Societe Generale", "深圳市")
+    Accents,
+    /// Transliterate everything to ASCII ("Societe Generale", "Shen Zhen Shi")
+    Ascii,
+}
+
+/// Applies all tiers after the OUI overrides.
+pub struct Normalizer {
+    /// Canonical names keyed by `name_key` of the organization name
+    canonical_names: HashMap<String, String>,
+    transliteration: Transliteration,
+}
+
+impl Normalizer {
+    /// `canonical_names` maps organization names, as written in the IEEE data or close to
+    /// it, to vendor names.
+    pub fn new(canonical_names: &HashMap<String, String>, transliteration: Transliteration) -> Self {
+        Self {
+            canonical_names: canonical_names
+                .iter()
+                .map(|(org_name, vendor)| (name_key(org_name), vendor.clone()))
+                .collect(),
+            transliteration,
+        }
+    }
+
+    /// The vendor name for an IEEE organization name.
+    pub fn normalize(&self, org_name: &str) -> String {
+        let folded = fold_unicode(org_name);
+        if let Some(vendor) = self.canonical_names.get(&name_key(&folded)) {
+            return vendor.clone();
+        }
+        if let Some(vendor) = map_organization_name(&folded) {
+            return vendor.to_string();
+        }
+        transliterate(&strip_corporate_suffixes(&folded), self.transliteration)
+    }
+}
+
+/// Fold full-width ASCII forms ("ＳＯＮＹ（株）") to plain ASCII and collapse all kinds
+/// of whitespace, including the ideographic space, to single spaces.
+pub fn fold_unicode(name: &str) -> String {
+    let folded: String = name
+        .chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            c if c.is_whitespace() => ' ',
+            c => c,
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Lowercase ASCII rendering of a name for matching: accents dropped, other scripts
+/// transliterated, whitespace collapsed.
+fn match_text(name: &str) -> String {
+    deunicode(&fold_unicode(name))
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Key for exact name lookups: the words of `match_text` without punctuation, so
+/// "Hon Hai Precision Ind. Co.,Ltd." and "HON HAI PRECISION IND CO LTD" are equal.
+pub fn name_key(name: &str) -> String {
+    match_text(name)
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Write a name using the given transliteration.
+pub fn transliterate(name: &str, transliteration: Transliteration) -> String {
+    let result = match transliteration {
+        Transliteration::Keep => return name.to_string(),
+        Transliteration::Accents => name
+            .chars()
+            .map(|c| match (is_accented_latin(c), deunicode_char(c)) {
+                (true, Some(plain)) => plain.to_string(),
+                _ => c.to_string(),
+            })
+            .collect::<String>(),
+        Transliteration::Ascii => deunicode(name),
+    };
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Latin letters with diacritics (Latin-1 Supplement, Latin Extended-A/B and
+/// Latin Extended Additional), excluding the ×/÷ signs
+fn is_accented_latin(c: char) -> bool {
+    matches!(c, '\u{C0}'..='\u{24F}' | '\u{1E00}'..='\u{1EFF}') && c != '×' && c != '÷'
+}
 
 /// Tier 2: Map IEEE organization names to our canonical vendor names via substring matching.
 /// Returns None if no mapping matches (falls through to Tier 3).
 pub fn map_organization_name(org_name: &str) -> Option<&'static str> {
-    let lower = org_name.to_lowercase();
+    let lower = match_text(org_name);
 
     // Order matters: more specific matches first
     let rules: &[(&[&str], &str)] = &[
@@ -106,9 +214,30 @@ pub fn map_organization_name(org_name: &str) -> Option<&'static str> {
 }
 
 /// Tier 3: Strip common corporate suffixes to produce cleaner names.
-/// Applied only when Tier 1 and Tier 2 don't match.
+/// Applied only when Tier 1 and Tier 2 don't match. Matching ignores ASCII case, since
+/// many IEEE entries are written in capitals ("FOO TECHNOLOGY CO.,LTD").
 pub fn strip_corporate_suffixes(name: &str) -> String {
+    // Chinese, Japanese and Korean company forms, longest first; they follow the name
+    // without a space
+    let cjk_suffixes = [
+        "股份有限公司",
+        "有限责任公司",
+        "有限責任公司",
+        "有限公司",
+        "株式会社",
+        "(株)",
+        "주식회사",
+        "(주)",
+        "公司",
+    ];
+    // Japanese and Korean names often lead with the company form instead
+    let cjk_prefixes = ["株式会社", "(株)", "주식회사", "(주)"];
+
     let suffixes = [
+        " Co.,Ltd.",
+        " Co.,Ltd",
+        " Co., Ltd.",
+        " Co., Ltd",
         ", Inc.",
         ", Inc",
         " Inc.",
@@ -154,13 +283,20 @@ pub fn strip_corporate_suffixes(name: &str) -> String {
     ];
 
     let mut result = name.trim().to_string();
+    for prefix in &cjk_prefixes {
+        if let Some(rest) = result.strip_prefix(prefix).filter(|r| !r.trim().is_empty()) {
+            result = rest.trim_start().to_string();
+        }
+    }
 
     // Multiple passes to handle combinations like "Foo Technologies, Inc."
     for _ in 0..3 {
         let before = result.clone();
-        for suffix in &suffixes {
-            if result.ends_with(suffix) {
-                result = result[..result.len() - suffix.len()].trim_end().to_string();
+        for suffix in cjk_suffixes.iter().chain(&suffixes) {
+            // Never strip the whole name ("株式会社" alone stays)
+            let stem = strip_suffix_ignore_ascii_case(&result, suffix).filter(|s| !s.trim().is_empty());
+            if let Some(stem) = stem {
+                result = stem.trim_end().to_string();
             }
         }
         // Also strip trailing comma left after removal
@@ -173,6 +309,44 @@ pub fn strip_corporate_suffixes(name: &str) -> String {
     result
 }
 
+fn strip_suffix_ignore_ascii_case<'a>(name: &'a str, suffix: &str) -> Option<&'a str> {
+    let split = name.len().checked_sub(suffix.len())?;
+    if !name.is_char_boundary(split) || !name[split..].eq_ignore_ascii_case(suffix) {
+        return None;
+    }
+    Some(&name[..split])
+}
+
+/// Why a generated vendor name still looks like a raw organization name, if it does.
+/// Used for the post-generation report, to find names worth a canonical-name entry.
+pub fn unnormalized_reason(name: &str) -> Option<&'static str> {
+    const CORPORATE_WORDS: &[&str] = &[
+        "inc", "llc", "ltd", "corp", "corporation", "co", "gmbh", "limited", "srl", "sas", "bv", "kk",
+    ];
+    const CJK_CORPORATE_WORDS: &[&str] = &["公司", "株式会社", "주식회사"];
+
+    let lower = name.to_lowercase();
+    if lower
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| CORPORATE_WORDS.contains(&word))
+        || CJK_CORPORATE_WORDS.iter().any(|word| name.contains(word))
+    {
+        return Some("corporate suffix");
+    }
+    if name.contains([',', ';', '(', ')']) {
+        return Some("punctuation");
+    }
+    let letters = name.chars().filter(|c| c.is_alphabetic()).count();
+    let shouting = !name.chars().any(|c| c.is_lowercase()) && name.chars().any(|c| c.is_uppercase());
+    if name.contains(' ') && letters > 6 && shouting {
+        return Some("all capitals");
+    }
+    if name.chars().count() > 40 {
+        return Some("long");
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -195,5 +369,48 @@ mod tests {
         assert_eq!(strip_corporate_suffixes("Bar Electronics Corporation"), "Bar");
         assert_eq!(strip_corporate_suffixes("Simple Name"), "Simple Name");
         assert_eq!(strip_corporate_suffixes("Baz GmbH"), "Baz");
+        assert_eq!(strip_corporate_suffixes("FOO TECHNOLOGY CO.,LTD."), "FOO");
+        assert_eq!(strip_corporate_suffixes("深圳市某某科技有限公司"), "深圳市某某科技");
+        assert_eq!(strip_corporate_suffixes("株式会社バッファロー"), "バッファロー");
+        assert_eq!(strip_corporate_suffixes("株式会社"), "株式会社");
+    }
+
+    #[test]
+    fn test_fold_and_transliterate() {
+        assert_eq!(fold_unicode("ＳＯＮＹ（株）"), "SONY(株)");
+        assert_eq!(fold_unicode("Foo\u{3000}\u{3000}Bar "), "Foo Bar");
+        assert_eq!(name_key("Hon Hai Precision Ind. Co.,Ltd."), "hon hai precision ind co ltd");
+        assert_eq!(name_key("HON HAI PRECISION IND CO LTD"), "hon hai precision ind co ltd");
+
+        assert_eq!(
+            transliterate("Société Générale", Transliteration::Keep),
+            "Société Générale"
+        );
+        assert_eq!(transliterate("Société 深圳", Transliteration::Accents), "Societe 深圳");
+        assert!(transliterate("深圳", Transliteration::Ascii).is_ascii());
+    }
+
+    #[test]
+    fn test_normalizer() {
+        let canonical_names = HashMap::from([(
+            "Hon Hai Precision Ind. Co.,Ltd.".to_string(),
+            "Foxconn".to_string(),
+        )]);
+        let normalizer = Normalizer::new(&canonical_names, Transliteration::Accents);
+
+        assert_eq!(normalizer.normalize("HON HAI PRECISION IND. CO., LTD."), "Foxconn");
+        assert_eq!(normalizer.normalize("ＳＡＭＳＵＮＧ ＥＬＥＣＴＲＯＮＩＣＳ"), "Samsung");
+        assert_eq!(normalizer.normalize("Hüttig Elektronik GmbH"), "Huttig Elektronik");
+    }
+
+    #[test]
+    fn test_unnormalized_reason() {
+        assert_eq!(unnormalized_reason("Acme Co Ltd"), Some("corporate suffix"));
+        assert_eq!(unnormalized_reason("某某科技有限公司"), Some("corporate suffix"));
+        assert_eq!(unnormalized_reason("Foo (Shenzhen)"), Some("punctuation"));
+        assert_eq!(unnormalized_reason("SHENZHEN FOO TECH"), Some("all capitals"));
+        assert_eq!(unnormalized_reason(&"Shenzhen Foo ".repeat(4)), Some("long"));
+        assert_eq!(unnormalized_reason("Ubiquiti"), None);
+        assert_eq!(unnormalized_reason("HP"), None);
     }
 }